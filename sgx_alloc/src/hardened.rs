@@ -0,0 +1,303 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Hardened allocator mode
+//!
+//! With the `hardened` feature, `System` surrounds every allocation with canaries and
+//! delays its release, so that heap corruption inside the enclave is caught when the
+//! corrupted block is freed instead of surfacing later as an unrelated failure:
+//!
+//! * A header before each block records its size and a canary keyed with a random secret
+//!   and the block's address; a second canary follows the block. Overflows, underflows and
+//!   frees with the wrong layout change one of them.
+//! * A freed block is filled with a poison pattern and its header is marked freed before it
+//!   enters a quarantine of bounded size. Freeing it again is a double free; a block whose
+//!   poison has changed when it leaves the quarantine was written after it was freed.
+//! * Blocks leave the quarantine in random order, so that the order in which memory is
+//!   reused cannot be predicted from the order in which it was freed.
+//!
+//! A detected fault is passed to the hook set with `set_heap_fault_hook`, if any, which can
+//! report it, and the enclave then aborts.
+
+use crate::system::MIN_ALIGN;
+use core::alloc::Layout;
+use core::arch::asm;
+use core::ffi::c_void;
+use core::hint;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// Quarantine limits: the number of blocks and their total size. Larger blocks are released
+// right away.
+const QUARANTINE_SLOTS: usize = 256;
+const QUARANTINE_BYTES: usize = 1 << 20;
+
+const HEADER_SIZE: usize = 2 * mem::size_of::<usize>();
+const CANARY_SIZE: usize = mem::size_of::<usize>();
+const POISON: u8 = 0xdf;
+const FREED_TAG: usize = 0x6672_6565_6421_2121;
+
+///
+/// A heap corruption found by the hardened allocator.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeapFault {
+    /// The canary after the block was overwritten.
+    Overflow,
+    /// The header before the block was overwritten.
+    Underflow,
+    /// The block was freed with another size than it was allocated with.
+    LayoutMismatch,
+    /// The block was freed while in quarantine.
+    DoubleFree,
+    /// The block was written while in quarantine.
+    UseAfterFree,
+}
+
+static HOOK: AtomicUsize = AtomicUsize::new(0);
+static KEY: AtomicUsize = AtomicUsize::new(0);
+static LOCK: AtomicBool = AtomicBool::new(false);
+static mut QUARANTINE: Quarantine = Quarantine {
+    blocks: [(0, 0, 0); QUARANTINE_SLOTS],
+    len: 0,
+    bytes: 0,
+    rng: 0,
+};
+
+// Freed blocks as (address, size, alignment).
+struct Quarantine {
+    blocks: [(usize, usize, usize); QUARANTINE_SLOTS],
+    len: usize,
+    bytes: usize,
+    rng: u64,
+}
+
+///
+/// Sets the function called with a heap fault and the address of the affected block before
+/// the enclave aborts.
+///
+/// # Description
+///
+/// The hook runs with the heap in an inconsistent state: it should only record or report the
+/// fault, without allocating.
+///
+pub fn set_heap_fault_hook(hook: fn(HeapFault, usize)) {
+    HOOK.store(hook as usize, Ordering::Release);
+}
+
+fn fault(kind: HeapFault, addr: usize) -> ! {
+    let hook = HOOK.load(Ordering::Acquire);
+    if hook != 0 {
+        let hook: fn(HeapFault, usize) = unsafe { mem::transmute(hook) };
+        hook(kind, addr);
+    }
+    unsafe { libc::abort() }
+}
+
+fn rdrand() -> usize {
+    loop {
+        let value: usize;
+        let ok: u8;
+        unsafe {
+            asm!(
+                "rdrand {0}",
+                "setc {1}",
+                out(reg) value,
+                out(reg_byte) ok,
+                options(nomem, nostack),
+            );
+        }
+        if ok == 1 {
+            return value;
+        }
+    }
+}
+
+fn key() -> usize {
+    match KEY.load(Ordering::Relaxed) {
+        0 => {
+            let key = rdrand() | 1;
+            match KEY.compare_exchange(0, key, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => key,
+                Err(current) => current,
+            }
+        }
+        key => key,
+    }
+}
+
+fn header_canary(key: usize, addr: usize, size: usize) -> usize {
+    key ^ addr ^ size.rotate_left(32)
+}
+
+fn trailer_canary(key: usize, addr: usize, size: usize) -> usize {
+    key.rotate_left(17) ^ addr ^ size
+}
+
+fn freed_canary(key: usize, addr: usize) -> usize {
+    key ^ addr ^ FREED_TAG
+}
+
+// The offset of the block from the start of the underlying allocation.
+fn offset(align: usize) -> usize {
+    align.max(HEADER_SIZE)
+}
+
+fn with_quarantine<R>(f: impl FnOnce(&mut Quarantine) -> R) -> R {
+    while LOCK
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        hint::spin_loop();
+    }
+    let result = f(unsafe { &mut *ptr::addr_of_mut!(QUARANTINE) });
+    LOCK.store(false, Ordering::Release);
+    result
+}
+
+pub(crate) unsafe fn alloc(layout: Layout, zeroed: bool) -> *mut u8 {
+    let align = layout.align().max(MIN_ALIGN);
+    let offset = offset(align);
+    let total = match offset
+        .checked_add(layout.size())
+        .and_then(|n| n.checked_add(CANARY_SIZE))
+    {
+        Some(total) => total,
+        None => return ptr::null_mut(),
+    };
+    let base = if align <= MIN_ALIGN {
+        libc::malloc(total)
+    } else {
+        libc::memalign(align, total)
+    } as *mut u8;
+    if base.is_null() {
+        return base;
+    }
+
+    let block = base.add(offset);
+    let addr = block as usize;
+    let key = key();
+    let header = block.sub(HEADER_SIZE) as *mut usize;
+    header.write(layout.size());
+    header.add(1).write(header_canary(key, addr, layout.size()));
+    (block.add(layout.size()) as *mut usize).write_unaligned(trailer_canary(
+        key,
+        addr,
+        layout.size(),
+    ));
+    if zeroed {
+        ptr::write_bytes(block, 0, layout.size());
+    }
+    block
+}
+
+pub(crate) unsafe fn dealloc(block: *mut u8, layout: Layout) {
+    let addr = block as usize;
+    let size = layout.size();
+    let key = key();
+    let header = block.sub(HEADER_SIZE) as *mut usize;
+    let canary = header.add(1).read();
+    if canary == freed_canary(key, addr) {
+        fault(HeapFault::DoubleFree, addr);
+    }
+    if canary != header_canary(key, addr, header.read()) {
+        fault(HeapFault::Underflow, addr);
+    }
+    if header.read() != size {
+        fault(HeapFault::LayoutMismatch, addr);
+    }
+    if (block.add(size) as *const usize).read_unaligned() != trailer_canary(key, addr, size) {
+        fault(HeapFault::Overflow, addr);
+    }
+
+    header.add(1).write(freed_canary(key, addr));
+    ptr::write_bytes(block, POISON, size);
+    if size > QUARANTINE_BYTES {
+        release(addr, layout.align());
+        return;
+    }
+
+    let mut entry = Some((addr, size, layout.align()));
+    loop {
+        let evicted = with_quarantine(|q| {
+            if let Some(e) = entry {
+                if q.len < QUARANTINE_SLOTS && q.bytes + e.1 <= QUARANTINE_BYTES {
+                    q.blocks[q.len] = e;
+                    q.len += 1;
+                    q.bytes += e.1;
+                    entry = None;
+                    return None;
+                }
+            }
+            if q.len == 0 {
+                return None;
+            }
+            if q.rng == 0 {
+                q.rng = rdrand() as u64 | 1;
+            }
+            // xorshift64
+            q.rng ^= q.rng << 13;
+            q.rng ^= q.rng >> 7;
+            q.rng ^= q.rng << 17;
+            let index = (q.rng % q.len as u64) as usize;
+            let evicted = q.blocks[index];
+            q.len -= 1;
+            q.blocks[index] = q.blocks[q.len];
+            q.bytes -= evicted.1;
+            Some(evicted)
+        });
+        match evicted {
+            Some((addr, size, align)) => {
+                check_poison(addr, size, key);
+                release(addr, align);
+            }
+            None if entry.is_none() => return,
+            None => {
+                let (addr, _, align) = entry.take().unwrap();
+                release(addr, align);
+                return;
+            }
+        }
+    }
+}
+
+unsafe fn check_poison(addr: usize, size: usize, key: usize) {
+    let block = addr as *const u8;
+    let header = block.sub(HEADER_SIZE) as *const usize;
+    if header.add(1).read() != freed_canary(key, addr)
+        || (0..size).any(|i| block.add(i).read() != POISON)
+    {
+        fault(HeapFault::UseAfterFree, addr);
+    }
+}
+
+unsafe fn release(addr: usize, align: usize) {
+    let align = align.max(MIN_ALIGN);
+    libc::free((addr - offset(align)) as *mut c_void);
+}
+
+mod libc {
+    use core::ffi::c_void;
+    type size_t = usize;
+    extern "C" {
+        pub fn malloc(size: size_t) -> *mut c_void;
+        pub fn free(p: *mut c_void);
+        pub fn memalign(align: size_t, size: size_t) -> *mut c_void;
+        pub fn abort() -> !;
+    }
+}
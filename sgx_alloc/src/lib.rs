@@ -41,4 +41,6 @@ pub use system::System;
 
 pub mod alignalloc;
 pub mod alignbox;
+#[cfg(feature = "hardened")]
+pub mod hardened;
 pub mod rsrvmem;
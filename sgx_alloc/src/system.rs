@@ -30,12 +30,12 @@ use core::ptr::{self, NonNull};
 // add fast paths for low alignment values. In practice, the alignment is a
 // constant at the call site and the branch will be optimized out.
 #[cfg(target_arch = "x86")]
-const MIN_ALIGN: usize = 8;
+pub(crate) const MIN_ALIGN: usize = 8;
 
 // The alignment of sgx tlibc is 16
 // https://github.com/intel/linux-sgx/blob/master/sdk/tlibc/stdlib/malloc.c#L541
 #[cfg(target_arch = "x86_64")]
-const MIN_ALIGN: usize = 16;
+pub(crate) const MIN_ALIGN: usize = 16;
 
 pub struct System;
 
@@ -220,6 +220,7 @@ mod realloc_fallback {
     }
 }
 
+#[cfg(not(feature = "hardened"))]
 mod platform {
     use super::*;
     use core::alloc::{GlobalAlloc, Layout};
@@ -270,6 +271,35 @@ mod platform {
     }
 }
 
+#[cfg(feature = "hardened")]
+mod platform {
+    use crate::hardened;
+    use core::alloc::{GlobalAlloc, Layout};
+
+    unsafe impl GlobalAlloc for super::System {
+        #[inline]
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            hardened::alloc(layout, false)
+        }
+
+        #[inline]
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            hardened::alloc(layout, true)
+        }
+
+        #[inline]
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            hardened::dealloc(ptr, layout)
+        }
+
+        #[inline]
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            self.realloc_fallback(ptr, layout, new_size)
+        }
+    }
+}
+
+#[cfg(not(feature = "hardened"))]
 mod libc {
     use core::ffi::c_void;
     type size_t = usize;
@@ -0,0 +1,470 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! OCALL surface of an enclave, read from its EDL files.
+//!
+//! Every OCALL an enclave can make is declared in the `untrusted` section of
+//! one of the EDL files it is built from, either directly or through
+//! `from "..." import` statements. [`OcallSurfaceBuilder`] follows those
+//! imports the way `sgx_edger8r` does and produces an [`OcallSurface`]:
+//!
+//! * `ocall_surface.txt`, a sorted manifest with one OCALL per line, meant to
+//!   be kept with release artifacts so that reviews can diff the untrusted
+//!   interface between releases;
+//! * `ocall_surface.rs`, which embeds the same manifest as `OCALL_SURFACE`
+//!   for registration with `std::ocall::register_surface`, so that the
+//!   running enclave reports it through `t_ocall_surface_ecall`.
+//!
+//! ```no_run
+//! use sgx_build_helper::edl::OcallSurfaceBuilder;
+//!
+//! let out_dir = std::env::var_os("OUT_DIR").unwrap();
+//! let surface = OcallSurfaceBuilder::new()
+//!     .search_path("../../edl")
+//!     .edl("enclave/Enclave.edl")
+//!     .build()
+//!     .unwrap();
+//! surface.write_to(out_dir.as_ref()).unwrap();
+//! surface.emit_rerun_if_changed();
+//! ```
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/ocall_surface.rs"));
+//!
+//! std::ocall::register_surface(OCALL_SURFACE).unwrap();
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// First line of every manifest; bumped if the line format ever changes.
+pub const MANIFEST_HEADER: &str = "# sgx ocall surface v1";
+
+/// One OCALL declared in an EDL file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ocall {
+    /// Name of the OCALL function.
+    pub name: String,
+    /// File name of the EDL that declares it.
+    pub edl: String,
+    /// The declaration, with whitespace collapsed.
+    pub declaration: String,
+}
+
+/// Paths produced by [`OcallSurface::write_to`].
+#[derive(Clone, Debug)]
+pub struct SurfaceFiles {
+    pub manifest: PathBuf,
+    pub rust_source: PathBuf,
+}
+
+/// The OCALLs reachable from a set of EDL files, sorted by name.
+#[derive(Clone, Debug, Default)]
+pub struct OcallSurface {
+    ocalls: Vec<Ocall>,
+    sources: Vec<PathBuf>,
+}
+
+impl OcallSurface {
+    /// The OCALLs, sorted by name.
+    pub fn ocalls(&self) -> &[Ocall] {
+        &self.ocalls
+    }
+
+    /// Returns `true` if `name` is one of the OCALLs.
+    pub fn contains(&self, name: &str) -> bool {
+        self.ocalls
+            .binary_search_by(|o| o.name.as_str().cmp(name))
+            .is_ok()
+    }
+
+    /// Every EDL file that was read.
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+
+    /// The manifest: [`MANIFEST_HEADER`], then one line per OCALL holding
+    /// its name, EDL file and declaration, separated by tabs.
+    pub fn manifest(&self) -> String {
+        let mut manifest = String::from(MANIFEST_HEADER);
+        manifest.push('\n');
+        for ocall in &self.ocalls {
+            let _ = writeln!(
+                manifest,
+                "{}\t{}\t{}",
+                ocall.name, ocall.edl, ocall.declaration
+            );
+        }
+        manifest
+    }
+
+    /// Rust source defining `OCALL_SURFACE`, the manifest as a `&str`.
+    pub fn rust_source(&self) -> String {
+        format!(
+            "/// OCALL surface manifest generated by `sgx_build_helper::edl`.\n\
+             pub static OCALL_SURFACE: &str = {:?};\n",
+            self.manifest()
+        )
+    }
+
+    /// Writes the manifest and the Rust source into `dir`.
+    pub fn write_to(&self, dir: &Path) -> io::Result<SurfaceFiles> {
+        fs::create_dir_all(dir)?;
+        let files = SurfaceFiles {
+            manifest: dir.join("ocall_surface.txt"),
+            rust_source: dir.join("ocall_surface.rs"),
+        };
+        crate::target::write_if_changed(&files.manifest, &self.manifest())?;
+        crate::target::write_if_changed(&files.rust_source, &self.rust_source())?;
+        Ok(files)
+    }
+
+    /// Prints `cargo:rerun-if-changed` for every EDL file that was read.
+    pub fn emit_rerun_if_changed(&self) {
+        for path in &self.sources {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+}
+
+/// Builder collecting the OCALLs reachable from one or more EDL files.
+#[derive(Clone, Debug, Default)]
+pub struct OcallSurfaceBuilder {
+    roots: Vec<PathBuf>,
+    search_paths: Vec<PathBuf>,
+}
+
+impl OcallSurfaceBuilder {
+    /// Creates a builder without EDL files or search paths.
+    pub fn new() -> OcallSurfaceBuilder {
+        OcallSurfaceBuilder::default()
+    }
+
+    /// Adds an EDL file of the enclave, usually its `Enclave.edl`.
+    pub fn edl<P: Into<PathBuf>>(mut self, path: P) -> OcallSurfaceBuilder {
+        self.roots.push(path.into());
+        self
+    }
+
+    /// Adds a directory to look up imported EDL files in, like the
+    /// `--search-path` option of `sgx_edger8r`.
+    pub fn search_path<P: Into<PathBuf>>(mut self, dir: P) -> OcallSurfaceBuilder {
+        self.search_paths.push(dir.into());
+        self
+    }
+
+    /// Parses the EDL files and their imports.
+    ///
+    /// An OCALL declared with different signatures in two files is an error,
+    /// as `sgx_edger8r` would reject it as well.
+    pub fn build(&self) -> io::Result<OcallSurface> {
+        let mut loader = Loader {
+            search_paths: &self.search_paths,
+            files: BTreeMap::new(),
+        };
+        let mut ocalls: BTreeMap<String, Ocall> = BTreeMap::new();
+        for root in &self.roots {
+            let path = loader.load(root, &mut Vec::new())?;
+            for ocall in loader.files[&path].reachable.clone() {
+                match ocalls.get(&ocall.name) {
+                    Some(existing) if existing.declaration != ocall.declaration => {
+                        return Err(invalid(format!(
+                            "OCALL `{}` is declared differently in {} and {}",
+                            ocall.name, existing.edl, ocall.edl
+                        )));
+                    }
+                    Some(_) => (),
+                    None => {
+                        ocalls.insert(ocall.name.clone(), ocall);
+                    }
+                }
+            }
+        }
+        Ok(OcallSurface {
+            ocalls: ocalls.into_values().collect(),
+            sources: loader.files.into_keys().collect(),
+        })
+    }
+}
+
+struct EdlFile {
+    // Its own OCALLs followed by the imported ones.
+    reachable: Vec<Ocall>,
+}
+
+struct Loader<'a> {
+    search_paths: &'a [PathBuf],
+    files: BTreeMap<PathBuf, EdlFile>,
+}
+
+impl Loader<'_> {
+    // Loads `path` and, recursively, its imports; `stack` detects cycles.
+    fn load(&mut self, path: &Path, stack: &mut Vec<PathBuf>) -> io::Result<PathBuf> {
+        let path = fs::canonicalize(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        if self.files.contains_key(&path) {
+            return Ok(path);
+        }
+        if stack.contains(&path) {
+            return Err(invalid(format!("import cycle through {}", path.display())));
+        }
+
+        let text = fs::read_to_string(&path)?;
+        let edl_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let parsed = parse(&text, &edl_name)
+            .map_err(|msg| invalid(format!("{}: {}", path.display(), msg)))?;
+
+        stack.push(path.clone());
+        let mut reachable = parsed.ocalls;
+        for import in parsed.imports {
+            let dep = self.resolve(&path, &import.file)?;
+            let dep = self.load(&dep, stack)?;
+            for ocall in &self.files[&dep].reachable {
+                let wanted = match &import.names {
+                    Some(names) => names.contains(&ocall.name),
+                    None => true,
+                };
+                if wanted {
+                    reachable.push(ocall.clone());
+                }
+            }
+        }
+        stack.pop();
+
+        self.files.insert(path.clone(), EdlFile { reachable });
+        Ok(path)
+    }
+
+    // Imports are looked up next to the importing file first, then in the
+    // search paths in order.
+    fn resolve(&self, from: &Path, file: &str) -> io::Result<PathBuf> {
+        let beside = from.parent().map(|dir| dir.join(file));
+        beside
+            .into_iter()
+            .chain(self.search_paths.iter().map(|dir| dir.join(file)))
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{}: cannot find imported EDL `{}`", from.display(), file),
+                )
+            })
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+struct Import {
+    file: String,
+    // `None` for `import *`.
+    names: Option<BTreeSet<String>>,
+}
+
+struct Parsed {
+    imports: Vec<Import>,
+    ocalls: Vec<Ocall>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Punct(char),
+}
+
+// Tokens with the byte offset they start at and the one they end at.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize, usize)>, String> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+        } else if text[i..].starts_with("//") {
+            i = text[i..].find('\n').map_or(bytes.len(), |n| i + n);
+        } else if text[i..].starts_with("/*") {
+            i = text[i + 2..]
+                .find("*/")
+                .map(|n| i + n + 4)
+                .ok_or("unterminated comment")?;
+        } else if c == b'"' {
+            let end = text[i + 1..]
+                .find('"')
+                .map(|n| i + 1 + n)
+                .ok_or("unterminated string")?;
+            tokens.push((Token::Str(text[i + 1..end].to_owned()), i, end + 1));
+            i = end + 1;
+        } else if c.is_ascii_alphanumeric() || c == b'_' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(text[start..i].to_owned()), start, i));
+        } else {
+            let ch = text[i..].chars().next().unwrap_or_default();
+            tokens.push((Token::Punct(ch), i, i + ch.len_utf8()));
+            i += ch.len_utf8();
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse(text: &str, edl_name: &str) -> Result<Parsed, String> {
+    let tokens = tokenize(text)?;
+    let mut parsed = Parsed {
+        imports: Vec::new(),
+        ocalls: Vec::new(),
+    };
+
+    let body = match tokens
+        .iter()
+        .position(|t| t.0 == Token::Ident("enclave".into()))
+    {
+        Some(pos) if tokens.get(pos + 1).map(|t| &t.0) == Some(&Token::Punct('{')) => pos + 2,
+        _ => return Err("missing `enclave {`".into()),
+    };
+    let end = matching_brace(&tokens, body - 1)?;
+
+    let mut i = body;
+    while i < end {
+        match &tokens[i].0 {
+            Token::Ident(kw) if kw == "from" => {
+                let file = match tokens.get(i + 1).map(|t| &t.0) {
+                    Some(Token::Str(file)) => file.clone(),
+                    _ => return Err("expected a file name after `from`".into()),
+                };
+                if tokens.get(i + 2).map(|t| &t.0) != Some(&Token::Ident("import".into())) {
+                    return Err(format!("expected `import` after `from \"{}\"`", file));
+                }
+                let semi = find_punct(&tokens, i + 3, end, ';')?;
+                let names = if tokens[i + 3].0 == Token::Punct('*') {
+                    None
+                } else {
+                    Some(
+                        tokens[i + 3..semi]
+                            .iter()
+                            .filter_map(|t| match &t.0 {
+                                Token::Ident(name) => Some(name.clone()),
+                                _ => None,
+                            })
+                            .collect(),
+                    )
+                };
+                parsed.imports.push(Import { file, names });
+                i = semi + 1;
+            }
+            Token::Ident(kw) if kw == "import" => {
+                // The older `import "file.edl";` form imports everything.
+                if let Some(Token::Str(file)) = tokens.get(i + 1).map(|t| &t.0) {
+                    parsed.imports.push(Import {
+                        file: file.clone(),
+                        names: None,
+                    });
+                }
+                i = find_punct(&tokens, i + 1, end, ';')? + 1;
+            }
+            Token::Ident(kw) if kw == "include" => i += 2,
+            Token::Ident(kw) if kw == "untrusted" => {
+                let open = find_punct(&tokens, i + 1, end, '{')?;
+                let close = matching_brace(&tokens, open)?;
+                let mut start = open + 1;
+                while start < close {
+                    let semi = find_punct(&tokens, start, close, ';')?;
+                    parsed
+                        .ocalls
+                        .push(declaration(text, &tokens[start..=semi], edl_name)?);
+                    start = semi + 1;
+                }
+                i = close + 1;
+            }
+            Token::Punct('{') => i = matching_brace(&tokens, i)? + 1,
+            _ => i += 1,
+        }
+    }
+    Ok(parsed)
+}
+
+fn find_punct(
+    tokens: &[(Token, usize, usize)],
+    from: usize,
+    end: usize,
+    ch: char,
+) -> Result<usize, String> {
+    (from..end)
+        .find(|&i| tokens[i].0 == Token::Punct(ch))
+        .ok_or_else(|| format!("expected `{}`", ch))
+}
+
+fn matching_brace(tokens: &[(Token, usize, usize)], open: usize) -> Result<usize, String> {
+    let mut depth = 0_usize;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.0 {
+            Token::Punct('{') => depth += 1,
+            Token::Punct('}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i);
+                }
+            }
+            _ => (),
+        }
+    }
+    Err("unbalanced `{`".into())
+}
+
+// The name of a declaration is the identifier right before its parameter
+// list, outside of any `[...]` attribute.
+fn declaration(
+    text: &str,
+    tokens: &[(Token, usize, usize)],
+    edl_name: &str,
+) -> Result<Ocall, String> {
+    let mut brackets = 0_usize;
+    let mut name = None;
+    for pair in tokens.windows(2) {
+        match pair[0].0 {
+            Token::Punct('[') => brackets += 1,
+            Token::Punct(']') => brackets = brackets.saturating_sub(1),
+            _ => (),
+        }
+        if let (Token::Ident(ident), Token::Punct('('), 0) = (&pair[0].0, &pair[1].0, brackets) {
+            name = Some(ident.clone());
+            break;
+        }
+    }
+    let first = &tokens[0];
+    let last = &tokens[tokens.len() - 1];
+    let name =
+        name.ok_or_else(|| format!("cannot find the name of `{}`", &text[first.1..last.2]))?;
+    Ok(Ocall {
+        name,
+        edl: edl_name.to_owned(),
+        declaration: text[first.1..last.2]
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "),
+    })
+}
@@ -0,0 +1,413 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Target spec, linker script and LVI flags for enclave builds.
+//!
+//! Enclave crates used to carry their own copy of
+//! `x86_64-unknown-linux-sgx.json`, `Enclave.lds` and the mitigation flags
+//! from `buildenv.mk`. [`EnclaveTarget`] generates all three for the
+//! toolchain that is running the build script:
+//!
+//! ```no_run
+//! use sgx_build_helper::target::EnclaveTarget;
+//!
+//! let out_dir = std::env::var_os("OUT_DIR").unwrap();
+//! let target = EnclaveTarget::from_env();
+//! let files = target.write_to(out_dir.as_ref()).unwrap();
+//! target.emit_link_args(&files);
+//! ```
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Name of the generated target, which is also the file stem of the JSON spec.
+pub const TARGET_NAME: &str = "x86_64-unknown-linux-sgx";
+
+/// Symbols every enclave image must export for `sgx_urts` to load it.
+pub const REQUIRED_EXPORTS: &[&str] = &[
+    "g_global_data_sim",
+    "g_global_data",
+    "enclave_entry",
+    "g_peak_heap_used",
+    "g_peak_rsrv_mem_committed",
+];
+
+/// Load Value Injection (CVE-2020-0551) mitigation level.
+///
+/// The levels mirror `MITIGATION-CVE-2020-0551` in `buildenv.mk`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LviMitigation {
+    /// No mitigation.
+    None,
+    /// Control-flow hardening: fences before indirect branches and returns.
+    ControlFlow,
+    /// Control-flow hardening plus a fence after every load.
+    Load,
+}
+
+impl LviMitigation {
+    /// Reads `MITIGATION-CVE-2020-0551` (`LOAD` or `CF`) from the environment.
+    pub fn from_env() -> LviMitigation {
+        println!("cargo:rerun-if-env-changed=MITIGATION-CVE-2020-0551");
+        match env::var("MITIGATION-CVE-2020-0551").as_deref() {
+            Ok("LOAD") => LviMitigation::Load,
+            Ok("CF") => LviMitigation::ControlFlow,
+            _ => LviMitigation::None,
+        }
+    }
+
+    /// LLVM target features enabling the mitigation in Rust code.
+    pub fn target_features(self) -> &'static [&'static str] {
+        match self {
+            LviMitigation::None => &[],
+            LviMitigation::ControlFlow => &["+lvi-cfi"],
+            LviMitigation::Load => &["+lvi-cfi", "+lvi-load-hardening"],
+        }
+    }
+
+    /// Extra rustc flags for the mitigation, on top of the target features.
+    pub fn rustflags(self) -> Vec<String> {
+        match self {
+            LviMitigation::None => Vec::new(),
+            LviMitigation::ControlFlow => {
+                vec!["-C".to_owned(), "llvm-args=--x86-experimental-lvi-inline-asm-hardening".to_owned()]
+            }
+            LviMitigation::Load => vec![
+                "-C".to_owned(),
+                "llvm-args=--x86-experimental-lvi-inline-asm-hardening".to_owned(),
+                "-C".to_owned(),
+                "llvm-args=--x86-lvi-load-no-cbranch".to_owned(),
+            ],
+        }
+    }
+
+    /// C and assembler flags, identical to `MITIGATION_CFLAGS` in `buildenv.mk`.
+    pub fn cflags(self) -> Vec<&'static str> {
+        match self {
+            LviMitigation::None => Vec::new(),
+            LviMitigation::ControlFlow => vec![
+                "-mindirect-branch-register",
+                "-fcf-protection=none",
+                "-mfunction-return=thunk-extern",
+                "-fno-plt",
+                "-Wa,-mlfence-before-indirect-branch=all",
+                "-Wa,-mlfence-before-ret=shl",
+            ],
+            LviMitigation::Load => vec![
+                "-mindirect-branch-register",
+                "-fcf-protection=none",
+                "-mfunction-return=thunk-extern",
+                "-fno-plt",
+                "-Wa,-mlfence-after-load=yes",
+                "-Wa,-mlfence-before-indirect-branch=memory",
+                "-Wa,-mlfence-before-ret=shl",
+            ],
+        }
+    }
+
+    /// Subdirectory of `$SGX_SDK/lib64` holding the mitigated trusted libraries.
+    pub fn sdk_lib_subdir(self) -> Option<&'static str> {
+        match self {
+            LviMitigation::None => None,
+            LviMitigation::ControlFlow => Some("cve_2020_0551_cf"),
+            LviMitigation::Load => Some("cve_2020_0551_load"),
+        }
+    }
+}
+
+/// The rustc and LLVM versions a target spec is generated for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Toolchain {
+    pub rustc: (u32, u32, u32),
+    pub llvm_major: u32,
+}
+
+impl Toolchain {
+    /// Queries `$RUSTC -vV`, falling back to `rustc` when `RUSTC` is unset.
+    pub fn detect() -> io::Result<Toolchain> {
+        let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+        let out = Command::new(rustc).arg("-vV").output()?;
+        if !out.status.success() {
+            return Err(io::Error::new(io::ErrorKind::Other, "rustc -vV failed"));
+        }
+        Toolchain::parse(&String::from_utf8_lossy(&out.stdout))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unrecognized rustc -vV output"))
+    }
+
+    /// Parses the output of `rustc -vV`.
+    pub fn parse(verbose_version: &str) -> Option<Toolchain> {
+        let mut rustc = None;
+        let mut llvm_major = None;
+        for line in verbose_version.lines() {
+            if let Some(v) = line.strip_prefix("release: ") {
+                let mut parts = v.split(|c: char| c == '.' || c == '-').map(|p| p.parse::<u32>());
+                if let (Some(Ok(a)), Some(Ok(b)), Some(Ok(c))) = (parts.next(), parts.next(), parts.next()) {
+                    rustc = Some((a, b, c));
+                }
+            } else if let Some(v) = line.strip_prefix("LLVM version: ") {
+                llvm_major = v.split('.').next().and_then(|m| m.parse().ok());
+            }
+        }
+        Some(Toolchain { rustc: rustc?, llvm_major: llvm_major? })
+    }
+
+    fn data_layout(&self) -> &'static str {
+        if self.llvm_major >= 18 {
+            "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-i128:128-f80:128-n8:16:32:64-S128"
+        } else if self.llvm_major >= 10 {
+            "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+        } else {
+            "e-m:e-i64:64-f80:128-n8:16:32:64-S128"
+        }
+    }
+
+    // Newer rustc parses the width fields as integers rather than strings.
+    fn numeric_widths(&self) -> bool {
+        self.rustc >= (1, 91, 0)
+    }
+
+    fn stack_probes(&self) -> &'static str {
+        if self.llvm_major >= 16 {
+            r#"{ "kind": "inline" }"#
+        } else {
+            r#"{ "kind": "inline-or-call", "min-llvm-version-for-inline": [11, 0, 1] }"#
+        }
+    }
+}
+
+/// Paths produced by [`EnclaveTarget::write_to`].
+#[derive(Clone, Debug)]
+pub struct TargetFiles {
+    pub target_json: PathBuf,
+    pub linker_script: PathBuf,
+}
+
+/// Builder for the enclave target spec, linker script and flags.
+#[derive(Clone, Debug)]
+pub struct EnclaveTarget {
+    toolchain: Toolchain,
+    lvi: LviMitigation,
+    exports: Vec<String>,
+    sdk_dir: PathBuf,
+    sim: bool,
+}
+
+impl EnclaveTarget {
+    /// Creates a builder for an explicit toolchain without mitigations.
+    pub fn new(toolchain: Toolchain) -> EnclaveTarget {
+        EnclaveTarget {
+            toolchain,
+            lvi: LviMitigation::None,
+            exports: REQUIRED_EXPORTS.iter().map(|s| s.to_string()).collect(),
+            sdk_dir: PathBuf::from("/opt/sgxsdk"),
+            sim: false,
+        }
+    }
+
+    /// Creates a builder for the running toolchain, reading `SGX_SDK`,
+    /// `SGX_MODE` and `MITIGATION-CVE-2020-0551` from the environment.
+    pub fn from_env() -> EnclaveTarget {
+        let toolchain = crate::t!(Toolchain::detect());
+        println!("cargo:rerun-if-env-changed=SGX_SDK");
+        let sdk_dir = env::var_os("SGX_SDK").map(PathBuf::from).unwrap_or_else(|| {
+            if Path::new("/opt/sgxsdk").exists() {
+                PathBuf::from("/opt/sgxsdk")
+            } else {
+                PathBuf::from("/opt/intel/sgxsdk")
+            }
+        });
+        println!("cargo:rerun-if-env-changed=SGX_MODE");
+        let sim = matches!(env::var("SGX_MODE").as_deref(), Ok("SW") | Ok("SIM"));
+        EnclaveTarget::new(toolchain)
+            .lvi(LviMitigation::from_env())
+            .sdk_dir(sdk_dir)
+            .simulation(sim)
+    }
+
+    /// Sets the LVI mitigation level.
+    pub fn lvi(mut self, lvi: LviMitigation) -> EnclaveTarget {
+        self.lvi = lvi;
+        self
+    }
+
+    /// Sets the Intel SGX SDK installation directory.
+    pub fn sdk_dir<P: Into<PathBuf>>(mut self, dir: P) -> EnclaveTarget {
+        self.sdk_dir = dir.into();
+        self
+    }
+
+    /// Links against the simulation variants of the trusted runtime.
+    pub fn simulation(mut self, sim: bool) -> EnclaveTarget {
+        self.sim = sim;
+        self
+    }
+
+    /// Exports an additional symbol, typically an ECALL bridge function.
+    pub fn export<S: Into<String>>(mut self, symbol: S) -> EnclaveTarget {
+        let symbol = symbol.into();
+        if !self.exports.contains(&symbol) {
+            self.exports.push(symbol);
+        }
+        self
+    }
+
+    /// The contents of `x86_64-unknown-linux-sgx.json`.
+    pub fn target_json(&self) -> String {
+        let mut features = String::new();
+        for (i, f) in self.lvi.target_features().iter().enumerate() {
+            if i > 0 {
+                features.push(',');
+            }
+            features.push_str(f);
+        }
+
+        let mut json = String::new();
+        json.push_str("{\n");
+        let mut field = |key: &str, value: &str| {
+            let _ = writeln!(json, "  \"{}\": {},", key, value);
+        };
+        field("arch", "\"x86_64\"");
+        field("cpu", "\"x86-64\"");
+        field("crt-static-respected", "true");
+        field("data-layout", &format!("\"{}\"", self.toolchain.data_layout()));
+        field("dynamic-linking", "true");
+        field("env", "\"sgx\"");
+        field("executables", "true");
+        if !features.is_empty() {
+            field("features", &format!("\"{}\"", features));
+        }
+        field("has-rpath", "true");
+        field("has-thread-local", "true");
+        field("linker-flavor", "\"gcc\"");
+        field("linker-is-gnu", "true");
+        field("llvm-target", "\"x86_64-unknown-linux-gnu\"");
+        field("max-atomic-width", "64");
+        field("os", "\"linux\"");
+        field("position-independent-executables", "true");
+        field(
+            "pre-link-args",
+            r#"{ "gcc": ["-Wl,--as-needed", "-Wl,-z,noexecstack", "-m64"] }"#,
+        );
+        field("relro-level", "\"full\"");
+        field("stack-probes", self.toolchain.stack_probes());
+        field("supported-sanitizers", r#"["address", "cfi", "leak", "memory", "thread"]"#);
+        let (c_int_width, pointer_width) =
+            if self.toolchain.numeric_widths() { ("32", "64") } else { ("\"32\"", "\"64\"") };
+        field("target-c-int-width", c_int_width);
+        field("target-endian", "\"little\"");
+        field("target-family", "\"unix\"");
+        field("target-pointer-width", pointer_width);
+        json.push_str("  \"vendor\": \"mesalock\"\n}\n");
+        json
+    }
+
+    /// The contents of the `Enclave.lds` version script.
+    pub fn linker_script(&self) -> String {
+        let mut lds = String::from("enclave.so\n{\n    global:\n");
+        for symbol in &self.exports {
+            let _ = writeln!(lds, "        {};", symbol);
+        }
+        lds.push_str("    local:\n        *;\n};\n");
+        lds
+    }
+
+    /// Flags to put in `RUSTFLAGS` when building the enclave staticlib.
+    pub fn rustflags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        if !self.lvi.target_features().is_empty() {
+            flags.push("-C".to_owned());
+            flags.push(format!("target-feature={}", self.lvi.target_features().join(",")));
+        }
+        flags.extend(self.lvi.rustflags());
+        flags
+    }
+
+    /// Flags for C and assembly sources compiled into the enclave.
+    pub fn cflags(&self) -> Vec<&'static str> {
+        self.lvi.cflags()
+    }
+
+    /// Trusted library search path, including the mitigated subdirectory.
+    pub fn sdk_lib_dir(&self) -> PathBuf {
+        let lib = self.sdk_dir.join("lib64");
+        match self.lvi.sdk_lib_subdir() {
+            Some(sub) => lib.join(sub),
+            None => lib,
+        }
+    }
+
+    /// Linker arguments for the final `enclave.so`, given the generated files.
+    pub fn link_args(&self, files: &TargetFiles) -> Vec<String> {
+        let suffix = if self.sim { "_sim" } else { "" };
+        vec![
+            "-Wl,--no-undefined".to_owned(),
+            "-nostdlib".to_owned(),
+            "-nodefaultlibs".to_owned(),
+            "-nostartfiles".to_owned(),
+            format!("-L{}", self.sdk_lib_dir().display()),
+            "-Wl,--whole-archive".to_owned(),
+            format!("-lsgx_trts{}", suffix),
+            "-Wl,--no-whole-archive".to_owned(),
+            "-Wl,--start-group".to_owned(),
+            "-lsgx_tstdc".to_owned(),
+            "-lsgx_tcxx".to_owned(),
+            "-lsgx_tcrypto".to_owned(),
+            format!("-lsgx_tservice{}", suffix),
+            "-Wl,--end-group".to_owned(),
+            format!("-Wl,--version-script={}", files.linker_script.display()),
+            "-Wl,-z,relro,-z,now,-z,noexecstack".to_owned(),
+            "-Wl,-Bstatic".to_owned(),
+            "-Wl,-Bsymbolic".to_owned(),
+            "-Wl,-pie,-eenclave_entry".to_owned(),
+            "-Wl,--export-dynamic".to_owned(),
+            "-Wl,--gc-sections".to_owned(),
+            "-Wl,--defsym,__ImageBase=0".to_owned(),
+        ]
+    }
+
+    /// Writes the target spec and linker script into `dir`.
+    pub fn write_to(&self, dir: &Path) -> io::Result<TargetFiles> {
+        fs::create_dir_all(dir)?;
+        let files = TargetFiles {
+            target_json: dir.join(format!("{}.json", TARGET_NAME)),
+            linker_script: dir.join("Enclave.lds"),
+        };
+        write_if_changed(&files.target_json, &self.target_json())?;
+        write_if_changed(&files.linker_script, &self.linker_script())?;
+        Ok(files)
+    }
+
+    /// Prints `cargo:rustc-link-arg` directives for a crate producing the
+    /// enclave image.
+    pub fn emit_link_args(&self, files: &TargetFiles) {
+        for arg in self.link_args(files) {
+            println!("cargo:rustc-link-arg={}", arg);
+        }
+    }
+}
+
+// Leave the mtime alone when nothing changed so cargo does not rebuild.
+pub(crate) fn write_if_changed(path: &Path, contents: &str) -> io::Result<()> {
+    match fs::read_to_string(path) {
+        Ok(old) if old == contents => Ok(()),
+        _ => fs::write(path, contents),
+    }
+}
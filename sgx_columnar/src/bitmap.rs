@@ -0,0 +1,188 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use alloc::vec::Vec;
+use sgx_types::*;
+
+///
+/// A view of a validity or selection bitmap in the Arrow layout.
+///
+/// Bit `i` of the view is bit `(offset + i) % 8` of byte `(offset + i) / 8`, the
+/// least significant bit first. A set bit means valid or selected.
+///
+#[derive(Clone, Copy, Debug)]
+pub struct BitSlice<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    len: usize,
+}
+
+impl<'a> BitSlice<'a> {
+    ///
+    /// Returns a view of `len` bits from bit `offset` of `bytes`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `bytes` holds fewer than `offset + len` bits.
+    ///
+    pub fn new(bytes: &'a [u8], offset: usize, len: usize) -> SgxResult<BitSlice<'a>> {
+        let bits = offset
+            .checked_add(len)
+            .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+        if bytes.len() < (bits + 7) / 8 {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        Ok(BitSlice { bytes, offset, len })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        assert!(i < self.len, "bit index out of range");
+        let bit = self.offset + i;
+        (self.bytes[bit / 8] >> (bit % 8)) & 1 == 1
+    }
+
+    ///
+    /// Returns the number of set bits. Bits outside the view are ignored.
+    ///
+    pub fn count_ones(&self) -> usize {
+        let whole = self.len / 8;
+        let mut count: usize = (0..whole).map(|k| self.byte(k).count_ones() as usize).sum();
+        for i in whole * 8..self.len {
+            count += self.get(i) as usize;
+        }
+        count
+    }
+
+    // Bits 8k to 8k + 7 of the view, which must all lie within it.
+    #[inline(always)]
+    pub(crate) fn byte(&self, k: usize) -> u8 {
+        let bit = self.offset + k * 8;
+        let shift = bit % 8;
+        let lo = self.bytes[bit / 8] >> shift;
+        if shift == 0 {
+            lo
+        } else {
+            lo | (self.bytes[bit / 8 + 1] << (8 - shift))
+        }
+    }
+}
+
+///
+/// An owned bitmap in the Arrow layout, with the bits past its length cleared.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bitmap {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl Bitmap {
+    ///
+    /// Returns a bitmap of `len` cleared bits.
+    ///
+    pub fn new(len: usize) -> Bitmap {
+        Bitmap {
+            bytes: vec![0; (len + 7) / 8],
+            len,
+        }
+    }
+
+    ///
+    /// Copies the bits of `bits` into a new bitmap at offset zero.
+    ///
+    pub fn from_bit_slice(bits: &BitSlice<'_>) -> Bitmap {
+        let mut bitmap = Bitmap::new(bits.len());
+        let whole = bits.len() / 8;
+        for k in 0..whole {
+            bitmap.bytes[k] = bits.byte(k);
+        }
+        for i in whole * 8..bits.len() {
+            bitmap.set(i, bits.get(i));
+        }
+        bitmap
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        self.as_bit_slice().get(i)
+    }
+
+    pub fn set(&mut self, i: usize, value: bool) {
+        assert!(i < self.len, "bit index out of range");
+        if value {
+            self.bytes[i / 8] |= 1 << (i % 8);
+        } else {
+            self.bytes[i / 8] &= !(1 << (i % 8));
+        }
+    }
+
+    ///
+    /// Appends a bit.
+    ///
+    pub fn push(&mut self, value: bool) {
+        if self.len % 8 == 0 {
+            self.bytes.push(0);
+        }
+        self.len += 1;
+        self.set(self.len - 1, value);
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.bytes.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    ///
+    /// Returns the bytes of the bitmap, to hand out as an Arrow buffer.
+    ///
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn as_bit_slice(&self) -> BitSlice<'_> {
+        BitSlice {
+            bytes: &self.bytes,
+            offset: 0,
+            len: self.len,
+        }
+    }
+
+    pub(crate) fn from_parts(bytes: Vec<u8>, len: usize) -> Bitmap {
+        debug_assert_eq!(bytes.len(), (len + 7) / 8);
+        Bitmap { bytes, len }
+    }
+}
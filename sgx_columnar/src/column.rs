@@ -0,0 +1,466 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::bitmap::{BitSlice, Bitmap};
+use alloc::vec::Vec;
+use core::mem;
+use core::ptr;
+use core::str;
+use sgx_trts::trts::{rsgx_raw_is_outside_enclave, rsgx_raw_is_within_enclave};
+use sgx_types::*;
+
+mod private {
+    pub trait Sealed {}
+}
+
+///
+/// The fixed-width types of Arrow primitive arrays, in their native layout.
+///
+pub trait NativeType:
+    Copy + Default + PartialOrd + Send + Sync + 'static + private::Sealed
+{
+    ///
+    /// Addition that wraps on overflow for integers, as Arrow compute kernels do.
+    ///
+    fn add_wrapping(self, other: Self) -> Self;
+}
+
+///
+/// The integer types, which may serve as group-by keys.
+///
+pub trait IntegerType: NativeType + Eq {
+    fn to_bits(self) -> u64;
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {$(
+        impl private::Sealed for $t {}
+
+        impl NativeType for $t {
+            #[inline(always)]
+            fn add_wrapping(self, other: $t) -> $t {
+                self.wrapping_add(other)
+            }
+        }
+
+        impl IntegerType for $t {
+            #[inline(always)]
+            fn to_bits(self) -> u64 {
+                self as u64
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_float {
+    ($($t:ty),*) => {$(
+        impl private::Sealed for $t {}
+
+        impl NativeType for $t {
+            #[inline(always)]
+            fn add_wrapping(self, other: $t) -> $t {
+                self + other
+            }
+        }
+    )*};
+}
+
+impl_integer!(i8, i16, i32, i64, u8, u16, u32, u64);
+impl_float!(f32, f64);
+
+fn is_within_enclave<T>(slice: &[T]) -> bool {
+    slice.is_empty()
+        || rsgx_raw_is_within_enclave(slice.as_ptr() as *const u8, mem::size_of_val(slice))
+}
+
+///
+/// A borrowed Arrow primitive array: a values buffer and an optional validity bitmap.
+///
+/// # Description
+///
+/// The buffers must be in enclave memory. Buffers that the untrusted side supplies
+/// may change while they are read, and must first be copied in with
+/// `ColumnBuffer::copy_from_untrusted`.
+///
+/// The values under null slots are unspecified, as in Arrow, and are never read by
+/// the kernels.
+///
+#[derive(Clone, Copy, Debug)]
+pub struct PrimitiveColumn<'a, T: NativeType> {
+    values: &'a [T],
+    validity: Option<BitSlice<'a>>,
+}
+
+impl<'a, T: NativeType> PrimitiveColumn<'a, T> {
+    ///
+    /// Returns a column of `values`, with bit `offset + i` of `validity` telling
+    /// whether value `i` is valid, or all values valid without `validity`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The validity bitmap is too short, or a buffer is not within the enclave.
+    ///
+    pub fn new(
+        values: &'a [T],
+        validity: Option<(&'a [u8], usize)>,
+    ) -> SgxResult<PrimitiveColumn<'a, T>> {
+        if !is_within_enclave(values) {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let validity = match validity {
+            Some((bytes, offset)) => {
+                if !is_within_enclave(bytes) {
+                    return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+                }
+                Some(BitSlice::new(bytes, offset, values.len())?)
+            }
+            None => None,
+        };
+        Ok(PrimitiveColumn { values, validity })
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    ///
+    /// Returns the values buffer, including the unspecified values of null slots.
+    ///
+    pub fn values(&self) -> &'a [T] {
+        self.values
+    }
+
+    pub fn validity(&self) -> Option<BitSlice<'a>> {
+        self.validity
+    }
+
+    pub fn is_valid(&self, i: usize) -> bool {
+        self.validity.map_or(true, |v| v.get(i))
+    }
+
+    pub fn get(&self, i: usize) -> Option<T> {
+        if self.is_valid(i) {
+            Some(self.values[i])
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// Returns the number of null slots, counted from the bitmap.
+    ///
+    /// The null count that Arrow producers record beside the buffers is not trusted.
+    ///
+    pub fn null_count(&self) -> usize {
+        self.validity.map_or(0, |v| v.len() - v.count_ones())
+    }
+}
+
+///
+/// An owned Arrow primitive array.
+///
+/// The values buffer is aligned for `T`. Arrow recommends, but does not require,
+/// 64-byte alignment; consumers that need it must copy.
+///
+#[derive(Clone, Debug, Default)]
+pub struct ColumnBuffer<T: NativeType> {
+    values: Vec<T>,
+    validity: Option<Bitmap>,
+}
+
+impl<T: NativeType> ColumnBuffer<T> {
+    ///
+    /// Returns a column of `values`, all valid.
+    ///
+    pub fn from_vec(values: Vec<T>) -> ColumnBuffer<T> {
+        ColumnBuffer {
+            values,
+            validity: None,
+        }
+    }
+
+    ///
+    /// Returns a column of `values` with the validity bitmap `validity`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The lengths of `values` and `validity` differ.
+    ///
+    pub fn with_validity(values: Vec<T>, validity: Bitmap) -> SgxResult<ColumnBuffer<T>> {
+        if values.len() != validity.len() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        Ok(ColumnBuffer {
+            values,
+            validity: Some(validity),
+        })
+    }
+
+    ///
+    /// Copies an Arrow primitive array of `len` slots from untrusted memory.
+    ///
+    /// # Description
+    ///
+    /// `values` points to the values buffer, already advanced by the offset of the
+    /// array, and `validity` to the validity bitmap, whose bits start at bit
+    /// `offset`. Both buffers are copied before anything is read from them, so the
+    /// untrusted side cannot change them between validation and use.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// A buffer is null or not entirely outside the enclave, or its size overflows.
+    ///
+    /// # Safety
+    ///
+    /// The buffers must be readable for their whole size.
+    ///
+    pub unsafe fn copy_from_untrusted(
+        values: *const T,
+        validity: Option<*const u8>,
+        offset: usize,
+        len: usize,
+    ) -> SgxResult<ColumnBuffer<T>> {
+        let size = len
+            .checked_mul(mem::size_of::<T>())
+            .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+        if len > 0
+            && (values.is_null()
+                || values as usize % mem::align_of::<T>() != 0
+                || !rsgx_raw_is_outside_enclave(values as *const u8, size))
+        {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let mut copied = Vec::with_capacity(len);
+        if len > 0 {
+            ptr::copy_nonoverlapping(values, copied.as_mut_ptr(), len);
+            copied.set_len(len);
+        }
+
+        let validity = match validity {
+            Some(bytes) => {
+                let bits = offset
+                    .checked_add(len)
+                    .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+                let size = (bits + 7) / 8;
+                if size > 0 && (bytes.is_null() || !rsgx_raw_is_outside_enclave(bytes, size)) {
+                    return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+                }
+                let mut raw = vec![0_u8; size];
+                if size > 0 {
+                    ptr::copy_nonoverlapping(bytes, raw.as_mut_ptr(), size);
+                }
+                Some(Bitmap::from_bit_slice(&BitSlice::new(&raw, offset, len)?))
+            }
+            None => None,
+        };
+        Ok(ColumnBuffer {
+            values: copied,
+            validity,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    pub fn validity(&self) -> Option<&Bitmap> {
+        self.validity.as_ref()
+    }
+
+    pub fn null_count(&self) -> usize {
+        self.validity
+            .as_ref()
+            .map_or(0, |v| v.len() - v.count_ones())
+    }
+
+    pub fn as_column(&self) -> PrimitiveColumn<'_, T> {
+        PrimitiveColumn {
+            values: &self.values,
+            validity: self.validity.as_ref().map(|v| v.as_bit_slice()),
+        }
+    }
+
+    pub fn into_parts(self) -> (Vec<T>, Option<Bitmap>) {
+        (self.values, self.validity)
+    }
+}
+
+///
+/// A borrowed Arrow variable-length binary array: 32-bit offsets into a data buffer.
+///
+#[derive(Clone, Copy, Debug)]
+pub struct BinaryColumn<'a> {
+    offsets: &'a [i32],
+    data: &'a [u8],
+    validity: Option<BitSlice<'a>>,
+}
+
+impl<'a> BinaryColumn<'a> {
+    ///
+    /// Returns a column whose value `i` is `data[offsets[i]..offsets[i + 1]]`.
+    ///
+    /// # Description
+    ///
+    /// The offsets are checked once here, so that reading values needs no checks:
+    /// there must be at least one, none negative, in non-decreasing order, and the
+    /// last within `data`. This holds for the offsets of null slots too, as Arrow
+    /// requires.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The offsets are invalid, the validity bitmap is too short, or a buffer is
+    /// not within the enclave.
+    ///
+    pub fn new(
+        offsets: &'a [i32],
+        data: &'a [u8],
+        validity: Option<(&'a [u8], usize)>,
+    ) -> SgxResult<BinaryColumn<'a>> {
+        if offsets.is_empty() || !is_within_enclave(offsets) || !is_within_enclave(data) {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        if offsets[0] < 0
+            || offsets.windows(2).any(|w| w[0] > w[1])
+            || offsets[offsets.len() - 1] as usize > data.len()
+        {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let len = offsets.len() - 1;
+        let validity = match validity {
+            Some((bytes, offset)) => {
+                if !is_within_enclave(bytes) {
+                    return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+                }
+                Some(BitSlice::new(bytes, offset, len)?)
+            }
+            None => None,
+        };
+        Ok(BinaryColumn {
+            offsets,
+            data,
+            validity,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn validity(&self) -> Option<BitSlice<'a>> {
+        self.validity
+    }
+
+    pub fn is_valid(&self, i: usize) -> bool {
+        self.validity.map_or(true, |v| v.get(i))
+    }
+
+    pub fn get(&self, i: usize) -> Option<&'a [u8]> {
+        if self.is_valid(i) {
+            Some(&self.data[self.offsets[i] as usize..self.offsets[i + 1] as usize])
+        } else {
+            None
+        }
+    }
+
+    pub fn null_count(&self) -> usize {
+        self.validity.map_or(0, |v| v.len() - v.count_ones())
+    }
+}
+
+///
+/// A borrowed Arrow UTF-8 string array, a binary array whose values are UTF-8.
+///
+#[derive(Clone, Copy, Debug)]
+pub struct Utf8Column<'a> {
+    inner: BinaryColumn<'a>,
+}
+
+impl<'a> Utf8Column<'a> {
+    ///
+    /// Returns a string column over the same buffers as `BinaryColumn::new`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// As `BinaryColumn::new`, or a valid slot is not UTF-8.
+    ///
+    pub fn new(
+        offsets: &'a [i32],
+        data: &'a [u8],
+        validity: Option<(&'a [u8], usize)>,
+    ) -> SgxResult<Utf8Column<'a>> {
+        let inner = BinaryColumn::new(offsets, data, validity)?;
+        for i in 0..inner.len() {
+            if let Some(value) = inner.get(i) {
+                str::from_utf8(value).map_err(|_| sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+            }
+        }
+        Ok(Utf8Column { inner })
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn is_valid(&self, i: usize) -> bool {
+        self.inner.is_valid(i)
+    }
+
+    pub fn get(&self, i: usize) -> Option<&'a str> {
+        // Valid slots were checked to be UTF-8 when the column was created.
+        self.inner
+            .get(i)
+            .map(|value| unsafe { str::from_utf8_unchecked(value) })
+    }
+
+    pub fn null_count(&self) -> usize {
+        self.inner.null_count()
+    }
+
+    pub fn as_binary(&self) -> &BinaryColumn<'a> {
+        &self.inner
+    }
+}
@@ -0,0 +1,448 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+// The kernels are written over blocks of LANES values with no data-dependent
+// branches, which the compiler turns into vector code. Each is compiled twice,
+// for the baseline target and for AVX2, and the AVX2 build is chosen when the
+// CPU reports it.
+
+use crate::bitmap::{BitSlice, Bitmap};
+use crate::column::{ColumnBuffer, IntegerType, NativeType, PrimitiveColumn};
+use alloc::vec::Vec;
+use sgx_trts::is_x86_feature_detected;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+const LANES: usize = 8;
+
+fn has_avx2() -> bool {
+    is_x86_feature_detected!("avx2")
+}
+
+macro_rules! dispatch {
+    ($portable:ident, $avx2:ident, <$($g:ident: $b:path),*>, ($($arg:ident: $ty:ty),*) -> $ret:ty) => {
+        #[target_feature(enable = "avx2")]
+        unsafe fn $avx2<$($g: $b),*>($($arg: $ty),*) -> $ret {
+            $portable($($arg),*)
+        }
+    };
+}
+
+#[inline(always)]
+fn sum_dense<T: NativeType>(values: &[T]) -> T {
+    let mut acc = [T::default(); LANES];
+    let chunks = values.chunks_exact(LANES);
+    let rest = chunks.remainder();
+    for chunk in chunks {
+        for l in 0..LANES {
+            acc[l] = acc[l].add_wrapping(chunk[l]);
+        }
+    }
+    let mut sum = acc.iter().fold(T::default(), |s, v| s.add_wrapping(*v));
+    for v in rest {
+        sum = sum.add_wrapping(*v);
+    }
+    sum
+}
+
+#[inline(always)]
+fn sum_masked<T: NativeType>(values: &[T], validity: &BitSlice<'_>) -> T {
+    let mut acc = [T::default(); LANES];
+    let chunks = values.chunks_exact(LANES);
+    let rest = chunks.remainder();
+    for (k, chunk) in chunks.enumerate() {
+        let mask = validity.byte(k);
+        for l in 0..LANES {
+            let v = if (mask >> l) & 1 == 1 {
+                chunk[l]
+            } else {
+                T::default()
+            };
+            acc[l] = acc[l].add_wrapping(v);
+        }
+    }
+    let mut sum = acc.iter().fold(T::default(), |s, v| s.add_wrapping(*v));
+    let base = values.len() - rest.len();
+    for (i, v) in rest.iter().enumerate() {
+        if validity.get(base + i) {
+            sum = sum.add_wrapping(*v);
+        }
+    }
+    sum
+}
+
+dispatch!(sum_dense, sum_dense_avx2, <T: NativeType>, (values: &[T]) -> T);
+dispatch!(sum_masked, sum_masked_avx2, <T: NativeType>, (values: &[T], validity: &BitSlice<'_>) -> T);
+
+///
+/// Returns the sum of the valid values of `column`, or `None` if it has none.
+///
+/// # Description
+///
+/// Integer sums wrap on overflow. Floating-point values are summed in several
+/// lanes that are added at the end, so the result may differ in the last bits from
+/// a sequential sum.
+///
+pub fn sum<T: NativeType>(column: &PrimitiveColumn<'_, T>) -> Option<T> {
+    let values = column.values();
+    match column.validity() {
+        None if values.is_empty() => None,
+        None if has_avx2() => Some(unsafe { sum_dense_avx2(values) }),
+        None => Some(sum_dense(values)),
+        Some(validity) => {
+            if validity.count_ones() == 0 {
+                return None;
+            }
+            if has_avx2() {
+                Some(unsafe { sum_masked_avx2(values, &validity) })
+            } else {
+                Some(sum_masked(values, &validity))
+            }
+        }
+    }
+}
+
+///
+/// The comparisons of `compare_scalar`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[inline(always)]
+fn compare_with<T: NativeType, F: Fn(T) -> bool>(values: &[T], f: F, out: &mut [u8]) {
+    let chunks = values.chunks_exact(LANES);
+    let rest = chunks.remainder();
+    for (chunk, byte) in chunks.zip(out.iter_mut()) {
+        let mut bits = 0_u8;
+        for (l, v) in chunk.iter().enumerate() {
+            bits |= (f(*v) as u8) << l;
+        }
+        *byte = bits;
+    }
+    if !rest.is_empty() {
+        let mut bits = 0_u8;
+        for (l, v) in rest.iter().enumerate() {
+            bits |= (f(*v) as u8) << l;
+        }
+        out[values.len() / LANES] = bits;
+    }
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn compare_with_avx2<T: NativeType, F: Fn(T) -> bool>(values: &[T], f: F, out: &mut [u8]) {
+    compare_with(values, f, out)
+}
+
+fn compare_values<T: NativeType, F: Fn(T) -> bool>(values: &[T], f: F, out: &mut [u8]) {
+    if has_avx2() {
+        unsafe { compare_with_avx2(values, f, out) }
+    } else {
+        compare_with(values, f, out)
+    }
+}
+
+///
+/// Compares every value of `column` with `scalar`, and returns the bitmap of the
+/// slots where the comparison holds, for `filter`.
+///
+/// Null slots are never selected. NaN compares as IEEE 754 says: only `Ne` holds.
+///
+pub fn compare_scalar<T: NativeType>(
+    column: &PrimitiveColumn<'_, T>,
+    op: CmpOp,
+    scalar: T,
+) -> Bitmap {
+    let values = column.values();
+    let mut bytes = vec![0_u8; (values.len() + 7) / 8];
+    match op {
+        CmpOp::Eq => compare_values(values, |v| v == scalar, &mut bytes),
+        CmpOp::Ne => compare_values(values, |v| v != scalar, &mut bytes),
+        CmpOp::Lt => compare_values(values, |v| v < scalar, &mut bytes),
+        CmpOp::Le => compare_values(values, |v| v <= scalar, &mut bytes),
+        CmpOp::Gt => compare_values(values, |v| v > scalar, &mut bytes),
+        CmpOp::Ge => compare_values(values, |v| v >= scalar, &mut bytes),
+    }
+    if let Some(validity) = column.validity() {
+        for (k, byte) in bytes.iter_mut().enumerate().take(values.len() / 8) {
+            *byte &= validity.byte(k);
+        }
+        for i in values.len() / 8 * 8..values.len() {
+            if !validity.get(i) {
+                bytes[i / 8] &= !(1 << (i % 8));
+            }
+        }
+    }
+    Bitmap::from_parts(bytes, values.len())
+}
+
+///
+/// Returns the slots of `column` whose bit in `selection` is set, in order.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// `selection` and `column` differ in length.
+///
+pub fn filter<T: NativeType>(
+    column: &PrimitiveColumn<'_, T>,
+    selection: &Bitmap,
+) -> SgxResult<ColumnBuffer<T>> {
+    if selection.len() != column.len() {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    let values = column.values();
+    let count = selection.count_ones();
+    let mut out = Vec::with_capacity(count);
+    let mut indices = Vec::new();
+    for (k, byte) in selection.as_bytes().iter().enumerate() {
+        let base = k * 8;
+        match *byte {
+            0 => (),
+            0xff => out.extend_from_slice(&values[base..base + 8]),
+            mut bits => {
+                while bits != 0 {
+                    out.push(values[base + bits.trailing_zeros() as usize]);
+                    bits &= bits - 1;
+                }
+            }
+        }
+        if column.validity().is_some() && *byte != 0 {
+            let mut bits = *byte;
+            while bits != 0 {
+                indices.push(base + bits.trailing_zeros() as usize);
+                bits &= bits - 1;
+            }
+        }
+    }
+    match column.validity() {
+        None => Ok(ColumnBuffer::from_vec(out)),
+        Some(validity) => {
+            let mut bitmap = Bitmap::new(out.len());
+            for (j, i) in indices.into_iter().enumerate() {
+                bitmap.set(j, validity.get(i));
+            }
+            ColumnBuffer::with_validity(out, bitmap)
+        }
+    }
+}
+
+///
+/// The result of `group_by_sum`: one slot per distinct key, in order of first
+/// appearance.
+///
+#[derive(Clone, Debug)]
+pub struct GroupedSums<K: IntegerType, V: NativeType> {
+    ///
+    /// The distinct keys. Null keys form one group, whose key is null.
+    ///
+    pub keys: ColumnBuffer<K>,
+    ///
+    /// The sum of the valid values of each group, null if it has none.
+    ///
+    pub sums: ColumnBuffer<V>,
+    ///
+    /// The number of valid values of each group.
+    ///
+    pub counts: Vec<u64>,
+}
+
+const EMPTY: u32 = u32::MAX;
+
+struct GroupTable<K: IntegerType> {
+    slots: Vec<u32>,
+    shift: u32,
+    seed: u64,
+    keys: Vec<K>,
+}
+
+impl<K: IntegerType> GroupTable<K> {
+    fn new(seed: u64) -> GroupTable<K> {
+        GroupTable {
+            slots: vec![EMPTY; 16],
+            shift: 64 - 4,
+            seed,
+            keys: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    fn hash(&self, key: K) -> u64 {
+        (key.to_bits() ^ self.seed).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    }
+
+    // Returns the group of the key whose hash is `hash`, creating it if needed.
+    fn group(&mut self, key: K, hash: u64) -> usize {
+        let mask = self.slots.len() - 1;
+        let mut slot = (hash >> self.shift) as usize;
+        loop {
+            match self.slots[slot] {
+                EMPTY => break,
+                g if self.keys[g as usize] == key => return g as usize,
+                _ => slot = (slot + 1) & mask,
+            }
+        }
+        let group = self.keys.len();
+        self.keys.push(key);
+        self.slots[slot] = group as u32;
+        if self.keys.len() * 2 > self.slots.len() {
+            self.grow();
+        }
+        group
+    }
+
+    fn grow(&mut self) {
+        self.slots = vec![EMPTY; self.slots.len() * 2];
+        self.shift -= 1;
+        let mask = self.slots.len() - 1;
+        for (g, key) in self.keys.iter().enumerate() {
+            let mut slot = (self.hash(*key) >> self.shift) as usize;
+            while self.slots[slot] != EMPTY {
+                slot = (slot + 1) & mask;
+            }
+            self.slots[slot] = g as u32;
+        }
+    }
+}
+
+#[inline(always)]
+fn hash_block<K: IntegerType>(keys: &[K], seed: u64, out: &mut [u64; LANES]) {
+    for l in 0..LANES {
+        out[l] = (keys[l].to_bits() ^ seed).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+}
+
+dispatch!(hash_block, hash_block_avx2, <K: IntegerType>, (keys: &[K], seed: u64, out: &mut [u64; LANES]) -> ());
+
+///
+/// Groups the rows of `values` by the rows of `keys`, and sums each group.
+///
+/// # Description
+///
+/// The keys are hashed a block at a time with a seed drawn from the enclave's
+/// random source, so that keys chosen by an untrusted party cannot predictably
+/// collide in the hash table.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// `keys` and `values` differ in length, or there are 2^31 groups or more.
+///
+/// **SGX_ERROR_UNEXPECTED**
+///
+/// The seed could not be drawn.
+///
+pub fn group_by_sum<K: IntegerType, V: NativeType>(
+    keys: &PrimitiveColumn<'_, K>,
+    values: &PrimitiveColumn<'_, V>,
+) -> SgxResult<GroupedSums<K, V>> {
+    if keys.len() != values.len() {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    let mut seed = [0_u8; 8];
+    rsgx_read_rand(&mut seed)?;
+    let mut table = GroupTable::new(u64::from_le_bytes(seed));
+    let avx2 = has_avx2();
+
+    let mut sums: Vec<V> = Vec::new();
+    let mut counts: Vec<u64> = Vec::new();
+    let mut null_group: Option<usize> = None;
+    // The group of each entry of `sums`, or `None` for the null group.
+    let mut group_keys: Vec<Option<usize>> = Vec::new();
+    let mut by_table: Vec<usize> = Vec::new();
+
+    let key_values = keys.values();
+    let mut hashes = [0_u64; LANES];
+    for base in (0..keys.len()).step_by(LANES) {
+        let n = LANES.min(keys.len() - base);
+        if n == LANES {
+            let block = &key_values[base..base + LANES];
+            if avx2 {
+                unsafe { hash_block_avx2(block, table.seed, &mut hashes) };
+            } else {
+                hash_block(block, table.seed, &mut hashes);
+            }
+        } else {
+            for l in 0..n {
+                hashes[l] = table.hash(key_values[base + l]);
+            }
+        }
+        for (l, hash) in hashes.iter().enumerate().take(n) {
+            let row = base + l;
+            let out = if keys.is_valid(row) {
+                let group = table.group(key_values[row], *hash);
+                if group >= i32::MAX as usize {
+                    return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+                }
+                if group == by_table.len() {
+                    by_table.push(sums.len());
+                    sums.push(V::default());
+                    counts.push(0);
+                    group_keys.push(Some(group));
+                }
+                by_table[group]
+            } else {
+                *null_group.get_or_insert_with(|| {
+                    sums.push(V::default());
+                    counts.push(0);
+                    group_keys.push(None);
+                    sums.len() - 1
+                })
+            };
+            if let Some(v) = values.get(row) {
+                sums[out] = sums[out].add_wrapping(v);
+                counts[out] += 1;
+            }
+        }
+    }
+
+    let mut out_keys = Vec::with_capacity(group_keys.len());
+    let mut key_validity = Bitmap::new(group_keys.len());
+    for (i, group) in group_keys.iter().enumerate() {
+        match group {
+            Some(g) => {
+                out_keys.push(table.keys[*g]);
+                key_validity.set(i, true);
+            }
+            None => out_keys.push(K::default()),
+        }
+    }
+    let keys = if null_group.is_some() {
+        ColumnBuffer::with_validity(out_keys, key_validity)?
+    } else {
+        ColumnBuffer::from_vec(out_keys)
+    };
+
+    let mut sum_validity = Bitmap::new(counts.len());
+    for (i, count) in counts.iter().enumerate() {
+        sum_validity.set(i, *count > 0);
+    }
+    let sums = if sum_validity.count_ones() == counts.len() {
+        ColumnBuffer::from_vec(sums)
+    } else {
+        ColumnBuffer::with_validity(sums, sum_validity)?
+    };
+    Ok(GroupedSums { keys, sums, counts })
+}
@@ -0,0 +1,58 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Columnar Data Processing
+//!
+//! The library processes data in the Arrow columnar layout inside the enclave, one
+//! column at a time rather than one row at a time.
+//!
+//! * Borrowed columns over Arrow buffers, `PrimitiveColumn`, `BinaryColumn` and
+//!   `Utf8Column`, checked once when created so that the kernels read them without
+//!   further checks.
+//! * Owned columns, `ColumnBuffer`, including copies of buffers supplied by the
+//!   untrusted side, which are copied before they are validated.
+//! * Vectorized kernels: `sum`, `compare_scalar` and `filter`, and a hash
+//!   `group_by_sum`. They use AVX2 when the CPU reports it.
+//!
+//! The buffers are those of the Arrow C data interface, so that columns can be
+//! exchanged with Arrow implementations outside the enclave without conversion.
+//! Null counts that producers record beside the buffers are not trusted, and are
+//! counted from the validity bitmaps instead.
+//!
+
+#![no_std]
+#![cfg_attr(
+    all(target_env = "sgx", target_vendor = "mesalock"),
+    feature(rustc_private)
+)]
+
+#[macro_use]
+extern crate alloc;
+
+extern crate sgx_trts;
+extern crate sgx_types;
+
+mod bitmap;
+pub use self::bitmap::{BitSlice, Bitmap};
+
+mod column;
+pub use self::column::{
+    BinaryColumn, ColumnBuffer, IntegerType, NativeType, PrimitiveColumn, Utf8Column,
+};
+
+mod kernels;
+pub use self::kernels::{compare_scalar, filter, group_by_sum, sum, CmpOp, GroupedSums};
@@ -0,0 +1,69 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Gating of sensitive OCALL classes.
+//!
+//! The OCALL wrappers of the classes below ask the checker installed with
+//! [`set_ocall_gate`] before leaving the enclave, and fail with `EPERM` when
+//! it denies the call. Until a checker is installed every OCALL is made.
+
+use super::*;
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// OCALLs of `sgx_process.edl`, which act on the host process.
+pub const OCALL_CLASS_PROCESS: u32 = 0x0000_0001;
+/// File system OCALLs that create, modify or remove files, including opening
+/// a file for writing.
+pub const OCALL_CLASS_FS_WRITE: u32 = 0x0000_0002;
+
+pub const OCALL_CLASS_ALL: u32 = OCALL_CLASS_PROCESS | OCALL_CLASS_FS_WRITE;
+
+static GATE: AtomicUsize = AtomicUsize::new(0);
+
+/// Installs the checker consulted by the gated OCALL wrappers.
+///
+/// The checker can be installed once, so that it cannot be replaced later;
+/// returns `false` if one is installed already.
+pub fn set_ocall_gate(check: fn(u32) -> bool) -> bool {
+    GATE.compare_exchange(0, check as usize, Ordering::AcqRel, Ordering::Acquire)
+        .is_ok()
+}
+
+/// Returns `true` if a checker is installed.
+pub fn is_ocall_gate_set() -> bool {
+    GATE.load(Ordering::Acquire) != 0
+}
+
+// Sets errno to EPERM when the OCALL must not be made.
+pub(crate) fn ocall_allowed(class: u32) -> bool {
+    let gate = GATE.load(Ordering::Acquire);
+    if gate == 0 {
+        return true;
+    }
+    let check: fn(u32) -> bool = unsafe { mem::transmute(gate) };
+    if check(class) {
+        true
+    } else {
+        set_errno(EPERM);
+        false
+    }
+}
+
+pub(crate) fn opens_for_writing(flags: c_int) -> bool {
+    flags & (O_ACCMODE | O_CREAT | O_TRUNC | O_APPEND) != 0
+}
@@ -0,0 +1,260 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Pluggable backend of the OCALLs that carry data in from the host.
+//!
+//! The wrappers of `read`, `write`, `recv`, `send`, `clock_gettime` and
+//! `epoll_wait` hand the call to the [`HostCalls`] backend installed with
+//! [`set_host_calls`], once they have checked their buffers. Until a backend
+//! is installed they make the OCALL themselves, as [`Native`] does. A backend
+//! that records the calls, or answers them from a recording, lets a run of
+//! the enclave be reproduced exactly.
+
+use super::*;
+use alloc::boxed::Box;
+use core::cmp;
+use core::ptr;
+use core::slice;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use sgx_types::sgx_is_within_enclave;
+
+static HOST: AtomicPtr<&'static dyn HostCalls> = AtomicPtr::new(ptr::null_mut());
+
+/// The OCALLs routed through a backend. Errors are `errno` values.
+pub trait HostCalls: Sync {
+    fn read(&self, fd: c_int, buf: &mut [u8]) -> Result<usize, c_int>;
+
+    fn write(&self, fd: c_int, buf: &[u8]) -> Result<usize, c_int>;
+
+    fn recv(&self, sockfd: c_int, buf: &mut [u8], flags: c_int) -> Result<usize, c_int>;
+
+    fn send(&self, sockfd: c_int, buf: &[u8], flags: c_int) -> Result<usize, c_int>;
+
+    fn clock_gettime(&self, clk_id: clockid_t) -> Result<timespec, c_int>;
+
+    fn epoll_wait(
+        &self,
+        epfd: c_int,
+        events: &mut [epoll_event],
+        timeout: c_int,
+    ) -> Result<usize, c_int>;
+}
+
+/// The backend that makes the OCALLs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Native;
+
+impl HostCalls for Native {
+    fn read(&self, fd: c_int, buf: &mut [u8]) -> Result<usize, c_int> {
+        size(unsafe { ocall::native_read(fd, buf.as_mut_ptr() as *mut c_void, buf.len()) })
+    }
+
+    fn write(&self, fd: c_int, buf: &[u8]) -> Result<usize, c_int> {
+        size(unsafe { ocall::native_write(fd, buf.as_ptr() as *const c_void, buf.len()) })
+    }
+
+    fn recv(&self, sockfd: c_int, buf: &mut [u8], flags: c_int) -> Result<usize, c_int> {
+        size(unsafe {
+            ocall::native_recv(sockfd, buf.as_mut_ptr() as *mut c_void, buf.len(), flags)
+        })
+    }
+
+    fn send(&self, sockfd: c_int, buf: &[u8], flags: c_int) -> Result<usize, c_int> {
+        size(unsafe { ocall::native_send(sockfd, buf.as_ptr() as *const c_void, buf.len(), flags) })
+    }
+
+    fn clock_gettime(&self, clk_id: clockid_t) -> Result<timespec, c_int> {
+        let mut tp = timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        match unsafe { ocall::native_clock_gettime(clk_id, &mut tp) } {
+            -1 => Err(errno()),
+            _ => Ok(tp),
+        }
+    }
+
+    fn epoll_wait(
+        &self,
+        epfd: c_int,
+        events: &mut [epoll_event],
+        timeout: c_int,
+    ) -> Result<usize, c_int> {
+        let max = cmp::min(events.len(), c_int::MAX as usize) as c_int;
+        let n = unsafe { ocall::native_epoll_wait(epfd, events.as_mut_ptr(), max, timeout) };
+        size(n as ssize_t)
+    }
+}
+
+/// Installs the backend of the routed OCALLs.
+///
+/// The backend can be installed once, before the enclave does any I/O;
+/// returns `false` if one is installed already.
+pub fn set_host_calls(host: &'static dyn HostCalls) -> bool {
+    let host = Box::into_raw(Box::new(host));
+    match HOST.compare_exchange(ptr::null_mut(), host, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => true,
+        Err(_) => {
+            drop(unsafe { Box::from_raw(host) });
+            false
+        }
+    }
+}
+
+/// Returns the installed backend, if any.
+pub fn host_calls() -> Option<&'static dyn HostCalls> {
+    let host = HOST.load(Ordering::Acquire);
+    if host.is_null() {
+        None
+    } else {
+        Some(unsafe { *host })
+    }
+}
+
+fn size(ret: ssize_t) -> Result<usize, c_int> {
+    if ret < 0 {
+        Err(errno())
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+// Converts the answer of a backend back to the C convention, never claiming
+// more bytes than the buffer holds.
+fn ret(result: Result<usize, c_int>, len: usize) -> ssize_t {
+    match result {
+        Ok(n) => cmp::min(n, len) as ssize_t,
+        Err(e) => {
+            set_errno(e);
+            -1
+        }
+    }
+}
+
+fn valid(buf: *const c_void, len: size_t) -> bool {
+    if buf.is_null() || unsafe { sgx_is_within_enclave(buf, len) } == 0 {
+        set_errno(EINVAL);
+        false
+    } else {
+        true
+    }
+}
+
+pub(crate) unsafe fn read(
+    host: &dyn HostCalls,
+    fd: c_int,
+    buf: *mut c_void,
+    count: size_t,
+) -> ssize_t {
+    if !valid(buf, count) {
+        return -1;
+    }
+    ret(
+        host.read(fd, slice::from_raw_parts_mut(buf as *mut u8, count)),
+        count,
+    )
+}
+
+pub(crate) unsafe fn write(
+    host: &dyn HostCalls,
+    fd: c_int,
+    buf: *const c_void,
+    count: size_t,
+) -> ssize_t {
+    if !valid(buf, count) {
+        return -1;
+    }
+    ret(
+        host.write(fd, slice::from_raw_parts(buf as *const u8, count)),
+        count,
+    )
+}
+
+pub(crate) unsafe fn recv(
+    host: &dyn HostCalls,
+    sockfd: c_int,
+    buf: *mut c_void,
+    len: size_t,
+    flags: c_int,
+) -> ssize_t {
+    if !valid(buf, len) {
+        return -1;
+    }
+    ret(
+        host.recv(
+            sockfd,
+            slice::from_raw_parts_mut(buf as *mut u8, len),
+            flags,
+        ),
+        len,
+    )
+}
+
+pub(crate) unsafe fn send(
+    host: &dyn HostCalls,
+    sockfd: c_int,
+    buf: *const c_void,
+    len: size_t,
+    flags: c_int,
+) -> ssize_t {
+    if !valid(buf, len) {
+        return -1;
+    }
+    ret(
+        host.send(sockfd, slice::from_raw_parts(buf as *const u8, len), flags),
+        len,
+    )
+}
+
+pub(crate) unsafe fn clock_gettime(
+    host: &dyn HostCalls,
+    clk_id: clockid_t,
+    tp: *mut timespec,
+) -> c_int {
+    if !valid(tp as *const c_void, mem::size_of::<timespec>()) {
+        return -1;
+    }
+    match host.clock_gettime(clk_id) {
+        Ok(time) => {
+            *tp = time;
+            0
+        }
+        Err(e) => {
+            set_errno(e);
+            -1
+        }
+    }
+}
+
+pub(crate) unsafe fn epoll_wait(
+    host: &dyn HostCalls,
+    epfd: c_int,
+    events: *mut epoll_event,
+    maxevents: c_int,
+    timeout: c_int,
+) -> c_int {
+    if maxevents <= 0 {
+        set_errno(EINVAL);
+        return -1;
+    }
+    let len = maxevents as usize;
+    if !valid(events as *const c_void, len * mem::size_of::<epoll_event>()) {
+        return -1;
+    }
+    let events = slice::from_raw_parts_mut(events, len);
+    ret(host.epoll_wait(epfd, events, timeout), len) as c_int
+}
@@ -1684,4 +1684,6 @@ pub unsafe fn makedev(major: c_uint, minor: c_uint) -> dev_t {
     dev
 }
 
+pub mod gate;
+pub mod host;
 pub mod ocall;
@@ -650,6 +650,13 @@ extern "C" {
         cpusetsize: size_t,
         mask: *mut cpu_set_t,
     ) -> sgx_status_t;
+    pub fn u_cpu_topology_ocall(
+        result: *mut c_int,
+        error: *mut c_int,
+        entries: *mut u32,
+        cap: size_t,
+        ncpus: *mut size_t,
+    ) -> sgx_status_t;
     // pipe
     pub fn u_pipe_ocall(result: *mut c_int, error: *mut c_int, fds: *mut c_int) -> sgx_status_t;
     pub fn u_pipe2_ocall(
@@ -685,6 +692,27 @@ extern "C" {
     pub fn u_raise_ocall(result: *mut c_int, signum: c_int) -> sgx_status_t;
     //process
     pub fn u_getpid_ocall(result: *mut pid_t) -> sgx_status_t;
+    //log
+    pub fn u_log_ship_ocall(
+        result: *mut ssize_t,
+        error: *mut c_int,
+        batch: *const u8,
+        len: size_t,
+        count: size_t,
+    ) -> sgx_status_t;
+    //dcap
+    pub fn u_dcap_target_info_ocall(
+        result: *mut uint32_t,
+        target_info: *mut sgx_target_info_t,
+        quote_size: *mut uint32_t,
+    ) -> sgx_status_t;
+    pub fn u_dcap_quote_ocall(
+        result: *mut uint32_t,
+        report: *const sgx_report_t,
+        quote: *mut uint8_t,
+        cap: uint32_t,
+        quote_size: *mut uint32_t,
+    ) -> sgx_status_t;
 }
 
 pub unsafe fn malloc(size: size_t) -> *mut c_void {
@@ -1023,6 +1051,10 @@ pub unsafe fn getpwuid_r(
 }
 
 pub unsafe fn open(path: *const c_char, flags: c_int) -> c_int {
+    if gate::opens_for_writing(flags) && !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_open_ocall(
@@ -1044,6 +1076,10 @@ pub unsafe fn open(path: *const c_char, flags: c_int) -> c_int {
 }
 
 pub unsafe fn open64(path: *const c_char, oflag: c_int, mode: c_int) -> c_int {
+    if gate::opens_for_writing(oflag) && !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_open64_ocall(
@@ -1066,6 +1102,10 @@ pub unsafe fn open64(path: *const c_char, oflag: c_int, mode: c_int) -> c_int {
 }
 
 pub unsafe fn openat(dirfd: c_int, pathname: *const c_char, flags: c_int) -> c_int {
+    if gate::opens_for_writing(flags) && !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
 
@@ -1249,6 +1289,10 @@ pub unsafe fn lseek64(fd: c_int, offset: off64_t, whence: c_int) -> off64_t {
 }
 
 pub unsafe fn ftruncate(fd: c_int, length: off_t) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_ftruncate_ocall(
@@ -1270,6 +1314,10 @@ pub unsafe fn ftruncate(fd: c_int, length: off_t) -> c_int {
 }
 
 pub unsafe fn ftruncate64(fd: c_int, length: off64_t) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_ftruncate64_ocall(
@@ -1291,6 +1339,10 @@ pub unsafe fn ftruncate64(fd: c_int, length: off64_t) -> c_int {
 }
 
 pub unsafe fn truncate(path: *const c_char, length: off_t) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_truncate_ocall(
@@ -1312,6 +1364,10 @@ pub unsafe fn truncate(path: *const c_char, length: off_t) -> c_int {
 }
 
 pub unsafe fn truncate64(path: *const c_char, length: off64_t) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_truncate64_ocall(
@@ -1365,6 +1421,10 @@ pub unsafe fn fdatasync(fd: c_int) -> c_int {
 }
 
 pub unsafe fn fchmod(fd: c_int, mode: mode_t) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_fchmod_ocall(
@@ -1386,6 +1446,10 @@ pub unsafe fn fchmod(fd: c_int, mode: mode_t) -> c_int {
 }
 
 pub unsafe fn unlink(pathname: *const c_char) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_unlink_ocall(
@@ -1406,6 +1470,10 @@ pub unsafe fn unlink(pathname: *const c_char) -> c_int {
 }
 
 pub unsafe fn link(oldpath: *const c_char, newpath: *const c_char) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_link_ocall(
@@ -1427,6 +1495,10 @@ pub unsafe fn link(oldpath: *const c_char, newpath: *const c_char) -> c_int {
 }
 
 pub unsafe fn unlinkat(dirfd: c_int, pathname: *const c_char, flags: c_int) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
 
@@ -1456,6 +1528,10 @@ pub unsafe fn linkat(
     newpath: *const c_char,
     flags: c_int,
 ) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_linkat_ocall(
@@ -1480,6 +1556,10 @@ pub unsafe fn linkat(
 }
 
 pub unsafe fn rename(oldpath: *const c_char, newpath: *const c_char) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_rename_ocall(
@@ -1501,6 +1581,10 @@ pub unsafe fn rename(oldpath: *const c_char, newpath: *const c_char) -> c_int {
 }
 
 pub unsafe fn chmod(path: *const c_char, mode: mode_t) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_chmod_ocall(
@@ -1544,6 +1628,10 @@ pub unsafe fn readlink(path: *const c_char, buf: *mut c_char, bufsz: size_t) ->
 }
 
 pub unsafe fn symlink(path1: *const c_char, path2: *const c_char) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_symlink_ocall(
@@ -1591,6 +1679,10 @@ pub unsafe fn realpath(pathname: *const c_char) -> *mut c_char {
 }
 
 pub unsafe fn mkdir(pathname: *const c_char, mode: mode_t) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut error: c_int = 0;
     let mut result: c_int = 0;
     let status = u_mkdir_ocall(
@@ -1612,6 +1704,10 @@ pub unsafe fn mkdir(pathname: *const c_char, mode: mode_t) -> c_int {
 }
 
 pub unsafe fn rmdir(pathname: *const c_char) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut error: c_int = 0;
     let mut result: c_int = 0;
     let status = u_rmdir_ocall(
@@ -1751,6 +1847,13 @@ pub unsafe fn fstatat64(
 }
 
 pub unsafe fn read(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t {
+    match host::host_calls() {
+        Some(host) => host::read(host, fd, buf, count),
+        None => native_read(fd, buf, count),
+    }
+}
+
+pub(crate) unsafe fn native_read(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t {
     let mut result: ssize_t = 0;
     let mut error: c_int = 0;
 
@@ -2061,6 +2164,13 @@ pub unsafe fn preadv64(fd: c_int, iov: *const iovec, iovcnt: c_int, offset: off6
 }
 
 pub unsafe fn write(fd: c_int, buf: *const c_void, count: size_t) -> ssize_t {
+    match host::host_calls() {
+        Some(host) => host::write(host, fd, buf, count),
+        None => native_write(fd, buf, count),
+    }
+}
+
+pub(crate) unsafe fn native_write(fd: c_int, buf: *const c_void, count: size_t) -> ssize_t {
     let mut result: ssize_t = 0;
     let mut error: c_int = 0;
 
@@ -2571,6 +2681,10 @@ pub unsafe fn eventfd(initval: c_uint, flags: c_int) -> c_int {
 }
 
 pub unsafe fn futimens(fd: c_int, times: *const timespec) -> c_int {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_FS_WRITE) {
+        return -1;
+    }
+
     let mut result: c_int = 0;
     let mut error: c_int = 0;
 
@@ -2594,6 +2708,13 @@ pub unsafe fn futimens(fd: c_int, times: *const timespec) -> c_int {
 
 // time
 pub unsafe fn clock_gettime(clk_id: clockid_t, tp: *mut timespec) -> c_int {
+    match host::host_calls() {
+        Some(host) => host::clock_gettime(host, clk_id, tp),
+        None => native_clock_gettime(clk_id, tp),
+    }
+}
+
+pub(crate) unsafe fn native_clock_gettime(clk_id: clockid_t, tp: *mut timespec) -> c_int {
     let mut result: c_int = 0;
     let mut error: c_int = 0;
     let status = u_clock_gettime_ocall(
@@ -2789,6 +2910,13 @@ pub unsafe fn connect(sockfd: c_int, address: *const sockaddr, addrlen: socklen_
 }
 
 pub unsafe fn send(sockfd: c_int, buf: *const c_void, len: size_t, flags: c_int) -> ssize_t {
+    match host::host_calls() {
+        Some(host) => host::send(host, sockfd, buf, len, flags),
+        None => native_send(sockfd, buf, len, flags),
+    }
+}
+
+pub(crate) unsafe fn native_send(sockfd: c_int, buf: *const c_void, len: size_t, flags: c_int) -> ssize_t {
     let mut result: ssize_t = 0;
     let mut error: c_int = 0;
 
@@ -2999,6 +3127,52 @@ pub unsafe fn sendmsg(sockfd: c_int, msg: *const msghdr, flags: c_int) -> ssize_
 }
 
 pub unsafe fn recv(sockfd: c_int, buf: *mut c_void, len: size_t, flags: c_int) -> ssize_t {
+    match host::host_calls() {
+        Some(host) => host::recv(host, sockfd, buf, len, flags),
+        None => native_recv(sockfd, buf, len, flags),
+    }
+}
+
+/// Receives into a buffer outside the enclave, which the host writes
+/// directly, without the bounce buffer of `recv`. The bytes are as
+/// untrusted as the buffer, and must be copied in before they are checked.
+pub unsafe fn recv_untrusted(sockfd: c_int, buf: *mut c_void, len: size_t, flags: c_int) -> ssize_t {
+    let mut result: ssize_t = 0;
+    let mut error: c_int = 0;
+
+    if buf.is_null() || sgx_is_outside_enclave(buf, len) == 0 {
+        set_errno(EINVAL);
+        return -1;
+    }
+    if len >= usize::MAX {
+        set_errno(EINVAL);
+        return -1;
+    }
+
+    let status = u_recv_ocall(
+        &mut result as *mut ssize_t,
+        &mut error as *mut c_int,
+        sockfd,
+        buf,
+        len,
+        flags,
+    );
+
+    if status == sgx_status_t::SGX_SUCCESS {
+        if result == -1 {
+            set_errno(error);
+        } else if result < -1 || result as size_t > len {
+            set_errno(ESGX);
+            result = -1;
+        }
+    } else {
+        set_errno(ESGX);
+        result = -1;
+    }
+    result
+}
+
+pub(crate) unsafe fn native_recv(sockfd: c_int, buf: *mut c_void, len: size_t, flags: c_int) -> ssize_t {
     let mut result: ssize_t = 0;
     let mut error: c_int = 0;
 
@@ -3649,6 +3823,18 @@ pub unsafe fn epoll_wait(
     events: *mut epoll_event,
     maxevents: c_int,
     timeout: c_int,
+) -> c_int {
+    match host::host_calls() {
+        Some(host) => host::epoll_wait(host, epfd, events, maxevents, timeout),
+        None => native_epoll_wait(epfd, events, maxevents, timeout),
+    }
+}
+
+pub(crate) unsafe fn native_epoll_wait(
+    epfd: c_int,
+    events: *mut epoll_event,
+    maxevents: c_int,
+    timeout: c_int,
 ) -> c_int {
     let mut result: c_int = 0;
     let mut error: c_int = 0;
@@ -3757,6 +3943,40 @@ pub unsafe fn sched_getaffinity(pid: pid_t, cpusetsize: size_t, mask: *mut cpu_s
     result
 }
 
+/// Fills `entries` with one `(cpu, core, package, node)` quadruple per online
+/// host CPU and stores the number of CPUs in `ncpus`. Fails with `ERANGE` if
+/// `cap` cannot hold them all; `ncpus` is still set then.
+///
+/// Only the shape of the answer is checked here; the values come from the
+/// host and must be validated by the caller.
+pub unsafe fn cpu_topology(entries: *mut u32, cap: size_t, ncpus: *mut size_t) -> c_int {
+    let mut result: c_int = 0;
+    let mut error: c_int = 0;
+    let mut count: size_t = 0;
+    let status = u_cpu_topology_ocall(
+        &mut result as *mut c_int,
+        &mut error as *mut c_int,
+        entries,
+        cap,
+        &mut count as *mut size_t,
+    );
+    if status == sgx_status_t::SGX_SUCCESS {
+        if result == -1 {
+            set_errno(error);
+        } else if result != 0 || count.checked_mul(4).map_or(true, |n| n > cap) {
+            set_errno(ESGX);
+            result = -1;
+        }
+    } else {
+        set_errno(ESGX);
+        result = -1;
+    }
+    if !ncpus.is_null() {
+        *ncpus = count;
+    }
+    result
+}
+
 pub unsafe fn pipe(fds: *mut c_int) -> c_int {
     let mut result: c_int = 0;
     let mut error: c_int = 0;
@@ -3889,6 +4109,10 @@ pub unsafe fn pthread_sigmask(signum: c_int, set: &sigset_t, oldset: &mut sigset
 }
 
 pub unsafe fn getpid() -> pid_t {
+    if !gate::ocall_allowed(gate::OCALL_CLASS_PROCESS) {
+        return -1;
+    }
+
     let mut result = -1;
     let status = u_getpid_ocall(&mut result as *mut pid_t);
     if status != sgx_status_t::SGX_SUCCESS {
@@ -3896,3 +4120,55 @@ pub unsafe fn getpid() -> pid_t {
     }
     result
 }
+
+pub unsafe fn log_ship(batch: *const u8, len: size_t, count: size_t) -> ssize_t {
+    let mut result: ssize_t = 0;
+    let mut error: c_int = 0;
+
+    if batch.is_null() || sgx_is_within_enclave(batch as *const c_void, len) == 0 {
+        set_errno(EINVAL);
+        return -1;
+    }
+    if len >= usize::MAX {
+        set_errno(EINVAL);
+        return -1;
+    }
+
+    let tmp_buf = if len <= MAX_OCALL_ALLOC_SIZE {
+        sgx_ocalloc(len)
+    } else {
+        malloc(len)
+    };
+    if tmp_buf.is_null() {
+        set_errno(ENOMEM);
+        return -1;
+    }
+    ptr::copy_nonoverlapping(batch, tmp_buf as *mut u8, len);
+
+    let status = u_log_ship_ocall(
+        &mut result as *mut ssize_t,
+        &mut error as *mut c_int,
+        tmp_buf as *const u8,
+        len,
+        count,
+    );
+
+    if status == sgx_status_t::SGX_SUCCESS {
+        if result == -1 {
+            set_errno(error);
+        } else if result < 0 || result as size_t > count {
+            set_errno(ESGX);
+            result = -1;
+        }
+    } else {
+        set_errno(ESGX);
+        result = -1;
+    }
+
+    if len <= MAX_OCALL_ALLOC_SIZE {
+        sgx_ocfree();
+    } else {
+        free(tmp_buf);
+    }
+    result
+}
@@ -0,0 +1,116 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::dispatch::dispatch;
+use crate::tensor::Tensor;
+use sgx_types::*;
+
+///
+/// An elementwise activation, with the semantics of the ONNX operator of the same name.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Activation {
+    Identity,
+    Relu,
+    LeakyRelu(f32),
+    Clip(f32, f32),
+    Sigmoid,
+    Tanh,
+}
+
+#[inline(always)]
+fn relu_portable(values: &mut [f32]) {
+    for v in values {
+        *v = if *v > 0.0 { *v } else { 0.0 };
+    }
+}
+
+#[inline(always)]
+fn leaky_relu_portable(values: &mut [f32], alpha: f32) {
+    for v in values {
+        *v = if *v >= 0.0 { *v } else { *v * alpha };
+    }
+}
+
+#[inline(always)]
+fn clip_portable(values: &mut [f32], min: f32, max: f32) {
+    for v in values {
+        let lower = if *v < min { min } else { *v };
+        *v = if lower > max { max } else { lower };
+    }
+}
+
+dispatch!(fn relu = relu_portable(values: &mut [f32]));
+dispatch!(fn leaky_relu = leaky_relu_portable(values: &mut [f32], alpha: f32));
+dispatch!(fn clip = clip_portable(values: &mut [f32], min: f32, max: f32));
+
+impl Activation {
+    /// Applies the activation to each of `values`.
+    pub fn apply(&self, values: &mut [f32]) {
+        match *self {
+            Activation::Identity => {}
+            Activation::Relu => relu(values),
+            Activation::LeakyRelu(alpha) => leaky_relu(values, alpha),
+            Activation::Clip(min, max) => clip(values, min, max),
+            Activation::Sigmoid => values
+                .iter_mut()
+                .for_each(|v| *v = 1.0 / (1.0 + (-*v).exp())),
+            Activation::Tanh => values.iter_mut().for_each(|v| *v = v.tanh()),
+        }
+    }
+
+    /// Applies the activation to each element of `tensor`.
+    pub fn apply_tensor(&self, tensor: &mut Tensor) {
+        self.apply(tensor.data_mut())
+    }
+}
+
+///
+/// Applies the ONNX `Softmax` operator to `tensor` in place, along `axis`.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// `axis` is not an axis of `tensor`.
+///
+pub fn softmax(tensor: &mut Tensor, axis: usize) -> SgxError {
+    let shape = tensor.shape();
+    if axis >= shape.len() {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    let dim = shape[axis];
+    let inner: usize = shape[axis + 1..].iter().product();
+    let outer: usize = shape[..axis].iter().product();
+    let data = tensor.data_mut();
+    for o in 0..outer {
+        for i in 0..inner {
+            let base = o * dim * inner + i;
+            let max = (0..dim).fold(f32::NEG_INFINITY, |m, d| m.max(data[base + d * inner]));
+            let mut sum = 0.0;
+            for d in 0..dim {
+                let e = (data[base + d * inner] - max).exp();
+                data[base + d * inner] = e;
+                sum += e;
+            }
+            for d in 0..dim {
+                data[base + d * inner] /= sum;
+            }
+        }
+    }
+    Ok(())
+}
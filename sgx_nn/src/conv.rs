@@ -0,0 +1,162 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::gemm::matmul_acc;
+use crate::tensor::{element_count, Tensor};
+use sgx_types::*;
+
+///
+/// The attributes of the ONNX `Conv` operator for 2-D convolutions.
+///
+/// `pads` is `[top, left, bottom, right]`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConvAttrs {
+    pub strides: [usize; 2],
+    pub pads: [usize; 4],
+    pub dilations: [usize; 2],
+    pub group: usize,
+}
+
+impl Default for ConvAttrs {
+    fn default() -> Self {
+        ConvAttrs {
+            strides: [1, 1],
+            pads: [0, 0, 0, 0],
+            dilations: [1, 1],
+            group: 1,
+        }
+    }
+}
+
+fn output_size(
+    input: usize,
+    pad: usize,
+    kernel: usize,
+    stride: usize,
+    dilation: usize,
+) -> SgxResult<usize> {
+    let padded = input
+        .checked_add(pad)
+        .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+    let span = dilation
+        .checked_mul(kernel - 1)
+        .and_then(|s| s.checked_add(1))
+        .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+    if padded < span {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    Ok((padded - span) / stride + 1)
+}
+
+///
+/// Computes the 2-D convolution of `x`, of shape `[N, C, H, W]`, with `w`, of shape
+/// `[M, C / group, kH, kW]`, adding `bias`, of shape `[M]`, if given.
+///
+/// This is the ONNX `Conv` operator. The result has shape `[N, M, oH, oW]`. The input is
+/// unfolded into columns (im2col) so that each group is a single matrix product.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// The shapes do not match, or a stride, dilation, group or kernel size is zero.
+///
+pub fn conv2d(
+    x: &Tensor,
+    w: &Tensor,
+    bias: Option<&Tensor>,
+    attrs: &ConvAttrs,
+) -> SgxResult<Tensor> {
+    let (batch, channels, height, width) = match *x.shape() {
+        [n, c, h, w] => (n, c, h, w),
+        _ => return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
+    };
+    let (maps, group_channels, kh, kw) = match *w.shape() {
+        [m, c, kh, kw] => (m, c, kh, kw),
+        _ => return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
+    };
+    let group = attrs.group;
+    if group == 0
+        || kh == 0
+        || kw == 0
+        || attrs.strides.contains(&0)
+        || attrs.dilations.contains(&0)
+        || channels % group != 0
+        || maps % group != 0
+        || group_channels != channels / group
+    {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    if let Some(bias) = bias {
+        if bias.shape() != [maps] {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+    }
+
+    let [pad_top, pad_left, pad_bottom, pad_right] = attrs.pads;
+    let [stride_h, stride_w] = attrs.strides;
+    let [dil_h, dil_w] = attrs.dilations;
+    let out_h = output_size(height, pad_top + pad_bottom, kh, stride_h, dil_h)?;
+    let out_w = output_size(width, pad_left + pad_right, kw, stride_w, dil_w)?;
+    let out_hw = out_h * out_w;
+    let group_maps = maps / group;
+    let patch = element_count(&[group_channels, kh, kw])?;
+
+    let mut y = Tensor::zeros(&[batch, maps, out_h, out_w])?;
+    let mut columns = vec![0.0_f32; element_count(&[patch, out_hw])?];
+    let xd = x.data();
+    let wd = w.data();
+    for n in 0..batch {
+        for g in 0..group {
+            // Unfold the input channels of the group: row (c, i, j) of `columns` holds
+            // the input under kernel position (i, j) of channel c for every output pixel.
+            for c in 0..group_channels {
+                let plane = &xd[((n * channels) + g * group_channels + c) * height * width..]
+                    [..height * width];
+                for i in 0..kh {
+                    for j in 0..kw {
+                        let row = &mut columns[((c * kh + i) * kw + j) * out_hw..][..out_hw];
+                        for oh in 0..out_h {
+                            let ih = (oh * stride_h + i * dil_h).wrapping_sub(pad_top);
+                            for ow in 0..out_w {
+                                let iw = (ow * stride_w + j * dil_w).wrapping_sub(pad_left);
+                                row[oh * out_w + ow] = if ih < height && iw < width {
+                                    plane[ih * width + iw]
+                                } else {
+                                    0.0
+                                };
+                            }
+                        }
+                    }
+                }
+            }
+
+            let out =
+                &mut y.data_mut()[(n * maps + g * group_maps) * out_hw..][..group_maps * out_hw];
+            if let Some(bias) = bias {
+                let bias = &bias.data()[g * group_maps..][..group_maps];
+                for (map, &b) in out.chunks_exact_mut(out_hw).zip(bias) {
+                    map.iter_mut().for_each(|v| *v = b);
+                }
+            }
+            let weights = &wd[g * group_maps * patch..][..group_maps * patch];
+            matmul_acc(group_maps, out_hw, patch, weights, &columns, out);
+        }
+    }
+    Ok(y)
+}
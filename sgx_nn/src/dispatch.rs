@@ -0,0 +1,65 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+// Each kernel is written once, as an #[inline(always)] function over slices
+// with no data-dependent branches, and compiled three times: for the baseline
+// target, for AVX2 and for AVX-512. The compiler vectorizes the inner loops to
+// the widest registers each build allows. The build is chosen once per call
+// from the features the CPU reports.
+
+use sgx_trts::is_x86_feature_detected;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Level {
+    Baseline,
+    Avx2,
+    Avx512,
+}
+
+pub(crate) fn level() -> Level {
+    if is_x86_feature_detected!("avx512f") {
+        Level::Avx512
+    } else if is_x86_feature_detected!("avx2") {
+        Level::Avx2
+    } else {
+        Level::Baseline
+    }
+}
+
+macro_rules! dispatch {
+    ($vis:vis fn $name:ident = $portable:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)?) => {
+        $vis fn $name($($arg: $ty),*) $(-> $ret)? {
+            #[target_feature(enable = "avx512f")]
+            unsafe fn avx512($($arg: $ty),*) $(-> $ret)? {
+                $portable($($arg),*)
+            }
+
+            #[target_feature(enable = "avx2")]
+            unsafe fn avx2($($arg: $ty),*) $(-> $ret)? {
+                $portable($($arg),*)
+            }
+
+            match $crate::dispatch::level() {
+                // SAFETY: the CPU reports the features the functions are compiled for.
+                $crate::dispatch::Level::Avx512 => unsafe { avx512($($arg),*) },
+                $crate::dispatch::Level::Avx2 => unsafe { avx2($($arg),*) },
+                $crate::dispatch::Level::Baseline => $portable($($arg),*),
+            }
+        }
+    };
+}
+pub(crate) use dispatch;
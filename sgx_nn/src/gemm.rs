@@ -0,0 +1,176 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::dispatch::dispatch;
+use crate::tensor::Tensor;
+use sgx_types::*;
+use std::vec::Vec;
+
+// Block sizes of the matrix product: a KC x NC block of B, 128 KiB, stays in
+// the L2 cache while the rows of A stream past it.
+const KC: usize = 128;
+const NC: usize = 256;
+
+#[inline(always)]
+fn matmul_acc_portable(m: usize, n: usize, k: usize, a: &[f32], b: &[f32], c: &mut [f32]) {
+    for jb in (0..n).step_by(NC) {
+        let je = (jb + NC).min(n);
+        for pb in (0..k).step_by(KC) {
+            let pe = (pb + KC).min(k);
+            for i in 0..m {
+                let c_row = &mut c[i * n + jb..i * n + je];
+                for p in pb..pe {
+                    let a_ip = a[i * k + p];
+                    let b_row = &b[p * n + jb..p * n + je];
+                    for (c, &b) in c_row.iter_mut().zip(b_row) {
+                        *c += a_ip * b;
+                    }
+                }
+            }
+        }
+    }
+}
+
+dispatch!(pub(crate) fn matmul_acc = matmul_acc_portable(m: usize, n: usize, k: usize, a: &[f32], b: &[f32], c: &mut [f32]));
+
+fn transpose(rows: usize, cols: usize, data: &[f32]) -> Vec<f32> {
+    let mut out = vec![0.0; data.len()];
+    for r in 0..rows {
+        for c in 0..cols {
+            out[c * rows + r] = data[r * cols + c];
+        }
+    }
+    out
+}
+
+fn matrix_dims(t: &Tensor) -> SgxResult<(usize, usize)> {
+    match *t.shape() {
+        [rows, cols] => Ok((rows, cols)),
+        _ => Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
+    }
+}
+
+///
+/// The attributes of the ONNX `Gemm` operator.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GemmAttrs {
+    pub alpha: f32,
+    pub beta: f32,
+    pub trans_a: bool,
+    pub trans_b: bool,
+}
+
+impl Default for GemmAttrs {
+    fn default() -> Self {
+        GemmAttrs {
+            alpha: 1.0,
+            beta: 1.0,
+            trans_a: false,
+            trans_b: false,
+        }
+    }
+}
+
+///
+/// Computes `alpha * A' * B' + beta * C`, the ONNX `Gemm` operator.
+///
+/// `A'` is `a` or its transpose, of shape `[M, K]`, and `B'` is `b` or its transpose, of
+/// shape `[K, N]`. `c` is broadcast to `[M, N]` from any of the shapes `[]`, `[1]`, `[N]`,
+/// `[1, N]`, `[M, 1]` or `[M, N]`.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// The shapes do not match.
+///
+pub fn gemm(a: &Tensor, b: &Tensor, c: Option<&Tensor>, attrs: &GemmAttrs) -> SgxResult<Tensor> {
+    let (ar, ac) = matrix_dims(a)?;
+    let (br, bc) = matrix_dims(b)?;
+    let (m, k) = if attrs.trans_a { (ac, ar) } else { (ar, ac) };
+    let (kb, n) = if attrs.trans_b { (bc, br) } else { (br, bc) };
+    if k != kb {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+
+    let mut a_data = if attrs.trans_a {
+        transpose(ar, ac, a.data())
+    } else {
+        a.data().to_vec()
+    };
+    if attrs.alpha != 1.0 {
+        a_data.iter_mut().for_each(|v| *v *= attrs.alpha);
+    }
+    let b_transposed;
+    let b_data = if attrs.trans_b {
+        b_transposed = transpose(br, bc, b.data());
+        &b_transposed[..]
+    } else {
+        b.data()
+    };
+
+    let mut y = Tensor::zeros(&[m, n])?;
+    if let Some(c) = c {
+        broadcast_bias(c, m, n, attrs.beta, y.data_mut())?;
+    }
+    matmul_acc(m, n, k, &a_data, b_data, y.data_mut());
+    Ok(y)
+}
+
+fn broadcast_bias(c: &Tensor, m: usize, n: usize, beta: f32, y: &mut [f32]) -> SgxError {
+    let (cm, cn) = match *c.shape() {
+        [] => (1, 1),
+        [cn] => (1, cn),
+        [cm, cn] => (cm, cn),
+        _ => return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
+    };
+    if (cm != 1 && cm != m) || (cn != 1 && cn != n) {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    let c = c.data();
+    for i in 0..m {
+        for j in 0..n {
+            let ci = if cm == 1 { 0 } else { i };
+            let cj = if cn == 1 { 0 } else { j };
+            y[i * n + j] = beta * c[ci * cn + cj];
+        }
+    }
+    Ok(())
+}
+
+///
+/// Computes the matrix product of `a`, of shape `[M, K]`, and `b`, of shape `[K, N]`.
+///
+/// This is the ONNX `MatMul` operator for 2-D inputs.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// The shapes do not match.
+///
+pub fn matmul(a: &Tensor, b: &Tensor) -> SgxResult<Tensor> {
+    let (m, k) = matrix_dims(a)?;
+    let (kb, n) = matrix_dims(b)?;
+    if k != kb {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    let mut y = Tensor::zeros(&[m, n])?;
+    matmul_acc(m, n, k, a.data(), b.data(), y.data_mut());
+    Ok(y)
+}
@@ -0,0 +1,68 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Neural Network Inference Kernels
+//!
+//! The library provides the kernels needed to run small ONNX models inside the
+//! enclave, with the semantics of the ONNX operators they are named after.
+//!
+//! * `Tensor`, a dense row-major `f32` tensor.
+//! * `gemm` and `matmul`, for the `Gemm` and `MatMul` operators.
+//! * `conv2d`, for the `Conv` operator on NCHW inputs, including grouped and
+//!   dilated convolutions.
+//! * `Activation` and `softmax`, for the elementwise activations and `Softmax`.
+//! * `WeightStore`, which loads named weight tensors on demand from a sealed
+//!   dataset opened with `SealedMmap`, so that the weights of a model never
+//!   have to be resident in the enclave all at once.
+//!
+//! The kernels use AVX-512 or AVX2 when the CPU reports them. All code paths
+//! perform the same floating-point operations in the same order, so a model
+//! gives bit-identical results whichever path is taken. None of the kernels
+//! branch on the values of inputs or weights.
+//!
+
+#![cfg_attr(not(target_env = "sgx"), no_std)]
+#![cfg_attr(
+    all(target_env = "sgx", target_vendor = "mesalock"),
+    feature(rustc_private)
+)]
+#![feature(avx512_target_feature)]
+
+#[cfg(not(target_env = "sgx"))]
+#[macro_use]
+extern crate sgx_tstd as std;
+
+extern crate sgx_trts;
+extern crate sgx_tseal;
+extern crate sgx_types;
+
+mod dispatch;
+
+mod tensor;
+pub use self::tensor::Tensor;
+
+mod gemm;
+pub use self::gemm::{gemm, matmul, GemmAttrs};
+
+mod conv;
+pub use self::conv::{conv2d, ConvAttrs};
+
+mod activation;
+pub use self::activation::{softmax, Activation};
+
+mod weights;
+pub use self::weights::{write_weights, WeightStore};
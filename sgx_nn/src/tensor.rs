@@ -0,0 +1,111 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use sgx_types::*;
+use std::vec::Vec;
+
+///
+/// A dense tensor of `f32` in row-major order.
+///
+/// A tensor of rank 0 is a scalar and holds one value.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tensor {
+    shape: Vec<usize>,
+    data: Vec<f32>,
+}
+
+pub(crate) fn element_count(shape: &[usize]) -> SgxResult<usize> {
+    shape.iter().try_fold(1_usize, |n, &d| {
+        n.checked_mul(d)
+            .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+    })
+}
+
+impl Tensor {
+    ///
+    /// Creates a tensor of shape `shape` over `data`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The length of `data` is not the number of elements of `shape`.
+    ///
+    pub fn new(shape: &[usize], data: Vec<f32>) -> SgxResult<Tensor> {
+        if element_count(shape)? != data.len() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        Ok(Tensor {
+            shape: shape.to_vec(),
+            data,
+        })
+    }
+
+    pub fn zeros(shape: &[usize]) -> SgxResult<Tensor> {
+        let len = element_count(shape)?;
+        Ok(Tensor {
+            shape: shape.to_vec(),
+            data: vec![0.0; len],
+        })
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn rank(&self) -> usize {
+        self.shape.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [f32] {
+        &mut self.data
+    }
+
+    ///
+    /// Changes the shape of the tensor, keeping its data.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `shape` does not have the same number of elements.
+    ///
+    pub fn reshape(&mut self, shape: &[usize]) -> SgxError {
+        if element_count(shape)? != self.data.len() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        self.shape = shape.to_vec();
+        Ok(())
+    }
+
+    pub fn into_data(self) -> Vec<f32> {
+        self.data
+    }
+}
@@ -0,0 +1,221 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+// A weight file is one sealed dataset: a header listing the tensors, followed
+// by their data. Integers are little-endian.
+//
+//   magic "SGXW" | version u8 | count u32
+//   count x { name length u16 | name | rank u8 | rank x dim u64 | offset u64 }
+//   data: the elements of each tensor as f32, at its offset from the start
+//
+// The header is read when the store is opened; the data of a tensor is read
+// from the sealed pages when the tensor is requested.
+
+use crate::tensor::{element_count, Tensor};
+use core::convert::TryInto;
+use sgx_tseal::{PageStore, SealedMmap, SealedMmapWriter};
+use sgx_types::*;
+use std::collections::BTreeMap;
+use std::string::String;
+use std::vec::Vec;
+
+const WEIGHTS_MAGIC: [u8; 4] = *b"SGXW";
+const WEIGHTS_VERSION: u8 = 1;
+
+struct Entry {
+    shape: Vec<usize>,
+    offset: u64,
+    len: usize,
+}
+
+///
+/// Writes `tensors` as a weight file to `writer`.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// Two tensors have the same name, or a name is longer than 65535 bytes, or a tensor has
+/// more than 255 dimensions.
+///
+/// The errors of `SealedMmapWriter::write`.
+///
+pub fn write_weights<S: PageStore>(
+    writer: &mut SealedMmapWriter<S>,
+    tensors: &[(&str, &Tensor)],
+) -> SgxError {
+    let mut names = BTreeMap::new();
+    let mut header_len = WEIGHTS_MAGIC.len() + 1 + 4;
+    for (name, tensor) in tensors {
+        if name.len() > u16::MAX as usize
+            || tensor.rank() > u8::MAX as usize
+            || names.insert(*name, ()).is_some()
+        {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        header_len += 2 + name.len() + 1 + 8 * tensor.rank() + 8;
+    }
+
+    let mut header = Vec::with_capacity(header_len);
+    header.extend_from_slice(&WEIGHTS_MAGIC);
+    header.push(WEIGHTS_VERSION);
+    header.extend_from_slice(&(tensors.len() as u32).to_le_bytes());
+    let mut offset = header_len as u64;
+    for (name, tensor) in tensors {
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        header.push(tensor.rank() as u8);
+        for &dim in tensor.shape() {
+            header.extend_from_slice(&(dim as u64).to_le_bytes());
+        }
+        header.extend_from_slice(&offset.to_le_bytes());
+        offset += 4 * tensor.len() as u64;
+    }
+    writer.write(&header)?;
+
+    let mut buf = Vec::with_capacity(4096);
+    for (_, tensor) in tensors {
+        for chunk in tensor.data().chunks(1024) {
+            buf.clear();
+            chunk
+                .iter()
+                .for_each(|v| buf.extend_from_slice(&v.to_le_bytes()));
+            writer.write(&buf)?;
+        }
+    }
+    Ok(())
+}
+
+///
+/// Named weight tensors loaded on demand from a sealed weight file.
+///
+/// Only the pages of the tensors requested are unsealed, and the `SealedMmap` keeps at
+/// most its resident limit of them in memory.
+///
+pub struct WeightStore<S: PageStore> {
+    mmap: SealedMmap<S>,
+    entries: BTreeMap<String, Entry>,
+}
+
+impl<S: PageStore> WeightStore<S> {
+    ///
+    /// Reads the header of the weight file in `mmap`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The data is not a weight file, or a tensor lies outside the data.
+    ///
+    /// **SGX_ERROR_INVALID_VERSION**
+    ///
+    /// The file was written by a newer version of the format.
+    ///
+    /// The errors of `SealedMmap::get`.
+    ///
+    pub fn open(mut mmap: SealedMmap<S>) -> SgxResult<WeightStore<S>> {
+        let total = mmap.len();
+        let mut pos = 0_u64;
+        let mut read = |n: u64| -> SgxResult<Vec<u8>> {
+            let end = pos
+                .checked_add(n)
+                .filter(|&end| end <= total)
+                .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+            let bytes = mmap.get(pos..end)?.into_owned();
+            pos = end;
+            Ok(bytes)
+        };
+        fn le<const N: usize>(bytes: Vec<u8>) -> [u8; N] {
+            bytes.try_into().unwrap_or([0; N])
+        }
+
+        if read(4)? != WEIGHTS_MAGIC {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        if read(1)?[0] != WEIGHTS_VERSION {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_VERSION);
+        }
+        let count = u32::from_le_bytes(le(read(4)?));
+        let mut entries = BTreeMap::new();
+        for _ in 0..count {
+            let name_len = u16::from_le_bytes(le(read(2)?));
+            let name = String::from_utf8(read(name_len as u64)?)
+                .map_err(|_| sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+            let rank = read(1)?[0];
+            let mut shape = Vec::with_capacity(rank as usize);
+            for _ in 0..rank {
+                let dim = u64::from_le_bytes(le(read(8)?));
+                shape.push(
+                    usize::try_from(dim).map_err(|_| sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?,
+                );
+            }
+            let offset = u64::from_le_bytes(le(read(8)?));
+            let len = element_count(&shape)?;
+            let in_bounds = (len as u64)
+                .checked_mul(4)
+                .and_then(|size| size.checked_add(offset))
+                .map_or(false, |end| end <= total);
+            if !in_bounds || entries.insert(name, Entry { shape, offset, len }).is_some() {
+                return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+            }
+        }
+        Ok(WeightStore { mmap, entries })
+    }
+
+    /// The names of the tensors, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|name| name.as_str())
+    }
+
+    pub fn shape(&self, name: &str) -> Option<&[usize]> {
+        self.entries.get(name).map(|entry| &entry.shape[..])
+    }
+
+    ///
+    /// Reads the tensor named `name`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// There is no tensor named `name`.
+    ///
+    /// The errors of `SealedMmap::get`.
+    ///
+    pub fn tensor(&mut self, name: &str) -> SgxResult<Tensor> {
+        let entry = self
+            .entries
+            .get(name)
+            .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+        let bytes = self
+            .mmap
+            .get(entry.offset..entry.offset + 4 * entry.len as u64)?;
+        let data = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        Tensor::new(&entry.shape, data)
+    }
+
+    pub fn mmap(&mut self) -> &mut SealedMmap<S> {
+        &mut self.mmap
+    }
+
+    pub fn into_inner(self) -> SealedMmap<S> {
+        self.mmap
+    }
+}
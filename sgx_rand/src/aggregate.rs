@@ -0,0 +1,350 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Streaming aggregation with differentially private release.
+//!
+//! An `Aggregator` accumulates a statistic over a stream of records inside the enclave.
+//! Its exact value cannot be read out; the only way out is `release`, which charges a
+//! `PrivacyBudget` for the release and adds noise from a `Mechanism` to every released
+//! value. Results meant to leave the enclave, for example through an OCALL, should be
+//! taken from a release.
+//!
+//! The privacy guarantee holds for neighbouring streams that differ by one record.
+//! Services in which one user may submit several records in an epoch have to bound the
+//! contribution of each user before updating the aggregator.
+//!
+//! # Example
+//!
+//! ```rust
+//! use sgx_rand::aggregate::{Mechanism, Sum, StreamingAggregation};
+//! use sgx_rand::distributions::{DiscreteLaplace, PrivacyAccountant};
+//!
+//! let sum = Sum::new(0, 100);
+//! let mechanism = Mechanism::Laplace(DiscreteLaplace::calibrated(100, 0.1));
+//! let mut stream = StreamingAggregation::new(sum, mechanism, PrivacyAccountant::new(1.0, 0.0));
+//! for latency in [12, 40, 250, 7] {
+//!     stream.push(latency);
+//! }
+//! let released = stream.flush(&mut sgx_rand::tcs_rng()).unwrap();
+//! println!("total latency ~ {}", released);
+//! ```
+
+use std::vec::Vec;
+use crate::Rng;
+use crate::distributions::{DiscreteLaplace, DiscreteGaussian, PrivacyAccountant, BudgetExceeded, IndependentSample};
+
+/// A statistic over a stream of `i64` records that can only leave the enclave with noise.
+pub trait Aggregator {
+    /// The noised result of a release.
+    type Output;
+
+    /// Add one record.
+    fn update(&mut self, value: i64);
+
+    /// Fold in an aggregator with the same parameters, for example one filled by another
+    /// thread. Panics if the parameters differ.
+    fn merge(&mut self, other: &Self);
+
+    /// The largest change, in both the L1 and the L2 norm, that adding or removing one
+    /// record makes to the values the aggregator releases.
+    fn sensitivity(&self) -> u64;
+
+    /// Produce the output, adding a sample of `noise` to every released value.
+    fn noised<F: FnMut() -> i64>(&self, noise: F) -> Self::Output;
+}
+
+/// A hook that decides whether a release may go ahead, charging the privacy budget if it
+/// does.
+///
+/// `PrivacyAccountant` implements it; services with a budget shared between enclaves or
+/// persisted across restarts can provide their own.
+pub trait PrivacyBudget {
+    /// Charge the release of a statistic of sensitivity `sensitivity` with noise from
+    /// `laplace`.
+    fn charge_laplace(&mut self, laplace: &DiscreteLaplace, sensitivity: u64) -> Result<(), BudgetExceeded>;
+
+    /// Charge the release of a statistic of sensitivity `sensitivity` with noise from
+    /// `gaussian`.
+    fn charge_gaussian(&mut self, gaussian: &DiscreteGaussian, sensitivity: u64) -> Result<(), BudgetExceeded>;
+}
+
+impl PrivacyBudget for PrivacyAccountant {
+    fn charge_laplace(&mut self, laplace: &DiscreteLaplace, sensitivity: u64) -> Result<(), BudgetExceeded> {
+        PrivacyAccountant::charge_laplace(self, laplace, sensitivity)
+    }
+
+    fn charge_gaussian(&mut self, gaussian: &DiscreteGaussian, sensitivity: u64) -> Result<(), BudgetExceeded> {
+        PrivacyAccountant::charge_gaussian(self, gaussian, sensitivity)
+    }
+}
+
+impl<'a, B: PrivacyBudget + ?Sized> PrivacyBudget for &'a mut B {
+    fn charge_laplace(&mut self, laplace: &DiscreteLaplace, sensitivity: u64) -> Result<(), BudgetExceeded> {
+        (**self).charge_laplace(laplace, sensitivity)
+    }
+
+    fn charge_gaussian(&mut self, gaussian: &DiscreteGaussian, sensitivity: u64) -> Result<(), BudgetExceeded> {
+        (**self).charge_gaussian(gaussian, sensitivity)
+    }
+}
+
+/// The noise added to a release.
+///
+/// Laplace noise gives pure DP for the L1 sensitivity of the released values, Gaussian
+/// noise gives zCDP for their L2 sensitivity. The same sample scale is used for every
+/// value of a release.
+#[derive(Clone, Debug)]
+pub enum Mechanism {
+    Laplace(DiscreteLaplace),
+    Gaussian(DiscreteGaussian),
+}
+
+/// Charge `budget` for releasing `aggregator` with `mechanism`, then release it.
+///
+/// A refused charge releases nothing.
+pub fn release<A, B, R>(aggregator: &A, mechanism: &Mechanism, budget: &mut B, rng: &mut R) -> Result<A::Output, BudgetExceeded>
+    where A: Aggregator, B: PrivacyBudget + ?Sized, R: Rng
+{
+    let sensitivity = aggregator.sensitivity();
+    match *mechanism {
+        Mechanism::Laplace(ref laplace) => {
+            budget.charge_laplace(laplace, sensitivity)?;
+            Ok(aggregator.noised(|| laplace.ind_sample(rng)))
+        }
+        Mechanism::Gaussian(ref gaussian) => {
+            budget.charge_gaussian(gaussian, sensitivity)?;
+            Ok(aggregator.noised(|| gaussian.ind_sample(rng)))
+        }
+    }
+}
+
+/// The number of records.
+#[derive(Clone, Debug, Default)]
+pub struct Count {
+    count: u64,
+}
+
+impl Count {
+    pub fn new() -> Count {
+        Count::default()
+    }
+}
+
+impl Aggregator for Count {
+    type Output = i64;
+
+    fn update(&mut self, _value: i64) {
+        self.count = self.count.saturating_add(1);
+    }
+
+    fn merge(&mut self, other: &Count) {
+        self.count = self.count.saturating_add(other.count);
+    }
+
+    fn sensitivity(&self) -> u64 {
+        1
+    }
+
+    fn noised<F: FnMut() -> i64>(&self, mut noise: F) -> i64 {
+        saturate(self.count as i128 + noise() as i128)
+    }
+}
+
+/// The sum of the records, each clamped to `[lower, upper]`.
+#[derive(Clone, Debug)]
+pub struct Sum {
+    lower: i64,
+    upper: i64,
+    sum: i128,
+}
+
+impl Sum {
+    /// Construct a new `Sum` clamping records to `[lower, upper]`. Panics if
+    /// `lower > upper`.
+    pub fn new(lower: i64, upper: i64) -> Sum {
+        assert!(lower <= upper, "Sum::new called with `lower` > `upper`");
+        Sum { lower, upper, sum: 0 }
+    }
+}
+
+impl Aggregator for Sum {
+    type Output = i64;
+
+    fn update(&mut self, value: i64) {
+        self.sum += value.clamp(self.lower, self.upper) as i128;
+    }
+
+    fn merge(&mut self, other: &Sum) {
+        assert!(self.lower == other.lower && self.upper == other.upper, "Sum::merge called with different bounds");
+        self.sum += other.sum;
+    }
+
+    fn sensitivity(&self) -> u64 {
+        self.lower.unsigned_abs().max(self.upper.unsigned_abs())
+    }
+
+    fn noised<F: FnMut() -> i64>(&self, mut noise: F) -> i64 {
+        saturate(self.sum + noise() as i128)
+    }
+}
+
+/// A quantile sketch: a histogram of the records, clamped to `[lower, upper]`, over
+/// equal-width buckets.
+///
+/// Each record falls in one bucket, so the released histogram has sensitivity 1 and
+/// quantiles are read from it after noising.
+#[derive(Clone, Debug)]
+pub struct Quantiles {
+    lower: i64,
+    upper: i64,
+    width: u128,
+    counts: Vec<u64>,
+}
+
+impl Quantiles {
+    /// Construct a new `Quantiles` sketch over `[lower, upper]` with at most `buckets`
+    /// buckets. Panics if `lower > upper` or `buckets == 0`.
+    pub fn new(lower: i64, upper: i64, buckets: usize) -> Quantiles {
+        assert!(lower <= upper, "Quantiles::new called with `lower` > `upper`");
+        assert!(buckets > 0, "Quantiles::new called with `buckets` == 0");
+        let span = (upper as i128 - lower as i128) as u128 + 1;
+        let width = (span + buckets as u128 - 1) / buckets as u128;
+        let buckets = ((span + width - 1) / width) as usize;
+        Quantiles { lower, upper, width, counts: vec![0; buckets] }
+    }
+}
+
+impl Aggregator for Quantiles {
+    type Output = NoisyHistogram;
+
+    fn update(&mut self, value: i64) {
+        let value = value.clamp(self.lower, self.upper);
+        let index = ((value as i128 - self.lower as i128) as u128 / self.width) as usize;
+        self.counts[index] = self.counts[index].saturating_add(1);
+    }
+
+    fn merge(&mut self, other: &Quantiles) {
+        assert!(self.lower == other.lower && self.upper == other.upper && self.width == other.width,
+                "Quantiles::merge called with different buckets");
+        for (count, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count = count.saturating_add(*other);
+        }
+    }
+
+    fn sensitivity(&self) -> u64 {
+        1
+    }
+
+    fn noised<F: FnMut() -> i64>(&self, mut noise: F) -> NoisyHistogram {
+        NoisyHistogram {
+            lower: self.lower,
+            upper: self.upper,
+            width: self.width,
+            counts: self.counts.iter().map(|&c| saturate(c as i128 + noise() as i128)).collect(),
+        }
+    }
+}
+
+/// The released histogram of a `Quantiles` sketch.
+#[derive(Clone, Debug)]
+pub struct NoisyHistogram {
+    lower: i64,
+    upper: i64,
+    width: u128,
+    counts: Vec<i64>,
+}
+
+impl NoisyHistogram {
+    /// The noised count of each bucket. Counts may be negative.
+    pub fn counts(&self) -> &[i64] {
+        &self.counts
+    }
+
+    /// The smallest value that falls in bucket `index`.
+    pub fn bucket_lower(&self, index: usize) -> i64 {
+        saturate(self.lower as i128 + index as i128 * self.width as i128)
+    }
+
+    /// An estimate of the `q`-quantile: the middle of the first bucket at which the
+    /// cumulative count, with negative counts taken as zero, reaches `q` of the total.
+    /// Returns `None` if every noised count is zero or below. Panics if `q` is not in
+    /// `[0, 1]`.
+    pub fn quantile(&self, q: f64) -> Option<i64> {
+        assert!((0.0..=1.0).contains(&q), "NoisyHistogram::quantile called with `q` outside [0, 1]");
+        let total: f64 = self.counts.iter().map(|&c| c.max(0) as f64).sum();
+        if total == 0.0 {
+            return None;
+        }
+        let target = q * total;
+        let mut cumulative = 0.0;
+        for (index, &count) in self.counts.iter().enumerate() {
+            cumulative += count.max(0) as f64;
+            if count > 0 && cumulative >= target {
+                let middle = self.bucket_lower(index) as i128 + (self.width / 2) as i128;
+                return Some(saturate(middle.min(self.upper as i128)));
+            }
+        }
+        None
+    }
+}
+
+/// An aggregator over a stream of epochs, released and reset at the end of each.
+pub struct StreamingAggregation<A, B> {
+    template: A,
+    current: A,
+    mechanism: Mechanism,
+    budget: B,
+}
+
+impl<A: Aggregator + Clone, B: PrivacyBudget> StreamingAggregation<A, B> {
+    /// Construct a new `StreamingAggregation` starting each epoch from `aggregator`.
+    pub fn new(aggregator: A, mechanism: Mechanism, budget: B) -> StreamingAggregation<A, B> {
+        StreamingAggregation { current: aggregator.clone(), template: aggregator, mechanism, budget }
+    }
+
+    /// Add one record to the current epoch.
+    pub fn push(&mut self, value: i64) {
+        self.current.update(value);
+    }
+
+    /// Fold a partial aggregate into the current epoch.
+    pub fn merge(&mut self, partial: &A) {
+        self.current.merge(partial);
+    }
+
+    pub fn budget(&self) -> &B {
+        &self.budget
+    }
+
+    /// Release the current epoch and start a new one.
+    ///
+    /// If the budget refuses the charge, nothing is released and the epoch is kept.
+    pub fn flush<R: Rng>(&mut self, rng: &mut R) -> Result<A::Output, BudgetExceeded> {
+        let output = release(&self.current, &self.mechanism, &mut self.budget, rng)?;
+        self.current = self.template.clone();
+        Ok(output)
+    }
+
+    /// Drop the records of the current epoch without releasing them.
+    pub fn discard(&mut self) {
+        self.current = self.template.clone();
+    }
+}
+
+fn saturate(value: i128) -> i64 {
+    value.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
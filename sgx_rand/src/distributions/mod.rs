@@ -32,11 +32,13 @@ pub use self::range::Range;
 pub use self::gamma::{Gamma, ChiSquared, FisherF, StudentT};
 pub use self::normal::{Normal, LogNormal};
 pub use self::exponential::Exp;
+pub use self::privacy::{DiscreteLaplace, DiscreteGaussian, PrivacyAccountant, BudgetExceeded};
 
 pub mod range;
 pub mod gamma;
 pub mod normal;
 pub mod exponential;
+pub mod privacy;
 
 /// Types that can be used to create a random instance of `Support`.
 pub trait Sample<Support> {
@@ -0,0 +1,303 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Noise for differential privacy.
+//!
+//! `DiscreteLaplace` and `DiscreteGaussian` add integer noise to integer-valued
+//! statistics. Floating-point noise is deliberately not offered: the gaps in
+//! floating-point samples reveal the noised value (Mironov, CCS 2012).
+//!
+//! Both samplers draw from a precomputed table of the cumulative distribution, scanning
+//! the whole table for every sample with branch-free comparisons, so neither the time
+//! taken nor the memory accessed depends on the noise drawn. The tables are cut where the
+//! remaining tail mass falls below 2^-64.
+//!
+//! `PrivacyAccountant` keeps track of the privacy budget spent by a sequence of releases.
+
+use std::vec::Vec;
+use std::fmt;
+use crate::Rng;
+use crate::distributions::{Sample, IndependentSample};
+
+/// The largest table, in entries, a sampler may use. It bounds the scale of
+/// `DiscreteLaplace` to about 1470 and that of `DiscreteGaussian` to about 6950.
+pub const MAX_TABLE_SIZE: usize = 1 << 16;
+
+// ln(2^64): the tail is cut where the probability mass falls below 2^-64.
+const TAIL_LOG: f64 = 44.361_419_555_836_5;
+
+// Cumulative distribution of |X| for a symmetric distribution with unnormalized
+// weights `weight(k)`, scaled to 2^64. Entry `i` is P(|X| <= i); the final entry,
+// which would be 2^64, is left out.
+fn cdf_table<F: Fn(f64) -> f64>(limit: usize, weight: F) -> Vec<u64> {
+    let weights: Vec<f64> = (0..=limit)
+        .map(|k| if k == 0 { 1.0 } else { 2.0 * weight(k as f64) })
+        .collect();
+    let total: f64 = weights.iter().sum();
+    let mut cumulative = 0.0;
+    weights[..limit]
+        .iter()
+        .map(|w| {
+            cumulative += w;
+            let scaled = cumulative / total * 18_446_744_073_709_551_616.0;
+            if scaled >= 18_446_744_073_709_551_615.0 { u64::MAX } else { scaled as u64 }
+        })
+        .collect()
+}
+
+// Draws |X| from `table` and a sign, in time independent of the result.
+fn sample_table<R: Rng>(table: &[u64], rng: &mut R) -> i64 {
+    let u = rng.next_u64() as u128;
+    let mut magnitude = 0_i64;
+    for &threshold in table {
+        // 1 when u >= threshold: the subtraction borrows exactly when u < threshold.
+        let below = (u.wrapping_sub(threshold as u128) >> 127) as i64;
+        magnitude += 1 - below;
+    }
+    let sign = (rng.next_u32() & 1) as i64;
+    magnitude * (1 - 2 * sign)
+}
+
+/// The discrete Laplace distribution, the Laplace mechanism for integer statistics.
+///
+/// `P(X = x)` is proportional to `exp(-|x| / scale)`. Adding a sample to a statistic of
+/// sensitivity `d` is `(d / scale)`-differentially private.
+///
+/// # Example
+///
+/// ```rust
+/// use sgx_rand::distributions::{DiscreteLaplace, IndependentSample};
+///
+/// let laplace = DiscreteLaplace::calibrated(1, 0.5);
+/// let noised = 1234 + laplace.ind_sample(&mut sgx_rand::tcs_rng());
+/// println!("{} released with 0.5-DP", noised);
+/// ```
+#[derive(Clone, Debug)]
+pub struct DiscreteLaplace {
+    scale: f64,
+    table: Vec<u64>,
+}
+
+impl DiscreteLaplace {
+    /// Construct a new `DiscreteLaplace` with the given `scale`. Panics if `scale <= 0`
+    /// or the table would exceed `MAX_TABLE_SIZE` entries.
+    pub fn new(scale: f64) -> DiscreteLaplace {
+        assert!(scale > 0.0, "DiscreteLaplace::new called with `scale` <= 0");
+        let limit = (scale * TAIL_LOG).ceil() as usize + 1;
+        assert!(limit <= MAX_TABLE_SIZE, "DiscreteLaplace::new called with `scale` too large");
+        let table = cdf_table(limit, |k| (-k / scale).exp());
+        DiscreteLaplace { scale, table }
+    }
+
+    /// The noise for `epsilon`-differential privacy of a statistic of sensitivity
+    /// `sensitivity`.
+    pub fn calibrated(sensitivity: u64, epsilon: f64) -> DiscreteLaplace {
+        assert!(epsilon > 0.0, "DiscreteLaplace::calibrated called with `epsilon` <= 0");
+        DiscreteLaplace::new(sensitivity as f64 / epsilon)
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// The privacy loss `epsilon` for a statistic of sensitivity `sensitivity`.
+    pub fn epsilon(&self, sensitivity: u64) -> f64 {
+        sensitivity as f64 / self.scale
+    }
+}
+
+impl Sample<i64> for DiscreteLaplace {
+    fn sample<R: Rng>(&mut self, rng: &mut R) -> i64 { self.ind_sample(rng) }
+}
+impl IndependentSample<i64> for DiscreteLaplace {
+    fn ind_sample<R: Rng>(&self, rng: &mut R) -> i64 {
+        sample_table(&self.table, rng)
+    }
+}
+
+/// The discrete Gaussian distribution (Canonne, Kamath and Steinke, 2020).
+///
+/// `P(X = x)` is proportional to `exp(-x^2 / (2 * sigma^2))`. Adding a sample to a
+/// statistic of sensitivity `d` satisfies `(d^2 / (2 * sigma^2))`-zero-concentrated
+/// differential privacy.
+///
+/// # Example
+///
+/// ```rust
+/// use sgx_rand::distributions::{DiscreteGaussian, IndependentSample};
+///
+/// let gaussian = DiscreteGaussian::new(4.0);
+/// let v = gaussian.ind_sample(&mut sgx_rand::tcs_rng());
+/// println!("{} is from a discrete N(0, 16) distribution", v);
+/// ```
+#[derive(Clone, Debug)]
+pub struct DiscreteGaussian {
+    sigma: f64,
+    table: Vec<u64>,
+}
+
+impl DiscreteGaussian {
+    /// Construct a new `DiscreteGaussian` with the given `sigma`. Panics if `sigma <= 0`
+    /// or the table would exceed `MAX_TABLE_SIZE` entries.
+    pub fn new(sigma: f64) -> DiscreteGaussian {
+        assert!(sigma > 0.0, "DiscreteGaussian::new called with `sigma` <= 0");
+        let limit = (sigma * (2.0 * TAIL_LOG).sqrt()).ceil() as usize + 1;
+        assert!(limit <= MAX_TABLE_SIZE, "DiscreteGaussian::new called with `sigma` too large");
+        let variance = sigma * sigma;
+        let table = cdf_table(limit, |k| (-k * k / (2.0 * variance)).exp());
+        DiscreteGaussian { sigma, table }
+    }
+
+    /// The noise for `rho`-zero-concentrated differential privacy of a statistic of
+    /// sensitivity `sensitivity`.
+    pub fn calibrated(sensitivity: u64, rho: f64) -> DiscreteGaussian {
+        assert!(rho > 0.0, "DiscreteGaussian::calibrated called with `rho` <= 0");
+        DiscreteGaussian::new(sensitivity as f64 / (2.0 * rho).sqrt())
+    }
+
+    pub fn sigma(&self) -> f64 {
+        self.sigma
+    }
+
+    /// The zCDP parameter `rho` for a statistic of sensitivity `sensitivity`.
+    pub fn rho(&self, sensitivity: u64) -> f64 {
+        let d = sensitivity as f64;
+        d * d / (2.0 * self.sigma * self.sigma)
+    }
+}
+
+impl Sample<i64> for DiscreteGaussian {
+    fn sample<R: Rng>(&mut self, rng: &mut R) -> i64 { self.ind_sample(rng) }
+}
+impl IndependentSample<i64> for DiscreteGaussian {
+    fn ind_sample<R: Rng>(&self, rng: &mut R) -> i64 {
+        sample_table(&self.table, rng)
+    }
+}
+
+/// A charge was refused because it would exceed the privacy budget.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BudgetExceeded;
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("privacy budget exceeded")
+    }
+}
+
+/// Tracks the privacy budget `(epsilon, delta)` spent by a sequence of releases.
+///
+/// Pure `epsilon`-DP charges compose by summation; zCDP charges compose by summation of
+/// `rho` and are converted to `(epsilon, delta)`-DP with
+/// `epsilon = rho + 2 * sqrt(rho * ln(1 / delta))`. Since a pure `epsilon`-DP release is
+/// also `(epsilon^2 / 2)`-zCDP, the accountant uses whichever of the two compositions
+/// gives the smaller total.
+///
+/// A charge that would take the total above the budget is refused and has no effect.
+///
+/// # Example
+///
+/// ```rust
+/// use sgx_rand::distributions::{DiscreteGaussian, PrivacyAccountant};
+///
+/// let mut accountant = PrivacyAccountant::new(1.0, 1e-9);
+/// let gaussian = DiscreteGaussian::calibrated(1, 0.01);
+/// while accountant.charge_gaussian(&gaussian, 1).is_ok() {
+///     // release a statistic
+/// }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct PrivacyAccountant {
+    epsilon: f64,
+    delta: f64,
+    pure: f64,
+    pure_squares: f64,
+    rho: f64,
+}
+
+impl PrivacyAccountant {
+    /// Construct a new `PrivacyAccountant` with a budget of `(epsilon, delta)`. Panics if
+    /// `epsilon <= 0` or `delta` is not in `[0, 1)`.
+    pub fn new(epsilon: f64, delta: f64) -> PrivacyAccountant {
+        assert!(epsilon > 0.0, "PrivacyAccountant::new called with `epsilon` <= 0");
+        assert!((0.0..1.0).contains(&delta), "PrivacyAccountant::new called with `delta` outside [0, 1)");
+        PrivacyAccountant { epsilon, delta, pure: 0.0, pure_squares: 0.0, rho: 0.0 }
+    }
+
+    pub fn budget(&self) -> (f64, f64) {
+        (self.epsilon, self.delta)
+    }
+
+    /// The `epsilon` spent so far, at the `delta` of the budget.
+    pub fn spent(&self) -> f64 {
+        self.total(self.pure, self.pure_squares, self.rho)
+    }
+
+    /// The `epsilon` left, never negative.
+    pub fn remaining(&self) -> f64 {
+        (self.epsilon - self.spent()).max(0.0)
+    }
+
+    /// Charge a pure `epsilon`-DP release.
+    pub fn charge_pure(&mut self, epsilon: f64) -> Result<(), BudgetExceeded> {
+        assert!(epsilon >= 0.0, "PrivacyAccountant::charge_pure called with `epsilon` < 0");
+        self.charge(self.pure + epsilon, self.pure_squares + epsilon * epsilon, self.rho)
+    }
+
+    /// Charge a `rho`-zCDP release.
+    pub fn charge_zcdp(&mut self, rho: f64) -> Result<(), BudgetExceeded> {
+        assert!(rho >= 0.0, "PrivacyAccountant::charge_zcdp called with `rho` < 0");
+        self.charge(self.pure, self.pure_squares, self.rho + rho)
+    }
+
+    /// Charge the release of a statistic of sensitivity `sensitivity` with noise from
+    /// `laplace`.
+    pub fn charge_laplace(&mut self, laplace: &DiscreteLaplace, sensitivity: u64) -> Result<(), BudgetExceeded> {
+        self.charge_pure(laplace.epsilon(sensitivity))
+    }
+
+    /// Charge the release of a statistic of sensitivity `sensitivity` with noise from
+    /// `gaussian`.
+    pub fn charge_gaussian(&mut self, gaussian: &DiscreteGaussian, sensitivity: u64) -> Result<(), BudgetExceeded> {
+        self.charge_zcdp(gaussian.rho(sensitivity))
+    }
+
+    fn charge(&mut self, pure: f64, pure_squares: f64, rho: f64) -> Result<(), BudgetExceeded> {
+        if self.total(pure, pure_squares, rho) > self.epsilon {
+            return Err(BudgetExceeded);
+        }
+        self.pure = pure;
+        self.pure_squares = pure_squares;
+        self.rho = rho;
+        Ok(())
+    }
+
+    fn total(&self, pure: f64, pure_squares: f64, rho: f64) -> f64 {
+        let convert = |rho: f64| {
+            if rho == 0.0 {
+                0.0
+            } else if self.delta == 0.0 {
+                f64::INFINITY
+            } else {
+                rho + 2.0 * (rho * (1.0 / self.delta).ln()).sqrt()
+            }
+        };
+        let basic = pure + convert(rho);
+        let concentrated = convert(rho + pure_squares / 2.0);
+        basic.min(concentrated)
+    }
+}
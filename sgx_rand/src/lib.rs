@@ -24,6 +24,7 @@
 
 extern crate sgx_types;
 extern crate sgx_trts;
+extern crate sgx_tcrypto;
 #[cfg(not(target_env = "sgx"))]
 #[macro_use]
 extern crate sgx_tstd as std;
@@ -41,6 +42,7 @@ pub use os::SgxRng;
 
 pub use isaac::{IsaacRng, Isaac64Rng};
 pub use chacha::ChaChaRng;
+pub use stream::{tcs_rng, reseed_streams, StreamSeed, TcsRng};
 
 #[cfg(target_pointer_width = "32")]
 use IsaacRng as IsaacWordRng;
@@ -51,12 +53,14 @@ use distributions::{Range, IndependentSample};
 use distributions::range::SampleRange;
 
 pub mod distributions;
+pub mod aggregate;
 pub mod isaac;
 pub mod chacha;
 pub mod reseeding;
 mod rand_impls;
 pub mod os;
 pub mod read;
+pub mod stream;
 
 #[allow(bad_style)]
 type w64 = w<u64>;
@@ -0,0 +1,212 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Per-TCS random number streams and deterministic sub-generators.
+//!
+//! `tcs_rng` returns a ChaCha20 generator private to the calling thread. Inside an
+//! enclave thread-local storage belongs to the TCS, so every TCS gets its own stream.
+//! Streams are not seeded from RDRAND each: they are derived from one master seed, with
+//! the TCS and a unique stream number as domain separation, so that creating a stream is
+//! cheap and no two streams can coincide.
+//!
+//! A generator's state is plain memory. If that memory is ever duplicated, for example
+//! when enclave state is restored from a snapshot, the copies would produce the same
+//! output. `reseed_streams` replaces the master seed, and every stream re-derives itself
+//! from the new seed before its next output.
+//!
+//! `StreamSeed` derives deterministic generators for reproducible sampling: the same seed,
+//! domain and index always give the same stream. Keep the seed sealed between runs.
+
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use std::vec::Vec;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::SgxMutex;
+use std::ptr;
+use sgx_tcrypto::rsgx_hmac_sha256_slice;
+use sgx_trts::enclave::SgxThreadData;
+use sgx_types::*;
+
+use crate::{ChaChaRng, Rng, SeedableRng, SgxRng};
+
+const SEED_SIZE: usize = 32;
+const TCS_DOMAIN: &[u8] = b"sgx_rand tcs stream";
+
+/// Key material from which generators are derived.
+///
+/// The seed is wiped when dropped.
+pub struct StreamSeed([u8; SEED_SIZE]);
+
+impl Drop for StreamSeed {
+    fn drop(&mut self) {
+        for b in self.0.iter_mut() {
+            unsafe { ptr::write_volatile(b, 0) };
+        }
+    }
+}
+
+impl StreamSeed {
+    /// Generate a new seed from the enclave's hardware RNG.
+    pub fn generate() -> io::Result<StreamSeed> {
+        let mut seed = [0_u8; SEED_SIZE];
+        SgxRng::new()?.fill_bytes(&mut seed);
+        Ok(StreamSeed(seed))
+    }
+
+    /// Use `bytes` as a seed, e.g. a seed unsealed from an earlier run.
+    pub fn from_bytes(bytes: [u8; SEED_SIZE]) -> StreamSeed {
+        StreamSeed(bytes)
+    }
+
+    /// The seed bytes, to be sealed for later runs.
+    pub fn as_bytes(&self) -> &[u8; SEED_SIZE] {
+        &self.0
+    }
+
+    /// Derive the child seed for `domain`.
+    ///
+    /// Child seeds of different domains are independent, and a child seed reveals nothing
+    /// about its parent.
+    pub fn derive(&self, domain: &[u8]) -> io::Result<StreamSeed> {
+        self.prf(domain, None).map(StreamSeed)
+    }
+
+    /// The deterministic generator number `index` of `domain`.
+    pub fn rng(&self, domain: &[u8], index: u64) -> io::Result<ChaChaRng> {
+        let mut key = self.prf(domain, Some(index))?;
+        let mut words = [0_u32; SEED_SIZE / 4];
+        for (w, b) in words.iter_mut().zip(key.chunks_exact(4)) {
+            *w = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        }
+        let rng = ChaChaRng::from_seed(&words[..]);
+        for b in key.iter_mut() {
+            unsafe { ptr::write_volatile(b, 0) };
+        }
+        for w in words.iter_mut() {
+            unsafe { ptr::write_volatile(w, 0) };
+        }
+        Ok(rng)
+    }
+
+    // HMAC-SHA256 over an unambiguous encoding of the domain and the optional index.
+    fn prf(&self, domain: &[u8], index: Option<u64>) -> io::Result<[u8; SEED_SIZE]> {
+        let mut input = Vec::with_capacity(8 + domain.len() + 9);
+        input.extend_from_slice(&(domain.len() as u64).to_le_bytes());
+        input.extend_from_slice(domain);
+        match index {
+            Some(index) => {
+                input.push(1);
+                input.extend_from_slice(&index.to_le_bytes());
+            }
+            None => input.push(0),
+        }
+        rsgx_hmac_sha256_slice(&self.0, &input[..]).map_err(io::Error::from)
+    }
+}
+
+static MASTER: SgxMutex<Option<StreamSeed>> = SgxMutex::new(None);
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+static NEXT_STREAM: AtomicU64 = AtomicU64::new(0);
+
+/// Replace the master seed of the per-TCS streams with a fresh one.
+///
+/// Every stream re-derives itself from the new seed before its next output. Call this
+/// after enclave state that may include generator state has been restored.
+pub fn reseed_streams() -> io::Result<()> {
+    let seed = StreamSeed::generate()?;
+    let mut master = MASTER.lock().unwrap_or_else(|e| e.into_inner());
+    *master = Some(seed);
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    Ok(())
+}
+
+fn derive_stream() -> io::Result<(u64, ChaChaRng)> {
+    let mut master = MASTER.lock().unwrap_or_else(|e| e.into_inner());
+    if master.is_none() {
+        *master = Some(StreamSeed::generate()?);
+    }
+    let generation = GENERATION.load(Ordering::SeqCst);
+    let stream = NEXT_STREAM.fetch_add(1, Ordering::Relaxed);
+    let tcs = SgxThreadData::current().get_tcs() as u64;
+
+    let mut domain = Vec::with_capacity(TCS_DOMAIN.len() + 8);
+    domain.extend_from_slice(TCS_DOMAIN);
+    domain.extend_from_slice(&tcs.to_le_bytes());
+    let seed = master.as_ref().ok_or(io::Error::from(sgx_status_t::SGX_ERROR_UNEXPECTED))?;
+    seed.rng(&domain[..], stream).map(|rng| (generation, rng))
+}
+
+struct TcsRngInner {
+    generation: u64,
+    rng: ChaChaRng,
+}
+
+impl TcsRngInner {
+    fn refresh(&mut self) {
+        if self.generation != GENERATION.load(Ordering::SeqCst) {
+            let (generation, rng) = match derive_stream() {
+                Ok(stream) => stream,
+                Err(e) => panic!("could not reseed tcs_rng: {}", e),
+            };
+            self.generation = generation;
+            self.rng = rng;
+        }
+    }
+}
+
+/// The generator of the calling TCS.
+#[derive(Clone)]
+pub struct TcsRng {
+    rng: Rc<RefCell<TcsRngInner>>,
+}
+
+/// Retrieve the lazily-initialized generator of the calling TCS.
+///
+/// The generator is ChaCha20 keyed by a stream of the master seed; see the module
+/// documentation.
+pub fn tcs_rng() -> TcsRng {
+    thread_local!(static TCS_RNG_KEY: Rc<RefCell<TcsRngInner>> = {
+        let (generation, rng) = match derive_stream() {
+            Ok(stream) => stream,
+            Err(e) => panic!("could not initialize tcs_rng: {}", e),
+        };
+        Rc::new(RefCell::new(TcsRngInner { generation, rng }))
+    });
+
+    TcsRng { rng: TCS_RNG_KEY.with(|t| t.clone()) }
+}
+
+impl Rng for TcsRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut inner = self.rng.borrow_mut();
+        inner.refresh();
+        inner.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut inner = self.rng.borrow_mut();
+        inner.refresh();
+        inner.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        let mut inner = self.rng.borrow_mut();
+        inner.refresh();
+        inner.rng.fill_bytes(bytes)
+    }
+}
@@ -0,0 +1,314 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::der::{
+    self, explicit, BIT_STRING, GENERALIZED_TIME, OCTET_STRING, SEQUENCE, SET, UTC_TIME,
+};
+use alloc::vec::Vec;
+use core::ptr;
+use sgx_tcrypto::{rsgx_sha256_slice, SgxEccHandle};
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_tse::rsgx_create_report;
+use sgx_types::*;
+
+pub(crate) const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+pub(crate) const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+pub(crate) const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+// 1.2.840.113741.1337.6, the quote extension of Gramine's RA-TLS.
+pub(crate) const OID_SGX_QUOTE: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf8, 0x4d, 0x8a, 0x39, 0x06];
+
+const SUBJECT_NAME: &[u8] = b"SGX RA-TLS";
+
+///
+/// The source of the quotes that bind RA-TLS certificates to the enclave.
+///
+/// # Description
+///
+/// Quoting needs the quoting enclave, which is reached through OCALLs that the
+/// application defines; an implementation of this trait forwards to them. EPID
+/// and DCAP quotes are both accepted, as long as the quote embeds the report body
+/// at the usual offset.
+///
+pub trait SgxQuoteProvider {
+    ///
+    /// Returns the target information of the quoting enclave.
+    ///
+    fn target_info(&self) -> SgxResult<sgx_target_info_t>;
+
+    ///
+    /// Returns the quote of a report targeted at the quoting enclave.
+    ///
+    fn quote(&self, report: &sgx_report_t) -> SgxResult<Vec<u8>>;
+}
+
+///
+/// A fresh P-256 key pair with a self-signed X.509 certificate that carries a
+/// quote of the enclave.
+///
+/// # Description
+///
+/// The report data of the quote is the SHA-256 hash of the DER encoded public key
+/// of the certificate, followed by zeros. A peer that verifies the quote and that
+/// hash knows that the key was generated in the quoted enclave.
+///
+/// The private key never leaves the enclave other than through `private_key_der`,
+/// and is wiped when the identity is dropped.
+///
+pub struct SgxRaTlsIdentity {
+    private: sgx_ec256_private_t,
+    public: sgx_ec256_public_t,
+    quote: Vec<u8>,
+    certificate: Vec<u8>,
+}
+
+impl SgxRaTlsIdentity {
+    ///
+    /// Generates a key pair, quotes the enclave and issues the certificate.
+    ///
+    /// # Errors
+    ///
+    /// The errors of the key generation, of `rsgx_create_report`, and of the
+    /// provider are returned as they are.
+    ///
+    pub fn generate<P: SgxQuoteProvider + ?Sized>(provider: &P) -> SgxResult<SgxRaTlsIdentity> {
+        let ecc = SgxEccHandle::new();
+        ecc.open()?;
+        let (private, public) = ecc.create_key_pair()?;
+        let mut identity = SgxRaTlsIdentity {
+            private,
+            public,
+            quote: Vec::new(),
+            certificate: Vec::new(),
+        };
+
+        let spki = subject_public_key_info(&public);
+        let target_info = provider.target_info()?;
+        let report = rsgx_create_report(&target_info, &report_data(&spki)?)?;
+        identity.quote = provider.quote(&report)?;
+
+        let mut serial = [0_u8; 16];
+        rsgx_read_rand(&mut serial)?;
+        serial[0] &= 0x7f;
+        serial[0] |= 0x40;
+
+        let name = name();
+        let tbs = der::constructed(
+            SEQUENCE,
+            &[
+                &der::tlv(explicit(0), &der::unsigned(&[2])),
+                &der::unsigned(&serial),
+                &signature_algorithm(),
+                &name,
+                &der::constructed(
+                    SEQUENCE,
+                    &[
+                        &der::tlv(UTC_TIME, b"000101000000Z"),
+                        &der::tlv(GENERALIZED_TIME, b"99991231235959Z"),
+                    ],
+                ),
+                &name,
+                &spki,
+                &der::tlv(
+                    explicit(3),
+                    &der::constructed(
+                        SEQUENCE,
+                        &[&der::constructed(
+                            SEQUENCE,
+                            &[
+                                &der::tlv(der::OID, OID_SGX_QUOTE),
+                                &der::tlv(OCTET_STRING, &identity.quote),
+                            ],
+                        )],
+                    ),
+                ),
+            ],
+        );
+        let signature = ecc.ecdsa_sign_slice(tbs.as_slice(), &identity.private)?;
+        identity.certificate = der::constructed(
+            SEQUENCE,
+            &[
+                &tbs,
+                &signature_algorithm(),
+                &der::bit_string(&signature_der(&signature)),
+            ],
+        );
+        Ok(identity)
+    }
+
+    ///
+    /// Returns the DER encoded certificate, to present to TLS peers.
+    ///
+    pub fn certificate_der(&self) -> &[u8] {
+        &self.certificate
+    }
+
+    ///
+    /// Returns the private key as a DER encoded PKCS #8 structure, as TLS libraries
+    /// take it.
+    ///
+    /// # Description
+    ///
+    /// The caller must wipe the returned buffer once the key is loaded.
+    ///
+    pub fn private_key_der(&self) -> Vec<u8> {
+        let mut d = self.private.r;
+        d.reverse();
+        let ec_private_key = der::constructed(
+            SEQUENCE,
+            &[
+                &der::unsigned(&[1]),
+                &der::tlv(OCTET_STRING, &d),
+                &der::tlv(
+                    explicit(1),
+                    &der::bit_string(&uncompressed_point(&self.public)),
+                ),
+            ],
+        );
+        wipe(&mut d);
+        let key = der::constructed(
+            SEQUENCE,
+            &[
+                &der::unsigned(&[0]),
+                &ec_algorithm(),
+                &der::tlv(OCTET_STRING, &ec_private_key),
+            ],
+        );
+        let mut ec_private_key = ec_private_key;
+        wipe(&mut ec_private_key);
+        key
+    }
+
+    ///
+    /// Returns the public key of the certificate.
+    ///
+    pub fn public_key(&self) -> &sgx_ec256_public_t {
+        &self.public
+    }
+
+    ///
+    /// Returns the quote embedded in the certificate.
+    ///
+    pub fn quote(&self) -> &[u8] {
+        &self.quote
+    }
+}
+
+impl Drop for SgxRaTlsIdentity {
+    fn drop(&mut self) {
+        wipe(&mut self.private.r);
+    }
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
+
+pub(crate) fn report_data(spki: &[u8]) -> SgxResult<sgx_report_data_t> {
+    let hash = rsgx_sha256_slice(spki)?;
+    let mut report_data = sgx_report_data_t::default();
+    report_data.d[..hash.len()].copy_from_slice(&hash);
+    Ok(report_data)
+}
+
+fn name() -> Vec<u8> {
+    der::constructed(
+        SEQUENCE,
+        &[&der::constructed(
+            SET,
+            &[&der::constructed(
+                SEQUENCE,
+                &[
+                    &der::tlv(der::OID, OID_COMMON_NAME),
+                    &der::tlv(der::UTF8_STRING, SUBJECT_NAME),
+                ],
+            )],
+        )],
+    )
+}
+
+fn signature_algorithm() -> Vec<u8> {
+    der::constructed(SEQUENCE, &[&der::tlv(der::OID, OID_ECDSA_WITH_SHA256)])
+}
+
+fn ec_algorithm() -> Vec<u8> {
+    der::constructed(
+        SEQUENCE,
+        &[
+            &der::tlv(der::OID, OID_EC_PUBLIC_KEY),
+            &der::tlv(der::OID, OID_PRIME256V1),
+        ],
+    )
+}
+
+// The SEC 1 uncompressed encoding of a point, whose coordinates the SDK keeps in
+// little-endian order.
+fn uncompressed_point(public: &sgx_ec256_public_t) -> [u8; 65] {
+    let mut point = [0_u8; 65];
+    point[0] = 0x04;
+    point[1..33].copy_from_slice(&public.gx);
+    point[33..].copy_from_slice(&public.gy);
+    point[1..33].reverse();
+    point[33..].reverse();
+    point
+}
+
+pub(crate) fn subject_public_key_info(public: &sgx_ec256_public_t) -> Vec<u8> {
+    der::constructed(
+        SEQUENCE,
+        &[
+            &ec_algorithm(),
+            &der::tlv(
+                BIT_STRING,
+                &[&[0_u8][..], &uncompressed_point(public)].concat(),
+            ),
+        ],
+    )
+}
+
+// The big-endian bytes of a signature component, which the SDK keeps as
+// little-endian words.
+pub(crate) fn component_to_be(words: &[u32; SGX_NISTP_ECP256_KEY_SIZE]) -> [u8; 32] {
+    let mut be = [0_u8; 32];
+    for (chunk, word) in be.chunks_exact_mut(4).zip(words.iter()) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    be.reverse();
+    be
+}
+
+pub(crate) fn component_from_be(be: &[u8; 32]) -> [u32; SGX_NISTP_ECP256_KEY_SIZE] {
+    let mut le = *be;
+    le.reverse();
+    let mut words = [0_u32; SGX_NISTP_ECP256_KEY_SIZE];
+    for (word, chunk) in words.iter_mut().zip(le.chunks_exact(4)) {
+        *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    words
+}
+
+fn signature_der(signature: &sgx_ec256_signature_t) -> Vec<u8> {
+    der::constructed(
+        SEQUENCE,
+        &[
+            &der::unsigned(&component_to_be(&signature.x)),
+            &der::unsigned(&component_to_be(&signature.y)),
+        ],
+    )
+}
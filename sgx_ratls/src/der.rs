@@ -0,0 +1,163 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+// A minimal DER encoder and decoder, for the few X.509 structures RA-TLS needs.
+
+use alloc::vec::Vec;
+
+pub(crate) const BOOLEAN: u8 = 0x01;
+pub(crate) const INTEGER: u8 = 0x02;
+pub(crate) const BIT_STRING: u8 = 0x03;
+pub(crate) const OCTET_STRING: u8 = 0x04;
+pub(crate) const OID: u8 = 0x06;
+pub(crate) const UTF8_STRING: u8 = 0x0c;
+pub(crate) const UTC_TIME: u8 = 0x17;
+pub(crate) const GENERALIZED_TIME: u8 = 0x18;
+pub(crate) const SEQUENCE: u8 = 0x30;
+pub(crate) const SET: u8 = 0x31;
+
+// The tag of an explicit context-specific field.
+pub(crate) const fn explicit(n: u8) -> u8 {
+    0xa0 | n
+}
+
+pub(crate) fn tlv(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(contents.len() + 6);
+    out.push(tag);
+    let len = contents.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = (len as u32).to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (4 - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(contents);
+    out
+}
+
+pub(crate) fn constructed(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+    tlv(tag, &parts.concat())
+}
+
+// A non-negative INTEGER from its big-endian magnitude.
+pub(crate) fn unsigned(be: &[u8]) -> Vec<u8> {
+    let skip = be.iter().take_while(|b| **b == 0).count();
+    let be = &be[skip..];
+    let mut contents = Vec::with_capacity(be.len() + 1);
+    if be.first().map_or(true, |b| b & 0x80 != 0) {
+        contents.push(0);
+    }
+    contents.extend_from_slice(be);
+    tlv(INTEGER, &contents)
+}
+
+pub(crate) fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    let mut contents = Vec::with_capacity(bytes.len() + 1);
+    contents.push(0);
+    contents.extend_from_slice(bytes);
+    tlv(BIT_STRING, &contents)
+}
+
+pub(crate) struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> Reader<'a> {
+        Reader { buf }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub(crate) fn peek_tag(&self) -> Option<u8> {
+        self.buf.first().copied()
+    }
+
+    // Reads a field of the given tag, and returns its contents.
+    pub(crate) fn read(&mut self, tag: u8) -> Option<&'a [u8]> {
+        let (header, len) = self.header(tag)?;
+        let contents = &self.buf[header..header + len];
+        self.buf = &self.buf[header + len..];
+        Some(contents)
+    }
+
+    // Reads a field of the given tag, and returns its whole encoding.
+    pub(crate) fn read_raw(&mut self, tag: u8) -> Option<&'a [u8]> {
+        let (header, len) = self.header(tag)?;
+        let raw = &self.buf[..header + len];
+        self.buf = &self.buf[header + len..];
+        Some(raw)
+    }
+
+    // Reads the field if it has the given tag.
+    pub(crate) fn read_optional(&mut self, tag: u8) -> Option<Option<&'a [u8]>> {
+        if self.peek_tag() == Some(tag) {
+            self.read(tag).map(Some)
+        } else {
+            Some(None)
+        }
+    }
+
+    // Returns the size of the header and of the contents of the next field,
+    // which must be in DER: definite and minimal lengths only.
+    fn header(&self, tag: u8) -> Option<(usize, usize)> {
+        if self.buf.len() < 2 || self.buf[0] != tag {
+            return None;
+        }
+        let first = self.buf[1] as usize;
+        let (header, len) = if first < 0x80 {
+            (2, first)
+        } else {
+            let n = first & 0x7f;
+            if n == 0 || n > 4 || self.buf.len() < 2 + n || self.buf[2] == 0 {
+                return None;
+            }
+            let len = self.buf[2..2 + n]
+                .iter()
+                .fold(0_usize, |len, b| (len << 8) | *b as usize);
+            if len < 0x80 {
+                return None;
+            }
+            (2 + n, len)
+        };
+        if self.buf.len() - header < len {
+            return None;
+        }
+        Some((header, len))
+    }
+}
+
+// Decodes a non-negative INTEGER into a big-endian number of `N` bytes.
+pub(crate) fn read_unsigned<const N: usize>(contents: &[u8]) -> Option<[u8; N]> {
+    let magnitude = match contents {
+        [] => return None,
+        [0, rest @ ..] if rest.first().map_or(false, |b| b & 0x80 != 0) => rest,
+        [b, ..] if b & 0x80 != 0 => return None,
+        [0, _, ..] => return None,
+        _ => contents,
+    };
+    if magnitude.len() > N {
+        return None;
+    }
+    let mut out = [0_u8; N];
+    out[N - magnitude.len()..].copy_from_slice(magnitude);
+    Some(out)
+}
@@ -0,0 +1,91 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Remote Attestation TLS
+//!
+//! The library binds TLS keys to enclaves, as RA-TLS does. An enclave generates a
+//! key pair and a self-signed certificate whose extension carries a quote of the
+//! enclave, with the hash of the public key in the report data. A peer that accepts
+//! the certificate after verifying the quote knows that it talks to that enclave.
+//!
+//! ```ignore
+//! let identity = SgxRaTlsIdentity::generate(&provider)?;
+//! let verifier = SgxRaTlsVerifier::new(&quote_verifier, SgxRaTlsPolicy::new().mr_signer(signer));
+//! let body = verifier.verify_certificate(peer_certificate)?;
+//! ```
+//!
+//! The library does not depend on any TLS implementation. `certificate_der` and
+//! `private_key_der` feed the certificate and key settings of rustls or mbedtls on
+//! both sides of a mutually attested connection.
+//!
+//! For mbedtls, `verify_mbedtls` is the whole body of the verify callback:
+//!
+//! ```ignore
+//! extern "C" fn verify(ctx: *mut c_void, crt: *mut mbedtls_x509_crt, depth: c_int, flags: *mut u32) -> c_int {
+//!     let verifier = unsafe { &*(ctx as *const SgxRaTlsVerifier<MyQuoteVerifier>) };
+//!     let der = unsafe { slice::from_raw_parts((*crt).raw.p, (*crt).raw.len) };
+//!     verifier.verify_mbedtls(der, depth, unsafe { &mut *flags })
+//! }
+//! ```
+//!
+//! For rustls, `verify_chain` is the body of `ServerCertVerifier::verify_server_cert`
+//! and `ClientCertVerifier::verify_client_cert`. The trait implementations themselves
+//! are left to the application, since their signatures differ between the rustls
+//! releases enclaves build against:
+//!
+//! ```ignore
+//! impl ServerCertVerifier for AttestedServer {
+//!     fn verify_server_cert(&self, _: &RootCertStore, presented: &[Certificate], _: DNSNameRef, _: &[u8])
+//!         -> Result<ServerCertVerified, TLSError> {
+//!         let (end_entity, rest) = presented.split_first().ok_or(TLSError::NoCertificatesPresented)?;
+//!         let rest: Vec<&[u8]> = rest.iter().map(|c| &c.0[..]).collect();
+//!         self.verifier.verify_chain(&end_entity.0, &rest)
+//!             .map(|_| ServerCertVerified::assertion())
+//!             .map_err(|e| TLSError::General(e.to_string()))
+//!     }
+//! }
+//! ```
+//!
+//! The quote extension uses the OID of Gramine (1.2.840.113741.1337.6), so that the
+//! certificates interoperate with Gramine enclaves.
+//!
+
+#![no_std]
+#![cfg_attr(
+    all(target_env = "sgx", target_vendor = "mesalock"),
+    feature(rustc_private)
+)]
+
+#![allow(non_camel_case_types)]
+
+extern crate alloc;
+
+extern crate sgx_tcrypto;
+extern crate sgx_trts;
+extern crate sgx_tse;
+extern crate sgx_types;
+
+mod der;
+
+mod cert;
+pub use self::cert::{SgxQuoteProvider, SgxRaTlsIdentity};
+
+mod verify;
+pub use self::verify::{
+    SgxQuoteVerifier, SgxRaTlsPolicy, SgxRaTlsVerifier, MBEDTLS_X509_BADCERT_NOT_TRUSTED,
+    MBEDTLS_X509_BADCERT_OTHER,
+};
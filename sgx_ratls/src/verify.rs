@@ -0,0 +1,392 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::cert::{self, OID_ECDSA_WITH_SHA256, OID_EC_PUBLIC_KEY, OID_PRIME256V1, OID_SGX_QUOTE};
+use crate::der::{self, explicit, Reader, BIT_STRING, BOOLEAN, INTEGER, OCTET_STRING, SEQUENCE};
+use core::mem;
+use core::ptr;
+use sgx_tcrypto::SgxEccHandle;
+use sgx_types::*;
+
+// The report body sits at the same offset in EPID and in DCAP quotes.
+const QUOTE_REPORT_BODY_OFFSET: usize = 48;
+const REPORT_BODY_SIZE: usize = 384;
+
+/// The mbedtls verification flag of a certificate that does not chain to a trusted CA.
+pub const MBEDTLS_X509_BADCERT_NOT_TRUSTED: u32 = 0x08;
+/// The mbedtls verification flag for a certificate rejected for another reason.
+pub const MBEDTLS_X509_BADCERT_OTHER: u32 = 0x0100;
+
+///
+/// Verifies the quotes that RA-TLS certificates carry.
+///
+/// # Description
+///
+/// The implementation checks that the quote is genuine and that the platform is
+/// up to date, with the attestation service or the quote verification library of
+/// the deployment. `SgxRaTlsVerifier` then checks the identity of the enclave and
+/// the binding of the quote to the certificate key.
+///
+pub trait SgxQuoteVerifier {
+    ///
+    /// Returns `Ok` if the quote is genuine and acceptable.
+    ///
+    fn verify_quote(&self, quote: &[u8]) -> SgxResult<()>;
+}
+
+///
+/// The enclaves that an `SgxRaTlsVerifier` accepts.
+///
+/// # Description
+///
+/// A policy must pin MRENCLAVE or MRSIGNER, or both; anything else would accept
+/// any enclave. Debug enclaves are refused unless `allow_debug` is set.
+///
+#[derive(Clone, Copy, Default)]
+pub struct SgxRaTlsPolicy {
+    mr_enclave: Option<sgx_measurement_t>,
+    mr_signer: Option<sgx_measurement_t>,
+    isv_prod_id: Option<sgx_prod_id_t>,
+    min_isv_svn: sgx_isv_svn_t,
+    allow_debug: bool,
+}
+
+impl SgxRaTlsPolicy {
+    pub fn new() -> SgxRaTlsPolicy {
+        SgxRaTlsPolicy::default()
+    }
+
+    pub fn mr_enclave(mut self, mr_enclave: sgx_measurement_t) -> SgxRaTlsPolicy {
+        self.mr_enclave = Some(mr_enclave);
+        self
+    }
+
+    pub fn mr_signer(mut self, mr_signer: sgx_measurement_t) -> SgxRaTlsPolicy {
+        self.mr_signer = Some(mr_signer);
+        self
+    }
+
+    pub fn isv_prod_id(mut self, isv_prod_id: sgx_prod_id_t) -> SgxRaTlsPolicy {
+        self.isv_prod_id = Some(isv_prod_id);
+        self
+    }
+
+    pub fn min_isv_svn(mut self, min_isv_svn: sgx_isv_svn_t) -> SgxRaTlsPolicy {
+        self.min_isv_svn = min_isv_svn;
+        self
+    }
+
+    pub fn allow_debug(mut self, allow_debug: bool) -> SgxRaTlsPolicy {
+        self.allow_debug = allow_debug;
+        self
+    }
+
+    fn check(&self, body: &sgx_report_body_t) -> SgxError {
+        if self.mr_enclave.is_none() && self.mr_signer.is_none() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        if let Some(mr_enclave) = self.mr_enclave {
+            if mr_enclave.m != body.mr_enclave.m {
+                return Err(sgx_status_t::SGX_ERROR_INVALID_ENCLAVE);
+            }
+        }
+        if let Some(mr_signer) = self.mr_signer {
+            if mr_signer.m != body.mr_signer.m {
+                return Err(sgx_status_t::SGX_ERROR_INVALID_ENCLAVE);
+            }
+        }
+        if let Some(isv_prod_id) = self.isv_prod_id {
+            if isv_prod_id != body.isv_prod_id {
+                return Err(sgx_status_t::SGX_ERROR_INVALID_ENCLAVE);
+            }
+        }
+        if body.isv_svn < self.min_isv_svn {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_ISVSVN);
+        }
+        if !self.allow_debug && body.attributes.flags & SGX_FLAGS_DEBUG != 0 {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_ATTRIBUTE);
+        }
+        Ok(())
+    }
+}
+
+///
+/// Verifies the RA-TLS certificates of peers.
+///
+pub struct SgxRaTlsVerifier<'a, V: SgxQuoteVerifier + ?Sized> {
+    verifier: &'a V,
+    policy: SgxRaTlsPolicy,
+}
+
+impl<'a, V: SgxQuoteVerifier + ?Sized> SgxRaTlsVerifier<'a, V> {
+    pub fn new(verifier: &'a V, policy: SgxRaTlsPolicy) -> SgxRaTlsVerifier<'a, V> {
+        SgxRaTlsVerifier { verifier, policy }
+    }
+
+    ///
+    /// Verifies a DER encoded RA-TLS certificate.
+    ///
+    /// # Description
+    ///
+    /// The certificate must be signed by its own key, carry exactly one quote that
+    /// the quote verifier accepts, and the report data of the quote must be the
+    /// hash of the certificate key. The enclave of the quote must then satisfy the
+    /// policy. The validity period is not checked, as the quote is the proof of
+    /// freshness.
+    ///
+    /// # Return value
+    ///
+    /// The report body of the quote, for further checks by the caller.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The certificate is malformed, or the policy pins neither MRENCLAVE nor MRSIGNER.
+    ///
+    /// **SGX_ERROR_INVALID_SIGNATURE**
+    ///
+    /// The self-signature is invalid, or the quote is not bound to the certificate key.
+    ///
+    /// **SGX_ERROR_INVALID_ENCLAVE**
+    ///
+    /// MRENCLAVE, MRSIGNER or the product ID do not match the policy.
+    ///
+    /// **SGX_ERROR_INVALID_ISVSVN**
+    ///
+    /// The security version of the enclave is below the minimum of the policy.
+    ///
+    /// **SGX_ERROR_INVALID_ATTRIBUTE**
+    ///
+    /// The enclave is a debug enclave and the policy does not allow those.
+    ///
+    /// The errors of the quote verifier are returned as they are.
+    ///
+    pub fn verify_certificate(&self, certificate: &[u8]) -> SgxResult<sgx_report_body_t> {
+        let parsed = parse(certificate).ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+
+        let ecc = SgxEccHandle::new();
+        ecc.open()?;
+        if !ecc.check_point(&parsed.public)? {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        if !ecc.ecdsa_verify_slice(parsed.tbs, &parsed.public, &parsed.signature)? {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_SIGNATURE);
+        }
+
+        self.verifier.verify_quote(parsed.quote)?;
+        let body = report_body(parsed.quote).ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+        if body.report_data.d != cert::report_data(parsed.spki)?.d {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_SIGNATURE);
+        }
+        self.policy.check(&body)?;
+        Ok(body)
+    }
+
+    ///
+    /// Verifies the certificate chain presented by a TLS peer.
+    ///
+    /// # Description
+    ///
+    /// This is the body of a rustls `ServerCertVerifier::verify_server_cert` or
+    /// `ClientCertVerifier::verify_client_cert`, which receive the end-entity
+    /// certificate and the intermediates in DER. An RA-TLS certificate is
+    /// self-signed, so a peer that presents intermediates is rejected.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The peer presented intermediate certificates.
+    ///
+    /// The errors of `verify_certificate`.
+    ///
+    pub fn verify_chain(
+        &self,
+        end_entity: &[u8],
+        intermediates: &[&[u8]],
+    ) -> SgxResult<sgx_report_body_t> {
+        if !intermediates.is_empty() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        self.verify_certificate(end_entity)
+    }
+
+    ///
+    /// The body of an mbedtls verify callback.
+    ///
+    /// # Description
+    ///
+    /// mbedtls calls the callback set with `mbedtls_ssl_conf_verify` once for each
+    /// certificate of the chain, with its DER encoding (`crt->raw`), its depth and
+    /// the verification flags found so far. The RA-TLS certificate is the one at
+    /// depth 0: if it verifies, `MBEDTLS_X509_BADCERT_NOT_TRUSTED`, which mbedtls
+    /// sets for every self-signed certificate, is cleared; otherwise
+    /// `MBEDTLS_X509_BADCERT_OTHER` is set, so that the handshake fails. Any
+    /// certificate above depth 0 is flagged, as RA-TLS chains have only one.
+    ///
+    /// The callback returns 0 in both cases, as mbedtls expects, and leaves the
+    /// decision to the flags.
+    ///
+    pub fn verify_mbedtls(&self, certificate: &[u8], depth: i32, flags: &mut u32) -> i32 {
+        if depth != 0 {
+            *flags |= MBEDTLS_X509_BADCERT_OTHER;
+            return 0;
+        }
+        match self.verify_certificate(certificate) {
+            Ok(_) => *flags &= !MBEDTLS_X509_BADCERT_NOT_TRUSTED,
+            Err(_) => *flags |= MBEDTLS_X509_BADCERT_OTHER,
+        }
+        0
+    }
+}
+
+struct Parsed<'a> {
+    tbs: &'a [u8],
+    spki: &'a [u8],
+    public: sgx_ec256_public_t,
+    signature: sgx_ec256_signature_t,
+    quote: &'a [u8],
+}
+
+fn parse(certificate: &[u8]) -> Option<Parsed<'_>> {
+    let mut outer = Reader::new(certificate);
+    let mut cert = Reader::new(outer.read(SEQUENCE)?);
+    if !outer.is_empty() {
+        return None;
+    }
+    let tbs = cert.read_raw(SEQUENCE)?;
+    check_signature_algorithm(cert.read(SEQUENCE)?)?;
+    let signature = parse_signature(cert.read(BIT_STRING)?)?;
+    if !cert.is_empty() {
+        return None;
+    }
+
+    let mut fields = Reader::new(Reader::new(tbs).read(SEQUENCE)?);
+    let version = fields.read(explicit(0))?;
+    if Reader::new(version).read(INTEGER)? != [2] {
+        return None;
+    }
+    fields.read(INTEGER)?;
+    check_signature_algorithm(fields.read(SEQUENCE)?)?;
+    fields.read(SEQUENCE)?;
+    fields.read(SEQUENCE)?;
+    fields.read(SEQUENCE)?;
+    let spki = fields.read_raw(SEQUENCE)?;
+    let public = parse_public_key(spki)?;
+    fields.read_optional(0x81)?;
+    fields.read_optional(0x82)?;
+    let extensions = fields.read(explicit(3))?;
+    if !fields.is_empty() {
+        return None;
+    }
+    let quote = find_quote(extensions)?;
+
+    Some(Parsed {
+        tbs,
+        spki,
+        public,
+        signature,
+        quote,
+    })
+}
+
+fn check_signature_algorithm(algorithm: &[u8]) -> Option<()> {
+    let mut algorithm = Reader::new(algorithm);
+    if algorithm.read(der::OID)? != OID_ECDSA_WITH_SHA256 || !algorithm.is_empty() {
+        return None;
+    }
+    Some(())
+}
+
+fn parse_signature(bits: &[u8]) -> Option<sgx_ec256_signature_t> {
+    let (unused, bits) = bits.split_first()?;
+    if *unused != 0 {
+        return None;
+    }
+    let mut outer = Reader::new(bits);
+    let mut signature = Reader::new(outer.read(SEQUENCE)?);
+    let r = der::read_unsigned::<32>(signature.read(INTEGER)?)?;
+    let s = der::read_unsigned::<32>(signature.read(INTEGER)?)?;
+    if !signature.is_empty() || !outer.is_empty() {
+        return None;
+    }
+    Some(sgx_ec256_signature_t {
+        x: cert::component_from_be(&r),
+        y: cert::component_from_be(&s),
+    })
+}
+
+fn parse_public_key(spki: &[u8]) -> Option<sgx_ec256_public_t> {
+    let mut spki = Reader::new(Reader::new(spki).read(SEQUENCE)?);
+    let mut algorithm = Reader::new(spki.read(SEQUENCE)?);
+    if algorithm.read(der::OID)? != OID_EC_PUBLIC_KEY
+        || algorithm.read(der::OID)? != OID_PRIME256V1
+        || !algorithm.is_empty()
+    {
+        return None;
+    }
+    let key = spki.read(BIT_STRING)?;
+    if !spki.is_empty() || key.len() != 66 || key[0] != 0 || key[1] != 0x04 {
+        return None;
+    }
+    let mut public = sgx_ec256_public_t::default();
+    public.gx.copy_from_slice(&key[2..34]);
+    public.gy.copy_from_slice(&key[34..]);
+    public.gx.reverse();
+    public.gy.reverse();
+    Some(public)
+}
+
+fn find_quote(extensions: &[u8]) -> Option<&[u8]> {
+    let mut outer = Reader::new(extensions);
+    let mut extensions = Reader::new(outer.read(SEQUENCE)?);
+    if !outer.is_empty() {
+        return None;
+    }
+    let mut quote = None;
+    while !extensions.is_empty() {
+        let mut extension = Reader::new(extensions.read(SEQUENCE)?);
+        let id = extension.read(der::OID)?;
+        extension.read_optional(BOOLEAN)?;
+        let value = extension.read(OCTET_STRING)?;
+        if !extension.is_empty() {
+            return None;
+        }
+        if id == OID_SGX_QUOTE {
+            if quote.is_some() {
+                return None;
+            }
+            quote = Some(value);
+        }
+    }
+    quote
+}
+
+fn report_body(quote: &[u8]) -> Option<sgx_report_body_t> {
+    if quote.len() < QUOTE_REPORT_BODY_OFFSET + REPORT_BODY_SIZE {
+        return None;
+    }
+    match u16::from_le_bytes([quote[0], quote[1]]) {
+        3 => (),
+        4 if quote[4..8] == [0; 4] => (),
+        _ => return None,
+    }
+    let body = &quote[QUOTE_REPORT_BODY_OFFSET..QUOTE_REPORT_BODY_OFFSET + REPORT_BODY_SIZE];
+    debug_assert_eq!(mem::size_of::<sgx_report_body_t>(), REPORT_BODY_SIZE);
+    Some(unsafe { ptr::read_unaligned(body.as_ptr() as *const sgx_report_body_t) })
+}
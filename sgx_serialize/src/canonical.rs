@@ -0,0 +1,401 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//!
+//! Canonical encoding of `Serializable` types, for hashing and signing.
+//!
+//! A signature over an encoding is only as good as the encoding is unique:
+//! if one value has two encodings, or the encoder output depends on hash map
+//! iteration order, a verifier that re-encodes a record can reject a valid
+//! signature, and two parties can disagree on what was signed. Every record
+//! that is hashed or signed, such as an audit log record or a policy
+//! document, is encoded with [`to_vec`]. The encoding is deterministic and
+//! independent of the order of fields in the source:
+//!
+//! * Integers are big-endian at their fixed width; `usize` and `isize` take
+//!   8 bytes. Booleans and option tags take one byte.
+//! * Floats are their IEEE 754 bits, with `-0.0` written as `0.0`. NaN has no
+//!   canonical form and is rejected.
+//! * Strings, sequences and maps are prefixed with their length as a `u32`.
+//! * Struct fields and struct variant fields are written as name and value
+//!   pairs, sorted by name. Enum variants are written by name, not index.
+//! * Map entries are sorted by their encoded key; duplicate keys are
+//!   rejected.
+//!
+//! A sequence keeps the order it is given in, so unordered sets must be
+//! `BTreeSet`s rather than `HashSet`s.
+//!
+//! ```
+//! use sgx_serialize::canonical;
+//!
+//! let record = (1_u64, String::from("login"));
+//! let signed_bytes = canonical::to_vec(&record).unwrap();
+//! ```
+//!
+
+use crate::serialize::{self, Serializable};
+use std::fmt;
+use std::mem;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+///
+/// A value that has no canonical encoding.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Error {
+    /// A NaN float.
+    NotANumber,
+    /// A string, sequence or map longer than `u32::MAX`.
+    TooLong,
+    /// A map with two equal keys, or a struct with two equal field names.
+    DuplicateKey,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Error::NotANumber => "NaN has no canonical encoding",
+            Error::TooLong => "length does not fit 32 bits",
+            Error::DuplicateKey => "duplicate key",
+        };
+        f.write_str(msg)
+    }
+}
+
+///
+/// Encodes a value canonically.
+///
+pub fn to_vec<T: Serializable + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut encoder = Encoder::new();
+    value.encode(&mut encoder)?;
+    Ok(encoder.into_inner())
+}
+
+// A struct or map being collected, to be sorted when it is complete.
+enum Frame {
+    Fields(Vec<(String, Vec<u8>)>),
+    Entries(Vec<(Vec<u8>, Vec<u8>)>, Option<Vec<u8>>),
+}
+
+///
+/// The canonical encoder.
+///
+#[derive(Default)]
+pub struct Encoder {
+    out: Vec<u8>,
+    frames: Vec<Frame>,
+}
+
+impl Encoder {
+    pub fn new() -> Encoder {
+        Encoder::default()
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.out
+    }
+
+    fn len(&mut self, len: usize) -> Result<(), Error> {
+        let len = u32::try_from(len).map_err(|_| Error::TooLong)?;
+        self.out.extend_from_slice(&len.to_be_bytes());
+        Ok(())
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.len(bytes.len())?;
+        self.out.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    // Encodes what `f` writes into a buffer of its own.
+    fn capture<F>(&mut self, f: F) -> Result<Vec<u8>, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        let parent = mem::take(&mut self.out);
+        let result = f(self);
+        let captured = mem::replace(&mut self.out, parent);
+        result.map(|()| captured)
+    }
+
+    // Writes the named fields collected by `f`, sorted by name.
+    fn fields<F>(&mut self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.frames.push(Frame::Fields(Vec::new()));
+        let result = f(self);
+        let mut fields = match self.frames.pop() {
+            Some(Frame::Fields(fields)) => fields,
+            _ => unreachable!(),
+        };
+        result?;
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
+        if fields.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err(Error::DuplicateKey);
+        }
+        self.len(fields.len())?;
+        for (name, value) in fields {
+            self.bytes(name.as_bytes())?;
+            self.out.extend_from_slice(&value);
+        }
+        Ok(())
+    }
+
+    fn field<F>(&mut self, name: &str, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        let value = self.capture(f)?;
+        match self.frames.last_mut() {
+            Some(Frame::Fields(fields)) => fields.push((name.to_string(), value)),
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+}
+
+impl serialize::Encoder for Encoder {
+    type Error = Error;
+
+    fn emit_nil(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn emit_usize(&mut self, v: usize) -> Result<(), Error> {
+        self.emit_u64(v as u64)
+    }
+
+    fn emit_u128(&mut self, v: u128) -> Result<(), Error> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_u64(&mut self, v: u64) -> Result<(), Error> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_u32(&mut self, v: u32) -> Result<(), Error> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_u16(&mut self, v: u16) -> Result<(), Error> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_u8(&mut self, v: u8) -> Result<(), Error> {
+        self.out.push(v);
+        Ok(())
+    }
+
+    fn emit_isize(&mut self, v: isize) -> Result<(), Error> {
+        self.emit_i64(v as i64)
+    }
+
+    fn emit_i64(&mut self, v: i64) -> Result<(), Error> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_i32(&mut self, v: i32) -> Result<(), Error> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_i16(&mut self, v: i16) -> Result<(), Error> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_i8(&mut self, v: i8) -> Result<(), Error> {
+        self.out.push(v as u8);
+        Ok(())
+    }
+
+    fn emit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.out.push(v as u8);
+        Ok(())
+    }
+
+    fn emit_f64(&mut self, v: f64) -> Result<(), Error> {
+        if v.is_nan() {
+            return Err(Error::NotANumber);
+        }
+        // Adding 0.0 turns -0.0 into 0.0 and leaves every other value alone.
+        self.out
+            .extend_from_slice(&(v + 0.0).to_bits().to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_f32(&mut self, v: f32) -> Result<(), Error> {
+        if v.is_nan() {
+            return Err(Error::NotANumber);
+        }
+        self.out
+            .extend_from_slice(&(v + 0.0).to_bits().to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_char(&mut self, v: char) -> Result<(), Error> {
+        self.emit_u32(v as u32)
+    }
+
+    fn emit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.bytes(v.as_bytes())
+    }
+
+    fn emit_i128(&mut self, v: i128) -> Result<(), Error> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_enum_variant<F>(
+        &mut self,
+        v_name: &str,
+        _v_id: usize,
+        len: usize,
+        f: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.bytes(v_name.as_bytes())?;
+        self.len(len)?;
+        f(self)
+    }
+
+    fn emit_enum_struct_variant<F>(
+        &mut self,
+        v_name: &str,
+        _v_id: usize,
+        _len: usize,
+        f: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.bytes(v_name.as_bytes())?;
+        self.fields(f)
+    }
+
+    fn emit_enum_struct_variant_field<F>(
+        &mut self,
+        f_name: &str,
+        _f_idx: usize,
+        f: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.field(f_name, f)
+    }
+
+    fn emit_struct<F>(&mut self, _name: &str, _len: usize, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.fields(f)
+    }
+
+    fn emit_struct_field<F>(&mut self, f_name: &str, _f_idx: usize, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.field(f_name, f)
+    }
+
+    fn emit_option<F>(&mut self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        f(self)
+    }
+
+    fn emit_option_none(&mut self) -> Result<(), Error> {
+        self.out.push(0);
+        Ok(())
+    }
+
+    fn emit_option_some<F>(&mut self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.out.push(1);
+        f(self)
+    }
+
+    fn emit_seq<F>(&mut self, len: usize, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.len(len)?;
+        f(self)
+    }
+
+    fn emit_map<F>(&mut self, _len: usize, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.frames.push(Frame::Entries(Vec::new(), None));
+        let result = f(self);
+        let mut entries = match self.frames.pop() {
+            Some(Frame::Entries(entries, _)) => entries,
+            _ => unreachable!(),
+        };
+        result?;
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        if entries.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err(Error::DuplicateKey);
+        }
+        self.len(entries.len())?;
+        for (key, value) in entries {
+            self.out.extend_from_slice(&key);
+            self.out.extend_from_slice(&value);
+        }
+        Ok(())
+    }
+
+    fn emit_map_elt_key<F>(&mut self, _idx: usize, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        let key = self.capture(f)?;
+        match self.frames.last_mut() {
+            Some(Frame::Entries(_, pending)) => *pending = Some(key),
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn emit_map_elt_val<F>(&mut self, _idx: usize, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        let value = self.capture(f)?;
+        match self.frames.last_mut() {
+            Some(Frame::Entries(entries, pending)) => {
+                entries.push((pending.take().unwrap_or_default(), value))
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+}
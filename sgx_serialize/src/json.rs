@@ -0,0 +1,1058 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//!
+//! JSON for untrusted input.
+//!
+//! [`Parser`] is a streaming, SAX-style pull parser: each call to
+//! [`Parser::next`] returns one [`Event`]. It keeps an explicit stack instead
+//! of recursing, and enforces the hard caps of [`Limits`] on the input size,
+//! the nesting depth and the length of strings and numbers as it goes, so a
+//! hostile document costs at most the limits, never a stack overflow.
+//!
+//! Strings and numbers are returned as slices of the input. A [`Str`] is
+//! decoded only on demand, and without allocating if it holds no escapes.
+//!
+//! [`Value::parse`] builds a bounded DOM on top of the parser, which also
+//! caps the number of nodes and rejects duplicate object keys. [`Writer`]
+//! serializes, with the same explicit-stack discipline.
+//!
+//! ```
+//! use sgx_serialize::json::{Limits, Value};
+//!
+//! let limits = Limits::new().max_depth(8);
+//! let doc = Value::parse(r#"{"tcbStatus": "UpToDate", "version": 3}"#, &limits).unwrap();
+//! assert_eq!(doc.get("version").and_then(|v| v.as_u64()), Some(3));
+//! ```
+//!
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::format;
+use std::slice;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+/// The largest nesting depth [`Limits::max_depth`] accepts.
+pub const MAX_DEPTH: usize = 512;
+
+///
+/// Hard caps on a JSON document.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Limits {
+    max_input: usize,
+    max_depth: usize,
+    max_string: usize,
+    max_number: usize,
+    max_nodes: usize,
+    duplicate_keys: bool,
+}
+
+impl Limits {
+    /// Limits suited to API payloads and attestation collateral: 1 MiB of
+    /// input, a depth of 64, strings of 64 KiB, numbers of 64 characters and
+    /// 65536 nodes.
+    pub const fn new() -> Limits {
+        Limits {
+            max_input: 1 << 20,
+            max_depth: 64,
+            max_string: 64 << 10,
+            max_number: 64,
+            max_nodes: 1 << 16,
+            duplicate_keys: false,
+        }
+    }
+
+    /// Sets the maximum size of the input in bytes.
+    pub fn max_input(mut self, bytes: usize) -> Limits {
+        self.max_input = bytes;
+        self
+    }
+
+    /// Sets the maximum nesting depth of arrays and objects, at most
+    /// [`MAX_DEPTH`].
+    pub fn max_depth(mut self, depth: usize) -> Limits {
+        self.max_depth = depth.min(MAX_DEPTH);
+        self
+    }
+
+    /// Sets the maximum length of a string or key in bytes, as written in the
+    /// input.
+    pub fn max_string(mut self, bytes: usize) -> Limits {
+        self.max_string = bytes;
+        self
+    }
+
+    /// Sets the maximum length of a number in characters.
+    pub fn max_number(mut self, chars: usize) -> Limits {
+        self.max_number = chars;
+        self
+    }
+
+    /// Sets the maximum number of values in a [`Value`] tree.
+    pub fn max_nodes(mut self, nodes: usize) -> Limits {
+        self.max_nodes = nodes;
+        self
+    }
+
+    pub(crate) fn input_limit(&self) -> usize {
+        self.max_input
+    }
+
+    pub(crate) fn depth_limit(&self) -> usize {
+        self.max_depth
+    }
+
+    pub(crate) fn string_limit(&self) -> usize {
+        self.max_string
+    }
+
+    pub(crate) fn node_limit(&self) -> usize {
+        self.max_nodes
+    }
+
+    /// Accepts objects with duplicate keys in [`Value::parse`]; the last one
+    /// wins in [`Value::get`]. Rejected by default, as parsers disagree on
+    /// which one wins.
+    pub fn duplicate_keys(mut self, allow: bool) -> Limits {
+        self.duplicate_keys = allow;
+        self
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits::new()
+    }
+}
+
+///
+/// The kind of a JSON error.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The input ended inside a value.
+    UnexpectedEnd,
+    /// A character that is not allowed here.
+    UnexpectedCharacter,
+    /// A malformed escape sequence or an unpaired surrogate.
+    InvalidEscape,
+    /// A malformed number.
+    InvalidNumber,
+    /// An unescaped control character in a string.
+    ControlCharacter,
+    /// Characters after the top-level value.
+    TrailingCharacters,
+    /// The input is larger than [`Limits::max_input`].
+    InputTooLarge,
+    /// The nesting is deeper than [`Limits::max_depth`].
+    TooDeep,
+    /// A string is longer than [`Limits::max_string`].
+    StringTooLong,
+    /// A number is longer than [`Limits::max_number`].
+    NumberTooLong,
+    /// The document has more values than [`Limits::max_nodes`].
+    TooManyNodes,
+    /// An object has the same key twice.
+    DuplicateKey,
+    /// A [`Writer`] call that does not fit the document written so far, or a
+    /// number that is not finite.
+    InvalidWrite,
+}
+
+///
+/// A JSON error and the byte offset in the input at which it was found.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+    offset: usize,
+}
+
+impl Error {
+    fn new(kind: ErrorKind, offset: usize) -> Error {
+        Error { kind, offset }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The byte offset in the input; 0 for errors of a [`Writer`].
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} at byte {}", self.kind, self.offset)
+    }
+}
+
+///
+/// A string as written in the input, between the quotes. Its escapes are
+/// validated by the parser.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Str<'a> {
+    raw: &'a str,
+    escaped: bool,
+}
+
+impl<'a> Str<'a> {
+    /// The string as written, escapes included.
+    pub fn raw(&self) -> &'a str {
+        self.raw
+    }
+
+    /// Returns true if the string holds escape sequences.
+    pub fn is_escaped(&self) -> bool {
+        self.escaped
+    }
+
+    /// Decodes the escapes. Borrows the input if there are none.
+    pub fn decode(&self) -> Cow<'a, str> {
+        if !self.escaped {
+            return Cow::Borrowed(self.raw);
+        }
+        let mut out = String::with_capacity(self.raw.len());
+        let mut chars = self.raw.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('b') => out.push('\u{8}'),
+                Some('f') => out.push('\u{c}'),
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some('u') => {
+                    let mut code = hex4(&mut chars);
+                    if (0xd800..0xdc00).contains(&code) {
+                        // The parser checked that a low surrogate follows.
+                        chars.next();
+                        chars.next();
+                        let low = hex4(&mut chars);
+                        code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                    }
+                    out.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                Some(c) => out.push(c),
+                None => (),
+            }
+        }
+        Cow::Owned(out)
+    }
+
+    /// Compares the decoded string with `s`, without allocating if there are
+    /// no escapes.
+    pub fn eq_str(&self, s: &str) -> bool {
+        if self.escaped {
+            self.decode() == s
+        } else {
+            self.raw == s
+        }
+    }
+}
+
+fn hex4(chars: &mut std::str::Chars<'_>) -> u32 {
+    let mut code = 0;
+    for _ in 0..4 {
+        code = code * 16 + chars.next().and_then(|c| c.to_digit(16)).unwrap_or(0);
+    }
+    code
+}
+
+///
+/// A number as written in the input, validated against the JSON grammar.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Number<'a>(&'a str);
+
+impl<'a> Number<'a> {
+    pub fn as_str(&self) -> &'a str {
+        self.0
+    }
+
+    /// The number as an `i64`, if it is an integer in range.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.0.parse().ok()
+    }
+
+    /// The number as a `u64`, if it is a non-negative integer in range.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.0.parse().ok()
+    }
+
+    /// The number as the closest `f64`.
+    pub fn as_f64(&self) -> Option<f64> {
+        self.0.parse().ok()
+    }
+}
+
+///
+/// An event of the streaming parser.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event<'a> {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    /// The key of the next member of the current object.
+    Key(Str<'a>),
+    String(Str<'a>),
+    Number(Number<'a>),
+    Bool(bool),
+    Null,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Frame {
+    Object,
+    Array,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    Value,
+    ArrayFirst,
+    ArrayNext,
+    ObjectFirst,
+    ObjectKey,
+    ObjectNext,
+    Done,
+}
+
+///
+/// A streaming JSON parser over one document.
+///
+pub struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    stack: Vec<Frame>,
+    state: State,
+    limits: Limits,
+    error: Option<Error>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str, limits: &Limits) -> Parser<'a> {
+        let error = if input.len() > limits.max_input {
+            Some(Error::new(ErrorKind::InputTooLarge, limits.max_input))
+        } else {
+            None
+        };
+        Parser {
+            input,
+            pos: 0,
+            stack: Vec::new(),
+            state: State::Value,
+            limits: *limits,
+            error,
+        }
+    }
+
+    /// The byte offset of the parser in the input.
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
+    /// The current nesting depth.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Returns the next event, or `None` at the end of the document. After an
+    /// error, every call returns the same error.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<Event<'a>>, Error> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let result = self.step();
+        if let Err(error) = result {
+            self.error = Some(error);
+        }
+        result
+    }
+
+    fn error(&self, kind: ErrorKind) -> Error {
+        Error::new(kind, self.pos)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect_byte(&mut self) -> Result<u8, Error> {
+        self.skip_whitespace();
+        self.peek()
+            .ok_or_else(|| self.error(ErrorKind::UnexpectedEnd))
+    }
+
+    fn after_value(&mut self) {
+        self.state = match self.stack.last() {
+            None => State::Done,
+            Some(Frame::Object) => State::ObjectNext,
+            Some(Frame::Array) => State::ArrayNext,
+        };
+    }
+
+    fn push(&mut self, frame: Frame) -> Result<(), Error> {
+        if self.stack.len() >= self.limits.max_depth {
+            return Err(self.error(ErrorKind::TooDeep));
+        }
+        self.stack.push(frame);
+        Ok(())
+    }
+
+    fn pop(&mut self) {
+        self.pos += 1;
+        self.stack.pop();
+        self.after_value();
+    }
+
+    fn step(&mut self) -> Result<Option<Event<'a>>, Error> {
+        loop {
+            match self.state {
+                State::Done => {
+                    self.skip_whitespace();
+                    return if self.pos < self.input.len() {
+                        Err(self.error(ErrorKind::TrailingCharacters))
+                    } else {
+                        Ok(None)
+                    };
+                }
+                State::Value => return self.value().map(Some),
+                State::ArrayFirst => {
+                    if self.expect_byte()? == b']' {
+                        self.pop();
+                        return Ok(Some(Event::EndArray));
+                    }
+                    self.state = State::Value;
+                }
+                State::ArrayNext => match self.expect_byte()? {
+                    b',' => {
+                        self.pos += 1;
+                        self.state = State::Value;
+                    }
+                    b']' => {
+                        self.pop();
+                        return Ok(Some(Event::EndArray));
+                    }
+                    _ => return Err(self.error(ErrorKind::UnexpectedCharacter)),
+                },
+                State::ObjectFirst => {
+                    if self.expect_byte()? == b'}' {
+                        self.pop();
+                        return Ok(Some(Event::EndObject));
+                    }
+                    self.state = State::ObjectKey;
+                }
+                State::ObjectKey => {
+                    if self.expect_byte()? != b'"' {
+                        return Err(self.error(ErrorKind::UnexpectedCharacter));
+                    }
+                    let key = self.string()?;
+                    if self.expect_byte()? != b':' {
+                        return Err(self.error(ErrorKind::UnexpectedCharacter));
+                    }
+                    self.pos += 1;
+                    self.state = State::Value;
+                    return Ok(Some(Event::Key(key)));
+                }
+                State::ObjectNext => match self.expect_byte()? {
+                    b',' => {
+                        self.pos += 1;
+                        self.state = State::ObjectKey;
+                    }
+                    b'}' => {
+                        self.pop();
+                        return Ok(Some(Event::EndObject));
+                    }
+                    _ => return Err(self.error(ErrorKind::UnexpectedCharacter)),
+                },
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Event<'a>, Error> {
+        let event = match self.expect_byte()? {
+            b'{' => {
+                self.push(Frame::Object)?;
+                self.pos += 1;
+                self.state = State::ObjectFirst;
+                return Ok(Event::StartObject);
+            }
+            b'[' => {
+                self.push(Frame::Array)?;
+                self.pos += 1;
+                self.state = State::ArrayFirst;
+                return Ok(Event::StartArray);
+            }
+            b'"' => Event::String(self.string()?),
+            b't' => self.literal("true", Event::Bool(true))?,
+            b'f' => self.literal("false", Event::Bool(false))?,
+            b'n' => self.literal("null", Event::Null)?,
+            b'-' | b'0'..=b'9' => Event::Number(self.number()?),
+            _ => return Err(self.error(ErrorKind::UnexpectedCharacter)),
+        };
+        self.after_value();
+        Ok(event)
+    }
+
+    fn literal(&mut self, word: &str, event: Event<'a>) -> Result<Event<'a>, Error> {
+        let rest = &self.input.as_bytes()[self.pos..];
+        if rest.starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(event)
+        } else if word.as_bytes().starts_with(rest) {
+            Err(Error::new(ErrorKind::UnexpectedEnd, self.input.len()))
+        } else {
+            Err(self.error(ErrorKind::UnexpectedCharacter))
+        }
+    }
+
+    // Scans a string from its opening quote, leaving the position after the
+    // closing one.
+    fn string(&mut self) -> Result<Str<'a>, Error> {
+        let bytes = self.input.as_bytes();
+        let start = self.pos + 1;
+        let mut pos = start;
+        let mut escaped = false;
+        loop {
+            if pos - start > self.limits.max_string {
+                return Err(Error::new(ErrorKind::StringTooLong, start));
+            }
+            match bytes.get(pos) {
+                None => return Err(Error::new(ErrorKind::UnexpectedEnd, pos)),
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    escaped = true;
+                    pos = self.escape(pos)?;
+                }
+                Some(&b) if b < 0x20 => {
+                    return Err(Error::new(ErrorKind::ControlCharacter, pos));
+                }
+                Some(_) => pos += 1,
+            }
+        }
+        if pos - start > self.limits.max_string {
+            return Err(Error::new(ErrorKind::StringTooLong, start));
+        }
+        self.pos = pos + 1;
+        Ok(Str {
+            raw: &self.input[start..pos],
+            escaped,
+        })
+    }
+
+    // Validates the escape sequence at `pos` and returns the position after it.
+    fn escape(&self, pos: usize) -> Result<usize, Error> {
+        let bytes = self.input.as_bytes();
+        match bytes.get(pos + 1) {
+            None => Err(Error::new(ErrorKind::UnexpectedEnd, pos + 1)),
+            Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => Ok(pos + 2),
+            Some(b'u') => {
+                let code = self.unicode(pos)?;
+                match code {
+                    0xd800..=0xdbff => {
+                        if bytes.get(pos + 6) != Some(&b'\\') || bytes.get(pos + 7) != Some(&b'u') {
+                            return Err(Error::new(ErrorKind::InvalidEscape, pos));
+                        }
+                        match self.unicode(pos + 6)? {
+                            0xdc00..=0xdfff => Ok(pos + 12),
+                            _ => Err(Error::new(ErrorKind::InvalidEscape, pos + 6)),
+                        }
+                    }
+                    0xdc00..=0xdfff => Err(Error::new(ErrorKind::InvalidEscape, pos)),
+                    _ => Ok(pos + 6),
+                }
+            }
+            Some(_) => Err(Error::new(ErrorKind::InvalidEscape, pos)),
+        }
+    }
+
+    // Reads the four hex digits of the `\u` escape at `pos`.
+    fn unicode(&self, pos: usize) -> Result<u32, Error> {
+        let digits = self
+            .input
+            .as_bytes()
+            .get(pos + 2..pos + 6)
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEnd, self.input.len()))?;
+        let mut code = 0;
+        for &d in digits {
+            let digit = (d as char)
+                .to_digit(16)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidEscape, pos))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Number<'a>, Error> {
+        let bytes = self.input.as_bytes();
+        let start = self.pos;
+        let mut pos = start;
+        let digits = |pos: &mut usize| {
+            let first = *pos;
+            while let Some(b'0'..=b'9') = bytes.get(*pos) {
+                *pos += 1;
+            }
+            *pos > first
+        };
+
+        if bytes.get(pos) == Some(&b'-') {
+            pos += 1;
+        }
+        match bytes.get(pos) {
+            Some(b'0') => pos += 1,
+            Some(b'1'..=b'9') => {
+                digits(&mut pos);
+            }
+            _ => return Err(Error::new(ErrorKind::InvalidNumber, start)),
+        }
+        if bytes.get(pos) == Some(&b'.') {
+            pos += 1;
+            if !digits(&mut pos) {
+                return Err(Error::new(ErrorKind::InvalidNumber, start));
+            }
+        }
+        if let Some(b'e' | b'E') = bytes.get(pos) {
+            pos += 1;
+            if let Some(b'+' | b'-') = bytes.get(pos) {
+                pos += 1;
+            }
+            if !digits(&mut pos) {
+                return Err(Error::new(ErrorKind::InvalidNumber, start));
+            }
+        }
+        if pos - start > self.limits.max_number {
+            return Err(Error::new(ErrorKind::NumberTooLong, start));
+        }
+        self.pos = pos;
+        Ok(Number(&self.input[start..pos]))
+    }
+}
+
+///
+/// A parsed JSON document, borrowing its strings and numbers from the input.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value<'a> {
+    Null,
+    Bool(bool),
+    Number(Number<'a>),
+    String(Str<'a>),
+    Array(Vec<Value<'a>>),
+    /// The members in input order.
+    Object(Vec<(Str<'a>, Value<'a>)>),
+}
+
+enum Partial<'a> {
+    Array(Vec<Value<'a>>),
+    Object(
+        Vec<(Str<'a>, Value<'a>)>,
+        Option<Str<'a>>,
+        BTreeSet<Cow<'a, str>>,
+    ),
+}
+
+impl<'a> Value<'a> {
+    /// Parses a whole document within `limits`.
+    pub fn parse(input: &'a str, limits: &Limits) -> Result<Value<'a>, Error> {
+        let mut parser = Parser::new(input, limits);
+        let mut stack: Vec<Partial<'a>> = Vec::new();
+        let mut nodes = 0_usize;
+        let mut root = None;
+
+        while let Some(event) = parser.next()? {
+            let value = match event {
+                Event::StartObject => {
+                    stack.push(Partial::Object(Vec::new(), None, BTreeSet::new()));
+                    continue;
+                }
+                Event::StartArray => {
+                    stack.push(Partial::Array(Vec::new()));
+                    continue;
+                }
+                Event::Key(key) => {
+                    if let Some(Partial::Object(_, pending, keys)) = stack.last_mut() {
+                        if !limits.duplicate_keys && !keys.insert(key.decode()) {
+                            return Err(Error::new(ErrorKind::DuplicateKey, parser.offset()));
+                        }
+                        *pending = Some(key);
+                    }
+                    continue;
+                }
+                Event::EndObject => match stack.pop() {
+                    Some(Partial::Object(members, _, _)) => Value::Object(members),
+                    _ => unreachable!(),
+                },
+                Event::EndArray => match stack.pop() {
+                    Some(Partial::Array(items)) => Value::Array(items),
+                    _ => unreachable!(),
+                },
+                Event::String(s) => Value::String(s),
+                Event::Number(n) => Value::Number(n),
+                Event::Bool(b) => Value::Bool(b),
+                Event::Null => Value::Null,
+            };
+            nodes += 1;
+            if nodes > limits.max_nodes {
+                return Err(Error::new(ErrorKind::TooManyNodes, parser.offset()));
+            }
+            match stack.last_mut() {
+                None => root = Some(value),
+                Some(Partial::Array(items)) => items.push(value),
+                Some(Partial::Object(members, pending, _)) => {
+                    if let Some(key) = pending.take() {
+                        members.push((key, value));
+                    }
+                }
+            }
+        }
+        root.ok_or_else(|| Error::new(ErrorKind::UnexpectedEnd, input.len()))
+    }
+
+    /// The value of the member `key` of an object. If duplicate keys were
+    /// allowed, the last one wins.
+    pub fn get(&self, key: &str) -> Option<&Value<'a>> {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .rev()
+                .find(|(k, _)| k.eq_str(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// The element `index` of an array.
+    pub fn index(&self, index: usize) -> Option<&Value<'a>> {
+        match self {
+            Value::Array(items) => items.get(index),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<Number<'a>> {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_number().and_then(|n| n.as_i64())
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_number().and_then(|n| n.as_u64())
+    }
+
+    /// The decoded string.
+    pub fn as_str(&self) -> Option<Cow<'a, str>> {
+        match self {
+            Value::String(s) => Some(s.decode()),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value<'a>]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(Str<'a>, Value<'a>)]> {
+        match self {
+            Value::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+
+    /// Serializes the value into `writer`.
+    pub fn write(&self, writer: &mut Writer) -> Result<(), Error> {
+        enum Iter<'b, 'a> {
+            Array(slice::Iter<'b, Value<'a>>),
+            Object(slice::Iter<'b, (Str<'a>, Value<'a>)>),
+        }
+
+        let mut stack: Vec<Iter<'_, 'a>> = Vec::new();
+        let mut next = Some(self);
+        loop {
+            if let Some(value) = next.take() {
+                match value {
+                    Value::Null => writer.null()?,
+                    Value::Bool(b) => writer.bool(*b)?,
+                    Value::Number(n) => writer.raw_number(n)?,
+                    Value::String(s) => writer.raw_string(s)?,
+                    Value::Array(items) => {
+                        writer.begin_array()?;
+                        stack.push(Iter::Array(items.iter()));
+                    }
+                    Value::Object(members) => {
+                        writer.begin_object()?;
+                        stack.push(Iter::Object(members.iter()));
+                    }
+                }
+            }
+            match stack.last_mut() {
+                None => return Ok(()),
+                Some(Iter::Array(items)) => match items.next() {
+                    Some(item) => next = Some(item),
+                    None => {
+                        stack.pop();
+                        writer.end_array()?;
+                    }
+                },
+                Some(Iter::Object(members)) => match members.next() {
+                    Some((key, value)) => {
+                        writer.raw_key(key)?;
+                        next = Some(value);
+                    }
+                    None => {
+                        stack.pop();
+                        writer.end_object()?;
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut writer = Writer::new();
+        self.write(&mut writer).map_err(|_| fmt::Error)?;
+        f.write_str(&writer.finish().map_err(|_| fmt::Error)?)
+    }
+}
+
+///
+/// A streaming JSON serializer that checks that the calls form one
+/// well-formed document.
+///
+#[derive(Debug, Default)]
+pub struct Writer {
+    out: String,
+    // The open containers, and whether a member was written into each.
+    stack: Vec<(Frame, bool)>,
+    // Whether a key was written and awaits its value.
+    keyed: bool,
+    done: bool,
+}
+
+impl Writer {
+    pub fn new() -> Writer {
+        Writer::default()
+    }
+
+    fn invalid() -> Error {
+        Error::new(ErrorKind::InvalidWrite, 0)
+    }
+
+    // Writes the separator before a value, checking that one may come here.
+    fn before_value(&mut self) -> Result<(), Error> {
+        match self.stack.last_mut() {
+            None if self.done => return Err(Writer::invalid()),
+            None => self.done = true,
+            Some((Frame::Object, _)) => {
+                if !self.keyed {
+                    return Err(Writer::invalid());
+                }
+                self.keyed = false;
+            }
+            Some((Frame::Array, written)) => {
+                if *written {
+                    self.out.push(',');
+                }
+                *written = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn before_key(&mut self) -> Result<(), Error> {
+        match self.stack.last_mut() {
+            Some((Frame::Object, written)) if !self.keyed => {
+                if *written {
+                    self.out.push(',');
+                }
+                *written = true;
+                self.keyed = true;
+                Ok(())
+            }
+            _ => Err(Writer::invalid()),
+        }
+    }
+
+    fn end(&mut self, frame: Frame, close: char) -> Result<(), Error> {
+        match self.stack.last() {
+            Some((f, _)) if *f == frame && !self.keyed => {
+                self.stack.pop();
+                self.out.push(close);
+                Ok(())
+            }
+            _ => Err(Writer::invalid()),
+        }
+    }
+
+    pub fn begin_object(&mut self) -> Result<(), Error> {
+        self.before_value()?;
+        self.out.push('{');
+        self.stack.push((Frame::Object, false));
+        Ok(())
+    }
+
+    pub fn end_object(&mut self) -> Result<(), Error> {
+        self.end(Frame::Object, '}')
+    }
+
+    pub fn begin_array(&mut self) -> Result<(), Error> {
+        self.before_value()?;
+        self.out.push('[');
+        self.stack.push((Frame::Array, false));
+        Ok(())
+    }
+
+    pub fn end_array(&mut self) -> Result<(), Error> {
+        self.end(Frame::Array, ']')
+    }
+
+    /// Writes the key of the next member of the current object.
+    pub fn key(&mut self, key: &str) -> Result<(), Error> {
+        self.before_key()?;
+        self.escape(key);
+        self.out.push(':');
+        Ok(())
+    }
+
+    fn raw_key(&mut self, key: &Str<'_>) -> Result<(), Error> {
+        self.before_key()?;
+        self.out.push('"');
+        self.out.push_str(key.raw);
+        self.out.push_str("\":");
+        Ok(())
+    }
+
+    pub fn string(&mut self, s: &str) -> Result<(), Error> {
+        self.before_value()?;
+        self.escape(s);
+        Ok(())
+    }
+
+    fn raw_string(&mut self, s: &Str<'_>) -> Result<(), Error> {
+        self.before_value()?;
+        self.out.push('"');
+        self.out.push_str(s.raw);
+        self.out.push('"');
+        Ok(())
+    }
+
+    fn raw_number(&mut self, n: &Number<'_>) -> Result<(), Error> {
+        self.before_value()?;
+        self.out.push_str(n.0);
+        Ok(())
+    }
+
+    pub fn i64(&mut self, n: i64) -> Result<(), Error> {
+        self.before_value()?;
+        self.out.push_str(&n.to_string());
+        Ok(())
+    }
+
+    pub fn u64(&mut self, n: u64) -> Result<(), Error> {
+        self.before_value()?;
+        self.out.push_str(&n.to_string());
+        Ok(())
+    }
+
+    /// Writes a finite float; NaN and the infinities have no JSON form.
+    pub fn f64(&mut self, n: f64) -> Result<(), Error> {
+        if !n.is_finite() {
+            return Err(Writer::invalid());
+        }
+        self.before_value()?;
+        self.out.push_str(&format!("{:?}", n));
+        Ok(())
+    }
+
+    pub fn bool(&mut self, b: bool) -> Result<(), Error> {
+        self.before_value()?;
+        self.out.push_str(if b { "true" } else { "false" });
+        Ok(())
+    }
+
+    pub fn null(&mut self) -> Result<(), Error> {
+        self.before_value()?;
+        self.out.push_str("null");
+        Ok(())
+    }
+
+    fn escape(&mut self, s: &str) {
+        self.out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                c if (c as u32) < 0x20 => {
+                    self.out.push_str(&format!("\\u{:04x}", c as u32));
+                }
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+    }
+
+    /// Returns the document, if it is complete.
+    pub fn finish(self) -> Result<String, Error> {
+        if self.done && self.stack.is_empty() {
+            Ok(self.out)
+        } else {
+            Err(Writer::invalid())
+        }
+    }
+}
@@ -29,7 +29,13 @@ extern crate sgx_tstd as std;
 
 mod serialize;
 pub use self::serialize::{Decoder, Encoder, DeSerializable, Serializable, SerializeHelper, DeSerializeHelper};
+pub use self::serialize::Codec;
 
 mod opaque;
 mod leb128;
 
+pub mod canonical;
+pub mod json;
+pub mod msgpack;
+pub mod ocall;
+
@@ -0,0 +1,775 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//!
+//! MessagePack encoding of `Serializable` types.
+//!
+//! The encoding is compact, so it suits ECALL and OCALL payloads: integers
+//! take the smallest MessagePack form that holds them, structs, tuples and
+//! enum variants are positional arrays (an enum variant is its index
+//! followed by its fields), `None` is nil and `Some(v)` a one-element array,
+//! so that nested options stay unambiguous. 128-bit integers that do not fit
+//! 64 bits are 16-byte big-endian binaries.
+//!
+//! [`Decoder`] reads untrusted input under the [`Limits`] of the JSON module:
+//! the input size, the nesting depth, the length of strings and the number
+//! of values are capped, and no length prefix is trusted beyond the bytes
+//! that remain, so a hostile payload cannot make the decoder recurse or
+//! allocate without bound.
+//!
+//! ```
+//! use sgx_serialize::json::Limits;
+//! use sgx_serialize::msgpack;
+//!
+//! let data = msgpack::to_vec(&(7_u32, String::from("seven"))).unwrap();
+//! let (n, s): (u32, String) = msgpack::from_slice(&data, &Limits::new()).unwrap();
+//! ```
+//!
+
+use crate::json::Limits;
+use crate::serialize::{self, DeSerializable, Serializable};
+use std::borrow::Cow;
+use std::fmt;
+use std::str;
+use std::vec::Vec;
+
+///
+/// The kind of a MessagePack error.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The input ended inside a value.
+    UnexpectedEnd,
+    /// A value of another type than the one expected.
+    TypeMismatch,
+    /// An integer that does not fit the expected type, or a container of
+    /// another length than the one expected.
+    OutOfRange,
+    /// A string that is not UTF-8.
+    InvalidUtf8,
+    /// Bytes after the value.
+    TrailingBytes,
+    /// The input is larger than `Limits::max_input`.
+    InputTooLarge,
+    /// The nesting is deeper than `Limits::max_depth`.
+    TooDeep,
+    /// A string is longer than `Limits::max_string`.
+    StringTooLong,
+    /// The input has more values than `Limits::max_nodes`.
+    TooManyNodes,
+    /// A length that does not fit MessagePack.
+    TooLong,
+    /// Reported by the decoding code of a type.
+    Invalid,
+}
+
+///
+/// A MessagePack error and the byte offset at which it was found.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+    offset: usize,
+}
+
+impl Error {
+    fn new(kind: ErrorKind, offset: usize) -> Error {
+        Error { kind, offset }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The byte offset in the input; the output length for encoding errors.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} at byte {}", self.kind, self.offset)
+    }
+}
+
+///
+/// Encodes a value into MessagePack.
+///
+pub fn to_vec<T: Serializable + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut encoder = Encoder::new();
+    value.encode(&mut encoder)?;
+    Ok(encoder.into_inner())
+}
+
+///
+/// Decodes a value from MessagePack within `limits`, requiring that it spans
+/// all of `data`.
+///
+pub fn from_slice<T: DeSerializable>(data: &[u8], limits: &Limits) -> Result<T, Error> {
+    let mut decoder = Decoder::new(data, limits)?;
+    let value = T::decode(&mut decoder)?;
+    decoder.finish()?;
+    Ok(value)
+}
+
+///
+/// A MessagePack encoder writing into a buffer.
+///
+#[derive(Debug, Default)]
+pub struct Encoder {
+    out: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Encoder {
+        Encoder::default()
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.out
+    }
+
+    fn too_long(&self) -> Error {
+        Error::new(ErrorKind::TooLong, self.out.len())
+    }
+
+    fn uint(&mut self, v: u64) {
+        if v < 0x80 {
+            self.out.push(v as u8);
+        } else if v <= u8::MAX as u64 {
+            self.out.push(0xcc);
+            self.out.push(v as u8);
+        } else if v <= u16::MAX as u64 {
+            self.out.push(0xcd);
+            self.out.extend_from_slice(&(v as u16).to_be_bytes());
+        } else if v <= u32::MAX as u64 {
+            self.out.push(0xce);
+            self.out.extend_from_slice(&(v as u32).to_be_bytes());
+        } else {
+            self.out.push(0xcf);
+            self.out.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+
+    fn int(&mut self, v: i64) {
+        if v >= 0 {
+            self.uint(v as u64);
+        } else if v >= -32 {
+            self.out.push(v as u8);
+        } else if v >= i8::MIN as i64 {
+            self.out.push(0xd0);
+            self.out.push(v as u8);
+        } else if v >= i16::MIN as i64 {
+            self.out.push(0xd1);
+            self.out.extend_from_slice(&(v as i16).to_be_bytes());
+        } else if v >= i32::MIN as i64 {
+            self.out.push(0xd2);
+            self.out.extend_from_slice(&(v as i32).to_be_bytes());
+        } else {
+            self.out.push(0xd3);
+            self.out.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+
+    // Writes the header of a string, binary, array or map: the fix form
+    // for lengths below `fix_max`, then the 8, 16 and 32-bit forms. Arrays
+    // and maps have no 8-bit form.
+    fn header(
+        &mut self,
+        len: usize,
+        fix: Option<(u8, usize)>,
+        forms: [u8; 3],
+    ) -> Result<(), Error> {
+        match fix {
+            Some((base, max)) if len < max => self.out.push(base | len as u8),
+            _ if forms[0] != 0 && len <= u8::MAX as usize => {
+                self.out.push(forms[0]);
+                self.out.push(len as u8);
+            }
+            _ if len <= u16::MAX as usize => {
+                self.out.push(forms[1]);
+                self.out.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            _ if len <= u32::MAX as usize => {
+                self.out.push(forms[2]);
+                self.out.extend_from_slice(&(len as u32).to_be_bytes());
+            }
+            _ => return Err(self.too_long()),
+        }
+        Ok(())
+    }
+
+    fn array(&mut self, len: usize) -> Result<(), Error> {
+        self.header(len, Some((0x90, 16)), [0, 0xdc, 0xdd])
+    }
+
+    fn bin(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.header(bytes.len(), None, [0xc4, 0xc5, 0xc6])?;
+        self.out.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+impl serialize::Encoder for Encoder {
+    type Error = Error;
+
+    fn emit_nil(&mut self) -> Result<(), Error> {
+        self.out.push(0xc0);
+        Ok(())
+    }
+
+    fn emit_usize(&mut self, v: usize) -> Result<(), Error> {
+        self.uint(v as u64);
+        Ok(())
+    }
+
+    fn emit_u128(&mut self, v: u128) -> Result<(), Error> {
+        match u64::try_from(v) {
+            Ok(v) => {
+                self.uint(v);
+                Ok(())
+            }
+            Err(_) => self.bin(&v.to_be_bytes()),
+        }
+    }
+
+    fn emit_u64(&mut self, v: u64) -> Result<(), Error> {
+        self.uint(v);
+        Ok(())
+    }
+
+    fn emit_u32(&mut self, v: u32) -> Result<(), Error> {
+        self.uint(v as u64);
+        Ok(())
+    }
+
+    fn emit_u16(&mut self, v: u16) -> Result<(), Error> {
+        self.uint(v as u64);
+        Ok(())
+    }
+
+    fn emit_u8(&mut self, v: u8) -> Result<(), Error> {
+        self.uint(v as u64);
+        Ok(())
+    }
+
+    fn emit_isize(&mut self, v: isize) -> Result<(), Error> {
+        self.int(v as i64);
+        Ok(())
+    }
+
+    fn emit_i64(&mut self, v: i64) -> Result<(), Error> {
+        self.int(v);
+        Ok(())
+    }
+
+    fn emit_i32(&mut self, v: i32) -> Result<(), Error> {
+        self.int(v as i64);
+        Ok(())
+    }
+
+    fn emit_i16(&mut self, v: i16) -> Result<(), Error> {
+        self.int(v as i64);
+        Ok(())
+    }
+
+    fn emit_i8(&mut self, v: i8) -> Result<(), Error> {
+        self.int(v as i64);
+        Ok(())
+    }
+
+    fn emit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.out.push(if v { 0xc3 } else { 0xc2 });
+        Ok(())
+    }
+
+    fn emit_f64(&mut self, v: f64) -> Result<(), Error> {
+        self.out.push(0xcb);
+        self.out.extend_from_slice(&v.to_bits().to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_f32(&mut self, v: f32) -> Result<(), Error> {
+        self.out.push(0xca);
+        self.out.extend_from_slice(&v.to_bits().to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_char(&mut self, v: char) -> Result<(), Error> {
+        self.emit_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn emit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.header(v.len(), Some((0xa0, 32)), [0xd9, 0xda, 0xdb])?;
+        self.out.extend_from_slice(v.as_bytes());
+        Ok(())
+    }
+
+    fn emit_i128(&mut self, v: i128) -> Result<(), Error> {
+        match i64::try_from(v) {
+            Ok(v) => {
+                self.int(v);
+                Ok(())
+            }
+            Err(_) => self.bin(&v.to_be_bytes()),
+        }
+    }
+
+    fn emit_enum_variant<F>(
+        &mut self,
+        _v_name: &str,
+        v_id: usize,
+        len: usize,
+        f: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.array(len + 1)?;
+        self.uint(v_id as u64);
+        f(self)
+    }
+
+    fn emit_struct<F>(&mut self, _name: &str, len: usize, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.array(len)?;
+        f(self)
+    }
+
+    fn emit_tuple<F>(&mut self, len: usize, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.array(len)?;
+        f(self)
+    }
+
+    fn emit_option<F>(&mut self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        f(self)
+    }
+
+    fn emit_option_none(&mut self) -> Result<(), Error> {
+        self.emit_nil()
+    }
+
+    fn emit_option_some<F>(&mut self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.array(1)?;
+        f(self)
+    }
+
+    fn emit_seq<F>(&mut self, len: usize, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.array(len)?;
+        f(self)
+    }
+
+    fn emit_map<F>(&mut self, len: usize, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        self.header(len, Some((0x80, 16)), [0, 0xde, 0xdf])?;
+        f(self)
+    }
+}
+
+// The value classes of MessagePack headers.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Header {
+    Nil,
+    Bool(bool),
+    Uint(u64),
+    Int(i64),
+    F32,
+    F64,
+    Str(usize),
+    Bin(usize),
+    Array(usize),
+    Map(usize),
+}
+
+///
+/// A MessagePack decoder over untrusted input.
+///
+pub struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    limits: Limits,
+    depth: usize,
+    nodes: usize,
+}
+
+impl<'a> Decoder<'a> {
+    ///
+    /// Creates a decoder over `data`, failing if it is larger than
+    /// `Limits::max_input`.
+    ///
+    pub fn new(data: &'a [u8], limits: &Limits) -> Result<Decoder<'a>, Error> {
+        if data.len() > limits.input_limit() {
+            return Err(Error::new(ErrorKind::InputTooLarge, limits.input_limit()));
+        }
+        Ok(Decoder {
+            data,
+            pos: 0,
+            limits: *limits,
+            depth: 0,
+            nodes: 0,
+        })
+    }
+
+    /// The byte offset of the decoder in the input.
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
+    /// Checks that the whole input was consumed.
+    pub fn finish(&self) -> Result<(), Error> {
+        if self.pos == self.data.len() {
+            Ok(())
+        } else {
+            Err(self.err(ErrorKind::TrailingBytes))
+        }
+    }
+
+    fn err(&self, kind: ErrorKind) -> Error {
+        Error::new(kind, self.pos)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.data.len() - self.pos < len {
+            return Err(Error::new(ErrorKind::UnexpectedEnd, self.data.len()));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn be(&mut self, len: usize) -> Result<u64, Error> {
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0, |acc, &b| acc << 8 | b as u64))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    // Reads the header of the next value, counting it against the node limit.
+    fn header(&mut self) -> Result<Header, Error> {
+        let start = self.pos;
+        self.nodes += 1;
+        if self.nodes > self.limits.node_limit() {
+            return Err(self.err(ErrorKind::TooManyNodes));
+        }
+        let b = self.take(1)?[0];
+        let header = match b {
+            0x00..=0x7f => Header::Uint(b as u64),
+            0x80..=0x8f => Header::Map((b & 0x0f) as usize),
+            0x90..=0x9f => Header::Array((b & 0x0f) as usize),
+            0xa0..=0xbf => Header::Str((b & 0x1f) as usize),
+            0xc0 => Header::Nil,
+            0xc2 => Header::Bool(false),
+            0xc3 => Header::Bool(true),
+            0xc4 => Header::Bin(self.be(1)? as usize),
+            0xc5 => Header::Bin(self.be(2)? as usize),
+            0xc6 => Header::Bin(self.be(4)? as usize),
+            0xca => Header::F32,
+            0xcb => Header::F64,
+            0xcc => Header::Uint(self.be(1)?),
+            0xcd => Header::Uint(self.be(2)?),
+            0xce => Header::Uint(self.be(4)?),
+            0xcf => Header::Uint(self.be(8)?),
+            0xd0 => Header::Int(self.be(1)? as u8 as i8 as i64),
+            0xd1 => Header::Int(self.be(2)? as u16 as i16 as i64),
+            0xd2 => Header::Int(self.be(4)? as u32 as i32 as i64),
+            0xd3 => Header::Int(self.be(8)? as i64),
+            0xd9 => Header::Str(self.be(1)? as usize),
+            0xda => Header::Str(self.be(2)? as usize),
+            0xdb => Header::Str(self.be(4)? as usize),
+            0xdc => Header::Array(self.be(2)? as usize),
+            0xdd => Header::Array(self.be(4)? as usize),
+            0xde => Header::Map(self.be(2)? as usize),
+            0xdf => Header::Map(self.be(4)? as usize),
+            0xe0..=0xff => Header::Int(b as i8 as i64),
+            // Extensions and the reserved 0xc1.
+            _ => return Err(Error::new(ErrorKind::TypeMismatch, start)),
+        };
+        // Every element takes at least one byte, so no length may exceed
+        // what remains of the input.
+        let remaining = self.data.len() - self.pos;
+        let fits = match header {
+            Header::Str(len) => {
+                if len > self.limits.string_limit() {
+                    return Err(Error::new(ErrorKind::StringTooLong, start));
+                }
+                len <= remaining
+            }
+            Header::Bin(len) | Header::Array(len) => len <= remaining,
+            Header::Map(len) => len.saturating_mul(2) <= remaining,
+            _ => true,
+        };
+        if !fits {
+            return Err(Error::new(ErrorKind::UnexpectedEnd, self.data.len()));
+        }
+        Ok(header)
+    }
+
+    fn integer(&mut self) -> Result<i128, Error> {
+        let start = self.pos;
+        match self.header()? {
+            Header::Uint(v) => Ok(v as i128),
+            Header::Int(v) => Ok(v as i128),
+            _ => Err(Error::new(ErrorKind::TypeMismatch, start)),
+        }
+    }
+
+    fn integer_as<T: TryFrom<i128>>(&mut self) -> Result<T, Error> {
+        let start = self.pos;
+        let v = self.integer()?;
+        T::try_from(v).map_err(|_| Error::new(ErrorKind::OutOfRange, start))
+    }
+
+    fn wide<T>(
+        &mut self,
+        narrow: fn(i128) -> Option<T>,
+        bytes: fn([u8; 16]) -> T,
+    ) -> Result<T, Error> {
+        let start = self.pos;
+        match self.header()? {
+            Header::Uint(v) => narrow(v as i128),
+            Header::Int(v) => narrow(v as i128),
+            Header::Bin(16) => {
+                let mut buf = [0_u8; 16];
+                buf.copy_from_slice(self.take(16)?);
+                Some(bytes(buf))
+            }
+            _ => return Err(Error::new(ErrorKind::TypeMismatch, start)),
+        }
+        .ok_or_else(|| Error::new(ErrorKind::OutOfRange, start))
+    }
+
+    // Reads an array header of exactly `len` elements.
+    fn array(&mut self, len: usize) -> Result<(), Error> {
+        let start = self.pos;
+        match self.header()? {
+            Header::Array(n) if n == len => Ok(()),
+            Header::Array(_) => Err(Error::new(ErrorKind::OutOfRange, start)),
+            _ => Err(Error::new(ErrorKind::TypeMismatch, start)),
+        }
+    }
+
+    // Runs `f` one nesting level deeper.
+    fn nested<T, F>(&mut self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<T, Error>,
+    {
+        if self.depth >= self.limits.depth_limit() {
+            return Err(self.err(ErrorKind::TooDeep));
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+}
+
+impl<'a> serialize::Decoder for Decoder<'a> {
+    type Error = Error;
+
+    fn read_nil(&mut self) -> Result<(), Error> {
+        let start = self.pos;
+        match self.header()? {
+            Header::Nil => Ok(()),
+            _ => Err(Error::new(ErrorKind::TypeMismatch, start)),
+        }
+    }
+
+    fn read_usize(&mut self) -> Result<usize, Error> {
+        self.integer_as()
+    }
+
+    fn read_u128(&mut self) -> Result<u128, Error> {
+        self.wide(|v| u128::try_from(v).ok(), u128::from_be_bytes)
+    }
+
+    fn read_u64(&mut self) -> Result<u64, Error> {
+        self.integer_as()
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        self.integer_as()
+    }
+
+    fn read_u16(&mut self) -> Result<u16, Error> {
+        self.integer_as()
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        self.integer_as()
+    }
+
+    fn read_isize(&mut self) -> Result<isize, Error> {
+        self.integer_as()
+    }
+
+    fn read_i128(&mut self) -> Result<i128, Error> {
+        self.wide(Some, i128::from_be_bytes)
+    }
+
+    fn read_i64(&mut self) -> Result<i64, Error> {
+        self.integer_as()
+    }
+
+    fn read_i32(&mut self) -> Result<i32, Error> {
+        self.integer_as()
+    }
+
+    fn read_i16(&mut self) -> Result<i16, Error> {
+        self.integer_as()
+    }
+
+    fn read_i8(&mut self) -> Result<i8, Error> {
+        self.integer_as()
+    }
+
+    fn read_bool(&mut self) -> Result<bool, Error> {
+        let start = self.pos;
+        match self.header()? {
+            Header::Bool(b) => Ok(b),
+            _ => Err(Error::new(ErrorKind::TypeMismatch, start)),
+        }
+    }
+
+    fn read_f64(&mut self) -> Result<f64, Error> {
+        let start = self.pos;
+        match self.header()? {
+            Header::F64 => Ok(f64::from_bits(self.be(8)?)),
+            Header::F32 => Ok(f32::from_bits(self.be(4)? as u32) as f64),
+            _ => Err(Error::new(ErrorKind::TypeMismatch, start)),
+        }
+    }
+
+    fn read_f32(&mut self) -> Result<f32, Error> {
+        let start = self.pos;
+        match self.header()? {
+            Header::F32 => Ok(f32::from_bits(self.be(4)? as u32)),
+            _ => Err(Error::new(ErrorKind::TypeMismatch, start)),
+        }
+    }
+
+    fn read_char(&mut self) -> Result<char, Error> {
+        let start = self.pos;
+        let s = self.read_str()?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(Error::new(ErrorKind::OutOfRange, start)),
+        }
+    }
+
+    fn read_str(&mut self) -> Result<Cow<str>, Error> {
+        let start = self.pos;
+        match self.header()? {
+            Header::Str(len) => {
+                let bytes = self.take(len)?;
+                str::from_utf8(bytes)
+                    .map(Cow::Borrowed)
+                    .map_err(|_| Error::new(ErrorKind::InvalidUtf8, start))
+            }
+            _ => Err(Error::new(ErrorKind::TypeMismatch, start)),
+        }
+    }
+
+    fn read_enum_variant<T, F>(&mut self, _names: &[&str], mut f: F) -> Result<T, Error>
+    where
+        F: FnMut(&mut Self, usize) -> Result<T, Error>,
+    {
+        let start = self.pos;
+        match self.header()? {
+            Header::Array(n) if n >= 1 => {
+                let id = self.read_usize()?;
+                self.nested(|d| f(d, id))
+            }
+            _ => Err(Error::new(ErrorKind::TypeMismatch, start)),
+        }
+    }
+
+    fn read_struct<T, F>(&mut self, _s_name: &str, len: usize, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<T, Error>,
+    {
+        self.array(len)?;
+        self.nested(f)
+    }
+
+    fn read_tuple<T, F>(&mut self, len: usize, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<T, Error>,
+    {
+        self.array(len)?;
+        self.nested(f)
+    }
+
+    fn read_option<T, F>(&mut self, mut f: F) -> Result<T, Error>
+    where
+        F: FnMut(&mut Self, bool) -> Result<T, Error>,
+    {
+        if self.peek() == Some(0xc0) {
+            self.read_nil()?;
+            return f(self, false);
+        }
+        self.array(1)?;
+        self.nested(|d| f(d, true))
+    }
+
+    fn read_seq<T, F>(&mut self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Self, usize) -> Result<T, Error>,
+    {
+        let start = self.pos;
+        match self.header()? {
+            Header::Array(len) => self.nested(|d| f(d, len)),
+            _ => Err(Error::new(ErrorKind::TypeMismatch, start)),
+        }
+    }
+
+    fn read_map<T, F>(&mut self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Self, usize) -> Result<T, Error>,
+    {
+        let start = self.pos;
+        match self.header()? {
+            Header::Map(len) => self.nested(|d| f(d, len)),
+            _ => Err(Error::new(ErrorKind::TypeMismatch, start)),
+        }
+    }
+
+    fn error(&mut self, _err: &str) -> Error {
+        self.err(ErrorKind::Invalid)
+    }
+}
@@ -0,0 +1,70 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//!
+//! Serialization of OCALL payloads.
+//!
+//! Everything marshalled to the host goes through [`to_vec`], which only
+//! accepts values tagged [`Public`]. A `Confidential` value has no
+//! `Serializable` implementation, and neither has a type that contains one,
+//! so it has to be released with `Confidential::declassify`, which the audit
+//! log records, before it can be sent. Payloads received from the host are
+//! untrusted and public by nature; [`from_slice`] returns them tagged as
+//! such.
+//!
+//! Both ends use `Codec::Opaque`, the codec of `SerializeHelper::new`.
+//!
+//! ```
+//! use sgx_serialize::ocall;
+//! use std::classify::Public;
+//!
+//! let payload = ocall::to_vec(&Public::new((7_u32, String::from("seven")))).unwrap();
+//! let echoed: Public<(u32, String)> = ocall::from_slice(&payload).unwrap();
+//! ```
+//!
+
+use crate::serialize::{
+    DeSerializable, DeSerializeHelper, Decoder, Encoder, Serializable, SerializeHelper,
+};
+use std::classify::Public;
+use std::vec::Vec;
+
+impl<T: Serializable> Serializable for Public<T> {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        self.as_ref().encode(s)
+    }
+}
+
+impl<T: DeSerializable> DeSerializable for Public<T> {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
+        T::decode(d).map(Public::new)
+    }
+}
+
+///
+/// Encodes a public value for an OCALL. Returns `None` if the value cannot be encoded.
+///
+pub fn to_vec<T: Serializable>(value: &Public<T>) -> Option<Vec<u8>> {
+    SerializeHelper::new().encode(value)
+}
+
+///
+/// Decodes a payload received from the host. Returns `None` if it is malformed.
+///
+pub fn from_slice<T: DeSerializable>(data: &[u8]) -> Option<Public<T>> {
+    DeSerializeHelper::<Public<T>>::new(data.to_vec()).decode()
+}
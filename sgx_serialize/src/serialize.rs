@@ -1103,26 +1103,49 @@ where
     }
 }
 
-use std::io::Cursor;
+use std::io::{Cursor, Write};
 use std::marker::PhantomData;
+use crate::json::Limits;
+use crate::msgpack;
 use crate::opaque::Encoder as DataEncoder;
 use crate::opaque::Decoder as DataDecoder;
 
+/// The wire format of SerializeHelper and DeSerializeHelper.
+///
+/// The codec is chosen per helper with `with_codec`; `new` always uses
+/// `Codec::Opaque`, so a library that does not ask for another codec keeps
+/// reading and writing the format it always has.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Codec {
+    /// The LEB128 based format of the opaque encoder.
+    Opaque,
+    /// MessagePack, decoded under the default JSON `Limits`.
+    MessagePack,
+}
+
 ///  SerializeHelper make it easy to obtain serialize function.
 pub struct SerializeHelper {
     cursor: RefCell<Cursor<Vec<u8>>>,
+    codec: Codec,
 }
 
 impl SerializeHelper {
-    /// Create a new instance of SerializeHelper
+    /// Create a new instance of SerializeHelper that encodes with `Codec::Opaque`.
     ///
     /// ```
     /// let helper = SerializeHelper::new();
     /// ```
     ///
     pub fn new() -> SerializeHelper {
+        SerializeHelper::with_codec(Codec::Opaque)
+    }
+
+    /// Create a new instance of SerializeHelper that encodes with `codec`. The
+    /// payload must be decoded with a DeSerializeHelper using the same codec.
+    pub fn with_codec(codec: Codec) -> SerializeHelper {
         SerializeHelper {
             cursor: RefCell::new(Cursor::new(Vec::new())),
+            codec,
         }
     }
 
@@ -1150,10 +1173,17 @@ impl SerializeHelper {
     pub fn encode< E: Serializable >(&self, target: E) -> Option<Vec<u8>> {
         {
             let mut cursor = self.cursor.borrow_mut();
-            let mut encoder = DataEncoder::new(&mut cursor);
-            match target.encode(&mut encoder) {
-                Result::Err(_) => return Option::None,
-                _ => {},
+            match self.codec {
+                Codec::Opaque => {
+                    let mut encoder = DataEncoder::new(&mut cursor);
+                    if target.encode(&mut encoder).is_err() {
+                        return Option::None;
+                    }
+                }
+                Codec::MessagePack => {
+                    let data = msgpack::to_vec(&target).ok()?;
+                    cursor.write_all(&data).ok()?;
+                }
             }
         }
         let data = self.cursor.borrow().clone().into_inner();
@@ -1164,12 +1194,13 @@ impl SerializeHelper {
 ///  DeSerializeHelper make it easy to obtain deserialize function.
 pub struct DeSerializeHelper<'a, T:'a + ?Sized> {
     data: Vec<u8>,
+    codec: Codec,
     marker: PhantomData<&'a T>,
 }
 
 impl<'a, T: 'a + ?Sized + DeSerializable> DeSerializeHelper<'a, T> {
     /// Create a new instance of DeSerializeHelper, parameter data must be a variable of
-    /// `Vec<u8>` which return by SerializeHelper::encode.
+    /// `Vec<u8>` which return by SerializeHelper::encode, with `Codec::Opaque`.
     ///
     /// ```
     /// #[derive(Serializable, DeSerializable)]
@@ -1184,8 +1215,14 @@ impl<'a, T: 'a + ?Sized + DeSerializable> DeSerializeHelper<'a, T> {
     /// ```
     ///
     pub fn new(data: Vec<u8>) -> DeSerializeHelper<'a, T> {
+        DeSerializeHelper::with_codec(data, Codec::Opaque)
+    }
+
+    /// Create a new instance of DeSerializeHelper that decodes with `codec`.
+    pub fn with_codec(data: Vec<u8>, codec: Codec) -> DeSerializeHelper<'a, T> {
         DeSerializeHelper {
-            data: data,
+            data,
+            codec,
             marker: PhantomData,
         }
     }
@@ -1206,6 +1243,9 @@ impl<'a, T: 'a + ?Sized + DeSerializable> DeSerializeHelper<'a, T> {
     /// ```
     ///
     pub fn decode(&self) -> Option<T> {
+        if self.codec == Codec::MessagePack {
+            return msgpack::from_slice(&self.data, &Limits::new()).ok();
+        }
         let mut decoder = DataDecoder::new(&self.data[..], 0);
         match DeSerializable::decode(&mut decoder) {
             Result::Err(_) => Option::None,
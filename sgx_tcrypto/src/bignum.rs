@@ -0,0 +1,768 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Big integers
+//!
+//! Arbitrary-precision unsigned integers with constant-time modular arithmetic:
+//! Montgomery multiplication, modular exponentiation and CRT recombination.
+//!
+//! A `BigUint` has a fixed width in 64-bit limbs. The time taken by an operation depends
+//! on the widths of its operands, never on their values, so a secret should be held at
+//! the width of its modulus, as `from_bytes_be` and the `Montgomery` operations do; the
+//! width is treated as public. Functions that inspect values, such as `bits`, say so.
+
+use crate::fp256::{adc, mac, mont_mul, select_assign, sub_assign};
+use alloc::vec::Vec;
+use core::cmp;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+///
+/// An unsigned integer of a fixed number of 64-bit limbs, least significant first.
+///
+/// # Description
+///
+/// `BigUint` deliberately does not implement `PartialEq`: use `ct_eq`.
+///
+#[derive(Clone, Debug)]
+pub struct BigUint {
+    limbs: Vec<u64>,
+}
+
+impl BigUint {
+    ///
+    /// Zero, `limbs` limbs wide.
+    ///
+    pub fn zero(limbs: usize) -> BigUint {
+        BigUint {
+            limbs: vec![0; cmp::max(limbs, 1)],
+        }
+    }
+
+    pub fn from_u64(value: u64) -> BigUint {
+        BigUint { limbs: vec![value] }
+    }
+
+    pub fn from_limbs(limbs: &[u64]) -> BigUint {
+        let mut n = BigUint::zero(limbs.len());
+        n.limbs[..limbs.len()].copy_from_slice(limbs);
+        n
+    }
+
+    ///
+    /// Reads a big-endian integer; the width is that of `bytes`, rounded up to whole limbs.
+    ///
+    pub fn from_bytes_be(bytes: &[u8]) -> BigUint {
+        let mut n = BigUint::zero((bytes.len() + 7) / 8);
+        for (i, b) in bytes.iter().rev().enumerate() {
+            n.limbs[i / 8] |= (*b as u64) << (8 * (i % 8));
+        }
+        n
+    }
+
+    ///
+    /// Writes the integer big-endian in `len` bytes.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The value does not fit. This check inspects the value.
+    ///
+    pub fn to_bytes_be(&self, len: usize) -> SgxResult<Vec<u8>> {
+        let mut out = vec![0_u8; len];
+        for i in 0..self.limbs.len() * 8 {
+            let b = (self.limbs[i / 8] >> (8 * (i % 8))) as u8;
+            if i < len {
+                out[len - 1 - i] = b;
+            } else if b != 0 {
+                return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+            }
+        }
+        Ok(out)
+    }
+
+    ///
+    /// A uniformly random integer below `bound`, at the width of `bound`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `bound` is zero.
+    ///
+    pub fn random_below(bound: &BigUint) -> SgxResult<BigUint> {
+        let bits = bound.bits();
+        if bits == 0 {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let mut n = BigUint::zero(bound.limbs.len());
+        let top = bits % 64;
+        loop {
+            let mut bytes = vec![0_u8; n.limbs.len() * 8];
+            rsgx_read_rand(&mut bytes)?;
+            for (limb, chunk) in n.limbs.iter_mut().zip(bytes.chunks_exact(8)) {
+                let mut b = [0_u8; 8];
+                b.copy_from_slice(chunk);
+                *limb = u64::from_le_bytes(b);
+            }
+            let used = (bits + 63) / 64;
+            for limb in n.limbs[used..].iter_mut() {
+                *limb = 0;
+            }
+            if top != 0 {
+                n.limbs[used - 1] &= (1 << top) - 1;
+            }
+            if n.ct_lt(bound) {
+                return Ok(n);
+            }
+        }
+    }
+
+    pub fn limbs(&self) -> &[u64] {
+        &self.limbs
+    }
+
+    pub fn width(&self) -> usize {
+        self.limbs.len()
+    }
+
+    ///
+    /// The same value at a width of `limbs` limbs, which must hold it.
+    ///
+    pub fn resize(&self, limbs: usize) -> BigUint {
+        let mut n = BigUint::zero(limbs);
+        let len = cmp::min(self.limbs.len(), n.limbs.len());
+        n.limbs[..len].copy_from_slice(&self.limbs[..len]);
+        n
+    }
+
+    ///
+    /// The number of significant bits. This inspects the value.
+    ///
+    pub fn bits(&self) -> usize {
+        for (i, limb) in self.limbs.iter().enumerate().rev() {
+            if *limb != 0 {
+                return i * 64 + 64 - limb.leading_zeros() as usize;
+            }
+        }
+        0
+    }
+
+    pub fn bit(&self, i: usize) -> bool {
+        self.limbs
+            .get(i / 64)
+            .map_or(false, |limb| (limb >> (i % 64)) & 1 == 1)
+    }
+
+    pub fn is_odd(&self) -> bool {
+        self.limbs[0] & 1 == 1
+    }
+
+    pub fn ct_is_zero(&self) -> bool {
+        self.limbs.iter().fold(0, |acc, limb| acc | limb) == 0
+    }
+
+    pub fn ct_eq(&self, other: &BigUint) -> bool {
+        let len = cmp::max(self.limbs.len(), other.limbs.len());
+        let mut diff = 0;
+        for i in 0..len {
+            diff |=
+                self.limbs.get(i).copied().unwrap_or(0) ^ other.limbs.get(i).copied().unwrap_or(0);
+        }
+        diff == 0
+    }
+
+    pub fn ct_lt(&self, other: &BigUint) -> bool {
+        let len = cmp::max(self.limbs.len(), other.limbs.len());
+        let mut a = self.resize(len);
+        sub_assign(&mut a.limbs, &other.limbs) == 1
+    }
+
+    ///
+    /// The sum, one limb wider than the wider operand.
+    ///
+    pub fn add(&self, other: &BigUint) -> BigUint {
+        let len = cmp::max(self.limbs.len(), other.limbs.len());
+        let mut n = self.resize(len + 1);
+        let mut carry = 0;
+        for i in 0..=len {
+            let (s, c) = adc(n.limbs[i], other.limbs.get(i).copied().unwrap_or(0), carry);
+            n.limbs[i] = s;
+            carry = c;
+        }
+        n
+    }
+
+    ///
+    /// The difference and whether it wrapped, at the width of the wider operand.
+    ///
+    pub fn overflowing_sub(&self, other: &BigUint) -> (BigUint, bool) {
+        let len = cmp::max(self.limbs.len(), other.limbs.len());
+        let mut n = self.resize(len);
+        let borrow = sub_assign(&mut n.limbs, &other.limbs);
+        (n, borrow == 1)
+    }
+
+    ///
+    /// The product, as wide as both operands together.
+    ///
+    pub fn mul(&self, other: &BigUint) -> BigUint {
+        let mut n = BigUint::zero(self.limbs.len() + other.limbs.len());
+        for (i, a) in self.limbs.iter().enumerate() {
+            let mut carry = 0;
+            for (j, b) in other.limbs.iter().enumerate() {
+                let (lo, hi) = mac(n.limbs[i + j], *a, *b, carry);
+                n.limbs[i + j] = lo;
+                carry = hi;
+            }
+            n.limbs[i + other.limbs.len()] = carry;
+        }
+        n
+    }
+
+    ///
+    /// Selects `b` if `choice` is set and `a` otherwise, without branching. Both operands
+    /// must have the same width.
+    ///
+    pub fn ct_select(a: &BigUint, b: &BigUint, choice: bool) -> BigUint {
+        let mut n = a.clone();
+        select_assign(&mut n.limbs, &b.limbs, choice as u64);
+        n
+    }
+
+    ///
+    /// `self / d` for an odd `d` that divides `self` exactly, at the width of `self`
+    /// (Jebelean's exact division: a product with `d^-1 mod 2^(64 * width)`).
+    ///
+    pub fn div_exact(&self, d: &BigUint) -> BigUint {
+        let width = self.limbs.len();
+        let d = d.resize(width);
+        // d * inv = 1 mod 2^64 holds for inv = d; each Newton step doubles the valid bits.
+        let mut inv = d.clone();
+        let mut valid = 3;
+        while valid < 64 * width {
+            let (t, _) = BigUint::from_u64(2)
+                .resize(width)
+                .overflowing_sub(&d.mul(&inv).resize(width));
+            inv = inv.mul(&t).resize(width);
+            valid *= 2;
+        }
+        self.mul(&inv).resize(width)
+    }
+
+    ///
+    /// Whether the value is probably prime: trial division by the primes below 1000, then
+    /// `rounds` Miller-Rabin rounds with random bases. A composite passes with probability
+    /// at most 4^-rounds. The trial division takes time that depends on the value.
+    ///
+    pub fn is_probable_prime(&self, rounds: usize) -> SgxResult<bool> {
+        if self.bits() <= 10 {
+            let v = self.limbs[0];
+            return Ok(v >= 2 && (2..v).take_while(|d| d * d <= v).all(|d| v % d != 0));
+        }
+        if !self.is_odd() {
+            return Ok(false);
+        }
+        for p in (3_u64..1000).step_by(2).filter(|p| {
+            (3..*p)
+                .step_by(2)
+                .take_while(|d| d * d <= *p)
+                .all(|d| p % d != 0)
+        }) {
+            let rem = self.limbs.iter().rev().fold(0_u64, |rem, limb| {
+                (((rem as u128) << 64 | *limb as u128) % p as u128) as u64
+            });
+            if rem == 0 {
+                return Ok(false);
+            }
+        }
+
+        let ctx = Montgomery::new(self)?;
+        let one = BigUint::from_u64(1).resize(self.limbs.len());
+        let (n_minus_1, _) = self.overflowing_sub(&one);
+        let s = (0..).find(|i| n_minus_1.bit(*i)).unwrap_or(0);
+        let mut d = BigUint::zero(self.limbs.len());
+        for i in 0..self.bits() - s {
+            if n_minus_1.bit(i + s) {
+                d.limbs[i / 64] |= 1 << (i % 64);
+            }
+        }
+        let (bound, _) = n_minus_1.overflowing_sub(&BigUint::from_u64(2));
+        'rounds: for _ in 0..rounds {
+            let a = BigUint::random_below(&bound)?
+                .add(&BigUint::from_u64(2))
+                .resize(self.limbs.len());
+            let mut x = ctx.pow(&a, &d);
+            if x.ct_eq(&one) || x.ct_eq(&n_minus_1) {
+                continue;
+            }
+            for _ in 1..s {
+                x = ctx.mul(&x, &x);
+                if x.ct_eq(&n_minus_1) {
+                    continue 'rounds;
+                }
+            }
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    ///
+    /// A random prime of exactly `bits` bits, at least 16, whose two top bits are set, so
+    /// that the product of two such primes has exactly `2 * bits` bits.
+    ///
+    pub fn random_prime(bits: usize) -> SgxResult<BigUint> {
+        if bits < 16 {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let limbs = (bits + 63) / 64;
+        let mut bytes = vec![0_u8; limbs * 8];
+        loop {
+            rsgx_read_rand(&mut bytes)?;
+            let mut n = BigUint::zero(limbs);
+            for (limb, chunk) in n.limbs.iter_mut().zip(bytes.chunks_exact(8)) {
+                let mut b = [0_u8; 8];
+                b.copy_from_slice(chunk);
+                *limb = u64::from_le_bytes(b);
+            }
+            let top = (bits - 1) % 64;
+            let last = &mut n.limbs[limbs - 1];
+            *last &= if top == 63 {
+                u64::MAX
+            } else {
+                (1 << (top + 1)) - 1
+            };
+            *last |= 1 << top;
+            if top == 0 {
+                n.limbs[limbs - 2] |= 1 << 63;
+            } else {
+                *last |= 1 << (top - 1);
+            }
+            n.limbs[0] |= 1;
+            if n.is_probable_prime(40)? {
+                for b in bytes.iter_mut() {
+                    unsafe { core::ptr::write_volatile(b, 0) };
+                }
+                return Ok(n);
+            }
+        }
+    }
+
+    ///
+    /// Overwrites the value with zeros.
+    ///
+    pub fn wipe(&mut self) {
+        for limb in self.limbs.iter_mut() {
+            unsafe { core::ptr::write_volatile(limb, 0) };
+        }
+    }
+}
+
+///
+/// Arithmetic modulo an odd modulus in Montgomery form.
+///
+/// # Description
+///
+/// Operands and results are ordinary integers below the modulus, at its width; the
+/// conversion to and from Montgomery form is internal. Every operation runs in time that
+/// depends only on the widths of the modulus and the operands.
+///
+#[derive(Clone, Debug)]
+pub struct Montgomery {
+    m: BigUint,
+    m0_inv: u64,
+    r2: BigUint,
+}
+
+impl Montgomery {
+    ///
+    /// Prepares arithmetic modulo `modulus`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The modulus is even or below 3.
+    ///
+    pub fn new(modulus: &BigUint) -> SgxResult<Montgomery> {
+        if !modulus.is_odd() || modulus.bits() < 2 {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let m = modulus.resize((modulus.bits() + 63) / 64);
+        // -m^-1 mod 2^64 by Newton's iteration; each step doubles the correct bits.
+        let m0 = m.limbs[0];
+        let mut inv = 1_u64;
+        for _ in 0..6 {
+            inv = inv.wrapping_mul(2_u64.wrapping_sub(m0.wrapping_mul(inv)));
+        }
+        let mut ctx = Montgomery {
+            m0_inv: inv.wrapping_neg(),
+            r2: BigUint::zero(m.limbs.len()),
+            m,
+        };
+        let n = ctx.m.limbs.len();
+        let mut r2 = BigUint::zero(2 * n + 1);
+        r2.limbs[2 * n] = 1;
+        ctx.r2 = ctx.reduce(&r2);
+        Ok(ctx)
+    }
+
+    pub fn modulus(&self) -> &BigUint {
+        &self.m
+    }
+
+    ///
+    /// `x mod m`, for `x` of any width, by shifting in one bit at a time.
+    ///
+    pub fn reduce(&self, x: &BigUint) -> BigUint {
+        let n = self.m.limbs.len();
+        let mut r = vec![0_u64; n + 1];
+        let mut t = vec![0_u64; n + 1];
+        for i in (0..x.limbs.len() * 64).rev() {
+            let mut carry = (x.limbs[i / 64] >> (i % 64)) & 1;
+            for limb in r.iter_mut() {
+                let next = *limb >> 63;
+                *limb = (*limb << 1) | carry;
+                carry = next;
+            }
+            t.copy_from_slice(&r);
+            let borrow = sub_assign(&mut t, &self.m.limbs);
+            select_assign(&mut r, &t, borrow ^ 1);
+        }
+        BigUint::from_limbs(&r[..n])
+    }
+
+    // Montgomery product a * b / R mod m of operands below m at its width.
+    fn mont_mul(&self, a: &[u64], b: &[u64]) -> Vec<u64> {
+        let n = self.m.limbs.len();
+        let mut t = vec![0_u64; n + 2];
+        mont_mul(&mut t, a, b, &self.m.limbs, self.m0_inv);
+        t.truncate(n);
+        t
+    }
+
+    fn fit(&self, a: &BigUint) -> Vec<u64> {
+        let n = self.m.limbs.len();
+        if a.limbs.len() == n {
+            a.limbs.clone()
+        } else {
+            self.reduce(a).limbs
+        }
+    }
+
+    fn enter(&self, a: &BigUint) -> Vec<u64> {
+        self.mont_mul(&self.fit(a), &self.r2.limbs)
+    }
+
+    fn leave(&self, a: &[u64]) -> BigUint {
+        let mut one = vec![0_u64; self.m.limbs.len()];
+        one[0] = 1;
+        BigUint {
+            limbs: self.mont_mul(a, &one),
+        }
+    }
+
+    ///
+    /// `(a + b) mod m` for `a` and `b` below `m`.
+    ///
+    pub fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        let n = self.m.limbs.len();
+        let mut s = a.resize(n).add(&b.resize(n)).limbs;
+        let mut t = s.clone();
+        let borrow = sub_assign(&mut t, &self.m.limbs);
+        select_assign(&mut s, &t, borrow ^ 1);
+        BigUint::from_limbs(&s[..n])
+    }
+
+    ///
+    /// `(a - b) mod m` for `a` and `b` below `m`.
+    ///
+    pub fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        let n = self.m.limbs.len();
+        let mut d = a.resize(n);
+        let borrow = sub_assign(&mut d.limbs, &b.resize(n).limbs);
+        let mut t = d.clone();
+        let mut carry = 0;
+        for (x, y) in t.limbs.iter_mut().zip(self.m.limbs.iter()) {
+            let (s, c) = adc(*x, *y, carry);
+            *x = s;
+            carry = c;
+        }
+        select_assign(&mut d.limbs, &t.limbs, borrow);
+        d
+    }
+
+    ///
+    /// `(a * b) mod m`. Operands wider than the modulus are reduced first.
+    ///
+    pub fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        let am = self.enter(a);
+        BigUint {
+            limbs: self.mont_mul(&am, &self.fit(b)),
+        }
+    }
+
+    ///
+    /// `base^exp mod m`, with a fixed window of four bits over all bits of the width of
+    /// `exp`; every window multiplies by an entry selected by scanning the whole table.
+    ///
+    pub fn pow(&self, base: &BigUint, exp: &BigUint) -> BigUint {
+        const WINDOW: usize = 4;
+        let n = self.m.limbs.len();
+        let mut table = Vec::with_capacity(1 << WINDOW);
+        let mut one = BigUint::zero(n);
+        one.limbs[0] = 1;
+        table.push(self.enter(&one));
+        table.push(self.enter(base));
+        for i in 2..(1 << WINDOW) {
+            let next = self.mont_mul(&table[i - 1], &table[1]);
+            table.push(next);
+        }
+
+        let mut acc = table[0].clone();
+        let mut entry = vec![0_u64; n];
+        for w in (0..exp.limbs.len() * 64 / WINDOW).rev() {
+            for _ in 0..WINDOW {
+                acc = self.mont_mul(&acc, &acc);
+            }
+            let bits = (exp.limbs[w * WINDOW / 64] >> ((w * WINDOW) % 64)) & ((1 << WINDOW) - 1);
+            for (i, candidate) in table.iter().enumerate() {
+                let hit = ((i as u64 ^ bits).wrapping_sub(1) >> 63) & 1;
+                select_assign(&mut entry, candidate, hit);
+            }
+            acc = self.mont_mul(&acc, &entry);
+        }
+        for value in table.iter_mut() {
+            for limb in value.iter_mut() {
+                unsafe { core::ptr::write_volatile(limb, 0) };
+            }
+        }
+        self.leave(&acc)
+    }
+
+    ///
+    /// `a^-1 mod m` for a prime modulus, by Fermat's little theorem. The result is zero if
+    /// `a` is a multiple of `m`.
+    ///
+    pub fn inv_prime(&self, a: &BigUint) -> BigUint {
+        let (exp, _) = self.m.overflowing_sub(&BigUint::from_u64(2));
+        self.pow(a, &exp)
+    }
+}
+
+///
+/// Arithmetic modulo `n = p * q` through the Chinese remainder theorem, for distinct
+/// odd primes `p` and `q`.
+///
+#[derive(Clone, Debug)]
+pub struct Crt {
+    p: Montgomery,
+    q: Montgomery,
+    q_inv_p: BigUint,
+    n: BigUint,
+}
+
+impl Crt {
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// A factor is even, below 3, or the factors are equal.
+    ///
+    pub fn new(p: &BigUint, q: &BigUint) -> SgxResult<Crt> {
+        let p = Montgomery::new(p)?;
+        let q = Montgomery::new(q)?;
+        if p.modulus().ct_eq(q.modulus()) {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let q_inv_p = p.inv_prime(&p.reduce(q.modulus()));
+        let n = p.modulus().mul(q.modulus());
+        Ok(Crt { p, q, q_inv_p, n })
+    }
+
+    pub fn p(&self) -> &Montgomery {
+        &self.p
+    }
+
+    pub fn q(&self) -> &Montgomery {
+        &self.q
+    }
+
+    pub fn modulus(&self) -> &BigUint {
+        &self.n
+    }
+
+    ///
+    /// The `x` below `n` with `x = xp mod p` and `x = xq mod q` (Garner's formula), at the
+    /// width of `n`.
+    ///
+    pub fn combine(&self, xp: &BigUint, xq: &BigUint) -> BigUint {
+        let xq = self.q.reduce(xq);
+        let xq_p = self.p.reduce(&xq);
+        let xp = self.p.reduce(xp);
+        let h = self.p.mul(&self.q_inv_p, &self.p.sub(&xp, &xq_p));
+        let x = h.mul(self.q.modulus()).add(&xq);
+        x.resize(self.n.limbs.len())
+    }
+
+    ///
+    /// `base^e mod n` given `ep = e mod (p - 1)` and `eq = e mod (q - 1)`.
+    ///
+    pub fn pow(&self, base: &BigUint, ep: &BigUint, eq: &BigUint) -> BigUint {
+        let xp = self.p.pow(base, ep);
+        let xq = self.q.pow(base, eq);
+        self.combine(&xp, &xq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::rsgx_hex_decode;
+
+    fn big(hex: &str) -> BigUint {
+        let padded = if hex.len() % 2 == 1 {
+            format!("0{}", hex)
+        } else {
+            hex.into()
+        };
+        BigUint::from_bytes_be(&rsgx_hex_decode(&padded).unwrap())
+    }
+
+    const A: &str = "fedcba9876543210fedcba9876543210fedcba98";
+    const B: &str = "0123456789abcdef0123456789abcdef01234567";
+    // 2^127 - 1 and 2^89 - 1.
+    const M127: &str = "7fffffffffffffffffffffffffffffff";
+    const M89: &str = "1ffffffffffffffffffffff";
+
+    #[test]
+    fn bytes_round_trip() {
+        let bytes = rsgx_hex_decode(A).unwrap();
+        let n = BigUint::from_bytes_be(&bytes);
+        assert_eq!(n.width(), 3);
+        assert_eq!(n.bits(), 160);
+        assert_eq!(n.to_bytes_be(20).unwrap(), bytes);
+        let mut wide = vec![0_u8; 12];
+        wide.extend_from_slice(&bytes);
+        assert_eq!(n.to_bytes_be(32).unwrap(), wide);
+        assert_eq!(
+            n.to_bytes_be(19),
+            Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+        );
+    }
+
+    #[test]
+    fn integer_known_answers() {
+        let (a, b) = (big(A), big(B));
+        assert!(a
+            .add(&b)
+            .ct_eq(&big("ffffffffffffffffffffffffffffffffffffffff")));
+        assert!(a.mul(&b).ct_eq(&big(
+            "121fa00ad77d742247acc9140513b7447d39f214994d38c070f2ce8b6bb6f59e3b65a5823e20b28"
+        )));
+        let (d, wrapped) = b.overflowing_sub(&a);
+        assert!(wrapped);
+        assert!(d.ct_eq(&big("ffffffff02468acf13579bde02468acf13579bde02468acf")));
+        let (d, wrapped) = a.overflowing_sub(&b);
+        assert!(!wrapped);
+        assert!(d.add(&b).ct_eq(&a));
+        assert!(b.ct_lt(&a) && !a.ct_lt(&b) && !a.ct_lt(&a));
+        assert!(a.mul(&b).div_exact(&b).ct_eq(&a));
+        assert!(BigUint::ct_select(&a, &b, true).ct_eq(&b));
+        assert!(BigUint::ct_select(&a, &b, false).ct_eq(&a));
+    }
+
+    #[test]
+    fn montgomery_known_answers() {
+        let ctx = Montgomery::new(&big(M127)).unwrap();
+        let (a, b) = (big(A), big(B));
+        let (ar, br) = (ctx.reduce(&a), ctx.reduce(&b));
+        assert!(ctx
+            .mul(&ar, &br)
+            .ct_eq(&big("5004c60b375de64277e5809d6ceb0f48")));
+        assert!(ctx
+            .pow(&ar, &b)
+            .ct_eq(&big("65837af8db3a390d73fa06d9e9551b76")));
+        let inv = ctx.inv_prime(&ar);
+        assert!(inv.ct_eq(&big("799a50865927b262d073a4460afbbd1e")));
+        assert!(ctx.mul(&ar, &inv).ct_eq(&BigUint::from_u64(1)));
+        assert!(ctx.add(&ctx.sub(&ar, &br), &br).ct_eq(&ar));
+        assert!(ctx.sub(&ar, &ar).ct_is_zero());
+
+        assert!(Montgomery::new(&BigUint::from_u64(10)).is_err());
+        assert!(Montgomery::new(&BigUint::from_u64(1)).is_err());
+    }
+
+    #[test]
+    fn crt_known_answers() {
+        let crt = Crt::new(&big(M127), &big(M89)).unwrap();
+        let b = big(B);
+        let ep = big("9abcdef0123456789abcdef05b05b05");
+        let eq = big("1234568acf13578acf13467");
+        let x = crt.pow(&BigUint::from_u64(7), &ep, &eq);
+        assert!(x.ct_eq(&big(
+            "f1f8bb7af866b9b0a5341bfdc7b835119f6dd5e842f698df1c1bab"
+        )));
+        let direct = Montgomery::new(crt.modulus())
+            .unwrap()
+            .pow(&BigUint::from_u64(7), &b);
+        assert!(x.ct_eq(&direct));
+        assert!(crt
+            .combine(&crt.p().reduce(&x), &crt.q().reduce(&x))
+            .ct_eq(&x));
+
+        assert!(Crt::new(&big(M127), &big(M127)).is_err());
+    }
+
+    #[test]
+    fn primality() {
+        for p in [2_u64, 3, 5, 997, 1009, 65537, 2_147_483_647] {
+            assert!(BigUint::from_u64(p).is_probable_prime(20).unwrap(), "{}", p);
+        }
+        assert!(big(M127).is_probable_prime(20).unwrap());
+        assert!(big(M89).is_probable_prime(20).unwrap());
+        // 561 is a Carmichael number; 3215031751 is a strong pseudoprime to bases 2, 3, 5
+        // and 7.
+        for c in [0_u64, 1, 4, 561, 1_000_001, 3_215_031_751] {
+            assert!(
+                !BigUint::from_u64(c).is_probable_prime(20).unwrap(),
+                "{}",
+                c
+            );
+        }
+        assert!(!big(M127).mul(&big(M89)).is_probable_prime(20).unwrap());
+
+        let p = BigUint::random_prime(80).unwrap();
+        assert_eq!(p.bits(), 80);
+        assert!(p.bit(78) && p.is_odd());
+        assert!(p.is_probable_prime(20).unwrap());
+        assert!(BigUint::random_prime(15).is_err());
+    }
+
+    #[test]
+    fn random_below_bound() {
+        let bound = big("10000000000000001");
+        for _ in 0..32 {
+            let n = BigUint::random_below(&bound).unwrap();
+            assert!(n.ct_lt(&bound));
+            assert_eq!(n.width(), bound.width());
+        }
+        assert!(BigUint::random_below(&BigUint::zero(1)).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use super::{BLAKE3_BLOCK_LEN, BLAKE3_CHUNK_LEN, CHUNK_END, CHUNK_START, IV, MSG_SCHEDULE};
+use core::arch::x86_64::*;
+
+pub(crate) const DEGREE: usize = 16;
+
+#[inline(always)]
+unsafe fn add(a: __m512i, b: __m512i) -> __m512i {
+    _mm512_add_epi32(a, b)
+}
+
+#[inline(always)]
+unsafe fn xor(a: __m512i, b: __m512i) -> __m512i {
+    _mm512_xor_si512(a, b)
+}
+
+#[inline(always)]
+unsafe fn set1(x: u32) -> __m512i {
+    _mm512_set1_epi32(x as i32)
+}
+
+#[inline(always)]
+unsafe fn load(words: &[u32; DEGREE]) -> __m512i {
+    _mm512_loadu_si512(words.as_ptr() as *const i32)
+}
+
+#[inline(always)]
+unsafe fn store(words: &mut [u32; DEGREE], x: __m512i) {
+    _mm512_storeu_si512(words.as_mut_ptr() as *mut i32, x)
+}
+
+#[inline(always)]
+unsafe fn rot16(x: __m512i) -> __m512i {
+    _mm512_ror_epi32::<16>(x)
+}
+
+#[inline(always)]
+unsafe fn rot12(x: __m512i) -> __m512i {
+    _mm512_ror_epi32::<12>(x)
+}
+
+#[inline(always)]
+unsafe fn rot8(x: __m512i) -> __m512i {
+    _mm512_ror_epi32::<8>(x)
+}
+
+#[inline(always)]
+unsafe fn rot7(x: __m512i) -> __m512i {
+    _mm512_ror_epi32::<7>(x)
+}
+
+#[inline(always)]
+unsafe fn g(
+    v: &mut [__m512i; 16],
+    a: usize,
+    b: usize,
+    c: usize,
+    d: usize,
+    mx: __m512i,
+    my: __m512i,
+) {
+    v[a] = add(add(v[a], v[b]), mx);
+    v[d] = rot16(xor(v[d], v[a]));
+    v[c] = add(v[c], v[d]);
+    v[b] = rot12(xor(v[b], v[c]));
+    v[a] = add(add(v[a], v[b]), my);
+    v[d] = rot8(xor(v[d], v[a]));
+    v[c] = add(v[c], v[d]);
+    v[b] = rot7(xor(v[b], v[c]));
+}
+
+#[inline(always)]
+unsafe fn round(v: &mut [__m512i; 16], m: &[__m512i; 16], schedule: &[usize; 16]) {
+    g(v, 0, 4, 8, 12, m[schedule[0]], m[schedule[1]]);
+    g(v, 1, 5, 9, 13, m[schedule[2]], m[schedule[3]]);
+    g(v, 2, 6, 10, 14, m[schedule[4]], m[schedule[5]]);
+    g(v, 3, 7, 11, 15, m[schedule[6]], m[schedule[7]]);
+    g(v, 0, 5, 10, 15, m[schedule[8]], m[schedule[9]]);
+    g(v, 1, 6, 11, 12, m[schedule[10]], m[schedule[11]]);
+    g(v, 2, 7, 8, 13, m[schedule[12]], m[schedule[13]]);
+    g(v, 3, 4, 9, 14, m[schedule[14]], m[schedule[15]]);
+}
+
+// Hashes sixteen whole chunks, one per 32-bit lane.
+#[target_feature(enable = "avx512f")]
+pub(crate) unsafe fn hash_chunks(
+    input: &[u8],
+    key: &[u32; 8],
+    counter: u64,
+    flags: u32,
+    out: &mut [[u32; 8]],
+) {
+    let mut h = [set1(0); 8];
+    for (hi, k) in h.iter_mut().zip(key.iter()) {
+        *hi = set1(*k);
+    }
+    let mut counter_lo = [0_u32; DEGREE];
+    let mut counter_hi = [0_u32; DEGREE];
+    for lane in 0..DEGREE {
+        let chunk_counter = counter + lane as u64;
+        counter_lo[lane] = chunk_counter as u32;
+        counter_hi[lane] = (chunk_counter >> 32) as u32;
+    }
+    let counter_lo = load(&counter_lo);
+    let counter_hi = load(&counter_hi);
+
+    let blocks = BLAKE3_CHUNK_LEN / BLAKE3_BLOCK_LEN;
+    for block in 0..blocks {
+        let mut block_flags = flags;
+        if block == 0 {
+            block_flags |= CHUNK_START;
+        }
+        if block == blocks - 1 {
+            block_flags |= CHUNK_END;
+        }
+
+        // Transpose word w of this block in every chunk into vector m[w].
+        let mut m = [set1(0); 16];
+        for (w, mw) in m.iter_mut().enumerate() {
+            let mut lanes = [0_u32; DEGREE];
+            for (lane, word) in lanes.iter_mut().enumerate() {
+                let offset = lane * BLAKE3_CHUNK_LEN + block * BLAKE3_BLOCK_LEN + 4 * w;
+                let b = &input[offset..offset + 4];
+                *word = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+            }
+            *mw = load(&lanes);
+        }
+
+        let mut v = [
+            h[0],
+            h[1],
+            h[2],
+            h[3],
+            h[4],
+            h[5],
+            h[6],
+            h[7],
+            set1(IV[0]),
+            set1(IV[1]),
+            set1(IV[2]),
+            set1(IV[3]),
+            counter_lo,
+            counter_hi,
+            set1(BLAKE3_BLOCK_LEN as u32),
+            set1(block_flags),
+        ];
+        for schedule in MSG_SCHEDULE.iter() {
+            round(&mut v, &m, schedule);
+        }
+        for i in 0..8 {
+            h[i] = xor(v[i], v[i + 8]);
+        }
+    }
+
+    for (i, hi) in h.iter().enumerate() {
+        let mut lanes = [0_u32; DEGREE];
+        store(&mut lanes, *hi);
+        for (cv, word) in out.iter_mut().zip(lanes.iter()) {
+            cv[i] = *word;
+        }
+    }
+}
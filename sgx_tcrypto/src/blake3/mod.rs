@@ -0,0 +1,634 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # BLAKE3
+//!
+//! The BLAKE3 hash function in its three modes: hashing, keyed hashing and key derivation,
+//! with extendable output.
+//!
+//! BLAKE3 splits its input into 1 KiB chunks, hashes the chunks independently and combines
+//! them in a binary tree. When the input spans several chunks, whole chunks are hashed side
+//! by side with AVX-512 (16 chunks) or AVX2 (8 chunks), selected at runtime from the CPU
+//! features reported to the enclave. Other CPUs use the portable implementation. All
+//! backends produce the same output.
+//!
+//! The state of a `Blake3Hasher` can be exported between ECALLs and imported again, so that
+//! an input larger than enclave memory is hashed piece by piece. With the `thread` feature,
+//! `ParallelHasher` also spreads large inputs over worker threads, each hashing whole
+//! subtrees of the BLAKE3 tree.
+
+use alloc::vec::Vec;
+use sgx_trts::is_x86_feature_detected;
+use sgx_types::*;
+
+mod avx2;
+mod avx512;
+#[cfg(feature = "thread")]
+mod parallel;
+mod portable;
+
+#[cfg(feature = "thread")]
+pub use self::parallel::ParallelHasher;
+
+pub const BLAKE3_OUT_LEN: usize = 32;
+pub const BLAKE3_KEY_LEN: usize = 32;
+pub const BLAKE3_BLOCK_LEN: usize = 64;
+pub const BLAKE3_CHUNK_LEN: usize = 1024;
+
+pub type Blake3Hash = [u8; BLAKE3_OUT_LEN];
+
+pub(crate) const IV: [u32; 8] = [
+    0x6A09_E667,
+    0xBB67_AE85,
+    0x3C6E_F372,
+    0xA54F_F53A,
+    0x510E_527F,
+    0x9B05_688C,
+    0x1F83_D9AB,
+    0x5BE0_CD19,
+];
+
+pub(crate) const CHUNK_START: u32 = 1 << 0;
+pub(crate) const CHUNK_END: u32 = 1 << 1;
+pub(crate) const PARENT: u32 = 1 << 2;
+pub(crate) const ROOT: u32 = 1 << 3;
+pub(crate) const KEYED_HASH: u32 = 1 << 4;
+pub(crate) const DERIVE_KEY_CONTEXT: u32 = 1 << 5;
+pub(crate) const DERIVE_KEY_MATERIAL: u32 = 1 << 6;
+
+// Enough for 2^54 chunks, the most a 64-bit byte count can address.
+pub(crate) const MAX_DEPTH: usize = 54;
+
+const STATE_VERSION: u8 = 1;
+// version, flags, key, chunk counter, chunk chaining value, blocks compressed, block
+// length, block, stack length.
+const STATE_FIXED_LEN: usize = 1 + 4 + 32 + 8 + 32 + 1 + 1 + BLAKE3_BLOCK_LEN + 1;
+
+const MSG_PERMUTATION: [usize; 16] = [2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8];
+
+// The message word order of each of the seven rounds.
+pub(crate) const MSG_SCHEDULE: [[usize; 16]; 7] = {
+    let mut schedule = [[0_usize; 16]; 7];
+    let mut i = 0;
+    while i < 16 {
+        schedule[0][i] = i;
+        i += 1;
+    }
+    let mut round = 1;
+    while round < 7 {
+        let mut i = 0;
+        while i < 16 {
+            schedule[round][i] = schedule[round - 1][MSG_PERMUTATION[i]];
+            i += 1;
+        }
+        round += 1;
+    }
+    schedule
+};
+
+///
+/// The implementation used for hashing whole chunks in parallel.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Blake3Backend {
+    Portable,
+    Avx2,
+    Avx512,
+}
+
+impl Blake3Backend {
+    ///
+    /// Returns the fastest backend supported by the CPU.
+    ///
+    pub fn detect() -> Blake3Backend {
+        if is_x86_feature_detected!("avx512f") {
+            Blake3Backend::Avx512
+        } else if is_x86_feature_detected!("avx2") {
+            Blake3Backend::Avx2
+        } else {
+            Blake3Backend::Portable
+        }
+    }
+
+    ///
+    /// Returns the number of chunks the backend hashes at once.
+    ///
+    pub fn degree(&self) -> usize {
+        match self {
+            Blake3Backend::Portable => 1,
+            Blake3Backend::Avx2 => avx2::DEGREE,
+            Blake3Backend::Avx512 => avx512::DEGREE,
+        }
+    }
+
+    // Hashes `degree()` whole chunks, numbered from `counter`, into their chaining values.
+    pub(crate) fn hash_chunks(
+        &self,
+        input: &[u8],
+        key: &[u32; 8],
+        counter: u64,
+        flags: u32,
+        out: &mut [[u32; 8]],
+    ) {
+        debug_assert_eq!(input.len(), self.degree() * BLAKE3_CHUNK_LEN);
+        debug_assert_eq!(out.len(), self.degree());
+        match self {
+            Blake3Backend::Portable => {
+                let mut chunk = ChunkState::new(key, counter, flags);
+                chunk.update(input);
+                out[0] = chunk.output().chaining_value();
+            }
+            // The backend is only ever one that detect() returned.
+            Blake3Backend::Avx2 => unsafe { avx2::hash_chunks(input, key, counter, flags, out) },
+            Blake3Backend::Avx512 => unsafe {
+                avx512::hash_chunks(input, key, counter, flags, out)
+            },
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn words_from_le_bytes<const N: usize>(bytes: &[u8]) -> [u32; N] {
+    let mut words = [0_u32; N];
+    for (word, b) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+        *word = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    }
+    words
+}
+
+// The input of one compression, kept unevaluated so that it can either be chained into the
+// tree or extended into the root output.
+#[derive(Copy, Clone)]
+pub(crate) struct Output {
+    input_cv: [u32; 8],
+    block_words: [u32; 16],
+    counter: u64,
+    block_len: u32,
+    flags: u32,
+}
+
+impl Output {
+    pub(crate) fn chaining_value(&self) -> [u32; 8] {
+        let words = portable::compress(
+            &self.input_cv,
+            &self.block_words,
+            self.counter,
+            self.block_len,
+            self.flags,
+        );
+        let mut cv = [0_u32; 8];
+        cv.copy_from_slice(&words[..8]);
+        cv
+    }
+
+    pub(crate) fn root_output_bytes(&self, out: &mut [u8]) {
+        for (counter, block) in out.chunks_mut(2 * BLAKE3_OUT_LEN).enumerate() {
+            let words = portable::compress(
+                &self.input_cv,
+                &self.block_words,
+                counter as u64,
+                self.block_len,
+                self.flags | ROOT,
+            );
+            for (word, bytes) in words.iter().zip(block.chunks_mut(4)) {
+                bytes.copy_from_slice(&word.to_le_bytes()[..bytes.len()]);
+            }
+        }
+    }
+}
+
+pub(crate) fn parent_output(
+    left: &[u32; 8],
+    right: &[u32; 8],
+    key: &[u32; 8],
+    flags: u32,
+) -> Output {
+    let mut block_words = [0_u32; 16];
+    block_words[..8].copy_from_slice(left);
+    block_words[8..].copy_from_slice(right);
+    Output {
+        input_cv: *key,
+        block_words,
+        counter: 0,
+        block_len: BLAKE3_BLOCK_LEN as u32,
+        flags: PARENT | flags,
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ChunkState {
+    pub(crate) cv: [u32; 8],
+    pub(crate) chunk_counter: u64,
+    pub(crate) block: [u8; BLAKE3_BLOCK_LEN],
+    pub(crate) block_len: u8,
+    pub(crate) blocks_compressed: u8,
+    pub(crate) flags: u32,
+}
+
+impl ChunkState {
+    pub(crate) fn new(key: &[u32; 8], chunk_counter: u64, flags: u32) -> ChunkState {
+        ChunkState {
+            cv: *key,
+            chunk_counter,
+            block: [0; BLAKE3_BLOCK_LEN],
+            block_len: 0,
+            blocks_compressed: 0,
+            flags,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        BLAKE3_BLOCK_LEN * self.blocks_compressed as usize + self.block_len as usize
+    }
+
+    fn start_flag(&self) -> u32 {
+        if self.blocks_compressed == 0 {
+            CHUNK_START
+        } else {
+            0
+        }
+    }
+
+    pub(crate) fn update(&mut self, mut input: &[u8]) {
+        while !input.is_empty() {
+            // Only compress a full block once more input arrives: the last block of a chunk
+            // is compressed with CHUNK_END, possibly as the root.
+            if self.block_len as usize == BLAKE3_BLOCK_LEN {
+                let block_words = words_from_le_bytes(&self.block);
+                let words = portable::compress(
+                    &self.cv,
+                    &block_words,
+                    self.chunk_counter,
+                    BLAKE3_BLOCK_LEN as u32,
+                    self.flags | self.start_flag(),
+                );
+                self.cv.copy_from_slice(&words[..8]);
+                self.blocks_compressed += 1;
+                self.block = [0; BLAKE3_BLOCK_LEN];
+                self.block_len = 0;
+            }
+
+            let want = BLAKE3_BLOCK_LEN - self.block_len as usize;
+            let take = want.min(input.len());
+            self.block[self.block_len as usize..][..take].copy_from_slice(&input[..take]);
+            self.block_len += take as u8;
+            input = &input[take..];
+        }
+    }
+
+    pub(crate) fn output(&self) -> Output {
+        Output {
+            input_cv: self.cv,
+            block_words: words_from_le_bytes(&self.block),
+            counter: self.chunk_counter,
+            block_len: self.block_len as u32,
+            flags: self.flags | self.start_flag() | CHUNK_END,
+        }
+    }
+}
+
+fn push_words(out: &mut Vec<u8>, words: &[u32; 8]) {
+    for word in words.iter() {
+        out.extend_from_slice(&word.to_le_bytes());
+    }
+}
+
+///
+/// An incremental BLAKE3 hasher.
+///
+/// # Description
+///
+/// The hasher accepts input in pieces of any size. Each call to `update` that carries more
+/// than a few chunks of input hashes them with the SIMD backend of the CPU.
+///
+#[derive(Clone)]
+pub struct Blake3Hasher {
+    pub(crate) key: [u32; 8],
+    pub(crate) chunk_state: ChunkState,
+    pub(crate) cv_stack: [[u32; 8]; MAX_DEPTH],
+    pub(crate) cv_stack_len: u8,
+    pub(crate) flags: u32,
+}
+
+impl Blake3Hasher {
+    fn new_internal(key: &[u32; 8], flags: u32) -> Blake3Hasher {
+        Blake3Hasher {
+            key: *key,
+            chunk_state: ChunkState::new(key, 0, flags),
+            cv_stack: [[0; 8]; MAX_DEPTH],
+            cv_stack_len: 0,
+            flags,
+        }
+    }
+
+    ///
+    /// Creates a hasher for the default hash mode.
+    ///
+    pub fn new() -> Blake3Hasher {
+        Self::new_internal(&IV, 0)
+    }
+
+    ///
+    /// Creates a hasher for the keyed hash mode, a MAC or PRF under a 256-bit key.
+    ///
+    pub fn new_keyed(key: &[u8; BLAKE3_KEY_LEN]) -> Blake3Hasher {
+        Self::new_internal(&words_from_le_bytes(key), KEYED_HASH)
+    }
+
+    ///
+    /// Creates a hasher for the key derivation mode.
+    ///
+    /// # Description
+    ///
+    /// The context string should be hardcoded, globally unique and application-specific. The
+    /// key material is the input of the hasher.
+    ///
+    pub fn new_derive_key(context: &str) -> Blake3Hasher {
+        let mut context_hasher = Self::new_internal(&IV, DERIVE_KEY_CONTEXT);
+        context_hasher.update(context.as_bytes());
+        let context_key = context_hasher.finalize();
+        Self::new_internal(&words_from_le_bytes(&context_key), DERIVE_KEY_MATERIAL)
+    }
+
+    ///
+    /// Adds input to the hash state.
+    ///
+    pub fn update(&mut self, input: &[u8]) -> &mut Blake3Hasher {
+        self.update_with(Blake3Backend::detect(), input)
+    }
+
+    ///
+    /// Adds input to the hash state, hashing whole chunks with the given backend.
+    ///
+    /// # Description
+    ///
+    /// Hashing with a backend other than the one returned by `Blake3Backend::detect` is only
+    /// safe if the CPU supports it.
+    ///
+    /// # Parameters
+    ///
+    /// **backend**
+    ///
+    /// The implementation for hashing whole chunks.
+    ///
+    /// **input**
+    ///
+    /// The input to add.
+    ///
+    pub fn update_with(&mut self, backend: Blake3Backend, mut input: &[u8]) -> &mut Blake3Hasher {
+        let degree = backend.degree();
+        let mut cvs = [[0_u32; 8]; avx512::DEGREE];
+        while !input.is_empty() {
+            if self.chunk_state.len() == BLAKE3_CHUNK_LEN {
+                self.flush_chunk();
+            }
+
+            // Whole chunks go through the backend, keeping back at least one byte so that
+            // the last chunk stays in the chunk state for finalization.
+            let batch = degree * BLAKE3_CHUNK_LEN;
+            if degree > 1 && self.chunk_state.len() == 0 && input.len() > batch {
+                let counter = self.chunk_state.chunk_counter;
+                backend.hash_chunks(
+                    &input[..batch],
+                    &self.key,
+                    counter,
+                    self.flags,
+                    &mut cvs[..degree],
+                );
+                for (i, cv) in cvs[..degree].iter().enumerate() {
+                    self.push_cv(*cv, counter + i as u64 + 1);
+                }
+                self.chunk_state = ChunkState::new(&self.key, counter + degree as u64, self.flags);
+                input = &input[batch..];
+                continue;
+            }
+
+            let want = BLAKE3_CHUNK_LEN - self.chunk_state.len();
+            let take = want.min(input.len());
+            self.chunk_state.update(&input[..take]);
+            input = &input[take..];
+        }
+        self
+    }
+
+    // Adds the chaining value of a completed subtree, merging every subtree it completes.
+    // `total` counts the subtrees of its size so far; its number of trailing zero bits is
+    // the number of merges.
+    pub(crate) fn push_cv(&mut self, mut cv: [u32; 8], mut total: u64) {
+        while total & 1 == 0 {
+            self.cv_stack_len -= 1;
+            let left = self.cv_stack[self.cv_stack_len as usize];
+            cv = parent_output(&left, &cv, &self.key, self.flags).chaining_value();
+            total >>= 1;
+        }
+        self.cv_stack[self.cv_stack_len as usize] = cv;
+        self.cv_stack_len += 1;
+    }
+
+    // Moves a full chunk state into the tree.
+    pub(crate) fn flush_chunk(&mut self) {
+        let cv = self.chunk_state.output().chaining_value();
+        let total_chunks = self.chunk_state.chunk_counter + 1;
+        self.push_cv(cv, total_chunks);
+        self.chunk_state = ChunkState::new(&self.key, total_chunks, self.flags);
+    }
+
+    // Adds the chaining value of a subtree of 2^level chunks that follows the input so far.
+    // The chunk state must be empty and its counter a multiple of 2^level.
+    #[cfg(feature = "thread")]
+    pub(crate) fn push_subtree_cv(&mut self, cv: [u32; 8], level: u32) {
+        let counter = self.chunk_state.chunk_counter;
+        debug_assert_eq!(self.chunk_state.len(), 0);
+        debug_assert_eq!(counter & ((1 << level) - 1), 0);
+        self.push_cv(cv, (counter >> level) + 1);
+        self.chunk_state = ChunkState::new(&self.key, counter + (1 << level), self.flags);
+    }
+
+    fn root_output(&self) -> Output {
+        let mut output = self.chunk_state.output();
+        for left in self.cv_stack[..self.cv_stack_len as usize].iter().rev() {
+            output = parent_output(left, &output.chaining_value(), &self.key, self.flags);
+        }
+        output
+    }
+
+    ///
+    /// Returns the 256-bit hash of the input so far.
+    ///
+    /// # Description
+    ///
+    /// The hasher is left unchanged and more input can be added afterwards.
+    ///
+    pub fn finalize(&self) -> Blake3Hash {
+        let mut hash = [0_u8; BLAKE3_OUT_LEN];
+        self.root_output().root_output_bytes(&mut hash);
+        hash
+    }
+
+    ///
+    /// Fills `out` with output of any length. The first 32 bytes equal `finalize`.
+    ///
+    pub fn finalize_xof(&self, out: &mut [u8]) {
+        self.root_output().root_output_bytes(out);
+    }
+
+    ///
+    /// Returns the number of input bytes so far.
+    ///
+    pub fn count(&self) -> u64 {
+        self.chunk_state.chunk_counter * BLAKE3_CHUNK_LEN as u64 + self.chunk_state.len() as u64
+    }
+
+    ///
+    /// Exports the hash state, to continue hashing later with `import_state`.
+    ///
+    /// # Description
+    ///
+    /// The state holds the input of the current chunk and, in the keyed modes, the key.
+    /// It must not leave the enclave unsealed.
+    ///
+    pub fn export_state(&self) -> Vec<u8> {
+        let stack_len = self.cv_stack_len as usize;
+        let mut state = Vec::with_capacity(STATE_FIXED_LEN + stack_len * BLAKE3_OUT_LEN);
+        state.push(STATE_VERSION);
+        state.extend_from_slice(&self.flags.to_le_bytes());
+        push_words(&mut state, &self.key);
+        state.extend_from_slice(&self.chunk_state.chunk_counter.to_le_bytes());
+        push_words(&mut state, &self.chunk_state.cv);
+        state.push(self.chunk_state.blocks_compressed);
+        state.push(self.chunk_state.block_len);
+        state.extend_from_slice(&self.chunk_state.block);
+        state.push(self.cv_stack_len);
+        for cv in self.cv_stack[..stack_len].iter() {
+            push_words(&mut state, cv);
+        }
+        state
+    }
+
+    ///
+    /// Restores a hasher from a state returned by `export_state`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The state is malformed or from an unsupported version.
+    ///
+    pub fn import_state(state: &[u8]) -> SgxResult<Blake3Hasher> {
+        if state.len() < STATE_FIXED_LEN || state[0] != STATE_VERSION {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let flags = u32::from_le_bytes([state[1], state[2], state[3], state[4]]);
+        let key: [u32; 8] = words_from_le_bytes(&state[5..37]);
+        let mut counter = [0_u8; 8];
+        counter.copy_from_slice(&state[37..45]);
+        let chunk_counter = u64::from_le_bytes(counter);
+        let cv: [u32; 8] = words_from_le_bytes(&state[45..77]);
+        let blocks_compressed = state[77];
+        let block_len = state[78];
+        let mut block = [0_u8; BLAKE3_BLOCK_LEN];
+        block.copy_from_slice(&state[79..143]);
+        let cv_stack_len = state[143];
+
+        // One stack entry per set bit of the number of completed chunks, as push_cv leaves it.
+        let valid_flags = [0, KEYED_HASH, DERIVE_KEY_CONTEXT, DERIVE_KEY_MATERIAL];
+        if !valid_flags.contains(&flags)
+            || block_len as usize > BLAKE3_BLOCK_LEN
+            || (blocks_compressed as usize + 1) * BLAKE3_BLOCK_LEN > BLAKE3_CHUNK_LEN
+            || block[block_len as usize..].iter().any(|b| *b != 0)
+            || cv_stack_len as u32 != chunk_counter.count_ones()
+            || cv_stack_len as usize >= MAX_DEPTH
+            || state.len() != STATE_FIXED_LEN + cv_stack_len as usize * BLAKE3_OUT_LEN
+        {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+
+        let mut hasher = Blake3Hasher::new_internal(&key, flags);
+        hasher.chunk_state = ChunkState {
+            cv,
+            chunk_counter,
+            block,
+            block_len,
+            blocks_compressed,
+            flags,
+        };
+        for (entry, bytes) in hasher
+            .cv_stack
+            .iter_mut()
+            .zip(state[STATE_FIXED_LEN..].chunks_exact(BLAKE3_OUT_LEN))
+        {
+            *entry = words_from_le_bytes(bytes);
+        }
+        hasher.cv_stack_len = cv_stack_len;
+        Ok(hasher)
+    }
+
+    ///
+    /// Discards the input so far, keeping the key and mode.
+    ///
+    pub fn reset(&mut self) -> &mut Blake3Hasher {
+        self.chunk_state = ChunkState::new(&self.key, 0, self.flags);
+        self.cv_stack_len = 0;
+        self
+    }
+}
+
+// Returns the chaining value of the subtree over `input`, whole chunks numbered from
+// `chunk_counter`. The number of chunks must be a power of two that divides the counter.
+#[cfg(feature = "thread")]
+pub(crate) fn hash_subtree(
+    key: &[u32; 8],
+    flags: u32,
+    backend: Blake3Backend,
+    input: &[u8],
+    chunk_counter: u64,
+) -> [u32; 8] {
+    let mut hasher = Blake3Hasher::new_internal(key, flags);
+    hasher.chunk_state = ChunkState::new(key, chunk_counter, flags);
+    hasher.update_with(backend, input);
+    hasher.root_output().chaining_value()
+}
+
+impl Default for Blake3Hasher {
+    fn default() -> Blake3Hasher {
+        Blake3Hasher::new()
+    }
+}
+
+///
+/// The rsgx_blake3_slice function hashes a slice with BLAKE3.
+///
+pub fn rsgx_blake3_slice(src: &[u8]) -> Blake3Hash {
+    Blake3Hasher::new().update(src).finalize()
+}
+
+///
+/// The rsgx_blake3_keyed_slice function computes the keyed BLAKE3 hash of a slice.
+///
+pub fn rsgx_blake3_keyed_slice(key: &[u8; BLAKE3_KEY_LEN], src: &[u8]) -> Blake3Hash {
+    Blake3Hasher::new_keyed(key).update(src).finalize()
+}
+
+///
+/// The rsgx_blake3_derive_key function derives a 256-bit key from key material under a
+/// context string.
+///
+pub fn rsgx_blake3_derive_key(context: &str, key_material: &[u8]) -> [u8; BLAKE3_KEY_LEN] {
+    Blake3Hasher::new_derive_key(context)
+        .update(key_material)
+        .finalize()
+}
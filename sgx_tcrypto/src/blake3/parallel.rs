@@ -0,0 +1,208 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use super::{hash_subtree, Blake3Backend, Blake3Hash, Blake3Hasher, BLAKE3_CHUNK_LEN};
+use alloc::vec::Vec;
+use std::panic;
+use std::thread;
+
+// Pieces smaller than this are not worth a thread.
+const MIN_PIECE_CHUNKS: usize = 64;
+
+///
+/// A BLAKE3 hasher that hashes large inputs on several threads.
+///
+/// # Description
+///
+/// `update` splits the whole chunks of its input into equal subtrees of a power of two
+/// chunks each and hashes groups of subtrees on worker threads, each of which takes a TCS
+/// of its own. The calling thread hashes the first group. Workers that cannot be started,
+/// for example because no TCS is free, have their groups hashed by the calling thread.
+///
+/// The output is the BLAKE3 output of the whole input, whichever the number of workers.
+///
+#[derive(Clone)]
+pub struct ParallelHasher {
+    hasher: Blake3Hasher,
+    workers: usize,
+}
+
+impl ParallelHasher {
+    ///
+    /// Creates a hasher for the default hash mode, with one worker per available thread.
+    ///
+    pub fn new() -> ParallelHasher {
+        Self::from_hasher(Blake3Hasher::new())
+    }
+
+    ///
+    /// Continues hashing from `hasher`, in whichever mode it was created.
+    ///
+    pub fn from_hasher(hasher: Blake3Hasher) -> ParallelHasher {
+        let workers = thread::available_parallelism().map_or(1, |n| n.get());
+        ParallelHasher { hasher, workers }
+    }
+
+    ///
+    /// Sets the number of threads, the calling thread included.
+    ///
+    pub fn workers(mut self, workers: usize) -> ParallelHasher {
+        self.workers = workers.max(1);
+        self
+    }
+
+    ///
+    /// Adds input to the hash state.
+    ///
+    pub fn update(&mut self, mut input: &[u8]) -> &mut ParallelHasher {
+        let backend = Blake3Backend::detect();
+
+        // Complete the current chunk, so that the rest starts at a chunk boundary.
+        let partial = self.hasher.chunk_state.len() % BLAKE3_CHUNK_LEN;
+        if partial != 0 {
+            let take = (BLAKE3_CHUNK_LEN - partial).min(input.len());
+            self.hasher.update_with(backend, &input[..take]);
+            input = &input[take..];
+        }
+
+        // The last chunk always stays in the chunk state for finalization.
+        let whole_chunks = input.len().saturating_sub(1) / BLAKE3_CHUNK_LEN;
+        let per_worker = whole_chunks / self.workers;
+        if self.workers == 1 || per_worker < MIN_PIECE_CHUNKS {
+            self.hasher.update_with(backend, input);
+            return self;
+        }
+        if self.hasher.chunk_state.len() == BLAKE3_CHUNK_LEN {
+            self.hasher.flush_chunk();
+        }
+
+        // A subtree must start at a multiple of its size, so hash up to the next multiple
+        // first.
+        let level = usize::BITS - 1 - per_worker.leading_zeros();
+        let piece_chunks = 1_usize << level;
+        let counter = self.hasher.chunk_state.chunk_counter;
+        let misaligned = (counter as usize) & (piece_chunks - 1);
+        if misaligned != 0 {
+            let take = (piece_chunks - misaligned) * BLAKE3_CHUNK_LEN;
+            self.hasher.update_with(backend, &input[..take]);
+            self.hasher.flush_chunk();
+            input = &input[take..];
+        }
+
+        let pieces = (input.len() - 1) / BLAKE3_CHUNK_LEN / piece_chunks;
+        if pieces > 0 {
+            let piece_len = piece_chunks * BLAKE3_CHUNK_LEN;
+            let (body, rest) = input.split_at(pieces * piece_len);
+            for cv in self.hash_pieces(backend, body, piece_chunks) {
+                self.hasher.push_subtree_cv(cv, level);
+            }
+            input = rest;
+        }
+        self.hasher.update_with(backend, input);
+        self
+    }
+
+    // Hashes `body`, a whole number of subtrees of `piece_chunks` chunks each, into the
+    // subtree chaining values in order.
+    fn hash_pieces(
+        &self,
+        backend: Blake3Backend,
+        body: &[u8],
+        piece_chunks: usize,
+    ) -> Vec<[u32; 8]> {
+        let key = self.hasher.key;
+        let flags = self.hasher.flags;
+        let counter = self.hasher.chunk_state.chunk_counter;
+        let piece_len = piece_chunks * BLAKE3_CHUNK_LEN;
+        let pieces = body.len() / piece_len;
+        let group_len = (pieces + self.workers - 1) / self.workers * piece_len;
+
+        let hash_group = move |index: usize, group: &[u8]| -> Vec<[u32; 8]> {
+            let first = counter + (index * group_len / BLAKE3_CHUNK_LEN) as u64;
+            group
+                .chunks(piece_len)
+                .enumerate()
+                .map(|(i, piece)| {
+                    hash_subtree(
+                        &key,
+                        flags,
+                        backend,
+                        piece,
+                        first + (i * piece_chunks) as u64,
+                    )
+                })
+                .collect()
+        };
+
+        let groups: Vec<&[u8]> = body.chunks(group_len).collect();
+        thread::scope(|s| {
+            let handles: Vec<_> = groups
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(index, group)| {
+                    thread::Builder::new()
+                        .spawn_scoped(s, move || hash_group(index, group))
+                        .ok()
+                })
+                .collect();
+
+            let mut cvs = hash_group(0, groups[0]);
+            for ((index, group), handle) in groups.iter().enumerate().skip(1).zip(handles) {
+                match handle {
+                    Some(handle) => match handle.join() {
+                        Ok(group_cvs) => cvs.extend(group_cvs),
+                        Err(e) => panic::resume_unwind(e),
+                    },
+                    None => cvs.extend(hash_group(index, group)),
+                }
+            }
+            cvs
+        })
+    }
+
+    ///
+    /// Returns the 256-bit hash of the input so far.
+    ///
+    pub fn finalize(&self) -> Blake3Hash {
+        self.hasher.finalize()
+    }
+
+    ///
+    /// Fills `out` with output of any length. The first 32 bytes equal `finalize`.
+    ///
+    pub fn finalize_xof(&self, out: &mut [u8]) {
+        self.hasher.finalize_xof(out)
+    }
+
+    ///
+    /// Returns the underlying hasher, for example to export its state.
+    ///
+    pub fn hasher(&self) -> &Blake3Hasher {
+        &self.hasher
+    }
+
+    pub fn into_hasher(self) -> Blake3Hasher {
+        self.hasher
+    }
+}
+
+impl Default for ParallelHasher {
+    fn default() -> ParallelHasher {
+        ParallelHasher::new()
+    }
+}
@@ -18,6 +18,7 @@
 //!
 //! Cryptographic Functions
 //!
+use crate::ecdsa_nonce::{self, SgxEcdsaNonce};
 use core::cell::{Cell, RefCell};
 use core::mem;
 use core::ops::{DerefMut, Drop};
@@ -2856,6 +2857,50 @@ impl SgxEccHandle {
         }
     }
 
+    ///
+    /// ecdsa_sign_slice_with_nonce computes a digital signature with a given private key over an input dataset,
+    /// choosing the nonce as `nonce` specifies.
+    ///
+    /// # Description
+    ///
+    /// `SgxEcdsaNonce::Random` signs as `ecdsa_sign_slice` does. `SgxEcdsaNonce::Deterministic` and
+    /// `SgxEcdsaNonce::Hedged` derive the nonce from the private key and the SHA-256 digest of the data as
+    /// RFC 6979 specifies, the latter with random bytes as additional input. All three produce standard
+    /// ECDSA signatures that `ecdsa_verify_slice` accepts.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The private key is zero or not below the group order.
+    ///
+    /// **SGX_ERROR_INVALID_STATE**
+    ///
+    /// The ECC state is not initialized.
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// The signature generation process failed due to an internal cryptography library failure.
+    ///
+    pub fn ecdsa_sign_slice_with_nonce<T>(
+        &self,
+        data: &[T],
+        private: &sgx_ec256_private_t,
+        nonce: SgxEcdsaNonce,
+    ) -> SgxResult<sgx_ec256_signature_t>
+    where
+        T: Copy + ContiguousMemory,
+    {
+        if !self.initflag.get() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_STATE);
+        }
+
+        match nonce {
+            SgxEcdsaNonce::Random => self.ecdsa_sign_slice(data, private),
+            _ => ecdsa_nonce::sign_hash(&rsgx_sha256_slice(data)?, private, nonce),
+        }
+    }
+
     ///
     /// ecdsa_verify_msg verifies the input digital signature with a given public key over an input dataset.
     ///
@@ -0,0 +1,321 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::crypto::SgxEccHandle;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use sgx_types::*;
+
+struct Entry<'a> {
+    data: &'a [u8],
+    public: &'a sgx_ec256_public_t,
+    signature: &'a sgx_ec256_signature_t,
+}
+
+///
+/// Collects ECDSA P-256 signatures and verifies them one after another.
+///
+/// # Description
+///
+/// This is not batch verification and is no faster than verifying each signature on its
+/// own. ECDSA signatures do not carry the point `R`, only its x-coordinate, so they cannot
+/// be combined into one verification equation the way `Ed25519BatchVerifier` combines
+/// Ed25519 signatures: every signature still costs a full verification. The verifier only
+/// spares the repeated setup: one ECC context serves all signatures, and every distinct
+/// public key is checked to be on the curve only once. A signature under a key that is not
+/// on the curve is invalid without further computation.
+///
+#[derive(Default)]
+pub struct SgxEcdsaMultiVerifier<'a> {
+    entries: Vec<Entry<'a>>,
+}
+
+impl<'a> SgxEcdsaMultiVerifier<'a> {
+    pub fn new() -> SgxEcdsaMultiVerifier<'a> {
+        SgxEcdsaMultiVerifier {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> SgxEcdsaMultiVerifier<'a> {
+        SgxEcdsaMultiVerifier {
+            entries: Vec::with_capacity(capacity),
+        }
+    }
+
+    ///
+    /// Adds the signature of `data` to the batch.
+    ///
+    pub fn push(
+        &mut self,
+        data: &'a [u8],
+        public: &'a sgx_ec256_public_t,
+        signature: &'a sgx_ec256_signature_t,
+    ) -> &mut SgxEcdsaMultiVerifier<'a> {
+        self.entries.push(Entry {
+            data,
+            public,
+            signature,
+        });
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    ///
+    /// Verifies all signatures of the batch, stopping at the first invalid one.
+    ///
+    /// # Return value
+    ///
+    /// **true**
+    ///
+    /// All signatures are valid. An empty batch is valid.
+    ///
+    /// **false**
+    ///
+    /// At least one signature is invalid. Use `verify_each` to find which.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_OUT_OF_MEMORY**
+    ///
+    /// Not enough memory is available to complete this operation.
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// The verification process failed due to an internal cryptography library failure.
+    ///
+    pub fn verify(&self) -> SgxResult<bool> {
+        let mut valid = true;
+        self.run(|_, result| {
+            valid = result;
+            result
+        })?;
+        Ok(valid)
+    }
+
+    ///
+    /// Verifies the signatures of the batch and reports the validity of each.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_OUT_OF_MEMORY**
+    ///
+    /// Not enough memory is available to complete this operation.
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// The verification process failed due to an internal cryptography library failure.
+    ///
+    pub fn verify_each(&self) -> SgxResult<Vec<bool>> {
+        let mut results = vec![false; self.entries.len()];
+        self.run(|index, result| {
+            results[index] = result;
+            true
+        })?;
+        Ok(results)
+    }
+
+    // Verifies the entries in order, passing each result to `f` until it returns false.
+    fn run<F>(&self, mut f: F) -> SgxError
+    where
+        F: FnMut(usize, bool) -> bool,
+    {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+        let handle = SgxEccHandle::new();
+        handle.open()?;
+
+        let mut on_curve = BTreeMap::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            let key = (entry.public.gx, entry.public.gy);
+            let key_valid = match on_curve.get(&key) {
+                Some(valid) => *valid,
+                None => {
+                    let valid = handle.check_point(entry.public)?;
+                    on_curve.insert(key, valid);
+                    valid
+                }
+            };
+            let result = key_valid
+                && handle.ecdsa_verify_slice(entry.data, entry.public, entry.signature)?;
+            if !f(index, result) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::rsgx_hex_decode;
+
+    // RFC 6979, appendix A.2.5: the P-256 key and its SHA-256 signatures.
+    const UX: &str = "60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6";
+    const UY: &str = "7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299";
+    const SIGNATURES: [(&[u8], &str, &str); 2] = [
+        (
+            b"sample",
+            "efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716",
+            "f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8",
+        ),
+        (
+            b"test",
+            "f1abb023518351cd71d881567b1ea663ed3efcf6c5132b354f28d3b0b7d38367",
+            "019f4113742a2b14bd25926b49c649155f267e60d3814b4c0cc84250e46f0083",
+        ),
+    ];
+    // The group order n, little-endian.
+    const ORDER: [u8; 32] = [
+        0x51, 0x25, 0x63, 0xfc, 0xc2, 0xca, 0xb9, 0xf3, 0x84, 0x9e, 0x17, 0xa7, 0xad, 0xfa, 0xe6,
+        0xbc, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff,
+        0xff, 0xff,
+    ];
+
+    fn le_bytes(hex: &str) -> [u8; 32] {
+        let mut bytes = [0_u8; 32];
+        bytes.copy_from_slice(&rsgx_hex_decode(hex).unwrap());
+        bytes.reverse();
+        bytes
+    }
+
+    fn words(bytes: &[u8; 32]) -> [u32; 8] {
+        let mut words = [0_u32; 8];
+        for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+            *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        words
+    }
+
+    fn public() -> sgx_ec256_public_t {
+        sgx_ec256_public_t {
+            gx: le_bytes(UX),
+            gy: le_bytes(UY),
+        }
+    }
+
+    fn signatures() -> Vec<(&'static [u8], sgx_ec256_signature_t)> {
+        SIGNATURES
+            .iter()
+            .map(|(msg, r, s)| {
+                let signature = sgx_ec256_signature_t {
+                    x: words(&le_bytes(r)),
+                    y: words(&le_bytes(s)),
+                };
+                (*msg, signature)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rfc6979_signatures() {
+        let public = public();
+        let signatures = signatures();
+        let mut verifier = SgxEcdsaMultiVerifier::new();
+        assert!(verifier.verify().unwrap());
+        for (msg, signature) in signatures.iter() {
+            verifier.push(msg, &public, signature);
+        }
+        assert_eq!(verifier.len(), 2);
+        assert!(verifier.verify().unwrap());
+        assert_eq!(verifier.verify_each().unwrap(), vec![true, true]);
+    }
+
+    #[test]
+    fn rejects_bad_signatures() {
+        let public = public();
+        let signatures = signatures();
+        let (msg, good) = signatures[0];
+        let mut bad_r = good;
+        bad_r.x[0] ^= 1;
+        let mut bad_s = good;
+        bad_s.y[7] ^= 0x8000_0000;
+        let zero = sgx_ec256_signature_t {
+            x: [0; 8],
+            y: good.y,
+        };
+
+        let mut verifier = SgxEcdsaMultiVerifier::with_capacity(6);
+        verifier
+            .push(msg, &public, &good)
+            .push(msg, &public, &bad_r)
+            .push(msg, &public, &bad_s)
+            .push(msg, &public, &zero)
+            .push(signatures[1].0, &public, &good)
+            .push(signatures[1].0, &public, &signatures[1].1);
+        assert!(!verifier.verify().unwrap());
+        assert_eq!(
+            verifier.verify_each().unwrap(),
+            vec![true, false, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_scalars() {
+        // r and s must lie in [1, n - 1]; n and 2^256 - 1 are not reduced modulo n.
+        let public = public();
+        let (msg, good) = signatures()[0];
+        let mut signatures = Vec::new();
+        for bad in [ORDER, [0xff; 32]] {
+            signatures.push(sgx_ec256_signature_t {
+                x: words(&bad),
+                y: good.y,
+            });
+            signatures.push(sgx_ec256_signature_t {
+                x: good.x,
+                y: words(&bad),
+            });
+        }
+
+        let mut verifier = SgxEcdsaMultiVerifier::new();
+        for signature in signatures.iter() {
+            verifier.push(msg, &public, signature);
+        }
+        assert_eq!(verifier.verify_each().unwrap(), vec![false; 4]);
+    }
+
+    #[test]
+    fn rejects_key_off_curve() {
+        let public = public();
+        let mut off_curve = public;
+        off_curve.gy[0] ^= 1;
+        let signatures = signatures();
+        let (msg, signature) = signatures[0];
+
+        let mut verifier = SgxEcdsaMultiVerifier::new();
+        verifier
+            .push(msg, &off_curve, &signature)
+            .push(msg, &public, &signature)
+            .push(msg, &off_curve, &signature);
+        assert_eq!(verifier.verify_each().unwrap(), vec![false, true, false]);
+        verifier.clear();
+        assert!(verifier.is_empty());
+    }
+}
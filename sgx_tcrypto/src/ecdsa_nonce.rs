@@ -0,0 +1,227 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::p256::{rfc6979_nonce, wipe, Point, Scalar};
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+///
+/// How ECDSA signing chooses its per-signature nonce.
+///
+/// # Description
+///
+/// A nonce that repeats, or that an attacker can predict or bias, reveals the private key.
+/// `Random` relies entirely on the random number generator. `Deterministic` derives the
+/// nonce from the key and the message digest as RFC 6979 specifies, so it needs no
+/// randomness at all; signing the same message twice gives the same signature. `Hedged`
+/// also mixes 32 bytes from RDRAND into the derivation (RFC 6979, section 3.6): the nonce
+/// stays secret and unique if either the key or the random bytes are, and signatures of
+/// the same message differ.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SgxEcdsaNonce {
+    /// A random nonce, chosen by the SDK's signing function.
+    Random,
+    /// The deterministic nonce of RFC 6979.
+    Deterministic,
+    /// The nonce of RFC 6979 with fresh random bytes as additional input.
+    Hedged,
+}
+
+impl Default for SgxEcdsaNonce {
+    fn default() -> Self {
+        SgxEcdsaNonce::Random
+    }
+}
+
+// Signs a SHA-256 digest with a nonce derived as RFC 6979 specifies, with `extra` as
+// additional input.
+fn sign_with_rfc6979(
+    hash: &sgx_sha256_hash_t,
+    private: &sgx_ec256_private_t,
+    extra: &[u8],
+) -> SgxResult<sgx_ec256_signature_t> {
+    let mut bytes = private.r;
+    bytes.reverse();
+    let x = Scalar::from_bytes_be(&bytes);
+    wipe(&mut bytes);
+    let mut x = match x {
+        Some(x) if !x.is_zero() => x,
+        _ => return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
+    };
+    let h = Scalar::from_bytes_be_reduced(hash);
+
+    let signature = rfc6979_nonce(&x, hash, extra).and_then(|mut k| {
+        let r = Point::GENERATOR
+            .mul_ct(&k)
+            .to_affine()
+            .map(|(rx, _)| Scalar::from_bytes_be_reduced(&rx));
+        let s = r.map(|r| k.invert().mul(&h.add(&r.mul(&x))));
+        k.wipe();
+        match (r, s) {
+            // r = 0 or s = 0 happens with probability about 2^-256.
+            (Some(r), Some(s)) if !r.is_zero() && !s.is_zero() => Ok(sgx_ec256_signature_t {
+                x: to_words(&r),
+                y: to_words(&s),
+            }),
+            _ => Err(sgx_status_t::SGX_ERROR_UNEXPECTED),
+        }
+    });
+    x.wipe();
+    signature
+}
+
+fn to_words(s: &Scalar) -> [u32; 8] {
+    let mut bytes = s.to_bytes_be();
+    bytes.reverse();
+    let mut words = [0_u32; 8];
+    for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+        let mut b = [0_u8; 4];
+        b.copy_from_slice(chunk);
+        *word = u32::from_le_bytes(b);
+    }
+    words
+}
+
+// Signs a SHA-256 digest with a `Deterministic` or `Hedged` nonce.
+pub(crate) fn sign_hash(
+    hash: &sgx_sha256_hash_t,
+    private: &sgx_ec256_private_t,
+    nonce: SgxEcdsaNonce,
+) -> SgxResult<sgx_ec256_signature_t> {
+    match nonce {
+        SgxEcdsaNonce::Hedged => {
+            let mut extra = [0_u8; 32];
+            rsgx_read_rand(&mut extra)?;
+            let signature = sign_with_rfc6979(hash, private, &extra);
+            wipe(&mut extra);
+            signature
+        }
+        _ => sign_with_rfc6979(hash, private, &[]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{rsgx_sha256_slice, SgxEccHandle};
+    use crate::encoding::rsgx_hex_decode;
+
+    fn le_bytes(hex: &str) -> [u8; 32] {
+        let mut bytes = [0_u8; 32];
+        bytes.copy_from_slice(&rsgx_hex_decode(hex).unwrap());
+        bytes.reverse();
+        bytes
+    }
+
+    fn scalar_words(hex: &str) -> [u32; 8] {
+        let mut bytes = [0_u8; 32];
+        bytes.copy_from_slice(&rsgx_hex_decode(hex).unwrap());
+        to_words(&Scalar::from_bytes_be(&bytes).unwrap())
+    }
+
+    // RFC 6979, appendix A.2.5: the P-256 key, its public key U and its SHA-256
+    // signatures.
+    fn private() -> sgx_ec256_private_t {
+        sgx_ec256_private_t {
+            r: le_bytes("c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721"),
+        }
+    }
+
+    fn public() -> sgx_ec256_public_t {
+        sgx_ec256_public_t {
+            gx: le_bytes("60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6"),
+            gy: le_bytes("7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299"),
+        }
+    }
+
+    const SIGNATURES: [(&[u8], &str, &str); 2] = [
+        (
+            b"sample",
+            "efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716",
+            "f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8",
+        ),
+        (
+            b"test",
+            "f1abb023518351cd71d881567b1ea663ed3efcf6c5132b354f28d3b0b7d38367",
+            "019f4113742a2b14bd25926b49c649155f267e60d3814b4c0cc84250e46f0083",
+        ),
+    ];
+
+    fn verifies(message: &[u8], signature: &sgx_ec256_signature_t) -> bool {
+        let ecc = SgxEccHandle::new();
+        ecc.open().unwrap();
+        ecc.ecdsa_verify_slice(message, &public(), signature)
+            .unwrap()
+    }
+
+    #[test]
+    fn rfc6979_signatures() {
+        for (message, r, s) in SIGNATURES.iter() {
+            let hash = rsgx_sha256_slice(message).unwrap();
+            let signature = sign_hash(&hash, &private(), SgxEcdsaNonce::Deterministic).unwrap();
+            assert_eq!(signature.x, scalar_words(r));
+            assert_eq!(signature.y, scalar_words(s));
+            assert!(verifies(message, &signature));
+        }
+    }
+
+    #[test]
+    fn additional_input() {
+        // "sample" with the bytes 0x00..0x1f as additional input, from an independent
+        // implementation of RFC 6979, section 3.6.
+        let hash = rsgx_sha256_slice(b"sample").unwrap();
+        let extra: [u8; 32] = core::array::from_fn(|i| i as u8);
+        let signature = sign_with_rfc6979(&hash, &private(), &extra).unwrap();
+        assert_eq!(
+            signature.x,
+            scalar_words("25404cfdb1228f680881e195dae0665f43f988c40cbc4e23927810d7c4635d74")
+        );
+        assert_eq!(
+            signature.y,
+            scalar_words("8f076e7b9ea4bde92fb16b5cf25d0d3656db01a6e19c885b53cb8754f1b819c3")
+        );
+        assert!(verifies(b"sample", &signature));
+    }
+
+    #[test]
+    fn hedged_signatures() {
+        let hash = rsgx_sha256_slice(b"sample").unwrap();
+        let deterministic = sign_hash(&hash, &private(), SgxEcdsaNonce::Deterministic).unwrap();
+        let first = sign_hash(&hash, &private(), SgxEcdsaNonce::Hedged).unwrap();
+        let second = sign_hash(&hash, &private(), SgxEcdsaNonce::Hedged).unwrap();
+        assert_ne!(first.x, deterministic.x);
+        assert_ne!(first.x, second.x);
+        assert!(verifies(b"sample", &first));
+        assert!(verifies(b"sample", &second));
+    }
+
+    #[test]
+    fn rejects_bad_keys() {
+        let hash = rsgx_sha256_slice(b"sample").unwrap();
+        let zero = sgx_ec256_private_t::default();
+        let order = sgx_ec256_private_t {
+            r: le_bytes("ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551"),
+        };
+        for private in [zero, order].iter() {
+            assert_eq!(
+                sign_hash(&hash, private, SgxEcdsaNonce::Deterministic).err(),
+                Some(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+            );
+        }
+    }
+}
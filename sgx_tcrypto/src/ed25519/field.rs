@@ -0,0 +1,231 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+// Arithmetic in GF(2^255 - 19), in five limbs of 51 bits.
+//
+// The arithmetic is branch-free. Decoding and the comparisons are not, and are only used
+// on public values.
+
+const MASK: u64 = (1 << 51) - 1;
+
+#[derive(Copy, Clone)]
+pub(crate) struct Fe(pub(crate) [u64; 5]);
+
+impl Fe {
+    pub(crate) const ZERO: Fe = Fe([0, 0, 0, 0, 0]);
+    pub(crate) const ONE: Fe = Fe([1, 0, 0, 0, 0]);
+
+    // -121665 / 121666
+    pub(crate) const D: Fe = Fe([
+        929_955_233_495_203,
+        466_365_720_129_213,
+        1_662_059_464_998_953,
+        2_033_849_074_728_123,
+        1_442_794_654_840_575,
+    ]);
+    pub(crate) const D2: Fe = Fe([
+        1_859_910_466_990_425,
+        932_731_440_258_426,
+        1_072_319_116_312_658,
+        1_815_898_335_770_999,
+        633_789_495_995_903,
+    ]);
+    // 2^((p - 1) / 4)
+    pub(crate) const SQRT_M1: Fe = Fe([
+        1_718_705_420_411_056,
+        234_908_883_556_509,
+        2_233_514_472_574_048,
+        2_117_202_627_021_982,
+        765_476_049_583_133,
+    ]);
+
+    fn carry(mut l: [u64; 5]) -> Fe {
+        for i in 0..4 {
+            l[i + 1] += l[i] >> 51;
+            l[i] &= MASK;
+        }
+        l[0] += (l[4] >> 51) * 19;
+        l[4] &= MASK;
+        l[1] += l[0] >> 51;
+        l[0] &= MASK;
+        Fe(l)
+    }
+
+    pub(crate) fn add(&self, b: &Fe) -> Fe {
+        let (a, b) = (&self.0, &b.0);
+        Fe::carry([
+            a[0] + b[0],
+            a[1] + b[1],
+            a[2] + b[2],
+            a[3] + b[3],
+            a[4] + b[4],
+        ])
+    }
+
+    pub(crate) fn sub(&self, b: &Fe) -> Fe {
+        // Add 16p first so that no limb underflows.
+        let (a, b) = (&self.0, &b.0);
+        Fe::carry([
+            (a[0] + 36_028_797_018_963_664) - b[0],
+            (a[1] + 36_028_797_018_963_952) - b[1],
+            (a[2] + 36_028_797_018_963_952) - b[2],
+            (a[3] + 36_028_797_018_963_952) - b[3],
+            (a[4] + 36_028_797_018_963_952) - b[4],
+        ])
+    }
+
+    pub(crate) fn neg(&self) -> Fe {
+        Fe::ZERO.sub(self)
+    }
+
+    pub(crate) fn mul(&self, b: &Fe) -> Fe {
+        #[inline(always)]
+        fn m(x: u64, y: u64) -> u128 {
+            x as u128 * y as u128
+        }
+
+        let (a, b) = (&self.0, &b.0);
+        let b1_19 = b[1] * 19;
+        let b2_19 = b[2] * 19;
+        let b3_19 = b[3] * 19;
+        let b4_19 = b[4] * 19;
+
+        let c0 = m(a[0], b[0]) + m(a[4], b1_19) + m(a[3], b2_19) + m(a[2], b3_19) + m(a[1], b4_19);
+        let mut c1 =
+            m(a[1], b[0]) + m(a[0], b[1]) + m(a[4], b2_19) + m(a[3], b3_19) + m(a[2], b4_19);
+        let mut c2 =
+            m(a[2], b[0]) + m(a[1], b[1]) + m(a[0], b[2]) + m(a[4], b3_19) + m(a[3], b4_19);
+        let mut c3 = m(a[3], b[0]) + m(a[2], b[1]) + m(a[1], b[2]) + m(a[0], b[3]) + m(a[4], b4_19);
+        let mut c4 = m(a[4], b[0]) + m(a[3], b[1]) + m(a[2], b[2]) + m(a[1], b[3]) + m(a[0], b[4]);
+
+        c1 += c0 >> 51;
+        c2 += c1 >> 51;
+        c3 += c2 >> 51;
+        c4 += c3 >> 51;
+        let carry = (c4 >> 51) as u64;
+        Fe::carry([
+            (c0 as u64 & MASK) + carry * 19,
+            c1 as u64 & MASK,
+            c2 as u64 & MASK,
+            c3 as u64 & MASK,
+            c4 as u64 & MASK,
+        ])
+    }
+
+    pub(crate) fn square(&self) -> Fe {
+        self.mul(self)
+    }
+
+    fn pow2k(&self, k: u32) -> Fe {
+        let mut x = *self;
+        for _ in 0..k {
+            x = x.square();
+        }
+        x
+    }
+
+    // Returns self^(2^250 - 1).
+    fn pow22501(&self) -> Fe {
+        let z2 = self.square();
+        let z9 = self.mul(&z2.pow2k(2));
+        let z11 = z2.mul(&z9);
+        let z_5_0 = z9.mul(&z11.square());
+        let z_10_0 = z_5_0.pow2k(5).mul(&z_5_0);
+        let z_20_0 = z_10_0.pow2k(10).mul(&z_10_0);
+        let z_40_0 = z_20_0.pow2k(20).mul(&z_20_0);
+        let z_50_0 = z_40_0.pow2k(10).mul(&z_10_0);
+        let z_100_0 = z_50_0.pow2k(50).mul(&z_50_0);
+        let z_200_0 = z_100_0.pow2k(100).mul(&z_100_0);
+        z_200_0.pow2k(50).mul(&z_50_0)
+    }
+
+    // self^(p - 2), the inverse of a non-zero element.
+    pub(crate) fn invert(&self) -> Fe {
+        let z11 = self.mul(&self.square().pow2k(2)).mul(&self.square());
+        self.pow22501().pow2k(5).mul(&z11)
+    }
+
+    // Returns `b` if `choice` is 1 and `self` if it is 0, without branching.
+    pub(crate) fn select(&self, b: &Fe, choice: u64) -> Fe {
+        let mask = 0_u64.wrapping_sub(choice);
+        let mut l = self.0;
+        for (x, y) in l.iter_mut().zip(b.0.iter()) {
+            *x ^= mask & (*x ^ y);
+        }
+        Fe(l)
+    }
+
+    // self^((p - 5) / 8)
+    pub(crate) fn pow_p58(&self) -> Fe {
+        self.pow22501().pow2k(2).mul(self)
+    }
+
+    // Loads 255 bits, ignoring the top bit. The value may be non-canonical.
+    pub(crate) fn from_bytes(bytes: &[u8; 32]) -> Fe {
+        let load = |i: usize| {
+            let mut b = [0_u8; 8];
+            b.copy_from_slice(&bytes[i..i + 8]);
+            u64::from_le_bytes(b)
+        };
+        Fe([
+            load(0) & MASK,
+            (load(6) >> 3) & MASK,
+            (load(12) >> 6) & MASK,
+            (load(19) >> 1) & MASK,
+            (load(24) >> 12) & MASK,
+        ])
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; 32] {
+        let mut l = Fe::carry(self.0).0;
+        // Subtract p if the value is at least p, that is if adding 19 carries out of bit 255.
+        let mut q = (l[0] + 19) >> 51;
+        for limb in l[1..].iter() {
+            q = (limb + q) >> 51;
+        }
+        l[0] += 19 * q;
+        for i in 0..4 {
+            l[i + 1] += l[i] >> 51;
+            l[i] &= MASK;
+        }
+        l[4] &= MASK;
+
+        let mut bytes = [0_u8; 32];
+        let packed = [
+            l[0] | (l[1] << 51),
+            (l[1] >> 13) | (l[2] << 38),
+            (l[2] >> 26) | (l[3] << 25),
+            (l[3] >> 39) | (l[4] << 12),
+        ];
+        for (chunk, word) in bytes.chunks_exact_mut(8).zip(packed.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub(crate) fn is_zero(&self) -> bool {
+        self.to_bytes() == [0; 32]
+    }
+
+    pub(crate) fn is_negative(&self) -> bool {
+        self.to_bytes()[0] & 1 == 1
+    }
+
+    pub(crate) fn equals(&self, b: &Fe) -> bool {
+        self.to_bytes() == b.to_bytes()
+    }
+}
@@ -0,0 +1,488 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Ed25519
+//!
+//! Ed25519 signature verification (RFC 8032), one signature at a time or in batches.
+//!
+//! A batch is checked with a single random linear combination of the verification
+//! equations of its signatures,
+//!
+//! `[8]( sum(z_i * R_i) + sum(z_i * k_i * A_i) - (sum(z_i * s_i)) * B ) = 0`,
+//!
+//! with fresh random 128-bit `z_i`. All terms share one chain of point doublings, and the
+//! terms of signatures by the same public key are merged, which makes a batch several
+//! times cheaper than verifying its signatures one by one. A batch that holds an invalid
+//! signature passes with probability at most 2^-128.
+//!
+//! Both single and batch verification use the cofactored equation, so that a signature
+//! is accepted alone exactly when it is accepted in any batch. Non-canonical encodings of
+//! `S` and of points are rejected.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+mod field;
+pub(crate) mod point;
+mod scalar;
+mod sha512;
+
+use self::point::{multiscalar_mul, Point};
+use self::scalar::Scalar;
+use self::sha512::Sha512;
+
+pub const ED25519_PUBLIC_KEY_SIZE: usize = 32;
+pub const ED25519_SIGNATURE_SIZE: usize = 64;
+
+pub type Ed25519PublicKey = [u8; ED25519_PUBLIC_KEY_SIZE];
+pub type Ed25519Signature = [u8; ED25519_SIGNATURE_SIZE];
+
+// A signature decoded for verification: s * B = R + k * A.
+struct Decoded {
+    a: Point,
+    r: Point,
+    s: Scalar,
+    k: Scalar,
+}
+
+fn decode(
+    public_key: &Ed25519PublicKey,
+    msg: &[u8],
+    signature: &Ed25519Signature,
+) -> Option<Decoded> {
+    let mut r_bytes = [0_u8; 32];
+    let mut s_bytes = [0_u8; 32];
+    r_bytes.copy_from_slice(&signature[..32]);
+    s_bytes.copy_from_slice(&signature[32..]);
+
+    let s = Scalar::from_canonical_bytes(&s_bytes)?;
+    let a = Point::decompress(public_key)?;
+    let r = Point::decompress(&r_bytes)?;
+    let digest = Sha512::new()
+        .update(&r_bytes)
+        .update(public_key)
+        .update(msg)
+        .finalize();
+    let k = Scalar::from_bytes_mod_order(&digest);
+    Some(Decoded { a, r, s, k })
+}
+
+fn verify_decoded(decoded: &Decoded) -> bool {
+    let check = multiscalar_mul(
+        &[decoded.s, decoded.k.neg(), Scalar::ONE.neg()],
+        &[Point::BASEPOINT, decoded.a, decoded.r],
+    );
+    check.mul_by_cofactor().is_identity()
+}
+
+///
+/// The rsgx_ed25519_verify function verifies an Ed25519 signature.
+///
+/// # Parameters
+///
+/// **public_key**
+///
+/// The encoded public key of the signer.
+///
+/// **msg**
+///
+/// The signed message.
+///
+/// **signature**
+///
+/// The signature to verify.
+///
+/// # Return value
+///
+/// **true**
+///
+/// The signature is valid.
+///
+/// **false**
+///
+/// The signature or the public key is invalid.
+///
+pub fn rsgx_ed25519_verify(
+    public_key: &Ed25519PublicKey,
+    msg: &[u8],
+    signature: &Ed25519Signature,
+) -> SgxResult<bool> {
+    Ok(decode(public_key, msg, signature).map_or(false, |decoded| verify_decoded(&decoded)))
+}
+
+struct Entry<'a> {
+    public_key: &'a Ed25519PublicKey,
+    msg: &'a [u8],
+    signature: &'a Ed25519Signature,
+}
+
+///
+/// Collects Ed25519 signatures and verifies them together.
+///
+#[derive(Default)]
+pub struct Ed25519BatchVerifier<'a> {
+    entries: Vec<Entry<'a>>,
+}
+
+impl<'a> Ed25519BatchVerifier<'a> {
+    pub fn new() -> Ed25519BatchVerifier<'a> {
+        Ed25519BatchVerifier {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Ed25519BatchVerifier<'a> {
+        Ed25519BatchVerifier {
+            entries: Vec::with_capacity(capacity),
+        }
+    }
+
+    ///
+    /// Adds a signature to the batch.
+    ///
+    pub fn push(
+        &mut self,
+        public_key: &'a Ed25519PublicKey,
+        msg: &'a [u8],
+        signature: &'a Ed25519Signature,
+    ) -> &mut Ed25519BatchVerifier<'a> {
+        self.entries.push(Entry {
+            public_key,
+            msg,
+            signature,
+        });
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    ///
+    /// Verifies all signatures of the batch at once.
+    ///
+    /// # Return value
+    ///
+    /// **true**
+    ///
+    /// All signatures are valid. An empty batch is valid.
+    ///
+    /// **false**
+    ///
+    /// At least one signature or public key is invalid. Use `verify_each` to find which.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// The random coefficients could not be generated.
+    ///
+    pub fn verify(&self) -> SgxResult<bool> {
+        let mut decoded = Vec::with_capacity(self.entries.len());
+        for entry in self.entries.iter() {
+            match decode(entry.public_key, entry.msg, entry.signature) {
+                Some(d) => decoded.push(d),
+                None => return Ok(false),
+            }
+        }
+        match decoded.len() {
+            0 => return Ok(true),
+            1 => return Ok(verify_decoded(&decoded[0])),
+            _ => (),
+        }
+
+        let mut random = vec![0_u8; 16 * decoded.len()];
+        rsgx_read_rand(&mut random)?;
+
+        // The base point term, then one term per R and one per distinct public key.
+        let mut scalars = vec![Scalar::ZERO];
+        let mut points = vec![Point::BASEPOINT];
+        let mut key_terms = BTreeMap::new();
+        let mut base = Scalar::ZERO;
+        for ((d, entry), z) in decoded
+            .iter()
+            .zip(self.entries.iter())
+            .zip(random.chunks_exact(16))
+        {
+            let z = Scalar::from_bytes_mod_order(z);
+            base = base.add(&z.mul(&d.s));
+            scalars.push(z);
+            points.push(d.r);
+
+            let zk = z.mul(&d.k);
+            let index = *key_terms.entry(entry.public_key).or_insert_with(|| {
+                scalars.push(Scalar::ZERO);
+                points.push(d.a);
+                scalars.len() - 1
+            });
+            scalars[index] = scalars[index].add(&zk);
+        }
+        scalars[0] = base.neg();
+
+        Ok(multiscalar_mul(&scalars, &points)
+            .mul_by_cofactor()
+            .is_identity())
+    }
+
+    ///
+    /// Verifies the signatures of the batch and reports the validity of each.
+    ///
+    /// # Description
+    ///
+    /// The batch is first verified at once. Only if that fails are the signatures verified
+    /// one by one.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// The random coefficients could not be generated.
+    ///
+    pub fn verify_each(&self) -> SgxResult<Vec<bool>> {
+        if self.verify()? {
+            return Ok(vec![true; self.entries.len()]);
+        }
+        self.entries
+            .iter()
+            .map(|entry| rsgx_ed25519_verify(entry.public_key, entry.msg, entry.signature))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::point::Point;
+    use super::sha512::Sha512;
+    use super::*;
+    use crate::encoding::rsgx_hex_decode;
+
+    // RFC 8032, section 7.1: secret key, public key, message, signature.
+    const VECTORS: [(&str, &str, &str, &str); 4] = [
+        (
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "",
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+             5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+        ),
+        (
+            "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            "72",
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+             085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+        ),
+        (
+            "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+            "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+            "af82",
+            "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac\
+             18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+        ),
+        (
+            "833fe62409237b9d62ec77587520911e9a759cec1d19755b7da901b96dca3d42",
+            "ec172b93ad5e563bf4932c70e1245034c35467ef2efd4d64ebf819683467e2bf",
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            "dc2a4459e7369633a52b1bf277839a00201009a3efbf3ecb69bea2186c26b589\
+             09351fc9ac90b3ecfdfbc7c66431e0303dca179c138ac17ad9bef1177331a704",
+        ),
+    ];
+
+    // The group order L, little-endian.
+    const ORDER: [u8; 32] = [
+        0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde,
+        0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x10,
+    ];
+
+    struct Vector {
+        secret: [u8; 32],
+        public_key: Ed25519PublicKey,
+        msg: Vec<u8>,
+        signature: Ed25519Signature,
+    }
+
+    fn vectors() -> Vec<Vector> {
+        VECTORS
+            .iter()
+            .map(|(secret, public_key, msg, signature)| {
+                let mut v = Vector {
+                    secret: [0; 32],
+                    public_key: [0; 32],
+                    msg: rsgx_hex_decode(msg).unwrap(),
+                    signature: [0; 64],
+                };
+                v.secret.copy_from_slice(&rsgx_hex_decode(secret).unwrap());
+                v.public_key
+                    .copy_from_slice(&rsgx_hex_decode(public_key).unwrap());
+                v.signature
+                    .copy_from_slice(&rsgx_hex_decode(signature).unwrap());
+                v
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sha512_known_answers() {
+        let cases: [(&[u8], &str); 3] = [
+            (
+                b"",
+                "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce\
+                 47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+            ),
+            (
+                b"abc",
+                "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                 2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            ),
+            (
+                b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmno\
+                  ijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+                "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018\
+                 501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909",
+            ),
+        ];
+        for (msg, digest) in cases.iter() {
+            let one_shot = Sha512::new().update(msg).finalize();
+            assert_eq!(&one_shot[..], &rsgx_hex_decode(digest).unwrap()[..]);
+            let mut split = Sha512::new();
+            for chunk in msg.chunks(7) {
+                split.update(chunk);
+            }
+            assert_eq!(split.finalize(), one_shot);
+        }
+    }
+
+    #[test]
+    fn rfc8032_public_keys() {
+        // The public key is the encoding of [s]B, s the clamped lower half of SHA-512(secret).
+        for v in vectors() {
+            let digest = Sha512::new().update(&v.secret).finalize();
+            let mut s = [0_u8; 32];
+            s.copy_from_slice(&digest[..32]);
+            s[0] &= 248;
+            s[31] &= 127;
+            s[31] |= 64;
+            assert_eq!(Point::BASEPOINT.mul_ct(&s).compress(), v.public_key);
+        }
+    }
+
+    #[test]
+    fn rfc8032_signatures() {
+        let vectors = vectors();
+        let mut batch = Ed25519BatchVerifier::new();
+        for v in vectors.iter() {
+            assert!(rsgx_ed25519_verify(&v.public_key, &v.msg, &v.signature).unwrap());
+            batch.push(&v.public_key, &v.msg, &v.signature);
+        }
+        assert!(batch.verify().unwrap());
+        assert_eq!(batch.verify_each().unwrap(), vec![true; vectors.len()]);
+    }
+
+    #[test]
+    fn rejects_bad_signatures() {
+        let vectors = vectors();
+        let v = &vectors[2];
+        for bit in [0, 7, 255, 256, 300, 511] {
+            let mut signature = v.signature;
+            signature[bit / 8] ^= 1 << (bit % 8);
+            assert!(!rsgx_ed25519_verify(&v.public_key, &v.msg, &signature).unwrap());
+        }
+        let mut msg = v.msg.clone();
+        msg[0] ^= 1;
+        assert!(!rsgx_ed25519_verify(&v.public_key, &msg, &v.signature).unwrap());
+        assert!(!rsgx_ed25519_verify(&vectors[1].public_key, &v.msg, &v.signature).unwrap());
+        assert!(!rsgx_ed25519_verify(&v.public_key, &vectors[1].msg, &v.signature).unwrap());
+    }
+
+    #[test]
+    fn rejects_non_canonical_s() {
+        // S + L satisfies the verification equation, but S must be below L.
+        for v in vectors() {
+            let mut signature = v.signature;
+            let mut carry = 0_u16;
+            for (b, l) in signature[32..].iter_mut().zip(ORDER.iter()) {
+                let sum = *b as u16 + *l as u16 + carry;
+                *b = sum as u8;
+                carry = sum >> 8;
+            }
+            assert_eq!(carry, 0);
+            assert!(!rsgx_ed25519_verify(&v.public_key, &v.msg, &signature).unwrap());
+        }
+        let v = &vectors()[0];
+        let mut signature = v.signature;
+        signature[63] |= 0xe0;
+        assert!(!rsgx_ed25519_verify(&v.public_key, &v.msg, &signature).unwrap());
+    }
+
+    #[test]
+    fn rejects_non_canonical_points() {
+        // y = 0 encodes a point; y = p is the same field element, but not canonical.
+        let mut zero = [0_u8; 32];
+        assert!(Point::decompress(&zero).is_some());
+        zero[31] = 0x80;
+        assert!(Point::decompress(&zero).is_some());
+        let mut p = [0xff_u8; 32];
+        p[0] = 0xed;
+        p[31] = 0x7f;
+        assert!(Point::decompress(&p).is_none());
+
+        let v = &vectors()[0];
+        let mut signature = v.signature;
+        signature[..32].copy_from_slice(&p);
+        assert!(!rsgx_ed25519_verify(&v.public_key, &v.msg, &signature).unwrap());
+        assert!(!rsgx_ed25519_verify(&p, &v.msg, &v.signature).unwrap());
+    }
+
+    #[test]
+    fn batch_reports_invalid_signature() {
+        let vectors = vectors();
+        let mut bad = vectors[1].signature;
+        bad[40] ^= 0x10;
+        let mut batch = Ed25519BatchVerifier::with_capacity(vectors.len() + 1);
+        assert!(batch.verify().unwrap());
+        for (i, v) in vectors.iter().enumerate() {
+            let signature = if i == 1 { &bad } else { &v.signature };
+            batch.push(&v.public_key, &v.msg, signature);
+        }
+        // A second signature by the same key shares its key term.
+        batch.push(
+            &vectors[0].public_key,
+            &vectors[0].msg,
+            &vectors[0].signature,
+        );
+        assert_eq!(batch.len(), 5);
+        assert!(!batch.verify().unwrap());
+        assert_eq!(
+            batch.verify_each().unwrap(),
+            vec![true, false, true, true, true]
+        );
+        batch.clear();
+        assert!(batch.is_empty());
+    }
+}
@@ -0,0 +1,269 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+// Points of the twisted Edwards curve -x^2 + y^2 = 1 + d x^2 y^2 in extended coordinates
+// (X : Y : Z : T) with x = X/Z, y = Y/Z and xy = T/Z, with the formulas of RFC 8032,
+// section 5.1.4.
+
+use super::field::Fe;
+use super::scalar::Scalar;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::SgxResult;
+
+#[derive(Copy, Clone)]
+pub(crate) struct Point {
+    x: Fe,
+    y: Fe,
+    z: Fe,
+    t: Fe,
+}
+
+// A point prepared for addition: (Y + X, Y - X, 2Z, 2dT).
+#[derive(Copy, Clone)]
+pub(crate) struct Cached {
+    y_plus_x: Fe,
+    y_minus_x: Fe,
+    z2: Fe,
+    t2d: Fe,
+}
+
+// Width of the non-adjacent forms of the scalars; each point gets a table of its
+// 2^(NAF_WIDTH - 2) smallest odd multiples.
+const NAF_WIDTH: u32 = 5;
+const TABLE_SIZE: usize = 1 << (NAF_WIDTH - 2);
+
+impl Point {
+    pub(crate) const IDENTITY: Point = Point {
+        x: Fe::ZERO,
+        y: Fe::ONE,
+        z: Fe::ONE,
+        t: Fe::ZERO,
+    };
+
+    pub(crate) const BASEPOINT: Point = Point {
+        x: Fe([
+            1_738_742_601_995_546,
+            1_146_398_526_822_698,
+            2_070_867_633_025_821,
+            562_264_141_797_630,
+            587_772_402_128_613,
+        ]),
+        y: Fe([
+            1_801_439_850_948_184,
+            1_351_079_888_211_148,
+            450_359_962_737_049,
+            900_719_925_474_099,
+            1_801_439_850_948_198,
+        ]),
+        z: Fe::ONE,
+        t: Fe([
+            1_841_354_044_333_475,
+            16_398_895_984_059,
+            755_974_180_946_558,
+            900_171_276_175_154,
+            1_821_297_809_914_039,
+        ]),
+    };
+
+    // Decodes a point as RFC 8032, section 5.1.3 does, rejecting y >= p.
+    pub(crate) fn decompress(bytes: &[u8; 32]) -> Option<Point> {
+        let y = Fe::from_bytes(bytes);
+        let mut canonical = y.to_bytes();
+        canonical[31] |= bytes[31] & 0x80;
+        if canonical != *bytes {
+            return None;
+        }
+        let sign = bytes[31] >> 7 == 1;
+
+        let yy = y.square();
+        let u = yy.sub(&Fe::ONE);
+        let v = Fe::D.mul(&yy).add(&Fe::ONE);
+        let v3 = v.square().mul(&v);
+        let v7 = v3.square().mul(&v);
+        let mut x = u.mul(&v3).mul(&u.mul(&v7).pow_p58());
+
+        let vxx = v.mul(&x.square());
+        if !vxx.equals(&u) {
+            if vxx.equals(&u.neg()) {
+                x = x.mul(&Fe::SQRT_M1);
+            } else {
+                return None;
+            }
+        }
+        if x.is_zero() && sign {
+            return None;
+        }
+        if x.is_negative() != sign {
+            x = x.neg();
+        }
+        Some(Point {
+            x,
+            y,
+            z: Fe::ONE,
+            t: x.mul(&y),
+        })
+    }
+
+    // Encodes the point as RFC 8032, section 5.1.2 does.
+    pub(crate) fn compress(&self) -> [u8; 32] {
+        let z_inv = self.z.invert();
+        let x = self.x.mul(&z_inv);
+        let mut bytes = self.y.mul(&z_inv).to_bytes();
+        bytes[31] ^= (x.to_bytes()[0] & 1) << 7;
+        bytes
+    }
+
+    pub(crate) fn select(&self, b: &Point, choice: u64) -> Point {
+        Point {
+            x: self.x.select(&b.x, choice),
+            y: self.y.select(&b.y, choice),
+            z: self.z.select(&b.z, choice),
+            t: self.t.select(&b.t, choice),
+        }
+    }
+
+    // Computes scalar * self for a secret little-endian scalar, with a double-and-add-always
+    // ladder over all 256 bits, so that neither time nor memory access depends on it.
+    pub(crate) fn mul_ct(&self, scalar: &[u8; 32]) -> Point {
+        let cached = self.to_cached();
+        let mut acc = Point::IDENTITY;
+        for i in (0..256).rev() {
+            acc = acc.double();
+            let sum = acc.add(&cached);
+            acc = acc.select(&sum, ((scalar[i / 8] >> (i % 8)) & 1) as u64);
+        }
+        acc
+    }
+
+    pub(crate) fn to_cached(self) -> Cached {
+        Cached {
+            y_plus_x: self.y.add(&self.x),
+            y_minus_x: self.y.sub(&self.x),
+            z2: self.z.add(&self.z),
+            t2d: self.t.mul(&Fe::D2),
+        }
+    }
+
+    pub(crate) fn add(&self, q: &Cached) -> Point {
+        let a = self.y.sub(&self.x).mul(&q.y_minus_x);
+        let b = self.y.add(&self.x).mul(&q.y_plus_x);
+        let c = self.t.mul(&q.t2d);
+        let d = self.z.mul(&q.z2);
+        self.finish_add(a, b, c, d)
+    }
+
+    pub(crate) fn sub(&self, q: &Cached) -> Point {
+        let a = self.y.sub(&self.x).mul(&q.y_plus_x);
+        let b = self.y.add(&self.x).mul(&q.y_minus_x);
+        let c = self.t.mul(&q.t2d).neg();
+        let d = self.z.mul(&q.z2);
+        self.finish_add(a, b, c, d)
+    }
+
+    fn finish_add(&self, a: Fe, b: Fe, c: Fe, d: Fe) -> Point {
+        let e = b.sub(&a);
+        let f = d.sub(&c);
+        let g = d.add(&c);
+        let h = b.add(&a);
+        Point {
+            x: e.mul(&f),
+            y: g.mul(&h),
+            z: f.mul(&g),
+            t: e.mul(&h),
+        }
+    }
+
+    pub(crate) fn double(&self) -> Point {
+        let a = self.x.square();
+        let b = self.y.square();
+        let zz = self.z.square();
+        let c = zz.add(&zz);
+        let h = a.add(&b);
+        let e = h.sub(&self.x.add(&self.y).square());
+        let g = a.sub(&b);
+        let f = c.add(&g);
+        Point {
+            x: e.mul(&f),
+            y: g.mul(&h),
+            z: f.mul(&g),
+            t: e.mul(&h),
+        }
+    }
+
+    pub(crate) fn mul_by_cofactor(&self) -> Point {
+        self.double().double().double()
+    }
+
+    pub(crate) fn is_identity(&self) -> bool {
+        self.x.is_zero() && self.y.equals(&self.z)
+    }
+
+    // The odd multiples P, 3P, 5P, ... of the point.
+    fn odd_multiples(&self) -> [Cached; TABLE_SIZE] {
+        let double = self.double().to_cached();
+        let mut table = [self.to_cached(); TABLE_SIZE];
+        let mut current = *self;
+        for entry in table.iter_mut().skip(1) {
+            current = current.add(&double);
+            *entry = current.to_cached();
+        }
+        table
+    }
+}
+
+// A random secret scalar for `mul_ct`, clamped as X25519 does: a multiple of the cofactor,
+// below 2^255 and with bit 254 set.
+pub(crate) fn random_secret_scalar() -> SgxResult<[u8; 32]> {
+    let mut scalar = [0_u8; 32];
+    rsgx_read_rand(&mut scalar)?;
+    scalar[0] &= 248;
+    scalar[31] &= 127;
+    scalar[31] |= 64;
+    Ok(scalar)
+}
+
+// Computes the sum of scalars[i] * points[i], sharing one chain of doublings between all
+// terms (Straus' method, with a width-5 NAF per scalar).
+pub(crate) fn multiscalar_mul(scalars: &[Scalar], points: &[Point]) -> Point {
+    let nafs: Vec<[i8; 256]> = scalars
+        .iter()
+        .map(|s| s.non_adjacent_form(NAF_WIDTH))
+        .collect();
+    let tables: Vec<[Cached; TABLE_SIZE]> = points.iter().map(|p| p.odd_multiples()).collect();
+
+    let top = nafs
+        .iter()
+        .filter_map(|naf| naf.iter().rposition(|d| *d != 0))
+        .max();
+    let mut acc = Point::IDENTITY;
+    if let Some(top) = top {
+        for i in (0..=top).rev() {
+            acc = acc.double();
+            for (naf, table) in nafs.iter().zip(tables.iter()) {
+                let digit = naf[i];
+                match digit.cmp(&0) {
+                    Ordering::Greater => acc = acc.add(&table[(digit / 2) as usize]),
+                    Ordering::Less => acc = acc.sub(&table[(-digit / 2) as usize]),
+                    Ordering::Equal => (),
+                }
+            }
+        }
+    }
+    acc
+}
@@ -0,0 +1,178 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+// Integers modulo the group order L = 2^252 + 27742317777372353535851937790883648493, in
+// four little-endian 64-bit limbs.
+//
+// As with the field, only public values pass through here and nothing runs in constant
+// time.
+
+const L: [u64; 4] = [
+    0x5812_631a_5cf5_d3ed,
+    0x14de_f9de_a2f7_9cd6,
+    0,
+    0x1000_0000_0000_0000,
+];
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Scalar(pub(crate) [u64; 4]);
+
+fn geq_l(x: &[u64; 4]) -> bool {
+    for i in (0..4).rev() {
+        if x[i] != L[i] {
+            return x[i] > L[i];
+        }
+    }
+    true
+}
+
+fn sub_l(x: &mut [u64; 4]) {
+    let mut borrow = false;
+    for (limb, l) in x.iter_mut().zip(L.iter()) {
+        let (d, b1) = limb.overflowing_sub(*l);
+        let (d, b2) = d.overflowing_sub(borrow as u64);
+        *limb = d;
+        borrow = b1 || b2;
+    }
+}
+
+impl Scalar {
+    pub(crate) const ZERO: Scalar = Scalar([0; 4]);
+    pub(crate) const ONE: Scalar = Scalar([1, 0, 0, 0]);
+
+    // Reduces a little-endian integer of any length, one bit at a time.
+    pub(crate) fn from_bytes_mod_order(bytes: &[u8]) -> Scalar {
+        let mut r = [0_u64; 4];
+        for byte in bytes.iter().rev() {
+            for bit in (0..8).rev() {
+                // r < L < 2^253, so doubling cannot overflow.
+                r[3] = (r[3] << 1) | (r[2] >> 63);
+                r[2] = (r[2] << 1) | (r[1] >> 63);
+                r[1] = (r[1] << 1) | (r[0] >> 63);
+                r[0] = (r[0] << 1) | ((byte >> bit) & 1) as u64;
+                if geq_l(&r) {
+                    sub_l(&mut r);
+                }
+            }
+        }
+        Scalar(r)
+    }
+
+    // Rejects encodings of values of L and above, as RFC 8032 requires of S.
+    pub(crate) fn from_canonical_bytes(bytes: &[u8; 32]) -> Option<Scalar> {
+        let mut limbs = [0_u64; 4];
+        for (limb, chunk) in limbs.iter_mut().zip(bytes.chunks_exact(8)) {
+            let mut b = [0_u8; 8];
+            b.copy_from_slice(chunk);
+            *limb = u64::from_le_bytes(b);
+        }
+        if geq_l(&limbs) {
+            None
+        } else {
+            Some(Scalar(limbs))
+        }
+    }
+
+    pub(crate) fn add(&self, b: &Scalar) -> Scalar {
+        let mut r = [0_u64; 4];
+        let mut carry = 0_u128;
+        for ((r, x), y) in r.iter_mut().zip(self.0.iter()).zip(b.0.iter()) {
+            let s = *x as u128 + *y as u128 + carry;
+            *r = s as u64;
+            carry = s >> 64;
+        }
+        // Both are below L < 2^253, so the sum fits and one subtraction suffices.
+        if geq_l(&r) {
+            sub_l(&mut r);
+        }
+        Scalar(r)
+    }
+
+    pub(crate) fn neg(&self) -> Scalar {
+        let mut r = [0_u64; 4];
+        let mut borrow = false;
+        for i in 0..4 {
+            let (d, b1) = L[i].overflowing_sub(self.0[i]);
+            let (d, b2) = d.overflowing_sub(borrow as u64);
+            r[i] = d;
+            borrow = b1 || b2;
+        }
+        // L - 0 is L itself.
+        if geq_l(&r) {
+            sub_l(&mut r);
+        }
+        Scalar(r)
+    }
+
+    pub(crate) fn mul(&self, b: &Scalar) -> Scalar {
+        let mut wide = [0_u64; 8];
+        for i in 0..4 {
+            let mut carry = 0_u128;
+            for j in 0..4 {
+                let t = self.0[i] as u128 * b.0[j] as u128 + wide[i + j] as u128 + carry;
+                wide[i + j] = t as u64;
+                carry = t >> 64;
+            }
+            wide[i + 4] = carry as u64;
+        }
+        let mut bytes = [0_u8; 64];
+        for (chunk, limb) in bytes.chunks_exact_mut(8).zip(wide.iter()) {
+            chunk.copy_from_slice(&limb.to_le_bytes());
+        }
+        Scalar::from_bytes_mod_order(&bytes)
+    }
+
+    // The width-w non-adjacent form: odd digits in (-2^(w-1), 2^(w-1)), at least w - 1
+    // zeros between two nonzero digits.
+    pub(crate) fn non_adjacent_form(&self, w: u32) -> [i8; 256] {
+        let mut naf = [0_i8; 256];
+        let mut k = [self.0[0], self.0[1], self.0[2], self.0[3], 0];
+        let window = 1_u64 << w;
+        let mut pos = 0;
+        while k.iter().any(|limb| *limb != 0) {
+            if k[0] & 1 == 1 {
+                let mut digit = (k[0] & (window - 1)) as i64;
+                if digit >= (window / 2) as i64 {
+                    digit -= window as i64;
+                }
+                naf[pos] = digit as i8;
+                // k -= digit, leaving the low w bits zero.
+                if digit > 0 {
+                    let mut borrow = digit as u64;
+                    for limb in k.iter_mut() {
+                        let (d, b) = limb.overflowing_sub(borrow);
+                        *limb = d;
+                        borrow = b as u64;
+                    }
+                } else {
+                    let mut carry = (-digit) as u64;
+                    for limb in k.iter_mut() {
+                        let (s, c) = limb.overflowing_add(carry);
+                        *limb = s;
+                        carry = c as u64;
+                    }
+                }
+            }
+            for i in 0..4 {
+                k[i] = (k[i] >> 1) | (k[i + 1] << 63);
+            }
+            k[4] >>= 1;
+            pos += 1;
+        }
+        naf
+    }
+}
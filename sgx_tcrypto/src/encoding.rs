@@ -0,0 +1,355 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Encodings
+//!
+//! Hex, base64 (RFC 4648, with padding) and PEM (RFC 7468) for key material.
+//!
+//! The codecs compute every character from its value with arithmetic and masks instead of
+//! table lookups and branches, so that neither timing nor the cache reveals the encoded
+//! bytes. Only the length of the input and the position of padding and line breaks, which
+//! are public, influence control flow. Invalid input is detected without branching on the
+//! data and reported once the whole input has been processed.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ptr;
+use sgx_types::*;
+
+const PEM_BEGIN: &str = "-----BEGIN ";
+const PEM_END: &str = "-----END ";
+const PEM_DASHES: &str = "-----";
+const PEM_LINE: usize = 64;
+
+// The lowercase hex digit of a nibble.
+fn hex_char(n: u8) -> u8 {
+    let n = n as i16;
+    (n + 48 + (((9 - n) >> 8) & 39)) as u8
+}
+
+// The value of a hex digit of either case, or -1.
+fn hex_value(c: u8) -> i16 {
+    let c = c as i16;
+    let mut v = -1;
+    v += (((0x2f - c) & (c - 0x3a)) >> 8) & (c - 47);
+    v += (((0x40 - c) & (c - 0x47)) >> 8) & (c - 54);
+    v += (((0x60 - c) & (c - 0x67)) >> 8) & (c - 86);
+    v
+}
+
+// The base64 character of a 6-bit value.
+fn base64_char(n: u8) -> u8 {
+    let n = n as i16;
+    let mut c = n + 65;
+    c += ((25 - n) >> 8) & 6;
+    c -= ((51 - n) >> 8) & 75;
+    c -= ((61 - n) >> 8) & 15;
+    c += ((62 - n) >> 8) & 3;
+    c as u8
+}
+
+// The value of a base64 character, or -1.
+fn base64_value(c: u8) -> i16 {
+    let c = c as i16;
+    let mut v = -1;
+    v += (((0x40 - c) & (c - 0x5b)) >> 8) & (c - 64);
+    v += (((0x60 - c) & (c - 0x7b)) >> 8) & (c - 70);
+    v += (((0x2f - c) & (c - 0x3a)) >> 8) & (c + 5);
+    v += (((0x2a - c) & (c - 0x2c)) >> 8) & 63;
+    v += (((0x2e - c) & (c - 0x30)) >> 8) & 64;
+    v
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
+
+///
+/// The rsgx_hex_encode function encodes `data` as lowercase hex.
+///
+pub fn rsgx_hex_encode(data: &[u8]) -> String {
+    let mut out = Vec::with_capacity(data.len() * 2);
+    for b in data {
+        out.push(hex_char(b >> 4));
+        out.push(hex_char(b & 0x0f));
+    }
+    String::from_utf8(out).expect("hex is ASCII")
+}
+
+///
+/// The rsgx_hex_decode function decodes hex of either case.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// `text` has an odd length or holds a character that is not a hex digit.
+///
+pub fn rsgx_hex_decode(text: &str) -> SgxResult<Vec<u8>> {
+    let text = text.as_bytes();
+    if text.len() % 2 != 0 {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    let mut out = Vec::with_capacity(text.len() / 2);
+    let mut invalid = 0_i16;
+    for pair in text.chunks_exact(2) {
+        let hi = hex_value(pair[0]);
+        let lo = hex_value(pair[1]);
+        invalid |= hi | lo;
+        out.push(((hi << 4) | lo) as u8);
+    }
+    if invalid < 0 {
+        wipe(&mut out);
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    Ok(out)
+}
+
+///
+/// The rsgx_base64_encode function encodes `data` as base64 with the standard alphabet
+/// and padding.
+///
+pub fn rsgx_base64_encode(data: &[u8]) -> String {
+    let mut out = Vec::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(base64_char((n >> (18 - 6 * i) & 0x3f) as u8));
+            } else {
+                out.push(b'=');
+            }
+        }
+    }
+    String::from_utf8(out).expect("base64 is ASCII")
+}
+
+///
+/// The rsgx_base64_decode function decodes base64 with the standard alphabet and padding.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// `text` is not canonical padded base64: its length is not a multiple of 4, it holds a
+/// character outside the alphabet, misplaced padding, or non-zero bits after the last
+/// byte.
+///
+pub fn rsgx_base64_decode(text: &str) -> SgxResult<Vec<u8>> {
+    decode_base64(text.as_bytes())
+}
+
+fn decode_base64(text: &[u8]) -> SgxResult<Vec<u8>> {
+    if text.len() % 4 != 0 {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    let padding = text
+        .iter()
+        .rev()
+        .take(2)
+        .take_while(|&&c| c == b'=')
+        .count();
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut invalid = 0_i16;
+    let chunks = text.len() / 4;
+    for (index, chunk) in text.chunks_exact(4).enumerate() {
+        let padding = if index + 1 == chunks { padding } else { 0 };
+        let mut n = 0_u32;
+        for &c in &chunk[..4 - padding] {
+            let v = base64_value(c);
+            invalid |= v;
+            n = n << 6 | (v & 0x3f) as u32;
+        }
+        n <<= 6 * padding as u32;
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        out.extend_from_slice(&bytes[..3 - padding]);
+        if padding > 0 {
+            // The bits of the last character beyond the last byte must be zero.
+            let extra = (n & (0xff_ffff >> (24 - 8 * padding))) as i32;
+            invalid |= (-extra >> 16) as i16;
+        }
+    }
+    if invalid < 0 {
+        wipe(&mut out);
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    Ok(out)
+}
+
+///
+/// A PEM block: a label, such as `CERTIFICATE` or `PRIVATE KEY`, and its binary contents.
+///
+/// # Description
+///
+/// Blocks are parsed in the strict form of RFC 7468: no explicit headers, and only
+/// whitespace between the encapsulation boundaries besides the base64 text. Text before,
+/// between and after blocks is ignored. The contents are wiped when the block is dropped.
+///
+#[derive(Clone, PartialEq, Eq)]
+pub struct SgxPemBlock {
+    label: String,
+    contents: Vec<u8>,
+}
+
+impl Drop for SgxPemBlock {
+    fn drop(&mut self) {
+        wipe(&mut self.contents);
+    }
+}
+
+impl SgxPemBlock {
+    ///
+    /// Creates a block of `contents` under `label`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `label` is not a valid RFC 7468 label: printable ASCII, without hyphens or spaces
+    /// at its ends or next to each other.
+    ///
+    pub fn new(label: &str, contents: Vec<u8>) -> SgxResult<SgxPemBlock> {
+        if !is_label(label) {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        Ok(SgxPemBlock {
+            label: label.into(),
+            contents,
+        })
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn contents(&self) -> &[u8] {
+        &self.contents
+    }
+
+    ///
+    /// Encodes the block, with lines of 64 characters and a final line break.
+    ///
+    pub fn encode(&self) -> String {
+        let body = rsgx_base64_encode(&self.contents);
+        let mut out = String::with_capacity(body.len() + body.len() / PEM_LINE + 64);
+        out.push_str(PEM_BEGIN);
+        out.push_str(&self.label);
+        out.push_str(PEM_DASHES);
+        out.push('\n');
+        for line in body.as_bytes().chunks(PEM_LINE) {
+            out.push_str(core::str::from_utf8(line).expect("base64 is ASCII"));
+            out.push('\n');
+        }
+        out.push_str(PEM_END);
+        out.push_str(&self.label);
+        out.push_str(PEM_DASHES);
+        out.push('\n');
+
+        let mut body = body.into_bytes();
+        wipe(&mut body);
+        out
+    }
+
+    ///
+    /// Parses the first block of `pem`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `pem` holds no block, or its first block is malformed.
+    ///
+    pub fn parse(pem: &str) -> SgxResult<SgxPemBlock> {
+        let mut lines = pem.lines();
+        parse_block(&mut lines)?.ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+    }
+
+    ///
+    /// Parses all blocks of `pem` in order, for example a certificate chain.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// A block is malformed.
+    ///
+    pub fn parse_all(pem: &str) -> SgxResult<Vec<SgxPemBlock>> {
+        let mut lines = pem.lines();
+        let mut blocks = Vec::new();
+        while let Some(block) = parse_block(&mut lines)? {
+            blocks.push(block);
+        }
+        Ok(blocks)
+    }
+}
+
+fn is_label(label: &str) -> bool {
+    let bytes = label.as_bytes();
+    let separator = |c: u8| c == b' ' || c == b'-';
+    bytes.iter().all(|&c| (0x20..0x7f).contains(&c))
+        && !bytes.first().map_or(false, |&c| separator(c))
+        && !bytes.last().map_or(false, |&c| separator(c))
+        && !bytes.windows(2).any(|w| separator(w[0]) && separator(w[1]))
+}
+
+fn boundary<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    let label = line
+        .trim_end()
+        .strip_prefix(prefix)?
+        .strip_suffix(PEM_DASHES)?;
+    if is_label(label) {
+        Some(label)
+    } else {
+        None
+    }
+}
+
+// Parses the next block of `lines`, or returns `None` if there is none.
+fn parse_block<'a, I: Iterator<Item = &'a str>>(lines: &mut I) -> SgxResult<Option<SgxPemBlock>> {
+    let invalid = sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+    let label = match lines.find_map(|line| boundary(line, PEM_BEGIN)) {
+        Some(label) => label,
+        None => return Ok(None),
+    };
+
+    let mut body = Vec::new();
+    let mut ended = false;
+    for line in lines.by_ref() {
+        if let Some(end) = boundary(line, PEM_END) {
+            ended = end == label;
+            break;
+        }
+        body.extend(line.bytes().filter(|c| !c.is_ascii_whitespace()));
+    }
+    let contents = if ended {
+        decode_base64(&body)
+    } else {
+        Err(invalid)
+    };
+    wipe(&mut body);
+    Ok(Some(SgxPemBlock {
+        label: label.into(),
+        contents: contents?,
+    }))
+}
@@ -0,0 +1,368 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Envelope encryption
+//!
+//! A two-level key hierarchy: a master key held by a `KeyBackend`, sealed in the enclave
+//! or in a KMS, wraps data-encryption keys (DEKs), and DEKs encrypt messages with
+//! AES-128-GCM. Every message carries its DEK in wrapped form, so it can be decrypted by
+//! anyone with access to the master key and nothing else needs to be stored.
+//!
+//! `SgxEnvelope` keeps the current DEK and a cache of unwrapped DEKs, so the backend is
+//! only called when a DEK is created or first seen. The DEK is rotated after a configured
+//! number of messages, or on request with `rotate`.
+//!
+//! A message is laid out as:
+//!
+//! ```text
+//! version (1) | wrapped DEK length (2, LE) | wrapped DEK | nonce (12) | ciphertext | tag (16)
+//! ```
+//!
+//! The header, everything before the ciphertext, is authenticated together with the
+//! caller's additional data.
+
+use crate::crypto::{rsgx_rijndael128GCM_decrypt, rsgx_rijndael128GCM_encrypt, rsgx_sha256_slice};
+use crate::kms::KeyBackend;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::ptr;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+const ENVELOPE_VERSION: u8 = 1;
+const HEADER_FIXED_SIZE: usize = 1 + 2 + SGX_AESGCM_IV_SIZE;
+const DEFAULT_MAX_MESSAGES: u64 = 1 << 32;
+const DEFAULT_CACHE_CAPACITY: usize = 64;
+
+struct DataKey {
+    key: sgx_aes_gcm_128bit_key_t,
+    wrapped: Vec<u8>,
+    messages: u64,
+}
+
+impl Drop for DataKey {
+    fn drop(&mut self) {
+        wipe(&mut self.key);
+    }
+}
+
+struct CachedKey {
+    key: sgx_aes_gcm_128bit_key_t,
+    last_used: u64,
+}
+
+impl Drop for CachedKey {
+    fn drop(&mut self) {
+        wipe(&mut self.key);
+    }
+}
+
+///
+/// Envelope encryption under the master key of a `KeyBackend`.
+///
+/// # Description
+///
+/// A DEK encrypts at most `max_messages` messages, by default 2^32, the limit for random
+/// 96-bit GCM nonces. Up to `cache_capacity` unwrapped DEKs, by default 64, are kept for
+/// decryption; the least recently used is dropped first.
+///
+pub struct SgxEnvelope<B: KeyBackend> {
+    backend: B,
+    current: Option<DataKey>,
+    cache: BTreeMap<sgx_sha256_hash_t, CachedKey>,
+    max_messages: u64,
+    cache_capacity: usize,
+    tick: u64,
+}
+
+impl<B: KeyBackend> SgxEnvelope<B> {
+    pub fn new(backend: B) -> SgxEnvelope<B> {
+        SgxEnvelope {
+            backend,
+            current: None,
+            cache: BTreeMap::new(),
+            max_messages: DEFAULT_MAX_MESSAGES,
+            cache_capacity: DEFAULT_CACHE_CAPACITY,
+            tick: 0,
+        }
+    }
+
+    ///
+    /// Rotates the DEK after `messages` messages, at most 2^32.
+    ///
+    pub fn max_messages(mut self, messages: u64) -> Self {
+        self.max_messages = messages.clamp(1, DEFAULT_MAX_MESSAGES);
+        self
+    }
+
+    ///
+    /// Keeps up to `capacity` unwrapped DEKs for decryption; 0 disables the cache.
+    ///
+    pub fn cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache_capacity = capacity;
+        self.evict();
+        self
+    }
+
+    pub fn backend(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    pub fn into_backend(self) -> B {
+        let SgxEnvelope { backend, .. } = self;
+        backend
+    }
+
+    ///
+    /// Replaces the DEK; the next message is encrypted under a new one. Messages under the
+    /// previous DEK still decrypt.
+    ///
+    pub fn rotate(&mut self) -> SgxError {
+        let (mut key, wrapped) = self.backend.generate_data_key(SGX_AESGCM_KEY_SIZE)?;
+        if key.len() != SGX_AESGCM_KEY_SIZE
+            || wrapped.is_empty()
+            || wrapped.len() > u16::MAX as usize
+        {
+            wipe(&mut key);
+            return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+        }
+        let mut data_key = DataKey {
+            key: sgx_aes_gcm_128bit_key_t::default(),
+            wrapped,
+            messages: 0,
+        };
+        data_key.key.copy_from_slice(&key);
+        wipe(&mut key);
+
+        if let Some(previous) = self.current.replace(data_key) {
+            let id = rsgx_sha256_slice(&previous.wrapped)?;
+            self.insert(id, previous.key);
+        }
+        Ok(())
+    }
+
+    ///
+    /// Encrypts `plaintext`, authenticating it together with `aad`.
+    ///
+    /// # Errors
+    ///
+    /// Errors of the backend when a new DEK is needed, and of AES-GCM.
+    ///
+    pub fn encrypt(&mut self, plaintext: &[u8], aad: &[u8]) -> SgxResult<Vec<u8>> {
+        let due = match self.current {
+            Some(ref current) => current.messages >= self.max_messages,
+            None => true,
+        };
+        if due {
+            self.rotate()?;
+        }
+        let current = self
+            .current
+            .as_mut()
+            .ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)?;
+        current.messages += 1;
+
+        let header_len = HEADER_FIXED_SIZE + current.wrapped.len();
+        let mut message = vec![0_u8; header_len + plaintext.len() + SGX_AESGCM_MAC_SIZE];
+        message[0] = ENVELOPE_VERSION;
+        message[1..3].copy_from_slice(&(current.wrapped.len() as u16).to_le_bytes());
+        message[3..3 + current.wrapped.len()].copy_from_slice(&current.wrapped);
+        rsgx_read_rand(&mut message[header_len - SGX_AESGCM_IV_SIZE..header_len])?;
+
+        let (header, body) = message.split_at_mut(header_len);
+        let (ciphertext, tag) = body.split_at_mut(plaintext.len());
+        let nonce = &header[header_len - SGX_AESGCM_IV_SIZE..];
+        let mut full_aad = Vec::with_capacity(header_len + aad.len());
+        full_aad.extend_from_slice(header);
+        full_aad.extend_from_slice(aad);
+
+        let mut mac = sgx_aes_gcm_128bit_tag_t::default();
+        rsgx_rijndael128GCM_encrypt(
+            &current.key,
+            plaintext,
+            nonce,
+            &full_aad,
+            ciphertext,
+            &mut mac,
+        )?;
+        tag.copy_from_slice(&mac);
+        Ok(message)
+    }
+
+    ///
+    /// Decrypts a message produced by `encrypt` with the same `aad`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The message is not an envelope.
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The message or `aad` was modified, or the DEK was not wrapped by this master key.
+    ///
+    pub fn decrypt(&mut self, message: &[u8], aad: &[u8]) -> SgxResult<Vec<u8>> {
+        let header = parse_header(message)?;
+        let header_len = HEADER_FIXED_SIZE + header.wrapped.len();
+        let (ciphertext, tag) =
+            message[header_len..].split_at(message.len() - header_len - SGX_AESGCM_MAC_SIZE);
+
+        let key = self.unwrap(header.wrapped)?;
+        let mut full_aad = Vec::with_capacity(header_len + aad.len());
+        full_aad.extend_from_slice(&message[..header_len]);
+        full_aad.extend_from_slice(aad);
+        let mut mac = sgx_aes_gcm_128bit_tag_t::default();
+        mac.copy_from_slice(tag);
+
+        let mut plaintext = vec![0_u8; ciphertext.len()];
+        let result = rsgx_rijndael128GCM_decrypt(
+            &key.key,
+            ciphertext,
+            header.nonce,
+            &full_aad,
+            &mac,
+            &mut plaintext,
+        );
+        match result {
+            Ok(()) => Ok(plaintext),
+            Err(e) => {
+                wipe(&mut plaintext);
+                Err(e)
+            }
+        }
+    }
+
+    fn unwrap(&mut self, wrapped: &[u8]) -> SgxResult<CachedKey> {
+        if let Some(ref current) = self.current {
+            if current.wrapped == wrapped {
+                return Ok(CachedKey {
+                    key: current.key,
+                    last_used: 0,
+                });
+            }
+        }
+        self.tick += 1;
+        let id = rsgx_sha256_slice(wrapped)?;
+        if let Some(cached) = self.cache.get_mut(&id) {
+            cached.last_used = self.tick;
+            return Ok(CachedKey {
+                key: cached.key,
+                last_used: 0,
+            });
+        }
+
+        let mut key = self.backend.unwrap(wrapped)?;
+        if key.len() != SGX_AESGCM_KEY_SIZE {
+            wipe(&mut key);
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+        let mut data_key = sgx_aes_gcm_128bit_key_t::default();
+        data_key.copy_from_slice(&key);
+        wipe(&mut key);
+        self.insert(id, data_key);
+        let cached = CachedKey {
+            key: data_key,
+            last_used: 0,
+        };
+        wipe(&mut data_key);
+        Ok(cached)
+    }
+
+    fn insert(&mut self, id: sgx_sha256_hash_t, key: sgx_aes_gcm_128bit_key_t) {
+        self.tick += 1;
+        self.cache.insert(
+            id,
+            CachedKey {
+                key,
+                last_used: self.tick,
+            },
+        );
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.cache.len() > self.cache_capacity {
+            let oldest = self
+                .cache
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(id, _)| *id);
+            match oldest {
+                Some(id) => {
+                    self.cache.remove(&id);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+struct Header<'a> {
+    wrapped: &'a [u8],
+    nonce: &'a [u8],
+}
+
+fn parse_header(message: &[u8]) -> SgxResult<Header<'_>> {
+    if message.len() < HEADER_FIXED_SIZE + SGX_AESGCM_MAC_SIZE || message[0] != ENVELOPE_VERSION {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    let wrapped_len = u16::from_le_bytes([message[1], message[2]]) as usize;
+    if wrapped_len == 0 || message.len() < HEADER_FIXED_SIZE + wrapped_len + SGX_AESGCM_MAC_SIZE {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    Ok(Header {
+        wrapped: &message[3..3 + wrapped_len],
+        nonce: &message[3 + wrapped_len..HEADER_FIXED_SIZE + wrapped_len],
+    })
+}
+
+///
+/// Returns the wrapped DEK of an envelope message, for example to find messages under a
+/// DEK that must be re-encrypted.
+///
+pub fn wrapped_key(message: &[u8]) -> SgxResult<&[u8]> {
+    parse_header(message).map(|header| header.wrapped)
+}
+
+///
+/// Encrypts `plaintext` under the envelope `envelope`; see `SgxEnvelope::encrypt`.
+///
+pub fn encrypt<B: KeyBackend>(
+    envelope: &mut SgxEnvelope<B>,
+    plaintext: &[u8],
+    aad: &[u8],
+) -> SgxResult<Vec<u8>> {
+    envelope.encrypt(plaintext, aad)
+}
+
+///
+/// Decrypts an envelope message; see `SgxEnvelope::decrypt`.
+///
+pub fn decrypt<B: KeyBackend>(
+    envelope: &mut SgxEnvelope<B>,
+    message: &[u8],
+    aad: &[u8],
+) -> SgxResult<Vec<u8>> {
+    envelope.decrypt(message, aad)
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
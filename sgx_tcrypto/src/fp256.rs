@@ -0,0 +1,401 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+// Multi-precision limb arithmetic, least significant limb first, shared by the
+// hand-written modules: the carry primitives, and Montgomery multiplication (CIOS) over
+// any number of limbs, which `bignum` runs at the width of its modulus.
+//
+// `Modulus` is arithmetic modulo an odd 256-bit modulus m in Montgomery form on four
+// limbs; the moduli of `p256` and `zk::bn254` are defined here. Values in Montgomery form
+// are `a * 2^256 mod m`.
+//
+// The arithmetic is branch-free.
+
+pub(crate) struct Modulus {
+    pub(crate) m: [u64; 4],
+    // -m^-1 mod 2^64
+    pub(crate) m_inv: u64,
+    // 2^512 mod m
+    pub(crate) r2: [u64; 4],
+}
+
+// The field prime p of P-256.
+pub(crate) const P256_P: Modulus = Modulus {
+    m: [
+        0xffff_ffff_ffff_ffff,
+        0x0000_0000_ffff_ffff,
+        0x0000_0000_0000_0000,
+        0xffff_ffff_0000_0001,
+    ],
+    m_inv: 1,
+    r2: [
+        0x0000_0000_0000_0003,
+        0xffff_fffb_ffff_ffff,
+        0xffff_ffff_ffff_fffe,
+        0x0000_0004_ffff_fffd,
+    ],
+};
+
+// The group order n of P-256.
+pub(crate) const P256_N: Modulus = Modulus {
+    m: [
+        0xf3b9_cac2_fc63_2551,
+        0xbce6_faad_a717_9e84,
+        0xffff_ffff_ffff_ffff,
+        0xffff_ffff_0000_0000,
+    ],
+    m_inv: 0xccd1_c8aa_ee00_bc4f,
+    r2: [
+        0x8324_4c95_be79_eea2,
+        0x4699_799c_49bd_6fa6,
+        0x2845_b239_2b6b_ec59,
+        0x66e1_2d94_f3d9_5620,
+    ],
+};
+
+// The field prime p of BN254.
+pub(crate) const BN254_P: Modulus = Modulus {
+    m: [
+        0x3c20_8c16_d87c_fd47,
+        0x9781_6a91_6871_ca8d,
+        0xb850_45b6_8181_585d,
+        0x3064_4e72_e131_a029,
+    ],
+    m_inv: 0x87d2_0782_e486_6389,
+    r2: [
+        0xf32c_fc5b_538a_fa89,
+        0xb5e7_1911_d445_01fb,
+        0x47ab_1eff_0a41_7ff6,
+        0x06d8_9f71_cab8_351f,
+    ],
+};
+
+#[inline(always)]
+pub(crate) fn adc(a: u64, b: u64, carry: u64) -> (u64, u64) {
+    let t = a as u128 + b as u128 + carry as u128;
+    (t as u64, (t >> 64) as u64)
+}
+
+#[inline(always)]
+pub(crate) fn sbb(a: u64, b: u64, borrow: u64) -> (u64, u64) {
+    let t = (a as u128).wrapping_sub(b as u128 + borrow as u128);
+    (t as u64, (t >> 127) as u64)
+}
+
+// acc + a * b + carry, as the low limb and the carry.
+#[inline(always)]
+pub(crate) fn mac(acc: u64, a: u64, b: u64, carry: u64) -> (u64, u64) {
+    let t = acc as u128 + a as u128 * b as u128 + carry as u128;
+    (t as u64, (t >> 64) as u64)
+}
+
+// a -= b over a.len() limbs, b zero-extended; returns the borrow.
+pub(crate) fn sub_assign(a: &mut [u64], b: &[u64]) -> u64 {
+    let mut borrow = 0;
+    for (i, x) in a.iter_mut().enumerate() {
+        let (d, br) = sbb(*x, b.get(i).copied().unwrap_or(0), borrow);
+        *x = d;
+        borrow = br;
+    }
+    borrow
+}
+
+// a = if choice == 1 { b } else { a }.
+pub(crate) fn select_assign(a: &mut [u64], b: &[u64], choice: u64) {
+    let mask = 0_u64.wrapping_sub(choice);
+    for (x, y) in a.iter_mut().zip(b.iter()) {
+        *x ^= mask & (*x ^ y);
+    }
+}
+
+// The Montgomery product a * b / 2^(64n) mod m of operands below the odd n-limb modulus
+// m, with m_inv = -m^-1 mod 2^64. `t` is scratch space of n + 2 limbs and receives the
+// product in its low n limbs.
+pub(crate) fn mont_mul(t: &mut [u64], a: &[u64], b: &[u64], m: &[u64], m_inv: u64) {
+    let n = m.len();
+    for x in t.iter_mut() {
+        *x = 0;
+    }
+    for bi in b[..n].iter() {
+        let mut carry = 0;
+        for j in 0..n {
+            let (x, c) = mac(t[j], a[j], *bi, carry);
+            t[j] = x;
+            carry = c;
+        }
+        let (x, c) = adc(t[n], carry, 0);
+        t[n] = x;
+        t[n + 1] = c;
+
+        let q = t[0].wrapping_mul(m_inv);
+        let (_, mut carry) = mac(t[0], q, m[0], 0);
+        for j in 1..n {
+            let (x, c) = mac(t[j], q, m[j], carry);
+            t[j - 1] = x;
+            carry = c;
+        }
+        let (x, c) = adc(t[n], carry, 0);
+        t[n - 1] = x;
+        t[n] = t[n + 1] + c;
+    }
+    // t < 2m: subtract m if the top limb is set or the low limbs are at least m.
+    let mut borrow = 0;
+    for j in 0..n {
+        borrow = sbb(t[j], m[j], borrow).1;
+    }
+    let mask = 0_u64.wrapping_sub(t[n] | (borrow ^ 1));
+    let mut borrow = 0;
+    for j in 0..n {
+        let (x, br) = sbb(t[j], m[j] & mask, borrow);
+        t[j] = x;
+        borrow = br;
+    }
+    t[n] = 0;
+}
+
+pub(crate) fn sub_limbs(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], u64) {
+    let mut d = *a;
+    let borrow = sub_assign(&mut d, b);
+    (d, borrow)
+}
+
+pub(crate) fn select_limbs(a: &[u64; 4], b: &[u64; 4], choice: u64) -> [u64; 4] {
+    let mut l = *a;
+    select_assign(&mut l, b, choice);
+    l
+}
+
+pub(crate) fn load_be(bytes: &[u8; 32]) -> [u64; 4] {
+    let mut l = [0_u64; 4];
+    for (i, limb) in l.iter_mut().enumerate() {
+        let mut b = [0_u8; 8];
+        b.copy_from_slice(&bytes[24 - 8 * i..32 - 8 * i]);
+        *limb = u64::from_be_bytes(b);
+    }
+    l
+}
+
+pub(crate) fn store_be(l: &[u64; 4]) -> [u8; 32] {
+    let mut bytes = [0_u8; 32];
+    for (i, limb) in l.iter().enumerate() {
+        bytes[24 - 8 * i..32 - 8 * i].copy_from_slice(&limb.to_be_bytes());
+    }
+    bytes
+}
+
+impl Modulus {
+    // Subtracts m from the value `hi * 2^256 + l` if it is at least m; the value must be
+    // below 2m.
+    pub(crate) fn reduce_once(&self, l: &[u64; 4], hi: u64) -> [u64; 4] {
+        let (d, borrow) = sub_limbs(l, &self.m);
+        select_limbs(l, &d, hi | (borrow ^ 1))
+    }
+
+    pub(crate) fn add(&self, a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+        let mut s = [0_u64; 4];
+        let mut carry = 0;
+        for i in 0..4 {
+            let (x, c) = adc(a[i], b[i], carry);
+            s[i] = x;
+            carry = c;
+        }
+        self.reduce_once(&s, carry)
+    }
+
+    pub(crate) fn sub(&self, a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+        let (d, borrow) = sub_limbs(a, b);
+        let mask = 0_u64.wrapping_sub(borrow);
+        let mut s = [0_u64; 4];
+        let mut carry = 0;
+        for i in 0..4 {
+            let (x, c) = adc(d[i], self.m[i] & mask, carry);
+            s[i] = x;
+            carry = c;
+        }
+        s
+    }
+
+    // The Montgomery product a * b / 2^256 mod m.
+    pub(crate) fn mul(&self, a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+        let mut t = [0_u64; 6];
+        mont_mul(&mut t, a, b, &self.m, self.m_inv);
+        [t[0], t[1], t[2], t[3]]
+    }
+
+    pub(crate) fn enter(&self, a: &[u64; 4]) -> [u64; 4] {
+        self.mul(a, &self.r2)
+    }
+
+    pub(crate) fn leave(&self, a: &[u64; 4]) -> [u64; 4] {
+        self.mul(a, &[1, 0, 0, 0])
+    }
+
+    // a^e in Montgomery form, for a public exponent e: the bits of e decide the branches.
+    pub(crate) fn pow(&self, a: &[u64; 4], e: &[u64; 4]) -> [u64; 4] {
+        let mut x = self.enter(&[1, 0, 0, 0]);
+        for i in (0..256).rev() {
+            x = self.mul(&x, &x);
+            if (e[i / 64] >> (i % 64)) & 1 == 1 {
+                x = self.mul(&x, a);
+            }
+        }
+        x
+    }
+
+    // a^(m - 2), the inverse of a non-zero element in Montgomery form.
+    pub(crate) fn invert(&self, a: &[u64; 4]) -> [u64; 4] {
+        let (e, _) = sub_limbs(&self.m, &[2, 0, 0, 0]);
+        self.pow(a, &e)
+    }
+
+    pub(crate) fn is_canonical(&self, l: &[u64; 4]) -> bool {
+        sub_limbs(l, &self.m).1 == 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum::{BigUint, Montgomery};
+    use crate::encoding::rsgx_hex_decode;
+
+    fn limbs(hex: &str) -> [u64; 4] {
+        let mut bytes = [0_u8; 32];
+        bytes.copy_from_slice(&rsgx_hex_decode(hex).unwrap());
+        load_be(&bytes)
+    }
+
+    const GX: &str = "6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296";
+    const GY: &str = "4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5";
+
+    // (modulus, GX * GY, GX^-1, GX^GY, (m - 1)^2 / 2^256), all mod m.
+    const VECTORS: [(&Modulus, &str, &str, &str, &str); 3] = [
+        (
+            &P256_P,
+            "823cd15f6dd3c71933565064513a6b2bd183e554c6a08622f713ebbbface98be",
+            "e060cbb088706d5d24936933b69b16ab707d656273744b65664c49e577f35238",
+            "2f3db69bc9d93323c351f3e768d332806ad3a7652ea632e89e23312f7b5f9f96",
+            "fffffffe00000003fffffffd0000000200000001fffffffe0000000300000000",
+        ),
+        (
+            &P256_N,
+            "1543b5272ef9466b6179ca8d535b2e2af16b758c41da31d772eca81accce4d18",
+            "4a8dbb62c2548ddcb57084bceeab15480df0052a93d05ca48caaa102e7efdd93",
+            "575c7cdf7e8ad1838474d439597a7fb68702b68b778ff18d97069da8e461733d",
+            "60d066334905c1e907f8b6041e607725badef3e243566fafce1bc8f79c197c79",
+        ),
+        (
+            &BN254_P,
+            "3019d1ab16b6854b9484af48452ff098da5688e2ded0cced7cd1069ef5f25a04",
+            "2c6ea766a2a37d01209a2770065d0cc4777786372a82982118065bbf94f9c371",
+            "0807817570649f89461f51119150edebb3c6051b1c38a2479e925dcd72787c89",
+            "2e67157159e5c639cf63e9cfb74492d9eb2022850278edf8ed84884a014afa37",
+        ),
+    ];
+
+    #[test]
+    fn modulus_constants() {
+        for (modulus, _, _, _, _) in VECTORS.iter() {
+            assert_eq!(modulus.m[0].wrapping_mul(modulus.m_inv), u64::MAX);
+            // 2^512 mod m, by the bit-serial reduction of `bignum`.
+            let ctx = Montgomery::new(&BigUint::from_limbs(&modulus.m)).unwrap();
+            let mut r2 = [0_u64; 9];
+            r2[8] = 1;
+            assert_eq!(ctx.reduce(&BigUint::from_limbs(&r2)).limbs(), &modulus.r2);
+        }
+    }
+
+    #[test]
+    fn modulus_known_answers() {
+        let (gx, gy) = (limbs(GX), limbs(GY));
+        for (modulus, product, inverse, power, square) in VECTORS.iter() {
+            let (a, b) = (modulus.enter(&gx), modulus.enter(&gy));
+            // GX is above the BN254 prime; entering reduces it.
+            if modulus.is_canonical(&gx) {
+                assert_eq!(modulus.leave(&a), gx);
+            }
+            assert_eq!(modulus.leave(&modulus.mul(&a, &b)), limbs(product));
+            assert_eq!(modulus.leave(&modulus.invert(&a)), limbs(inverse));
+            assert_eq!(modulus.leave(&modulus.pow(&a, &gy)), limbs(power));
+            assert_eq!(
+                modulus.leave(&modulus.mul(&modulus.invert(&a), &a)),
+                [1, 0, 0, 0]
+            );
+
+            let (m_minus_1, _) = sub_limbs(&modulus.m, &[1, 0, 0, 0]);
+            assert_eq!(modulus.mul(&m_minus_1, &m_minus_1), limbs(square));
+            assert_eq!(modulus.add(&m_minus_1, &[2, 0, 0, 0]), [1, 0, 0, 0]);
+            assert_eq!(modulus.sub(&[1, 0, 0, 0], &[2, 0, 0, 0]), m_minus_1);
+            assert!(modulus.is_canonical(&m_minus_1));
+            assert!(!modulus.is_canonical(&modulus.m));
+            assert_eq!(modulus.reduce_once(&modulus.m, 0), [0; 4]);
+        }
+    }
+
+    #[test]
+    fn mont_mul_other_widths() {
+        // m = 2^191 - 19 on three limbs: a * b / 2^192 mod m.
+        let m = [0xffff_ffff_ffff_ffed, u64::MAX, 0x7fff_ffff_ffff_ffff];
+        let m_inv = 0x86bc_a1af_286b_ca1b;
+        let a = [0x0123_4567_89ab_cdef; 3];
+        let b = [
+            0xfedc_ba98_7654_3210,
+            0x0123_4567_89ab_cdef,
+            0x7edc_ba98_7654_3210,
+        ];
+        let mut t = [u64::MAX; 5];
+        mont_mul(&mut t, &a, &b, &m, m_inv);
+        assert_eq!(
+            t,
+            [
+                0x95ac_bb50_b209_8ee5,
+                0x954f_2f2c_bf5e_819f,
+                0x5c69_5cff_da75_590d,
+                0,
+                0,
+            ]
+        );
+
+        // One limb: 3 * 5 / 2^64 mod 7, where 2^64 = 2 mod 7.
+        let mut t = [0_u64; 3];
+        mont_mul(&mut t, &[3], &[5], &[7], 0x9249_2492_4924_9249);
+        assert_eq!(t[0], 4);
+    }
+
+    #[test]
+    fn limb_primitives() {
+        assert_eq!(adc(u64::MAX, u64::MAX, 1), (u64::MAX, 1));
+        assert_eq!(sbb(0, 1, 0), (u64::MAX, 1));
+        assert_eq!(sbb(0, u64::MAX, 1), (0, 1));
+        assert_eq!(sbb(5, 3, 1), (1, 0));
+        assert_eq!(
+            mac(u64::MAX, u64::MAX, u64::MAX, u64::MAX),
+            (u64::MAX, u64::MAX)
+        );
+
+        let mut a = [0, 0, 1];
+        assert_eq!(sub_assign(&mut a, &[1]), 0);
+        assert_eq!(a, [u64::MAX, u64::MAX, 0]);
+        assert_eq!(sub_assign(&mut a, &[0, 0, 1]), 1);
+        let mut a = [1_u64, 2];
+        select_assign(&mut a, &[3, 4], 0);
+        assert_eq!(a, [1, 2]);
+        select_assign(&mut a, &[3, 4], 1);
+        assert_eq!(a, [3, 4]);
+        assert_eq!(store_be(&limbs(GX)).to_vec(), rsgx_hex_decode(GX).unwrap());
+    }
+}
@@ -0,0 +1,325 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Exponential ElGamal.
+//!
+//! A plaintext `m` is encrypted as `(r * G, m * G + r * H)` for the public key `H = x * G`.
+//! Decryption recovers `m * G` and then `m` by a baby-step giant-step search over
+//! `[0, max]`, with a table shared between decryptions. The search takes time that
+//! depends on `m`; the rest runs in constant time.
+
+use crate::ed25519::point::{random_secret_scalar, Point};
+use alloc::collections::BTreeMap;
+use core::ptr;
+use sgx_types::*;
+
+pub const ELGAMAL_PUBLIC_KEY_SIZE: usize = 32;
+pub const ELGAMAL_CIPHERTEXT_SIZE: usize = 64;
+
+fn scalar_of(m: u64) -> [u8; 32] {
+    let mut scalar = [0_u8; 32];
+    scalar[..8].copy_from_slice(&m.to_le_bytes());
+    scalar
+}
+
+///
+/// An exponential ElGamal ciphertext.
+///
+#[derive(Clone, Copy)]
+pub struct ElGamalCiphertext {
+    c1: Point,
+    c2: Point,
+}
+
+impl ElGamalCiphertext {
+    pub fn to_bytes(&self) -> [u8; ELGAMAL_CIPHERTEXT_SIZE] {
+        let mut bytes = [0_u8; ELGAMAL_CIPHERTEXT_SIZE];
+        bytes[..32].copy_from_slice(&self.c1.compress());
+        bytes[32..].copy_from_slice(&self.c2.compress());
+        bytes
+    }
+
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The bytes do not encode two points.
+    ///
+    pub fn from_bytes(bytes: &[u8; ELGAMAL_CIPHERTEXT_SIZE]) -> SgxResult<ElGamalCiphertext> {
+        let mut c1 = [0_u8; 32];
+        let mut c2 = [0_u8; 32];
+        c1.copy_from_slice(&bytes[..32]);
+        c2.copy_from_slice(&bytes[32..]);
+        match (Point::decompress(&c1), Point::decompress(&c2)) {
+            (Some(c1), Some(c2)) => Ok(ElGamalCiphertext { c1, c2 }),
+            _ => Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
+        }
+    }
+
+    ///
+    /// A ciphertext of the sum of the plaintexts.
+    ///
+    pub fn add(&self, other: &ElGamalCiphertext) -> ElGamalCiphertext {
+        ElGamalCiphertext {
+            c1: self.c1.add(&other.c1.to_cached()),
+            c2: self.c2.add(&other.c2.to_cached()),
+        }
+    }
+}
+
+///
+/// An exponential ElGamal public key.
+///
+#[derive(Clone, Copy)]
+pub struct ElGamalPublicKey {
+    h: Point,
+}
+
+impl ElGamalPublicKey {
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The bytes do not encode a point of the prime-order subgroup.
+    ///
+    pub fn from_bytes(bytes: &[u8; ELGAMAL_PUBLIC_KEY_SIZE]) -> SgxResult<ElGamalPublicKey> {
+        let h = Point::decompress(bytes).ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+        if h.mul_by_cofactor().is_identity() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        Ok(ElGamalPublicKey { h })
+    }
+
+    pub fn to_bytes(&self) -> [u8; ELGAMAL_PUBLIC_KEY_SIZE] {
+        self.h.compress()
+    }
+
+    pub fn encrypt(&self, m: u64) -> SgxResult<ElGamalCiphertext> {
+        let mut r = random_secret_scalar()?;
+        let mut m = scalar_of(m);
+        let c = ElGamalCiphertext {
+            c1: Point::BASEPOINT.mul_ct(&r),
+            c2: Point::BASEPOINT
+                .mul_ct(&m)
+                .add(&self.h.mul_ct(&r).to_cached()),
+        };
+        wipe(&mut r);
+        wipe(&mut m);
+        Ok(c)
+    }
+
+    ///
+    /// A ciphertext of the plaintext of `c` plus `m`.
+    ///
+    pub fn add_plain(&self, c: &ElGamalCiphertext, m: u64) -> ElGamalCiphertext {
+        ElGamalCiphertext {
+            c1: c.c1,
+            c2: c
+                .c2
+                .add(&Point::BASEPOINT.mul_ct(&scalar_of(m)).to_cached()),
+        }
+    }
+
+    ///
+    /// A fresh ciphertext of the same plaintext, unlinkable to `c`.
+    ///
+    pub fn rerandomize(&self, c: &ElGamalCiphertext) -> SgxResult<ElGamalCiphertext> {
+        Ok(c.add(&self.encrypt(0)?))
+    }
+}
+
+///
+/// An exponential ElGamal private key. The secret is wiped when the key is dropped.
+///
+pub struct ElGamalPrivateKey {
+    x: [u8; 32],
+    public: ElGamalPublicKey,
+}
+
+impl Drop for ElGamalPrivateKey {
+    fn drop(&mut self) {
+        wipe(&mut self.x);
+    }
+}
+
+impl ElGamalPrivateKey {
+    pub fn generate() -> SgxResult<ElGamalPrivateKey> {
+        Ok(ElGamalPrivateKey::from_secret(&random_secret_scalar()?))
+    }
+
+    ///
+    /// The key of a secret saved with `secret`, e.g. after sealing.
+    ///
+    pub fn from_secret(secret: &[u8; 32]) -> ElGamalPrivateKey {
+        ElGamalPrivateKey {
+            x: *secret,
+            public: ElGamalPublicKey {
+                h: Point::BASEPOINT.mul_ct(secret),
+            },
+        }
+    }
+
+    pub fn secret(&self) -> &[u8; 32] {
+        &self.x
+    }
+
+    pub fn public_key(&self) -> &ElGamalPublicKey {
+        &self.public
+    }
+
+    ///
+    /// Decrypts `c`, whose plaintext must lie in the range of `table`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The plaintext is outside the range of the table.
+    ///
+    pub fn decrypt(&self, c: &ElGamalCiphertext, table: &ElGamalDecryptionTable) -> SgxResult<u64> {
+        let mg = c.c2.sub(&c.c1.mul_ct(&self.x).to_cached());
+        table
+            .search(&mg)
+            .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+    }
+}
+
+///
+/// The baby steps of the search for plaintexts in `[0, max]`.
+///
+/// # Description
+///
+/// The table holds `ceil(sqrt(max + 1))` points; a decryption takes at most as many
+/// point additions.
+///
+pub struct ElGamalDecryptionTable {
+    max: u64,
+    step: u64,
+    baby: BTreeMap<[u8; 32], u64>,
+    giant: Point,
+}
+
+impl ElGamalDecryptionTable {
+    pub fn new(max: u64) -> ElGamalDecryptionTable {
+        let mut step = 1_u64;
+        while step.checked_mul(step).map_or(false, |s| s <= max) {
+            step += 1;
+        }
+        let generator = Point::BASEPOINT.to_cached();
+        let mut baby = BTreeMap::new();
+        let mut p = Point::IDENTITY;
+        for j in 0..step {
+            baby.insert(p.compress(), j);
+            p = p.add(&generator);
+        }
+        ElGamalDecryptionTable {
+            max,
+            step,
+            baby,
+            giant: p,
+        }
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    fn search(&self, target: &Point) -> Option<u64> {
+        let giant = self.giant.to_cached();
+        let mut p = *target;
+        for i in 0..self.step {
+            if let Some(j) = self.baby.get(&p.compress()) {
+                let m = i * self.step + j;
+                return if m <= self.max { Some(m) } else { None };
+            }
+            p = p.sub(&giant);
+        }
+        None
+    }
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_answer() {
+        // The public key of x = 1 is the encoding of the base point.
+        let mut one = [0_u8; 32];
+        one[0] = 1;
+        let key = ElGamalPrivateKey::from_secret(&one);
+        let mut basepoint = [0x66_u8; 32];
+        basepoint[0] = 0x58;
+        assert_eq!(key.public_key().to_bytes(), basepoint);
+
+        // With c1 = G, decryption of (G, (m + 1) * G) under x = 1 yields m.
+        let table = ElGamalDecryptionTable::new(100);
+        let c = ElGamalCiphertext {
+            c1: Point::BASEPOINT,
+            c2: Point::BASEPOINT.mul_ct(&scalar_of(43)),
+        };
+        assert_eq!(key.decrypt(&c, &table), Ok(42));
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = ElGamalPrivateKey::generate().unwrap();
+        let public = ElGamalPublicKey::from_bytes(&key.public_key().to_bytes()).unwrap();
+        let table = ElGamalDecryptionTable::new(1_000);
+        for m in [0, 1, 31, 32, 999, 1_000] {
+            let c = public.encrypt(m).unwrap();
+            let c = ElGamalCiphertext::from_bytes(&c.to_bytes()).unwrap();
+            assert_eq!(key.decrypt(&c, &table), Ok(m));
+        }
+        let c = public.encrypt(1_001).unwrap();
+        assert!(key.decrypt(&c, &table).is_err());
+
+        let restored = ElGamalPrivateKey::from_secret(key.secret());
+        let c = public.encrypt(7).unwrap();
+        assert_eq!(restored.decrypt(&c, &table), Ok(7));
+    }
+
+    #[test]
+    fn homomorphic_operations() {
+        let key = ElGamalPrivateKey::generate().unwrap();
+        let public = key.public_key();
+        let table = ElGamalDecryptionTable::new(10_000);
+        let a = public.encrypt(1_234).unwrap();
+        let b = public.encrypt(4_321).unwrap();
+        assert_eq!(key.decrypt(&a.add(&b), &table), Ok(5_555));
+        assert_eq!(key.decrypt(&public.add_plain(&a, 66), &table), Ok(1_300));
+
+        let fresh = public.rerandomize(&a).unwrap();
+        assert_ne!(fresh.to_bytes(), a.to_bytes());
+        assert_eq!(key.decrypt(&fresh, &table), Ok(1_234));
+    }
+
+    #[test]
+    fn rejects_small_order_keys() {
+        let mut identity = [0_u8; 32];
+        identity[0] = 1;
+        assert!(ElGamalPublicKey::from_bytes(&identity).is_err());
+    }
+}
@@ -0,0 +1,29 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Additively homomorphic encryption
+//!
+//! * `paillier`: Paillier encryption over `n = p * q`, with decryption through the CRT.
+//!   Plaintexts are integers modulo `n`.
+//! * `elgamal`: exponential ElGamal over Curve25519 in Edwards form. Plaintexts are small
+//!   integers, and decryption searches a bounded range.
+//!
+//! In both, the product (Paillier) or sum (ElGamal) of ciphertexts encrypts the sum of
+//! their plaintexts, so an enclave can aggregate values it cannot read.
+
+pub mod elgamal;
+pub mod paillier;
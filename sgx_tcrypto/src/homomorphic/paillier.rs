@@ -0,0 +1,359 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Paillier encryption with `g = n + 1`.
+
+use crate::bignum::{BigUint, Crt, Montgomery};
+use alloc::vec::Vec;
+use sgx_types::*;
+
+///
+/// A Paillier ciphertext, an integer below `n^2`.
+///
+#[derive(Clone, Debug)]
+pub struct PaillierCiphertext(BigUint);
+
+impl PaillierCiphertext {
+    pub fn value(&self) -> &BigUint {
+        &self.0
+    }
+
+    ///
+    /// The big-endian encoding, as wide as `n^2`.
+    ///
+    pub fn to_bytes(&self, key: &PaillierPublicKey) -> Vec<u8> {
+        self.0
+            .to_bytes_be(key.ciphertext_size())
+            .unwrap_or_default()
+    }
+}
+
+///
+/// A Paillier public key.
+///
+#[derive(Clone, Debug)]
+pub struct PaillierPublicKey {
+    n: BigUint,
+    n2: Montgomery,
+}
+
+impl PaillierPublicKey {
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `n` is even or too small.
+    ///
+    pub fn new(n: &BigUint) -> SgxResult<PaillierPublicKey> {
+        if n.bits() < 16 {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let n = n.resize((n.bits() + 63) / 64);
+        let n2 = Montgomery::new(&n.mul(&n))?;
+        Ok(PaillierPublicKey { n, n2 })
+    }
+
+    pub fn n(&self) -> &BigUint {
+        &self.n
+    }
+
+    pub fn ciphertext_size(&self) -> usize {
+        (self.n2.modulus().bits() + 7) / 8
+    }
+
+    ///
+    /// Reads a ciphertext produced by `PaillierCiphertext::to_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The value is not below `n^2`.
+    ///
+    pub fn ciphertext_from_bytes(&self, bytes: &[u8]) -> SgxResult<PaillierCiphertext> {
+        let c = BigUint::from_bytes_be(bytes);
+        if !c.ct_lt(self.n2.modulus()) {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        Ok(PaillierCiphertext(c.resize(self.n2.modulus().width())))
+    }
+
+    // A random r^n mod n^2 with r a unit modulo n.
+    fn random_mask(&self) -> SgxResult<BigUint> {
+        let r = loop {
+            let r = BigUint::random_below(&self.n)?;
+            if !r.ct_is_zero() {
+                break r;
+            }
+        };
+        Ok(self.n2.pow(&r, &self.n))
+    }
+
+    ///
+    /// Encrypts `m`, which is reduced modulo `n`.
+    ///
+    pub fn encrypt(&self, m: &BigUint) -> SgxResult<PaillierCiphertext> {
+        let mask = self.random_mask()?;
+        self.encrypt_with_mask(m, &mask)
+    }
+
+    fn encrypt_with_mask(&self, m: &BigUint, mask: &BigUint) -> SgxResult<PaillierCiphertext> {
+        // (1 + n)^m = 1 + m * n mod n^2.
+        let m = Montgomery::new(&self.n)?.reduce(m);
+        let gm = self.n2.add(
+            &self.n2.reduce(&m.mul(&self.n)),
+            &BigUint::from_u64(1).resize(self.n2.modulus().width()),
+        );
+        Ok(PaillierCiphertext(self.n2.mul(&gm, mask)))
+    }
+
+    ///
+    /// A ciphertext of the sum of the plaintexts of `a` and `b`.
+    ///
+    pub fn add(&self, a: &PaillierCiphertext, b: &PaillierCiphertext) -> PaillierCiphertext {
+        PaillierCiphertext(self.n2.mul(&a.0, &b.0))
+    }
+
+    ///
+    /// A ciphertext of the plaintext of `a` plus `m`.
+    ///
+    pub fn add_plain(&self, a: &PaillierCiphertext, m: &BigUint) -> SgxResult<PaillierCiphertext> {
+        let one = BigUint::from_u64(1).resize(self.n2.modulus().width());
+        let b = self.encrypt_with_mask(m, &one)?;
+        Ok(self.add(a, &b))
+    }
+
+    ///
+    /// A ciphertext of the plaintext of `a` times `k`.
+    ///
+    pub fn mul_plain(&self, a: &PaillierCiphertext, k: &BigUint) -> PaillierCiphertext {
+        PaillierCiphertext(self.n2.pow(&a.0, k))
+    }
+
+    ///
+    /// A fresh ciphertext of the same plaintext, unlinkable to `a`.
+    ///
+    pub fn rerandomize(&self, a: &PaillierCiphertext) -> SgxResult<PaillierCiphertext> {
+        let mask = self.random_mask()?;
+        Ok(PaillierCiphertext(self.n2.mul(&a.0, &mask)))
+    }
+}
+
+///
+/// A Paillier private key.
+///
+/// # Description
+///
+/// Decryption works modulo `p^2` and `q^2` and recombines through the CRT; the
+/// exponentiations run in constant time. The factors are wiped when the key is dropped.
+///
+pub struct PaillierPrivateKey {
+    public: PaillierPublicKey,
+    p: BigUint,
+    q: BigUint,
+    p2: Montgomery,
+    q2: Montgomery,
+    hp: BigUint,
+    hq: BigUint,
+    crt: Crt,
+}
+
+impl Drop for PaillierPrivateKey {
+    fn drop(&mut self) {
+        self.p.wipe();
+        self.q.wipe();
+        self.hp.wipe();
+        self.hq.wipe();
+    }
+}
+
+impl PaillierPrivateKey {
+    ///
+    /// Generates a key with a modulus of `bits` bits, at least 1024 and even.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `bits` is odd or below 1024.
+    ///
+    pub fn generate(bits: usize) -> SgxResult<PaillierPrivateKey> {
+        if bits < 1024 || bits % 2 != 0 {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        loop {
+            let p = BigUint::random_prime(bits / 2)?;
+            let q = BigUint::random_prime(bits / 2)?;
+            if p.ct_eq(&q) {
+                continue;
+            }
+            return PaillierPrivateKey::from_primes(&p, &q);
+        }
+    }
+
+    ///
+    /// The key of the distinct odd primes `p` and `q`, of the same bit length.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The primes are equal, even, or of different bit lengths.
+    ///
+    pub fn from_primes(p: &BigUint, q: &BigUint) -> SgxResult<PaillierPrivateKey> {
+        if p.bits() != q.bits() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let crt = Crt::new(p, q)?;
+        let public = PaillierPublicKey::new(crt.modulus())?;
+        let p = crt.p().modulus().clone();
+        let q = crt.q().modulus().clone();
+        let p2 = Montgomery::new(&p.mul(&p))?;
+        let q2 = Montgomery::new(&q.mul(&q))?;
+
+        let one = BigUint::from_u64(1);
+        let g = public.n.add(&one);
+        let (p_minus_1, _) = p.overflowing_sub(&one);
+        let (q_minus_1, _) = q.overflowing_sub(&one);
+        let hp = crt.p().inv_prime(&l_function(&p2.pow(&g, &p_minus_1), &p));
+        let hq = crt.q().inv_prime(&l_function(&q2.pow(&g, &q_minus_1), &q));
+        Ok(PaillierPrivateKey {
+            public,
+            p,
+            q,
+            p2,
+            q2,
+            hp,
+            hq,
+            crt,
+        })
+    }
+
+    pub fn public_key(&self) -> &PaillierPublicKey {
+        &self.public
+    }
+
+    ///
+    /// Decrypts `c` to its plaintext below `n`.
+    ///
+    pub fn decrypt(&self, c: &PaillierCiphertext) -> BigUint {
+        let one = BigUint::from_u64(1);
+        let (p_minus_1, _) = self.p.overflowing_sub(&one);
+        let (q_minus_1, _) = self.q.overflowing_sub(&one);
+        let mp = self.crt.p().mul(
+            &l_function(&self.p2.pow(&c.0, &p_minus_1), &self.p),
+            &self.hp,
+        );
+        let mq = self.crt.q().mul(
+            &l_function(&self.q2.pow(&c.0, &q_minus_1), &self.q),
+            &self.hq,
+        );
+        self.crt.combine(&mp, &mq)
+    }
+}
+
+// L(x) = (x - 1) / p for x = 1 mod p, at the width of p.
+fn l_function(x: &BigUint, p: &BigUint) -> BigUint {
+    let (x_minus_1, _) = x.overflowing_sub(&BigUint::from_u64(1));
+    x_minus_1.div_exact(p).resize(p.width())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::rsgx_hex_decode;
+
+    fn big(hex: &str) -> BigUint {
+        BigUint::from_bytes_be(&rsgx_hex_decode(hex).unwrap())
+    }
+
+    // 2^64 - 59 and 2^64 - 83.
+    fn key() -> PaillierPrivateKey {
+        PaillierPrivateKey::from_primes(&big("ffffffffffffffc5"), &big("ffffffffffffffad")).unwrap()
+    }
+
+    #[test]
+    fn known_answer() {
+        let key = key();
+        let public = key.public_key();
+        assert!(public.n().ct_eq(&big("ffffffffffffff720000000000001321")));
+        assert_eq!(public.ciphertext_size(), 32);
+
+        // r^n mod n^2 for r = 0x0123456789abcdef0123456789abcdef.
+        let mask = big("7dc6e6e658f953f577dde0a75b1740a16f964151d1644f100eed7af287a110ec");
+        let m = big("1122334455667788");
+        let c = public.encrypt_with_mask(&m, &mask).unwrap();
+        assert_eq!(
+            c.to_bytes(public),
+            rsgx_hex_decode("7e207dc33b489849bd91d176cf7ecad2e6f6b772e2bdb209fdda53a225520191")
+                .unwrap()
+        );
+        assert!(key.decrypt(&c).ct_eq(&m));
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = key();
+        let public = key.public_key();
+        let m = big("0123456789abcdef0123456789abcdef");
+        let c = public.encrypt(&m).unwrap();
+        assert!(key.decrypt(&c).ct_eq(&m));
+
+        let bytes = c.to_bytes(public);
+        let read = public.ciphertext_from_bytes(&bytes).unwrap();
+        assert!(read.value().ct_eq(c.value()));
+        assert!(public.ciphertext_from_bytes(&[0xff_u8; 32]).is_err());
+
+        let fresh = public.rerandomize(&c).unwrap();
+        assert!(!fresh.value().ct_eq(c.value()));
+        assert!(key.decrypt(&fresh).ct_eq(&m));
+    }
+
+    #[test]
+    fn homomorphic_operations() {
+        let key = key();
+        let public = key.public_key();
+        let a = public.encrypt(&BigUint::from_u64(1_000_003)).unwrap();
+        let b = public.encrypt(&BigUint::from_u64(999_983)).unwrap();
+        assert!(key
+            .decrypt(&public.add(&a, &b))
+            .ct_eq(&BigUint::from_u64(1_999_986)));
+        let sum = public.add_plain(&a, &BigUint::from_u64(17)).unwrap();
+        assert!(key.decrypt(&sum).ct_eq(&BigUint::from_u64(1_000_020)));
+        let product = public.mul_plain(&a, &BigUint::from_u64(1_000));
+        assert!(key
+            .decrypt(&product)
+            .ct_eq(&BigUint::from_u64(1_000_003_000)));
+
+        // Plaintexts wrap modulo n: (n - 1) + 2 = 1.
+        let (n_minus_1, _) = public.n().overflowing_sub(&BigUint::from_u64(1));
+        let c = public.encrypt(&n_minus_1).unwrap();
+        let c = public.add_plain(&c, &BigUint::from_u64(2)).unwrap();
+        assert!(key.decrypt(&c).ct_eq(&BigUint::from_u64(1)));
+    }
+
+    #[test]
+    fn rejects_bad_primes() {
+        let p = big("ffffffffffffffc5");
+        assert!(PaillierPrivateKey::from_primes(&p, &p).is_err());
+        assert!(PaillierPrivateKey::from_primes(&p, &big("7fffffffffffffe7")).is_err());
+        assert!(PaillierPrivateKey::from_primes(&p, &big("fffffffffffffff0")).is_err());
+        assert!(PaillierPrivateKey::generate(512).is_err());
+        assert!(PaillierPrivateKey::generate(1025).is_err());
+    }
+}
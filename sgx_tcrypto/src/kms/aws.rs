@@ -0,0 +1,525 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! AWS KMS backend.
+//!
+//! Requests are JSON over HTTPS, authenticated with Signature Version 4. The enclave does
+//! not carry an HTTP/TLS stack of its own, so the connection is supplied by the caller
+//! through `KmsTransport`; the transport must terminate TLS inside the enclave for the
+//! key material in requests and responses to stay confidential.
+
+use super::{rsgx_hmac_sha256_parts, KeyBackend};
+use crate::crypto::SgxShaHandle;
+use crate::encoding::{rsgx_base64_decode, rsgx_base64_encode, rsgx_hex_encode};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use sgx_types::*;
+
+const CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+const TARGET_PREFIX: &str = "TrentService.";
+const MAX_DATA_KEY_SIZE: usize = 1024;
+
+///
+/// A response from the KMS endpoint.
+///
+pub struct KmsResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+///
+/// The HTTPS connection used by `AwsKmsBackend`.
+///
+pub trait KmsTransport {
+    ///
+    /// Sends a `POST /` request to `host` with the given headers and body and returns the
+    /// response. A failure to reach the endpoint is reported as an error, an HTTP error
+    /// status as a response.
+    ///
+    fn post(
+        &mut self,
+        host: &str,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) -> SgxResult<KmsResponse>;
+
+    ///
+    /// Returns the current time in seconds since the Unix epoch, used to date requests.
+    ///
+    fn now(&mut self) -> SgxResult<u64>;
+}
+
+///
+/// AWS credentials used to sign requests.
+///
+#[derive(Clone, Default)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl Drop for AwsCredentials {
+    fn drop(&mut self) {
+        unsafe {
+            for b in self.secret_access_key.as_bytes_mut() {
+                core::ptr::write_volatile(b, 0);
+            }
+        }
+    }
+}
+
+///
+/// A `KeyBackend` whose master keys are held by AWS KMS.
+///
+/// # Description
+///
+/// Wrapping uses the symmetric key `key_id` through `Encrypt`, `Decrypt` and
+/// `GenerateDataKey`. Signing and MACs need keys of their own kind, an asymmetric
+/// `ECC_NIST_P256` key and an `HMAC_256` key, configured with `sign_key_id` and
+/// `mac_key_id`; without them those operations return
+/// **SGX_ERROR_FEATURE_NOT_SUPPORTED**.
+///
+/// # Errors
+///
+/// **SGX_ERROR_SERVICE_UNAVAILABLE**
+///
+/// The service answered with a server error or throttled the request.
+///
+/// **SGX_ERROR_SERVICE_INVALID_PRIVILEGE**
+///
+/// The credentials may not use the key, or the key does not exist or is disabled.
+///
+/// **SGX_ERROR_MAC_MISMATCH**
+///
+/// `Decrypt` rejected the wrapped key.
+///
+/// **SGX_ERROR_UNEXPECTED**
+///
+/// Any other error response, or a response that could not be parsed.
+///
+pub struct AwsKmsBackend<T: KmsTransport> {
+    transport: T,
+    region: String,
+    credentials: AwsCredentials,
+    key_id: String,
+    sign_key_id: Option<String>,
+    mac_key_id: Option<String>,
+}
+
+impl<T: KmsTransport> AwsKmsBackend<T> {
+    pub fn new(
+        transport: T,
+        region: &str,
+        credentials: AwsCredentials,
+        key_id: &str,
+    ) -> AwsKmsBackend<T> {
+        AwsKmsBackend {
+            transport,
+            region: region.into(),
+            credentials,
+            key_id: key_id.into(),
+            sign_key_id: None,
+            mac_key_id: None,
+        }
+    }
+
+    pub fn sign_key_id(mut self, key_id: &str) -> Self {
+        self.sign_key_id = Some(key_id.into());
+        self
+    }
+
+    pub fn mac_key_id(mut self, key_id: &str) -> Self {
+        self.mac_key_id = Some(key_id.into());
+        self
+    }
+
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+
+    fn host(&self) -> String {
+        format!("kms.{}.amazonaws.com", self.region)
+    }
+
+    fn call(&mut self, action: &str, body: &str) -> SgxResult<Vec<u8>> {
+        let host = self.host();
+        let now = self.transport.now()?;
+        let headers = sign_request(
+            &self.region,
+            &self.credentials,
+            &host,
+            action,
+            body.as_bytes(),
+            now,
+        )?;
+        let response = self.transport.post(&host, &headers, body.as_bytes())?;
+        match response.status {
+            200 => Ok(response.body),
+            429 | 500..=599 => Err(sgx_status_t::SGX_ERROR_SERVICE_UNAVAILABLE),
+            _ => Err(map_error(&response.body)),
+        }
+    }
+}
+
+impl<T: KmsTransport> KeyBackend for AwsKmsBackend<T> {
+    fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    fn sign(&mut self, msg: &[u8]) -> SgxResult<sgx_ec256_signature_t> {
+        let key_id = self
+            .sign_key_id
+            .clone()
+            .ok_or(sgx_status_t::SGX_ERROR_FEATURE_NOT_SUPPORTED)?;
+        let digest = sha256(msg)?;
+        let mut body = String::new();
+        json_begin(&mut body, "KeyId", &key_id)?;
+        json_field(&mut body, "Message", &rsgx_base64_encode(&digest))?;
+        json_field(&mut body, "MessageType", "DIGEST")?;
+        json_field(&mut body, "SigningAlgorithm", "ECDSA_SHA_256")?;
+        body.push('}');
+
+        let response = self.call("Sign", &body)?;
+        let der = json_bytes(&response, "Signature")?;
+        der_to_signature(&der)
+    }
+
+    fn wrap(&mut self, key: &[u8]) -> SgxResult<Vec<u8>> {
+        let mut body = String::new();
+        json_begin(&mut body, "KeyId", &self.key_id)?;
+        json_field(&mut body, "Plaintext", &rsgx_base64_encode(key))?;
+        body.push('}');
+
+        let response = self.call("Encrypt", &body);
+        wipe_string(body);
+        json_bytes(&response?, "CiphertextBlob")
+    }
+
+    fn unwrap(&mut self, wrapped: &[u8]) -> SgxResult<Vec<u8>> {
+        let mut body = String::new();
+        json_begin(&mut body, "KeyId", &self.key_id)?;
+        json_field(&mut body, "CiphertextBlob", &rsgx_base64_encode(wrapped))?;
+        body.push('}');
+
+        let mut response = self.call("Decrypt", &body)?;
+        let key = json_bytes(&response, "Plaintext");
+        wipe(&mut response);
+        key
+    }
+
+    fn mac(&mut self, data: &[u8]) -> SgxResult<sgx_hmac_256bit_tag_t> {
+        let key_id = self
+            .mac_key_id
+            .clone()
+            .ok_or(sgx_status_t::SGX_ERROR_FEATURE_NOT_SUPPORTED)?;
+        let mut body = String::new();
+        json_begin(&mut body, "KeyId", &key_id)?;
+        json_field(&mut body, "Message", &rsgx_base64_encode(data))?;
+        json_field(&mut body, "MacAlgorithm", "HMAC_SHA_256")?;
+        body.push('}');
+
+        let response = self.call("GenerateMac", &body)?;
+        let mac = json_bytes(&response, "Mac")?;
+        let mut tag = sgx_hmac_256bit_tag_t::default();
+        if mac.len() != tag.len() {
+            return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+        }
+        tag.copy_from_slice(&mac);
+        Ok(tag)
+    }
+
+    ///
+    /// Generates the data key in KMS with `GenerateDataKey`, so that it never exists
+    /// unwrapped outside KMS and this enclave.
+    ///
+    fn generate_data_key(&mut self, len: usize) -> SgxResult<(Vec<u8>, Vec<u8>)> {
+        if len == 0 || len > MAX_DATA_KEY_SIZE {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let mut body = String::new();
+        json_begin(&mut body, "KeyId", &self.key_id)?;
+        let _ = write!(body, ",\"NumberOfBytes\":{}}}", len);
+
+        let mut response = self.call("GenerateDataKey", &body)?;
+        let result = json_bytes(&response, "Plaintext")
+            .and_then(|key| json_bytes(&response, "CiphertextBlob").map(|wrapped| (key, wrapped)));
+        wipe(&mut response);
+        let (key, wrapped) = result?;
+        if key.len() != len {
+            return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+        }
+        Ok((key, wrapped))
+    }
+}
+
+///
+/// Builds the headers of a KMS request: the `Authorization` header of AWS Signature
+/// Version 4 together with the headers it covers.
+///
+/// `now` is in seconds since the Unix epoch.
+///
+pub fn sign_request(
+    region: &str,
+    credentials: &AwsCredentials,
+    host: &str,
+    action: &str,
+    body: &[u8],
+    now: u64,
+) -> SgxResult<Vec<(String, String)>> {
+    let amz_date = amz_date(now);
+    let date = &amz_date[..8];
+
+    let mut headers: Vec<(String, String)> = vec![
+        ("content-type".into(), CONTENT_TYPE.into()),
+        ("host".into(), host.into()),
+        ("x-amz-date".into(), amz_date.clone()),
+    ];
+    if let Some(ref token) = credentials.session_token {
+        headers.push(("x-amz-security-token".into(), token.clone()));
+    }
+    headers.push((
+        "x-amz-target".into(),
+        format!("{}{}", TARGET_PREFIX, action),
+    ));
+
+    let signed_headers = headers
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<&str>>()
+        .join(";");
+    let mut canonical = String::from("POST\n/\n\n");
+    for (k, v) in headers.iter() {
+        let _ = writeln!(canonical, "{}:{}", k, v.trim());
+    }
+    let _ = write!(
+        canonical,
+        "\n{}\n{}",
+        signed_headers,
+        rsgx_hex_encode(&sha256(body)?)
+    );
+
+    let scope = format!("{}/{}/kms/aws4_request", date, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        rsgx_hex_encode(&sha256(canonical.as_bytes())?)
+    );
+
+    let mut secret = format!("AWS4{}", credentials.secret_access_key).into_bytes();
+    let k_date = rsgx_hmac_sha256_parts(&secret, &[date.as_bytes()]);
+    wipe(&mut secret);
+    let k_region = rsgx_hmac_sha256_parts(&k_date?, &[region.as_bytes()])?;
+    let k_service = rsgx_hmac_sha256_parts(&k_region, &[b"kms"])?;
+    let k_signing = rsgx_hmac_sha256_parts(&k_service, &[b"aws4_request"])?;
+    let signature = rsgx_hmac_sha256_parts(&k_signing, &[string_to_sign.as_bytes()])?;
+
+    headers.push((
+        "authorization".into(),
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key_id,
+            scope,
+            signed_headers,
+            rsgx_hex_encode(&signature)
+        ),
+    ));
+    Ok(headers)
+}
+
+fn map_error(body: &[u8]) -> sgx_status_t {
+    let kind = json_string(body, "__type").unwrap_or_default();
+    let kind = kind.rsplit('#').next().unwrap_or("");
+    match kind {
+        "InvalidCiphertextException" | "IncorrectKeyException" => {
+            sgx_status_t::SGX_ERROR_MAC_MISMATCH
+        }
+        "AccessDeniedException"
+        | "NotFoundException"
+        | "DisabledException"
+        | "KMSInvalidStateException"
+        | "InvalidKeyUsageException"
+        | "UnrecognizedClientException"
+        | "InvalidSignatureException" => sgx_status_t::SGX_ERROR_SERVICE_INVALID_PRIVILEGE,
+        "ThrottlingException" | "KMSInternalException" | "DependencyTimeoutException" => {
+            sgx_status_t::SGX_ERROR_SERVICE_UNAVAILABLE
+        }
+        _ => sgx_status_t::SGX_ERROR_UNEXPECTED,
+    }
+}
+
+fn sha256(data: &[u8]) -> SgxResult<sgx_sha256_hash_t> {
+    let handle = SgxShaHandle::new();
+    handle.init()?;
+    if !data.is_empty() {
+        handle.update_slice(data)?;
+    }
+    handle.get_hash()
+}
+
+fn amz_date(now: u64) -> String {
+    let days = (now / 86400) as i64;
+    let secs = now % 86400;
+    // Civil date from days since 1970-01-01 (proleptic Gregorian calendar).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { core::ptr::write_volatile(b, 0) };
+    }
+}
+
+fn wipe_string(s: String) {
+    let mut bytes = s.into_bytes();
+    wipe(&mut bytes);
+}
+
+fn json_begin(out: &mut String, name: &str, value: &str) -> SgxError {
+    out.push('{');
+    json_value(out, name, value)
+}
+
+fn json_field(out: &mut String, name: &str, value: &str) -> SgxError {
+    out.push(',');
+    json_value(out, name, value)
+}
+
+fn json_value(out: &mut String, name: &str, value: &str) -> SgxError {
+    let _ = write!(out, "\"{}\":\"", name);
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if (c as u32) < 0x20 => return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    Ok(())
+}
+
+// Finds the string value of the top-level member `name`. KMS responses are flat objects
+// whose values are base64, identifiers and messages, so escapes are only skipped over.
+fn json_string(body: &[u8], name: &str) -> Option<String> {
+    let key = format!("\"{}\"", name);
+    let key = key.as_bytes();
+    let mut i = 0;
+    while i + key.len() <= body.len() {
+        if &body[i..i + key.len()] != key {
+            i += 1;
+            continue;
+        }
+        let mut j = i + key.len();
+        while j < body.len() && body[j].is_ascii_whitespace() {
+            j += 1;
+        }
+        if j >= body.len() || body[j] != b':' {
+            i += 1;
+            continue;
+        }
+        j += 1;
+        while j < body.len() && body[j].is_ascii_whitespace() {
+            j += 1;
+        }
+        if j >= body.len() || body[j] != b'"' {
+            return None;
+        }
+        let start = j + 1;
+        let mut end = start;
+        while end < body.len() && body[end] != b'"' {
+            end += if body[end] == b'\\' { 2 } else { 1 };
+        }
+        if end >= body.len() {
+            return None;
+        }
+        return core::str::from_utf8(&body[start..end])
+            .ok()
+            .map(String::from);
+    }
+    None
+}
+
+fn json_bytes(body: &[u8], name: &str) -> SgxResult<Vec<u8>> {
+    let mut value = json_string(body, name).ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)?;
+    let bytes = rsgx_base64_decode(&value);
+    unsafe { wipe(value.as_bytes_mut()) };
+    bytes.map_err(|_| sgx_status_t::SGX_ERROR_UNEXPECTED)
+}
+
+// Converts a DER `ECDSA-Sig-Value` into the little-endian form used by the SGX SDK.
+fn der_to_signature(der: &[u8]) -> SgxResult<sgx_ec256_signature_t> {
+    let invalid = sgx_status_t::SGX_ERROR_UNEXPECTED;
+    if der.len() < 2 || der[0] != 0x30 || der[1] as usize != der.len() - 2 || der[1] >= 0x80 {
+        return Err(invalid);
+    }
+    let mut rest = &der[2..];
+    let mut read_integer = || -> SgxResult<[u32; 8]> {
+        if rest.len() < 2 || rest[0] != 0x02 || rest[1] as usize > rest.len() - 2 {
+            return Err(invalid);
+        }
+        let len = rest[1] as usize;
+        let mut value = &rest[2..2 + len];
+        rest = &rest[2 + len..];
+        while value.len() > 1 && value[0] == 0 {
+            value = &value[1..];
+        }
+        if value.is_empty() || value.len() > 32 {
+            return Err(invalid);
+        }
+        let mut words = [0_u32; 8];
+        for (i, b) in value.iter().rev().enumerate() {
+            words[i / 4] |= (*b as u32) << (8 * (i % 4));
+        }
+        Ok(words)
+    };
+    let x = read_integer()?;
+    let y = read_integer()?;
+    if !rest.is_empty() {
+        return Err(invalid);
+    }
+    Ok(sgx_ec256_signature_t { x, y })
+}
@@ -0,0 +1,155 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Key backends
+//!
+//! `KeyBackend` abstracts where a master key is kept: sealed inside the enclave (see
+//! `SgxSealedKeyBackend` in `sgx_tseal`) or in a remote key management service such as
+//! AWS KMS (`aws::AwsKmsBackend`). Code written against the trait does not change when the
+//! custody of the key does.
+
+use crate::crypto::SgxShaHandle;
+use alloc::vec::Vec;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+pub mod aws;
+
+///
+/// A master key held by a backend.
+///
+/// # Description
+///
+/// A backend offers four operations on its key: ECDSA P-256 signing, wrapping and
+/// unwrapping of other keys, and a MAC from which `derive` builds key derivation. A
+/// backend without one of them returns **SGX_ERROR_FEATURE_NOT_SUPPORTED**.
+///
+/// Wrapped keys are opaque; they can only be unwrapped by the backend, and master key,
+/// that wrapped them.
+///
+pub trait KeyBackend {
+    ///
+    /// Identifies the master key, for example to record which key wrapped a data key.
+    ///
+    fn key_id(&self) -> &str;
+
+    ///
+    /// Signs `msg` with ECDSA P-256 over SHA-256.
+    ///
+    fn sign(&mut self, msg: &[u8]) -> SgxResult<sgx_ec256_signature_t>;
+
+    ///
+    /// Encrypts `key` under the master key.
+    ///
+    fn wrap(&mut self, key: &[u8]) -> SgxResult<Vec<u8>>;
+
+    ///
+    /// Decrypts a key wrapped by `wrap`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The wrapped key was not wrapped under this master key, or has been modified.
+    ///
+    fn unwrap(&mut self, wrapped: &[u8]) -> SgxResult<Vec<u8>>;
+
+    ///
+    /// Computes HMAC-SHA256 of `data` under the master key.
+    ///
+    fn mac(&mut self, data: &[u8]) -> SgxResult<sgx_hmac_256bit_tag_t>;
+
+    ///
+    /// Derives `out.len()` bytes, at most 8160, bound to `context`: HKDF-Expand over
+    /// SHA-256 with `mac(context)` as the pseudorandom key.
+    ///
+    fn derive(&mut self, context: &[u8], out: &mut [u8]) -> SgxError {
+        let prk = self.mac(context)?;
+        rsgx_hkdf_sha256_expand(&prk, &[], out)
+    }
+
+    ///
+    /// Generates a random data key of `len` bytes, returning it and its wrapped form.
+    ///
+    fn generate_data_key(&mut self, len: usize) -> SgxResult<(Vec<u8>, Vec<u8>)> {
+        let mut key = vec![0_u8; len];
+        rsgx_read_rand(&mut key)?;
+        let wrapped = self.wrap(&key)?;
+        Ok((key, wrapped))
+    }
+}
+
+///
+/// The rsgx_hmac_sha256_parts function computes HMAC-SHA256 (RFC 2104) with a key of any
+/// length over the concatenation of `parts`.
+///
+pub fn rsgx_hmac_sha256_parts(key: &[u8], parts: &[&[u8]]) -> SgxResult<sgx_hmac_256bit_tag_t> {
+    let sha256 = |parts: &[&[u8]]| -> SgxResult<sgx_sha256_hash_t> {
+        let handle = SgxShaHandle::new();
+        handle.init()?;
+        for part in parts.iter().filter(|p| !p.is_empty()) {
+            handle.update_slice(part)?;
+        }
+        handle.get_hash()
+    };
+
+    let mut block = [0_u8; 64];
+    if key.len() > block.len() {
+        block[..SGX_SHA256_HASH_SIZE].copy_from_slice(&sha256(&[key])?);
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut ipad = [0x36_u8; 64];
+    let mut opad = [0x5c_u8; 64];
+    for ((i, o), k) in ipad.iter_mut().zip(opad.iter_mut()).zip(block.iter()) {
+        *i ^= k;
+        *o ^= k;
+    }
+
+    let mut inner_parts = Vec::with_capacity(parts.len() + 1);
+    inner_parts.push(&ipad[..]);
+    inner_parts.extend_from_slice(parts);
+    let inner = sha256(&inner_parts)?;
+    sha256(&[&opad, &inner])
+}
+
+///
+/// The rsgx_hkdf_sha256_expand function fills `out` with HKDF-Expand (RFC 5869) over
+/// SHA-256.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// `out` is longer than 255 * 32 bytes.
+///
+pub fn rsgx_hkdf_sha256_expand(prk: &[u8], info: &[u8], out: &mut [u8]) -> SgxError {
+    if out.len() > 255 * SGX_SHA256_HASH_SIZE {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    let mut previous: Option<sgx_hmac_256bit_tag_t> = None;
+    for (i, chunk) in out.chunks_mut(SGX_SHA256_HASH_SIZE).enumerate() {
+        let counter = [i as u8 + 1];
+        let t = match previous {
+            Some(ref t) => rsgx_hmac_sha256_parts(prk, &[t, info, &counter])?,
+            None => rsgx_hmac_sha256_parts(prk, &[info, &counter])?,
+        };
+        chunk.copy_from_slice(&t[..chunk.len()]);
+        previous = Some(t);
+    }
+    Ok(())
+}
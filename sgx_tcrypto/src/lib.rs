@@ -23,11 +23,46 @@
 
 #![no_std]
 #![cfg_attr(target_env = "sgx", feature(rustc_private))]
+#![feature(stdsimd)]
+#![feature(avx512_target_feature)]
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 #![allow(clippy::too_many_arguments)]
 
+#[cfg(all(not(target_env = "sgx"), feature = "thread"))]
+extern crate sgx_tstd as std;
+#[cfg(all(target_env = "sgx", feature = "thread"))]
+extern crate std;
+
+#[macro_use]
+extern crate alloc;
+
+extern crate sgx_trts;
 extern crate sgx_types;
 
 mod crypto;
 pub use self::crypto::*;
+
+mod ecdsa_multi;
+pub use self::ecdsa_multi::SgxEcdsaMultiVerifier;
+
+mod ecdsa_nonce;
+pub use self::ecdsa_nonce::SgxEcdsaNonce;
+
+mod fp256;
+mod p256;
+
+mod transcript;
+pub use self::transcript::SgxTranscript;
+
+pub mod bignum;
+pub mod blake3;
+pub mod ed25519;
+pub mod encoding;
+pub mod envelope;
+pub mod homomorphic;
+pub mod kms;
+pub mod merkle;
+pub mod mpc;
+pub mod vrf;
+pub mod zk;
@@ -0,0 +1,409 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Merkle Trees
+//!
+//! Merkle trees over SHA-256, BLAKE3, or any other 256-bit hash through `MerkleHasher`.
+//!
+//! The tree shape and the domain separation of leaves and inner nodes follow RFC 6962:
+//! a leaf hashes as `H(0x00 || data)` and an inner node as `H(0x01 || left || right)`. A
+//! level with an odd number of nodes promotes its last node unchanged to the next level.
+//!
+//! `MerkleTree` keeps all levels and produces inclusion proofs for single leaves and compact
+//! multiproofs for sets of leaves. `MerkleStream` computes the same root over leaves pushed
+//! one at a time, in O(log n) memory.
+
+use crate::blake3::Blake3Hasher;
+use crate::crypto::SgxShaHandle;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use sgx_types::*;
+
+pub type MerkleDigest = [u8; 32];
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+///
+/// The hash function of a Merkle tree.
+///
+pub trait MerkleHasher {
+    /// Hashes the concatenation of `parts`.
+    fn hash(parts: &[&[u8]]) -> SgxResult<MerkleDigest>;
+
+    fn hash_leaf(data: &[u8]) -> SgxResult<MerkleDigest> {
+        Self::hash(&[&[LEAF_PREFIX], data])
+    }
+
+    fn hash_node(left: &MerkleDigest, right: &MerkleDigest) -> SgxResult<MerkleDigest> {
+        Self::hash(&[&[NODE_PREFIX], left, right])
+    }
+}
+
+///
+/// SHA-256 through the SGX cryptography library.
+///
+pub struct Sha256Hasher;
+
+impl MerkleHasher for Sha256Hasher {
+    fn hash(parts: &[&[u8]]) -> SgxResult<MerkleDigest> {
+        let handle = SgxShaHandle::new();
+        handle.init()?;
+        for part in parts.iter().filter(|p| !p.is_empty()) {
+            handle.update_slice(part)?;
+        }
+        handle.get_hash()
+    }
+}
+
+impl MerkleHasher for Blake3Hasher {
+    fn hash(parts: &[&[u8]]) -> SgxResult<MerkleDigest> {
+        let mut hasher = Blake3Hasher::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        Ok(hasher.finalize())
+    }
+}
+
+///
+/// A Merkle tree with all of its levels.
+///
+pub struct MerkleTree<H: MerkleHasher = Sha256Hasher> {
+    // levels[0] holds the leaf hashes, the last level the root.
+    levels: Vec<Vec<MerkleDigest>>,
+    marker: PhantomData<H>,
+}
+
+///
+/// A proof that one leaf is included in a tree.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof {
+    pub leaf_index: usize,
+    pub leaf_count: usize,
+    /// The siblings on the path from the leaf to the root, leaf level first.
+    pub path: Vec<MerkleDigest>,
+}
+
+///
+/// A proof that a set of leaves is included in a tree.
+///
+/// Siblings shared by several paths, or computable from the proven leaves, are omitted.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleMultiProof {
+    /// The proven leaf indices, sorted and without duplicates.
+    pub leaf_indices: Vec<usize>,
+    pub leaf_count: usize,
+    /// The missing siblings, level by level from the leaves, in index order within a level.
+    pub nodes: Vec<MerkleDigest>,
+}
+
+fn next_level<H: MerkleHasher>(level: &[MerkleDigest]) -> SgxResult<Vec<MerkleDigest>> {
+    let mut next = Vec::with_capacity((level.len() + 1) / 2);
+    for pair in level.chunks(2) {
+        match pair {
+            [left, right] => next.push(H::hash_node(left, right)?),
+            [last] => next.push(*last),
+            _ => unreachable!(),
+        }
+    }
+    Ok(next)
+}
+
+impl<H: MerkleHasher> MerkleTree<H> {
+    ///
+    /// Builds a tree over `leaves`, hashing each of them as a leaf.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// There are no leaves.
+    ///
+    pub fn from_leaves<'a, I>(leaves: I) -> SgxResult<Self>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let hashes = leaves
+            .into_iter()
+            .map(H::hash_leaf)
+            .collect::<SgxResult<Vec<_>>>()?;
+        Self::from_leaf_hashes(hashes)
+    }
+
+    ///
+    /// Builds a tree over leaves that are already hashed with `MerkleHasher::hash_leaf`.
+    ///
+    pub fn from_leaf_hashes(hashes: Vec<MerkleDigest>) -> SgxResult<Self> {
+        if hashes.is_empty() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let mut levels = vec![hashes];
+        while levels[levels.len() - 1].len() > 1 {
+            let next = next_level::<H>(&levels[levels.len() - 1])?;
+            levels.push(next);
+        }
+        Ok(MerkleTree {
+            levels,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn root(&self) -> MerkleDigest {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn leaf_hash(&self, index: usize) -> Option<MerkleDigest> {
+        self.levels[0].get(index).copied()
+    }
+
+    pub fn proof(&self, leaf_index: usize) -> Option<MerkleProof> {
+        if leaf_index >= self.leaf_count() {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut index = leaf_index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if sibling < level.len() {
+                path.push(level[sibling]);
+            }
+            index /= 2;
+        }
+        Some(MerkleProof {
+            leaf_index,
+            leaf_count: self.leaf_count(),
+            path,
+        })
+    }
+
+    pub fn multiproof(&self, leaf_indices: &[usize]) -> Option<MerkleMultiProof> {
+        let mut known: Vec<usize> = leaf_indices.to_vec();
+        known.sort_unstable();
+        known.dedup();
+        if known.is_empty() || known[known.len() - 1] >= self.leaf_count() {
+            return None;
+        }
+        let proven = known.clone();
+
+        let mut nodes = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            for (i, &index) in known.iter().enumerate() {
+                let sibling = index ^ 1;
+                let sibling_known = if sibling < index {
+                    i > 0 && known[i - 1] == sibling
+                } else {
+                    known.get(i + 1) == Some(&sibling)
+                };
+                if sibling < level.len() && !sibling_known {
+                    nodes.push(level[sibling]);
+                }
+            }
+            known = parents(&known);
+        }
+        Some(MerkleMultiProof {
+            leaf_indices: proven,
+            leaf_count: self.leaf_count(),
+            nodes,
+        })
+    }
+}
+
+fn parents(indices: &[usize]) -> Vec<usize> {
+    let mut parents: Vec<usize> = indices.iter().map(|i| i / 2).collect();
+    parents.dedup();
+    parents
+}
+
+impl MerkleProof {
+    ///
+    /// Checks that `leaf`, unhashed, is included under `root`.
+    ///
+    pub fn verify<H: MerkleHasher>(&self, root: &MerkleDigest, leaf: &[u8]) -> SgxResult<bool> {
+        self.verify_hash::<H>(root, &H::hash_leaf(leaf)?)
+    }
+
+    pub fn verify_hash<H: MerkleHasher>(
+        &self,
+        root: &MerkleDigest,
+        leaf_hash: &MerkleDigest,
+    ) -> SgxResult<bool> {
+        if self.leaf_index >= self.leaf_count {
+            return Ok(false);
+        }
+        let mut hash = *leaf_hash;
+        let mut index = self.leaf_index;
+        let mut width = self.leaf_count;
+        let mut path = self.path.iter();
+        while width > 1 {
+            let sibling = index ^ 1;
+            if sibling < width {
+                let node = match path.next() {
+                    Some(node) => node,
+                    None => return Ok(false),
+                };
+                hash = if sibling < index {
+                    H::hash_node(node, &hash)?
+                } else {
+                    H::hash_node(&hash, node)?
+                };
+            }
+            index /= 2;
+            width = (width + 1) / 2;
+        }
+        Ok(path.next().is_none() && &hash == root)
+    }
+}
+
+impl MerkleMultiProof {
+    ///
+    /// Checks that `leaves`, unhashed and in the order of `leaf_indices`, are included
+    /// under `root`.
+    ///
+    pub fn verify<H: MerkleHasher>(&self, root: &MerkleDigest, leaves: &[&[u8]]) -> SgxResult<bool> {
+        let hashes = leaves
+            .iter()
+            .map(|leaf| H::hash_leaf(leaf))
+            .collect::<SgxResult<Vec<_>>>()?;
+        self.verify_hashes::<H>(root, &hashes)
+    }
+
+    pub fn verify_hashes<H: MerkleHasher>(
+        &self,
+        root: &MerkleDigest,
+        leaf_hashes: &[MerkleDigest],
+    ) -> SgxResult<bool> {
+        let sorted = self.leaf_indices.windows(2).all(|w| w[0] < w[1]);
+        if !sorted
+            || self.leaf_indices.is_empty()
+            || self.leaf_indices.len() != leaf_hashes.len()
+            || self.leaf_indices[self.leaf_indices.len() - 1] >= self.leaf_count
+        {
+            return Ok(false);
+        }
+
+        let mut known: Vec<(usize, MerkleDigest)> = self
+            .leaf_indices
+            .iter()
+            .copied()
+            .zip(leaf_hashes.iter().copied())
+            .collect();
+        let mut nodes = self.nodes.iter();
+        let mut width = self.leaf_count;
+        while width > 1 {
+            let mut next: Vec<(usize, MerkleDigest)> = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let (index, hash) = known[i];
+                let sibling = index ^ 1;
+                let parent = if sibling >= width {
+                    hash
+                } else if sibling > index && known.get(i + 1).map(|k| k.0) == Some(sibling) {
+                    i += 1;
+                    H::hash_node(&hash, &known[i].1)?
+                } else {
+                    let node = match nodes.next() {
+                        Some(node) => node,
+                        None => return Ok(false),
+                    };
+                    if sibling < index {
+                        H::hash_node(node, &hash)?
+                    } else {
+                        H::hash_node(&hash, node)?
+                    }
+                };
+                next.push((index / 2, parent));
+                i += 1;
+            }
+            known = next;
+            width = (width + 1) / 2;
+        }
+        Ok(nodes.next().is_none() && known.len() == 1 && &known[0].1 == root)
+    }
+}
+
+///
+/// Streaming construction of a Merkle root.
+///
+/// Leaves are pushed one at a time and only the roots of the complete subtrees built so far
+/// are kept. The result equals the root of a `MerkleTree` over the same leaves.
+///
+pub struct MerkleStream<H: MerkleHasher = Sha256Hasher> {
+    // (height, root) of complete subtrees, heights strictly decreasing.
+    stack: Vec<(u32, MerkleDigest)>,
+    count: usize,
+    marker: PhantomData<H>,
+}
+
+impl<H: MerkleHasher> Default for MerkleStream<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: MerkleHasher> MerkleStream<H> {
+    pub fn new() -> Self {
+        MerkleStream {
+            stack: Vec::new(),
+            count: 0,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn push(&mut self, leaf: &[u8]) -> SgxError {
+        self.push_hash(H::hash_leaf(leaf)?)
+    }
+
+    pub fn push_hash(&mut self, leaf_hash: MerkleDigest) -> SgxError {
+        let mut node = (0, leaf_hash);
+        while let Some(&(height, left)) = self.stack.last() {
+            if height != node.0 {
+                break;
+            }
+            self.stack.pop();
+            node = (height + 1, H::hash_node(&left, &node.1)?);
+        }
+        self.stack.push(node);
+        self.count += 1;
+        Ok(())
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.count
+    }
+
+    ///
+    /// Returns the root over the leaves pushed so far, or None if there are none.
+    ///
+    pub fn root(&self) -> SgxResult<Option<MerkleDigest>> {
+        let mut nodes = self.stack.iter().rev();
+        let mut root = match nodes.next() {
+            Some(&(_, hash)) => hash,
+            None => return Ok(None),
+        };
+        for (_, left) in nodes {
+            root = H::hash_node(left, &root)?;
+        }
+        Ok(Some(root))
+    }
+}
@@ -0,0 +1,825 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Two-of-n threshold ECDSA over P-256.
+//!
+//! A signing key is split into `n` Shamir shares of degree one. Any two holders sign
+//! together, and the signature verifies with the SDK's ECDSA under the public key; no
+//! holder alone learns the key.
+//!
+//! Signing has two phases, as in Gennaro and Goldfeder ("Fast multiparty threshold ECDSA
+//! with fast trustless setup", CCS 2018). Presigning does not depend on the message and
+//! runs three rounds. Each signer `i` picks nonce shares `k_i` and `gamma_i` and weighs
+//! its key share into an additive share `w_i`; the cross products `k_i * gamma_j` and
+//! `k_i * w_j` are shared with MtA, a multiplicative-to-additive conversion over Paillier
+//! encryption. The signers learn `delta = k * gamma` and `R = delta^-1 * gamma * G =
+//! k^-1 * G`, and keep shares `sigma_i` of `k * x`. Signing is then local: each signer
+//! releases `s_i = m * k_i + r * sigma_i`, and `s = s_1 + s_2`.
+//!
+//! A presignature must sign at most once, since two signatures with one nonce reveal the
+//! key. `SgxPresignature::sign` consumes it, and nonce shares are wiped when dropped.
+//!
+//! As elsewhere in this module, the protocol is secure against semi-honest parties: MtA
+//! runs without the range proofs that would bind a malicious one. The combined signature
+//! is verified before it is returned.
+
+use crate::bignum::{BigUint, Montgomery};
+use crate::crypto::{rsgx_sha256_slice, SgxEccHandle};
+use crate::homomorphic::paillier::{PaillierCiphertext, PaillierPrivateKey, PaillierPublicKey};
+use crate::p256::{Point, Scalar, ORDER};
+use alloc::vec::Vec;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+///
+/// The smallest Paillier modulus, in bits, that a signer accepts for MtA.
+///
+pub const THRESHOLD_ECDSA_PAILLIER_BITS: usize = 2048;
+
+// The MtA mask is drawn from [0, 2^MTA_MASK_BITS), which hides a product of two scalars
+// statistically and leaves the sum far below the Paillier modulus.
+const MTA_MASK_BITS: usize = 640;
+
+const PUBLIC_KEY_SIZE: usize = 64;
+
+fn public_to_bytes(public: &sgx_ec256_public_t) -> [u8; PUBLIC_KEY_SIZE] {
+    let mut bytes = [0_u8; PUBLIC_KEY_SIZE];
+    bytes[..32].copy_from_slice(&public.gx);
+    bytes[32..].copy_from_slice(&public.gy);
+    bytes
+}
+
+fn point_from_bytes(bytes: &[u8]) -> SgxResult<Point> {
+    let mut public = sgx_ec256_public_t::default();
+    public.gx.copy_from_slice(&bytes[..32]);
+    public.gy.copy_from_slice(&bytes[32..PUBLIC_KEY_SIZE]);
+    Point::from_public(&public).ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+}
+
+fn scalar_to_biguint(s: &Scalar) -> BigUint {
+    BigUint::from_bytes_be(&s.to_bytes_be())
+}
+
+fn scalar_from_biguint(x: &BigUint, order: &Montgomery) -> SgxResult<Scalar> {
+    let mut reduced = order.reduce(x);
+    let mut bytes = [0_u8; 32];
+    bytes.copy_from_slice(&reduced.to_bytes_be(32)?);
+    let s = Scalar::from_bytes_be(&bytes);
+    reduced.wipe();
+    wipe(&mut bytes);
+    s.ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)
+}
+
+fn scalar_to_words(s: &Scalar) -> [u32; 8] {
+    let mut bytes = s.to_bytes_be();
+    bytes.reverse();
+    let mut words = [0_u32; 8];
+    for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+        let mut b = [0_u8; 4];
+        b.copy_from_slice(chunk);
+        *word = u32::from_le_bytes(b);
+    }
+    words
+}
+
+fn digest(message: &[u8]) -> SgxResult<Scalar> {
+    Ok(Scalar::from_bytes_be_reduced(&rsgx_sha256_slice(message)?))
+}
+
+// The Lagrange coefficient of share `i` for interpolating at zero from shares `i` and `j`:
+// j / (j - i).
+fn lagrange(i: u16, j: u16) -> Scalar {
+    let j_s = Scalar::from_u64(u64::from(j));
+    j_s.mul(&j_s.sub(&Scalar::from_u64(u64::from(i))).invert())
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { core::ptr::write_volatile(b, 0) };
+    }
+}
+
+///
+/// One holder's share of a two-of-n signing key.
+///
+pub struct SgxEcdsaKeyShare {
+    index: u16,
+    secret: Scalar,
+    public_key: sgx_ec256_public_t,
+}
+
+impl Drop for SgxEcdsaKeyShare {
+    fn drop(&mut self) {
+        self.secret.wipe();
+    }
+}
+
+impl SgxEcdsaKeyShare {
+    ///
+    /// The share of a secret saved with `secret`, e.g. after sealing.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The index is zero, the secret is not below the group order, or the public key is
+    /// not on the curve.
+    ///
+    pub fn from_secret(
+        index: u16,
+        secret: &[u8; 32],
+        public_key: &sgx_ec256_public_t,
+    ) -> SgxResult<SgxEcdsaKeyShare> {
+        if index == 0 || Point::from_public(public_key).is_none() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let secret =
+            Scalar::from_bytes_be(secret).ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+        Ok(SgxEcdsaKeyShare {
+            index,
+            secret,
+            public_key: *public_key,
+        })
+    }
+
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    ///
+    /// The share itself, big-endian.
+    ///
+    pub fn secret(&self) -> [u8; 32] {
+        self.secret.to_bytes_be()
+    }
+
+    pub fn public_key(&self) -> &sgx_ec256_public_t {
+        &self.public_key
+    }
+}
+
+///
+/// Splits a P-256 private key into `parties` shares, any two of which can sign.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// There are fewer than two parties, or the key is zero or not below the group order.
+///
+pub fn rsgx_threshold_ecdsa_split(
+    private: &sgx_ec256_private_t,
+    parties: u16,
+) -> SgxResult<Vec<SgxEcdsaKeyShare>> {
+    let mut bytes = private.r;
+    bytes.reverse();
+    let x = Scalar::from_bytes_be(&bytes);
+    wipe(&mut bytes);
+    let mut x = x.ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+    if parties < 2 || x.is_zero() {
+        x.wipe();
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+
+    let public_key = Point::GENERATOR
+        .mul_ct(&x)
+        .to_public()
+        .ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)?;
+    // f(t) = x + a * t, and share i is f(i).
+    let mut a = Scalar::random()?;
+    let shares = (1..=parties)
+        .map(|index| SgxEcdsaKeyShare {
+            index,
+            secret: x.add(&a.mul(&Scalar::from_u64(u64::from(index)))),
+            public_key,
+        })
+        .collect();
+    x.wipe();
+    a.wipe();
+    Ok(shares)
+}
+
+///
+/// Generates a fresh P-256 key and splits it into `parties` shares, any two of which can
+/// sign. The key itself never exists outside this call.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// There are fewer than two parties.
+///
+pub fn rsgx_threshold_ecdsa_deal(parties: u16) -> SgxResult<Vec<SgxEcdsaKeyShare>> {
+    let mut private = sgx_ec256_private_t {
+        r: Scalar::random()?.to_bytes_be(),
+    };
+    private.r.reverse();
+    let shares = rsgx_threshold_ecdsa_split(&private, parties);
+    wipe(&mut private.r);
+    shares
+}
+
+// What a signer learns from the other signer's first message and its own MtA responses.
+struct PeerState {
+    gamma_point: Point,
+    // The responder's shares of k_j * gamma_i and k_j * w_i.
+    beta_delta: Scalar,
+    beta_sigma: Scalar,
+}
+
+///
+/// One of the two signers of a presigning session.
+///
+/// # Description
+///
+/// Both signers run the same rounds:
+///
+/// 1. Send `message` to the other signer.
+/// 2. Pass the other signer's message to `respond`, and send the response back.
+/// 3. Pass the other signer's response to `finish_mta`, and send the returned share of
+///    `delta` back.
+/// 4. Pass the other signer's share of `delta` to `finish`.
+///
+/// The Paillier key may serve many sessions; generating one takes far longer than
+/// presigning.
+///
+pub struct SgxPresigner<'a> {
+    paillier: &'a PaillierPrivateKey,
+    public_key: sgx_ec256_public_t,
+    order: Montgomery,
+    k: Scalar,
+    gamma: Scalar,
+    w: Scalar,
+    gamma_point: Point,
+    encrypted_k: PaillierCiphertext,
+    peer: Option<PeerState>,
+    delta: Option<Scalar>,
+    sigma: Scalar,
+}
+
+impl<'a> Drop for SgxPresigner<'a> {
+    fn drop(&mut self) {
+        self.k.wipe();
+        self.gamma.wipe();
+        self.w.wipe();
+        self.sigma.wipe();
+        if let Some(peer) = self.peer.as_mut() {
+            peer.beta_delta.wipe();
+            peer.beta_sigma.wipe();
+        }
+    }
+}
+
+impl<'a> SgxPresigner<'a> {
+    ///
+    /// Starts presigning with the holder of share `peer_index`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The peer index is zero or that of `share`, or the Paillier modulus is shorter than
+    /// `THRESHOLD_ECDSA_PAILLIER_BITS`.
+    ///
+    pub fn new(
+        share: &SgxEcdsaKeyShare,
+        peer_index: u16,
+        paillier: &'a PaillierPrivateKey,
+    ) -> SgxResult<SgxPresigner<'a>> {
+        if peer_index == 0
+            || peer_index == share.index
+            || paillier.public_key().n().bits() < THRESHOLD_ECDSA_PAILLIER_BITS
+        {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let k = Scalar::random()?;
+        let gamma = Scalar::random()?;
+        let encrypted_k = paillier.public_key().encrypt(&scalar_to_biguint(&k))?;
+        Ok(SgxPresigner {
+            paillier,
+            public_key: share.public_key,
+            order: Montgomery::new(&BigUint::from_bytes_be(&ORDER))?,
+            k,
+            gamma,
+            w: share.secret.mul(&lagrange(share.index, peer_index)),
+            gamma_point: Point::GENERATOR.mul_ct(&gamma),
+            encrypted_k,
+            peer: None,
+            delta: None,
+            sigma: Scalar::ZERO,
+        })
+    }
+
+    ///
+    /// The first message: the Paillier modulus, `k_i` encrypted under it, and
+    /// `gamma_i * G`.
+    ///
+    pub fn message(&self) -> SgxResult<Vec<u8>> {
+        let key = self.paillier.public_key();
+        let n_size = (key.n().bits() + 7) / 8;
+        let mut message = Vec::with_capacity(2 + n_size + key.ciphertext_size() + PUBLIC_KEY_SIZE);
+        message.extend_from_slice(&(n_size as u16).to_le_bytes());
+        message.extend_from_slice(&key.n().to_bytes_be(n_size)?);
+        message.extend_from_slice(&self.encrypted_k.to_bytes(key));
+        let gamma_point = self
+            .gamma_point
+            .to_public()
+            .ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)?;
+        message.extend_from_slice(&public_to_bytes(&gamma_point));
+        Ok(message)
+    }
+
+    // Returns Enc(a * b + mask) under `key` and the responder's share -mask.
+    fn mta_respond(
+        &self,
+        key: &PaillierPublicKey,
+        encrypted_a: &PaillierCiphertext,
+        b: &Scalar,
+    ) -> SgxResult<(PaillierCiphertext, Scalar)> {
+        let mut mask_bytes = [0_u8; MTA_MASK_BITS / 8];
+        rsgx_read_rand(&mut mask_bytes)?;
+        let mut mask = BigUint::from_bytes_be(&mask_bytes);
+        wipe(&mut mask_bytes);
+        let mut b = scalar_to_biguint(b);
+        let product = key.mul_plain(encrypted_a, &b);
+        let response = key.rerandomize(&key.add_plain(&product, &mask)?);
+        let share = scalar_from_biguint(&mask, &self.order).map(|s| s.neg());
+        mask.wipe();
+        b.wipe();
+        Ok((response?, share?))
+    }
+
+    ///
+    /// Answers the other signer's first message with its two MtA responses.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The message is malformed, or its Paillier modulus is too short.
+    ///
+    /// **SGX_ERROR_INVALID_STATE**
+    ///
+    /// The signer has already responded.
+    ///
+    pub fn respond(&mut self, peer_message: &[u8]) -> SgxResult<Vec<u8>> {
+        if self.peer.is_some() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_STATE);
+        }
+        let invalid = sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+        if peer_message.len() < 2 {
+            return Err(invalid);
+        }
+        let n_size = u16::from_le_bytes([peer_message[0], peer_message[1]]) as usize;
+        if peer_message.len() < 2 + n_size {
+            return Err(invalid);
+        }
+        let n = BigUint::from_bytes_be(&peer_message[2..2 + n_size]);
+        if n.bits() < THRESHOLD_ECDSA_PAILLIER_BITS {
+            return Err(invalid);
+        }
+        let key = PaillierPublicKey::new(&n)?;
+        let rest = &peer_message[2 + n_size..];
+        let c_size = key.ciphertext_size();
+        if rest.len() != c_size + PUBLIC_KEY_SIZE {
+            return Err(invalid);
+        }
+        let encrypted_k = key.ciphertext_from_bytes(&rest[..c_size])?;
+        let gamma_point = point_from_bytes(&rest[c_size..])?;
+
+        let (c_delta, beta_delta) = self.mta_respond(&key, &encrypted_k, &self.gamma)?;
+        let (c_sigma, beta_sigma) = self.mta_respond(&key, &encrypted_k, &self.w)?;
+        self.peer = Some(PeerState {
+            gamma_point,
+            beta_delta,
+            beta_sigma,
+        });
+        let mut response = c_delta.to_bytes(&key);
+        response.extend_from_slice(&c_sigma.to_bytes(&key));
+        Ok(response)
+    }
+
+    ///
+    /// Completes MtA with the other signer's response, and returns this signer's share of
+    /// `delta`, to send to the other signer.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The response is malformed.
+    ///
+    /// **SGX_ERROR_INVALID_STATE**
+    ///
+    /// `respond` has not completed, or MtA has already completed.
+    ///
+    pub fn finish_mta(&mut self, peer_response: &[u8]) -> SgxResult<[u8; 32]> {
+        let (beta_delta, beta_sigma) = match (&self.peer, self.delta) {
+            (Some(peer), None) => (peer.beta_delta, peer.beta_sigma),
+            _ => return Err(sgx_status_t::SGX_ERROR_INVALID_STATE),
+        };
+        let key = self.paillier.public_key();
+        let c_size = key.ciphertext_size();
+        if peer_response.len() != 2 * c_size {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let c_delta = key.ciphertext_from_bytes(&peer_response[..c_size])?;
+        let c_sigma = key.ciphertext_from_bytes(&peer_response[c_size..])?;
+
+        let mut plain = self.paillier.decrypt(&c_delta);
+        let alpha_delta = scalar_from_biguint(&plain, &self.order);
+        plain.wipe();
+        let mut plain = self.paillier.decrypt(&c_sigma);
+        let alpha_sigma = scalar_from_biguint(&plain, &self.order);
+        plain.wipe();
+        let (mut alpha_delta, mut alpha_sigma) = (alpha_delta?, alpha_sigma?);
+
+        let delta = self.k.mul(&self.gamma).add(&alpha_delta).add(&beta_delta);
+        self.sigma = self.k.mul(&self.w).add(&alpha_sigma).add(&beta_sigma);
+        self.delta = Some(delta);
+        alpha_delta.wipe();
+        alpha_sigma.wipe();
+        Ok(delta.to_bytes_be())
+    }
+
+    ///
+    /// Completes presigning with the other signer's share of `delta`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The share is not below the group order.
+    ///
+    /// **SGX_ERROR_INVALID_STATE**
+    ///
+    /// `finish_mta` has not completed.
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// `delta` or `r` is zero, which happens only with negligible probability unless a
+    /// signer deviates from the protocol.
+    ///
+    pub fn finish(self, peer_delta: &[u8; 32]) -> SgxResult<SgxPresignature> {
+        let (delta, peer) = match (self.delta, &self.peer) {
+            (Some(delta), Some(peer)) => (delta, peer),
+            _ => return Err(sgx_status_t::SGX_ERROR_INVALID_STATE),
+        };
+        let peer_delta =
+            Scalar::from_bytes_be(peer_delta).ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+        let delta = delta.add(&peer_delta);
+        if delta.is_zero() {
+            return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+        }
+        let big_r = self
+            .gamma_point
+            .add(&peer.gamma_point)
+            .mul_ct(&delta.invert());
+        let (x, _) = big_r
+            .to_affine()
+            .ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)?;
+        let r = Scalar::from_bytes_be_reduced(&x);
+        if r.is_zero() {
+            return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+        }
+        Ok(SgxPresignature {
+            r,
+            k: self.k,
+            sigma: self.sigma,
+            public_key: self.public_key,
+        })
+    }
+}
+
+///
+/// A signer's half of a presigned nonce, good for one signature.
+///
+pub struct SgxPresignature {
+    r: Scalar,
+    k: Scalar,
+    sigma: Scalar,
+    public_key: sgx_ec256_public_t,
+}
+
+impl Drop for SgxPresignature {
+    fn drop(&mut self) {
+        self.k.wipe();
+        self.sigma.wipe();
+    }
+}
+
+impl SgxPresignature {
+    ///
+    /// The first half of the signature, big-endian, known before the message.
+    ///
+    pub fn r(&self) -> [u8; 32] {
+        self.r.to_bytes_be()
+    }
+
+    pub fn public_key(&self) -> &sgx_ec256_public_t {
+        &self.public_key
+    }
+
+    ///
+    /// Signs the SHA-256 digest of `message`, consuming the presignature.
+    ///
+    pub fn sign(self, message: &[u8]) -> SgxResult<SgxPartialSignature> {
+        let m = digest(message)?;
+        Ok(SgxPartialSignature {
+            r: self.r.to_bytes_be(),
+            s: m.mul(&self.k).add(&self.r.mul(&self.sigma)).to_bytes_be(),
+        })
+    }
+}
+
+///
+/// One signer's share of a signature: `r` and `s_i`, big-endian.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SgxPartialSignature {
+    pub r: [u8; 32],
+    pub s: [u8; 32],
+}
+
+///
+/// Combines the partial signatures of two signers into an ECDSA signature of `message`,
+/// and verifies it.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// There are not two partial signatures, or they are from different presignings.
+///
+/// **SGX_ERROR_INVALID_SIGNATURE**
+///
+/// The combined signature does not verify: a signer deviated from the protocol.
+///
+pub fn rsgx_threshold_ecdsa_combine(
+    public_key: &sgx_ec256_public_t,
+    message: &[u8],
+    partials: &[SgxPartialSignature],
+) -> SgxResult<sgx_ec256_signature_t> {
+    let invalid = sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+    if partials.len() != 2 || partials[0].r != partials[1].r {
+        return Err(invalid);
+    }
+    let r = Scalar::from_bytes_be(&partials[0].r).ok_or(invalid)?;
+    let s0 = Scalar::from_bytes_be(&partials[0].s).ok_or(invalid)?;
+    let s1 = Scalar::from_bytes_be(&partials[1].s).ok_or(invalid)?;
+    let s = s0.add(&s1);
+    if r.is_zero() || s.is_zero() {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_SIGNATURE);
+    }
+    let signature = sgx_ec256_signature_t {
+        x: scalar_to_words(&r),
+        y: scalar_to_words(&s),
+    };
+
+    let ecc = SgxEccHandle::new();
+    ecc.open()?;
+    if !ecc.ecdsa_verify_slice(message, public_key, &signature)? {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_SIGNATURE);
+    }
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::rsgx_hex_decode;
+
+    fn le32(hex: &str) -> [u8; 32] {
+        let mut bytes = [0_u8; 32];
+        bytes.copy_from_slice(&rsgx_hex_decode(hex).unwrap());
+        bytes.reverse();
+        bytes
+    }
+
+    fn paillier(p: &str, q: &str) -> PaillierPrivateKey {
+        let p = BigUint::from_bytes_be(&rsgx_hex_decode(p).unwrap());
+        let q = BigUint::from_bytes_be(&rsgx_hex_decode(q).unwrap());
+        PaillierPrivateKey::from_primes(&p, &q).unwrap()
+    }
+
+    // Fixed 1024-bit primes, so that the tests do not spend their time generating keys.
+    fn paillier_keys() -> (PaillierPrivateKey, PaillierPrivateKey) {
+        (
+            paillier(
+                "c4440c7152bdde2432aa87077674c44fe96d0cdd639fe8adc8c014e92a0398f8\
+                 79fa9a08796661cddf76fcaeb1c93f48f572bc18881ba7ca23cc52ca06ac1274\
+                 38ad6cee530ded62a2250a57341d33dac581c9cfb6d98c16a159f67a97fbb23c\
+                 e7cf2c27907b4ec17e97bcca81dda641407a7d75ab3cae789e1b441c2005d3f7",
+                "ec5fbb2325a60116eb28ae56a46a58988d521cb4909a3f92c6085c95067adcaa\
+                 d9135daef0ccb013621e421b84f80dbfed656eb5576487e8031359686c0ccab3\
+                 4701e730adffadb67ac67ec2ddf079c857cbf592e15fd6a5f6e08c1e3e52b1a3\
+                 0dc73e863c44a14e1f9de6efee9163ca232c481413b95197e81e9ed854007871",
+            ),
+            paillier(
+                "f226fc604c738e51c13b089fedb768960e0d4e1d4d9e46cfd0179b9b9c315efb\
+                 a21439d7183fa0b5f1b4c663cd8848e7da21d276208d50af10eddd2cd1c44c8a\
+                 72c78cfffc9d46b0a0c1cfe34b7507e57ee8a99cf54c239562bdc9ba3c1a76ce\
+                 c66c10f0827155487a0b0a00e116de87cc97738d8e0f5fe3f688064f9745d663",
+                "faf352ec6215b5d729091584bbacf971dbade87e4f2fea362400fba53cc61ac5\
+                 618749d781bd3fd4693cce7ec6f1b93316342c3915d71c6fed00ccf5ce4abbce\
+                 e4ae84090c45ab4f2eebe1fcd630efa99222906d9012f8dad690af5317e4e732\
+                 fb89fcad6071e95c881e9b636f2e431889eb4c35c1984059660ccb640aa87e5f",
+            ),
+        )
+    }
+
+    fn presign(
+        a: &SgxEcdsaKeyShare,
+        b: &SgxEcdsaKeyShare,
+        keys: &(PaillierPrivateKey, PaillierPrivateKey),
+    ) -> (SgxPresignature, SgxPresignature) {
+        let mut pa = SgxPresigner::new(a, b.index(), &keys.0).unwrap();
+        let mut pb = SgxPresigner::new(b, a.index(), &keys.1).unwrap();
+        let (ma, mb) = (pa.message().unwrap(), pb.message().unwrap());
+        let ra = pa.respond(&mb).unwrap();
+        let rb = pb.respond(&ma).unwrap();
+        let da = pa.finish_mta(&rb).unwrap();
+        let db = pb.finish_mta(&ra).unwrap();
+        (pa.finish(&db).unwrap(), pb.finish(&da).unwrap())
+    }
+
+    // Textbook ECDSA verification, independent of the SDK: x(s^-1 * (m * G + r * Q)) = r.
+    fn verifies(public: &sgx_ec256_public_t, message: &[u8], sig: &sgx_ec256_signature_t) -> bool {
+        let scalar = |words: &[u32; 8]| {
+            let mut bytes = [0_u8; 32];
+            for (chunk, word) in bytes.chunks_exact_mut(4).zip(words.iter()) {
+                chunk.copy_from_slice(&word.to_le_bytes());
+            }
+            bytes.reverse();
+            Scalar::from_bytes_be(&bytes).unwrap()
+        };
+        let (r, s) = (scalar(&sig.x), scalar(&sig.y));
+        let s_inv = s.invert();
+        let q = Point::from_public(public).unwrap();
+        let p = Point::GENERATOR
+            .mul_ct(&digest(message).unwrap().mul(&s_inv))
+            .add(&q.mul_ct(&r.mul(&s_inv)));
+        let (x, _) = p.to_affine().unwrap();
+        Scalar::from_bytes_be_reduced(&x).to_bytes_be() == r.to_bytes_be()
+    }
+
+    #[test]
+    fn split_known_answer() {
+        // The key of RFC 6979, A.2.5, and its public key U.
+        let private = sgx_ec256_private_t {
+            r: le32("c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721"),
+        };
+        let shares = rsgx_threshold_ecdsa_split(&private, 3).unwrap();
+        assert_eq!(shares.len(), 3);
+        for (i, share) in shares.iter().enumerate() {
+            assert_eq!(share.index(), i as u16 + 1);
+            assert_eq!(
+                share.public_key().gx,
+                le32("60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6")
+            );
+            assert_eq!(
+                share.public_key().gy,
+                le32("7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299")
+            );
+        }
+
+        // Any two shares interpolate to the key at zero.
+        let mut x = private.r;
+        x.reverse();
+        for (i, j) in [(1, 2), (1, 3), (2, 3)] {
+            let si = Scalar::from_bytes_be(&shares[i - 1].secret()).unwrap();
+            let sj = Scalar::from_bytes_be(&shares[j - 1].secret()).unwrap();
+            let (i, j) = (i as u16, j as u16);
+            let at_zero = si.mul(&lagrange(i, j)).add(&sj.mul(&lagrange(j, i)));
+            assert_eq!(at_zero.to_bytes_be(), x);
+        }
+        // 2 / (2 - 1) = 2 and 1 / (1 - 2) = -1.
+        assert_eq!(
+            lagrange(1, 2).to_bytes_be(),
+            Scalar::from_u64(2).to_bytes_be()
+        );
+        assert_eq!(
+            lagrange(2, 1).to_bytes_be(),
+            Scalar::from_u64(1).neg().to_bytes_be()
+        );
+
+        let restored =
+            SgxEcdsaKeyShare::from_secret(2, &shares[1].secret(), shares[1].public_key()).unwrap();
+        assert_eq!(restored.secret(), shares[1].secret());
+    }
+
+    #[test]
+    fn sign_round_trip() {
+        let keys = paillier_keys();
+        let shares = rsgx_threshold_ecdsa_deal(3).unwrap();
+        let public = *shares[0].public_key();
+        let message = b"threshold ECDSA";
+
+        let (a, b) = presign(&shares[0], &shares[2], &keys);
+        assert_eq!(a.r(), b.r());
+        let partials = [a.sign(message).unwrap(), b.sign(message).unwrap()];
+        let sig = rsgx_threshold_ecdsa_combine(&public, message, &partials).unwrap();
+        assert_eq!(
+            sig.x,
+            scalar_to_words(&Scalar::from_bytes_be(&partials[0].r).unwrap())
+        );
+        assert!(verifies(&public, message, &sig));
+        assert!(!verifies(&public, b"another message", &sig));
+
+        assert_eq!(
+            rsgx_threshold_ecdsa_combine(&public, b"another message", &partials).err(),
+            Some(sgx_status_t::SGX_ERROR_INVALID_SIGNATURE)
+        );
+        let mut tampered = partials;
+        tampered[1].s[31] ^= 1;
+        assert_eq!(
+            rsgx_threshold_ecdsa_combine(&public, message, &tampered).err(),
+            Some(sgx_status_t::SGX_ERROR_INVALID_SIGNATURE)
+        );
+        assert_eq!(
+            rsgx_threshold_ecdsa_combine(&public, message, &partials[..1]).err(),
+            Some(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+        );
+
+        // Another pair of holders signs under the same key with a fresh nonce.
+        let (a, b) = presign(&shares[2], &shares[1], &keys);
+        let partials2 = [a.sign(message).unwrap(), b.sign(message).unwrap()];
+        assert_ne!(partials2[0].r, partials[0].r);
+        let sig = rsgx_threshold_ecdsa_combine(&public, message, &partials2).unwrap();
+        assert!(verifies(&public, message, &sig));
+
+        let mixed = [partials[0], partials2[1]];
+        assert_eq!(
+            rsgx_threshold_ecdsa_combine(&public, message, &mixed).err(),
+            Some(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let zero = sgx_ec256_private_t::default();
+        assert!(rsgx_threshold_ecdsa_split(&zero, 2).is_err());
+        let order = sgx_ec256_private_t {
+            r: {
+                let mut r = ORDER;
+                r.reverse();
+                r
+            },
+        };
+        assert!(rsgx_threshold_ecdsa_split(&order, 2).is_err());
+        assert!(rsgx_threshold_ecdsa_deal(1).is_err());
+
+        let shares = rsgx_threshold_ecdsa_deal(2).unwrap();
+        assert!(
+            SgxEcdsaKeyShare::from_secret(0, &shares[0].secret(), shares[0].public_key()).is_err()
+        );
+        assert!(SgxEcdsaKeyShare::from_secret(
+            1,
+            &shares[0].secret(),
+            &sgx_ec256_public_t::default()
+        )
+        .is_err());
+
+        let keys = paillier_keys();
+        assert!(SgxPresigner::new(&shares[0], 1, &keys.0).is_err());
+        assert!(SgxPresigner::new(&shares[0], 0, &keys.0).is_err());
+        let small = PaillierPrivateKey::from_primes(
+            &BigUint::from_u64(0xffff_ffff_ffff_ffc5),
+            &BigUint::from_u64(0xffff_ffff_ffff_ffad),
+        )
+        .unwrap();
+        assert!(SgxPresigner::new(&shares[0], 2, &small).is_err());
+
+        let mut a = SgxPresigner::new(&shares[0], 2, &keys.0).unwrap();
+        let b = SgxPresigner::new(&shares[1], 1, &keys.1).unwrap();
+        assert_eq!(
+            a.finish_mta(&[]),
+            Err(sgx_status_t::SGX_ERROR_INVALID_STATE)
+        );
+        let message = b.message().unwrap();
+        assert_eq!(
+            a.respond(&message[..message.len() - 1]),
+            Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+        );
+        a.respond(&message).unwrap();
+        assert_eq!(
+            a.respond(&message),
+            Err(sgx_status_t::SGX_ERROR_INVALID_STATE)
+        );
+        assert!(a.finish(&[0_u8; 32]).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Garbled circuit primitives.
+//!
+//! Labels follow free-XOR: the two labels of a wire differ by a global offset `delta`,
+//! whose least significant bit is set, so the least significant bit of a label is its
+//! permute bit. XOR and NOT gates cost nothing; an AND gate costs two blocks with
+//! half-gates. Gate tables are hashed with the tweakable circular correlation-robust hash
+//! `H(x, i) = pi(pi(x) ^ i) ^ pi(x)` of Guo, Katz, Wang and Yu (S&P 2020), where `pi` is
+//! AES-128 under a fixed public key.
+//!
+//! `SgxGarbler` and `SgxEvaluator` number AND gates in the order they are processed; both
+//! sides must process the gates of a circuit in the same order.
+
+use super::Block;
+use crate::crypto::rsgx_aes_ctr_encrypt;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+// The digits of pi, as the fixed public key of `pi`.
+const FIXED_KEY: sgx_aes_ctr_128bit_key_t = [
+    0x24, 0x3f, 0x6a, 0x88, 0x85, 0xa3, 0x08, 0xd3, 0x13, 0x19, 0x8a, 0x2e, 0x03, 0x70, 0x73, 0x44,
+];
+
+///
+/// AES-128 as a public random permutation of blocks.
+///
+pub struct FixedKeyAes;
+
+impl FixedKeyAes {
+    ///
+    /// Computes `pi(x)`.
+    ///
+    pub fn permute(x: Block) -> SgxResult<Block> {
+        // One block of AES-CTR over zeros with the counter set to x is AES_k(x).
+        let mut ctr = x.to_bytes();
+        let mut out = [0_u8; 16];
+        rsgx_aes_ctr_encrypt(&FIXED_KEY, &[0_u8; 16], &mut ctr, 128, &mut out)?;
+        Ok(Block::from_bytes(&out))
+    }
+
+    ///
+    /// Computes the tweakable circular correlation-robust hash `H(x, tweak)`.
+    ///
+    pub fn tccr(x: Block, tweak: u128) -> SgxResult<Block> {
+        let px = Self::permute(x)?;
+        Ok(Self::permute(px ^ Block(tweak))? ^ px)
+    }
+}
+
+///
+/// The garbling side of a circuit.
+///
+/// # Description
+///
+/// Wires are identified by their zero label; the one label is the zero label XOR
+/// `delta`. The garbler sends the evaluator the tables of the AND gates, the labels of its
+/// own inputs, and the decoding bits of the outputs; the evaluator obtains the labels of
+/// its inputs by oblivious transfer of the pairs `(zero, zero ^ delta)`.
+///
+pub struct SgxGarbler {
+    delta: Block,
+    gates: u64,
+}
+
+impl Drop for SgxGarbler {
+    fn drop(&mut self) {
+        unsafe { core::ptr::write_volatile(&mut self.delta, Block::ZERO) };
+    }
+}
+
+impl SgxGarbler {
+    pub fn new() -> SgxResult<SgxGarbler> {
+        let delta = random_block()?;
+        Ok(SgxGarbler {
+            delta: Block(delta.0 | 1),
+            gates: 0,
+        })
+    }
+
+    ///
+    /// The global offset between the zero and one labels of every wire.
+    ///
+    pub fn delta(&self) -> Block {
+        self.delta
+    }
+
+    ///
+    /// A zero label for a new input wire.
+    ///
+    pub fn input(&self) -> SgxResult<Block> {
+        random_block()
+    }
+
+    ///
+    /// The label encoding `bit` on the wire with zero label `zero`.
+    ///
+    pub fn encode(&self, zero: Block, bit: bool) -> Block {
+        zero ^ self.delta.and_bit(bit)
+    }
+
+    ///
+    /// The label pair `(zero, one)` of a wire, as sent by oblivious transfer.
+    ///
+    pub fn labels(&self, zero: Block) -> (Block, Block) {
+        (zero, zero ^ self.delta)
+    }
+
+    pub fn xor(&self, a: Block, b: Block) -> Block {
+        a ^ b
+    }
+
+    pub fn not(&self, a: Block) -> Block {
+        a ^ self.delta
+    }
+
+    ///
+    /// Garbles an AND gate of the wires with zero labels `a` and `b`, returning the zero
+    /// label of the output wire and the table to send to the evaluator.
+    ///
+    pub fn and(&mut self, a: Block, b: Block) -> SgxResult<(Block, [Block; 2])> {
+        let (j0, j1) = tweaks(self.gates);
+        self.gates += 1;
+        let (pa, pb) = (a.lsb(), b.lsb());
+        let a1 = a ^ self.delta;
+        let b1 = b ^ self.delta;
+
+        // Garbler half gate.
+        let ha0 = FixedKeyAes::tccr(a, j0)?;
+        let ha1 = FixedKeyAes::tccr(a1, j0)?;
+        let tg = ha0 ^ ha1 ^ self.delta.and_bit(pb);
+        let wg = ha0 ^ tg.and_bit(pa);
+
+        // Evaluator half gate.
+        let hb0 = FixedKeyAes::tccr(b, j1)?;
+        let hb1 = FixedKeyAes::tccr(b1, j1)?;
+        let te = hb0 ^ hb1 ^ a;
+        let we = hb0 ^ (te ^ a).and_bit(pb);
+
+        Ok((wg ^ we, [tg, te]))
+    }
+
+    ///
+    /// The decoding bit of an output wire, for the evaluator's `decode`.
+    ///
+    pub fn decoding_bit(&self, zero: Block) -> bool {
+        zero.lsb()
+    }
+
+    ///
+    /// The number of AND gates garbled so far.
+    ///
+    pub fn gates(&self) -> u64 {
+        self.gates
+    }
+}
+
+///
+/// The evaluating side of a circuit.
+///
+#[derive(Default)]
+pub struct SgxEvaluator {
+    gates: u64,
+}
+
+impl SgxEvaluator {
+    pub fn new() -> SgxEvaluator {
+        SgxEvaluator { gates: 0 }
+    }
+
+    pub fn xor(&self, a: Block, b: Block) -> Block {
+        a ^ b
+    }
+
+    ///
+    /// NOT is applied by the garbler alone; the label is unchanged.
+    ///
+    pub fn not(&self, a: Block) -> Block {
+        a
+    }
+
+    ///
+    /// Evaluates an AND gate with the table produced by `SgxGarbler::and`.
+    ///
+    pub fn and(&mut self, a: Block, b: Block, table: &[Block; 2]) -> SgxResult<Block> {
+        let (j0, j1) = tweaks(self.gates);
+        self.gates += 1;
+        let [tg, te] = *table;
+        let wg = FixedKeyAes::tccr(a, j0)? ^ tg.and_bit(a.lsb());
+        let we = FixedKeyAes::tccr(b, j1)? ^ (te ^ a).and_bit(b.lsb());
+        Ok(wg ^ we)
+    }
+
+    ///
+    /// The bit an output label encodes, given the garbler's decoding bit.
+    ///
+    pub fn decode(&self, label: Block, decoding_bit: bool) -> bool {
+        label.lsb() ^ decoding_bit
+    }
+
+    pub fn gates(&self) -> u64 {
+        self.gates
+    }
+}
+
+fn tweaks(gate: u64) -> (u128, u128) {
+    let j = (gate as u128) << 1;
+    (j, j | 1)
+}
+
+fn random_block() -> SgxResult<Block> {
+    let mut bytes = [0_u8; 16];
+    rsgx_read_rand(&mut bytes)?;
+    Ok(Block::from_bytes(&bytes))
+}
@@ -0,0 +1,93 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Secure multi-party computation building blocks
+//!
+//! Oblivious transfer, garbled circuits and threshold signing, for designs in which an
+//! enclave computes jointly with a party that does not trust it, or with another enclave
+//! it need not trust.
+//!
+//! * `ot`: base oblivious transfer (Chou and Orlandi, "The Simplest Protocol for Oblivious
+//!   Transfer") over Curve25519 in Edwards form, and its extension to many transfers with
+//!   IKNP (Ishai, Kilian, Nissim and Petrank, CRYPTO 2003).
+//! * `garble`: free-XOR garbling with half-gates (Zahur, Rosulek and Evans, EUROCRYPT
+//!   2015), hashing with fixed-key AES.
+//! * `ecdsa`: two-of-n threshold ECDSA over P-256, with MtA over Paillier encryption.
+//!
+//! The protocols are secure against semi-honest parties. Each side is a state machine that
+//! consumes and produces messages; moving the messages between the parties is left to the
+//! caller.
+
+use core::ops::{BitAnd, BitXor, BitXorAssign};
+
+pub mod ecdsa;
+pub mod garble;
+pub mod ot;
+
+///
+/// A 128-bit value: an OT output, a wire label, or a row of an OT extension matrix.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Block(pub u128);
+
+impl Block {
+    pub const ZERO: Block = Block(0);
+
+    pub fn from_bytes(bytes: &[u8; 16]) -> Block {
+        Block(u128::from_le_bytes(*bytes))
+    }
+
+    pub fn to_bytes(self) -> [u8; 16] {
+        self.0.to_le_bytes()
+    }
+
+    ///
+    /// The least significant bit, the permute bit of a wire label.
+    ///
+    pub fn lsb(self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    ///
+    /// Returns `self` if `bit` is set and zero otherwise, without branching.
+    ///
+    pub fn and_bit(self, bit: bool) -> Block {
+        Block(self.0 & 0_u128.wrapping_sub(bit as u128))
+    }
+}
+
+impl BitXor for Block {
+    type Output = Block;
+
+    fn bitxor(self, rhs: Block) -> Block {
+        Block(self.0 ^ rhs.0)
+    }
+}
+
+impl BitXorAssign for Block {
+    fn bitxor_assign(&mut self, rhs: Block) {
+        self.0 ^= rhs.0;
+    }
+}
+
+impl BitAnd for Block {
+    type Output = Block;
+
+    fn bitand(self, rhs: Block) -> Block {
+        Block(self.0 & rhs.0)
+    }
+}
@@ -0,0 +1,388 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Oblivious transfer.
+//!
+//! In an oblivious transfer the sender obtains two values and the receiver one of them, of
+//! its choice, without the sender learning which. These are random OTs: the values are
+//! random blocks, and the sender transfers chosen messages by sending `m0 ^ x0` and
+//! `m1 ^ x1`, of which the receiver can only unmask the one it chose.
+//!
+//! Base OT takes one message from the sender and one per transfer from the receiver:
+//!
+//! ```text
+//! sender:   SgxBaseOtSender::new()        -> message()           ---> receiver
+//! receiver: rsgx_base_ot_receive(message, choices) -> responses  ---> sender
+//! sender:   finish(responses)             -> pairs (x0, x1)
+//! ```
+//!
+//! OT extension turns 128 base OTs, run in the opposite direction, into any number of OTs
+//! at the cost of symmetric cryptography only:
+//!
+//! ```text
+//! receiver: SgxOtExtReceiver::new()       -> base_message()      ---> sender
+//! sender:   SgxOtExtSender::new(message)  -> responses           ---> receiver
+//! receiver: setup(responses)
+//! receiver: extend(choices)               -> (matrix, outputs)   ---> sender (matrix)
+//! sender:   extend(matrix, count)         -> pairs (x0, x1)
+//! ```
+//!
+//! `extend` can be called any number of times after setup; both sides must call it with
+//! the same counts in the same order.
+
+use super::garble::FixedKeyAes;
+use super::Block;
+use crate::crypto::rsgx_aes_ctr_encrypt;
+use crate::ed25519::point::{random_secret_scalar, Point};
+use crate::transcript::SgxTranscript;
+use alloc::vec::Vec;
+use core::ptr;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+///
+/// The number of base OTs behind an OT extension, and its security parameter.
+///
+pub const OT_EXTENSION_BASE_COUNT: usize = 128;
+
+const BASE_OT_LABEL: &[u8] = b"sgx base ot v1";
+
+fn base_key(index: usize, a: &[u8; 32], b: &[u8; 32], shared: &Point) -> SgxResult<Block> {
+    let mut transcript = SgxTranscript::new(BASE_OT_LABEL)?;
+    transcript.append_u64(b"index", index as u64)?;
+    transcript.append_message(b"sender", a)?;
+    transcript.append_message(b"receiver", b)?;
+    transcript.append_message(b"shared", &shared.compress())?;
+    let mut key = [0_u8; 16];
+    transcript.challenge_bytes(b"key", &mut key)?;
+    Ok(Block::from_bytes(&key))
+}
+
+fn wipe<T: Copy + Default>(values: &mut [T]) {
+    for v in values.iter_mut() {
+        unsafe { ptr::write_volatile(v, T::default()) };
+    }
+}
+
+///
+/// The sender of a batch of base OTs.
+///
+pub struct SgxBaseOtSender {
+    secret: [u8; 32],
+    point: Point,
+    message: [u8; 32],
+}
+
+impl Drop for SgxBaseOtSender {
+    fn drop(&mut self) {
+        wipe(&mut self.secret);
+    }
+}
+
+impl SgxBaseOtSender {
+    pub fn new() -> SgxResult<SgxBaseOtSender> {
+        let secret = random_secret_scalar()?;
+        let point = Point::BASEPOINT.mul_ct(&secret);
+        Ok(SgxBaseOtSender {
+            secret,
+            point,
+            message: point.compress(),
+        })
+    }
+
+    ///
+    /// The message to send to the receiver.
+    ///
+    pub fn message(&self) -> [u8; 32] {
+        self.message
+    }
+
+    ///
+    /// Computes the pair of values of each transfer from the receiver's responses.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// A response is not a valid point.
+    ///
+    pub fn finish(&self, responses: &[[u8; 32]]) -> SgxResult<Vec<(Block, Block)>> {
+        let a = self.point.to_cached();
+        responses
+            .iter()
+            .enumerate()
+            .map(|(i, response)| {
+                let b =
+                    Point::decompress(response).ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+                let k0 = b.mul_ct(&self.secret);
+                let k1 = b.sub(&a).mul_ct(&self.secret);
+                Ok((
+                    base_key(i, &self.message, response, &k0)?,
+                    base_key(i, &self.message, response, &k1)?,
+                ))
+            })
+            .collect()
+    }
+}
+
+///
+/// The rsgx_base_ot_receive function runs the receiver's side of a batch of base OTs,
+/// returning the responses to send to the sender and the chosen value of each transfer.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// The sender's message is not a valid point.
+///
+pub fn rsgx_base_ot_receive(
+    message: &[u8; 32],
+    choices: &[bool],
+) -> SgxResult<(Vec<[u8; 32]>, Vec<Block>)> {
+    let a = Point::decompress(message).ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+    let a_cached = a.to_cached();
+    let mut responses = Vec::with_capacity(choices.len());
+    let mut keys = Vec::with_capacity(choices.len());
+    for (i, &choice) in choices.iter().enumerate() {
+        let mut secret = random_secret_scalar()?;
+        let b0 = Point::BASEPOINT.mul_ct(&secret);
+        let b1 = b0.add(&a_cached);
+        let response = b0.select(&b1, choice as u64).compress();
+        let shared = a.mul_ct(&secret);
+        wipe(&mut secret);
+        keys.push(base_key(i, message, &response, &shared)?);
+        responses.push(response);
+    }
+    Ok((responses, keys))
+}
+
+// The bytes of each column of the extension matrix for `count` transfers, a whole number
+// of AES blocks.
+fn column_bytes(count: usize) -> usize {
+    (count + 127) / 128 * 16
+}
+
+// Expands `seed` into `len` bytes at AES-CTR block `counter`.
+fn prg(seed: &Block, counter: u64, len: usize) -> SgxResult<Vec<u8>> {
+    let key = seed.to_bytes();
+    let mut ctr = (counter as u128).to_be_bytes();
+    let zeros = vec![0_u8; len];
+    let mut out = vec![0_u8; len];
+    rsgx_aes_ctr_encrypt(&key, &zeros, &mut ctr, 128, &mut out)?;
+    Ok(out)
+}
+
+// Transposes 128 columns of `count` bits into `count` rows of 128 bits.
+fn transpose(columns: &[Vec<u8>], count: usize) -> Vec<Block> {
+    let mut rows = vec![Block::ZERO; count];
+    for (i, column) in columns.iter().enumerate() {
+        for (j, row) in rows.iter_mut().enumerate() {
+            let bit = (column[j / 8] >> (j % 8)) & 1;
+            row.0 |= (bit as u128) << i;
+        }
+    }
+    rows
+}
+
+///
+/// The receiver of an OT extension, which is the sender of its base OTs.
+///
+pub struct SgxOtExtReceiver {
+    base: SgxBaseOtSender,
+    seeds: Vec<(Block, Block)>,
+    counter: u64,
+    index: u64,
+}
+
+impl Drop for SgxOtExtReceiver {
+    fn drop(&mut self) {
+        wipe(&mut self.seeds);
+    }
+}
+
+impl SgxOtExtReceiver {
+    pub fn new() -> SgxResult<SgxOtExtReceiver> {
+        Ok(SgxOtExtReceiver {
+            base: SgxBaseOtSender::new()?,
+            seeds: Vec::new(),
+            counter: 0,
+            index: 0,
+        })
+    }
+
+    ///
+    /// The base OT message to send to the extension sender.
+    ///
+    pub fn base_message(&self) -> [u8; 32] {
+        self.base.message()
+    }
+
+    ///
+    /// Completes the base OTs with the extension sender's responses.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// There are not `OT_EXTENSION_BASE_COUNT` responses, or one is not a valid point.
+    ///
+    pub fn setup(&mut self, responses: &[[u8; 32]]) -> SgxError {
+        if responses.len() != OT_EXTENSION_BASE_COUNT {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        self.seeds = self.base.finish(responses)?;
+        Ok(())
+    }
+
+    ///
+    /// Runs `choices.len()` transfers, returning the matrix to send to the extension
+    /// sender and the chosen value of each transfer.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_STATE**
+    ///
+    /// `setup` has not completed.
+    ///
+    pub fn extend(&mut self, choices: &[bool]) -> SgxResult<(Vec<u8>, Vec<Block>)> {
+        if self.seeds.len() != OT_EXTENSION_BASE_COUNT {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_STATE);
+        }
+        let count = choices.len();
+        let len = column_bytes(count);
+        let mut packed = vec![0_u8; len];
+        for (j, &choice) in choices.iter().enumerate() {
+            packed[j / 8] |= (choice as u8) << (j % 8);
+        }
+
+        let mut matrix = Vec::with_capacity(OT_EXTENSION_BASE_COUNT * len);
+        let mut columns = Vec::with_capacity(OT_EXTENSION_BASE_COUNT);
+        for (k0, k1) in self.seeds.iter() {
+            let t = prg(k0, self.counter, len)?;
+            let mut u = prg(k1, self.counter, len)?;
+            for ((u, t), r) in u.iter_mut().zip(t.iter()).zip(packed.iter()) {
+                *u ^= t ^ r;
+            }
+            matrix.extend_from_slice(&u);
+            columns.push(t);
+        }
+        wipe(&mut packed);
+        self.counter += (len / 16) as u64;
+
+        let mut rows = transpose(&columns, count);
+        for column in columns.iter_mut() {
+            wipe(column);
+        }
+        let outputs = rows
+            .iter()
+            .enumerate()
+            .map(|(j, t)| FixedKeyAes::tccr(*t, (self.index + j as u64) as u128))
+            .collect::<SgxResult<Vec<Block>>>();
+        wipe(&mut rows);
+        self.index += count as u64;
+        Ok((matrix, outputs?))
+    }
+}
+
+///
+/// The sender of an OT extension, which is the receiver of its base OTs.
+///
+pub struct SgxOtExtSender {
+    delta: Block,
+    seeds: Vec<Block>,
+    counter: u64,
+    index: u64,
+}
+
+impl Drop for SgxOtExtSender {
+    fn drop(&mut self) {
+        wipe(&mut self.seeds);
+        unsafe { ptr::write_volatile(&mut self.delta, Block::ZERO) };
+    }
+}
+
+impl SgxOtExtSender {
+    ///
+    /// Runs the base OTs against the extension receiver's `base_message`, returning the
+    /// sender and the responses to send back.
+    ///
+    pub fn new(base_message: &[u8; 32]) -> SgxResult<(SgxOtExtSender, Vec<[u8; 32]>)> {
+        let mut bytes = [0_u8; 16];
+        rsgx_read_rand(&mut bytes)?;
+        let delta = Block::from_bytes(&bytes);
+        wipe(&mut bytes);
+        let choices: Vec<bool> = (0..OT_EXTENSION_BASE_COUNT)
+            .map(|i| (delta.0 >> i) & 1 == 1)
+            .collect();
+        let (responses, seeds) = rsgx_base_ot_receive(base_message, &choices)?;
+        Ok((
+            SgxOtExtSender {
+                delta,
+                seeds,
+                counter: 0,
+                index: 0,
+            },
+            responses,
+        ))
+    }
+
+    ///
+    /// Runs `count` transfers with the extension receiver's matrix, returning the pair of
+    /// values of each transfer.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The matrix does not have the size for `count` transfers.
+    ///
+    pub fn extend(&mut self, matrix: &[u8], count: usize) -> SgxResult<Vec<(Block, Block)>> {
+        let len = column_bytes(count);
+        if matrix.len() != OT_EXTENSION_BASE_COUNT * len {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let mut columns = Vec::with_capacity(OT_EXTENSION_BASE_COUNT);
+        for (i, (seed, u)) in self.seeds.iter().zip(matrix.chunks_exact(len)).enumerate() {
+            let mut q = prg(seed, self.counter, len)?;
+            let mask = 0_u8.wrapping_sub(((self.delta.0 >> i) & 1) as u8);
+            for (q, u) in q.iter_mut().zip(u.iter()) {
+                *q ^= u & mask;
+            }
+            columns.push(q);
+        }
+        self.counter += (len / 16) as u64;
+
+        let mut rows = transpose(&columns, count);
+        for column in columns.iter_mut() {
+            wipe(column);
+        }
+        let pairs = rows
+            .iter()
+            .enumerate()
+            .map(|(j, q)| {
+                let tweak = (self.index + j as u64) as u128;
+                Ok((
+                    FixedKeyAes::tccr(*q, tweak)?,
+                    FixedKeyAes::tccr(*q ^ self.delta, tweak)?,
+                ))
+            })
+            .collect::<SgxResult<Vec<(Block, Block)>>>();
+        wipe(&mut rows);
+        self.index += count as u64;
+        pairs
+    }
+}
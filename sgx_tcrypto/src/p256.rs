@@ -0,0 +1,553 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+// The NIST P-256 group, for protocols that need more than the SDK's ECDSA and ECDH:
+// arithmetic modulo the field prime p and the group order n in Montgomery form, and
+// points in projective coordinates with the complete addition formulas of Renes,
+// Costello and Batina ("Complete addition formulas for prime order elliptic curves",
+// EUROCRYPT 2016).
+//
+// The arithmetic is branch-free. Decoding and the comparisons are not, and are only used
+// on public values.
+
+use crate::fp256::{load_be, select_limbs, store_be, P256_N as N, P256_P as P};
+use crate::kms::rsgx_hmac_sha256_parts;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+// The group order n, big-endian.
+pub(crate) const ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xbc, 0xe6, 0xfa, 0xad, 0xa7, 0x17, 0x9e, 0x84, 0xf3, 0xb9, 0xca, 0xc2, 0xfc, 0x63, 0x25, 0x51,
+];
+
+// An element of GF(p) in Montgomery form.
+#[derive(Copy, Clone)]
+struct Fe([u64; 4]);
+
+impl Fe {
+    const ZERO: Fe = Fe([0, 0, 0, 0]);
+    // 2^256 mod p
+    const ONE: Fe = Fe([
+        0x0000_0000_0000_0001,
+        0xffff_ffff_0000_0000,
+        0xffff_ffff_ffff_ffff,
+        0x0000_0000_ffff_fffe,
+    ]);
+    const B: Fe = Fe([
+        0xd89c_df62_29c4_bddf,
+        0xacf0_05cd_7884_3090,
+        0xe5a2_20ab_f721_2ed6,
+        0xdc30_061d_0487_4834,
+    ]);
+
+    fn add(&self, b: &Fe) -> Fe {
+        Fe(P.add(&self.0, &b.0))
+    }
+
+    fn sub(&self, b: &Fe) -> Fe {
+        Fe(P.sub(&self.0, &b.0))
+    }
+
+    fn mul(&self, b: &Fe) -> Fe {
+        Fe(P.mul(&self.0, &b.0))
+    }
+
+    fn from_bytes_be(bytes: &[u8; 32]) -> Option<Fe> {
+        let l = load_be(bytes);
+        if P.is_canonical(&l) {
+            Some(Fe(P.enter(&l)))
+        } else {
+            None
+        }
+    }
+
+    fn to_bytes_be(self) -> [u8; 32] {
+        store_be(&P.leave(&self.0))
+    }
+
+    fn select(&self, b: &Fe, choice: u64) -> Fe {
+        Fe(select_limbs(&self.0, &b.0, choice))
+    }
+
+    // x^3 - 3x + b
+    fn curve_rhs(&self) -> Fe {
+        let x3 = self.mul(self).mul(self);
+        x3.sub(&self.add(self).add(self)).add(&Fe::B)
+    }
+
+    // A square root, a^((p + 1) / 4) since p = 3 mod 4, if there is one.
+    fn sqrt(&self) -> Option<Fe> {
+        const EXPONENT: [u64; 4] = [
+            0x0000_0000_0000_0000,
+            0x0000_0000_4000_0000,
+            0x4000_0000_0000_0000,
+            0x3fff_ffff_c000_0000,
+        ];
+        let root = Fe(P.pow(&self.0, &EXPONENT));
+        if root.mul(&root).to_bytes_be() == self.to_bytes_be() {
+            Some(root)
+        } else {
+            None
+        }
+    }
+}
+
+// An integer modulo the group order n, in Montgomery form.
+#[derive(Copy, Clone)]
+pub(crate) struct Scalar([u64; 4]);
+
+impl Scalar {
+    pub(crate) const ZERO: Scalar = Scalar([0, 0, 0, 0]);
+
+    pub(crate) fn from_u64(x: u64) -> Scalar {
+        Scalar(N.enter(&[x, 0, 0, 0]))
+    }
+
+    // Reads a big-endian integer below n.
+    pub(crate) fn from_bytes_be(bytes: &[u8; 32]) -> Option<Scalar> {
+        let l = load_be(bytes);
+        if N.is_canonical(&l) {
+            Some(Scalar(N.enter(&l)))
+        } else {
+            None
+        }
+    }
+
+    // Reads a big-endian integer and reduces it modulo n, as ECDSA does with a digest.
+    pub(crate) fn from_bytes_be_reduced(bytes: &[u8; 32]) -> Scalar {
+        Scalar(N.enter(&N.reduce_once(&load_be(bytes), 0)))
+    }
+
+    pub(crate) fn to_bytes_be(self) -> [u8; 32] {
+        store_be(&N.leave(&self.0))
+    }
+
+    // A uniformly random non-zero scalar.
+    pub(crate) fn random() -> SgxResult<Scalar> {
+        let mut bytes = [0_u8; 32];
+        loop {
+            rsgx_read_rand(&mut bytes)?;
+            if let Some(s) = Scalar::from_bytes_be(&bytes) {
+                if !s.is_zero() {
+                    wipe(&mut bytes);
+                    return Ok(s);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn add(&self, b: &Scalar) -> Scalar {
+        Scalar(N.add(&self.0, &b.0))
+    }
+
+    pub(crate) fn sub(&self, b: &Scalar) -> Scalar {
+        Scalar(N.sub(&self.0, &b.0))
+    }
+
+    pub(crate) fn neg(&self) -> Scalar {
+        Scalar::ZERO.sub(self)
+    }
+
+    pub(crate) fn mul(&self, b: &Scalar) -> Scalar {
+        Scalar(N.mul(&self.0, &b.0))
+    }
+
+    // The inverse of a non-zero scalar; zero has none and maps to zero.
+    pub(crate) fn invert(&self) -> Scalar {
+        Scalar(N.invert(&self.0))
+    }
+
+    pub(crate) fn is_zero(&self) -> bool {
+        self.0.iter().fold(0, |acc, l| acc | l) == 0
+    }
+
+    pub(crate) fn wipe(&mut self) {
+        for l in self.0.iter_mut() {
+            unsafe { core::ptr::write_volatile(l, 0) };
+        }
+    }
+}
+
+// A point in projective coordinates (X : Y : Z), the affine point (X/Z, Y/Z).
+#[derive(Copy, Clone)]
+pub(crate) struct Point {
+    x: Fe,
+    y: Fe,
+    z: Fe,
+}
+
+impl Point {
+    pub(crate) const IDENTITY: Point = Point {
+        x: Fe::ZERO,
+        y: Fe::ONE,
+        z: Fe::ZERO,
+    };
+
+    pub(crate) const GENERATOR: Point = Point {
+        x: Fe([
+            0x79e7_30d4_18a9_143c,
+            0x75ba_95fc_5fed_b601,
+            0x79fb_732b_7762_2510,
+            0x1890_5f76_a537_55c6,
+        ]),
+        y: Fe([
+            0xddf2_5357_ce95_560a,
+            0x8b4a_b8e4_ba19_e45c,
+            0xd2e8_8688_dd21_f325,
+            0x8571_ff18_2588_5d85,
+        ]),
+        z: Fe::ONE,
+    };
+
+    // Reads a public key, which must be a point on the curve.
+    pub(crate) fn from_public(public: &sgx_ec256_public_t) -> Option<Point> {
+        let mut gx = public.gx;
+        let mut gy = public.gy;
+        gx.reverse();
+        gy.reverse();
+        let x = Fe::from_bytes_be(&gx)?;
+        let y = Fe::from_bytes_be(&gy)?;
+        if y.mul(&y).to_bytes_be() != x.curve_rhs().to_bytes_be() {
+            return None;
+        }
+        Some(Point { x, y, z: Fe::ONE })
+    }
+
+    // Reads a compressed SEC1 encoding, 0x02 or 0x03 for the parity of y, then x.
+    pub(crate) fn from_sec1(bytes: &[u8; 33]) -> Option<Point> {
+        if bytes[0] != 0x02 && bytes[0] != 0x03 {
+            return None;
+        }
+        let mut xb = [0_u8; 32];
+        xb.copy_from_slice(&bytes[1..]);
+        let x = Fe::from_bytes_be(&xb)?;
+        let mut y = x.curve_rhs().sqrt()?;
+        if y.to_bytes_be()[31] & 1 != bytes[0] & 1 {
+            y = Fe::ZERO.sub(&y);
+        }
+        Some(Point { x, y, z: Fe::ONE })
+    }
+
+    // The compressed SEC1 encoding; the identity has none here.
+    pub(crate) fn to_sec1(self) -> Option<[u8; 33]> {
+        let (x, y) = self.to_affine()?;
+        let mut bytes = [0_u8; 33];
+        bytes[0] = 0x02 | (y[31] & 1);
+        bytes[1..].copy_from_slice(&x);
+        Some(bytes)
+    }
+
+    // The affine coordinates as a public key; the identity has none.
+    pub(crate) fn to_public(self) -> Option<sgx_ec256_public_t> {
+        let (mut gx, mut gy) = self.to_affine()?;
+        gx.reverse();
+        gy.reverse();
+        Some(sgx_ec256_public_t { gx, gy })
+    }
+
+    // The big-endian affine coordinates; the identity has none.
+    pub(crate) fn to_affine(self) -> Option<([u8; 32], [u8; 32])> {
+        if self.is_identity() {
+            return None;
+        }
+        let z_inv = Fe(P.invert(&self.z.0));
+        Some((
+            self.x.mul(&z_inv).to_bytes_be(),
+            self.y.mul(&z_inv).to_bytes_be(),
+        ))
+    }
+
+    pub(crate) fn is_identity(&self) -> bool {
+        self.z.to_bytes_be() == [0; 32]
+    }
+
+    // Algorithm 4 of Renes, Costello and Batina, for a = -3. It is complete: it also
+    // doubles, and handles the identity.
+    pub(crate) fn add(&self, q: &Point) -> Point {
+        let (x1, y1, z1) = (&self.x, &self.y, &self.z);
+        let (x2, y2, z2) = (&q.x, &q.y, &q.z);
+        let mut t0 = x1.mul(x2);
+        let mut t1 = y1.mul(y2);
+        let mut t2 = z1.mul(z2);
+        let mut t3 = x1.add(y1);
+        let mut t4 = x2.add(y2);
+        t3 = t3.mul(&t4);
+        t4 = t0.add(&t1);
+        t3 = t3.sub(&t4);
+        t4 = y1.add(z1);
+        let mut x3 = y2.add(z2);
+        t4 = t4.mul(&x3);
+        x3 = t1.add(&t2);
+        t4 = t4.sub(&x3);
+        x3 = x1.add(z1);
+        let mut y3 = x2.add(z2);
+        x3 = x3.mul(&y3);
+        y3 = t0.add(&t2);
+        y3 = x3.sub(&y3);
+        let mut z3 = Fe::B.mul(&t2);
+        x3 = y3.sub(&z3);
+        z3 = x3.add(&x3);
+        x3 = x3.add(&z3);
+        z3 = t1.sub(&x3);
+        x3 = t1.add(&x3);
+        y3 = Fe::B.mul(&y3);
+        t1 = t2.add(&t2);
+        t2 = t1.add(&t2);
+        y3 = y3.sub(&t2);
+        y3 = y3.sub(&t0);
+        t1 = y3.add(&y3);
+        y3 = t1.add(&y3);
+        t1 = t0.add(&t0);
+        t0 = t1.add(&t0);
+        t0 = t0.sub(&t2);
+        t1 = t4.mul(&y3);
+        t2 = t0.mul(&y3);
+        y3 = x3.mul(&z3);
+        y3 = y3.add(&t2);
+        x3 = x3.mul(&t3);
+        x3 = x3.sub(&t1);
+        z3 = z3.mul(&t4);
+        t1 = t3.mul(&t0);
+        z3 = z3.add(&t1);
+        Point {
+            x: x3,
+            y: y3,
+            z: z3,
+        }
+    }
+
+    // Returns `b` if `choice` is 1 and `self` if it is 0, without branching.
+    pub(crate) fn select(&self, b: &Point, choice: u64) -> Point {
+        Point {
+            x: self.x.select(&b.x, choice),
+            y: self.y.select(&b.y, choice),
+            z: self.z.select(&b.z, choice),
+        }
+    }
+
+    // Multiplies by a secret scalar: a double and an addition for each of the 256 bits,
+    // keeping the sum or not by selection.
+    pub(crate) fn mul_ct(&self, scalar: &Scalar) -> Point {
+        let k = N.leave(&scalar.0);
+        let mut acc = Point::IDENTITY;
+        for i in (0..256).rev() {
+            acc = acc.add(&acc);
+            let sum = acc.add(self);
+            acc = acc.select(&sum, (k[i / 64] >> (i % 64)) & 1);
+        }
+        acc
+    }
+}
+
+// The nonce of RFC 6979, section 3.2, for the private key `x` and the digest `h1`, with
+// HMAC-SHA256. `extra` is appended to the seeding input as section 3.6 allows; an empty
+// `extra` gives the deterministic nonce of the RFC.
+pub(crate) fn rfc6979_nonce(x: &Scalar, h1: &[u8; 32], extra: &[u8]) -> SgxResult<Scalar> {
+    let mut key = [0_u8; 32];
+    let mut v = [0x01_u8; 32];
+    let mut x_octets = x.to_bytes_be();
+    let h_octets = Scalar::from_bytes_be_reduced(h1).to_bytes_be();
+    for round in [0x00_u8, 0x01] {
+        key = rsgx_hmac_sha256_parts(&key, &[&v, &[round], &x_octets, &h_octets, extra])?;
+        v = rsgx_hmac_sha256_parts(&key, &[&v])?;
+    }
+    wipe(&mut x_octets);
+    let k = loop {
+        v = rsgx_hmac_sha256_parts(&key, &[&v])?;
+        if let Some(k) = Scalar::from_bytes_be(&v) {
+            if !k.is_zero() {
+                break k;
+            }
+        }
+        key = rsgx_hmac_sha256_parts(&key, &[&v, &[0x00]])?;
+        v = rsgx_hmac_sha256_parts(&key, &[&v])?;
+    };
+    wipe(&mut key);
+    wipe(&mut v);
+    Ok(k)
+}
+
+pub(crate) fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { core::ptr::write_volatile(b, 0) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::rsgx_hex_decode;
+
+    fn be(hex: &str) -> [u8; 32] {
+        let mut bytes = [0_u8; 32];
+        bytes.copy_from_slice(&rsgx_hex_decode(hex).unwrap());
+        bytes
+    }
+
+    fn scalar(hex: &str) -> Scalar {
+        Scalar::from_bytes_be(&be(hex)).unwrap()
+    }
+
+    fn affine(point: &Point) -> ([u8; 32], [u8; 32]) {
+        point.to_affine().unwrap()
+    }
+
+    const GX: &str = "6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296";
+    const GY: &str = "4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5";
+    // RFC 6979, appendix A.2.5.
+    const X: &str = "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721";
+
+    #[test]
+    fn scalar_known_answers() {
+        let three = Scalar::from_u64(3);
+        assert_eq!(
+            three.invert().to_bytes_be(),
+            be("aaaaaaaa00000000aaaaaaaaaaaaaaaa7def51c91a0fbf034d26872ca84218e1")
+        );
+        assert_eq!(
+            three.mul(&three.invert()).to_bytes_be(),
+            Scalar::from_u64(1).to_bytes_be()
+        );
+        assert_eq!(
+            three.neg().to_bytes_be(),
+            be("ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc63254e")
+        );
+        assert!(three.add(&three.neg()).is_zero());
+        assert_eq!(
+            three.sub(&Scalar::from_u64(5)).to_bytes_be(),
+            Scalar::from_u64(2).neg().to_bytes_be()
+        );
+
+        assert!(Scalar::from_bytes_be(&ORDER).is_none());
+        assert!(Scalar::from_bytes_be_reduced(&ORDER).is_zero());
+        assert_eq!(Scalar::from_bytes_be(&be(X)).unwrap().to_bytes_be(), be(X));
+        assert_eq!(
+            Scalar::from_bytes_be_reduced(&[0xff; 32]).to_bytes_be(),
+            be("00000000ffffffff00000000000000004319055258e8617b0c46353d039cdaae")
+        );
+    }
+
+    #[test]
+    fn point_known_answers() {
+        assert_eq!(affine(&Point::GENERATOR), (be(GX), be(GY)));
+        let g2 = Point::GENERATOR.add(&Point::GENERATOR);
+        let (x2, y2) = (
+            be("7cf27b188d034f7e8a52380304b51ac3c08969e277f21b35a60b48fc47669978"),
+            be("07775510db8ed040293d9ac69f7430dbba7dade63ce982299e04b79d227873d1"),
+        );
+        assert_eq!(affine(&g2), (x2, y2));
+        assert_eq!(
+            affine(&Point::GENERATOR.mul_ct(&Scalar::from_u64(2))),
+            (x2, y2)
+        );
+        let g3 = (
+            be("5ecbe4d1a6330a44c8f7ef951d4bf165e6c6b721efada985fb41661bc6e7fd6c"),
+            be("8734640c4998ff7e374b06ce1a64a2ecd82ab036384fb83d9a79b127a27d5032"),
+        );
+        assert_eq!(affine(&g2.add(&Point::GENERATOR)), g3);
+        assert_eq!(affine(&Point::GENERATOR.mul_ct(&Scalar::from_u64(3))), g3);
+
+        // U = x * G.
+        assert_eq!(
+            affine(&Point::GENERATOR.mul_ct(&scalar(X))),
+            (
+                be("60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6"),
+                be("7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299"),
+            )
+        );
+
+        // (n - 1) * G = -G, and the sum with G is the identity.
+        let minus_g = Point::GENERATOR.mul_ct(&Scalar::from_u64(1).neg());
+        assert_eq!(
+            affine(&minus_g),
+            (
+                be(GX),
+                be("b01cbd1c01e58065711814b583f061e9d431cca994cea1313449bf97c840ae0a")
+            )
+        );
+        assert!(minus_g.add(&Point::GENERATOR).is_identity());
+        assert!(Point::GENERATOR.mul_ct(&Scalar::ZERO).is_identity());
+        assert!(Point::IDENTITY.add(&Point::IDENTITY).is_identity());
+        assert!(Point::IDENTITY.to_affine().is_none());
+        assert_eq!(
+            affine(&Point::IDENTITY.add(&Point::GENERATOR)),
+            (be(GX), be(GY))
+        );
+        assert_eq!(affine(&Point::IDENTITY.select(&g2, 1)), (x2, y2));
+        assert!(Point::IDENTITY.select(&g2, 0).is_identity());
+    }
+
+    #[test]
+    fn encoding_round_trip() {
+        let mut sec1 = [0_u8; 33];
+        sec1[0] = 0x03;
+        sec1[1..].copy_from_slice(&be(GX));
+        assert_eq!(Point::GENERATOR.to_sec1(), Some(sec1));
+        assert_eq!(affine(&Point::from_sec1(&sec1).unwrap()), (be(GX), be(GY)));
+        sec1[0] = 0x02;
+        let minus_g = Point::from_sec1(&sec1).unwrap();
+        assert!(minus_g.add(&Point::GENERATOR).is_identity());
+        assert!(Point::IDENTITY.to_sec1().is_none());
+
+        let public = Point::GENERATOR.mul_ct(&scalar(X)).to_public().unwrap();
+        let mut gx = be("60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6");
+        gx.reverse();
+        assert_eq!(public.gx, gx);
+        let point = Point::from_public(&public).unwrap();
+        assert_eq!(point.to_public().unwrap().gy, public.gy);
+
+        // x = 1 has no point: 1 - 3 + b is not a square.
+        let mut bad = [0_u8; 33];
+        bad[0] = 0x02;
+        bad[32] = 0x01;
+        assert!(Point::from_sec1(&bad).is_none());
+        // x = p.
+        let mut bad = [0xff_u8; 33];
+        bad[0] = 0x02;
+        bad[1..].copy_from_slice(&be(
+            "ffffffff00000001000000000000000000000000ffffffffffffffffffffffff",
+        ));
+        assert!(Point::from_sec1(&bad).is_none());
+        sec1[0] = 0x04;
+        assert!(Point::from_sec1(&sec1).is_none());
+        let mut off_curve = public;
+        off_curve.gy[0] ^= 1;
+        assert!(Point::from_public(&off_curve).is_none());
+    }
+
+    #[test]
+    fn rfc6979_known_answers() {
+        // RFC 6979, appendix A.2.5, with SHA-256.
+        let x = scalar(X);
+        let sample = be("af2bdbe1aa9b6ec1e2ade1d694f41fc71a831d0268e9891562113d8a62add1bf");
+        let test = be("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08");
+        assert_eq!(
+            rfc6979_nonce(&x, &sample, &[]).unwrap().to_bytes_be(),
+            be("a6e3c57dd01abe90086538398355dd4c3b17aa873382b0f24d6129493d8aad60")
+        );
+        assert_eq!(
+            rfc6979_nonce(&x, &test, &[]).unwrap().to_bytes_be(),
+            be("d16b6ae827f17175e040871a1c7ec3500192c4c92677336ec2537acaee0008e0")
+        );
+        let extra: [u8; 32] = core::array::from_fn(|i| i as u8);
+        assert_eq!(
+            rfc6979_nonce(&x, &sample, &extra).unwrap().to_bytes_be(),
+            be("e7eb519fdfdf2373299ac1322cff7b26e78d5041e24740b2e2ecd18d01b56ebf")
+        );
+    }
+}
@@ -0,0 +1,129 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::crypto::SgxShaHandle;
+use crate::kms::rsgx_hkdf_sha256_expand;
+use alloc::vec::Vec;
+use core::ptr;
+use sgx_types::*;
+
+const TRANSCRIPT_LABEL: &[u8] = b"sgx transcript v1";
+const REPORT_DATA_LABEL: &[u8] = b"sgx report data";
+
+const OP_PROTOCOL: u8 = 0;
+const OP_MESSAGE: u8 = 1;
+const OP_CHALLENGE: u8 = 2;
+
+///
+/// A domain-separated transcript of a protocol, in the manner of Merlin.
+///
+/// # Description
+///
+/// Both sides of a protocol append the same labeled messages to their transcripts and
+/// draw challenges from them, so that a challenge depends on the protocol name and on
+/// every message, label and length that precedes it. This makes a Fiat–Shamir transform
+/// non-interactive without ad-hoc hashes of concatenations, in which `a || b` and
+/// `a' || b'` can collide.
+///
+/// The state is a 32-byte SHA-256 chaining value. Every operation replaces it with the
+/// hash of the previous state, an operation byte, the length and bytes of the label, and
+/// the length and bytes of the message. A challenge is HKDF-Expand over SHA-256 with the
+/// state as pseudorandom key, and is itself recorded in the state, so that two challenges
+/// never repeat. A cloned transcript forks the protocol.
+///
+#[derive(Clone)]
+pub struct SgxTranscript {
+    state: sgx_sha256_hash_t,
+}
+
+impl Drop for SgxTranscript {
+    fn drop(&mut self) {
+        for b in self.state.iter_mut() {
+            unsafe { ptr::write_volatile(b, 0) };
+        }
+    }
+}
+
+impl SgxTranscript {
+    ///
+    /// Starts the transcript of the protocol named `protocol`.
+    ///
+    pub fn new(protocol: &[u8]) -> SgxResult<SgxTranscript> {
+        let mut transcript = SgxTranscript {
+            state: sgx_sha256_hash_t::default(),
+        };
+        transcript.absorb(OP_PROTOCOL, TRANSCRIPT_LABEL, protocol)?;
+        Ok(transcript)
+    }
+
+    ///
+    /// Appends `message` under `label`.
+    ///
+    pub fn append_message(&mut self, label: &[u8], message: &[u8]) -> SgxError {
+        self.absorb(OP_MESSAGE, label, message)
+    }
+
+    ///
+    /// Appends `value` under `label`, encoded as 8 little-endian bytes.
+    ///
+    pub fn append_u64(&mut self, label: &[u8], value: u64) -> SgxError {
+        self.absorb(OP_MESSAGE, label, &value.to_le_bytes())
+    }
+
+    ///
+    /// Fills `out` with a challenge bound to the transcript so far and to `label`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `out` is longer than 8160 bytes.
+    ///
+    pub fn challenge_bytes(&mut self, label: &[u8], out: &mut [u8]) -> SgxError {
+        let length = (out.len() as u64).to_le_bytes();
+        let mut info = Vec::with_capacity(1 + 4 + label.len() + 8);
+        info.push(OP_CHALLENGE);
+        info.extend_from_slice(&(label.len() as u32).to_le_bytes());
+        info.extend_from_slice(label);
+        info.extend_from_slice(&length);
+        rsgx_hkdf_sha256_expand(&self.state, &info, out)?;
+        self.absorb(OP_CHALLENGE, label, &length)
+    }
+
+    ///
+    /// Draws a challenge to use as the report data of an attestation report, binding the
+    /// report to the protocol run. The verifier recomputes it from its own transcript.
+    ///
+    pub fn report_data(&mut self) -> SgxResult<sgx_report_data_t> {
+        let mut report_data = sgx_report_data_t::default();
+        self.challenge_bytes(REPORT_DATA_LABEL, &mut report_data.d)?;
+        Ok(report_data)
+    }
+
+    fn absorb(&mut self, op: u8, label: &[u8], message: &[u8]) -> SgxError {
+        let handle = SgxShaHandle::new();
+        handle.init()?;
+        handle.update_slice(&self.state)?;
+        handle.update_slice(&[op])?;
+        handle.update_slice(&(label.len() as u32).to_le_bytes())?;
+        handle.update_slice(label)?;
+        handle.update_slice(&(message.len() as u64).to_le_bytes())?;
+        handle.update_slice(message)?;
+        self.state = handle.get_hash()?;
+        Ok(())
+    }
+}
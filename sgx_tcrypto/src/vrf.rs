@@ -0,0 +1,365 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Verifiable random functions
+//!
+//! ECVRF-P256-SHA256-TAI, the elliptic curve VRF of RFC 9381 (suite 0x01) on P-256 with
+//! SHA-256, try-and-increment hashing to the curve and RFC 6979 nonces.
+//!
+//! The holder of a private key maps an input `alpha` to a pseudorandom output `beta`
+//! together with a proof. Anyone with the public key can check the proof and derive the
+//! same `beta`, and nobody can compute `beta` without the private key. Since an enclave
+//! cannot choose `beta` for a given `alpha`, its outputs serve for leader election and
+//! lotteries that others can audit.
+//!
+//! Keys are ordinary SGX ECDSA P-256 keys, so an enclave identity key can also serve as
+//! its VRF key.
+
+use crate::crypto::SgxShaHandle;
+use crate::p256::{rfc6979_nonce, wipe, Point, Scalar};
+use sgx_types::*;
+
+pub const ECVRF_PROOF_SIZE: usize = 81;
+pub const ECVRF_OUTPUT_SIZE: usize = 32;
+
+const SUITE: u8 = 0x01;
+const POINT_SIZE: usize = 33;
+const CHALLENGE_SIZE: usize = 16;
+
+fn sha256(parts: &[&[u8]]) -> SgxResult<[u8; 32]> {
+    let handle = SgxShaHandle::new();
+    handle.init()?;
+    for part in parts.iter().filter(|p| !p.is_empty()) {
+        handle.update_slice(part)?;
+    }
+    handle.get_hash()
+}
+
+fn encode(point: &Point) -> SgxResult<[u8; POINT_SIZE]> {
+    point.to_sec1().ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)
+}
+
+// ECVRF_encode_to_curve_try_and_increment, with the public key as the salt.
+fn encode_to_curve(public: &[u8; POINT_SIZE], alpha: &[u8]) -> SgxResult<Point> {
+    for counter in 0..=u8::MAX {
+        let hash = sha256(&[&[SUITE, 0x01], public, alpha, &[counter, 0x00]])?;
+        let mut candidate = [0x02_u8; POINT_SIZE];
+        candidate[1..].copy_from_slice(&hash);
+        if let Some(point) = Point::from_sec1(&candidate) {
+            return Ok(point);
+        }
+    }
+    // Fails with probability 2^-256.
+    Err(sgx_status_t::SGX_ERROR_UNEXPECTED)
+}
+
+// ECVRF_challenge_generation: the first 16 bytes of the hash of the five points.
+fn challenge(points: [&Point; 5]) -> SgxResult<[u8; CHALLENGE_SIZE]> {
+    let handle = SgxShaHandle::new();
+    handle.init()?;
+    handle.update_slice(&[SUITE, 0x02])?;
+    for point in points.iter() {
+        handle.update_slice(&encode(point)?)?;
+    }
+    handle.update_slice(&[0x00_u8])?;
+    let hash = handle.get_hash()?;
+    let mut c = [0_u8; CHALLENGE_SIZE];
+    c.copy_from_slice(&hash[..CHALLENGE_SIZE]);
+    Ok(c)
+}
+
+fn challenge_scalar(c: &[u8; CHALLENGE_SIZE]) -> Scalar {
+    let mut bytes = [0_u8; 32];
+    bytes[32 - CHALLENGE_SIZE..].copy_from_slice(c);
+    Scalar::from_bytes_be_reduced(&bytes)
+}
+
+///
+/// The rsgx_ecvrf_prove function computes the VRF proof of `alpha` under a P-256 private
+/// key.
+///
+/// # Description
+///
+/// The proof is `Gamma || c || s`: a compressed point and two big-endian integers of 16
+/// and 32 bytes. The output `beta` follows from the proof with `rsgx_ecvrf_proof_to_hash`.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// The private key is zero or not below the group order.
+///
+/// **SGX_ERROR_UNEXPECTED**
+///
+/// Hashing failed.
+///
+pub fn rsgx_ecvrf_prove(
+    private: &sgx_ec256_private_t,
+    alpha: &[u8],
+) -> SgxResult<[u8; ECVRF_PROOF_SIZE]> {
+    let mut bytes = private.r;
+    bytes.reverse();
+    let x = Scalar::from_bytes_be(&bytes);
+    wipe(&mut bytes);
+    let mut x = x.ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+    if x.is_zero() {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+
+    let result = prove(&x, alpha);
+    x.wipe();
+    result
+}
+
+fn prove(x: &Scalar, alpha: &[u8]) -> SgxResult<[u8; ECVRF_PROOF_SIZE]> {
+    let y = Point::GENERATOR.mul_ct(x);
+    let h = encode_to_curve(&encode(&y)?, alpha)?;
+    let gamma = h.mul_ct(x);
+    let mut k = rfc6979_nonce(x, &sha256(&[&encode(&h)?])?, &[])?;
+    let c = challenge([&y, &h, &gamma, &Point::GENERATOR.mul_ct(&k), &h.mul_ct(&k)])?;
+    let s = k.add(&challenge_scalar(&c).mul(x));
+    k.wipe();
+
+    let mut proof = [0_u8; ECVRF_PROOF_SIZE];
+    proof[..POINT_SIZE].copy_from_slice(&encode(&gamma)?);
+    proof[POINT_SIZE..POINT_SIZE + CHALLENGE_SIZE].copy_from_slice(&c);
+    proof[POINT_SIZE + CHALLENGE_SIZE..].copy_from_slice(&s.to_bytes_be());
+    Ok(proof)
+}
+
+fn decode_proof(proof: &[u8; ECVRF_PROOF_SIZE]) -> Option<(Point, [u8; CHALLENGE_SIZE], Scalar)> {
+    let mut gamma = [0_u8; POINT_SIZE];
+    let mut c = [0_u8; CHALLENGE_SIZE];
+    let mut s = [0_u8; 32];
+    gamma.copy_from_slice(&proof[..POINT_SIZE]);
+    c.copy_from_slice(&proof[POINT_SIZE..POINT_SIZE + CHALLENGE_SIZE]);
+    s.copy_from_slice(&proof[POINT_SIZE + CHALLENGE_SIZE..]);
+    Some((Point::from_sec1(&gamma)?, c, Scalar::from_bytes_be(&s)?))
+}
+
+///
+/// The rsgx_ecvrf_verify function checks the VRF proof of `alpha` under a P-256 public
+/// key.
+///
+/// # Description
+///
+/// A malformed proof fails verification rather than returning an error.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// The public key is not a point on the curve.
+///
+/// **SGX_ERROR_UNEXPECTED**
+///
+/// Hashing failed.
+///
+pub fn rsgx_ecvrf_verify(
+    public: &sgx_ec256_public_t,
+    proof: &[u8; ECVRF_PROOF_SIZE],
+    alpha: &[u8],
+) -> SgxResult<bool> {
+    let y = Point::from_public(public).ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+    let (gamma, c, s) = match decode_proof(proof) {
+        Some(decoded) => decoded,
+        None => return Ok(false),
+    };
+    let h = encode_to_curve(&encode(&y)?, alpha)?;
+    let c_scalar = challenge_scalar(&c);
+    // U = s * B - c * Y and V = s * H - c * Gamma.
+    let u = Point::GENERATOR.mul_ct(&s).add(&y.mul_ct(&c_scalar.neg()));
+    let v = h.mul_ct(&s).add(&gamma.mul_ct(&c_scalar.neg()));
+    if u.is_identity() || v.is_identity() {
+        return Ok(false);
+    }
+    Ok(challenge([&y, &h, &gamma, &u, &v])? == c)
+}
+
+///
+/// The rsgx_ecvrf_proof_to_hash function computes the VRF output `beta` of a proof. Only
+/// a proof that passed `rsgx_ecvrf_verify` yields a trustworthy output.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// The proof is malformed.
+///
+/// **SGX_ERROR_UNEXPECTED**
+///
+/// Hashing failed.
+///
+pub fn rsgx_ecvrf_proof_to_hash(
+    proof: &[u8; ECVRF_PROOF_SIZE],
+) -> SgxResult<[u8; ECVRF_OUTPUT_SIZE]> {
+    let (gamma, _, _) = decode_proof(proof).ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+    // The cofactor of P-256 is one.
+    sha256(&[&[SUITE, 0x03], &encode(&gamma)?, &[0x00]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::rsgx_hex_decode;
+
+    fn private(hex: &str) -> sgx_ec256_private_t {
+        let mut r = [0_u8; 32];
+        r.copy_from_slice(&rsgx_hex_decode(hex).unwrap());
+        r.reverse();
+        sgx_ec256_private_t { r }
+    }
+
+    fn public(sec1: &str) -> sgx_ec256_public_t {
+        let mut bytes = [0_u8; POINT_SIZE];
+        bytes.copy_from_slice(&rsgx_hex_decode(sec1).unwrap());
+        Point::from_sec1(&bytes).unwrap().to_public().unwrap()
+    }
+
+    const SK_1: &str = "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721";
+    const PK_1: &str = "0360fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6";
+    const SK_3: &str = "2ca1411a41b17b24cc8c3b089cfd033f1920202a6c0de8abb97df1498d50d2c8";
+    const PK_3: &str = "03596375e6ce57e0f20294fc46bdfcfd19a39f8161b58695b3ec5b3d16427c274d";
+
+    // RFC 9381, appendix B.1. `h` is empty where the RFC does not list it.
+    struct Example {
+        sk: &'static str,
+        pk: &'static str,
+        alpha: &'static [u8],
+        h: &'static str,
+        pi: &'static str,
+        beta: &'static str,
+    }
+
+    const EXAMPLES: [Example; 3] = [
+        Example {
+            sk: SK_1,
+            pk: PK_1,
+            alpha: b"sample",
+            h: "0272a877532e9ac193aff4401234266f59900a4a9e3fc3cfc6a4b7e467a15d06d4",
+            pi: "035b5c726e8c0e2c488a107c600578ee75cb702343c153cb1eb8dec77f4b5071b4\
+                 a53f0a46f018bc2c56e58d383f2305e0\
+                 975972c26feea0eb122fe7893c15af376b33edf7de17c6ea056d4d82de6bc02f",
+            beta: "a3ad7b0ef73d8fc6655053ea22f9bede8c743f08bbed3d38821f0e16474b505e",
+        },
+        Example {
+            sk: SK_1,
+            pk: PK_1,
+            alpha: b"test",
+            h: "02173119b4fff5e6f8afed4868a29fe8920f1b54c2cf89cc7b301d0d473de6b974",
+            pi: "034dac60aba508ba0c01aa9be80377ebd7562c4a52d74722e0abae7dc3080ddb56\
+                 c19e067b15a8a8174905b13617804534\
+                 214f935b94c2287f797e393eb0816969d864f37625b443f30f1a5a33f2b3c854",
+            beta: "a284f94ceec2ff4b3794629da7cbafa49121972671b466cab4ce170aa365f26d",
+        },
+        Example {
+            sk: SK_3,
+            pk: PK_3,
+            alpha: b"Example using ECDSA key from Appendix L.4.2 of ANSI.X9-62-2005",
+            h: "",
+            pi: "03d03398bf53aa23831d7d1b2937e005fb0062cbefa06796579f2a1fc7e7b8c667\
+                 d091c00b0f5c3619d10ecea44363b5a5\
+                 99cadc5b2957e223fec62e81f7b4825fc799a771a3d7334b9186bdbee87316b1",
+            beta: "90871e06da5caa39a3c61578ebb844de8635e27ac0b13e829997d0d95dd98c19",
+        },
+    ];
+
+    #[test]
+    fn rfc9381_examples() {
+        for Example {
+            sk,
+            pk,
+            alpha,
+            h,
+            pi,
+            beta,
+        } in EXAMPLES.iter()
+        {
+            let proof = rsgx_ecvrf_prove(&private(sk), alpha).unwrap();
+            assert_eq!(proof.to_vec(), rsgx_hex_decode(pi).unwrap());
+            assert_eq!(
+                rsgx_ecvrf_proof_to_hash(&proof).unwrap().to_vec(),
+                rsgx_hex_decode(beta).unwrap()
+            );
+            assert_eq!(rsgx_ecvrf_verify(&public(pk), &proof, alpha), Ok(true));
+
+            let mut pk_bytes = [0_u8; POINT_SIZE];
+            pk_bytes.copy_from_slice(&rsgx_hex_decode(pk).unwrap());
+            let y = Point::GENERATOR.mul_ct(&Scalar::from_bytes_be_reduced(&{
+                let mut x = [0_u8; 32];
+                x.copy_from_slice(&rsgx_hex_decode(sk).unwrap());
+                x
+            }));
+            assert_eq!(y.to_sec1(), Some(pk_bytes));
+            if !h.is_empty() {
+                let point = encode_to_curve(&pk_bytes, alpha).unwrap();
+                assert_eq!(
+                    point.to_sec1().unwrap().to_vec(),
+                    rsgx_hex_decode(h).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_bad_proofs() {
+        let Example { sk, pk, alpha, .. } = EXAMPLES[0];
+        let proof = rsgx_ecvrf_prove(&private(sk), alpha).unwrap();
+        let public = public(pk);
+        assert_eq!(rsgx_ecvrf_verify(&public, &proof, b"other"), Ok(false));
+        assert_eq!(
+            rsgx_ecvrf_verify(&self::public(PK_3), &proof, alpha),
+            Ok(false)
+        );
+        for i in [
+            0,
+            1,
+            POINT_SIZE,
+            POINT_SIZE + CHALLENGE_SIZE,
+            ECVRF_PROOF_SIZE - 1,
+        ] {
+            let mut tampered = proof;
+            tampered[i] ^= 1;
+            assert_eq!(
+                rsgx_ecvrf_verify(&public, &tampered, alpha),
+                Ok(false),
+                "{}",
+                i
+            );
+        }
+
+        // Gamma not on the curve, and s not below the group order.
+        let mut bad = proof;
+        bad[0] = 0x04;
+        assert!(rsgx_ecvrf_proof_to_hash(&bad).is_err());
+        assert_eq!(rsgx_ecvrf_verify(&public, &bad, alpha), Ok(false));
+        let mut bad = proof;
+        bad[POINT_SIZE + CHALLENGE_SIZE..].copy_from_slice(&[0xff; 32]);
+        assert_eq!(rsgx_ecvrf_verify(&public, &bad, alpha), Ok(false));
+
+        let mut off_curve = public;
+        off_curve.gy[0] ^= 1;
+        assert_eq!(
+            rsgx_ecvrf_verify(&off_curve, &proof, alpha),
+            Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+        );
+        assert_eq!(
+            rsgx_ecvrf_prove(&sgx_ec256_private_t::default(), alpha).err(),
+            Some(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+        );
+    }
+}
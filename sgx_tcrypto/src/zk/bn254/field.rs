@@ -0,0 +1,383 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+// The field tower of BN254:
+//
+// Fp2 = Fp[u] / (u^2 + 1)
+// Fp6 = Fp2[v] / (v^3 - xi), xi = 9 + u
+// Fp12 = Fp6[w] / (w^2 - v)
+//
+// The arithmetic handles public values only and is not constant-time.
+
+use crate::fp256::{load_be, BN254_P as P};
+
+pub(crate) trait Field: Copy {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn add(&self, b: &Self) -> Self;
+    fn sub(&self, b: &Self) -> Self;
+    fn mul(&self, b: &Self) -> Self;
+    fn neg(&self) -> Self;
+    // The inverse of a non-zero element; zero maps to zero.
+    fn invert(&self) -> Self;
+    fn is_zero(&self) -> bool;
+
+    fn square(&self) -> Self {
+        self.mul(self)
+    }
+
+    fn double(&self) -> Self {
+        self.add(self)
+    }
+
+    fn equals(&self, b: &Self) -> bool {
+        self.sub(b).is_zero()
+    }
+
+    // self^e for an exponent of little-endian limbs.
+    fn pow(&self, e: &[u64]) -> Self {
+        let mut x = Self::one();
+        for i in (0..64 * e.len()).rev() {
+            x = x.square();
+            if (e[i / 64] >> (i % 64)) & 1 == 1 {
+                x = x.mul(self);
+            }
+        }
+        x
+    }
+}
+
+#[derive(Copy, Clone)]
+pub(crate) struct Fp([u64; 4]);
+
+impl Fp {
+    // Reads a big-endian integer below p.
+    pub(crate) fn from_bytes_be(bytes: &[u8; 32]) -> Option<Fp> {
+        let l = load_be(bytes);
+        if P.is_canonical(&l) {
+            Some(Fp(P.enter(&l)))
+        } else {
+            None
+        }
+    }
+
+    // An ordinary integer below p, for constants.
+    pub(crate) fn from_canonical(l: &[u64; 4]) -> Fp {
+        Fp(P.enter(l))
+    }
+
+    pub(crate) fn from_u64(x: u64) -> Fp {
+        Fp(P.enter(&[x, 0, 0, 0]))
+    }
+}
+
+impl Field for Fp {
+    fn zero() -> Fp {
+        Fp([0; 4])
+    }
+
+    fn one() -> Fp {
+        // 2^256 mod p
+        Fp([
+            0xd35d_438d_c58f_0d9d,
+            0x0a78_eb28_f5c7_0b3d,
+            0x666e_a36f_7879_462c,
+            0x0e0a_77c1_9a07_df2f,
+        ])
+    }
+
+    fn add(&self, b: &Fp) -> Fp {
+        Fp(P.add(&self.0, &b.0))
+    }
+
+    fn sub(&self, b: &Fp) -> Fp {
+        Fp(P.sub(&self.0, &b.0))
+    }
+
+    fn mul(&self, b: &Fp) -> Fp {
+        Fp(P.mul(&self.0, &b.0))
+    }
+
+    fn neg(&self) -> Fp {
+        Fp::zero().sub(self)
+    }
+
+    fn invert(&self) -> Fp {
+        Fp(P.invert(&self.0))
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == [0; 4]
+    }
+}
+
+#[derive(Copy, Clone)]
+pub(crate) struct Fp2 {
+    pub(crate) c0: Fp,
+    pub(crate) c1: Fp,
+}
+
+impl Fp2 {
+    pub(crate) fn new(c0: Fp, c1: Fp) -> Fp2 {
+        Fp2 { c0, c1 }
+    }
+
+    pub(crate) fn conjugate(&self) -> Fp2 {
+        Fp2::new(self.c0, self.c1.neg())
+    }
+
+    pub(crate) fn mul_by_fp(&self, b: &Fp) -> Fp2 {
+        Fp2::new(self.c0.mul(b), self.c1.mul(b))
+    }
+
+    // Multiplies by xi = 9 + u.
+    fn mul_by_xi(&self) -> Fp2 {
+        let nine = Fp::from_u64(9);
+        Fp2::new(
+            self.c0.mul(&nine).sub(&self.c1),
+            self.c1.mul(&nine).add(&self.c0),
+        )
+    }
+}
+
+impl Field for Fp2 {
+    fn zero() -> Fp2 {
+        Fp2::new(Fp::zero(), Fp::zero())
+    }
+
+    fn one() -> Fp2 {
+        Fp2::new(Fp::one(), Fp::zero())
+    }
+
+    fn add(&self, b: &Fp2) -> Fp2 {
+        Fp2::new(self.c0.add(&b.c0), self.c1.add(&b.c1))
+    }
+
+    fn sub(&self, b: &Fp2) -> Fp2 {
+        Fp2::new(self.c0.sub(&b.c0), self.c1.sub(&b.c1))
+    }
+
+    fn mul(&self, b: &Fp2) -> Fp2 {
+        Fp2::new(
+            self.c0.mul(&b.c0).sub(&self.c1.mul(&b.c1)),
+            self.c0.mul(&b.c1).add(&self.c1.mul(&b.c0)),
+        )
+    }
+
+    fn neg(&self) -> Fp2 {
+        Fp2::new(self.c0.neg(), self.c1.neg())
+    }
+
+    fn invert(&self) -> Fp2 {
+        let norm = self.c0.square().add(&self.c1.square()).invert();
+        self.conjugate().mul_by_fp(&norm)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.c0.is_zero() && self.c1.is_zero()
+    }
+}
+
+#[derive(Copy, Clone)]
+pub(crate) struct Fp6 {
+    pub(crate) c0: Fp2,
+    pub(crate) c1: Fp2,
+    pub(crate) c2: Fp2,
+}
+
+impl Fp6 {
+    pub(crate) fn new(c0: Fp2, c1: Fp2, c2: Fp2) -> Fp6 {
+        Fp6 { c0, c1, c2 }
+    }
+
+    // Multiplies by v.
+    fn mul_by_v(&self) -> Fp6 {
+        Fp6::new(self.c2.mul_by_xi(), self.c0, self.c1)
+    }
+}
+
+impl Field for Fp6 {
+    fn zero() -> Fp6 {
+        Fp6::new(Fp2::zero(), Fp2::zero(), Fp2::zero())
+    }
+
+    fn one() -> Fp6 {
+        Fp6::new(Fp2::one(), Fp2::zero(), Fp2::zero())
+    }
+
+    fn add(&self, b: &Fp6) -> Fp6 {
+        Fp6::new(self.c0.add(&b.c0), self.c1.add(&b.c1), self.c2.add(&b.c2))
+    }
+
+    fn sub(&self, b: &Fp6) -> Fp6 {
+        Fp6::new(self.c0.sub(&b.c0), self.c1.sub(&b.c1), self.c2.sub(&b.c2))
+    }
+
+    fn mul(&self, b: &Fp6) -> Fp6 {
+        let (a0, a1, a2) = (&self.c0, &self.c1, &self.c2);
+        let (b0, b1, b2) = (&b.c0, &b.c1, &b.c2);
+        Fp6::new(
+            a0.mul(b0).add(&a1.mul(b2).add(&a2.mul(b1)).mul_by_xi()),
+            a0.mul(b1).add(&a1.mul(b0)).add(&a2.mul(b2).mul_by_xi()),
+            a0.mul(b2).add(&a1.mul(b1)).add(&a2.mul(b0)),
+        )
+    }
+
+    fn neg(&self) -> Fp6 {
+        Fp6::new(self.c0.neg(), self.c1.neg(), self.c2.neg())
+    }
+
+    fn invert(&self) -> Fp6 {
+        let (a0, a1, a2) = (&self.c0, &self.c1, &self.c2);
+        let t0 = a0.square().sub(&a1.mul(a2).mul_by_xi());
+        let t1 = a2.square().mul_by_xi().sub(&a0.mul(a1));
+        let t2 = a1.square().sub(&a0.mul(a2));
+        let det = a0
+            .mul(&t0)
+            .add(&a2.mul(&t1).add(&a1.mul(&t2)).mul_by_xi())
+            .invert();
+        Fp6::new(t0.mul(&det), t1.mul(&det), t2.mul(&det))
+    }
+
+    fn is_zero(&self) -> bool {
+        self.c0.is_zero() && self.c1.is_zero() && self.c2.is_zero()
+    }
+}
+
+#[derive(Copy, Clone)]
+pub(crate) struct Fp12 {
+    pub(crate) c0: Fp6,
+    pub(crate) c1: Fp6,
+}
+
+impl Fp12 {
+    pub(crate) fn new(c0: Fp6, c1: Fp6) -> Fp12 {
+        Fp12 { c0, c1 }
+    }
+
+    // self^(p^6), since w^(p^6) = -w.
+    pub(crate) fn conjugate(&self) -> Fp12 {
+        Fp12::new(self.c0, self.c1.neg())
+    }
+}
+
+impl Field for Fp12 {
+    fn zero() -> Fp12 {
+        Fp12::new(Fp6::zero(), Fp6::zero())
+    }
+
+    fn one() -> Fp12 {
+        Fp12::new(Fp6::one(), Fp6::zero())
+    }
+
+    fn add(&self, b: &Fp12) -> Fp12 {
+        Fp12::new(self.c0.add(&b.c0), self.c1.add(&b.c1))
+    }
+
+    fn sub(&self, b: &Fp12) -> Fp12 {
+        Fp12::new(self.c0.sub(&b.c0), self.c1.sub(&b.c1))
+    }
+
+    fn mul(&self, b: &Fp12) -> Fp12 {
+        let (a0, a1) = (&self.c0, &self.c1);
+        let (b0, b1) = (&b.c0, &b.c1);
+        Fp12::new(
+            a0.mul(b0).add(&a1.mul(b1).mul_by_v()),
+            a0.mul(b1).add(&a1.mul(b0)),
+        )
+    }
+
+    fn neg(&self) -> Fp12 {
+        Fp12::new(self.c0.neg(), self.c1.neg())
+    }
+
+    fn invert(&self) -> Fp12 {
+        let norm = self.c0.square().sub(&self.c1.square().mul_by_v()).invert();
+        Fp12::new(self.c0.mul(&norm), self.c1.mul(&norm).neg())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.c0.is_zero() && self.c1.is_zero()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fp2(a: u64, b: u64) -> Fp2 {
+        Fp2::new(Fp::from_u64(a), Fp::from_u64(b))
+    }
+
+    fn fp6(a: u64) -> Fp6 {
+        Fp6::new(fp2(a, a + 1), fp2(a + 2, a + 3), fp2(a + 4, a + 5))
+    }
+
+    #[test]
+    fn fp_known_answers() {
+        assert!(Fp::one().equals(&Fp::from_u64(1)));
+        assert!(Fp::from_u64(6).equals(&Fp::from_u64(2).mul(&Fp::from_u64(3))));
+        // p - 1 = -1, and (p + 1) / 2 = 1 / 2.
+        let minus_one = Fp::from_bytes_be(&[
+            0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81,
+            0x58, 0x5d, 0x97, 0x81, 0x6a, 0x91, 0x68, 0x71, 0xca, 0x8d, 0x3c, 0x20, 0x8c, 0x16,
+            0xd8, 0x7c, 0xfd, 0x46,
+        ])
+        .unwrap();
+        assert!(minus_one.equals(&Fp::one().neg()));
+        assert!(minus_one.square().equals(&Fp::one()));
+        let half = Fp::from_canonical(&[
+            0x9e10_460b_6c3e_7ea4,
+            0xcbc0_b548_b438_e546,
+            0xdc28_22db_40c0_ac2e,
+            0x1832_2739_7098_d014,
+        ]);
+        assert!(Fp::from_u64(2).invert().equals(&half));
+        assert!(Fp::zero().invert().is_zero());
+
+        let p = [
+            0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81,
+            0x58, 0x5d, 0x97, 0x81, 0x6a, 0x91, 0x68, 0x71, 0xca, 0x8d, 0x3c, 0x20, 0x8c, 0x16,
+            0xd8, 0x7c, 0xfd, 0x47,
+        ];
+        assert!(Fp::from_bytes_be(&p).is_none());
+    }
+
+    #[test]
+    fn tower_identities() {
+        // u^2 = -1, v^3 = xi and w^2 = v.
+        let u = fp2(0, 1);
+        assert!(u.square().equals(&Fp2::one().neg()));
+        let v = Fp6::new(Fp2::zero(), Fp2::one(), Fp2::zero());
+        let xi = Fp6::new(fp2(9, 1), Fp2::zero(), Fp2::zero());
+        assert!(v.square().mul(&v).equals(&xi));
+        let w = Fp12::new(Fp6::zero(), Fp6::one());
+        assert!(w.square().equals(&Fp12::new(v, Fp6::zero())));
+
+        let a = fp2(3, 4);
+        assert!(a.mul(&a.invert()).equals(&Fp2::one()));
+        assert!(a.mul(&a.conjugate()).equals(&fp2(25, 0)));
+        let b = fp6(7);
+        assert!(b.mul(&b.invert()).equals(&Fp6::one()));
+        let c = Fp12::new(fp6(11), fp6(17));
+        assert!(c.mul(&c.invert()).equals(&Fp12::one()));
+        let b = Fp12::new(b, Fp6::zero());
+        assert!(c.mul(&b).equals(&b.mul(&c)));
+        // pow agrees with repeated multiplication.
+        assert!(c.pow(&[5]).equals(&c.square().square().mul(&c)));
+    }
+}
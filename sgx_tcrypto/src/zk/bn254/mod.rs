@@ -0,0 +1,591 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Pairings on BN254, also known as alt_bn128.
+//!
+//! Points are encoded as by the Ethereum precompiles (EIP-196 and EIP-197): a point of
+//! G1 is `x || y`, and a point of G2 is `x.c1 || x.c0 || y.c1 || y.c0`, with every
+//! coordinate a 32-byte big-endian integer. The point at infinity is all zeros.
+//!
+//! The pairing is the optimal ate pairing. Inputs are public, and the arithmetic runs in
+//! variable time.
+
+mod field;
+
+use self::field::{Field, Fp, Fp12, Fp2, Fp6};
+use crate::fp256::{load_be, sub_limbs};
+use alloc::vec::Vec;
+use sgx_types::*;
+
+pub const BN254_G1_SIZE: usize = 64;
+pub const BN254_G2_SIZE: usize = 128;
+
+// The order r of G1 and G2.
+const ORDER: [u64; 4] = [
+    0x43e1_f593_f000_0001,
+    0x2833_e848_79b9_7091,
+    0xb850_45b6_8181_585d,
+    0x3064_4e72_e131_a029,
+];
+
+// b' = 3 / xi, of the twist y^2 = x^3 + b' that holds G2.
+const TWIST_B: [[u64; 4]; 2] = [
+    [
+        0x3267_e6dc_24a1_38e5,
+        0xb5b4_c5e5_59db_efa3,
+        0x81be_1899_1be0_6ac3,
+        0x2b14_9d40_ceb8_aaae,
+    ],
+    [
+        0xe4a2_bd06_85c3_15d2,
+        0xa74f_a084_e52d_1852,
+        0xcd2c_afad_eed8_fdf4,
+        0x0097_13b0_3af0_fed4,
+    ],
+];
+
+// xi^((p - 1) / 3) and xi^((p - 1) / 2), for the Frobenius map on the twist.
+const FROBENIUS_X: [[u64; 4]; 2] = [
+    [
+        0x99e3_9557_176f_553d,
+        0xb78c_c310_c2c3_330c,
+        0x4c0b_ec3c_f559_b143,
+        0x2fb3_4798_4f79_11f7,
+    ],
+    [
+        0x1665_d51c_640f_cba2,
+        0x32ae_2a1d_0b7c_9dce,
+        0x4ba4_cc8b_d75a_0794,
+        0x16c9_e550_61eb_ae20,
+    ],
+];
+const FROBENIUS_Y: [[u64; 4]; 2] = [
+    [
+        0xdc54_0146_71a0_135a,
+        0xdbaa_e0ed_a9c9_5998,
+        0xdc5e_c698_b6e2_f9b9,
+        0x063c_f305_489a_f5dc,
+    ],
+    [
+        0x82d3_7f63_2623_b0e3,
+        0x2180_7dc9_8fa2_5bd2,
+        0x0704_b5a7_ec79_6f2b,
+        0x07c0_3cbc_ac41_049a,
+    ],
+];
+// xi^((p^2 - 1) / 3), which lies in Fp; xi^((p^2 - 1) / 2) is -1.
+const FROBENIUS2_X: [u64; 4] = [
+    0xe4bd_44e5_607c_fd48,
+    0xc28f_069f_bb96_6e3d,
+    0x5e6d_d9e7_e0ac_ccb0,
+    0x3064_4e72_e131_a029,
+];
+
+// 6x + 2 for the BN parameter x = 4965661367192848881.
+const ATE_LOOP_COUNT: u128 = 0x1_9d79_7039_be76_3ba8;
+const ATE_LOOP_BITS: usize = 65;
+
+// (p^6 + 1) / r, the exponent of the final exponentiation after f^(p^6 - 1).
+const FINAL_EXPONENT: [u64; 20] = [
+    0x5250_a540_36e3_f812,
+    0xa563_5f15_9678_9051,
+    0xd113_8bf5_4d5b_d1d4,
+    0xa8ce_2533_be36_c7a2,
+    0x94f6_9f6b_84e0_9bf6,
+    0x42ad_1f5e_50ef_3644,
+    0x0fcc_420e_48c3_454c,
+    0x758e_4408_ecc9_952c,
+    0xc901_bf18_87c6_042c,
+    0xa733_cd65_b14b_b3b5,
+    0xdf6d_76bd_cf51_b0d8,
+    0xca64_c0fd_82eb_59e1,
+    0x1d2e_5726_e392_76a1,
+    0xc2d1_ea74_a391_cae9,
+    0x0740_9206_c82d_647e,
+    0x051c_6d1a_a5af_dd17,
+    0xb37f_6019_1966_7af5,
+    0x150e_578c_5084_015b,
+    0xfbde_a556_c239_98e4,
+    0x000f_d14c_c52f_5b83,
+];
+
+fn fp2(c: &[[u64; 4]; 2]) -> Fp2 {
+    Fp2::new(Fp::from_canonical(&c[0]), Fp::from_canonical(&c[1]))
+}
+
+// A point in affine coordinates, or the point at infinity.
+#[derive(Copy, Clone)]
+pub(crate) struct Affine<F> {
+    x: F,
+    y: F,
+    infinity: bool,
+}
+
+// A point in Jacobian coordinates (X : Y : Z), the affine point (X/Z^2, Y/Z^3).
+#[derive(Copy, Clone)]
+pub(crate) struct Jacobian<F> {
+    x: F,
+    y: F,
+    z: F,
+}
+
+pub(crate) type G1 = Affine<Fp>;
+pub(crate) type G2 = Affine<Fp2>;
+
+impl<F: Field> Affine<F> {
+    pub(crate) fn identity() -> Affine<F> {
+        Affine {
+            x: F::zero(),
+            y: F::one(),
+            infinity: true,
+        }
+    }
+
+    pub(crate) fn is_identity(&self) -> bool {
+        self.infinity
+    }
+
+    pub(crate) fn neg(&self) -> Affine<F> {
+        Affine {
+            x: self.x,
+            y: self.y.neg(),
+            infinity: self.infinity,
+        }
+    }
+
+    fn is_on_curve(&self, b: &F) -> bool {
+        self.infinity || self.y.square().equals(&self.x.square().mul(&self.x).add(b))
+    }
+
+    pub(crate) fn to_jacobian(self) -> Jacobian<F> {
+        if self.infinity {
+            Jacobian::identity()
+        } else {
+            Jacobian {
+                x: self.x,
+                y: self.y,
+                z: F::one(),
+            }
+        }
+    }
+}
+
+impl<F: Field> Jacobian<F> {
+    pub(crate) fn identity() -> Jacobian<F> {
+        Jacobian {
+            x: F::one(),
+            y: F::one(),
+            z: F::zero(),
+        }
+    }
+
+    pub(crate) fn is_identity(&self) -> bool {
+        self.z.is_zero()
+    }
+
+    // dbl-2009-l, for a = 0.
+    pub(crate) fn double(&self) -> Jacobian<F> {
+        if self.is_identity() {
+            return *self;
+        }
+        let a = self.x.square();
+        let b = self.y.square();
+        let c = b.square();
+        let d = self.x.add(&b).square().sub(&a).sub(&c).double();
+        let e = a.double().add(&a);
+        let x3 = e.square().sub(&d.double());
+        let c8 = c.double().double().double();
+        Jacobian {
+            x: x3,
+            y: e.mul(&d.sub(&x3)).sub(&c8),
+            z: self.y.mul(&self.z).double(),
+        }
+    }
+
+    pub(crate) fn add(&self, q: &Jacobian<F>) -> Jacobian<F> {
+        if self.is_identity() {
+            return *q;
+        }
+        if q.is_identity() {
+            return *self;
+        }
+        let z1z1 = self.z.square();
+        let z2z2 = q.z.square();
+        let u1 = self.x.mul(&z2z2);
+        let u2 = q.x.mul(&z1z1);
+        let s1 = self.y.mul(&q.z).mul(&z2z2);
+        let s2 = q.y.mul(&self.z).mul(&z1z1);
+        let h = u2.sub(&u1);
+        let r = s2.sub(&s1);
+        if h.is_zero() {
+            return if r.is_zero() {
+                self.double()
+            } else {
+                Jacobian::identity()
+            };
+        }
+        let h2 = h.square();
+        let h3 = h.mul(&h2);
+        let u1h2 = u1.mul(&h2);
+        let x3 = r.square().sub(&h3).sub(&u1h2.double());
+        Jacobian {
+            x: x3,
+            y: r.mul(&u1h2.sub(&x3)).sub(&s1.mul(&h3)),
+            z: self.z.mul(&q.z).mul(&h),
+        }
+    }
+
+    // Multiplies by a scalar of little-endian limbs.
+    pub(crate) fn mul(&self, scalar: &[u64; 4]) -> Jacobian<F> {
+        let mut acc = Jacobian::identity();
+        for i in (0..256).rev() {
+            acc = acc.double();
+            if (scalar[i / 64] >> (i % 64)) & 1 == 1 {
+                acc = acc.add(self);
+            }
+        }
+        acc
+    }
+
+    pub(crate) fn to_affine(self) -> Affine<F> {
+        if self.is_identity() {
+            return Affine::identity();
+        }
+        let z_inv = self.z.invert();
+        let z_inv2 = z_inv.square();
+        Affine {
+            x: self.x.mul(&z_inv2),
+            y: self.y.mul(&z_inv2).mul(&z_inv),
+            infinity: false,
+        }
+    }
+}
+
+fn read_fp(bytes: &[u8]) -> SgxResult<Fp> {
+    let mut b = [0_u8; 32];
+    b.copy_from_slice(&bytes[..32]);
+    Fp::from_bytes_be(&b).ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+}
+
+// Reads a point of G1, checking that it is on the curve; G1 is the whole curve.
+pub(crate) fn decode_g1(bytes: &[u8; BN254_G1_SIZE]) -> SgxResult<G1> {
+    if bytes.iter().all(|b| *b == 0) {
+        return Ok(G1::identity());
+    }
+    let point = G1 {
+        x: read_fp(&bytes[..32])?,
+        y: read_fp(&bytes[32..])?,
+        infinity: false,
+    };
+    if !point.is_on_curve(&Fp::from_u64(3)) {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    Ok(point)
+}
+
+// Reads a point of G2, checking that it is on the twist and in the subgroup of order r.
+pub(crate) fn decode_g2(bytes: &[u8; BN254_G2_SIZE]) -> SgxResult<G2> {
+    if bytes.iter().all(|b| *b == 0) {
+        return Ok(G2::identity());
+    }
+    let point = G2 {
+        x: Fp2::new(read_fp(&bytes[32..64])?, read_fp(&bytes[..32])?),
+        y: Fp2::new(read_fp(&bytes[96..])?, read_fp(&bytes[64..96])?),
+        infinity: false,
+    };
+    if !point.is_on_curve(&fp2(&TWIST_B)) || !point.to_jacobian().mul(&ORDER).is_identity() {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    Ok(point)
+}
+
+// Reads a scalar below r.
+pub(crate) fn decode_scalar(bytes: &[u8; 32]) -> SgxResult<[u64; 4]> {
+    let scalar = load_be(bytes);
+    if sub_limbs(&scalar, &ORDER).1 == 0 {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    Ok(scalar)
+}
+
+// The line through `t` with slope `lambda`, at the untwisted image of `p`:
+// y_P - lambda * x_P * w + (lambda * x_T - y_T) * w^3, where w^3 = v * w.
+fn line(lambda: &Fp2, t: &G2, p: &G1) -> Fp12 {
+    Fp12::new(
+        Fp6::new(Fp2::new(p.y, Fp::zero()), Fp2::zero(), Fp2::zero()),
+        Fp6::new(
+            lambda.mul_by_fp(&p.x).neg(),
+            lambda.mul(&t.x).sub(&t.y),
+            Fp2::zero(),
+        ),
+    )
+}
+
+// Doubles `t` and returns the tangent at `t`, evaluated at `p`.
+fn double_step(t: &mut G2, p: &G1) -> Fp12 {
+    let x2 = t.x.square();
+    let lambda = x2.double().add(&x2).mul(&t.y.double().invert());
+    let l = line(&lambda, t, p);
+    let x3 = lambda.square().sub(&t.x.double());
+    t.y = lambda.mul(&t.x.sub(&x3)).sub(&t.y);
+    t.x = x3;
+    l
+}
+
+// Adds `q` to `t` and returns the line through both, evaluated at `p`.
+fn add_step(t: &mut G2, q: &G2, p: &G1) -> SgxResult<Fp12> {
+    let dx = q.x.sub(&t.x);
+    if dx.is_zero() {
+        // Only reachable with points outside G2, which decoding rejects.
+        return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+    }
+    let lambda = q.y.sub(&t.y).mul(&dx.invert());
+    let l = line(&lambda, t, p);
+    let x3 = lambda.square().sub(&t.x).sub(&q.x);
+    t.y = lambda.mul(&t.x.sub(&x3)).sub(&t.y);
+    t.x = x3;
+    Ok(l)
+}
+
+// The product of the Miller loops of the pairs, sharing the squarings.
+fn miller_loop(pairs: &[(G1, G2)]) -> SgxResult<Fp12> {
+    let pairs: Vec<&(G1, G2)> = pairs
+        .iter()
+        .filter(|(p, q)| !p.is_identity() && !q.is_identity())
+        .collect();
+    let mut ts: Vec<G2> = pairs.iter().map(|(_, q)| *q).collect();
+    let mut f = Fp12::one();
+    for i in (0..ATE_LOOP_BITS - 1).rev() {
+        f = f.square();
+        for ((p, _), t) in pairs.iter().zip(ts.iter_mut()) {
+            f = f.mul(&double_step(t, p));
+        }
+        if (ATE_LOOP_COUNT >> i) & 1 == 1 {
+            for ((p, q), t) in pairs.iter().zip(ts.iter_mut()) {
+                f = f.mul(&add_step(t, q, p)?);
+            }
+        }
+    }
+
+    let (fx, fy) = (fp2(&FROBENIUS_X), fp2(&FROBENIUS_Y));
+    let f2x = Fp::from_canonical(&FROBENIUS2_X);
+    for ((p, q), t) in pairs.iter().zip(ts.iter_mut()) {
+        // pi(Q) and -pi^2(Q).
+        let q1 = G2 {
+            x: q.x.conjugate().mul(&fx),
+            y: q.y.conjugate().mul(&fy),
+            infinity: false,
+        };
+        let q2 = G2 {
+            x: q.x.mul_by_fp(&f2x),
+            y: q.y,
+            infinity: false,
+        };
+        f = f.mul(&add_step(t, &q1, p)?);
+        f = f.mul(&add_step(t, &q2, p)?);
+    }
+    Ok(f)
+}
+
+fn final_exponentiation(f: &Fp12) -> Fp12 {
+    f.conjugate().mul(&f.invert()).pow(&FINAL_EXPONENT)
+}
+
+// Whether the product of the pairings of the pairs is one.
+pub(crate) fn pairing_check(pairs: &[(G1, G2)]) -> SgxResult<bool> {
+    Ok(final_exponentiation(&miller_loop(pairs)?).equals(&Fp12::one()))
+}
+
+///
+/// Checks that `e(a_1, b_1) * ... * e(a_k, b_k) = 1`, as the EIP-197 precompile does.
+///
+/// # Description
+///
+/// The check costs one Miller loop per pair and a single final exponentiation. An empty
+/// list passes.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// A point of G1 is not on the curve, or a point of G2 is not in G2.
+///
+pub fn rsgx_bn254_pairing_check(
+    pairs: &[([u8; BN254_G1_SIZE], [u8; BN254_G2_SIZE])],
+) -> SgxResult<bool> {
+    let pairs = pairs
+        .iter()
+        .map(|(a, b)| Ok((decode_g1(a)?, decode_g2(b)?)))
+        .collect::<SgxResult<Vec<_>>>()?;
+    pairing_check(&pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum::BigUint;
+    use crate::encoding::rsgx_hex_decode;
+
+    const P: &str = "30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd47";
+
+    fn limbs(hex: &str) -> Vec<u64> {
+        BigUint::from_bytes_be(&rsgx_hex_decode(hex).unwrap())
+            .limbs()
+            .to_vec()
+    }
+
+    fn g1_bytes(hex: &str) -> [u8; BN254_G1_SIZE] {
+        let mut bytes = [0_u8; BN254_G1_SIZE];
+        bytes.copy_from_slice(&rsgx_hex_decode(hex).unwrap());
+        bytes
+    }
+
+    fn g2_bytes(hex: &str) -> [u8; BN254_G2_SIZE] {
+        let mut bytes = [0_u8; BN254_G2_SIZE];
+        bytes.copy_from_slice(&rsgx_hex_decode(hex).unwrap());
+        bytes
+    }
+
+    fn same<F: Field>(a: &Affine<F>, b: &Affine<F>) -> bool {
+        a.infinity == b.infinity && a.x.equals(&b.x) && a.y.equals(&b.y)
+    }
+
+    fn xi() -> Fp2 {
+        Fp2::new(Fp::from_u64(9), Fp::from_u64(1))
+    }
+
+    // The generators of EIP-197.
+    const G1_GEN: &str = "0000000000000000000000000000000000000000000000000000000000000001\
+                          0000000000000000000000000000000000000000000000000000000000000002";
+    const G2_GEN: &str = "198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2\
+                          1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed\
+                          090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b\
+                          12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa";
+
+    // Multiples of the generators, from an independent implementation.
+    const G1_2: &str = "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3\
+                        15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4";
+    const G1_6: &str = "09f4ca411a3f52f4e0792fd9e792779856719215d3b32a762afe3d5b8c684af9\
+                        0d8ef3d795acd4b35d4366ab22e4ad335273aa59429e26929d0f64583474d9c8";
+    const G1_NEG_42: &str = "0988f35db6971fd77c8f9afdae27f7fb355577586de4c517537d17882f9b3f34\
+                             0ca94ecca181d76348490cac1313833536ccc1e2d27d318766f3b0ea9d304b44";
+    const G2_2: &str = "203e205db4f19b37b60121b83a7333706db86431c6d835849957ed8c3928ad79\
+                        27dc7234fd11d3e8c36c59277c3e6f149d5cd3cfa9a62aee49f8130962b4b3b9\
+                        195e8aa5b7827463722b8c153931579d3505566b4edf48d498e185f0509de152\
+                        04bb53b8977e5f92a0bc372742c4830944a59b4fe6b1c0466e2a6dad122b5d2e";
+    const G2_7: &str = "2903ba015a9abde26a5d081e84551e63be0fd4516e46ee6d593edeba46362455\
+                        224bdc5d4327fcf8ed702e01de1c2f1657a253ba75e32a89c390142aaa28b308\
+                        03c8b7cda6b2dedb7aeeaf5fda464ad17036bea1c4e6f7adbaed1ebe0335e0d8\
+                        1d92fff52a265017eeccb372e37d7a7bd431800eca28dfd82e21e8054114233f";
+
+    #[test]
+    fn constants() {
+        assert!(fp2(&TWIST_B)
+            .mul(&xi())
+            .equals(&Fp2::new(Fp::from_u64(3), Fp::zero())));
+        // (p - 1) / 3, (p - 1) / 2 and (p^2 - 1) / 3.
+        let third = limbs("10216f7ba065e00de81ac1e7808072c9dd2b2385cd7b438469602eb24829a9c2");
+        let half = limbs("183227397098d014dc2822db40c0ac2ecbc0b548b438e5469e10460b6c3e7ea3");
+        let third2 = limbs(
+            "030c96e8276995341dde2529566d9b5ee5592c705cbd1cacb7a4a8c966ece684\
+             56cd8a31d35b6b9818c55d8979dcee498cab57b9adf8eb00691c1d8b62747890",
+        );
+        assert!(fp2(&FROBENIUS_X).equals(&xi().pow(&third)));
+        assert!(fp2(&FROBENIUS_Y).equals(&xi().pow(&half)));
+        let f2x = xi().pow(&third2);
+        assert!(f2x.equals(&Fp2::new(Fp::from_canonical(&FROBENIUS2_X), Fp::zero())));
+
+        // 6x + 2, and r * FINAL_EXPONENT = p^6 + 1.
+        assert_eq!(ATE_LOOP_COUNT, 6 * 4_965_661_367_192_848_881 + 2);
+        assert_eq!(128 - ATE_LOOP_COUNT.leading_zeros() as usize, ATE_LOOP_BITS);
+        let p = BigUint::from_bytes_be(&rsgx_hex_decode(P).unwrap());
+        let p2 = p.mul(&p);
+        let p6_plus_1 = p2.mul(&p2).mul(&p2).add(&BigUint::from_u64(1));
+        let product = BigUint::from_limbs(&FINAL_EXPONENT).mul(&BigUint::from_limbs(&ORDER));
+        assert!(product.ct_eq(&p6_plus_1));
+    }
+
+    #[test]
+    fn point_known_answers() {
+        let g1 = decode_g1(&g1_bytes(G1_GEN)).unwrap();
+        let g2 = decode_g2(&g2_bytes(G2_GEN)).unwrap();
+        let two = g1.to_jacobian().double().to_affine();
+        assert!(same(&two, &decode_g1(&g1_bytes(G1_2)).unwrap()));
+        let six = g1.to_jacobian().mul(&[6, 0, 0, 0]).to_affine();
+        assert!(same(&six, &decode_g1(&g1_bytes(G1_6)).unwrap()));
+        let neg_42 = g1.to_jacobian().mul(&[42, 0, 0, 0]).to_affine().neg();
+        assert!(same(&neg_42, &decode_g1(&g1_bytes(G1_NEG_42)).unwrap()));
+        let two = g2.to_jacobian().add(&g2.to_jacobian()).to_affine();
+        assert!(same(&two, &decode_g2(&g2_bytes(G2_2)).unwrap()));
+        let seven = g2.to_jacobian().mul(&[7, 0, 0, 0]).to_affine();
+        assert!(same(&seven, &decode_g2(&g2_bytes(G2_7)).unwrap()));
+
+        assert!(g1.to_jacobian().mul(&ORDER).is_identity());
+        assert!(g1.to_jacobian().add(&g1.neg().to_jacobian()).is_identity());
+        assert!(decode_g1(&[0_u8; BN254_G1_SIZE]).unwrap().is_identity());
+        assert!(decode_g2(&[0_u8; BN254_G2_SIZE]).unwrap().is_identity());
+    }
+
+    #[test]
+    fn rejects_invalid_points() {
+        let mut off_curve = g1_bytes(G1_GEN);
+        off_curve[63] = 3;
+        assert!(decode_g1(&off_curve).is_err());
+        let mut unreduced = g1_bytes(G1_GEN);
+        unreduced[..32].copy_from_slice(&rsgx_hex_decode(P).unwrap());
+        assert!(decode_g1(&unreduced).is_err());
+        let mut off_twist = g2_bytes(G2_GEN);
+        off_twist[127] ^= 1;
+        assert!(decode_g2(&off_twist).is_err());
+
+        let order =
+            rsgx_hex_decode("30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001")
+                .unwrap();
+        let mut scalar = [0_u8; 32];
+        scalar.copy_from_slice(&order);
+        assert!(decode_scalar(&scalar).is_err());
+        scalar[31] = 0;
+        assert_eq!(decode_scalar(&scalar).unwrap()[0], 0x43e1_f593_f000_0000);
+    }
+
+    #[test]
+    fn pairing_known_answers() {
+        let (g1, g2) = (g1_bytes(G1_GEN), g2_bytes(G2_GEN));
+        // e(6 * G1, 7 * G2) * e(-42 * G1, G2) = 1.
+        let pairs = [(g1_bytes(G1_6), g2_bytes(G2_7)), (g1_bytes(G1_NEG_42), g2)];
+        assert_eq!(rsgx_bn254_pairing_check(&pairs), Ok(true));
+        let pairs = [(g1_bytes(G1_6), g2_bytes(G2_2)), (g1_bytes(G1_NEG_42), g2)];
+        assert_eq!(rsgx_bn254_pairing_check(&pairs), Ok(false));
+        // e(2 * G1, G2) = e(G1, 2 * G2).
+        let mut neg_g1 = g1;
+        neg_g1[32..].copy_from_slice(
+            &rsgx_hex_decode("30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd45")
+                .unwrap(),
+        );
+        let pairs = [(g1_bytes(G1_2), g2), (neg_g1, g2_bytes(G2_2))];
+        assert_eq!(rsgx_bn254_pairing_check(&pairs), Ok(true));
+
+        // Non-degenerate, of order r, and trivial on the point at infinity.
+        assert_eq!(rsgx_bn254_pairing_check(&[(g1, g2)]), Ok(false));
+        let gt = final_exponentiation(
+            &miller_loop(&[(decode_g1(&g1).unwrap(), decode_g2(&g2).unwrap())]).unwrap(),
+        );
+        assert!(gt.pow(&ORDER).equals(&Fp12::one()));
+        assert_eq!(rsgx_bn254_pairing_check(&[([0_u8; 64], g2)]), Ok(true));
+        assert_eq!(rsgx_bn254_pairing_check(&[]), Ok(true));
+    }
+}
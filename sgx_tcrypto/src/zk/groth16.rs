@@ -0,0 +1,276 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Groth16 proof verification over BN254.
+//!
+//! A proof `(A, B, C)` for public inputs `a_1, ..., a_l` is valid when
+//!
+//! `e(A, B) = e(alpha, beta) * e(L, gamma) * e(C, delta)`, with
+//! `L = IC_0 + a_1 * IC_1 + ... + a_l * IC_l`,
+//!
+//! which is checked as one product of four pairings. Points use the encodings of the
+//! Ethereum precompiles, so keys and proofs exported for an Ethereum verifier (by snarkjs
+//! or gnark, for instance) are read as they are.
+
+use super::bn254::{
+    decode_g1, decode_g2, decode_scalar, pairing_check, BN254_G1_SIZE, BN254_G2_SIZE, G1, G2,
+};
+use alloc::vec::Vec;
+use sgx_types::*;
+
+pub const GROTH16_PROOF_SIZE: usize = 2 * BN254_G1_SIZE + BN254_G2_SIZE;
+
+///
+/// A Groth16 proof.
+///
+#[derive(Clone, Copy)]
+pub struct Groth16Proof {
+    a: G1,
+    b: G2,
+    c: G1,
+}
+
+impl Groth16Proof {
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `a` or `c` is not on the curve, or `b` is not in G2.
+    ///
+    pub fn new(
+        a: &[u8; BN254_G1_SIZE],
+        b: &[u8; BN254_G2_SIZE],
+        c: &[u8; BN254_G1_SIZE],
+    ) -> SgxResult<Groth16Proof> {
+        Ok(Groth16Proof {
+            a: decode_g1(a)?,
+            b: decode_g2(b)?,
+            c: decode_g1(c)?,
+        })
+    }
+
+    ///
+    /// Reads `A || B || C`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// A point is invalid.
+    ///
+    pub fn from_bytes(bytes: &[u8; GROTH16_PROOF_SIZE]) -> SgxResult<Groth16Proof> {
+        let mut a = [0_u8; BN254_G1_SIZE];
+        let mut b = [0_u8; BN254_G2_SIZE];
+        let mut c = [0_u8; BN254_G1_SIZE];
+        a.copy_from_slice(&bytes[..BN254_G1_SIZE]);
+        b.copy_from_slice(&bytes[BN254_G1_SIZE..BN254_G1_SIZE + BN254_G2_SIZE]);
+        c.copy_from_slice(&bytes[BN254_G1_SIZE + BN254_G2_SIZE..]);
+        Groth16Proof::new(&a, &b, &c)
+    }
+}
+
+///
+/// A Groth16 verifying key.
+///
+#[derive(Clone)]
+pub struct Groth16VerifyingKey {
+    alpha: G1,
+    beta: G2,
+    gamma: G2,
+    delta: G2,
+    ic: Vec<G1>,
+}
+
+impl Groth16VerifyingKey {
+    ///
+    /// Reads a key with one point `ic` more than the circuit has public inputs.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// A point is invalid, or `ic` is empty.
+    ///
+    pub fn new(
+        alpha: &[u8; BN254_G1_SIZE],
+        beta: &[u8; BN254_G2_SIZE],
+        gamma: &[u8; BN254_G2_SIZE],
+        delta: &[u8; BN254_G2_SIZE],
+        ic: &[[u8; BN254_G1_SIZE]],
+    ) -> SgxResult<Groth16VerifyingKey> {
+        if ic.is_empty() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        Ok(Groth16VerifyingKey {
+            alpha: decode_g1(alpha)?,
+            beta: decode_g2(beta)?,
+            gamma: decode_g2(gamma)?,
+            delta: decode_g2(delta)?,
+            ic: ic.iter().map(decode_g1).collect::<SgxResult<Vec<G1>>>()?,
+        })
+    }
+
+    ///
+    /// The number of public inputs of the circuit.
+    ///
+    pub fn input_count(&self) -> usize {
+        self.ic.len() - 1
+    }
+
+    ///
+    /// Verifies `proof` for the public inputs, each a 32-byte big-endian field element.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The number of inputs does not match the key, or an input is not below the group
+    /// order.
+    ///
+    pub fn verify(&self, proof: &Groth16Proof, inputs: &[[u8; 32]]) -> SgxResult<bool> {
+        if inputs.len() != self.input_count() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let mut l = self.ic[0].to_jacobian();
+        for (input, ic) in inputs.iter().zip(self.ic[1..].iter()) {
+            l = l.add(&ic.to_jacobian().mul(&decode_scalar(input)?));
+        }
+        pairing_check(&[
+            (proof.a, proof.b),
+            (self.alpha.neg(), self.beta),
+            (l.to_affine().neg(), self.gamma),
+            (proof.c.neg(), self.delta),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::rsgx_hex_decode;
+
+    fn g1(hex: &str) -> [u8; BN254_G1_SIZE] {
+        let mut bytes = [0_u8; BN254_G1_SIZE];
+        bytes.copy_from_slice(&rsgx_hex_decode(hex).unwrap());
+        bytes
+    }
+
+    fn g2(hex: &str) -> [u8; BN254_G2_SIZE] {
+        let mut bytes = [0_u8; BN254_G2_SIZE];
+        bytes.copy_from_slice(&rsgx_hex_decode(hex).unwrap());
+        bytes
+    }
+
+    fn input(x: u8) -> [u8; 32] {
+        let mut bytes = [0_u8; 32];
+        bytes[31] = x;
+        bytes
+    }
+
+    // A key and proof for one public input a_1 = 5, from an independent implementation:
+    // alpha, beta, gamma, delta, IC_0, IC_1, A and B are the multiples 11, 13, 17, 19, 23,
+    // 29, 31 and 37 of the generators, and C is chosen to satisfy the equation.
+    fn key() -> Groth16VerifyingKey {
+        Groth16VerifyingKey::new(
+            &g1(
+                "2a14705537b009189da8808651eecdb82482477fe92ac12ca8b71f80fc3d49ef\
+                 2df7ee7f243ea8b38e1ddf14029258877a618c779fd4717db6177e19ea67ec38",
+            ),
+            &g2(
+                "009edaf0698a8c56f51139588acc094cee3c37d427bb6d2eab830aae529097d1\
+                 23ad66f3a7cca9dc75049635faebd124316244b91de5fb2764cd151572a905f7\
+                 2700e8a29b7bb45f3022a18a07bdc66d0254559e17cce64e3b4ad21578fcf410\
+                 1ad4f87d3b4375a39988ac099b042b1e7c0c715678e4c2bea8905f607cf950f8",
+            ),
+            &g2(
+                "227071bba5ff3b47ed8b504bb5b215bc701d7a3259b933bff1a4164eae499c2c\
+                 0c51a367b61d3119677b29739ddccbb78002b5558d8f49ff16e299c1b41f8098\
+                 08bb188b2a6187bb1e87834c85a6a917763d65b98febf2c45ea339dd77fac415\
+                 18fd2fd13be8494c39e8a91325d1ef3ba7d1a205d10788e38bc9e09d9be87769",
+            ),
+            &g2(
+                "25407be35f18c6594174374841311466c0e66ff003762448c06bca4fa5e9c54e\
+                 15cbba9ab73bc73d0ba4ad132a15cb0c73107a9c19b040c4c73d89f6bf75404d\
+                 1edef86c1a42fa85ab6ae8d268a7e9b46890b2130dd83b91c86c504cf1f93fbf\
+                 2c750c045112e4ab07f18b12475309cebdcb726bda1ca9948bacd498a28cf411",
+            ),
+            &[
+                g1(
+                    "1e28260f0ee971dec1e84cf81ff2776ad314d2cfb9ef81d4c970620c29b811f1\
+                    28fc8a72d4ff12654c3c39dab54eaef9638d28de738959779fcd3e7ac918b396",
+                ),
+                g1(
+                    "1605ffc1ea2e1aef15d774d3207176420c5cc454b19b55558562b0c7ddf00a7d\
+                    0cf605873faa8028df38ec2d0800d5ddc67f1776338d675491fe87f6bb7354b3",
+                ),
+            ],
+        )
+        .unwrap()
+    }
+
+    const A: &str = "14b4fa251277a6f4cbbfe379a152a976641f58a4a2bffd3b677ea093bdad853c\
+                     28ce094a6d16280abcf8d84efa062c85511819dd87d8da255885ce0580ebee36";
+    const B: &str = "0476be093a6d2b4bbf907172049874af11e1b6267606e00804d3ff0037ec57fd\
+                     3010c68cb50161b7d1d96bb71edfec9880171954e56871abf3d93cc94d745fa1\
+                     14c059d74e5b6c4ec14ae5864ebe23a71781d86c29fb8fb6cce94f70d3de7a21\
+                     01b33461f39d9e887dbb100f170a2345dde3c07e256d1dfa2b657ba5cd030427";
+    const C: &str = "114b1201ff08bb5d27c8120d977364bc8a321cb3d4c0d2289862283c8319dcb1\
+                     2e071e6c81efbd57d1c9251863065b24919c140a4404e99c5ff1d66ac9e34b5a";
+
+    #[test]
+    fn verifies_known_proof() {
+        let key = key();
+        assert_eq!(key.input_count(), 1);
+        let proof = Groth16Proof::new(&g1(A), &g2(B), &g1(C)).unwrap();
+        assert_eq!(key.verify(&proof, &[input(5)]), Ok(true));
+
+        let mut bytes = [0_u8; GROTH16_PROOF_SIZE];
+        bytes[..64].copy_from_slice(&g1(A));
+        bytes[64..192].copy_from_slice(&g2(B));
+        bytes[192..].copy_from_slice(&g1(C));
+        let proof = Groth16Proof::from_bytes(&bytes).unwrap();
+        assert_eq!(key.verify(&proof, &[input(5)]), Ok(true));
+    }
+
+    #[test]
+    fn rejects_bad_proofs() {
+        let key = key();
+        let proof = Groth16Proof::new(&g1(A), &g2(B), &g1(C)).unwrap();
+        assert_eq!(key.verify(&proof, &[input(6)]), Ok(false));
+        let swapped = Groth16Proof::new(&g1(C), &g2(B), &g1(A)).unwrap();
+        assert_eq!(key.verify(&swapped, &[input(5)]), Ok(false));
+
+        assert_eq!(
+            key.verify(&proof, &[]),
+            Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+        );
+        assert_eq!(
+            key.verify(&proof, &[input(5), input(5)]),
+            Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+        );
+        assert_eq!(
+            key.verify(&proof, &[[0xff_u8; 32]]),
+            Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+        );
+
+        let mut off_curve = g1(A);
+        off_curve[63] ^= 1;
+        assert!(Groth16Proof::new(&off_curve, &g2(B), &g1(C)).is_err());
+        assert!(Groth16VerifyingKey::new(&g1(A), &g2(B), &g2(B), &g2(B), &[]).is_err());
+    }
+}
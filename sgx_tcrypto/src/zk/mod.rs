@@ -0,0 +1,30 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Zero-knowledge proof verification
+//!
+//! Verifiers for proofs that clients attach to their claims, so that an enclave can check
+//! a claim before acting on it.
+//!
+//! * `bn254`: pairing checks on BN254 (alt_bn128). A pairing check is also the last step
+//!   of verifiers built on KZG polynomial commitments, such as PLONK's.
+//! * `groth16`: Groth16 proof verification over BN254.
+//!
+//! Proofs, keys and inputs are public, and verification runs in variable time.
+
+pub mod bn254;
+pub mod groth16;
@@ -30,6 +30,11 @@
 #![allow(dead_code)]
 #![allow(clippy::missing_safety_doc)]
 
+#[cfg(all(not(target_env = "sgx"), feature = "std"))]
+extern crate sgx_tstd as std;
+#[cfg(all(target_env = "sgx", feature = "std"))]
+extern crate std;
+
 #[macro_use]
 extern crate alloc;
 
@@ -41,4 +46,7 @@ extern crate sgx_types;
 mod dh;
 pub use self::dh::*;
 
+mod session;
+pub use self::session::*;
+
 mod ecp;
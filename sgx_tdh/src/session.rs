@@ -0,0 +1,578 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Typed local attestation sessions
+//!
+//! `SgxDhSession` drives the responder or initiator side of the DH exchange with typed
+//! messages, checks the peer enclave against an `SgxDhPeerPolicy`, and yields an
+//! `SgxDhEstablished` session that can be turned into an encrypted `SgxDhChannel`.
+//!
+use crate::dh::*;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::mem;
+use core::ptr;
+use core::slice;
+use sgx_tcrypto::*;
+use sgx_types::*;
+
+const MSG1_TAG: u8 = 1;
+const MSG2_TAG: u8 = 2;
+const MSG3_TAG: u8 = 3;
+
+const I2R_KEY_LABEL: &[u8] = b"SGX-DH-CHANNEL-I2R";
+const R2I_KEY_LABEL: &[u8] = b"SGX-DH-CHANNEL-R2I";
+
+/// The largest plaintext carried in a single channel frame.
+pub const SGX_DH_CHANNEL_MAX_FRAME: usize = 64 * 1024;
+
+///
+/// An identity policy for the peer of a DH session.
+///
+/// MRENCLAVE and MRSIGNER are checked against allowlists. An empty allowlist accepts any
+/// value, but at least one of the two allowlists must be non-empty.
+///
+#[derive(Clone, Default)]
+pub struct SgxDhPeerPolicy {
+    mr_enclaves: Vec<sgx_measurement_t>,
+    mr_signers: Vec<sgx_measurement_t>,
+    isv_prod_id: Option<sgx_prod_id_t>,
+    min_isv_svn: sgx_isv_svn_t,
+    allow_debug: bool,
+}
+
+impl SgxDhPeerPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allow_mr_enclave(mut self, mr_enclave: sgx_measurement_t) -> Self {
+        self.mr_enclaves.push(mr_enclave);
+        self
+    }
+
+    pub fn allow_mr_signer(mut self, mr_signer: sgx_measurement_t) -> Self {
+        self.mr_signers.push(mr_signer);
+        self
+    }
+
+    pub fn isv_prod_id(mut self, isv_prod_id: sgx_prod_id_t) -> Self {
+        self.isv_prod_id = Some(isv_prod_id);
+        self
+    }
+
+    pub fn min_isv_svn(mut self, isv_svn: sgx_isv_svn_t) -> Self {
+        self.min_isv_svn = isv_svn;
+        self
+    }
+
+    pub fn allow_debug(mut self, allow: bool) -> Self {
+        self.allow_debug = allow;
+        self
+    }
+
+    ///
+    /// Checks the identity of a DH session peer against the policy.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// Both allowlists are empty.
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// MRENCLAVE, MRSIGNER or the product ID is not allowed.
+    ///
+    /// **SGX_ERROR_INVALID_ISVSVN**
+    ///
+    /// The ISVSVN is below the minimum.
+    ///
+    /// **SGX_ERROR_INVALID_ATTRIBUTE**
+    ///
+    /// The peer is a debug enclave and debug is not allowed.
+    ///
+    pub fn verify(&self, identity: &sgx_dh_session_enclave_identity_t) -> SgxError {
+        if self.mr_enclaves.is_empty() && self.mr_signers.is_empty() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        if !self.allow_debug && (identity.attributes.flags & SGX_FLAGS_DEBUG) != 0 {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_ATTRIBUTE);
+        }
+
+        let allowed = |list: &[sgx_measurement_t], m: &sgx_measurement_t| {
+            list.is_empty() || list.iter().any(|a| a.m == m.m)
+        };
+        if !allowed(&self.mr_enclaves, &identity.mr_enclave)
+            || !allowed(&self.mr_signers, &identity.mr_signer)
+            || self
+                .isv_prod_id
+                .map_or(false, |id| id != identity.isv_prod_id)
+        {
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+        if identity.isv_svn < self.min_isv_svn {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_ISVSVN);
+        }
+        Ok(())
+    }
+}
+
+///
+/// A DH session message with its wire encoding.
+///
+/// The encoding is a one-byte tag followed by the raw `sgx_dh_msg1_t`, `sgx_dh_msg2_t` or
+/// `sgx_dh_msg3_t` (including its additional properties).
+///
+#[derive(Clone)]
+pub enum SgxDhMessage {
+    Msg1(SgxDhMsg1),
+    Msg2(SgxDhMsg2),
+    Msg3(SgxDhMsg3),
+}
+
+impl SgxDhMessage {
+    pub fn to_bytes(&self) -> SgxResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        match self {
+            SgxDhMessage::Msg1(msg1) => {
+                bytes.push(MSG1_TAG);
+                bytes.extend_from_slice(unsafe { raw_bytes(msg1) });
+            }
+            SgxDhMessage::Msg2(msg2) => {
+                bytes.push(MSG2_TAG);
+                bytes.extend_from_slice(unsafe { raw_bytes(msg2) });
+            }
+            SgxDhMessage::Msg3(msg3) => {
+                let len = msg3.calc_raw_sealed_data_size();
+                if len == u32::MAX {
+                    return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+                }
+                bytes.resize(1 + len as usize, 0);
+                bytes[0] = MSG3_TAG;
+                // sgx_dh_msg3_t is packed, so any byte offset is suitably aligned.
+                let p = bytes[1..].as_mut_ptr() as *mut sgx_dh_msg3_t;
+                unsafe { msg3.to_raw_dh_msg3_t(p, len) }
+                    .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+            }
+        }
+        Ok(bytes)
+    }
+
+    ///
+    /// Decodes a message. The length must match the message exactly.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The tag is unknown or the length is wrong.
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> SgxResult<SgxDhMessage> {
+        let (&tag, body) = bytes
+            .split_first()
+            .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+        match tag {
+            MSG1_TAG => unsafe { from_raw_bytes(body) }.map(SgxDhMessage::Msg1),
+            MSG2_TAG => unsafe { from_raw_bytes(body) }.map(SgxDhMessage::Msg2),
+            MSG3_TAG => {
+                if body.len() < mem::size_of::<sgx_dh_msg3_t>() || body.len() > u32::MAX as usize {
+                    return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+                }
+                let mut raw = body.to_vec();
+                let len = raw.len() as u32;
+                let msg3 = unsafe {
+                    SgxDhMsg3::from_raw_dh_msg3_t(raw.as_mut_ptr() as *mut sgx_dh_msg3_t, len)
+                }
+                .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+                if msg3.calc_raw_sealed_data_size() != len {
+                    return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+                }
+                Ok(SgxDhMessage::Msg3(msg3))
+            }
+            _ => Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
+        }
+    }
+}
+
+unsafe fn raw_bytes<T: Copy>(value: &T) -> &[u8] {
+    slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>())
+}
+
+unsafe fn from_raw_bytes<T: Copy>(bytes: &[u8]) -> SgxResult<T> {
+    if bytes.len() != mem::size_of::<T>() {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    Ok(ptr::read_unaligned(bytes.as_ptr() as *const T))
+}
+
+enum SgxDhSessionState {
+    Responder(SgxDhResponder),
+    Initiator(SgxDhInitiator),
+    Done,
+}
+
+///
+/// The result of feeding a message to an `SgxDhSession`.
+///
+pub enum SgxDhStep {
+    /// Send the message to the peer and wait for its reply.
+    Send(SgxDhMessage),
+    /// Send the message to the peer; the session is established on this side.
+    SendAndEstablished(SgxDhMessage, Box<SgxDhEstablished>),
+    /// The session is established.
+    Established(Box<SgxDhEstablished>),
+}
+
+///
+/// A local attestation session driven by typed messages.
+///
+/// The responder calls `start` to produce Msg1 and then `step` with Msg2, which yields
+/// Msg3 and the established session. The initiator calls `step` with Msg1, which yields
+/// Msg2, and then with Msg3. The peer identity is checked against the policy before the
+/// session is reported as established; the responder does not release Msg3 to a peer
+/// that fails the policy.
+///
+/// Any error, including an unexpected message, ends the session.
+///
+pub struct SgxDhSession {
+    state: SgxDhSessionState,
+    role: sgx_dh_session_role_t,
+    policy: SgxDhPeerPolicy,
+}
+
+impl SgxDhSession {
+    pub fn responder(policy: SgxDhPeerPolicy) -> SgxDhSession {
+        SgxDhSession {
+            state: SgxDhSessionState::Responder(SgxDhResponder::init_session()),
+            role: sgx_dh_session_role_t::SGX_DH_SESSION_RESPONDER,
+            policy,
+        }
+    }
+
+    pub fn initiator(policy: SgxDhPeerPolicy) -> SgxDhSession {
+        SgxDhSession {
+            state: SgxDhSessionState::Initiator(SgxDhInitiator::init_session()),
+            role: sgx_dh_session_role_t::SGX_DH_SESSION_INITIATOR,
+            policy,
+        }
+    }
+
+    pub fn role(&self) -> sgx_dh_session_role_t {
+        self.role
+    }
+
+    ///
+    /// Generates Msg1. Only the responder starts a session.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_STATE**
+    ///
+    /// The session is not a responder, or it has already started.
+    ///
+    pub fn start(&mut self) -> SgxResult<SgxDhMessage> {
+        let result = match self.state {
+            SgxDhSessionState::Responder(ref mut responder) => {
+                let mut msg1 = SgxDhMsg1::default();
+                responder
+                    .gen_msg1(&mut msg1)
+                    .map(|_| SgxDhMessage::Msg1(msg1))
+            }
+            _ => Err(sgx_status_t::SGX_ERROR_INVALID_STATE),
+        };
+        if result.is_err() {
+            self.state = SgxDhSessionState::Done;
+        }
+        result
+    }
+
+    ///
+    /// Processes a message from the peer.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_STATE**
+    ///
+    /// The message is not the one expected next.
+    ///
+    /// Errors from the DH protocol functions and from `SgxDhPeerPolicy::verify` are
+    /// returned unchanged.
+    ///
+    pub fn step(&mut self, msg: &SgxDhMessage) -> SgxResult<SgxDhStep> {
+        let result = self.step_inner(msg);
+        if !matches!(result, Ok(SgxDhStep::Send(_))) {
+            self.state = SgxDhSessionState::Done;
+        }
+        result
+    }
+
+    fn step_inner(&mut self, msg: &SgxDhMessage) -> SgxResult<SgxDhStep> {
+        match (&mut self.state, msg) {
+            (SgxDhSessionState::Responder(responder), SgxDhMessage::Msg2(msg2)) => {
+                let mut msg3 = SgxDhMsg3::new();
+                let mut established = Box::new(SgxDhEstablished::new(self.role));
+                responder.proc_msg2(
+                    msg2,
+                    &mut msg3,
+                    &mut established.aek,
+                    &mut established.peer,
+                )?;
+                self.policy.verify(&established.peer)?;
+                Ok(SgxDhStep::SendAndEstablished(
+                    SgxDhMessage::Msg3(msg3),
+                    established,
+                ))
+            }
+            (SgxDhSessionState::Initiator(initiator), SgxDhMessage::Msg1(msg1)) => {
+                let mut msg2 = SgxDhMsg2::default();
+                initiator.proc_msg1(msg1, &mut msg2)?;
+                Ok(SgxDhStep::Send(SgxDhMessage::Msg2(msg2)))
+            }
+            (SgxDhSessionState::Initiator(initiator), SgxDhMessage::Msg3(msg3)) => {
+                let mut established = Box::new(SgxDhEstablished::new(self.role));
+                initiator.proc_msg3(msg3, &mut established.aek, &mut established.peer)?;
+                self.policy.verify(&established.peer)?;
+                Ok(SgxDhStep::Established(established))
+            }
+            _ => Err(sgx_status_t::SGX_ERROR_INVALID_STATE),
+        }
+    }
+}
+
+///
+/// An established DH session: the AEK and the verified peer identity.
+///
+/// The AEK is wiped when the value is dropped.
+///
+pub struct SgxDhEstablished {
+    aek: sgx_key_128bit_t,
+    peer: sgx_dh_session_enclave_identity_t,
+    role: sgx_dh_session_role_t,
+}
+
+impl SgxDhEstablished {
+    fn new(role: sgx_dh_session_role_t) -> SgxDhEstablished {
+        SgxDhEstablished {
+            aek: sgx_key_128bit_t::default(),
+            peer: sgx_dh_session_enclave_identity_t::default(),
+            role,
+        }
+    }
+
+    pub fn aek(&self) -> &sgx_key_128bit_t {
+        &self.aek
+    }
+
+    pub fn peer_identity(&self) -> &sgx_dh_session_enclave_identity_t {
+        &self.peer
+    }
+
+    pub fn role(&self) -> sgx_dh_session_role_t {
+        self.role
+    }
+
+    ///
+    /// Creates an encrypted channel over `transport`.
+    ///
+    /// Each direction uses its own key, derived from the AEK with AES-CMAC.
+    ///
+    pub fn into_channel<T: SgxDhTransport>(self, transport: T) -> SgxResult<SgxDhChannel<T>> {
+        let i2r = rsgx_rijndael128_cmac_slice(&self.aek, I2R_KEY_LABEL)?;
+        let r2i = rsgx_rijndael128_cmac_slice(&self.aek, R2I_KEY_LABEL)?;
+        let (send_key, recv_key) = match self.role {
+            sgx_dh_session_role_t::SGX_DH_SESSION_INITIATOR => (i2r, r2i),
+            sgx_dh_session_role_t::SGX_DH_SESSION_RESPONDER => (r2i, i2r),
+        };
+        Ok(SgxDhChannel {
+            transport,
+            send_key,
+            recv_key,
+            send_seq: 0,
+            recv_seq: 0,
+            peer: self.peer,
+            #[cfg(feature = "std")]
+            pending: Vec::new(),
+            #[cfg(feature = "std")]
+            pending_pos: 0,
+        })
+    }
+}
+
+impl Drop for SgxDhEstablished {
+    fn drop(&mut self) {
+        wipe(&mut self.aek);
+    }
+}
+
+///
+/// A frame-oriented transport for `SgxDhChannel`, usually backed by OCALLs.
+///
+/// Frames must be delivered whole and in order.
+///
+pub trait SgxDhTransport {
+    fn send(&mut self, frame: &[u8]) -> SgxError;
+    fn recv(&mut self) -> SgxResult<Vec<u8>>;
+}
+
+///
+/// An AES-GCM protected channel keyed by a DH session.
+///
+/// Each frame is the ciphertext followed by the 16-byte tag. The nonce is a per-direction
+/// frame counter, so frames that are dropped, replayed or reordered fail to decrypt.
+///
+/// With the `std` feature the channel implements `Read` and `Write`. Each `write` sends at
+/// most `SGX_DH_CHANNEL_MAX_FRAME` bytes as one frame, and an empty frame from the peer
+/// reads as end of stream.
+///
+pub struct SgxDhChannel<T: SgxDhTransport> {
+    transport: T,
+    send_key: sgx_aes_gcm_128bit_key_t,
+    recv_key: sgx_aes_gcm_128bit_key_t,
+    send_seq: u64,
+    recv_seq: u64,
+    peer: sgx_dh_session_enclave_identity_t,
+    #[cfg(feature = "std")]
+    pending: Vec<u8>,
+    #[cfg(feature = "std")]
+    pending_pos: usize,
+}
+
+impl<T: SgxDhTransport> SgxDhChannel<T> {
+    pub fn peer_identity(&self) -> &sgx_dh_session_enclave_identity_t {
+        &self.peer
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    ///
+    /// Encrypts `data` into one frame and sends it.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `data` is longer than `SGX_DH_CHANNEL_MAX_FRAME`.
+    ///
+    /// **SGX_ERROR_INVALID_STATE**
+    ///
+    /// The frame counter is exhausted.
+    ///
+    pub fn send(&mut self, data: &[u8]) -> SgxError {
+        if data.len() > SGX_DH_CHANNEL_MAX_FRAME {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let iv = next_iv(&mut self.send_seq)?;
+        let mut frame = vec![0_u8; data.len() + SGX_AESGCM_MAC_SIZE];
+        let (ciphertext, tag) = frame.split_at_mut(data.len());
+        let mut mac = sgx_aes_gcm_128bit_tag_t::default();
+        rsgx_rijndael128GCM_encrypt(&self.send_key, data, &iv, &[], ciphertext, &mut mac)?;
+        tag.copy_from_slice(&mac);
+        self.transport.send(&frame)
+    }
+
+    ///
+    /// Receives and decrypts one frame.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The frame was modified, replayed, reordered or sent under a different key.
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The frame is too short or too long.
+    ///
+    pub fn recv(&mut self) -> SgxResult<Vec<u8>> {
+        let frame = self.transport.recv()?;
+        if frame.len() < SGX_AESGCM_MAC_SIZE
+            || frame.len() > SGX_DH_CHANNEL_MAX_FRAME + SGX_AESGCM_MAC_SIZE
+        {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let iv = next_iv(&mut self.recv_seq)?;
+        let (ciphertext, tag) = frame.split_at(frame.len() - SGX_AESGCM_MAC_SIZE);
+        let mut mac = sgx_aes_gcm_128bit_tag_t::default();
+        mac.copy_from_slice(tag);
+        let mut data = vec![0_u8; ciphertext.len()];
+        rsgx_rijndael128GCM_decrypt(&self.recv_key, ciphertext, &iv, &[], &mac, &mut data)?;
+        Ok(data)
+    }
+}
+
+impl<T: SgxDhTransport> Drop for SgxDhChannel<T> {
+    fn drop(&mut self) {
+        wipe(&mut self.send_key);
+        wipe(&mut self.recv_key);
+    }
+}
+
+fn next_iv(seq: &mut u64) -> SgxResult<[u8; SGX_AESGCM_IV_SIZE]> {
+    let mut iv = [0_u8; SGX_AESGCM_IV_SIZE];
+    iv[4..].copy_from_slice(&seq.to_le_bytes());
+    *seq = seq
+        .checked_add(1)
+        .ok_or(sgx_status_t::SGX_ERROR_INVALID_STATE)?;
+    Ok(iv)
+}
+
+fn wipe(key: &mut [u8; 16]) {
+    for b in key.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
+
+#[cfg(feature = "std")]
+mod io_impl {
+    use super::*;
+    use std::io::{self, Read, Write};
+
+    impl<T: SgxDhTransport> Read for SgxDhChannel<T> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            if self.pending_pos == self.pending.len() {
+                self.pending = self.recv().map_err(io::Error::from_sgx_error)?;
+                self.pending_pos = 0;
+            }
+            let available = &self.pending[self.pending_pos..];
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            self.pending_pos += n;
+            Ok(n)
+        }
+    }
+
+    impl<T: SgxDhTransport> Write for SgxDhChannel<T> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            let n = buf.len().min(SGX_DH_CHANNEL_MAX_FRAME);
+            self.send(&buf[..n]).map_err(io::Error::from_sgx_error)?;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
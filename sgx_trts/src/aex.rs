@@ -0,0 +1,221 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # AEX-Notify
+//!
+//! When AEX-Notify is enabled for the current thread, every Asynchronous Enclave Exit
+//! (interrupt, exception, or page fault delivered outside of the enclave) is reported back
+//! to the enclave on resume, and the registered AEX handlers are called before execution
+//! continues. Handlers can be used to apply mitigations, such as prefetching the working set
+//! after an exit, or to profile exits.
+//!
+//! Handlers are registered per thread, and AEX-Notify must be enabled for the SSA frame of
+//! the thread with rsgx_set_ssa_aexnotify. The enclave must be built with AEX-Notify enabled
+//! in its configuration, otherwise no handler is ever called.
+//!
+//! An `AexStats` collector counts exits per code region. A single-stepping attack, for example
+//! through SGX-Step, causes an exit after almost every instruction, so a region whose count
+//! grows much faster than the timer interrupt rate is a strong indication of an attack.
+
+use alloc::boxed::Box;
+use core::ptr;
+use core::sync::atomic::{AtomicU64, Ordering};
+use sgx_types::*;
+
+///
+/// A registered AEX handler. Pass it to rsgx_unregister_aex_handler to remove the handler and
+/// free its list node.
+///
+#[derive(Debug)]
+pub struct AexHandle {
+    node: *mut sgx_aex_mitigation_node_t,
+    handler: sgx_aex_mitigation_fn_t,
+}
+
+///
+/// rsgx_register_aex_handler registers an AEX handler for the current thread.
+///
+/// # Description
+///
+/// The handler is called with the exception information of the exit and `args` after each
+/// AEX, once AEX-Notify is enabled with rsgx_set_ssa_aexnotify. The handler runs before the
+/// interrupted code resumes, so it must be short and must not make OCALLs.
+///
+/// # Requirements
+///
+/// Library: libsgx_trts.a (Intel SGX SDK 2.19 or later)
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// The handler is already registered for the current thread.
+///
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn rsgx_register_aex_handler(
+    handler: sgx_aex_mitigation_fn_t,
+    args: *const c_void,
+) -> SgxResult<AexHandle> {
+    let node = Box::into_raw(Box::new(sgx_aex_mitigation_node_t {
+        handler,
+        args,
+        next: ptr::null_mut(),
+    }));
+    let ret = unsafe { sgx_register_aex_handler(node, handler, args) };
+    match ret {
+        sgx_status_t::SGX_SUCCESS => Ok(AexHandle { node, handler }),
+        _ => {
+            drop(unsafe { Box::from_raw(node) });
+            Err(ret)
+        }
+    }
+}
+
+///
+/// rsgx_unregister_aex_handler removes an AEX handler of the current thread.
+///
+/// It must be called on the thread that registered the handler.
+///
+pub fn rsgx_unregister_aex_handler(handle: AexHandle) -> SgxError {
+    let ret = unsafe { sgx_unregister_aex_handler(handle.handler) };
+    match ret {
+        sgx_status_t::SGX_SUCCESS => {
+            drop(unsafe { Box::from_raw(handle.node) });
+            Ok(())
+        }
+        _ => Err(ret),
+    }
+}
+
+///
+/// rsgx_set_ssa_aexnotify enables or disables AEX-Notify for the current thread.
+///
+pub fn rsgx_set_ssa_aexnotify(enable: bool) -> SgxError {
+    let ret = unsafe { sgx_set_ssa_aexnotify(enable as int32_t) };
+    match ret {
+        sgx_status_t::SGX_SUCCESS => Ok(()),
+        _ => Err(ret),
+    }
+}
+
+///
+/// A code region whose asynchronous exits are counted by `AexStats`.
+///
+#[derive(Debug)]
+pub struct AexRegion {
+    name: &'static str,
+    start: usize,
+    end: usize,
+    exits: AtomicU64,
+}
+
+impl AexRegion {
+    /// The region `[start, end)` of enclave code.
+    pub const fn new(name: &'static str, start: usize, end: usize) -> AexRegion {
+        AexRegion {
+            name,
+            start,
+            end,
+            exits: AtomicU64::new(0),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn exits(&self) -> u64 {
+        self.exits.load(Ordering::Relaxed)
+    }
+
+    fn contains(&self, rip: usize) -> bool {
+        rip >= self.start && rip < self.end
+    }
+}
+
+///
+/// Counts asynchronous exits per code region.
+///
+/// The collector is meant to live in a static, as the AEX handler keeps a reference to it:
+///
+/// ```ignore
+/// static REGIONS: [AexRegion; 1] = [AexRegion::new("sign", SIGN_START, SIGN_END)];
+/// static STATS: AexStats = AexStats::new(&REGIONS);
+///
+/// let handle = STATS.register()?;
+/// rsgx_set_ssa_aexnotify(true)?;
+/// sign(...);
+/// if let Some(region) = STATS.exceeded(MAX_EXITS) { ... }
+/// ```
+///
+#[derive(Debug)]
+pub struct AexStats {
+    regions: &'static [AexRegion],
+    total: AtomicU64,
+}
+
+impl AexStats {
+    pub const fn new(regions: &'static [AexRegion]) -> AexStats {
+        AexStats {
+            regions,
+            total: AtomicU64::new(0),
+        }
+    }
+
+    /// Registers the collector as an AEX handler of the current thread.
+    pub fn register(&'static self) -> SgxResult<AexHandle> {
+        rsgx_register_aex_handler(aex_stats_handler, self as *const AexStats as *const c_void)
+    }
+
+    /// Records one exit at `rip`.
+    pub fn record(&self, rip: usize) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        if let Some(region) = self.regions.iter().find(|r| r.contains(rip)) {
+            region.exits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The number of exits recorded anywhere in the enclave.
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    pub fn regions(&self) -> &'static [AexRegion] {
+        self.regions
+    }
+
+    /// Returns the first region with more than `max_exits` exits since the last reset.
+    pub fn exceeded(&self, max_exits: u64) -> Option<&'static AexRegion> {
+        self.regions.iter().find(|r| r.exits() > max_exits)
+    }
+
+    pub fn reset(&self) {
+        self.total.store(0, Ordering::Relaxed);
+        for region in self.regions {
+            region.exits.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+extern "C" fn aex_stats_handler(info: *const sgx_exception_info_t, args: *const c_void) {
+    if info.is_null() || args.is_null() {
+        return;
+    }
+    let stats = unsafe { &*(args as *const AexStats) };
+    let rip = unsafe { (*info).cpu_context.rip };
+    stats.record(rip as usize);
+}
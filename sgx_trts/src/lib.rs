@@ -68,6 +68,7 @@
 #![feature(specialization)]
 #![feature(vec_into_raw_parts)]
 #![feature(rustc_attrs)]
+#![feature(naked_functions)]
 #![allow(incomplete_features)]
 #![allow(non_camel_case_types)]
 #![allow(non_upper_case_globals)]
@@ -86,6 +87,7 @@ extern crate alloc;
 #[macro_use]
 mod macros;
 
+pub mod aex;
 pub mod ascii;
 pub mod c_str;
 pub mod cpu_feature;
@@ -95,6 +97,8 @@ pub mod enclave;
 pub mod memchr;
 pub mod memeq;
 pub mod oom;
+pub mod retguard;
+pub mod stack;
 pub mod trts;
 pub mod veh;
 
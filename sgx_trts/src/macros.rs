@@ -76,3 +76,32 @@ macro_rules! global_dtors_object {
         }
     };
 }
+
+/// protected_ecall defines ECALL functions whose bodies run with return address
+/// protection, see `retguard`.
+///
+/// Each function is exported unmangled with the C calling convention, for the bridge
+/// code generated by the Edger8r tool, and returns the value of its body.
+///
+/// ```ignore
+/// protected_ecall! {
+///     fn ecall_process(input: *const u8, len: usize) -> sgx_status_t {
+///         process(input, len)
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! protected_ecall {
+    ($(
+        $(#[$attr:meta])*
+        fn $name:ident($($arg:ident: $ty:ty),* $(,)?) -> $ret:ty $body:block
+    )*) => {
+        $(
+            $(#[$attr])*
+            #[no_mangle]
+            pub extern "C" fn $name($($arg: $ty),*) -> $ret {
+                $crate::retguard::rsgx_protect_return(move || $body)
+            }
+        )*
+    };
+}
@@ -0,0 +1,116 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Return address protection
+//!
+//! A software analogue of pointer authentication for the return addresses of ECALL
+//! bodies, for enclaves that cannot rely on a CET shadow stack.
+//!
+//! `rsgx_call_protected` runs a function through a trampoline that, on entry, stores its
+//! own return address XORed with a per-thread cookie, the stack guard of the thread data,
+//! and, on exit, checks the return address against the stored copy before returning. A
+//! linear overflow of a buffer in the function's frames that reaches the ECALL's frame has
+//! to overwrite both; without the cookie it cannot make them match, and the enclave aborts
+//! instead of returning to an address of the attacker's choice.
+//!
+//! `protected_ecall!` defines ECALL functions whose bodies run through the trampoline. The
+//! body must not unwind: build the enclave with `panic = "abort"`, or catch panics inside
+//! it, for example with `std::panic::catch_ecall`.
+
+use crate::trts::rsgx_abort;
+use core::arch::asm;
+use core::ffi::c_void;
+
+///
+/// The function type that `rsgx_call_protected` calls.
+///
+pub type protected_function_t = unsafe extern "C" fn(arg: *mut c_void) -> u64;
+
+extern "C" fn return_address_violation() -> ! {
+    rsgx_abort()
+}
+
+///
+/// rsgx_call_protected calls `f(arg)` and checks, before returning, that its own return
+/// address was not modified in the meantime. The enclave aborts if it was.
+///
+/// # Safety
+///
+/// `f` must be safe to call with `arg`, and must not unwind.
+///
+#[naked]
+pub unsafe extern "C" fn rsgx_call_protected(f: protected_function_t, arg: *mut c_void) -> u64 {
+    asm!(
+        "push rbx",
+        "mov rbx, qword ptr [rsp + 8]",
+        "xor rbx, qword ptr fs:[0x28]",
+        "push rbx",
+        "sub rsp, 8",
+        "mov rax, rdi",
+        "mov rdi, rsi",
+        "call rax",
+        "add rsp, 8",
+        "pop rbx",
+        "xor rbx, qword ptr fs:[0x28]",
+        "cmp rbx, qword ptr [rsp + 8]",
+        "jne 2f",
+        "pop rbx",
+        "ret",
+        "2:",
+        "call {violation}",
+        "ud2",
+        violation = sym return_address_violation,
+        options(noreturn),
+    );
+}
+
+struct Call<F, R> {
+    f: Option<F>,
+    result: Option<R>,
+}
+
+unsafe extern "C" fn call_closure<F: FnOnce() -> R, R>(arg: *mut c_void) -> u64 {
+    let call = &mut *(arg as *mut Call<F, R>);
+    if let Some(f) = call.f.take() {
+        call.result = Some(f());
+    }
+    0
+}
+
+///
+/// rsgx_protect_return runs `f` through `rsgx_call_protected` and returns its result.
+///
+/// # Description
+///
+/// `f` must not unwind; see the module documentation.
+///
+pub fn rsgx_protect_return<F: FnOnce() -> R, R>(f: F) -> R {
+    let mut call = Call {
+        f: Some(f),
+        result: None,
+    };
+    unsafe {
+        rsgx_call_protected(
+            call_closure::<F, R>,
+            &mut call as *mut Call<F, R> as *mut c_void,
+        );
+    }
+    match call.result {
+        Some(result) => result,
+        None => rsgx_abort(),
+    }
+}
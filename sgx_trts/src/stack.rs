@@ -0,0 +1,122 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Stack scrubbing
+//!
+//! Secrets outlive the functions that use them in the stack frames those functions leave
+//! behind: spilled registers, temporaries and copies the compiler made. They stay there until
+//! a later call happens to overwrite them, which may be never, and can be read back by any
+//! bug that discloses stack memory in a later ECALL.
+//!
+//! `rsgx_scrub_stack_after` and `SgxStackScrubGuard` bound that lifetime. Wrapping the body
+//! of an ECALL with either overwrites with zeroes all of the thread's committed stack below
+//! the ECALL's own frame once the body returns, that is every frame the body used. Scrubbing
+//! is opt-in, per ECALL: it costs a pass over the committed stack of the thread on every
+//! return.
+
+use crate::enclave::rsgx_get_thread_data;
+use core::arch::asm;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+// Bytes below the stack pointer of `rsgx_scrub_stack` that are left alone: the red zone of
+// the x86-64 ABI, which the function may use itself.
+const RED_ZONE: usize = 128;
+
+///
+/// rsgx_scrub_stack overwrites with zeroes the committed stack of the current thread below
+/// the frame of its caller.
+///
+/// # Description
+///
+/// The stack is cleared with a single `rep stosq` between the lowest committed stack address
+/// and the stack pointer, followed by a compiler barrier, so that the stores can neither be
+/// elided nor moved. The function does nothing when it does not run on the thread's enclave
+/// stack.
+///
+#[inline(never)]
+pub fn rsgx_scrub_stack() {
+    let td = unsafe { &*rsgx_get_thread_data() };
+    let low = td.stack_limit_addr.max(td.stack_commit_addr);
+    let sp: usize;
+    unsafe {
+        asm!("mov {}, rsp", out(reg) sp, options(nomem, nostack, preserves_flags));
+    }
+    if sp > td.stack_base_addr || sp < low {
+        return;
+    }
+
+    let start = (low + 7) & !7;
+    let end = sp.saturating_sub(RED_ZONE) & !7;
+    if end > start {
+        unsafe {
+            asm!(
+                "rep stosq",
+                inout("rcx") (end - start) / 8 => _,
+                inout("rdi") start => _,
+                in("rax") 0_usize,
+                options(nostack, preserves_flags),
+            );
+        }
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+#[inline(never)]
+fn call<F: FnOnce() -> R, R>(f: F) -> R {
+    f()
+}
+
+///
+/// rsgx_scrub_stack_after calls `f` and scrubs the stack it used before returning its result.
+///
+/// # Description
+///
+/// `f` runs in a frame below the caller's, so that none of its locals end up in the
+/// caller's frame, where they would escape the scrubbing. Wrap the whole body of an ECALL
+/// function generated by the Edger8r tool to scrub the stack on every return.
+///
+pub fn rsgx_scrub_stack_after<F: FnOnce() -> R, R>(f: F) -> R {
+    let result = call(f);
+    rsgx_scrub_stack();
+    result
+}
+
+///
+/// Scrubs the stack below its owner's frame when dropped, see `rsgx_scrub_stack`.
+///
+/// # Description
+///
+/// A guard created at the start of an ECALL scrubs the stack on every return, early returns
+/// and unwinding included. Values inlined into the ECALL's own frame are not covered; use
+/// `rsgx_scrub_stack_after` to keep them out of it.
+///
+#[derive(Default)]
+pub struct SgxStackScrubGuard {
+    _private: (),
+}
+
+impl SgxStackScrubGuard {
+    pub fn new() -> SgxStackScrubGuard {
+        SgxStackScrubGuard { _private: () }
+    }
+}
+
+impl Drop for SgxStackScrubGuard {
+    fn drop(&mut self) {
+        rsgx_scrub_stack();
+    }
+}
@@ -0,0 +1,239 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::se::*;
+use sgx_types::*;
+
+///
+/// The Key Separation and Sharing (KSS) identity of an enclave.
+///
+/// These fields are only meaningful when the enclave was launched with `SGX_FLAGS_KSS`
+/// set in its attributes. Otherwise they are all zero.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SgxKssIdentity {
+    pub config_id: sgx_config_id_t,
+    pub config_svn: sgx_config_svn_t,
+    pub isv_family_id: sgx_isvfamily_id_t,
+    pub isv_ext_prod_id: sgx_isvext_prod_id_t,
+}
+
+impl Default for SgxKssIdentity {
+    fn default() -> Self {
+        SgxKssIdentity {
+            config_id: [0_u8; SGX_CONFIGID_SIZE],
+            config_svn: 0,
+            isv_family_id: sgx_isvfamily_id_t::default(),
+            isv_ext_prod_id: sgx_isvext_prod_id_t::default(),
+        }
+    }
+}
+
+impl SgxKssIdentity {
+    pub fn from_report_body(body: &sgx_report_body_t) -> Self {
+        SgxKssIdentity {
+            config_id: body.config_id,
+            config_svn: body.config_svn,
+            isv_family_id: body.isv_family_id,
+            isv_ext_prod_id: body.isv_ext_prod_id,
+        }
+    }
+}
+
+///
+/// The rsgx_is_kss_enabled function returns true if the calling enclave was launched with KSS.
+///
+pub fn rsgx_is_kss_enabled() -> bool {
+    (rsgx_self_report().body.attributes.flags & SGX_FLAGS_KSS) != 0
+}
+
+///
+/// The rsgx_self_kss_identity function returns the KSS identity of the calling enclave,
+/// or None if the enclave was launched without KSS.
+///
+pub fn rsgx_self_kss_identity() -> Option<SgxKssIdentity> {
+    let report = rsgx_self_report();
+    if (report.body.attributes.flags & SGX_FLAGS_KSS) != 0 {
+        Some(SgxKssIdentity::from_report_body(&report.body))
+    } else {
+        None
+    }
+}
+
+///
+/// The rsgx_self_target function generates the target info of the calling enclave,
+/// including its CONFIGID and CONFIGSVN.
+///
+/// Another enclave passes the result to rsgx_create_report to create a report that
+/// the calling enclave can verify.
+///
+pub fn rsgx_self_target() -> SgxResult<sgx_target_info_t> {
+    let mut target_info = sgx_target_info_t::default();
+    let ret = unsafe { sgx_self_target(&mut target_info as *mut sgx_target_info_t) };
+    match ret {
+        sgx_status_t::SGX_SUCCESS => Ok(target_info),
+        _ => Err(ret),
+    }
+}
+
+///
+/// The rsgx_target_info_from_report function derives the target info of the enclave that
+/// generated `report`.
+///
+/// A report created for a KSS enclave must carry the target's CONFIGID and CONFIGSVN, otherwise
+/// the target cannot verify it. Both are copied together with the measurement, attributes and
+/// MISCSELECT.
+///
+pub fn rsgx_target_info_from_report(report: &sgx_report_t) -> sgx_target_info_t {
+    sgx_target_info_t {
+        mr_enclave: report.body.mr_enclave,
+        attributes: report.body.attributes,
+        misc_select: report.body.misc_select,
+        config_svn: report.body.config_svn,
+        config_id: report.body.config_id,
+        ..Default::default()
+    }
+}
+
+///
+/// A builder for sgx_key_request_t.
+///
+/// The request starts from the identity of the calling enclave: its ISVSVN, CPUSVN and
+/// CONFIGSVN, an MRSIGNER policy and the default attribute and MISCSELECT masks used for
+/// sealing. The KSS policy bits can be set individually, and are rejected by `build`
+/// unless the enclave was launched with KSS.
+///
+#[derive(Clone, Copy)]
+pub struct SgxKeyRequestBuilder {
+    request: sgx_key_request_t,
+    self_body: sgx_report_body_t,
+}
+
+impl SgxKeyRequestBuilder {
+    pub fn new(key_name: u16) -> Self {
+        let report = rsgx_self_report();
+        let request = sgx_key_request_t {
+            key_name,
+            key_policy: SGX_KEYPOLICY_MRSIGNER,
+            isv_svn: report.body.isv_svn,
+            cpu_svn: report.body.cpu_svn,
+            config_svn: report.body.config_svn,
+            attribute_mask: sgx_attributes_t {
+                flags: TSEAL_DEFAULT_FLAGSMASK,
+                xfrm: 0,
+            },
+            misc_mask: TSEAL_DEFAULT_MISCMASK,
+            ..Default::default()
+        };
+        SgxKeyRequestBuilder {
+            request,
+            self_body: report.body,
+        }
+    }
+
+    pub fn key_policy(mut self, key_policy: u16) -> Self {
+        self.request.key_policy = key_policy;
+        self
+    }
+
+    pub fn config_id(self, enable: bool) -> Self {
+        self.policy_bit(SGX_KEYPOLICY_CONFIGID, enable)
+    }
+
+    pub fn isv_family_id(self, enable: bool) -> Self {
+        self.policy_bit(SGX_KEYPOLICY_ISVFAMILYID, enable)
+    }
+
+    pub fn isv_ext_prod_id(self, enable: bool) -> Self {
+        self.policy_bit(SGX_KEYPOLICY_ISVEXTPRODID, enable)
+    }
+
+    /// Sets all KSS policy bits if the enclave was launched with KSS, and clears them otherwise.
+    pub fn kss_if_enabled(self) -> Self {
+        let enabled = (self.self_body.attributes.flags & SGX_FLAGS_KSS) != 0;
+        self.policy_bit(SGX_KEYPOLICY_KSS, enabled)
+    }
+
+    pub fn isv_svn(mut self, isv_svn: sgx_isv_svn_t) -> Self {
+        self.request.isv_svn = isv_svn;
+        self
+    }
+
+    pub fn config_svn(mut self, config_svn: sgx_config_svn_t) -> Self {
+        self.request.config_svn = config_svn;
+        self
+    }
+
+    pub fn cpu_svn(mut self, cpu_svn: sgx_cpu_svn_t) -> Self {
+        self.request.cpu_svn = cpu_svn;
+        self
+    }
+
+    pub fn attribute_mask(mut self, attribute_mask: sgx_attributes_t) -> Self {
+        self.request.attribute_mask = attribute_mask;
+        self
+    }
+
+    pub fn misc_mask(mut self, misc_mask: sgx_misc_select_t) -> Self {
+        self.request.misc_mask = misc_mask;
+        self
+    }
+
+    pub fn key_id(mut self, key_id: sgx_key_id_t) -> Self {
+        self.request.key_id = key_id;
+        self
+    }
+
+    fn policy_bit(mut self, bit: u16, enable: bool) -> Self {
+        if enable {
+            self.request.key_policy |= bit;
+        } else {
+            self.request.key_policy &= !bit;
+        }
+        self
+    }
+
+    ///
+    /// Checks the request against the calling enclave and returns it.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_ATTRIBUTE**
+    ///
+    /// KSS policy bits are set but the enclave was launched without KSS.
+    ///
+    /// **SGX_ERROR_INVALID_ISVSVN**
+    ///
+    /// The ISVSVN or CONFIGSVN is greater than the enclave's own.
+    ///
+    pub fn build(&self) -> SgxResult<sgx_key_request_t> {
+        let kss = (self.self_body.attributes.flags & SGX_FLAGS_KSS) != 0;
+        if (self.request.key_policy & SGX_KEYPOLICY_KSS) != 0 && !kss {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_ATTRIBUTE);
+        }
+        if self.request.isv_svn > self.self_body.isv_svn
+            || self.request.config_svn > self.self_body.config_svn
+        {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_ISVSVN);
+        }
+        Ok(self.request)
+    }
+
+    pub fn get_key(&self) -> SgxResult<sgx_key_128bit_t> {
+        rsgx_get_key(&self.build()?)
+    }
+}
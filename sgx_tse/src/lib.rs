@@ -28,3 +28,9 @@ extern crate sgx_types;
 
 mod se;
 pub use self::se::*;
+
+mod kss;
+pub use self::kss::*;
+
+mod policy;
+pub use self::policy::SgxIdentityPolicy;
@@ -0,0 +1,151 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::se::rsgx_verify_report;
+use sgx_types::*;
+
+///
+/// An identity policy for attested enclaves.
+///
+/// The policy is checked against the report body of a local report, or of a quote whose
+/// signature has already been verified (for example by the QvE). Fields that are not
+/// set are not checked. The KSS fields are compared only if set, and setting any of them
+/// also requires the enclave to have been launched with KSS.
+///
+#[derive(Clone, Copy, Default)]
+pub struct SgxIdentityPolicy {
+    mr_enclave: Option<sgx_measurement_t>,
+    mr_signer: Option<sgx_measurement_t>,
+    isv_prod_id: Option<sgx_prod_id_t>,
+    min_isv_svn: sgx_isv_svn_t,
+    allow_debug: bool,
+    config_id: Option<sgx_config_id_t>,
+    min_config_svn: Option<sgx_config_svn_t>,
+    isv_family_id: Option<sgx_isvfamily_id_t>,
+    isv_ext_prod_id: Option<sgx_isvext_prod_id_t>,
+}
+
+impl SgxIdentityPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mr_enclave(mut self, mr_enclave: sgx_measurement_t) -> Self {
+        self.mr_enclave = Some(mr_enclave);
+        self
+    }
+
+    pub fn mr_signer(mut self, mr_signer: sgx_measurement_t) -> Self {
+        self.mr_signer = Some(mr_signer);
+        self
+    }
+
+    pub fn isv_prod_id(mut self, isv_prod_id: sgx_prod_id_t) -> Self {
+        self.isv_prod_id = Some(isv_prod_id);
+        self
+    }
+
+    pub fn min_isv_svn(mut self, isv_svn: sgx_isv_svn_t) -> Self {
+        self.min_isv_svn = isv_svn;
+        self
+    }
+
+    pub fn allow_debug(mut self, allow: bool) -> Self {
+        self.allow_debug = allow;
+        self
+    }
+
+    pub fn config_id(mut self, config_id: sgx_config_id_t) -> Self {
+        self.config_id = Some(config_id);
+        self
+    }
+
+    pub fn min_config_svn(mut self, config_svn: sgx_config_svn_t) -> Self {
+        self.min_config_svn = Some(config_svn);
+        self
+    }
+
+    pub fn isv_family_id(mut self, isv_family_id: sgx_isvfamily_id_t) -> Self {
+        self.isv_family_id = Some(isv_family_id);
+        self
+    }
+
+    pub fn isv_ext_prod_id(mut self, isv_ext_prod_id: sgx_isvext_prod_id_t) -> Self {
+        self.isv_ext_prod_id = Some(isv_ext_prod_id);
+        self
+    }
+
+    fn requires_kss(&self) -> bool {
+        self.config_id.is_some()
+            || self.min_config_svn.is_some()
+            || self.isv_family_id.is_some()
+            || self.isv_ext_prod_id.is_some()
+    }
+
+    ///
+    /// Checks a report body against the policy.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// A measurement, product ID or KSS identity does not match.
+    ///
+    /// **SGX_ERROR_INVALID_ISVSVN**
+    ///
+    /// The ISVSVN or CONFIGSVN is below the minimum.
+    ///
+    /// **SGX_ERROR_INVALID_ATTRIBUTE**
+    ///
+    /// The enclave is a debug enclave and debug is not allowed, or KSS fields are required
+    /// but the enclave was launched without KSS.
+    ///
+    pub fn verify(&self, body: &sgx_report_body_t) -> SgxError {
+        if !self.allow_debug && (body.attributes.flags & SGX_FLAGS_DEBUG) != 0 {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_ATTRIBUTE);
+        }
+        if self.requires_kss() && (body.attributes.flags & SGX_FLAGS_KSS) == 0 {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_ATTRIBUTE);
+        }
+
+        let matches = self.mr_enclave.map_or(true, |m| m.m == body.mr_enclave.m)
+            && self.mr_signer.map_or(true, |m| m.m == body.mr_signer.m)
+            && self.isv_prod_id.map_or(true, |id| id == body.isv_prod_id)
+            && self.config_id.map_or(true, |id| id == body.config_id)
+            && self.isv_family_id.map_or(true, |id| id == body.isv_family_id)
+            && self.isv_ext_prod_id.map_or(true, |id| id == body.isv_ext_prod_id);
+        if !matches {
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+
+        if body.isv_svn < self.min_isv_svn
+            || self.min_config_svn.map_or(false, |svn| body.config_svn < svn)
+        {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_ISVSVN);
+        }
+        Ok(())
+    }
+
+    ///
+    /// Verifies the MAC of a local report with rsgx_verify_report, then checks its body
+    /// against the policy.
+    ///
+    pub fn verify_report(&self, report: &sgx_report_t) -> SgxError {
+        rsgx_verify_report(report)?;
+        self.verify(&report.body)
+    }
+}
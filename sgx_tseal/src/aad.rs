@@ -19,6 +19,7 @@
 //! Provides APIs to authenticate and verify the input data with AES-GMAC.
 //!
 use crate::internal::*;
+use crate::policy::SgxSealPolicy;
 use alloc::boxed::Box;
 use alloc::slice;
 use core::marker::PhantomData;
@@ -143,6 +144,10 @@ impl<'a, T: 'a + Copy + ContiguousMemory> SgxMacAadata<'a, T> {
     /// ---|---|---
     /// KEYPOLICY_MRENCLAVE | 0x0001 | -Derive key using the enclave??s ENCLAVE measurement register
     /// KEYPOLICY_MRSIGNER |0x0002 | -Derive key using the enclave??s SIGNER measurement register
+    /// KEYPOLICY_NOISVPRODID | 0x0004 | -Derive key without the enclave's ISVPRODID
+    /// KEYPOLICY_CONFIGID | 0x0008 | -Derive key with the enclave's CONFIGID (KSS)
+    /// KEYPOLICY_ISVFAMILYID | 0x0010 | -Derive key with the enclave's ISVFAMILYID (KSS)
+    /// KEYPOLICY_ISVEXTPRODID | 0x0020 | -Derive key with the enclave's ISVEXTPRODID (KSS)
     ///
     /// **attribute_mask**
     ///
@@ -207,6 +212,21 @@ impl<'a, T: 'a + Copy + ContiguousMemory> SgxMacAadata<'a, T> {
         })
     }
 
+    ///
+    /// Authenticates the data with the key policy, attribute mask and misc mask of `policy`.
+    ///
+    /// This is equivalent to calling `mac_aadata_ex` with the values of `policy`.
+    /// See `SgxSealPolicy`.
+    ///
+    pub fn mac_aadata_policy(policy: &SgxSealPolicy, additional_text: &T) -> SgxResult<Self> {
+        Self::mac_aadata_ex(
+            policy.get_key_policy(),
+            policy.get_attribute_mask(),
+            policy.get_misc_mask(),
+            additional_text,
+        )
+    }
+
     ///
     /// This function is used to verify the authenticity of the input sealed data structure using AES-GMAC. This function verifies the MAC generated with sgx_mac_aadata or sgx_mac_aadata_ex.
     ///
@@ -238,6 +258,10 @@ impl<'a, T: 'a + Copy + ContiguousMemory> SgxMacAadata<'a, T> {
     ///
     /// The ISVSVN in the data blob is greater than the ISVSVN value of the enclave.
     ///
+    /// **SGX_ERROR_INVALID_ATTRIBUTE**
+    ///
+    /// The enclave holds production secrets and is debuggable, see `rsgx_set_secret_class`.
+    ///
     /// **SGX_ERROR_MAC_MISMATCH**
     ///
     /// The tag verification fails. The error may be caused by a platform update, software update, or corruption of the sealed_data_t structure.
@@ -359,6 +383,21 @@ impl<'a, T: 'a + Copy + ContiguousMemory> SgxMacAadata<'a, [T]> {
         })
     }
 
+    ///
+    /// Authenticates the data with the key policy, attribute mask and misc mask of `policy`.
+    ///
+    /// This is equivalent to calling `mac_aadata_ex` with the values of `policy`.
+    /// See `SgxSealPolicy`.
+    ///
+    pub fn mac_aadata_policy(policy: &SgxSealPolicy, additional_text: &[T]) -> SgxResult<Self> {
+        Self::mac_aadata_ex(
+            policy.get_key_policy(),
+            policy.get_attribute_mask(),
+            policy.get_misc_mask(),
+            additional_text,
+        )
+    }
+
     ///
     /// This function is used to verify the authenticity of the input sealed data structure using AES-GMAC. This function verifies the MAC generated with sgx_mac_aadataorsgx_mac_aadata_ex.
     ///
@@ -0,0 +1,316 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Sealing of many small records under one seal key.
+//!
+//! Where `seal_data` uses a fresh key request for every record, a batch shares one key
+//! request, and so one random key ID and one derived seal key, between all of its records.
+//! Each record is encrypted with its own AES-GCM operation under that key and its own nonce,
+//! the index of the record in the batch, which is unique per key because the key ID is.
+//! Each record is a separate blob and can be unsealed on its own.
+
+use crate::cache::get_seal_key;
+use crate::guard::rsgx_check_unseal_guard;
+use crate::policy::SgxSealPolicy;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::ptr;
+use sgx_tcrypto::*;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_tse::rsgx_self_report;
+use sgx_types::*;
+
+const BLOB_VERSION: u8 = 1;
+const BLOB_HEADER_SIZE: usize = 1 // version
+    + 2 // key policy
+    + 2 // ISVSVN
+    + 2 // CONFIGSVN
+    + SGX_CPUSVN_SIZE
+    + 16 // attribute mask
+    + 4 // misc mask
+    + SGX_KEYID_SIZE
+    + SGX_SEAL_IV_SIZE
+    + SGX_SEAL_TAG_SIZE
+    + 4; // ciphertext length
+
+///
+/// A record sealed with `seal_batch`.
+///
+/// The blob carries the parts of the key request needed to derive its key again, and is
+/// serialized with `to_bytes` in a compact form, about 100 bytes larger than the record.
+///
+#[derive(Clone)]
+pub struct SgxSealedBlob {
+    key_request: sgx_key_request_t,
+    nonce: [u8; SGX_SEAL_IV_SIZE],
+    tag: [u8; SGX_SEAL_TAG_SIZE],
+    ciphertext: Box<[u8]>,
+}
+
+fn record_nonce(index: usize) -> [u8; SGX_SEAL_IV_SIZE] {
+    let mut nonce = [0_u8; SGX_SEAL_IV_SIZE];
+    nonce[SGX_SEAL_IV_SIZE - 8..].copy_from_slice(&(index as u64).to_be_bytes());
+    nonce
+}
+
+pub(crate) fn fresh_key_request(policy: &SgxSealPolicy) -> SgxResult<sgx_key_request_t> {
+    let key_policy = policy.get_key_policy();
+    let attribute_mask = policy.get_attribute_mask();
+    if (key_policy
+        & !(SGX_KEYPOLICY_MRENCLAVE
+            | SGX_KEYPOLICY_MRSIGNER
+            | SGX_KEYPOLICY_KSS
+            | SGX_KEYPOLICY_NOISVPRODID))
+        != 0
+        || (key_policy & (SGX_KEYPOLICY_MRENCLAVE | SGX_KEYPOLICY_MRSIGNER)) == 0
+        || (attribute_mask.flags & SGX_FLAGS_INITTED) == 0
+        || (attribute_mask.flags & SGX_FLAGS_DEBUG) == 0
+    {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+
+    let mut key_id = sgx_key_id_t::default();
+    rsgx_read_rand(&mut key_id.id)?;
+    let report = rsgx_self_report();
+    Ok(sgx_key_request_t {
+        key_name: SGX_KEYSELECT_SEAL,
+        key_policy,
+        isv_svn: report.body.isv_svn,
+        cpu_svn: report.body.cpu_svn,
+        attribute_mask,
+        key_id,
+        misc_mask: policy.get_misc_mask(),
+        config_svn: report.body.config_svn,
+        ..Default::default()
+    })
+}
+
+pub(crate) fn wipe(key: &mut sgx_align_key_128bit_t) {
+    unsafe { ptr::write_volatile(key, sgx_align_key_128bit_t::default()) };
+}
+
+fn wipe_bytes(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
+
+impl SgxSealedBlob {
+    ///
+    /// Seals each record of `records` with the default seal policy.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// A record is empty, larger than 4 GB, or not within the enclave.
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// The seal key could not be derived, or a crypto library failure occurred.
+    ///
+    pub fn seal_batch(records: &[&[u8]]) -> SgxResult<Vec<SgxSealedBlob>> {
+        Self::seal_batch_policy(&SgxSealPolicy::default(), records)
+    }
+
+    ///
+    /// Seals each record of `records` with the key policy of `policy`.
+    ///
+    /// The seal key is derived once for the batch and wiped when the batch is sealed.
+    ///
+    pub fn seal_batch_policy(
+        policy: &SgxSealPolicy,
+        records: &[&[u8]],
+    ) -> SgxResult<Vec<SgxSealedBlob>> {
+        for record in records {
+            if record.is_empty()
+                || record.len() >= u32::MAX as usize
+                || !sgx_trts::trts::rsgx_slice_is_within_enclave(record)
+            {
+                return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+            }
+        }
+        if records.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let key_request = fresh_key_request(policy)?;
+        let mut seal_key = get_seal_key(&key_request).map_err(|ret| {
+            if ret != sgx_status_t::SGX_ERROR_OUT_OF_MEMORY {
+                sgx_status_t::SGX_ERROR_UNEXPECTED
+            } else {
+                ret
+            }
+        })?;
+
+        let mut blobs = Vec::with_capacity(records.len());
+        for (index, record) in records.iter().enumerate() {
+            let nonce = record_nonce(index);
+            let mut ciphertext = vec![0_u8; record.len()].into_boxed_slice();
+            let mut tag = [0_u8; SGX_SEAL_TAG_SIZE];
+            if let Err(e) = rsgx_rijndael128GCM_encrypt(
+                &seal_key.key,
+                record,
+                &nonce,
+                &[],
+                &mut ciphertext,
+                &mut tag,
+            ) {
+                wipe(&mut seal_key);
+                return Err(e);
+            }
+            blobs.push(SgxSealedBlob {
+                key_request,
+                nonce,
+                tag,
+                ciphertext,
+            });
+        }
+        wipe(&mut seal_key);
+        Ok(blobs)
+    }
+
+    ///
+    /// Unseals the record.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_ATTRIBUTE**
+    ///
+    /// The enclave holds production secrets and is debuggable, see `rsgx_set_secret_class`.
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The blob was not sealed by this enclave or signer, or has been modified.
+    ///
+    pub fn unseal(&self) -> SgxResult<Vec<u8>> {
+        rsgx_check_unseal_guard()?;
+
+        let mut seal_key = get_seal_key(&self.key_request).map_err(|ret| match ret {
+            sgx_status_t::SGX_ERROR_INVALID_CPUSVN
+            | sgx_status_t::SGX_ERROR_INVALID_ISVSVN
+            | sgx_status_t::SGX_ERROR_OUT_OF_MEMORY => ret,
+            _ => sgx_status_t::SGX_ERROR_MAC_MISMATCH,
+        })?;
+        let mut plaintext = vec![0_u8; self.ciphertext.len()];
+        let result = rsgx_rijndael128GCM_decrypt(
+            &seal_key.key,
+            &self.ciphertext,
+            &self.nonce,
+            &[],
+            &self.tag,
+            &mut plaintext,
+        );
+        wipe(&mut seal_key);
+        match result {
+            Ok(()) => Ok(plaintext),
+            Err(_) => {
+                wipe_bytes(&mut plaintext);
+                Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH)
+            }
+        }
+    }
+
+    pub fn get_key_request(&self) -> &sgx_key_request_t {
+        &self.key_request
+    }
+
+    pub fn get_encrypt_txt(&self) -> &[u8] {
+        &self.ciphertext
+    }
+
+    /// The size of the serialized blob.
+    pub fn serialized_size(&self) -> usize {
+        BLOB_HEADER_SIZE + self.ciphertext.len()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let req = &self.key_request;
+        let mut out = Vec::with_capacity(self.serialized_size());
+        out.push(BLOB_VERSION);
+        out.extend_from_slice(&req.key_policy.to_le_bytes());
+        out.extend_from_slice(&req.isv_svn.to_le_bytes());
+        out.extend_from_slice(&req.config_svn.to_le_bytes());
+        out.extend_from_slice(&req.cpu_svn.svn);
+        out.extend_from_slice(&req.attribute_mask.flags.to_le_bytes());
+        out.extend_from_slice(&req.attribute_mask.xfrm.to_le_bytes());
+        out.extend_from_slice(&req.misc_mask.to_le_bytes());
+        out.extend_from_slice(&req.key_id.id);
+        out.extend_from_slice(&self.nonce);
+        out.extend_from_slice(&self.tag);
+        out.extend_from_slice(&(self.ciphertext.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.ciphertext);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<SgxSealedBlob> {
+        fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+            if bytes.len() < n {
+                return None;
+            }
+            let (head, tail) = bytes.split_at(n);
+            *bytes = tail;
+            Some(head)
+        }
+        fn array<const N: usize>(bytes: &mut &[u8]) -> Option<[u8; N]> {
+            take(bytes, N)?.try_into().ok()
+        }
+
+        let mut rest = bytes;
+        if take(&mut rest, 1)? != [BLOB_VERSION] {
+            return None;
+        }
+        let key_policy = u16::from_le_bytes(array(&mut rest)?);
+        let isv_svn = u16::from_le_bytes(array(&mut rest)?);
+        let config_svn = u16::from_le_bytes(array(&mut rest)?);
+        let cpu_svn = sgx_cpu_svn_t {
+            svn: array(&mut rest)?,
+        };
+        let attribute_mask = sgx_attributes_t {
+            flags: u64::from_le_bytes(array(&mut rest)?),
+            xfrm: u64::from_le_bytes(array(&mut rest)?),
+        };
+        let misc_mask = u32::from_le_bytes(array(&mut rest)?);
+        let key_id = sgx_key_id_t {
+            id: array(&mut rest)?,
+        };
+        let nonce = array(&mut rest)?;
+        let tag = array(&mut rest)?;
+        let len = u32::from_le_bytes(array(&mut rest)?) as usize;
+        if rest.len() != len || len == 0 {
+            return None;
+        }
+
+        Some(SgxSealedBlob {
+            key_request: sgx_key_request_t {
+                key_name: SGX_KEYSELECT_SEAL,
+                key_policy,
+                isv_svn,
+                cpu_svn,
+                attribute_mask,
+                key_id,
+                misc_mask,
+                config_svn,
+                ..Default::default()
+            },
+            nonce,
+            tag,
+            ciphertext: rest.to_vec().into_boxed_slice(),
+        })
+    }
+}
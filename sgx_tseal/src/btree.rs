@@ -0,0 +1,815 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::internal::SgxInternalSealedData;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use core::mem;
+use core::ops::{Bound, RangeBounds};
+use sgx_tcrypto::rsgx_sha256_slice;
+use sgx_types::*;
+
+///
+/// Identifier of a page in untrusted storage.
+///
+pub type PageId = u64;
+
+const PAGE_AAD_MAGIC: &[u8; 8] = b"SBTREEPG";
+const NODE_LEAF: u8 = 0;
+const NODE_INTERNAL: u8 = 1;
+
+///
+/// The default maximum number of keys in a node before it is split.
+///
+pub const SEALED_BTREE_DEFAULT_ORDER: usize = 64;
+
+///
+/// The default number of nodes kept in enclave memory.
+///
+pub const SEALED_BTREE_DEFAULT_MAX_RESIDENT: usize = 256;
+
+///
+/// Untrusted storage for the sealed pages of a SealedBTreeMap.
+///
+/// Pages are opaque sealed blobs. The store is not trusted: every page read
+/// back is checked against the hash recorded in its parent, so a store that
+/// returns a stale, swapped or modified page causes `SGX_ERROR_MAC_MISMATCH`.
+///
+pub trait PageStore {
+    ///
+    /// Read the page last written under `id`.
+    ///
+    fn read_page(&mut self, id: PageId) -> SgxResult<Vec<u8>>;
+
+    ///
+    /// Write `page` under `id`, replacing any previous page.
+    ///
+    /// A SealedBTreeMap never overwrites a page of the last committed version,
+    /// so a write that is lost or torn leaves that version intact.
+    ///
+    fn write_page(&mut self, id: PageId, page: &[u8]) -> SgxError;
+
+    ///
+    /// Release the page under `id`, which will not be read again.
+    ///
+    fn free_page(&mut self, id: PageId) -> SgxError;
+
+    ///
+    /// Make `root` the committed version.
+    ///
+    /// This is called by `flush` once every page of the new version has been
+    /// written and before any page of the previous version is freed. A store
+    /// that keeps the root next to its pages switches it here, atomically; the
+    /// default does nothing and leaves the root to the caller of `flush`.
+    ///
+    fn commit(&mut self, _root: &SealedBTreeRoot) -> SgxError {
+        Ok(())
+    }
+}
+
+///
+/// The trusted root of a SealedBTreeMap.
+///
+/// This is all the state needed to reopen a map from its store. It must be kept
+/// in trusted memory or sealed together with a rollback-protected counter, as
+/// the hash is what binds every page of the tree to this version.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SealedBTreeRoot {
+    pub page: PageId,
+    pub hash: sgx_sha256_hash_t,
+    pub next_page: PageId,
+    pub len: u64,
+}
+
+#[derive(Clone, Copy)]
+struct ChildRef {
+    page: PageId,
+    hash: sgx_sha256_hash_t,
+}
+
+struct Node {
+    leaf: bool,
+    keys: Vec<Vec<u8>>,
+    // Values of a leaf, one per key.
+    values: Vec<Vec<u8>>,
+    // Children of an internal node, one more than its keys. Child `i` holds
+    // the keys in `[keys[i - 1], keys[i])`.
+    children: Vec<ChildRef>,
+    dirty: bool,
+    on_disk: bool,
+    hash: sgx_sha256_hash_t,
+    tick: u64,
+}
+
+impl Node {
+    fn new_leaf() -> Node {
+        Node {
+            leaf: true,
+            keys: Vec::new(),
+            values: Vec::new(),
+            children: Vec::new(),
+            dirty: true,
+            on_disk: false,
+            hash: [0; SGX_SHA256_HASH_SIZE],
+            tick: 0,
+        }
+    }
+
+    fn new_internal(keys: Vec<Vec<u8>>, children: Vec<ChildRef>) -> Node {
+        Node {
+            leaf: false,
+            keys,
+            values: Vec::new(),
+            children,
+            ..Node::new_leaf()
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        if self.leaf {
+            self.keys.is_empty()
+        } else {
+            self.children.is_empty()
+        }
+    }
+
+    fn child_index(&self, key: &[u8]) -> usize {
+        self.keys.partition_point(|k| k.as_slice() <= key)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(if self.leaf { NODE_LEAF } else { NODE_INTERNAL });
+        put_u32(&mut buf, self.keys.len() as u32);
+        for key in self.keys.iter() {
+            put_bytes(&mut buf, key);
+        }
+        if self.leaf {
+            for value in self.values.iter() {
+                put_bytes(&mut buf, value);
+            }
+        } else {
+            put_u32(&mut buf, self.children.len() as u32);
+            for child in self.children.iter() {
+                buf.extend_from_slice(&child.page.to_le_bytes());
+                buf.extend_from_slice(&child.hash);
+            }
+        }
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> SgxResult<Node> {
+        let mut reader = Reader { buf };
+        let leaf = match reader.take(1)?[0] {
+            NODE_LEAF => true,
+            NODE_INTERNAL => false,
+            _ => return Err(sgx_status_t::SGX_ERROR_UNEXPECTED),
+        };
+        let nkeys = reader.u32()? as usize;
+        let mut keys = Vec::new();
+        for _ in 0..nkeys {
+            keys.push(reader.bytes()?);
+        }
+        let mut node = Node::new_leaf();
+        if leaf {
+            for _ in 0..nkeys {
+                node.values.push(reader.bytes()?);
+            }
+        } else {
+            let nchildren = reader.u32()? as usize;
+            if nchildren != nkeys + 1 {
+                return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+            }
+            for _ in 0..nchildren {
+                let mut page = [0_u8; 8];
+                page.copy_from_slice(reader.take(8)?);
+                let mut hash = [0_u8; SGX_SHA256_HASH_SIZE];
+                hash.copy_from_slice(reader.take(SGX_SHA256_HASH_SIZE)?);
+                node.children.push(ChildRef {
+                    page: u64::from_le_bytes(page),
+                    hash,
+                });
+            }
+        }
+        if !reader.buf.is_empty() {
+            return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+        }
+        node.leaf = leaf;
+        node.keys = keys;
+        node.dirty = false;
+        node.on_disk = true;
+        Ok(node)
+    }
+}
+
+fn put_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    put_u32(buf, bytes.len() as u32);
+    buf.extend_from_slice(bytes);
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> SgxResult<&'a [u8]> {
+        if self.buf.len() < n {
+            return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+        }
+        let (head, tail) = self.buf.split_at(n);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> SgxResult<u32> {
+        let mut v = [0_u8; 4];
+        v.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(v))
+    }
+
+    fn bytes(&mut self) -> SgxResult<Vec<u8>> {
+        let len = self.u32()? as usize;
+        self.take(len).map(|b| b.to_vec())
+    }
+}
+
+fn page_aad(id: PageId) -> [u8; 16] {
+    let mut aad = [0_u8; 16];
+    aad[..8].copy_from_slice(PAGE_AAD_MAGIC);
+    aad[8..].copy_from_slice(&id.to_le_bytes());
+    aad
+}
+
+fn seal_page(id: PageId, plain: &[u8]) -> SgxResult<Vec<u8>> {
+    seal_blob(&page_aad(id), plain)
+}
+
+fn unseal_page(id: PageId, blob: &[u8]) -> SgxResult<Vec<u8>> {
+    unseal_blob(&page_aad(id), blob)
+}
+
+// Seals `plain` into the raw sgx_sealed_data_t format, with `aad` as its additional text.
+pub(crate) fn seal_blob(aad: &[u8], plain: &[u8]) -> SgxResult<Vec<u8>> {
+    let sealed = SgxInternalSealedData::seal_data(aad, plain)?;
+    let size = SgxInternalSealedData::calc_raw_sealed_data_size(
+        sealed.get_add_mac_txt_len(),
+        sealed.get_encrypt_txt_len(),
+    );
+    if size == u32::MAX {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    // sgx_sealed_data_t needs 8-byte alignment, which a Vec<u8> does not give.
+    let mut raw = vec![0_u64; (size as usize + 7) / mem::size_of::<u64>()];
+    let ptr = raw.as_mut_ptr() as *mut sgx_sealed_data_t;
+    unsafe { sealed.to_raw_sealed_data_t(ptr, size) }.ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)?;
+    let bytes = unsafe { core::slice::from_raw_parts(raw.as_ptr() as *const u8, size as usize) };
+    Ok(bytes.to_vec())
+}
+
+// Unseals a blob of `seal_blob`, which must carry `aad` as its additional text.
+pub(crate) fn unseal_blob(aad: &[u8], blob: &[u8]) -> SgxResult<Vec<u8>> {
+    let len = u32::try_from(blob.len()).map_err(|_| sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+    let mut raw = vec![0_u64; (blob.len() + 7) / mem::size_of::<u64>()];
+    let ptr = raw.as_mut_ptr() as *mut u8;
+    unsafe { core::ptr::copy_nonoverlapping(blob.as_ptr(), ptr, blob.len()) };
+    let sealed = unsafe { SgxInternalSealedData::from_raw_sealed_data_t(ptr as *mut sgx_sealed_data_t, len) }
+        .ok_or(sgx_status_t::SGX_ERROR_MAC_MISMATCH)?;
+    let unsealed = sealed.unseal_data()?;
+    if unsealed.get_additional_txt() != aad {
+        return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+    }
+    Ok(unsealed.get_decrypt_txt().to_vec())
+}
+
+///
+/// An ordered map from byte keys to byte values that holds only its hot nodes
+/// in enclave memory.
+///
+/// The map is a B+tree. Nodes that have not been used recently are sealed one
+/// page at a time and written to a PageStore outside the enclave, so the index
+/// can grow far beyond the EPC. Each internal node records the SHA-256 hash
+/// of the sealed page of every child, and the hash of the root page is kept in
+/// the SealedBTreeRoot returned by `flush`. Reading a page therefore detects
+/// not only tampering but also replay of an older version of the page.
+///
+/// Pages are written copy-on-write. A modified node never replaces a page of
+/// the last committed version: it moves to a fresh page, and the pages it
+/// supersedes are freed only after the next root has been committed.
+///
+/// Keys are compared as byte strings. Removal does not merge underfull nodes;
+/// only nodes that become empty are released.
+///
+pub struct SealedBTreeMap<S: PageStore> {
+    store: S,
+    nodes: BTreeMap<PageId, Node>,
+    // Pages written since the last commit, which no committed root refers to.
+    fresh: BTreeSet<PageId>,
+    // Pages of the committed version that the next commit makes unreachable.
+    superseded: Vec<PageId>,
+    root: PageId,
+    next_page: PageId,
+    len: u64,
+    order: usize,
+    max_resident: usize,
+    tick: u64,
+}
+
+impl<S: PageStore> SealedBTreeMap<S> {
+    ///
+    /// Create an empty map whose pages are written to `store`.
+    ///
+    /// Page identifiers are allocated from zero, so `store` should be empty.
+    ///
+    pub fn new(store: S) -> SealedBTreeMap<S> {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(0, Node::new_leaf());
+        SealedBTreeMap {
+            store,
+            nodes,
+            fresh: BTreeSet::new(),
+            superseded: Vec::new(),
+            root: 0,
+            next_page: 1,
+            len: 0,
+            order: SEALED_BTREE_DEFAULT_ORDER,
+            max_resident: SEALED_BTREE_DEFAULT_MAX_RESIDENT,
+            tick: 0,
+        }
+    }
+
+    ///
+    /// Reopen a map from `store` at the version described by `root`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The root page in `store` does not match `root`.
+    ///
+    pub fn open(store: S, root: SealedBTreeRoot) -> SgxResult<SealedBTreeMap<S>> {
+        let mut map = SealedBTreeMap {
+            store,
+            nodes: BTreeMap::new(),
+            fresh: BTreeSet::new(),
+            superseded: Vec::new(),
+            root: root.page,
+            next_page: root.next_page,
+            len: root.len,
+            order: SEALED_BTREE_DEFAULT_ORDER,
+            max_resident: SEALED_BTREE_DEFAULT_MAX_RESIDENT,
+            tick: 0,
+        };
+        map.load(ChildRef {
+            page: root.page,
+            hash: root.hash,
+        })?;
+        Ok(map)
+    }
+
+    ///
+    /// Set the maximum number of keys in a node. Nodes are split when they
+    /// grow beyond it. The value is clamped to at least 3.
+    ///
+    pub fn set_order(&mut self, order: usize) {
+        self.order = order.max(3);
+    }
+
+    ///
+    /// Set the number of nodes kept in enclave memory. The nodes on the path
+    /// of the current operation are always resident, so the bound may be
+    /// exceeded by the height of the tree.
+    ///
+    pub fn set_max_resident(&mut self, max_resident: usize) {
+        self.max_resident = max_resident.max(1);
+    }
+
+    ///
+    /// Get the number of entries in the map.
+    ///
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    ///
+    /// Returns true if the map contains no entries.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    ///
+    /// Get the number of nodes currently held in enclave memory.
+    ///
+    pub fn resident_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    ///
+    /// Get a reference to the underlying store.
+    ///
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    ///
+    /// Look up the value of `key`.
+    ///
+    pub fn get(&mut self, key: &[u8]) -> SgxResult<Option<Vec<u8>>> {
+        let mut id = self.root;
+        let value = loop {
+            let node = self.touch(id);
+            if node.leaf {
+                break node
+                    .keys
+                    .binary_search_by(|k| k.as_slice().cmp(key))
+                    .ok()
+                    .map(|i| node.values[i].clone());
+            }
+            let child = node.children[node.child_index(key)];
+            self.load(child)?;
+            id = child.page;
+        };
+        self.shrink()?;
+        Ok(value)
+    }
+
+    ///
+    /// Returns true if the map contains `key`.
+    ///
+    pub fn contains_key(&mut self, key: &[u8]) -> SgxResult<bool> {
+        self.get(key).map(|v| v.is_some())
+    }
+
+    ///
+    /// Insert `value` under `key`, returning the previous value.
+    ///
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) -> SgxResult<Option<Vec<u8>>> {
+        let (old, split) = self.insert_rec(self.root, key, value)?;
+        if let Some((sep, right)) = split {
+            let left = self.root;
+            let id = self.alloc_page();
+            let empty = [0; SGX_SHA256_HASH_SIZE];
+            let children = vec![
+                ChildRef { page: left, hash: empty },
+                ChildRef { page: right, hash: empty },
+            ];
+            self.nodes.insert(id, Node::new_internal(vec![sep], children));
+            self.root = id;
+        }
+        if old.is_none() {
+            self.len += 1;
+        }
+        self.shrink()?;
+        Ok(old)
+    }
+
+    ///
+    /// Remove `key`, returning its value.
+    ///
+    pub fn remove(&mut self, key: &[u8]) -> SgxResult<Option<Vec<u8>>> {
+        let old = self.remove_rec(self.root, key)?;
+        if old.is_some() {
+            self.len -= 1;
+            let root = &self.nodes[&self.root];
+            if !root.leaf && root.children.len() <= 1 {
+                let old_root = self.root;
+                match root.children.first().copied() {
+                    Some(child) => {
+                        self.load(child)?;
+                        self.root = child.page;
+                    }
+                    None => {
+                        self.root = self.alloc_page();
+                        self.nodes.insert(self.root, Node::new_leaf());
+                    }
+                }
+                self.release(old_root)?;
+            }
+        }
+        self.shrink()?;
+        Ok(old)
+    }
+
+    ///
+    /// Collect the entries whose keys fall in `range`, in key order.
+    ///
+    pub fn range<R: RangeBounds<[u8]>>(&mut self, range: R) -> SgxResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut out = Vec::new();
+        self.range_rec(self.root, &range, &mut out)?;
+        self.shrink()?;
+        Ok(out)
+    }
+
+    ///
+    /// Write every modified node to the store and return the new root.
+    ///
+    /// Modified nodes are written to fresh pages, children before their
+    /// parents, so the store never holds a parent that refers to a child page
+    /// it has not yet received. The new root is then passed to
+    /// `PageStore::commit`, and only after that are the pages of the previous
+    /// version that are no longer reachable freed. Until the commit, the
+    /// previous root still opens the previous version.
+    ///
+    /// Pages that fail to be freed are retried by the next flush.
+    ///
+    pub fn flush(&mut self) -> SgxResult<SealedBTreeRoot> {
+        let child = self.flush_rec(self.root)?;
+        self.root = child.page;
+        let root = SealedBTreeRoot {
+            page: child.page,
+            hash: child.hash,
+            next_page: self.next_page,
+            len: self.len,
+        };
+        self.store.commit(&root)?;
+        self.fresh.clear();
+        while let Some(id) = self.superseded.pop() {
+            if let Err(e) = self.store.free_page(id) {
+                self.superseded.push(id);
+                return Err(e);
+            }
+        }
+        Ok(root)
+    }
+
+    ///
+    /// Flush the map and return its root together with the store.
+    ///
+    pub fn into_inner(mut self) -> SgxResult<(SealedBTreeRoot, S)> {
+        let root = self.flush()?;
+        Ok((root, self.store))
+    }
+
+    fn alloc_page(&mut self) -> PageId {
+        let id = self.next_page;
+        self.next_page += 1;
+        id
+    }
+
+    fn touch(&mut self, id: PageId) -> &mut Node {
+        self.tick += 1;
+        let node = self.nodes.get_mut(&id).expect("node on the current path is resident");
+        node.tick = self.tick;
+        node
+    }
+
+    fn load(&mut self, child: ChildRef) -> SgxError {
+        if self.nodes.contains_key(&child.page) {
+            return Ok(());
+        }
+        let blob = self.store.read_page(child.page)?;
+        if rsgx_sha256_slice(&blob)? != child.hash {
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+        let mut node = Node::decode(&unseal_page(child.page, &blob)?)?;
+        node.hash = child.hash;
+        self.nodes.insert(child.page, node);
+        Ok(())
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn insert_rec(
+        &mut self,
+        id: PageId,
+        key: &[u8],
+        value: &[u8],
+    ) -> SgxResult<(Option<Vec<u8>>, Option<(Vec<u8>, PageId)>)> {
+        let order = self.order;
+        let node = self.touch(id);
+        node.dirty = true;
+        if node.leaf {
+            match node.keys.binary_search_by(|k| k.as_slice().cmp(key)) {
+                Ok(i) => {
+                    let old = mem::replace(&mut node.values[i], value.to_vec());
+                    return Ok((Some(old), None));
+                }
+                Err(i) => {
+                    node.keys.insert(i, key.to_vec());
+                    node.values.insert(i, value.to_vec());
+                }
+            }
+            if node.keys.len() <= order {
+                return Ok((None, None));
+            }
+            let mid = node.keys.len() / 2;
+            let mut right = Node::new_leaf();
+            right.keys = node.keys.split_off(mid);
+            right.values = node.values.split_off(mid);
+            let sep = right.keys[0].clone();
+            let right_id = self.alloc_page();
+            self.nodes.insert(right_id, right);
+            return Ok((None, Some((sep, right_id))));
+        }
+
+        let idx = node.child_index(key);
+        let child = node.children[idx];
+        self.load(child)?;
+        let (old, split) = self.insert_rec(child.page, key, value)?;
+        let (sep, right_id) = match split {
+            Some(split) => split,
+            None => return Ok((old, None)),
+        };
+        let node = self.touch(id);
+        node.keys.insert(idx, sep);
+        node.children.insert(
+            idx + 1,
+            ChildRef {
+                page: right_id,
+                hash: [0; SGX_SHA256_HASH_SIZE],
+            },
+        );
+        if node.keys.len() <= order {
+            return Ok((old, None));
+        }
+        let mid = node.keys.len() / 2;
+        let keys = node.keys.split_off(mid + 1);
+        let children = node.children.split_off(mid + 1);
+        let sep = node.keys.pop().expect("split of a non-empty node");
+        let right_id = self.alloc_page();
+        self.nodes.insert(right_id, Node::new_internal(keys, children));
+        Ok((old, Some((sep, right_id))))
+    }
+
+    fn remove_rec(&mut self, id: PageId, key: &[u8]) -> SgxResult<Option<Vec<u8>>> {
+        let node = self.touch(id);
+        if node.leaf {
+            return Ok(match node.keys.binary_search_by(|k| k.as_slice().cmp(key)) {
+                Ok(i) => {
+                    node.dirty = true;
+                    node.keys.remove(i);
+                    Some(node.values.remove(i))
+                }
+                Err(_) => None,
+            });
+        }
+
+        let idx = node.child_index(key);
+        let child = node.children[idx];
+        self.load(child)?;
+        let old = self.remove_rec(child.page, key)?;
+        if old.is_none() {
+            return Ok(None);
+        }
+        let child_empty = self.nodes[&child.page].is_empty();
+        let node = self.touch(id);
+        node.dirty = true;
+        if child_empty {
+            node.children.remove(idx);
+            if !node.keys.is_empty() {
+                node.keys.remove(idx.saturating_sub(1));
+            }
+            self.release(child.page)?;
+        }
+        Ok(old)
+    }
+
+    fn range_rec<R: RangeBounds<[u8]>>(
+        &mut self,
+        id: PageId,
+        range: &R,
+        out: &mut Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> SgxError {
+        let node = self.touch(id);
+        if node.leaf {
+            for (k, v) in node.keys.iter().zip(node.values.iter()) {
+                if range.contains(k.as_slice()) {
+                    out.push((k.clone(), v.clone()));
+                }
+            }
+            return Ok(());
+        }
+        let first = match range.start_bound() {
+            Bound::Included(start) | Bound::Excluded(start) => node.child_index(start),
+            Bound::Unbounded => 0,
+        };
+        let last = match range.end_bound() {
+            Bound::Included(end) | Bound::Excluded(end) => node.child_index(end),
+            Bound::Unbounded => node.children.len() - 1,
+        };
+        if first > last {
+            return Ok(());
+        }
+        let children: Vec<ChildRef> = node.children[first..=last].to_vec();
+        for child in children {
+            self.load(child)?;
+            self.range_rec(child.page, range, out)?;
+        }
+        Ok(())
+    }
+
+    // Writes a modified node and returns the reference its parent must hold.
+    // A node whose page belongs to the committed version moves to a fresh
+    // page, and is then resident under the new identifier.
+    fn write_node(&mut self, id: PageId) -> SgxResult<ChildRef> {
+        let node = &self.nodes[&id];
+        if !node.dirty {
+            return Ok(ChildRef { page: id, hash: node.hash });
+        }
+        let plain = node.encode();
+        let page = if node.on_disk && !self.fresh.contains(&id) {
+            self.alloc_page()
+        } else {
+            id
+        };
+        let blob = seal_page(page, &plain)?;
+        let hash = rsgx_sha256_slice(&blob)?;
+        self.store.write_page(page, &blob)?;
+        self.fresh.insert(page);
+        let mut node = self.nodes.remove(&id).expect("written node is resident");
+        node.hash = hash;
+        node.dirty = false;
+        node.on_disk = true;
+        self.nodes.insert(page, node);
+        if page != id {
+            self.superseded.push(id);
+        }
+        Ok(ChildRef { page, hash })
+    }
+
+    fn flush_rec(&mut self, id: PageId) -> SgxResult<ChildRef> {
+        let node = &self.nodes[&id];
+        if node.dirty && !node.leaf {
+            let resident: Vec<(usize, PageId)> = node
+                .children
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| self.nodes.contains_key(&c.page))
+                .map(|(i, c)| (i, c.page))
+                .collect();
+            for (i, page) in resident {
+                let child = self.flush_rec(page)?;
+                self.nodes.get_mut(&id).expect("flushed node is resident").children[i] = child;
+            }
+        }
+        self.write_node(id)
+    }
+
+    // Drops a node that is no longer referenced by the tree. Its page is freed
+    // at once if no committed root refers to it, and after the next commit
+    // otherwise.
+    fn release(&mut self, id: PageId) -> SgxError {
+        let on_disk = self.nodes.remove(&id).map_or(true, |n| n.on_disk);
+        if !on_disk {
+            return Ok(());
+        }
+        if self.fresh.remove(&id) {
+            self.store.free_page(id)
+        } else {
+            self.superseded.push(id);
+            Ok(())
+        }
+    }
+
+    // Evicts the least recently used nodes that have no resident children,
+    // writing them out first if they are modified.
+    fn shrink(&mut self) -> SgxError {
+        while self.nodes.len() > self.max_resident {
+            let mut candidates = Vec::new();
+            self.collect_evictable(self.root, &mut candidates);
+            if candidates.is_empty() {
+                break;
+            }
+            candidates.sort_unstable_by_key(|&(tick, _, _, _)| tick);
+            let excess = self.nodes.len() - self.max_resident;
+            for &(_, id, parent, idx) in candidates.iter().take(excess) {
+                let child = self.write_node(id)?;
+                self.nodes.remove(&child.page);
+                let parent = self.nodes.get_mut(&parent).expect("parent of a resident node is resident");
+                parent.children[idx] = child;
+            }
+        }
+        Ok(())
+    }
+
+    fn collect_evictable(&self, id: PageId, out: &mut Vec<(u64, PageId, PageId, usize)>) {
+        let node = &self.nodes[&id];
+        for (idx, child) in node.children.iter().enumerate() {
+            if let Some(c) = self.nodes.get(&child.page) {
+                if c.children.iter().any(|g| self.nodes.contains_key(&g.page)) {
+                    self.collect_evictable(child.page, out);
+                } else {
+                    out.push((c.tick, child.page, id, idx));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! A cache of derived seal keys.
+//!
+//! EGETKEY is by far the most expensive step of sealing or unsealing a small record. Keys are
+//! cached per key request, i.e. per key ID, policy, masks and SVNs, so repeated unsealing of
+//! the same blobs and batches sealed under one key ID derive the key only once.
+//!
+//! Cached keys are wiped when they are evicted, when the capacity is changed and when the
+//! cache is invalidated. The host should notify the enclave of TCB or SVN changes, on which
+//! the enclave calls rsgx_invalidate_seal_key_cache.
+
+use alloc::vec::Vec;
+use core::mem;
+use core::ptr;
+use core::slice;
+use sgx_tse::rsgx_get_align_key;
+use sgx_tsync::SgxSpinlock;
+use sgx_types::*;
+
+const DEFAULT_CAPACITY: usize = 32;
+
+/// The largest number of seal keys the cache holds.
+pub const SEAL_KEY_CACHE_MAX_CAPACITY: usize = 64;
+
+// The slots are allocated once, at the maximum capacity, and keys are only ever written to and
+// wiped in place, so that no copy of a key is left behind in memory freed or spared by growing,
+// shrinking or shifting a vector.
+#[derive(Clone, Copy, Default)]
+struct Slot {
+    // The time of the last use, zero if the slot is free.
+    tick: u64,
+    request: sgx_key_request_t,
+    key: sgx_align_key_128bit_t,
+}
+
+impl Slot {
+    fn wipe(&mut self) {
+        unsafe { ptr::write_volatile(self, Slot::default()) };
+    }
+}
+
+struct SealKeyCache {
+    capacity: usize,
+    tick: u64,
+    slots: Vec<Slot>,
+}
+
+impl SealKeyCache {
+    fn used(&self) -> usize {
+        self.slots.iter().filter(|s| s.tick != 0).count()
+    }
+
+    // Wipes the least recently used key.
+    fn evict(&mut self) {
+        if let Some(slot) = self
+            .slots
+            .iter_mut()
+            .filter(|s| s.tick != 0)
+            .min_by_key(|s| s.tick)
+        {
+            slot.wipe();
+        }
+    }
+}
+
+static LOCK: SgxSpinlock = SgxSpinlock::new();
+static mut CACHE: SealKeyCache = SealKeyCache {
+    capacity: DEFAULT_CAPACITY,
+    tick: 0,
+    slots: Vec::new(),
+};
+
+fn request_bytes(request: &sgx_key_request_t) -> &[u8] {
+    unsafe {
+        slice::from_raw_parts(
+            request as *const sgx_key_request_t as *const u8,
+            mem::size_of::<sgx_key_request_t>(),
+        )
+    }
+}
+
+fn lookup(request: &sgx_key_request_t) -> Option<sgx_align_key_128bit_t> {
+    let _guard = LOCK.lock();
+    let cache = unsafe { &mut *ptr::addr_of_mut!(CACHE) };
+    let bytes = request_bytes(request);
+    cache.tick += 1;
+    let tick = cache.tick;
+    let slot = cache
+        .slots
+        .iter_mut()
+        .find(|s| s.tick != 0 && request_bytes(&s.request) == bytes)?;
+    slot.tick = tick;
+    Some(slot.key)
+}
+
+fn insert(request: &sgx_key_request_t, key: &sgx_align_key_128bit_t) {
+    let _guard = LOCK.lock();
+    let cache = unsafe { &mut *ptr::addr_of_mut!(CACHE) };
+    if cache.capacity == 0 {
+        return;
+    }
+    if cache.slots.is_empty() {
+        cache.slots = vec![Slot::default(); SEAL_KEY_CACHE_MAX_CAPACITY];
+    }
+    let bytes = request_bytes(request);
+    if cache.slots.iter().any(|s| s.tick != 0 && request_bytes(&s.request) == bytes) {
+        return;
+    }
+    if cache.used() >= cache.capacity {
+        cache.evict();
+    }
+    cache.tick += 1;
+    let tick = cache.tick;
+    if let Some(slot) = cache.slots.iter_mut().find(|s| s.tick == 0) {
+        slot.request = *request;
+        slot.key = *key;
+        slot.tick = tick;
+    }
+}
+
+///
+/// Derives the seal key for `key_request`, using the cache if possible.
+///
+pub(crate) fn get_seal_key(key_request: &sgx_key_request_t) -> SgxResult<sgx_align_key_128bit_t> {
+    if let Some(key) = lookup(key_request) {
+        return Ok(key);
+    }
+    let key = rsgx_get_align_key(key_request)?;
+    insert(key_request, &key);
+    Ok(key)
+}
+
+///
+/// rsgx_prewarm_seal_key derives the seal key for `key_request` into the cache.
+///
+/// Call it while warming up the enclave, with the key requests of the blobs it is about to
+/// unseal (see `get_key_request` of the sealed data types), so that serving the first request
+/// does not wait for EGETKEY. It has no effect if the cache is disabled.
+///
+/// # Errors
+///
+/// The errors of `rsgx_get_align_key`.
+///
+pub fn rsgx_prewarm_seal_key(key_request: &sgx_key_request_t) -> SgxError {
+    get_seal_key(key_request).map(|_| ())
+}
+
+///
+/// rsgx_invalidate_seal_key_cache wipes all cached seal keys.
+///
+/// Call it when the host reports a TCB recovery or SVN change, so that no key derived for the
+/// previous TCB is used again.
+///
+pub fn rsgx_invalidate_seal_key_cache() {
+    let _guard = LOCK.lock();
+    let cache = unsafe { &mut *ptr::addr_of_mut!(CACHE) };
+    cache.slots.iter_mut().for_each(Slot::wipe);
+}
+
+///
+/// rsgx_set_seal_key_cache_capacity sets the maximum number of cached seal keys, at most
+/// `SEAL_KEY_CACHE_MAX_CAPACITY`.
+///
+/// A capacity of zero disables the cache. The least recently used keys beyond the new capacity
+/// are wiped.
+///
+pub fn rsgx_set_seal_key_cache_capacity(capacity: usize) {
+    let _guard = LOCK.lock();
+    let cache = unsafe { &mut *ptr::addr_of_mut!(CACHE) };
+    cache.capacity = capacity.min(SEAL_KEY_CACHE_MAX_CAPACITY);
+    while cache.used() > cache.capacity {
+        cache.evict();
+    }
+}
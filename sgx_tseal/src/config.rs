@@ -0,0 +1,652 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Signed dynamic configuration
+//!
+//! Runtime configuration and feature flags that an operator can change without re-signing
+//! the enclave. An update is a complete set of entries with a version number, signed with
+//! Ed25519 by one of the operator keys the enclave was built with. The enclave checks the
+//! signature and that the version is newer than that of every update applied before, so
+//! that an old update cannot be replayed.
+//!
+//! An update is applied atomically: it is checked, passed to an optional validator,
+//! recorded in the audit log and sealed, and only then does it replace the active
+//! configuration. If any step fails, the active configuration is left unchanged. The
+//! configuration it replaced is kept, and `rollback` makes it active again, for example
+//! when the new configuration turns out to be bad. Rolling back does not lower the version
+//! an update must exceed.
+//!
+//! The sealed state is bound to a `MonotonicCounter`, as the identity keyring is, so that
+//! an older configuration cannot be restored by replaying an older copy of it.
+//!
+//! The operator signs `SgxConfigUpdate::signing_payload(version, entries)` and sends the
+//! enclave the encoding of `SgxConfigUpdate::new(version, entries, operator_key, signature)`.
+
+use crate::batch::SgxSealedBlob;
+use crate::counter::MonotonicCounter;
+use crate::policy::SgxSealPolicy;
+use alloc::collections::btree_map;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::{ptr, str};
+use sgx_tcrypto::ed25519::{
+    rsgx_ed25519_verify, Ed25519PublicKey, Ed25519Signature, ED25519_PUBLIC_KEY_SIZE,
+    ED25519_SIGNATURE_SIZE,
+};
+use sgx_tcrypto::rsgx_sha256_slice;
+use sgx_types::*;
+
+const STATE_VERSION: u8 = 1;
+const UPDATE_LABEL: &[u8] = b"sgx config update v1";
+
+pub const CONFIG_KEY_MAX_SIZE: usize = 256;
+pub const CONFIG_UPDATE_MAX_SIZE: usize = 1024 * 1024;
+
+pub type SgxConfigDigest = sgx_sha256_hash_t;
+
+///
+/// Untrusted storage for the sealed configuration.
+///
+pub trait ConfigStore {
+    ///
+    /// Read the configuration last stored, or `None` at first launch.
+    ///
+    fn load(&mut self) -> SgxResult<Option<Vec<u8>>>;
+
+    ///
+    /// Replace the stored configuration with `sealed`.
+    ///
+    fn store(&mut self, sealed: &[u8]) -> SgxError;
+}
+
+///
+/// The audit log that records every change of the configuration and every refused update.
+///
+pub trait ConfigAuditLog {
+    ///
+    /// Record `event`. An error aborts the change the event records.
+    ///
+    fn record(&mut self, event: &SgxConfigEvent) -> SgxError;
+}
+
+///
+/// An entry of the audit log.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SgxConfigEvent {
+    /// An update is about to replace the configuration `from`.
+    Applied {
+        from: u64,
+        to: u64,
+        digest: SgxConfigDigest,
+        operator: Ed25519PublicKey,
+    },
+    /// An update, or the change it started, failed. `version` is 0 if the update could not
+    /// be decoded.
+    Rejected { version: u64, error: sgx_status_t },
+    /// The configuration `from` is about to be replaced by the configuration it replaced.
+    RolledBack { from: u64, to: u64 },
+}
+
+///
+/// The operator keys and how the configuration is sealed.
+///
+#[derive(Clone, Default)]
+pub struct SgxConfigPolicy {
+    operator_keys: Vec<Ed25519PublicKey>,
+    seal_policy: SgxSealPolicy,
+}
+
+impl SgxConfigPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accepts updates signed with `key`. At least one operator key is required.
+    pub fn operator_key(mut self, key: Ed25519PublicKey) -> Self {
+        if !self.operator_keys.contains(&key) {
+            self.operator_keys.push(key);
+        }
+        self
+    }
+
+    /// The policy of the seal key of the configuration. Defaults to `SgxSealPolicy::default()`.
+    pub fn seal_policy(mut self, policy: SgxSealPolicy) -> Self {
+        self.seal_policy = policy;
+        self
+    }
+
+    pub fn get_operator_keys(&self) -> &[Ed25519PublicKey] {
+        &self.operator_keys
+    }
+}
+
+///
+/// A version of the configuration.
+///
+/// Version 0 is the empty configuration the enclave starts with.
+///
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct SgxConfig {
+    version: u64,
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl SgxConfig {
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.entries.get(key).map(Vec::as_slice)
+    }
+
+    ///
+    /// Returns the value of `key` if it is valid UTF-8.
+    ///
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        str::from_utf8(self.get(key)?).ok()
+    }
+
+    ///
+    /// Returns whether the feature flag `key` is set, that is whether its value is `true` or
+    /// `1`. A missing flag is not set.
+    ///
+    pub fn flag(&self, key: &str) -> bool {
+        matches!(self.get(key), Some(b"true") | Some(b"1"))
+    }
+
+    pub fn iter(&self) -> btree_map::Iter<'_, String, Vec<u8>> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    ///
+    /// The SHA-256 digest of the signing payload of this configuration.
+    ///
+    pub fn digest(&self) -> SgxResult<SgxConfigDigest> {
+        rsgx_sha256_slice(&SgxConfigUpdate::signing_payload(
+            self.version,
+            &self.entries,
+        ))
+    }
+}
+
+///
+/// A signed update of the configuration.
+///
+#[derive(Clone)]
+pub struct SgxConfigUpdate {
+    config: SgxConfig,
+    operator: Ed25519PublicKey,
+    signature: Ed25519Signature,
+}
+
+impl SgxConfigUpdate {
+    pub fn new(
+        version: u64,
+        entries: BTreeMap<String, Vec<u8>>,
+        operator: Ed25519PublicKey,
+        signature: Ed25519Signature,
+    ) -> Self {
+        SgxConfigUpdate {
+            config: SgxConfig { version, entries },
+            operator,
+            signature,
+        }
+    }
+
+    ///
+    /// The message an operator signs to publish `entries` as version `version`.
+    ///
+    pub fn signing_payload(version: u64, entries: &BTreeMap<String, Vec<u8>>) -> Vec<u8> {
+        let mut out = UPDATE_LABEL.to_vec();
+        out.extend_from_slice(&version.to_le_bytes());
+        encode_entries(&mut out, entries);
+        out
+    }
+
+    ///
+    /// Decodes an update. The signature is not checked.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The update is malformed, longer than `CONFIG_UPDATE_MAX_SIZE`, or has an empty,
+    /// overlong, duplicate or unsorted key, or a key that is not UTF-8.
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> SgxResult<Self> {
+        if bytes.len() > CONFIG_UPDATE_MAX_SIZE {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let mut bytes = bytes;
+        let config = decode_config(&mut bytes)?;
+        let operator = take(&mut bytes, ED25519_PUBLIC_KEY_SIZE)?
+            .try_into()
+            .unwrap();
+        let signature = take(&mut bytes, ED25519_SIGNATURE_SIZE)?
+            .try_into()
+            .unwrap();
+        if !bytes.is_empty() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        Ok(SgxConfigUpdate {
+            config,
+            operator,
+            signature,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_config(&mut out, &self.config);
+        out.extend_from_slice(&self.operator);
+        out.extend_from_slice(&self.signature);
+        out
+    }
+
+    pub fn version(&self) -> u64 {
+        self.config.version
+    }
+
+    pub fn operator(&self) -> &Ed25519PublicKey {
+        &self.operator
+    }
+
+    pub fn config(&self) -> &SgxConfig {
+        &self.config
+    }
+
+    fn verify(&self, policy: &SgxConfigPolicy) -> SgxError {
+        if !policy.operator_keys.contains(&self.operator) {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_SIGNATURE);
+        }
+        let payload = SgxConfigUpdate::signing_payload(self.config.version, &self.config.entries);
+        if !rsgx_ed25519_verify(&self.operator, &payload, &self.signature)? {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_SIGNATURE);
+        }
+        Ok(())
+    }
+}
+
+///
+/// A sealed, rollback-protected configuration that changes only through signed updates.
+///
+pub struct SgxDynamicConfig<S: ConfigStore, C: MonotonicCounter, A: ConfigAuditLog> {
+    store: S,
+    counter: C,
+    audit: A,
+    policy: SgxConfigPolicy,
+    version: u64,
+    latest: u64,
+    current: SgxConfig,
+    previous: Option<SgxConfig>,
+}
+
+impl<S: ConfigStore, C: MonotonicCounter, A: ConfigAuditLog> SgxDynamicConfig<S, C, A> {
+    ///
+    /// Opens the configuration kept in `store`, or starts with the empty configuration at
+    /// first launch.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The policy has no operator key, or the stored configuration is malformed.
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The stored configuration is not the latest one, was not sealed by this enclave or
+    /// signer, or has been modified; or the store is empty although a configuration was
+    /// stored before.
+    ///
+    /// Errors of the store, the counter and unsealing are passed on.
+    ///
+    pub fn open(store: S, counter: C, audit: A, policy: SgxConfigPolicy) -> SgxResult<Self> {
+        if policy.operator_keys.is_empty() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let mut config = SgxDynamicConfig {
+            store,
+            counter,
+            audit,
+            policy,
+            version: 0,
+            latest: 0,
+            current: SgxConfig::default(),
+            previous: None,
+        };
+        let current = config.counter.read()?;
+        match config.store.load()? {
+            Some(sealed) => {
+                let blob = SgxSealedBlob::from_bytes(&sealed)
+                    .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+                let mut plaintext = blob.unseal()?;
+                let decoded = config.decode(&plaintext);
+                wipe(&mut plaintext);
+                decoded?;
+
+                if config.version == current.wrapping_add(1) {
+                    config.counter.increment()?;
+                } else if config.version != current {
+                    return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+                }
+            }
+            None => {
+                // A missing configuration after the counter has moved is a rollback to
+                // before the first launch.
+                if current != 0 {
+                    return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+                }
+                config.version = current;
+                config.persist()?;
+            }
+        }
+        Ok(config)
+    }
+
+    ///
+    /// The active configuration.
+    ///
+    pub fn config(&self) -> &SgxConfig {
+        &self.current
+    }
+
+    ///
+    /// The configuration the active one replaced, which `rollback` restores.
+    ///
+    pub fn previous(&self) -> Option<&SgxConfig> {
+        self.previous.as_ref()
+    }
+
+    ///
+    /// The version of the newest update applied. The next update must be newer.
+    ///
+    pub fn latest_version(&self) -> u64 {
+        self.latest
+    }
+
+    ///
+    /// Applies the encoded, signed update `update`.
+    ///
+    pub fn apply(&mut self, update: &[u8]) -> SgxResult<&SgxConfig> {
+        self.apply_with(update, |_| Ok(()))
+    }
+
+    ///
+    /// Applies the encoded, signed update `update` if `validate` accepts the configuration
+    /// it carries.
+    ///
+    /// # Description
+    ///
+    /// The update is decoded, its signature and version are checked and `validate` is
+    /// called. Then an `Applied` event is recorded in the audit log, the new configuration
+    /// is sealed and stored, and it becomes the active configuration. If any step fails,
+    /// a `Rejected` event is recorded, as far as the audit log allows, and the active
+    /// configuration is left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The update is malformed.
+    ///
+    /// **SGX_ERROR_INVALID_SIGNATURE**
+    ///
+    /// The update is not signed by an operator key of the policy, or its signature is
+    /// invalid.
+    ///
+    /// **SGX_ERROR_INVALID_STATE**
+    ///
+    /// The update is not newer than the newest update applied.
+    ///
+    /// Errors of `validate`, of the audit log, of the store and of the counter are passed
+    /// on.
+    ///
+    pub fn apply_with<F>(&mut self, update: &[u8], validate: F) -> SgxResult<&SgxConfig>
+    where
+        F: FnOnce(&SgxConfig) -> SgxError,
+    {
+        let update = match SgxConfigUpdate::from_bytes(update) {
+            Ok(update) => update,
+            Err(e) => return Err(self.reject(0, e)),
+        };
+        let version = update.version();
+        if let Err(e) = self.check(&update).and_then(|_| validate(&update.config)) {
+            return Err(self.reject(version, e));
+        }
+        let digest = match update.config.digest() {
+            Ok(digest) => digest,
+            Err(e) => return Err(self.reject(version, e)),
+        };
+        self.audit.record(&SgxConfigEvent::Applied {
+            from: self.current.version,
+            to: version,
+            digest,
+            operator: update.operator,
+        })?;
+
+        let replaced = core::mem::replace(&mut self.current, update.config);
+        let previous = self.previous.replace(replaced);
+        let latest = self.latest;
+        self.latest = version;
+        if let Err(e) = self.persist() {
+            self.current = self.previous.take().unwrap();
+            self.previous = previous;
+            self.latest = latest;
+            return Err(self.reject(version, e));
+        }
+        Ok(&self.current)
+    }
+
+    ///
+    /// Makes the configuration the active one replaced active again.
+    ///
+    /// # Description
+    ///
+    /// A `RolledBack` event is recorded in the audit log, then the change is sealed and
+    /// stored. Only one step can be rolled back: afterwards there is no previous
+    /// configuration until the next update. The version the next update must exceed is
+    /// not lowered.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_STATE**
+    ///
+    /// There is no previous configuration.
+    ///
+    /// Errors of the audit log, the store and the counter are passed on. The active
+    /// configuration is then left unchanged.
+    ///
+    pub fn rollback(&mut self) -> SgxResult<&SgxConfig> {
+        let to = match self.previous {
+            Some(ref previous) => previous.version,
+            None => return Err(sgx_status_t::SGX_ERROR_INVALID_STATE),
+        };
+        self.audit.record(&SgxConfigEvent::RolledBack {
+            from: self.current.version,
+            to,
+        })?;
+
+        let previous = self.previous.take().unwrap();
+        let replaced = core::mem::replace(&mut self.current, previous);
+        if let Err(e) = self.persist() {
+            self.previous = Some(core::mem::replace(&mut self.current, replaced));
+            return Err(e);
+        }
+        Ok(&self.current)
+    }
+
+    ///
+    /// Closes the configuration, returning its store, counter and audit log.
+    ///
+    pub fn into_parts(self) -> (S, C, A) {
+        let SgxDynamicConfig {
+            store,
+            counter,
+            audit,
+            ..
+        } = self;
+        (store, counter, audit)
+    }
+
+    fn check(&self, update: &SgxConfigUpdate) -> SgxError {
+        update.verify(&self.policy)?;
+        if update.version() <= self.latest {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_STATE);
+        }
+        Ok(())
+    }
+
+    // Records a refused update and returns the error to pass on. A failure to record it
+    // does not hide the original error.
+    fn reject(&mut self, version: u64, error: sgx_status_t) -> sgx_status_t {
+        let _ = self
+            .audit
+            .record(&SgxConfigEvent::Rejected { version, error });
+        error
+    }
+
+    fn persist(&mut self) -> SgxError {
+        let version = self.version + 1;
+        let mut plaintext = self.encode(version);
+        let sealed = SgxSealedBlob::seal_batch_policy(&self.policy.seal_policy, &[&plaintext]);
+        wipe(&mut plaintext);
+        let blob = sealed?.pop().ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)?;
+
+        self.store.store(&blob.to_bytes())?;
+        let current = self.counter.increment()?;
+        if current != version {
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+        self.version = version;
+        Ok(())
+    }
+
+    fn encode(&self, version: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.push(STATE_VERSION);
+        out.extend_from_slice(&version.to_le_bytes());
+        out.extend_from_slice(&self.latest.to_le_bytes());
+        encode_config(&mut out, &self.current);
+        match self.previous {
+            Some(ref previous) => {
+                out.push(1);
+                encode_config(&mut out, previous);
+            }
+            None => out.push(0),
+        }
+        out
+    }
+
+    fn decode(&mut self, bytes: &[u8]) -> SgxError {
+        let mut bytes = bytes;
+        if take(&mut bytes, 1)? != [STATE_VERSION] {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        self.version = u64_of(&mut bytes)?;
+        self.latest = u64_of(&mut bytes)?;
+        self.current = decode_config(&mut bytes)?;
+        self.previous = match take(&mut bytes, 1)?[0] {
+            0 => None,
+            1 => Some(decode_config(&mut bytes)?),
+            _ => return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
+        };
+        if !bytes.is_empty() || self.current.version > self.latest {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        Ok(())
+    }
+}
+
+fn encode_entries(out: &mut Vec<u8>, entries: &BTreeMap<String, Vec<u8>>) {
+    out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for (key, value) in entries.iter() {
+        out.extend_from_slice(&(key.len() as u16).to_le_bytes());
+        out.extend_from_slice(key.as_bytes());
+        out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        out.extend_from_slice(value);
+    }
+}
+
+fn encode_config(out: &mut Vec<u8>, config: &SgxConfig) {
+    out.extend_from_slice(&config.version.to_le_bytes());
+    encode_entries(out, &config.entries);
+}
+
+fn decode_config(bytes: &mut &[u8]) -> SgxResult<SgxConfig> {
+    let version = u64_of(bytes)?;
+    let count = u32_of(bytes)?;
+    let mut entries = BTreeMap::new();
+    for _ in 0..count {
+        let key_len = u16_of(bytes)? as usize;
+        if key_len == 0 || key_len > CONFIG_KEY_MAX_SIZE {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let key = str::from_utf8(take(bytes, key_len)?)
+            .map_err(|_| sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+        let value_len = u32_of(bytes)? as usize;
+        let value = take(bytes, value_len)?;
+        // Keys are strictly increasing, so that every configuration has one encoding.
+        if entries
+            .keys()
+            .next_back()
+            .map_or(false, |last: &String| last.as_str() >= key)
+        {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        entries.insert(String::from(key), value.to_vec());
+    }
+    Ok(SgxConfig { version, entries })
+}
+
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> SgxResult<&'a [u8]> {
+    if bytes.len() < n {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    let (head, tail) = bytes.split_at(n);
+    *bytes = tail;
+    Ok(head)
+}
+
+fn u16_of(bytes: &mut &[u8]) -> SgxResult<u16> {
+    Ok(u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap()))
+}
+
+fn u32_of(bytes: &mut &[u8]) -> SgxResult<u32> {
+    Ok(u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()))
+}
+
+fn u64_of(bytes: &mut &[u8]) -> SgxResult<u64> {
+    Ok(u64::from_le_bytes(take(bytes, 8)?.try_into().unwrap()))
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
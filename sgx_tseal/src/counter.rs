@@ -0,0 +1,485 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Monotonic counters
+//!
+//! The SDK no longer ships platform-service counters, so rollback protection needs a
+//! counter the enclave can trust from somewhere else. Three backends implement
+//! `MonotonicCounter`:
+//!
+//! * `SgxPseCounter`, the platform-service counters of SDKs before 2.8, behind the `pse`
+//!   feature.
+//! * `SgxRemoteCounter`, a client of a counter service reached over an attested channel.
+//!   Every request carries a fresh nonce and every response is authenticated with the
+//!   session key, so the host can neither forge nor replay a response.
+//! * `SgxQuorumCounter`, a counter kept in sealed records on several untrusted replicas,
+//!   for example NVRAM or files on different disks or hosts. An increment must reach a
+//!   majority of the replicas and a read takes the highest value of a majority, so that a
+//!   host which rolls back fewer than half of the replicas is not noticed by the counter
+//!   and one which rolls back more cannot answer at all.
+
+use crate::batch::SgxSealedBlob;
+use crate::policy::SgxSealPolicy;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::ptr;
+use sgx_tcrypto::rsgx_rijndael128_cmac_slice;
+use sgx_trts::memeq::ConsttimeMemEq;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+///
+/// A persistent counter that can only move forward.
+///
+/// Sealed state that embeds the counter value at the time it was sealed is fresh
+/// exactly when that value is the current one, which is how the state is protected
+/// against rollback to an older sealed copy.
+///
+pub trait MonotonicCounter {
+    ///
+    /// Returns the current value.
+    ///
+    fn read(&mut self) -> SgxResult<u64>;
+
+    ///
+    /// Increments the counter and returns the new value.
+    ///
+    fn increment(&mut self) -> SgxResult<u64>;
+}
+
+#[cfg(feature = "pse")]
+pub use self::pse::{sgx_mc_uuid_t, SgxPseCounter};
+
+#[cfg(feature = "pse")]
+mod pse {
+    use super::MonotonicCounter;
+    use sgx_types::*;
+
+    // The counters were removed from the SDK headers with the platform services in 2.8.
+    pub const SGX_MC_UUID_COUNTER_ID_SIZE: usize = 3;
+    pub const SGX_MC_UUID_NONCE_SIZE: usize = 13;
+
+    #[repr(C, packed)]
+    #[derive(Clone, Copy, Default)]
+    pub struct sgx_mc_uuid_t {
+        pub counter_id: [uint8_t; SGX_MC_UUID_COUNTER_ID_SIZE],
+        pub nonce: [uint8_t; SGX_MC_UUID_NONCE_SIZE],
+    }
+
+    #[link(name = "sgx_tservice")]
+    extern "C" {
+        fn sgx_create_pse_session() -> sgx_status_t;
+        fn sgx_close_pse_session() -> sgx_status_t;
+        fn sgx_create_monotonic_counter(
+            counter_uuid: *mut sgx_mc_uuid_t,
+            counter_value: *mut uint32_t,
+        ) -> sgx_status_t;
+        fn sgx_destroy_monotonic_counter(counter_uuid: *const sgx_mc_uuid_t) -> sgx_status_t;
+        fn sgx_increment_monotonic_counter(
+            counter_uuid: *const sgx_mc_uuid_t,
+            counter_value: *mut uint32_t,
+        ) -> sgx_status_t;
+        fn sgx_read_monotonic_counter(
+            counter_uuid: *const sgx_mc_uuid_t,
+            counter_value: *mut uint32_t,
+        ) -> sgx_status_t;
+    }
+
+    fn in_session<F>(f: F) -> SgxResult<u32>
+    where
+        F: FnOnce(*mut uint32_t) -> sgx_status_t,
+    {
+        let ret = unsafe { sgx_create_pse_session() };
+        if ret != sgx_status_t::SGX_SUCCESS {
+            return Err(ret);
+        }
+        let mut value: uint32_t = 0;
+        let ret = f(&mut value);
+        unsafe { sgx_close_pse_session() };
+        match ret {
+            sgx_status_t::SGX_SUCCESS => Ok(value),
+            _ => Err(ret),
+        }
+    }
+
+    ///
+    /// A counter of the platform services, available with SDKs before 2.8 on platforms
+    /// that have the Management Engine counters.
+    ///
+    /// The counter is 32 bits wide. Only the enclave that created it, identified by its
+    /// signer, can use it.
+    ///
+    pub struct SgxPseCounter {
+        uuid: sgx_mc_uuid_t,
+    }
+
+    impl SgxPseCounter {
+        ///
+        /// Creates a counter with the value 0.
+        ///
+        /// # Errors
+        ///
+        /// **SGX_ERROR_SERVICE_UNAVAILABLE**
+        ///
+        /// The platform services are not available.
+        ///
+        /// **SGX_ERROR_MC_USED_UP**, **SGX_ERROR_MC_OVER_QUOTA**
+        ///
+        /// No more counters can be created.
+        ///
+        pub fn create() -> SgxResult<SgxPseCounter> {
+            let mut uuid = sgx_mc_uuid_t::default();
+            in_session(|value| unsafe { sgx_create_monotonic_counter(&mut uuid, value) })?;
+            Ok(SgxPseCounter { uuid })
+        }
+
+        ///
+        /// Opens the counter `uuid`, which was returned by `uuid` after `create`.
+        ///
+        pub fn open(uuid: sgx_mc_uuid_t) -> SgxPseCounter {
+            SgxPseCounter { uuid }
+        }
+
+        pub fn uuid(&self) -> sgx_mc_uuid_t {
+            self.uuid
+        }
+
+        ///
+        /// Destroys the counter. Further operations on its UUID fail with
+        /// **SGX_ERROR_MC_NOT_FOUND**.
+        ///
+        pub fn destroy(self) -> SgxError {
+            in_session(|_| unsafe { sgx_destroy_monotonic_counter(&self.uuid) }).map(|_| ())
+        }
+    }
+
+    impl MonotonicCounter for SgxPseCounter {
+        fn read(&mut self) -> SgxResult<u64> {
+            in_session(|value| unsafe { sgx_read_monotonic_counter(&self.uuid, value) })
+                .map(u64::from)
+        }
+
+        fn increment(&mut self) -> SgxResult<u64> {
+            in_session(|value| unsafe { sgx_increment_monotonic_counter(&self.uuid, value) })
+                .map(u64::from)
+        }
+    }
+}
+
+pub const COUNTER_ID_SIZE: usize = 16;
+pub type SgxCounterId = [u8; COUNTER_ID_SIZE];
+
+const NONCE_SIZE: usize = 16;
+const OP_READ: u8 = 0x01;
+const OP_INCREMENT: u8 = 0x02;
+const OP_RESPONSE: u8 = 0x80;
+const REQUEST_SIZE: usize = 1 + COUNTER_ID_SIZE + NONCE_SIZE;
+const RESPONSE_SIZE: usize = REQUEST_SIZE + 8;
+
+///
+/// The channel to a remote counter service.
+///
+/// The channel is set up by the caller, who attests the service and agrees on a session
+/// key with it; the transport itself may be untrusted.
+///
+pub trait CounterTransport {
+    ///
+    /// Sends `request` to the service and returns its response.
+    ///
+    fn exchange(&mut self, request: &[u8]) -> SgxResult<Vec<u8>>;
+}
+
+///
+/// A counter kept by a remote, attested counter service.
+///
+/// # Description
+///
+/// A request is `op || counter ID || nonce || CMAC`, where `op` is 1 for a read and 2 for
+/// an increment, the nonce is 16 random bytes and the CMAC is taken with the session key
+/// over the preceding bytes. The service answers `op | 0x80 || counter ID || nonce ||
+/// value || CMAC`, with the value as 8 big-endian bytes.
+///
+/// The client also remembers the last value it saw, and refuses a service whose counter
+/// moves backwards.
+///
+pub struct SgxRemoteCounter<T: CounterTransport> {
+    transport: T,
+    key: sgx_cmac_128bit_key_t,
+    counter_id: SgxCounterId,
+    last: u64,
+}
+
+impl<T: CounterTransport> SgxRemoteCounter<T> {
+    ///
+    /// Creates a client of the counter `counter_id`, authenticated with `session_key`.
+    ///
+    pub fn new(
+        transport: T,
+        session_key: &sgx_cmac_128bit_key_t,
+        counter_id: SgxCounterId,
+    ) -> SgxRemoteCounter<T> {
+        SgxRemoteCounter {
+            transport,
+            key: *session_key,
+            counter_id,
+            last: 0,
+        }
+    }
+
+    pub fn counter_id(&self) -> &SgxCounterId {
+        &self.counter_id
+    }
+
+    fn call(&mut self, op: u8) -> SgxResult<u64> {
+        let mut nonce = [0_u8; NONCE_SIZE];
+        rsgx_read_rand(&mut nonce)?;
+
+        let mut request = Vec::with_capacity(REQUEST_SIZE + SGX_CMAC_MAC_SIZE);
+        request.push(op);
+        request.extend_from_slice(&self.counter_id);
+        request.extend_from_slice(&nonce);
+        let mac = rsgx_rijndael128_cmac_slice(&self.key, &request)?;
+        request.extend_from_slice(&mac);
+
+        let response = self.transport.exchange(&request)?;
+        if response.len() != RESPONSE_SIZE + SGX_CMAC_MAC_SIZE {
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+        let (body, mac) = response.split_at(RESPONSE_SIZE);
+        let expected = rsgx_rijndael128_cmac_slice(&self.key, body)?;
+        if !expected[..].consttime_memeq(mac)
+            || body[0] != op | OP_RESPONSE
+            || body[1..REQUEST_SIZE] != request[1..REQUEST_SIZE]
+        {
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+
+        let value = u64::from_be_bytes(body[REQUEST_SIZE..].try_into().unwrap());
+        let fresh = if op == OP_INCREMENT {
+            value > self.last
+        } else {
+            value >= self.last
+        };
+        if !fresh {
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+        self.last = value;
+        Ok(value)
+    }
+}
+
+impl<T: CounterTransport> MonotonicCounter for SgxRemoteCounter<T> {
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The response is not authentic, does not answer this request, or goes back in time.
+    ///
+    /// Errors of the transport are passed on.
+    ///
+    fn read(&mut self) -> SgxResult<u64> {
+        self.call(OP_READ)
+    }
+
+    fn increment(&mut self) -> SgxResult<u64> {
+        self.call(OP_INCREMENT)
+    }
+}
+
+impl<T: CounterTransport> Drop for SgxRemoteCounter<T> {
+    fn drop(&mut self) {
+        unsafe { ptr::write_volatile(&mut self.key, sgx_cmac_128bit_key_t::default()) };
+    }
+}
+
+const RECORD_VERSION: u8 = 1;
+const RECORD_SIZE: usize = 1 + COUNTER_ID_SIZE + 8;
+
+///
+/// Untrusted storage for one replica of a quorum counter.
+///
+pub trait CounterReplica {
+    ///
+    /// Read the record last stored, or `None` if there is none.
+    ///
+    fn load(&mut self) -> SgxResult<Option<Vec<u8>>>;
+
+    ///
+    /// Replace the stored record with `sealed`.
+    ///
+    fn store(&mut self, sealed: &[u8]) -> SgxError;
+}
+
+///
+/// A counter kept on a quorum of untrusted replicas.
+///
+/// # Description
+///
+/// Each replica holds the counter value, sealed together with the counter ID so that the
+/// records of different counters cannot be swapped. An increment seals the next value and
+/// stores it on every replica; it succeeds once a majority has stored it. A read loads
+/// every replica and needs a majority of valid answers, of which it takes the highest.
+/// Since any two majorities share a replica, the read sees the last successful increment.
+///
+/// A replica that fails, or holds a record that does not unseal or belongs to another
+/// counter, does not answer. An empty replica answers 0.
+///
+/// An increment that fails after reaching some replicas may still show in later reads.
+///
+pub struct SgxQuorumCounter<R: CounterReplica> {
+    replicas: Vec<R>,
+    counter_id: SgxCounterId,
+    policy: SgxSealPolicy,
+    last: u64,
+}
+
+impl<R: CounterReplica> SgxQuorumCounter<R> {
+    ///
+    /// Creates a counter over `replicas`, sealing its records with `policy`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `replicas` is empty.
+    ///
+    pub fn new(
+        replicas: Vec<R>,
+        counter_id: SgxCounterId,
+        policy: SgxSealPolicy,
+    ) -> SgxResult<SgxQuorumCounter<R>> {
+        if replicas.is_empty() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        Ok(SgxQuorumCounter {
+            replicas,
+            counter_id,
+            policy,
+            last: 0,
+        })
+    }
+
+    pub fn counter_id(&self) -> &SgxCounterId {
+        &self.counter_id
+    }
+
+    ///
+    /// The number of replicas that must answer a read or store an increment.
+    ///
+    pub fn quorum(&self) -> usize {
+        self.replicas.len() / 2 + 1
+    }
+
+    ///
+    /// Returns the replicas.
+    ///
+    pub fn into_replicas(self) -> Vec<R> {
+        self.replicas
+    }
+
+    fn decode(&self, sealed: &[u8]) -> Option<u64> {
+        let plaintext = SgxSealedBlob::from_bytes(sealed)?.unseal().ok()?;
+        if plaintext.len() != RECORD_SIZE
+            || plaintext[0] != RECORD_VERSION
+            || plaintext[1..1 + COUNTER_ID_SIZE] != self.counter_id
+        {
+            return None;
+        }
+        Some(u64::from_le_bytes(
+            plaintext[1 + COUNTER_ID_SIZE..].try_into().unwrap(),
+        ))
+    }
+
+    fn collect(&mut self) -> SgxResult<u64> {
+        let mut answers = 0;
+        let mut value = 0;
+        for i in 0..self.replicas.len() {
+            let answer = match self.replicas[i].load() {
+                Ok(Some(sealed)) => self.decode(&sealed),
+                Ok(None) => Some(0),
+                Err(_) => None,
+            };
+            if let Some(v) = answer {
+                answers += 1;
+                value = value.max(v);
+            }
+        }
+        if answers < self.quorum() {
+            return Err(sgx_status_t::SGX_ERROR_SERVICE_UNAVAILABLE);
+        }
+        if value < self.last {
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+        self.last = value;
+        Ok(value)
+    }
+}
+
+impl<R: CounterReplica> MonotonicCounter for SgxQuorumCounter<R> {
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_SERVICE_UNAVAILABLE**
+    ///
+    /// Fewer than a quorum of replicas answered.
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The replicas went back to a value below one this counter has already returned.
+    ///
+    fn read(&mut self) -> SgxResult<u64> {
+        self.collect()
+    }
+
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_SERVICE_UNAVAILABLE**
+    ///
+    /// Fewer than a quorum of replicas answered the read or stored the new value.
+    ///
+    /// Errors of `read` and of sealing are passed on.
+    ///
+    fn increment(&mut self) -> SgxResult<u64> {
+        let next = self
+            .collect()?
+            .checked_add(1)
+            .ok_or(sgx_status_t::SGX_ERROR_MC_USED_UP)?;
+
+        let mut record = Vec::with_capacity(RECORD_SIZE);
+        record.push(RECORD_VERSION);
+        record.extend_from_slice(&self.counter_id);
+        record.extend_from_slice(&next.to_le_bytes());
+        let blob = SgxSealedBlob::seal_batch_policy(&self.policy, &[&record])?
+            .pop()
+            .ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)?;
+        let sealed = blob.to_bytes();
+
+        let stored = self
+            .replicas
+            .iter_mut()
+            .map(|replica| replica.store(&sealed))
+            .filter(Result::is_ok)
+            .count();
+        if stored < self.quorum() {
+            return Err(sgx_status_t::SGX_ERROR_SERVICE_UNAVAILABLE);
+        }
+        self.last = next;
+        Ok(next)
+    }
+}
@@ -0,0 +1,410 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Self-describing sealed envelopes.
+//!
+//! An envelope is a versioned blob holding everything needed to unseal it again: the key
+//! policy and SVNs of its key request, the additional authenticated data and the encrypted
+//! payload. The whole header and the AAD are authenticated together with the payload.
+//!
+//! Layout, integers little-endian:
+//!
+//! | field           | size |
+//! |-----------------|------|
+//! | magic `SGXE`    | 4    |
+//! | version         | 1    |
+//! | key policy      | 2    |
+//! | ISVSVN          | 2    |
+//! | CONFIGSVN       | 2    |
+//! | CPUSVN          | 16   |
+//! | attribute mask  | 16   |
+//! | misc mask       | 4    |
+//! | key ID          | 32   |
+//! | IV              | 12   |
+//! | AAD length      | 4    |
+//! | payload length  | 4    |
+//! | AAD             |      |
+//! | payload         |      |
+//! | tag             | 16   |
+
+use crate::batch::{fresh_key_request, wipe};
+use crate::cache::get_seal_key;
+use crate::guard::rsgx_check_unseal_guard;
+use crate::policy::SgxSealPolicy;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::ptr;
+use sgx_tcrypto::*;
+use sgx_trts::trts::{rsgx_read_rand, rsgx_slice_is_within_enclave};
+use sgx_tse::rsgx_self_report;
+use sgx_types::*;
+
+const ENVELOPE_MAGIC: [u8; 4] = *b"SGXE";
+/// The envelope format version written by `SgxSealedEnvelope`.
+pub const SGX_ENVELOPE_VERSION: u8 = 1;
+const ENVELOPE_HEADER_SIZE: usize = 4 // magic
+    + 1 // version
+    + 2 // key policy
+    + 2 // ISVSVN
+    + 2 // CONFIGSVN
+    + SGX_CPUSVN_SIZE
+    + 16 // attribute mask
+    + 4 // misc mask
+    + SGX_KEYID_SIZE
+    + SGX_SEAL_IV_SIZE
+    + 4 // AAD length
+    + 4; // payload length
+
+///
+/// The policy of a sealed envelope.
+///
+/// It combines a key derivation policy with an optional minimum ISVSVN. An envelope sealed
+/// with a minimum ISVSVN derives its key for that ISVSVN rather than the current one, so that
+/// any version of the enclave from the minimum on can unseal it.
+///
+#[derive(Clone, Copy, Default)]
+pub struct SgxEnvelopePolicy {
+    seal_policy: SgxSealPolicy,
+    isv_svn_min: Option<sgx_isv_svn_t>,
+}
+
+impl SgxEnvelopePolicy {
+    /// Binds the key to MRSIGNER, with the default masks.
+    pub fn mr_signer() -> Self {
+        Self::from_seal_policy(SgxSealPolicy::new().mr_signer())
+    }
+
+    /// Binds the key to MRENCLAVE, with the default masks.
+    pub fn mr_enclave() -> Self {
+        Self::from_seal_policy(SgxSealPolicy::new().mr_enclave())
+    }
+
+    pub fn from_seal_policy(seal_policy: SgxSealPolicy) -> Self {
+        SgxEnvelopePolicy {
+            seal_policy,
+            isv_svn_min: None,
+        }
+    }
+
+    pub fn with_isv_svn_min(mut self, isv_svn: sgx_isv_svn_t) -> Self {
+        self.isv_svn_min = Some(isv_svn);
+        self
+    }
+
+    pub fn get_seal_policy(&self) -> &SgxSealPolicy {
+        &self.seal_policy
+    }
+
+    pub fn get_isv_svn_min(&self) -> Option<sgx_isv_svn_t> {
+        self.isv_svn_min
+    }
+
+    // The ISVSVN envelopes are sealed for under this policy.
+    fn target_isv_svn(&self, report: &sgx_report_t) -> sgx_isv_svn_t {
+        self.isv_svn_min.unwrap_or(report.body.isv_svn)
+    }
+}
+
+///
+/// A sealed, versioned and self-describing envelope.
+///
+#[derive(Clone)]
+pub struct SgxSealedEnvelope {
+    key_request: sgx_key_request_t,
+    iv: [u8; SGX_SEAL_IV_SIZE],
+    aad: Vec<u8>,
+    ciphertext: Vec<u8>,
+    tag: [u8; SGX_SEAL_TAG_SIZE],
+}
+
+impl SgxSealedEnvelope {
+    ///
+    /// Seals `data` under `policy`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `data` is larger than 4 GB or not within the enclave, or the key policy is invalid.
+    ///
+    /// **SGX_ERROR_INVALID_ISVSVN**
+    ///
+    /// The minimum ISVSVN of the policy is above the ISVSVN of the enclave.
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// The seal key could not be derived, or a crypto library failure occurred.
+    ///
+    pub fn seal(data: &[u8], policy: &SgxEnvelopePolicy) -> SgxResult<SgxSealedEnvelope> {
+        Self::seal_with_aad(data, &[], policy)
+    }
+
+    ///
+    /// Seals `data` under `policy`, authenticating `aad` with it.
+    ///
+    /// The AAD is stored in the clear.
+    ///
+    pub fn seal_with_aad(
+        data: &[u8],
+        aad: &[u8],
+        policy: &SgxEnvelopePolicy,
+    ) -> SgxResult<SgxSealedEnvelope> {
+        if data.len() >= u32::MAX as usize
+            || aad.len() >= u32::MAX as usize
+            || (!data.is_empty() && !rsgx_slice_is_within_enclave(data))
+        {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+
+        let report = rsgx_self_report();
+        let isv_svn = policy.target_isv_svn(&report);
+        if isv_svn > report.body.isv_svn {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_ISVSVN);
+        }
+        let mut key_request = fresh_key_request(&policy.seal_policy)?;
+        key_request.isv_svn = isv_svn;
+
+        let mut envelope = SgxSealedEnvelope {
+            key_request,
+            iv: [0_u8; SGX_SEAL_IV_SIZE],
+            aad: aad.to_vec(),
+            ciphertext: vec![0_u8; data.len()],
+            tag: [0_u8; SGX_SEAL_TAG_SIZE],
+        };
+        rsgx_read_rand(&mut envelope.iv)?;
+
+        let mut seal_key = get_seal_key(&envelope.key_request).map_err(|ret| {
+            if ret != sgx_status_t::SGX_ERROR_OUT_OF_MEMORY {
+                sgx_status_t::SGX_ERROR_UNEXPECTED
+            } else {
+                ret
+            }
+        })?;
+        let authenticated = envelope.authenticated_data();
+        let result = rsgx_rijndael128GCM_encrypt(
+            &seal_key.key,
+            data,
+            &envelope.iv,
+            &authenticated,
+            &mut envelope.ciphertext,
+            &mut envelope.tag,
+        );
+        wipe(&mut seal_key);
+        result.map(|_| envelope)
+    }
+
+    ///
+    /// Unseals the payload. The AAD, authenticated along with it, is returned by `get_aad`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_ATTRIBUTE**
+    ///
+    /// The enclave holds production secrets and is debuggable, see `rsgx_set_secret_class`.
+    ///
+    /// **SGX_ERROR_INVALID_CPUSVN** or **SGX_ERROR_INVALID_ISVSVN**
+    ///
+    /// The envelope was sealed on a newer TCB or by a newer version of the enclave.
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The envelope was not sealed by this enclave or signer, or has been modified.
+    ///
+    pub fn unseal(&self) -> SgxResult<Vec<u8>> {
+        rsgx_check_unseal_guard()?;
+
+        let mut seal_key = get_seal_key(&self.key_request).map_err(|ret| match ret {
+            sgx_status_t::SGX_ERROR_INVALID_CPUSVN
+            | sgx_status_t::SGX_ERROR_INVALID_ISVSVN
+            | sgx_status_t::SGX_ERROR_OUT_OF_MEMORY => ret,
+            _ => sgx_status_t::SGX_ERROR_MAC_MISMATCH,
+        })?;
+        let mut plaintext = vec![0_u8; self.ciphertext.len()];
+        let result = rsgx_rijndael128GCM_decrypt(
+            &seal_key.key,
+            &self.ciphertext,
+            &self.iv,
+            &self.authenticated_data(),
+            &self.tag,
+            &mut plaintext,
+        );
+        wipe(&mut seal_key);
+        match result {
+            Ok(()) => Ok(plaintext),
+            Err(_) => Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH),
+        }
+    }
+
+    ///
+    /// Returns true if the envelope was sealed for an older ISVSVN than `policy` seals for, or
+    /// on a different TCB than the current one.
+    ///
+    /// Such an envelope can still be unsealed, but should be resealed with `upgrade` so that
+    /// it no longer depends on the old key.
+    ///
+    pub fn needs_upgrade(&self, policy: &SgxEnvelopePolicy) -> bool {
+        let report = rsgx_self_report();
+        self.key_request.isv_svn < policy.target_isv_svn(&report)
+            || self.key_request.cpu_svn.svn != report.body.cpu_svn.svn
+    }
+
+    ///
+    /// Unseals the envelope and seals its payload and AAD again under `policy`, for the
+    /// current TCB.
+    ///
+    /// # Errors
+    ///
+    /// The errors of `unseal` and `seal_with_aad`.
+    ///
+    pub fn upgrade(&self, policy: &SgxEnvelopePolicy) -> SgxResult<SgxSealedEnvelope> {
+        let mut plaintext = self.unseal()?;
+        let result = Self::seal_with_aad(&plaintext, &self.aad, policy);
+        for b in plaintext.iter_mut() {
+            unsafe { ptr::write_volatile(b, 0) };
+        }
+        result
+    }
+
+    pub fn get_key_request(&self) -> &sgx_key_request_t {
+        &self.key_request
+    }
+
+    /// The ISVSVN the envelope was sealed for.
+    pub fn get_isv_svn(&self) -> sgx_isv_svn_t {
+        self.key_request.isv_svn
+    }
+
+    pub fn get_aad(&self) -> &[u8] {
+        &self.aad
+    }
+
+    pub fn get_encrypt_txt(&self) -> &[u8] {
+        &self.ciphertext
+    }
+
+    /// The size of the serialized envelope.
+    pub fn serialized_size(&self) -> usize {
+        ENVELOPE_HEADER_SIZE + self.aad.len() + self.ciphertext.len() + SGX_SEAL_TAG_SIZE
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.serialized_size());
+        out.extend_from_slice(&self.authenticated_data());
+        out.extend_from_slice(&self.ciphertext);
+        out.extend_from_slice(&self.tag);
+        out
+    }
+
+    ///
+    /// Parses a serialized envelope.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `bytes` is not an envelope, or its lengths are inconsistent.
+    ///
+    /// **SGX_ERROR_INVALID_VERSION**
+    ///
+    /// The envelope was written by a newer version of the format.
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> SgxResult<SgxSealedEnvelope> {
+        fn take<'a>(bytes: &mut &'a [u8], n: usize) -> SgxResult<&'a [u8]> {
+            if bytes.len() < n {
+                return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+            }
+            let (head, tail) = bytes.split_at(n);
+            *bytes = tail;
+            Ok(head)
+        }
+        fn array<const N: usize>(bytes: &mut &[u8]) -> SgxResult<[u8; N]> {
+            take(bytes, N)?
+                .try_into()
+                .map_err(|_| sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+        }
+
+        let mut rest = bytes;
+        if array::<4>(&mut rest)? != ENVELOPE_MAGIC {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        if array::<1>(&mut rest)?[0] != SGX_ENVELOPE_VERSION {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_VERSION);
+        }
+        let key_policy = u16::from_le_bytes(array(&mut rest)?);
+        let isv_svn = u16::from_le_bytes(array(&mut rest)?);
+        let config_svn = u16::from_le_bytes(array(&mut rest)?);
+        let cpu_svn = sgx_cpu_svn_t {
+            svn: array(&mut rest)?,
+        };
+        let attribute_mask = sgx_attributes_t {
+            flags: u64::from_le_bytes(array(&mut rest)?),
+            xfrm: u64::from_le_bytes(array(&mut rest)?),
+        };
+        let misc_mask = u32::from_le_bytes(array(&mut rest)?);
+        let key_id = sgx_key_id_t {
+            id: array(&mut rest)?,
+        };
+        let iv = array(&mut rest)?;
+        let aad_len = u32::from_le_bytes(array(&mut rest)?) as usize;
+        let payload_len = u32::from_le_bytes(array(&mut rest)?) as usize;
+        let aad = take(&mut rest, aad_len)?.to_vec();
+        let ciphertext = take(&mut rest, payload_len)?.to_vec();
+        let tag = array(&mut rest)?;
+        if !rest.is_empty() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+
+        Ok(SgxSealedEnvelope {
+            key_request: sgx_key_request_t {
+                key_name: SGX_KEYSELECT_SEAL,
+                key_policy,
+                isv_svn,
+                cpu_svn,
+                attribute_mask,
+                key_id,
+                misc_mask,
+                config_svn,
+                ..Default::default()
+            },
+            iv,
+            aad,
+            ciphertext,
+            tag,
+        })
+    }
+
+    // The header and the AAD, which are authenticated with the payload.
+    fn authenticated_data(&self) -> Vec<u8> {
+        let req = &self.key_request;
+        let mut out = Vec::with_capacity(ENVELOPE_HEADER_SIZE + self.aad.len());
+        out.extend_from_slice(&ENVELOPE_MAGIC);
+        out.push(SGX_ENVELOPE_VERSION);
+        out.extend_from_slice(&req.key_policy.to_le_bytes());
+        out.extend_from_slice(&req.isv_svn.to_le_bytes());
+        out.extend_from_slice(&req.config_svn.to_le_bytes());
+        out.extend_from_slice(&req.cpu_svn.svn);
+        out.extend_from_slice(&req.attribute_mask.flags.to_le_bytes());
+        out.extend_from_slice(&req.attribute_mask.xfrm.to_le_bytes());
+        out.extend_from_slice(&req.misc_mask.to_le_bytes());
+        out.extend_from_slice(&req.key_id.id);
+        out.extend_from_slice(&self.iv);
+        out.extend_from_slice(&(self.aad.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(self.ciphertext.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.aad);
+        out
+    }
+}
@@ -0,0 +1,412 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Distributed freshness
+//!
+//! Rollback protection across a cluster of enclaves, after ROTE. Instead of a hardware
+//! counter, every enclave keeps its counter, together with a digest of the state it
+//! protects, on the other enclaves of its cluster. Before it unseals, an enclave asks its
+//! peers for the latest state they hold for it and accepts its sealed copy only if it is
+//! that state. A host that restarts one enclave with old sealed state cannot also roll back
+//! the peers running on other hosts.
+//!
+//! Each enclave plays both roles: `SgxFreshnessQuorum` is the client, and
+//! `SgxFreshnessService` answers the requests of the other enclaves. Messages travel over
+//! channels the caller sets up with `CounterTransport`, after attesting the peer and
+//! agreeing on a session key with it.
+//!
+//! A write must be stored by a quorum of peers and a read needs answers from a quorum, of
+//! which it takes the highest state. With the default quorum, a majority of the peers, the
+//! cluster keeps working while fewer than half of the peers are down, and a rollback must
+//! subvert at least half of them.
+//!
+//! The peers keep the states they hold in enclave memory. A cluster whose enclaves all
+//! restart at once loses them, and must be set up again.
+
+use crate::counter::{CounterTransport, MonotonicCounter, SgxCounterId, COUNTER_ID_SIZE};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::ptr;
+use sgx_tcrypto::rsgx_rijndael128_cmac_slice;
+use sgx_trts::memeq::ConsttimeMemEq;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+pub const STATE_DIGEST_SIZE: usize = 32;
+pub type SgxStateDigest = [u8; STATE_DIGEST_SIZE];
+
+const NONCE_SIZE: usize = 16;
+const OP_READ: u8 = 0x11;
+const OP_WRITE: u8 = 0x12;
+const OP_RESPONSE: u8 = 0x80;
+const HEADER_SIZE: usize = 1 + COUNTER_ID_SIZE + NONCE_SIZE;
+const STATE_SIZE: usize = 8 + STATE_DIGEST_SIZE;
+
+///
+/// The latest protected state of an enclave: a counter value and a digest of the state.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SgxFreshState {
+    pub value: u64,
+    pub digest: SgxStateDigest,
+}
+
+impl SgxFreshState {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.value.to_be_bytes());
+        out.extend_from_slice(&self.digest);
+    }
+
+    fn decode(bytes: &[u8]) -> SgxFreshState {
+        SgxFreshState {
+            value: u64::from_be_bytes(bytes[..8].try_into().unwrap()),
+            digest: bytes[8..STATE_SIZE].try_into().unwrap(),
+        }
+    }
+}
+
+fn seal_message(key: &sgx_cmac_128bit_key_t, mut message: Vec<u8>) -> SgxResult<Vec<u8>> {
+    let mac = rsgx_rijndael128_cmac_slice(key, &message)?;
+    message.extend_from_slice(&mac);
+    Ok(message)
+}
+
+fn open_message<'a>(key: &sgx_cmac_128bit_key_t, message: &'a [u8]) -> SgxResult<&'a [u8]> {
+    if message.len() < SGX_CMAC_MAC_SIZE {
+        return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+    }
+    let (body, mac) = message.split_at(message.len() - SGX_CMAC_MAC_SIZE);
+    let expected = rsgx_rijndael128_cmac_slice(key, body)?;
+    if !expected[..].consttime_memeq(mac) {
+        return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+    }
+    Ok(body)
+}
+
+fn wipe(key: &mut sgx_cmac_128bit_key_t) {
+    unsafe { ptr::write_volatile(key, sgx_cmac_128bit_key_t::default()) };
+}
+
+///
+/// An attested peer of the cluster, with the session key of its channel.
+///
+pub struct SgxFreshnessPeer<T: CounterTransport> {
+    transport: T,
+    key: sgx_cmac_128bit_key_t,
+}
+
+impl<T: CounterTransport> SgxFreshnessPeer<T> {
+    pub fn new(transport: T, session_key: &sgx_cmac_128bit_key_t) -> SgxFreshnessPeer<T> {
+        SgxFreshnessPeer {
+            transport,
+            key: *session_key,
+        }
+    }
+
+    // Sends a request and returns the state in the authenticated answer to it.
+    fn call(
+        &mut self,
+        op: u8,
+        counter_id: &SgxCounterId,
+        state: Option<&SgxFreshState>,
+    ) -> SgxResult<SgxFreshState> {
+        let mut nonce = [0_u8; NONCE_SIZE];
+        rsgx_read_rand(&mut nonce)?;
+
+        let mut request = Vec::with_capacity(HEADER_SIZE + STATE_SIZE + SGX_CMAC_MAC_SIZE);
+        request.push(op);
+        request.extend_from_slice(counter_id);
+        request.extend_from_slice(&nonce);
+        if let Some(state) = state {
+            state.encode(&mut request);
+        }
+        let request = seal_message(&self.key, request)?;
+
+        let response = self.transport.exchange(&request)?;
+        let body = open_message(&self.key, &response)?;
+        if body.len() != HEADER_SIZE + STATE_SIZE
+            || body[0] != op | OP_RESPONSE
+            || body[1..HEADER_SIZE] != request[1..HEADER_SIZE]
+        {
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+        Ok(SgxFreshState::decode(&body[HEADER_SIZE..]))
+    }
+}
+
+impl<T: CounterTransport> Drop for SgxFreshnessPeer<T> {
+    fn drop(&mut self) {
+        wipe(&mut self.key);
+    }
+}
+
+///
+/// The client side of the freshness protocol: keeps the state of one counter on the
+/// peers of the cluster.
+///
+pub struct SgxFreshnessQuorum<T: CounterTransport> {
+    peers: Vec<SgxFreshnessPeer<T>>,
+    counter_id: SgxCounterId,
+    quorum: usize,
+    last: SgxFreshState,
+}
+
+impl<T: CounterTransport> SgxFreshnessQuorum<T> {
+    ///
+    /// Creates a client for the counter `counter_id`, kept on `peers`, with a quorum of a
+    /// majority of the peers.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `peers` is empty.
+    ///
+    pub fn new(
+        peers: Vec<SgxFreshnessPeer<T>>,
+        counter_id: SgxCounterId,
+    ) -> SgxResult<SgxFreshnessQuorum<T>> {
+        let quorum = peers.len() / 2 + 1;
+        Self::with_quorum(peers, counter_id, quorum)
+    }
+
+    ///
+    /// Creates a client that needs `quorum` peers for every read and write.
+    ///
+    /// # Description
+    ///
+    /// A quorum of at most half of the peers lets two writes or a write and a read miss
+    /// each other, and so gives up rollback protection for availability.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `quorum` is 0 or larger than the number of peers.
+    ///
+    pub fn with_quorum(
+        peers: Vec<SgxFreshnessPeer<T>>,
+        counter_id: SgxCounterId,
+        quorum: usize,
+    ) -> SgxResult<SgxFreshnessQuorum<T>> {
+        if quorum == 0 || quorum > peers.len() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        Ok(SgxFreshnessQuorum {
+            peers,
+            counter_id,
+            quorum,
+            last: SgxFreshState::default(),
+        })
+    }
+
+    pub fn counter_id(&self) -> &SgxCounterId {
+        &self.counter_id
+    }
+
+    pub fn quorum(&self) -> usize {
+        self.quorum
+    }
+
+    ///
+    /// Returns the latest state held by a quorum of the peers.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_SERVICE_UNAVAILABLE**
+    ///
+    /// Fewer than a quorum of peers answered.
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// Two peers hold different digests for the same value, or the peers went back to a
+    /// state older than one this client has already seen.
+    ///
+    pub fn fresh_state(&mut self) -> SgxResult<SgxFreshState> {
+        let mut answers = 0;
+        let mut latest = SgxFreshState::default();
+        let mut conflict = false;
+        for peer in self.peers.iter_mut() {
+            if let Ok(state) = peer.call(OP_READ, &self.counter_id, None) {
+                answers += 1;
+                if state.value > latest.value {
+                    latest = state;
+                    conflict = false;
+                } else if state.value == latest.value && state.digest != latest.digest {
+                    conflict = true;
+                }
+            }
+        }
+        if answers < self.quorum {
+            return Err(sgx_status_t::SGX_ERROR_SERVICE_UNAVAILABLE);
+        }
+        if conflict
+            || latest.value < self.last.value
+            || (latest.value == self.last.value && latest.digest != self.last.digest)
+        {
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+        self.last = latest;
+        Ok(latest)
+    }
+
+    ///
+    /// Checks that `state`, read from sealed storage, is the latest state of the counter.
+    /// Call it after unsealing and before trusting the unsealed data.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// `state` is not the latest state; the sealed data has been rolled back.
+    ///
+    /// Errors of `fresh_state` are passed on.
+    ///
+    pub fn verify(&mut self, state: &SgxFreshState) -> SgxError {
+        if self.fresh_state()? == *state {
+            Ok(())
+        } else {
+            Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH)
+        }
+    }
+
+    ///
+    /// Records `digest` as the new latest state and returns it, with the counter moved
+    /// forward by one. Seal the returned state along with the data it protects.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_SERVICE_UNAVAILABLE**
+    ///
+    /// Fewer than a quorum of peers answered the read or stored the new state. The new
+    /// state may still have reached some peers, and then shows in later reads.
+    ///
+    /// Errors of `fresh_state` are passed on.
+    ///
+    pub fn advance(&mut self, digest: &SgxStateDigest) -> SgxResult<SgxFreshState> {
+        let current = self.fresh_state()?;
+        let next = SgxFreshState {
+            value: current
+                .value
+                .checked_add(1)
+                .ok_or(sgx_status_t::SGX_ERROR_MC_USED_UP)?,
+            digest: *digest,
+        };
+
+        let mut stored = 0;
+        for peer in self.peers.iter_mut() {
+            if let Ok(state) = peer.call(OP_WRITE, &self.counter_id, Some(&next)) {
+                if state == next {
+                    stored += 1;
+                }
+            }
+        }
+        if stored < self.quorum {
+            return Err(sgx_status_t::SGX_ERROR_SERVICE_UNAVAILABLE);
+        }
+        self.last = next;
+        Ok(next)
+    }
+}
+
+///
+/// The counter value of the freshness protocol. An increment records an all-zero digest,
+/// for users such as `SgxIdentityKeyring` that embed the value in their sealed state.
+///
+impl<T: CounterTransport> MonotonicCounter for SgxFreshnessQuorum<T> {
+    fn read(&mut self) -> SgxResult<u64> {
+        self.fresh_state().map(|state| state.value)
+    }
+
+    fn increment(&mut self) -> SgxResult<u64> {
+        self.advance(&[0; STATE_DIGEST_SIZE])
+            .map(|state| state.value)
+    }
+}
+
+///
+/// The peer side of the freshness protocol: holds the latest states of the counters of
+/// other enclaves and answers their requests.
+///
+#[derive(Default)]
+pub struct SgxFreshnessService {
+    states: BTreeMap<SgxCounterId, SgxFreshState>,
+}
+
+impl SgxFreshnessService {
+    pub fn new() -> SgxFreshnessService {
+        SgxFreshnessService {
+            states: BTreeMap::new(),
+        }
+    }
+
+    ///
+    /// Returns the state held for `counter_id`; a counter never written is at 0.
+    ///
+    pub fn state(&self, counter_id: &SgxCounterId) -> SgxFreshState {
+        self.states.get(counter_id).copied().unwrap_or_default()
+    }
+
+    ///
+    /// Answers `request`, received on the channel with the session key `session_key`.
+    ///
+    /// # Description
+    ///
+    /// A write is stored only if it moves the counter forward. The answer always holds the
+    /// state stored after the request, so the writer sees whether it was accepted.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The request is not authentic.
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The request is malformed.
+    ///
+    pub fn handle(
+        &mut self,
+        session_key: &sgx_cmac_128bit_key_t,
+        request: &[u8],
+    ) -> SgxResult<Vec<u8>> {
+        let body = open_message(session_key, request)?;
+        if body.len() < HEADER_SIZE {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let counter_id: SgxCounterId = body[1..1 + COUNTER_ID_SIZE].try_into().unwrap();
+        let state = match (body[0], body.len() - HEADER_SIZE) {
+            (OP_READ, 0) => self.state(&counter_id),
+            (OP_WRITE, STATE_SIZE) => {
+                let proposed = SgxFreshState::decode(&body[HEADER_SIZE..]);
+                let current = self.states.entry(counter_id).or_default();
+                if proposed.value > current.value {
+                    *current = proposed;
+                }
+                *current
+            }
+            _ => return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
+        };
+
+        let mut response = Vec::with_capacity(HEADER_SIZE + STATE_SIZE + SGX_CMAC_MAC_SIZE);
+        response.push(body[0] | OP_RESPONSE);
+        response.extend_from_slice(&body[1..HEADER_SIZE]);
+        state.encode(&mut response);
+        seal_message(session_key, response)
+    }
+}
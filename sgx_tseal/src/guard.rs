@@ -0,0 +1,84 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use core::sync::atomic::{AtomicU8, Ordering};
+use sgx_tse::rsgx_self_report;
+use sgx_types::*;
+
+///
+/// The class of the secrets an enclave holds in sealed data.
+///
+/// Once an enclave declares that it holds `Production` secrets, unsealing is refused while the
+/// enclave is debuggable, since the memory of a debug enclave can be read by the host. The
+/// check is made for every unseal and unmac call of this crate, so it cannot be forgotten by
+/// individual callers.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SgxSecretClass {
+    Development,
+    Production,
+}
+
+static SECRET_CLASS: AtomicU8 = AtomicU8::new(SgxSecretClass::Development as u8);
+
+///
+/// rsgx_set_secret_class declares the class of the secrets held by the enclave.
+///
+/// The class can only be raised: once set to `Production` it stays so for the lifetime of the
+/// enclave.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_STATE**
+///
+/// The class was already set to `Production` and `Development` was requested.
+///
+pub fn rsgx_set_secret_class(class: SgxSecretClass) -> SgxError {
+    let prev = SECRET_CLASS.fetch_max(class as u8, Ordering::SeqCst);
+    if prev > class as u8 {
+        Err(sgx_status_t::SGX_ERROR_INVALID_STATE)
+    } else {
+        Ok(())
+    }
+}
+
+pub fn rsgx_get_secret_class() -> SgxSecretClass {
+    if SECRET_CLASS.load(Ordering::SeqCst) == SgxSecretClass::Production as u8 {
+        SgxSecretClass::Production
+    } else {
+        SgxSecretClass::Development
+    }
+}
+
+///
+/// rsgx_check_unseal_guard checks whether the enclave may unseal its secrets.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_ATTRIBUTE**
+///
+/// The enclave holds `Production` secrets and was launched in debug mode.
+///
+pub fn rsgx_check_unseal_guard() -> SgxError {
+    if rsgx_get_secret_class() == SgxSecretClass::Production
+        && (rsgx_self_report().body.attributes.flags & SGX_FLAGS_DEBUG) != 0
+    {
+        Err(sgx_status_t::SGX_ERROR_INVALID_ATTRIBUTE)
+    } else {
+        Ok(())
+    }
+}
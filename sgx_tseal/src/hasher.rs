@@ -0,0 +1,98 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Sealing of BLAKE3 hash state, to hash one input across several ECALLs.
+
+use crate::batch::SgxSealedBlob;
+use crate::policy::SgxSealPolicy;
+use core::ptr;
+use sgx_tcrypto::blake3::Blake3Hasher;
+use sgx_types::*;
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
+
+///
+/// The rsgx_seal_hasher_state function seals the state of a BLAKE3 hasher with the default
+/// seal policy.
+///
+/// # Description
+///
+/// The state includes the buffered input of the current chunk and, for keyed hashers, the
+/// key. Restore it with `rsgx_unseal_hasher_state` and continue hashing from there.
+///
+/// # Errors
+///
+/// **SGX_ERROR_UNEXPECTED**
+///
+/// The seal key could not be derived, or a crypto library failure occurred.
+///
+pub fn rsgx_seal_hasher_state(hasher: &Blake3Hasher) -> SgxResult<SgxSealedBlob> {
+    rsgx_seal_hasher_state_policy(&SgxSealPolicy::default(), hasher)
+}
+
+///
+/// The rsgx_seal_hasher_state_policy function seals the state of a BLAKE3 hasher with the
+/// key policy of `policy`.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// The policy is invalid.
+///
+/// **SGX_ERROR_UNEXPECTED**
+///
+/// The seal key could not be derived, or a crypto library failure occurred.
+///
+pub fn rsgx_seal_hasher_state_policy(
+    policy: &SgxSealPolicy,
+    hasher: &Blake3Hasher,
+) -> SgxResult<SgxSealedBlob> {
+    let mut state = hasher.export_state();
+    let result = SgxSealedBlob::seal_batch_policy(policy, &[&state]);
+    wipe(&mut state);
+    result?.pop().ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)
+}
+
+///
+/// The rsgx_unseal_hasher_state function restores a BLAKE3 hasher sealed by
+/// `rsgx_seal_hasher_state`.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// The blob does not hold a hasher state.
+///
+/// **SGX_ERROR_INVALID_ATTRIBUTE**
+///
+/// The enclave holds production secrets and is debuggable, see `rsgx_set_secret_class`.
+///
+/// **SGX_ERROR_MAC_MISMATCH**
+///
+/// The blob was not sealed by this enclave or signer, or has been modified.
+///
+pub fn rsgx_unseal_hasher_state(blob: &SgxSealedBlob) -> SgxResult<Blake3Hasher> {
+    let mut state = blob.unseal()?;
+    let result = Blake3Hasher::import_state(&state);
+    wipe(&mut state);
+    result
+}
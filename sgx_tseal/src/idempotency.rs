@@ -0,0 +1,475 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Idempotent execution
+//!
+//! A sealed log of idempotency keys, so that an operation with an externally visible
+//! effect, such as a transfer or a signature, runs at most once per key even if the enclave
+//! is restarted with an older copy of its sealed state.
+//!
+//! `execute_once` records the key as pending before it runs the operation, and records
+//! the result of the operation once it returns. Both records are sealed and bound to a
+//! `MonotonicCounter`, as the identity keyring is, so that an older log is refused. A
+//! request that comes again gets the recorded result back instead of a second run.
+//!
+//! A key that is still pending when the log is opened belongs to an operation that was
+//! interrupted, and that may or may not have taken effect. The log does not guess: the key
+//! is refused until the caller settles it with `complete` or `discard`.
+
+use crate::batch::SgxSealedBlob;
+use crate::counter::MonotonicCounter;
+use crate::policy::SgxSealPolicy;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::ptr;
+use sgx_types::*;
+
+const LOG_VERSION: u8 = 1;
+const STATE_PENDING: u8 = 0;
+const STATE_DONE: u8 = 1;
+
+pub const IDEMPOTENCY_KEY_MAX_SIZE: usize = 256;
+pub const IDEMPOTENCY_RESULT_MAX_SIZE: usize = 64 * 1024;
+
+///
+/// Untrusted storage for the sealed log.
+///
+pub trait IdempotencyStore {
+    ///
+    /// Read the log last stored, or `None` at first launch.
+    ///
+    fn load(&mut self) -> SgxResult<Option<Vec<u8>>>;
+
+    ///
+    /// Replace the stored log with `sealed`.
+    ///
+    fn store(&mut self, sealed: &[u8]) -> SgxError;
+}
+
+///
+/// How the log is sealed and how many keys it keeps.
+///
+#[derive(Clone, Copy)]
+pub struct SgxIdempotencyPolicy {
+    max_entries: usize,
+    seal_policy: SgxSealPolicy,
+}
+
+impl Default for SgxIdempotencyPolicy {
+    fn default() -> Self {
+        SgxIdempotencyPolicy {
+            max_entries: 4096,
+            seal_policy: SgxSealPolicy::default(),
+        }
+    }
+}
+
+impl SgxIdempotencyPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of keys the log keeps. When it is full, the oldest completed key is
+    /// dropped, after which a request with that key runs again. Defaults to 4096.
+    pub fn max_entries(mut self, entries: usize) -> Self {
+        self.max_entries = entries.max(1);
+        self
+    }
+
+    /// The policy of the seal key of the log. Defaults to `SgxSealPolicy::default()`.
+    pub fn seal_policy(mut self, policy: SgxSealPolicy) -> Self {
+        self.seal_policy = policy;
+        self
+    }
+
+    pub fn get_max_entries(&self) -> usize {
+        self.max_entries
+    }
+}
+
+///
+/// The outcome of `execute_once`.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SgxExecution {
+    /// The operation ran now and returned this result.
+    Executed(Vec<u8>),
+    /// The operation had already run for this key; this is its recorded result.
+    Replayed(Vec<u8>),
+}
+
+impl SgxExecution {
+    pub fn result(&self) -> &[u8] {
+        match self {
+            SgxExecution::Executed(result) | SgxExecution::Replayed(result) => result,
+        }
+    }
+
+    pub fn into_result(self) -> Vec<u8> {
+        match self {
+            SgxExecution::Executed(result) | SgxExecution::Replayed(result) => result,
+        }
+    }
+}
+
+struct Entry {
+    seq: u64,
+    // `None` while the operation is pending.
+    result: Option<Vec<u8>>,
+}
+
+///
+/// A sealed, rollback-protected log of idempotency keys.
+///
+pub struct SgxIdempotencyLog<S: IdempotencyStore, C: MonotonicCounter> {
+    store: S,
+    counter: C,
+    policy: SgxIdempotencyPolicy,
+    version: u64,
+    next_seq: u64,
+    entries: BTreeMap<Vec<u8>, Entry>,
+}
+
+impl<S: IdempotencyStore, C: MonotonicCounter> SgxIdempotencyLog<S, C> {
+    ///
+    /// Opens the log kept in `store`, or creates it at first launch.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The stored log is not the latest one, was not sealed by this enclave or signer, or
+    /// has been modified; or the store is empty although a log was created before.
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The stored log is malformed.
+    ///
+    /// Errors of the store, the counter and unsealing are passed on.
+    ///
+    pub fn open(store: S, counter: C, policy: SgxIdempotencyPolicy) -> SgxResult<Self> {
+        let mut log = SgxIdempotencyLog {
+            store,
+            counter,
+            policy,
+            version: 0,
+            next_seq: 0,
+            entries: BTreeMap::new(),
+        };
+        let current = log.counter.read()?;
+        match log.store.load()? {
+            Some(sealed) => {
+                let blob = SgxSealedBlob::from_bytes(&sealed)
+                    .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+                let mut plaintext = blob.unseal()?;
+                let decoded = log.decode(&plaintext);
+                wipe(&mut plaintext);
+                decoded?;
+
+                if log.version == current.wrapping_add(1) {
+                    log.counter.increment()?;
+                } else if log.version != current {
+                    return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+                }
+            }
+            None => {
+                // A missing log after the counter has moved is a rollback to before the
+                // first launch.
+                if current != 0 {
+                    return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+                }
+                log.version = current;
+                log.persist()?;
+            }
+        }
+        Ok(log)
+    }
+
+    ///
+    /// Runs `f` unless it already ran for `key`, and returns its result.
+    ///
+    /// # Description
+    ///
+    /// The key is recorded as pending before `f` runs. If `f` succeeds, its result is
+    /// recorded and returned as `Executed`; if `f` fails, the key is removed again, so that
+    /// the request may be retried, and the error is returned. An operation must therefore
+    /// not take effect when it fails. A later call with the same key returns the recorded
+    /// result as `Replayed` without running its operation.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_STATE**
+    ///
+    /// The key is pending: its operation was interrupted and has not been settled with
+    /// `complete` or `discard`.
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The key is empty or longer than `IDEMPOTENCY_KEY_MAX_SIZE`, or the result is longer
+    /// than `IDEMPOTENCY_RESULT_MAX_SIZE`. In the latter case the key stays pending.
+    ///
+    /// **SGX_ERROR_OUT_OF_MEMORY**
+    ///
+    /// The log is full of pending keys.
+    ///
+    /// Errors of `f`, of the store and of the counter are passed on. If recording the
+    /// result fails, the key stays pending.
+    ///
+    pub fn execute_once<F>(&mut self, key: &[u8], f: F) -> SgxResult<SgxExecution>
+    where
+        F: FnOnce() -> SgxResult<Vec<u8>>,
+    {
+        check_key(key)?;
+        if let Some(entry) = self.entries.get(key) {
+            return match entry.result {
+                Some(ref result) => Ok(SgxExecution::Replayed(result.clone())),
+                None => Err(sgx_status_t::SGX_ERROR_INVALID_STATE),
+            };
+        }
+
+        self.make_room()?;
+        let seq = self.next_seq;
+        self.entries
+            .insert(key.to_vec(), Entry { seq, result: None });
+        self.next_seq += 1;
+        if let Err(e) = self.persist() {
+            self.entries.remove(key);
+            return Err(e);
+        }
+
+        match f() {
+            Ok(result) => {
+                self.complete(key, &result)?;
+                Ok(SgxExecution::Executed(result))
+            }
+            Err(e) => {
+                self.discard(key)?;
+                Err(e)
+            }
+        }
+    }
+
+    ///
+    /// Returns the recorded result for `key`, or `None` if the key is unknown or pending.
+    ///
+    pub fn result(&self, key: &[u8]) -> Option<&[u8]> {
+        self.entries.get(key)?.result.as_deref()
+    }
+
+    ///
+    /// Returns the keys whose operations were interrupted, oldest first.
+    ///
+    pub fn pending(&self) -> Vec<&[u8]> {
+        let mut pending: Vec<(u64, &[u8])> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.result.is_none())
+            .map(|(key, entry)| (entry.seq, key.as_slice()))
+            .collect();
+        pending.sort_unstable();
+        pending.into_iter().map(|(_, key)| key).collect()
+    }
+
+    ///
+    /// Records `result` for the pending `key`, after the caller has found out that its
+    /// operation took effect.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_STATE**
+    ///
+    /// The key is not pending.
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The result is longer than `IDEMPOTENCY_RESULT_MAX_SIZE`.
+    ///
+    pub fn complete(&mut self, key: &[u8], result: &[u8]) -> SgxError {
+        if result.len() > IDEMPOTENCY_RESULT_MAX_SIZE {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        match self.entries.get_mut(key) {
+            Some(entry) if entry.result.is_none() => entry.result = Some(result.to_vec()),
+            _ => return Err(sgx_status_t::SGX_ERROR_INVALID_STATE),
+        }
+        self.persist().map_err(|e| {
+            if let Some(entry) = self.entries.get_mut(key) {
+                entry.result = None;
+            }
+            e
+        })
+    }
+
+    ///
+    /// Removes the pending `key`, after the caller has found out that its operation did
+    /// not take effect. The key may then be executed again.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_STATE**
+    ///
+    /// The key is not pending.
+    ///
+    pub fn discard(&mut self, key: &[u8]) -> SgxError {
+        let entry = match self.entries.get(key) {
+            Some(entry) if entry.result.is_none() => self.entries.remove(key).unwrap(),
+            _ => return Err(sgx_status_t::SGX_ERROR_INVALID_STATE),
+        };
+        self.persist().map_err(|e| {
+            self.entries.insert(key.to_vec(), entry);
+            e
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Drops the oldest completed keys until there is room for one more.
+    fn make_room(&mut self) -> SgxError {
+        while self.entries.len() >= self.policy.max_entries {
+            let oldest = self
+                .entries
+                .iter()
+                .filter(|(_, entry)| entry.result.is_some())
+                .min_by_key(|(_, entry)| entry.seq)
+                .map(|(key, _)| key.clone())
+                .ok_or(sgx_status_t::SGX_ERROR_OUT_OF_MEMORY)?;
+            self.entries.remove(&oldest);
+        }
+        Ok(())
+    }
+
+    fn persist(&mut self) -> SgxError {
+        let version = self.version + 1;
+        let mut plaintext = self.encode(version);
+        let sealed = SgxSealedBlob::seal_batch_policy(&self.policy.seal_policy, &[&plaintext]);
+        wipe(&mut plaintext);
+        let blob = sealed?.pop().ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)?;
+
+        self.store.store(&blob.to_bytes())?;
+        let current = self.counter.increment()?;
+        if current != version {
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+        self.version = version;
+        Ok(())
+    }
+
+    fn encode(&self, version: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.push(LOG_VERSION);
+        out.extend_from_slice(&version.to_le_bytes());
+        out.extend_from_slice(&self.next_seq.to_le_bytes());
+        out.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for (key, entry) in self.entries.iter() {
+            out.extend_from_slice(&entry.seq.to_le_bytes());
+            out.extend_from_slice(&(key.len() as u16).to_le_bytes());
+            out.extend_from_slice(key);
+            match entry.result {
+                Some(ref result) => {
+                    out.push(STATE_DONE);
+                    out.extend_from_slice(&(result.len() as u32).to_le_bytes());
+                    out.extend_from_slice(result);
+                }
+                None => out.push(STATE_PENDING),
+            }
+        }
+        out
+    }
+
+    fn decode(&mut self, bytes: &[u8]) -> SgxError {
+        fn take<'a>(bytes: &mut &'a [u8], n: usize) -> SgxResult<&'a [u8]> {
+            if bytes.len() < n {
+                return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+            }
+            let (head, tail) = bytes.split_at(n);
+            *bytes = tail;
+            Ok(head)
+        }
+        fn u16_of(bytes: &mut &[u8]) -> SgxResult<u16> {
+            Ok(u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap()))
+        }
+        fn u32_of(bytes: &mut &[u8]) -> SgxResult<u32> {
+            Ok(u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()))
+        }
+        fn u64_of(bytes: &mut &[u8]) -> SgxResult<u64> {
+            Ok(u64::from_le_bytes(take(bytes, 8)?.try_into().unwrap()))
+        }
+
+        let mut bytes = bytes;
+        if take(&mut bytes, 1)? != [LOG_VERSION] {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        self.version = u64_of(&mut bytes)?;
+        self.next_seq = u64_of(&mut bytes)?;
+        let count = u32_of(&mut bytes)?;
+        let mut entries = BTreeMap::new();
+        for _ in 0..count {
+            let seq = u64_of(&mut bytes)?;
+            let key_len = u16_of(&mut bytes)? as usize;
+            let key = take(&mut bytes, key_len)?;
+            check_key(key)?;
+            let result = match take(&mut bytes, 1)?[0] {
+                STATE_PENDING => None,
+                STATE_DONE => {
+                    let len = u32_of(&mut bytes)? as usize;
+                    Some(take(&mut bytes, len)?.to_vec())
+                }
+                _ => return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
+            };
+            if seq >= self.next_seq
+                || entries
+                    .insert(key.to_vec(), Entry { seq, result })
+                    .is_some()
+            {
+                return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+            }
+        }
+        if !bytes.is_empty() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        self.entries = entries;
+        Ok(())
+    }
+
+    ///
+    /// Closes the log, returning its store and counter.
+    ///
+    pub fn into_parts(self) -> (S, C) {
+        let SgxIdempotencyLog { store, counter, .. } = self;
+        (store, counter)
+    }
+}
+
+fn check_key(key: &[u8]) -> SgxError {
+    if key.is_empty() || key.len() > IDEMPOTENCY_KEY_MAX_SIZE {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    Ok(())
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
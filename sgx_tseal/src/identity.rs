@@ -0,0 +1,522 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Enclave identity keys
+//!
+//! A keyring of long-term ECDSA P-256 identity keys. The first key is generated at first
+//! launch; the keyring is sealed after every change and bound to a `MonotonicCounter`, so
+//! that an older copy of it is refused. The private keys never leave the keyring: signing
+//! and attestation go through `SgxIdentityKey` handles.
+//!
+//! Keys rotate on a schedule. After a rotation the new key signs and the previous keys stay
+//! available for the overlap period, so that relying parties can move to the new key before
+//! the old one disappears. The enclave has no trusted clock, so the caller passes the
+//! current time, in seconds, to every operation that depends on it.
+
+use crate::batch::SgxSealedBlob;
+use crate::counter::MonotonicCounter;
+use crate::policy::SgxSealPolicy;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::ptr;
+use sgx_tcrypto::vrf::{rsgx_ecvrf_prove, ECVRF_PROOF_SIZE};
+use sgx_tcrypto::{rsgx_sha256_slice, SgxEccHandle, SgxEcdsaNonce};
+use sgx_tse::rsgx_create_report;
+use sgx_types::*;
+
+const KEYRING_VERSION: u8 = 1;
+const NOT_RETIRED: u64 = u64::MAX;
+const ENTRY_SIZE: usize = 4 + 8 + 8 + SGX_ECP256_KEY_SIZE * 3;
+const REPORT_DATA_LABEL: &[u8] = b"sgx identity key v1";
+
+///
+/// Untrusted storage for the sealed keyring.
+///
+pub trait IdentityKeyStore {
+    ///
+    /// Read the keyring last stored, or `None` at first launch.
+    ///
+    fn load(&mut self) -> SgxResult<Option<Vec<u8>>>;
+
+    ///
+    /// Replace the stored keyring with `sealed`.
+    ///
+    fn store(&mut self, sealed: &[u8]) -> SgxError;
+}
+
+///
+/// How identity keys are sealed and rotated.
+///
+#[derive(Clone, Copy)]
+pub struct SgxIdentityKeyPolicy {
+    rotation_period: u64,
+    overlap: u64,
+    seal_policy: SgxSealPolicy,
+    ecdsa_nonce: SgxEcdsaNonce,
+}
+
+impl Default for SgxIdentityKeyPolicy {
+    fn default() -> Self {
+        SgxIdentityKeyPolicy {
+            rotation_period: 90 * 24 * 3600,
+            overlap: 7 * 24 * 3600,
+            seal_policy: SgxSealPolicy::default(),
+            ecdsa_nonce: SgxEcdsaNonce::default(),
+        }
+    }
+}
+
+impl SgxIdentityKeyPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Seconds from the creation of a key to its scheduled rotation. Defaults to 90 days.
+    pub fn rotation_period(mut self, seconds: u64) -> Self {
+        self.rotation_period = seconds;
+        self
+    }
+
+    /// Seconds a key stays available after it is rotated out. Defaults to 7 days.
+    pub fn overlap(mut self, seconds: u64) -> Self {
+        self.overlap = seconds;
+        self
+    }
+
+    /// The policy of the seal key of the keyring. Defaults to `SgxSealPolicy::default()`.
+    pub fn seal_policy(mut self, policy: SgxSealPolicy) -> Self {
+        self.seal_policy = policy;
+        self
+    }
+
+    /// How `SgxIdentityKey::sign` chooses its nonces. Defaults to `SgxEcdsaNonce::Random`.
+    pub fn ecdsa_nonce(mut self, nonce: SgxEcdsaNonce) -> Self {
+        self.ecdsa_nonce = nonce;
+        self
+    }
+
+    pub fn get_rotation_period(&self) -> u64 {
+        self.rotation_period
+    }
+
+    pub fn get_overlap(&self) -> u64 {
+        self.overlap
+    }
+
+    pub fn get_ecdsa_nonce(&self) -> SgxEcdsaNonce {
+        self.ecdsa_nonce
+    }
+}
+
+struct KeyEntry {
+    key_id: u32,
+    created: u64,
+    retired: u64,
+    private: sgx_ec256_private_t,
+    public: sgx_ec256_public_t,
+}
+
+impl Drop for KeyEntry {
+    fn drop(&mut self) {
+        unsafe { ptr::write_volatile(&mut self.private, sgx_ec256_private_t::default()) };
+    }
+}
+
+///
+/// A handle to one identity key of a keyring.
+///
+#[derive(Clone, Copy)]
+pub struct SgxIdentityKey<'a> {
+    entry: &'a KeyEntry,
+    expires: Option<u64>,
+    nonce: SgxEcdsaNonce,
+}
+
+impl<'a> SgxIdentityKey<'a> {
+    pub fn key_id(&self) -> u32 {
+        self.entry.key_id
+    }
+
+    pub fn public_key(&self) -> &sgx_ec256_public_t {
+        &self.entry.public
+    }
+
+    /// The time the key was generated.
+    pub fn created(&self) -> u64 {
+        self.entry.created
+    }
+
+    /// Whether the key is the one new signatures should use.
+    pub fn is_active(&self) -> bool {
+        self.entry.retired == NOT_RETIRED
+    }
+
+    /// The time after which a rotated-out key is removed, `None` for the active key.
+    pub fn expires(&self) -> Option<u64> {
+        self.expires
+    }
+
+    ///
+    /// Signs `data` with ECDSA P-256 over SHA-256, choosing the nonce as the keyring policy
+    /// specifies.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_OUT_OF_MEMORY**
+    ///
+    /// Not enough memory is available to complete this operation.
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// The signing process failed due to an internal cryptography library failure.
+    ///
+    pub fn sign(&self, data: &[u8]) -> SgxResult<sgx_ec256_signature_t> {
+        let handle = SgxEccHandle::new();
+        handle.open()?;
+        handle.ecdsa_sign_slice_with_nonce(data, &self.entry.private, self.nonce)
+    }
+
+    ///
+    /// Computes the ECVRF proof of `alpha` with the key, see `sgx_tcrypto::vrf`. Relying
+    /// parties check it with `rsgx_ecvrf_verify` under `public_key`, and derive the output
+    /// with `rsgx_ecvrf_proof_to_hash`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// Hashing failed.
+    ///
+    pub fn vrf_prove(&self, alpha: &[u8]) -> SgxResult<[u8; ECVRF_PROOF_SIZE]> {
+        rsgx_ecvrf_prove(&self.entry.private, alpha)
+    }
+
+    ///
+    /// The report data that binds the key to a report: SHA-256 over a label, the key ID
+    /// and the public key, followed by 32 zero bytes.
+    ///
+    pub fn report_data(&self) -> SgxResult<sgx_report_data_t> {
+        let mut input = Vec::with_capacity(REPORT_DATA_LABEL.len() + 4 + 2 * SGX_ECP256_KEY_SIZE);
+        input.extend_from_slice(REPORT_DATA_LABEL);
+        input.extend_from_slice(&self.entry.key_id.to_le_bytes());
+        input.extend_from_slice(&self.entry.public.gx);
+        input.extend_from_slice(&self.entry.public.gy);
+        let hash = rsgx_sha256_slice(&input)?;
+
+        let mut report_data = sgx_report_data_t::default();
+        report_data.d[..SGX_SHA256_HASH_SIZE].copy_from_slice(&hash);
+        Ok(report_data)
+    }
+
+    ///
+    /// Creates a report for `target_info` whose report data binds the key, see
+    /// `report_data`. A quoting enclave turns it into a quote for remote verifiers.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The target info is invalid.
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// The report or the hash could not be created.
+    ///
+    pub fn attest(&self, target_info: &sgx_target_info_t) -> SgxResult<sgx_report_t> {
+        rsgx_create_report(target_info, &self.report_data()?)
+    }
+}
+
+///
+/// The sealed, rollback-protected keyring of the enclave identity keys.
+///
+/// # Description
+///
+/// Every change to the keyring increments the counter and stores the keyring sealed
+/// together with the new counter value. The keyring is stored before the counter is
+/// incremented, so a crash between the two leaves a keyring one ahead of the counter,
+/// which `open` accepts and catches the counter up to.
+///
+pub struct SgxIdentityKeyring<S: IdentityKeyStore, C: MonotonicCounter> {
+    store: S,
+    counter: C,
+    policy: SgxIdentityKeyPolicy,
+    version: u64,
+    next_key_id: u32,
+    // Sorted by key ID; the last key is the active one.
+    keys: Vec<KeyEntry>,
+}
+
+impl<S: IdentityKeyStore, C: MonotonicCounter> SgxIdentityKeyring<S, C> {
+    ///
+    /// Opens the keyring in `store`, or creates it with a first key if the store is empty.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The stored keyring is not the latest one, was not sealed by this enclave or signer,
+    /// or has been modified; or the store is empty although a keyring was created before.
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The stored keyring is malformed.
+    ///
+    /// Errors of the store, the counter, unsealing and key generation are passed on.
+    ///
+    pub fn open(store: S, counter: C, policy: SgxIdentityKeyPolicy, now: u64) -> SgxResult<Self> {
+        let mut keyring = SgxIdentityKeyring {
+            store,
+            counter,
+            policy,
+            version: 0,
+            next_key_id: 0,
+            keys: Vec::new(),
+        };
+        let current = keyring.counter.read()?;
+        match keyring.store.load()? {
+            Some(sealed) => {
+                let blob = SgxSealedBlob::from_bytes(&sealed)
+                    .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+                let mut plaintext = blob.unseal()?;
+                let decoded = keyring.decode(&plaintext);
+                wipe(&mut plaintext);
+                decoded?;
+
+                if keyring.version == current.wrapping_add(1) {
+                    keyring.counter.increment()?;
+                } else if keyring.version != current {
+                    return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+                }
+            }
+            None => {
+                // A missing keyring after the counter has moved is a rollback to before
+                // the first launch.
+                if current != 0 {
+                    return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+                }
+                keyring.version = current;
+                keyring.generate(now)?;
+                keyring.persist()?;
+            }
+        }
+        Ok(keyring)
+    }
+
+    fn handle<'a>(&self, entry: &'a KeyEntry) -> SgxIdentityKey<'a> {
+        let expires = if entry.retired == NOT_RETIRED {
+            None
+        } else {
+            Some(entry.retired.saturating_add(self.policy.overlap))
+        };
+        SgxIdentityKey {
+            entry,
+            expires,
+            nonce: self.policy.ecdsa_nonce,
+        }
+    }
+
+    ///
+    /// The key that new signatures use.
+    ///
+    pub fn active(&self) -> SgxIdentityKey<'_> {
+        self.handle(self.keys.last().expect("keyring has an active key"))
+    }
+
+    ///
+    /// The key with ID `key_id`, if it is still in the keyring.
+    ///
+    pub fn key(&self, key_id: u32) -> Option<SgxIdentityKey<'_>> {
+        self.keys
+            .iter()
+            .find(|entry| entry.key_id == key_id)
+            .map(|entry| self.handle(entry))
+    }
+
+    ///
+    /// All keys in the keyring: the rotated-out keys still in their overlap period, oldest
+    /// first, then the active key.
+    ///
+    pub fn keys(&self) -> Vec<SgxIdentityKey<'_>> {
+        self.keys.iter().map(|entry| self.handle(entry)).collect()
+    }
+
+    ///
+    /// Whether the active key is due for rotation at `now`.
+    ///
+    pub fn rotation_due(&self, now: u64) -> bool {
+        now >= self
+            .active()
+            .created()
+            .saturating_add(self.policy.rotation_period)
+    }
+
+    ///
+    /// Generates a new active key, keeping the previous one for the overlap period.
+    ///
+    /// # Description
+    ///
+    /// Keys whose overlap period has ended are removed at the same time. The keyring is
+    /// persisted before the function returns.
+    ///
+    pub fn rotate(&mut self, now: u64) -> SgxResult<SgxIdentityKey<'_>> {
+        if let Some(active) = self.keys.last_mut() {
+            active.retired = now;
+        }
+        self.remove_expired(now);
+        self.generate(now)?;
+        self.persist()?;
+        Ok(self.active())
+    }
+
+    ///
+    /// Performs the scheduled maintenance at `now`: rotates the active key if it is due
+    /// and removes expired keys. Returns whether the keyring changed.
+    ///
+    pub fn maintain(&mut self, now: u64) -> SgxResult<bool> {
+        if self.rotation_due(now) {
+            self.rotate(now)?;
+            return Ok(true);
+        }
+        if self.remove_expired(now) {
+            self.persist()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn remove_expired(&mut self, now: u64) -> bool {
+        let overlap = self.policy.overlap;
+        let before = self.keys.len();
+        self.keys.retain(|entry| {
+            entry.retired == NOT_RETIRED || now < entry.retired.saturating_add(overlap)
+        });
+        self.keys.len() != before
+    }
+
+    fn generate(&mut self, now: u64) -> SgxError {
+        let handle = SgxEccHandle::new();
+        handle.open()?;
+        let (private, public) = handle.create_key_pair()?;
+        self.keys.push(KeyEntry {
+            key_id: self.next_key_id,
+            created: now,
+            retired: NOT_RETIRED,
+            private,
+            public,
+        });
+        self.next_key_id = self
+            .next_key_id
+            .checked_add(1)
+            .ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)?;
+        Ok(())
+    }
+
+    fn persist(&mut self) -> SgxError {
+        let version = self.version + 1;
+        let mut plaintext = self.encode(version);
+        let sealed = SgxSealedBlob::seal_batch_policy(&self.policy.seal_policy, &[&plaintext]);
+        wipe(&mut plaintext);
+        let blob = sealed?.pop().ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)?;
+
+        self.store.store(&blob.to_bytes())?;
+        let current = self.counter.increment()?;
+        if current != version {
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+        self.version = version;
+        Ok(())
+    }
+
+    fn encode(&self, version: u64) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + 8 + 4 + 4 + self.keys.len() * ENTRY_SIZE);
+        out.push(KEYRING_VERSION);
+        out.extend_from_slice(&version.to_le_bytes());
+        out.extend_from_slice(&self.next_key_id.to_le_bytes());
+        out.extend_from_slice(&(self.keys.len() as u32).to_le_bytes());
+        for entry in self.keys.iter() {
+            out.extend_from_slice(&entry.key_id.to_le_bytes());
+            out.extend_from_slice(&entry.created.to_le_bytes());
+            out.extend_from_slice(&entry.retired.to_le_bytes());
+            out.extend_from_slice(&entry.private.r);
+            out.extend_from_slice(&entry.public.gx);
+            out.extend_from_slice(&entry.public.gy);
+        }
+        out
+    }
+
+    fn decode(&mut self, bytes: &[u8]) -> SgxError {
+        fn u32_at(bytes: &[u8], at: usize) -> u32 {
+            u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+        }
+        fn u64_at(bytes: &[u8], at: usize) -> u64 {
+            u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+        }
+
+        const HEADER: usize = 1 + 8 + 4 + 4;
+        if bytes.len() < HEADER || bytes[0] != KEYRING_VERSION {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let count = u32_at(bytes, 13) as usize;
+        if count == 0 || bytes.len() != HEADER + count * ENTRY_SIZE {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        self.version = u64_at(bytes, 1);
+        self.next_key_id = u32_at(bytes, 9);
+        self.keys = bytes[HEADER..]
+            .chunks_exact(ENTRY_SIZE)
+            .map(|e| {
+                let mut entry = KeyEntry {
+                    key_id: u32_at(e, 0),
+                    created: u64_at(e, 4),
+                    retired: u64_at(e, 12),
+                    private: sgx_ec256_private_t::default(),
+                    public: sgx_ec256_public_t::default(),
+                };
+                let keys = &e[20..];
+                entry
+                    .private
+                    .r
+                    .copy_from_slice(&keys[..SGX_ECP256_KEY_SIZE]);
+                entry
+                    .public
+                    .gx
+                    .copy_from_slice(&keys[SGX_ECP256_KEY_SIZE..2 * SGX_ECP256_KEY_SIZE]);
+                entry
+                    .public
+                    .gy
+                    .copy_from_slice(&keys[2 * SGX_ECP256_KEY_SIZE..]);
+                entry
+            })
+            .collect();
+        Ok(())
+    }
+
+    ///
+    /// Closes the keyring, returning its store and counter.
+    ///
+    pub fn into_parts(self) -> (S, C) {
+        let SgxIdentityKeyring { store, counter, .. } = self;
+        (store, counter)
+    }
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
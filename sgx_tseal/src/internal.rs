@@ -24,9 +24,9 @@ use sgx_trts::trts::*;
 use sgx_tse::*;
 use sgx_types::*;
 
-/* intel sgx sdk 2.4 */
-const KEY_POLICY_KSS: uint16_t =
-    SGX_KEYPOLICY_CONFIGID | SGX_KEYPOLICY_ISVFAMILYID | SGX_KEYPOLICY_ISVEXTPRODID;
+use crate::cache::get_seal_key;
+use crate::guard::rsgx_check_unseal_guard;
+use crate::policy::SgxSealPolicy;
 
 #[derive(Clone, Default)]
 pub struct SgxInternalUnsealedData {
@@ -264,23 +264,18 @@ impl SgxInternalSealedData {
     }
 
     pub fn seal_data(additional_text: &[u8], encrypt_text: &[u8]) -> SgxResult<Self> {
-        //let attribute_mask = sgx_attributes_t{flags: SGX_FLAGS_RESERVED | SGX_FLAGS_INITTED | SGX_FLAGS_DEBUG, xfrm: 0};
-        /* intel sgx sdk 1.8 */
-        let attribute_mask = sgx_attributes_t {
-            flags: TSEAL_DEFAULT_FLAGSMASK,
-            xfrm: 0,
-        };
-        /* intel sgx sdk 2.4 */
-        let mut key_policy = SGX_KEYPOLICY_MRSIGNER;
-        let report = rsgx_self_report();
-        if (report.body.attributes.flags & SGX_FLAGS_KSS) != 0 {
-            key_policy = SGX_KEYPOLICY_MRSIGNER | KEY_POLICY_KSS;
-        }
+        Self::seal_data_policy(&SgxSealPolicy::default(), additional_text, encrypt_text)
+    }
 
+    pub fn seal_data_policy(
+        policy: &SgxSealPolicy,
+        additional_text: &[u8],
+        encrypt_text: &[u8],
+    ) -> SgxResult<Self> {
         Self::seal_data_ex(
-            key_policy,
-            attribute_mask,
-            TSEAL_DEFAULT_MISCMASK,
+            policy.get_key_policy(),
+            policy.get_attribute_mask(),
+            policy.get_misc_mask(),
             additional_text,
             encrypt_text,
         )
@@ -309,7 +304,7 @@ impl SgxInternalSealedData {
         if (key_policy
             & (!(SGX_KEYPOLICY_MRENCLAVE
                 | SGX_KEYPOLICY_MRSIGNER
-                | KEY_POLICY_KSS
+                | SGX_KEYPOLICY_KSS
                 | SGX_KEYPOLICY_NOISVPRODID))
             != 0)
             || ((key_policy & (SGX_KEYPOLICY_MRENCLAVE | SGX_KEYPOLICY_MRSIGNER)) == 0)
@@ -375,6 +370,8 @@ impl SgxInternalSealedData {
     }
 
     pub fn unseal_data(&self) -> SgxResult<SgxInternalUnsealedData> {
+        rsgx_check_unseal_guard()?;
+
         let additional_len = self.get_add_mac_txt_len();
         let encrypt_len = self.get_encrypt_txt_len();
 
@@ -404,21 +401,14 @@ impl SgxInternalSealedData {
     }
 
     pub fn mac_aadata(additional_text: &[u8]) -> SgxResult<Self> {
-        let attribute_mask = sgx_attributes_t {
-            flags: TSEAL_DEFAULT_FLAGSMASK,
-            xfrm: 0,
-        };
-        let mut key_policy: u16 = SGX_KEYPOLICY_MRSIGNER;
-        let report = rsgx_self_report();
-
-        if (report.body.attributes.flags & SGX_FLAGS_KSS) != 0 {
-            key_policy = SGX_KEYPOLICY_MRSIGNER | KEY_POLICY_KSS;
-        }
+        Self::mac_aadata_policy(&SgxSealPolicy::default(), additional_text)
+    }
 
+    pub fn mac_aadata_policy(policy: &SgxSealPolicy, additional_text: &[u8]) -> SgxResult<Self> {
         Self::mac_aadata_ex(
-            key_policy,
-            attribute_mask,
-            TSEAL_DEFAULT_MISCMASK,
+            policy.get_key_policy(),
+            policy.get_attribute_mask(),
+            policy.get_misc_mask(),
             additional_text,
         )
     }
@@ -443,7 +433,7 @@ impl SgxInternalSealedData {
         if (key_policy
             & (!(SGX_KEYPOLICY_MRENCLAVE
                 | SGX_KEYPOLICY_MRSIGNER
-                | KEY_POLICY_KSS
+                | SGX_KEYPOLICY_KSS
                 | SGX_KEYPOLICY_NOISVPRODID))
             != 0)
             || ((key_policy & (SGX_KEYPOLICY_MRENCLAVE | SGX_KEYPOLICY_MRSIGNER)) == 0)
@@ -502,6 +492,8 @@ impl SgxInternalSealedData {
     }
 
     pub fn unmac_aadata(&self) -> SgxResult<SgxInternalUnsealedData> {
+        rsgx_check_unseal_guard()?;
+
         let additional_len = self.get_add_mac_txt_len();
         let encrypt_len = self.get_encrypt_txt_len();
 
@@ -537,7 +529,7 @@ impl SgxInternalSealedData {
         payload_iv: &[u8],
         key_request: &sgx_key_request_t,
     ) -> SgxResult<Self> {
-        let mut seal_key = rsgx_get_align_key(key_request).map_err(|ret| {
+        let mut seal_key = get_seal_key(key_request).map_err(|ret| {
             if ret != sgx_status_t::SGX_ERROR_OUT_OF_MEMORY {
                 sgx_status_t::SGX_ERROR_UNEXPECTED
             } else {
@@ -572,7 +564,7 @@ impl SgxInternalSealedData {
     }
 
     fn unseal_data_helper(&self) -> SgxResult<SgxInternalUnsealedData> {
-        let mut seal_key = rsgx_get_align_key(self.get_key_request()).map_err(|ret| {
+        let mut seal_key = get_seal_key(self.get_key_request()).map_err(|ret| {
             if (ret == sgx_status_t::SGX_ERROR_INVALID_CPUSVN)
                 || (ret == sgx_status_t::SGX_ERROR_INVALID_ISVSVN)
                 || (ret == sgx_status_t::SGX_ERROR_OUT_OF_MEMORY)
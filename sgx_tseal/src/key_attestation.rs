@@ -0,0 +1,616 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Key attestation
+//!
+//! Statements that bind a key pair generated inside the enclave to the identity of the
+//! enclave and to the policy the key was generated under, the SGX analogue of Android key
+//! attestation. A verifier that trusts the statement knows that the private key exists only
+//! inside an enclave with the stated measurements, and what the enclave lets it be used for.
+//!
+//! A statement reaches a verifier in one of two forms:
+//!
+//! * In a quote: `SgxAttestedKey::attest` creates a report whose report data is the
+//!   SHA-256 hash of the statement. The verifier checks the quote, then the statement
+//!   against the report body with `SgxKeyAttestation::matches_report`.
+//! * As a signed claim: `SgxKeyAttestation::sign_claim` signs the statement with an
+//!   identity key, itself attested once with `SgxIdentityKey::attest`. The verifier checks
+//!   the claim with `SgxKeyAttestation::verify_claim`.
+//!
+//! The statement has a fixed layout of `SGX_KEY_ATTESTATION_SIZE` bytes; integers are
+//! little-endian and the public key is encoded as in `sgx_ec256_public_t`:
+//!
+//! | Offset | Size | Field                                                     |
+//! |--------|------|-----------------------------------------------------------|
+//! | 0      | 1    | Version, 1                                                |
+//! | 1      | 1    | Origin: 0 generated in the enclave, 1 imported            |
+//! | 2      | 1    | Purposes, a combination of `SGX_KEY_PURPOSE_*`            |
+//! | 3      | 1    | Exportable: 0 or 1                                        |
+//! | 4      | 1    | ECDSA nonce: 0 random, 1 deterministic, 2 hedged          |
+//! | 5      | 2    | Key policy of the seal key                                |
+//! | 7      | 16   | Attribute mask of the seal key: flags, then XFRM          |
+//! | 23     | 4    | MISCSELECT mask of the seal key                           |
+//! | 27     | 64   | Public key: x, then y                                     |
+//! | 91     | 32   | Challenge of the verifier                                 |
+//! | 123    | 32   | MRENCLAVE                                                 |
+//! | 155    | 32   | MRSIGNER                                                  |
+//! | 187    | 2    | ISVPRODID                                                 |
+//! | 189    | 2    | ISVSVN                                                    |
+//! | 191    | 16   | Attributes of the enclave: flags, then XFRM               |
+//!
+//! Bytes 2 to 26 are the encoded generation policy, whose SHA-256 hash is
+//! `SgxKeyGenerationPolicy::hash`. The report data and the signed claim both cover the
+//! label `sgx key attestation v1` followed by the statement.
+
+use crate::batch::SgxSealedBlob;
+use crate::identity::SgxIdentityKey;
+use crate::policy::SgxSealPolicy;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::ptr;
+use sgx_tcrypto::{rsgx_sha256_slice, SgxEccHandle, SgxEcdsaNonce};
+use sgx_tse::{rsgx_create_report, rsgx_self_report};
+use sgx_types::*;
+
+pub const SGX_KEY_ATTESTATION_SIZE: usize = 207;
+
+/// The key signs with ECDSA P-256.
+pub const SGX_KEY_PURPOSE_SIGN: u8 = 0x01;
+/// The key agrees on shared secrets with ECDH P-256.
+pub const SGX_KEY_PURPOSE_AGREE: u8 = 0x02;
+
+const ATTESTATION_VERSION: u8 = 1;
+const ATTESTATION_LABEL: &[u8] = b"sgx key attestation v1";
+const POLICY_SIZE: usize = 25;
+const SEALED_KEY_VERSION: u8 = 1;
+
+///
+/// Where the key of a statement comes from.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SgxKeyOrigin {
+    /// Generated inside the enclave.
+    Generated,
+    /// Imported into the enclave from outside.
+    Imported,
+}
+
+///
+/// The policy a key is generated under, as attested by its statement.
+///
+#[derive(Clone, Copy)]
+pub struct SgxKeyGenerationPolicy {
+    purposes: u8,
+    exportable: bool,
+    ecdsa_nonce: SgxEcdsaNonce,
+    seal_policy: SgxSealPolicy,
+}
+
+impl Default for SgxKeyGenerationPolicy {
+    fn default() -> Self {
+        SgxKeyGenerationPolicy {
+            purposes: SGX_KEY_PURPOSE_SIGN,
+            exportable: false,
+            ecdsa_nonce: SgxEcdsaNonce::default(),
+            seal_policy: SgxSealPolicy::default(),
+        }
+    }
+}
+
+impl SgxKeyGenerationPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What the key may be used for, a combination of `SGX_KEY_PURPOSE_*`. Defaults to
+    /// `SGX_KEY_PURPOSE_SIGN`.
+    pub fn purposes(mut self, purposes: u8) -> Self {
+        self.purposes = purposes;
+        self
+    }
+
+    /// Whether `SgxAttestedKey::export_private` may release the private key. Defaults to
+    /// false.
+    pub fn exportable(mut self, exportable: bool) -> Self {
+        self.exportable = exportable;
+        self
+    }
+
+    /// How `SgxAttestedKey::sign` chooses its nonces. Defaults to `SgxEcdsaNonce::Random`.
+    pub fn ecdsa_nonce(mut self, nonce: SgxEcdsaNonce) -> Self {
+        self.ecdsa_nonce = nonce;
+        self
+    }
+
+    /// The policy of the seal key of `SgxAttestedKey::seal`. Defaults to
+    /// `SgxSealPolicy::default()`.
+    pub fn seal_policy(mut self, policy: SgxSealPolicy) -> Self {
+        self.seal_policy = policy;
+        self
+    }
+
+    pub fn get_purposes(&self) -> u8 {
+        self.purposes
+    }
+
+    pub fn is_exportable(&self) -> bool {
+        self.exportable
+    }
+
+    pub fn get_ecdsa_nonce(&self) -> SgxEcdsaNonce {
+        self.ecdsa_nonce
+    }
+
+    pub fn get_seal_policy(&self) -> SgxSealPolicy {
+        self.seal_policy
+    }
+
+    ///
+    /// The SHA-256 hash of the encoded policy, bytes 2 to 26 of a statement.
+    ///
+    pub fn hash(&self) -> SgxResult<sgx_sha256_hash_t> {
+        rsgx_sha256_slice(&self.encode())
+    }
+
+    fn encode(&self) -> [u8; POLICY_SIZE] {
+        let mask = self.seal_policy.get_attribute_mask();
+        let mut out = [0_u8; POLICY_SIZE];
+        out[0] = self.purposes;
+        out[1] = self.exportable as u8;
+        out[2] = match self.ecdsa_nonce {
+            SgxEcdsaNonce::Random => 0,
+            SgxEcdsaNonce::Deterministic => 1,
+            SgxEcdsaNonce::Hedged => 2,
+        };
+        out[3..5].copy_from_slice(&self.seal_policy.get_key_policy().to_le_bytes());
+        out[5..13].copy_from_slice(&mask.flags.to_le_bytes());
+        out[13..21].copy_from_slice(&mask.xfrm.to_le_bytes());
+        out[21..25].copy_from_slice(&self.seal_policy.get_misc_mask().to_le_bytes());
+        out
+    }
+
+    fn decode(bytes: &[u8]) -> Option<SgxKeyGenerationPolicy> {
+        let ecdsa_nonce = match bytes[2] {
+            0 => SgxEcdsaNonce::Random,
+            1 => SgxEcdsaNonce::Deterministic,
+            2 => SgxEcdsaNonce::Hedged,
+            _ => return None,
+        };
+        if bytes[1] > 1 {
+            return None;
+        }
+        let seal_policy = SgxSealPolicy::from_parts(
+            u16_at(bytes, 3),
+            sgx_attributes_t {
+                flags: u64_at(bytes, 5),
+                xfrm: u64_at(bytes, 13),
+            },
+            u32_at(bytes, 21),
+        );
+        Some(SgxKeyGenerationPolicy {
+            purposes: bytes[0],
+            exportable: bytes[1] == 1,
+            ecdsa_nonce,
+            seal_policy,
+        })
+    }
+}
+
+///
+/// A key attestation statement, see the module documentation for its layout.
+///
+#[derive(Clone, Copy)]
+pub struct SgxKeyAttestation {
+    origin: SgxKeyOrigin,
+    policy: SgxKeyGenerationPolicy,
+    public: sgx_ec256_public_t,
+    challenge: [u8; 32],
+    mr_enclave: sgx_measurement_t,
+    mr_signer: sgx_measurement_t,
+    isv_prod_id: sgx_prod_id_t,
+    isv_svn: sgx_isv_svn_t,
+    attributes: sgx_attributes_t,
+}
+
+impl SgxKeyAttestation {
+    fn new(
+        origin: SgxKeyOrigin,
+        policy: SgxKeyGenerationPolicy,
+        public: sgx_ec256_public_t,
+        challenge: &[u8; 32],
+    ) -> SgxKeyAttestation {
+        let body = rsgx_self_report().body;
+        SgxKeyAttestation {
+            origin,
+            policy,
+            public,
+            challenge: *challenge,
+            mr_enclave: body.mr_enclave,
+            mr_signer: body.mr_signer,
+            isv_prod_id: body.isv_prod_id,
+            isv_svn: body.isv_svn,
+            attributes: body.attributes,
+        }
+    }
+
+    pub fn origin(&self) -> SgxKeyOrigin {
+        self.origin
+    }
+
+    pub fn policy(&self) -> &SgxKeyGenerationPolicy {
+        &self.policy
+    }
+
+    pub fn public_key(&self) -> &sgx_ec256_public_t {
+        &self.public
+    }
+
+    /// The challenge the verifier passed to the enclave, proving the statement is fresh.
+    pub fn challenge(&self) -> &[u8; 32] {
+        &self.challenge
+    }
+
+    pub fn mr_enclave(&self) -> &sgx_measurement_t {
+        &self.mr_enclave
+    }
+
+    pub fn mr_signer(&self) -> &sgx_measurement_t {
+        &self.mr_signer
+    }
+
+    pub fn isv_prod_id(&self) -> sgx_prod_id_t {
+        self.isv_prod_id
+    }
+
+    pub fn isv_svn(&self) -> sgx_isv_svn_t {
+        self.isv_svn
+    }
+
+    pub fn attributes(&self) -> sgx_attributes_t {
+        self.attributes
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(SGX_KEY_ATTESTATION_SIZE);
+        out.push(ATTESTATION_VERSION);
+        out.push(match self.origin {
+            SgxKeyOrigin::Generated => 0,
+            SgxKeyOrigin::Imported => 1,
+        });
+        out.extend_from_slice(&self.policy.encode());
+        out.extend_from_slice(&self.public.gx);
+        out.extend_from_slice(&self.public.gy);
+        out.extend_from_slice(&self.challenge);
+        out.extend_from_slice(&self.mr_enclave.m);
+        out.extend_from_slice(&self.mr_signer.m);
+        out.extend_from_slice(&self.isv_prod_id.to_le_bytes());
+        out.extend_from_slice(&self.isv_svn.to_le_bytes());
+        out.extend_from_slice(&self.attributes.flags.to_le_bytes());
+        out.extend_from_slice(&self.attributes.xfrm.to_le_bytes());
+        out
+    }
+
+    ///
+    /// Decodes a statement encoded by `to_bytes`, or returns `None` if `bytes` is not one.
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Option<SgxKeyAttestation> {
+        if bytes.len() != SGX_KEY_ATTESTATION_SIZE || bytes[0] != ATTESTATION_VERSION {
+            return None;
+        }
+        let origin = match bytes[1] {
+            0 => SgxKeyOrigin::Generated,
+            1 => SgxKeyOrigin::Imported,
+            _ => return None,
+        };
+        let policy = SgxKeyGenerationPolicy::decode(&bytes[2..2 + POLICY_SIZE])?;
+        let mut statement = SgxKeyAttestation {
+            origin,
+            policy,
+            public: sgx_ec256_public_t::default(),
+            challenge: [0_u8; 32],
+            mr_enclave: sgx_measurement_t::default(),
+            mr_signer: sgx_measurement_t::default(),
+            isv_prod_id: u16_at(bytes, 187),
+            isv_svn: u16_at(bytes, 189),
+            attributes: sgx_attributes_t {
+                flags: u64_at(bytes, 191),
+                xfrm: u64_at(bytes, 199),
+            },
+        };
+        statement.public.gx.copy_from_slice(&bytes[27..59]);
+        statement.public.gy.copy_from_slice(&bytes[59..91]);
+        statement.challenge.copy_from_slice(&bytes[91..123]);
+        statement.mr_enclave.m.copy_from_slice(&bytes[123..155]);
+        statement.mr_signer.m.copy_from_slice(&bytes[155..187]);
+        Some(statement)
+    }
+
+    fn message(&self) -> Vec<u8> {
+        let mut message = Vec::with_capacity(ATTESTATION_LABEL.len() + SGX_KEY_ATTESTATION_SIZE);
+        message.extend_from_slice(ATTESTATION_LABEL);
+        message.extend_from_slice(&self.to_bytes());
+        message
+    }
+
+    ///
+    /// The report data that binds the statement to a report: SHA-256 over the label and
+    /// the statement, followed by 32 zero bytes.
+    ///
+    pub fn report_data(&self) -> SgxResult<sgx_report_data_t> {
+        let hash = rsgx_sha256_slice(&self.message())?;
+        let mut report_data = sgx_report_data_t::default();
+        report_data.d[..SGX_SHA256_HASH_SIZE].copy_from_slice(&hash);
+        Ok(report_data)
+    }
+
+    ///
+    /// Checks that a report body, taken from a verified report or quote, carries the
+    /// report data of the statement and the enclave identity the statement claims.
+    ///
+    pub fn matches_report(&self, body: &sgx_report_body_t) -> SgxResult<bool> {
+        let report_data = self.report_data()?;
+        Ok(body.report_data.d[..] == report_data.d[..]
+            && body.mr_enclave.m == self.mr_enclave.m
+            && body.mr_signer.m == self.mr_signer.m
+            && body.isv_prod_id == self.isv_prod_id
+            && body.isv_svn == self.isv_svn
+            && body.attributes.flags == self.attributes.flags
+            && body.attributes.xfrm == self.attributes.xfrm)
+    }
+
+    ///
+    /// Signs the statement with an identity key, making it a claim that verifiers who know
+    /// the identity key check without a quote.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// The signing process failed due to an internal cryptography library failure.
+    ///
+    pub fn sign_claim(&self, signer: &SgxIdentityKey<'_>) -> SgxResult<sgx_ec256_signature_t> {
+        signer.sign(&self.message())
+    }
+
+    ///
+    /// Verifies a claim signed by `sign_claim` under the public identity key `signer`.
+    ///
+    pub fn verify_claim(
+        &self,
+        signer: &sgx_ec256_public_t,
+        signature: &sgx_ec256_signature_t,
+    ) -> SgxResult<bool> {
+        let handle = SgxEccHandle::new();
+        handle.open()?;
+        handle.ecdsa_verify_slice(&self.message(), signer, signature)
+    }
+}
+
+///
+/// A key pair held by the enclave together with its attestation statement.
+///
+/// # Description
+///
+/// The key can only be used as its generation policy allows: signing needs
+/// `SGX_KEY_PURPOSE_SIGN`, key agreement `SGX_KEY_PURPOSE_AGREE`, and the private key leaves
+/// the enclave only if the policy makes it exportable. Sealing keeps the key in the enclave
+/// and is always allowed. The private key is wiped when the key is dropped.
+///
+pub struct SgxAttestedKey {
+    private: sgx_ec256_private_t,
+    statement: SgxKeyAttestation,
+}
+
+impl Drop for SgxAttestedKey {
+    fn drop(&mut self) {
+        wipe(&mut self.private.r);
+    }
+}
+
+impl SgxAttestedKey {
+    ///
+    /// Generates a key pair under `policy`, attested with the verifier's `challenge`.
+    ///
+    pub fn generate(
+        policy: SgxKeyGenerationPolicy,
+        challenge: &[u8; 32],
+    ) -> SgxResult<SgxAttestedKey> {
+        let handle = SgxEccHandle::new();
+        handle.open()?;
+        let (private, public) = handle.create_key_pair()?;
+        Ok(Self::from_private(
+            SgxKeyOrigin::Generated,
+            policy,
+            private,
+            public,
+            challenge,
+        ))
+    }
+
+    pub(crate) fn from_private(
+        origin: SgxKeyOrigin,
+        policy: SgxKeyGenerationPolicy,
+        private: sgx_ec256_private_t,
+        public: sgx_ec256_public_t,
+        challenge: &[u8; 32],
+    ) -> SgxAttestedKey {
+        SgxAttestedKey {
+            private,
+            statement: SgxKeyAttestation::new(origin, policy, public, challenge),
+        }
+    }
+
+    pub fn statement(&self) -> &SgxKeyAttestation {
+        &self.statement
+    }
+
+    pub fn public_key(&self) -> &sgx_ec256_public_t {
+        &self.statement.public
+    }
+
+    ///
+    /// Creates a report for `target_info` whose report data binds the statement, see
+    /// `SgxKeyAttestation::report_data`. A quoting enclave turns it into a quote.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The target info is invalid.
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// The report or the hash could not be created.
+    ///
+    pub fn attest(&self, target_info: &sgx_target_info_t) -> SgxResult<sgx_report_t> {
+        rsgx_create_report(target_info, &self.statement.report_data()?)
+    }
+
+    ///
+    /// Signs `data` with ECDSA P-256 over SHA-256.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_FEATURE_NOT_SUPPORTED**
+    ///
+    /// The policy of the key does not include `SGX_KEY_PURPOSE_SIGN`.
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// The signing process failed due to an internal cryptography library failure.
+    ///
+    pub fn sign(&self, data: &[u8]) -> SgxResult<sgx_ec256_signature_t> {
+        let policy = &self.statement.policy;
+        if policy.purposes & SGX_KEY_PURPOSE_SIGN == 0 {
+            return Err(sgx_status_t::SGX_ERROR_FEATURE_NOT_SUPPORTED);
+        }
+        let handle = SgxEccHandle::new();
+        handle.open()?;
+        handle.ecdsa_sign_slice_with_nonce(data, &self.private, policy.ecdsa_nonce)
+    }
+
+    ///
+    /// Computes the ECDH shared secret with the public key `peer`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_FEATURE_NOT_SUPPORTED**
+    ///
+    /// The policy of the key does not include `SGX_KEY_PURPOSE_AGREE`.
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `peer` is not a valid point on the curve.
+    ///
+    pub fn agree(&self, peer: &sgx_ec256_public_t) -> SgxResult<sgx_ec256_dh_shared_t> {
+        if self.statement.policy.purposes & SGX_KEY_PURPOSE_AGREE == 0 {
+            return Err(sgx_status_t::SGX_ERROR_FEATURE_NOT_SUPPORTED);
+        }
+        let handle = SgxEccHandle::new();
+        handle.open()?;
+        handle.compute_shared_dhkey(&self.private, peer)
+    }
+
+    ///
+    /// Returns the private key.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_FEATURE_NOT_SUPPORTED**
+    ///
+    /// The policy of the key does not make it exportable.
+    ///
+    pub fn export_private(&self) -> SgxResult<sgx_ec256_private_t> {
+        if !self.statement.policy.exportable {
+            return Err(sgx_status_t::SGX_ERROR_FEATURE_NOT_SUPPORTED);
+        }
+        Ok(self.private)
+    }
+
+    ///
+    /// Seals the key and its statement with the seal policy of its generation policy.
+    ///
+    pub fn seal(&self) -> SgxResult<SgxSealedBlob> {
+        let mut plaintext = Vec::with_capacity(1 + SGX_ECP256_KEY_SIZE + SGX_KEY_ATTESTATION_SIZE);
+        plaintext.push(SEALED_KEY_VERSION);
+        plaintext.extend_from_slice(&self.private.r);
+        plaintext.extend_from_slice(&self.statement.to_bytes());
+
+        let sealed =
+            SgxSealedBlob::seal_batch_policy(&self.statement.policy.seal_policy, &[&plaintext]);
+        wipe(&mut plaintext);
+        sealed?.pop().ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)
+    }
+
+    ///
+    /// Restores a key sealed by `seal`. The statement is the one made when the key was
+    /// generated.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The blob does not hold a sealed key.
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The blob was modified or sealed by another enclave.
+    ///
+    pub fn unseal(blob: &SgxSealedBlob) -> SgxResult<SgxAttestedKey> {
+        let mut plaintext = blob.unseal()?;
+        let key = Self::decode(&plaintext);
+        wipe(&mut plaintext);
+        key
+    }
+
+    fn decode(bytes: &[u8]) -> SgxResult<SgxAttestedKey> {
+        let invalid = sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+        if bytes.len() != 1 + SGX_ECP256_KEY_SIZE + SGX_KEY_ATTESTATION_SIZE
+            || bytes[0] != SEALED_KEY_VERSION
+        {
+            return Err(invalid);
+        }
+        let statement =
+            SgxKeyAttestation::from_bytes(&bytes[1 + SGX_ECP256_KEY_SIZE..]).ok_or(invalid)?;
+        let mut key = SgxAttestedKey {
+            private: sgx_ec256_private_t::default(),
+            statement,
+        };
+        key.private
+            .r
+            .copy_from_slice(&bytes[1..1 + SGX_ECP256_KEY_SIZE]);
+        Ok(key)
+    }
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap())
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
@@ -0,0 +1,278 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Sealed key backend
+//!
+//! A `KeyBackend` whose master key lives only inside the enclave and is persisted sealed.
+
+use crate::batch::SgxSealedBlob;
+use crate::policy::SgxSealPolicy;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ptr;
+use sgx_tcrypto::kms::{rsgx_hmac_sha256_parts, KeyBackend};
+use sgx_tcrypto::{
+    rsgx_rijndael128GCM_decrypt, rsgx_rijndael128GCM_encrypt, SgxEccHandle, SgxEcdsaNonce,
+};
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+const BACKEND_VERSION: u8 = 1;
+const WRAP_VERSION: u8 = 1;
+const ROOT_SIZE: usize = 32;
+const WRAP_LABEL: &[u8] = b"sgx sealed key backend wrap";
+const MAC_LABEL: &[u8] = b"sgx sealed key backend mac";
+
+///
+/// A `KeyBackend` holding its master key in the enclave.
+///
+/// # Description
+///
+/// The master key consists of a random 256-bit root secret, from which the wrapping and
+/// MAC keys are derived, and an ECDSA P-256 key pair for signing. Keys are wrapped with
+/// AES-GCM under a random nonce, authenticated together with the key ID.
+///
+/// `seal` persists the master key with the seal policy of the backend; `unseal` restores
+/// it. The master key is wiped when the backend is dropped.
+///
+pub struct SgxSealedKeyBackend {
+    key_id: String,
+    root: [u8; ROOT_SIZE],
+    private: sgx_ec256_private_t,
+    public: sgx_ec256_public_t,
+    policy: SgxSealPolicy,
+    nonce: SgxEcdsaNonce,
+}
+
+impl Drop for SgxSealedKeyBackend {
+    fn drop(&mut self) {
+        wipe(&mut self.root);
+        wipe(&mut self.private.r);
+    }
+}
+
+impl SgxSealedKeyBackend {
+    ///
+    /// Generates a new master key named `key_id`.
+    ///
+    pub fn generate(key_id: &str) -> SgxResult<SgxSealedKeyBackend> {
+        if key_id.len() > u16::MAX as usize {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let mut root = [0_u8; ROOT_SIZE];
+        rsgx_read_rand(&mut root)?;
+        let handle = SgxEccHandle::new();
+        handle.open()?;
+        let (private, public) = handle.create_key_pair()?;
+        Ok(SgxSealedKeyBackend {
+            key_id: key_id.into(),
+            root,
+            private,
+            public,
+            policy: SgxSealPolicy::default(),
+            nonce: SgxEcdsaNonce::default(),
+        })
+    }
+
+    pub fn seal_policy(mut self, policy: SgxSealPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    ///
+    /// How `sign` chooses its nonces. Defaults to `SgxEcdsaNonce::Random`. The choice is
+    /// not sealed; a restored backend starts with the default.
+    ///
+    pub fn ecdsa_nonce(mut self, nonce: SgxEcdsaNonce) -> Self {
+        self.nonce = nonce;
+        self
+    }
+
+    ///
+    /// The public key matching the signatures of `sign`.
+    ///
+    pub fn public_key(&self) -> &sgx_ec256_public_t {
+        &self.public
+    }
+
+    ///
+    /// Seals the master key with the seal policy of the backend.
+    ///
+    pub fn seal(&self) -> SgxResult<SgxSealedBlob> {
+        let mut plaintext =
+            Vec::with_capacity(1 + 2 + self.key_id.len() + ROOT_SIZE + 3 * SGX_ECP256_KEY_SIZE);
+        plaintext.push(BACKEND_VERSION);
+        plaintext.extend_from_slice(&(self.key_id.len() as u16).to_le_bytes());
+        plaintext.extend_from_slice(self.key_id.as_bytes());
+        plaintext.extend_from_slice(&self.root);
+        plaintext.extend_from_slice(&self.private.r);
+        plaintext.extend_from_slice(&self.public.gx);
+        plaintext.extend_from_slice(&self.public.gy);
+
+        let sealed = SgxSealedBlob::seal_batch_policy(&self.policy, &[&plaintext]);
+        wipe(&mut plaintext);
+        sealed?.pop().ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)
+    }
+
+    ///
+    /// Restores a master key sealed by `seal`. The restored backend seals with the default
+    /// policy until `seal_policy` sets another.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The blob does not hold a sealed master key.
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The blob was modified or sealed by another enclave.
+    ///
+    pub fn unseal(blob: &SgxSealedBlob) -> SgxResult<SgxSealedKeyBackend> {
+        let mut plaintext = blob.unseal()?;
+        let backend = Self::decode(&plaintext);
+        wipe(&mut plaintext);
+        backend
+    }
+
+    fn decode(bytes: &[u8]) -> SgxResult<SgxSealedKeyBackend> {
+        let invalid = sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+        if bytes.len() < 3 || bytes[0] != BACKEND_VERSION {
+            return Err(invalid);
+        }
+        let id_len = u16::from_le_bytes([bytes[1], bytes[2]]) as usize;
+        if bytes.len() != 3 + id_len + ROOT_SIZE + 3 * SGX_ECP256_KEY_SIZE {
+            return Err(invalid);
+        }
+        let (id, rest) = bytes[3..].split_at(id_len);
+        let key_id = core::str::from_utf8(id).map_err(|_| invalid)?;
+
+        let mut backend = SgxSealedKeyBackend {
+            key_id: key_id.into(),
+            root: [0_u8; ROOT_SIZE],
+            private: sgx_ec256_private_t::default(),
+            public: sgx_ec256_public_t::default(),
+            policy: SgxSealPolicy::default(),
+            nonce: SgxEcdsaNonce::default(),
+        };
+        let (root, keys) = rest.split_at(ROOT_SIZE);
+        backend.root.copy_from_slice(root);
+        backend
+            .private
+            .r
+            .copy_from_slice(&keys[..SGX_ECP256_KEY_SIZE]);
+        backend
+            .public
+            .gx
+            .copy_from_slice(&keys[SGX_ECP256_KEY_SIZE..2 * SGX_ECP256_KEY_SIZE]);
+        backend
+            .public
+            .gy
+            .copy_from_slice(&keys[2 * SGX_ECP256_KEY_SIZE..]);
+        Ok(backend)
+    }
+
+    fn wrap_key(&self) -> SgxResult<sgx_aes_gcm_128bit_key_t> {
+        let mut okm = rsgx_hmac_sha256_parts(&self.root, &[WRAP_LABEL])?;
+        let mut key = sgx_aes_gcm_128bit_key_t::default();
+        key.copy_from_slice(&okm[..SGX_AESGCM_KEY_SIZE]);
+        wipe(&mut okm);
+        Ok(key)
+    }
+}
+
+impl KeyBackend for SgxSealedKeyBackend {
+    fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    fn sign(&mut self, msg: &[u8]) -> SgxResult<sgx_ec256_signature_t> {
+        let handle = SgxEccHandle::new();
+        handle.open()?;
+        handle.ecdsa_sign_slice_with_nonce(msg, &self.private, self.nonce)
+    }
+
+    ///
+    /// Wraps `key` as a version byte, a 12-byte nonce, the ciphertext and the 16-byte tag.
+    ///
+    fn wrap(&mut self, key: &[u8]) -> SgxResult<Vec<u8>> {
+        let mut wrapped = vec![0_u8; 1 + SGX_AESGCM_IV_SIZE + key.len() + SGX_AESGCM_MAC_SIZE];
+        wrapped[0] = WRAP_VERSION;
+        let (nonce, rest) = wrapped[1..].split_at_mut(SGX_AESGCM_IV_SIZE);
+        rsgx_read_rand(nonce)?;
+        let (ciphertext, tag) = rest.split_at_mut(key.len());
+
+        let mut wrap_key = self.wrap_key()?;
+        let mut mac = sgx_aes_gcm_128bit_tag_t::default();
+        let result = rsgx_rijndael128GCM_encrypt(
+            &wrap_key,
+            key,
+            nonce,
+            self.key_id.as_bytes(),
+            ciphertext,
+            &mut mac,
+        );
+        wipe(&mut wrap_key);
+        result?;
+        tag.copy_from_slice(&mac);
+        Ok(wrapped)
+    }
+
+    fn unwrap(&mut self, wrapped: &[u8]) -> SgxResult<Vec<u8>> {
+        if wrapped.len() < 1 + SGX_AESGCM_IV_SIZE + SGX_AESGCM_MAC_SIZE
+            || wrapped[0] != WRAP_VERSION
+        {
+            return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+        }
+        let (nonce, rest) = wrapped[1..].split_at(SGX_AESGCM_IV_SIZE);
+        let (ciphertext, tag) = rest.split_at(rest.len() - SGX_AESGCM_MAC_SIZE);
+        let mut mac = sgx_aes_gcm_128bit_tag_t::default();
+        mac.copy_from_slice(tag);
+
+        let mut wrap_key = self.wrap_key()?;
+        let mut key = vec![0_u8; ciphertext.len()];
+        let result = rsgx_rijndael128GCM_decrypt(
+            &wrap_key,
+            ciphertext,
+            nonce,
+            self.key_id.as_bytes(),
+            &mac,
+            &mut key,
+        );
+        wipe(&mut wrap_key);
+        match result {
+            Ok(()) => Ok(key),
+            Err(e) => {
+                wipe(&mut key);
+                Err(e)
+            }
+        }
+    }
+
+    fn mac(&mut self, data: &[u8]) -> SgxResult<sgx_hmac_256bit_tag_t> {
+        let mut mac_key = rsgx_hmac_sha256_parts(&self.root, &[MAC_LABEL])?;
+        let tag = rsgx_hmac_sha256_parts(&mac_key, &[data]);
+        wipe(&mut mac_key);
+        tag
+    }
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
@@ -0,0 +1,355 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Key import
+//!
+//! Import of a P-256 key pair from an external party into the enclave, with a signed
+//! receipt proving that the enclave holds the key under a non-exportable policy.
+//!
+//! 1. The enclave generates a transport key, an `SgxAttestedKey` with
+//!    `SGX_KEY_PURPOSE_AGREE`, and sends its statement to the external party.
+//! 2. The external party verifies the statement and wraps the key pair to the transport
+//!    key, as `rsgx_wrap_key_for_import` does.
+//! 3. `rsgx_import_key` unwraps the key pair, checks it, and returns the imported key and a
+//!    receipt signed by an identity key of the enclave.
+//!
+//! A wrapped key pair of `SGX_KEY_IMPORT_WRAPPED_SIZE` bytes consists of a version byte,
+//! 1, the ephemeral public key of the external party (64 bytes, encoded as in
+//! `sgx_ec256_public_t`), a 12-byte AES-GCM nonce, the 96-byte ciphertext and the 16-byte
+//! tag. The plaintext is the private key followed by the public key, encoded as in
+//! `sgx_ec256_private_t` and `sgx_ec256_public_t`. The AES-128-GCM key is the first 16
+//! bytes of HKDF-SHA256 with the ECDH shared secret (the x-coordinate, little-endian) as
+//! input key material, the label `sgx key import v1` as salt, and the transport public key
+//! followed by the ephemeral public key as info. The version byte and the ephemeral public
+//! key are the additional authenticated data.
+//!
+//! The imported key is attested like a generated one, with origin `Imported` and the
+//! SHA-256 hash of the wrapped key pair as challenge. The receipt is that statement, the
+//! SHA-256 hash of its policy, and an ECDSA signature over the label
+//! `sgx key import receipt v1` followed by both, `SGX_KEY_IMPORT_RECEIPT_SIZE` bytes in
+//! all.
+
+use crate::identity::SgxIdentityKey;
+use crate::key_attestation::*;
+use alloc::vec::Vec;
+use core::ptr;
+use sgx_tcrypto::kms::{rsgx_hkdf_sha256_expand, rsgx_hmac_sha256_parts};
+use sgx_tcrypto::{
+    rsgx_rijndael128GCM_decrypt, rsgx_rijndael128GCM_encrypt, rsgx_sha256_slice, SgxEccHandle,
+};
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+pub const SGX_KEY_IMPORT_WRAPPED_SIZE: usize =
+    1 + 2 * SGX_ECP256_KEY_SIZE + SGX_AESGCM_IV_SIZE + PAYLOAD_SIZE + SGX_AESGCM_MAC_SIZE;
+pub const SGX_KEY_IMPORT_RECEIPT_SIZE: usize =
+    SGX_KEY_ATTESTATION_SIZE + SGX_SHA256_HASH_SIZE + 2 * SGX_ECP256_KEY_SIZE;
+
+const IMPORT_VERSION: u8 = 1;
+const IMPORT_LABEL: &[u8] = b"sgx key import v1";
+const RECEIPT_LABEL: &[u8] = b"sgx key import receipt v1";
+const PAYLOAD_SIZE: usize = 3 * SGX_ECP256_KEY_SIZE;
+const HEADER_SIZE: usize = 1 + 2 * SGX_ECP256_KEY_SIZE;
+
+///
+/// The proof that the enclave imported a key under a non-exportable policy.
+///
+#[derive(Clone, Copy)]
+pub struct SgxKeyImportReceipt {
+    statement: SgxKeyAttestation,
+    policy_hash: sgx_sha256_hash_t,
+    signature: sgx_ec256_signature_t,
+}
+
+impl SgxKeyImportReceipt {
+    /// The attestation statement of the imported key.
+    pub fn statement(&self) -> &SgxKeyAttestation {
+        &self.statement
+    }
+
+    /// The SHA-256 hash of the policy the key was imported under.
+    pub fn policy_hash(&self) -> &sgx_sha256_hash_t {
+        &self.policy_hash
+    }
+
+    /// The SHA-256 hash of the wrapped key pair the enclave imported.
+    pub fn wrapped_hash(&self) -> &sgx_sha256_hash_t {
+        self.statement.challenge()
+    }
+
+    pub fn signature(&self) -> &sgx_ec256_signature_t {
+        &self.signature
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(SGX_KEY_IMPORT_RECEIPT_SIZE);
+        out.extend_from_slice(&self.statement.to_bytes());
+        out.extend_from_slice(&self.policy_hash);
+        for word in self.signature.x.iter().chain(self.signature.y.iter()) {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        out
+    }
+
+    ///
+    /// Decodes a receipt encoded by `to_bytes`, or returns `None` if `bytes` is not one.
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Option<SgxKeyImportReceipt> {
+        if bytes.len() != SGX_KEY_IMPORT_RECEIPT_SIZE {
+            return None;
+        }
+        let (statement, rest) = bytes.split_at(SGX_KEY_ATTESTATION_SIZE);
+        let (policy_hash, signature) = rest.split_at(SGX_SHA256_HASH_SIZE);
+        let mut receipt = SgxKeyImportReceipt {
+            statement: SgxKeyAttestation::from_bytes(statement)?,
+            policy_hash: sgx_sha256_hash_t::default(),
+            signature: sgx_ec256_signature_t::default(),
+        };
+        receipt.policy_hash.copy_from_slice(policy_hash);
+        let words = receipt
+            .signature
+            .x
+            .iter_mut()
+            .chain(receipt.signature.y.iter_mut());
+        for (word, chunk) in words.zip(signature.chunks_exact(4)) {
+            *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        Some(receipt)
+    }
+
+    fn message(statement: &SgxKeyAttestation, policy_hash: &sgx_sha256_hash_t) -> Vec<u8> {
+        let mut message = Vec::with_capacity(
+            RECEIPT_LABEL.len() + SGX_KEY_ATTESTATION_SIZE + SGX_SHA256_HASH_SIZE,
+        );
+        message.extend_from_slice(RECEIPT_LABEL);
+        message.extend_from_slice(&statement.to_bytes());
+        message.extend_from_slice(policy_hash);
+        message
+    }
+
+    ///
+    /// Verifies the receipt under the public identity key `signer`: the signature, that the
+    /// key was imported, and that the policy hash is that of a non-exportable policy.
+    ///
+    pub fn verify(&self, signer: &sgx_ec256_public_t) -> SgxResult<bool> {
+        let policy = self.statement.policy();
+        if self.statement.origin() != SgxKeyOrigin::Imported
+            || policy.is_exportable()
+            || policy.hash()? != self.policy_hash
+        {
+            return Ok(false);
+        }
+        let handle = SgxEccHandle::new();
+        handle.open()?;
+        handle.ecdsa_verify_slice(
+            &Self::message(&self.statement, &self.policy_hash),
+            signer,
+            &self.signature,
+        )
+    }
+}
+
+// The AES-GCM key of a wrapped key pair, see the module documentation.
+fn wrap_key(
+    shared: &sgx_ec256_dh_shared_t,
+    transport: &sgx_ec256_public_t,
+    ephemeral: &sgx_ec256_public_t,
+) -> SgxResult<sgx_aes_gcm_128bit_key_t> {
+    let mut prk = rsgx_hmac_sha256_parts(IMPORT_LABEL, &[&shared.s])?;
+    let mut info = [0_u8; 4 * SGX_ECP256_KEY_SIZE];
+    info[..32].copy_from_slice(&transport.gx);
+    info[32..64].copy_from_slice(&transport.gy);
+    info[64..96].copy_from_slice(&ephemeral.gx);
+    info[96..].copy_from_slice(&ephemeral.gy);
+    let mut key = sgx_aes_gcm_128bit_key_t::default();
+    let result = rsgx_hkdf_sha256_expand(&prk, &info, &mut key);
+    wipe(&mut prk);
+    result.map(|()| key)
+}
+
+///
+/// The rsgx_wrap_key_for_import function wraps a P-256 key pair to the transport key of
+/// `transport`, for `rsgx_import_key` in the enclave that attested it.
+///
+/// # Description
+///
+/// The caller must have verified `transport` before: that it comes from a quote or claim
+/// it trusts, with the expected enclave identity and a fresh challenge.
+///
+/// # Errors
+///
+/// **SGX_ERROR_FEATURE_NOT_SUPPORTED**
+///
+/// The policy of the transport key does not include `SGX_KEY_PURPOSE_AGREE`.
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// The transport public key is not a valid point on the curve.
+///
+/// **SGX_ERROR_UNEXPECTED**
+///
+/// An internal cryptography library failure occurred.
+///
+pub fn rsgx_wrap_key_for_import(
+    transport: &SgxKeyAttestation,
+    private: &sgx_ec256_private_t,
+    public: &sgx_ec256_public_t,
+) -> SgxResult<Vec<u8>> {
+    if transport.policy().get_purposes() & SGX_KEY_PURPOSE_AGREE == 0 {
+        return Err(sgx_status_t::SGX_ERROR_FEATURE_NOT_SUPPORTED);
+    }
+    let handle = SgxEccHandle::new();
+    handle.open()?;
+    let (mut ephemeral_private, ephemeral) = handle.create_key_pair()?;
+    let shared = handle.compute_shared_dhkey(&ephemeral_private, transport.public_key());
+    wipe(&mut ephemeral_private.r);
+    let mut shared = shared?;
+    let key = wrap_key(&shared, transport.public_key(), &ephemeral);
+    wipe(&mut shared.s);
+    let mut key = key?;
+
+    let mut payload = [0_u8; PAYLOAD_SIZE];
+    payload[..32].copy_from_slice(&private.r);
+    payload[32..64].copy_from_slice(&public.gx);
+    payload[64..].copy_from_slice(&public.gy);
+
+    let mut wrapped = vec![0_u8; SGX_KEY_IMPORT_WRAPPED_SIZE];
+    wrapped[0] = IMPORT_VERSION;
+    wrapped[1..33].copy_from_slice(&ephemeral.gx);
+    wrapped[33..HEADER_SIZE].copy_from_slice(&ephemeral.gy);
+    let (header, rest) = wrapped.split_at_mut(HEADER_SIZE);
+    let (nonce, rest) = rest.split_at_mut(SGX_AESGCM_IV_SIZE);
+    let (ciphertext, tag) = rest.split_at_mut(PAYLOAD_SIZE);
+    rsgx_read_rand(nonce)?;
+
+    let mut mac = sgx_aes_gcm_128bit_tag_t::default();
+    let result = rsgx_rijndael128GCM_encrypt(&key, &payload, nonce, header, ciphertext, &mut mac);
+    wipe(&mut key);
+    wipe(&mut payload);
+    result?;
+    tag.copy_from_slice(&mac);
+    Ok(wrapped)
+}
+
+///
+/// The rsgx_import_key function imports a key pair wrapped to `transport` under `policy`,
+/// and signs the receipt of the import with `signer`.
+///
+/// # Description
+///
+/// The imported key pair is checked to be consistent: the private key signs a test message
+/// that the public key verifies. The returned key is not persisted; `SgxAttestedKey::seal`
+/// keeps it inside the enclave across restarts.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// `policy` makes the key exportable, `wrapped` is malformed, or the key pair it holds is
+/// invalid or inconsistent.
+///
+/// **SGX_ERROR_MAC_MISMATCH**
+///
+/// `wrapped` was not wrapped to `transport`, or has been modified.
+///
+/// **SGX_ERROR_FEATURE_NOT_SUPPORTED**
+///
+/// The policy of the transport key does not include `SGX_KEY_PURPOSE_AGREE`.
+///
+/// **SGX_ERROR_UNEXPECTED**
+///
+/// An internal cryptography library failure occurred.
+///
+pub fn rsgx_import_key(
+    transport: &SgxAttestedKey,
+    wrapped: &[u8],
+    policy: SgxKeyGenerationPolicy,
+    signer: &SgxIdentityKey<'_>,
+) -> SgxResult<(SgxAttestedKey, SgxKeyImportReceipt)> {
+    if policy.is_exportable()
+        || wrapped.len() != SGX_KEY_IMPORT_WRAPPED_SIZE
+        || wrapped[0] != IMPORT_VERSION
+    {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    let (header, rest) = wrapped.split_at(HEADER_SIZE);
+    let (nonce, rest) = rest.split_at(SGX_AESGCM_IV_SIZE);
+    let (ciphertext, tag) = rest.split_at(PAYLOAD_SIZE);
+    let mut ephemeral = sgx_ec256_public_t::default();
+    ephemeral.gx.copy_from_slice(&header[1..33]);
+    ephemeral.gy.copy_from_slice(&header[33..]);
+    let mut mac = sgx_aes_gcm_128bit_tag_t::default();
+    mac.copy_from_slice(tag);
+
+    let mut shared = transport.agree(&ephemeral)?;
+    let key = wrap_key(&shared, transport.public_key(), &ephemeral);
+    wipe(&mut shared.s);
+    let mut key = key?;
+    let mut payload = [0_u8; PAYLOAD_SIZE];
+    let result = rsgx_rijndael128GCM_decrypt(&key, ciphertext, nonce, header, &mac, &mut payload);
+    wipe(&mut key);
+    if let Err(e) = result {
+        wipe(&mut payload);
+        return Err(e);
+    }
+
+    let mut private = sgx_ec256_private_t::default();
+    let mut public = sgx_ec256_public_t::default();
+    private.r.copy_from_slice(&payload[..32]);
+    public.gx.copy_from_slice(&payload[32..64]);
+    public.gy.copy_from_slice(&payload[64..]);
+    wipe(&mut payload);
+
+    let consistent = check_key_pair(&private, &public);
+    if !matches!(consistent, Ok(true)) {
+        wipe(&mut private.r);
+        consistent?;
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+
+    let challenge = rsgx_sha256_slice(wrapped)?;
+    let imported =
+        SgxAttestedKey::from_private(SgxKeyOrigin::Imported, policy, private, public, &challenge);
+    wipe(&mut private.r);
+
+    let statement = *imported.statement();
+    let policy_hash = policy.hash()?;
+    let signature = signer.sign(&SgxKeyImportReceipt::message(&statement, &policy_hash))?;
+    let receipt = SgxKeyImportReceipt {
+        statement,
+        policy_hash,
+        signature,
+    };
+    Ok((imported, receipt))
+}
+
+// The pairwise consistency test of an imported key pair.
+fn check_key_pair(private: &sgx_ec256_private_t, public: &sgx_ec256_public_t) -> SgxResult<bool> {
+    let handle = SgxEccHandle::new();
+    handle.open()?;
+    if !handle.check_point(public)? {
+        return Ok(false);
+    }
+    let signature = handle.ecdsa_sign_slice(IMPORT_LABEL, private)?;
+    handle.ecdsa_verify_slice(IMPORT_LABEL, public, &signature)
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
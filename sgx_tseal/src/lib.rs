@@ -81,12 +81,18 @@
 #![allow(unused_assignments)]
 #![allow(clippy::missing_safety_doc)]
 
+#[cfg(all(not(target_env = "sgx"), feature = "std"))]
+extern crate sgx_tstd as std;
+#[cfg(all(target_env = "sgx", feature = "std"))]
+extern crate std;
+
 #[macro_use]
 extern crate alloc;
 
 extern crate sgx_tcrypto;
 extern crate sgx_trts;
 extern crate sgx_tse;
+extern crate sgx_tsync;
 extern crate sgx_types;
 
 mod seal;
@@ -96,3 +102,112 @@ mod aad;
 pub use self::aad::SgxMacAadata;
 
 mod internal;
+
+mod policy;
+pub use self::policy::SgxSealPolicy;
+
+mod guard;
+pub use self::guard::{
+    rsgx_check_unseal_guard, rsgx_get_secret_class, rsgx_set_secret_class, SgxSecretClass,
+};
+
+mod batch;
+pub use self::batch::SgxSealedBlob;
+
+mod scatter;
+pub use self::scatter::{rsgx_seal_into_untrusted, rsgx_seal_into_untrusted_policy};
+
+mod hasher;
+pub use self::hasher::{
+    rsgx_seal_hasher_state, rsgx_seal_hasher_state_policy, rsgx_unseal_hasher_state,
+};
+
+mod cache;
+pub use self::cache::{
+    rsgx_invalidate_seal_key_cache, rsgx_prewarm_seal_key, rsgx_set_seal_key_cache_capacity,
+    SEAL_KEY_CACHE_MAX_CAPACITY,
+};
+
+mod key_backend;
+pub use self::key_backend::SgxSealedKeyBackend;
+
+mod envelope;
+pub use self::envelope::{SgxEnvelopePolicy, SgxSealedEnvelope, SGX_ENVELOPE_VERSION};
+
+#[cfg(feature = "counter")]
+mod counter;
+#[cfg(feature = "counter")]
+pub use self::counter::{
+    CounterReplica, CounterTransport, MonotonicCounter, SgxCounterId, SgxQuorumCounter,
+    SgxRemoteCounter, COUNTER_ID_SIZE,
+};
+#[cfg(feature = "pse")]
+pub use self::counter::{sgx_mc_uuid_t, SgxPseCounter};
+
+#[cfg(feature = "freshness")]
+mod freshness;
+#[cfg(feature = "freshness")]
+pub use self::freshness::{
+    SgxFreshState, SgxFreshnessPeer, SgxFreshnessQuorum, SgxFreshnessService, SgxStateDigest,
+    STATE_DIGEST_SIZE,
+};
+
+#[cfg(feature = "identity")]
+mod identity;
+#[cfg(feature = "identity")]
+pub use self::identity::{IdentityKeyStore, SgxIdentityKey, SgxIdentityKeyPolicy, SgxIdentityKeyring};
+
+#[cfg(feature = "idempotency")]
+mod idempotency;
+#[cfg(feature = "idempotency")]
+pub use self::idempotency::{
+    IdempotencyStore, SgxExecution, SgxIdempotencyLog, SgxIdempotencyPolicy,
+    IDEMPOTENCY_KEY_MAX_SIZE, IDEMPOTENCY_RESULT_MAX_SIZE,
+};
+
+#[cfg(feature = "config")]
+mod config;
+#[cfg(feature = "config")]
+pub use self::config::{
+    ConfigAuditLog, ConfigStore, SgxConfig, SgxConfigDigest, SgxConfigEvent, SgxConfigPolicy,
+    SgxConfigUpdate, SgxDynamicConfig, CONFIG_KEY_MAX_SIZE, CONFIG_UPDATE_MAX_SIZE,
+};
+
+#[cfg(feature = "key_attestation")]
+mod key_attestation;
+#[cfg(feature = "key_attestation")]
+pub use self::key_attestation::{
+    SgxAttestedKey, SgxKeyAttestation, SgxKeyGenerationPolicy, SgxKeyOrigin,
+    SGX_KEY_ATTESTATION_SIZE, SGX_KEY_PURPOSE_AGREE, SGX_KEY_PURPOSE_SIGN,
+};
+
+#[cfg(feature = "key_import")]
+mod key_import;
+#[cfg(feature = "key_import")]
+pub use self::key_import::{
+    rsgx_import_key, rsgx_wrap_key_for_import, SgxKeyImportReceipt, SGX_KEY_IMPORT_RECEIPT_SIZE,
+    SGX_KEY_IMPORT_WRAPPED_SIZE,
+};
+
+#[cfg(feature = "btree")]
+mod btree;
+#[cfg(feature = "btree")]
+pub use self::btree::{
+    PageId, PageStore, SealedBTreeMap, SealedBTreeRoot, SEALED_BTREE_DEFAULT_MAX_RESIDENT,
+    SEALED_BTREE_DEFAULT_ORDER,
+};
+
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "mmap")]
+pub use self::mmap::{
+    SealedMmap, SealedMmapRoot, SealedMmapWriter, SEALED_MMAP_DEFAULT_MAX_RESIDENT,
+    SEALED_MMAP_DEFAULT_PAGE_SIZE,
+};
+
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "std")]
+pub use self::stream::{
+    SealingWriter, UnsealingReader, SEALING_DEFAULT_CHUNK_SIZE, SEALING_MAX_CHUNK_SIZE,
+};
@@ -0,0 +1,385 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::btree::{seal_blob, unseal_blob, PageId, PageStore};
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::ptr;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+
+const PAGE_AAD_MAGIC: &[u8; 8] = b"SMMAPPG\0";
+
+///
+/// The default size of the pages of a sealed dataset.
+///
+pub const SEALED_MMAP_DEFAULT_PAGE_SIZE: usize = 64 * 1024;
+
+///
+/// The default number of pages kept decrypted in enclave memory.
+///
+pub const SEALED_MMAP_DEFAULT_MAX_RESIDENT: usize = 256;
+
+///
+/// The trusted description of a sealed dataset, returned by `SealedMmapWriter::finish`.
+///
+/// Every page is bound to the random `id` of its dataset and to its index, so the
+/// store can neither reorder pages nor mix in pages of another dataset. The root
+/// itself must be kept in trusted memory or sealed, as a store could otherwise
+/// substitute a whole other dataset with its own root.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SealedMmapRoot {
+    pub id: [u8; 16],
+    pub len: u64,
+    pub page_size: u32,
+}
+
+impl SealedMmapRoot {
+    fn pages(&self) -> u64 {
+        (self.len + self.page_size as u64 - 1) / self.page_size as u64
+    }
+
+    // The length of page `index`, which is short only for the last page.
+    fn page_len(&self, index: u64) -> usize {
+        let start = index * self.page_size as u64;
+        (self.len - start).min(self.page_size as u64) as usize
+    }
+
+    fn aad(&self, index: u64) -> [u8; 32] {
+        let mut aad = [0_u8; 32];
+        aad[..8].copy_from_slice(PAGE_AAD_MAGIC);
+        aad[8..24].copy_from_slice(&self.id);
+        aad[24..].copy_from_slice(&index.to_le_bytes());
+        aad
+    }
+}
+
+///
+/// Writes a dataset to a PageStore, one sealed page at a time.
+///
+/// Data is buffered until it fills a page, so the writer holds at most one page
+/// of plaintext.
+///
+pub struct SealedMmapWriter<S: PageStore> {
+    store: S,
+    root: SealedMmapRoot,
+    buf: Vec<u8>,
+}
+
+impl<S: PageStore> SealedMmapWriter<S> {
+    ///
+    /// Create a writer of a new dataset with pages of `SEALED_MMAP_DEFAULT_PAGE_SIZE`.
+    ///
+    pub fn new(store: S) -> SgxResult<SealedMmapWriter<S>> {
+        Self::with_page_size(store, SEALED_MMAP_DEFAULT_PAGE_SIZE)
+    }
+
+    ///
+    /// Create a writer of a new dataset with pages of `page_size` bytes.
+    ///
+    /// Data pages are written from page 0, so `store` should be empty.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `page_size` is zero or not below 4 GB.
+    ///
+    pub fn with_page_size(store: S, page_size: usize) -> SgxResult<SealedMmapWriter<S>> {
+        if page_size == 0 || page_size >= u32::MAX as usize {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        let mut root = SealedMmapRoot {
+            page_size: page_size as u32,
+            ..Default::default()
+        };
+        rsgx_read_rand(&mut root.id)?;
+        Ok(SealedMmapWriter {
+            store,
+            root,
+            buf: Vec::with_capacity(page_size),
+        })
+    }
+
+    ///
+    /// Append `data` to the dataset.
+    ///
+    pub fn write(&mut self, mut data: &[u8]) -> SgxError {
+        let page_size = self.root.page_size as usize;
+        while !data.is_empty() {
+            let n = (page_size - self.buf.len()).min(data.len());
+            self.buf.extend_from_slice(&data[..n]);
+            self.root.len += n as u64;
+            data = &data[n..];
+            if self.buf.len() == page_size {
+                self.write_page()?;
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Write the last partial page and return the root of the dataset together
+    /// with the store.
+    ///
+    pub fn finish(mut self) -> SgxResult<(SealedMmapRoot, S)> {
+        if !self.buf.is_empty() {
+            self.write_page()?;
+        }
+        Ok((self.root, self.store))
+    }
+
+    fn write_page(&mut self) -> SgxError {
+        let index = (self.root.len - 1) / self.root.page_size as u64;
+        let blob = seal_blob(&self.root.aad(index), &self.buf);
+        wipe(&mut self.buf);
+        self.buf.clear();
+        self.store.write_page(index as PageId, &blob?)
+    }
+}
+
+// A decrypted page, wiped when it is evicted.
+struct Page {
+    data: Vec<u8>,
+    tick: u64,
+}
+
+///
+/// Read access by address range to a sealed dataset larger than the EPC.
+///
+/// Pages are read from the store and unsealed when first accessed, and kept in a
+/// cache of at most `max_resident` pages. When the cache is full, the least
+/// recently used page is wiped and dropped before the next one is loaded, so the
+/// plaintext held by the enclave stays bounded whatever the size of the dataset.
+///
+/// Ranges within one page are served straight from the cache. Ranges that span
+/// pages are copied into a buffer, page by page.
+///
+pub struct SealedMmap<S: PageStore> {
+    store: S,
+    root: SealedMmapRoot,
+    pages: BTreeMap<u64, Page>,
+    // The resident pages by last access.
+    lru: BTreeMap<u64, u64>,
+    max_resident: usize,
+    tick: u64,
+}
+
+impl<S: PageStore> SealedMmap<S> {
+    ///
+    /// Open the dataset described by `root` in `store`.
+    ///
+    /// No page is read until it is accessed.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// The page size of `root` is zero.
+    ///
+    pub fn open(store: S, root: SealedMmapRoot) -> SgxResult<SealedMmap<S>> {
+        if root.page_size == 0 {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        Ok(SealedMmap {
+            store,
+            root,
+            pages: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            max_resident: SEALED_MMAP_DEFAULT_MAX_RESIDENT,
+            tick: 0,
+        })
+    }
+
+    ///
+    /// Set the number of pages kept in enclave memory, at least 1.
+    ///
+    /// Pages beyond the new bound are evicted at once.
+    ///
+    pub fn set_max_resident(&mut self, max_resident: usize) {
+        self.max_resident = max_resident.max(1);
+        self.shrink(self.max_resident);
+    }
+
+    ///
+    /// Get the length of the dataset in bytes.
+    ///
+    pub fn len(&self) -> u64 {
+        self.root.len
+    }
+
+    ///
+    /// Returns true if the dataset is empty.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.root.len == 0
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.root.page_size as usize
+    }
+
+    pub fn root(&self) -> &SealedMmapRoot {
+        &self.root
+    }
+
+    ///
+    /// Get the number of pages currently held in enclave memory.
+    ///
+    pub fn resident_pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    ///
+    /// Get a reference to the underlying store.
+    ///
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    ///
+    /// Get the bytes of `range`.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `range` is reversed or extends past the end of the dataset.
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// A page read from the store is not the page of this dataset at that index.
+    ///
+    pub fn get(&mut self, range: Range<u64>) -> SgxResult<Cow<'_, [u8]>> {
+        self.check_range(&range)?;
+        if range.is_empty() {
+            return Ok(Cow::Borrowed(&[]));
+        }
+        let page_size = self.root.page_size as u64;
+        let first = range.start / page_size;
+        if (range.end - 1) / page_size == first {
+            let start = (range.start - first * page_size) as usize;
+            let end = (range.end - first * page_size) as usize;
+            return Ok(Cow::Borrowed(&self.page(first)?[start..end]));
+        }
+        let mut buf = vec![0_u8; (range.end - range.start) as usize];
+        self.read_at(range.start, &mut buf)?;
+        Ok(Cow::Owned(buf))
+    }
+
+    ///
+    /// Fill `buf` with the bytes at `offset`.
+    ///
+    /// # Errors
+    ///
+    /// As `get`.
+    ///
+    pub fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> SgxError {
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+        self.check_range(&(offset..end))?;
+        let page_size = self.root.page_size as u64;
+        let mut pos = offset;
+        let mut done = 0;
+        while done < buf.len() {
+            let index = pos / page_size;
+            let start = (pos - index * page_size) as usize;
+            let page = self.page(index)?;
+            let n = (page.len() - start).min(buf.len() - done);
+            buf[done..done + n].copy_from_slice(&page[start..start + n]);
+            done += n;
+            pos += n as u64;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Get the plaintext of page `index`, which holds the bytes from
+    /// `index * page_size()`.
+    ///
+    pub fn page(&mut self, index: u64) -> SgxResult<&[u8]> {
+        if index >= self.root.pages() {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(page) = self.pages.get_mut(&index) {
+            self.lru.remove(&page.tick);
+            self.lru.insert(tick, index);
+            page.tick = tick;
+        } else {
+            // Evict first, so that the cache never exceeds its bound.
+            self.shrink(self.max_resident - 1);
+            let blob = self.store.read_page(index as PageId)?;
+            let data = unseal_blob(&self.root.aad(index), &blob)?;
+            if data.len() != self.root.page_len(index) {
+                return Err(sgx_status_t::SGX_ERROR_MAC_MISMATCH);
+            }
+            self.pages.insert(index, Page { data, tick });
+            self.lru.insert(tick, index);
+        }
+        Ok(&self.pages[&index].data)
+    }
+
+    ///
+    /// Wipe and drop every cached page.
+    ///
+    pub fn evict_all(&mut self) {
+        self.shrink(0);
+    }
+
+    ///
+    /// Wipe the cache and return the store.
+    ///
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    fn check_range(&self, range: &Range<u64>) -> SgxError {
+        if range.start > range.end || range.end > self.root.len {
+            return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+        }
+        Ok(())
+    }
+
+    fn shrink(&mut self, target: usize) {
+        while self.pages.len() > target {
+            let tick = match self.lru.keys().next() {
+                Some(tick) => *tick,
+                None => break,
+            };
+            let index = self.lru.remove(&tick).expect("least recent page is listed");
+            self.pages.remove(&index);
+        }
+    }
+}
+
+impl Drop for Page {
+    fn drop(&mut self) {
+        wipe(&mut self.data);
+    }
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
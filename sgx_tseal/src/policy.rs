@@ -0,0 +1,132 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use sgx_tse::rsgx_is_kss_enabled;
+use sgx_types::*;
+
+///
+/// The key derivation policy used to seal data.
+///
+/// The default policy is the one used by `seal_data` and `mac_aadata`: MRSIGNER, the default
+/// attribute and MISCSELECT masks and, if the enclave was launched with KSS, all KSS fields.
+///
+/// The KSS fields bind the seal key to the enclave's CONFIGID, ISVFAMILYID or ISVEXTPRODID,
+/// so that data sealed by one configuration or product of a family cannot be unsealed by
+/// another.
+///
+#[derive(Clone, Copy)]
+pub struct SgxSealPolicy {
+    key_policy: u16,
+    attribute_mask: sgx_attributes_t,
+    misc_mask: sgx_misc_select_t,
+}
+
+impl Default for SgxSealPolicy {
+    fn default() -> Self {
+        let key_policy = if rsgx_is_kss_enabled() {
+            SGX_KEYPOLICY_MRSIGNER | SGX_KEYPOLICY_KSS
+        } else {
+            SGX_KEYPOLICY_MRSIGNER
+        };
+        SgxSealPolicy {
+            key_policy,
+            attribute_mask: sgx_attributes_t {
+                flags: TSEAL_DEFAULT_FLAGSMASK,
+                xfrm: 0,
+            },
+            misc_mask: TSEAL_DEFAULT_MISCMASK,
+        }
+    }
+}
+
+impl SgxSealPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds the key to MRENCLAVE instead of MRSIGNER.
+    pub fn mr_enclave(mut self) -> Self {
+        self.key_policy = (self.key_policy & !SGX_KEYPOLICY_MRSIGNER) | SGX_KEYPOLICY_MRENCLAVE;
+        self
+    }
+
+    /// Binds the key to MRSIGNER instead of MRENCLAVE.
+    pub fn mr_signer(mut self) -> Self {
+        self.key_policy = (self.key_policy & !SGX_KEYPOLICY_MRENCLAVE) | SGX_KEYPOLICY_MRSIGNER;
+        self
+    }
+
+    pub fn no_isv_prod_id(self, enable: bool) -> Self {
+        self.policy_bit(SGX_KEYPOLICY_NOISVPRODID, enable)
+    }
+
+    pub fn config_id(self, enable: bool) -> Self {
+        self.policy_bit(SGX_KEYPOLICY_CONFIGID, enable)
+    }
+
+    pub fn isv_family_id(self, enable: bool) -> Self {
+        self.policy_bit(SGX_KEYPOLICY_ISVFAMILYID, enable)
+    }
+
+    pub fn isv_ext_prod_id(self, enable: bool) -> Self {
+        self.policy_bit(SGX_KEYPOLICY_ISVEXTPRODID, enable)
+    }
+
+    pub fn attribute_mask(mut self, attribute_mask: sgx_attributes_t) -> Self {
+        self.attribute_mask = attribute_mask;
+        self
+    }
+
+    pub fn misc_mask(mut self, misc_mask: sgx_misc_select_t) -> Self {
+        self.misc_mask = misc_mask;
+        self
+    }
+
+    pub fn get_key_policy(&self) -> u16 {
+        self.key_policy
+    }
+
+    pub fn get_attribute_mask(&self) -> sgx_attributes_t {
+        self.attribute_mask
+    }
+
+    pub fn get_misc_mask(&self) -> sgx_misc_select_t {
+        self.misc_mask
+    }
+
+    #[cfg(feature = "key_attestation")]
+    pub(crate) fn from_parts(
+        key_policy: u16,
+        attribute_mask: sgx_attributes_t,
+        misc_mask: sgx_misc_select_t,
+    ) -> Self {
+        SgxSealPolicy {
+            key_policy,
+            attribute_mask,
+            misc_mask,
+        }
+    }
+
+    fn policy_bit(mut self, bit: u16, enable: bool) -> Self {
+        if enable {
+            self.key_policy |= bit;
+        } else {
+            self.key_policy &= !bit;
+        }
+        self
+    }
+}
@@ -0,0 +1,208 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Sealing straight into untrusted memory.
+//!
+//! `seal_data` encrypts into a buffer on the enclave heap, which `to_raw_sealed_data_t` then
+//! copies out to the host: a large sealed write goes through enclave memory twice, and needs
+//! an enclave allocation the size of the payload. `rsgx_seal_into_untrusted` instead writes
+//! the sealed data straight into a buffer of the host, once its size has been checked.
+//!
+//! The plaintext can be gathered from several slices of the enclave. It is encrypted a chunk at
+//! a time through a small enclave buffer that stays in cache, so that the ciphertext is never
+//! read back from untrusted memory, where the host could change it before it is
+//! authenticated.
+//!
+//! The output is an ordinary `sgx_sealed_data_t`, which `SgxSealedData::from_raw_sealed_data_t`
+//! and `unseal_data` read back.
+
+use crate::batch::{fresh_key_request, wipe};
+use crate::cache::get_seal_key;
+use crate::internal::SgxInternalSealedData;
+use crate::policy::SgxSealPolicy;
+use core::mem;
+use core::ptr;
+use sgx_tcrypto::SgxAesHandle;
+use sgx_trts::trts::*;
+use sgx_types::*;
+
+const CHUNK_SIZE: usize = 4096;
+
+///
+/// rsgx_seal_into_untrusted seals data with the default seal policy into a buffer outside
+/// the enclave.
+///
+/// See `rsgx_seal_into_untrusted_policy`.
+///
+pub unsafe fn rsgx_seal_into_untrusted(
+    additional_text: &[u8],
+    encrypt_parts: &[&[u8]],
+    p: *mut sgx_sealed_data_t,
+    len: u32,
+) -> SgxResult<u32> {
+    rsgx_seal_into_untrusted_policy(
+        &SgxSealPolicy::default(),
+        additional_text,
+        encrypt_parts,
+        p,
+        len,
+    )
+}
+
+///
+/// rsgx_seal_into_untrusted_policy seals data with the key policy of `policy` into a buffer
+/// outside the enclave.
+///
+/// # Parameters
+///
+/// **additional_text**
+///
+/// The additional data, authenticated but not encrypted. It can be empty, and within or
+/// outside the enclave.
+///
+/// **encrypt_parts**
+///
+/// The data to encrypt, as the concatenation of the slices. All must be within the enclave.
+///
+/// **p**
+///
+/// The untrusted buffer to write the sealed data to.
+///
+/// **len**
+///
+/// The size of the buffer, which must be at least `calc_raw_sealed_data_size` of the sizes of
+/// the additional text and of the data to encrypt.
+///
+/// # Return value
+///
+/// The number of bytes of the buffer written.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// The data to encrypt is empty, too large, or not within the enclave; the buffer is null, too
+/// small, or not entirely outside the enclave; or the key policy is invalid.
+///
+/// **SGX_ERROR_OUT_OF_MEMORY**
+///
+/// The enclave is out of memory.
+///
+/// **SGX_ERROR_UNEXPECTED**
+///
+/// The seal key could not be derived, or a crypto library failure occurred.
+///
+pub unsafe fn rsgx_seal_into_untrusted_policy(
+    policy: &SgxSealPolicy,
+    additional_text: &[u8],
+    encrypt_parts: &[&[u8]],
+    p: *mut sgx_sealed_data_t,
+    len: u32,
+) -> SgxResult<u32> {
+    let encrypt_len = encrypt_parts
+        .iter()
+        .try_fold(0_usize, |acc, part| acc.checked_add(part.len()))
+        .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+    let additional_len = additional_text.len();
+    if encrypt_len == 0 || encrypt_len >= u32::MAX as usize || additional_len >= u32::MAX as usize {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    let size =
+        SgxInternalSealedData::calc_raw_sealed_data_size(additional_len as u32, encrypt_len as u32);
+    if size == u32::MAX || len < size || p.is_null() {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    if !rsgx_raw_is_outside_enclave(p as *const u8, size as usize) {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    if encrypt_parts
+        .iter()
+        .any(|part| !part.is_empty() && !rsgx_slice_is_within_enclave(part))
+    {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    if additional_len > 0
+        && !rsgx_slice_is_within_enclave(additional_text)
+        && !rsgx_slice_is_outside_enclave(additional_text)
+    {
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+
+    let key_request = fresh_key_request(policy)?;
+    let mut seal_key = get_seal_key(&key_request).map_err(|ret| {
+        if ret != sgx_status_t::SGX_ERROR_OUT_OF_MEMORY {
+            sgx_status_t::SGX_ERROR_UNEXPECTED
+        } else {
+            ret
+        }
+    })?;
+
+    let out = (p as *mut u8).add(mem::size_of::<sgx_sealed_data_t>());
+    let tag = encrypt_into(&seal_key.key, additional_text, encrypt_parts, out);
+    wipe(&mut seal_key);
+    let tag = tag?;
+
+    if additional_len > 0 {
+        ptr::copy_nonoverlapping(
+            additional_text.as_ptr(),
+            out.add(encrypt_len),
+            additional_len,
+        );
+    }
+
+    let header = sgx_sealed_data_t {
+        key_request,
+        plain_text_offset: encrypt_len as u32,
+        aes_data: sgx_aes_gcm_data_t {
+            payload_size: (encrypt_len + additional_len) as u32,
+            payload_tag: tag,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    ptr::copy_nonoverlapping(
+        &header as *const sgx_sealed_data_t as *const u8,
+        p as *mut u8,
+        mem::size_of::<sgx_sealed_data_t>(),
+    );
+
+    Ok(size)
+}
+
+// Encrypts the parts to `out`, through an enclave buffer, and returns the tag.
+unsafe fn encrypt_into(
+    key: &sgx_key_128bit_t,
+    additional_text: &[u8],
+    encrypt_parts: &[&[u8]],
+    mut out: *mut u8,
+) -> SgxResult<sgx_aes_gcm_128bit_tag_t> {
+    let handle = SgxAesHandle::new();
+    handle.init(key, &[0_u8; SGX_SEAL_IV_SIZE], additional_text)?;
+
+    let mut chunk = [0_u8; CHUNK_SIZE];
+    for part in encrypt_parts.iter() {
+        for src in part.chunks(CHUNK_SIZE) {
+            let dst = &mut chunk[..src.len()];
+            handle.update(src, dst)?;
+            ptr::copy_nonoverlapping(dst.as_ptr(), out, dst.len());
+            out = out.add(dst.len());
+        }
+    }
+    let tag = handle.get_mac();
+    handle.close()?;
+    tag
+}
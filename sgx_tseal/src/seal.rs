@@ -28,6 +28,7 @@
 //! The library also provides APIs to help calculate the sealed data size, encrypt text length, and Message Authentication Code (MAC) text length.
 //!
 use crate::internal::*;
+use crate::policy::SgxSealPolicy;
 use alloc::boxed::Box;
 use alloc::slice;
 use core::marker::PhantomData;
@@ -229,6 +230,10 @@ impl<'a, T: 'a + Copy + ContiguousMemory> SgxSealedData<'a, T> {
     /// ---|---|---
     /// KEYPOLICY_MRENCLAVE | 0x0001 | -Derive key using the enclave??s ENCLAVE measurement register
     /// KEYPOLICY_MRSIGNER |0x0002 | -Derive key using the enclave??s SIGNER measurement register
+    /// KEYPOLICY_NOISVPRODID | 0x0004 | -Derive key without the enclave's ISVPRODID
+    /// KEYPOLICY_CONFIGID | 0x0008 | -Derive key with the enclave's CONFIGID (KSS)
+    /// KEYPOLICY_ISVFAMILYID | 0x0010 | -Derive key with the enclave's ISVFAMILYID (KSS)
+    /// KEYPOLICY_ISVEXTPRODID | 0x0020 | -Derive key with the enclave's ISVEXTPRODID (KSS)
     ///
     /// **attribute_mask**
     ///
@@ -302,6 +307,26 @@ impl<'a, T: 'a + Copy + ContiguousMemory> SgxSealedData<'a, T> {
         })
     }
 
+    ///
+    /// Seals the data with the key policy, attribute mask and misc mask of `policy`.
+    ///
+    /// This is equivalent to calling `seal_data_ex` with the values of `policy`, and is
+    /// the usual way to seal to the KSS identity of the enclave. See `SgxSealPolicy`.
+    ///
+    pub fn seal_data_policy(
+        policy: &SgxSealPolicy,
+        additional_text: &[u8],
+        encrypt_text: &'a T,
+    ) -> SgxResult<Self> {
+        Self::seal_data_ex(
+            policy.get_key_policy(),
+            policy.get_attribute_mask(),
+            policy.get_misc_mask(),
+            additional_text,
+            encrypt_text,
+        )
+    }
+
     ///
     /// This function is used to AES-GCM decrypt the input sealed data structure.
     /// Two output data sets result: one is the decrypted data; the second is the
@@ -335,6 +360,10 @@ impl<'a, T: 'a + Copy + ContiguousMemory> SgxSealedData<'a, T> {
     /// The CPUSVN in the sealed data blob is beyond the CPUSVN value of the platform.
     /// SGX_ERROR_INVALID_ISVSVN The ISVSVN in the sealed data blob is greater than the ISVSVN value of the enclave.
     ///
+    /// **SGX_ERROR_INVALID_ATTRIBUTE**
+    ///
+    /// The enclave holds production secrets and is debuggable, see `rsgx_set_secret_class`.
+    ///
     /// **SGX_ERROR_MAC_MISMATCH**
     ///
     /// The tag verification failed during unsealing. The error may be caused by a platform update,
@@ -547,6 +576,10 @@ impl<'a, T: 'a + Copy + ContiguousMemory> SgxSealedData<'a, [T]> {
     /// ---|---|---
     /// KEYPOLICY_MRENCLAVE | 0x0001 | -Derive key using the enclave??s ENCLAVE measurement register
     /// KEYPOLICY_MRSIGNER |0x0002 | -Derive key using the enclave??s SIGNER measurement register
+    /// KEYPOLICY_NOISVPRODID | 0x0004 | -Derive key without the enclave's ISVPRODID
+    /// KEYPOLICY_CONFIGID | 0x0008 | -Derive key with the enclave's CONFIGID (KSS)
+    /// KEYPOLICY_ISVFAMILYID | 0x0010 | -Derive key with the enclave's ISVFAMILYID (KSS)
+    /// KEYPOLICY_ISVEXTPRODID | 0x0020 | -Derive key with the enclave's ISVEXTPRODID (KSS)
     ///
     /// **attribute_mask**
     ///
@@ -618,6 +651,26 @@ impl<'a, T: 'a + Copy + ContiguousMemory> SgxSealedData<'a, [T]> {
         })
     }
 
+    ///
+    /// Seals the data with the key policy, attribute mask and misc mask of `policy`.
+    ///
+    /// This is equivalent to calling `seal_data_ex` with the values of `policy`, and is
+    /// the usual way to seal to the KSS identity of the enclave. See `SgxSealPolicy`.
+    ///
+    pub fn seal_data_policy(
+        policy: &SgxSealPolicy,
+        additional_text: &[u8],
+        encrypt_text: &'a [T],
+    ) -> SgxResult<Self> {
+        Self::seal_data_ex(
+            policy.get_key_policy(),
+            policy.get_attribute_mask(),
+            policy.get_misc_mask(),
+            additional_text,
+            encrypt_text,
+        )
+    }
+
     ///
     /// This function is used to AES-GCM decrypt the input sealed data structure.
     /// Two output data sets result: one is the decrypted data; the second is the
@@ -651,6 +704,10 @@ impl<'a, T: 'a + Copy + ContiguousMemory> SgxSealedData<'a, [T]> {
     /// The CPUSVN in the sealed data blob is beyond the CPUSVN value of the platform.
     /// SGX_ERROR_INVALID_ISVSVN The ISVSVN in the sealed data blob is greater than the ISVSVN value of the enclave.
     ///
+    /// **SGX_ERROR_INVALID_ATTRIBUTE**
+    ///
+    /// The enclave holds production secrets and is debuggable, see `rsgx_set_secret_class`.
+    ///
     /// **SGX_ERROR_MAC_MISMATCH**
     ///
     /// The tag verification failed during unsealing. The error may be caused by a platform update,
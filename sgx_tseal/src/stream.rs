@@ -0,0 +1,487 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Streaming sealing of large payloads.
+//!
+//! `SealingWriter` seals a stream in chunks of a fixed size, and `UnsealingReader`
+//! unseals it again, so that neither holds more than one chunk in the enclave heap.
+//!
+//! The stream starts with a header carrying the key request, a random nonce prefix and
+//! the chunk size. Each chunk is encrypted with AES-GCM under the nonce
+//!
+//!   nonce prefix (7 bytes) | chunk counter (4 bytes, big-endian) | last flag (1 byte)
+//!
+//! and authenticates the header. Chunks that are reordered, dropped, duplicated or taken
+//! from another stream fail to decrypt, and so does a stream cut short, since only its
+//! final chunk is sealed with the last flag set. All chunks but the final one hold exactly
+//! the chunk size of plaintext; the final one holds at most the chunk size.
+
+use crate::batch::{fresh_key_request, wipe};
+use crate::cache::get_seal_key;
+use crate::guard::rsgx_check_unseal_guard;
+use crate::policy::SgxSealPolicy;
+use core::convert::TryInto;
+use core::ptr;
+use sgx_tcrypto::*;
+use sgx_trts::trts::rsgx_read_rand;
+use sgx_types::*;
+use std::io::{self, Read, Write};
+use std::vec::Vec;
+
+const STREAM_MAGIC: [u8; 4] = *b"SGXS";
+const STREAM_VERSION: u8 = 1;
+const NONCE_PREFIX_SIZE: usize = 7;
+const STREAM_HEADER_SIZE: usize = 4 // magic
+    + 1 // version
+    + 2 // key policy
+    + 2 // ISVSVN
+    + 2 // CONFIGSVN
+    + SGX_CPUSVN_SIZE
+    + 16 // attribute mask
+    + 4 // misc mask
+    + SGX_KEYID_SIZE
+    + NONCE_PREFIX_SIZE
+    + 4; // chunk size
+
+/// The chunk size used by `SealingWriter::new`.
+pub const SEALING_DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The largest chunk size a stream may use.
+pub const SEALING_MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+fn chunk_nonce(
+    prefix: &[u8; NONCE_PREFIX_SIZE],
+    counter: u32,
+    last: bool,
+) -> [u8; SGX_SEAL_IV_SIZE] {
+    let mut nonce = [0_u8; SGX_SEAL_IV_SIZE];
+    nonce[..NONCE_PREFIX_SIZE].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_SIZE..NONCE_PREFIX_SIZE + 4].copy_from_slice(&counter.to_be_bytes());
+    nonce[SGX_SEAL_IV_SIZE - 1] = last as u8;
+    nonce
+}
+
+fn encode_header(
+    key_request: &sgx_key_request_t,
+    prefix: &[u8; NONCE_PREFIX_SIZE],
+    chunk_size: u32,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(STREAM_HEADER_SIZE);
+    out.extend_from_slice(&STREAM_MAGIC);
+    out.push(STREAM_VERSION);
+    out.extend_from_slice(&key_request.key_policy.to_le_bytes());
+    out.extend_from_slice(&key_request.isv_svn.to_le_bytes());
+    out.extend_from_slice(&key_request.config_svn.to_le_bytes());
+    out.extend_from_slice(&key_request.cpu_svn.svn);
+    out.extend_from_slice(&key_request.attribute_mask.flags.to_le_bytes());
+    out.extend_from_slice(&key_request.attribute_mask.xfrm.to_le_bytes());
+    out.extend_from_slice(&key_request.misc_mask.to_le_bytes());
+    out.extend_from_slice(&key_request.key_id.id);
+    out.extend_from_slice(prefix);
+    out.extend_from_slice(&chunk_size.to_le_bytes());
+    out
+}
+
+fn decode_header(
+    header: &[u8; STREAM_HEADER_SIZE],
+) -> io::Result<(sgx_key_request_t, [u8; NONCE_PREFIX_SIZE], usize)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid sealed stream header");
+    let mut rest = &header[..];
+    let mut take = |n: usize| {
+        let (head, tail) = rest.split_at(n);
+        rest = tail;
+        head
+    };
+    if take(4) != STREAM_MAGIC {
+        return Err(invalid());
+    }
+    if take(1) != [STREAM_VERSION] {
+        return Err(io::Error::from_sgx_error(
+            sgx_status_t::SGX_ERROR_INVALID_VERSION,
+        ));
+    }
+    let key_policy = u16::from_le_bytes(take(2).try_into().unwrap());
+    let isv_svn = u16::from_le_bytes(take(2).try_into().unwrap());
+    let config_svn = u16::from_le_bytes(take(2).try_into().unwrap());
+    let cpu_svn = sgx_cpu_svn_t {
+        svn: take(SGX_CPUSVN_SIZE).try_into().unwrap(),
+    };
+    let attribute_mask = sgx_attributes_t {
+        flags: u64::from_le_bytes(take(8).try_into().unwrap()),
+        xfrm: u64::from_le_bytes(take(8).try_into().unwrap()),
+    };
+    let misc_mask = u32::from_le_bytes(take(4).try_into().unwrap());
+    let key_id = sgx_key_id_t {
+        id: take(SGX_KEYID_SIZE).try_into().unwrap(),
+    };
+    let prefix = take(NONCE_PREFIX_SIZE).try_into().unwrap();
+    let chunk_size = u32::from_le_bytes(take(4).try_into().unwrap()) as usize;
+    if chunk_size == 0 || chunk_size > SEALING_MAX_CHUNK_SIZE {
+        return Err(invalid());
+    }
+
+    let key_request = sgx_key_request_t {
+        key_name: SGX_KEYSELECT_SEAL,
+        key_policy,
+        isv_svn,
+        cpu_svn,
+        attribute_mask,
+        key_id,
+        misc_mask,
+        config_svn,
+        ..Default::default()
+    };
+    Ok((key_request, prefix, chunk_size))
+}
+
+fn wipe_bytes(bytes: &mut [u8]) {
+    for b in bytes.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
+
+///
+/// A writer that seals everything written to it into a stream of chunks on `W`.
+///
+/// The stream must be ended with `finish`, which seals the final chunk. A writer dropped
+/// without `finish` leaves a stream that `UnsealingReader` rejects as truncated.
+///
+/// `flush` flushes `W` but does not seal a partial chunk, since only the final chunk may
+/// be shorter than the chunk size.
+///
+pub struct SealingWriter<W: Write> {
+    inner: Option<W>,
+    key: sgx_align_key_128bit_t,
+    key_request: sgx_key_request_t,
+    header: Vec<u8>,
+    prefix: [u8; NONCE_PREFIX_SIZE],
+    counter: u32,
+    chunk_size: usize,
+    plaintext: Vec<u8>,
+    chunk: Vec<u8>,
+}
+
+impl<W: Write> SealingWriter<W> {
+    ///
+    /// Starts a stream on `inner` sealed with the default seal policy, in chunks of
+    /// `SEALING_DEFAULT_CHUNK_SIZE` bytes, and writes its header.
+    ///
+    pub fn new(inner: W) -> io::Result<SealingWriter<W>> {
+        Self::with_policy(inner, &SgxSealPolicy::default(), SEALING_DEFAULT_CHUNK_SIZE)
+    }
+
+    ///
+    /// Starts a stream on `inner` sealed with the key policy of `policy`, in chunks of
+    /// `chunk_size` bytes, and writes its header.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_PARAMETER**
+    ///
+    /// `chunk_size` is zero or larger than `SEALING_MAX_CHUNK_SIZE`, or the key policy is
+    /// invalid.
+    ///
+    /// **SGX_ERROR_UNEXPECTED**
+    ///
+    /// The seal key could not be derived.
+    ///
+    /// The errors of writing to `inner`.
+    ///
+    pub fn with_policy(
+        mut inner: W,
+        policy: &SgxSealPolicy,
+        chunk_size: usize,
+    ) -> io::Result<SealingWriter<W>> {
+        if chunk_size == 0 || chunk_size > SEALING_MAX_CHUNK_SIZE {
+            return Err(io::Error::from_sgx_error(
+                sgx_status_t::SGX_ERROR_INVALID_PARAMETER,
+            ));
+        }
+        let key_request = fresh_key_request(policy).map_err(io::Error::from_sgx_error)?;
+        let mut prefix = [0_u8; NONCE_PREFIX_SIZE];
+        rsgx_read_rand(&mut prefix).map_err(io::Error::from_sgx_error)?;
+        let key = get_seal_key(&key_request).map_err(|ret| {
+            if ret != sgx_status_t::SGX_ERROR_OUT_OF_MEMORY {
+                io::Error::from_sgx_error(sgx_status_t::SGX_ERROR_UNEXPECTED)
+            } else {
+                io::Error::from_sgx_error(ret)
+            }
+        })?;
+
+        let mut writer = SealingWriter {
+            inner: None,
+            key,
+            key_request,
+            header: encode_header(&key_request, &prefix, chunk_size as u32),
+            prefix,
+            counter: 0,
+            chunk_size,
+            plaintext: Vec::with_capacity(chunk_size),
+            chunk: vec![0_u8; chunk_size + SGX_SEAL_TAG_SIZE],
+        };
+        inner.write_all(&writer.header)?;
+        writer.inner = Some(inner);
+        Ok(writer)
+    }
+
+    pub fn get_key_request(&self) -> &sgx_key_request_t {
+        &self.key_request
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    ///
+    /// Seals the final chunk, flushes `W` and returns it.
+    ///
+    pub fn finish(mut self) -> io::Result<W> {
+        self.seal_chunk(true)?;
+        let mut inner = self.inner.take().unwrap();
+        inner.flush()?;
+        Ok(inner)
+    }
+
+    fn seal_chunk(&mut self, last: bool) -> io::Result<()> {
+        let len = self.plaintext.len();
+        let nonce = chunk_nonce(&self.prefix, self.counter, last);
+        let (ciphertext, tag) = self.chunk[..len + SGX_SEAL_TAG_SIZE].split_at_mut(len);
+        let mut mac = [0_u8; SGX_SEAL_TAG_SIZE];
+        rsgx_rijndael128GCM_encrypt(
+            &self.key.key,
+            &self.plaintext,
+            &nonce,
+            &self.header,
+            ciphertext,
+            &mut mac,
+        )
+        .map_err(io::Error::from_sgx_error)?;
+        tag.copy_from_slice(&mac);
+        wipe_bytes(&mut self.plaintext);
+        self.plaintext.clear();
+
+        self.inner
+            .as_mut()
+            .unwrap()
+            .write_all(&self.chunk[..len + SGX_SEAL_TAG_SIZE])?;
+        if !last {
+            self.counter = self.counter.checked_add(1).ok_or_else(|| {
+                io::Error::from_sgx_error(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for SealingWriter<W> {
+    fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
+        let written = buf.len();
+        while !buf.is_empty() {
+            // A full chunk is sealed only once more data arrives, since the final chunk
+            // must be sealed with the last flag.
+            if self.plaintext.len() == self.chunk_size {
+                self.seal_chunk(false)?;
+            }
+            let n = buf.len().min(self.chunk_size - self.plaintext.len());
+            self.plaintext.extend_from_slice(&buf[..n]);
+            buf = &buf[n..];
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for SealingWriter<W> {
+    fn drop(&mut self) {
+        wipe(&mut self.key);
+        wipe_bytes(&mut self.plaintext);
+    }
+}
+
+///
+/// A reader that unseals a stream written by `SealingWriter` from `R`.
+///
+/// Each chunk is authenticated before any of its plaintext is returned. Reading fails with
+/// `SGX_ERROR_MAC_MISMATCH` if a chunk was modified, reordered, dropped or taken from
+/// another stream, or if the stream ends before its final chunk.
+///
+pub struct UnsealingReader<R: Read> {
+    inner: R,
+    key: sgx_align_key_128bit_t,
+    key_request: sgx_key_request_t,
+    header: Vec<u8>,
+    prefix: [u8; NONCE_PREFIX_SIZE],
+    counter: u32,
+    chunk_size: usize,
+    // The sealed chunk being read, and one byte beyond it to tell whether it is the
+    // final one.
+    chunk: Vec<u8>,
+    chunk_len: usize,
+    plaintext: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read> UnsealingReader<R> {
+    ///
+    /// Reads the header of the stream from `inner` and derives its key.
+    ///
+    /// # Errors
+    ///
+    /// **SGX_ERROR_INVALID_ATTRIBUTE**
+    ///
+    /// The enclave holds production secrets and is debuggable, see `rsgx_set_secret_class`.
+    ///
+    /// **SGX_ERROR_INVALID_VERSION**
+    ///
+    /// The stream was written by a newer version of the format.
+    ///
+    /// **SGX_ERROR_INVALID_CPUSVN** or **SGX_ERROR_INVALID_ISVSVN**
+    ///
+    /// The stream was sealed on a newer TCB or by a newer version of the enclave.
+    ///
+    /// **SGX_ERROR_MAC_MISMATCH**
+    ///
+    /// The stream was not sealed by this enclave or signer.
+    ///
+    /// An error of kind `InvalidData` if the header is malformed, and the errors of
+    /// reading from `inner`.
+    ///
+    pub fn new(mut inner: R) -> io::Result<UnsealingReader<R>> {
+        rsgx_check_unseal_guard().map_err(io::Error::from_sgx_error)?;
+
+        let mut header = [0_u8; STREAM_HEADER_SIZE];
+        inner.read_exact(&mut header).map_err(|e| {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid sealed stream header")
+            } else {
+                e
+            }
+        })?;
+        let (key_request, prefix, chunk_size) = decode_header(&header)?;
+        let key = get_seal_key(&key_request).map_err(|ret| match ret {
+            sgx_status_t::SGX_ERROR_INVALID_CPUSVN
+            | sgx_status_t::SGX_ERROR_INVALID_ISVSVN
+            | sgx_status_t::SGX_ERROR_OUT_OF_MEMORY => io::Error::from_sgx_error(ret),
+            _ => io::Error::from_sgx_error(sgx_status_t::SGX_ERROR_MAC_MISMATCH),
+        })?;
+
+        Ok(UnsealingReader {
+            inner,
+            key,
+            key_request,
+            header: header.to_vec(),
+            prefix,
+            counter: 0,
+            chunk_size,
+            chunk: vec![0_u8; chunk_size + SGX_SEAL_TAG_SIZE + 1],
+            chunk_len: 0,
+            plaintext: Vec::with_capacity(chunk_size),
+            pos: 0,
+            done: false,
+        })
+    }
+
+    pub fn get_key_request(&self) -> &sgx_key_request_t {
+        &self.key_request
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    fn next_chunk(&mut self) -> io::Result<()> {
+        while self.chunk_len < self.chunk.len() {
+            match self.inner.read(&mut self.chunk[self.chunk_len..]) {
+                Ok(0) => break,
+                Ok(n) => self.chunk_len += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let full = self.chunk_size + SGX_SEAL_TAG_SIZE;
+        let last = self.chunk_len < self.chunk.len();
+        let len = if last { self.chunk_len } else { full };
+        if len < SGX_SEAL_TAG_SIZE {
+            return Err(io::Error::from_sgx_error(
+                sgx_status_t::SGX_ERROR_MAC_MISMATCH,
+            ));
+        }
+
+        let nonce = chunk_nonce(&self.prefix, self.counter, last);
+        let (ciphertext, tag) = self.chunk[..len].split_at(len - SGX_SEAL_TAG_SIZE);
+        let mut mac = [0_u8; SGX_SEAL_TAG_SIZE];
+        mac.copy_from_slice(tag);
+        wipe_bytes(&mut self.plaintext);
+        self.plaintext.resize(ciphertext.len(), 0);
+        self.pos = 0;
+        if rsgx_rijndael128GCM_decrypt(
+            &self.key.key,
+            ciphertext,
+            &nonce,
+            &self.header,
+            &mac,
+            &mut self.plaintext,
+        )
+        .is_err()
+        {
+            self.plaintext.clear();
+            return Err(io::Error::from_sgx_error(
+                sgx_status_t::SGX_ERROR_MAC_MISMATCH,
+            ));
+        }
+
+        if last {
+            self.done = true;
+            self.chunk_len = 0;
+        } else {
+            self.chunk[0] = self.chunk[full];
+            self.chunk_len = 1;
+            self.counter = self
+                .counter
+                .checked_add(1)
+                .ok_or_else(|| io::Error::from_sgx_error(sgx_status_t::SGX_ERROR_MAC_MISMATCH))?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for UnsealingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.plaintext.len() {
+            if self.done || buf.is_empty() {
+                return Ok(0);
+            }
+            self.next_chunk()?;
+        }
+        let n = buf.len().min(self.plaintext.len() - self.pos);
+        buf[..n].copy_from_slice(&self.plaintext[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl<R: Read> Drop for UnsealingReader<R> {
+    fn drop(&mut self) {
+        wipe(&mut self.key);
+        wipe_bytes(&mut self.plaintext);
+    }
+}
@@ -81,6 +81,7 @@ pub use crate::sys_common::backtrace::__rust_begin_short_backtrace;
 use crate::cell::UnsafeCell;
 use crate::ffi::c_void;
 use crate::fmt;
+#[cfg(not(minimal_tcb))]
 use crate::fs;
 use crate::enclave;
 use crate::io;
@@ -482,6 +483,7 @@ pub enum PrintFormat {
 /// symbol information on crash point.
 /// * Always return `Ok(())`
 pub fn enable_backtrace<P: AsRef<Path>>(path: P, format: PrintFormat) -> io::Result<()> {
+    #[cfg(not(minimal_tcb))]
     let _ = fs::metadata(&path)?;
     enclave::set_enclave_path(path)?;
     let style = match format {
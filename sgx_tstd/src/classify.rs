@@ -0,0 +1,205 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Data classification tags.
+//!
+//! Values that must not leave the enclave in the clear are wrapped in
+//! [`Confidential`], values that may are wrapped in [`Public`]. The OCALL
+//! serialization of `sgx_serialize::ocall` only accepts `Public` values, and
+//! `Confidential` implements neither `Serializable` nor `Display`, so a secret
+//! that is about to be marshalled to the host, logged or formatted into an
+//! error message fails to compile instead of leaking.
+//!
+//! The only way from `Confidential` to `Public` is [`Confidential::declassify`],
+//! which states a reason and reports a [`Declassification`] to the audit log
+//! registered with [`set_audit_log`]. Declassifications are counted even when
+//! no audit log is registered.
+//!
+//! The tags are a guard against mistakes, not a sandbox: code that means to
+//! can always copy a value out of [`Confidential::expose`].
+//!
+//! # Examples
+//!
+//! ```
+//! use std::classify::{self, Confidential, Public};
+//!
+//! fn audit(event: &classify::Declassification<'_>) {
+//!     println!("declassified {}: {}", event.type_name(), event.reason());
+//! }
+//! classify::set_audit_log(audit).unwrap();
+//!
+//! let balance = Confidential::new(1_250_u64);
+//! let rounded = balance.map(|b| b / 1000 * 1000);
+//! let public: Public<u64> = rounded.declassify("rounded balance for the dashboard");
+//! assert_eq!(*public, 1000);
+//! ```
+
+use crate::any;
+use crate::ecall::{self, RequestId};
+use crate::fmt;
+use crate::io;
+use crate::ops::Deref;
+use crate::sync::atomic::{AtomicU64, Ordering};
+use crate::sync::OnceLock;
+
+static AUDIT_LOG: OnceLock<fn(&Declassification<'_>)> = OnceLock::new();
+static DECLASSIFIED: AtomicU64 = AtomicU64::new(0);
+
+/// A value that must not leave the enclave in the clear.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Confidential<T>(T);
+
+impl<T> Confidential<T> {
+    /// Tags `value` as confidential.
+    pub const fn new(value: T) -> Confidential<T> {
+        Confidential(value)
+    }
+
+    /// Returns a reference to the value, for use inside the enclave.
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    /// Returns a mutable reference to the value, for use inside the enclave.
+    pub fn expose_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+
+    /// Computes a new confidential value from this one.
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Confidential<U> {
+        Confidential(f(self.0))
+    }
+
+    /// Releases the value for use outside the enclave, and reports the
+    /// release to the audit log.
+    ///
+    /// `reason` is recorded with the type of the value and the request, if
+    /// any, during which the value was released.
+    pub fn declassify(self, reason: &str) -> Public<T> {
+        DECLASSIFIED.fetch_add(1, Ordering::Relaxed);
+        if let Some(log) = AUDIT_LOG.get() {
+            log(&Declassification {
+                type_name: any::type_name::<T>(),
+                reason,
+                request_id: ecall::current().map(|ctx| ctx.request_id()),
+            });
+        }
+        Public(self.0)
+    }
+}
+
+impl<T> From<T> for Confidential<T> {
+    fn from(value: T) -> Confidential<T> {
+        Confidential(value)
+    }
+}
+
+impl<T> fmt::Debug for Confidential<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Confidential<{}>(..)", any::type_name::<T>())
+    }
+}
+
+/// A value that may leave the enclave.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Public<T>(T);
+
+impl<T> Public<T> {
+    /// Tags `value` as public.
+    ///
+    /// This asserts that `value` holds nothing confidential. A value derived
+    /// from a [`Confidential`] one has to be released with
+    /// [`Confidential::declassify`] instead.
+    pub const fn new(value: T) -> Public<T> {
+        Public(value)
+    }
+
+    /// Returns the value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+
+    /// Computes a new public value from this one.
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Public<U> {
+        Public(f(self.0))
+    }
+}
+
+impl<T> Deref for Public<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> AsRef<T> for Public<T> {
+    fn as_ref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Public<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A release of a confidential value, reported to the audit log.
+#[derive(Clone, Copy, Debug)]
+pub struct Declassification<'a> {
+    type_name: &'static str,
+    reason: &'a str,
+    request_id: Option<RequestId>,
+}
+
+impl<'a> Declassification<'a> {
+    /// Returns the name of the type of the released value.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns the reason given for the release.
+    pub fn reason(&self) -> &'a str {
+        self.reason
+    }
+
+    /// Returns the request during which the value was released, if any.
+    pub fn request_id(&self) -> Option<RequestId> {
+        self.request_id
+    }
+}
+
+/// Registers the audit log that [`Confidential::declassify`] reports to.
+///
+/// The audit log can be registered once. Registering the same function again
+/// succeeds, a different one fails with [`io::ErrorKind::AlreadyExists`].
+pub fn set_audit_log(log: fn(&Declassification<'_>)) -> io::Result<()> {
+    if *AUDIT_LOG.get_or_init(|| log) as usize == log as usize {
+        Ok(())
+    } else {
+        Err(io::const_io_error!(
+            io::ErrorKind::AlreadyExists,
+            "a different audit log is registered",
+        ))
+    }
+}
+
+/// Returns how many values were declassified since the enclave started.
+pub fn declassified() -> u64 {
+    DECLASSIFIED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,815 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Vectors and strings with a fixed capacity stored inline.
+//!
+//! [`ArrayVec`] and [`ArrayString`] never allocate: their elements live in an
+//! array inside the value, so they can be kept on the stack or embedded in
+//! other types on request hot paths. Operations that would exceed the
+//! capacity panic, and each has a `try_` form that returns a
+//! [`CapacityError`] instead. The API follows the `arrayvec` crate so that
+//! ported code maps directly.
+
+use crate::borrow::{Borrow, BorrowMut};
+use crate::cmp::Ordering;
+use crate::error;
+use crate::fmt;
+use crate::hash::{Hash, Hasher};
+use crate::mem::{self, MaybeUninit};
+use crate::ops::{Deref, DerefMut};
+use crate::ptr;
+use crate::slice;
+use crate::str;
+
+/// The error returned when an inline collection is full.
+///
+/// It carries the element that could not be added.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CapacityError<T = ()> {
+    element: T,
+}
+
+impl<T> CapacityError<T> {
+    /// Creates a new `CapacityError` holding `element`.
+    pub const fn new(element: T) -> CapacityError<T> {
+        CapacityError { element }
+    }
+
+    /// Returns the element that could not be added.
+    pub fn element(self) -> T {
+        self.element
+    }
+
+    /// Drops the element, keeping only the error.
+    pub fn simplify(self) -> CapacityError {
+        CapacityError { element: () }
+    }
+}
+
+impl<T> fmt::Display for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "insufficient capacity".fmt(f)
+    }
+}
+
+impl<T> fmt::Debug for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "CapacityError: insufficient capacity".fmt(f)
+    }
+}
+
+impl<T> error::Error for CapacityError<T> {
+    #[allow(deprecated)]
+    fn description(&self) -> &str {
+        "insufficient capacity"
+    }
+}
+
+/// A vector with a fixed capacity of `CAP` elements stored inline.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::array_vec::ArrayVec;
+///
+/// let mut v = ArrayVec::<u32, 4>::new();
+/// v.push(1);
+/// v.push(2);
+/// assert_eq!(&v[..], &[1, 2]);
+///
+/// v.extend([3, 4]);
+/// assert!(v.is_full());
+/// assert!(v.try_push(5).is_err());
+/// ```
+pub struct ArrayVec<T, const CAP: usize> {
+    xs: [MaybeUninit<T>; CAP],
+    len: usize,
+}
+
+impl<T, const CAP: usize> ArrayVec<T, CAP> {
+    /// The capacity of the vector.
+    pub const CAPACITY: usize = CAP;
+
+    /// Creates an empty vector.
+    pub const fn new() -> ArrayVec<T, CAP> {
+        ArrayVec { xs: MaybeUninit::uninit_array(), len: 0 }
+    }
+
+    /// Returns the number of elements.
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the vector has no elements.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the capacity of the vector.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        CAP
+    }
+
+    /// Returns `true` if the vector is at capacity.
+    #[inline]
+    pub const fn is_full(&self) -> bool {
+        self.len == CAP
+    }
+
+    /// Returns how many more elements fit.
+    #[inline]
+    pub const fn remaining_capacity(&self) -> usize {
+        CAP - self.len
+    }
+
+    /// Appends an element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the vector is full.
+    #[track_caller]
+    pub fn push(&mut self, element: T) {
+        self.try_push(element).unwrap()
+    }
+
+    /// Appends an element, or returns it in an error if the vector is full.
+    pub fn try_push(&mut self, element: T) -> Result<(), CapacityError<T>> {
+        if self.len < CAP {
+            unsafe { self.push_unchecked(element) };
+            Ok(())
+        } else {
+            Err(CapacityError::new(element))
+        }
+    }
+
+    /// Appends an element without checking the capacity.
+    ///
+    /// # Safety
+    ///
+    /// The vector must not be full.
+    pub unsafe fn push_unchecked(&mut self, element: T) {
+        debug_assert!(self.len < CAP);
+        self.xs.get_unchecked_mut(self.len).write(element);
+        self.len += 1;
+    }
+
+    /// Removes the last element and returns it.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { self.xs.get_unchecked(self.len).assume_init_read() })
+    }
+
+    /// Inserts an element at `index`, shifting later elements up.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len` or the vector is full.
+    #[track_caller]
+    pub fn insert(&mut self, index: usize, element: T) {
+        self.try_insert(index, element).unwrap()
+    }
+
+    /// Inserts an element at `index`, or returns it in an error if the vector
+    /// is full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn try_insert(&mut self, index: usize, element: T) -> Result<(), CapacityError<T>> {
+        assert!(index <= self.len, "insertion index (is {}) should be <= len (is {})", index, self.len);
+        if self.len == CAP {
+            return Err(CapacityError::new(element));
+        }
+        unsafe {
+            let p = self.as_mut_ptr().add(index);
+            ptr::copy(p, p.add(1), self.len - index);
+            ptr::write(p, element);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes and returns the element at `index`, shifting later elements
+    /// down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    #[track_caller]
+    pub fn remove(&mut self, index: usize) -> T {
+        self.pop_at(index)
+            .unwrap_or_else(|| panic!("removal index (is {}) should be < len (is {})", index, self.len))
+    }
+
+    /// Removes and returns the element at `index`, or `None` if it is out of
+    /// bounds.
+    pub fn pop_at(&mut self, index: usize) -> Option<T> {
+        if index >= self.len {
+            return None;
+        }
+        unsafe {
+            let p = self.as_mut_ptr().add(index);
+            let element = ptr::read(p);
+            ptr::copy(p.add(1), p, self.len - index - 1);
+            self.len -= 1;
+            Some(element)
+        }
+    }
+
+    /// Removes the element at `index` and replaces it with the last element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    #[track_caller]
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        let len = self.len;
+        assert!(index < len, "swap_remove index (is {}) should be < len (is {})", index, len);
+        self.swap(index, len - 1);
+        self.pop().unwrap()
+    }
+
+    /// Shortens the vector to `len` elements, dropping the rest.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let tail = self.len - len;
+        self.len = len;
+        unsafe {
+            ptr::drop_in_place(slice::from_raw_parts_mut(self.as_mut_ptr().add(len), tail));
+        }
+    }
+
+    /// Removes every element.
+    pub fn clear(&mut self) {
+        self.truncate(0)
+    }
+
+    /// Keeps only the elements for which `f` returns `true`.
+    pub fn retain<F: FnMut(&mut T) -> bool>(&mut self, mut f: F) {
+        let len = self.len;
+        let mut deleted = 0;
+        for i in 0..len {
+            if !f(&mut self[i]) {
+                deleted += 1;
+            } else if deleted > 0 {
+                self.swap(i - deleted, i);
+            }
+        }
+        self.truncate(len - deleted);
+    }
+
+    /// Copies the elements of `other` to the end of the vector.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, without copying anything, if they do not all fit.
+    pub fn try_extend_from_slice(&mut self, other: &[T]) -> Result<(), CapacityError>
+    where
+        T: Copy,
+    {
+        if other.len() > self.remaining_capacity() {
+            return Err(CapacityError::new(()));
+        }
+        unsafe {
+            ptr::copy_nonoverlapping(other.as_ptr(), self.as_mut_ptr().add(self.len), other.len());
+        }
+        self.len += other.len();
+        Ok(())
+    }
+
+    /// Returns the array if the vector is full, or the vector otherwise.
+    pub fn into_inner(self) -> Result<[T; CAP], ArrayVec<T, CAP>> {
+        if self.len < CAP {
+            return Err(self);
+        }
+        let this = mem::ManuallyDrop::new(self);
+        Ok(unsafe { ptr::read(this.xs.as_ptr() as *const [T; CAP]) })
+    }
+
+    /// Sets the length of the vector.
+    ///
+    /// # Safety
+    ///
+    /// `len` must not exceed the capacity and the first `len` elements must
+    /// be initialized.
+    pub unsafe fn set_len(&mut self, len: usize) {
+        debug_assert!(len <= CAP);
+        self.len = len;
+    }
+
+    /// Returns the elements as a slice.
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.as_ptr(), self.len) }
+    }
+
+    /// Returns the elements as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr(), self.len) }
+    }
+
+    /// Returns a raw pointer to the buffer.
+    pub fn as_ptr(&self) -> *const T {
+        self.xs.as_ptr() as *const T
+    }
+
+    /// Returns a raw mutable pointer to the buffer.
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.xs.as_mut_ptr() as *mut T
+    }
+}
+
+impl<T, const CAP: usize> Drop for ArrayVec<T, CAP> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T, const CAP: usize> Deref for ArrayVec<T, CAP> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, const CAP: usize> DerefMut for ArrayVec<T, CAP> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T, const CAP: usize> AsRef<[T]> for ArrayVec<T, CAP> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T, const CAP: usize> AsMut<[T]> for ArrayVec<T, CAP> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T, const CAP: usize> Borrow<[T]> for ArrayVec<T, CAP> {
+    fn borrow(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T, const CAP: usize> BorrowMut<[T]> for ArrayVec<T, CAP> {
+    fn borrow_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T, const CAP: usize> Default for ArrayVec<T, CAP> {
+    fn default() -> ArrayVec<T, CAP> {
+        ArrayVec::new()
+    }
+}
+
+impl<T: Clone, const CAP: usize> Clone for ArrayVec<T, CAP> {
+    fn clone(&self) -> ArrayVec<T, CAP> {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: fmt::Debug, const CAP: usize> fmt::Debug for ArrayVec<T, CAP> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: PartialEq, const CAP: usize> PartialEq for ArrayVec<T, CAP> {
+    fn eq(&self, other: &ArrayVec<T, CAP>) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: PartialEq, const CAP: usize> PartialEq<[T]> for ArrayVec<T, CAP> {
+    fn eq(&self, other: &[T]) -> bool {
+        **self == *other
+    }
+}
+
+impl<T: Eq, const CAP: usize> Eq for ArrayVec<T, CAP> {}
+
+impl<T: PartialOrd, const CAP: usize> PartialOrd for ArrayVec<T, CAP> {
+    fn partial_cmp(&self, other: &ArrayVec<T, CAP>) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Ord, const CAP: usize> Ord for ArrayVec<T, CAP> {
+    fn cmp(&self, other: &ArrayVec<T, CAP>) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Hash, const CAP: usize> Hash for ArrayVec<T, CAP> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<T, const CAP: usize> From<[T; CAP]> for ArrayVec<T, CAP> {
+    fn from(array: [T; CAP]) -> ArrayVec<T, CAP> {
+        let array = mem::ManuallyDrop::new(array);
+        let mut v = ArrayVec::new();
+        unsafe {
+            ptr::copy_nonoverlapping(array.as_ptr(), v.as_mut_ptr(), CAP);
+            v.set_len(CAP);
+        }
+        v
+    }
+}
+
+impl<T: Clone, const CAP: usize> TryFrom<&[T]> for ArrayVec<T, CAP> {
+    type Error = CapacityError;
+
+    fn try_from(slice: &[T]) -> Result<ArrayVec<T, CAP>, CapacityError> {
+        if slice.len() > CAP {
+            return Err(CapacityError::new(()));
+        }
+        Ok(slice.iter().cloned().collect())
+    }
+}
+
+impl<T, const CAP: usize> Extend<T> for ArrayVec<T, CAP> {
+    /// Extends the vector from an iterator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the vector becomes overfull.
+    #[track_caller]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for element in iter {
+            self.push(element);
+        }
+    }
+}
+
+impl<T, const CAP: usize> FromIterator<T> for ArrayVec<T, CAP> {
+    /// Creates a vector from an iterator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the iterator yields more than `CAP` elements.
+    #[track_caller]
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> ArrayVec<T, CAP> {
+        let mut v = ArrayVec::new();
+        v.extend(iter);
+        v
+    }
+}
+
+impl<'a, T, const CAP: usize> IntoIterator for &'a ArrayVec<T, CAP> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> slice::Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T, const CAP: usize> IntoIterator for &'a mut ArrayVec<T, CAP> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> slice::IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+impl<T, const CAP: usize> IntoIterator for ArrayVec<T, CAP> {
+    type Item = T;
+    type IntoIter = IntoIter<T, CAP>;
+
+    fn into_iter(mut self) -> IntoIter<T, CAP> {
+        let end = self.len;
+        // The iterator owns the elements from here on.
+        self.len = 0;
+        IntoIter { v: self, index: 0, end }
+    }
+}
+
+/// An owning iterator over the elements of an [`ArrayVec`].
+pub struct IntoIter<T, const CAP: usize> {
+    v: ArrayVec<T, CAP>,
+    index: usize,
+    end: usize,
+}
+
+impl<T, const CAP: usize> Iterator for IntoIter<T, CAP> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.index == self.end {
+            return None;
+        }
+        let element = unsafe { ptr::read(self.v.as_ptr().add(self.index)) };
+        self.index += 1;
+        Some(element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.index;
+        (len, Some(len))
+    }
+}
+
+impl<T, const CAP: usize> DoubleEndedIterator for IntoIter<T, CAP> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.index == self.end {
+            return None;
+        }
+        self.end -= 1;
+        Some(unsafe { ptr::read(self.v.as_ptr().add(self.end)) })
+    }
+}
+
+impl<T, const CAP: usize> ExactSizeIterator for IntoIter<T, CAP> {}
+
+impl<T, const CAP: usize> Drop for IntoIter<T, CAP> {
+    fn drop(&mut self) {
+        for _ in self.by_ref() {}
+    }
+}
+
+/// A string with a fixed capacity of `CAP` bytes stored inline.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::array_vec::ArrayString;
+///
+/// let mut s = ArrayString::<8>::new();
+/// s.push_str("tcs-");
+/// s.push('7');
+/// assert_eq!(&s, "tcs-7");
+/// assert!(s.try_push_str("overflow").is_err());
+/// ```
+#[derive(Clone, Copy)]
+pub struct ArrayString<const CAP: usize> {
+    xs: [u8; CAP],
+    len: usize,
+}
+
+impl<const CAP: usize> ArrayString<CAP> {
+    /// Creates an empty string.
+    pub const fn new() -> ArrayString<CAP> {
+        ArrayString { xs: [0; CAP], len: 0 }
+    }
+
+    /// Creates a string from `s`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `s` is longer than `CAP` bytes.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from(s: &str) -> Result<ArrayString<CAP>, CapacityError<&str>> {
+        let mut string = ArrayString::new();
+        string.try_push_str(s)?;
+        Ok(string)
+    }
+
+    /// Returns the length in bytes.
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the string is empty.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the capacity in bytes.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        CAP
+    }
+
+    /// Returns `true` if the string is at capacity.
+    #[inline]
+    pub const fn is_full(&self) -> bool {
+        self.len == CAP
+    }
+
+    /// Returns how many more bytes fit.
+    #[inline]
+    pub const fn remaining_capacity(&self) -> usize {
+        CAP - self.len
+    }
+
+    /// Appends a character.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no room for it.
+    #[track_caller]
+    pub fn push(&mut self, c: char) {
+        self.try_push(c).unwrap()
+    }
+
+    /// Appends a character, or returns it in an error if there is no room.
+    pub fn try_push(&mut self, c: char) -> Result<(), CapacityError<char>> {
+        let mut buf = [0; 4];
+        self.try_push_str(c.encode_utf8(&mut buf)).map_err(|_| CapacityError::new(c))
+    }
+
+    /// Appends a string slice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no room for it.
+    #[track_caller]
+    pub fn push_str(&mut self, s: &str) {
+        self.try_push_str(s).unwrap()
+    }
+
+    /// Appends a string slice, or returns it in an error if there is no room.
+    pub fn try_push_str<'a>(&mut self, s: &'a str) -> Result<(), CapacityError<&'a str>> {
+        if s.len() > self.remaining_capacity() {
+            return Err(CapacityError::new(s));
+        }
+        self.xs[self.len..self.len + s.len()].copy_from_slice(s.as_bytes());
+        self.len += s.len();
+        Ok(())
+    }
+
+    /// Removes the last character and returns it.
+    pub fn pop(&mut self) -> Option<char> {
+        let c = self.chars().next_back()?;
+        self.len -= c.len_utf8();
+        Some(c)
+    }
+
+    /// Shortens the string to `new_len` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_len` is not on a character boundary.
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len < self.len {
+            assert!(self.is_char_boundary(new_len), "new_len is not on a char boundary");
+            self.len = new_len;
+        }
+    }
+
+    /// Empties the string.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Returns the string as a string slice.
+    pub fn as_str(&self) -> &str {
+        unsafe { str::from_utf8_unchecked(&self.xs[..self.len]) }
+    }
+
+    /// Returns the string as a mutable string slice.
+    pub fn as_mut_str(&mut self) -> &mut str {
+        unsafe { str::from_utf8_unchecked_mut(&mut self.xs[..self.len]) }
+    }
+}
+
+impl<const CAP: usize> Deref for ArrayString<CAP> {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const CAP: usize> DerefMut for ArrayString<CAP> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut str {
+        self.as_mut_str()
+    }
+}
+
+impl<const CAP: usize> AsRef<str> for ArrayString<CAP> {
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+impl<const CAP: usize> Borrow<str> for ArrayString<CAP> {
+    fn borrow(&self) -> &str {
+        self
+    }
+}
+
+impl<const CAP: usize> Default for ArrayString<CAP> {
+    fn default() -> ArrayString<CAP> {
+        ArrayString::new()
+    }
+}
+
+impl<const CAP: usize> fmt::Write for ArrayString<CAP> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.try_push_str(s).map_err(|_| fmt::Error)
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.try_push(c).map_err(|_| fmt::Error)
+    }
+}
+
+impl<const CAP: usize> fmt::Display for ArrayString<CAP> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<const CAP: usize> fmt::Debug for ArrayString<CAP> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<const CAP: usize> PartialEq for ArrayString<CAP> {
+    fn eq(&self, other: &ArrayString<CAP>) -> bool {
+        **self == **other
+    }
+}
+
+impl<const CAP: usize> PartialEq<str> for ArrayString<CAP> {
+    fn eq(&self, other: &str) -> bool {
+        &**self == other
+    }
+}
+
+impl<const CAP: usize> PartialEq<&str> for ArrayString<CAP> {
+    fn eq(&self, other: &&str) -> bool {
+        &**self == *other
+    }
+}
+
+impl<const CAP: usize> PartialEq<ArrayString<CAP>> for str {
+    fn eq(&self, other: &ArrayString<CAP>) -> bool {
+        self == &**other
+    }
+}
+
+impl<const CAP: usize> Eq for ArrayString<CAP> {}
+
+impl<const CAP: usize> PartialOrd for ArrayString<CAP> {
+    fn partial_cmp(&self, other: &ArrayString<CAP>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const CAP: usize> Ord for ArrayString<CAP> {
+    fn cmp(&self, other: &ArrayString<CAP>) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<const CAP: usize> Hash for ArrayString<CAP> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<'a, const CAP: usize> TryFrom<&'a str> for ArrayString<CAP> {
+    type Error = CapacityError<&'a str>;
+
+    fn try_from(s: &'a str) -> Result<ArrayString<CAP>, CapacityError<&'a str>> {
+        ArrayString::from(s)
+    }
+}
+
+impl<const CAP: usize> str::FromStr for ArrayString<CAP> {
+    type Err = CapacityError;
+
+    fn from_str(s: &str) -> Result<ArrayString<CAP>, CapacityError> {
+        ArrayString::from(s).map_err(CapacityError::simplify)
+    }
+}
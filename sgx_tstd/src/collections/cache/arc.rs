@@ -0,0 +1,485 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! An adaptive replacement cache bounded by entry count and weight.
+//!
+//! This follows Megiddo and Modha, "ARC: A Self-Tuning, Low Overhead
+//! Replacement Cache" (FAST 2003). Resident entries live in `T1` (seen once
+//! recently) or `T2` (seen at least twice); `B1` and `B2` remember the keys
+//! recently evicted from each. A hit on a ghost key shifts the target size
+//! `p` of `T1`, so the cache adapts between recency and frequency.
+
+use super::list::{List, Slab};
+use super::{cache_builder, is_expired, CacheMetrics, CacheStats, Config, EvictionReason};
+use crate::borrow::Borrow;
+use crate::boxed::Box;
+use crate::cmp;
+use crate::collections::HashMap;
+use crate::fmt;
+use crate::hash::Hash;
+use crate::time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Which {
+    T1,
+    T2,
+    B1,
+    B2,
+}
+
+struct Entry<K, V> {
+    key: K,
+    // `None` for ghost entries in B1 and B2.
+    value: Option<V>,
+    weight: usize,
+    expires: Option<Instant>,
+    which: Which,
+}
+
+/// A cache that balances recency and frequency of use, evicting entries when
+/// it is over its entry count or weight bound.
+///
+/// Compared with [`LruCache`](super::lru::LruCache), a single scan over many
+/// keys cannot flush entries that are used repeatedly. The cache remembers up
+/// to `capacity` evicted keys, without their values, to steer that balance.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::ArcCache;
+///
+/// let mut cache = ArcCache::new(2);
+/// cache.insert(1, "a");
+/// cache.insert(2, "b");
+/// cache.get(&1);
+/// cache.insert(3, "c");
+/// assert!(cache.contains_key(&1));
+/// ```
+pub struct ArcCache<K, V> {
+    map: HashMap<K, usize>,
+    slab: Slab<Entry<K, V>>,
+    t1: List,
+    t2: List,
+    b1: List,
+    b2: List,
+    p: usize,
+    bytes: usize,
+    config: Config<K, V>,
+    stats: CacheStats,
+}
+
+cache_builder! {
+    /// Configures an [`ArcCache`].
+    ArcCacheBuilder => ArcCache
+}
+
+impl<K: Hash + Eq + Clone, V> ArcCache<K, V> {
+    /// Creates a cache holding at most `capacity` entries.
+    pub fn new(capacity: usize) -> ArcCache<K, V> {
+        ArcCache::from_config(Config::new(capacity))
+    }
+
+    /// Returns a builder for a cache holding at most `capacity` entries.
+    pub fn builder(capacity: usize) -> ArcCacheBuilder<K, V> {
+        ArcCacheBuilder { config: Config::new(capacity) }
+    }
+
+    fn from_config(config: Config<K, V>) -> ArcCache<K, V> {
+        ArcCache {
+            map: HashMap::new(),
+            slab: Slab::new(),
+            t1: List::new(),
+            t2: List::new(),
+            b1: List::new(),
+            b2: List::new(),
+            p: 0,
+            bytes: 0,
+            config,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Returns the number of resident entries, including expired ones not
+    /// yet purged.
+    pub fn len(&self) -> usize {
+        self.t1.len() + self.t2.len()
+    }
+
+    /// Returns `true` if the cache holds no resident entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of resident entries.
+    pub fn capacity(&self) -> usize {
+        self.config.capacity
+    }
+
+    /// Returns the total weight of the resident entries.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Returns the current target size of the recency list.
+    pub fn recency_target(&self) -> usize {
+        self.p
+    }
+
+    /// Returns the counters of the cache.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Returns a reference to the value of `key`, recording the use.
+    pub fn get<Q: ?Sized>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let idx = self.lookup(key)?;
+        self.slab.get(idx).value.as_ref()
+    }
+
+    /// Returns a mutable reference to the value of `key`, recording the use.
+    ///
+    /// The weight of the entry is not recomputed.
+    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let idx = self.lookup(key)?;
+        self.slab.get_mut(idx).value.as_mut()
+    }
+
+    /// Returns a reference to the value of `key` without recording the use.
+    pub fn peek<Q: ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let entry = self.slab.get(*self.map.get(key)?);
+        if is_expired(entry.expires) { None } else { entry.value.as_ref() }
+    }
+
+    /// Returns `true` if a live resident entry for `key` exists.
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        self.peek(key).is_some()
+    }
+
+    /// Inserts an entry with the default time to live, returning the previous
+    /// value of `key`.
+    ///
+    /// An entry heavier than the byte bound is not cached.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let expires = self.config.expiry(None);
+        self.insert_entry(key, value, expires)
+    }
+
+    /// Inserts an entry that expires after `ttl`, returning the previous value
+    /// of `key`.
+    pub fn insert_with_ttl(&mut self, key: K, value: V, ttl: Duration) -> Option<V> {
+        let expires = self.config.expiry(Some(ttl));
+        self.insert_entry(key, value, expires)
+    }
+
+    /// Removes the entry of `key`, returning its value.
+    ///
+    /// The key is also forgotten by the ghost lists.
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let idx = *self.map.get(key)?;
+        let entry = self.forget(idx, EvictionReason::Removed);
+        if is_expired(entry.expires) { None } else { entry.value }
+    }
+
+    /// Drops every expired resident entry, returning how many were dropped.
+    pub fn purge_expired(&mut self) -> usize {
+        let expired: Vec<usize> = self
+            .map
+            .values()
+            .copied()
+            .filter(|&idx| {
+                let entry = self.slab.get(idx);
+                entry.value.is_some() && is_expired(entry.expires)
+            })
+            .collect();
+        for &idx in &expired {
+            self.forget(idx, EvictionReason::Expired);
+        }
+        expired.len()
+    }
+
+    /// Removes all entries, forgets all ghost keys and resets adaptation.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.map.shrink_to_fit();
+        self.slab.clear();
+        self.slab.shrink_to_fit();
+        self.t1.clear();
+        self.t2.clear();
+        self.b1.clear();
+        self.b2.clear();
+        self.p = 0;
+        self.bytes = 0;
+    }
+
+    fn lookup<Q: ?Sized>(&mut self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let idx = match self.map.get(key) {
+            Some(&idx) => idx,
+            None => {
+                self.config.miss(&mut self.stats);
+                return None;
+            }
+        };
+        let (which, expires) = {
+            let entry = self.slab.get(idx);
+            (entry.which, entry.expires)
+        };
+        match which {
+            Which::B1 | Which::B2 => {
+                self.config.miss(&mut self.stats);
+                None
+            }
+            Which::T1 | Which::T2 if is_expired(expires) => {
+                self.forget(idx, EvictionReason::Expired);
+                self.config.miss(&mut self.stats);
+                None
+            }
+            Which::T1 | Which::T2 => {
+                self.relink(idx, Which::T2);
+                self.config.hit(&mut self.stats);
+                Some(idx)
+            }
+        }
+    }
+
+    fn insert_entry(&mut self, key: K, value: V, expires: Option<Instant>) -> Option<V> {
+        let c = self.config.capacity;
+        let weight = self.config.weigh(&key, &value);
+        if weight > self.config.max_bytes || c == 0 {
+            return self.remove(&key);
+        }
+
+        let mut old = None;
+        match self.map.get(&key).copied() {
+            Some(idx) => {
+                let which = self.slab.get(idx).which;
+                match which {
+                    Which::T1 | Which::T2 => {
+                        let entry = self.slab.get_mut(idx);
+                        let prev = entry.value.replace(value);
+                        let prev_weight = entry.weight;
+                        let prev_expired = is_expired(entry.expires);
+                        entry.weight = weight;
+                        entry.expires = expires;
+                        self.bytes = self.bytes - prev_weight + weight;
+                        self.config.evicted(&mut self.stats, EvictionReason::Replaced, prev_weight);
+                        if !prev_expired {
+                            old = prev;
+                        }
+                        self.relink(idx, Which::T2);
+                    }
+                    Which::B1 => {
+                        let delta = cmp::max(self.b2.len() / cmp::max(self.b1.len(), 1), 1);
+                        self.p = cmp::min(c, self.p + delta);
+                        self.replace(false);
+                        self.resurrect(idx, value, weight, expires);
+                    }
+                    Which::B2 => {
+                        let delta = cmp::max(self.b1.len() / cmp::max(self.b2.len(), 1), 1);
+                        self.p = self.p.saturating_sub(delta);
+                        self.replace(true);
+                        self.resurrect(idx, value, weight, expires);
+                    }
+                }
+            }
+            None => {
+                if self.t1.len() + self.b1.len() >= c {
+                    if self.t1.len() < c {
+                        if let Some(idx) = self.b1.back() {
+                            self.forget(idx, EvictionReason::Capacity);
+                        }
+                        self.replace(false);
+                    } else if let Some(idx) = self.t1.back() {
+                        self.forget(idx, EvictionReason::Capacity);
+                    }
+                } else {
+                    let total = self.t1.len() + self.t2.len() + self.b1.len() + self.b2.len();
+                    if total >= c {
+                        if total >= 2 * c {
+                            if let Some(idx) = self.b2.back() {
+                                self.forget(idx, EvictionReason::Capacity);
+                            }
+                        }
+                        self.replace(false);
+                    }
+                }
+                let idx = self.slab.insert(Entry {
+                    key: key.clone(),
+                    value: Some(value),
+                    weight,
+                    expires,
+                    which: Which::T1,
+                });
+                self.t1.push_front(&mut self.slab, idx);
+                self.map.insert(key, idx);
+                self.bytes += weight;
+                self.config.inserted(&mut self.stats, weight);
+            }
+        }
+
+        while self.bytes > self.config.max_bytes && !self.is_empty() {
+            self.replace(false);
+        }
+        self.trim_ghosts();
+        old
+    }
+
+    // Moves a ghost entry back to T2 with a fresh value.
+    fn resurrect(&mut self, idx: usize, value: V, weight: usize, expires: Option<Instant>) {
+        let entry = self.slab.get_mut(idx);
+        entry.value = Some(value);
+        entry.weight = weight;
+        entry.expires = expires;
+        self.bytes += weight;
+        self.config.inserted(&mut self.stats, weight);
+        self.relink(idx, Which::T2);
+    }
+
+    // The REPLACE routine of the paper: demotes the LRU entry of T1 or T2 to
+    // the corresponding ghost list when the resident set is full.
+    fn replace(&mut self, hit_in_b2: bool) {
+        let resident = self.t1.len() + self.t2.len();
+        if resident < self.config.capacity && self.bytes <= self.config.max_bytes {
+            return;
+        }
+        let t1 = self.t1.len();
+        let from_t1 = t1 > 0 && (t1 > self.p || (hit_in_b2 && t1 == self.p) || self.t2.len() == 0);
+        let (idx, ghost) = if from_t1 {
+            (self.t1.back(), Which::B1)
+        } else {
+            (self.t2.back(), Which::B2)
+        };
+        if let Some(idx) = idx {
+            self.demote(idx, ghost);
+        }
+    }
+
+    fn demote(&mut self, idx: usize, ghost: Which) {
+        let entry = self.slab.get_mut(idx);
+        let weight = entry.weight;
+        let reason = if is_expired(entry.expires) {
+            EvictionReason::Expired
+        } else {
+            EvictionReason::Capacity
+        };
+        entry.value = None;
+        entry.weight = 0;
+        entry.expires = None;
+        self.bytes -= weight;
+        self.config.evicted(&mut self.stats, reason, weight);
+        self.relink(idx, ghost);
+    }
+
+    fn trim_ghosts(&mut self) {
+        let c = self.config.capacity;
+        while self.t1.len() + self.b1.len() > c {
+            match self.b1.back() {
+                Some(idx) => self.drop_ghost(idx),
+                None => break,
+            }
+        }
+        while self.t1.len() + self.t2.len() + self.b1.len() + self.b2.len() > 2 * c {
+            match self.b2.back().or_else(|| self.b1.back()) {
+                Some(idx) => self.drop_ghost(idx),
+                None => break,
+            }
+        }
+    }
+
+    fn drop_ghost(&mut self, idx: usize) {
+        let which = self.slab.get(idx).which;
+        let (list, slab) = self.list_mut(which);
+        list.unlink(slab, idx);
+        let entry = self.slab.remove(idx);
+        self.map.remove(&entry.key);
+    }
+
+    fn relink(&mut self, idx: usize, to: Which) {
+        let from = self.slab.get(idx).which;
+        if from == to {
+            let (list, slab) = self.list_mut(to);
+            list.move_to_front(slab, idx);
+        } else {
+            let (list, slab) = self.list_mut(from);
+            list.unlink(slab, idx);
+            slab.get_mut(idx).which = to;
+            let (list, slab) = self.list_mut(to);
+            list.push_front(slab, idx);
+        }
+    }
+
+    // Removes an entry from whichever list holds it.
+    fn forget(&mut self, idx: usize, reason: EvictionReason) -> Entry<K, V> {
+        let which = self.slab.get(idx).which;
+        let (list, slab) = self.list_mut(which);
+        list.unlink(slab, idx);
+        let entry = self.slab.remove(idx);
+        self.map.remove(&entry.key);
+        if entry.value.is_some() {
+            self.bytes -= entry.weight;
+            self.config.evicted(&mut self.stats, reason, entry.weight);
+        }
+        entry
+    }
+
+    fn list_mut(&mut self, which: Which) -> (&mut List, &mut Slab<Entry<K, V>>) {
+        let list = match which {
+            Which::T1 => &mut self.t1,
+            Which::T2 => &mut self.t2,
+            Which::B1 => &mut self.b1,
+            Which::B2 => &mut self.b2,
+        };
+        (list, &mut self.slab)
+    }
+}
+
+impl<K, V> fmt::Debug for ArcCache<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArcCache")
+            .field("t1", &self.t1.len())
+            .field("t2", &self.t2.len())
+            .field("b1", &self.b1.len())
+            .field("b2", &self.b2.len())
+            .field("p", &self.p)
+            .field("bytes", &self.bytes)
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
+}
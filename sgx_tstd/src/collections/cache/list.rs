@@ -0,0 +1,193 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! An index-linked list over a slab, shared by the cache implementations.
+//!
+//! Nodes never move once inserted, so a `HashMap` can point at them by index,
+//! and several lists can thread through the same slab.
+
+pub(crate) const NIL: usize = usize::MAX;
+
+struct Node<T> {
+    value: T,
+    prev: usize,
+    next: usize,
+}
+
+pub(crate) struct Slab<T> {
+    nodes: Vec<Option<Node<T>>>,
+    free: Vec<usize>,
+}
+
+impl<T> Slab<T> {
+    pub(crate) const fn new() -> Slab<T> {
+        Slab { nodes: Vec::new(), free: Vec::new() }
+    }
+
+    pub(crate) fn insert(&mut self, value: T) -> usize {
+        let node = Some(Node { value, prev: NIL, next: NIL });
+        match self.free.pop() {
+            Some(idx) => {
+                self.nodes[idx] = node;
+                idx
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Frees a node. The node must already be unlinked from its list.
+    pub(crate) fn remove(&mut self, idx: usize) -> T {
+        let node = self.nodes[idx].take().expect("invalid slab index");
+        self.free.push(idx);
+        node.value
+    }
+
+    pub(crate) fn get(&self, idx: usize) -> &T {
+        &self.node(idx).value
+    }
+
+    pub(crate) fn get_mut(&mut self, idx: usize) -> &mut T {
+        &mut self.node_mut(idx).value
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        while let Some(None) = self.nodes.last() {
+            self.nodes.pop();
+        }
+        let len = self.nodes.len();
+        self.free.retain(|&idx| idx < len);
+        self.nodes.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+
+    fn node(&self, idx: usize) -> &Node<T> {
+        self.nodes[idx].as_ref().expect("invalid slab index")
+    }
+
+    fn node_mut(&mut self, idx: usize) -> &mut Node<T> {
+        self.nodes[idx].as_mut().expect("invalid slab index")
+    }
+}
+
+/// A doubly linked list of slab nodes, from most to least recently used.
+#[derive(Clone, Copy)]
+pub(crate) struct List {
+    head: usize,
+    tail: usize,
+    len: usize,
+}
+
+impl List {
+    pub(crate) const fn new() -> List {
+        List { head: NIL, tail: NIL, len: 0 }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn front(&self) -> Option<usize> {
+        if self.head == NIL { None } else { Some(self.head) }
+    }
+
+    pub(crate) fn back(&self) -> Option<usize> {
+        if self.tail == NIL { None } else { Some(self.tail) }
+    }
+
+    pub(crate) fn push_front<T>(&mut self, slab: &mut Slab<T>, idx: usize) {
+        {
+            let node = slab.node_mut(idx);
+            node.prev = NIL;
+            node.next = self.head;
+        }
+        if self.head != NIL {
+            slab.node_mut(self.head).prev = idx;
+        } else {
+            self.tail = idx;
+        }
+        self.head = idx;
+        self.len += 1;
+    }
+
+    pub(crate) fn unlink<T>(&mut self, slab: &mut Slab<T>, idx: usize) {
+        let (prev, next) = {
+            let node = slab.node_mut(idx);
+            let links = (node.prev, node.next);
+            node.prev = NIL;
+            node.next = NIL;
+            links
+        };
+        if prev != NIL {
+            slab.node_mut(prev).next = next;
+        } else {
+            self.head = next;
+        }
+        if next != NIL {
+            slab.node_mut(next).prev = prev;
+        } else {
+            self.tail = prev;
+        }
+        self.len -= 1;
+    }
+
+    pub(crate) fn move_to_front<T>(&mut self, slab: &mut Slab<T>, idx: usize) {
+        if self.head != idx {
+            self.unlink(slab, idx);
+            self.push_front(slab, idx);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self = List::new();
+    }
+
+    pub(crate) fn iter<'a, T>(&self, slab: &'a Slab<T>) -> ListIter<'a, T> {
+        ListIter { slab, cur: self.head, remaining: self.len }
+    }
+}
+
+pub(crate) struct ListIter<'a, T> {
+    slab: &'a Slab<T>,
+    cur: usize,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for ListIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.cur == NIL {
+            return None;
+        }
+        let node = self.slab.node(self.cur);
+        self.cur = node.next;
+        self.remaining -= 1;
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
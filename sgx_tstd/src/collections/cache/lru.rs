@@ -0,0 +1,343 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! A least-recently-used cache bounded by entry count and weight.
+
+use super::list::{List, Slab};
+use super::{cache_builder, is_expired, CacheMetrics, CacheStats, Config, EvictionReason};
+use crate::borrow::Borrow;
+use crate::boxed::Box;
+use crate::collections::HashMap;
+use crate::fmt;
+use crate::hash::Hash;
+use crate::time::{Duration, Instant};
+
+struct Entry<K, V> {
+    key: K,
+    value: V,
+    weight: usize,
+    expires: Option<Instant>,
+}
+
+/// A cache that evicts the least recently used entry when it is over its
+/// entry count or weight bound.
+///
+/// Lookups through [`get`](LruCache::get) refresh an entry, lookups through
+/// [`peek`](LruCache::peek) do not.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::LruCache;
+/// use std::time::Duration;
+///
+/// let mut cache = LruCache::builder(1024)
+///     .max_bytes(64 * 1024)
+///     .weigher(|k: &String, v: &Vec<u8>| k.len() + v.len())
+///     .ttl(Duration::from_secs(60))
+///     .build();
+///
+/// cache.insert("a".to_string(), vec![0; 100]);
+/// assert!(cache.get("a").is_some());
+/// assert_eq!(cache.bytes(), 101);
+/// ```
+pub struct LruCache<K, V> {
+    map: HashMap<K, usize>,
+    slab: Slab<Entry<K, V>>,
+    list: List,
+    bytes: usize,
+    config: Config<K, V>,
+    stats: CacheStats,
+}
+
+cache_builder! {
+    /// Configures an [`LruCache`].
+    LruCacheBuilder => LruCache
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+    /// Creates a cache holding at most `capacity` entries.
+    pub fn new(capacity: usize) -> LruCache<K, V> {
+        LruCache::from_config(Config::new(capacity))
+    }
+
+    /// Returns a builder for a cache holding at most `capacity` entries.
+    pub fn builder(capacity: usize) -> LruCacheBuilder<K, V> {
+        LruCacheBuilder { config: Config::new(capacity) }
+    }
+
+    fn from_config(config: Config<K, V>) -> LruCache<K, V> {
+        LruCache {
+            map: HashMap::new(),
+            slab: Slab::new(),
+            list: List::new(),
+            bytes: 0,
+            config,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Returns the number of entries, including expired ones not yet purged.
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    /// Returns `true` if the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.list.len() == 0
+    }
+
+    /// Returns the maximum number of entries.
+    pub fn capacity(&self) -> usize {
+        self.config.capacity
+    }
+
+    /// Returns the total weight of the entries.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Returns the maximum total weight of the entries.
+    pub fn max_bytes(&self) -> usize {
+        self.config.max_bytes
+    }
+
+    /// Returns the counters of the cache.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Returns a reference to the value of `key`, marking it most recently used.
+    pub fn get<Q: ?Sized>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let idx = self.lookup(key)?;
+        self.list.move_to_front(&mut self.slab, idx);
+        Some(&self.slab.get(idx).value)
+    }
+
+    /// Returns a mutable reference to the value of `key`, marking it most
+    /// recently used.
+    ///
+    /// The weight of the entry is not recomputed.
+    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let idx = self.lookup(key)?;
+        self.list.move_to_front(&mut self.slab, idx);
+        Some(&mut self.slab.get_mut(idx).value)
+    }
+
+    /// Returns a reference to the value of `key` without marking it used or
+    /// updating the counters.
+    pub fn peek<Q: ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let entry = self.slab.get(*self.map.get(key)?);
+        if is_expired(entry.expires) { None } else { Some(&entry.value) }
+    }
+
+    /// Returns `true` if a live entry for `key` exists.
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        self.peek(key).is_some()
+    }
+
+    /// Inserts an entry with the default time to live, returning the previous
+    /// value of `key`.
+    ///
+    /// An entry heavier than the byte bound is not cached.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let expires = self.config.expiry(None);
+        self.insert_entry(key, value, expires)
+    }
+
+    /// Inserts an entry that expires after `ttl`, returning the previous value
+    /// of `key`.
+    pub fn insert_with_ttl(&mut self, key: K, value: V, ttl: Duration) -> Option<V> {
+        let expires = self.config.expiry(Some(ttl));
+        self.insert_entry(key, value, expires)
+    }
+
+    /// Removes the entry of `key`, returning its value.
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let idx = *self.map.get(key)?;
+        let entry = self.unlink(idx, EvictionReason::Removed);
+        if is_expired(entry.expires) { None } else { Some(entry.value) }
+    }
+
+    /// Removes and returns the least recently used entry.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let idx = self.list.back()?;
+        let entry = self.unlink(idx, EvictionReason::Removed);
+        Some((entry.key, entry.value))
+    }
+
+    /// Drops every expired entry, returning how many were dropped.
+    pub fn purge_expired(&mut self) -> usize {
+        let expired: Vec<usize> = self
+            .map
+            .values()
+            .copied()
+            .filter(|&idx| is_expired(self.slab.get(idx).expires))
+            .collect();
+        for &idx in &expired {
+            self.unlink(idx, EvictionReason::Expired);
+        }
+        expired.len()
+    }
+
+    /// Changes the entry count bound, evicting entries as needed.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.config.capacity = capacity;
+        self.enforce_bounds();
+    }
+
+    /// Changes the byte bound, evicting entries as needed.
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.config.max_bytes = max_bytes;
+        self.enforce_bounds();
+    }
+
+    /// Removes all entries and releases the memory of the index.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.map.shrink_to_fit();
+        self.slab.clear();
+        self.slab.shrink_to_fit();
+        self.list.clear();
+        self.bytes = 0;
+    }
+
+    /// Returns an iterator over the entries from most to least recently used.
+    ///
+    /// Expired entries not yet purged are included.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { inner: self.list.iter(&self.slab) }
+    }
+
+    fn lookup<Q: ?Sized>(&mut self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let idx = match self.map.get(key) {
+            Some(&idx) => idx,
+            None => {
+                self.config.miss(&mut self.stats);
+                return None;
+            }
+        };
+        if is_expired(self.slab.get(idx).expires) {
+            self.unlink(idx, EvictionReason::Expired);
+            self.config.miss(&mut self.stats);
+            return None;
+        }
+        self.config.hit(&mut self.stats);
+        Some(idx)
+    }
+
+    fn insert_entry(&mut self, key: K, value: V, expires: Option<Instant>) -> Option<V> {
+        let weight = self.config.weigh(&key, &value);
+        let old = match self.map.get(&key) {
+            Some(&idx) => {
+                let entry = self.unlink(idx, EvictionReason::Replaced);
+                if is_expired(entry.expires) { None } else { Some(entry.value) }
+            }
+            None => None,
+        };
+        if weight > self.config.max_bytes || self.config.capacity == 0 {
+            return old;
+        }
+
+        let idx = self.slab.insert(Entry { key: key.clone(), value, weight, expires });
+        self.list.push_front(&mut self.slab, idx);
+        self.map.insert(key, idx);
+        self.bytes += weight;
+        self.config.inserted(&mut self.stats, weight);
+        self.enforce_bounds();
+        old
+    }
+
+    fn enforce_bounds(&mut self) {
+        while self.list.len() > self.config.capacity || self.bytes > self.config.max_bytes {
+            let idx = match self.list.back() {
+                Some(idx) => idx,
+                None => break,
+            };
+            let reason = if is_expired(self.slab.get(idx).expires) {
+                EvictionReason::Expired
+            } else {
+                EvictionReason::Capacity
+            };
+            self.unlink(idx, reason);
+        }
+    }
+
+    fn unlink(&mut self, idx: usize, reason: EvictionReason) -> Entry<K, V> {
+        self.list.unlink(&mut self.slab, idx);
+        let entry = self.slab.remove(idx);
+        self.map.remove(&entry.key);
+        self.bytes -= entry.weight;
+        self.config.evicted(&mut self.stats, reason, entry.weight);
+        entry
+    }
+}
+
+impl<K, V> fmt::Debug for LruCache<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LruCache")
+            .field("len", &self.list.len())
+            .field("capacity", &self.config.capacity)
+            .field("bytes", &self.bytes)
+            .field("max_bytes", &self.config.max_bytes)
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An iterator over the entries of an [`LruCache`], most recently used first.
+pub struct Iter<'a, K, V> {
+    inner: super::list::ListIter<'a, Entry<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next().map(|e| (&e.key, &e.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
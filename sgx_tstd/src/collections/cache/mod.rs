@@ -0,0 +1,197 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Bounded caches sized for the EPC.
+//!
+//! Both caches bound their contents by entry count and by an estimate of the
+//! bytes they hold, as computed by a weigher. Entries can carry a time to
+//! live, measured with the host monotonic clock, after which lookups treat
+//! them as absent. Every cache keeps [`CacheStats`] and can forward events to
+//! a [`CacheMetrics`] hook.
+
+use crate::boxed::Box;
+use crate::mem;
+use crate::time::{Duration, Instant};
+
+pub mod arc;
+pub(crate) mod list;
+pub mod lru;
+
+/// Why an entry left a cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EvictionReason {
+    /// The cache was over its entry or byte bound.
+    Capacity,
+    /// The entry outlived its time to live.
+    Expired,
+    /// The entry was overwritten by an insert with the same key.
+    Replaced,
+    /// The entry was removed explicitly.
+    Removed,
+}
+
+/// Hooks called by a cache as it serves requests.
+///
+/// All methods have empty default implementations.
+pub trait CacheMetrics: Send + Sync {
+    /// A lookup found a live entry.
+    fn on_hit(&self) {}
+    /// A lookup found no live entry.
+    fn on_miss(&self) {}
+    /// An entry of the given weight was inserted.
+    fn on_insert(&self, _weight: usize) {}
+    /// An entry of the given weight left the cache.
+    fn on_evict(&self, _reason: EvictionReason, _weight: usize) {}
+}
+
+/// Counters kept by every cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups that found a live entry.
+    pub hits: u64,
+    /// Lookups that found no live entry.
+    pub misses: u64,
+    /// Entries inserted.
+    pub inserts: u64,
+    /// Entries evicted to respect the bounds.
+    pub evictions: u64,
+    /// Entries dropped because their time to live passed.
+    pub expirations: u64,
+}
+
+pub(crate) type Weigher<K, V> = Box<dyn Fn(&K, &V) -> usize + Send + Sync>;
+
+/// Bounds and hooks shared by the cache builders.
+pub(crate) struct Config<K, V> {
+    pub(crate) capacity: usize,
+    pub(crate) max_bytes: usize,
+    pub(crate) ttl: Option<Duration>,
+    pub(crate) weigher: Option<Weigher<K, V>>,
+    pub(crate) metrics: Option<Box<dyn CacheMetrics>>,
+}
+
+impl<K, V> Config<K, V> {
+    pub(crate) fn new(capacity: usize) -> Config<K, V> {
+        Config { capacity, max_bytes: usize::MAX, ttl: None, weigher: None, metrics: None }
+    }
+
+    pub(crate) fn weigh(&self, key: &K, value: &V) -> usize {
+        match self.weigher {
+            Some(ref weigher) => weigher(key, value),
+            None => mem::size_of::<K>() + mem::size_of::<V>(),
+        }
+    }
+
+    pub(crate) fn expiry(&self, ttl: Option<Duration>) -> Option<Instant> {
+        ttl.or(self.ttl).and_then(|ttl| Instant::_now().checked_add(ttl))
+    }
+
+    pub(crate) fn hit(&self, stats: &mut CacheStats) {
+        stats.hits += 1;
+        if let Some(ref m) = self.metrics {
+            m.on_hit();
+        }
+    }
+
+    pub(crate) fn miss(&self, stats: &mut CacheStats) {
+        stats.misses += 1;
+        if let Some(ref m) = self.metrics {
+            m.on_miss();
+        }
+    }
+
+    pub(crate) fn inserted(&self, stats: &mut CacheStats, weight: usize) {
+        stats.inserts += 1;
+        if let Some(ref m) = self.metrics {
+            m.on_insert(weight);
+        }
+    }
+
+    pub(crate) fn evicted(&self, stats: &mut CacheStats, reason: EvictionReason, weight: usize) {
+        match reason {
+            EvictionReason::Capacity => stats.evictions += 1,
+            EvictionReason::Expired => stats.expirations += 1,
+            EvictionReason::Replaced | EvictionReason::Removed => {}
+        }
+        if let Some(ref m) = self.metrics {
+            m.on_evict(reason, weight);
+        }
+    }
+}
+
+pub(crate) fn is_expired(expires: Option<Instant>) -> bool {
+    match expires {
+        Some(expires) => Instant::_now() >= expires,
+        None => false,
+    }
+}
+
+macro_rules! cache_builder {
+    ($(#[$attr:meta])* $builder:ident => $cache:ident) => {
+        $(#[$attr])*
+        pub struct $builder<K, V> {
+            config: Config<K, V>,
+        }
+
+        impl<K: Hash + Eq + Clone, V> $builder<K, V> {
+            /// Sets the maximum total weight of the entries.
+            pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+                self.config.max_bytes = max_bytes;
+                self
+            }
+
+            /// Sets the time to live of entries inserted without an explicit one.
+            pub fn ttl(mut self, ttl: Duration) -> Self {
+                self.config.ttl = Some(ttl);
+                self
+            }
+
+            /// Sets the function estimating the bytes held by an entry.
+            ///
+            /// Defaults to the inline size of the key and the value.
+            pub fn weigher<F>(mut self, weigher: F) -> Self
+            where
+                F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+            {
+                self.config.weigher = Some(Box::new(weigher));
+                self
+            }
+
+            /// Installs a metrics hook.
+            pub fn metrics<M: CacheMetrics + 'static>(mut self, metrics: M) -> Self {
+                self.config.metrics = Some(Box::new(metrics));
+                self
+            }
+
+            /// Builds the cache.
+            pub fn build(self) -> $cache<K, V> {
+                $cache::from_config(self.config)
+            }
+        }
+
+        impl<K, V> fmt::Debug for $builder<K, V> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($builder))
+                    .field("capacity", &self.config.capacity)
+                    .field("max_bytes", &self.config.max_bytes)
+                    .field("ttl", &self.config.ttl)
+                    .finish_non_exhaustive()
+            }
+        }
+    };
+}
+pub(crate) use cache_builder;
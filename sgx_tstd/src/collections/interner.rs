@@ -0,0 +1,224 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! A string interner backed by an arena.
+//!
+//! [`Interner`] stores each distinct string once and hands out small
+//! [`Symbol`] handles that compare and hash as integers. Strings are copied
+//! into large chunks instead of individual allocations, and every string is
+//! freed at once when the interner is cleared or dropped. This suits parsers
+//! that see the same identifiers many times.
+
+use crate::collections::HashMap;
+use crate::fmt;
+use crate::num::NonZeroU32;
+use crate::slice;
+use crate::str;
+use crate::string::String;
+use crate::vec::Vec;
+
+const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+/// A handle to a string in an [`Interner`].
+///
+/// Symbols are only meaningful to the interner that created them, and only
+/// until it is cleared.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(NonZeroU32);
+
+impl Symbol {
+    /// Returns the index of the symbol, counting from zero in the order the
+    /// strings were interned.
+    pub fn index(self) -> usize {
+        self.0.get() as usize - 1
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Symbol({})", self.index())
+    }
+}
+
+/// Interns strings, returning a stable [`Symbol`] for each distinct string.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::interner::Interner;
+///
+/// let mut names = Interner::new();
+/// let a = names.intern("alice");
+/// let b = names.intern("bob");
+///
+/// assert_eq!(names.intern("alice"), a);
+/// assert_ne!(a, b);
+/// assert_eq!(names.resolve(b), "bob");
+/// ```
+pub struct Interner {
+    // Strings point into these chunks, which are never grown in place and so
+    // never move. They are freed together.
+    chunks: Vec<String>,
+    chunk_size: usize,
+    map: HashMap<&'static str, Symbol>,
+    strings: Vec<&'static str>,
+}
+
+impl Interner {
+    /// Creates an empty interner.
+    pub fn new() -> Interner {
+        Interner::with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Creates an empty interner that allocates string storage in chunks of
+    /// `chunk_size` bytes. Strings longer than a chunk get a chunk of their
+    /// own.
+    pub fn with_chunk_size(chunk_size: usize) -> Interner {
+        Interner {
+            chunks: Vec::new(),
+            chunk_size: chunk_size.max(1),
+            map: HashMap::new(),
+            strings: Vec::new(),
+        }
+    }
+
+    /// Returns the symbol of `s`, interning it if it is new.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than `u32::MAX - 1` strings are interned.
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(&sym) = self.map.get(s) {
+            return sym;
+        }
+        let id = u32::try_from(self.strings.len() + 1)
+            .ok()
+            .and_then(NonZeroU32::new)
+            .expect("too many interned strings");
+        let sym = Symbol(id);
+        let stored = self.alloc(s);
+        self.map.insert(stored, sym);
+        self.strings.push(stored);
+        sym
+    }
+
+    /// Returns the symbol of `s` if it has been interned.
+    pub fn get(&self, s: &str) -> Option<Symbol> {
+        self.map.get(s).copied()
+    }
+
+    /// Returns the string of `sym`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sym` did not come from this interner.
+    pub fn resolve(&self, sym: Symbol) -> &str {
+        self.try_resolve(sym).expect("symbol from another interner")
+    }
+
+    /// Returns the string of `sym`, or `None` if it did not come from this
+    /// interner.
+    pub fn try_resolve(&self, sym: Symbol) -> Option<&str> {
+        self.strings.get(sym.index()).copied()
+    }
+
+    /// Returns the number of distinct strings.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Returns `true` if no string has been interned.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Returns the number of bytes reserved for string storage.
+    pub fn allocated_bytes(&self) -> usize {
+        self.chunks.iter().map(String::capacity).sum()
+    }
+
+    /// Frees every string at once. Symbols handed out before are invalid
+    /// afterwards.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.strings.clear();
+        self.chunks.clear();
+    }
+
+    /// Returns an iterator over the symbols and their strings, in the order
+    /// they were interned.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { inner: self.strings.iter().enumerate() }
+    }
+
+    fn alloc(&mut self, s: &str) -> &'static str {
+        let fits = self.chunks.last().map_or(false, |c| c.capacity() - c.len() >= s.len());
+        if !fits {
+            self.chunks.push(String::with_capacity(self.chunk_size.max(s.len())));
+        }
+        let chunk = self.chunks.last_mut().unwrap();
+        let start = chunk.len();
+        chunk.push_str(s);
+        // SAFETY: the chunk had room for `s`, so pushing did not reallocate
+        // and the bytes stay put until the chunk is dropped. The returned
+        // reference never escapes with a lifetime longer than `&self`.
+        unsafe {
+            let bytes = slice::from_raw_parts(chunk.as_ptr().add(start), s.len());
+            str::from_utf8_unchecked(bytes)
+        }
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Interner {
+        Interner::new()
+    }
+}
+
+impl fmt::Debug for Interner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a Interner {
+    type Item = (Symbol, &'a str);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// An iterator over the strings of an [`Interner`].
+pub struct Iter<'a> {
+    inner: crate::iter::Enumerate<slice::Iter<'a, &'static str>>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (Symbol, &'a str);
+
+    fn next(&mut self) -> Option<(Symbol, &'a str)> {
+        self.inner.next().map(|(i, s)| (Symbol(NonZeroU32::new(i as u32 + 1).unwrap()), *s))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
@@ -425,11 +425,22 @@ pub use alloc_crate::collections::{LinkedList, VecDeque};
 pub use self::hash_map::HashMap;
 pub use self::hash_set::HashSet;
 
+pub use self::arc_cache::ArcCache;
+pub use self::lru_cache::LruCache;
+pub use self::pairing_heap::{DeadlineQueue, PairingHeap};
+
 pub use alloc_crate::collections::TryReserveError;
 pub use alloc_crate::collections::TryReserveErrorKind;
 
+mod cache;
 mod hash;
 
+pub mod array_vec;
+pub mod interner;
+pub mod pairing_heap;
+pub mod probabilistic;
+pub mod small_vec;
+
 pub mod hash_map {
     //! A hash map implemented with quadratic probing and SIMD lookup.
     pub use super::hash::map::*;
@@ -439,3 +450,15 @@ pub mod hash_set {
     //! A hash set implemented as a `HashMap` where the value is `()`.
     pub use super::hash::set::*;
 }
+
+pub mod lru_cache {
+    //! A least-recently-used cache bounded by entry count and weight.
+    pub use super::cache::lru::*;
+    pub use super::cache::{CacheMetrics, CacheStats, EvictionReason};
+}
+
+pub mod arc_cache {
+    //! An adaptive replacement cache bounded by entry count and weight.
+    pub use super::cache::arc::*;
+    pub use super::cache::{CacheMetrics, CacheStats, EvictionReason};
+}
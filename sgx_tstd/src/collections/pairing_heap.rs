@@ -0,0 +1,477 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! A pairing heap with decrease-key, and a deadline queue built on it.
+//!
+//! Unlike [`BinaryHeap`](super::BinaryHeap), [`PairingHeap`] returns a
+//! [`Handle`] for every entry, through which the entry's key can later be
+//! changed or the entry removed without searching the heap. Decreasing a key
+//! takes constant time and popping the minimum amortized `O(log n)`.
+//!
+//! [`DeadlineQueue`] orders items by the time they are due and is the
+//! structure behind timers and expiry: schedule an item, move or cancel it
+//! through its handle, and pop whatever has expired.
+
+use crate::fmt;
+use crate::mem;
+use crate::time::{Duration, Instant};
+use crate::vec::Vec;
+
+const NIL: usize = usize::MAX;
+
+/// A handle to an entry of a [`PairingHeap`] or [`DeadlineQueue`].
+///
+/// A handle stays valid until its entry is popped or removed. Using it after
+/// that is harmless: the operation reports that the entry is gone, even if
+/// its slot has been reused.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Handle {
+    index: usize,
+    generation: u64,
+}
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    child: usize,
+    sibling: usize,
+    // The parent for a first child, the previous sibling otherwise.
+    prev: usize,
+}
+
+struct Slot<K, V> {
+    generation: u64,
+    node: Option<Node<K, V>>,
+}
+
+/// A min-heap with stable handles and decrease-key.
+///
+/// Entries with equal keys are popped in no particular order.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::pairing_heap::PairingHeap;
+///
+/// let mut heap = PairingHeap::new();
+/// heap.push(5, "e");
+/// let b = heap.push(7, "b");
+/// heap.push(3, "c");
+///
+/// heap.decrease_key(b, 1);
+/// assert_eq!(heap.pop(), Some((1, "b")));
+/// assert_eq!(heap.pop(), Some((3, "c")));
+/// ```
+pub struct PairingHeap<K, V> {
+    slots: Vec<Slot<K, V>>,
+    free: Vec<usize>,
+    root: usize,
+    len: usize,
+}
+
+impl<K: Ord, V> PairingHeap<K, V> {
+    /// Creates an empty heap.
+    pub const fn new() -> PairingHeap<K, V> {
+        PairingHeap { slots: Vec::new(), free: Vec::new(), root: NIL, len: 0 }
+    }
+
+    /// Creates an empty heap with room for `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> PairingHeap<K, V> {
+        PairingHeap { slots: Vec::with_capacity(capacity), free: Vec::new(), root: NIL, len: 0 }
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the heap is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds an entry and returns its handle.
+    pub fn push(&mut self, key: K, value: V) -> Handle {
+        let node = Node { key, value, child: NIL, sibling: NIL, prev: NIL };
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index].node = Some(node);
+                index
+            }
+            None => {
+                self.slots.push(Slot { generation: 0, node: Some(node) });
+                self.slots.len() - 1
+            }
+        };
+        self.root = self.meld(self.root, index);
+        self.len += 1;
+        Handle { index, generation: self.slots[index].generation }
+    }
+
+    /// Returns the entry with the smallest key.
+    pub fn peek(&self) -> Option<(&K, &V)> {
+        self.entry(self.root)
+    }
+
+    /// Removes and returns the entry with the smallest key.
+    pub fn pop(&mut self) -> Option<(K, V)> {
+        if self.root == NIL {
+            return None;
+        }
+        let root = self.root;
+        let child = self.node(root).child;
+        self.root = self.merge_pairs(child);
+        Some(self.release(root))
+    }
+
+    /// Returns the entry of `handle`.
+    pub fn get(&self, handle: Handle) -> Option<(&K, &V)> {
+        if self.is_live(handle) { self.entry(handle.index) } else { None }
+    }
+
+    /// Returns the value of `handle` for modification.
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut V> {
+        if !self.is_live(handle) {
+            return None;
+        }
+        self.slots[handle.index].node.as_mut().map(|n| &mut n.value)
+    }
+
+    /// Returns `true` if the entry of `handle` is still in the heap.
+    pub fn contains(&self, handle: Handle) -> bool {
+        self.is_live(handle)
+    }
+
+    /// Lowers the key of `handle` to `key`.
+    ///
+    /// Returns `false`, leaving the heap unchanged, if the entry is gone or
+    /// `key` is greater than its current key.
+    pub fn decrease_key(&mut self, handle: Handle, key: K) -> bool {
+        if !self.is_live(handle) || key > self.node(handle.index).key {
+            return false;
+        }
+        let index = handle.index;
+        self.node_mut(index).key = key;
+        if index != self.root {
+            self.cut(index);
+            self.root = self.meld(self.root, index);
+        }
+        true
+    }
+
+    /// Sets the key of `handle` to `key`, returning the previous key, or
+    /// `None` if the entry is gone.
+    ///
+    /// Raising a key costs as much as a removal followed by a push.
+    pub fn update_key(&mut self, handle: Handle, key: K) -> Option<K> {
+        if !self.is_live(handle) {
+            return None;
+        }
+        let index = handle.index;
+        if key <= self.node(index).key {
+            let old = mem::replace(&mut self.node_mut(index).key, key);
+            if index != self.root {
+                self.cut(index);
+                self.root = self.meld(self.root, index);
+            }
+            return Some(old);
+        }
+        self.detach(index);
+        let old = mem::replace(&mut self.node_mut(index).key, key);
+        self.root = self.meld(self.root, index);
+        Some(old)
+    }
+
+    /// Removes the entry of `handle` and returns it.
+    pub fn remove(&mut self, handle: Handle) -> Option<(K, V)> {
+        if !self.is_live(handle) {
+            return None;
+        }
+        self.detach(handle.index);
+        Some(self.release(handle.index))
+    }
+
+    /// Removes every entry. All handles become invalid.
+    pub fn clear(&mut self) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if slot.node.take().is_some() {
+                slot.generation += 1;
+                self.free.push(index);
+            }
+        }
+        self.root = NIL;
+        self.len = 0;
+    }
+
+    /// Returns an iterator over the entries in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Handle, &K, &V)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let node = slot.node.as_ref()?;
+            Some((Handle { index, generation: slot.generation }, &node.key, &node.value))
+        })
+    }
+
+    fn is_live(&self, handle: Handle) -> bool {
+        self.slots
+            .get(handle.index)
+            .map_or(false, |s| s.generation == handle.generation && s.node.is_some())
+    }
+
+    fn entry(&self, index: usize) -> Option<(&K, &V)> {
+        let node = self.slots.get(index)?.node.as_ref()?;
+        Some((&node.key, &node.value))
+    }
+
+    fn node(&self, index: usize) -> &Node<K, V> {
+        self.slots[index].node.as_ref().expect("live pairing heap node")
+    }
+
+    fn node_mut(&mut self, index: usize) -> &mut Node<K, V> {
+        self.slots[index].node.as_mut().expect("live pairing heap node")
+    }
+
+    fn release(&mut self, index: usize) -> (K, V) {
+        let slot = &mut self.slots[index];
+        let node = slot.node.take().expect("live pairing heap node");
+        slot.generation += 1;
+        self.free.push(index);
+        self.len -= 1;
+        (node.key, node.value)
+    }
+
+    // Links two detached trees and returns the new root.
+    fn meld(&mut self, a: usize, b: usize) -> usize {
+        if a == NIL {
+            return b;
+        }
+        if b == NIL {
+            return a;
+        }
+        let (parent, child) = if self.node(b).key < self.node(a).key { (b, a) } else { (a, b) };
+        let first = self.node(parent).child;
+        if first != NIL {
+            self.node_mut(first).prev = child;
+        }
+        let c = self.node_mut(child);
+        c.sibling = first;
+        c.prev = parent;
+        self.node_mut(parent).child = child;
+        parent
+    }
+
+    // Melds a list of siblings in two passes and returns the new root.
+    fn merge_pairs(&mut self, first: usize) -> usize {
+        let mut pairs = Vec::new();
+        let mut cur = first;
+        while cur != NIL {
+            let a = cur;
+            let b = self.node(a).sibling;
+            cur = if b == NIL { NIL } else { self.node(b).sibling };
+            self.unlink_siblings(a);
+            if b != NIL {
+                self.unlink_siblings(b);
+            }
+            pairs.push(self.meld(a, b));
+        }
+        let mut root = NIL;
+        while let Some(tree) = pairs.pop() {
+            root = self.meld(tree, root);
+        }
+        root
+    }
+
+    fn unlink_siblings(&mut self, index: usize) {
+        let node = self.node_mut(index);
+        node.sibling = NIL;
+        node.prev = NIL;
+    }
+
+    // Detaches a non-root node, with its subtree, from its parent.
+    fn cut(&mut self, index: usize) {
+        let (prev, sibling) = {
+            let node = self.node(index);
+            (node.prev, node.sibling)
+        };
+        if self.node(prev).child == index {
+            self.node_mut(prev).child = sibling;
+        } else {
+            self.node_mut(prev).sibling = sibling;
+        }
+        if sibling != NIL {
+            self.node_mut(sibling).prev = prev;
+        }
+        self.unlink_siblings(index);
+    }
+
+    // Takes a node out of the heap, keeping its children in the heap.
+    fn detach(&mut self, index: usize) {
+        let child = mem::replace(&mut self.node_mut(index).child, NIL);
+        if index == self.root {
+            self.root = self.merge_pairs(child);
+        } else {
+            self.cut(index);
+            let sub = self.merge_pairs(child);
+            self.root = self.meld(self.root, sub);
+        }
+    }
+}
+
+impl<K: Ord, V> Default for PairingHeap<K, V> {
+    fn default() -> PairingHeap<K, V> {
+        PairingHeap::new()
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for PairingHeap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter().map(|(_, k, v)| (k, v))).finish()
+    }
+}
+
+/// A queue of items ordered by the time they are due.
+///
+/// Deadlines are [`Instant`]s by default; any ordered type can be used, such
+/// as a [`Duration`] since some trusted epoch.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::pairing_heap::DeadlineQueue;
+/// use std::time::Duration;
+///
+/// let mut sessions = DeadlineQueue::new();
+/// let a = sessions.schedule(Duration::from_secs(30), "alice");
+/// sessions.schedule(Duration::from_secs(20), "bob");
+///
+/// // Alice was active again.
+/// sessions.reschedule(a, Duration::from_secs(90));
+///
+/// let expired: Vec<_> = sessions.drain_expired(Duration::from_secs(60)).collect();
+/// assert_eq!(expired, [(Duration::from_secs(20), "bob")]);
+/// ```
+pub struct DeadlineQueue<T, D = Instant> {
+    heap: PairingHeap<D, T>,
+}
+
+impl<T, D: Ord + Copy> DeadlineQueue<T, D> {
+    /// Creates an empty queue.
+    pub const fn new() -> DeadlineQueue<T, D> {
+        DeadlineQueue { heap: PairingHeap::new() }
+    }
+
+    /// Returns the number of scheduled items.
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Returns `true` if nothing is scheduled.
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Schedules `item` to expire at `deadline`.
+    pub fn schedule(&mut self, deadline: D, item: T) -> Handle {
+        self.heap.push(deadline, item)
+    }
+
+    /// Moves the deadline of a scheduled item, earlier or later.
+    ///
+    /// Returns `false` if the item is no longer scheduled.
+    pub fn reschedule(&mut self, handle: Handle, deadline: D) -> bool {
+        self.heap.update_key(handle, deadline).is_some()
+    }
+
+    /// Unschedules an item and returns it.
+    pub fn cancel(&mut self, handle: Handle) -> Option<T> {
+        self.heap.remove(handle).map(|(_, item)| item)
+    }
+
+    /// Returns the deadline of a scheduled item.
+    pub fn deadline(&self, handle: Handle) -> Option<D> {
+        self.heap.get(handle).map(|(d, _)| *d)
+    }
+
+    /// Returns a scheduled item for modification.
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        self.heap.get_mut(handle)
+    }
+
+    /// Returns the earliest deadline.
+    pub fn next_deadline(&self) -> Option<D> {
+        self.heap.peek().map(|(d, _)| *d)
+    }
+
+    /// Removes and returns the earliest item if its deadline is at or before
+    /// `now`.
+    pub fn pop_expired(&mut self, now: D) -> Option<(D, T)> {
+        match self.heap.peek() {
+            Some((deadline, _)) if *deadline <= now => self.heap.pop(),
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator that removes the items due at or before `now`,
+    /// earliest first.
+    pub fn drain_expired(&mut self, now: D) -> DrainExpired<'_, T, D> {
+        DrainExpired { queue: self, now }
+    }
+
+    /// Unschedules every item.
+    pub fn clear(&mut self) {
+        self.heap.clear()
+    }
+}
+
+impl<T> DeadlineQueue<T, Instant> {
+    /// Returns how long after `now` the earliest item is due, zero if it is
+    /// already due.
+    pub fn time_until_next(&self, now: Instant) -> Option<Duration> {
+        self.next_deadline().map(|d| d.saturating_duration_since(now))
+    }
+}
+
+impl<T, D: Ord + Copy> Default for DeadlineQueue<T, D> {
+    fn default() -> DeadlineQueue<T, D> {
+        DeadlineQueue::new()
+    }
+}
+
+impl<T: fmt::Debug, D: Ord + Copy + fmt::Debug> fmt::Debug for DeadlineQueue<T, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeadlineQueue")
+            .field("len", &self.len())
+            .field("next_deadline", &self.next_deadline())
+            .finish()
+    }
+}
+
+/// An iterator over the expired items of a [`DeadlineQueue`].
+///
+/// Created by [`DeadlineQueue::drain_expired`]. Items not consumed stay in
+/// the queue.
+pub struct DrainExpired<'a, T, D> {
+    queue: &'a mut DeadlineQueue<T, D>,
+    now: D,
+}
+
+impl<T, D: Ord + Copy> Iterator for DrainExpired<'_, T, D> {
+    type Item = (D, T);
+
+    fn next(&mut self) -> Option<(D, T)> {
+        self.queue.pop_expired(self.now)
+    }
+}
@@ -0,0 +1,207 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use super::FilterKeys;
+use crate::fmt;
+use crate::hash::Hash;
+use crate::vec::Vec;
+
+/// A Bloom filter with a fixed number of bits.
+///
+/// Items can be added but not removed. The false positive rate rises as items
+/// are added past the count the filter was sized for.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::probabilistic::BloomFilter;
+///
+/// let mut revoked = BloomFilter::new(10_000, 0.001);
+/// revoked.insert("serial-1234");
+///
+/// assert!(revoked.contains("serial-1234"));
+/// ```
+#[derive(Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+    keys: FilterKeys,
+    inserted: usize,
+}
+
+impl BloomFilter {
+    /// Creates a filter sized for `expected_items` with a false positive rate
+    /// of about `fp_rate`, keyed from the enclave RNG.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fp_rate` is not strictly between 0 and 1.
+    pub fn new(expected_items: usize, fp_rate: f64) -> BloomFilter {
+        BloomFilter::with_keys(expected_items, fp_rate, FilterKeys::random())
+    }
+
+    /// Creates a filter like [`new`](BloomFilter::new) with the given keys.
+    pub fn with_keys(expected_items: usize, fp_rate: f64, keys: FilterKeys) -> BloomFilter {
+        assert!(fp_rate > 0.0 && fp_rate < 1.0, "false positive rate must be in (0, 1)");
+        let n = expected_items.max(1) as f64;
+        let ln2 = crate::f64::consts::LN_2;
+        let num_bits = (-n * fp_rate.ln() / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let num_hashes = ((num_bits as f64 / n) * ln2).round().clamp(1.0, 32.0) as u32;
+        BloomFilter::with_params(num_bits, num_hashes, keys)
+    }
+
+    /// Creates a filter with exactly `num_bits` bits (rounded up to a multiple
+    /// of 64) and `num_hashes` hash functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_bits` or `num_hashes` is zero.
+    pub fn with_params(num_bits: u64, num_hashes: u32, keys: FilterKeys) -> BloomFilter {
+        assert!(num_bits > 0 && num_hashes > 0, "a Bloom filter needs bits and hashes");
+        let words = ((num_bits + 63) / 64) as usize;
+        BloomFilter {
+            bits: vec![0; words],
+            num_bits: words as u64 * 64,
+            num_hashes,
+            keys,
+            inserted: 0,
+        }
+    }
+
+    /// Adds an item, returning `false` if it may already have been present.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) -> bool {
+        let (h1, h2) = self.hashes(item);
+        let mut added = false;
+        for i in 0..self.num_hashes {
+            let (word, mask) = self.position(h1, h2, i);
+            if self.bits[word] & mask == 0 {
+                self.bits[word] |= mask;
+                added = true;
+            }
+        }
+        if added {
+            self.inserted += 1;
+        }
+        added
+    }
+
+    /// Returns `true` if the item may be in the set, `false` if it is not.
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        let (h1, h2) = self.hashes(item);
+        (0..self.num_hashes).all(|i| {
+            let (word, mask) = self.position(h1, h2, i);
+            self.bits[word] & mask != 0
+        })
+    }
+
+    /// Adds every item of `other`, which must have the same size and keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the filters are not compatible.
+    pub fn union(&mut self, other: &BloomFilter) {
+        assert!(
+            self.num_bits == other.num_bits
+                && self.num_hashes == other.num_hashes
+                && self.keys == other.keys,
+            "union of incompatible Bloom filters"
+        );
+        for (a, b) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *a |= *b;
+        }
+        self.inserted += other.inserted;
+    }
+
+    /// Removes every item.
+    pub fn clear(&mut self) {
+        self.bits.iter_mut().for_each(|w| *w = 0);
+        self.inserted = 0;
+    }
+
+    /// Returns the number of inserts that set at least one bit since the
+    /// filter was created or restored.
+    pub fn len(&self) -> usize {
+        self.inserted
+    }
+
+    /// Returns `true` if no bit is set.
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|w| *w == 0)
+    }
+
+    /// Returns the number of bits in the filter.
+    pub fn num_bits(&self) -> u64 {
+        self.num_bits
+    }
+
+    /// Returns the number of hash functions.
+    pub fn num_hashes(&self) -> u32 {
+        self.num_hashes
+    }
+
+    /// Returns the keys of the hash functions.
+    pub fn keys(&self) -> FilterKeys {
+        self.keys
+    }
+
+    /// Returns the false positive rate expected at the current fill.
+    pub fn estimated_fp_rate(&self) -> f64 {
+        let set: u32 = self.bits.iter().map(|w| w.count_ones()).sum();
+        (set as f64 / self.num_bits as f64).powi(self.num_hashes as i32)
+    }
+
+    /// Returns the bit array as 64-bit words, for sealing.
+    pub fn as_words(&self) -> &[u64] {
+        &self.bits
+    }
+
+    /// Restores a filter from words returned by [`as_words`].
+    ///
+    /// [`as_words`]: BloomFilter::as_words
+    ///
+    /// # Panics
+    ///
+    /// Panics if `words` is empty or `num_hashes` is zero.
+    pub fn from_words(words: Vec<u64>, num_hashes: u32, keys: FilterKeys) -> BloomFilter {
+        assert!(!words.is_empty() && num_hashes > 0, "a Bloom filter needs bits and hashes");
+        BloomFilter { num_bits: words.len() as u64 * 64, bits: words, num_hashes, keys, inserted: 0 }
+    }
+
+    fn hashes<T: Hash + ?Sized>(&self, item: &T) -> (u64, u64) {
+        let h1 = self.keys.hash(item);
+        let h2 = self.keys.rehash(h1) | 1;
+        (h1, h2)
+    }
+
+    // Enhanced double hashing (Kirsch and Mitzenmacher).
+    fn position(&self, h1: u64, h2: u64, i: u32) -> (usize, u64) {
+        let i = u64::from(i);
+        let bit = h1.wrapping_add(i.wrapping_mul(h2)).wrapping_add(i * i * i) % self.num_bits;
+        ((bit / 64) as usize, 1 << (bit % 64))
+    }
+}
+
+impl fmt::Debug for BloomFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BloomFilter")
+            .field("num_bits", &self.num_bits)
+            .field("num_hashes", &self.num_hashes)
+            .field("inserted", &self.inserted)
+            .finish_non_exhaustive()
+    }
+}
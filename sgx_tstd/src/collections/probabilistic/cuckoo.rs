@@ -0,0 +1,246 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use super::FilterKeys;
+use crate::error;
+use crate::fmt;
+use crate::hash::Hash;
+use crate::vec::Vec;
+
+const BUCKET_SIZE: usize = 4;
+const MAX_KICKS: usize = 500;
+
+/// A cuckoo filter of 16-bit fingerprints in buckets of four.
+///
+/// Unlike a [`BloomFilter`](super::BloomFilter), items can be removed, and
+/// lookups touch at most two buckets. Removing an item that was never
+/// inserted may remove another item with the same fingerprint.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::probabilistic::CuckooFilter;
+///
+/// let mut seen = CuckooFilter::with_capacity(1024);
+/// seen.insert(&42u64).unwrap();
+/// assert!(seen.contains(&42u64));
+///
+/// assert!(seen.remove(&42u64));
+/// assert!(!seen.contains(&42u64));
+/// ```
+#[derive(Clone)]
+pub struct CuckooFilter {
+    buckets: Vec<[u16; BUCKET_SIZE]>,
+    mask: usize,
+    keys: FilterKeys,
+    len: usize,
+    // A fingerprint displaced by an insert that ran out of kicks. It keeps
+    // the filter free of false negatives; inserts fail until it is placed.
+    victim: Option<(usize, u16)>,
+    rng: u64,
+}
+
+/// The error returned by [`CuckooFilter::insert`] when the filter is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CuckooFilterFull;
+
+impl fmt::Display for CuckooFilterFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "cuckoo filter is full".fmt(f)
+    }
+}
+
+impl error::Error for CuckooFilterFull {
+    #[allow(deprecated)]
+    fn description(&self) -> &str {
+        "cuckoo filter is full"
+    }
+}
+
+impl CuckooFilter {
+    /// Creates a filter for about `capacity` items, keyed from the enclave RNG.
+    pub fn with_capacity(capacity: usize) -> CuckooFilter {
+        CuckooFilter::with_keys(capacity, FilterKeys::random())
+    }
+
+    /// Creates a filter like [`with_capacity`](CuckooFilter::with_capacity)
+    /// with the given keys.
+    pub fn with_keys(capacity: usize, keys: FilterKeys) -> CuckooFilter {
+        // Buckets of four reach about 95% occupancy before inserts fail.
+        let buckets = (capacity.max(1) * 100 / 95 + BUCKET_SIZE - 1) / BUCKET_SIZE;
+        let buckets = buckets.next_power_of_two();
+        let (k0, k1) = keys.to_parts();
+        CuckooFilter {
+            buckets: vec![[0; BUCKET_SIZE]; buckets],
+            mask: buckets - 1,
+            keys,
+            len: 0,
+            victim: None,
+            rng: (k0 ^ k1.rotate_left(32)) | 1,
+        }
+    }
+
+    /// Adds an item.
+    ///
+    /// An item may be added more than once; each copy must then be removed
+    /// separately.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CuckooFilterFull`] if there is no room for the item. The
+    /// filter is unchanged in that case.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) -> Result<(), CuckooFilterFull> {
+        if self.victim.is_some() {
+            return Err(CuckooFilterFull);
+        }
+        let (i1, fp) = self.index_and_fingerprint(item);
+        let i2 = self.alt_index(i1, fp);
+        if self.put(i1, fp) || self.put(i2, fp) {
+            self.len += 1;
+            return Ok(());
+        }
+
+        let mut index = if self.next_rand() & 1 == 0 { i1 } else { i2 };
+        let mut fp = fp;
+        for _ in 0..MAX_KICKS {
+            let slot = (self.next_rand() as usize) % BUCKET_SIZE;
+            crate::mem::swap(&mut fp, &mut self.buckets[index][slot]);
+            index = self.alt_index(index, fp);
+            if self.put(index, fp) {
+                self.len += 1;
+                return Ok(());
+            }
+        }
+        // The new item is stored; the last displaced one waits aside.
+        self.victim = Some((index, fp));
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Returns `true` if the item may be in the set, `false` if it is not.
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        let (i1, fp) = self.index_and_fingerprint(item);
+        let i2 = self.alt_index(i1, fp);
+        self.buckets[i1].contains(&fp)
+            || self.buckets[i2].contains(&fp)
+            || self.victim.map_or(false, |(i, v)| v == fp && (i == i1 || i == i2))
+    }
+
+    /// Removes one copy of an item, returning `true` if a matching
+    /// fingerprint was found.
+    pub fn remove<T: Hash + ?Sized>(&mut self, item: &T) -> bool {
+        let (i1, fp) = self.index_and_fingerprint(item);
+        let i2 = self.alt_index(i1, fp);
+        if let Some((i, v)) = self.victim {
+            if v == fp && (i == i1 || i == i2) {
+                self.victim = None;
+                self.len -= 1;
+                return true;
+            }
+        }
+        for index in [i1, i2] {
+            if let Some(slot) = self.buckets[index].iter_mut().find(|s| **s == fp) {
+                *slot = 0;
+                self.len -= 1;
+                if let Some((i, v)) = self.victim.take() {
+                    let alt = self.alt_index(i, v);
+                    if !self.put(i, v) && !self.put(alt, v) {
+                        self.victim = Some((i, v));
+                    }
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Removes every item.
+    pub fn clear(&mut self) {
+        self.buckets.iter_mut().for_each(|b| *b = [0; BUCKET_SIZE]);
+        self.victim = None;
+        self.len = 0;
+    }
+
+    /// Returns the number of items in the filter.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the filter holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of fingerprint slots.
+    pub fn slots(&self) -> usize {
+        self.buckets.len() * BUCKET_SIZE
+    }
+
+    /// Returns the fraction of slots in use.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / self.slots() as f64
+    }
+
+    /// Returns the keys of the hash functions.
+    pub fn keys(&self) -> FilterKeys {
+        self.keys
+    }
+
+    fn index_and_fingerprint<T: Hash + ?Sized>(&self, item: &T) -> (usize, u16) {
+        let h = self.keys.hash(item);
+        // Zero marks an empty slot.
+        let fp = match (h >> 48) as u16 {
+            0 => 1,
+            fp => fp,
+        };
+        ((h as usize) & self.mask, fp)
+    }
+
+    fn alt_index(&self, index: usize, fp: u16) -> usize {
+        (index ^ self.keys.rehash(u64::from(fp)) as usize) & self.mask
+    }
+
+    fn put(&mut self, index: usize, fp: u16) -> bool {
+        match self.buckets[index].iter_mut().find(|s| **s == 0) {
+            Some(slot) => {
+                *slot = fp;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // xorshift64, only used to pick which fingerprint to displace.
+    fn next_rand(&mut self) -> u64 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng = x;
+        x
+    }
+}
+
+impl fmt::Debug for CuckooFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CuckooFilter")
+            .field("len", &self.len)
+            .field("slots", &self.slots())
+            .field("full", &self.victim.is_some())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,86 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Space-efficient probabilistic set membership.
+//!
+//! [`BloomFilter`] and [`CuckooFilter`] answer "is this item in the set?"
+//! with no false negatives and a tunable rate of false positives, using a
+//! few bits per item. They suit revocation lists and dedup sets that would
+//! not fit in the EPC as a [`HashSet`](crate::collections::HashSet).
+//!
+//! Both filters hash items with SipHash-1-3 keyed by [`FilterKeys`], which are
+//! drawn from the enclave RNG by default. An adversary who does not know the
+//! keys cannot craft items that collide, so they cannot raise the false
+//! positive rate or, for a cuckoo filter, force inserts to fail. Keep the
+//! keys with the filter if it is sealed and restored later.
+
+use crate::hash::{Hash, Hasher, SipHasher13};
+use crate::sys;
+
+mod bloom;
+mod cuckoo;
+
+pub use self::bloom::BloomFilter;
+pub use self::cuckoo::{CuckooFilter, CuckooFilterFull};
+
+/// The secret keys of the hash functions of a filter.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct FilterKeys {
+    k0: u64,
+    k1: u64,
+}
+
+impl FilterKeys {
+    /// Draws fresh keys from the enclave RNG.
+    pub fn random() -> FilterKeys {
+        let (k0, k1) = sys::hashmap_random_keys();
+        FilterKeys { k0, k1 }
+    }
+
+    /// Creates keys from their raw parts, as returned by [`to_parts`].
+    ///
+    /// [`to_parts`]: FilterKeys::to_parts
+    pub const fn from_parts(k0: u64, k1: u64) -> FilterKeys {
+        FilterKeys { k0, k1 }
+    }
+
+    /// Returns the raw parts of the keys.
+    pub const fn to_parts(&self) -> (u64, u64) {
+        (self.k0, self.k1)
+    }
+
+    #[allow(deprecated)]
+    fn hash<T: Hash + ?Sized>(&self, item: &T) -> u64 {
+        let mut hasher = SipHasher13::new_with_keys(self.k0, self.k1);
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // A second, independent hash of an already hashed value.
+    #[allow(deprecated)]
+    fn rehash(&self, value: u64) -> u64 {
+        let mut hasher = SipHasher13::new_with_keys(self.k1, self.k0);
+        hasher.write_u64(value);
+        hasher.finish()
+    }
+}
+
+impl crate::fmt::Debug for FilterKeys {
+    fn fmt(&self, f: &mut crate::fmt::Formatter<'_>) -> crate::fmt::Result {
+        f.write_str("FilterKeys { .. }")
+    }
+}
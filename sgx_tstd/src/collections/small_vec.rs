@@ -0,0 +1,629 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! A vector that stores a few elements inline before spilling to the heap.
+//!
+//! [`SmallVec`] behaves like [`Vec`] but keeps up to `N` elements inside the
+//! value itself, allocating only when it outgrows them. Most short-lived
+//! vectors on request paths never allocate. The API follows the `smallvec`
+//! crate, where the inline capacity is given as an array type such as
+//! `SmallVec<[u8; 16]>`, so ported code maps directly.
+
+use crate::borrow::{Borrow, BorrowMut};
+use crate::cmp::Ordering;
+use crate::fmt;
+use crate::hash::{Hash, Hasher};
+use crate::mem::{self, MaybeUninit};
+use crate::ops::{Deref, DerefMut};
+use crate::ptr;
+use crate::slice;
+use crate::vec::Vec;
+
+/// Array types that can back the inline storage of a [`SmallVec`].
+///
+/// # Safety
+///
+/// `size` must return the number of `Item`s in the array.
+pub unsafe trait Array {
+    /// The element type.
+    type Item;
+
+    /// Returns the number of elements in the array.
+    fn size() -> usize;
+}
+
+unsafe impl<T, const N: usize> Array for [T; N] {
+    type Item = T;
+
+    #[inline]
+    fn size() -> usize {
+        N
+    }
+}
+
+enum Data<A: Array> {
+    Inline(MaybeUninit<A>),
+    Heap(Vec<A::Item>),
+}
+
+/// A vector that holds up to `A::size()` elements without allocating.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::small_vec::SmallVec;
+///
+/// let mut v: SmallVec<[u32; 4]> = SmallVec::new();
+/// v.extend(0..4);
+/// assert!(!v.spilled());
+///
+/// v.push(4);
+/// assert!(v.spilled());
+/// assert_eq!(&v[..], &[0, 1, 2, 3, 4]);
+/// ```
+pub struct SmallVec<A: Array> {
+    // Number of initialized inline elements; unused once spilled.
+    len: usize,
+    data: Data<A>,
+}
+
+impl<A: Array> SmallVec<A> {
+    /// Creates an empty vector.
+    #[inline]
+    pub fn new() -> SmallVec<A> {
+        SmallVec { len: 0, data: Data::Inline(MaybeUninit::uninit()) }
+    }
+
+    /// Creates an empty vector with room for at least `capacity` elements.
+    pub fn with_capacity(capacity: usize) -> SmallVec<A> {
+        let mut v = SmallVec::new();
+        v.reserve(capacity);
+        v
+    }
+
+    /// Creates a vector that takes over the heap buffer of `vec`.
+    pub fn from_vec(vec: Vec<A::Item>) -> SmallVec<A> {
+        SmallVec { len: 0, data: Data::Heap(vec) }
+    }
+
+    /// Creates a vector holding every element of `buf` inline.
+    pub fn from_buf(buf: A) -> SmallVec<A> {
+        SmallVec { len: A::size(), data: Data::Inline(MaybeUninit::new(buf)) }
+    }
+
+    /// Returns the number of elements that fit inline.
+    #[inline]
+    pub fn inline_size(&self) -> usize {
+        A::size()
+    }
+
+    /// Returns the number of elements.
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self.data {
+            Data::Inline(_) => self.len,
+            Data::Heap(ref v) => v.len(),
+        }
+    }
+
+    /// Returns `true` if the vector has no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of elements the vector can hold without
+    /// allocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        match self.data {
+            Data::Inline(_) => A::size(),
+            Data::Heap(ref v) => v.capacity(),
+        }
+    }
+
+    /// Returns `true` if the elements have moved to the heap.
+    #[inline]
+    pub fn spilled(&self) -> bool {
+        matches!(self.data, Data::Heap(_))
+    }
+
+    /// Appends an element.
+    pub fn push(&mut self, element: A::Item) {
+        if let Data::Inline(ref mut buf) = self.data {
+            if self.len < A::size() {
+                unsafe { ptr::write((buf.as_mut_ptr() as *mut A::Item).add(self.len), element) };
+                self.len += 1;
+                return;
+            }
+        }
+        self.reserve(1);
+        self.heap_mut().push(element);
+    }
+
+    /// Removes the last element and returns it.
+    pub fn pop(&mut self) -> Option<A::Item> {
+        match self.data {
+            Data::Inline(ref buf) => {
+                if self.len == 0 {
+                    return None;
+                }
+                self.len -= 1;
+                Some(unsafe { ptr::read((buf.as_ptr() as *const A::Item).add(self.len)) })
+            }
+            Data::Heap(ref mut v) => v.pop(),
+        }
+    }
+
+    /// Inserts an element at `index`, shifting later elements up.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, element: A::Item) {
+        let len = self.len();
+        assert!(index <= len, "insertion index (is {}) should be <= len (is {})", index, len);
+        if let Data::Inline(ref mut buf) = self.data {
+            if len < A::size() {
+                unsafe {
+                    let p = (buf.as_mut_ptr() as *mut A::Item).add(index);
+                    ptr::copy(p, p.add(1), len - index);
+                    ptr::write(p, element);
+                }
+                self.len += 1;
+                return;
+            }
+        }
+        self.reserve(1);
+        self.heap_mut().insert(index, element);
+    }
+
+    /// Removes and returns the element at `index`, shifting later elements
+    /// down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> A::Item {
+        match self.data {
+            Data::Inline(ref mut buf) => {
+                let len = self.len;
+                assert!(index < len, "removal index (is {}) should be < len (is {})", index, len);
+                unsafe {
+                    let p = (buf.as_mut_ptr() as *mut A::Item).add(index);
+                    let element = ptr::read(p);
+                    ptr::copy(p.add(1), p, len - index - 1);
+                    self.len -= 1;
+                    element
+                }
+            }
+            Data::Heap(ref mut v) => v.remove(index),
+        }
+    }
+
+    /// Removes the element at `index` and replaces it with the last element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    pub fn swap_remove(&mut self, index: usize) -> A::Item {
+        let len = self.len();
+        assert!(index < len, "swap_remove index (is {}) should be < len (is {})", index, len);
+        self.swap(index, len - 1);
+        self.pop().unwrap()
+    }
+
+    /// Shortens the vector to `len` elements, dropping the rest.
+    pub fn truncate(&mut self, len: usize) {
+        match self.data {
+            Data::Inline(ref mut buf) => {
+                if len >= self.len {
+                    return;
+                }
+                let tail = self.len - len;
+                self.len = len;
+                unsafe {
+                    let p = (buf.as_mut_ptr() as *mut A::Item).add(len);
+                    ptr::drop_in_place(slice::from_raw_parts_mut(p, tail));
+                }
+            }
+            Data::Heap(ref mut v) => v.truncate(len),
+        }
+    }
+
+    /// Removes every element.
+    pub fn clear(&mut self) {
+        self.truncate(0)
+    }
+
+    /// Keeps only the elements for which `f` returns `true`.
+    pub fn retain<F: FnMut(&mut A::Item) -> bool>(&mut self, mut f: F) {
+        let len = self.len();
+        let mut deleted = 0;
+        for i in 0..len {
+            if !f(&mut self[i]) {
+                deleted += 1;
+            } else if deleted > 0 {
+                self.swap(i - deleted, i);
+            }
+        }
+        self.truncate(len - deleted);
+    }
+
+    /// Reserves room for at least `additional` more elements, moving to the
+    /// heap if they do not fit inline.
+    pub fn reserve(&mut self, additional: usize) {
+        let len = self.len();
+        match self.data {
+            Data::Inline(_) => {
+                let needed = len.checked_add(additional).expect("capacity overflow");
+                if needed > A::size() {
+                    self.spill(needed.max(A::size() * 2));
+                }
+            }
+            Data::Heap(ref mut v) => v.reserve(additional),
+        }
+    }
+
+    /// Moves the elements back inline if they fit, or shrinks the heap
+    /// buffer otherwise.
+    pub fn shrink_to_fit(&mut self) {
+        let inline = match self.data {
+            Data::Heap(ref mut v) if v.len() <= A::size() => mem::take(v),
+            Data::Heap(ref mut v) => {
+                v.shrink_to_fit();
+                return;
+            }
+            Data::Inline(_) => return,
+        };
+        let mut buf = MaybeUninit::<A>::uninit();
+        let len = inline.len();
+        let mut inline = mem::ManuallyDrop::new(inline);
+        unsafe {
+            ptr::copy_nonoverlapping(inline.as_ptr(), buf.as_mut_ptr() as *mut A::Item, len);
+            inline.set_len(0);
+            mem::ManuallyDrop::drop(&mut inline);
+        }
+        self.data = Data::Inline(buf);
+        self.len = len;
+    }
+
+    /// Converts the vector into a `Vec`, allocating if it was inline.
+    pub fn into_vec(mut self) -> Vec<A::Item> {
+        if !self.spilled() {
+            self.spill(self.len);
+        }
+        mem::take(self.heap_mut())
+    }
+
+    /// Converts the vector into its array if it is inline and full, or
+    /// returns it otherwise.
+    pub fn into_inner(self) -> Result<A, SmallVec<A>> {
+        if self.spilled() || self.len != A::size() {
+            return Err(self);
+        }
+        let mut this = mem::ManuallyDrop::new(self);
+        match mem::replace(&mut this.data, Data::Heap(Vec::new())) {
+            Data::Inline(buf) => Ok(unsafe { buf.assume_init() }),
+            Data::Heap(_) => unreachable!(),
+        }
+    }
+
+    /// Returns the elements as a slice.
+    pub fn as_slice(&self) -> &[A::Item] {
+        match self.data {
+            Data::Inline(ref buf) => unsafe { slice::from_raw_parts(buf.as_ptr() as *const A::Item, self.len) },
+            Data::Heap(ref v) => v,
+        }
+    }
+
+    /// Returns the elements as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [A::Item] {
+        match self.data {
+            Data::Inline(ref mut buf) => unsafe {
+                slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut A::Item, self.len)
+            },
+            Data::Heap(ref mut v) => v,
+        }
+    }
+
+    /// Copies the elements of `other` to the end of the vector.
+    pub fn extend_from_slice(&mut self, other: &[A::Item])
+    where
+        A::Item: Clone,
+    {
+        self.reserve(other.len());
+        self.extend(other.iter().cloned());
+    }
+
+    /// Resizes the vector to `len` elements, filling with clones of `value`.
+    pub fn resize(&mut self, len: usize, value: A::Item)
+    where
+        A::Item: Clone,
+    {
+        let current = self.len();
+        if len > current {
+            self.reserve(len - current);
+            for _ in current..len {
+                self.push(value.clone());
+            }
+        } else {
+            self.truncate(len);
+        }
+    }
+
+    /// Creates a vector holding clones of the elements of `slice`.
+    pub fn from_slice(slice: &[A::Item]) -> SmallVec<A>
+    where
+        A::Item: Clone,
+    {
+        let mut v = SmallVec::with_capacity(slice.len());
+        v.extend_from_slice(slice);
+        v
+    }
+
+    // Moves the inline elements into a heap buffer of at least `capacity`.
+    fn spill(&mut self, capacity: usize) {
+        if let Data::Inline(ref buf) = self.data {
+            let mut vec = Vec::with_capacity(capacity.max(self.len));
+            unsafe {
+                ptr::copy_nonoverlapping(buf.as_ptr() as *const A::Item, vec.as_mut_ptr(), self.len);
+                vec.set_len(self.len);
+            }
+            self.len = 0;
+            self.data = Data::Heap(vec);
+        }
+    }
+
+    fn heap_mut(&mut self) -> &mut Vec<A::Item> {
+        match self.data {
+            Data::Heap(ref mut v) => v,
+            Data::Inline(_) => unreachable!("SmallVec is not spilled"),
+        }
+    }
+
+    // Returns the start of the buffer regardless of the current length.
+    fn buf_ptr(&self) -> *const A::Item {
+        match self.data {
+            Data::Inline(ref buf) => buf.as_ptr() as *const A::Item,
+            Data::Heap(ref v) => v.as_ptr(),
+        }
+    }
+
+    unsafe fn set_len(&mut self, len: usize) {
+        match self.data {
+            Data::Inline(_) => self.len = len,
+            Data::Heap(ref mut v) => v.set_len(len),
+        }
+    }
+}
+
+impl<A: Array> Drop for SmallVec<A> {
+    fn drop(&mut self) {
+        if let Data::Inline(_) = self.data {
+            unsafe { ptr::drop_in_place(self.as_mut_slice()) };
+        }
+    }
+}
+
+impl<A: Array> Deref for SmallVec<A> {
+    type Target = [A::Item];
+
+    #[inline]
+    fn deref(&self) -> &[A::Item] {
+        self.as_slice()
+    }
+}
+
+impl<A: Array> DerefMut for SmallVec<A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [A::Item] {
+        self.as_mut_slice()
+    }
+}
+
+impl<A: Array> AsRef<[A::Item]> for SmallVec<A> {
+    fn as_ref(&self) -> &[A::Item] {
+        self
+    }
+}
+
+impl<A: Array> AsMut<[A::Item]> for SmallVec<A> {
+    fn as_mut(&mut self) -> &mut [A::Item] {
+        self
+    }
+}
+
+impl<A: Array> Borrow<[A::Item]> for SmallVec<A> {
+    fn borrow(&self) -> &[A::Item] {
+        self
+    }
+}
+
+impl<A: Array> BorrowMut<[A::Item]> for SmallVec<A> {
+    fn borrow_mut(&mut self) -> &mut [A::Item] {
+        self
+    }
+}
+
+impl<A: Array> Default for SmallVec<A> {
+    fn default() -> SmallVec<A> {
+        SmallVec::new()
+    }
+}
+
+impl<A: Array> Clone for SmallVec<A>
+where
+    A::Item: Clone,
+{
+    fn clone(&self) -> SmallVec<A> {
+        SmallVec::from_slice(self)
+    }
+}
+
+impl<A: Array> fmt::Debug for SmallVec<A>
+where
+    A::Item: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<A: Array, B: Array> PartialEq<SmallVec<B>> for SmallVec<A>
+where
+    A::Item: PartialEq<B::Item>,
+{
+    fn eq(&self, other: &SmallVec<B>) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl<A: Array> Eq for SmallVec<A> where A::Item: Eq {}
+
+impl<A: Array> PartialOrd for SmallVec<A>
+where
+    A::Item: PartialOrd,
+{
+    fn partial_cmp(&self, other: &SmallVec<A>) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<A: Array> Ord for SmallVec<A>
+where
+    A::Item: Ord,
+{
+    fn cmp(&self, other: &SmallVec<A>) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<A: Array> Hash for SmallVec<A>
+where
+    A::Item: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<A: Array> From<Vec<A::Item>> for SmallVec<A> {
+    fn from(vec: Vec<A::Item>) -> SmallVec<A> {
+        SmallVec::from_vec(vec)
+    }
+}
+
+impl<A: Array> From<&[A::Item]> for SmallVec<A>
+where
+    A::Item: Clone,
+{
+    fn from(slice: &[A::Item]) -> SmallVec<A> {
+        SmallVec::from_slice(slice)
+    }
+}
+
+impl<A: Array> Extend<A::Item> for SmallVec<A> {
+    fn extend<I: IntoIterator<Item = A::Item>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for element in iter {
+            self.push(element);
+        }
+    }
+}
+
+impl<A: Array> FromIterator<A::Item> for SmallVec<A> {
+    fn from_iter<I: IntoIterator<Item = A::Item>>(iter: I) -> SmallVec<A> {
+        let mut v = SmallVec::new();
+        v.extend(iter);
+        v
+    }
+}
+
+impl<'a, A: Array> IntoIterator for &'a SmallVec<A> {
+    type Item = &'a A::Item;
+    type IntoIter = slice::Iter<'a, A::Item>;
+
+    fn into_iter(self) -> slice::Iter<'a, A::Item> {
+        self.iter()
+    }
+}
+
+impl<'a, A: Array> IntoIterator for &'a mut SmallVec<A> {
+    type Item = &'a mut A::Item;
+    type IntoIter = slice::IterMut<'a, A::Item>;
+
+    fn into_iter(self) -> slice::IterMut<'a, A::Item> {
+        self.iter_mut()
+    }
+}
+
+impl<A: Array> IntoIterator for SmallVec<A> {
+    type Item = A::Item;
+    type IntoIter = IntoIter<A>;
+
+    fn into_iter(mut self) -> IntoIter<A> {
+        let end = self.len();
+        // The iterator owns the elements from here on.
+        unsafe { self.set_len(0) };
+        IntoIter { data: self, index: 0, end }
+    }
+}
+
+/// An owning iterator over the elements of a [`SmallVec`].
+pub struct IntoIter<A: Array> {
+    data: SmallVec<A>,
+    index: usize,
+    end: usize,
+}
+
+impl<A: Array> Iterator for IntoIter<A> {
+    type Item = A::Item;
+
+    fn next(&mut self) -> Option<A::Item> {
+        if self.index == self.end {
+            return None;
+        }
+        let element = unsafe { ptr::read(self.data.buf_ptr().add(self.index)) };
+        self.index += 1;
+        Some(element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.index;
+        (len, Some(len))
+    }
+}
+
+impl<A: Array> DoubleEndedIterator for IntoIter<A> {
+    fn next_back(&mut self) -> Option<A::Item> {
+        if self.index == self.end {
+            return None;
+        }
+        self.end -= 1;
+        Some(unsafe { ptr::read(self.data.buf_ptr().add(self.end)) })
+    }
+}
+
+impl<A: Array> ExactSizeIterator for IntoIter<A> {}
+
+impl<A: Array> Drop for IntoIter<A> {
+    fn drop(&mut self) {
+        for _ in self.by_ref() {}
+    }
+}
@@ -0,0 +1,481 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Periodic jobs running inside the enclave.
+//!
+//! A [`Scheduler`] keeps a set of jobs, each with a fixed interval, and runs
+//! the due ones whenever it is ticked. Ticks come from the host: either an
+//! ECALL that calls [`Scheduler::tick`] on a host timer, or the driver thread
+//! started by [`Scheduler::spawn`], which waits for the next due time with a
+//! timed `u_thread_wait_event_ocall`.
+//!
+//! The ticks decide *when* the enclave looks at its jobs; whether a job is
+//! due is decided against an [`ElapsedClock`]. Every run reports how many
+//! intervals were skipped since the previous one.
+//!
+//! SGX offers no trusted wall clock since the platform services were removed
+//! in SDK 2.8. [`MonotonicClock`], the default, reads the host monotonic
+//! clock through its own OCALL, so with it the host controls both the ticks
+//! and the clock: it can make jobs run early, late or not at all, and hide
+//! the missed intervals. Only once a trusted time source, such as an attested
+//! time service, is installed with [`Scheduler::with_clock`] does a host that
+//! ticks early or too often fail to make jobs run ahead of schedule, and a
+//! host that withholds ticks get detected through the missed intervals.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::cron::Scheduler;
+//! use std::time::Duration;
+//!
+//! let scheduler = Scheduler::new();
+//! scheduler.register("rotate-keys", Duration::from_secs(3600), |run| {
+//!     if run.missed > 0 {
+//!         // the host withheld ticks for `run.missed` intervals
+//!     }
+//! }).unwrap();
+//!
+//! // from the ECALL invoked by the host timer:
+//! let report = scheduler.tick().unwrap();
+//! ```
+
+use crate::boxed::Box;
+use crate::collections::pairing_heap::{DeadlineQueue, Handle};
+use crate::fmt;
+use crate::io;
+use crate::panic::{self, AssertUnwindSafe};
+use crate::sync::atomic::{AtomicU64, Ordering};
+use crate::sync::{Arc, SgxMutex};
+use crate::time::{Duration, Instant};
+#[cfg(feature = "thread")]
+use crate::sync::SgxCondvar;
+#[cfg(feature = "thread")]
+use crate::thread::{self, JoinHandle};
+
+/// A source of elapsed time used to decide when jobs are due.
+pub trait ElapsedClock: Send + Sync {
+    /// Returns the time elapsed since an arbitrary but fixed origin.
+    ///
+    /// Successive calls must never go backwards.
+    fn elapsed(&self) -> io::Result<Duration>;
+}
+
+/// An [`ElapsedClock`] backed by the host monotonic clock.
+///
+/// The host can set this clock to any value that does not go backwards, so
+/// it gives no guarantee against the host that also delivers the ticks.
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicClock {
+    origin: Instant,
+}
+
+impl MonotonicClock {
+    /// Creates a clock whose origin is now.
+    pub fn new() -> MonotonicClock {
+        MonotonicClock { origin: Instant::_now() }
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> MonotonicClock {
+        MonotonicClock::new()
+    }
+}
+
+impl ElapsedClock for MonotonicClock {
+    fn elapsed(&self) -> io::Result<Duration> {
+        Ok(Instant::_now().saturating_duration_since(self.origin))
+    }
+}
+
+/// An identifier of a registered job.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JobId(u64);
+
+impl JobId {
+    /// Returns the numeric value of the ID.
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Information passed to a job each time it runs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct JobRun {
+    /// The job being run.
+    pub id: JobId,
+    /// The clock reading at which this run was due.
+    pub scheduled: Duration,
+    /// The clock reading at which this run started.
+    pub now: Duration,
+    /// Number of whole intervals that passed without a run since the
+    /// previous one.
+    pub missed: u64,
+}
+
+/// Counters of a registered job.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct JobStats {
+    /// Number of completed runs.
+    pub runs: u64,
+    /// Number of runs that panicked.
+    pub panics: u64,
+    /// Total number of missed intervals.
+    pub missed: u64,
+    /// Clock reading at the start of the latest run.
+    pub last_run: Option<Duration>,
+}
+
+/// The outcome of one [`Scheduler::tick`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct TickReport {
+    /// The clock reading the tick was evaluated at.
+    pub now: Duration,
+    /// Number of jobs that ran, including those that panicked.
+    pub ran: usize,
+    /// Number of jobs that panicked.
+    pub panicked: usize,
+    /// Number of missed intervals detected across all jobs.
+    pub missed: u64,
+    /// Time until the next job is due, if any job is registered.
+    pub next_due: Option<Duration>,
+}
+
+type Job = Box<dyn FnMut(&JobRun) + Send>;
+
+struct Entry {
+    id: JobId,
+    name: String,
+    interval: Duration,
+    next_due: Duration,
+    timer: Handle,
+    // `None` while the job is running outside the lock.
+    job: Option<Job>,
+    stats: JobStats,
+}
+
+#[derive(Default)]
+struct Jobs {
+    entries: Vec<Entry>,
+    // Due times of the entries that are not running.
+    queue: DeadlineQueue<JobId, Duration>,
+}
+
+struct Inner {
+    clock: Box<dyn ElapsedClock>,
+    jobs: SgxMutex<Jobs>,
+    last_now: SgxMutex<Duration>,
+    next_id: AtomicU64,
+    #[cfg(feature = "thread")]
+    driver: SgxMutex<DriverState>,
+    #[cfg(feature = "thread")]
+    wakeup: SgxCondvar,
+}
+
+#[cfg(feature = "thread")]
+#[derive(Default)]
+struct DriverState {
+    stop: bool,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// A set of periodic jobs.
+///
+/// Cloning yields another handle to the same set of jobs.
+#[derive(Clone)]
+pub struct Scheduler {
+    inner: Arc<Inner>,
+}
+
+impl Scheduler {
+    /// Creates a scheduler using [`MonotonicClock`].
+    pub fn new() -> Scheduler {
+        Scheduler::with_clock(MonotonicClock::new())
+    }
+
+    /// Creates a scheduler that decides due times with `clock`.
+    pub fn with_clock<C: ElapsedClock + 'static>(clock: C) -> Scheduler {
+        Scheduler {
+            inner: Arc::new(Inner {
+                clock: Box::new(clock),
+                jobs: SgxMutex::new(Jobs::default()),
+                last_now: SgxMutex::new(Duration::ZERO),
+                next_id: AtomicU64::new(1),
+                #[cfg(feature = "thread")]
+                driver: SgxMutex::new(DriverState::default()),
+                #[cfg(feature = "thread")]
+                wakeup: SgxCondvar::new(),
+            }),
+        }
+    }
+
+    /// Registers `job` to run every `interval`, first one interval from now.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidInput` for a zero interval, or the error of the clock.
+    pub fn register<F>(&self, name: &str, interval: Duration, job: F) -> io::Result<JobId>
+    where
+        F: FnMut(&JobRun) + Send + 'static,
+    {
+        if interval.is_zero() {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "job interval must be non-zero",
+            ));
+        }
+        let now = self.now()?;
+        let id = JobId(self.inner.next_id.fetch_add(1, Ordering::Relaxed));
+        let next_due = now.saturating_add(interval);
+        let mut jobs = self.lock_jobs();
+        let timer = jobs.queue.schedule(next_due, id);
+        jobs.entries.push(Entry {
+            id,
+            name: name.to_owned(),
+            interval,
+            next_due,
+            timer,
+            job: Some(Box::new(job)),
+            stats: JobStats::default(),
+        });
+        drop(jobs);
+        self.notify_driver();
+        Ok(id)
+    }
+
+    /// Removes a job. Returns `false` if no such job is registered.
+    ///
+    /// A run of the job that is already in progress completes.
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut jobs = self.lock_jobs();
+        match jobs.entries.iter().position(|e| e.id == id) {
+            Some(pos) => {
+                let entry = jobs.entries.swap_remove(pos);
+                jobs.queue.cancel(entry.timer);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the counters of a job.
+    pub fn stats(&self, id: JobId) -> Option<JobStats> {
+        self.lock_jobs().entries.iter().find(|e| e.id == id).map(|e| e.stats)
+    }
+
+    /// Returns the name of a job.
+    pub fn name(&self, id: JobId) -> Option<String> {
+        self.lock_jobs().entries.iter().find(|e| e.id == id).map(|e| e.name.clone())
+    }
+
+    /// Runs every job that is due according to the clock.
+    ///
+    /// Jobs run on the calling thread, outside of the scheduler's lock, so a
+    /// job may register or cancel jobs.
+    ///
+    /// A job that panics does not unwind through the scheduler: the panic is
+    /// caught, counted in [`JobStats::panics`] and [`TickReport::panicked`],
+    /// and the job stays registered and runs again at its next due time. The
+    /// other due jobs of the tick still run.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the clock, or `InvalidData` if the clock went
+    /// backwards. No job runs in either case.
+    pub fn tick(&self) -> io::Result<TickReport> {
+        let now = self.now()?;
+        let mut report = TickReport { now, ..TickReport::default() };
+
+        let mut due = Vec::new();
+        {
+            let mut jobs = self.lock_jobs();
+            let Jobs { entries, queue } = &mut *jobs;
+            // Jobs still running from a concurrent tick stay due.
+            let mut running = Vec::new();
+            while let Some((_, id)) = queue.pop_expired(now) {
+                let entry = match entries.iter_mut().find(|e| e.id == id) {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+                let job = match entry.job.take() {
+                    Some(job) => job,
+                    None => {
+                        running.push(id);
+                        continue;
+                    }
+                };
+                let late = now - entry.next_due;
+                let missed = duration_div(late, entry.interval);
+                let run = JobRun { id: entry.id, scheduled: entry.next_due, now, missed };
+
+                // Keep the original phase, skipping the missed intervals.
+                let skip = entry.interval.saturating_mul((missed + 1).min(u32::MAX as u64) as u32);
+                entry.next_due = entry.next_due.saturating_add(skip);
+                entry.timer = queue.schedule(entry.next_due, id);
+                entry.stats.missed += missed;
+                entry.stats.last_run = Some(now);
+                report.missed += missed;
+                due.push((run, job));
+            }
+            for id in running {
+                if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+                    entry.timer = queue.schedule(entry.next_due, id);
+                }
+            }
+        }
+
+        let mut finished = Vec::with_capacity(due.len());
+        for (run, mut job) in due {
+            let ok = panic::catch_unwind(AssertUnwindSafe(|| job(&run))).is_ok();
+            finished.push((run.id, job, ok));
+        }
+        report.ran = finished.len();
+
+        let mut jobs = self.lock_jobs();
+        for (id, job, ok) in finished {
+            if !ok {
+                report.panicked += 1;
+            }
+            if let Some(entry) = jobs.entries.iter_mut().find(|e| e.id == id) {
+                entry.job = Some(job);
+                if ok {
+                    entry.stats.runs += 1;
+                } else {
+                    entry.stats.panics += 1;
+                }
+            }
+        }
+        report.next_due = jobs.queue.next_deadline().map(|d| d.saturating_sub(now));
+        Ok(report)
+    }
+
+    fn now(&self) -> io::Result<Duration> {
+        let now = self.inner.clock.elapsed()?;
+        let mut last = self.inner.last_now.lock().unwrap_or_else(|e| e.into_inner());
+        if now < *last {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidData,
+                "scheduler clock went backwards",
+            ));
+        }
+        *last = now;
+        Ok(now)
+    }
+
+    fn lock_jobs(&self) -> crate::sync::SgxMutexGuard<'_, Jobs> {
+        self.inner.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[cfg(feature = "thread")]
+    fn notify_driver(&self) {
+        self.inner.wakeup.notify_all();
+    }
+
+    #[cfg(not(feature = "thread"))]
+    fn notify_driver(&self) {}
+}
+
+#[cfg(feature = "thread")]
+impl Scheduler {
+    /// Starts a driver thread that ticks the scheduler whenever a job is due.
+    ///
+    /// The thread sleeps at most `max_sleep` between ticks, which bounds how
+    /// long a missed wake-up from the host goes unnoticed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AlreadyExists` if a driver is running, or the error of the
+    /// thread spawn.
+    pub fn spawn(&self, max_sleep: Duration) -> io::Result<()> {
+        let mut driver = self.inner.driver.lock().unwrap_or_else(|e| e.into_inner());
+        if driver.handle.is_some() {
+            return Err(io::const_io_error!(
+                io::ErrorKind::AlreadyExists,
+                "scheduler driver is already running",
+            ));
+        }
+        driver.stop = false;
+
+        let scheduler = self.clone();
+        let handle = thread::Builder::new()
+            .name("cron".to_owned())
+            .spawn(move || scheduler.drive(max_sleep))?;
+        driver.handle = Some(handle);
+        Ok(())
+    }
+
+    /// Stops the driver thread started by [`Scheduler::spawn`] and waits for it.
+    pub fn shutdown(&self) {
+        let handle = {
+            let mut driver = self.inner.driver.lock().unwrap_or_else(|e| e.into_inner());
+            driver.stop = true;
+            driver.handle.take()
+        };
+        self.inner.wakeup.notify_all();
+        if let Some(handle) = handle {
+            let _ = handle.join();
+        }
+    }
+
+    fn drive(&self, max_sleep: Duration) {
+        let mut sleep = Duration::ZERO;
+        loop {
+            let driver = self.inner.driver.lock().unwrap_or_else(|e| e.into_inner());
+            let driver = if driver.stop || sleep.is_zero() {
+                driver
+            } else {
+                match self.inner.wakeup.wait_timeout(driver, sleep) {
+                    Ok((driver, _)) => driver,
+                    Err(e) => e.into_inner().0,
+                }
+            };
+            if driver.stop {
+                return;
+            }
+            drop(driver);
+
+            sleep = match self.tick() {
+                Ok(report) => report.next_due.map_or(max_sleep, |d| d.min(max_sleep)),
+                Err(_) => max_sleep,
+            };
+            if sleep.is_zero() {
+                sleep = Duration::from_millis(1);
+            }
+        }
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Scheduler {
+        Scheduler::new()
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let jobs = self.lock_jobs();
+        let mut list = f.debug_list();
+        for entry in jobs.entries.iter() {
+            list.entry(&(entry.id, &entry.name, entry.interval, entry.stats));
+        }
+        list.finish()
+    }
+}
+
+fn duration_div(num: Duration, den: Duration) -> u64 {
+    (num.as_nanos() / den.as_nanos()) as u64
+}
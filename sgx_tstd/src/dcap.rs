@@ -0,0 +1,206 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! ECDSA quotes of the enclave, for DCAP attestation.
+//!
+//! [`QuoteGenerator`] has the quoting enclave of the platform sign a report of
+//! this enclave. The quoting enclave is reached through the
+//! `u_dcap_target_info_ocall` and `u_dcap_quote_ocall` OCALLs declared in
+//! `sgx_dcap.edl`, which `sgx_urts` implements with the DCAP quote library when
+//! built with its `dcap` feature. The quote is verified by the relying party,
+//! with `sgx_urts::dcap::QuoteVerifier` or any DCAP verifier.
+//!
+//! The host is not trusted to return the quote of the report it was given: a
+//! quote that does not carry the body of that report is rejected. Whether the
+//! quote is genuine can only be decided by its verifier.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::dcap::QuoteGenerator;
+//!
+//! let mut generator = QuoteGenerator::new()?;
+//! let mut report_data = sgx_report_data_t::default();
+//! report_data.d[..32].copy_from_slice(&public_key_hash);
+//! let quote = generator.generate(&report_data)?;
+//! ```
+
+use crate::io;
+use crate::mem;
+use crate::slice;
+use crate::vec::Vec;
+use sgx_types::*;
+
+// The report body follows the 48-byte quote header in every quote version.
+const QUOTE_HEADER_SIZE: usize = 48;
+const REPORT_BODY_SIZE: usize = mem::size_of::<sgx_report_body_t>();
+const QUOTE_VERSION_3: u16 = 3;
+const ATT_KEY_TYPE_ECDSA_P256: u16 = 2;
+
+/// Produces DCAP quotes of the enclave.
+///
+/// The generator keeps the target information of the quoting enclave between
+/// quotes. If the quoting enclave rejects a report because it was reloaded or
+/// updated since, the target information is fetched again and the report is
+/// recreated once.
+pub struct QuoteGenerator {
+    target_info: sgx_target_info_t,
+    quote_size: u32,
+}
+
+impl QuoteGenerator {
+    /// Fetches the target information of the quoting enclave.
+    pub fn new() -> io::Result<QuoteGenerator> {
+        let (target_info, quote_size) = target_info()?;
+        Ok(QuoteGenerator {
+            target_info,
+            quote_size,
+        })
+    }
+
+    /// Returns the target information of the quoting enclave, to which the
+    /// reports of the generator are targeted.
+    pub fn qe_target_info(&self) -> &sgx_target_info_t {
+        &self.target_info
+    }
+
+    /// Returns the size of the quotes of the platform, as the quote library
+    /// reported it.
+    pub fn quote_size(&self) -> usize {
+        self.quote_size as usize
+    }
+
+    /// Returns a quote of the enclave that carries `report_data`.
+    ///
+    /// # Errors
+    ///
+    /// Errors of the quote library are of kind `Other` and carry the name of
+    /// their `sgx_quote3_error_t`. An error of kind `InvalidData` means that
+    /// the host returned something other than a quote of the report.
+    pub fn generate(&mut self, report_data: &sgx_report_data_t) -> io::Result<Vec<u8>> {
+        match self.try_generate(report_data) {
+            Err(QuoteError::Library(
+                sgx_quote3_error_t::SGX_QL_INVALID_REPORT
+                | sgx_quote3_error_t::SGX_QL_ATT_KEY_NOT_INITIALIZED,
+            )) => {
+                let (target_info, quote_size) = target_info()?;
+                self.target_info = target_info;
+                self.quote_size = quote_size;
+                self.try_generate(report_data).map_err(QuoteError::into_io)
+            }
+            result => result.map_err(QuoteError::into_io),
+        }
+    }
+
+    fn try_generate(&self, report_data: &sgx_report_data_t) -> Result<Vec<u8>, QuoteError> {
+        let mut report = sgx_report_t::default();
+        let status = unsafe { sgx_create_report(&self.target_info, report_data, &mut report) };
+        if status != sgx_status_t::SGX_SUCCESS {
+            return Err(QuoteError::Io(io::Error::from_sgx_error(status)));
+        }
+
+        let mut quote = vec![0_u8; self.quote_size as usize];
+        let mut result = 0_u32;
+        let mut size = 0_u32;
+        let status = unsafe {
+            libc::u_dcap_quote_ocall(
+                &mut result,
+                &report,
+                quote.as_mut_ptr(),
+                self.quote_size,
+                &mut size,
+            )
+        };
+        check(status, result)?;
+        if size > self.quote_size {
+            return Err(QuoteError::Io(invalid_quote()));
+        }
+        quote.truncate(size as usize);
+        check_quote(&quote, &report.body)?;
+        Ok(quote)
+    }
+}
+
+enum QuoteError {
+    Io(io::Error),
+    Library(sgx_quote3_error_t),
+}
+
+impl QuoteError {
+    fn into_io(self) -> io::Error {
+        match self {
+            QuoteError::Io(e) => e,
+            QuoteError::Library(code) => io::Error::new(io::ErrorKind::Other, code.as_str()),
+        }
+    }
+}
+
+fn check(status: sgx_status_t, result: u32) -> Result<(), QuoteError> {
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(QuoteError::Io(io::Error::from_sgx_error(status)));
+    }
+    if result != sgx_quote3_error_t::SGX_QL_SUCCESS as u32 {
+        let code = sgx_quote3_error_t::from_repr(result)
+            .unwrap_or(sgx_quote3_error_t::SGX_QL_ERROR_UNEXPECTED);
+        return Err(QuoteError::Library(code));
+    }
+    Ok(())
+}
+
+fn target_info() -> io::Result<(sgx_target_info_t, u32)> {
+    let mut target_info = sgx_target_info_t::default();
+    let mut quote_size = 0_u32;
+    let mut result = 0_u32;
+    let status =
+        unsafe { libc::u_dcap_target_info_ocall(&mut result, &mut target_info, &mut quote_size) };
+    check(status, result).map_err(QuoteError::into_io)?;
+    if (quote_size as usize) < QUOTE_HEADER_SIZE + REPORT_BODY_SIZE {
+        return Err(invalid_quote());
+    }
+    Ok((target_info, quote_size))
+}
+
+fn invalid_quote() -> io::Error {
+    io::const_io_error!(
+        io::ErrorKind::InvalidData,
+        "the host returned an invalid quote"
+    )
+}
+
+// Checks that `quote` is an ECDSA quote of the report whose body is `body`.
+fn check_quote(quote: &[u8], body: &sgx_report_body_t) -> Result<(), QuoteError> {
+    if quote.len() < QUOTE_HEADER_SIZE + REPORT_BODY_SIZE
+        || u16::from_le_bytes([quote[0], quote[1]]) != QUOTE_VERSION_3
+        || u16::from_le_bytes([quote[2], quote[3]]) != ATT_KEY_TYPE_ECDSA_P256
+    {
+        return Err(QuoteError::Io(invalid_quote()));
+    }
+    let body = unsafe {
+        slice::from_raw_parts(
+            body as *const sgx_report_body_t as *const u8,
+            REPORT_BODY_SIZE,
+        )
+    };
+    if &quote[QUOTE_HEADER_SIZE..QUOTE_HEADER_SIZE + REPORT_BODY_SIZE] != body {
+        return Err(QuoteError::Io(invalid_quote()));
+    }
+    Ok(())
+}
+
+mod libc {
+    pub use sgx_libc::ocall::{u_dcap_quote_ocall, u_dcap_target_info_ocall};
+}
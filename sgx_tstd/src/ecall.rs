@@ -0,0 +1,411 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Per-ECALL request context.
+//!
+//! An [`EcallContext`] carries the request ID, an optional deadline, caller
+//! supplied metadata and a [`CancellationToken`] for one unit of work entering
+//! the enclave. The ECALL entry point installs it with [`EcallContext::scope`],
+//! after which any function on the same thread can reach it through
+//! [`current`] or [`with_current`] instead of taking it as a parameter.
+//!
+//! Threads spawned with [`thread::spawn`](crate::thread::spawn) or a
+//! [`thread::Builder`](crate::thread::Builder) inherit the context of the
+//! spawning thread, so work fanned out for a request keeps its request ID and
+//! observes its cancellation.
+//!
+//! Deadlines are measured with [`Instant`], whose source is the untrusted
+//! host clock. They are meant for giving up on work early, not as a security
+//! boundary.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::ecall::{self, EcallContext};
+//! use std::time::Duration;
+//!
+//! let ctx = EcallContext::builder()
+//!     .timeout(Duration::from_secs(5))
+//!     .metadata("caller", "billing")
+//!     .build();
+//!
+//! ctx.scope(|| {
+//!     let id = ecall::current().map(|ctx| ctx.request_id());
+//!     assert!(id.is_some());
+//! });
+//! ```
+
+use crate::cell::RefCell;
+use crate::cmp;
+use crate::fmt;
+use crate::sync::atomic::{AtomicBool, Ordering};
+use crate::sync::Arc;
+use crate::sys::rand;
+use crate::tenant::Tenant;
+use crate::time::{Duration, Instant};
+
+thread_local! {
+    static CURRENT: RefCell<Option<EcallContext>> = RefCell::new(None);
+}
+
+/// An opaque, randomly generated identifier of a request.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RequestId(u128);
+
+impl RequestId {
+    /// Generates a fresh request ID from the enclave RNG.
+    pub fn generate() -> RequestId {
+        let mut bytes = [0_u8; 16];
+        rand::fill_bytes(&mut bytes);
+        RequestId(u128::from_le_bytes(bytes))
+    }
+
+    /// Wraps an ID assigned by the caller, e.g. one propagated from the host.
+    pub const fn from_u128(id: u128) -> RequestId {
+        RequestId(id)
+    }
+
+    /// Returns the numeric value of the ID.
+    pub const fn as_u128(&self) -> u128 {
+        self.0
+    }
+}
+
+impl fmt::Debug for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RequestId({:032x})", self.0)
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+/// A cooperative cancellation flag shared between a request and the work it
+/// started.
+///
+/// Cancelling a token also cancels every token derived from it with
+/// [`child_token`](CancellationToken::child_token); cancelling a child leaves
+/// its parent untouched.
+#[derive(Clone)]
+pub struct CancellationToken {
+    inner: Arc<TokenInner>,
+}
+
+struct TokenInner {
+    cancelled: AtomicBool,
+    parent: Option<CancellationToken>,
+}
+
+impl CancellationToken {
+    /// Creates a token that is not cancelled.
+    pub fn new() -> CancellationToken {
+        CancellationToken {
+            inner: Arc::new(TokenInner { cancelled: AtomicBool::new(false), parent: None }),
+        }
+    }
+
+    /// Creates a token which is cancelled together with `self`.
+    pub fn child_token(&self) -> CancellationToken {
+        CancellationToken {
+            inner: Arc::new(TokenInner {
+                cancelled: AtomicBool::new(false),
+                parent: Some(self.clone()),
+            }),
+        }
+    }
+
+    /// Cancels this token and all of its children.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Release);
+    }
+
+    /// Returns `true` if this token or one of its ancestors was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        let mut token = Some(self);
+        while let Some(t) = token {
+            if t.inner.cancelled.load(Ordering::Acquire) {
+                return true;
+            }
+            token = t.inner.parent.as_ref();
+        }
+        false
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> CancellationToken {
+        CancellationToken::new()
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken").field("is_cancelled", &self.is_cancelled()).finish()
+    }
+}
+
+/// The context of one request served by the enclave.
+///
+/// Cloning is cheap; all clones share the same metadata and token.
+#[derive(Clone)]
+pub struct EcallContext {
+    inner: Arc<ContextInner>,
+}
+
+struct ContextInner {
+    request_id: RequestId,
+    deadline: Option<Instant>,
+    metadata: Vec<(String, String)>,
+    token: CancellationToken,
+    tenant: Option<Tenant>,
+}
+
+impl EcallContext {
+    /// Creates a context with a fresh request ID, no deadline and no metadata.
+    pub fn new() -> EcallContext {
+        EcallContext::builder().build()
+    }
+
+    /// Returns a builder for a context.
+    pub fn builder() -> ContextBuilder {
+        ContextBuilder::new()
+    }
+
+    /// Returns the request ID.
+    pub fn request_id(&self) -> RequestId {
+        self.inner.request_id
+    }
+
+    /// Returns the deadline of the request, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.inner.deadline
+    }
+
+    /// Returns the time left until the deadline, or `None` without a deadline.
+    ///
+    /// Returns a zero duration once the deadline has passed.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.inner.deadline.map(|deadline| deadline.saturating_duration_since(Instant::_now()))
+    }
+
+    /// Returns `true` if the deadline has passed.
+    pub fn is_expired(&self) -> bool {
+        self.remaining() == Some(Duration::ZERO)
+    }
+
+    /// Returns the value of the metadata entry `key`.
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.inner.metadata.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Returns an iterator over all metadata entries, in insertion order.
+    pub fn metadata_iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.inner.metadata.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Returns the tenant the request belongs to, if any.
+    pub fn tenant(&self) -> Option<&Tenant> {
+        self.inner.tenant.as_ref()
+    }
+
+    /// Returns the cancellation token of the request.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.inner.token
+    }
+
+    /// Returns `true` if the request was cancelled or its deadline passed.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.token.is_cancelled() || self.is_expired()
+    }
+
+    /// Cancels the request and everything derived from it.
+    pub fn cancel(&self) {
+        self.inner.token.cancel()
+    }
+
+    /// Returns a builder for a context nested in this one.
+    ///
+    /// The child keeps the request ID, metadata and tenant, gets a child
+    /// cancellation token, and may only tighten the deadline.
+    pub fn child(&self) -> ContextBuilder {
+        ContextBuilder {
+            request_id: Some(self.inner.request_id),
+            deadline: self.inner.deadline,
+            metadata: self.inner.metadata.clone(),
+            token: Some(self.inner.token.child_token()),
+            tenant: self.inner.tenant.clone(),
+            parent_deadline: self.inner.deadline,
+        }
+    }
+
+    /// Runs `f` with this context installed as the current one, restoring the
+    /// previously installed context afterwards, also when `f` panics.
+    pub fn scope<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        struct Reset(Option<EcallContext>);
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                set_current(self.0.take());
+            }
+        }
+
+        let _reset = Reset(set_current(Some(self.clone())));
+        f()
+    }
+}
+
+impl Default for EcallContext {
+    fn default() -> EcallContext {
+        EcallContext::new()
+    }
+}
+
+impl fmt::Debug for EcallContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EcallContext")
+            .field("request_id", &self.inner.request_id)
+            .field("deadline", &self.inner.deadline)
+            .field("metadata", &self.inner.metadata)
+            .field("token", &self.inner.token)
+            .field("tenant", &self.inner.tenant.as_ref().map(Tenant::id))
+            .finish()
+    }
+}
+
+/// Configures a new [`EcallContext`].
+#[derive(Debug)]
+pub struct ContextBuilder {
+    request_id: Option<RequestId>,
+    deadline: Option<Instant>,
+    metadata: Vec<(String, String)>,
+    token: Option<CancellationToken>,
+    tenant: Option<Tenant>,
+    parent_deadline: Option<Instant>,
+}
+
+impl ContextBuilder {
+    /// Creates an empty builder.
+    pub fn new() -> ContextBuilder {
+        ContextBuilder {
+            request_id: None,
+            deadline: None,
+            metadata: Vec::new(),
+            token: None,
+            tenant: None,
+            parent_deadline: None,
+        }
+    }
+
+    /// Uses `id` instead of a freshly generated request ID.
+    pub fn request_id(mut self, id: RequestId) -> ContextBuilder {
+        self.request_id = Some(id);
+        self
+    }
+
+    /// Sets the deadline of the request.
+    pub fn deadline(mut self, deadline: Instant) -> ContextBuilder {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the deadline to `timeout` from now.
+    pub fn timeout(mut self, timeout: Duration) -> ContextBuilder {
+        self.deadline = Instant::_now().checked_add(timeout);
+        self
+    }
+
+    /// Adds a metadata entry, replacing an existing entry with the same key.
+    pub fn metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> ContextBuilder {
+        let key = key.into();
+        let value = value.into();
+        match self.metadata.iter_mut().find(|(k, _)| *k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.metadata.push((key, value)),
+        }
+        self
+    }
+
+    /// Uses `token` as the cancellation token of the request.
+    pub fn cancellation_token(mut self, token: CancellationToken) -> ContextBuilder {
+        self.token = Some(token);
+        self
+    }
+
+    // Makes the request belong to `tenant`. Only `Tenant::scope` calls this,
+    // after it has counted the request against the quota of the tenant and
+    // checked that the current request does not belong to another tenant.
+    pub(crate) fn tenant(mut self, tenant: Tenant) -> ContextBuilder {
+        self.tenant = Some(tenant);
+        self
+    }
+
+    /// Builds the context.
+    pub fn build(self) -> EcallContext {
+        let deadline = match (self.deadline, self.parent_deadline) {
+            (Some(own), Some(parent)) => Some(cmp::min(own, parent)),
+            (own, parent) => own.or(parent),
+        };
+        EcallContext {
+            inner: Arc::new(ContextInner {
+                request_id: self.request_id.unwrap_or_else(RequestId::generate),
+                deadline,
+                metadata: self.metadata,
+                token: self.token.unwrap_or_default(),
+                tenant: self.tenant,
+            }),
+        }
+    }
+}
+
+impl Default for ContextBuilder {
+    fn default() -> ContextBuilder {
+        ContextBuilder::new()
+    }
+}
+
+/// Returns a handle to the context installed on the current thread.
+pub fn current() -> Option<EcallContext> {
+    CURRENT.try_with(|slot| slot.borrow().clone()).ok().flatten()
+}
+
+/// Calls `f` with a reference to the context installed on the current thread.
+pub fn with_current<F, R>(f: F) -> R
+where
+    F: FnOnce(Option<&EcallContext>) -> R,
+{
+    let ctx = current();
+    f(ctx.as_ref())
+}
+
+/// Returns `true` if the current context was cancelled or its deadline passed.
+///
+/// Returns `false` when no context is installed.
+pub fn is_cancelled() -> bool {
+    with_current(|ctx| ctx.map_or(false, EcallContext::is_cancelled))
+}
+
+// Replaces the context of the current thread, returning the previous one.
+// Used by the thread spawning code, the thread pool and the executor to
+// propagate the context of a request to the work done for it.
+pub(crate) fn set_current(ctx: Option<EcallContext>) -> Option<EcallContext> {
+    CURRENT.try_with(move |slot| slot.replace(ctx)).ok().flatten()
+}
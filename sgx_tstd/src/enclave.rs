@@ -19,6 +19,7 @@ use core::sync::atomic::{AtomicU64, Ordering};
 use crate::io;
 use crate::path::{Path, PathBuf};
 use crate::sync::SgxThreadSpinlock;
+#[cfg(not(minimal_tcb))]
 use crate::untrusted::fs;
 use sgx_trts::enclave;
 use sgx_types::*;
@@ -121,7 +122,11 @@ pub fn get_enclave_path() -> Option<PathBuf> {
 ///
 /// set_enclave_path is to set the path or name of the enclave.
 ///
+/// With the `minimal` feature the path is not checked for existence, as
+/// that would require the untrusted file system.
+///
 pub fn set_enclave_path<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    #[cfg(not(minimal_tcb))]
     let _ = fs::metadata(&path)?;
     unsafe {
         LOCK.lock();
@@ -131,4 +136,60 @@ pub fn set_enclave_path<P: AsRef<Path>>(path: P) -> io::Result<()> {
         LOCK.unlock();
         Ok(())
     }
-}
\ No newline at end of file
+}
+///
+/// The security-relevant attributes the enclave was launched with.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SecurityPosture {
+    debug: bool,
+    provision_key: bool,
+    kss: bool,
+}
+
+impl SecurityPosture {
+    /// The enclave is a debug enclave, its memory can be read and modified by the host.
+    pub fn is_debug(&self) -> bool {
+        self.debug
+    }
+
+    /// The enclave has access to the provisioning key.
+    pub fn has_provision_key(&self) -> bool {
+        self.provision_key
+    }
+
+    /// The enclave was launched with Key Separation and Sharing.
+    pub fn is_kss_enabled(&self) -> bool {
+        self.kss
+    }
+
+    ///
+    /// require_production fails if the enclave is debuggable.
+    ///
+    /// Call it before provisioning or deriving secrets that must not be exposed to the host.
+    /// Sealed data is guarded by `sgx_tseal::rsgx_set_secret_class` instead, which checks every
+    /// unseal call.
+    ///
+    pub fn require_production(&self) -> io::Result<()> {
+        if self.debug {
+            Err(io::const_io_error!(
+                io::ErrorKind::PermissionDenied,
+                "production secrets are not available to a debug enclave",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+///
+/// security_posture is to get the debug, provisioning key and KSS state of the enclave.
+///
+pub fn security_posture() -> SecurityPosture {
+    let flags = unsafe { (*sgx_self_report()).body.attributes.flags };
+    SecurityPosture {
+        debug: (flags & SGX_FLAGS_DEBUG) != 0,
+        provision_key: (flags & SGX_FLAGS_PROVISION_KEY) != 0,
+        kss: (flags & SGX_FLAGS_KSS) != 0,
+    }
+}
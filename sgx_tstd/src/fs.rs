@@ -25,7 +25,7 @@ use crate::path::{Path, PathBuf};
 use crate::sys::fs as fs_imp;
 use crate::sys_common::{AsInner, AsInnerMut, FromInner, IntoInner};
 use crate::time::SystemTime;
-#[cfg(not(feature = "untrusted_fs"))]
+#[cfg(not(tstd_untrusted_fs))]
 use crate::untrusted::path::PathEx;
 
 /// An object providing access to an open file on the filesystem.
@@ -0,0 +1,313 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Health and readiness reporting to the host.
+//!
+//! Subsystems register checks with [`register`]: that storage is reachable,
+//! that attestation collateral is fresh, that keys are loaded. [`report`]
+//! runs all checks and folds their outcomes into a [`Report`] with an
+//! overall [`Readiness`]. The host polls it through the `t_health_ecall`
+//! ECALL declared in `sgx_health.edl` (see `SgxEnclave::health` in
+//! `sgx_urts`), and an orchestrator can route traffic on the result.
+//!
+//! A failing [`Criticality::Required`] check makes the enclave not ready; a
+//! failing [`Criticality::Optional`] check, or any degraded check, makes it
+//! degraded, still serving but with reduced function.
+//!
+//! Checks run on the thread of the ECALL, one after the other. They should
+//! be cheap and must not block for long, since the enclave cannot interrupt
+//! them.
+//!
+//! The report is produced by the enclave but travels through the host, which
+//! can alter or withhold it. It is fit for scheduling decisions, not as
+//! evidence of the state of the enclave.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::health::{self, Criticality, Status};
+//!
+//! health::register("keys", Criticality::Required, || {
+//!     if keys::loaded() {
+//!         Status::Pass
+//!     } else {
+//!         Status::Fail("identity keyring is not open".to_owned())
+//!     }
+//! })?;
+//! health::register("prewarm", Criticality::Optional, health::prewarm_check)?;
+//!
+//! let report = health::report();
+//! ```
+
+use crate::io;
+use crate::prewarm;
+use crate::slice;
+use crate::string::String;
+use crate::sync::{PoisonError, SgxMutex};
+use crate::time::{Duration, Instant};
+use crate::vec::Vec;
+use sgx_types::sgx_status_t;
+
+const REPORT_VERSION: u8 = 1;
+const MAX_MESSAGE: usize = 1024;
+
+/// The outcome of a check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// The subsystem works.
+    Pass,
+    /// The subsystem works with reduced function.
+    Degraded(String),
+    /// The subsystem does not work.
+    Fail(String),
+}
+
+impl Status {
+    fn code(&self) -> u8 {
+        match self {
+            Status::Pass => 0,
+            Status::Degraded(_) => 1,
+            Status::Fail(_) => 2,
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            Status::Pass => "",
+            Status::Degraded(message) | Status::Fail(message) => message,
+        }
+    }
+}
+
+/// How a failing check affects the readiness of the enclave.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Criticality {
+    /// The enclave cannot serve without the subsystem.
+    Required,
+    /// The enclave can serve without the subsystem, with reduced function.
+    Optional,
+}
+
+/// The overall state of the enclave.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Readiness {
+    Ready,
+    Degraded,
+    NotReady,
+}
+
+/// A health check.
+pub type Check = fn() -> Status;
+
+struct Entry {
+    name: &'static str,
+    criticality: Criticality,
+    check: Check,
+}
+
+static CHECKS: SgxMutex<Vec<Entry>> = SgxMutex::new(Vec::new());
+
+/// Registers a check called `name`.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::AlreadyExists`] if a check of that name is
+/// registered.
+pub fn register(name: &'static str, criticality: Criticality, check: Check) -> io::Result<()> {
+    let mut checks = CHECKS.lock().unwrap_or_else(PoisonError::into_inner);
+    if checks.iter().any(|entry| entry.name == name) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "a health check of that name is registered",
+        ));
+    }
+    checks.push(Entry {
+        name,
+        criticality,
+        check,
+    });
+    Ok(())
+}
+
+/// Removes the check called `name`, returning true if it was registered.
+pub fn unregister(name: &str) -> bool {
+    let mut checks = CHECKS.lock().unwrap_or_else(PoisonError::into_inner);
+    let len = checks.len();
+    checks.retain(|entry| entry.name != name);
+    checks.len() != len
+}
+
+/// A check that is degraded until [`prewarm`](crate::prewarm::prewarm) has
+/// completed.
+pub fn prewarm_check() -> Status {
+    if prewarm::is_warm() {
+        Status::Pass
+    } else {
+        Status::Degraded("warm-up has not completed".to_owned())
+    }
+}
+
+/// The outcome of one check in a [`Report`].
+#[derive(Clone, Debug)]
+pub struct CheckReport {
+    pub name: &'static str,
+    pub criticality: Criticality,
+    pub status: Status,
+    /// How long the check ran.
+    pub elapsed: Duration,
+}
+
+/// The outcome of all checks, as returned by [`report`].
+#[derive(Clone, Debug)]
+pub struct Report {
+    readiness: Readiness,
+    checks: Vec<CheckReport>,
+}
+
+impl Report {
+    pub fn readiness(&self) -> Readiness {
+        self.readiness
+    }
+
+    /// Returns true if the enclave is ready or degraded.
+    pub fn is_serving(&self) -> bool {
+        self.readiness != Readiness::NotReady
+    }
+
+    /// The outcomes of the checks, in registration order.
+    pub fn checks(&self) -> &[CheckReport] {
+        &self.checks
+    }
+
+    /// Encodes the report as the `t_health_ecall` ECALL returns it.
+    ///
+    /// All integers are little-endian: the version (1) and the readiness as
+    /// `u8`s (0 ready, 1 degraded, 2 not ready), the number of checks as a
+    /// `u16`, then for each check its name (a `u16` length and UTF-8 bytes),
+    /// its criticality as a `u8` (0 required, 1 optional), its status as a
+    /// `u8` (0 pass, 1 degraded, 2 fail), its message like the name, at most
+    /// 1024 bytes, and its running time in microseconds as a `u64`.
+    pub fn encode(&self) -> Vec<u8> {
+        fn put_str(out: &mut Vec<u8>, s: &str, max: usize) {
+            let mut end = s.len().min(max);
+            while !s.is_char_boundary(end) {
+                end -= 1;
+            }
+            out.extend_from_slice(&(end as u16).to_le_bytes());
+            out.extend_from_slice(&s.as_bytes()[..end]);
+        }
+
+        let mut out = Vec::new();
+        out.push(REPORT_VERSION);
+        out.push(match self.readiness {
+            Readiness::Ready => 0,
+            Readiness::Degraded => 1,
+            Readiness::NotReady => 2,
+        });
+        let checks = &self.checks[..self.checks.len().min(u16::MAX as usize)];
+        out.extend_from_slice(&(checks.len() as u16).to_le_bytes());
+        for check in checks {
+            put_str(&mut out, check.name, u16::MAX as usize);
+            out.push(match check.criticality {
+                Criticality::Required => 0,
+                Criticality::Optional => 1,
+            });
+            out.push(check.status.code());
+            put_str(&mut out, check.status.message(), MAX_MESSAGE);
+            let micros = check.elapsed.as_micros().min(u64::MAX as u128) as u64;
+            out.extend_from_slice(&micros.to_le_bytes());
+        }
+        out
+    }
+}
+
+/// Runs all registered checks and returns the report.
+///
+/// Checks run without the registry lock, so that they may register or
+/// remove others; those changes show in the next report.
+pub fn report() -> Report {
+    let checks: Vec<(&'static str, Criticality, Check)> = CHECKS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .map(|entry| (entry.name, entry.criticality, entry.check))
+        .collect();
+
+    let mut readiness = Readiness::Ready;
+    let mut reports = Vec::with_capacity(checks.len());
+    for (name, criticality, check) in checks {
+        let start = Instant::_now();
+        let status = check();
+        let elapsed = Instant::_now().saturating_duration_since(start);
+        let effect = match (&status, criticality) {
+            (Status::Pass, _) => Readiness::Ready,
+            (Status::Fail(_), Criticality::Required) => Readiness::NotReady,
+            _ => Readiness::Degraded,
+        };
+        readiness = readiness.max(effect);
+        reports.push(CheckReport {
+            name,
+            criticality,
+            status,
+            elapsed,
+        });
+    }
+    Report {
+        readiness,
+        checks: reports,
+    }
+}
+
+///
+/// Runs [`report`] on behalf of the host and writes the encoded report (see
+/// [`Report::encode`]) to `buf`.
+///
+/// # Parameters
+///
+/// **buf**, **cap**
+///
+/// The buffer of the host for the report, and its size.
+///
+/// **len**
+///
+/// Receives the size of the encoded report, also when it does not fit.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// A pointer is null.
+///
+/// **SGX_ERROR_OUT_OF_MEMORY**
+///
+/// The report is larger than `cap`; `len` holds the size it needs.
+///
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[no_mangle]
+pub extern "C" fn t_health_ecall(buf: *mut u8, cap: usize, len: *mut usize) -> sgx_status_t {
+    if len.is_null() || (buf.is_null() && cap > 0) {
+        return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+    }
+    let encoded = report().encode();
+    unsafe { *len = encoded.len() };
+    if encoded.len() > cap {
+        return sgx_status_t::SGX_ERROR_OUT_OF_MEMORY;
+    }
+    let out = unsafe { slice::from_raw_parts_mut(buf, cap) };
+    out[..encoded.len()].copy_from_slice(&encoded);
+    sgx_status_t::SGX_SUCCESS
+}
@@ -71,7 +71,10 @@ where
     R: Read,
     W: Write,
 {
-    crate::sys::kernel_copy::copy_spec(reader, writer)
+    #[cfg(not(minimal_tcb))]
+    return crate::sys::kernel_copy::copy_spec(reader, writer);
+    #[cfg(minimal_tcb)]
+    return generic_copy(reader, writer);
 }
 
 /// The userspace read-write-loop implementation of `io::copy` that is used when
@@ -0,0 +1,728 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! A durable job queue with at-least-once delivery.
+//!
+//! A [`JobQueue`] keeps opaque job payloads in a write-ahead log held in a
+//! protected file, so background work queued by an enclave survives
+//! restarts. Every operation appends one record to the log and flushes it
+//! before it returns; the state of the queue is rebuilt from the log when it
+//! is opened.
+//!
+//! A worker takes a job with [`JobQueue::lease`] and reports the outcome with
+//! [`JobQueue::ack`] or [`JobQueue::nack`]. A job that is not acknowledged
+//! before its lease runs out, or whose worker dies with the enclave, is
+//! delivered again, so jobs must tolerate running more than once. Failed
+//! attempts are retried after an exponential backoff; a job that has failed
+//! [`QueueOptions::max_attempts`] times is moved to the dead letters, where
+//! it stays until it is requeued or purged.
+//!
+//! The log lives in two protected files, `<path>.0` and `<path>.1`. When the
+//! log grows, the live jobs are written to the other file, which then takes
+//! over. Leases and backoff delays do not survive a restart: after
+//! [`JobQueue::open`], every job that is not dead is ready.
+//!
+//! Protected files are confidential and tamper-evident, but the host can
+//! replace them with older copies. A rolled-back queue may deliver jobs
+//! again, which at-least-once delivery allows, but may also lose jobs queued
+//! since the copy was made. Jobs with effects that must not repeat should go
+//! through an idempotency log such as `sgx_tseal::SgxIdempotencyLog`.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::jobqueue::{JobQueue, QueueOptions};
+//!
+//! let queue = JobQueue::open("mail.queue", QueueOptions::new())?;
+//! queue.enqueue(b"welcome user 42")?;
+//!
+//! while let Some(lease) = queue.lease()? {
+//!     match send(lease.payload()) {
+//!         Ok(()) => queue.ack(lease)?,
+//!         Err(_) => queue.nack(lease)?,
+//!     }
+//! }
+//! ```
+
+use crate::collections::BTreeMap;
+use crate::ffi::OsString;
+use crate::io::{self, Write};
+use crate::path::{Path, PathBuf};
+use crate::sgxfs::{self, SgxFile};
+use crate::sync::{PoisonError, SgxMutex};
+use crate::time::{Duration, Instant};
+use crate::vec::Vec;
+
+const LOG_MAGIC: &[u8; 8] = b"SGXJOBQ1";
+const HEADER_SIZE: usize = 8 + 8 + 8;
+
+const REC_ENQUEUE: u8 = 1;
+const REC_LEASE: u8 = 2;
+const REC_ACK: u8 = 3;
+const REC_RETRY: u8 = 4;
+const REC_DEAD: u8 = 5;
+const REC_REQUEUE: u8 = 6;
+const REC_PURGE: u8 = 7;
+const REC_STATE: u8 = 8;
+const REC_COMMIT: u8 = 9;
+
+/// The identifier of a job, unique within its queue.
+pub type JobId = u64;
+
+/// Settings of a [`JobQueue`].
+#[derive(Debug, Clone, Copy)]
+pub struct QueueOptions {
+    max_attempts: u32,
+    lease_time: Duration,
+    backoff: Duration,
+    max_backoff: Duration,
+    max_payload: usize,
+    compact_after: usize,
+}
+
+impl Default for QueueOptions {
+    fn default() -> Self {
+        QueueOptions {
+            max_attempts: 5,
+            lease_time: Duration::from_secs(30),
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(300),
+            max_payload: 1024 * 1024,
+            compact_after: 4096,
+        }
+    }
+}
+
+impl QueueOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attempts after which a failing job becomes a dead letter. Defaults to 5.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// How long a worker holds a leased job. Defaults to 30 seconds.
+    pub fn lease_time(mut self, lease_time: Duration) -> Self {
+        self.lease_time = lease_time;
+        self
+    }
+
+    /// The delay before the first retry, doubled for every further one up to
+    /// `max_backoff`. Defaults to 1 second and 5 minutes.
+    pub fn backoff(mut self, backoff: Duration, max_backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self.max_backoff = max_backoff.max(backoff);
+        self
+    }
+
+    /// The largest payload accepted by [`JobQueue::enqueue`]. Defaults to 1 MiB.
+    pub fn max_payload(mut self, bytes: usize) -> Self {
+        self.max_payload = bytes.min(u32::MAX as usize);
+        self
+    }
+
+    /// The number of log records after which the log is compacted. Defaults
+    /// to 4096.
+    pub fn compact_after(mut self, records: usize) -> Self {
+        self.compact_after = records.max(1);
+        self
+    }
+
+    fn retry_delay(&self, attempts: u32) -> Duration {
+        let shift = attempts.saturating_sub(1).min(31);
+        self.backoff
+            .checked_mul(1 << shift)
+            .map_or(self.max_backoff, |delay| delay.min(self.max_backoff))
+    }
+}
+
+/// A job handed to a worker by [`JobQueue::lease`].
+#[derive(Debug)]
+pub struct Lease {
+    id: JobId,
+    attempt: u32,
+    token: u64,
+    payload: Vec<u8>,
+}
+
+impl Lease {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    /// The attempt this lease is for, starting at 1.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn into_payload(self) -> Vec<u8> {
+        self.payload
+    }
+}
+
+/// Counts of the jobs of a queue by state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// Jobs that can be leased now.
+    pub ready: usize,
+    /// Jobs waiting for their retry delay.
+    pub delayed: usize,
+    /// Jobs held by workers.
+    pub leased: usize,
+    /// Jobs that failed too often.
+    pub dead: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum JobState {
+    Ready(Option<Instant>),
+    Leased { token: u64, until: Instant },
+    Dead,
+}
+
+struct Job {
+    payload: Vec<u8>,
+    attempts: u32,
+    state: JobState,
+}
+
+struct Inner {
+    base: PathBuf,
+    slot: u8,
+    generation: u64,
+    file: SgxFile,
+    records: usize,
+    next_id: JobId,
+    next_token: u64,
+    jobs: BTreeMap<JobId, Job>,
+}
+
+// The protected file is only used with the queue mutex held.
+unsafe impl Send for Inner {}
+
+/// A durable queue of jobs, shared between the threads of the enclave.
+pub struct JobQueue {
+    options: QueueOptions,
+    inner: SgxMutex<Inner>,
+}
+
+fn slot_path(base: &Path, slot: u8) -> PathBuf {
+    let mut name = OsString::from(base.as_os_str());
+    name.push(if slot == 0 { ".0" } else { ".1" });
+    PathBuf::from(name)
+}
+
+fn stale_lease() -> io::Error {
+    io::const_io_error!(io::ErrorKind::InvalidInput, "the lease is no longer held")
+}
+
+fn record(tag: u8, id: JobId) -> Vec<u8> {
+    let mut out = Vec::with_capacity(1 + 8);
+    out.push(tag);
+    out.extend_from_slice(&id.to_le_bytes());
+    out
+}
+
+fn state_record(id: JobId, job: &Job) -> Vec<u8> {
+    let mut out = record(REC_STATE, id);
+    out.extend_from_slice(&job.attempts.to_le_bytes());
+    out.push(matches!(job.state, JobState::Dead) as u8);
+    out.extend_from_slice(&(job.payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(&job.payload);
+    out
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < n {
+            return None;
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Some(head)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+}
+
+// The state rebuilt from one log file.
+struct Replay {
+    generation: u64,
+    next_id: JobId,
+    jobs: BTreeMap<JobId, Job>,
+}
+
+// Replays a log. Returns `None` for a log whose snapshot was not committed,
+// and stops at a torn record at the end of the log.
+fn replay(bytes: &[u8]) -> Option<Replay> {
+    let mut reader = Reader { bytes };
+    if reader.take(LOG_MAGIC.len())? != LOG_MAGIC {
+        return None;
+    }
+    let mut log = Replay {
+        generation: reader.u64()?,
+        next_id: reader.u64()?,
+        jobs: BTreeMap::new(),
+    };
+    let mut committed = false;
+    while !reader.bytes.is_empty() {
+        let mut rec = Reader {
+            bytes: reader.bytes,
+        };
+        let applied = (|| {
+            let tag = rec.take(1)?[0];
+            let id = rec.u64()?;
+            match (tag, committed) {
+                (REC_STATE, false) => {
+                    let attempts = rec.u32()?;
+                    let dead = rec.take(1)?[0] != 0;
+                    let len = rec.u32()? as usize;
+                    let payload = rec.take(len)?.to_vec();
+                    let state = if dead {
+                        JobState::Dead
+                    } else {
+                        JobState::Ready(None)
+                    };
+                    log.jobs.insert(
+                        id,
+                        Job {
+                            payload,
+                            attempts,
+                            state,
+                        },
+                    );
+                }
+                (REC_COMMIT, false) => committed = true,
+                (REC_ENQUEUE, true) => {
+                    let len = rec.u32()? as usize;
+                    let payload = rec.take(len)?.to_vec();
+                    log.next_id = log.next_id.max(id + 1);
+                    log.jobs.insert(
+                        id,
+                        Job {
+                            payload,
+                            attempts: 0,
+                            state: JobState::Ready(None),
+                        },
+                    );
+                }
+                (REC_LEASE, true) => {
+                    if let Some(job) = log.jobs.get_mut(&id) {
+                        job.attempts = job.attempts.saturating_add(1);
+                    }
+                }
+                (REC_RETRY, true) => (),
+                (REC_DEAD, true) => {
+                    if let Some(job) = log.jobs.get_mut(&id) {
+                        job.state = JobState::Dead;
+                    }
+                }
+                (REC_REQUEUE, true) => {
+                    if let Some(job) = log.jobs.get_mut(&id) {
+                        job.attempts = 0;
+                        job.state = JobState::Ready(None);
+                    }
+                }
+                (REC_ACK, true) | (REC_PURGE, true) => {
+                    log.jobs.remove(&id);
+                }
+                _ => return None,
+            }
+            Some(())
+        })();
+        if applied.is_none() {
+            break;
+        }
+        reader.bytes = rec.bytes;
+    }
+    if committed {
+        Some(log)
+    } else {
+        None
+    }
+}
+
+fn read_slot(base: &Path, slot: u8) -> io::Result<Option<Replay>> {
+    match sgxfs::read(slot_path(base, slot)) {
+        Ok(bytes) => Ok(replay(&bytes)),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// Writes the live jobs to a fresh log in `slot` and returns it, open for
+// appending.
+fn write_snapshot(
+    base: &Path,
+    slot: u8,
+    generation: u64,
+    next_id: JobId,
+    jobs: &BTreeMap<JobId, Job>,
+) -> io::Result<SgxFile> {
+    let path = slot_path(base, slot);
+    let mut out = Vec::with_capacity(HEADER_SIZE);
+    out.extend_from_slice(LOG_MAGIC);
+    out.extend_from_slice(&generation.to_le_bytes());
+    out.extend_from_slice(&next_id.to_le_bytes());
+    for (id, job) in jobs.iter() {
+        out.extend_from_slice(&state_record(*id, job));
+    }
+    out.extend_from_slice(&record(REC_COMMIT, 0));
+
+    let mut file = SgxFile::create(&path)?;
+    file.write_all(&out)?;
+    file.flush()?;
+    drop(file);
+    sgxfs::OpenOptions::new().append(true).open(&path)
+}
+
+impl Inner {
+    fn append(&mut self, rec: &[u8]) -> io::Result<()> {
+        self.file.write_all(rec)?;
+        self.file.flush()?;
+        self.records += 1;
+        Ok(())
+    }
+
+    fn compact(&mut self) -> io::Result<()> {
+        let slot = 1 - self.slot;
+        let generation = self.generation + 1;
+        self.file = write_snapshot(&self.base, slot, generation, self.next_id, &self.jobs)?;
+        let old = slot_path(&self.base, self.slot);
+        self.slot = slot;
+        self.generation = generation;
+        self.records = 0;
+        let _ = sgxfs::remove(old);
+        Ok(())
+    }
+
+    // Returns the jobs whose leases ran out to the queue, as failed attempts.
+    fn reclaim(&mut self, options: &QueueOptions, now: Instant) -> io::Result<()> {
+        let expired: Vec<JobId> = self
+            .jobs
+            .iter()
+            .filter(|(_, job)| matches!(job.state, JobState::Leased { until, .. } if until <= now))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in expired {
+            self.fail(options, id, now)?;
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, options: &QueueOptions, id: JobId, now: Instant) -> io::Result<()> {
+        let attempts = self.jobs[&id].attempts;
+        let state = if attempts >= options.max_attempts {
+            self.append(&record(REC_DEAD, id))?;
+            JobState::Dead
+        } else {
+            self.append(&record(REC_RETRY, id))?;
+            JobState::Ready(now.checked_add(options.retry_delay(attempts)))
+        };
+        if let Some(job) = self.jobs.get_mut(&id) {
+            job.state = state;
+        }
+        Ok(())
+    }
+
+    fn held(&self, lease: &Lease) -> bool {
+        matches!(
+            self.jobs.get(&lease.id),
+            Some(Job { state: JobState::Leased { token, .. }, .. }) if *token == lease.token
+        )
+    }
+}
+
+impl JobQueue {
+    /// Opens the queue kept at `path`, creating it if it does not exist.
+    ///
+    /// The log is compacted on every open, which drops a torn record left at
+    /// its end by a crash.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a log file cannot be read or written, for instance because it
+    /// was sealed by an enclave of another signer or has been tampered with.
+    pub fn open<P: AsRef<Path>>(path: P, options: QueueOptions) -> io::Result<JobQueue> {
+        let base = path.as_ref().to_path_buf();
+        let (slot, log) = match (read_slot(&base, 0)?, read_slot(&base, 1)?) {
+            (Some(a), Some(b)) if b.generation > a.generation => (1, b),
+            (Some(a), _) => (0, a),
+            (None, Some(b)) => (1, b),
+            (None, None) => (
+                1,
+                Replay {
+                    generation: 0,
+                    next_id: 0,
+                    jobs: BTreeMap::new(),
+                },
+            ),
+        };
+
+        let mut jobs = log.jobs;
+        for job in jobs.values_mut() {
+            if !matches!(job.state, JobState::Dead) && job.attempts >= options.max_attempts {
+                job.state = JobState::Dead;
+            }
+        }
+        let next = 1 - slot;
+        let generation = log.generation + 1;
+        let file = write_snapshot(&base, next, generation, log.next_id, &jobs)?;
+        let _ = sgxfs::remove(slot_path(&base, slot));
+
+        Ok(JobQueue {
+            options,
+            inner: SgxMutex::new(Inner {
+                base,
+                slot: next,
+                generation,
+                file,
+                records: 0,
+                next_id: log.next_id,
+                next_token: 0,
+                jobs,
+            }),
+        })
+    }
+
+    pub fn options(&self) -> &QueueOptions {
+        &self.options
+    }
+
+    fn lock(&self) -> crate::sync::SgxMutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn finish(&self, inner: &mut Inner) -> io::Result<()> {
+        if inner.records >= self.options.compact_after.max(2 * inner.jobs.len()) {
+            inner.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Adds a job to the end of the queue and returns its identifier. The job
+    /// is durable once this returns.
+    pub fn enqueue(&self, payload: &[u8]) -> io::Result<JobId> {
+        if payload.len() > self.options.max_payload {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "job payload is too large",
+            ));
+        }
+        let mut inner = self.lock();
+        let id = inner.next_id;
+        let mut rec = record(REC_ENQUEUE, id);
+        rec.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        rec.extend_from_slice(payload);
+        inner.append(&rec)?;
+        inner.next_id += 1;
+        inner.jobs.insert(
+            id,
+            Job {
+                payload: payload.to_vec(),
+                attempts: 0,
+                state: JobState::Ready(None),
+            },
+        );
+        self.finish(&mut inner)?;
+        Ok(id)
+    }
+
+    /// Leases the oldest job that is ready, or returns `None` if no job is.
+    ///
+    /// Jobs whose leases ran out are returned to the queue first, counting as
+    /// failed attempts.
+    pub fn lease(&self) -> io::Result<Option<Lease>> {
+        let now = Instant::_now();
+        let mut inner = self.lock();
+        inner.reclaim(&self.options, now)?;
+
+        let id = inner.jobs.iter().find_map(|(id, job)| match job.state {
+            JobState::Ready(None) => Some(*id),
+            JobState::Ready(Some(at)) if at <= now => Some(*id),
+            _ => None,
+        });
+        let id = match id {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        inner.append(&record(REC_LEASE, id))?;
+
+        let token = inner.next_token;
+        inner.next_token += 1;
+        let until = now.checked_add(self.options.lease_time).unwrap_or(now);
+        let job = inner.jobs.get_mut(&id).unwrap();
+        job.attempts = job.attempts.saturating_add(1);
+        job.state = JobState::Leased { token, until };
+        let lease = Lease {
+            id,
+            attempt: job.attempts,
+            token,
+            payload: job.payload.clone(),
+        };
+        self.finish(&mut inner)?;
+        Ok(Some(lease))
+    }
+
+    /// Marks a leased job as done and removes it from the queue.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `InvalidInput` if the lease ran out and the job was returned
+    /// to the queue; the job will then run again.
+    pub fn ack(&self, lease: Lease) -> io::Result<()> {
+        let mut inner = self.lock();
+        if !inner.held(&lease) {
+            return Err(stale_lease());
+        }
+        inner.append(&record(REC_ACK, lease.id))?;
+        inner.jobs.remove(&lease.id);
+        self.finish(&mut inner)
+    }
+
+    /// Reports a failed attempt. The job is retried after its backoff delay,
+    /// or becomes a dead letter if it has used up its attempts.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `InvalidInput` if the lease ran out and the job was already
+    /// returned to the queue.
+    pub fn nack(&self, lease: Lease) -> io::Result<()> {
+        let now = Instant::_now();
+        let mut inner = self.lock();
+        if !inner.held(&lease) {
+            return Err(stale_lease());
+        }
+        inner.fail(&self.options, lease.id, now)?;
+        self.finish(&mut inner)
+    }
+
+    /// Extends a lease by the lease time, for jobs that take longer.
+    ///
+    /// The extension is not logged: a restart returns the job to the queue
+    /// regardless.
+    pub fn renew(&self, lease: &Lease) -> io::Result<()> {
+        let now = Instant::_now();
+        let mut inner = self.lock();
+        if !inner.held(lease) {
+            return Err(stale_lease());
+        }
+        if let Some(job) = inner.jobs.get_mut(&lease.id) {
+            if let JobState::Leased { ref mut until, .. } = job.state {
+                *until = now.checked_add(self.options.lease_time).unwrap_or(now);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the dead letters, oldest first, with the number of attempts
+    /// each of them failed.
+    pub fn dead_letters(&self) -> Vec<(JobId, u32, Vec<u8>)> {
+        self.lock()
+            .jobs
+            .iter()
+            .filter(|(_, job)| matches!(job.state, JobState::Dead))
+            .map(|(id, job)| (*id, job.attempts, job.payload.clone()))
+            .collect()
+    }
+
+    /// Returns a dead letter to the queue with a fresh set of attempts.
+    pub fn requeue(&self, id: JobId) -> io::Result<()> {
+        let mut inner = self.lock();
+        match inner.jobs.get(&id) {
+            Some(Job {
+                state: JobState::Dead,
+                ..
+            }) => (),
+            _ => {
+                return Err(io::const_io_error!(
+                    io::ErrorKind::NotFound,
+                    "no such dead letter",
+                ))
+            }
+        }
+        inner.append(&record(REC_REQUEUE, id))?;
+        let job = inner.jobs.get_mut(&id).unwrap();
+        job.attempts = 0;
+        job.state = JobState::Ready(None);
+        self.finish(&mut inner)
+    }
+
+    /// Deletes a dead letter.
+    pub fn purge(&self, id: JobId) -> io::Result<()> {
+        let mut inner = self.lock();
+        match inner.jobs.get(&id) {
+            Some(Job {
+                state: JobState::Dead,
+                ..
+            }) => (),
+            _ => {
+                return Err(io::const_io_error!(
+                    io::ErrorKind::NotFound,
+                    "no such dead letter",
+                ))
+            }
+        }
+        inner.append(&record(REC_PURGE, id))?;
+        inner.jobs.remove(&id);
+        self.finish(&mut inner)
+    }
+
+    /// Returns the number of jobs in the queue, dead letters included.
+    pub fn len(&self) -> usize {
+        self.lock().jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().jobs.is_empty()
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        let now = Instant::_now();
+        let inner = self.lock();
+        let mut stats = QueueStats::default();
+        for job in inner.jobs.values() {
+            match job.state {
+                JobState::Ready(Some(at)) if at > now => stats.delayed += 1,
+                JobState::Ready(_) => stats.ready += 1,
+                JobState::Leased { until, .. } if until <= now => stats.ready += 1,
+                JobState::Leased { .. } => stats.leased += 1,
+                JobState::Dead => stats.dead += 1,
+            }
+        }
+        stats
+    }
+}
@@ -187,6 +187,7 @@ pub use sgx_trts::{
 
 extern crate sgx_tprotected_fs;
 extern crate sgx_libc;
+extern crate sgx_tsync;
 
 // The standard macros that are not built-in to the compiler.
 #[macro_use]
@@ -252,34 +253,44 @@ pub mod thread;
 pub mod ascii;
 #[cfg(feature = "backtrace")]
 pub mod backtrace;
+pub mod classify;
 pub mod collections;
+pub mod cron;
+pub mod dcap;
+pub mod ecall;
+#[cfg(not(minimal_tcb))]
 pub mod env;
 pub mod error;
 pub mod ffi;
+pub mod health;
 pub mod sgxfs;
-#[cfg(feature = "untrusted_fs")]
+#[cfg(tstd_untrusted_fs)]
 pub mod fs;
 pub mod io;
+pub mod jobqueue;
+#[cfg(feature = "thread")]
+pub mod log;
+#[cfg(not(minimal_tcb))]
 pub mod net;
 pub mod num;
+pub mod ocall;
 pub mod os;
 pub mod panic;
 pub mod path;
+pub mod perf;
+pub mod prewarm;
+pub mod qos;
+pub mod quota;
+#[cfg(feature = "switchless")]
+pub mod switchless;
 pub mod sync;
+pub mod taint;
+pub mod tenant;
 pub mod time;
+pub mod topology;
 pub mod enclave;
 pub mod untrusted;
-
-
-pub mod task {
-    //! Types and Traits for working with asynchronous tasks.
-
-    #[doc(inline)]
-    pub use core::task::*;
-
-    #[doc(inline)]
-    pub use alloc_crate::task::*;
-}
+pub mod task;
 
 pub mod arch {
     // The `no_inline`-attribute is required to make the documentation of all
@@ -288,6 +299,8 @@ pub mod arch {
     // more information.
     #[doc(no_inline)] // Note (#82861): required for correct documentation
     pub use core::arch::*;
+
+    pub use sgx_trts::is_x86_feature_detected;
 }
 
 // Platform-abstraction modules
@@ -301,7 +314,7 @@ mod cpuid;
 mod panicking;
 mod personality;
 
-#[cfg(not(feature = "untrusted_fs"))]
+#[cfg(not(any(tstd_untrusted_fs, minimal_tcb)))]
 mod fs;
 
 pub use cpuid::*;
@@ -0,0 +1,528 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Log and metrics shipping to an untrusted collector.
+//!
+//! Request threads hand opaque records to a [`Shipper`], which keeps them in a
+//! bounded in-enclave queue. A dedicated flusher thread drains the queue and
+//! delivers records to the host in batches, one `u_log_ship_ocall` per batch
+//! (see `sgx_log.edl`). The host acknowledges how many records of each batch it
+//! accepted.
+//!
+//! Producers never wait for the host: when the queue is at its record or byte
+//! bound, new records are dropped and counted instead. Every record that is
+//! accepted by [`Shipper::send`] ends up either shipped or in one of the drop
+//! counters reported by [`Shipper::stats`].
+//!
+//! Batches go to an [`Exporter`]. The default, [`HostCollector`], hands them
+//! to the host, which forwards them as it sees fit; [`otlp::OtlpExporter`]
+//! instead sends them to an OpenTelemetry collector over a connection that
+//! ends inside the enclave, so the host can neither read nor alter them.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::log;
+//! use std::time::Duration;
+//!
+//! let shipper = log::Builder::new()
+//!     .max_bytes(256 * 1024)
+//!     .flush_interval(Duration::from_millis(200))
+//!     .spawn()
+//!     .unwrap();
+//!
+//! let _ = shipper.send(b"request served");
+//! shipper.shutdown();
+//! ```
+
+use crate::boxed::Box;
+use crate::collections::VecDeque;
+use crate::error;
+use crate::fmt;
+use crate::io;
+use crate::qos::{self, IoClass};
+use crate::sync::atomic::{AtomicU64, Ordering};
+use crate::sync::{Arc, SgxCondvar, SgxMutex};
+use crate::sys::log as imp;
+use crate::thread::{self, JoinHandle};
+use crate::time::Duration;
+
+pub mod otlp;
+
+const RECORD_HEADER_SIZE: usize = 4;
+
+/// A destination of batches of records.
+///
+/// The exporter is called from the flusher thread only, one batch at a time.
+pub trait Exporter: Send {
+    /// Delivers a batch and returns how many of its records were accepted.
+    ///
+    /// An error counts the whole batch as lost.
+    fn export(&mut self, batch: &Batch<'_>) -> io::Result<usize>;
+}
+
+/// A batch of records handed to an [`Exporter`].
+#[derive(Debug, Clone, Copy)]
+pub struct Batch<'a> {
+    encoded: &'a [u8],
+    count: usize,
+}
+
+impl<'a> Batch<'a> {
+    /// Returns the number of records in the batch.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the batch as sent to the host: every record is preceded by its
+    /// length as a little-endian `u32`.
+    pub fn encoded(&self) -> &'a [u8] {
+        self.encoded
+    }
+
+    /// Returns an iterator over the records of the batch.
+    pub fn iter(&self) -> Records<'a> {
+        Records { rest: self.encoded }
+    }
+}
+
+/// An iterator over the records of a [`Batch`].
+#[derive(Debug, Clone)]
+pub struct Records<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.rest.len() < RECORD_HEADER_SIZE {
+            return None;
+        }
+        let (len, rest) = self.rest.split_at(RECORD_HEADER_SIZE);
+        let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+        if len > rest.len() {
+            return None;
+        }
+        let (record, rest) = rest.split_at(len);
+        self.rest = rest;
+        Some(record)
+    }
+}
+
+/// The default [`Exporter`]: delivers batches to the host with
+/// `u_log_ship_ocall`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HostCollector;
+
+impl Exporter for HostCollector {
+    fn export(&mut self, batch: &Batch<'_>) -> io::Result<usize> {
+        imp::ship(batch.encoded, batch.count)
+    }
+}
+
+/// Shipper factory, which can be used in order to configure the bounds of the
+/// queue and the batching behaviour of the flusher thread.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    name: String,
+    max_records: usize,
+    max_bytes: usize,
+    batch_records: usize,
+    batch_bytes: usize,
+    flush_interval: Duration,
+    io_class: IoClass,
+}
+
+impl Builder {
+    /// Creates a builder with conservative defaults: 4096 queued records,
+    /// 1 MiB of queued payload, batches of at most 256 records or 64 KiB,
+    /// a flush at least once per second, and [`IoClass::Bulk`] OCALLs.
+    pub fn new() -> Builder {
+        Builder {
+            name: "log-shipper".to_owned(),
+            max_records: 4096,
+            max_bytes: 1024 * 1024,
+            batch_records: 256,
+            batch_bytes: 64 * 1024,
+            flush_interval: Duration::from_secs(1),
+            io_class: IoClass::Bulk,
+        }
+    }
+
+    /// Names the flusher thread.
+    pub fn name(mut self, name: String) -> Builder {
+        self.name = name;
+        self
+    }
+
+    /// Sets the maximum number of records held in the queue.
+    pub fn max_records(mut self, max_records: usize) -> Builder {
+        self.max_records = max_records;
+        self
+    }
+
+    /// Sets the maximum number of payload bytes held in the queue.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Builder {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Sets the maximum number of records delivered in one batch.
+    pub fn batch_records(mut self, batch_records: usize) -> Builder {
+        self.batch_records = batch_records;
+        self
+    }
+
+    /// Sets the maximum encoded size of a batch.
+    ///
+    /// Records larger than this bound are rejected by [`Shipper::send`].
+    pub fn batch_bytes(mut self, batch_bytes: usize) -> Builder {
+        self.batch_bytes = batch_bytes;
+        self
+    }
+
+    /// Sets the longest time a queued record waits before being flushed.
+    pub fn flush_interval(mut self, flush_interval: Duration) -> Builder {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /// Sets the I/O class of the OCALLs delivering batches.
+    pub fn io_class(mut self, io_class: IoClass) -> Builder {
+        self.io_class = io_class;
+        self
+    }
+
+    /// Spawns the flusher thread, delivering to the host, and returns a
+    /// handle for producers.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidInput` if a bound is zero, or the error of the thread
+    /// spawn, e.g. when no TCS is available.
+    pub fn spawn(self) -> io::Result<Shipper> {
+        self.spawn_with(HostCollector)
+    }
+
+    /// Spawns the flusher thread, delivering to `exporter`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`Builder::spawn`].
+    pub fn spawn_with<E: Exporter + 'static>(self, exporter: E) -> io::Result<Shipper> {
+        if self.max_records == 0
+            || self.max_bytes == 0
+            || self.batch_records == 0
+            || self.batch_bytes <= RECORD_HEADER_SIZE
+        {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "log shipper bounds must be non-zero",
+            ));
+        }
+
+        let inner = Arc::new(Inner {
+            queue: SgxMutex::new(Queue {
+                records: VecDeque::new(),
+                bytes: 0,
+                flush: false,
+                closed: false,
+            }),
+            cond: SgxCondvar::new(),
+            flusher: SgxMutex::new(None),
+            exporter: SgxMutex::new(Box::new(exporter)),
+            config: self.clone(),
+            counters: Counters::default(),
+        });
+
+        let flusher = {
+            let inner = inner.clone();
+            thread::Builder::new().name(self.name).spawn(move || inner.run())?
+        };
+        *inner.flusher.lock().unwrap_or_else(|e| e.into_inner()) = Some(flusher);
+
+        Ok(Shipper { inner })
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder::new()
+    }
+}
+
+/// A cloneable producer handle of a log shipping pipeline.
+///
+/// All clones feed the same queue and flusher thread. The flusher thread keeps
+/// its TCS until [`Shipper::shutdown`] is called; dropping the handles does not
+/// stop it.
+#[derive(Clone)]
+pub struct Shipper {
+    inner: Arc<Inner>,
+}
+
+impl Shipper {
+    /// Queues a record for delivery without waiting for the host.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SendError::Full`] if the queue is at one of its bounds,
+    /// [`SendError::TooLarge`] if the record can never fit in a batch, and
+    /// [`SendError::Closed`] after [`Shipper::shutdown`].
+    pub fn send(&self, record: &[u8]) -> Result<(), SendError> {
+        let config = &self.inner.config;
+        let counters = &self.inner.counters;
+        if record.len() > config.batch_bytes - RECORD_HEADER_SIZE || record.len() > u32::MAX as usize {
+            counters.dropped_too_large.fetch_add(1, Ordering::Relaxed);
+            return Err(SendError::TooLarge);
+        }
+
+        let mut queue = self.inner.queue.lock().unwrap_or_else(|e| e.into_inner());
+        if queue.closed {
+            return Err(SendError::Closed);
+        }
+        if queue.records.len() >= config.max_records
+            || queue.bytes + record.len() > config.max_bytes
+        {
+            drop(queue);
+            counters.dropped_full.fetch_add(1, Ordering::Relaxed);
+            return Err(SendError::Full);
+        }
+        queue.bytes += record.len();
+        queue.records.push_back(record.to_vec());
+        let wake = queue.records.len() >= config.batch_records;
+        drop(queue);
+
+        counters.accepted.fetch_add(1, Ordering::Relaxed);
+        if wake {
+            self.inner.cond.notify_one();
+        }
+        Ok(())
+    }
+
+    /// Asks the flusher thread to deliver the queued records now instead of
+    /// waiting for the flush interval.
+    pub fn flush(&self) {
+        let mut queue = self.inner.queue.lock().unwrap_or_else(|e| e.into_inner());
+        queue.flush = true;
+        drop(queue);
+        self.inner.cond.notify_one();
+    }
+
+    /// Returns a snapshot of the pipeline counters.
+    pub fn stats(&self) -> Stats {
+        let queue = self.inner.queue.lock().unwrap_or_else(|e| e.into_inner());
+        let (queued, queued_bytes) = (queue.records.len(), queue.bytes);
+        drop(queue);
+
+        let counters = &self.inner.counters;
+        Stats {
+            queued,
+            queued_bytes,
+            accepted: counters.accepted.load(Ordering::Relaxed),
+            shipped: counters.shipped.load(Ordering::Relaxed),
+            dropped_full: counters.dropped_full.load(Ordering::Relaxed),
+            dropped_too_large: counters.dropped_too_large.load(Ordering::Relaxed),
+            dropped_rejected: counters.dropped_rejected.load(Ordering::Relaxed),
+            batches: counters.batches.load(Ordering::Relaxed),
+            failed_batches: counters.failed_batches.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Stops accepting records, delivers what is still queued and joins the
+    /// flusher thread.
+    ///
+    /// Calling this more than once, or from several clones, is harmless.
+    pub fn shutdown(&self) {
+        let mut queue = self.inner.queue.lock().unwrap_or_else(|e| e.into_inner());
+        queue.closed = true;
+        drop(queue);
+        self.inner.cond.notify_one();
+
+        let flusher = self.inner.flusher.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(flusher) = flusher {
+            let _ = flusher.join();
+        }
+    }
+}
+
+impl fmt::Debug for Shipper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shipper").field("stats", &self.stats()).finish()
+    }
+}
+
+/// Counters of a log shipping pipeline, as returned by [`Shipper::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Records currently waiting in the queue.
+    pub queued: usize,
+    /// Payload bytes currently waiting in the queue.
+    pub queued_bytes: usize,
+    /// Records accepted into the queue.
+    pub accepted: u64,
+    /// Records acknowledged by the exporter.
+    pub shipped: u64,
+    /// Records refused by `send` because the queue was full.
+    pub dropped_full: u64,
+    /// Records refused by `send` because they were larger than a batch may be.
+    pub dropped_too_large: u64,
+    /// Records that were delivered but not acknowledged, or lost in a failed delivery.
+    pub dropped_rejected: u64,
+    /// Batches delivered to the exporter.
+    pub batches: u64,
+    /// Batches whose delivery failed.
+    pub failed_batches: u64,
+}
+
+/// An error returned from [`Shipper::send`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendError {
+    /// The queue is at its record or byte bound; the record was dropped.
+    Full,
+    /// The record is larger than a batch may be.
+    TooLarge,
+    /// The shipper has been shut down.
+    Closed,
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SendError::Full => "log queue is full".fmt(f),
+            SendError::TooLarge => "log record exceeds the batch size".fmt(f),
+            SendError::Closed => "log shipper is shut down".fmt(f),
+        }
+    }
+}
+
+impl error::Error for SendError {
+    #[allow(deprecated)]
+    fn description(&self) -> &str {
+        match *self {
+            SendError::Full => "log queue is full",
+            SendError::TooLarge => "log record exceeds the batch size",
+            SendError::Closed => "log shipper is shut down",
+        }
+    }
+}
+
+struct Queue {
+    records: VecDeque<Vec<u8>>,
+    bytes: usize,
+    flush: bool,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Counters {
+    accepted: AtomicU64,
+    shipped: AtomicU64,
+    dropped_full: AtomicU64,
+    dropped_too_large: AtomicU64,
+    dropped_rejected: AtomicU64,
+    batches: AtomicU64,
+    failed_batches: AtomicU64,
+}
+
+struct Inner {
+    queue: SgxMutex<Queue>,
+    cond: SgxCondvar,
+    flusher: SgxMutex<Option<JoinHandle<()>>>,
+    exporter: SgxMutex<Box<dyn Exporter>>,
+    config: Builder,
+    counters: Counters,
+}
+
+impl Inner {
+    fn run(&self) {
+        let mut batch = Vec::new();
+        loop {
+            let (count, closed) = self.next_batch(&mut batch);
+            if count > 0 {
+                self.deliver(&batch, count);
+            } else if closed {
+                return;
+            }
+        }
+    }
+
+    // Waits until a batch is due and encodes it into `batch`.
+    fn next_batch(&self, batch: &mut Vec<u8>) -> (usize, bool) {
+        let config = &self.config;
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        if !queue.closed && !queue.flush && queue.records.len() < config.batch_records {
+            queue = match self.cond.wait_timeout_while(queue, config.flush_interval, |q| {
+                !q.closed && !q.flush && q.records.len() < config.batch_records
+            }) {
+                Ok((queue, _)) => queue,
+                Err(e) => e.into_inner().0,
+            };
+        }
+
+        batch.clear();
+        let mut count = 0;
+        while count < config.batch_records {
+            let len = match queue.records.front() {
+                Some(record) => record.len(),
+                None => break,
+            };
+            if batch.len() + RECORD_HEADER_SIZE + len > config.batch_bytes {
+                break;
+            }
+            let record = queue.records.pop_front().unwrap();
+            queue.bytes -= record.len();
+            batch.extend_from_slice(&(record.len() as u32).to_le_bytes());
+            batch.extend_from_slice(&record);
+            count += 1;
+        }
+        if queue.records.is_empty() {
+            queue.flush = false;
+        }
+        (count, queue.closed)
+    }
+
+    fn deliver(&self, batch: &[u8], count: usize) {
+        let counters = &self.counters;
+        let batch = Batch {
+            encoded: batch,
+            count,
+        };
+        let mut exporter = self.exporter.lock().unwrap_or_else(|e| e.into_inner());
+        let shipped = qos::with_class(self.config.io_class, || {
+            let (_permit, _) = qos::admit(batch.encoded.len());
+            exporter.export(&batch)
+        });
+        match shipped {
+            Ok(acked) => {
+                let acked = acked.min(count);
+                counters.batches.fetch_add(1, Ordering::Relaxed);
+                counters.shipped.fetch_add(acked as u64, Ordering::Relaxed);
+                counters.dropped_rejected.fetch_add((count - acked) as u64, Ordering::Relaxed);
+            }
+            Err(_) => {
+                counters.failed_batches.fetch_add(1, Ordering::Relaxed);
+                counters.dropped_rejected.fetch_add(count as u64, Ordering::Relaxed);
+            }
+        }
+    }
+}
@@ -0,0 +1,473 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! OTLP/HTTP export of logs and metrics.
+//!
+//! [`OtlpExporter`] encodes batches as OTLP/HTTP JSON requests and posts them
+//! to an OpenTelemetry collector. The enclave does not carry an HTTP/TLS
+//! stack of its own, so the connection is supplied through [`OtlpTransport`];
+//! it must terminate TLS inside the enclave, against a collector certificate
+//! the enclave checks, for the telemetry to be out of the reach of the host.
+//! A [`RequestSigner`] may in addition sign every request body, so that the
+//! collector can tell telemetry of the enclave from telemetry forged by
+//! anyone holding the collector credentials.
+//!
+//! An exporter carries one signal. For logs, every record is the body of a
+//! log record. For metrics, every record is a data point encoded with
+//! [`Metric::encode`]; records that do not decode are dropped.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::log::{self, otlp};
+//!
+//! let exporter = otlp::OtlpExporter::logs(tls_connection)
+//!     .resource_attribute("service.name", "payments-enclave")
+//!     .header("authorization", "Bearer ...");
+//! let shipper = log::Builder::new().spawn_with(exporter).unwrap();
+//! let _ = shipper.send(b"request served");
+//! ```
+
+use super::{Batch, Exporter};
+use crate::boxed::Box;
+use crate::fmt::Write;
+use crate::io;
+use crate::str;
+use crate::string::String;
+use crate::time::{SystemTime, UNIX_EPOCH};
+use crate::vec::Vec;
+
+const METRIC_VERSION: u8 = 1;
+
+/// The connection to an OpenTelemetry collector.
+pub trait OtlpTransport: Send {
+    /// Sends a `POST` request for `path` with the given headers and body, and
+    /// returns the HTTP status of the response.
+    fn post(&mut self, path: &str, headers: &[(String, String)], body: &[u8]) -> io::Result<u16>;
+}
+
+/// Signs the bodies of export requests.
+pub trait RequestSigner: Send {
+    /// Returns the header, as a name and a value, that carries the signature
+    /// of `body`.
+    fn sign(&mut self, body: &[u8]) -> io::Result<(String, String)>;
+}
+
+/// The kind of telemetry an exporter carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Logs,
+    Metrics,
+}
+
+impl Signal {
+    fn path(self) -> &'static str {
+        match self {
+            Signal::Logs => "/v1/logs",
+            Signal::Metrics => "/v1/metrics",
+        }
+    }
+}
+
+/// How the points of a metric combine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    /// The current value of a quantity.
+    Gauge,
+    /// A cumulative, monotonic sum.
+    Counter,
+}
+
+/// The value of a data point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetricValue {
+    Int(i64),
+    Double(f64),
+}
+
+/// A data point of a metric, carried as one record of a metrics exporter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    pub name: String,
+    pub unit: String,
+    pub kind: MetricKind,
+    pub value: MetricValue,
+    /// Time of the point in nanoseconds since the Unix epoch, or 0 for the
+    /// time of export.
+    pub time_unix_nano: u64,
+    pub attributes: Vec<(String, String)>,
+}
+
+impl Metric {
+    /// Creates a point with no unit and no attributes, stamped at export.
+    pub fn new(name: &str, kind: MetricKind, value: MetricValue) -> Metric {
+        Metric {
+            name: name.to_owned(),
+            unit: String::new(),
+            kind,
+            value,
+            time_unix_nano: 0,
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Encodes the point as a record for [`Shipper::send`](super::Shipper::send).
+    pub fn encode(&self) -> Vec<u8> {
+        fn put_str(out: &mut Vec<u8>, s: &str) {
+            let s = &s.as_bytes()[..s.len().min(u16::MAX as usize)];
+            out.extend_from_slice(&(s.len() as u16).to_le_bytes());
+            out.extend_from_slice(s);
+        }
+
+        let mut out = Vec::new();
+        out.push(METRIC_VERSION);
+        out.push(match self.kind {
+            MetricKind::Gauge => 0,
+            MetricKind::Counter => 1,
+        });
+        match self.value {
+            MetricValue::Int(v) => {
+                out.push(0);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            MetricValue::Double(v) => {
+                out.push(1);
+                out.extend_from_slice(&v.to_bits().to_le_bytes());
+            }
+        }
+        out.extend_from_slice(&self.time_unix_nano.to_le_bytes());
+        put_str(&mut out, &self.name);
+        put_str(&mut out, &self.unit);
+        let attributes = &self.attributes[..self.attributes.len().min(u16::MAX as usize)];
+        out.extend_from_slice(&(attributes.len() as u16).to_le_bytes());
+        for (key, value) in attributes {
+            put_str(&mut out, key);
+            put_str(&mut out, value);
+        }
+        out
+    }
+
+    /// Decodes a point encoded with [`Metric::encode`].
+    pub fn decode(bytes: &[u8]) -> Option<Metric> {
+        struct Reader<'a>(&'a [u8]);
+        impl<'a> Reader<'a> {
+            fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+                if self.0.len() < n {
+                    return None;
+                }
+                let (head, tail) = self.0.split_at(n);
+                self.0 = tail;
+                Some(head)
+            }
+            fn u8(&mut self) -> Option<u8> {
+                self.take(1).map(|b| b[0])
+            }
+            fn u16(&mut self) -> Option<u16> {
+                self.take(2)
+                    .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+            }
+            fn u64(&mut self) -> Option<u64> {
+                self.take(8)
+                    .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+            }
+            fn string(&mut self) -> Option<String> {
+                let len = self.u16()? as usize;
+                String::from_utf8(self.take(len)?.to_vec()).ok()
+            }
+        }
+
+        let mut reader = Reader(bytes);
+        if reader.u8()? != METRIC_VERSION {
+            return None;
+        }
+        let kind = match reader.u8()? {
+            0 => MetricKind::Gauge,
+            1 => MetricKind::Counter,
+            _ => return None,
+        };
+        let value = match reader.u8()? {
+            0 => MetricValue::Int(reader.u64()? as i64),
+            1 => MetricValue::Double(f64::from_bits(reader.u64()?)),
+            _ => return None,
+        };
+        let time_unix_nano = reader.u64()?;
+        let name = reader.string()?;
+        let unit = reader.string()?;
+        let count = reader.u16()? as usize;
+        let mut attributes = Vec::with_capacity(count);
+        for _ in 0..count {
+            attributes.push((reader.string()?, reader.string()?));
+        }
+        if !reader.0.is_empty() || name.is_empty() {
+            return None;
+        }
+        Some(Metric {
+            name,
+            unit,
+            kind,
+            value,
+            time_unix_nano,
+            attributes,
+        })
+    }
+}
+
+/// An [`Exporter`] posting batches to an OpenTelemetry collector.
+pub struct OtlpExporter<T: OtlpTransport> {
+    transport: T,
+    signal: Signal,
+    scope: String,
+    resource: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    signer: Option<Box<dyn RequestSigner>>,
+}
+
+impl<T: OtlpTransport> OtlpExporter<T> {
+    /// Creates an exporter of `signal` over `transport`.
+    pub fn new(transport: T, signal: Signal) -> OtlpExporter<T> {
+        OtlpExporter {
+            transport,
+            signal,
+            scope: "sgx_tstd".to_owned(),
+            resource: Vec::new(),
+            headers: Vec::new(),
+            signer: None,
+        }
+    }
+
+    /// Creates an exporter of log records over `transport`.
+    pub fn logs(transport: T) -> OtlpExporter<T> {
+        OtlpExporter::new(transport, Signal::Logs)
+    }
+
+    /// Creates an exporter of metric points over `transport`.
+    pub fn metrics(transport: T) -> OtlpExporter<T> {
+        OtlpExporter::new(transport, Signal::Metrics)
+    }
+
+    /// Adds an attribute of the resource, such as `service.name`.
+    pub fn resource_attribute(mut self, key: &str, value: &str) -> OtlpExporter<T> {
+        self.resource.push((key.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Sets the name of the instrumentation scope. Defaults to `sgx_tstd`.
+    pub fn scope(mut self, name: &str) -> OtlpExporter<T> {
+        self.scope = name.to_owned();
+        self
+    }
+
+    /// Adds a header to every request, for instance to authenticate to the
+    /// collector.
+    pub fn header(mut self, name: &str, value: &str) -> OtlpExporter<T> {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Signs the body of every request with `signer`.
+    pub fn signer<S: RequestSigner + 'static>(mut self, signer: S) -> OtlpExporter<T> {
+        self.signer = Some(Box::new(signer));
+        self
+    }
+
+    pub fn signal(&self) -> Signal {
+        self.signal
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Encodes `batch` as the JSON body of an export request, and returns it
+    /// with the number of records it holds.
+    pub fn encode(&self, batch: &Batch<'_>) -> (String, usize) {
+        let now = SystemTime::_now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos().min(u64::MAX as u128) as u64);
+
+        let mut out = String::with_capacity(batch.encoded().len() * 2 + 256);
+        let (outer, scoped, items) = match self.signal {
+            Signal::Logs => ("resourceLogs", "scopeLogs", "logRecords"),
+            Signal::Metrics => ("resourceMetrics", "scopeMetrics", "metrics"),
+        };
+        let _ = write!(out, "{{\"{}\":[{{\"resource\":", outer);
+        push_attributes(&mut out, &self.resource);
+        let _ = write!(out, ",\"{}\":[{{\"scope\":{{\"name\":", scoped);
+        push_string(&mut out, &self.scope);
+        let _ = write!(out, "}},\"{}\":[", items);
+
+        let mut count = 0;
+        for record in batch.iter() {
+            let start = out.len();
+            if count > 0 {
+                out.push(',');
+            }
+            let encoded = match self.signal {
+                Signal::Logs => {
+                    push_log(&mut out, record, now);
+                    true
+                }
+                Signal::Metrics => match Metric::decode(record) {
+                    Some(metric) => push_metric(&mut out, &metric, now),
+                    None => false,
+                },
+            };
+            if encoded {
+                count += 1;
+            } else {
+                out.truncate(start);
+            }
+        }
+        out.push_str("]}]}]}");
+        (out, count)
+    }
+}
+
+impl<T: OtlpTransport> Exporter for OtlpExporter<T> {
+    fn export(&mut self, batch: &Batch<'_>) -> io::Result<usize> {
+        let (body, count) = self.encode(batch);
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let mut headers = self.headers.clone();
+        headers.push(("content-type".to_owned(), "application/json".to_owned()));
+        if let Some(signer) = self.signer.as_mut() {
+            headers.push(signer.sign(body.as_bytes())?);
+        }
+        let status = self
+            .transport
+            .post(self.signal.path(), &headers, body.as_bytes())?;
+        if (200..300).contains(&status) {
+            Ok(count)
+        } else {
+            Err(io::const_io_error!(
+                io::ErrorKind::Other,
+                "the collector refused the export request",
+            ))
+        }
+    }
+}
+
+fn push_log(out: &mut String, record: &[u8], now: u64) {
+    let _ = write!(out, "{{\"observedTimeUnixNano\":\"{}\",\"body\":", now);
+    match str::from_utf8(record) {
+        Ok(text) => {
+            out.push_str("{\"stringValue\":");
+            push_string(out, text);
+        }
+        Err(_) => {
+            out.push_str("{\"bytesValue\":\"");
+            push_base64(out, record);
+            out.push('"');
+        }
+    }
+    out.push_str("}}");
+}
+
+// Returns false for a point JSON cannot carry.
+fn push_metric(out: &mut String, metric: &Metric, now: u64) -> bool {
+    let time = if metric.time_unix_nano == 0 {
+        now
+    } else {
+        metric.time_unix_nano
+    };
+    out.push_str("{\"name\":");
+    push_string(out, &metric.name);
+    if !metric.unit.is_empty() {
+        out.push_str(",\"unit\":");
+        push_string(out, &metric.unit);
+    }
+    match metric.kind {
+        MetricKind::Gauge => out.push_str(",\"gauge\":{\"dataPoints\":[{"),
+        MetricKind::Counter => out.push_str(
+            ",\"sum\":{\"aggregationTemporality\":2,\"isMonotonic\":true,\"dataPoints\":[{",
+        ),
+    }
+    out.push_str("\"attributes\":");
+    push_attribute_list(out, &metric.attributes);
+    let _ = write!(out, ",\"timeUnixNano\":\"{}\",", time);
+    match metric.value {
+        MetricValue::Int(v) => {
+            let _ = write!(out, "\"asInt\":\"{}\"", v);
+        }
+        MetricValue::Double(v) if v.is_finite() => {
+            let _ = write!(out, "\"asDouble\":{:?}", v);
+        }
+        MetricValue::Double(_) => return false,
+    }
+    out.push_str("}]}}");
+    true
+}
+
+fn push_attributes(out: &mut String, attributes: &[(String, String)]) {
+    out.push_str("{\"attributes\":");
+    push_attribute_list(out, attributes);
+    out.push('}');
+}
+
+fn push_attribute_list(out: &mut String, attributes: &[(String, String)]) {
+    out.push('[');
+    for (i, (key, value)) in attributes.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"key\":");
+        push_string(out, key);
+        out.push_str(",\"value\":{\"stringValue\":");
+        push_string(out, value);
+        out.push_str("}}");
+    }
+    out.push(']');
+}
+
+fn push_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn push_base64(out: &mut String, bytes: &[u8]) {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+}
@@ -0,0 +1,323 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Sockets for `async fn` code running on a [`task::Executor`].
+//!
+//! The sockets are nonblocking and registered with the reactor of the
+//! executors, so an operation that would block suspends the task instead of
+//! the thread.
+//!
+//! [`task::Executor`]: crate::task::Executor
+
+use crate::fmt;
+use crate::io::{self, ErrorKind, Read, Write};
+use crate::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use crate::os::unix::io::AsRawFd;
+use crate::task::reactor::{Direction, Registration};
+
+/// A TCP socket server, listening for connections.
+///
+/// This is the asynchronous counterpart of [`TcpListener`].
+pub struct AsyncTcpListener {
+    // Dropped before the socket it refers to.
+    registration: Registration,
+    inner: TcpListener,
+}
+
+impl AsyncTcpListener {
+    /// Creates a listener bound to the specified address.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<AsyncTcpListener> {
+        AsyncTcpListener::from_std(TcpListener::bind(addr)?)
+    }
+
+    /// Turns a bound listener into an asynchronous one, switching it to
+    /// nonblocking mode.
+    pub fn from_std(listener: TcpListener) -> io::Result<AsyncTcpListener> {
+        listener.set_nonblocking(true)?;
+        Ok(AsyncTcpListener {
+            registration: Registration::new(listener.as_raw_fd())?,
+            inner: listener,
+        })
+    }
+
+    /// Accepts a new incoming connection.
+    pub async fn accept(&self) -> io::Result<(AsyncTcpStream, SocketAddr)> {
+        let (stream, addr) = self
+            .registration
+            .io(Direction::Read, || self.inner.accept())
+            .await?;
+        Ok((AsyncTcpStream::from_std(stream)?, addr))
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.inner.take_error()
+    }
+}
+
+impl fmt::Debug for AsyncTcpListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+/// A TCP stream between a local and a remote socket.
+///
+/// This is the asynchronous counterpart of [`TcpStream`]. One task may read
+/// while another writes, but two tasks should not read, or write, at the
+/// same time.
+pub struct AsyncTcpStream {
+    // Dropped before the socket it refers to.
+    registration: Registration,
+    inner: TcpStream,
+}
+
+impl AsyncTcpStream {
+    /// Opens a TCP connection to a remote host.
+    ///
+    /// The addresses are resolved with a blocking OCALL. They are tried in
+    /// turn, and the error of the last one is returned if none accepts the
+    /// connection.
+    pub async fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<AsyncTcpStream> {
+        let mut last_err = None;
+        for addr in addr.to_socket_addrs()? {
+            match AsyncTcpStream::connect_addr(&addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::const_io_error!(
+                ErrorKind::InvalidInput,
+                "could not resolve to any addresses"
+            )
+        }))
+    }
+
+    async fn connect_addr(addr: &SocketAddr) -> io::Result<AsyncTcpStream> {
+        let socket = match addr {
+            SocketAddr::V4(_) => TcpStream::new_v4()?,
+            SocketAddr::V6(_) => TcpStream::new_v6()?,
+        };
+        let stream = AsyncTcpStream::from_std(socket)?;
+        match stream.inner.connect_socket(addr) {
+            Ok(()) => return Ok(stream),
+            Err(ref e) if e.raw_os_error() == Some(sgx_libc::EINPROGRESS) => {}
+            Err(e) => return Err(e),
+        }
+
+        // The socket becomes writable once the connection is established or
+        // has failed.
+        stream
+            .registration
+            .io(Direction::Write, || match stream.inner.take_error()? {
+                Some(e) => Err(e),
+                None => stream.inner.peer_addr().map(drop).map_err(|e| {
+                    if e.kind() == ErrorKind::NotConnected {
+                        ErrorKind::WouldBlock.into()
+                    } else {
+                        e
+                    }
+                }),
+            })
+            .await?;
+        Ok(stream)
+    }
+
+    /// Turns a connected stream into an asynchronous one, switching it to
+    /// nonblocking mode.
+    pub fn from_std(stream: TcpStream) -> io::Result<AsyncTcpStream> {
+        stream.set_nonblocking(true)?;
+        Ok(AsyncTcpStream {
+            registration: Registration::new(stream.as_raw_fd())?,
+            inner: stream,
+        })
+    }
+
+    /// Reads into `buf`, returning the number of bytes read. Returns 0 once
+    /// the peer has shut down its side.
+    pub async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.registration
+            .io(Direction::Read, || (&self.inner).read(buf))
+            .await
+    }
+
+    /// Fills `buf` completely.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`ErrorKind::UnexpectedEof`] if the stream ends first.
+    pub async fn read_exact(&self, mut buf: &mut [u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf).await? {
+                0 => {
+                    return Err(io::const_io_error!(
+                        ErrorKind::UnexpectedEof,
+                        "failed to fill whole buffer",
+                    ))
+                }
+                n => buf = &mut buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Receives data without removing it from the queue.
+    pub async fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.registration
+            .io(Direction::Read, || self.inner.peek(buf))
+            .await
+    }
+
+    /// Writes from `buf`, returning the number of bytes written.
+    pub async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        self.registration
+            .io(Direction::Write, || (&self.inner).write(buf))
+            .await
+    }
+
+    /// Writes all of `buf`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`ErrorKind::WriteZero`] if the stream stops accepting
+    /// data.
+    pub async fn write_all(&self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf).await? {
+                0 => {
+                    return Err(io::const_io_error!(
+                        ErrorKind::WriteZero,
+                        "failed to write whole buffer",
+                    ))
+                }
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+
+    pub fn nodelay(&self) -> io::Result<bool> {
+        self.inner.nodelay()
+    }
+
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.inner.take_error()
+    }
+}
+
+impl fmt::Debug for AsyncTcpStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+/// A UDP socket.
+///
+/// This is the asynchronous counterpart of [`UdpSocket`].
+pub struct AsyncUdpSocket {
+    // Dropped before the socket it refers to.
+    registration: Registration,
+    inner: UdpSocket,
+}
+
+impl AsyncUdpSocket {
+    /// Creates a UDP socket bound to the specified address.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<AsyncUdpSocket> {
+        AsyncUdpSocket::from_std(UdpSocket::bind(addr)?)
+    }
+
+    /// Turns a bound socket into an asynchronous one, switching it to
+    /// nonblocking mode.
+    pub fn from_std(socket: UdpSocket) -> io::Result<AsyncUdpSocket> {
+        socket.set_nonblocking(true)?;
+        Ok(AsyncUdpSocket {
+            registration: Registration::new(socket.as_raw_fd())?,
+            inner: socket,
+        })
+    }
+
+    /// Receives a datagram, returning its length and its source address.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.registration
+            .io(Direction::Read, || self.inner.recv_from(buf))
+            .await
+    }
+
+    /// Sends a datagram to `addr`.
+    pub async fn send_to(&self, buf: &[u8], addr: &SocketAddr) -> io::Result<usize> {
+        self.registration
+            .io(Direction::Write, || self.inner.send_to(buf, addr))
+            .await
+    }
+
+    /// Sets the peer of [`AsyncUdpSocket::send`] and [`AsyncUdpSocket::recv`].
+    pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> io::Result<()> {
+        self.inner.connect(addr)
+    }
+
+    /// Receives a datagram from the connected peer.
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.registration
+            .io(Direction::Read, || self.inner.recv(buf))
+            .await
+    }
+
+    /// Sends a datagram to the connected peer.
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.registration
+            .io(Direction::Write, || self.inner.send(buf))
+            .await
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.inner.take_error()
+    }
+}
+
+impl fmt::Debug for AsyncUdpSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
@@ -30,6 +30,10 @@
 //!   and [`SocketAddrV6`] are respectively IPv4 and IPv6 socket addresses
 //! * [`ToSocketAddrs`] is a trait that used for generic address resolution when interacting
 //!   with networking objects like [`TcpListener`], [`TcpStream`] or [`UdpSocket`]
+//! * [`Poll`] waits for readiness on many nonblocking sockets at once, so that a few
+//!   threads can serve many connections
+//! * [`BufferPool`] holds untrusted buffers that sockets receive into, so that only
+//!   accepted payloads are copied into the enclave
 //! * Other types are return or parameter types for various methods in this module
 //!
 //! Rust disables inheritance of socket objects to child processes by default when possible.  For
@@ -38,23 +42,35 @@
 
 use crate::io::{self, ErrorKind};
 
+#[cfg(tstd_net)]
+pub use self::async_socket::{AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket};
 pub use self::ip_addr::{IpAddr, Ipv4Addr, Ipv6Addr, Ipv6MulticastScope};
 pub use self::parser::AddrParseError;
+#[cfg(tstd_net)]
+pub use self::poll::{Event, Events, Interest, Iter as EventsIter, Poll, Token};
+#[cfg(tstd_net)]
+pub use self::pool::{BufferPool, PooledBuf};
 pub use self::socket_addr::{SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs};
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 pub use self::tcp::IntoIncoming;
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 pub use self::tcp::{Incoming, TcpListener, TcpStream};
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 pub use self::udp::UdpSocket;
 
+#[cfg(tstd_net)]
+mod async_socket;
 mod display_buffer;
 mod ip_addr;
 mod parser;
+#[cfg(tstd_net)]
+mod poll;
+#[cfg(tstd_net)]
+mod pool;
 mod socket_addr;
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 mod tcp;
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 mod udp;
 
 /// Possible values which can be passed to the [`TcpStream::shutdown`] method.
@@ -0,0 +1,339 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Readiness polling of nonblocking sockets.
+//!
+//! A [`Poll`] wraps an epoll instance of the host, so that a thread of the
+//! enclave can wait on many nonblocking sockets at once and serve whichever
+//! are ready, instead of holding a TCS per connection. Each wait is a single
+//! OCALL, whatever the number of sockets registered.
+//!
+//! Sockets are registered level-triggered: a socket is reported for as long
+//! as it is readable or writable. The events are written by the host, which
+//! can report a socket that is not ready or withhold one that is; callers
+//! must treat an event as a hint, and retry an operation that fails with
+//! [`io::ErrorKind::WouldBlock`] after the next one.
+
+use crate::cmp;
+use crate::fmt;
+use crate::io;
+use crate::ops::{BitOr, BitOrAssign};
+use crate::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use crate::slice;
+use crate::sys::cvt;
+use crate::task::reactor::timeout_ms;
+use crate::time::Duration;
+use crate::vec::Vec;
+
+use sgx_libc::{c_int, epoll_event};
+
+/// An identifier of a registered socket, given back in its events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Token(pub usize);
+
+/// The readiness a socket is registered for.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Interest(u32);
+
+impl Interest {
+    /// Readiness for reading, or for accepting on a listener.
+    pub const READABLE: Interest = Interest((libc::EPOLLIN | libc::EPOLLRDHUP) as u32);
+    /// Readiness for writing, or for the completion of a nonblocking connect.
+    pub const WRITABLE: Interest = Interest(libc::EPOLLOUT as u32);
+
+    /// Returns `true` if the interest includes readability.
+    pub fn is_readable(self) -> bool {
+        self.0 & libc::EPOLLIN as u32 != 0
+    }
+
+    /// Returns `true` if the interest includes writability.
+    pub fn is_writable(self) -> bool {
+        self.0 & libc::EPOLLOUT as u32 != 0
+    }
+}
+
+impl BitOr for Interest {
+    type Output = Interest;
+
+    fn bitor(self, other: Interest) -> Interest {
+        Interest(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for Interest {
+    fn bitor_assign(&mut self, other: Interest) {
+        self.0 |= other.0;
+    }
+}
+
+impl fmt::Debug for Interest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interest")
+            .field("readable", &self.is_readable())
+            .field("writable", &self.is_writable())
+            .finish()
+    }
+}
+
+/// The readiness of a socket, as reported by [`Poll::poll`].
+#[derive(Clone, Copy)]
+pub struct Event(epoll_event);
+
+impl Event {
+    /// Returns the token the socket was registered with.
+    pub fn token(&self) -> Token {
+        Token(self.0.u64 as usize)
+    }
+
+    /// Returns `true` if the socket can be read from, or has hung up.
+    pub fn is_readable(&self) -> bool {
+        self.0.events & (libc::EPOLLIN | libc::EPOLLPRI | libc::EPOLLRDHUP | libc::EPOLLHUP) as u32
+            != 0
+    }
+
+    /// Returns `true` if the socket can be written to.
+    pub fn is_writable(&self) -> bool {
+        self.0.events & libc::EPOLLOUT as u32 != 0
+    }
+
+    /// Returns `true` if the socket has a pending error, which
+    /// `take_error` retrieves.
+    pub fn is_error(&self) -> bool {
+        self.0.events & libc::EPOLLERR as u32 != 0
+    }
+
+    /// Returns `true` if the peer has shut down its writing half, or the
+    /// connection is closed.
+    pub fn is_read_closed(&self) -> bool {
+        self.0.events & (libc::EPOLLRDHUP | libc::EPOLLHUP) as u32 != 0
+    }
+
+    /// Returns `true` if the connection is closed for writing.
+    pub fn is_write_closed(&self) -> bool {
+        self.0.events & (libc::EPOLLHUP | libc::EPOLLERR) as u32 != 0
+    }
+}
+
+impl fmt::Debug for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Event")
+            .field("token", &self.token())
+            .field("readable", &self.is_readable())
+            .field("writable", &self.is_writable())
+            .field("error", &self.is_error())
+            .finish()
+    }
+}
+
+/// A buffer of events, filled by [`Poll::poll`].
+pub struct Events {
+    buf: Vec<epoll_event>,
+    len: usize,
+}
+
+impl Events {
+    /// Creates a buffer that receives up to `capacity` events per poll.
+    pub fn with_capacity(capacity: usize) -> Events {
+        let capacity = capacity.clamp(1, c_int::MAX as usize);
+        Events {
+            buf: vec![epoll_event { events: 0, u64: 0 }; capacity],
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Returns an iterator over the events of the last poll.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.buf[..self.len].iter(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Events {
+    type Item = Event;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// An iterator over the events of an [`Events`].
+pub struct Iter<'a> {
+    inner: slice::Iter<'a, epoll_event>,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        self.inner.next().map(|ev| Event(*ev))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Waits for readiness events on a set of sockets.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::net::{Events, Interest, Poll, TcpListener, Token};
+///
+/// let listener = TcpListener::bind("127.0.0.1:8080")?;
+/// listener.set_nonblocking(true)?;
+///
+/// let poll = Poll::new()?;
+/// poll.register(&listener, Token(0), Interest::READABLE)?;
+///
+/// let mut events = Events::with_capacity(256);
+/// loop {
+///     poll.poll(&mut events, None)?;
+///     for event in &events {
+///         if event.token() == Token(0) {
+///             // Accept until `accept` fails with `WouldBlock`.
+///         }
+///     }
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Poll {
+    epoll: OwnedFd,
+}
+
+impl Poll {
+    /// Creates an epoll instance on the host.
+    pub fn new() -> io::Result<Poll> {
+        let epoll = cvt(unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) })?;
+        Ok(Poll {
+            epoll: unsafe { OwnedFd::from_raw_fd(epoll) },
+        })
+    }
+
+    /// Registers a socket for the readiness of `interest`.
+    ///
+    /// The socket should be in nonblocking mode, and must be deregistered
+    /// before it is closed.
+    pub fn register<S: AsRawFd + ?Sized>(
+        &self,
+        source: &S,
+        token: Token,
+        interest: Interest,
+    ) -> io::Result<()> {
+        self.ctl(libc::EPOLL_CTL_ADD, source.as_raw_fd(), token, interest)
+    }
+
+    /// Changes the token or the interest of a registered socket.
+    pub fn reregister<S: AsRawFd + ?Sized>(
+        &self,
+        source: &S,
+        token: Token,
+        interest: Interest,
+    ) -> io::Result<()> {
+        self.ctl(libc::EPOLL_CTL_MOD, source.as_raw_fd(), token, interest)
+    }
+
+    /// Removes a socket from the set.
+    pub fn deregister<S: AsRawFd + ?Sized>(&self, source: &S) -> io::Result<()> {
+        let mut ev = epoll_event { events: 0, u64: 0 };
+        cvt(unsafe {
+            libc::epoll_ctl(
+                self.epoll.as_raw_fd(),
+                libc::EPOLL_CTL_DEL,
+                source.as_raw_fd(),
+                &mut ev,
+            )
+        })
+        .map(drop)
+    }
+
+    /// Waits for at most `timeout`, or indefinitely if it is `None`, until
+    /// one of the sockets is ready, and fills `events` with the ready ones.
+    ///
+    /// `events` is left empty when the timeout expires. A wait interrupted by
+    /// a signal of the host fails with [`io::ErrorKind::Interrupted`].
+    pub fn poll(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<()> {
+        events.clear();
+        let n = cvt(unsafe {
+            libc::epoll_wait(
+                self.epoll.as_raw_fd(),
+                events.buf.as_mut_ptr(),
+                events.buf.len() as c_int,
+                timeout_ms(timeout),
+            )
+        })?;
+        events.len = cmp::min(n as usize, events.buf.len());
+        Ok(())
+    }
+
+    fn ctl(&self, op: c_int, fd: RawFd, token: Token, interest: Interest) -> io::Result<()> {
+        let mut ev = epoll_event {
+            events: interest.0,
+            u64: token.0 as u64,
+        };
+        cvt(unsafe { libc::epoll_ctl(self.epoll.as_raw_fd(), op, fd, &mut ev) }).map(drop)
+    }
+}
+
+impl AsRawFd for Poll {
+    fn as_raw_fd(&self) -> RawFd {
+        self.epoll.as_raw_fd()
+    }
+}
+
+impl AsFd for Poll {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.epoll.as_fd()
+    }
+}
+
+impl fmt::Debug for Poll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Poll")
+            .field("epoll", &self.epoll.as_raw_fd())
+            .finish()
+    }
+}
+
+mod libc {
+    pub use sgx_libc::ocall::{epoll_create1, epoll_ctl, epoll_wait};
+    pub use sgx_libc::*;
+}
@@ -0,0 +1,252 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Reusable untrusted receive buffers.
+//!
+//! A plain `read` of a socket goes through a bounce buffer: the host receives
+//! into memory outside the enclave, allocated for the call (with an extra
+//! pair of OCALLs past 16 KiB), and the whole buffer is then copied in,
+//! whether the enclave wants the bytes or not. A [`BufferPool`] allocates its
+//! buffers outside the enclave once, checks once that they lie there, and
+//! hands them to the host to receive into. The enclave then looks at what
+//! arrived and copies in only the payloads it accepts, a range at a time.
+//!
+//! The bytes of a [`PooledBuf`] stay in host memory, where the host can
+//! change them at any time. They are only ever read by copying them into the
+//! enclave, and a copy is what must be checked, never the buffer itself.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::net::{BufferPool, TcpStream};
+//!
+//! let pool = BufferPool::new(64, 16 * 1024)?;
+//! let stream = TcpStream::connect("127.0.0.1:8080")?;
+//! let buf = stream.recv_pooled(&pool)?;
+//! let mut header = [0_u8; 4];
+//! buf.copy_to(0, &mut header)?;
+//! if accepts(&header) {
+//!     let payload = buf.to_untrusted(4..buf.len())?;
+//!     handle(payload.validate()?);
+//! }
+//! ```
+
+use crate::fmt;
+use crate::io;
+use crate::ops::Range;
+use crate::ptr;
+use crate::sync::{Arc, PoisonError, SgxMutex};
+use crate::sys::net::Socket;
+use crate::taint::Untrusted;
+use crate::vec::Vec;
+use sgx_libc::c_void;
+
+struct Inner {
+    base: *mut u8,
+    buf_size: usize,
+    count: usize,
+    free: SgxMutex<Vec<usize>>,
+}
+
+// The buffers are plain bytes in host memory, handed out to one owner at a
+// time through `free`.
+unsafe impl Send for Inner {}
+unsafe impl Sync for Inner {}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        unsafe { sgx_libc::ocall::free(self.base as *mut c_void) };
+    }
+}
+
+/// A fixed set of equal buffers outside the enclave, for sockets to receive
+/// into.
+#[derive(Clone)]
+pub struct BufferPool {
+    inner: Arc<Inner>,
+}
+
+impl BufferPool {
+    /// Allocates `count` buffers of `buf_size` bytes outside the enclave, in
+    /// one block.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if either number is 0 or
+    /// their product overflows, and with the error of the allocation OCALL,
+    /// which also fails if the host returns memory inside the enclave.
+    pub fn new(count: usize, buf_size: usize) -> io::Result<BufferPool> {
+        let total = match count.checked_mul(buf_size) {
+            Some(total) if total > 0 => total,
+            _ => {
+                return Err(io::const_io_error!(
+                    io::ErrorKind::InvalidInput,
+                    "the buffer pool must hold buffers of at least a byte",
+                ))
+            }
+        };
+        let base = unsafe { sgx_libc::ocall::malloc(total) } as *mut u8;
+        if base.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(BufferPool {
+            inner: Arc::new(Inner {
+                base,
+                buf_size,
+                count,
+                free: SgxMutex::new((0..count).rev().collect()),
+            }),
+        })
+    }
+
+    /// Returns the number of buffers of the pool.
+    pub fn capacity(&self) -> usize {
+        self.inner.count
+    }
+
+    /// Returns the size of each buffer.
+    pub fn buf_size(&self) -> usize {
+        self.inner.buf_size
+    }
+
+    /// Returns the number of buffers not in use.
+    pub fn available(&self) -> usize {
+        self.inner
+            .free
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Takes an empty buffer out of the pool, if one is left. It goes back
+    /// when dropped.
+    pub fn acquire(&self) -> Option<PooledBuf> {
+        let index = self
+            .inner
+            .free
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop()?;
+        Some(PooledBuf {
+            pool: self.inner.clone(),
+            index,
+            len: 0,
+        })
+    }
+
+    pub(crate) fn recv(&self, socket: &Socket) -> io::Result<PooledBuf> {
+        let mut buf = self.acquire().ok_or_else(|| {
+            io::const_io_error!(io::ErrorKind::OutOfMemory, "the buffer pool is exhausted")
+        })?;
+        buf.len = unsafe { socket.recv_untrusted(buf.as_ptr(), self.inner.buf_size)? };
+        Ok(buf)
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("capacity", &self.capacity())
+            .field("buf_size", &self.buf_size())
+            .field("available", &self.available())
+            .finish()
+    }
+}
+
+/// A buffer of a [`BufferPool`], holding the bytes of one receive.
+pub struct PooledBuf {
+    pool: Arc<Inner>,
+    index: usize,
+    len: usize,
+}
+
+impl PooledBuf {
+    fn as_ptr(&self) -> *mut u8 {
+        unsafe { self.pool.base.add(self.index * self.pool.buf_size) }
+    }
+
+    /// Returns the number of bytes received.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn check(&self, range: &Range<usize>) -> io::Result<()> {
+        if range.start > range.end || range.end > self.len {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "range out of the received bytes",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Copies the received bytes from `offset` on into `dst`, which must be
+    /// filled.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if fewer bytes were
+    /// received.
+    pub fn copy_to(&self, offset: usize, dst: &mut [u8]) -> io::Result<()> {
+        let end = offset.checked_add(dst.len()).ok_or_else(|| {
+            io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "range out of the received bytes"
+            )
+        })?;
+        self.check(&(offset..end))?;
+        unsafe { ptr::copy_nonoverlapping(self.as_ptr().add(offset), dst.as_mut_ptr(), dst.len()) };
+        Ok(())
+    }
+
+    /// Copies a range of the received bytes into the enclave, tagged as
+    /// supplied by the host.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the range is out of the
+    /// received bytes, and with [`io::ErrorKind::OutOfMemory`] if the copy
+    /// cannot be allocated.
+    pub fn to_untrusted(&self, range: Range<usize>) -> io::Result<Untrusted<Vec<u8>>> {
+        self.check(&range)?;
+        unsafe { Untrusted::copy_from_host(self.as_ptr().add(range.start), range.len()) }
+    }
+}
+
+impl Drop for PooledBuf {
+    fn drop(&mut self) {
+        self.pool
+            .free
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(self.index);
+    }
+}
+
+impl fmt::Debug for PooledBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The bytes are host data, and are not formatted.
+        f.debug_struct("PooledBuf")
+            .field("index", &self.index)
+            .field("len", &self.len)
+            .finish()
+    }
+}
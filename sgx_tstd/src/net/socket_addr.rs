@@ -18,7 +18,7 @@
 #![allow(clippy::derive_hash_xor_eq)]
 
 use crate::cmp::Ordering;
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 use crate::convert::TryInto;
 use crate::fmt::{self, Write};
 use crate::hash;
@@ -28,7 +28,7 @@ use crate::mem;
 use crate::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use crate::option;
 use crate::slice;
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 use crate::sys_common::net::LookupHost;
 use crate::sys_common::{FromInner, IntoInner};
 use crate::vec;
@@ -854,7 +854,7 @@ impl ToSocketAddrs for (Ipv6Addr, u16) {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 #[allow(clippy::unnecessary_wraps, clippy::needless_collect)]
 fn resolve_socket_addr(lh: LookupHost) -> io::Result<vec::IntoIter<SocketAddr>> {
     let p = lh.port();
@@ -882,9 +882,9 @@ impl ToSocketAddrs for (&str, u16) {
             return Ok(vec![SocketAddr::V6(addr)].into_iter());
         }
 
-        #[cfg(not(feature = "net"))]
+        #[cfg(not(tstd_net))]
         let r = Err(io::const_io_error!(io::ErrorKind::InvalidInput, "invalid socket address"));
-        #[cfg(feature = "net")]
+        #[cfg(tstd_net)]
         let r = resolve_socket_addr((host, port).try_into()?);
         r
     }
@@ -906,9 +906,9 @@ impl ToSocketAddrs for str {
             return Ok(vec![addr].into_iter());
         }
 
-        #[cfg(not(feature = "net"))]
+        #[cfg(not(tstd_net))]
         let r = Err(io::const_io_error!(io::ErrorKind::InvalidInput, "invalid socket address"));
-        #[cfg(feature = "net")]
+        #[cfg(tstd_net)]
         let r = resolve_socket_addr(self.try_into()?);
         r
     }
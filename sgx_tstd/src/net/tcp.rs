@@ -20,7 +20,7 @@ use crate::io::prelude::*;
 use crate::fmt;
 use crate::io::{self, IoSlice, IoSliceMut};
 use crate::iter::FusedIterator;
-use crate::net::{Shutdown, SocketAddr, ToSocketAddrs};
+use crate::net::{BufferPool, PooledBuf, Shutdown, SocketAddr, ToSocketAddrs};
 use crate::sys_common::net as net_imp;
 use crate::sys_common::{AsInner, FromInner, IntoInner};
 use crate::time::Duration;
@@ -457,6 +457,18 @@ impl TcpStream {
         self.0.peek(buf)
     }
 
+    /// Receives data into a buffer of `pool`, outside the enclave, returning
+    /// the buffer. Nothing is copied into the enclave until the data is
+    /// taken out of the buffer.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::OutOfMemory`] if every buffer of the pool
+    /// is in use, besides the errors of `read`.
+    pub fn recv_pooled(&self, pool: &BufferPool) -> io::Result<PooledBuf> {
+        pool.recv(self.0.socket())
+    }
+
     /// Sets the value of the `SO_LINGER` option on this socket.
     ///
     /// This value controls how the socket is closed when data remains
@@ -17,7 +17,7 @@
 
 use crate::fmt;
 use crate::io::{self, ErrorKind};
-use crate::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use crate::net::{BufferPool, Ipv4Addr, Ipv6Addr, PooledBuf, SocketAddr, ToSocketAddrs};
 use crate::sys_common::net as net_imp;
 use crate::sys_common::{AsInner, FromInner, IntoInner};
 use crate::time::Duration;
@@ -737,6 +737,18 @@ impl UdpSocket {
         self.0.peek(buf)
     }
 
+    /// Receives a single datagram from the connected remote address into a
+    /// buffer of `pool`, outside the enclave, returning the buffer. A
+    /// datagram longer than the buffers is cut short.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::OutOfMemory`] if every buffer of the pool
+    /// is in use, besides the errors of `recv`.
+    pub fn recv_pooled(&self, pool: &BufferPool) -> io::Result<PooledBuf> {
+        pool.recv(self.0.socket())
+    }
+
     /// Moves this UDP socket into or out of nonblocking mode.
     ///
     /// This will result in `recv`, `recv_from`, `send`, and `send_to`
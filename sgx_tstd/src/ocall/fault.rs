@@ -0,0 +1,372 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Fault injection for the OCALLs that carry data in from the host.
+//!
+//! A malicious or merely flaky host can fail any OCALL, answer it late, or
+//! move fewer bytes than asked. [`install`] puts a [`FaultInjector`] in front
+//! of the routed OCALLs (`read`, `write`, `recv`, `send`, `clock_gettime` and
+//! `epoll_wait`) that does so at random, following the rules of a [`Faults`]
+//! plan:
+//!
+//! * [`fail`](Faults::fail) returns an `errno` without making the OCALL.
+//! * [`delay`](Faults::delay) sleeps outside the enclave before the OCALL.
+//! * [`truncate`](Faults::truncate) passes a shorter buffer to the OCALL,
+//!   giving short reads and writes, and fewer epoll events.
+//!
+//! The choices come from a generator seeded by the plan, so a failing run can
+//! be repeated with the same seed, as long as its threads make their calls in
+//! the same order. The module is only built with the `fault_injection`
+//! feature, which is meant for test builds of an enclave.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use std::ocall::fault::{self, Faults};
+//! use std::ocall::CallKind;
+//! use std::time::Duration;
+//!
+//! let faults = Faults::new()
+//!     .seed(7)
+//!     .fail(&[CallKind::Recv, CallKind::Send], 0.05, libc::ECONNRESET)
+//!     .delay(&[CallKind::EpollWait], 0.1, Duration::from_millis(20))
+//!     .truncate(&[CallKind::Read, CallKind::Recv], 0.5);
+//! let injector = fault::install(faults)?;
+//!
+//! run_server();
+//! println!("{} faults injected", injector.injected());
+//! ```
+
+use super::CallKind;
+use crate::boxed::Box;
+use crate::fmt;
+use crate::io;
+use crate::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use crate::time::Duration;
+use crate::vec::Vec;
+
+use sgx_libc::host::{self, HostCalls, Native};
+use sgx_libc::{c_int, clockid_t, epoll_event, time_t, timespec};
+
+/// What an injected fault does to a call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Fails the call with the `errno`.
+    Errno(c_int),
+    /// Delays the call.
+    Delay(Duration),
+    /// Shortens the buffer of the call.
+    Truncate,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    calls: u8,
+    probability: f64,
+    fault: Fault,
+}
+
+/// The plan of a [`FaultInjector`]: the faults to inject, and how often.
+///
+/// Every rule applies to each call of its kinds on its own, with its own
+/// probability. The delays of a call add up, its buffer is truncated once,
+/// and the first `errno` drawn fails it.
+#[derive(Clone, Debug, Default)]
+pub struct Faults {
+    seed: u64,
+    rules: Vec<Rule>,
+}
+
+impl Faults {
+    /// Creates a plan without faults, seeded with 0.
+    pub fn new() -> Faults {
+        Faults::default()
+    }
+
+    /// Sets the seed of the random choices.
+    pub fn seed(mut self, seed: u64) -> Faults {
+        self.seed = seed;
+        self
+    }
+
+    /// Fails the `calls` with `errno`, each with the given probability.
+    pub fn fail(self, calls: &[CallKind], probability: f64, errno: c_int) -> Faults {
+        self.rule(calls, probability, Fault::Errno(errno))
+    }
+
+    /// Delays the `calls` by `delay`, each with the given probability.
+    pub fn delay(self, calls: &[CallKind], probability: f64, delay: Duration) -> Faults {
+        self.rule(calls, probability, Fault::Delay(delay))
+    }
+
+    /// Truncates the buffers of the `calls` to a random non-zero length,
+    /// each with the given probability. `clock_gettime` has no buffer and is
+    /// never truncated.
+    pub fn truncate(self, calls: &[CallKind], probability: f64) -> Faults {
+        self.rule(calls, probability, Fault::Truncate)
+    }
+
+    /// Injects `fault` into the `calls`, each with the given probability,
+    /// which is clamped to `[0, 1]`.
+    pub fn rule(mut self, calls: &[CallKind], probability: f64, fault: Fault) -> Faults {
+        let probability = if probability.is_nan() {
+            0.0
+        } else {
+            probability.clamp(0.0, 1.0)
+        };
+        self.rules.push(Rule {
+            calls: calls.iter().fold(0, |mask, call| mask | bit(*call)),
+            probability,
+            fault,
+        });
+        self
+    }
+}
+
+pub(super) fn bit(call: CallKind) -> u8 {
+    1 << (call as u8)
+}
+
+// SplitMix64, stepped atomically so that concurrent calls draw distinct
+// values.
+pub(super) struct Rng(AtomicU64);
+
+impl Rng {
+    pub(super) fn new(seed: u64) -> Rng {
+        Rng(AtomicU64::new(seed))
+    }
+
+    pub(super) fn reseed(&self, seed: u64) {
+        self.0.store(seed, Ordering::Relaxed);
+    }
+
+    pub(super) fn next_u64(&self) -> u64 {
+        let mut z = self
+            .0
+            .fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)
+            .wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, n), for n > 0.
+    pub(super) fn below(&self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub(super) fn hit(&self, probability: f64) -> bool {
+        if probability >= 1.0 {
+            return true;
+        }
+        // 53 random bits, uniform in [0, 1).
+        ((self.next_u64() >> 11) as f64) * (1.0 / (1_u64 << 53) as f64) < probability
+    }
+
+    pub(super) fn fill(&self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+// The faults drawn for one call.
+#[derive(Default)]
+struct Plan {
+    errno: Option<c_int>,
+    delay: Duration,
+    truncate: bool,
+}
+
+/// The `HostCalls` backend that injects faults into the calls it passes on.
+pub struct FaultInjector {
+    faults: Faults,
+    inner: &'static dyn HostCalls,
+    rng: Rng,
+    enabled: AtomicBool,
+    injected: AtomicU64,
+}
+
+impl FaultInjector {
+    /// Creates an injector that passes the calls on to `inner`.
+    pub fn new(faults: Faults, inner: &'static dyn HostCalls) -> FaultInjector {
+        FaultInjector {
+            rng: Rng::new(faults.seed),
+            faults,
+            inner,
+            enabled: AtomicBool::new(true),
+            injected: AtomicU64::new(0),
+        }
+    }
+
+    /// Turns the injection on or off. The calls made while it is off are
+    /// passed on unchanged and draw nothing from the generator.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether the injection is on.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of faults injected so far.
+    pub fn injected(&self) -> u64 {
+        self.injected.load(Ordering::Relaxed)
+    }
+
+    fn plan(&self, call: CallKind) -> Plan {
+        let mut plan = Plan::default();
+        if !self.is_enabled() {
+            return plan;
+        }
+        for rule in &self.faults.rules {
+            if rule.calls & bit(call) == 0 || !self.rng.hit(rule.probability) {
+                continue;
+            }
+            match rule.fault {
+                Fault::Errno(errno) => {
+                    if plan.errno.is_none() {
+                        plan.errno = Some(errno);
+                    }
+                }
+                Fault::Delay(delay) => plan.delay = plan.delay.saturating_add(delay),
+                Fault::Truncate => plan.truncate = true,
+            }
+            self.injected.fetch_add(1, Ordering::Relaxed);
+        }
+        if !plan.delay.is_zero() {
+            sleep(plan.delay);
+        }
+        plan
+    }
+
+    // The length a buffer of `len` elements is truncated to.
+    fn truncated(&self, plan: &Plan, len: usize) -> usize {
+        if plan.truncate && len > 1 {
+            1 + self.rng.below(len - 1)
+        } else {
+            len
+        }
+    }
+}
+
+fn sleep(delay: Duration) {
+    let mut ts = timespec {
+        tv_sec: delay.as_secs().min(time_t::MAX as u64) as time_t,
+        tv_nsec: delay.subsec_nanos() as _,
+    };
+    let ts_ptr = &mut ts as *mut timespec;
+    // An interrupted sleep is as good a delay as any.
+    unsafe { sgx_libc::ocall::nanosleep(ts_ptr, ts_ptr) };
+}
+
+impl HostCalls for FaultInjector {
+    fn read(&self, fd: c_int, buf: &mut [u8]) -> Result<usize, c_int> {
+        let plan = self.plan(CallKind::Read);
+        if let Some(errno) = plan.errno {
+            return Err(errno);
+        }
+        let len = self.truncated(&plan, buf.len());
+        self.inner.read(fd, &mut buf[..len])
+    }
+
+    fn write(&self, fd: c_int, buf: &[u8]) -> Result<usize, c_int> {
+        let plan = self.plan(CallKind::Write);
+        if let Some(errno) = plan.errno {
+            return Err(errno);
+        }
+        let len = self.truncated(&plan, buf.len());
+        self.inner.write(fd, &buf[..len])
+    }
+
+    fn recv(&self, sockfd: c_int, buf: &mut [u8], flags: c_int) -> Result<usize, c_int> {
+        let plan = self.plan(CallKind::Recv);
+        if let Some(errno) = plan.errno {
+            return Err(errno);
+        }
+        let len = self.truncated(&plan, buf.len());
+        self.inner.recv(sockfd, &mut buf[..len], flags)
+    }
+
+    fn send(&self, sockfd: c_int, buf: &[u8], flags: c_int) -> Result<usize, c_int> {
+        let plan = self.plan(CallKind::Send);
+        if let Some(errno) = plan.errno {
+            return Err(errno);
+        }
+        let len = self.truncated(&plan, buf.len());
+        self.inner.send(sockfd, &buf[..len], flags)
+    }
+
+    fn clock_gettime(&self, clk_id: clockid_t) -> Result<timespec, c_int> {
+        let plan = self.plan(CallKind::ClockGettime);
+        if let Some(errno) = plan.errno {
+            return Err(errno);
+        }
+        self.inner.clock_gettime(clk_id)
+    }
+
+    fn epoll_wait(
+        &self,
+        epfd: c_int,
+        events: &mut [epoll_event],
+        timeout: c_int,
+    ) -> Result<usize, c_int> {
+        let plan = self.plan(CallKind::EpollWait);
+        if let Some(errno) = plan.errno {
+            return Err(errno);
+        }
+        let len = self.truncated(&plan, events.len());
+        self.inner.epoll_wait(epfd, &mut events[..len], timeout)
+    }
+}
+
+impl fmt::Debug for FaultInjector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FaultInjector")
+            .field("faults", &self.faults)
+            .field("enabled", &self.is_enabled())
+            .field("injected", &self.injected())
+            .finish()
+    }
+}
+
+/// Starts injecting the `faults` into the routed OCALLs.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::AlreadyExists`] if a backend is installed
+/// already.
+pub fn install(faults: Faults) -> io::Result<&'static FaultInjector> {
+    if host::host_calls().is_some() {
+        return Err(installed());
+    }
+    let injector: &'static FaultInjector = Box::leak(Box::new(FaultInjector::new(faults, &Native)));
+    if host::set_host_calls(injector) {
+        Ok(injector)
+    } else {
+        Err(installed())
+    }
+}
+
+pub(super) fn installed() -> io::Error {
+    io::const_io_error!(
+        io::ErrorKind::AlreadyExists,
+        "an OCALL backend is installed already",
+    )
+}
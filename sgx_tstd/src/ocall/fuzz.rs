@@ -0,0 +1,451 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! A fuzzer of the answers the host gives to OCALLs.
+//!
+//! Iago attacks do not break into the enclave directly: the host answers an
+//! OCALL with a value the enclave did not expect, such as a `read` of more
+//! bytes than asked, a clock running backwards or an epoll event for a
+//! descriptor never registered, and waits for the enclave to misuse it. The
+//! [`Fuzzer`] backend answers the routed OCALLs (`read`, `write`, `recv`,
+//! `send`, `clock_gettime` and `epoll_wait`) with such values, drawn from the
+//! whole range of their types, with a bias towards the edges: zero, one, the
+//! buffer length and one past it, the largest values, and out of range time
+//! fields. Mutated calls never reach the host; the buffers they fill get
+//! random bytes.
+//!
+//! [`Fuzzer::run`] calls a target once per iteration, each with a seed of its
+//! own, and reports as a [`Finding`] every iteration where the target
+//! panicked or the invariant check given with it failed, together with the
+//! answers the fuzzer made up. [`Fuzzer::run_one`] repeats an iteration from
+//! its seed. A panic is not a memory safety bug in itself, but it is one the
+//! host can trigger at will; out of bounds accesses and other undefined
+//! behavior that a mutation causes show up as aborts of the enclave. To tell
+//! which iteration aborted, [`Fuzzer::run_with`] passes the iteration and its
+//! seed to a callback before running it, and the callback can write them out
+//! of the enclave.
+//!
+//! The results the fuzzer answers are checked by `sgx_libc` like those of
+//! any backend, so they never claim more bytes than the buffer of the call.
+//! The module is built with the `fault_injection` feature.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use std::ocall::fuzz;
+//! use std::ocall::CallKind;
+//!
+//! let fuzzer = fuzz::install(0.2, &CallKind::ALL)?;
+//! let report = fuzzer.run(1, 10_000, || handle_request(&mut session), || session.check());
+//! for finding in &report.findings {
+//!     println!("{:?}", finding);
+//! }
+//! assert!(report.findings.is_empty());
+//! ```
+
+use super::fault::{bit, installed, Rng};
+use super::CallKind;
+use crate::any::Any;
+use crate::boxed::Box;
+use crate::fmt;
+use crate::io;
+use crate::panic::{catch_unwind, AssertUnwindSafe};
+use crate::string::{String, ToString};
+use crate::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use crate::sync::{PoisonError, SgxMutex};
+use crate::vec::Vec;
+
+use sgx_libc::host::{self, HostCalls, Native};
+use sgx_libc::{c_int, clockid_t, epoll_event, timespec};
+
+// The answers kept for a finding, counting from the start of the iteration.
+const MAX_MUTATIONS: usize = 64;
+
+const ERRNOS: [c_int; 10] = [
+    0,
+    -1,
+    c_int::MIN,
+    c_int::MAX,
+    sgx_libc::EINTR,
+    sgx_libc::EAGAIN,
+    sgx_libc::EINVAL,
+    sgx_libc::EBADF,
+    sgx_libc::EFAULT,
+    sgx_libc::ENOMEM,
+];
+
+/// An answer the fuzzer made up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Answer {
+    /// A byte or event count.
+    Size(usize),
+    /// A failure with this `errno`.
+    Errno(c_int),
+    /// The time of a `clock_gettime`.
+    Time { sec: i64, nsec: i64 },
+}
+
+/// A call the fuzzer answered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mutation {
+    /// The call answered.
+    pub call: CallKind,
+    /// Its length, in bytes or events.
+    pub len: usize,
+    /// The answer given.
+    pub answer: Answer,
+}
+
+/// How an iteration went wrong.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FindingKind {
+    /// The target panicked.
+    Panic,
+    /// The invariant check failed.
+    Invariant,
+}
+
+/// An iteration that went wrong.
+#[derive(Clone, Debug)]
+pub struct Finding {
+    /// The seed of the iteration, for [`Fuzzer::run_one`].
+    pub seed: u64,
+    /// The index of the iteration in [`Fuzzer::run`], 0 for
+    /// [`Fuzzer::run_one`].
+    pub iteration: u64,
+    pub kind: FindingKind,
+    /// The panic message, or the error of the invariant check.
+    pub message: String,
+    /// The first answers the fuzzer made up in the iteration.
+    pub mutations: Vec<Mutation>,
+}
+
+/// The outcome of [`Fuzzer::run`].
+#[derive(Clone, Debug, Default)]
+pub struct FuzzReport {
+    pub iterations: u64,
+    /// The number of answers made up in all iterations.
+    pub mutations: u64,
+    pub findings: Vec<Finding>,
+}
+
+/// The `HostCalls` backend that makes up the answers of the calls.
+pub struct Fuzzer {
+    rate: f64,
+    calls: u8,
+    inner: &'static dyn HostCalls,
+    rng: Rng,
+    enabled: AtomicBool,
+    mutated: AtomicU64,
+    log: SgxMutex<Vec<Mutation>>,
+}
+
+impl Fuzzer {
+    /// Creates a fuzzer that makes up the answer of each of the `calls` with
+    /// the probability `rate`, and passes the other calls on to `inner`.
+    ///
+    /// The fuzzer is off until [`run`](Fuzzer::run) or
+    /// [`set_enabled`](Fuzzer::set_enabled) turns it on.
+    pub fn new(rate: f64, calls: &[CallKind], inner: &'static dyn HostCalls) -> Fuzzer {
+        Fuzzer {
+            rate: if rate.is_nan() {
+                0.0
+            } else {
+                rate.clamp(0.0, 1.0)
+            },
+            calls: calls.iter().fold(0, |mask, call| mask | bit(*call)),
+            inner,
+            rng: Rng::new(0),
+            enabled: AtomicBool::new(false),
+            mutated: AtomicU64::new(0),
+            log: SgxMutex::new(Vec::new()),
+        }
+    }
+
+    /// Turns the fuzzer on or off.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether the fuzzer is on.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of answers made up so far.
+    pub fn mutated(&self) -> u64 {
+        self.mutated.load(Ordering::Relaxed)
+    }
+
+    /// Runs `iterations` iterations of `target`, the first with `seed`, and
+    /// calls `check` after each one that returned.
+    ///
+    /// The fuzzer is on during the calls of `target` only. A finding does
+    /// not stop the run.
+    pub fn run<F, C>(&self, seed: u64, iterations: u64, target: F, check: C) -> FuzzReport
+    where
+        F: FnMut(),
+        C: FnMut() -> Result<(), String>,
+    {
+        self.run_with(seed, iterations, target, check, |_, _| {})
+    }
+
+    /// Like [`run`](Fuzzer::run), but calls `progress` with the index and the
+    /// seed of each iteration before running it.
+    ///
+    /// An iteration that aborts the enclave returns no report, so `progress`
+    /// is where the seed to repeat it with [`run_one`](Fuzzer::run_one) has to
+    /// be recorded, outside of the enclave.
+    pub fn run_with<F, C, P>(
+        &self,
+        seed: u64,
+        iterations: u64,
+        mut target: F,
+        mut check: C,
+        mut progress: P,
+    ) -> FuzzReport
+    where
+        F: FnMut(),
+        C: FnMut() -> Result<(), String>,
+        P: FnMut(u64, u64),
+    {
+        let mut report = FuzzReport::default();
+        let seeds = Rng::new(seed);
+        for i in 0..iterations {
+            let seed = if i == 0 { seed } else { seeds.next_u64() };
+            progress(i, seed);
+            let before = self.mutated();
+            if let Some(mut finding) = self.run_one(seed, &mut target, &mut check) {
+                finding.iteration = i;
+                report.findings.push(finding);
+            }
+            report.mutations += self.mutated() - before;
+            report.iterations += 1;
+        }
+        report
+    }
+
+    /// Runs the iteration of `target` with `seed` once, and calls `check`
+    /// after it if it returned.
+    pub fn run_one<F, C>(&self, seed: u64, target: F, check: C) -> Option<Finding>
+    where
+        F: FnOnce(),
+        C: FnOnce() -> Result<(), String>,
+    {
+        self.rng.reseed(seed);
+        self.log
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+
+        self.set_enabled(true);
+        let result = catch_unwind(AssertUnwindSafe(target));
+        self.set_enabled(false);
+
+        let (kind, message) = match result {
+            Err(payload) => (FindingKind::Panic, panic_message(&*payload)),
+            Ok(()) => match catch_unwind(AssertUnwindSafe(check)) {
+                Ok(Ok(())) => return None,
+                Ok(Err(message)) => (FindingKind::Invariant, message),
+                Err(payload) => (FindingKind::Invariant, panic_message(&*payload)),
+            },
+        };
+        let mutations = self
+            .log
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        Some(Finding {
+            seed,
+            iteration: 0,
+            kind,
+            message,
+            mutations,
+        })
+    }
+
+    // Decides whether to make up the answer of a call.
+    fn mutates(&self, call: CallKind) -> bool {
+        self.is_enabled() && self.calls & bit(call) != 0 && self.rng.hit(self.rate)
+    }
+
+    fn record(&self, call: CallKind, len: usize, answer: Answer) {
+        self.mutated.fetch_add(1, Ordering::Relaxed);
+        let mut log = self.log.lock().unwrap_or_else(PoisonError::into_inner);
+        if log.len() < MAX_MUTATIONS {
+            log.push(Mutation { call, len, answer });
+        }
+    }
+
+    fn size(&self, len: usize) -> usize {
+        match self.rng.below(8) {
+            0 => 0,
+            1 => 1,
+            2 => len.saturating_sub(1),
+            3 => len,
+            4 => len.saturating_add(1),
+            5 => isize::MAX as usize,
+            6 => usize::MAX,
+            _ => self.rng.next_u64() as usize,
+        }
+    }
+
+    fn errno(&self) -> c_int {
+        if self.rng.hit(0.5) {
+            ERRNOS[self.rng.below(ERRNOS.len())]
+        } else {
+            self.rng.next_u64() as c_int
+        }
+    }
+
+    fn time_field(&self, edges: &[i64]) -> i64 {
+        if self.rng.hit(0.5) {
+            edges[self.rng.below(edges.len())]
+        } else {
+            self.rng.next_u64() as i64
+        }
+    }
+
+    // Makes up the answer of a call moving `len` bytes or events.
+    fn answer(&self, call: CallKind, len: usize) -> Result<usize, c_int> {
+        if self.rng.hit(0.25) {
+            let errno = self.errno();
+            self.record(call, len, Answer::Errno(errno));
+            Err(errno)
+        } else {
+            let size = self.size(len);
+            self.record(call, len, Answer::Size(size));
+            Ok(size)
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&'static str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+impl HostCalls for Fuzzer {
+    fn read(&self, fd: c_int, buf: &mut [u8]) -> Result<usize, c_int> {
+        if !self.mutates(CallKind::Read) {
+            return self.inner.read(fd, buf);
+        }
+        let answer = self.answer(CallKind::Read, buf.len());
+        self.rng.fill(buf);
+        answer
+    }
+
+    fn write(&self, fd: c_int, buf: &[u8]) -> Result<usize, c_int> {
+        if !self.mutates(CallKind::Write) {
+            return self.inner.write(fd, buf);
+        }
+        self.answer(CallKind::Write, buf.len())
+    }
+
+    fn recv(&self, sockfd: c_int, buf: &mut [u8], flags: c_int) -> Result<usize, c_int> {
+        if !self.mutates(CallKind::Recv) {
+            return self.inner.recv(sockfd, buf, flags);
+        }
+        let answer = self.answer(CallKind::Recv, buf.len());
+        self.rng.fill(buf);
+        answer
+    }
+
+    fn send(&self, sockfd: c_int, buf: &[u8], flags: c_int) -> Result<usize, c_int> {
+        if !self.mutates(CallKind::Send) {
+            return self.inner.send(sockfd, buf, flags);
+        }
+        self.answer(CallKind::Send, buf.len())
+    }
+
+    fn clock_gettime(&self, clk_id: clockid_t) -> Result<timespec, c_int> {
+        if !self.mutates(CallKind::ClockGettime) {
+            return self.inner.clock_gettime(clk_id);
+        }
+        if self.rng.hit(0.25) {
+            let errno = self.errno();
+            self.record(CallKind::ClockGettime, 1, Answer::Errno(errno));
+            return Err(errno);
+        }
+        let sec = self.time_field(&[0, -1, 1, i64::MIN, i64::MAX]);
+        let nsec = self.time_field(&[0, -1, 999_999_999, 1_000_000_000, i64::MIN, i64::MAX]);
+        self.record(CallKind::ClockGettime, 1, Answer::Time { sec, nsec });
+        Ok(timespec {
+            tv_sec: sec as _,
+            tv_nsec: nsec as _,
+        })
+    }
+
+    fn epoll_wait(
+        &self,
+        epfd: c_int,
+        events: &mut [epoll_event],
+        timeout: c_int,
+    ) -> Result<usize, c_int> {
+        if !self.mutates(CallKind::EpollWait) {
+            return self.inner.epoll_wait(epfd, events, timeout);
+        }
+        let answer = self.answer(CallKind::EpollWait, events.len());
+        for event in events.iter_mut() {
+            // Small tokens hit the descriptors the enclave registered.
+            let token = if self.rng.hit(0.5) {
+                self.rng.below(64) as u64
+            } else {
+                self.rng.next_u64()
+            };
+            *event = epoll_event {
+                events: self.rng.next_u64() as u32,
+                u64: token,
+            };
+        }
+        answer
+    }
+}
+
+impl fmt::Debug for Fuzzer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fuzzer")
+            .field("rate", &self.rate)
+            .field("enabled", &self.is_enabled())
+            .field("mutated", &self.mutated())
+            .finish()
+    }
+}
+
+/// Installs a [`Fuzzer`] in front of the routed OCALLs, which makes up the
+/// answer of each of the `calls` with the probability `rate` while it is on.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::AlreadyExists`] if a backend is installed
+/// already.
+pub fn install(rate: f64, calls: &[CallKind]) -> io::Result<&'static Fuzzer> {
+    if host::host_calls().is_some() {
+        return Err(installed());
+    }
+    let fuzzer: &'static Fuzzer = Box::leak(Box::new(Fuzzer::new(rate, calls, &Native)));
+    if host::set_host_calls(fuzzer) {
+        Ok(fuzzer)
+    } else {
+        Err(installed())
+    }
+}
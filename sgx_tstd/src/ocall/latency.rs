@@ -0,0 +1,448 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Latency histograms of the OCALLs, per OCALL.
+//!
+//! [`__wrap_sgx_ocall`] stands in for `sgx_ocall` of the trusted runtime
+//! when the enclave is linked with `-Wl,--wrap=sgx_ocall`, which the
+//! `ocall_latency` feature requires. Once [`enable`] is called, it reads the
+//! time stamp counter before and after every OCALL and adds the time to the
+//! histogram of the OCALL, picked by its index in the OCALL table generated
+//! by edger8r. The time covers the exit, the host side of the call with
+//! whatever scheduling delays the host adds, and the re-entry; it includes
+//! the ECALLs nested in the OCALL. Switchless OCALLs are not timed.
+//!
+//! The histograms are log-linear, as those of HdrHistogram: every power of
+//! two is split into 16 buckets, so that a recorded time is off by less than
+//! 1/16 of its value, and up to 2^40 ticks are told apart. A record is a few
+//! relaxed atomic additions, next to an OCALL of several thousand cycles.
+//!
+//! The enclave cannot read the TSC frequency itself, so [`enable`] takes it
+//! and the times are scaled with it; a wrong frequency skews the figures and
+//! nothing else. `RDTSC` must be allowed in enclave mode, as it is on SGX2
+//! processors; it faults on SGX1 ones, where latency recording must stay
+//! off.
+//!
+//! With the `thread` feature, [`metrics`] turns the histograms into points of
+//! the metrics exporters of [`log`](crate::log).
+//!
+//! # Examples
+//!
+//! ```
+//! use std::ocall::latency;
+//!
+//! latency::set_names(&["ocall_print", "u_read_ocall", "u_write_ocall"]);
+//! latency::enable(tsc_hz)?;
+//! serve();
+//! for ocall in latency::snapshot() {
+//!     let p99 = ocall.histogram.value_at_quantile(0.99);
+//!     println!("{}: {} calls, p99 {:?}", ocall.label(), ocall.histogram.count(), p99);
+//! }
+//! ```
+
+use crate::boxed::Box;
+use crate::io;
+use crate::ptr;
+use crate::string::{String, ToString};
+use crate::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+use crate::time::Duration;
+use crate::vec::Vec;
+use core::arch::x86_64::_rdtsc;
+use sgx_types::{c_uint, c_void, sgx_status_t};
+
+#[cfg(feature = "thread")]
+use crate::log::otlp::{Metric, MetricKind, MetricValue};
+
+/// The largest OCALL table whose OCALLs are timed. OCALLs of a higher index
+/// are made, not timed.
+pub const MAX_OCALLS: usize = 512;
+
+// Buckets per power of two, as a number of bits.
+const SUB_BITS: u32 = 4;
+const SUB_BUCKETS: usize = 1 << SUB_BITS;
+// Times from 2^MAX_BITS ticks up are counted in the last bucket.
+const MAX_BITS: u32 = 40;
+const MAX_TICKS: u64 = (1 << MAX_BITS) - 1;
+
+/// The number of buckets of a histogram.
+pub const BUCKETS: usize = (MAX_BITS as usize - SUB_BITS as usize + 1) * SUB_BUCKETS;
+
+// Values below 2 * SUB_BUCKETS have a bucket each. Above, the bucket is the
+// power of two of the value and its SUB_BITS bits below the leading one.
+fn bucket(ticks: u64) -> usize {
+    let ticks = ticks.min(MAX_TICKS);
+    if ticks < SUB_BUCKETS as u64 {
+        return ticks as usize;
+    }
+    let msb = 63 - ticks.leading_zeros();
+    let shift = msb - SUB_BITS;
+    (msb - SUB_BITS + 1) as usize * SUB_BUCKETS + (ticks >> shift) as usize - SUB_BUCKETS
+}
+
+// The lowest value of a bucket.
+fn bucket_floor(index: usize) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64;
+    }
+    ((index % SUB_BUCKETS + SUB_BUCKETS) as u64) << (index / SUB_BUCKETS - 1)
+}
+
+struct Recorder {
+    count: AtomicU64,
+    ticks: AtomicU64,
+    max: AtomicU64,
+    buckets: [AtomicU64; BUCKETS],
+}
+
+impl Recorder {
+    fn new() -> Recorder {
+        Recorder {
+            count: AtomicU64::new(0),
+            ticks: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+            buckets: [(); BUCKETS].map(|_| AtomicU64::new(0)),
+        }
+    }
+
+    fn record(&self, ticks: u64) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.ticks.fetch_add(ticks, Ordering::Relaxed);
+        self.max.fetch_max(ticks, Ordering::Relaxed);
+        self.buckets[bucket(ticks)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.ticks.store(0, Ordering::Relaxed);
+        self.max.store(0, Ordering::Relaxed);
+        for bucket in self.buckets.iter() {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+// The recorders are made on the first OCALL of their index and never freed.
+// They are published with a compare-and-swap rather than under a lock, as
+// an OCALL may be made with any lock of the enclave held.
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_RECORDER: AtomicPtr<Recorder> = AtomicPtr::new(ptr::null_mut());
+static RECORDERS: [AtomicPtr<Recorder>; MAX_OCALLS] = [NO_RECORDER; MAX_OCALLS];
+
+// The TSC frequency in Hz, or 0 while recording is off.
+static TSC_HZ: AtomicU64 = AtomicU64::new(0);
+static NAMES: AtomicPtr<&'static [&'static str]> = AtomicPtr::new(ptr::null_mut());
+
+fn recorder(index: usize) -> Option<&'static Recorder> {
+    let slot = RECORDERS.get(index)?;
+    let current = slot.load(Ordering::Acquire);
+    if !current.is_null() {
+        return Some(unsafe { &*current });
+    }
+    let new = Box::into_raw(Box::new(Recorder::new()));
+    match slot.compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => Some(unsafe { &*new }),
+        Err(current) => {
+            drop(unsafe { Box::from_raw(new) });
+            Some(unsafe { &*current })
+        }
+    }
+}
+
+/// Starts recording the OCALL times, given the frequency of the time stamp
+/// counter in Hz.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] if `tsc_hz` is 0.
+pub fn enable(tsc_hz: u64) -> io::Result<()> {
+    if tsc_hz == 0 {
+        return Err(io::const_io_error!(
+            io::ErrorKind::InvalidInput,
+            "the TSC frequency must not be 0",
+        ));
+    }
+    TSC_HZ.store(tsc_hz, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Stops recording. The histograms are kept.
+pub fn disable() {
+    TSC_HZ.store(0, Ordering::Relaxed);
+}
+
+/// Returns whether the OCALL times are recorded.
+pub fn is_enabled() -> bool {
+    TSC_HZ.load(Ordering::Relaxed) != 0
+}
+
+/// Names the OCALLs by index, in the order of the OCALL table generated by
+/// edger8r. OCALLs without a name are labelled with their index.
+pub fn set_names(names: &'static [&'static str]) {
+    let names = Box::into_raw(Box::new(names));
+    // The previous names may be in use by a snapshot, and are leaked.
+    NAMES.store(names, Ordering::Release);
+}
+
+fn name(index: usize) -> Option<&'static str> {
+    let names = NAMES.load(Ordering::Acquire);
+    if names.is_null() {
+        None
+    } else {
+        unsafe { *names }.get(index).copied()
+    }
+}
+
+/// Empties the histograms.
+pub fn reset() {
+    for slot in RECORDERS.iter() {
+        let recorder = slot.load(Ordering::Acquire);
+        if !recorder.is_null() {
+            unsafe { &*recorder }.reset();
+        }
+    }
+}
+
+/// A snapshot of the times of one OCALL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    tsc_hz: u64,
+    count: u64,
+    ticks: u64,
+    max: u64,
+    buckets: Vec<u64>,
+}
+
+impl Histogram {
+    fn duration(&self, ticks: u64) -> Duration {
+        if self.tsc_hz == 0 {
+            return Duration::ZERO;
+        }
+        let nanos = ticks as u128 * 1_000_000_000 / self.tsc_hz as u128;
+        Duration::from_nanos(nanos.min(u64::MAX as u128) as u64)
+    }
+
+    /// Returns the number of recorded OCALLs.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the total time of the recorded OCALLs.
+    pub fn total(&self) -> Duration {
+        self.duration(self.ticks)
+    }
+
+    /// Returns the mean time of the recorded OCALLs.
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => self.duration(self.ticks / count),
+        }
+    }
+
+    /// Returns the longest recorded time.
+    pub fn max(&self) -> Duration {
+        self.duration(self.max)
+    }
+
+    /// Returns the time that `quantile` of the recorded OCALLs did not
+    /// exceed, as the lowest value of its bucket. `quantile` is clamped to
+    /// `[0, 1]`; 1 gives the longest recorded time.
+    pub fn value_at_quantile(&self, quantile: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let quantile = if quantile.is_nan() {
+            0.0
+        } else {
+            quantile.clamp(0.0, 1.0)
+        };
+        if quantile >= 1.0 {
+            return self.max();
+        }
+        let rank = ((quantile * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return self.duration(bucket_floor(index).min(self.max));
+            }
+        }
+        self.max()
+    }
+
+    /// Returns the non-empty buckets, as the lowest time of the bucket and
+    /// the number of OCALLs in it.
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count != 0)
+            .map(move |(index, count)| (self.duration(bucket_floor(index)), *count))
+    }
+}
+
+/// The histogram of one OCALL, as returned by [`snapshot`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OcallLatency {
+    /// The index of the OCALL in the OCALL table.
+    pub index: usize,
+    /// The name given with [`set_names`], if any.
+    pub name: Option<&'static str>,
+    pub histogram: Histogram,
+}
+
+impl OcallLatency {
+    /// Returns the name of the OCALL, or its index if it has none.
+    pub fn label(&self) -> String {
+        match self.name {
+            Some(name) => name.to_string(),
+            None => self.index.to_string(),
+        }
+    }
+}
+
+/// Returns the histograms of the OCALLs made so far, by index.
+///
+/// The counts of an OCALL are read one after the other while other threads
+/// may be recording, and can be off by the OCALLs that end meanwhile.
+pub fn snapshot() -> Vec<OcallLatency> {
+    let tsc_hz = TSC_HZ.load(Ordering::Relaxed);
+    let mut ocalls = Vec::new();
+    for (index, slot) in RECORDERS.iter().enumerate() {
+        let recorder = slot.load(Ordering::Acquire);
+        if recorder.is_null() {
+            continue;
+        }
+        let recorder = unsafe { &*recorder };
+        let count = recorder.count.load(Ordering::Relaxed);
+        if count == 0 {
+            continue;
+        }
+        ocalls.push(OcallLatency {
+            index,
+            name: name(index),
+            histogram: Histogram {
+                tsc_hz,
+                count,
+                ticks: recorder.ticks.load(Ordering::Relaxed),
+                max: recorder.max.load(Ordering::Relaxed),
+                buckets: recorder
+                    .buckets
+                    .iter()
+                    .map(|bucket| bucket.load(Ordering::Relaxed))
+                    .collect(),
+            },
+        });
+    }
+    ocalls
+}
+
+/// The quantiles reported by [`metrics`].
+#[cfg(feature = "thread")]
+pub const QUANTILES: [f64; 4] = [0.5, 0.9, 0.99, 0.999];
+
+/// Turns a snapshot into metric points, to be encoded with
+/// [`Metric::encode`] and sent to a metrics [`Shipper`](crate::log::Shipper).
+///
+/// Every OCALL gives the counter `ocall.latency.count`, the counter
+/// `ocall.latency.sum` and the gauge `ocall.latency.max`, and the gauge
+/// `ocall.latency.quantile` for each of [`QUANTILES`], with a `quantile`
+/// attribute. The times are in nanoseconds and every point carries the
+/// label of its OCALL as the `ocall` attribute.
+#[cfg(feature = "thread")]
+pub fn metrics(ocalls: &[OcallLatency]) -> Vec<Metric> {
+    fn point(ocall: &OcallLatency, name: &str, kind: MetricKind, unit: &str, value: u64) -> Metric {
+        let mut metric = Metric::new(
+            name,
+            kind,
+            MetricValue::Int(value.min(i64::MAX as u64) as i64),
+        );
+        metric.unit = unit.to_string();
+        metric.attributes.push(("ocall".to_string(), ocall.label()));
+        metric
+    }
+
+    fn nanos(duration: Duration) -> u64 {
+        duration.as_nanos().min(u64::MAX as u128) as u64
+    }
+
+    let mut points = Vec::with_capacity(ocalls.len() * (3 + QUANTILES.len()));
+    for ocall in ocalls {
+        let histogram = &ocall.histogram;
+        points.push(point(
+            ocall,
+            "ocall.latency.count",
+            MetricKind::Counter,
+            "{call}",
+            histogram.count(),
+        ));
+        points.push(point(
+            ocall,
+            "ocall.latency.sum",
+            MetricKind::Counter,
+            "ns",
+            nanos(histogram.total()),
+        ));
+        points.push(point(
+            ocall,
+            "ocall.latency.max",
+            MetricKind::Gauge,
+            "ns",
+            nanos(histogram.max()),
+        ));
+        for quantile in QUANTILES {
+            let mut metric = point(
+                ocall,
+                "ocall.latency.quantile",
+                MetricKind::Gauge,
+                "ns",
+                nanos(histogram.value_at_quantile(quantile)),
+            );
+            metric
+                .attributes
+                .push(("quantile".to_string(), quantile.to_string()));
+            points.push(metric);
+        }
+    }
+    points
+}
+
+extern "C" {
+    fn __real_sgx_ocall(index: c_uint, ms: *mut c_void) -> sgx_status_t;
+}
+
+/// Times the OCALL and makes it through `__real_sgx_ocall`. The enclave must
+/// be linked with `-Wl,--wrap=sgx_ocall`.
+///
+/// # Safety
+///
+/// The arguments are those of `sgx_ocall`, as passed by the bridges
+/// generated by edger8r.
+#[no_mangle]
+pub unsafe extern "C" fn __wrap_sgx_ocall(index: c_uint, ms: *mut c_void) -> sgx_status_t {
+    if !is_enabled() {
+        return __real_sgx_ocall(index, ms);
+    }
+    let recorder = recorder(index as usize);
+    let start = _rdtsc();
+    let status = __real_sgx_ocall(index, ms);
+    let end = _rdtsc();
+    if let Some(recorder) = recorder {
+        recorder.record(end.saturating_sub(start));
+    }
+    status
+}
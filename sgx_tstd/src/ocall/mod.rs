@@ -0,0 +1,440 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! The untrusted interface of the enclave.
+//!
+//! The build script of an enclave can enumerate every OCALL reachable from
+//! its EDL files with `sgx_build_helper::edl`, which writes the result both
+//! as a release artifact (`ocall_surface.txt`) and as Rust source embedding
+//! the same manifest. Registering that manifest at startup makes the running
+//! enclave report its own untrusted interface: [`surface`] returns it inside
+//! the enclave, and `t_ocall_surface_ecall` (declared in
+//! `sgx_ocall_surface.edl`) copies it out to the host, byte for byte equal to
+//! the build artifact.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/ocall_surface.rs"));
+//!
+//! std::ocall::register_surface(OCALL_SURFACE).unwrap();
+//!
+//! let surface = std::ocall::surface().unwrap();
+//! assert!(surface.contains("u_thread_wait_event_ocall"));
+//! ```
+//!
+//! # Capabilities
+//!
+//! Some OCALLs are more dangerous than the rest: those acting on the host
+//! process and those writing to the untrusted file system. Once the enclave
+//! calls [`init_capability`] with its verified configuration, the OCALLs of
+//! these [`OcallClass`]es fail with `EPERM` unless the calling thread runs
+//! inside [`OcallCapability::scope`] of a token that grants their class. The
+//! token is handed out only once, so request handlers that are never given
+//! it cannot reach these OCALLs, even when compromised.
+//!
+//! ```ignore
+//! use std::ocall::{self, OcallClass};
+//!
+//! let capability = ocall::init_capability(&config, |config| verify_signature(config))?;
+//! assert!(capability.allows(OcallClass::FsWrite));
+//!
+//! capability.scope(|| std::untrusted::fs::write("state.bin", &state))?;
+//! ```
+//!
+//! # Record, replay and faults
+//!
+//! The OCALLs that carry data in from the host can be recorded to a file in
+//! hardware mode and answered from it in simulation, see [`replay`]. With the
+//! `fault_injection` feature, [`fault`] makes the same OCALLs fail, stall or
+//! come back short, to test how the enclave copes with a hostile host, and
+//! [`fuzz`] answers them with values chosen to break the enclave.
+//!
+//! # Latency
+//!
+//! With the `ocall_latency` feature, [`latency`] keeps a histogram of the
+//! time of every OCALL, host side included, to tell the host scheduling
+//! delays apart from the time spent in the enclave.
+
+use crate::cell::Cell;
+use crate::io;
+use crate::ptr;
+use crate::str;
+use crate::sync::OnceLock;
+use sgx_libc::gate;
+use sgx_types::sgx_status_t;
+
+#[cfg(feature = "fault_injection")]
+pub mod fault;
+#[cfg(feature = "fault_injection")]
+pub mod fuzz;
+#[cfg(feature = "ocall_latency")]
+pub mod latency;
+#[cfg(not(minimal_tcb))]
+pub mod replay;
+
+/// First line of a manifest, as written by `sgx_build_helper::edl`.
+pub const MANIFEST_HEADER: &str = "# sgx ocall surface v1";
+
+static SURFACE: OnceLock<Surface> = OnceLock::new();
+
+/// One OCALL of the surface.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OcallEntry<'a> {
+    name: &'a str,
+    edl: &'a str,
+    declaration: &'a str,
+}
+
+impl<'a> OcallEntry<'a> {
+    /// Returns the name of the OCALL function.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns the file name of the EDL that declares the OCALL.
+    pub fn edl(&self) -> &'a str {
+        self.edl
+    }
+
+    /// Returns the EDL declaration of the OCALL.
+    pub fn declaration(&self) -> &'a str {
+        self.declaration
+    }
+}
+
+/// A validated OCALL surface manifest.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Surface {
+    manifest: &'static str,
+    len: usize,
+}
+
+impl Surface {
+    /// Validates a manifest: the header, then one line per OCALL with three
+    /// tab separated fields, sorted by name without duplicates.
+    pub fn parse(manifest: &'static str) -> io::Result<Surface> {
+        let body = manifest
+            .strip_prefix(MANIFEST_HEADER)
+            .and_then(|rest| rest.strip_prefix('\n'))
+            .ok_or_else(|| invalid("missing OCALL surface manifest header"))?;
+        if !body.is_empty() && !body.ends_with('\n') {
+            return Err(invalid("truncated OCALL surface manifest"));
+        }
+
+        let mut len = 0;
+        let mut previous: Option<&str> = None;
+        for line in body.lines() {
+            let entry = parse_line(line).ok_or_else(|| invalid("malformed OCALL surface entry"))?;
+            if previous.map_or(false, |p| p >= entry.name) {
+                return Err(invalid("OCALL surface manifest is not sorted"));
+            }
+            previous = Some(entry.name);
+            len += 1;
+        }
+        Ok(Surface { manifest, len })
+    }
+
+    /// Returns the manifest text.
+    pub fn manifest(&self) -> &'static str {
+        self.manifest
+    }
+
+    /// Returns the number of OCALLs.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the enclave makes no OCALLs at all.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns an iterator over the OCALLs, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = OcallEntry<'static>> {
+        self.manifest[MANIFEST_HEADER.len() + 1..]
+            .lines()
+            .filter_map(parse_line)
+    }
+
+    /// Returns the OCALL named `name`, if it is part of the surface.
+    pub fn get(&self, name: &str) -> Option<OcallEntry<'static>> {
+        self.iter().find(|entry| entry.name == name)
+    }
+
+    /// Returns `true` if the OCALL named `name` is part of the surface.
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+}
+
+fn parse_line(line: &str) -> Option<OcallEntry<'_>> {
+    let mut fields = line.splitn(3, '\t');
+    let entry = OcallEntry {
+        name: fields.next()?,
+        edl: fields.next()?,
+        declaration: fields.next()?,
+    };
+    if entry.name.is_empty() || entry.edl.is_empty() || entry.declaration.is_empty() {
+        return None;
+    }
+    Some(entry)
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Registers the OCALL surface of the enclave, usually the `OCALL_SURFACE`
+/// generated by `sgx_build_helper::edl`.
+///
+/// The surface can be registered once. Registering the same manifest again
+/// succeeds, a different one fails with [`io::ErrorKind::AlreadyExists`].
+pub fn register_surface(manifest: &'static str) -> io::Result<()> {
+    let surface = Surface::parse(manifest)?;
+    if *SURFACE.get_or_init(|| surface) == surface {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "a different OCALL surface is registered",
+        ))
+    }
+}
+
+/// Returns the registered OCALL surface.
+pub fn surface() -> Option<Surface> {
+    SURFACE.get().copied()
+}
+
+///
+/// Copies the registered OCALL surface manifest to the host.
+///
+/// `needed` always receives the size of the manifest, so that a host which
+/// passed a buffer that is too small can retry with one of the right size.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// `needed` is null, or `buf` is null or smaller than the manifest.
+///
+/// **SGX_ERROR_INVALID_STATE**
+///
+/// No surface was registered with [`register_surface`].
+///
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[no_mangle]
+pub extern "C" fn t_ocall_surface_ecall(
+    buf: *mut u8,
+    len: usize,
+    needed: *mut usize,
+) -> sgx_status_t {
+    if needed.is_null() {
+        return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+    }
+    let manifest = match surface() {
+        Some(surface) => surface.manifest().as_bytes(),
+        None => return sgx_status_t::SGX_ERROR_INVALID_STATE,
+    };
+    unsafe { *needed = manifest.len() };
+    if buf.is_null() || len < manifest.len() {
+        return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+    }
+    unsafe { ptr::copy_nonoverlapping(manifest.as_ptr(), buf, manifest.len()) };
+    sgx_status_t::SGX_SUCCESS
+}
+
+/// A class of sensitive OCALLs, which are only made for a thread holding an
+/// [`OcallCapability`] once [`init_capability`] was called.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum OcallClass {
+    /// The OCALLs of `sgx_process.edl`, which act on the host process.
+    Process,
+    /// File system OCALLs that create, modify or remove files, including
+    /// opening a file for writing.
+    FsWrite,
+}
+
+impl OcallClass {
+    /// All classes.
+    pub const ALL: [OcallClass; 2] = [OcallClass::Process, OcallClass::FsWrite];
+
+    /// Returns the name of the class in a capability configuration.
+    pub fn name(self) -> &'static str {
+        match self {
+            OcallClass::Process => "process",
+            OcallClass::FsWrite => "fs_write",
+        }
+    }
+
+    /// Returns the class called `name` in a capability configuration.
+    pub fn from_name(name: &str) -> Option<OcallClass> {
+        OcallClass::ALL
+            .iter()
+            .copied()
+            .find(|class| class.name() == name)
+    }
+
+    fn bit(self) -> u32 {
+        match self {
+            OcallClass::Process => gate::OCALL_CLASS_PROCESS,
+            OcallClass::FsWrite => gate::OCALL_CLASS_FS_WRITE,
+        }
+    }
+}
+
+thread_local! {
+    static HELD: Cell<u32> = const { Cell::new(0) };
+}
+
+fn check(class: u32) -> bool {
+    HELD.try_with(|held| held.get() & class == class)
+        .unwrap_or(false)
+}
+
+/// The capability to make the OCALLs of some [`OcallClass`]es.
+///
+/// Tokens are only handed out by [`init_capability`] and cannot be cloned;
+/// [`restrict`](OcallCapability::restrict) derives a weaker one to give to
+/// code that needs fewer classes.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OcallCapability {
+    classes: u32,
+}
+
+impl OcallCapability {
+    /// Returns `true` if the token grants `class`.
+    pub fn allows(&self, class: OcallClass) -> bool {
+        self.classes & class.bit() != 0
+    }
+
+    /// Returns a token granting the classes of `classes` that this one grants.
+    pub fn restrict(&self, classes: &[OcallClass]) -> OcallCapability {
+        let wanted = classes.iter().fold(0, |bits, class| bits | class.bit());
+        OcallCapability {
+            classes: self.classes & wanted,
+        }
+    }
+
+    /// Runs `f` with the classes of the token enabled on the current thread,
+    /// restoring the previous state afterwards, also when `f` panics.
+    ///
+    /// Threads spawned by `f` do not inherit the classes.
+    pub fn scope<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        struct Reset(u32);
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                let _ = HELD.try_with(|held| held.set(self.0));
+            }
+        }
+
+        let previous = HELD
+            .try_with(|held| held.replace(held.get() | self.classes))
+            .unwrap_or(0);
+        let _reset = Reset(previous);
+        f()
+    }
+}
+
+/// Starts gating the sensitive OCALLs, and returns the token granting the
+/// classes listed in the capability configuration.
+///
+/// `verify` checks the authenticity of `config` before it is used, for
+/// example a signature over it or its hash against one built into the
+/// enclave. The configuration lists the granted classes by
+/// [name](OcallClass::name), one per line; empty lines and lines starting
+/// with `#` are ignored. Classes that are not listed become unreachable.
+///
+/// The gate can be initialized once, normally from the initialization ECALL
+/// before any request is served.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::PermissionDenied`] if `verify` rejects the
+/// configuration, with [`io::ErrorKind::InvalidData`] if it is malformed, and
+/// with [`io::ErrorKind::AlreadyExists`] if the gate was initialized before.
+pub fn init_capability<F>(config: &[u8], verify: F) -> io::Result<OcallCapability>
+where
+    F: FnOnce(&[u8]) -> bool,
+{
+    if !verify(config) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "capability configuration failed verification",
+        ));
+    }
+    let text = str::from_utf8(config).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "capability configuration is not UTF-8",
+        )
+    })?;
+
+    let mut classes = 0;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let class = OcallClass::from_name(line).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unknown OCALL class in capability configuration",
+            )
+        })?;
+        classes |= class.bit();
+    }
+
+    if !gate::set_ocall_gate(check) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "OCALL gate is already initialized",
+        ));
+    }
+    Ok(OcallCapability { classes })
+}
+
+/// Returns `true` once [`init_capability`] has succeeded.
+pub fn is_gated() -> bool {
+    gate::is_ocall_gate_set()
+}
+
+/// An OCALL routed through the `HostCalls` backend of `sgx_libc`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CallKind {
+    Read,
+    Write,
+    Recv,
+    Send,
+    ClockGettime,
+    EpollWait,
+}
+
+impl CallKind {
+    /// All routed OCALLs.
+    pub const ALL: [CallKind; 6] = [
+        CallKind::Read,
+        CallKind::Write,
+        CallKind::Recv,
+        CallKind::Send,
+        CallKind::ClockGettime,
+        CallKind::EpollWait,
+    ];
+}
@@ -0,0 +1,609 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Record and replay of the data the host feeds the enclave.
+//!
+//! [`record`] installs a [`Recorder`] as the `HostCalls` backend of
+//! `sgx_libc`, which appends every routed OCALL (`read`, `write`, `recv`,
+//! `send`, `clock_gettime` and `epoll_wait`) with its arguments and its
+//! answer to a recording file on the host. [`replay`] installs a [`Replay`]
+//! that answers the same OCALLs from such a file without leaving the enclave,
+//! so a run observed in hardware mode can be repeated call for call in
+//! simulation, under a debugger.
+//!
+//! The recording is written as the calls complete, so it survives a crash of
+//! the enclave. By default the data the enclave sends out is not recorded,
+//! only its length; [`Redaction`] also drops the data read from chosen
+//! descriptors, which then replays as zeros. A replay checks every call
+//! against the recording and panics at the first call that differs, naming
+//! its position.
+//!
+//! Calls are matched by their order, kind and buffer length. Descriptors
+//! are recorded but not compared, since the OCALLs that create them are not
+//! routed and still reach the host when replaying. The order of calls from
+//! several threads is only reproduced if the threads interleave the same
+//! way, so replays are most useful for one thread at a time.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use std::ocall::replay::{self, Redaction};
+//!
+//! // In hardware mode.
+//! replay::record("run.orec", Redaction::new().redact_incoming(key_server_fd))?;
+//!
+//! // Later, in simulation.
+//! let replay = replay::replay("run.orec")?;
+//! run_enclave();
+//! assert_eq!(replay.remaining(), 0);
+//! ```
+
+use crate::boxed::Box;
+use crate::collections::VecDeque;
+use crate::convert::TryInto;
+use crate::fmt;
+use crate::io::{self, Write};
+use crate::os::unix::io::{AsRawFd, RawFd};
+use crate::path::Path;
+use crate::sync::{PoisonError, SgxMutex, SgxMutexGuard};
+use crate::untrusted::fs::{self, File};
+use crate::vec::Vec;
+
+use super::CallKind;
+use sgx_libc::host::{self, HostCalls, Native};
+use sgx_libc::{c_int, clockid_t, epoll_event, timespec};
+
+/// First bytes of a recording.
+pub const RECORDING_MAGIC: &[u8; 8] = b"SGXOREC1";
+
+const EPOLL_EVENT_SIZE: usize = 12;
+const TIMESPEC_SIZE: usize = 16;
+
+impl CallKind {
+    fn code(self) -> u8 {
+        match self {
+            CallKind::Read => 1,
+            CallKind::Write => 2,
+            CallKind::Recv => 3,
+            CallKind::Send => 4,
+            CallKind::ClockGettime => 5,
+            CallKind::EpollWait => 6,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<CallKind> {
+        Some(match code {
+            1 => CallKind::Read,
+            2 => CallKind::Write,
+            3 => CallKind::Recv,
+            4 => CallKind::Send,
+            5 => CallKind::ClockGettime,
+            6 => CallKind::EpollWait,
+            _ => return None,
+        })
+    }
+
+    // Whether the data of the call flows out of the enclave.
+    fn is_outgoing(self) -> bool {
+        matches!(self, CallKind::Write | CallKind::Send)
+    }
+}
+
+/// The data a [`Recorder`] leaves out of the recording.
+#[derive(Clone, Debug)]
+pub struct Redaction {
+    outgoing: bool,
+    incoming_fds: Vec<c_int>,
+}
+
+impl Default for Redaction {
+    fn default() -> Redaction {
+        Redaction {
+            outgoing: true,
+            incoming_fds: Vec::new(),
+        }
+    }
+}
+
+impl Redaction {
+    /// Redacts the data the enclave writes and sends, and nothing it reads.
+    pub fn new() -> Redaction {
+        Redaction::default()
+    }
+
+    /// Records the data the enclave writes and sends, so that a replay also
+    /// checks it.
+    pub fn keep_outgoing(mut self) -> Redaction {
+        self.outgoing = false;
+        self
+    }
+
+    /// Redacts the data read or received from `fd`.
+    pub fn redact_incoming(mut self, fd: c_int) -> Redaction {
+        self.incoming_fds.push(fd);
+        self
+    }
+
+    fn redacts(&self, kind: CallKind, fd: c_int) -> bool {
+        if kind.is_outgoing() {
+            self.outgoing
+        } else {
+            matches!(kind, CallKind::Read | CallKind::Recv) && self.incoming_fds.contains(&fd)
+        }
+    }
+}
+
+// One routed OCALL. `result` is the return value, or the negated errno.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Record {
+    kind: CallKind,
+    fd: c_int,
+    arg: c_int,
+    len: u64,
+    result: i64,
+    redacted: bool,
+    data: Vec<u8>,
+}
+
+impl Record {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(self.kind.code());
+        out.extend_from_slice(&self.fd.to_le_bytes());
+        out.extend_from_slice(&self.arg.to_le_bytes());
+        out.extend_from_slice(&self.len.to_le_bytes());
+        out.extend_from_slice(&self.result.to_le_bytes());
+        out.push(self.redacted as u8);
+        out.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        if !self.redacted {
+            out.extend_from_slice(&self.data);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Record> {
+        let kind = CallKind::from_code(take(input, 1)?[0])?;
+        let fd = c_int::from_le_bytes(take(input, 4)?.try_into().ok()?);
+        let arg = c_int::from_le_bytes(take(input, 4)?.try_into().ok()?);
+        let len = u64::from_le_bytes(take(input, 8)?.try_into().ok()?);
+        let result = i64::from_le_bytes(take(input, 8)?.try_into().ok()?);
+        let redacted = match take(input, 1)?[0] {
+            0 => false,
+            1 => true,
+            _ => return None,
+        };
+        let data_len = u32::from_le_bytes(take(input, 4)?.try_into().ok()?) as usize;
+        let data = if redacted {
+            vec![0; data_len]
+        } else {
+            take(input, data_len)?.to_vec()
+        };
+        Some(Record {
+            kind,
+            fd,
+            arg,
+            len,
+            result,
+            redacted,
+            data,
+        })
+    }
+}
+
+fn take<'a>(input: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    if input.len() < n {
+        return None;
+    }
+    let (head, tail) = input.split_at(n);
+    *input = tail;
+    Some(head)
+}
+
+fn result_of(result: &Result<usize, c_int>) -> i64 {
+    match *result {
+        Ok(n) => n as i64,
+        Err(e) => -(e as i64),
+    }
+}
+
+/// The `HostCalls` backend that records the calls it passes to the host.
+pub struct Recorder {
+    state: SgxMutex<RecorderState>,
+    redaction: Redaction,
+}
+
+struct RecorderState {
+    file: File,
+    calls: u64,
+    error: Option<io::Error>,
+}
+
+impl Recorder {
+    /// Returns the number of calls recorded.
+    pub fn calls(&self) -> u64 {
+        self.lock().calls
+    }
+
+    /// Returns the first error met writing the recording. Recording stops at
+    /// that point, while the calls keep being made.
+    pub fn error(&self) -> Option<io::ErrorKind> {
+        self.lock().error.as_ref().map(io::Error::kind)
+    }
+
+    fn lock(&self) -> SgxMutexGuard<'_, RecorderState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn record(&self, kind: CallKind, fd: c_int, arg: c_int, len: usize, result: i64, data: &[u8]) {
+        let redacted = self.redaction.redacts(kind, fd);
+        let record = Record {
+            kind,
+            fd,
+            arg,
+            len: len as u64,
+            result,
+            redacted,
+            data: data.to_vec(),
+        };
+        let mut bytes = Vec::new();
+        record.encode(&mut bytes);
+
+        let mut state = self.lock();
+        if state.error.is_some() {
+            return;
+        }
+        // The recording bypasses the backend, so its writes are not recorded.
+        let fd = state.file.as_raw_fd();
+        match write_native(fd, &bytes) {
+            Ok(()) => state.calls += 1,
+            Err(e) => state.error = Some(e),
+        }
+    }
+}
+
+fn write_native(fd: RawFd, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        match Native.write(fd, buf) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => buf = &buf[n..],
+            Err(e) if e == sgx_libc::EINTR => {}
+            Err(e) => return Err(io::Error::from_raw_os_error(e)),
+        }
+    }
+    Ok(())
+}
+
+impl HostCalls for Recorder {
+    fn read(&self, fd: c_int, buf: &mut [u8]) -> Result<usize, c_int> {
+        let result = Native.read(fd, buf);
+        let n = result.map_or(0, |n| n.min(buf.len()));
+        self.record(
+            CallKind::Read,
+            fd,
+            0,
+            buf.len(),
+            result_of(&result),
+            &buf[..n],
+        );
+        result
+    }
+
+    fn write(&self, fd: c_int, buf: &[u8]) -> Result<usize, c_int> {
+        let result = Native.write(fd, buf);
+        self.record(CallKind::Write, fd, 0, buf.len(), result_of(&result), buf);
+        result
+    }
+
+    fn recv(&self, sockfd: c_int, buf: &mut [u8], flags: c_int) -> Result<usize, c_int> {
+        let result = Native.recv(sockfd, buf, flags);
+        let n = result.map_or(0, |n| n.min(buf.len()));
+        self.record(
+            CallKind::Recv,
+            sockfd,
+            flags,
+            buf.len(),
+            result_of(&result),
+            &buf[..n],
+        );
+        result
+    }
+
+    fn send(&self, sockfd: c_int, buf: &[u8], flags: c_int) -> Result<usize, c_int> {
+        let result = Native.send(sockfd, buf, flags);
+        self.record(
+            CallKind::Send,
+            sockfd,
+            flags,
+            buf.len(),
+            result_of(&result),
+            buf,
+        );
+        result
+    }
+
+    fn clock_gettime(&self, clk_id: clockid_t) -> Result<timespec, c_int> {
+        let result = Native.clock_gettime(clk_id);
+        let mut data = Vec::with_capacity(TIMESPEC_SIZE);
+        let code = match result {
+            Ok(ref tp) => {
+                data.extend_from_slice(&tp.tv_sec.to_le_bytes());
+                data.extend_from_slice(&tp.tv_nsec.to_le_bytes());
+                0
+            }
+            Err(e) => -(e as i64),
+        };
+        self.record(
+            CallKind::ClockGettime,
+            clk_id,
+            0,
+            TIMESPEC_SIZE,
+            code,
+            &data,
+        );
+        result
+    }
+
+    fn epoll_wait(
+        &self,
+        epfd: c_int,
+        events: &mut [epoll_event],
+        timeout: c_int,
+    ) -> Result<usize, c_int> {
+        let result = Native.epoll_wait(epfd, events, timeout);
+        let n = result.map_or(0, |n| n.min(events.len()));
+        let mut data = Vec::with_capacity(n * EPOLL_EVENT_SIZE);
+        for event in &events[..n] {
+            let (bits, token) = (event.events, event.u64);
+            data.extend_from_slice(&bits.to_le_bytes());
+            data.extend_from_slice(&token.to_le_bytes());
+        }
+        self.record(
+            CallKind::EpollWait,
+            epfd,
+            timeout,
+            events.len(),
+            result_of(&result),
+            &data,
+        );
+        result
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("calls", &self.calls())
+            .field("redaction", &self.redaction)
+            .finish()
+    }
+}
+
+/// The `HostCalls` backend that answers the calls from a recording.
+pub struct Replay {
+    state: SgxMutex<ReplayState>,
+}
+
+struct ReplayState {
+    records: VecDeque<Record>,
+    position: u64,
+}
+
+impl Replay {
+    /// Parses a recording.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if `recording` does not
+    /// start with [`RECORDING_MAGIC`] or holds a malformed call.
+    pub fn parse(recording: &[u8]) -> io::Result<Replay> {
+        let mut input = recording
+            .strip_prefix(&RECORDING_MAGIC[..])
+            .ok_or_else(|| invalid("not an OCALL recording"))?;
+        let mut records = VecDeque::new();
+        while !input.is_empty() {
+            let record =
+                Record::decode(&mut input).ok_or_else(|| invalid("malformed OCALL recording"))?;
+            records.push_back(record);
+        }
+        Ok(Replay {
+            state: SgxMutex::new(ReplayState {
+                records,
+                position: 0,
+            }),
+        })
+    }
+
+    /// Returns the number of calls replayed so far.
+    pub fn position(&self) -> u64 {
+        self.lock().position
+    }
+
+    /// Returns the number of recorded calls not replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.lock().records.len()
+    }
+
+    fn lock(&self) -> SgxMutexGuard<'_, ReplayState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Takes the next record, which must be a call of `kind` with a buffer of
+    // `len` bytes.
+    fn next(&self, kind: CallKind, len: usize) -> Record {
+        let mut state = self.lock();
+        let position = state.position;
+        let record = match state.records.pop_front() {
+            Some(record) => record,
+            None => {
+                drop(state);
+                panic!("OCALL replay exhausted at call {}: {:?}", position, kind);
+            }
+        };
+        if record.kind != kind || record.len != len as u64 {
+            drop(state);
+            panic!(
+                "OCALL replay diverged at call {}: recorded {:?} of {} bytes, got {:?} of {} bytes",
+                position, record.kind, record.len, kind, len
+            );
+        }
+        state.position += 1;
+        record
+    }
+
+    fn check_outgoing(&self, record: &Record, buf: &[u8]) {
+        if !record.redacted && record.data != buf {
+            panic!(
+                "OCALL replay diverged at call {}: the data of {:?} differs",
+                self.position() - 1,
+                record.kind
+            );
+        }
+    }
+}
+
+fn answer(record: &Record) -> Result<usize, c_int> {
+    if record.result < 0 {
+        Err((-record.result) as c_int)
+    } else {
+        Ok(record.result as usize)
+    }
+}
+
+impl HostCalls for Replay {
+    fn read(&self, _fd: c_int, buf: &mut [u8]) -> Result<usize, c_int> {
+        let record = self.next(CallKind::Read, buf.len());
+        let n = record.data.len().min(buf.len());
+        buf[..n].copy_from_slice(&record.data[..n]);
+        answer(&record)
+    }
+
+    fn write(&self, _fd: c_int, buf: &[u8]) -> Result<usize, c_int> {
+        let record = self.next(CallKind::Write, buf.len());
+        self.check_outgoing(&record, buf);
+        answer(&record)
+    }
+
+    fn recv(&self, _sockfd: c_int, buf: &mut [u8], _flags: c_int) -> Result<usize, c_int> {
+        let record = self.next(CallKind::Recv, buf.len());
+        let n = record.data.len().min(buf.len());
+        buf[..n].copy_from_slice(&record.data[..n]);
+        answer(&record)
+    }
+
+    fn send(&self, _sockfd: c_int, buf: &[u8], _flags: c_int) -> Result<usize, c_int> {
+        let record = self.next(CallKind::Send, buf.len());
+        self.check_outgoing(&record, buf);
+        answer(&record)
+    }
+
+    fn clock_gettime(&self, _clk_id: clockid_t) -> Result<timespec, c_int> {
+        let record = self.next(CallKind::ClockGettime, TIMESPEC_SIZE);
+        answer(&record)?;
+        let mut data = &record.data[..];
+        let (sec, nsec) = match (take(&mut data, 8), take(&mut data, 8)) {
+            (Some(sec), Some(nsec)) => (sec, nsec),
+            _ => return Err(sgx_libc::EINVAL),
+        };
+        Ok(timespec {
+            tv_sec: i64::from_le_bytes(sec.try_into().unwrap()) as _,
+            tv_nsec: i64::from_le_bytes(nsec.try_into().unwrap()) as _,
+        })
+    }
+
+    fn epoll_wait(
+        &self,
+        _epfd: c_int,
+        events: &mut [epoll_event],
+        _timeout: c_int,
+    ) -> Result<usize, c_int> {
+        let record = self.next(CallKind::EpollWait, events.len());
+        for (event, chunk) in events
+            .iter_mut()
+            .zip(record.data.chunks_exact(EPOLL_EVENT_SIZE))
+        {
+            *event = epoll_event {
+                events: u32::from_le_bytes(chunk[..4].try_into().unwrap()),
+                u64: u64::from_le_bytes(chunk[4..].try_into().unwrap()),
+            };
+        }
+        answer(&record)
+    }
+}
+
+impl fmt::Debug for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("Replay")
+            .field("position", &state.position)
+            .field("remaining", &state.records.len())
+            .finish()
+    }
+}
+
+/// Starts recording the routed OCALLs to a new file at `path`.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::AlreadyExists`] if a backend is installed
+/// already, besides the errors of creating the file.
+pub fn record<P: AsRef<Path>>(path: P, redaction: Redaction) -> io::Result<&'static Recorder> {
+    if host::host_calls().is_some() {
+        return Err(installed());
+    }
+    let mut file = File::create(path)?;
+    file.write_all(RECORDING_MAGIC)?;
+    let recorder: &'static Recorder = Box::leak(Box::new(Recorder {
+        state: SgxMutex::new(RecorderState {
+            file,
+            calls: 0,
+            error: None,
+        }),
+        redaction,
+    }));
+    if host::set_host_calls(recorder) {
+        Ok(recorder)
+    } else {
+        Err(installed())
+    }
+}
+
+/// Starts answering the routed OCALLs from the recording at `path`.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::AlreadyExists`] if a backend is installed
+/// already, besides the errors of reading and parsing the recording.
+pub fn replay<P: AsRef<Path>>(path: P) -> io::Result<&'static Replay> {
+    if host::host_calls().is_some() {
+        return Err(installed());
+    }
+    let replay: &'static Replay = Box::leak(Box::new(Replay::parse(&fs::read(path)?)?));
+    if host::set_host_calls(replay) {
+        Ok(replay)
+    } else {
+        Err(installed())
+    }
+}
+
+fn installed() -> io::Error {
+    io::const_io_error!(
+        io::ErrorKind::AlreadyExists,
+        "an OCALL backend is installed already",
+    )
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
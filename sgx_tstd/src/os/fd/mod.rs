@@ -29,7 +29,7 @@ mod raw;
 mod owned;
 
 // Implementations for `AsRawFd` etc. for network types.
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 mod net;
 
 
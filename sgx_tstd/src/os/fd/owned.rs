@@ -21,10 +21,12 @@
 
 use super::raw::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use crate::fmt;
+#[cfg(not(minimal_tcb))]
 use crate::fs;
 use crate::marker::PhantomData;
 use crate::mem::forget;
 use crate::sys::cvt;
+#[cfg(not(minimal_tcb))]
 use crate::sys_common::{AsInner, FromInner, IntoInner};
 
 /// A borrowed file descriptor.
@@ -103,7 +105,7 @@ impl BorrowedFd<'_> {
         let cmd = libc::F_DUPFD_CLOEXEC;
 
         // Avoid using file descriptors below 3 as they are used for stdio
-        let fd = cvt(unsafe { libc::fcntl_arg1(self.as_raw_fd(), cmd, 3) })?;
+        let fd = crate::quota::open_fd(|| cvt(unsafe { libc::fcntl_arg1(self.as_raw_fd(), cmd, 3) }))?;
         Ok(unsafe { OwnedFd::from_raw_fd(fd) })
     }
 }
@@ -155,6 +157,7 @@ impl Drop for OwnedFd {
             // the file descriptor was closed or not, and if we retried (for
             // something like EINTR), we might close another valid file descriptor
             // opened after we closed ours.
+            crate::quota::close_fd(self.fd);
             let _ = libc::close(self.fd);
         }
     }
@@ -242,6 +245,7 @@ impl AsFd for OwnedFd {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 impl AsFd for fs::File {
     #[inline]
     fn as_fd(&self) -> BorrowedFd<'_> {
@@ -249,6 +253,7 @@ impl AsFd for fs::File {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 impl From<fs::File> for OwnedFd {
     #[inline]
     fn from(file: fs::File) -> OwnedFd {
@@ -256,6 +261,7 @@ impl From<fs::File> for OwnedFd {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 impl From<OwnedFd> for fs::File {
     #[inline]
     fn from(owned_fd: OwnedFd) -> Self {
@@ -263,7 +269,7 @@ impl From<OwnedFd> for fs::File {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl AsFd for crate::net::TcpStream {
     #[inline]
     fn as_fd(&self) -> BorrowedFd<'_> {
@@ -271,7 +277,7 @@ impl AsFd for crate::net::TcpStream {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl From<crate::net::TcpStream> for OwnedFd {
     #[inline]
     #[allow(clippy::useless_conversion)]
@@ -280,7 +286,7 @@ impl From<crate::net::TcpStream> for OwnedFd {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl From<OwnedFd> for crate::net::TcpStream {
     #[inline]
     fn from(owned_fd: OwnedFd) -> Self {
@@ -290,7 +296,7 @@ impl From<OwnedFd> for crate::net::TcpStream {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl AsFd for crate::net::TcpListener {
     #[inline]
     fn as_fd(&self) -> BorrowedFd<'_> {
@@ -298,7 +304,7 @@ impl AsFd for crate::net::TcpListener {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl From<crate::net::TcpListener> for OwnedFd {
     #[inline]
     #[allow(clippy::useless_conversion)]
@@ -307,7 +313,7 @@ impl From<crate::net::TcpListener> for OwnedFd {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl From<OwnedFd> for crate::net::TcpListener {
     #[inline]
     fn from(owned_fd: OwnedFd) -> Self {
@@ -317,7 +323,7 @@ impl From<OwnedFd> for crate::net::TcpListener {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl AsFd for crate::net::UdpSocket {
     #[inline]
     fn as_fd(&self) -> BorrowedFd<'_> {
@@ -325,7 +331,7 @@ impl AsFd for crate::net::UdpSocket {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl From<crate::net::UdpSocket> for OwnedFd {
     #[inline]
     #[allow(clippy::useless_conversion)]
@@ -334,7 +340,7 @@ impl From<crate::net::UdpSocket> for OwnedFd {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl From<OwnedFd> for crate::net::UdpSocket {
     #[inline]
     fn from(owned_fd: OwnedFd) -> Self {
@@ -17,11 +17,14 @@
 
 //! Raw Unix-like file descriptors.
 
+#[cfg(not(minimal_tcb))]
 use crate::fs;
 #[cfg(feature = "stdio")]
 use crate::io;
 use crate::os::raw;
+#[cfg(not(minimal_tcb))]
 use crate::os::unix::io::OwnedFd;
+#[cfg(not(minimal_tcb))]
 use crate::sys_common::{AsInner, IntoInner};
 
 #[cfg(feature = "stdio")]
@@ -153,6 +156,7 @@ impl FromRawFd for RawFd {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 impl AsRawFd for fs::File {
     #[inline]
     fn as_raw_fd(&self) -> RawFd {
@@ -160,6 +164,7 @@ impl AsRawFd for fs::File {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 impl FromRawFd for fs::File {
     #[inline]
     unsafe fn from_raw_fd(fd: RawFd) -> fs::File {
@@ -167,6 +172,7 @@ impl FromRawFd for fs::File {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 impl IntoRawFd for fs::File {
     #[inline]
     fn into_raw_fd(self) -> RawFd {
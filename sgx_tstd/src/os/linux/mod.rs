@@ -17,7 +17,8 @@
 
 //! Linux-specific definitions.
 
+#[cfg(not(minimal_tcb))]
 pub mod fs;
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 pub mod net;
 pub mod raw;
@@ -22,5 +22,5 @@ pub mod raw;
 pub mod unix;
 
 pub mod fd;
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 mod net;
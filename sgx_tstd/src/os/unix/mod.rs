@@ -48,14 +48,15 @@
 use crate::os::linux as platform;
 
 pub mod ffi;
+#[cfg(not(minimal_tcb))]
 pub mod fs;
 pub mod io;
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 pub mod net;
 pub mod raw;
 #[cfg(feature = "thread")]
 pub mod thread;
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 pub mod ucred;
 
 /// A prelude for conveniently writing platform-specific code.
@@ -64,10 +65,13 @@ pub mod ucred;
 pub mod prelude {
     #[doc(no_inline)]
     pub use super::ffi::{OsStrExt, OsStringExt};
+    #[cfg(not(minimal_tcb))]
     #[doc(no_inline)]
     pub use super::fs::DirEntryExt;
+    #[cfg(not(minimal_tcb))]
     #[doc(no_inline)]
     pub use super::fs::FileExt;
+    #[cfg(not(minimal_tcb))]
     #[doc(no_inline)]
     pub use super::fs::{FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt};
     #[doc(no_inline)]
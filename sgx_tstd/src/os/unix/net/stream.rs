@@ -205,6 +205,14 @@ impl UnixStream {
 
     /// Gets the peer credentials for this Unix domain socket.
     ///
+    /// The credentials are retrieved from the host with `SO_PEERCRED`, and are
+    /// only as trustworthy as the host; see [`UCred`].
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if the host returns
+    /// credentials of the wrong size.
+    ///
     /// # Examples
     ///
     /// ```no_run
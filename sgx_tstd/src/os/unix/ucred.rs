@@ -27,6 +27,10 @@
 use sgx_libc::{gid_t, pid_t, uid_t};
 
 /// Credentials for a UNIX process for credentials passing.
+///
+/// In an enclave the credentials are reported by the host, which can report
+/// any process. They identify the peer well enough to log it, but must not be
+/// used to authenticate it.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct UCred {
     /// The UID part of the peer credential. This is the effective UID of the process at the domain
@@ -71,10 +75,15 @@ pub mod impl_linux {
                 &mut ucred_size,
             );
 
-            if ret == 0 && ucred_size as usize == mem::size_of::<ucred>() {
+            if ret != 0 {
+                Err(io::Error::last_os_error())
+            } else if ucred_size as usize == mem::size_of::<ucred>() {
                 Ok(UCred { uid: ucred.uid, gid: ucred.gid, pid: Some(ucred.pid) })
             } else {
-                Err(io::Error::last_os_error())
+                Err(io::const_io_error!(
+                    io::ErrorKind::InvalidData,
+                    "malformed peer credentials",
+                ))
             }
         }
     }
@@ -24,6 +24,7 @@ use crate::panicking;
 use crate::sync::atomic::{AtomicUsize, Ordering};
 use crate::sync::{SgxMutex, SgxRwLock};
 use crate::thread::Result;
+use sgx_types::sgx_status_t;
 
 #[doc(hidden)]
 #[allow_internal_unstable(libstd_sys_internals, const_format_args, core_panic)]
@@ -52,6 +53,8 @@ pub use crate::panicking::{set_hook, take_hook};
 
 pub use crate::panicking::update_hook;
 
+pub use crate::panicking::{set_abort_hook, AbortReason, AbortReport};
+
 pub use core::panic::{Location, PanicInfo};
 
 pub use core::panic::{AssertUnwindSafe, RefUnwindSafe, UnwindSafe};
@@ -140,6 +143,58 @@ pub fn catch_unwind<F: FnOnce() -> R + UnwindSafe, R>(f: F) -> Result<R> {
     unsafe { panicking::r#try(f) }
 }
 
+/// How panics are implemented in this enclave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicStrategy {
+    /// Panics unwind the stack, running destructors and poisoning locks, and
+    /// can be caught with [`catch_unwind`].
+    Unwind,
+    /// Panics abort the enclave after reporting through the abort hook.
+    Abort,
+}
+
+/// Returns the panic strategy the enclave was built with.
+///
+/// The strategy follows the `panic` setting of the Cargo profile, which also
+/// selects the `panic_unwind` or `panic_abort` runtime. Every crate linked
+/// into the enclave is built with the same setting.
+pub const fn strategy() -> PanicStrategy {
+    if cfg!(panic = "unwind") { PanicStrategy::Unwind } else { PanicStrategy::Abort }
+}
+
+/// Runs the body of an ECALL so that no panic escapes to the untrusted
+/// caller.
+///
+/// The bridge code generated for an ECALL is C; unwinding into it is
+/// undefined behavior. With the unwind strategy a panic in `f` is caught and
+/// reported as `SGX_ERROR_UNEXPECTED`, after poisoning any locks it held.
+/// With the abort strategy a panic never returns, and `f` runs unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use std::panic;
+/// use sgx_types::sgx_status_t;
+///
+/// #[no_mangle]
+/// pub extern "C" fn ecall_process(len: usize) -> sgx_status_t {
+///     panic::catch_ecall(|| {
+///         assert!(len > 0);
+///         sgx_status_t::SGX_SUCCESS
+///     })
+/// }
+/// ```
+pub fn catch_ecall<F: FnOnce() -> sgx_status_t + UnwindSafe>(f: F) -> sgx_status_t {
+    #[cfg(panic = "unwind")]
+    {
+        catch_unwind(f).unwrap_or(sgx_status_t::SGX_ERROR_UNEXPECTED)
+    }
+    #[cfg(not(panic = "unwind"))]
+    {
+        f()
+    }
+}
+
 /// Triggers a panic without invoking the panic hook.
 ///
 /// This is designed to be used in conjunction with [`catch_unwind`] to, for
@@ -35,13 +35,14 @@ use crate::intrinsics;
 use crate::io::set_output_capture;
 use crate::mem::{self, ManuallyDrop};
 #[cfg(feature = "backtrace")]
-use crate::sync::atomic::{AtomicBool, Ordering};
+use crate::sync::atomic::AtomicBool;
+use crate::sync::atomic::Ordering;
 use crate::sync::{PoisonError, SgxRwLock as RwLock};
 #[cfg(feature = "stdio")]
 use crate::sys::stdio::panic_output;
 #[cfg(feature = "backtrace")]
 use crate::sys_common::backtrace;
-#[cfg(feature = "stdio")]
+use crate::sync::atomic::AtomicPtr;
 use crate::sys_common::thread_info;
 use crate::thread;
 
@@ -109,6 +110,150 @@ impl Default for Hook {
 
 static HOOK: RwLock<Hook> = RwLock::new(Hook::Default);
 
+/// Why the enclave is aborting, as recorded in an [`AbortReport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AbortReason {
+    /// A thread panicked and the enclave was built with `panic = "abort"`.
+    Panic,
+    /// A thread panicked while it was already panicking.
+    PanicWhilePanicking,
+    /// The panic hook itself kept panicking.
+    RecursivePanic,
+    /// A thread panicked after [`always_abort`](crate::panic::always_abort).
+    AlwaysAbort,
+    /// A panic was raised where unwinding is not allowed.
+    CannotUnwind,
+}
+
+impl AbortReason {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            AbortReason::Panic => "panic",
+            AbortReason::PanicWhilePanicking => "panic_while_panicking",
+            AbortReason::RecursivePanic => "recursive_panic",
+            AbortReason::AlwaysAbort => "always_abort",
+            AbortReason::CannotUnwind => "cannot_unwind",
+        }
+    }
+}
+
+/// A description of an enclave abort caused by a panic.
+///
+/// The report is handed to the abort hook immediately before the enclave
+/// aborts. Its [`Display`](fmt::Display) form is a single line of
+/// `key=value` fields, which the default hook writes to standard error so
+/// the host can collect it.
+#[derive(Debug)]
+pub struct AbortReport<'a> {
+    reason: AbortReason,
+    location: &'a Location<'a>,
+    message: Option<&'a str>,
+    thread: Option<&'a str>,
+    request_id: Option<crate::ecall::RequestId>,
+}
+
+impl<'a> AbortReport<'a> {
+    /// Returns why the enclave is aborting.
+    pub fn reason(&self) -> AbortReason {
+        self.reason
+    }
+
+    /// Returns where the panic was raised.
+    pub fn location(&self) -> &Location<'_> {
+        self.location
+    }
+
+    /// Returns the panic message, if it is a string and could be obtained
+    /// safely.
+    pub fn message(&self) -> Option<&str> {
+        self.message
+    }
+
+    /// Returns the name of the panicking thread.
+    pub fn thread(&self) -> Option<&str> {
+        self.thread
+    }
+
+    /// Returns the request ID of the ECALL being served, if any.
+    pub fn request_id(&self) -> Option<crate::ecall::RequestId> {
+        self.request_id
+    }
+}
+
+impl fmt::Display for AbortReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "enclave abort: reason={} location={} thread={:?}",
+            self.reason.as_str(),
+            self.location,
+            self.thread.unwrap_or("<unnamed>"),
+        )?;
+        if let Some(id) = self.request_id {
+            write!(f, " request_id={id}")?;
+        }
+        if let Some(message) = self.message {
+            write!(f, " message={message:?}")?;
+        }
+        Ok(())
+    }
+}
+
+// A `fn(&AbortReport<'_>)`, or null for the default hook. A plain function
+// pointer keeps the abort path free of locks and allocation.
+static ABORT_HOOK: AtomicPtr<()> = AtomicPtr::new(crate::ptr::null_mut());
+
+/// Registers a function to run with the [`AbortReport`] just before the
+/// enclave aborts because of a panic, replacing any previous one.
+///
+/// The function runs after the panic hook, with the enclave in an unknown
+/// state. It must not panic and should do no more than record the report.
+pub fn set_abort_hook(hook: fn(&AbortReport<'_>)) {
+    ABORT_HOOK.store(hook as *mut (), Ordering::Release);
+}
+
+fn default_abort_hook(report: &AbortReport<'_>) {
+    rtprintpanic!("{report}\n");
+}
+
+/// Hands a report to the abort hook, then aborts the enclave.
+fn abort_with_report(
+    reason: AbortReason,
+    payload: Option<&mut dyn BoxMeUp>,
+    location: &Location<'_>,
+) -> ! {
+    let message = payload.and_then(|p| payload_as_str(p.get()));
+    // Looking up the thread may allocate the first time; skip it when the
+    // abort is meant to avoid allocation.
+    let thread = match reason {
+        AbortReason::AlwaysAbort | AbortReason::RecursivePanic => None,
+        _ => thread_info::current_thread(),
+    };
+    let report = AbortReport {
+        reason,
+        location,
+        message,
+        thread: thread.as_ref().and_then(|t| t.name()),
+        request_id: crate::ecall::current().map(|cx| cx.request_id()),
+    };
+    let hook = ABORT_HOOK.load(Ordering::Acquire);
+    if hook.is_null() {
+        default_abort_hook(&report);
+    } else {
+        let hook: fn(&AbortReport<'_>) = unsafe { mem::transmute(hook) };
+        hook(&report);
+    }
+    rsgx_abort()
+}
+
+fn payload_as_str(payload: &(dyn Any + Send)) -> Option<&str> {
+    match payload.downcast_ref::<&'static str>() {
+        Some(s) => Some(s),
+        None => payload.downcast_ref::<String>().map(|s| &s[..]),
+    }
+}
+
 /// Registers a custom panic hook, replacing any that was previously registered.
 ///
 /// The panic hook is invoked when a thread panics, but before the panic runtime
@@ -700,7 +845,8 @@ fn rust_panic_with_hook(
             let panicinfo = PanicInfo::internal_constructor(message, location, can_unwind);
             rtprintpanic!("{panicinfo}\npanicked after panic::always_abort(), aborting.\n");
         }
-        rsgx_abort()
+        let reason = if panics > 2 { AbortReason::RecursivePanic } else { AbortReason::AlwaysAbort };
+        abort_with_report(reason, None, location)
     }
 
     let mut info = PanicInfo::internal_constructor(message, location, can_unwind);
@@ -731,9 +877,16 @@ fn rust_panic_with_hook(
         // just abort. In the future we may consider resuming
         // unwinding or otherwise exiting the thread cleanly.
         rtprintpanic!("thread panicked while panicking. aborting.\n");
-        rsgx_abort()
+        let reason =
+            if panics > 1 { AbortReason::PanicWhilePanicking } else { AbortReason::CannotUnwind };
+        abort_with_report(reason, Some(payload), location)
     }
 
+    // The abort runtime would stop the enclave without saying why.
+    #[cfg(not(panic = "unwind"))]
+    abort_with_report(AbortReason::Panic, Some(payload), location);
+
+    #[cfg(panic = "unwind")]
     rust_panic(payload)
 }
 
@@ -89,7 +89,7 @@ use crate::cmp;
 use crate::collections::TryReserveError;
 use crate::error::Error;
 use crate::fmt;
-#[cfg(feature = "untrusted_fs")]
+#[cfg(tstd_untrusted_fs)]
 use crate::fs;
 use crate::hash::{Hash, Hasher};
 use crate::io;
@@ -2495,7 +2495,7 @@ impl Path {
     /// let metadata = path.metadata().expect("metadata call failed");
     /// println!("{:?}", metadata.file_type());
     /// ```
-    #[cfg(feature = "untrusted_fs")]
+    #[cfg(tstd_untrusted_fs)]
     #[inline]
     pub fn metadata(&self) -> io::Result<fs::Metadata> {
         fs::metadata(self)
@@ -2514,7 +2514,7 @@ impl Path {
     /// let metadata = path.symlink_metadata().expect("symlink_metadata call failed");
     /// println!("{:?}", metadata.file_type());
     /// ```
-    #[cfg(feature = "untrusted_fs")]
+    #[cfg(tstd_untrusted_fs)]
     #[inline]
     pub fn symlink_metadata(&self) -> io::Result<fs::Metadata> {
         fs::symlink_metadata(self)
@@ -2533,7 +2533,7 @@ impl Path {
     /// let path = Path::new("/foo/test/../test/bar.rs");
     /// assert_eq!(path.canonicalize().unwrap(), PathBuf::from("/foo/test/bar.rs"));
     /// ```
-    #[cfg(feature = "untrusted_fs")]
+    #[cfg(tstd_untrusted_fs)]
     #[inline]
     pub fn canonicalize(&self) -> io::Result<PathBuf> {
         fs::canonicalize(self)
@@ -2551,7 +2551,7 @@ impl Path {
     /// let path = Path::new("/laputa/sky_castle.rs");
     /// let path_link = path.read_link().expect("read_link call failed");
     /// ```
-    #[cfg(feature = "untrusted_fs")]
+    #[cfg(tstd_untrusted_fs)]
     #[inline]
     pub fn read_link(&self) -> io::Result<PathBuf> {
         fs::read_link(self)
@@ -2576,7 +2576,7 @@ impl Path {
     ///     }
     /// }
     /// ```
-    #[cfg(feature = "untrusted_fs")]
+    #[cfg(tstd_untrusted_fs)]
     #[inline]
     pub fn read_dir(&self) -> io::Result<fs::ReadDir> {
         fs::read_dir(self)
@@ -2606,7 +2606,7 @@ impl Path {
     /// check errors, call [`Path::try_exists`].
     ///
     /// [`try_exists()`]: Self::try_exists
-    #[cfg(feature = "untrusted_fs")]
+    #[cfg(tstd_untrusted_fs)]
     #[must_use]
     #[inline]
     pub fn exists(&self) -> bool {
@@ -2635,7 +2635,7 @@ impl Path {
     /// ```
     ///
     /// [`exists()`]: Self::exists
-    #[cfg(feature = "untrusted_fs")]
+    #[cfg(tstd_untrusted_fs)]
     #[inline]
     pub fn try_exists(&self) -> io::Result<bool> {
         fs::try_exists(self)
@@ -2668,7 +2668,7 @@ impl Path {
     /// it. Only using `is_file` can break workflows like `diff <( prog_a )` on
     /// a Unix-like system for example. See [`fs::File::open`] or
     /// [`fs::OpenOptions::open`] for more information.
-    #[cfg(feature = "untrusted_fs")]
+    #[cfg(tstd_untrusted_fs)]
     #[must_use]
     pub fn is_file(&self) -> bool {
         fs::metadata(self).map(|m| m.is_file()).unwrap_or(false)
@@ -2695,7 +2695,7 @@ impl Path {
     /// This is a convenience function that coerces errors to false. If you want to
     /// check errors, call [`fs::metadata`] and handle its [`Result`]. Then call
     /// [`fs::Metadata::is_dir`] if it was [`Ok`].
-    #[cfg(feature = "untrusted_fs")]
+    #[cfg(tstd_untrusted_fs)]
     #[must_use]
     pub fn is_dir(&self) -> bool {
         fs::metadata(self).map(|m| m.is_dir()).unwrap_or(false)
@@ -2727,7 +2727,7 @@ impl Path {
     /// This is a convenience function that coerces errors to false. If you want to
     /// check errors, call [`fs::symlink_metadata`] and handle its [`Result`]. Then call
     /// [`fs::Metadata::is_symlink`] if it was [`Ok`].
-    #[cfg(feature = "untrusted_fs")]
+    #[cfg(tstd_untrusted_fs)]
     #[must_use]
     pub fn is_symlink(&self) -> bool {
         fs::symlink_metadata(self).map(|m| m.is_symlink()).unwrap_or(false)
@@ -3098,7 +3098,7 @@ impl Error for StripPrefixError {
 ///
 /// [posix-semantics]: https://pubs.opengroup.org/onlinepubs/9699919799/basedefs/V1_chap04.html#tag_04_13
 /// [windows-path]: https://docs.microsoft.com/en-us/windows/win32/api/fileapi/nf-fileapi-getfullpathnamew
-#[cfg(feature = "untrusted_fs")]
+#[cfg(tstd_untrusted_fs)]
 pub fn absolute<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
     _absolute(path)
 }
@@ -0,0 +1,212 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Counts of asynchronous enclave exits, for transition profiling.
+//!
+//! The host sees every ECALL and OCALL of its threads and keeps their counts
+//! and the time spent in OCALLs (see `sgx_urts::perf`), but it never learns
+//! of an AEX: the interrupt or fault is handled by the kernel and the thread
+//! resumes in the enclave. The enclave does learn of them through AEX-Notify.
+//! [`enable`] registers a handler that counts the exits of the current
+//! thread, and `SgxEnclave::perf` in `sgx_urts` collects the counts through
+//! the `t_perf_ecall` ECALL declared in `sgx_perf.edl`, next to the host
+//! counters.
+//!
+//! Counts are kept per TCS, since that is what the enclave knows of a
+//! thread. The enclave must be built with AEX-Notify enabled, otherwise no
+//! exit is counted.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::perf;
+//!
+//! #[no_mangle]
+//! pub extern "C" fn ecall_serve() -> sgx_status_t {
+//!     let _ = perf::enable();
+//!     serve();
+//!     sgx_status_t::SGX_SUCCESS
+//! }
+//! ```
+
+use crate::io;
+use crate::slice;
+use crate::sync::atomic::{AtomicU64, Ordering};
+use crate::sync::{PoisonError, SgxMutex};
+use crate::vec::Vec;
+use sgx_trts::aex::{rsgx_register_aex_handler, rsgx_set_ssa_aexnotify};
+use sgx_trts::enclave::SgxThreadData;
+use sgx_types::{c_void, sgx_exception_info_t, sgx_status_t};
+
+const REPORT_VERSION: u8 = 1;
+
+struct Counter {
+    tcs: usize,
+    aexs: AtomicU64,
+}
+
+// One counter per TCS that ever enabled counting. They are never freed, as
+// the AEX handler of a thread refers to its counter for as long as the TCS
+// exists, and there are as many as the enclave has TCSs at most.
+static COUNTERS: SgxMutex<Vec<&'static Counter>> = SgxMutex::new(Vec::new());
+
+fn counter(tcs: usize) -> &'static Counter {
+    let mut counters = COUNTERS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(counter) = counters.iter().find(|c| c.tcs == tcs) {
+        return counter;
+    }
+    let counter: &'static Counter = Box::leak(Box::new(Counter {
+        tcs,
+        aexs: AtomicU64::new(0),
+    }));
+    counters.push(counter);
+    counter
+}
+
+extern "C" fn count_aex(_info: *const sgx_exception_info_t, args: *const c_void) {
+    let counter = unsafe { &*(args as *const Counter) };
+    counter.aexs.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Starts counting the asynchronous exits of the current thread.
+///
+/// The handler stays registered with the TCS of the thread, so calling it
+/// again, in this ECALL or a later one on the same TCS, only re-enables
+/// AEX-Notify.
+///
+/// # Errors
+///
+/// Fails if AEX-Notify cannot be enabled, such as on an SDK or a platform
+/// without it.
+pub fn enable() -> io::Result<()> {
+    let counter = counter(SgxThreadData::current().get_tcs());
+    match rsgx_register_aex_handler(count_aex, counter as *const Counter as *const c_void) {
+        // The list node of the handler is owned by the TCS from now on.
+        Ok(_) => {}
+        // The handler is registered with this TCS already.
+        Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER) => {}
+        Err(err) => return Err(io::Error::from_sgx_error(err)),
+    }
+    rsgx_set_ssa_aexnotify(true).map_err(io::Error::from_sgx_error)
+}
+
+/// Stops counting the asynchronous exits of the current thread, by
+/// disabling AEX-Notify for it. The count is kept.
+pub fn disable() -> io::Result<()> {
+    rsgx_set_ssa_aexnotify(false).map_err(io::Error::from_sgx_error)
+}
+
+/// The number of asynchronous exits counted on the TCS of the current
+/// thread.
+pub fn aex_count() -> u64 {
+    let tcs = SgxThreadData::current().get_tcs();
+    COUNTERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .find(|c| c.tcs == tcs)
+        .map_or(0, |c| c.aexs.load(Ordering::Relaxed))
+}
+
+/// The exits counted on one TCS.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ThreadCount {
+    /// The address of the TCS.
+    pub tcs: usize,
+    pub aexs: u64,
+}
+
+/// The counts of all TCSs that enabled counting, in the order they did.
+pub fn aex_counts() -> Vec<ThreadCount> {
+    COUNTERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .map(|c| ThreadCount {
+            tcs: c.tcs,
+            aexs: c.aexs.load(Ordering::Relaxed),
+        })
+        .collect()
+}
+
+/// Sets all counts to zero.
+pub fn reset() {
+    for counter in COUNTERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+    {
+        counter.aexs.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Encodes the counts as the `t_perf_ecall` ECALL returns them.
+///
+/// All integers are little-endian: the version (1) as a `u8`, the number of
+/// TCSs as a `u32`, then for each TCS its address and its count of exits as
+/// `u64`s.
+pub fn encode(counts: &[ThreadCount]) -> Vec<u8> {
+    let counts = &counts[..counts.len().min(u32::MAX as usize)];
+    let mut out = Vec::with_capacity(5 + counts.len() * 16);
+    out.push(REPORT_VERSION);
+    out.extend_from_slice(&(counts.len() as u32).to_le_bytes());
+    for count in counts {
+        out.extend_from_slice(&(count.tcs as u64).to_le_bytes());
+        out.extend_from_slice(&count.aexs.to_le_bytes());
+    }
+    out
+}
+
+///
+/// Writes the encoded counts of all TCSs (see [`encode`]) to `buf`, on
+/// behalf of the host.
+///
+/// # Parameters
+///
+/// **buf**, **cap**
+///
+/// The buffer of the host for the counts, and its size.
+///
+/// **len**
+///
+/// Receives the size of the encoded counts, also when they do not fit.
+///
+/// # Errors
+///
+/// **SGX_ERROR_INVALID_PARAMETER**
+///
+/// A pointer is null.
+///
+/// **SGX_ERROR_OUT_OF_MEMORY**
+///
+/// The counts are larger than `cap`; `len` holds the size they need.
+///
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[no_mangle]
+pub extern "C" fn t_perf_ecall(buf: *mut u8, cap: usize, len: *mut usize) -> sgx_status_t {
+    if len.is_null() || (buf.is_null() && cap > 0) {
+        return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+    }
+    let encoded = encode(&aex_counts());
+    unsafe { *len = encoded.len() };
+    if encoded.len() > cap {
+        return sgx_status_t::SGX_ERROR_OUT_OF_MEMORY;
+    }
+    let out = unsafe { slice::from_raw_parts_mut(buf, cap) };
+    out[..encoded.len()].copy_from_slice(&encoded);
+    sgx_status_t::SGX_SUCCESS
+}
@@ -0,0 +1,628 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Warm-up before serving traffic.
+//!
+//! The first request an enclave serves pays for everything that is set up
+//! lazily: thread data, hash seeds and the standard streams of the runtime,
+//! the seal keys derived on first use and the pools of the application. On
+//! large enclaves this shows as a latency spike on every restart.
+//!
+//! [`prewarm`] runs that work up front. The host triggers it through the
+//! `t_prewarm_ecall` ECALL declared in `sgx_prewarm.edl` (see
+//! `SgxEnclave::prewarm` in `sgx_urts`) after creating the enclave and before
+//! routing traffic to it. The runtime warms itself and, if a reserve is set
+//! with [`set_heap_reserve`], touches that much heap; then the tasks
+//! registered with [`register`] run in registration order. A task that
+//! succeeded never runs again, one that failed is retried by the next
+//! [`prewarm`].
+//!
+//! Seal keys are warmed with `sgx_tseal::rsgx_prewarm_seal_key`, from a task
+//! that knows the key requests of the blobs the enclave will unseal.
+//!
+//! # Snapshots
+//!
+//! Rebuilding large caches can dominate the cold start even when warm-up
+//! runs ahead of traffic. State registered with [`register_state`] can be
+//! sealed to a snapshot on shutdown and restored at the next launch:
+//! [`enable_snapshots`] restores the snapshot at the start of the next
+//! [`prewarm`] and saves a new one when the runtime exits. The snapshot is
+//! an [`SgxFile`](crate::sgxfs::SgxFile), so only enclaves of the same signer
+//! can read it, and records the identity of the enclave that wrote it. It is
+//! restored only if that identity passes the [`Binding`] of the
+//! [`SnapshotPolicy`]; otherwise the enclave starts cold.
+//!
+//! A snapshot can be replayed by the host, so it must only hold state that
+//! is safe to roll back, such as caches. State of
+//! [`StateClass::SessionKeys`] is left out unless the policy explicitly
+//! includes it.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::prewarm;
+//!
+//! prewarm::set_heap_reserve(64 * 1024 * 1024);
+//! prewarm::register("sessions", || {
+//!     // allocate the session pool
+//!     Ok(())
+//! })?;
+//!
+//! prewarm::register_state(
+//!     "routes",
+//!     prewarm::StateClass::Cache,
+//!     || Ok(routes::export()),
+//!     |bytes| routes::import(bytes),
+//! )?;
+//! prewarm::enable_snapshots("warm.snapshot", prewarm::SnapshotPolicy::new())?;
+//!
+//! let report = prewarm::prewarm();
+//! assert!(report.is_complete());
+//! ```
+
+use crate::io;
+use crate::path::{Path, PathBuf};
+use crate::ptr;
+use crate::sgxfs;
+use crate::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::sync::{OnceLock, PoisonError, SgxMutex};
+use crate::vec::Vec;
+use sgx_types::{sgx_self_report, sgx_status_t, SGX_FLAGS_DEBUG};
+
+// Heap pages are touched at this stride.
+const PAGE_SIZE: usize = 4096;
+
+/// A warm-up task. It should be idempotent: a failed task is run again.
+pub type Task = fn() -> io::Result<()>;
+
+struct Entry {
+    name: &'static str,
+    task: Task,
+    done: bool,
+}
+
+static TASKS: SgxMutex<Vec<Entry>> = SgxMutex::new(Vec::new());
+// Serializes runs, so that no task runs twice at the same time.
+static RUN: SgxMutex<()> = SgxMutex::new(());
+static HEAP_RESERVE: AtomicUsize = AtomicUsize::new(0);
+static RUNTIME_WARM: AtomicBool = AtomicBool::new(false);
+
+/// Registers a warm-up task called `name`.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::AlreadyExists`] if a task of that name is
+/// registered.
+pub fn register(name: &'static str, task: Task) -> io::Result<()> {
+    let mut tasks = TASKS.lock().unwrap_or_else(PoisonError::into_inner);
+    if tasks.iter().any(|entry| entry.name == name) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "a warm-up task of that name is registered",
+        ));
+    }
+    tasks.push(Entry {
+        name,
+        task,
+        done: false,
+    });
+    Ok(())
+}
+
+/// Sets the number of heap bytes the next [`prewarm`] touches, so that the
+/// allocator has grown its pool (and, with EDMM, the pages are committed)
+/// before the first request.
+pub fn set_heap_reserve(bytes: usize) {
+    HEAP_RESERVE.store(bytes, Ordering::Relaxed);
+}
+
+/// Returns true once a [`prewarm`] completed with all tasks done.
+pub fn is_warm() -> bool {
+    RUNTIME_WARM.load(Ordering::Acquire)
+        && TASKS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .all(|entry| entry.done)
+}
+
+/// The outcome of a [`prewarm`].
+#[derive(Debug, Default)]
+pub struct Report {
+    ran: Vec<&'static str>,
+    failed: Vec<(&'static str, io::Error)>,
+}
+
+impl Report {
+    /// The tasks that ran and succeeded, in order.
+    pub fn ran(&self) -> &[&'static str] {
+        &self.ran
+    }
+
+    /// The tasks that failed, with their errors.
+    pub fn failed(&self) -> &[(&'static str, io::Error)] {
+        &self.failed
+    }
+
+    /// Returns true if no task failed.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+fn warm_runtime() {
+    let _ = crate::thread::current();
+    let _ = crate::collections::hash_map::RandomState::new();
+    #[cfg(feature = "stdio")]
+    {
+        let _ = crate::io::stdout();
+        let _ = crate::io::stderr();
+    }
+}
+
+fn warm_heap(bytes: usize) -> io::Result<()> {
+    let mut reserve: Vec<u8> = Vec::new();
+    reserve.try_reserve_exact(bytes).map_err(|_| {
+        io::const_io_error!(
+            io::ErrorKind::OutOfMemory,
+            "cannot allocate the heap reserve"
+        )
+    })?;
+    let base = reserve.as_mut_ptr();
+    for offset in (0..bytes).step_by(PAGE_SIZE) {
+        unsafe { ptr::write_volatile(base.add(offset), 0) };
+    }
+    Ok(())
+}
+
+/// Warms the runtime and runs the pending warm-up tasks.
+///
+/// Concurrent calls run one after the other; the later ones find the work
+/// done.
+pub fn prewarm() -> Report {
+    let _run = RUN.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut report = Report::default();
+
+    if !RUNTIME_WARM.load(Ordering::Acquire) {
+        warm_runtime();
+        RUNTIME_WARM.store(true, Ordering::Release);
+    }
+    if let Some(snapshots) = SNAPSHOTS.get() {
+        if !SNAPSHOT_RESTORED.swap(true, Ordering::AcqRel) {
+            match restore_snapshot(&snapshots.path, &snapshots.policy) {
+                Ok(_) => (),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => report.failed.push(("snapshot", e)),
+            }
+        }
+    }
+    let reserve = HEAP_RESERVE.swap(0, Ordering::Relaxed);
+    if reserve > 0 {
+        if let Err(e) = warm_heap(reserve) {
+            HEAP_RESERVE.store(reserve, Ordering::Relaxed);
+            report.failed.push(("heap", e));
+        }
+    }
+
+    let pending: Vec<(&'static str, Task)> = TASKS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .filter(|entry| !entry.done)
+        .map(|entry| (entry.name, entry.task))
+        .collect();
+    for (name, task) in pending {
+        // Tasks run without the lock, so that they may register others.
+        let result = task();
+        let mut tasks = TASKS.lock().unwrap_or_else(PoisonError::into_inner);
+        match result {
+            Ok(()) => {
+                if let Some(entry) = tasks.iter_mut().find(|entry| entry.name == name) {
+                    entry.done = true;
+                }
+                report.ran.push(name);
+            }
+            Err(e) => report.failed.push((name, e)),
+        }
+    }
+    report
+}
+
+///
+/// Runs [`prewarm`] on behalf of the host.
+///
+/// # Errors
+///
+/// **SGX_ERROR_UNEXPECTED**
+///
+/// A warm-up task failed. It is retried by the next call.
+///
+#[no_mangle]
+pub extern "C" fn t_prewarm_ecall() -> sgx_status_t {
+    if prewarm().is_complete() {
+        sgx_status_t::SGX_SUCCESS
+    } else {
+        sgx_status_t::SGX_ERROR_UNEXPECTED
+    }
+}
+
+/// Saves a piece of warm state into a snapshot.
+pub type SaveFn = fn() -> io::Result<Vec<u8>>;
+
+/// Restores a piece of warm state from a snapshot.
+pub type RestoreFn = fn(&[u8]) -> io::Result<()>;
+
+/// What a piece of warm state holds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StateClass {
+    /// Derived data that can be rebuilt, such as lookup tables or parsed
+    /// configuration.
+    Cache,
+    /// Keys of live sessions. Left out of snapshots by default.
+    SessionKeys,
+}
+
+impl StateClass {
+    fn to_u8(self) -> u8 {
+        match self {
+            StateClass::Cache => 0,
+            StateClass::SessionKeys => 1,
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<StateClass> {
+        match value {
+            0 => Some(StateClass::Cache),
+            1 => Some(StateClass::SessionKeys),
+            _ => None,
+        }
+    }
+}
+
+/// Which enclaves may restore a snapshot.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    /// Only an enclave with the same measurement, configuration and SVN as
+    /// the one that wrote the snapshot.
+    Enclave,
+    /// Any enclave of the same signer and product, with an SVN at least that
+    /// of the one that wrote the snapshot.
+    Signer,
+}
+
+/// What goes into a snapshot and who may restore it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotPolicy {
+    binding: Binding,
+    session_keys: bool,
+}
+
+impl SnapshotPolicy {
+    /// A policy bound to the exact enclave that leaves session keys out.
+    pub const fn new() -> SnapshotPolicy {
+        SnapshotPolicy {
+            binding: Binding::Enclave,
+            session_keys: false,
+        }
+    }
+
+    /// Sets which enclaves may restore the snapshot.
+    pub fn binding(mut self, binding: Binding) -> SnapshotPolicy {
+        self.binding = binding;
+        self
+    }
+
+    /// Includes state of [`StateClass::SessionKeys`].
+    pub fn include_session_keys(mut self, include: bool) -> SnapshotPolicy {
+        self.session_keys = include;
+        self
+    }
+
+    fn includes(&self, class: StateClass) -> bool {
+        match class {
+            StateClass::Cache => true,
+            StateClass::SessionKeys => self.session_keys,
+        }
+    }
+}
+
+impl Default for SnapshotPolicy {
+    fn default() -> SnapshotPolicy {
+        SnapshotPolicy::new()
+    }
+}
+
+struct State {
+    name: &'static str,
+    class: StateClass,
+    save: SaveFn,
+    restore: RestoreFn,
+}
+
+struct Snapshots {
+    path: PathBuf,
+    policy: SnapshotPolicy,
+}
+
+static STATES: SgxMutex<Vec<State>> = SgxMutex::new(Vec::new());
+static SNAPSHOTS: OnceLock<Snapshots> = OnceLock::new();
+static SNAPSHOT_RESTORED: AtomicBool = AtomicBool::new(false);
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"SGXWARM1";
+const MAX_STATES: usize = 1024;
+
+/// Registers a piece of warm state called `name` for snapshots.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::AlreadyExists`] if state of that name is
+/// registered, and with [`io::ErrorKind::InvalidInput`] if the name is
+/// longer than 255 bytes.
+pub fn register_state(
+    name: &'static str,
+    class: StateClass,
+    save: SaveFn,
+    restore: RestoreFn,
+) -> io::Result<()> {
+    if name.len() > u8::MAX as usize {
+        return Err(io::const_io_error!(
+            io::ErrorKind::InvalidInput,
+            "warm state name is too long",
+        ));
+    }
+    let mut states = STATES.lock().unwrap_or_else(PoisonError::into_inner);
+    if states.iter().any(|state| state.name == name) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "warm state of that name is registered",
+        ));
+    }
+    states.push(State {
+        name,
+        class,
+        save,
+        restore,
+    });
+    Ok(())
+}
+
+/// Restores the snapshot at `path` at the start of the next [`prewarm`], and
+/// saves a new one there when the runtime exits.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::AlreadyExists`] if snapshots are enabled.
+pub fn enable_snapshots<P: AsRef<Path>>(path: P, policy: SnapshotPolicy) -> io::Result<()> {
+    let path = path.as_ref().to_path_buf();
+    SNAPSHOTS
+        .set(Snapshots {
+            path: path.clone(),
+            policy,
+        })
+        .map_err(|_| io::const_io_error!(io::ErrorKind::AlreadyExists, "snapshots are enabled"))?;
+    let _ = crate::sys_common::at_exit(move || {
+        let _ = save_snapshot(&path, &policy);
+    });
+    Ok(())
+}
+
+// The identity of the running enclave, as recorded in snapshots.
+#[derive(PartialEq, Eq)]
+struct Identity {
+    mr_enclave: [u8; 32],
+    mr_signer: [u8; 32],
+    config_id: [u8; 64],
+    prod_id: u16,
+    svn: u16,
+    config_svn: u16,
+    debug: bool,
+}
+
+const IDENTITY_SIZE: usize = 32 + 32 + 64 + 2 + 2 + 2 + 1;
+
+impl Identity {
+    fn current() -> Identity {
+        let body = unsafe { &(*sgx_self_report()).body };
+        Identity {
+            mr_enclave: body.mr_enclave.m,
+            mr_signer: body.mr_signer.m,
+            config_id: body.config_id,
+            prod_id: body.isv_prod_id,
+            svn: body.isv_svn,
+            config_svn: body.config_svn,
+            debug: body.attributes.flags & SGX_FLAGS_DEBUG != 0,
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.mr_enclave);
+        out.extend_from_slice(&self.mr_signer);
+        out.extend_from_slice(&self.config_id);
+        out.extend_from_slice(&self.prod_id.to_le_bytes());
+        out.extend_from_slice(&self.svn.to_le_bytes());
+        out.extend_from_slice(&self.config_svn.to_le_bytes());
+        out.push(self.debug as u8);
+    }
+
+    fn decode(bytes: &[u8]) -> Identity {
+        let mut identity = Identity {
+            mr_enclave: [0; 32],
+            mr_signer: [0; 32],
+            config_id: [0; 64],
+            prod_id: u16::from_le_bytes([bytes[128], bytes[129]]),
+            svn: u16::from_le_bytes([bytes[130], bytes[131]]),
+            config_svn: u16::from_le_bytes([bytes[132], bytes[133]]),
+            debug: bytes[134] != 0,
+        };
+        identity.mr_enclave.copy_from_slice(&bytes[..32]);
+        identity.mr_signer.copy_from_slice(&bytes[32..64]);
+        identity.config_id.copy_from_slice(&bytes[64..128]);
+        identity
+    }
+
+    // Whether an enclave of this identity may restore a snapshot written by
+    // `writer`.
+    fn accepts(&self, writer: &Identity, binding: Binding) -> bool {
+        match binding {
+            Binding::Enclave => self == writer,
+            Binding::Signer => {
+                self.mr_signer == writer.mr_signer
+                    && self.prod_id == writer.prod_id
+                    && self.debug == writer.debug
+                    && self.svn >= writer.svn
+            }
+        }
+    }
+}
+
+/// Seals the registered warm state that `policy` includes to `path`, and
+/// returns the number of pieces saved.
+///
+/// # Errors
+///
+/// The first error of a save function or of writing the file.
+pub fn save_snapshot<P: AsRef<Path>>(path: P, policy: &SnapshotPolicy) -> io::Result<usize> {
+    let states: Vec<(&'static str, StateClass, SaveFn)> = STATES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .filter(|state| policy.includes(state.class))
+        .map(|state| (state.name, state.class, state.save))
+        .collect();
+    let mut parts = Vec::with_capacity(states.len());
+    for (name, class, save) in states {
+        parts.push((name, class, save()?));
+    }
+
+    let mut out = Vec::from(&SNAPSHOT_MAGIC[..]);
+    Identity::current().encode(&mut out);
+    out.extend_from_slice(&(parts.len() as u32).to_le_bytes());
+    for (name, class, data) in parts.iter() {
+        out.push(name.len() as u8);
+        out.extend_from_slice(name.as_bytes());
+        out.push(class.to_u8());
+        out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+    sgxfs::write(path, &out)?;
+    Ok(parts.len())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidData,
+                "truncated warm state snapshot",
+            ));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+}
+
+/// Restores the warm state sealed at `path`, and returns the names of the
+/// pieces restored.
+///
+/// Pieces that are not registered, that `policy` excludes or whose restore
+/// function fails are skipped; their state is left to the warm-up tasks.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::PermissionDenied`] if the snapshot was written
+/// by an enclave whose identity `policy` does not accept, with
+/// [`io::ErrorKind::InvalidData`] if it is malformed, and with the error of
+/// reading the file otherwise.
+pub fn restore_snapshot<P: AsRef<Path>>(
+    path: P,
+    policy: &SnapshotPolicy,
+) -> io::Result<Vec<&'static str>> {
+    let bytes = sgxfs::read(path)?;
+    let mut reader = Reader { bytes: &bytes };
+    if reader.take(SNAPSHOT_MAGIC.len())? != SNAPSHOT_MAGIC {
+        return Err(io::const_io_error!(
+            io::ErrorKind::InvalidData,
+            "not a warm state snapshot",
+        ));
+    }
+    let writer = Identity::decode(reader.take(IDENTITY_SIZE)?);
+    if !Identity::current().accepts(&writer, policy.binding) {
+        return Err(io::const_io_error!(
+            io::ErrorKind::PermissionDenied,
+            "the warm state snapshot was written by another enclave",
+        ));
+    }
+
+    let count = reader.u32()? as usize;
+    if count > MAX_STATES {
+        return Err(io::const_io_error!(
+            io::ErrorKind::InvalidData,
+            "too many pieces in warm state snapshot",
+        ));
+    }
+    let mut parts = Vec::with_capacity(count);
+    for _ in 0..count {
+        let name_len = reader.u8()? as usize;
+        let name = reader.take(name_len)?;
+        let class = StateClass::from_u8(reader.u8()?).ok_or_else(|| {
+            io::const_io_error!(io::ErrorKind::InvalidData, "unknown warm state class")
+        })?;
+        let len = usize::try_from(reader.u64()?).unwrap_or(usize::MAX);
+        parts.push((name, class, reader.take(len)?));
+    }
+
+    let states: Vec<(&'static str, StateClass, RestoreFn)> = STATES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .filter(|state| policy.includes(state.class))
+        .map(|state| (state.name, state.class, state.restore))
+        .collect();
+    let mut restored = Vec::new();
+    for (name, class, data) in parts {
+        let state = states
+            .iter()
+            .find(|state| state.0.as_bytes() == name && state.1 == class);
+        if let Some(&(name, _, restore)) = state {
+            if restore(data).is_ok() {
+                restored.push(name);
+            }
+        }
+    }
+    Ok(restored)
+}
@@ -0,0 +1,269 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Priority classes for OCALL traffic.
+//!
+//! Bulk transfers, such as flushing a large sealed file, occupy the host
+//! threads serving OCALLs (including the untrusted workers of switchless
+//! calls) for a long time. Latency-sensitive control traffic, such as an
+//! attestation round trip, queued behind them can time out.
+//!
+//! Callers tag their I/O with an [`IoClass`] through [`with_class`]. The
+//! layers that move data in volume honor the class of the calling thread:
+//!
+//! * Writes and flushes of [`SgxFile`](crate::sgxfs::SgxFile) and of files
+//!   of the untrusted file system tagged [`IoClass::Bulk`] are split into
+//!   chunks of at most [`Policy::bulk_chunk`] bytes, each admitted on its
+//!   own, so control traffic gets in between.
+//! * At most [`Policy::max_bulk_in_flight`] bulk OCALLs run at the same time.
+//! * While a thread runs with [`IoClass::Control`], bulk OCALLs wait for it
+//!   to finish, but never longer than [`Policy::max_bulk_delay`] so that
+//!   steady control traffic cannot starve bulk transfers.
+//! * The batches of a [`log`](crate::log) shipper are bulk by default.
+//!
+//! Control and normal traffic is never delayed.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::qos::{self, IoClass};
+//!
+//! qos::with_class(IoClass::Control, || {
+//!     // attestation round trip
+//! });
+//!
+//! qos::with_class(IoClass::Bulk, || {
+//!     // file.write_all(&snapshot)?; file.flush()
+//! });
+//! ```
+
+use crate::cell::Cell;
+use crate::sync::{PoisonError, SgxCondvar, SgxMutex, SgxMutexGuard};
+use crate::time::Duration;
+
+/// The priority class of I/O.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum IoClass {
+    /// Latency-sensitive control traffic, such as attestation round trips.
+    Control,
+    /// Traffic without particular requirements.
+    #[default]
+    Normal,
+    /// Throughput-oriented bulk data, which yields to control traffic.
+    Bulk,
+}
+
+thread_local! {
+    static CLASS: Cell<IoClass> = const { Cell::new(IoClass::Normal) };
+}
+
+/// Returns the I/O class of the current thread.
+pub fn current() -> IoClass {
+    CLASS.try_with(Cell::get).unwrap_or_default()
+}
+
+/// Runs `f` with the I/O of the current thread tagged `class`, restoring the
+/// previous class afterwards, also when `f` panics.
+///
+/// Threads spawned by `f` start with [`IoClass::Normal`].
+pub fn with_class<F, R>(class: IoClass, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    struct Reset(IoClass, bool);
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            let _ = CLASS.try_with(|c| c.set(self.0));
+            if self.1 {
+                let mut state = lock();
+                state.control -= 1;
+                if state.control == 0 {
+                    drop(state);
+                    SCHEDULER.cond.notify_all();
+                }
+            }
+        }
+    }
+
+    let control = class == IoClass::Control;
+    if control {
+        lock().control += 1;
+    }
+    let previous = CLASS.try_with(|c| c.replace(class)).unwrap_or_default();
+    let _reset = Reset(previous, control);
+    f()
+}
+
+/// How bulk traffic is held back in favor of control traffic.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Policy {
+    max_bulk_in_flight: usize,
+    bulk_chunk: usize,
+    max_bulk_delay: Duration,
+}
+
+impl Policy {
+    /// Creates the default policy: two bulk OCALLs at a time, in chunks of
+    /// 64 KiB, delayed by control traffic for at most 10 milliseconds.
+    pub const fn new() -> Policy {
+        Policy {
+            max_bulk_in_flight: 2,
+            bulk_chunk: 64 * 1024,
+            max_bulk_delay: Duration::from_millis(10),
+        }
+    }
+
+    /// Sets how many bulk OCALLs may run at the same time; at least one.
+    pub fn max_bulk_in_flight(mut self, max: usize) -> Policy {
+        self.max_bulk_in_flight = max.max(1);
+        self
+    }
+
+    /// Sets the largest amount of data moved by one bulk OCALL; at least one byte.
+    pub fn bulk_chunk(mut self, bytes: usize) -> Policy {
+        self.bulk_chunk = bytes.max(1);
+        self
+    }
+
+    /// Sets how long a bulk OCALL waits at most for control traffic to end.
+    pub fn max_bulk_delay(mut self, delay: Duration) -> Policy {
+        self.max_bulk_delay = delay;
+        self
+    }
+
+    /// Returns the policy in force.
+    pub fn current() -> Policy {
+        lock().policy
+    }
+
+    /// Puts the policy in force.
+    pub fn apply(self) {
+        lock().policy = self;
+        SCHEDULER.cond.notify_all();
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy::new()
+    }
+}
+
+/// Counters of the scheduler, as returned by [`stats`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Threads currently running with [`IoClass::Control`].
+    pub control_active: usize,
+    /// Bulk OCALLs currently running.
+    pub bulk_in_flight: usize,
+    /// Bulk OCALLs admitted so far.
+    pub bulk_admitted: u64,
+    /// Bulk OCALLs that had to wait for control traffic or a free slot.
+    pub bulk_delayed: u64,
+}
+
+/// Returns a snapshot of the scheduler counters.
+pub fn stats() -> Stats {
+    let state = lock();
+    Stats {
+        control_active: state.control,
+        bulk_in_flight: state.bulk,
+        bulk_admitted: state.admitted,
+        bulk_delayed: state.delayed,
+    }
+}
+
+struct State {
+    policy: Policy,
+    control: usize,
+    bulk: usize,
+    admitted: u64,
+    delayed: u64,
+}
+
+struct Scheduler {
+    state: SgxMutex<State>,
+    cond: SgxCondvar,
+}
+
+static SCHEDULER: Scheduler = Scheduler {
+    state: SgxMutex::new(State {
+        policy: Policy::new(),
+        control: 0,
+        bulk: 0,
+        admitted: 0,
+        delayed: 0,
+    }),
+    cond: SgxCondvar::new(),
+};
+
+fn lock() -> SgxMutexGuard<'static, State> {
+    SCHEDULER
+        .state
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+// Held for the duration of one OCALL.
+pub(crate) struct Permit {
+    bulk: bool,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if self.bulk {
+            lock().bulk -= 1;
+            SCHEDULER.cond.notify_all();
+        }
+    }
+}
+
+// Admits one OCALL of the current thread, and returns how many of `len`
+// bytes it may move.
+pub(crate) fn admit(len: usize) -> (Permit, usize) {
+    if current() != IoClass::Bulk {
+        return (Permit { bulk: false }, len);
+    }
+
+    let mut state = lock();
+    let mut delayed = false;
+    if state.control > 0 {
+        delayed = true;
+        let delay = state.policy.max_bulk_delay;
+        state = match SCHEDULER
+            .cond
+            .wait_timeout_while(state, delay, |s| s.control > 0)
+        {
+            Ok((state, _)) => state,
+            Err(e) => e.into_inner().0,
+        };
+    }
+    if state.bulk >= state.policy.max_bulk_in_flight {
+        delayed = true;
+        state = SCHEDULER
+            .cond
+            .wait_while(state, |s| s.bulk >= s.policy.max_bulk_in_flight)
+            .unwrap_or_else(PoisonError::into_inner);
+    }
+    state.bulk += 1;
+    state.admitted += 1;
+    if delayed {
+        state.delayed += 1;
+    }
+    let chunk = state.policy.bulk_chunk;
+    (Permit { bulk: true }, len.min(chunk))
+}
@@ -0,0 +1,362 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Quotas on the untrusted resources used by the enclave.
+//!
+//! A logic bug inside the enclave, such as a file handle leaked per request,
+//! can exhaust resources of the host that other tenants depend on. The sys
+//! layer accounts for every use of the following [`Resource`]s, and refuses
+//! new ones once the configured limit is reached:
+//!
+//! * [`Resource::HostFds`], file descriptors opened on the host through
+//!   files, sockets and pipes. Exceeding it fails with `EMFILE`.
+//! * [`Resource::OutboundConnections`], TCP connections opened with
+//!   `connect`. Exceeding it fails with [`io::ErrorKind::Other`].
+//! * [`Resource::FsWriteBytes`], the total number of bytes written to files
+//!   of the untrusted file system. Writes are shortened to the remaining
+//!   budget, and fail with [`io::ErrorKind::FilesystemQuotaExceeded`] once it
+//!   is used up.
+//!
+//! Usage is tracked whether or not a limit is set, so that [`usage`] also
+//! serves as a metric. Descriptors obtained from outside the standard
+//! library, e.g. with [`FromRawFd`](crate::os::unix::io::FromRawFd) on the
+//! result of a raw OCALL, are not accounted for. The kernel copy paths of
+//! `io::copy` and `fs::copy` write on the host side, so they are not used
+//! while written bytes are limited.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::quota::{self, Limits, Resource};
+//!
+//! Limits::new()
+//!     .max_host_fds(256)
+//!     .max_outbound_connections(32)
+//!     .max_fs_write_bytes(1 << 30)
+//!     .apply();
+//!
+//! let fds = quota::usage(Resource::HostFds);
+//! assert!(fds.current <= 256);
+//! ```
+
+use crate::collections::BTreeMap;
+use crate::io;
+use crate::os::unix::io::RawFd;
+use crate::sync::atomic::{AtomicU64, Ordering};
+use crate::sync::{PoisonError, SgxMutex};
+
+/// An untrusted resource with a quota.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Resource {
+    /// File descriptors open on the host.
+    HostFds,
+    /// Outbound TCP connections.
+    OutboundConnections,
+    /// Bytes written to the untrusted file system since the enclave started.
+    FsWriteBytes,
+}
+
+impl Resource {
+    /// All resources.
+    pub const ALL: [Resource; 3] = [
+        Resource::HostFds,
+        Resource::OutboundConnections,
+        Resource::FsWriteBytes,
+    ];
+
+    fn counter(self) -> &'static Counter {
+        match self {
+            Resource::HostFds => &HOST_FDS,
+            Resource::OutboundConnections => &OUTBOUND_CONNECTIONS,
+            Resource::FsWriteBytes => &FS_WRITE_BYTES,
+        }
+    }
+}
+
+/// A snapshot of the usage of a resource.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// The amount in use; for [`Resource::FsWriteBytes`] the total so far.
+    pub current: u64,
+    /// The highest amount in use at any time.
+    pub peak: u64,
+    /// The configured limit, if any.
+    pub limit: Option<u64>,
+    /// How many requests were refused because of the limit.
+    pub rejected: u64,
+}
+
+const UNLIMITED: u64 = u64::MAX;
+
+// The usage and limit of one resource; also used for the quotas of `tenant`.
+pub(crate) struct Counter {
+    current: AtomicU64,
+    peak: AtomicU64,
+    limit: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl Counter {
+    pub(crate) const fn new() -> Counter {
+        Counter {
+            current: AtomicU64::new(0),
+            peak: AtomicU64::new(0),
+            limit: AtomicU64::new(UNLIMITED),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    // Takes up to `amount` of the remaining quota, and returns how much was
+    // granted; at least `min` or nothing.
+    pub(crate) fn acquire(&self, min: u64, amount: u64) -> u64 {
+        let mut current = self.current.load(Ordering::Relaxed);
+        loop {
+            let limit = self.limit.load(Ordering::Relaxed);
+            let granted = amount.min(limit.saturating_sub(current));
+            if granted < min || (granted == 0 && amount > 0) {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                return 0;
+            }
+            match self.current.compare_exchange_weak(
+                current,
+                current + granted,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    self.peak.fetch_max(current + granted, Ordering::Relaxed);
+                    return granted;
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub(crate) fn release(&self, amount: u64) {
+        let _ = self
+            .current
+            .fetch_update(Ordering::AcqRel, Ordering::Relaxed, |current| {
+                Some(current.saturating_sub(amount))
+            });
+    }
+
+    pub(crate) fn set_limit(&self, limit: Option<u64>) {
+        self.limit.store(limit.unwrap_or(UNLIMITED), Ordering::Relaxed);
+    }
+
+    pub(crate) fn usage(&self) -> Usage {
+        let limit = self.limit.load(Ordering::Relaxed);
+        Usage {
+            current: self.current.load(Ordering::Relaxed),
+            peak: self.peak.load(Ordering::Relaxed),
+            limit: if limit == UNLIMITED {
+                None
+            } else {
+                Some(limit)
+            },
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+static HOST_FDS: Counter = Counter::new();
+static OUTBOUND_CONNECTIONS: Counter = Counter::new();
+static FS_WRITE_BYTES: Counter = Counter::new();
+
+// The accounted descriptors, and whether each is an outbound connection.
+static TRACKED: SgxMutex<BTreeMap<RawFd, bool>> = SgxMutex::new(BTreeMap::new());
+
+/// Sets or, with `None`, removes the limit of `resource`.
+///
+/// Lowering a limit below the current usage takes nothing away; it only
+/// refuses new uses until the usage is below the limit again.
+pub fn set_limit(resource: Resource, limit: Option<u64>) {
+    resource.counter().set_limit(limit);
+}
+
+/// Returns the limit of `resource`, if any.
+pub fn limit(resource: Resource) -> Option<u64> {
+    resource.counter().usage().limit
+}
+
+/// Returns the usage of `resource`.
+pub fn usage(resource: Resource) -> Usage {
+    resource.counter().usage()
+}
+
+/// The limits of all resources, applied together.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    host_fds: Option<u64>,
+    outbound_connections: Option<u64>,
+    fs_write_bytes: Option<u64>,
+}
+
+impl Limits {
+    /// Creates limits that leave every resource unlimited.
+    pub fn new() -> Limits {
+        Limits::default()
+    }
+
+    /// Limits the number of file descriptors open on the host.
+    pub fn max_host_fds(mut self, max: u64) -> Limits {
+        self.host_fds = Some(max);
+        self
+    }
+
+    /// Limits the number of outbound TCP connections.
+    pub fn max_outbound_connections(mut self, max: u64) -> Limits {
+        self.outbound_connections = Some(max);
+        self
+    }
+
+    /// Limits the total number of bytes written to the untrusted file system.
+    pub fn max_fs_write_bytes(mut self, max: u64) -> Limits {
+        self.fs_write_bytes = Some(max);
+        self
+    }
+
+    /// Returns the limits currently in force.
+    pub fn current() -> Limits {
+        Limits {
+            host_fds: limit(Resource::HostFds),
+            outbound_connections: limit(Resource::OutboundConnections),
+            fs_write_bytes: limit(Resource::FsWriteBytes),
+        }
+    }
+
+    /// Returns the limit of `resource`.
+    pub fn get(&self, resource: Resource) -> Option<u64> {
+        match resource {
+            Resource::HostFds => self.host_fds,
+            Resource::OutboundConnections => self.outbound_connections,
+            Resource::FsWriteBytes => self.fs_write_bytes,
+        }
+    }
+
+    /// Puts the limits in force, replacing all previous ones.
+    pub fn apply(&self) {
+        for resource in Resource::ALL {
+            set_limit(resource, self.get(resource));
+        }
+    }
+}
+
+fn tracked() -> crate::sync::SgxMutexGuard<'static, BTreeMap<RawFd, bool>> {
+    TRACKED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Opens `N` host descriptors with `open` within the quota, and accounts for
+// them until they are closed.
+pub(crate) fn open_fds<const N: usize, F>(open: F) -> io::Result<[RawFd; N]>
+where
+    F: FnOnce() -> io::Result<[RawFd; N]>,
+{
+    if HOST_FDS.acquire(N as u64, N as u64) == 0 {
+        return Err(io::Error::from_raw_os_error(sgx_libc::EMFILE));
+    }
+    match open() {
+        Ok(fds) => {
+            let mut tracked = tracked();
+            for fd in fds {
+                if tracked.insert(fd, false).is_some() {
+                    // The host handed out a descriptor we believe is open.
+                    HOST_FDS.release(1);
+                }
+            }
+            Ok(fds)
+        }
+        Err(e) => {
+            HOST_FDS.release(N as u64);
+            Err(e)
+        }
+    }
+}
+
+pub(crate) fn open_fd<F>(open: F) -> io::Result<RawFd>
+where
+    F: FnOnce() -> io::Result<RawFd>,
+{
+    open_fds(|| open().map(|fd| [fd])).map(|[fd]| fd)
+}
+
+// Called when a descriptor is closed.
+pub(crate) fn close_fd(fd: RawFd) {
+    if HOST_FDS.current.load(Ordering::Acquire) == 0 {
+        return;
+    }
+    if let Some(outbound) = tracked().remove(&fd) {
+        HOST_FDS.release(1);
+        if outbound {
+            OUTBOUND_CONNECTIONS.release(1);
+        }
+    }
+}
+
+// Connects the socket `fd` with `connect` within the quota of outbound
+// connections. A socket that is connected already is not counted twice.
+pub(crate) fn connect<F>(fd: RawFd, connect: F) -> io::Result<()>
+where
+    F: FnOnce() -> io::Result<()>,
+{
+    if tracked().get(&fd) == Some(&true) {
+        return connect();
+    }
+    if OUTBOUND_CONNECTIONS.acquire(1, 1) == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "outbound connection quota exceeded",
+        ));
+    }
+    let result = connect();
+    let counted = result.is_ok()
+        && tracked()
+            .get_mut(&fd)
+            .map(|outbound| *outbound = true)
+            .is_some();
+    if !counted {
+        OUTBOUND_CONNECTIONS.release(1);
+    }
+    result
+}
+
+// Writes at most `len` bytes to the untrusted file system with `write`,
+// shortened to the remaining quota.
+pub(crate) fn write_fs<F>(len: usize, write: F) -> io::Result<usize>
+where
+    F: FnOnce(usize) -> io::Result<usize>,
+{
+    if len == 0 {
+        return write(0);
+    }
+    let granted = FS_WRITE_BYTES.acquire(1, len as u64);
+    if granted == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::FilesystemQuotaExceeded,
+            "untrusted file system write quota exceeded",
+        ));
+    }
+    let result = write(granted as usize);
+    let written = *result.as_ref().unwrap_or(&0) as u64;
+    FS_WRITE_BYTES.release(granted - written.min(granted));
+    result
+}
+
+// Whether copies may be done by the host, bypassing `write_fs`.
+pub(crate) fn host_copy_allowed() -> bool {
+    FS_WRITE_BYTES.limit.load(Ordering::Relaxed) == UNLIMITED
+}
@@ -17,10 +17,16 @@
 
 //! Filesystem manipulation operations.
 
+#[cfg(not(minimal_tcb))]
+use crate::ffi::OsString;
 use crate::io::{self, Read, Seek, SeekFrom, Write};
 use crate::path::Path;
+#[cfg(not(minimal_tcb))]
+use crate::path::PathBuf;
 use crate::sys::sgxfs as fs_imp;
 use crate::sys_common::{AsInner, AsInnerMut, FromInner, IntoInner};
+#[cfg(feature = "thread")]
+use crate::thread::ThreadPool;
 use sgx_types::{sgx_align_key_128bit_t, sgx_key_128bit_t};
 
 /// A reference to an open file on the filesystem.
@@ -30,6 +36,13 @@ use sgx_types::{sgx_align_key_128bit_t, sgx_key_128bit_t};
 /// that the file contains internally.
 ///
 /// Files are automatically closed when they go out of scope.
+///
+/// Large files can be processed piecewise through [`BufReader`] and
+/// [`BufWriter`], which only hold their buffer in the enclave; the protected
+/// FS decrypts and encrypts the nodes it reaches as it goes.
+///
+/// [`BufReader`]: crate::io::BufReader
+/// [`BufWriter`]: crate::io::BufWriter
 pub struct SgxFile {
     inner: fs_imp::SgxFile,
 }
@@ -44,6 +57,27 @@ pub struct SgxFile {
 #[derive(Clone, Debug)]
 pub struct OpenOptions(fs_imp::OpenOptions);
 
+/// Metadata information about a file.
+///
+/// This structure is returned from the [`SgxFile::metadata`] method. The
+/// protected FS keeps no more than the length of the plaintext.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Metadata {
+    len: u64,
+}
+
+impl Metadata {
+    /// Returns the size of the plaintext of the file, in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the file holds no plaintext.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
 /// Read the entire contents of a file into a bytes vector.
 ///
 /// This is a convenience function for using SgxFile::open and read_to_end
@@ -157,6 +191,55 @@ impl SgxFile {
     pub fn clear_cache(&self) -> io::Result<()> {
         self.inner.clear_cache()
     }
+
+    /// Queries metadata about the file.
+    ///
+    /// The length is found by seeking to the end of the file and back.
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        self.inner.len().map(|len| Metadata { len })
+    }
+
+    /// Writes the cached data and metadata of the file out to the host.
+    ///
+    /// The protected FS has no way to make the host sync its own caches to
+    /// the disk; the data is durable once the host has written it.
+    pub fn sync_all(&self) -> io::Result<()> {
+        self.inner.sync_all()
+    }
+
+    /// This function is similar to [`sync_all`](SgxFile::sync_all), which
+    /// it is for the protected FS, where data and metadata are written
+    /// together.
+    pub fn sync_data(&self) -> io::Result<()> {
+        self.inner.sync_all()
+    }
+
+    /// Truncates or extends the file.
+    ///
+    /// If `size` is less than the current size of the file, the file is
+    /// shrunk; otherwise it is extended with zeros. The cursor is left where
+    /// it was, or at the new end of the file if that is before it.
+    ///
+    /// The protected FS cannot shrink a file in place. The bytes kept are
+    /// copied to a protected file named after the file with a `.truncate~`
+    /// suffix, the file is rewritten from the copy, and the copy is removed.
+    /// If shrinking fails midway, the bytes kept are left in the copy and
+    /// the file must not be used any further.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file was not opened for writing, and with
+    /// [`io::ErrorKind::Unsupported`] if it was not opened by path.
+    pub fn set_len(&mut self, size: u64) -> io::Result<()> {
+        self.inner.set_len(size)
+    }
+}
+
+/// Indicates how much extra capacity is needed to read the rest of the file.
+fn buffer_capacity_required(file: &SgxFile) -> usize {
+    let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let pos = file.inner.tell().unwrap_or(0);
+    size.saturating_sub(pos) as usize
 }
 
 impl AsInner<fs_imp::SgxFile> for SgxFile {
@@ -179,6 +262,18 @@ impl Read for SgxFile {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.inner.read(buf)
     }
+
+    // Reserves space in the buffer based on the file size when available.
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        buf.reserve(buffer_capacity_required(self));
+        io::default_read_to_end(self, buf)
+    }
+
+    // Reserves space in the buffer based on the file size when available.
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        buf.reserve(buffer_capacity_required(self));
+        io::default_read_to_string(self, buf)
+    }
 }
 
 impl Write for SgxFile {
@@ -194,12 +289,28 @@ impl Seek for SgxFile {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         self.inner.seek(pos)
     }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        self.inner.tell()
+    }
 }
 
 impl<'a> Read for &'a SgxFile {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.inner.read(buf)
     }
+
+    // Reserves space in the buffer based on the file size when available.
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        buf.reserve(buffer_capacity_required(self));
+        io::default_read_to_end(self, buf)
+    }
+
+    // Reserves space in the buffer based on the file size when available.
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        buf.reserve(buffer_capacity_required(self));
+        io::default_read_to_string(self, buf)
+    }
 }
 
 impl<'a> Write for &'a SgxFile {
@@ -215,6 +326,10 @@ impl<'a> Seek for &'a SgxFile {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         self.inner.seek(pos)
     }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        self.inner.tell()
+    }
 }
 
 impl OpenOptions {
@@ -334,6 +449,145 @@ pub fn remove<P: AsRef<Path>>(path: P) -> io::Result<()> {
     fs_imp::remove(path.as_ref())
 }
 
+/// Creates a new, empty sealed directory at the provided path.
+///
+/// A sealed directory holds a manifest, a protected file named
+/// `.sgxfs_manifest`, that lists the protected files created in the
+/// directory. Opening a file of the directory for writing adds it to the
+/// manifest, and [`remove_file`] and [`rename`] keep it up to date, so that
+/// [`read_dir`] and [`exists`] can detect files deleted by the host.
+///
+/// The manifest does not protect against rollback: the host can restore an
+/// older manifest together with the older files it lists. Nor is it bound to
+/// the path of the directory, which the host can move. Creating a file in a
+/// sealed directory costs an extra open of the manifest, and a rewrite of it
+/// if the file is new.
+///
+/// # Errors
+///
+/// Fails if the directory already exists or cannot be created.
+#[cfg(not(minimal_tcb))]
+pub fn create_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    fs_imp::create_dir(path.as_ref())
+}
+
+/// Returns an iterator over the protected files of a sealed directory.
+///
+/// The files are listed from the manifest, in the order of their names, and
+/// the listing of the host is checked to hold all of them before this
+/// function returns. Files on the host that the manifest does not name are
+/// not listed.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::NotFound`] if `path` is not a sealed
+/// directory, and with [`io::ErrorKind::InvalidData`] if a file of the
+/// manifest is missing from the host or the manifest is corrupt.
+#[cfg(not(minimal_tcb))]
+pub fn read_dir<P: AsRef<Path>>(path: P) -> io::Result<ReadDir> {
+    fs_imp::read_dir(path.as_ref()).map(ReadDir)
+}
+
+/// Removes a protected file, and drops it from the manifest of its directory
+/// if that is a sealed directory.
+pub fn remove_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    fs_imp::remove(path.as_ref())
+}
+
+/// Renames a protected file encrypted with the auto key, replacing `to` if
+/// it exists.
+///
+/// The protected FS binds the contents of a file to its name, so the file
+/// is copied through the enclave to its new name and the original removed.
+/// This takes time in the size of the file, and is not atomic: if the
+/// enclave dies in between, both files may exist.
+#[cfg(not(minimal_tcb))]
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()> {
+    fs_imp::rename(from.as_ref(), to.as_ref(), None)
+}
+
+/// Renames a protected file encrypted with `key`, as [`rename`] does.
+#[cfg(not(minimal_tcb))]
+pub fn rename_ex<P: AsRef<Path>, Q: AsRef<Path>>(
+    from: P,
+    to: Q,
+    key: &sgx_key_128bit_t,
+) -> io::Result<()> {
+    fs_imp::rename(from.as_ref(), to.as_ref(), Some(key))
+}
+
+/// Returns `true` if the path points at a protected file.
+///
+/// In a sealed directory, only the files named by the manifest exist, and
+/// one that the host has deleted is an error. Elsewhere this only asks the
+/// host.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::InvalidData`] if the manifest names the file
+/// but the host does not have it, or if the manifest is corrupt.
+#[cfg(not(minimal_tcb))]
+pub fn exists<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    fs_imp::exists(path.as_ref())
+}
+
+/// Iterator over the protected files of a sealed directory.
+///
+/// This iterator is returned from the [`read_dir`] function.
+#[cfg(not(minimal_tcb))]
+pub struct ReadDir(fs_imp::ReadDir);
+
+/// A protected file of a sealed directory, returned by [`ReadDir`].
+#[cfg(not(minimal_tcb))]
+pub struct DirEntry(fs_imp::DirEntry);
+
+#[cfg(not(minimal_tcb))]
+impl Iterator for ReadDir {
+    type Item = DirEntry;
+
+    fn next(&mut self) -> Option<DirEntry> {
+        self.0.next().map(DirEntry)
+    }
+}
+
+#[cfg(not(minimal_tcb))]
+impl DirEntry {
+    /// Returns the full path to the file, the path given to [`read_dir`]
+    /// joined with the file name.
+    pub fn path(&self) -> PathBuf {
+        self.0.path()
+    }
+
+    /// Returns the name of the file, without any leading path component.
+    pub fn file_name(&self) -> OsString {
+        self.0.file_name().to_os_string()
+    }
+}
+
+/// Re-encrypts a protected file from `old_key` to `new_key`, in place.
+///
+/// The plaintext is streamed through the protected FS, a chunk at a time,
+/// into a journal named after the file with a `.rekey~` suffix, encrypted
+/// with `new_key`; the file is then rewritten from the journal and the
+/// journal removed. If the enclave dies in between, the file is either
+/// still intact under `old_key` or can be rewritten from a complete
+/// journal: calling `rekey` again with the same keys picks up where the
+/// last call stopped.
+///
+/// The file must not be open while it is rekeyed.
+///
+/// # Errors
+///
+/// Fails if the file cannot be opened with `old_key`, or if a journal is
+/// left over that cannot be opened with `new_key`.
+pub fn rekey<P: AsRef<Path>>(
+    path: P,
+    old_key: &sgx_key_128bit_t,
+    new_key: &sgx_key_128bit_t,
+) -> io::Result<()> {
+    fs_imp::rekey(path.as_ref(), old_key, new_key)
+}
+
 pub fn export_auto_key<P: AsRef<Path>>(path: P) -> io::Result<sgx_key_128bit_t> {
     fs_imp::export_auto_key(path.as_ref())
 }
@@ -346,6 +600,105 @@ pub fn import_auto_key<P: AsRef<Path>>(path: P, key: &sgx_key_128bit_t) -> io::R
     fs_imp::import_auto_key(path.as_ref(), key)
 }
 
+/// Flushes several protected files at once, on the workers of `pool`.
+///
+/// Flushing a file encrypts and MACs each of its dirty nodes in turn, which
+/// dominates the cost of checkpointing large files. The files are flushed
+/// concurrently, one by the calling thread and the others by jobs of the
+/// pool, and this function returns once all of them are flushed. The result
+/// does not depend on the order the flushes complete in: the first error, in
+/// the order of `files`, is returned. A checkpoint that must not be seen
+/// before its files are durable writes its commit record after this returns.
+///
+/// The nodes of a single file are still encrypted one at a time.
+///
+/// This must not be called from a job of `pool`, which could then wait for
+/// jobs queued behind itself.
+#[cfg(feature = "thread")]
+pub fn flush_all(files: &[&SgxFile], pool: &ThreadPool) -> io::Result<()> {
+    let files: Vec<&fs_imp::SgxFile> = files.iter().map(|file| file.as_inner()).collect();
+    fs_imp::flush_all(&files, pool)
+}
+
+/// A read-only protected file, shared by many threads.
+///
+/// An image serves reads at any offset through [`read_at`], which takes
+/// `&self`, so one image can be shared by all the threads of an enclave, for
+/// instance behind an `Arc`. The plaintext is cached in blocks of
+/// [`IMAGE_BLOCK_SIZE`] bytes, up to the cache size given when the image is
+/// opened. A cached block is read without taking any lock; only reads of
+/// blocks that are not cached go through the protected FS, one at a time.
+/// Blocks stay cached until the image is dropped, which suits large datasets
+/// that are read many times, such as model weights.
+///
+/// The file must not be written while the image is open.
+///
+/// [`read_at`]: SgxFileImage::read_at
+pub struct SgxFileImage {
+    inner: fs_imp::SgxFileImage,
+}
+
+/// The size of the blocks an [`SgxFileImage`] caches.
+pub const IMAGE_BLOCK_SIZE: usize = fs_imp::IMAGE_BLOCK_SIZE;
+
+impl SgxFileImage {
+    /// Opens an image of a file encrypted with the auto key, caching up to
+    /// `cache_size` bytes of it.
+    pub fn open<P: AsRef<Path>>(path: P, cache_size: usize) -> io::Result<SgxFileImage> {
+        let inner = fs_imp::SgxFileImage::open(path.as_ref(), None, cache_size)?;
+        Ok(SgxFileImage { inner })
+    }
+
+    /// Opens an image of a file encrypted with `key`, caching up to
+    /// `cache_size` bytes of it.
+    pub fn open_ex<P: AsRef<Path>>(
+        path: P,
+        key: &sgx_key_128bit_t,
+        cache_size: usize,
+    ) -> io::Result<SgxFileImage> {
+        let inner = fs_imp::SgxFileImage::open(path.as_ref(), Some(key), cache_size)?;
+        Ok(SgxFileImage { inner })
+    }
+
+    /// Returns the size of the plaintext of the file, in bytes.
+    pub fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    /// Returns `true` if the file holds no plaintext.
+    pub fn is_empty(&self) -> bool {
+        self.inner.len() == 0
+    }
+
+    /// Returns the number of bytes of cache the image holds.
+    pub fn cached_size(&self) -> usize {
+        self.inner.cached_size()
+    }
+
+    /// Reads bytes starting at `offset` into `buf`, and returns the number
+    /// of bytes read, which is only short of the size of `buf` at the end of
+    /// the file.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self.inner.read_at(buf, offset)
+    }
+
+    /// Reads the exact number of bytes required to fill `buf` from `offset`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::UnexpectedEof`] if the file ends before
+    /// `buf` is filled.
+    pub fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        if self.read_at(buf, offset)? < buf.len() {
+            return Err(io::const_io_error!(
+                io::ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            ));
+        }
+        Ok(())
+    }
+}
+
 /// Copies the contents of one file to another.
 /// This function will **overwrite** the contents of `to`.
 ///
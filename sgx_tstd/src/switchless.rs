@@ -0,0 +1,142 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Fallback of switchless OCALLs under load.
+//!
+//! A switchless OCALL is posted to a mailbox and spins until an untrusted
+//! worker picks it up, falling back to a regular OCALL only after
+//! `retries_before_fallback` spins. Once every worker is busy, the calls
+//! posted after that only spin, and the enclave thread burns its share of
+//! the wait and of the OCALL. [`__wrap_sgx_ocall_switchless`] stands in for
+//! `sgx_ocall_switchless` of the trusted runtime when the enclave is linked
+//! with `-Wl,--wrap=sgx_ocall_switchless`, which the `switchless` feature
+//! requires. It counts the switchless OCALLs in flight, the depth of the
+//! mailbox as the enclave sees it, and makes a regular OCALL straight away
+//! when that depth reaches [`set_max_in_flight`], usually the number of
+//! untrusted workers the host started.
+//!
+//! [`stats`] tells how many OCALLs went each way; a high share of fallbacks
+//! means the host should start more workers, and none at all with a low
+//! peak depth, that it could start fewer (see `sgx_urts::switchless`).
+//!
+//! # Examples
+//!
+//! ```
+//! use std::switchless;
+//!
+//! #[no_mangle]
+//! pub extern "C" fn ecall_init(untrusted_workers: usize) -> sgx_status_t {
+//!     switchless::set_max_in_flight(untrusted_workers);
+//!     sgx_status_t::SGX_SUCCESS
+//! }
+//! ```
+
+use crate::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use sgx_types::{c_uint, c_void, sgx_status_t};
+
+static MAX_IN_FLIGHT: AtomicUsize = AtomicUsize::new(usize::MAX);
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static PEAK_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static SWITCHLESS: AtomicU64 = AtomicU64::new(0);
+static FALLBACKS: AtomicU64 = AtomicU64::new(0);
+
+/// Sets the number of switchless OCALLs in flight from which the next ones
+/// are made as regular OCALLs. 0 makes every OCALL a regular one; the limit
+/// is off until it is set.
+pub fn set_max_in_flight(max: usize) {
+    MAX_IN_FLIGHT.store(max, Ordering::Relaxed);
+}
+
+/// Returns the limit set with [`set_max_in_flight`], `usize::MAX` if none.
+pub fn max_in_flight() -> usize {
+    MAX_IN_FLIGHT.load(Ordering::Relaxed)
+}
+
+/// Where the switchless OCALLs went.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// OCALLs posted to the mailbox.
+    pub switchless: u64,
+    /// OCALLs made the regular way because the mailbox was full.
+    pub fallbacks: u64,
+    /// OCALLs in the mailbox now.
+    pub in_flight: usize,
+    /// The most OCALLs in the mailbox at once.
+    pub peak_in_flight: usize,
+}
+
+impl Stats {
+    /// Returns the share of OCALLs that fell back, or 0 before any OCALL.
+    pub fn fallback_ratio(&self) -> f64 {
+        match self.switchless + self.fallbacks {
+            0 => 0.0,
+            total => self.fallbacks as f64 / total as f64,
+        }
+    }
+}
+
+/// Returns the counts of the switchless OCALLs made so far.
+///
+/// OCALLs posted to the mailbox may still fall back inside the runtime,
+/// once they spin too long; the host counts those as missed.
+pub fn stats() -> Stats {
+    Stats {
+        switchless: SWITCHLESS.load(Ordering::Relaxed),
+        fallbacks: FALLBACKS.load(Ordering::Relaxed),
+        in_flight: IN_FLIGHT.load(Ordering::Relaxed),
+        peak_in_flight: PEAK_IN_FLIGHT.load(Ordering::Relaxed),
+    }
+}
+
+/// Zeroes the counts, and the peak down to the current depth.
+pub fn reset() {
+    SWITCHLESS.store(0, Ordering::Relaxed);
+    FALLBACKS.store(0, Ordering::Relaxed);
+    PEAK_IN_FLIGHT.store(IN_FLIGHT.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+extern "C" {
+    fn sgx_ocall(index: c_uint, ms: *mut c_void) -> sgx_status_t;
+    fn __real_sgx_ocall_switchless(index: c_uint, ms: *mut c_void) -> sgx_status_t;
+}
+
+/// Makes the OCALL through `__real_sgx_ocall_switchless`, or as a regular
+/// OCALL if [`max_in_flight`] switchless OCALLs are in flight already. The
+/// enclave must be linked with `-Wl,--wrap=sgx_ocall_switchless`.
+///
+/// # Safety
+///
+/// The arguments are those of `sgx_ocall_switchless`, as passed by the
+/// bridges generated by edger8r. The marshalling of both kinds of OCALLs is
+/// the same, which is what lets the runtime fall back on its own.
+#[no_mangle]
+pub unsafe extern "C" fn __wrap_sgx_ocall_switchless(
+    index: c_uint,
+    ms: *mut c_void,
+) -> sgx_status_t {
+    let depth = IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+    if depth >= max_in_flight() {
+        IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+        FALLBACKS.fetch_add(1, Ordering::Relaxed);
+        return sgx_ocall(index, ms);
+    }
+    PEAK_IN_FLIGHT.fetch_max(depth + 1, Ordering::Relaxed);
+    SWITCHLESS.fetch_add(1, Ordering::Relaxed);
+    let status = __real_sgx_ocall_switchless(index, ms);
+    IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+    status
+}
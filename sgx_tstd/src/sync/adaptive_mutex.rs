@@ -0,0 +1,303 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::cell::UnsafeCell;
+use crate::fmt;
+use crate::ops::{Deref, DerefMut};
+use crate::sync::{poison, LockResult, MutexStats, TryLockError, TryLockResult};
+use crate::sys_common::mutex as sys;
+use crate::time::Duration;
+
+/// A mutual exclusion primitive that spins before it sleeps, and that can
+/// give up waiting.
+///
+/// A contended [`SgxMutex`] only spins for a few iterations before it
+/// leaves the enclave to sleep until the lock is released, and the woken
+/// thread takes another OCALL to come back. When the lock is held for a
+/// short time, the waiter would have been better off waiting inside the
+/// enclave: `SgxAdaptiveMutex` retries the lock a bounded number of times,
+/// backing off a little more on each attempt, before it sleeps. The number of attempts is set with
+/// [`set_spin_limit`].
+///
+/// A thread that cannot afford to wait for a low priority thread holding the
+/// lock can use [`lock_timeout`] instead of [`lock`]. The counters returned
+/// by [`stats`] tell how often the lock was contended, and how often waiting
+/// took an OCALL, which helps to find the locks worth splitting.
+///
+/// Poisoning works as for [`SgxMutex`]. The guard cannot be used with an
+/// [`SgxCondvar`].
+///
+/// [`SgxMutex`]: super::SgxMutex
+/// [`SgxCondvar`]: super::SgxCondvar
+/// [`set_spin_limit`]: Self::set_spin_limit
+/// [`lock_timeout`]: Self::lock_timeout
+/// [`lock`]: Self::lock
+/// [`stats`]: Self::stats
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::{Arc, SgxAdaptiveMutex};
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let counter = Arc::new(SgxAdaptiveMutex::new(0));
+/// let handles: Vec<_> = (0..4)
+///     .map(|_| {
+///         let counter = Arc::clone(&counter);
+///         thread::spawn(move || {
+///             for _ in 0..1000 {
+///                 *counter.lock().unwrap() += 1;
+///             }
+///         })
+///     })
+///     .collect();
+/// for handle in handles {
+///     handle.join().unwrap();
+/// }
+///
+/// let value = counter.lock_timeout(Duration::from_millis(10)).unwrap();
+/// assert_eq!(*value, 4000);
+/// let stats = counter.stats();
+/// assert!(stats.spun + stats.sleeps >= stats.contended);
+/// ```
+pub struct SgxAdaptiveMutex<T: ?Sized> {
+    inner: sys::MovableAdaptiveMutex,
+    poison: poison::Flag,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for SgxAdaptiveMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for SgxAdaptiveMutex<T> {}
+
+/// An RAII guard of an [`SgxAdaptiveMutex`], which unlocks it when dropped.
+#[must_use = "if unused the Mutex will immediately unlock"]
+#[must_not_suspend = "holding a MutexGuard across suspend \
+                      points can cause deadlocks, delays, \
+                      and cause Futures to not implement `Send`"]
+#[clippy::has_significant_drop]
+pub struct SgxAdaptiveMutexGuard<'a, T: ?Sized + 'a> {
+    lock: &'a SgxAdaptiveMutex<T>,
+    poison: poison::Guard,
+}
+
+impl<T: ?Sized> !Send for SgxAdaptiveMutexGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for SgxAdaptiveMutexGuard<'_, T> {}
+
+impl<T> SgxAdaptiveMutex<T> {
+    /// Creates a new mutex in an unlocked state ready for use.
+    #[inline]
+    pub const fn new(t: T) -> SgxAdaptiveMutex<T> {
+        SgxAdaptiveMutex {
+            inner: sys::MovableAdaptiveMutex::new(),
+            poison: poison::Flag::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+}
+
+impl<T: ?Sized> SgxAdaptiveMutex<T> {
+    /// Acquires the mutex, spinning and then blocking the current thread
+    /// until it is able to do so.
+    ///
+    /// # Errors
+    ///
+    /// If another user of this mutex panicked while holding the mutex, then
+    /// this call will return an error once the mutex is acquired.
+    pub fn lock(&self) -> LockResult<SgxAdaptiveMutexGuard<'_, T>> {
+        unsafe {
+            self.inner.raw_lock();
+            SgxAdaptiveMutexGuard::new(self)
+        }
+    }
+
+    /// Acquires the mutex, giving up once `dur` has passed.
+    ///
+    /// The time is measured with the monotonic clock of the host, so the
+    /// host can make the call give up early, as it can delay any wait.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TryLockError::WouldBlock`] if the mutex could not be
+    /// acquired in time, and [`TryLockError::Poisoned`] if it was acquired
+    /// but is poisoned.
+    pub fn lock_timeout(&self, dur: Duration) -> TryLockResult<SgxAdaptiveMutexGuard<'_, T>> {
+        unsafe {
+            if self.inner.raw_lock_timeout(dur) {
+                Ok(SgxAdaptiveMutexGuard::new(self)?)
+            } else {
+                Err(TryLockError::WouldBlock)
+            }
+        }
+    }
+
+    /// Attempts to acquire the mutex without spinning or blocking.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TryLockError::WouldBlock`] if the mutex is locked, and
+    /// [`TryLockError::Poisoned`] if it was acquired but is poisoned.
+    pub fn try_lock(&self) -> TryLockResult<SgxAdaptiveMutexGuard<'_, T>> {
+        unsafe {
+            if self.inner.try_lock() {
+                Ok(SgxAdaptiveMutexGuard::new(self)?)
+            } else {
+                Err(TryLockError::WouldBlock)
+            }
+        }
+    }
+
+    /// Sets the number of attempts at a held lock before sleeping, 100 by
+    /// default. Zero makes the mutex sleep at once, as [`SgxMutex`] does.
+    ///
+    /// [`SgxMutex`]: super::SgxMutex
+    pub fn set_spin_limit(&self, limit: u32) {
+        self.inner.set_spin_limit(limit);
+    }
+
+    /// Returns the counters of the mutex since it was created, or since
+    /// [`reset_stats`](Self::reset_stats).
+    pub fn stats(&self) -> MutexStats {
+        self.inner.stats()
+    }
+
+    /// Resets the counters of the mutex to zero.
+    pub fn reset_stats(&self) {
+        self.inner.reset_stats();
+    }
+
+    /// Determines whether the mutex is poisoned.
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.get()
+    }
+
+    /// Clear the poisoned state from a mutex.
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.poison.clear();
+    }
+
+    /// Consumes this mutex, returning the underlying data.
+    ///
+    /// # Errors
+    ///
+    /// If another user of this mutex panicked while holding the mutex, then
+    /// this call will return an error instead.
+    pub fn into_inner(self) -> LockResult<T>
+    where
+        T: Sized,
+    {
+        let data = self.data.into_inner();
+        poison::map_result(self.poison.borrow(), |()| data)
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// # Errors
+    ///
+    /// If another user of this mutex panicked while holding the mutex, then
+    /// this call will return an error instead.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let data = self.data.get_mut();
+        poison::map_result(self.poison.borrow(), |()| data)
+    }
+}
+
+impl<T> From<T> for SgxAdaptiveMutex<T> {
+    fn from(t: T) -> Self {
+        SgxAdaptiveMutex::new(t)
+    }
+}
+
+impl<T: ?Sized + Default> Default for SgxAdaptiveMutex<T> {
+    fn default() -> SgxAdaptiveMutex<T> {
+        SgxAdaptiveMutex::new(Default::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for SgxAdaptiveMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("SgxAdaptiveMutex");
+        match self.try_lock() {
+            Ok(guard) => {
+                d.field("data", &&*guard);
+            }
+            Err(TryLockError::Poisoned(err)) => {
+                d.field("data", &&**err.get_ref());
+            }
+            Err(TryLockError::WouldBlock) => {
+                struct LockedPlaceholder;
+                impl fmt::Debug for LockedPlaceholder {
+                    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                        f.write_str("<locked>")
+                    }
+                }
+                d.field("data", &LockedPlaceholder);
+            }
+        }
+        d.field("poisoned", &self.poison.get());
+        d.finish_non_exhaustive()
+    }
+}
+
+impl<'mutex, T: ?Sized> SgxAdaptiveMutexGuard<'mutex, T> {
+    unsafe fn new(
+        lock: &'mutex SgxAdaptiveMutex<T>,
+    ) -> LockResult<SgxAdaptiveMutexGuard<'mutex, T>> {
+        poison::map_result(lock.poison.guard(), |guard| SgxAdaptiveMutexGuard {
+            lock,
+            poison: guard,
+        })
+    }
+}
+
+impl<T: ?Sized> Deref for SgxAdaptiveMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for SgxAdaptiveMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for SgxAdaptiveMutexGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            self.lock.poison.done(&self.poison);
+            self.lock.inner.raw_unlock();
+        }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for SgxAdaptiveMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for SgxAdaptiveMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
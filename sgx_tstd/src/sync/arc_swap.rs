@@ -0,0 +1,159 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::fmt;
+use crate::hint;
+use crate::marker::PhantomData;
+use crate::ptr;
+use crate::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use crate::sync::{Arc, SgxMutex};
+
+/// An `Arc<T>` that can be replaced atomically while other threads read it.
+///
+/// Reads never take a lock: [`load`] bumps a per-slot reader count, clones
+/// the `Arc` and leaves. Writers are serialized among themselves and publish
+/// a new value by flipping between two slots, waiting only for readers that
+/// are in the middle of cloning the old one.
+///
+/// This fits data that is read on every request and replaced rarely, such as
+/// configuration.
+///
+/// [`load`]: ArcSwap::load
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::{Arc, ArcSwap};
+///
+/// let config = ArcSwap::new(1);
+/// assert_eq!(*config.load(), 1);
+///
+/// let old = config.store(Arc::new(2));
+/// assert_eq!(*old, 1);
+/// assert_eq!(*config.load(), 2);
+/// ```
+pub struct ArcSwap<T> {
+    slots: [AtomicPtr<T>; 2],
+    readers: [AtomicUsize; 2],
+    current: AtomicUsize,
+    writer: SgxMutex<()>,
+    _marker: PhantomData<Arc<T>>,
+}
+
+unsafe impl<T: Send + Sync> Send for ArcSwap<T> {}
+unsafe impl<T: Send + Sync> Sync for ArcSwap<T> {}
+
+impl<T> ArcSwap<T> {
+    /// Creates a new `ArcSwap` holding `value`.
+    pub fn new(value: T) -> ArcSwap<T> {
+        ArcSwap::from_arc(Arc::new(value))
+    }
+
+    /// Creates a new `ArcSwap` holding `value`.
+    pub fn from_arc(value: Arc<T>) -> ArcSwap<T> {
+        ArcSwap {
+            slots: [AtomicPtr::new(Arc::into_raw(value) as *mut T), AtomicPtr::new(ptr::null_mut())],
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            current: AtomicUsize::new(0),
+            writer: SgxMutex::new(()),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the current value.
+    pub fn load(&self) -> Arc<T> {
+        loop {
+            let i = self.current.load(Ordering::SeqCst);
+            self.readers[i].fetch_add(1, Ordering::SeqCst);
+            // A writer that has moved on from slot `i` waits for its reader
+            // count to drop to zero before releasing the slot, so the value is
+            // safe to clone once we are counted and `i` is still current.
+            if self.current.load(Ordering::SeqCst) == i {
+                let p = self.slots[i].load(Ordering::SeqCst);
+                let value = unsafe {
+                    Arc::increment_strong_count(p);
+                    Arc::from_raw(p)
+                };
+                self.readers[i].fetch_sub(1, Ordering::SeqCst);
+                return value;
+            }
+            self.readers[i].fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Replaces the value, returning the previous one.
+    pub fn store(&self, value: Arc<T>) -> Arc<T> {
+        let _guard = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        self.publish(value)
+    }
+
+    /// Replaces the value with `f` applied to the current one, returning the
+    /// previous value.
+    ///
+    /// Writers are serialized, so concurrent updates are not lost.
+    pub fn rcu<F>(&self, f: F) -> Arc<T>
+    where
+        F: FnOnce(&T) -> T,
+    {
+        let _guard = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let current = self.load();
+        self.publish(Arc::new(f(&current)))
+    }
+
+    // Must be called with `writer` held.
+    fn publish(&self, value: Arc<T>) -> Arc<T> {
+        let i = self.current.load(Ordering::SeqCst);
+        let j = 1 - i;
+        // Slot `j` is empty; wait out readers that counted themselves in it
+        // before noticing it was not current.
+        self.wait_for_readers(j);
+        self.slots[j].store(Arc::into_raw(value) as *mut T, Ordering::SeqCst);
+        self.current.store(j, Ordering::SeqCst);
+        self.wait_for_readers(i);
+        let old = self.slots[i].swap(ptr::null_mut(), Ordering::SeqCst);
+        unsafe { Arc::from_raw(old) }
+    }
+
+    fn wait_for_readers(&self, slot: usize) {
+        while self.readers[slot].load(Ordering::SeqCst) != 0 {
+            hint::spin_loop();
+        }
+    }
+}
+
+impl<T> Drop for ArcSwap<T> {
+    fn drop(&mut self) {
+        for slot in self.slots.iter_mut() {
+            let p = *slot.get_mut();
+            if !p.is_null() {
+                unsafe { drop(Arc::from_raw(p)) };
+            }
+        }
+    }
+}
+
+impl<T: Default> Default for ArcSwap<T> {
+    fn default() -> ArcSwap<T> {
+        ArcSwap::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ArcSwap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ArcSwap").field(&self.load()).finish()
+    }
+}
@@ -0,0 +1,82 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Waiting on an atomic, in the manner of the `atomic-wait` crate.
+//!
+//! [`wait`] blocks the current thread while an `AtomicU32` holds a given
+//! value, until [`wake_one`] or [`wake_all`] is called on the same atomic.
+//! This is the futex interface that lock implementations and crates such as
+//! `atomic-wait` are written against; in the enclave, a waiting thread sleeps
+//! on the untrusted event of its TCS, so a wait and a wake take one OCALL
+//! each.
+//!
+//! A wait can return spuriously, so callers must check the atomic again
+//! afterwards, as with a futex.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use std::sync::atomic::{AtomicU32, Ordering};
+//! use std::sync::atomic_wait;
+//!
+//! static READY: AtomicU32 = AtomicU32::new(0);
+//!
+//! // Waiting thread.
+//! while READY.load(Ordering::Acquire) == 0 {
+//!     atomic_wait::wait(&READY, 0);
+//! }
+//!
+//! // Waking thread.
+//! READY.store(1, Ordering::Release);
+//! atomic_wait::wake_all(&READY);
+//! ```
+
+use crate::sync::atomic::AtomicU32;
+use crate::sys::futex;
+use crate::time::Duration;
+
+/// Blocks while `atomic` holds `value`, until woken.
+///
+/// Returns at once if `atomic` does not hold `value`.
+#[inline]
+pub fn wait(atomic: &AtomicU32, value: u32) {
+    futex::futex_wait(atomic, value, None);
+}
+
+/// Blocks while `atomic` holds `value`, until woken or for at most `timeout`.
+///
+/// Returns `false` if the timeout expired.
+#[inline]
+pub fn wait_timeout(atomic: &AtomicU32, value: u32, timeout: Duration) -> bool {
+    futex::futex_wait(atomic, value, Some(timeout))
+}
+
+/// Wakes one thread waiting on `atomic`.
+///
+/// The atomic need not be alive: only its address is used.
+#[inline]
+pub fn wake_one(atomic: *const AtomicU32) {
+    futex::futex_wake_addr(atomic, 1);
+}
+
+/// Wakes all threads waiting on `atomic`.
+///
+/// The atomic need not be alive: only its address is used.
+#[inline]
+pub fn wake_all(atomic: *const AtomicU32) {
+    futex::futex_wake_addr(atomic, usize::MAX);
+}
@@ -0,0 +1,171 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::borrow::Borrow;
+use crate::boxed::Box;
+use crate::collections::HashMap;
+use crate::fmt;
+use crate::hash::Hash;
+use crate::mem;
+use crate::sync::{Arc, ArcSwap, SgxMutex};
+use crate::vec::Vec;
+
+type Update<K, V> = Box<dyn FnOnce(&mut HashMap<K, V>) + Send>;
+
+/// A copy-on-write hash map with lock-free reads.
+///
+/// Readers get an immutable snapshot through an [`ArcSwap`]. Writers queue
+/// their change and one of them applies every queued change to a single copy
+/// of the map before publishing it, so a burst of writes costs one clone
+/// rather than one per write. Every write is visible to [`snapshot`] by the
+/// time the writing call returns.
+///
+/// [`snapshot`]: CowMap::snapshot
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::CowMap;
+///
+/// let flags = CowMap::new();
+/// flags.insert("tls13", true);
+///
+/// let snapshot = flags.snapshot();
+/// flags.insert("tls13", false);
+///
+/// assert_eq!(snapshot.get("tls13"), Some(&true));
+/// assert_eq!(flags.get("tls13"), Some(false));
+/// ```
+pub struct CowMap<K, V> {
+    map: ArcSwap<HashMap<K, V>>,
+    pending: SgxMutex<Vec<Update<K, V>>>,
+    commit: SgxMutex<()>,
+}
+
+impl<K, V> CowMap<K, V>
+where
+    K: Hash + Eq + Clone + Send + 'static,
+    V: Clone + Send + 'static,
+{
+    /// Creates an empty map.
+    pub fn new() -> CowMap<K, V> {
+        CowMap::from_map(HashMap::new())
+    }
+
+    /// Creates a map holding the entries of `map`.
+    pub fn from_map(map: HashMap<K, V>) -> CowMap<K, V> {
+        CowMap {
+            map: ArcSwap::new(map),
+            pending: SgxMutex::new(Vec::new()),
+            commit: SgxMutex::new(()),
+        }
+    }
+
+    /// Returns the current contents of the map.
+    ///
+    /// The snapshot does not change when the map is written afterwards.
+    pub fn snapshot(&self) -> Arc<HashMap<K, V>> {
+        self.map.load()
+    }
+
+    /// Returns a copy of the value of `key`.
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        self.map.load().get(key).cloned()
+    }
+
+    /// Returns `true` if the map has an entry for `key`.
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        self.map.load().contains_key(key)
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.map.load().len()
+    }
+
+    /// Returns `true` if the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.load().is_empty()
+    }
+
+    /// Inserts an entry.
+    pub fn insert(&self, key: K, value: V) {
+        self.update(move |map| {
+            map.insert(key, value);
+        })
+    }
+
+    /// Removes the entry of `key`.
+    pub fn remove(&self, key: K) {
+        self.update(move |map| {
+            map.remove(&key);
+        })
+    }
+
+    /// Applies `f` to the map as part of the next published version.
+    ///
+    /// Updates queued by concurrent writers are applied in the order they
+    /// were queued.
+    pub fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&mut HashMap<K, V>) + Send + 'static,
+    {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).push(Box::new(f));
+
+        // Whoever holds `commit` drains the queue. If another writer took our
+        // update with it, it published before releasing the lock.
+        let _guard = self.commit.lock().unwrap_or_else(|e| e.into_inner());
+        let updates = mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()));
+        if updates.is_empty() {
+            return;
+        }
+        let mut map = HashMap::clone(&self.map.load());
+        for update in updates {
+            update(&mut map);
+        }
+        self.map.store(Arc::new(map));
+    }
+
+    /// Replaces the whole map.
+    pub fn replace(&self, map: HashMap<K, V>) {
+        self.update(move |current| *current = map)
+    }
+}
+
+impl<K, V> Default for CowMap<K, V>
+where
+    K: Hash + Eq + Clone + Send + 'static,
+    V: Clone + Send + 'static,
+{
+    fn default() -> CowMap<K, V> {
+        CowMap::new()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for CowMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.map.load().iter()).finish()
+    }
+}
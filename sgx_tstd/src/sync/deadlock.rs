@@ -0,0 +1,300 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Deadlock detection for the enclave locks.
+//!
+//! With the `lock_debug` feature, every [`SgxMutex`], [`SgxAdaptiveMutex`],
+//! [`SgxRwLock`] and [`SgxCondvar`] reports to a global wait-for graph which
+//! thread holds it and which threads are waiting for it. A debugger is of
+//! little help with an enclave that hangs, but the graph can tell why:
+//! [`check`] returns the cycles of threads that wait for each other, and
+//! [`threads`] what every thread holds and waits for at the time of the
+//! call. Threads are named by their `sgx_thread_t` and by the address of
+//! their TCS.
+//!
+//! As `parking_lot` does, the crate leaves it to the enclave to call [`check`],
+//! for instance from a thread that wakes up every few seconds. Alternatively,
+//! [`set_panic_on_cycle`] makes a thread panic instead of waiting when its
+//! wait would close a cycle, which unwinds the deadlock out of the test that
+//! caused it.
+//!
+//! A thread waiting with a timeout is not recorded as waiting, as it cannot
+//! stay blocked. A thread waiting on a condition variable is recorded as
+//! such until it holds the mutex again; it waits for no thread in particular,
+//! so it is never part of a cycle. The bookkeeping takes a spinlock around
+//! every lock and unlock, which makes the feature unfit for release builds.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use std::sync::deadlock;
+//! use std::thread;
+//! use std::time::Duration;
+//!
+//! thread::spawn(|| loop {
+//!     thread::sleep(Duration::from_secs(10));
+//!     for cycle in deadlock::check() {
+//!         println!("{}", cycle);
+//!     }
+//! });
+//! ```
+//!
+//! [`SgxMutex`]: crate::sync::SgxMutex
+//! [`SgxAdaptiveMutex`]: crate::sync::SgxAdaptiveMutex
+//! [`SgxRwLock`]: crate::sync::SgxRwLock
+//! [`SgxCondvar`]: crate::sync::SgxCondvar
+
+use crate::cell::UnsafeCell;
+use crate::fmt;
+use crate::sync::atomic::{AtomicBool, Ordering};
+use crate::sync::SgxSpinlock;
+use crate::thread;
+use crate::vec::Vec;
+
+use sgx_types::sgx_thread_t;
+
+/// The kind of a lock in the wait-for graph.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LockKind {
+    Mutex,
+    RwLock,
+    Condvar,
+}
+
+/// A lock as held or waited for by a thread.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LockRef {
+    /// The address of the lock, which stays the same while it is held.
+    pub lock: usize,
+    pub kind: LockKind,
+    /// Whether the access is exclusive: always for a mutex, for a write
+    /// access to a rwlock.
+    pub exclusive: bool,
+}
+
+/// What a thread holds and waits for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThreadState {
+    pub thread: sgx_thread_t,
+    /// The address of the TCS the thread runs on.
+    pub tcs: usize,
+    pub held: Vec<LockRef>,
+    pub waiting: Option<LockRef>,
+}
+
+/// A cycle of threads each waiting for a lock held by the next one, the
+/// last waiting for the first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Deadlock {
+    pub threads: Vec<ThreadState>,
+}
+
+impl fmt::Display for Deadlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "deadlock of {} threads:", self.threads.len())?;
+        for state in self.threads.iter() {
+            write!(f, "  thread {:#x} (TCS {:#x})", state.thread, state.tcs)?;
+            if let Some(wait) = state.waiting {
+                write!(f, " waits for {:?} {:#x}", wait.kind, wait.lock)?;
+            }
+            write!(f, ", holds")?;
+            for held in state.held.iter() {
+                write!(f, " {:?} {:#x}", held.kind, held.lock)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+struct Graph {
+    lock: SgxSpinlock,
+    threads: UnsafeCell<Vec<ThreadState>>,
+}
+
+unsafe impl Sync for Graph {}
+
+impl Graph {
+    fn with<R>(&self, f: impl FnOnce(&mut Vec<ThreadState>) -> R) -> R {
+        let _guard = self.lock.lock();
+        f(unsafe { &mut *self.threads.get() })
+    }
+}
+
+static GRAPH: Graph = Graph {
+    lock: SgxSpinlock::new(),
+    threads: UnsafeCell::new(Vec::new()),
+};
+
+static PANIC_ON_CYCLE: AtomicBool = AtomicBool::new(false);
+
+/// Makes a thread panic rather than wait for a lock when the wait would
+/// close a cycle of waiting threads.
+pub fn set_panic_on_cycle(enabled: bool) {
+    PANIC_ON_CYCLE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn panic_on_cycle() -> bool {
+    PANIC_ON_CYCLE.load(Ordering::Relaxed)
+}
+
+/// Returns the cycles of threads currently waiting for each other.
+pub fn check() -> Vec<Deadlock> {
+    GRAPH.with(|threads| {
+        let mut cycles: Vec<Deadlock> = Vec::new();
+        for start in 0..threads.len() {
+            // Report each cycle once, from the thread listed first.
+            if let Some(cycle) = find_cycle(threads, start) {
+                if cycle.iter().all(|&i| i >= start) {
+                    cycles.push(Deadlock {
+                        threads: cycle.into_iter().map(|i| threads[i].clone()).collect(),
+                    });
+                }
+            }
+        }
+        cycles
+    })
+}
+
+/// Returns what every thread that holds or waits for a lock holds and
+/// waits for.
+pub fn threads() -> Vec<ThreadState> {
+    GRAPH.with(|threads| threads.clone())
+}
+
+// The index of the thread holding `wait.lock` in a way that conflicts with
+// the access `waiter` waits for, if any.
+fn blocker(threads: &[ThreadState], waiter: usize, wait: &LockRef) -> Option<usize> {
+    if wait.kind == LockKind::Condvar {
+        return None;
+    }
+    threads.iter().enumerate().position(|(i, state)| {
+        i != waiter
+            && state
+                .held
+                .iter()
+                .any(|held| held.lock == wait.lock && (held.exclusive || wait.exclusive))
+    })
+}
+
+// Follows the waits from `start` and returns the cycle that leads back to
+// it. Only one blocker is followed per thread, which misses no cycle when
+// threads hold exclusive access, and all but some cycles through the readers
+// of a rwlock otherwise.
+fn find_cycle(threads: &[ThreadState], start: usize) -> Option<Vec<usize>> {
+    let mut path = Vec::new();
+    let mut current = start;
+    loop {
+        path.push(current);
+        let wait = threads[current].waiting.as_ref()?;
+        current = blocker(threads, current, wait)?;
+        if current == start {
+            return Some(path);
+        }
+        if path.contains(&current) || path.len() > threads.len() {
+            return None;
+        }
+    }
+}
+
+fn state(threads: &mut Vec<ThreadState>) -> usize {
+    let id = thread::rsgx_thread_self();
+    match threads.iter().position(|state| state.thread == id) {
+        Some(i) => i,
+        None => {
+            threads.push(ThreadState {
+                thread: id,
+                tcs: thread::current_td().get_tcs(),
+                held: Vec::new(),
+                waiting: None,
+            });
+            threads.len() - 1
+        }
+    }
+}
+
+fn forget_idle(threads: &mut Vec<ThreadState>, i: usize) {
+    if threads[i].held.is_empty() && threads[i].waiting.is_none() {
+        threads.swap_remove(i);
+    }
+}
+
+// Records that the current thread is about to block on `lock`. With
+// `detect`, panics instead if the wait closes a cycle and the panic mode
+// is on.
+pub(crate) fn wait(lock: usize, kind: LockKind, exclusive: bool, detect: bool) {
+    let deadlock = GRAPH.with(|threads| {
+        let i = state(threads);
+        threads[i].waiting = Some(LockRef {
+            lock,
+            kind,
+            exclusive,
+        });
+        if !detect || !panic_on_cycle() {
+            return None;
+        }
+        let cycle = find_cycle(threads, i)?;
+        let deadlock = Deadlock {
+            threads: cycle.into_iter().map(|j| threads[j].clone()).collect(),
+        };
+        threads[i].waiting = None;
+        forget_idle(threads, i);
+        Some(deadlock)
+    });
+    if let Some(deadlock) = deadlock {
+        panic!("{}", deadlock);
+    }
+}
+
+// Records that the current thread gave up waiting.
+pub(crate) fn cancel_wait() {
+    GRAPH.with(|threads| {
+        let i = state(threads);
+        threads[i].waiting = None;
+        forget_idle(threads, i);
+    })
+}
+
+// Records that the current thread holds `lock`, and waits no more.
+pub(crate) fn acquired(lock: usize, kind: LockKind, exclusive: bool) {
+    GRAPH.with(|threads| {
+        let i = state(threads);
+        threads[i].waiting = None;
+        threads[i].held.push(LockRef {
+            lock,
+            kind,
+            exclusive,
+        });
+    })
+}
+
+// Records that the current thread is about to release `lock`. Called before
+// the lock is released, so that the next owner is never recorded before
+// the last one is forgotten.
+pub(crate) fn released(lock: usize, exclusive: bool) {
+    GRAPH.with(|threads| {
+        let i = state(threads);
+        let held = &mut threads[i].held;
+        if let Some(j) = held
+            .iter()
+            .rposition(|held| held.lock == lock && held.exclusive == exclusive)
+        {
+            held.remove(j);
+        }
+        forget_idle(threads, i);
+    })
+}
@@ -30,28 +30,39 @@
 pub use alloc_crate::sync::{Arc, Weak};
 pub use core::sync::atomic;
 
+pub use self::adaptive_mutex::{SgxAdaptiveMutex, SgxAdaptiveMutexGuard};
+pub use self::arc_swap::ArcSwap;
 pub use self::barrier::{Barrier, BarrierWaitResult};
+pub use self::cow_map::CowMap;
 pub use self::condvar::{SgxCondvar, WaitTimeoutResult};
 pub use self::mutex::{SgxMutex, SgxMutexGuard};
 pub use self::once::{Once, OnceState, ONCE_INIT};
 pub use self::poison::{LockResult, PoisonError, TryLockError, TryLockResult};
 pub use self::rwlock::{SgxRwLock, SgxRwLockReadGuard, SgxRwLockWriteGuard};
-pub use self::spinlock::{SgxSpinlock, SgxSpinlockGuard};
+pub use crate::sys::locks::{MutexStats, RwLockPolicy};
+pub use sgx_tsync::{SgxSpinlock, SgxSpinlockGuard};
 
 pub use self::lazy_lock::LazyLock;
 pub use self::once_lock::OnceLock;
 
-pub(crate) use self::spinlock::SgxThreadSpinlock;
+pub(crate) use sgx_tsync::SgxThreadSpinlock;
 
+pub mod atomic_wait;
+#[cfg(feature = "lock_debug")]
+pub mod deadlock;
+#[cfg(feature = "thread")]
+pub mod mpmc;
 #[cfg(feature = "thread")]
 pub mod mpsc;
 
+mod adaptive_mutex;
+mod arc_swap;
 mod barrier;
 mod condvar;
+mod cow_map;
 mod lazy_lock;
 mod mutex;
 mod once;
 mod once_lock;
 mod poison;
 mod rwlock;
-mod spinlock;
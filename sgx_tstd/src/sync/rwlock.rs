@@ -17,10 +17,12 @@
 
 use crate::cell::UnsafeCell;
 use crate::fmt;
+use crate::mem;
 use crate::ops::{Deref, DerefMut};
 use crate::ptr::NonNull;
-use crate::sync::{poison, LockResult, TryLockError, TryLockResult};
+use crate::sync::{poison, LockResult, PoisonError, RwLockPolicy, TryLockError, TryLockResult};
 use crate::sys_common::rwlock as sys;
+use crate::time::Duration;
 
 /// A reader-writer lock
 ///
@@ -34,11 +36,12 @@ use crate::sys_common::rwlock as sys;
 /// become available. An `RwLock` will allow any number of readers to acquire the
 /// lock as long as a writer is not holding the lock.
 ///
-/// The priority policy of the lock is dependent on the underlying operating
-/// system's implementation, and this type does not guarantee that any
-/// particular policy will be used. In particular, a writer which is waiting to
-/// acquire the lock in `write` might or might not block concurrent calls to
-/// `read`, e.g.:
+/// By default a waiting writer does not block concurrent calls to `read`, so
+/// a steady stream of readers can starve writers. A lock created with
+/// [`with_policy`] and [`RwLockPolicy::WriterPreferred`] makes new readers
+/// wait while a writer is queued, and hands a released lock to the next
+/// writer first. With that policy, a thread that already holds a read lock
+/// and reads again while a writer is waiting will deadlock, e.g.:
 ///
 /// <details><summary>Potential deadlock example</summary>
 ///
@@ -89,6 +92,7 @@ use crate::sys_common::rwlock as sys;
 /// ```
 ///
 /// [`SgxMutex`]: super::SgxMutex
+/// [`with_policy`]: SgxRwLock::with_policy
 pub struct SgxRwLock<T: ?Sized> {
     inner: sys::MovableRwLock,
     poison: poison::Flag,
@@ -114,10 +118,11 @@ unsafe impl<T: ?Sized + Send + Sync> Sync for SgxRwLock<T> {}
 pub struct SgxRwLockReadGuard<'a, T: ?Sized + 'a> {
     // NB: we use a pointer instead of `&'a T` to avoid `noalias` violations, because a
     // `Ref` argument doesn't hold immutability for its whole scope, only until it drops.
-    // `NonNull` is also covariant over `T`, just like we would have with `&T`. `NonNull`
-    // is preferable over `const* T` to allow for niche optimization.
+    // `NonNull` is preferable over `const* T` to allow for niche optimization.
     data: NonNull<T>,
-    inner_lock: &'a sys::MovableRwLock,
+    // Unlike std, the guard is invariant over `T`: a guard that can be upgraded must not
+    // let a shorter lifetime be written into the lock.
+    lock: &'a SgxRwLock<T>,
 }
 
 impl<T: ?Sized> !Send for SgxRwLockReadGuard<'_, T> {}
@@ -161,6 +166,23 @@ impl<T> SgxRwLock<T> {
             data: UnsafeCell::new(t),
         }
     }
+
+    /// Creates a new instance of an `RwLock<T>` which is unlocked and admits
+    /// waiting readers and writers according to `policy`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{RwLockPolicy, SgxRwLock as RwLock};
+    ///
+    /// let lock = RwLock::with_policy(5, RwLockPolicy::WriterPreferred);
+    /// assert_eq!(lock.policy(), RwLockPolicy::WriterPreferred);
+    /// ```
+    pub fn with_policy(t: T, policy: RwLockPolicy) -> SgxRwLock<T> {
+        let lock = SgxRwLock::new(t);
+        lock.inner.set_policy(policy);
+        lock
+    }
 }
 
 impl<T: ?Sized> SgxRwLock<T> {
@@ -212,6 +234,52 @@ impl<T: ?Sized> SgxRwLock<T> {
         }
     }
 
+    /// Locks this `SgxRwLock` with shared read access, blocking the current
+    /// thread for at most about `dur`.
+    ///
+    /// Unlike [`read`], a thread that cannot get the lock in time gets
+    /// [`WouldBlock`] back and leaves the wait queue. The deadline is fixed
+    /// when the call starts and is measured on the monotonic clock of the
+    /// host, so a thread that is woken but loses the race for the lock only
+    /// waits out what is left of `dur`. A `dur` too large to add to the clock
+    /// is treated as the largest deadline, and if the host fails to report
+    /// the time the call waits without a deadline, like [`read`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return the [`Poisoned`] error if the `RwLock` is
+    /// poisoned and the lock was acquired.
+    ///
+    /// This function will return the [`WouldBlock`] error if the `RwLock`
+    /// could not be acquired before the timeout.
+    ///
+    /// [`read`]: Self::read
+    /// [`Poisoned`]: TryLockError::Poisoned
+    /// [`WouldBlock`]: TryLockError::WouldBlock
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::SgxRwLock as RwLock;
+    /// use std::time::Duration;
+    ///
+    /// let lock = RwLock::new(1);
+    ///
+    /// let n = lock.write().unwrap();
+    /// assert!(lock.read_timeout(Duration::from_millis(10)).is_err());
+    /// drop(n);
+    /// assert_eq!(*lock.read_timeout(Duration::from_millis(10)).unwrap(), 1);
+    /// ```
+    pub fn read_timeout(&self, dur: Duration) -> TryLockResult<SgxRwLockReadGuard<'_, T>> {
+        unsafe {
+            if self.inner.read_timeout(dur) {
+                Ok(SgxRwLockReadGuard::new(self)?)
+            } else {
+                Err(TryLockError::WouldBlock)
+            }
+        }
+    }
+
     /// Attempts to acquire this `SgxRwLock` with shared read access.
     ///
     /// If the access could not be granted at this time, then `Err` is returned.
@@ -298,6 +366,52 @@ impl<T: ?Sized> SgxRwLock<T> {
         }
     }
 
+    /// Locks this `SgxRwLock` with exclusive write access, blocking the current
+    /// thread for at most about `dur`.
+    ///
+    /// Unlike [`write`], a thread that cannot get the lock in time gets
+    /// [`WouldBlock`] back and leaves the wait queue. The deadline is fixed
+    /// when the call starts and is measured on the monotonic clock of the
+    /// host, so a thread that is woken but loses the race for the lock only
+    /// waits out what is left of `dur`. A `dur` too large to add to the clock
+    /// is treated as the largest deadline, and if the host fails to report
+    /// the time the call waits without a deadline, like [`write`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return the [`Poisoned`] error if the `RwLock` is
+    /// poisoned and the lock was acquired.
+    ///
+    /// This function will return the [`WouldBlock`] error if the `RwLock`
+    /// could not be acquired before the timeout.
+    ///
+    /// [`write`]: Self::write
+    /// [`Poisoned`]: TryLockError::Poisoned
+    /// [`WouldBlock`]: TryLockError::WouldBlock
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::SgxRwLock as RwLock;
+    /// use std::time::Duration;
+    ///
+    /// let lock = RwLock::new(1);
+    ///
+    /// let n = lock.read().unwrap();
+    /// assert!(lock.write_timeout(Duration::from_millis(10)).is_err());
+    /// drop(n);
+    /// *lock.write_timeout(Duration::from_millis(10)).unwrap() = 2;
+    /// ```
+    pub fn write_timeout(&self, dur: Duration) -> TryLockResult<SgxRwLockWriteGuard<'_, T>> {
+        unsafe {
+            if self.inner.write_timeout(dur) {
+                Ok(SgxRwLockWriteGuard::new(self)?)
+            } else {
+                Err(TryLockError::WouldBlock)
+            }
+        }
+    }
+
     /// Attempts to lock this `SgxRwLock` with exclusive write access.
     ///
     /// If the lock could not be acquired at this time, then `Err` is returned.
@@ -346,6 +460,13 @@ impl<T: ?Sized> SgxRwLock<T> {
         }
     }
 
+    /// Returns the policy that decides whether waiting readers or writers
+    /// enter this lock first.
+    #[inline]
+    pub fn policy(&self) -> RwLockPolicy {
+        self.inner.policy()
+    }
+
     /// Determines whether the lock is poisoned.
     ///
     /// If another thread is active, the lock can still become poisoned at any
@@ -513,9 +634,75 @@ impl<'rwlock, T: ?Sized> SgxRwLockReadGuard<'rwlock, T> {
     unsafe fn new(lock: &'rwlock SgxRwLock<T>) -> LockResult<SgxRwLockReadGuard<'rwlock, T>> {
         poison::map_result(lock.poison.borrow(), |()| SgxRwLockReadGuard {
             data: NonNull::new_unchecked(lock.data.get()),
-            inner_lock: &lock.inner,
+            lock,
         })
     }
+
+    /// Attempts to turn the shared access of this guard into exclusive access
+    /// without blocking, which succeeds if no other thread holds the lock.
+    ///
+    /// The lock is never released on the way, so the data cannot change
+    /// between reading it through `guard` and writing it through the guard
+    /// returned. This is an associated function, as `SgxRwLockReadGuard`
+    /// dereferences to the data.
+    ///
+    /// The write guard is returned even if the lock is poisoned, as the read
+    /// guard already gave access to the data.
+    ///
+    /// # Errors
+    ///
+    /// Returns `guard` back if other readers hold the lock, or another thread
+    /// is upgrading.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{SgxRwLock, SgxRwLockReadGuard};
+    ///
+    /// let lock = SgxRwLock::new(1);
+    /// let r = lock.read().unwrap();
+    /// let mut w = SgxRwLockReadGuard::try_upgrade(r).unwrap();
+    /// *w += 1;
+    /// ```
+    pub fn try_upgrade(
+        guard: Self,
+    ) -> Result<SgxRwLockWriteGuard<'rwlock, T>, SgxRwLockReadGuard<'rwlock, T>> {
+        let lock = guard.lock;
+        // SAFETY: the conditions of `SgxRwLockReadGuard::new` were satisfied when created.
+        if unsafe { lock.inner.try_upgrade() } {
+            mem::forget(guard);
+            Ok(unsafe { SgxRwLockWriteGuard::upgraded(lock) })
+        } else {
+            Err(guard)
+        }
+    }
+
+    /// Turns the shared access of this guard into exclusive access, blocking
+    /// until the other readers have released the lock. Readers arriving
+    /// meanwhile wait until the write guard is dropped.
+    ///
+    /// As with [`try_upgrade`](Self::try_upgrade), the lock is never released
+    /// on the way, and the write guard is returned even if the lock is
+    /// poisoned.
+    ///
+    /// # Errors
+    ///
+    /// Returns `guard` back at once if another thread is upgrading already:
+    /// each of the two would wait for the other to release its read access.
+    /// Dropping `guard` and calling [`SgxRwLock::write`] then waits for the
+    /// other upgrade to finish.
+    pub fn upgrade(
+        guard: Self,
+    ) -> Result<SgxRwLockWriteGuard<'rwlock, T>, SgxRwLockReadGuard<'rwlock, T>> {
+        let lock = guard.lock;
+        // SAFETY: the conditions of `SgxRwLockReadGuard::new` were satisfied when created.
+        if unsafe { lock.inner.upgrade() } {
+            mem::forget(guard);
+            Ok(unsafe { SgxRwLockWriteGuard::upgraded(lock) })
+        } else {
+            Err(guard)
+        }
+    }
 }
 
 impl<'rwlock, T: ?Sized> SgxRwLockWriteGuard<'rwlock, T> {
@@ -525,6 +712,47 @@ impl<'rwlock, T: ?Sized> SgxRwLockWriteGuard<'rwlock, T> {
     unsafe fn new(lock: &'rwlock SgxRwLock<T>) -> LockResult<SgxRwLockWriteGuard<'rwlock, T>> {
         poison::map_result(lock.poison.guard(), |guard| SgxRwLockWriteGuard { lock, poison: guard })
     }
+
+    // SAFETY: if and only if `lock.inner.try_upgrade()` (or `lock.inner.upgrade()`) has
+    // been successfully called from the same thread before instantiating this object.
+    unsafe fn upgraded(lock: &'rwlock SgxRwLock<T>) -> SgxRwLockWriteGuard<'rwlock, T> {
+        let poison = lock.poison.guard().unwrap_or_else(PoisonError::into_inner);
+        SgxRwLockWriteGuard { lock, poison }
+    }
+
+    /// Turns the exclusive access of this guard into shared access, letting
+    /// the waiting readers in.
+    ///
+    /// The lock is never released on the way, so no writer can change the
+    /// data between writing it through `guard` and reading it through the
+    /// guard returned. This is an associated function, as
+    /// `SgxRwLockWriteGuard` dereferences to the data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{SgxRwLock, SgxRwLockWriteGuard};
+    ///
+    /// let lock = SgxRwLock::new(1);
+    /// let mut w = lock.write().unwrap();
+    /// *w += 1;
+    /// let r = SgxRwLockWriteGuard::downgrade(w);
+    /// assert_eq!(*r, 2);
+    /// assert!(lock.try_read().is_ok());
+    /// ```
+    pub fn downgrade(guard: Self) -> SgxRwLockReadGuard<'rwlock, T> {
+        let lock = guard.lock;
+        lock.poison.done(&guard.poison);
+        mem::forget(guard);
+        // SAFETY: the conditions of `SgxRwLockWriteGuard::new` were satisfied when created.
+        unsafe {
+            lock.inner.downgrade();
+            SgxRwLockReadGuard {
+                data: NonNull::new_unchecked(lock.data.get()),
+                lock,
+            }
+        }
+    }
 }
 
 impl<T: fmt::Debug> fmt::Debug for SgxRwLockReadGuard<'_, T> {
@@ -580,7 +808,7 @@ impl<T: ?Sized> Drop for SgxRwLockReadGuard<'_, T> {
     fn drop(&mut self) {
         // SAFETY: the conditions of `SgxRwLockReadGuard::new` were satisfied when created.
         unsafe {
-            self.inner_lock.read_unlock();
+            self.lock.inner.read_unlock();
         }
     }
 }
@@ -17,6 +17,7 @@
 
 use crate::os::unix::prelude::*;
 
+use crate::cmp;
 use crate::ffi::{CStr, OsStr, OsString};
 use crate::fmt;
 use crate::io::{self, BorrowedCursor, Error, IoSlice, IoSliceMut, SeekFrom};
@@ -24,6 +25,8 @@ use crate::mem;
 use crate::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd};
 use crate::path::{Path, PathBuf};
 use crate::ptr;
+use crate::qos;
+use crate::quota;
 use crate::sync::Arc;
 use crate::sys::common::small_c_string::run_path_with_cstr;
 use crate::sys::fd::FileDesc;
@@ -385,7 +388,9 @@ impl File {
         // some platforms (like macOS, where `open64` is actually `open`), `mode_t` is `u16`.
         // However, since this is a variadic function, C integer promotion rules mean that on
         // the ABI level, this still gets passed as `c_int` (aka `u32` on Unix platforms).
-        let fd = cvt_r(|| unsafe { libc::open64(path.as_ptr(), flags, opts.mode as c_int) })?;
+        let fd = quota::open_fd(|| {
+            cvt_r(|| unsafe { libc::open64(path.as_ptr(), flags, opts.mode as c_int) })
+        })?;
         Ok(File(unsafe { FileDesc::from_raw_fd(fd) }))
     }
 
@@ -442,11 +447,21 @@ impl File {
     }
 
     pub fn write(&self, buf: &[u8]) -> io::Result<usize> {
-        self.0.write(buf)
+        let (_permit, len) = qos::admit(buf.len());
+        quota::write_fs(len, |granted| self.0.write(&buf[..granted]))
     }
 
     pub fn write_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
-        self.0.write_vectored(bufs)
+        let len = bufs.iter().fold(0_usize, |len, buf| len.saturating_add(buf.len()));
+        let (_permit, admitted) = qos::admit(len);
+        quota::write_fs(admitted, |granted| {
+            if granted == len {
+                self.0.write_vectored(bufs)
+            } else {
+                let buf = bufs.iter().find(|buf| !buf.is_empty()).map_or(&[][..], |buf| &**buf);
+                self.0.write(&buf[..cmp::min(buf.len(), granted)])
+            }
+        })
     }
 
     #[inline]
@@ -455,7 +470,8 @@ impl File {
     }
 
     pub fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
-        self.0.write_at(buf, offset)
+        let (_permit, len) = qos::admit(buf.len());
+        quota::write_fs(len, |granted| self.0.write_at(&buf[..granted], offset))
     }
 
     pub fn flush(&self) -> io::Result<()> {
@@ -747,6 +763,9 @@ pub fn copy(from: &Path, to: &Path) -> io::Result<u64> {
 
     use super::kernel_copy::{copy_regular_files, CopyResult};
 
+    if !quota::host_copy_allowed() {
+        return io::copy::generic_copy(&mut reader, &mut writer);
+    }
     match copy_regular_files(reader.as_raw_fd(), writer.as_raw_fd(), max_len) {
         CopyResult::Ended(bytes) => Ok(bytes),
         CopyResult::Error(e, _) => Err(e),
@@ -797,12 +816,14 @@ mod remove_dir_impl {
     use libc::{fdopendir, openat, unlinkat};
 
     pub fn openat_nofollow_dironly(parent_fd: Option<RawFd>, p: &CStr) -> io::Result<OwnedFd> {
-        let fd = cvt_r(|| unsafe {
-            openat(
-                parent_fd.unwrap_or(libc::AT_FDCWD),
-                p.as_ptr(),
-                libc::O_CLOEXEC | libc::O_RDONLY | libc::O_NOFOLLOW | libc::O_DIRECTORY,
-            )
+        let fd = crate::quota::open_fd(|| {
+            cvt_r(|| unsafe {
+                openat(
+                    parent_fd.unwrap_or(libc::AT_FDCWD),
+                    p.as_ptr(),
+                    libc::O_CLOEXEC | libc::O_RDONLY | libc::O_NOFOLLOW | libc::O_DIRECTORY,
+                )
+            })
         })?;
         Ok(unsafe { OwnedFd::from_raw_fd(fd) })
     }
@@ -0,0 +1,250 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Futex-style waiting on an atomic, for the enclave.
+//!
+//! SGX has no futex system call: a thread can only sleep on the untrusted
+//! event of its own TCS, until another thread sets that event. This module
+//! builds futex wait and wake on top of that. A waiting thread queues a node
+//! on its stack in a bucket of a fixed table, chosen by hashing the address
+//! of the atomic; waking an address dequeues the nodes queued for it, marks
+//! them woken and sets the events of their TCS.
+//!
+//! The value of the atomic is checked under the lock of its bucket, which a
+//! waker takes as well, so a wakeup that follows a change of the value is
+//! never missed. The event of a TCS is shared with the other blocking
+//! primitives of the thread and can be left set by an earlier wakeup, so a
+//! waiter only returns once its node is marked woken, or once its timeout
+//! expires.
+
+use crate::cell::UnsafeCell;
+use crate::ptr;
+use crate::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use crate::sync::SgxThreadSpinlock;
+use crate::sys::locks::mutex::{thread_set_event, thread_set_multiple_events, thread_wait_event};
+use crate::time::Duration;
+
+use sgx_libc as libc;
+use sgx_trts::enclave::SgxThreadData;
+
+const BUCKETS: usize = 256;
+
+// The number of TCS a wake collects under the bucket lock at a time.
+const WAKE_BATCH: usize = 32;
+
+const FOREVER: Duration = Duration::new(u64::MAX, 1_000_000_000 - 1);
+
+struct Waiter {
+    addr: usize,
+    tcs: usize,
+    woken: AtomicBool,
+    next: *mut Waiter,
+}
+
+struct Bucket {
+    lock: SgxThreadSpinlock,
+    // A list of waiters, oldest first, linked through nodes on the stacks of
+    // the waiting threads.
+    head: UnsafeCell<*mut Waiter>,
+}
+
+unsafe impl Sync for Bucket {}
+
+impl Bucket {
+    const fn new() -> Bucket {
+        Bucket {
+            lock: SgxThreadSpinlock::new(),
+            head: UnsafeCell::new(ptr::null_mut()),
+        }
+    }
+
+    // Must be called with the lock held.
+    unsafe fn push(&self, waiter: *mut Waiter) {
+        let mut link = self.head.get();
+        while !(*link).is_null() {
+            link = &mut (**link).next;
+        }
+        *link = waiter;
+    }
+
+    // Must be called with the lock held.
+    unsafe fn remove(&self, waiter: *mut Waiter) {
+        let mut link = self.head.get();
+        while !(*link).is_null() {
+            if *link == waiter {
+                *link = (*waiter).next;
+                return;
+            }
+            link = &mut (**link).next;
+        }
+    }
+
+    // Dequeues up to `tcss.len()` waiters of `addr`, marks them woken and
+    // stores their TCS in `tcss`, which the caller wakes once the lock is
+    // released. Returns the number dequeued. Must be called with the lock
+    // held.
+    unsafe fn take(&self, addr: usize, tcss: &mut [usize]) -> usize {
+        let mut count = 0;
+        let mut link = self.head.get();
+        while !(*link).is_null() && count < tcss.len() {
+            let waiter = *link;
+            if (*waiter).addr == addr {
+                *link = (*waiter).next;
+                tcss[count] = (*waiter).tcs;
+                count += 1;
+                // The waiter may return, and its node go away, as soon as
+                // it sees this.
+                (*waiter).woken.store(true, Ordering::Release);
+            } else {
+                link = &mut (*waiter).next;
+            }
+        }
+        count
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY: Bucket = Bucket::new();
+
+static TABLE: [Bucket; BUCKETS] = [EMPTY; BUCKETS];
+
+fn bucket(addr: usize) -> &'static Bucket {
+    // Fibonacci hashing of the address, without its alignment bits.
+    let hash = ((addr >> 2) as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    &TABLE[(hash >> (64 - BUCKETS.trailing_zeros())) as usize]
+}
+
+fn monotonic_now() -> Option<Duration> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let r = unsafe { libc::ocall::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    if r == 0 && ts.tv_sec >= 0 {
+        Some(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+    } else {
+        None
+    }
+}
+
+/// Waits for a wakeup on `futex`, if it holds `expected`.
+///
+/// Returns `false` if the timeout expired, and `true` otherwise: on a
+/// wakeup, or at once if `futex` did not hold `expected`.
+pub fn futex_wait(futex: &AtomicU32, expected: u32, timeout: Option<Duration>) -> bool {
+    // Without a clock, or past its range, the wait is a single timed wait.
+    let deadline = timeout.map(|dur| monotonic_now().and_then(|now| now.checked_add(dur)));
+
+    let addr = futex as *const AtomicU32 as usize;
+    let bucket = bucket(addr);
+    let mut waiter = Waiter {
+        addr,
+        tcs: SgxThreadData::current().get_tcs(),
+        woken: AtomicBool::new(false),
+        next: ptr::null_mut(),
+    };
+    // Other threads reach the node through the bucket, so it is only used
+    // through this pointer from now on.
+    let node: *mut Waiter = &mut waiter;
+    unsafe {
+        bucket.lock.lock();
+        if futex.load(Ordering::Relaxed) != expected {
+            bucket.lock.unlock();
+            return true;
+        }
+        bucket.push(node);
+        bucket.lock.unlock();
+    }
+
+    let mut waited = false;
+    loop {
+        let left = match (deadline, timeout) {
+            (Some(Some(deadline)), _) => {
+                match monotonic_now().and_then(|now| deadline.checked_sub(now)) {
+                    Some(left) if !left.is_zero() => left,
+                    _ => break,
+                }
+            }
+            (Some(None), Some(dur)) if !waited => dur,
+            (Some(None), _) => break,
+            (None, _) => FOREVER,
+        };
+        waited = true;
+        unsafe {
+            thread_wait_event((*node).tcs, left);
+            if (*node).woken.load(Ordering::Acquire) {
+                return true;
+            }
+        }
+    }
+
+    unsafe {
+        bucket.lock.lock();
+        let woken = (*node).woken.load(Ordering::Acquire);
+        if !woken {
+            bucket.remove(node);
+        }
+        bucket.lock.unlock();
+        woken
+    }
+}
+
+/// Wakes one thread waiting on `futex`. Returns whether there was one.
+pub fn futex_wake(futex: &AtomicU32) -> bool {
+    futex_wake_addr(futex as *const AtomicU32, 1) > 0
+}
+
+/// Wakes all threads waiting on `futex`.
+pub fn futex_wake_all(futex: &AtomicU32) {
+    futex_wake_addr(futex as *const AtomicU32, usize::MAX);
+}
+
+/// Wakes up to `max` threads waiting on the atomic at `futex`, which need
+/// not be alive any more. Returns the number of threads woken.
+pub fn futex_wake_addr(futex: *const AtomicU32, max: usize) -> usize {
+    let bucket = bucket(futex as usize);
+    // The TCS are collected on the stack, a batch at a time, so that nothing
+    // is allocated while the spinlock is held, and their events are set once
+    // it is released.
+    let mut tcss = [0_usize; WAKE_BATCH];
+    let mut woken = 0;
+    while woken < max {
+        let batch = (max - woken).min(WAKE_BATCH);
+        let count = unsafe {
+            bucket.lock.lock();
+            let count = bucket.take(futex as usize, &mut tcss[..batch]);
+            bucket.lock.unlock();
+            count
+        };
+        unsafe {
+            match count {
+                0 => {}
+                1 => {
+                    thread_set_event(tcss[0]);
+                }
+                _ => {
+                    thread_set_multiple_events(&tcss[..count]);
+                }
+            }
+        }
+        woken += count;
+        if count < batch {
+            break;
+        }
+    }
+    woken
+}
@@ -68,11 +68,11 @@ use crate::io::{BufRead, BufReader, BufWriter, Error, Read, Result, Take, Write}
 #[cfg(feature = "stdio")]
 use crate::io::{StderrLock, StdinLock, StdoutLock};
 use crate::mem::ManuallyDrop;
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 use crate::net::TcpStream;
 use crate::os::unix::fs::FileTypeExt;
 use crate::os::unix::io::{AsRawFd, FromRawFd, RawFd};
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 use crate::os::unix::net::UnixStream;
 use crate::ptr;
 use crate::sync::atomic::{AtomicBool, AtomicU8, Ordering};
@@ -173,8 +173,12 @@ impl<R: CopyRead, W: CopyWrite> SpecCopy for Copier<'_, '_, R, W> {
 
         let mut written = 0u64;
 
-        if let (CopyParams(input_meta, Some(readfd)), CopyParams(output_meta, Some(writefd))) =
-            (r_cfg, w_cfg)
+        // Copies done by the host would bypass the quota of written bytes.
+        if let (
+            CopyParams(input_meta, Some(readfd)),
+            CopyParams(output_meta, Some(writefd)),
+            true,
+        ) = (r_cfg, w_cfg, crate::quota::host_copy_allowed())
         {
             written += flush()?;
             let max_write = reader.min_limit();
@@ -317,7 +321,7 @@ impl CopyWrite for &File {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl CopyRead for TcpStream {
     fn properties(&self) -> CopyParams {
         // avoid the stat syscall since we can be fairly sure it's a socket
@@ -325,7 +329,7 @@ impl CopyRead for TcpStream {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl CopyRead for &TcpStream {
     fn properties(&self) -> CopyParams {
         // avoid the stat syscall since we can be fairly sure it's a socket
@@ -333,7 +337,7 @@ impl CopyRead for &TcpStream {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl CopyWrite for TcpStream {
     fn properties(&self) -> CopyParams {
         // avoid the stat syscall since we can be fairly sure it's a socket
@@ -341,7 +345,7 @@ impl CopyWrite for TcpStream {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl CopyWrite for &TcpStream {
     fn properties(&self) -> CopyParams {
         // avoid the stat syscall since we can be fairly sure it's a socket
@@ -349,7 +353,7 @@ impl CopyWrite for &TcpStream {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl CopyRead for UnixStream {
     fn properties(&self) -> CopyParams {
         // avoid the stat syscall since we can be fairly sure it's a socket
@@ -357,7 +361,7 @@ impl CopyRead for UnixStream {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl CopyRead for &UnixStream {
     fn properties(&self) -> CopyParams {
         // avoid the stat syscall since we can be fairly sure it's a socket
@@ -365,7 +369,7 @@ impl CopyRead for &UnixStream {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl CopyWrite for UnixStream {
     fn properties(&self) -> CopyParams {
         // avoid the stat syscall since we can be fairly sure it's a socket
@@ -373,7 +377,7 @@ impl CopyWrite for UnixStream {
     }
 }
 
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 impl CopyWrite for &UnixStream {
     fn properties(&self) -> CopyParams {
         // avoid the stat syscall since we can be fairly sure it's a socket
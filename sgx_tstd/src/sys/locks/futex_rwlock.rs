@@ -0,0 +1,582 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! A reader-writer lock on futex words, after the futex rwlock of std.
+//!
+//! On top of the lock of std, it has both policies of [`RwLockPolicy`],
+//! timed locking, and downgrade and upgrade. A reader that upgrades marks
+//! the lock, which keeps new readers out until the other readers have left
+//! and it holds the lock for writing.
+
+use crate::sync::atomic::{
+    AtomicU32, AtomicU8,
+    Ordering::{Acquire, Relaxed, Release},
+};
+use crate::sys::futex::{futex_wait, futex_wake, futex_wake_all};
+use crate::time::Duration;
+
+use sgx_libc as libc;
+use sgx_tsync::sys::RwLockPolicy;
+use sgx_types::SysError;
+
+pub struct RwLock {
+    // The state consists of a 29-bit reader counter, a 'reader upgrading'
+    // flag, a 'readers waiting' flag, and a 'writers waiting' flag.
+    // Bits 0..29:
+    //   0: Unlocked
+    //   1..=0x1FFF_FFFE: Locked by N readers
+    //   0x1FFF_FFFF: Write locked
+    // Bit 29: A reader is upgrading to a writer.
+    // Bit 30: Readers are waiting on this futex.
+    // Bit 31: Writers are waiting on the writer_notify futex.
+    state: AtomicU32,
+    // The 'condition variable' to notify writers through.
+    // Incremented on every signal.
+    writer_notify: AtomicU32,
+    // The 'condition variable' to notify the upgrading reader through.
+    upgrade_notify: AtomicU32,
+    policy: AtomicU8,
+}
+
+pub type MovableRwLock = RwLock;
+
+const READ_LOCKED: u32 = 1;
+const MASK: u32 = (1 << 29) - 1;
+const WRITE_LOCKED: u32 = MASK;
+const MAX_READERS: u32 = MASK - 1;
+const UPGRADING: u32 = 1 << 29;
+const READERS_WAITING: u32 = 1 << 30;
+const WRITERS_WAITING: u32 = 1 << 31;
+
+#[inline]
+fn is_unlocked(state: u32) -> bool {
+    state & MASK == 0
+}
+
+#[inline]
+fn is_write_locked(state: u32) -> bool {
+    state & MASK == WRITE_LOCKED
+}
+
+#[inline]
+fn has_readers_waiting(state: u32) -> bool {
+    state & READERS_WAITING != 0
+}
+
+#[inline]
+fn has_writers_waiting(state: u32) -> bool {
+    state & WRITERS_WAITING != 0
+}
+
+#[inline]
+fn has_reached_max_readers(state: u32) -> bool {
+    state & MASK == MAX_READERS
+}
+
+const READER_PREFERRED: u8 = 0;
+const WRITER_PREFERRED: u8 = 1;
+
+// Where a timed lock gives up, as the remaining time.
+// A deadline on the monotonic clock of the host. A timeout too large to
+// represent saturates, and a timeout whose start cannot be read from the clock
+// is treated as no deadline rather than one that has already passed.
+struct Deadline(Option<Duration>);
+
+impl Deadline {
+    fn new(timeout: Option<Duration>) -> Deadline {
+        Deadline(timeout.and_then(|dur| monotonic_now().map(|now| now.saturating_add(dur))))
+    }
+
+    // The time left, or `None` once the deadline has passed. Never is
+    // represented as `Some(None)`, and so is a failed clock read.
+    fn left(&self) -> Option<Option<Duration>> {
+        match self.0 {
+            None => Some(None),
+            Some(deadline) => match monotonic_now() {
+                None => Some(None),
+                Some(now) => match deadline.checked_sub(now) {
+                    Some(left) if !left.is_zero() => Some(Some(left)),
+                    _ => None,
+                },
+            },
+        }
+    }
+}
+
+fn monotonic_now() -> Option<Duration> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let r = unsafe { libc::ocall::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    if r == 0 && ts.tv_sec >= 0 {
+        Some(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+    } else {
+        None
+    }
+}
+
+impl RwLock {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            writer_notify: AtomicU32::new(0),
+            upgrade_notify: AtomicU32::new(0),
+            policy: AtomicU8::new(READER_PREFERRED),
+        }
+    }
+
+    #[inline]
+    pub unsafe fn policy(&self) -> RwLockPolicy {
+        match self.policy.load(Relaxed) {
+            WRITER_PREFERRED => RwLockPolicy::WriterPreferred,
+            _ => RwLockPolicy::ReaderPreferred,
+        }
+    }
+
+    #[inline]
+    pub unsafe fn set_policy(&self, policy: RwLockPolicy) {
+        let policy = match policy {
+            RwLockPolicy::ReaderPreferred => READER_PREFERRED,
+            RwLockPolicy::WriterPreferred => WRITER_PREFERRED,
+        };
+        self.policy.store(policy, Relaxed);
+    }
+
+    #[inline]
+    fn is_read_lockable(&self, state: u32) -> bool {
+        // A reader can lock while no writer holds the lock and no reader is
+        // upgrading. With the writer preferred policy, it also waits while
+        // other threads wait, as the lock goes to a writer first. This also
+        // keeps a woken reader from entering before the other readers were
+        // woken, which is needed for the case of a writer that still has to
+        // be woken.
+        state & MASK < MAX_READERS
+            && state & UPGRADING == 0
+            && (self.policy.load(Relaxed) == READER_PREFERRED
+                || !has_readers_waiting(state) && !has_writers_waiting(state))
+    }
+
+    #[inline]
+    pub unsafe fn try_read(&self) -> SysError {
+        self.state
+            .fetch_update(Acquire, Relaxed, |s| {
+                self.is_read_lockable(s).then_some(s + READ_LOCKED)
+            })
+            .map(drop)
+            .map_err(|_| libc::EBUSY)
+    }
+
+    #[inline]
+    pub unsafe fn read(&self) -> SysError {
+        let state = self.state.load(Relaxed);
+        if !self.is_read_lockable(state)
+            || self
+                .state
+                .compare_exchange_weak(state, state + READ_LOCKED, Acquire, Relaxed)
+                .is_err()
+        {
+            self.read_contended(&Deadline(None))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub unsafe fn read_timeout(&self, dur: Duration) -> SysError {
+        if self.try_read().is_ok() {
+            return Ok(());
+        }
+        self.read_contended(&Deadline::new(Some(dur)))
+    }
+
+    #[cold]
+    fn read_contended(&self, deadline: &Deadline) -> SysError {
+        let mut state = self.spin_read();
+
+        loop {
+            // If we can lock it, lock it.
+            if self.is_read_lockable(state) {
+                match self
+                    .state
+                    .compare_exchange_weak(state, state + READ_LOCKED, Acquire, Relaxed)
+                {
+                    Ok(_) => return Ok(()), // Locked!
+                    Err(s) => {
+                        state = s;
+                        continue;
+                    }
+                }
+            }
+
+            // Check for overflow.
+            if has_reached_max_readers(state) {
+                panic!("too many active read locks on RwLock");
+            }
+
+            let left = match deadline.left() {
+                Some(left) => left,
+                None => return Err(libc::ETIMEDOUT),
+            };
+
+            // Make sure the readers waiting bit is set before we go to sleep.
+            if !has_readers_waiting(state) {
+                if let Err(s) =
+                    self.state
+                        .compare_exchange(state, state | READERS_WAITING, Relaxed, Relaxed)
+                {
+                    state = s;
+                    continue;
+                }
+            }
+
+            // Wait for the state to change.
+            futex_wait(&self.state, state | READERS_WAITING, left);
+
+            // Spin again after waking up.
+            state = self.spin_read();
+        }
+    }
+
+    #[inline]
+    pub unsafe fn read_unlock(&self) -> SysError {
+        let state = self.state.fetch_sub(READ_LOCKED, Release) - READ_LOCKED;
+
+        // With the writer preferred policy, readers only wait on a read-locked
+        // RwLock if a writer waits too. With the other policy, readers left
+        // waiting by a race with a new reader are woken by the last reader.
+        if state & UPGRADING != 0 && state & MASK == READ_LOCKED {
+            // Only the upgrading reader is left.
+            self.wake_upgrader();
+        } else if is_unlocked(state) && (has_writers_waiting(state) || has_readers_waiting(state)) {
+            self.wake_writer_or_readers(state);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub unsafe fn try_write(&self) -> SysError {
+        self.state
+            .fetch_update(Acquire, Relaxed, |s| {
+                is_unlocked(s).then_some(s + WRITE_LOCKED)
+            })
+            .map(drop)
+            .map_err(|_| libc::EBUSY)
+    }
+
+    #[inline]
+    pub unsafe fn write(&self) -> SysError {
+        if self
+            .state
+            .compare_exchange_weak(0, WRITE_LOCKED, Acquire, Relaxed)
+            .is_err()
+        {
+            self.write_contended(&Deadline(None))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub unsafe fn write_timeout(&self, dur: Duration) -> SysError {
+        if self.try_write().is_ok() {
+            return Ok(());
+        }
+        self.write_contended(&Deadline::new(Some(dur)))
+    }
+
+    #[cold]
+    fn write_contended(&self, deadline: &Deadline) -> SysError {
+        let mut state = self.spin_write();
+
+        let mut other_writers_waiting = 0;
+
+        loop {
+            // If it's unlocked, we try to lock it.
+            if is_unlocked(state) {
+                match self.state.compare_exchange_weak(
+                    state,
+                    state | WRITE_LOCKED | other_writers_waiting,
+                    Acquire,
+                    Relaxed,
+                ) {
+                    Ok(_) => return Ok(()), // Locked!
+                    Err(s) => {
+                        state = s;
+                        continue;
+                    }
+                }
+            }
+
+            let left = match deadline.left() {
+                Some(left) => left,
+                None => {
+                    self.abandon_write();
+                    return Err(libc::ETIMEDOUT);
+                }
+            };
+
+            // Set the waiting bit indicating that we're waiting on it.
+            if !has_writers_waiting(state) {
+                if let Err(s) =
+                    self.state
+                        .compare_exchange(state, state | WRITERS_WAITING, Relaxed, Relaxed)
+                {
+                    state = s;
+                    continue;
+                }
+            }
+
+            // Other writers might be waiting now too, so we should make sure
+            // we keep that bit on once we manage lock it.
+            other_writers_waiting = WRITERS_WAITING;
+
+            // Examine the notification counter before we check if `state` has changed,
+            // to make sure we don't miss any notifications.
+            let seq = self.writer_notify.load(Acquire);
+
+            // Don't go to sleep if the lock has become available,
+            // or if the writers waiting bit is no longer set.
+            state = self.state.load(Relaxed);
+            if is_unlocked(state) || !has_writers_waiting(state) {
+                continue;
+            }
+
+            // Wait for the state to change.
+            futex_wait(&self.writer_notify, seq, left);
+
+            // Spin again after waking up.
+            state = self.spin_write();
+        }
+    }
+
+    // A writer that gives up may have taken the wakeup meant for another
+    // writer, and the writers waiting bit with it. Sets the bit again, so
+    // that the next unlock wakes a writer, or wakes the waiters at once if
+    // the lock is free.
+    #[cold]
+    fn abandon_write(&self) {
+        let state = self.state.fetch_or(WRITERS_WAITING, Relaxed) | WRITERS_WAITING;
+        if is_unlocked(state) {
+            self.wake_writer_or_readers(state);
+        }
+    }
+
+    #[inline]
+    pub unsafe fn write_unlock(&self) -> SysError {
+        let state = self.state.fetch_sub(WRITE_LOCKED, Release) - WRITE_LOCKED;
+
+        debug_assert!(is_unlocked(state));
+
+        if has_writers_waiting(state) || has_readers_waiting(state) {
+            self.wake_writer_or_readers(state);
+        }
+        Ok(())
+    }
+
+    /// Turns a write lock held by the current thread into a read lock, and
+    /// lets the waiting readers in along with it.
+    pub unsafe fn downgrade(&self) -> SysError {
+        let state = self.state.load(Relaxed);
+        if !is_write_locked(state) {
+            return Err(libc::EPERM);
+        }
+        let state = self.state.fetch_sub(WRITE_LOCKED - READ_LOCKED, Release)
+            - (WRITE_LOCKED - READ_LOCKED);
+        if has_readers_waiting(state) {
+            self.state.fetch_and(!READERS_WAITING, Relaxed);
+            futex_wake_all(&self.state);
+        }
+        Ok(())
+    }
+
+    /// Turns the read lock of the current thread into a write lock if no
+    /// other thread holds a read lock.
+    pub unsafe fn try_upgrade(&self) -> SysError {
+        self.state
+            .fetch_update(Acquire, Relaxed, |s| {
+                (s & MASK == READ_LOCKED).then_some(s - READ_LOCKED + WRITE_LOCKED)
+            })
+            .map(drop)
+            .map_err(|_| libc::EBUSY)
+    }
+
+    /// Turns the read lock of the current thread into a write lock, waiting
+    /// for the other readers to leave. Fails with `EDEADLK` if another reader
+    /// is upgrading, as both would wait for each other.
+    pub unsafe fn upgrade(&self) -> SysError {
+        let mut state = self
+            .state
+            .fetch_update(Relaxed, Relaxed, |s| {
+                (s & UPGRADING == 0 && s & MASK != 0 && !is_write_locked(s))
+                    .then_some(s | UPGRADING)
+            })
+            .map_err(|s| {
+                if s & UPGRADING != 0 {
+                    libc::EDEADLK
+                } else {
+                    libc::EPERM
+                }
+            })?
+            | UPGRADING;
+
+        loop {
+            if state & MASK == READ_LOCKED {
+                match self.state.compare_exchange_weak(
+                    state,
+                    (state & !UPGRADING) - READ_LOCKED + WRITE_LOCKED,
+                    Acquire,
+                    Relaxed,
+                ) {
+                    Ok(_) => return Ok(()),
+                    Err(s) => {
+                        state = s;
+                        continue;
+                    }
+                }
+            }
+
+            let seq = self.upgrade_notify.load(Acquire);
+            state = self.state.load(Relaxed);
+            if state & MASK == READ_LOCKED {
+                continue;
+            }
+            futex_wait(&self.upgrade_notify, seq, None);
+            state = self.state.load(Relaxed);
+        }
+    }
+
+    #[cold]
+    fn wake_upgrader(&self) {
+        self.upgrade_notify.fetch_add(1, Release);
+        futex_wake(&self.upgrade_notify);
+    }
+
+    /// Wake up waiting threads after unlocking.
+    ///
+    /// If both are waiting, this will wake up only one writer, but will fall
+    /// back to waking up readers if there was no writer to wake up. With the
+    /// reader preferred policy, it wakes up all readers and a writer.
+    #[cold]
+    fn wake_writer_or_readers(&self, mut state: u32) {
+        assert!(is_unlocked(state));
+
+        if self.policy.load(Relaxed) == READER_PREFERRED {
+            // Clear both bits even if the lock was taken in the meantime:
+            // the woken threads that cannot lock set them again.
+            let state = self
+                .state
+                .fetch_and(!(READERS_WAITING | WRITERS_WAITING), Relaxed);
+            if has_writers_waiting(state) {
+                self.wake_writer();
+            }
+            if has_readers_waiting(state) {
+                futex_wake_all(&self.state);
+            }
+            return;
+        }
+
+        // The readers waiting bit might be turned on at any point now,
+        // since readers will block when there's anything waiting.
+        // Writers will just lock the lock though, regardless of the waiting bits,
+        // so we don't have to worry about the writer waiting bit.
+        //
+        // If the lock gets locked in the meantime, we don't have to do
+        // anything, because then the thread that locked the lock will take
+        // care of waking up waiters when it unlocks.
+
+        // If only writers are waiting, wake one of them up.
+        if state == WRITERS_WAITING {
+            match self.state.compare_exchange(state, 0, Relaxed, Relaxed) {
+                Ok(_) => {
+                    self.wake_writer();
+                    return;
+                }
+                Err(s) => {
+                    // Maybe some readers are now waiting too. So, continue to the next `if`.
+                    state = s;
+                }
+            }
+        }
+
+        // If both writers and readers are waiting, leave the readers waiting
+        // and only wake up one writer.
+        if state == READERS_WAITING + WRITERS_WAITING {
+            if self
+                .state
+                .compare_exchange(state, READERS_WAITING, Relaxed, Relaxed)
+                .is_err()
+            {
+                // The lock got locked. Not our problem anymore.
+                return;
+            }
+            if self.wake_writer() {
+                return;
+            }
+            // No writers were actually blocked on futex_wait, so we continue
+            // to wake up readers instead, since we can't be sure if we notified a writer.
+            state = READERS_WAITING;
+        }
+
+        // If readers are waiting, wake them all up.
+        if state == READERS_WAITING
+            && self
+                .state
+                .compare_exchange(state, 0, Relaxed, Relaxed)
+                .is_ok()
+        {
+            futex_wake_all(&self.state);
+        }
+    }
+
+    /// This wakes one writer and returns true if we woke up a writer that was
+    /// blocked on futex_wait.
+    ///
+    /// If this returns false, it might still be the case that we notified a
+    /// writer that was about to go to sleep.
+    fn wake_writer(&self) -> bool {
+        self.writer_notify.fetch_add(1, Release);
+        futex_wake(&self.writer_notify)
+    }
+
+    /// Spin for a while, but stop directly at the given condition.
+    #[inline]
+    fn spin_until(&self, f: impl Fn(u32) -> bool) -> u32 {
+        let mut spin = 100; // Chosen by fair dice roll.
+        loop {
+            let state = self.state.load(Relaxed);
+            if f(state) || spin == 0 {
+                return state;
+            }
+            crate::hint::spin_loop();
+            spin -= 1;
+        }
+    }
+
+    #[inline]
+    fn spin_write(&self) -> u32 {
+        // Stop spinning when it's unlocked or when there's waiting writers, to keep things somewhat fair.
+        self.spin_until(|state| is_unlocked(state) || has_writers_waiting(state))
+    }
+
+    #[inline]
+    fn spin_read(&self) -> u32 {
+        // Stop spinning when it's unlocked or read locked, or when there's waiting threads.
+        self.spin_until(|state| {
+            !is_write_locked(state) || has_readers_waiting(state) || has_writers_waiting(state)
+        })
+    }
+}
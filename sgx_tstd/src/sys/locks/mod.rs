@@ -17,9 +17,19 @@
 
 #![allow(unused_imports)]
 
-pub(crate) mod mutex;
-pub(crate) mod rwlock;
-pub(crate) mod condvar;
-pub(crate) use mutex::{MovableMutex, MovableReentrantMutex, Mutex, ReentrantMutex};
-pub(crate) use rwlock::{MovableRwLock, RwLock};
-pub(crate) use condvar::MovableCondvar;
+// The mutex, condvar and rwlock of this crate sit on the futexes of
+// `sys::futex`. The reentrant and adaptive mutexes, and the queue-based locks
+// for `no_std` enclaves, live in `sgx_tsync`, along with the OCALLs of the
+// thread events.
+mod futex_condvar;
+mod futex_mutex;
+mod futex_rwlock;
+
+pub(crate) use sgx_tsync::sys::mutex;
+pub(crate) use self::futex_mutex::{MovableMutex, Mutex};
+pub(crate) use sgx_tsync::sys::{MovableReentrantMutex, ReentrantMutex};
+pub(crate) use sgx_tsync::sys::MovableAdaptiveMutex;
+pub use sgx_tsync::sys::MutexStats;
+pub(crate) use self::futex_rwlock::{MovableRwLock, RwLock};
+pub use sgx_tsync::sys::RwLockPolicy;
+pub(crate) use self::futex_condvar::{Condvar, MovableCondvar};
@@ -15,27 +15,17 @@
 // specific language governing permissions and limitations
 // under the License..
 
-use crate::ops::{Deref, DerefMut};
+use crate::io;
+use crate::sys::cvt;
 
-#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
-#[repr(align(64))]
-pub(super) struct CacheAligned<T>(pub T);
-
-impl<T> Deref for CacheAligned<T> {
-    type Target = T;
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl<T> DerefMut for CacheAligned<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
+/// Delivers an encoded batch of `count` records to the untrusted collector,
+/// returning how many records the host acknowledged.
+pub fn ship(batch: &[u8], count: usize) -> io::Result<usize> {
+    let acked = cvt(unsafe { libc::log_ship(batch.as_ptr(), batch.len(), count) })?;
+    Ok(acked as usize)
 }
 
-impl<T> CacheAligned<T> {
-    pub(super) fn new(t: T) -> Self {
-        CacheAligned(t)
-    }
+mod libc {
+    pub use sgx_libc::ocall::log_ship;
+    pub use sgx_libc::*;
 }
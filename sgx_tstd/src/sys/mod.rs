@@ -25,19 +25,25 @@ pub use self::rand::hashmap_random_keys;
 pub mod backtrace;
 pub mod cmath;
 pub mod common;
+#[cfg(not(minimal_tcb))]
 pub mod env;
 pub mod fd;
+#[cfg(not(minimal_tcb))]
 pub mod fs;
+pub mod futex;
 pub mod io;
+#[cfg(not(minimal_tcb))]
 pub mod kernel_copy;
 pub mod locks;
+#[cfg(feature = "thread")]
+pub mod log;
 pub mod memchr;
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 pub mod net;
 pub mod os;
 pub mod os_str;
 pub mod path;
-#[cfg(feature = "pipe")]
+#[cfg(tstd_pipe)]
 pub mod pipe;
 pub mod rand;
 pub mod sgxfs;
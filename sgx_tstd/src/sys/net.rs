@@ -21,6 +21,7 @@ use crate::io::{self, IoSlice, IoSliceMut};
 use crate::mem;
 use crate::net::{Shutdown, SocketAddr};
 use crate::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd};
+use crate::quota;
 use crate::str;
 use crate::sys::fd::FileDesc;
 use crate::sys_common::net::{getsockopt, setsockopt, sockaddr_to_addr};
@@ -81,17 +82,19 @@ impl Socket {
             // On platforms that support it we pass the SOCK_CLOEXEC
             // flag to atomically create the socket and set it as
             // CLOEXEC. On Linux this was added in 2.6.27.
-            let fd = cvt(libc::socket(fam, ty | libc::SOCK_CLOEXEC, 0))?;
+            let fd = quota::open_fd(|| cvt(libc::socket(fam, ty | libc::SOCK_CLOEXEC, 0)))?;
             Ok(Socket(FileDesc::from_raw_fd(fd)))
         }
     }
 
     pub fn new_pair(fam: c_int, ty: c_int) -> io::Result<(Socket, Socket)> {
         unsafe {
-            let mut fds = [0, 0];
-
             // Like above, set cloexec atomically
-            cvt(libc::socketpair(fam, ty | libc::SOCK_CLOEXEC, 0, fds.as_mut_ptr()))?;
+            let fds = quota::open_fds(|| {
+                let mut fds = [0, 0];
+                cvt(libc::socketpair(fam, ty | libc::SOCK_CLOEXEC, 0, fds.as_mut_ptr()))?;
+                Ok(fds)
+            })?;
             Ok((Socket(FileDesc::from_raw_fd(fds[0])), Socket(FileDesc::from_raw_fd(fds[1]))))
         }
     }
@@ -172,7 +175,9 @@ impl Socket {
         // platforms that support it. On Linux, this was added in 2.6.28,
         // glibc 2.10 and musl 0.9.5.
         unsafe {
-            let fd = cvt_r(|| libc::accept4(self.as_raw_fd(), storage, len, libc::SOCK_CLOEXEC))?;
+            let fd = quota::open_fd(|| {
+                cvt_r(|| libc::accept4(self.as_raw_fd(), storage, len, libc::SOCK_CLOEXEC))
+            })?;
             Ok(Socket(FileDesc::from_raw_fd(fd)))
         }
     }
@@ -192,6 +197,12 @@ impl Socket {
         self.recv_with_flags(buf, 0)
     }
 
+    /// Receives into `len` bytes at `buf`, outside the enclave.
+    pub unsafe fn recv_untrusted(&self, buf: *mut u8, len: usize) -> io::Result<usize> {
+        let ret = cvt(libc::recv_untrusted(self.as_raw_fd(), buf as *mut c_void, len, 0))?;
+        Ok(ret as usize)
+    }
+
     pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
         self.recv_with_flags(buf, MSG_PEEK)
     }
@@ -414,8 +425,8 @@ impl FromRawFd for Socket {
 
 mod libc {
     pub use sgx_libc::ocall::{
-        accept4, connect, gai_strerror, ioctl_arg1, poll, recv, recvfrom, recvmsg, sendmsg, shutdown, socket,
-        socketpair,
+        accept4, connect, gai_strerror, ioctl_arg1, poll, recv, recv_untrusted, recvfrom, recvmsg,
+        sendmsg, shutdown, socket, socketpair,
     };
     pub use sgx_libc::*;
 }
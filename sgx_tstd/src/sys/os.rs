@@ -15,6 +15,8 @@
 // specific language governing permissions and limitations
 // under the License..
 
+#![cfg_attr(minimal_tcb, allow(unused_imports))]
+
 use crate::os::unix::prelude::*;
 
 use crate::error::Error as StdError;
@@ -37,6 +39,7 @@ use sgx_trts::error as trts_error;
 use sgx_types::metadata::SE_PAGE_SIZE;
 
 const TMPBUF_SZ: usize = 128;
+#[cfg(not(minimal_tcb))]
 const PATH_SEPARATOR: u8 = b':';
 
 #[inline]
@@ -59,6 +62,7 @@ pub fn error_string(error: i32) -> String {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 pub fn getcwd() -> io::Result<PathBuf> {
     let mut buf = Vec::with_capacity(512);
     loop {
@@ -85,16 +89,19 @@ pub fn getcwd() -> io::Result<PathBuf> {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 pub fn chdir(p: &path::Path) -> io::Result<()> {
     let result = run_path_with_cstr(p, |p| unsafe { Ok(libc::chdir(p.as_ptr())) })?;
     if result == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
 }
 
+#[cfg(not(minimal_tcb))]
 #[allow(clippy::type_complexity)]
 pub struct SplitPaths<'a> {
     iter: iter::Map<slice::Split<'a, u8, fn(&u8) -> bool>, fn(&'a [u8]) -> PathBuf>,
 }
 
+#[cfg(not(minimal_tcb))]
 pub fn split_paths(unparsed: &OsStr) -> SplitPaths<'_> {
     fn bytes_to_path(b: &[u8]) -> PathBuf {
         PathBuf::from(<OsStr as OsStrExt>::from_bytes(b))
@@ -110,6 +117,7 @@ pub fn split_paths(unparsed: &OsStr) -> SplitPaths<'_> {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 impl<'a> Iterator for SplitPaths<'a> {
     type Item = PathBuf;
     fn next(&mut self) -> Option<PathBuf> {
@@ -120,9 +128,11 @@ impl<'a> Iterator for SplitPaths<'a> {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 #[derive(Debug)]
 pub struct JoinPathsError;
 
+#[cfg(not(minimal_tcb))]
 pub fn join_paths<I, T>(paths: I) -> Result<OsString, JoinPathsError>
 where
     I: Iterator<Item = T>,
@@ -143,12 +153,14 @@ where
     Ok(OsStringExt::from_vec(joined))
 }
 
+#[cfg(not(minimal_tcb))]
 impl fmt::Display for JoinPathsError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "path segment contains separator `{}`", char::from(PATH_SEPARATOR))
     }
 }
 
+#[cfg(not(minimal_tcb))]
 impl StdError for JoinPathsError {
     #[allow(deprecated)]
     fn description(&self) -> &str {
@@ -156,6 +168,7 @@ impl StdError for JoinPathsError {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 pub fn current_exe() -> io::Result<PathBuf> {
     match crate::fs::read_link("/proc/self/exe") {
         Err(ref e) if e.kind() == io::ErrorKind::NotFound => Err(io::const_io_error!(
@@ -166,13 +179,17 @@ pub fn current_exe() -> io::Result<PathBuf> {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 pub struct Env {
     iter: vec::IntoIter<(OsString, OsString)>,
 }
 
+#[cfg(not(minimal_tcb))]
 impl !Send for Env {}
+#[cfg(not(minimal_tcb))]
 impl !Sync for Env {}
 
+#[cfg(not(minimal_tcb))]
 impl Iterator for Env {
     type Item = (OsString, OsString);
     fn next(&mut self) -> Option<(OsString, OsString)> {
@@ -183,16 +200,20 @@ impl Iterator for Env {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 pub unsafe fn environ() -> *const *const libc::c_char {
     libc::environ()
 }
 
+#[cfg(not(minimal_tcb))]
 static ENV_LOCK: RwLock<()> = RwLock::new(());
 
+#[cfg(not(minimal_tcb))]
 pub fn env_read_lock() -> impl Drop {
     ENV_LOCK.read().unwrap_or_else(PoisonError::into_inner)
 }
 
+#[cfg(not(minimal_tcb))]
 /// Returns a vector of (variable, value) byte-vector pairs for all the
 /// environment variables of the current process.
 pub fn env() -> Env {
@@ -229,6 +250,7 @@ pub fn env() -> Env {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 pub fn getenv(k: &OsStr) -> io::Result<Option<OsString>> {
     // environment variables with a nul byte can't be set, so their value is
     // always None as well
@@ -243,6 +265,7 @@ pub fn getenv(k: &OsStr) -> io::Result<Option<OsString>> {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 pub fn setenv(k: &OsStr, v: &OsStr) -> io::Result<()> {
     run_with_cstr(k.as_bytes(), |k| {
         run_with_cstr(v.as_bytes(), |v| {
@@ -252,6 +275,7 @@ pub fn setenv(k: &OsStr, v: &OsStr) -> io::Result<()> {
     })
 }
 
+#[cfg(not(minimal_tcb))]
 pub fn unsetenv(n: &OsStr) -> io::Result<()> {
     run_with_cstr(n.as_bytes(), |nbuf| {
         let _guard = ENV_LOCK.write();
@@ -263,12 +287,14 @@ pub fn page_size() -> usize {
     SE_PAGE_SIZE
 }
 
+#[cfg(not(minimal_tcb))]
 pub fn temp_dir() -> PathBuf {
     crate::env::var_os("TMPDIR").map(PathBuf::from).unwrap_or_else(|| {
         PathBuf::from("/tmp")
     })
 }
 
+#[cfg(not(minimal_tcb))]
 pub fn home_dir() -> Option<PathBuf> {
     return crate::env::var_os("HOME").or_else(|| unsafe { fallback() }).map(PathBuf::from);
 
@@ -297,6 +323,7 @@ pub fn home_dir() -> Option<PathBuf> {
     }
 }
 
+#[cfg(not(minimal_tcb))]
 mod libc {
     pub use sgx_libc::ocall::{environ, getenv, setenv, unsetenv, getcwd, chdir, sysconf, getuid, getpwuid_r};
     pub use sgx_libc::*;
@@ -15,6 +15,7 @@
 // specific language governing permissions and limitations
 // under the License..
 
+#[cfg(not(minimal_tcb))]
 use crate::env;
 use crate::ffi::OsStr;
 use crate::io;
@@ -62,7 +63,7 @@ pub(crate) fn absolute(path: &Path) -> io::Result<PathBuf> {
             PathBuf::new()
         }
     } else {
-        env::current_dir()?
+        current_dir()?
     };
     normalized.extend(components);
 
@@ -78,3 +79,19 @@ pub(crate) fn absolute(path: &Path) -> io::Result<PathBuf> {
 
     Ok(normalized)
 }
+
+#[cfg(not(minimal_tcb))]
+#[inline]
+fn current_dir() -> io::Result<PathBuf> {
+    env::current_dir()
+}
+
+// The minimal profile has no getcwd OCALL, so relative paths cannot be resolved.
+#[cfg(minimal_tcb)]
+#[inline]
+fn current_dir() -> io::Result<PathBuf> {
+    Err(io::const_io_error!(
+        io::ErrorKind::Unsupported,
+        "the current directory is not available in the minimal profile",
+    ))
+}
@@ -18,6 +18,8 @@
 use alloc_crate::slice;
 use core::mem;
 
+pub use self::imp::fill_bytes;
+
 pub fn hashmap_random_keys() -> (u64, u64) {
     let mut v = (0, 0);
     unsafe {
@@ -15,16 +15,72 @@
 // specific language governing permissions and limitations
 // under the License..
 
+use crate::cmp;
 use crate::ffi::{CStr, CString};
 use crate::io::{self, Error, SeekFrom};
+use crate::mem;
 use crate::os::unix::prelude::*;
 use crate::path::Path;
+use crate::qos;
 use crate::sys_common::FromInner;
+#[cfg(feature = "thread")]
+use crate::thread::ThreadPool;
+use crate::vec::Vec;
 use sgx_libc as libc;
 use sgx_tprotected_fs::{self, SgxFileStream};
 use sgx_types::{sgx_align_key_128bit_t, sgx_key_128bit_t, sgx_status_t};
 
-pub struct SgxFile(SgxFileStream);
+pub struct SgxFile {
+    stream: SgxFileStream,
+    // How the file was opened, for `set_len` to open it again.
+    reopen: Option<Reopen>,
+}
+
+// Files are shrunk by copying the part kept to a file of this suffix and
+// back: the protected FS cannot truncate, and binds the contents of a file
+// to its name, so the copy cannot be renamed over the original.
+const TRUNCATE_SUFFIX: &[u8] = b".truncate~";
+const COPY_CHUNK: usize = 64 * 1024;
+
+struct Reopen {
+    path: CString,
+    mode: CString,
+    key: Option<sgx_key_128bit_t>,
+    auto: bool,
+    cache_size: Option<u64>,
+}
+
+impl Reopen {
+    fn open(&self, path: &CStr, mode: &str) -> io::Result<SgxFile> {
+        let mode = CString::new(mode)?;
+        SgxFile::open_c(path, &mode, self.key.as_ref(), self.auto, self.cache_size)
+    }
+
+    fn is_writable(&self) -> bool {
+        self.mode
+            .as_bytes()
+            .iter()
+            .any(|c| matches!(c, b'w' | b'a' | b'+'))
+    }
+
+    // A mode that opens the file again without truncating it.
+    fn reopen_mode(&self) -> &str {
+        match self.mode.to_str() {
+            Ok(mode) if mode.starts_with('a') => mode,
+            _ => "r+",
+        }
+    }
+}
+
+impl Drop for Reopen {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.as_mut() {
+            for b in key.iter_mut() {
+                unsafe { crate::ptr::write_volatile(b, 0) };
+            }
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct OpenOptions {
@@ -121,22 +177,40 @@ impl SgxFile {
             SgxFileStream::open(path, opts, key.unwrap())
         };
 
-        file.map(SgxFile).map_err(|err| match err {
-            1 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_UNEXPECTED),
-            2 => Error::from_raw_os_error(libc::ENOENT),
-            3 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_OUT_OF_MEMORY),
-            4 | 5 => Error::from_raw_os_error(err),
-            r if r > 4096 => {
-                let status =
-                    sgx_status_t::from_repr(r as u32).unwrap_or(sgx_status_t::SGX_ERROR_UNEXPECTED);
-                Error::from_sgx_error(status)
-            }
-            _ => Error::from_raw_os_error(err),
-        })
+        let reopen = Reopen {
+            path: path.to_owned(),
+            mode: opts.to_owned(),
+            key: key.copied(),
+            auto,
+            cache_size,
+        };
+        let file = file
+            .map(|stream| SgxFile {
+                stream,
+                reopen: Some(reopen),
+            })
+            .map_err(|err| match err {
+                1 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_UNEXPECTED),
+                2 => Error::from_raw_os_error(libc::ENOENT),
+                3 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_OUT_OF_MEMORY),
+                4 | 5 => Error::from_raw_os_error(err),
+                r if r > 4096 => {
+                    let status = sgx_status_t::from_repr(r as u32)
+                        .unwrap_or(sgx_status_t::SGX_ERROR_UNEXPECTED);
+                    Error::from_sgx_error(status)
+                }
+                _ => Error::from_raw_os_error(err),
+            })?;
+
+        #[cfg(not(minimal_tcb))]
+        if matches!(opts.to_bytes().first(), Some(b'w' | b'a')) {
+            dir::record(path)?;
+        }
+        Ok(file)
     }
 
     pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
-        self.0.read(buf).map_err(|err| match err {
+        self.stream.read(buf).map_err(|err| match err {
             1 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_UNEXPECTED),
             2 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
             3 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_OUT_OF_MEMORY),
@@ -151,7 +225,8 @@ impl SgxFile {
     }
 
     pub fn write(&self, buf: &[u8]) -> io::Result<usize> {
-        self.0.write(buf).map_err(|err| match err {
+        let (_permit, len) = qos::admit(buf.len());
+        self.stream.write(&buf[..len]).map_err(|err| match err {
             1 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_UNEXPECTED),
             2 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
             3 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_OUT_OF_MEMORY),
@@ -166,7 +241,7 @@ impl SgxFile {
     }
 
     pub fn tell(&self) -> io::Result<u64> {
-        self.0
+        self.stream
             .tell()
             .map_err(|err| match err {
                 r if r > 4096 => {
@@ -186,7 +261,7 @@ impl SgxFile {
             SeekFrom::Current(off) => (sgx_tprotected_fs::SeekFrom::Current, off),
         };
 
-        self.0.seek(offset, whence).map_err(|err| match err {
+        self.stream.seek(offset, whence).map_err(|err| match err {
             r if r > 4096 => {
                 let status =
                     sgx_status_t::from_repr(r as u32).unwrap_or(sgx_status_t::SGX_ERROR_UNEXPECTED);
@@ -200,7 +275,8 @@ impl SgxFile {
     }
 
     pub fn flush(&self) -> io::Result<()> {
-        self.0.flush().map_err(|err| match err {
+        let (_permit, _) = qos::admit(0);
+        self.stream.flush().map_err(|err| match err {
             1 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_UNEXPECTED),
             2 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
             3 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_OUT_OF_MEMORY),
@@ -215,15 +291,107 @@ impl SgxFile {
     }
 
     pub fn is_eof(&self) -> bool {
-        self.0.is_eof()
+        self.stream.is_eof()
     }
 
     pub fn clearerr(&self) {
-        self.0.clearerr()
+        self.stream.clearerr()
+    }
+
+    pub fn len(&self) -> io::Result<u64> {
+        let pos = self.tell()?;
+        let len = self.seek(SeekFrom::End(0))?;
+        self.seek(SeekFrom::Start(pos))?;
+        Ok(len)
+    }
+
+    pub fn sync_all(&self) -> io::Result<()> {
+        self.flush()
+    }
+
+    pub fn set_len(&mut self, size: u64) -> io::Result<()> {
+        match self.reopen.as_ref() {
+            Some(reopen) if reopen.is_writable() => (),
+            Some(_) => return Err(Error::from_raw_os_error(libc::EBADF)),
+            None => {
+                return Err(io::const_io_error!(
+                    io::ErrorKind::Unsupported,
+                    "the file was not opened by path",
+                ))
+            }
+        }
+        let pos = self.tell()?;
+        let len = self.seek(SeekFrom::End(0))?;
+        if size >= len {
+            let zeros = [0_u8; 4096];
+            let mut remaining = size - len;
+            while remaining > 0 {
+                let n = cmp::min(remaining, zeros.len() as u64) as usize;
+                self.write_all(&zeros[..n])?;
+                remaining -= n as u64;
+            }
+        } else {
+            // The reopen options are taken for the time of the copies, and
+            // put back whatever happens.
+            let reopen = self.reopen.take().unwrap();
+            let result = self.shrink(&reopen, size);
+            self.reopen = Some(reopen);
+            result?;
+        }
+        // The protected FS cannot seek past the end of a file.
+        self.seek(SeekFrom::Start(cmp::min(pos, size)))?;
+        Ok(())
+    }
+
+    fn shrink(&mut self, reopen: &Reopen, size: u64) -> io::Result<()> {
+        let tmp_path = suffixed(&reopen.path, TRUNCATE_SUFFIX)?;
+
+        self.flush()?;
+        self.seek(SeekFrom::Start(0))?;
+        copy_len(self, &reopen.open(&tmp_path, "w")?, size)?;
+
+        // The original is opened exclusively: it is closed by putting the
+        // copy in its place before it is opened again to be rewritten.
+        let tmp = reopen.open(&tmp_path, "r")?;
+        drop(mem::replace(&mut self.stream, tmp.stream));
+        copy_len(self, &reopen.open(&reopen.path, "w")?, size)?;
+        self.stream = reopen.open(&reopen.path, reopen.reopen_mode())?.stream;
+        remove_c(&tmp_path)
+    }
+
+    fn read_exact(&self, mut buf: &mut [u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf)? {
+                0 => {
+                    return Err(io::const_io_error!(
+                        io::ErrorKind::UnexpectedEof,
+                        "failed to fill whole buffer",
+                    ))
+                }
+                n => buf = &mut buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    fn write_all(&self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf) {
+                Ok(0) => {
+                    return Err(io::const_io_error!(
+                        io::ErrorKind::WriteZero,
+                        "failed to write whole buffer",
+                    ))
+                }
+                Ok(n) => buf = &buf[n..],
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
     }
 
     pub fn clear_cache(&self) -> io::Result<()> {
-        self.0.clear_cache().map_err(|err| match err {
+        self.stream.clear_cache().map_err(|err| match err {
             1 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_UNEXPECTED),
             2 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_INVALID_PARAMETER),
             3 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_OUT_OF_MEMORY),
@@ -239,8 +407,11 @@ impl SgxFile {
 }
 
 pub fn remove(path: &Path) -> io::Result<()> {
-    let path = cstr(path)?;
-    sgx_tprotected_fs::remove(&path).map_err(|err| match err {
+    remove_c(&cstr(path)?)
+}
+
+fn remove_c(path: &CStr) -> io::Result<()> {
+    sgx_tprotected_fs::remove(path).map_err(|err| match err {
         1 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_UNEXPECTED),
         2 => Error::from_raw_os_error(libc::ENOENT),
         3 => Error::from_sgx_error(sgx_status_t::SGX_ERROR_OUT_OF_MEMORY),
@@ -251,7 +422,11 @@ pub fn remove(path: &Path) -> io::Result<()> {
             Error::from_sgx_error(status)
         }
         _ => Error::from_raw_os_error(err),
-    })
+    })?;
+
+    #[cfg(not(minimal_tcb))]
+    dir::forget(path)?;
+    Ok(())
 }
 
 pub fn export_auto_key(path: &Path) -> io::Result<sgx_key_128bit_t> {
@@ -302,22 +477,213 @@ pub fn import_auto_key(path: &Path, key: &sgx_key_128bit_t) -> io::Result<()> {
     })
 }
 
+// A file is rekeyed through a journal: a protected file of this suffix,
+// under the new key, holding a header and a copy of the plaintext. The
+// original is rewritten from the journal once the header is marked
+// committed, and the journal removed after that. A rekey that finds a
+// committed journal was interrupted while rewriting and starts over from
+// the journal; one that finds an uncommitted journal discards it, as the
+// original was not touched yet.
+const REKEY_SUFFIX: &[u8] = b".rekey~";
+const REKEY_MAGIC: [u8; 8] = *b"SGXREKEY";
+const REKEY_COPYING: u8 = 0;
+const REKEY_COMMITTED: u8 = 1;
+const REKEY_HEADER_LEN: usize = 17;
+
+fn rekey_header(state: u8, len: u64) -> [u8; REKEY_HEADER_LEN] {
+    let mut header = [0_u8; REKEY_HEADER_LEN];
+    header[..8].copy_from_slice(&REKEY_MAGIC);
+    header[8] = state;
+    header[9..].copy_from_slice(&len.to_le_bytes());
+    header
+}
+
+// Reads the header of a journal, returning the length of the copy if the
+// journal is committed.
+fn read_rekey_header(journal: &SgxFile) -> io::Result<Option<u64>> {
+    let mut header = [0_u8; REKEY_HEADER_LEN];
+    journal.read_exact(&mut header)?;
+    if header[..8] != REKEY_MAGIC {
+        return Err(io::const_io_error!(
+            io::ErrorKind::InvalidData,
+            "not a rekey journal",
+        ));
+    }
+    let mut len = [0_u8; 8];
+    len.copy_from_slice(&header[9..]);
+    match header[8] {
+        REKEY_COMMITTED => Ok(Some(u64::from_le_bytes(len))),
+        _ => Ok(None),
+    }
+}
+
+pub fn rekey(
+    path: &Path,
+    old_key: &sgx_key_128bit_t,
+    new_key: &sgx_key_128bit_t,
+) -> io::Result<()> {
+    let path = cstr(path)?;
+    let journal_path = suffixed(&path, REKEY_SUFFIX)?;
+    let read = CString::new("r")?;
+    let write = CString::new("w+")?;
+
+    match SgxFile::open_c(&journal_path, &read, Some(new_key), false, None) {
+        Ok(journal) => {
+            if let Some(len) = read_rekey_header(&journal)? {
+                return finish_rekey(&path, &journal_path, journal, len, new_key);
+            }
+            drop(journal);
+            remove_c(&journal_path)?;
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => return Err(e),
+    }
+
+    let original = SgxFile::open_c(&path, &read, Some(old_key), false, None)?;
+    let len = original.len()?;
+    let journal = SgxFile::open_c(&journal_path, &write, Some(new_key), false, None)?;
+    journal.write_all(&rekey_header(REKEY_COPYING, len))?;
+    copy_len(&original, &journal, len)?;
+    journal.seek(SeekFrom::Start(0))?;
+    journal.write_all(&rekey_header(REKEY_COMMITTED, len))?;
+    journal.flush()?;
+    drop(original);
+    journal.seek(SeekFrom::Start(REKEY_HEADER_LEN as u64))?;
+    finish_rekey(&path, &journal_path, journal, len, new_key)
+}
+
+fn finish_rekey(
+    path: &CStr,
+    journal_path: &CStr,
+    journal: SgxFile,
+    len: u64,
+    new_key: &sgx_key_128bit_t,
+) -> io::Result<()> {
+    let write = CString::new("w")?;
+    let file = SgxFile::open_c(path, &write, Some(new_key), false, None)?;
+    copy_len(&journal, &file, len)?;
+    drop(file);
+    drop(journal);
+    remove_c(journal_path)
+}
+
+fn suffixed(path: &CStr, suffix: &[u8]) -> io::Result<CString> {
+    let mut suffixed = path.to_bytes().to_vec();
+    suffixed.extend_from_slice(suffix);
+    Ok(CString::new(suffixed)?)
+}
+
+// Copies `len` bytes from the cursor of `from` to that of `to`.
+fn copy_len(from: &SgxFile, to: &SgxFile, len: u64) -> io::Result<()> {
+    let mut buf = Vec::new();
+    buf.resize(cmp::min(len, COPY_CHUNK as u64) as usize, 0_u8);
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = cmp::min(remaining, buf.len() as u64) as usize;
+        let n = from.read(&mut buf[..n])?;
+        if n == 0 {
+            return Err(io::const_io_error!(
+                io::ErrorKind::UnexpectedEof,
+                "file shorter than expected",
+            ));
+        }
+        to.write_all(&buf[..n])?;
+        remaining -= n as u64;
+    }
+    to.flush()
+}
+
 fn cstr(path: &Path) -> io::Result<CString> {
     Ok(CString::new(path.as_os_str().as_bytes())?)
 }
 
 impl FromInner<SgxFileStream> for SgxFile {
     fn from_inner(stream: SgxFileStream) -> SgxFile {
-        SgxFile(stream)
+        SgxFile {
+            stream,
+            reopen: None,
+        }
+    }
+}
+
+// The protected FS encrypts and MACs the dirty nodes of a file one at a time
+// when it is flushed, under the lock of that file. Distinct files have
+// distinct locks, so their flushes can run on several threads.
+#[cfg(feature = "thread")]
+pub fn flush_all(files: &[&SgxFile], pool: &ThreadPool) -> io::Result<()> {
+    use crate::panic::{catch_unwind, AssertUnwindSafe};
+    use crate::sync::{Arc, PoisonError, SgxCondvar, SgxMutex};
+
+    // A file borrowed by a job: `flush_all` waits for all its jobs before it
+    // returns, and the protected FS locks each file it flushes.
+    struct Borrowed(*const SgxFile);
+    unsafe impl Send for Borrowed {}
+
+    impl Borrowed {
+        fn flush(&self) -> io::Result<()> {
+            unsafe { (*self.0).flush() }
+        }
+    }
+
+    struct Flushes {
+        results: SgxMutex<(usize, Vec<Option<io::Result<()>>>)>,
+        done: SgxCondvar,
+    }
+
+    let (first, rest) = match files.split_first() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+
+    let flushes = Arc::new(Flushes {
+        results: SgxMutex::new((rest.len(), (0..rest.len()).map(|_| None).collect())),
+        done: SgxCondvar::new(),
+    });
+    for (i, file) in rest.iter().enumerate() {
+        let file = Borrowed(*file as *const SgxFile);
+        let flushes = flushes.clone();
+        pool.execute(move || {
+            let result = catch_unwind(AssertUnwindSafe(|| file.flush())).unwrap_or_else(|_| {
+                Err(io::const_io_error!(
+                    io::ErrorKind::Other,
+                    "flush of protected file panicked",
+                ))
+            });
+            let mut results = flushes
+                .results
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            results.1[i] = Some(result);
+            results.0 -= 1;
+            if results.0 == 0 {
+                flushes.done.notify_all();
+            }
+        });
+    }
+
+    let first = first.flush();
+    let mut results = flushes
+        .results
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    while results.0 > 0 {
+        results = flushes
+            .done
+            .wait(results)
+            .unwrap_or_else(PoisonError::into_inner);
     }
+
+    first?;
+    results.1.drain(..).flatten().collect()
 }
 
+#[cfg(not(minimal_tcb))]
 pub fn copy(from: &Path, to: &Path) -> io::Result<u64> {
     use crate::sgxfs::SgxFile;
     use crate::sys_common::fs::NOT_FILE_ERROR;
 
     cfg_if! {
-        if #[cfg(feature = "untrusted_fs")] {
+        if #[cfg(tstd_untrusted_fs)] {
             use crate::fs;
         } else {
             use crate::untrusted::fs;
@@ -338,3 +704,428 @@ pub fn copy(from: &Path, to: &Path) -> io::Result<u64> {
     fs::set_permissions(to, perm)?;
     Ok(ret)
 }
+
+// Without the untrusted file system the source cannot be stat'ed and the
+// permissions of the destination are left as created.
+#[cfg(minimal_tcb)]
+pub fn copy(from: &Path, to: &Path) -> io::Result<u64> {
+    use crate::sgxfs::SgxFile;
+
+    let mut reader = SgxFile::open(from)?;
+    let mut writer = SgxFile::create(to)?;
+    io::copy::generic_copy(&mut reader, &mut writer)
+}
+
+// Sealed directories keep a manifest: a protected file, under the auto key,
+// naming the protected files of the directory. The host cannot forge or edit
+// it, so a listing checked against it cannot silently lose or gain files.
+// The host can still roll the manifest back to an older version along with
+// the files, or move it to another directory.
+#[cfg(not(minimal_tcb))]
+pub use self::dir::{create_dir, exists, read_dir, rename, DirEntry, ReadDir};
+
+#[cfg(not(minimal_tcb))]
+mod dir {
+    use super::{cstr, remove_c, SgxFile};
+    use crate::collections::{btree_set, BTreeSet};
+    use crate::ffi::{CStr, CString, OsStr};
+    use crate::fs;
+    use crate::io;
+    use crate::os::unix::prelude::*;
+    use crate::path::{Path, PathBuf};
+    use crate::sync::{PoisonError, SgxMutex};
+    use crate::vec::Vec;
+    use sgx_types::sgx_key_128bit_t;
+
+    const MANIFEST_NAME: &[u8] = b".sgxfs_manifest";
+    const MANIFEST_MAGIC: [u8; 8] = *b"SGXFSDIR";
+    const MANIFEST_VERSION: u8 = 1;
+
+    // Serializes the updates of manifests: the protected FS opens files
+    // exclusively, and each update rewrites the whole manifest.
+    static MANIFEST_LOCK: SgxMutex<()> = SgxMutex::new(());
+
+    type Manifest = BTreeSet<Vec<u8>>;
+
+    pub struct ReadDir {
+        dir: PathBuf,
+        names: btree_set::IntoIter<Vec<u8>>,
+    }
+
+    pub struct DirEntry {
+        dir: PathBuf,
+        name: Vec<u8>,
+    }
+
+    impl Iterator for ReadDir {
+        type Item = DirEntry;
+
+        fn next(&mut self) -> Option<DirEntry> {
+            self.names.next().map(|name| DirEntry {
+                dir: self.dir.clone(),
+                name,
+            })
+        }
+    }
+
+    impl DirEntry {
+        pub fn path(&self) -> PathBuf {
+            self.dir.join(self.file_name())
+        }
+
+        pub fn file_name(&self) -> &OsStr {
+            OsStr::from_bytes(&self.name)
+        }
+    }
+
+    pub fn create_dir(path: &Path) -> io::Result<()> {
+        fs::create_dir(path)?;
+        let _guard = MANIFEST_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        store(path, &Manifest::new())
+    }
+
+    pub fn read_dir(path: &Path) -> io::Result<ReadDir> {
+        let manifest = {
+            let _guard = MANIFEST_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+            load(path)?.ok_or_else(not_sealed)?
+        };
+
+        let mut listed = BTreeSet::new();
+        for entry in fs::read_dir(path)? {
+            listed.insert(entry?.file_name().as_bytes().to_vec());
+        }
+        if !manifest.is_subset(&listed) {
+            return Err(missing());
+        }
+
+        Ok(ReadDir {
+            dir: path.to_path_buf(),
+            names: manifest.into_iter(),
+        })
+    }
+
+    // A file on the host that the manifest does not name is not a protected
+    // file of the directory, and does not exist as far as it is concerned.
+    pub fn exists(path: &Path) -> io::Result<bool> {
+        let (dir, name) = split(path.as_os_str().as_bytes())?;
+        let manifest = {
+            let _guard = MANIFEST_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+            load(&dir)?
+        };
+
+        match manifest {
+            Some(manifest) if manifest.contains(name) => {
+                if host_exists(path)? {
+                    Ok(true)
+                } else {
+                    Err(missing())
+                }
+            }
+            Some(_) => Ok(false),
+            None => host_exists(path),
+        }
+    }
+
+    // The protected FS binds the contents of a file to its name, so a file
+    // is renamed by copying it through the enclave and removing the source.
+    pub fn rename(from: &Path, to: &Path, key: Option<&sgx_key_128bit_t>) -> io::Result<()> {
+        let from = cstr(from)?;
+        let to = cstr(to)?;
+        if from == to {
+            return Ok(());
+        }
+
+        let auto = key.is_none();
+        let reader = SgxFile::open_c(&from, &CString::new("r")?, key, auto, None)?;
+        let len = reader.len()?;
+        let writer = SgxFile::open_c(&to, &CString::new("w")?, key, auto, None)?;
+        let copied = super::copy_len(&reader, &writer, len);
+        drop(reader);
+        drop(writer);
+        if let Err(e) = copied {
+            let _ = remove_c(&to);
+            return Err(e);
+        }
+        remove_c(&from)
+    }
+
+    // Adds the file to the manifest of its directory, if it has one.
+    pub(super) fn record(path: &CStr) -> io::Result<()> {
+        update(path, |manifest, name| manifest.insert(name.to_vec()))
+    }
+
+    // Removes the file from the manifest of its directory, if it has one.
+    pub(super) fn forget(path: &CStr) -> io::Result<()> {
+        update(path, |manifest, name| manifest.remove(name))
+    }
+
+    fn update<F>(path: &CStr, f: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut Manifest, &[u8]) -> bool,
+    {
+        let (dir, name) = split(path.to_bytes())?;
+        if name == MANIFEST_NAME {
+            return Ok(());
+        }
+
+        let _guard = MANIFEST_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(mut manifest) = load(&dir)? {
+            if f(&mut manifest, name) {
+                return store(&dir, &manifest);
+            }
+        }
+        Ok(())
+    }
+
+    fn split(path: &[u8]) -> io::Result<(PathBuf, &[u8])> {
+        let path = Path::new(OsStr::from_bytes(path));
+        let name = path.file_name().ok_or(io::const_io_error!(
+            io::ErrorKind::InvalidInput,
+            "path does not name a file",
+        ))?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        Ok((dir, name.as_bytes()))
+    }
+
+    fn manifest_path(dir: &Path) -> io::Result<CString> {
+        cstr(&dir.join(OsStr::from_bytes(MANIFEST_NAME)))
+    }
+
+    fn load(dir: &Path) -> io::Result<Option<Manifest>> {
+        let path = manifest_path(dir)?;
+        if !host_exists(Path::new(OsStr::from_bytes(path.to_bytes())))? {
+            return Ok(None);
+        }
+
+        let file = SgxFile::open_c(&path, &CString::new("r")?, None, true, None)?;
+        let mut buf = Vec::new();
+        buf.resize(file.len()? as usize, 0_u8);
+        file.read_exact(&mut buf)?;
+        decode(&buf).map(Some).ok_or_else(corrupt)
+    }
+
+    fn store(dir: &Path, manifest: &Manifest) -> io::Result<()> {
+        let path = manifest_path(dir)?;
+        let file = SgxFile::open_c(&path, &CString::new("w")?, None, true, None)?;
+        file.write_all(&encode(manifest)?)?;
+        file.flush()
+    }
+
+    // The magic, the version, the number of names as a u32, then each name
+    // as a u16 length and its bytes, all little-endian.
+    fn encode(manifest: &Manifest) -> io::Result<Vec<u8>> {
+        let too_long = || io::const_io_error!(io::ErrorKind::InvalidInput, "manifest too large");
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&MANIFEST_MAGIC);
+        buf.push(MANIFEST_VERSION);
+        let count = u32::try_from(manifest.len()).map_err(|_| too_long())?;
+        buf.extend_from_slice(&count.to_le_bytes());
+        for name in manifest.iter() {
+            let len = u16::try_from(name.len()).map_err(|_| too_long())?;
+            buf.extend_from_slice(&len.to_le_bytes());
+            buf.extend_from_slice(name);
+        }
+        Ok(buf)
+    }
+
+    fn decode(buf: &[u8]) -> Option<Manifest> {
+        fn take<'a>(buf: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+            if buf.len() < n {
+                return None;
+            }
+            let (head, tail) = buf.split_at(n);
+            *buf = tail;
+            Some(head)
+        }
+
+        let mut buf = buf;
+        if take(&mut buf, MANIFEST_MAGIC.len())? != MANIFEST_MAGIC
+            || take(&mut buf, 1)? != [MANIFEST_VERSION]
+        {
+            return None;
+        }
+        let count = u32::from_le_bytes(take(&mut buf, 4)?.try_into().ok()?);
+        let mut manifest = Manifest::new();
+        for _ in 0..count {
+            let len = u16::from_le_bytes(take(&mut buf, 2)?.try_into().ok()?);
+            manifest.insert(take(&mut buf, len as usize)?.to_vec());
+        }
+        if buf.is_empty() {
+            Some(manifest)
+        } else {
+            None
+        }
+    }
+
+    fn host_exists(path: &Path) -> io::Result<bool> {
+        match fs::metadata(path) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn not_sealed() -> io::Error {
+        io::const_io_error!(io::ErrorKind::NotFound, "not a sealed directory")
+    }
+
+    fn missing() -> io::Error {
+        io::const_io_error!(
+            io::ErrorKind::InvalidData,
+            "sealed file missing from directory",
+        )
+    }
+
+    fn corrupt() -> io::Error {
+        io::const_io_error!(
+            io::ErrorKind::InvalidData,
+            "corrupt sealed directory manifest",
+        )
+    }
+}
+
+pub use self::image::{SgxFileImage, BLOCK_SIZE as IMAGE_BLOCK_SIZE};
+
+// A read-only image of a protected file, read through a cache of decrypted
+// blocks shared by all threads. A block is filled once, by whichever reader
+// first misses it, and is never replaced until the image is dropped, so a
+// hit is a single atomic load. Only misses take the lock of the file, which
+// has a single cursor.
+mod image {
+    use super::{cstr, SgxFile};
+    use crate::boxed::Box;
+    use crate::cmp;
+    use crate::ffi::CString;
+    use crate::io::{self, SeekFrom};
+    use crate::path::Path;
+    use crate::ptr;
+    use crate::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+    use crate::sync::{PoisonError, SgxMutex};
+    use sgx_types::sgx_key_128bit_t;
+
+    // Sixteen nodes of the protected FS.
+    pub const BLOCK_SIZE: usize = 64 * 1024;
+
+    pub struct SgxFileImage {
+        file: SgxMutex<SgxFile>,
+        len: u64,
+        blocks: Box<[AtomicPtr<Block>]>,
+        cached: AtomicUsize,
+        max_cached: usize,
+    }
+
+    struct Block {
+        data: Box<[u8]>,
+    }
+
+    // The file is only used under its lock, and the blocks are immutable once
+    // published.
+    unsafe impl Send for SgxFileImage {}
+    unsafe impl Sync for SgxFileImage {}
+
+    impl SgxFileImage {
+        pub fn open(
+            path: &Path,
+            key: Option<&sgx_key_128bit_t>,
+            cache_size: usize,
+        ) -> io::Result<SgxFileImage> {
+            let path = cstr(path)?;
+            let file = SgxFile::open_c(&path, &CString::new("r")?, key, key.is_none(), None)?;
+            let len = file.len()?;
+            let count = usize::try_from((len + BLOCK_SIZE as u64 - 1) / BLOCK_SIZE as u64)
+                .map_err(|_| io::const_io_error!(io::ErrorKind::InvalidInput, "file too large"))?;
+
+            Ok(SgxFileImage {
+                file: SgxMutex::new(file),
+                len,
+                blocks: (0..count)
+                    .map(|_| AtomicPtr::new(ptr::null_mut()))
+                    .collect(),
+                cached: AtomicUsize::new(0),
+                max_cached: cache_size / BLOCK_SIZE,
+            })
+        }
+
+        pub fn len(&self) -> u64 {
+            self.len
+        }
+
+        pub fn cached_size(&self) -> usize {
+            cmp::min(self.cached.load(Ordering::Relaxed), self.max_cached) * BLOCK_SIZE
+        }
+
+        pub fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+            let mut pos = offset;
+            let mut done = 0;
+            while done < buf.len() && pos < self.len {
+                let index = (pos / BLOCK_SIZE as u64) as usize;
+                let start = (pos % BLOCK_SIZE as u64) as usize;
+                let n = cmp::min(buf.len() - done, self.block_len(index) - start);
+                let dst = &mut buf[done..done + n];
+                match self.block(index)? {
+                    Some(block) => dst.copy_from_slice(&block.data[start..start + n]),
+                    None => self.read_file(pos, dst)?,
+                }
+                done += n;
+                pos += n as u64;
+            }
+            Ok(done)
+        }
+
+        fn block_len(&self, index: usize) -> usize {
+            let start = index as u64 * BLOCK_SIZE as u64;
+            cmp::min(self.len - start, BLOCK_SIZE as u64) as usize
+        }
+
+        // Returns the cached block, filling it if the cache has room.
+        fn block(&self, index: usize) -> io::Result<Option<&Block>> {
+            let slot = &self.blocks[index];
+            let block = slot.load(Ordering::Acquire);
+            if !block.is_null() {
+                return Ok(Some(unsafe { &*block }));
+            }
+
+            if self.cached.fetch_add(1, Ordering::Relaxed) >= self.max_cached {
+                self.cached.fetch_sub(1, Ordering::Relaxed);
+                return Ok(None);
+            }
+            let mut data = vec![0_u8; self.block_len(index)].into_boxed_slice();
+            if let Err(e) = self.read_file(index as u64 * BLOCK_SIZE as u64, &mut data) {
+                self.cached.fetch_sub(1, Ordering::Relaxed);
+                return Err(e);
+            }
+
+            let new = Box::into_raw(Box::new(Block { data }));
+            match slot.compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => Ok(Some(unsafe { &*new })),
+                Err(current) => {
+                    // Another reader filled the block first.
+                    self.cached.fetch_sub(1, Ordering::Relaxed);
+                    drop(unsafe { Box::from_raw(new) });
+                    Ok(Some(unsafe { &*current }))
+                }
+            }
+        }
+
+        fn read_file(&self, pos: u64, buf: &mut [u8]) -> io::Result<()> {
+            let file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+            file.seek(SeekFrom::Start(pos))?;
+            file.read_exact(buf)
+        }
+    }
+
+    impl Drop for SgxFileImage {
+        fn drop(&mut self) {
+            for slot in self.blocks.iter_mut() {
+                let block = *slot.get_mut();
+                if !block.is_null() {
+                    drop(unsafe { Box::from_raw(block) });
+                }
+            }
+        }
+    }
+}
@@ -137,10 +137,11 @@ impl Drop for Thread {
 }
 
 pub fn available_parallelism() -> io::Result<NonZeroUsize> {
-    let cpus = enclave::rsgx_get_cpu_core_num();
-    NonZeroUsize::new(cpus as usize).ok_or_else(|| io::const_io_error!(
+    // One TCS is taken by the calling thread.
+    let tcs = enclave::rsgx_get_tcs_max_num().saturating_sub(1);
+    NonZeroUsize::new(tcs as usize).ok_or_else(|| io::const_io_error!(
         io::ErrorKind::NotFound,
-        "The number of hardware threads is not known for the target platform",
+        "The enclave has no TCS to spare for another thread",
     ))
 }
 
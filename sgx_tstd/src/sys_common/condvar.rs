@@ -15,6 +15,8 @@
 // specific language governing permissions and limitations
 // under the License..
 
+#[cfg(feature = "lock_debug")]
+use crate::sync::deadlock::{self, LockKind};
 use crate::sys::locks as imp;
 use crate::sys_common::mutex::MovableMutex;
 use crate::time::Duration;
@@ -60,8 +62,15 @@ impl Condvar {
     #[inline]
     pub unsafe fn wait(&self, mutex: &MovableMutex) {
         self.check.verify(mutex);
+        #[cfg(feature = "lock_debug")]
+        {
+            deadlock::released(mutex.addr(), true);
+            deadlock::wait(self as *const Self as usize, LockKind::Condvar, false, false);
+        }
         let r = self.inner.wait(mutex.raw());
         debug_assert_eq!(r, Ok(()));
+        #[cfg(feature = "lock_debug")]
+        deadlock::acquired(mutex.addr(), LockKind::Mutex, true);
     }
 
     /// Waits for a signal on the specified mutex with a timeout duration
@@ -73,8 +82,12 @@ impl Condvar {
     #[inline]
     pub unsafe fn wait_timeout(&self, mutex: &MovableMutex, dur: Duration) -> bool {
         self.check.verify(mutex);
+        #[cfg(feature = "lock_debug")]
+        deadlock::released(mutex.addr(), true);
         let r = self.inner.wait_timeout(mutex.raw(), dur);
         debug_assert!(r == Err(libc::ETIMEDOUT) || r == Ok(()));
+        #[cfg(feature = "lock_debug")]
+        deadlock::acquired(mutex.addr(), LockKind::Mutex, true);
         r == Ok(())
     }
 }
@@ -38,15 +38,16 @@ pub mod at_exit_imp;
 #[cfg(feature = "backtrace")]
 pub mod backtrace;
 pub mod condvar;
+#[cfg(not(minimal_tcb))]
 pub mod fs;
 #[cfg(feature = "backtrace")]
 pub mod gnu;
 pub mod io;
-pub mod lazy_box;
+pub use sgx_tsync::lazy_box;
 pub mod memchr;
 pub mod mutex;
 pub mod once;
-#[cfg(feature = "net")]
+#[cfg(tstd_net)]
 pub mod net;
 pub mod remutex;
 pub mod rwlock;
@@ -27,7 +27,10 @@
 //! Synchronization library supports, as well as the OCALLs that each API function needs.
 //!
 
+#[cfg(feature = "lock_debug")]
+use crate::sync::deadlock::{self, LockKind};
 use crate::sys::locks as imp;
+use crate::time::Duration;
 
 use sgx_libc as libc;
 
@@ -104,8 +107,12 @@ impl MovableMutex {
     /// Locks the mutex blocking the current thread until it is available.
     #[inline]
     pub fn raw_lock(&self) {
+        #[cfg(feature = "lock_debug")]
+        deadlock::wait(self.addr(), LockKind::Mutex, true, true);
         let r = unsafe { self.0.lock() };
         debug_assert_eq!(r, Ok(()));
+        #[cfg(feature = "lock_debug")]
+        deadlock::acquired(self.addr(), LockKind::Mutex, true);
     }
 
     /// Attempts to lock the mutex without blocking, returning whether it was
@@ -114,6 +121,10 @@ impl MovableMutex {
     pub fn try_lock(&self) -> bool {
         let r = unsafe { self.0.try_lock() };
         debug_assert!(r == Err(libc::EBUSY) || r == Ok(()));
+        #[cfg(feature = "lock_debug")]
+        if r == Ok(()) {
+            deadlock::acquired(self.addr(), LockKind::Mutex, true);
+        }
         r == Ok(())
     }
 
@@ -123,7 +134,100 @@ impl MovableMutex {
     /// mutex.
     #[inline]
     pub unsafe fn raw_unlock(&self) {
+        #[cfg(feature = "lock_debug")]
+        deadlock::released(self.addr(), true);
         let r = self.0.unlock();
         debug_assert_eq!(r, Ok(()));
     }
+
+    #[cfg(feature = "lock_debug")]
+    #[inline]
+    pub(super) fn addr(&self) -> usize {
+        self as *const Self as usize
+    }
+}
+
+/// An SGX-based mutual exclusion lock that spins before sleeping.
+///
+/// This mutex does not implement poisoning.
+pub struct MovableAdaptiveMutex(imp::MovableAdaptiveMutex);
+
+unsafe impl Sync for MovableAdaptiveMutex {}
+
+impl MovableAdaptiveMutex {
+    /// Creates a new mutex.
+    #[inline]
+    pub const fn new() -> MovableAdaptiveMutex {
+        MovableAdaptiveMutex(imp::MovableAdaptiveMutex::new())
+    }
+
+    /// Locks the mutex blocking the current thread until it is available.
+    #[inline]
+    pub fn raw_lock(&self) {
+        #[cfg(feature = "lock_debug")]
+        deadlock::wait(self.addr(), LockKind::Mutex, true, true);
+        let r = unsafe { self.0.lock() };
+        debug_assert_eq!(r, Ok(()));
+        #[cfg(feature = "lock_debug")]
+        deadlock::acquired(self.addr(), LockKind::Mutex, true);
+    }
+
+    /// Locks the mutex blocking the current thread for at most `dur`,
+    /// returning whether it was acquired.
+    #[inline]
+    pub fn raw_lock_timeout(&self, dur: Duration) -> bool {
+        let r = unsafe { self.0.lock_timeout(dur) };
+        debug_assert!(r == Err(libc::ETIMEDOUT) || r == Ok(()));
+        #[cfg(feature = "lock_debug")]
+        if r == Ok(()) {
+            deadlock::acquired(self.addr(), LockKind::Mutex, true);
+        }
+        r == Ok(())
+    }
+
+    /// Attempts to lock the mutex without blocking, returning whether it was
+    /// successfully acquired or not.
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        let r = unsafe { self.0.try_lock() };
+        debug_assert!(r == Err(libc::EBUSY) || r == Ok(()));
+        #[cfg(feature = "lock_debug")]
+        if r == Ok(()) {
+            deadlock::acquired(self.addr(), LockKind::Mutex, true);
+        }
+        r == Ok(())
+    }
+
+    /// Unlocks the mutex.
+    ///
+    /// Behavior is undefined if the current thread does not actually hold the
+    /// mutex.
+    #[inline]
+    pub unsafe fn raw_unlock(&self) {
+        #[cfg(feature = "lock_debug")]
+        deadlock::released(self.addr(), true);
+        let r = self.0.unlock();
+        debug_assert_eq!(r, Ok(()));
+    }
+
+    #[inline]
+    pub fn set_spin_limit(&self, limit: u32) {
+        self.0.set_spin_limit(limit)
+    }
+
+    #[inline]
+    pub fn stats(&self) -> imp::MutexStats {
+        self.0.stats()
+    }
+
+    #[inline]
+    pub fn reset_stats(&self) {
+        self.0.reset_stats()
+    }
+
+    #[cfg(feature = "lock_debug")]
+    #[inline]
+    fn addr(&self) -> usize {
+        self as *const Self as usize
+    }
 }
@@ -24,6 +24,7 @@ use crate::io::{self, ErrorKind, IoSlice, IoSliceMut};
 use crate::mem;
 use crate::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
 use crate::ptr;
+use crate::quota;
 use crate::sys::common::small_c_string::run_with_cstr;
 use crate::sys::net::{cvt, cvt_gai, cvt_r, init, wrlen_t, Socket};
 use crate::sys_common::{AsInner, FromInner, IntoInner};
@@ -216,7 +217,9 @@ impl TcpStream {
 
         let sock = Socket::new_socket_addr_type(addr, c::SOCK_STREAM)?;
         let (addr, len) = addr.into_inner();
-        cvt_r(|| unsafe { c::connect(sock.as_raw(), addr.as_ptr(), len) })?;
+        quota::connect(sock.as_raw(), || {
+            cvt_r(|| unsafe { c::connect(sock.as_raw(), addr.as_ptr(), len) }).map(drop)
+        })?;
         Ok(TcpStream { inner: sock })
     }
 
@@ -226,19 +229,21 @@ impl TcpStream {
         init();
 
         let (addr, len) = addr.into_inner();
-        cvt_r(|| unsafe { c::connect(self.inner.as_raw(), addr.as_ptr(), len) }).map(drop)
+        quota::connect(self.inner.as_raw(), || {
+            cvt_r(|| unsafe { c::connect(self.inner.as_raw(), addr.as_ptr(), len) }).map(drop)
+        })
     }
 
     pub fn connect_timeout(addr: &SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
         init();
 
         let sock = Socket::new_socket_addr_type(addr, c::SOCK_STREAM)?;
-        sock.connect_timeout(addr, timeout)?;
+        quota::connect(sock.as_raw(), || sock.connect_timeout(addr, timeout))?;
         Ok(TcpStream { inner: sock })
     }
 
     pub fn connect_socket_timeout(&self, addr: &SocketAddr, timeout: Duration) -> io::Result<()> {
-        self.inner.connect_timeout(addr, timeout)
+        quota::connect(self.inner.as_raw(), || self.inner.connect_timeout(addr, timeout))
     }
 
     pub fn socket(&self) -> &Socket {
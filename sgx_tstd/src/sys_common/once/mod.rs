@@ -39,6 +39,15 @@
 //
 // All in all, this is instead implemented with atomics and lock-free
 // operations! Whee!
+//
+// Threads that lose the race queue themselves on the `Once` and park. In the
+// enclave, parking waits on the thread's TCS event (see
+// `sys_common::thread_parker`), the same primitive the mutex and condvar in
+// `sys::locks` block on. The wait and the wakeup are OCALLs, so the host
+// controls when a parked thread resumes: it can delay a wakeup, withhold it,
+// or deliver a spurious one. That only affects liveness. The state of the
+// `Once` and its queue live in enclave memory, and a woken thread re-checks
+// that state before returning, parking again if it is not done.
 
 //mod futex;
 mod generic;
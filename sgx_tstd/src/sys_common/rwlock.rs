@@ -15,7 +15,10 @@
 // specific language governing permissions and limitations
 // under the License..
 
+#[cfg(feature = "lock_debug")]
+use crate::sync::deadlock::{self, LockKind};
 use crate::sys::locks as imp;
+use crate::time::Duration;
 
 use sgx_libc as libc;
 
@@ -96,12 +99,30 @@ impl MovableRwLock {
         Self(imp::MovableRwLock::new())
     }
 
+    /// Returns the policy that decides whether waiting readers or writers
+    /// enter first.
+    #[inline]
+    pub fn policy(&self) -> imp::RwLockPolicy {
+        unsafe { self.0.policy() }
+    }
+
+    /// Changes the policy that decides whether waiting readers or writers
+    /// enter first.
+    #[inline]
+    pub fn set_policy(&self, policy: imp::RwLockPolicy) {
+        unsafe { self.0.set_policy(policy) }
+    }
+
     /// Acquires shared access to the underlying lock, blocking the current
     /// thread to do so.
     #[inline]
     pub fn read(&self) {
+        #[cfg(feature = "lock_debug")]
+        deadlock::wait(self.addr(), LockKind::RwLock, false, true);
         let r = unsafe { self.0.read() };
         debug_assert_eq!(r, Ok(()));
+        #[cfg(feature = "lock_debug")]
+        deadlock::acquired(self.addr(), LockKind::RwLock, false);
     }
 
     /// Attempts to acquire shared access to this lock, returning whether it
@@ -112,6 +133,23 @@ impl MovableRwLock {
     pub fn try_read(&self) -> bool {
         let r = unsafe { self.0.try_read() };
         debug_assert!(r == Err(libc::EBUSY) || r == Ok(()));
+        #[cfg(feature = "lock_debug")]
+        if r == Ok(()) {
+            deadlock::acquired(self.addr(), LockKind::RwLock, false);
+        }
+        r == Ok(())
+    }
+
+    /// Acquires shared access to the underlying lock, blocking the current
+    /// thread for at most about `dur`, returning whether it succeeded or not.
+    #[inline]
+    pub fn read_timeout(&self, dur: Duration) -> bool {
+        let r = unsafe { self.0.read_timeout(dur) };
+        debug_assert!(r == Err(libc::ETIMEDOUT) || r == Ok(()));
+        #[cfg(feature = "lock_debug")]
+        if r == Ok(()) {
+            deadlock::acquired(self.addr(), LockKind::RwLock, false);
+        }
         r == Ok(())
     }
 
@@ -119,8 +157,25 @@ impl MovableRwLock {
     /// to do so.
     #[inline]
     pub fn write(&self) {
+        #[cfg(feature = "lock_debug")]
+        deadlock::wait(self.addr(), LockKind::RwLock, true, true);
         let r = unsafe { self.0.write() };
         debug_assert_eq!(r, Ok(()));
+        #[cfg(feature = "lock_debug")]
+        deadlock::acquired(self.addr(), LockKind::RwLock, true);
+    }
+
+    /// Acquires write access to the underlying lock, blocking the current
+    /// thread for at most about `dur`, returning whether it succeeded or not.
+    #[inline]
+    pub fn write_timeout(&self, dur: Duration) -> bool {
+        let r = unsafe { self.0.write_timeout(dur) };
+        debug_assert!(r == Err(libc::ETIMEDOUT) || r == Ok(()));
+        #[cfg(feature = "lock_debug")]
+        if r == Ok(()) {
+            deadlock::acquired(self.addr(), LockKind::RwLock, true);
+        }
+        r == Ok(())
     }
 
     /// Attempts to acquire exclusive access to this lock, returning whether it
@@ -131,6 +186,10 @@ impl MovableRwLock {
     pub fn try_write(&self) -> bool {
         let r = unsafe { self.0.try_write() };
         debug_assert!(r == Err(libc::EBUSY) || r == Ok(()));
+        #[cfg(feature = "lock_debug")]
+        if r == Ok(()) {
+            deadlock::acquired(self.addr(), LockKind::RwLock, true);
+        }
         r == Ok(())
     }
 
@@ -139,6 +198,8 @@ impl MovableRwLock {
     /// Behavior is undefined if the current thread does not have shared access.
     #[inline]
     pub unsafe fn read_unlock(&self) {
+        #[cfg(feature = "lock_debug")]
+        deadlock::released(self.addr(), false);
         let r = self.0.read_unlock();
         debug_assert_eq!(r, Ok(()));
     }
@@ -149,7 +210,71 @@ impl MovableRwLock {
     /// exclusive access.
     #[inline]
     pub unsafe fn write_unlock(&self) {
+        #[cfg(feature = "lock_debug")]
+        deadlock::released(self.addr(), true);
         let r = self.0.write_unlock();
         debug_assert_eq!(r, Ok(()));
     }
+
+    /// Turns exclusive access into shared access.
+    ///
+    /// Behavior is undefined if the current thread does not have exclusive
+    /// access.
+    #[inline]
+    pub unsafe fn downgrade(&self) {
+        #[cfg(feature = "lock_debug")]
+        {
+            deadlock::released(self.addr(), true);
+            deadlock::acquired(self.addr(), LockKind::RwLock, false);
+        }
+        let r = self.0.downgrade();
+        debug_assert_eq!(r, Ok(()));
+    }
+
+    /// Attempts to turn shared access into exclusive access without
+    /// blocking, returning whether it succeeded.
+    ///
+    /// Behavior is undefined if the current thread does not have shared
+    /// access.
+    #[inline]
+    pub unsafe fn try_upgrade(&self) -> bool {
+        let r = self.0.try_upgrade();
+        debug_assert!(r == Err(libc::EBUSY) || r == Ok(()));
+        #[cfg(feature = "lock_debug")]
+        if r == Ok(()) {
+            deadlock::released(self.addr(), false);
+            deadlock::acquired(self.addr(), LockKind::RwLock, true);
+        }
+        r == Ok(())
+    }
+
+    /// Turns shared access into exclusive access, blocking until the other
+    /// readers have left. Returns `false`, keeping shared access, if another
+    /// thread is upgrading.
+    ///
+    /// Behavior is undefined if the current thread does not have shared
+    /// access.
+    #[inline]
+    pub unsafe fn upgrade(&self) -> bool {
+        // A second upgrader fails at once rather than wait, so its wait is
+        // not checked for a cycle.
+        #[cfg(feature = "lock_debug")]
+        deadlock::wait(self.addr(), LockKind::RwLock, true, false);
+        let r = self.0.upgrade();
+        debug_assert!(r == Err(libc::EDEADLK) || r == Ok(()));
+        #[cfg(feature = "lock_debug")]
+        if r == Ok(()) {
+            deadlock::released(self.addr(), false);
+            deadlock::acquired(self.addr(), LockKind::RwLock, true);
+        } else {
+            deadlock::cancel_wait();
+        }
+        r == Ok(())
+    }
+
+    #[cfg(feature = "lock_debug")]
+    #[inline]
+    fn addr(&self) -> usize {
+        self as *const Self as usize
+    }
 }
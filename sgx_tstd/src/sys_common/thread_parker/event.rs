@@ -0,0 +1,114 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Parker implementation based on the untrusted event of the thread's TCS.
+//!
+//! This follows the SGX parker of std: the state holds either `EMPTY`,
+//! `NOTIFIED`, or the TCS of the parked thread, which `unpark` wakes with
+//! `thread_set_event`. A TCS is page aligned, so it is never mistaken for
+//! one of the two markers. Unlike the parker built on a mutex and a condvar,
+//! a park takes a single OCALL and an unpark at most one.
+//!
+//! The event of a TCS is shared with the other blocking primitives of the
+//! thread, and a wakeup sent to a thread that has already returned from
+//! `park` stays pending until its next wait. Every wait therefore re-checks
+//! its own condition after it wakes up.
+
+use crate::pin::Pin;
+use crate::sync::atomic::AtomicUsize;
+use crate::sync::atomic::Ordering::{Acquire, Release};
+use crate::sys::locks::mutex::{thread_set_event, thread_wait_event};
+use crate::time::Duration;
+
+use sgx_trts::enclave::SgxThreadData;
+
+const EMPTY: usize = 0;
+const NOTIFIED: usize = 1;
+
+pub struct Parker {
+    state: AtomicUsize,
+}
+
+impl Parker {
+    /// Construct the parker in-place, as the other parker implementations
+    /// require.
+    #[allow(clippy::new_ret_no_self)]
+    pub unsafe fn new(parker: *mut Parker) {
+        parker.write(Parker {
+            state: AtomicUsize::new(EMPTY),
+        });
+    }
+
+    // Should only be called from the thread that owns this parker.
+    pub unsafe fn park(self: Pin<&Self>) {
+        let tcs = SgxThreadData::current().get_tcs();
+        if !self.register(tcs) {
+            return;
+        }
+        loop {
+            thread_wait_event(tcs, Duration::new(u64::MAX, 1_000_000_000 - 1));
+            if self
+                .state
+                .compare_exchange(NOTIFIED, EMPTY, Acquire, Acquire)
+                .is_ok()
+            {
+                return;
+            }
+            // Woken by another primitive of this thread, or spuriously.
+        }
+    }
+
+    // Should only be called from the thread that owns this parker.
+    pub unsafe fn park_timeout(self: Pin<&Self>, dur: Duration) {
+        let tcs = SgxThreadData::current().get_tcs();
+        if !self.register(tcs) {
+            return;
+        }
+        thread_wait_event(tcs, dur);
+        // Either consume the notification or withdraw the TCS. A wakeup may
+        // be spurious, which `park_timeout` allows.
+        match self.state.swap(EMPTY, Acquire) {
+            NOTIFIED => {}
+            state => debug_assert_eq!(state, tcs, "inconsistent park_timeout state"),
+        }
+    }
+
+    pub fn unpark(self: Pin<&Self>) {
+        match self.state.swap(NOTIFIED, Release) {
+            EMPTY | NOTIFIED => {}
+            tcs => unsafe {
+                thread_set_event(tcs);
+            },
+        }
+    }
+
+    // Publishes the TCS of the parking thread, or consumes a pending
+    // notification. Returns whether the thread has to wait.
+    fn register(&self, tcs: usize) -> bool {
+        match self.state.compare_exchange(EMPTY, tcs, Acquire, Acquire) {
+            Ok(_) => true,
+            Err(NOTIFIED) => {
+                // Read again with acquire ordering, so that the writes made by
+                // the latest `unpark` are visible.
+                let old = self.state.swap(EMPTY, Acquire);
+                debug_assert_eq!(old, NOTIFIED, "park state changed unexpectedly");
+                false
+            }
+            Err(_) => panic!("inconsistent park state"),
+        }
+    }
+}
@@ -0,0 +1,237 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Taint tracking for data supplied by the host.
+//!
+//! Everything that crosses into the enclave from the host is wrapped in
+//! [`Untrusted`], whose contents cannot be read directly. The only way to
+//! get at them is [`Untrusted::validate`], which hands the raw value to the
+//! [`Validate`] implementation of the type it is parsed into. Validators are
+//! registered per type, by implementing [`Validate`], so checking host data
+//! and turning it into a well-formed value is one step, and code that takes
+//! the parsed type never sees unchecked input.
+//!
+//! Host data enters through the ECALL and OCALL buffers copied in with
+//! [`Untrusted::copy_from_host`], the streams read with
+//! [`ReadUntrusted`], and the environment read with [`var_os`]. Values that
+//! arrive by other paths are tagged with [`Untrusted::new`].
+//!
+//! Like the tags of [`classify`](crate::classify), `Untrusted` guards against
+//! mistakes, not against code that means to bypass it: a validator that
+//! accepts everything unwraps the value.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::taint::{Untrusted, Validate};
+//!
+//! struct Port(u16);
+//!
+//! impl Validate<Vec<u8>> for Port {
+//!     type Error = &'static str;
+//!
+//!     fn validate(input: Vec<u8>) -> Result<Port, Self::Error> {
+//!         let bytes: [u8; 2] = input.try_into().map_err(|_| "port is not 2 bytes")?;
+//!         match u16::from_le_bytes(bytes) {
+//!             0 => Err("port 0"),
+//!             port => Ok(Port(port)),
+//!         }
+//!     }
+//! }
+//!
+//! let input = Untrusted::new(vec![0x50, 0x00]);
+//! let port: Port = input.validate().unwrap();
+//! assert_eq!(port.0, 80);
+//! ```
+
+#[cfg(not(minimal_tcb))]
+use crate::ffi::OsStr;
+use crate::ffi::OsString;
+use crate::fmt;
+use crate::io::{self, Read};
+use crate::ptr;
+use crate::string::{FromUtf8Error, String};
+use crate::sync::atomic::{AtomicU64, Ordering};
+use crate::vec::Vec;
+
+use sgx_trts::trts::rsgx_raw_is_outside_enclave;
+
+static REJECTED: AtomicU64 = AtomicU64::new(0);
+
+/// A value supplied by the host, readable only through a [`Validate`]
+/// implementation.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Untrusted<T>(T);
+
+/// Parses a value supplied by the host into `Self`, rejecting what is not
+/// well-formed.
+pub trait Validate<T>: Sized {
+    /// The reason an input is rejected.
+    type Error;
+
+    fn validate(input: T) -> Result<Self, Self::Error>;
+}
+
+impl<T> Untrusted<T> {
+    /// Tags `value` as supplied by the host.
+    pub const fn new(value: T) -> Untrusted<T> {
+        Untrusted(value)
+    }
+
+    /// Parses the value with the validator of `U`.
+    ///
+    /// Rejected inputs are counted, see [`rejected`].
+    pub fn validate<U: Validate<T>>(self) -> Result<U, U::Error> {
+        U::validate(self.0).map_err(|e| {
+            REJECTED.fetch_add(1, Ordering::Relaxed);
+            e
+        })
+    }
+
+    /// Parses a borrowed view of the value with the validator of `U`.
+    pub fn validate_ref<'a, U: Validate<&'a T>>(&'a self) -> Result<U, U::Error> {
+        Untrusted(&self.0).validate()
+    }
+
+    /// Computes a new untrusted value from this one.
+    ///
+    /// The result is as untrusted as the input, so `f` should only reshape
+    /// the value, e.g. split a message into its fields for separate
+    /// validation.
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Untrusted<U> {
+        Untrusted(f(self.0))
+    }
+
+    /// Borrows the value, still tagged.
+    pub fn as_ref(&self) -> Untrusted<&T> {
+        Untrusted(&self.0)
+    }
+}
+
+impl Untrusted<Vec<u8>> {
+    /// Copies `len` bytes at `ptr` into the enclave.
+    ///
+    /// This is for buffers the host passes to an ECALL as `user_check`
+    /// pointers, or returns from an OCALL. The copy is taken once, so the
+    /// host cannot change the bytes after they were validated.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `ptr` is null or the
+    /// buffer is not entirely outside the enclave, and with
+    /// [`io::ErrorKind::OutOfMemory`] if the copy cannot be allocated.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for reads of `len` bytes.
+    pub unsafe fn copy_from_host(ptr: *const u8, len: usize) -> io::Result<Untrusted<Vec<u8>>> {
+        if len == 0 {
+            return Ok(Untrusted(Vec::new()));
+        }
+        if ptr.is_null() || !rsgx_raw_is_outside_enclave(ptr, len) {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "buffer is not in untrusted memory",
+            ));
+        }
+
+        let mut buf = Vec::new();
+        buf.try_reserve_exact(len).map_err(|_| {
+            io::const_io_error!(io::ErrorKind::OutOfMemory, "cannot copy the host buffer")
+        })?;
+        ptr::copy_nonoverlapping(ptr, buf.as_mut_ptr(), len);
+        buf.set_len(len);
+        Ok(Untrusted(buf))
+    }
+
+    /// Returns the length of the bytes, which the host chose as well.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<T> fmt::Debug for Untrusted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Host data is not formatted, so that it cannot forge log lines.
+        write!(f, "Untrusted<{}>(..)", crate::any::type_name::<T>())
+    }
+}
+
+impl Validate<Vec<u8>> for String {
+    type Error = FromUtf8Error;
+
+    fn validate(input: Vec<u8>) -> Result<String, FromUtf8Error> {
+        String::from_utf8(input)
+    }
+}
+
+impl Validate<OsString> for String {
+    type Error = OsString;
+
+    fn validate(input: OsString) -> Result<String, OsString> {
+        input.into_string()
+    }
+}
+
+/// Reads from streams backed by the host into [`Untrusted`] buffers.
+///
+/// This is implemented for every reader, since sockets, pipes and untrusted
+/// files are all fed by the host.
+pub trait ReadUntrusted: Read {
+    /// Reads until the end of the stream, but no more than `limit` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if the stream holds more
+    /// than `limit` bytes, besides the errors of the reader.
+    fn read_untrusted(&mut self, limit: usize) -> io::Result<Untrusted<Vec<u8>>> {
+        let mut buf = Vec::new();
+        let mut bounded = self.take((limit as u64).saturating_add(1));
+        bounded.read_to_end(&mut buf)?;
+        if buf.len() > limit {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidData,
+                "untrusted input exceeds its limit",
+            ));
+        }
+        Ok(Untrusted(buf))
+    }
+
+    /// Reads exactly `len` bytes.
+    fn read_exact_untrusted(&mut self, len: usize) -> io::Result<Untrusted<Vec<u8>>> {
+        let mut buf = vec![0_u8; len];
+        self.read_exact(&mut buf)?;
+        Ok(Untrusted(buf))
+    }
+}
+
+impl<R: Read + ?Sized> ReadUntrusted for R {}
+
+/// Fetches the environment variable `key` from the host.
+#[cfg(not(minimal_tcb))]
+pub fn var_os<K: AsRef<OsStr>>(key: K) -> Option<Untrusted<OsString>> {
+    crate::env::var_os(key).map(Untrusted)
+}
+
+/// Returns the number of inputs rejected by their validator so far.
+pub fn rejected() -> u64 {
+    REJECTED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,842 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use crate::boxed::Box;
+use crate::cell::RefCell;
+use crate::collections::{BTreeMap, VecDeque};
+use crate::ecall::{self, EcallContext};
+use crate::fmt;
+use crate::future::Future;
+#[cfg(feature = "thread")]
+use crate::io;
+use crate::panic::{self, AssertUnwindSafe};
+use crate::pin::Pin;
+use crate::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use crate::sync::{Arc, PoisonError, SgxMutex, SgxMutexGuard, TryLockError, Weak};
+use crate::task::{Context, Poll, Wake, Waker};
+use crate::thread::{self, SgxThread, ThreadId};
+use crate::time::{Duration, Instant};
+use crate::vec::Vec;
+
+#[cfg(tstd_net)]
+use super::reactor::Reactor;
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Shared>>> = RefCell::new(None);
+}
+
+// The states of a task. A task is queued only on the transition to
+// `SCHEDULED`, so it is never in the run queue twice; a wakeup that arrives
+// while the task is polled is remembered as `NOTIFIED`.
+const IDLE: u8 = 0;
+const SCHEDULED: u8 = 1;
+const RUNNING: u8 = 2;
+const NOTIFIED: u8 = 3;
+const COMPLETE: u8 = 4;
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Runs `async` tasks on the enclave threads that join it.
+///
+/// Cloning an executor yields another handle to the same executor. Tasks
+/// keep running while a thread works for the executor, until
+/// [`Executor::shutdown`] is called.
+#[derive(Clone)]
+pub struct Executor {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: SgxMutex<State>,
+    shutdown: AtomicBool,
+    #[cfg(feature = "thread")]
+    workers: SgxMutex<Vec<thread::JoinHandle<()>>>,
+}
+
+struct State {
+    queue: VecDeque<Arc<Task>>,
+    // Workers parked on their TCS event, waiting for a task or a timer.
+    idle: Vec<SgxThread>,
+    // The worker that waits in the reactor, if any.
+    #[cfg(tstd_net)]
+    driver: Option<ThreadId>,
+    timers: BTreeMap<(Instant, u64), Waker>,
+    next_timer: u64,
+    // Every task that has not completed, so that shutdown can drop them.
+    tasks: BTreeMap<u64, Weak<Task>>,
+    next_task: u64,
+}
+
+struct Task {
+    id: u64,
+    state: AtomicU8,
+    future: SgxMutex<Option<BoxFuture>>,
+    context: Option<EcallContext>,
+    shared: Weak<Shared>,
+}
+
+// A thread to wake after the lock of the executor is released.
+enum Wakeup {
+    None,
+    Thread(SgxThread),
+    #[cfg(tstd_net)]
+    Reactor,
+}
+
+impl Wakeup {
+    fn send(self) {
+        match self {
+            Wakeup::None => {}
+            Wakeup::Thread(thread) => thread.unpark(),
+            #[cfg(tstd_net)]
+            Wakeup::Reactor => {
+                if let Some(reactor) = Reactor::get() {
+                    reactor.notify();
+                }
+            }
+        }
+    }
+}
+
+impl Executor {
+    /// Creates an executor without any worker.
+    pub fn new() -> Executor {
+        Executor {
+            shared: Arc::new(Shared {
+                state: SgxMutex::new(State {
+                    queue: VecDeque::new(),
+                    idle: Vec::new(),
+                    #[cfg(tstd_net)]
+                    driver: None,
+                    timers: BTreeMap::new(),
+                    next_timer: 0,
+                    tasks: BTreeMap::new(),
+                    next_task: 0,
+                }),
+                shutdown: AtomicBool::new(false),
+                #[cfg(feature = "thread")]
+                workers: SgxMutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Returns the executor the current thread works for, if any.
+    pub fn current() -> Option<Executor> {
+        CURRENT
+            .try_with(|current| current.borrow().clone())
+            .ok()
+            .flatten()
+            .map(|shared| Executor { shared })
+    }
+
+    /// Spawns a task, returning a handle that resolves to its output.
+    ///
+    /// The task captures the [`EcallContext`] of the calling thread. It is
+    /// first polled by the next worker that becomes free. A task that is
+    /// never awaited through its handle still runs to completion.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let join = Arc::new(SgxMutex::new(JoinState {
+            result: None,
+            waker: None,
+            done: false,
+        }));
+        let completion = Completion(join.clone());
+        let future = Box::pin(async move {
+            let result = CatchUnwind(Box::pin(future)).await;
+            completion.finish(result);
+        });
+
+        let mut state = self.shared.lock();
+        let id = state.next_task;
+        state.next_task += 1;
+        let task = Arc::new(Task {
+            id,
+            state: AtomicU8::new(SCHEDULED),
+            future: SgxMutex::new(Some(future)),
+            context: ecall::current(),
+            shared: Arc::downgrade(&self.shared),
+        });
+        if !self.shared.is_shutdown() {
+            state.tasks.insert(id, Arc::downgrade(&task));
+            state.queue.push_back(task);
+            let wakeup = state.notify_one();
+            drop(state);
+            wakeup.send();
+        }
+        JoinHandle { state: join }
+    }
+
+    /// Runs `future` to completion on the calling thread, working for the
+    /// executor while it waits.
+    ///
+    /// Tasks spawned on the executor are run by this thread too, so an
+    /// executor without workers makes progress for as long as some thread
+    /// is in `block_on`.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let _enter = Enter::new(&self.shared);
+        let main = Arc::new(MainWaker {
+            woken: AtomicBool::new(true),
+            thread: thread::current(),
+            shared: Arc::downgrade(&self.shared),
+        });
+        let waker = Waker::from(main.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if main.woken.swap(false, Ordering::AcqRel) {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                    return output;
+                }
+            }
+            self.shared.turn(&|| main.woken.load(Ordering::Acquire));
+        }
+    }
+
+    /// Works for the executor on the calling thread until it is shut down.
+    ///
+    /// This is how an ECALL donates its thread to the executor. With the
+    /// `Unbind` TCS policy the thread only works while it stays inside the
+    /// enclave.
+    pub fn run_worker(&self) {
+        let _enter = Enter::new(&self.shared);
+        while !self.shared.is_shutdown() {
+            self.shared.turn(&|| self.shared.is_shutdown());
+        }
+    }
+
+    /// Starts `count` enclave threads that work for the executor until it is
+    /// shut down.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first thread that could not be started, for
+    /// example because no TCS is free. The threads started before it keep
+    /// working.
+    #[cfg(feature = "thread")]
+    pub fn spawn_workers(&self, count: usize) -> io::Result<()> {
+        let mut workers = self
+            .shared
+            .workers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        for _ in 0..count {
+            let executor = self.clone();
+            let handle = thread::Builder::new()
+                .name(format!("async-worker-{}", workers.len()))
+                .spawn(move || executor.run_worker())?;
+            workers.push(handle);
+        }
+        Ok(())
+    }
+
+    /// Stops the executor.
+    ///
+    /// Tasks that have not completed are dropped, and their handles resolve
+    /// to an error. Workers return once they finish the task at hand; when
+    /// called outside of the executor, this waits for the threads started by
+    /// [`Executor::spawn_workers`] to exit. Threads in [`Executor::block_on`]
+    /// keep driving their own future.
+    pub fn shutdown(&self) {
+        if self.shared.shutdown.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let mut state = self.shared.lock();
+        let queue = crate::mem::take(&mut state.queue);
+        let tasks = crate::mem::take(&mut state.tasks);
+        let idle = crate::mem::take(&mut state.idle);
+        #[cfg(tstd_net)]
+        let wakeup = if state.driver.is_some() {
+            Wakeup::Reactor
+        } else {
+            Wakeup::None
+        };
+        drop(state);
+
+        // Dropping a future may wake other tasks, which needs the lock.
+        drop(queue);
+        for task in tasks.values().filter_map(Weak::upgrade) {
+            task.cancel();
+        }
+        for thread in idle {
+            thread.unpark();
+        }
+        #[cfg(tstd_net)]
+        wakeup.send();
+
+        #[cfg(feature = "thread")]
+        if !self.is_current() {
+            let workers = crate::mem::take(
+                &mut *self
+                    .shared
+                    .workers
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner),
+            );
+            for worker in workers {
+                let _ = worker.join();
+            }
+        }
+    }
+
+    /// Returns `true` once [`Executor::shutdown`] was called.
+    pub fn is_shutdown(&self) -> bool {
+        self.shared.is_shutdown()
+    }
+
+    #[cfg(feature = "thread")]
+    fn is_current(&self) -> bool {
+        CURRENT
+            .try_with(|current| {
+                current
+                    .borrow()
+                    .as_ref()
+                    .map_or(false, |shared| Arc::ptr_eq(shared, &self.shared))
+            })
+            .unwrap_or(false)
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Executor {
+        Executor::new()
+    }
+}
+
+impl fmt::Debug for Executor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.shared.lock();
+        f.debug_struct("Executor")
+            .field("tasks", &state.tasks.len())
+            .field("queued", &state.queue.len())
+            .field("idle", &state.idle.len())
+            .field("timers", &state.timers.len())
+            .field("shutdown", &self.shared.is_shutdown())
+            .finish()
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> SgxMutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::Acquire)
+    }
+
+    fn schedule(&self, task: Arc<Task>) {
+        let mut state = self.lock();
+        if self.is_shutdown() {
+            drop(state);
+            task.cancel();
+            return;
+        }
+        state.queue.push_back(task);
+        let wakeup = state.notify_one();
+        drop(state);
+        wakeup.send();
+    }
+
+    // Wakes `thread` if it waits for the executor.
+    fn wake_thread(&self, thread: &SgxThread) {
+        let mut state = self.lock();
+        let id = thread.id();
+        let wakeup = if let Some(pos) = state.idle.iter().position(|t| t.id() == id) {
+            Wakeup::Thread(state.idle.swap_remove(pos))
+        } else {
+            state.wakeup_driver(id)
+        };
+        drop(state);
+        wakeup.send();
+    }
+
+    // Does one unit of work: runs a task, fires the due timers, or waits
+    // until there may be more to do. Returns without waiting if `stop`
+    // holds, which is checked under the lock that wakers take.
+    fn turn(&self, stop: &dyn Fn() -> bool) {
+        let mut state = self.lock();
+        if stop() {
+            return;
+        }
+        if let Some(task) = state.queue.pop_front() {
+            drop(state);
+            task.run();
+            return;
+        }
+
+        let now = Instant::_now();
+        let due = state.take_due_timers(now);
+        if !due.is_empty() {
+            drop(state);
+            due.into_iter().for_each(Waker::wake);
+            return;
+        }
+        let timeout = state
+            .timers
+            .keys()
+            .next()
+            .map(|(deadline, _)| deadline.saturating_duration_since(now));
+
+        let current = thread::current();
+        #[cfg(tstd_net)]
+        if let Some(reactor) = Reactor::get() {
+            if state.driver.is_none() && reactor.try_drive(&current) {
+                state.driver = Some(current.id());
+                drop(state);
+                reactor.poll(timeout);
+                reactor.release();
+                self.lock().driver = None;
+                return;
+            }
+        }
+
+        let id = current.id();
+        state.idle.push(current);
+        drop(state);
+        match timeout {
+            Some(timeout) => thread::park_timeout(timeout),
+            None => thread::park(),
+        }
+        self.lock().idle.retain(|thread| thread.id() != id);
+    }
+}
+
+impl State {
+    // Picks the thread to wake for a newly queued task.
+    fn notify_one(&mut self) -> Wakeup {
+        if let Some(thread) = self.idle.pop() {
+            return Wakeup::Thread(thread);
+        }
+        #[cfg(tstd_net)]
+        if self.driver.is_some() {
+            return Wakeup::Reactor;
+        }
+        Wakeup::None
+    }
+
+    #[cfg(tstd_net)]
+    fn wakeup_driver(&self, id: ThreadId) -> Wakeup {
+        if self.driver == Some(id) {
+            Wakeup::Reactor
+        } else {
+            Wakeup::None
+        }
+    }
+
+    #[cfg(not(tstd_net))]
+    fn wakeup_driver(&self, _id: ThreadId) -> Wakeup {
+        Wakeup::None
+    }
+
+    fn take_due_timers(&mut self, now: Instant) -> Vec<Waker> {
+        let mut due = Vec::new();
+        while let Some(entry) = self.timers.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            due.push(entry.remove());
+        }
+        due
+    }
+}
+
+impl Task {
+    fn schedule(self: &Arc<Self>) {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            let next = match state {
+                IDLE => SCHEDULED,
+                RUNNING => NOTIFIED,
+                _ => return,
+            };
+            match self
+                .state
+                .compare_exchange_weak(state, next, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => break,
+                Err(actual) => state = actual,
+            }
+        }
+        if state == IDLE {
+            if let Some(shared) = self.shared.upgrade() {
+                shared.schedule(self.clone());
+            }
+        }
+    }
+
+    fn run(self: &Arc<Self>) {
+        self.state.store(RUNNING, Ordering::Release);
+        let waker = Waker::from(self.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut future = self.future.lock().unwrap_or_else(PoisonError::into_inner);
+        let ready = match future.as_mut() {
+            Some(future) => {
+                let _context = ContextGuard::install(self.context.clone());
+                future.as_mut().poll(&mut cx).is_ready()
+            }
+            None => true,
+        };
+        // A task that is polled while the executor shuts down is dropped
+        // here, as `cancel` skips it.
+        let shutdown = self
+            .shared
+            .upgrade()
+            .map_or(true, |shared| shared.is_shutdown());
+        if ready || shutdown {
+            *future = None;
+            drop(future);
+            self.complete();
+            return;
+        }
+        drop(future);
+
+        if self
+            .state
+            .compare_exchange(RUNNING, IDLE, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            // Woken while it was polled.
+            self.state.store(SCHEDULED, Ordering::Release);
+            if let Some(shared) = self.shared.upgrade() {
+                shared.schedule(self.clone());
+            }
+        }
+    }
+
+    fn complete(&self) {
+        self.state.store(COMPLETE, Ordering::Release);
+        if let Some(shared) = self.shared.upgrade() {
+            shared.lock().tasks.remove(&self.id);
+        }
+    }
+
+    fn cancel(&self) {
+        self.state.store(COMPLETE, Ordering::Release);
+        let future = match self.future.try_lock() {
+            Ok(mut future) => future.take(),
+            Err(TryLockError::Poisoned(e)) => e.into_inner().take(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+        drop(future);
+    }
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        self.schedule();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.schedule();
+    }
+}
+
+// The waker of the future passed to `block_on`.
+struct MainWaker {
+    woken: AtomicBool,
+    thread: SgxThread,
+    shared: Weak<Shared>,
+}
+
+impl Wake for MainWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if !self.woken.swap(true, Ordering::AcqRel) {
+            if let Some(shared) = self.shared.upgrade() {
+                shared.wake_thread(&self.thread);
+            }
+        }
+    }
+}
+
+// Marks the current thread as working for an executor.
+struct Enter(Option<Arc<Shared>>);
+
+impl Enter {
+    fn new(shared: &Arc<Shared>) -> Enter {
+        Enter(CURRENT.with(|current| current.replace(Some(shared.clone()))))
+    }
+}
+
+impl Drop for Enter {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        let _ = CURRENT.try_with(|current| current.replace(previous));
+    }
+}
+
+// Installs the request context of a task while it is polled.
+struct ContextGuard(Option<EcallContext>);
+
+impl ContextGuard {
+    fn install(context: Option<EcallContext>) -> ContextGuard {
+        ContextGuard(ecall::set_current(context))
+    }
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        ecall::set_current(self.0.take());
+    }
+}
+
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = thread::Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = self.0.as_mut();
+        match panic::catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+struct JoinState<T> {
+    result: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+    done: bool,
+}
+
+// Hands the output of a task to its handle, or an error if the task is
+// dropped before it completes.
+struct Completion<T>(Arc<SgxMutex<JoinState<T>>>);
+
+impl<T> Completion<T> {
+    fn finish(&self, result: thread::Result<T>) {
+        let mut state = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.done {
+            return;
+        }
+        state.done = true;
+        state.result = Some(result);
+        let waker = state.waker.take();
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        self.finish(Err(Box::new("task cancelled by executor shutdown")));
+    }
+}
+
+/// An owned permission to await the output of a task.
+///
+/// The handle resolves to `Ok` with the output of the task, or to `Err` with
+/// the panic payload if the task panicked. A task dropped by
+/// [`Executor::shutdown`] resolves to an `Err` with a `&'static str` payload.
+/// Dropping the handle detaches the task.
+pub struct JoinHandle<T> {
+    state: Arc<SgxMutex<JoinState<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Returns `true` once the task has completed, panicked or was dropped.
+    pub fn is_finished(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .done
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = thread::Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(result) = state.result.take() {
+            return Poll::Ready(result);
+        }
+        assert!(!state.done, "`JoinHandle` polled after completion");
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinHandle")
+            .field("finished", &self.is_finished())
+            .finish()
+    }
+}
+
+/// A future that completes at a deadline, returned by [`sleep`] and
+/// [`sleep_until`].
+///
+/// The deadline is read against the untrusted clock of [`Instant`], so the
+/// host decides when it passes.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Sleep {
+    deadline: Option<Instant>,
+    timer: Option<(Weak<Shared>, (Instant, u64))>,
+}
+
+impl Sleep {
+    /// The deadline of the timer, or `None` if it lies beyond what
+    /// [`Instant`] can represent and the timer never fires.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn is_elapsed(&self) -> bool {
+        self.deadline
+            .map_or(false, |deadline| Instant::_now() >= deadline)
+    }
+
+    fn cancel(&mut self) {
+        if let Some((shared, key)) = self.timer.take() {
+            if let Some(shared) = shared.upgrade() {
+                shared.lock().timers.remove(&key);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Sleep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sleep")
+            .field("deadline", &self.deadline)
+            .field("registered", &self.timer.is_some())
+            .finish()
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return Poll::Pending,
+        };
+        if Instant::_now() >= deadline {
+            self.cancel();
+            return Poll::Ready(());
+        }
+
+        let shared = match &self.timer {
+            Some((shared, _)) => shared.upgrade(),
+            None => Executor::current().map(|executor| executor.shared),
+        };
+        let shared = match shared {
+            Some(shared) => shared,
+            None => panic!("`Sleep` polled outside of an executor"),
+        };
+        let mut state = shared.lock();
+        let key = match self.timer {
+            Some((_, key)) => key,
+            None => {
+                let key = (deadline, state.next_timer);
+                state.next_timer += 1;
+                key
+            }
+        };
+        // A timer that fired early by the clock is armed again.
+        state.timers.insert(key, cx.waker().clone());
+        drop(state);
+        self.timer = Some((Arc::downgrade(&shared), key));
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// A future that yields to the executor once, returned by [`yield_now`].
+#[derive(Debug, Default)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Spawns a task on the executor the current thread works for.
+///
+/// # Panics
+///
+/// Panics if the current thread does not work for an executor.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match Executor::current() {
+        Some(executor) => executor.spawn(future),
+        None => panic!("`task::spawn` called outside of an executor"),
+    }
+}
+
+/// Runs `future` to completion on the calling thread with a fresh executor.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    Executor::new().block_on(future)
+}
+
+/// Waits until `duration` has elapsed.
+///
+/// The returned future must be polled by a thread that works for an
+/// executor.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        deadline: Instant::_now().checked_add(duration),
+        timer: None,
+    }
+}
+
+/// Waits until `deadline` has passed.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline: Some(deadline),
+        timer: None,
+    }
+}
+
+/// Yields to the other tasks of the executor once.
+pub fn yield_now() -> YieldNow {
+    YieldNow::default()
+}
@@ -0,0 +1,72 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Types and Traits for working with asynchronous tasks.
+//!
+//! Besides the types of `core` and `alloc`, this module provides an
+//! [`Executor`] that runs `async` code on enclave threads.
+//!
+//! An executor has no threads of its own. Enclave threads join it with
+//! [`Executor::run_worker`] or [`Executor::block_on`], or, with the `thread`
+//! feature, are started for it by [`Executor::spawn_workers`]. Tasks may be
+//! polled by a different worker each time they run, so they must be `Send`
+//! and must not keep state in thread locals across an `.await`. The
+//! [`EcallContext`](crate::ecall::EcallContext) installed when a task is
+//! spawned is installed again whenever the task is polled.
+//!
+//! A worker with nothing to do blocks on the event of its TCS, and a task
+//! woken for it sets that event, so an idle executor costs one OCALL per
+//! wakeup. Timers are served by the same wait: [`sleep`] and [`sleep_until`]
+//! bound how long an idle worker blocks.
+//!
+//! With the `net` feature, one idle worker at a time instead waits in
+//! `epoll_wait` on behalf of the sockets of `net::AsyncTcpListener`,
+//! `net::AsyncTcpStream` and `net::AsyncUdpSocket`, so `async fn` networking
+//! needs no thread per connection. The readiness events come from the host,
+//! which can delay or drop them; they only decide when an operation is
+//! retried, never what it returns.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::task::{self, Executor};
+//! use std::time::Duration;
+//!
+//! let executor = Executor::new();
+//! let answer = executor.block_on(async {
+//!     let handle = task::spawn(async {
+//!         task::sleep(Duration::from_millis(10)).await;
+//!         42
+//!     });
+//!     handle.await.unwrap()
+//! });
+//! assert_eq!(answer, 42);
+//! ```
+
+#[doc(inline)]
+pub use core::task::*;
+
+#[doc(inline)]
+pub use alloc_crate::task::*;
+
+pub use self::executor::{
+    block_on, sleep, sleep_until, spawn, yield_now, Executor, JoinHandle, Sleep, YieldNow,
+};
+
+mod executor;
+#[cfg(tstd_net)]
+pub(crate) mod reactor;
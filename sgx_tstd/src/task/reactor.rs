@@ -0,0 +1,345 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+// The I/O reactor of the executors: one epoll instance, created through
+// OCALLs when the first asynchronous socket is registered, and an eventfd
+// that interrupts the worker waiting in `epoll_wait` when it has other work.
+//
+// Sockets are registered edge-triggered. Readiness is tracked per direction
+// with a tick that advances on every event, so an operation that fails with
+// `WouldBlock` only clears the readiness it observed and never loses an event
+// that arrived meanwhile. The events are written by the host: a token that
+// names no registered socket is ignored, and a forged event costs no more
+// than one retried operation.
+
+use crate::cmp;
+use crate::collections::BTreeMap;
+use crate::future;
+use crate::io;
+use crate::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use crate::sync::{Arc, OnceLock, PoisonError, SgxMutex, SgxMutexGuard};
+use crate::sys::cvt;
+use crate::task::{Context, Poll, Waker};
+use crate::thread::SgxThread;
+use crate::time::Duration;
+use crate::vec::Vec;
+
+use sgx_libc::{c_int, c_void, epoll_event};
+
+static REACTOR: OnceLock<Reactor> = OnceLock::new();
+
+const WAKE_TOKEN: u64 = 0;
+const MAX_EVENTS: usize = 64;
+
+const READ_EVENTS: u32 =
+    (libc::EPOLLIN | libc::EPOLLPRI | libc::EPOLLRDHUP | libc::EPOLLHUP | libc::EPOLLERR) as u32;
+const WRITE_EVENTS: u32 = (libc::EPOLLOUT | libc::EPOLLHUP | libc::EPOLLERR) as u32;
+
+pub(crate) struct Reactor {
+    epoll: OwnedFd,
+    event: OwnedFd,
+    sources: SgxMutex<Sources>,
+    driver: SgxMutex<Driver>,
+}
+
+struct Sources {
+    map: BTreeMap<u64, Arc<ScheduledIo>>,
+    next_token: u64,
+}
+
+struct Driver {
+    driving: bool,
+    // Workers that found the reactor taken and wait to drive it.
+    standby: Vec<SgxThread>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Read = 0,
+    Write = 1,
+}
+
+struct ScheduledIo {
+    state: SgxMutex<IoState>,
+}
+
+struct IoState {
+    ready: [bool; 2],
+    ticks: [u64; 2],
+    wakers: [Option<Waker>; 2],
+}
+
+impl Reactor {
+    /// Returns the reactor if a socket was ever registered.
+    pub(crate) fn get() -> Option<&'static Reactor> {
+        REACTOR.get()
+    }
+
+    fn get_or_init() -> io::Result<&'static Reactor> {
+        REACTOR.get_or_try_init(Reactor::new)
+    }
+
+    fn new() -> io::Result<Reactor> {
+        let epoll = cvt(unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) })?;
+        let epoll = unsafe { OwnedFd::from_raw_fd(epoll) };
+        let event = cvt(unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) })?;
+        let event = unsafe { OwnedFd::from_raw_fd(event) };
+
+        let mut ev = epoll_event {
+            events: (libc::EPOLLIN | libc::EPOLLET) as u32,
+            u64: WAKE_TOKEN,
+        };
+        cvt(unsafe {
+            libc::epoll_ctl(
+                epoll.as_raw_fd(),
+                libc::EPOLL_CTL_ADD,
+                event.as_raw_fd(),
+                &mut ev,
+            )
+        })?;
+
+        Ok(Reactor {
+            epoll,
+            event,
+            sources: SgxMutex::new(Sources {
+                map: BTreeMap::new(),
+                next_token: WAKE_TOKEN,
+            }),
+            driver: SgxMutex::new(Driver {
+                driving: false,
+                standby: Vec::new(),
+            }),
+        })
+    }
+
+    fn sources(&self) -> SgxMutexGuard<'_, Sources> {
+        self.sources.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn deregister(&self, token: u64, fd: RawFd) {
+        self.sources().map.remove(&token);
+        let mut ev = epoll_event { events: 0, u64: 0 };
+        unsafe {
+            libc::epoll_ctl(self.epoll.as_raw_fd(), libc::EPOLL_CTL_DEL, fd, &mut ev);
+        }
+    }
+
+    /// Interrupts the worker waiting in `epoll_wait`, or the next one to
+    /// wait.
+    pub(crate) fn notify(&self) {
+        let one = 1_u64;
+        unsafe {
+            libc::write(
+                self.event.as_raw_fd(),
+                &one as *const u64 as *const c_void,
+                crate::mem::size_of::<u64>(),
+            );
+        }
+    }
+
+    /// Takes the reactor for `thread`. If another worker drives it,
+    /// `thread` is woken when the reactor is released.
+    pub(crate) fn try_drive(&self, thread: &SgxThread) -> bool {
+        let mut driver = self.driver.lock().unwrap_or_else(PoisonError::into_inner);
+        if !driver.driving {
+            driver.driving = true;
+            return true;
+        }
+        let id = thread.id();
+        if driver.standby.iter().all(|t| t.id() != id) {
+            driver.standby.push(thread.clone());
+        }
+        false
+    }
+
+    pub(crate) fn release(&self) {
+        let mut driver = self.driver.lock().unwrap_or_else(PoisonError::into_inner);
+        driver.driving = false;
+        let next = driver.standby.pop();
+        drop(driver);
+        if let Some(thread) = next {
+            thread.unpark();
+        }
+    }
+
+    /// Waits for events for at most `timeout` and wakes the tasks they
+    /// concern. Must only be called by the worker that drives the reactor.
+    pub(crate) fn poll(&self, timeout: Option<Duration>) {
+        let mut events = [epoll_event { events: 0, u64: 0 }; MAX_EVENTS];
+        let n = unsafe {
+            libc::epoll_wait(
+                self.epoll.as_raw_fd(),
+                events.as_mut_ptr(),
+                MAX_EVENTS as c_int,
+                timeout_ms(timeout),
+            )
+        };
+        if n <= 0 {
+            // Timed out, interrupted or failed; the worker goes round again.
+            return;
+        }
+
+        let mut wakers = Vec::new();
+        let sources = self.sources();
+        for ev in &events[..cmp::min(n as usize, MAX_EVENTS)] {
+            let (token, bits) = (ev.u64, ev.events);
+            if token == WAKE_TOKEN {
+                self.drain();
+            } else if let Some(io) = sources.map.get(&token) {
+                io.set_ready(bits, &mut wakers);
+            }
+        }
+        drop(sources);
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    fn drain(&self) {
+        let mut count = 0_u64;
+        unsafe {
+            libc::read(
+                self.event.as_raw_fd(),
+                &mut count as *mut u64 as *mut c_void,
+                crate::mem::size_of::<u64>(),
+            );
+        }
+    }
+}
+
+pub(crate) fn timeout_ms(timeout: Option<Duration>) -> c_int {
+    match timeout {
+        None => -1,
+        Some(timeout) => {
+            let mut ms = timeout.as_millis();
+            if timeout.subsec_nanos() % 1_000_000 != 0 {
+                ms += 1;
+            }
+            cmp::min(ms, c_int::MAX as u128) as c_int
+        }
+    }
+}
+
+impl ScheduledIo {
+    fn lock(&self) -> SgxMutexGuard<'_, IoState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn set_ready(&self, bits: u32, wakers: &mut Vec<Waker>) {
+        let mut state = self.lock();
+        for (dir, mask) in [
+            (Direction::Read, READ_EVENTS),
+            (Direction::Write, WRITE_EVENTS),
+        ] {
+            if bits & mask != 0 {
+                let i = dir as usize;
+                state.ready[i] = true;
+                state.ticks[i] = state.ticks[i].wrapping_add(1);
+                wakers.extend(state.wakers[i].take());
+            }
+        }
+    }
+}
+
+/// A socket registered with the reactor. Deregisters it when dropped, so it
+/// must be dropped before the socket is closed.
+pub(crate) struct Registration {
+    reactor: &'static Reactor,
+    token: u64,
+    fd: RawFd,
+    io: Arc<ScheduledIo>,
+}
+
+impl Registration {
+    pub(crate) fn new(fd: RawFd) -> io::Result<Registration> {
+        let reactor = Reactor::get_or_init()?;
+        // Sockets start out ready, so the first operation is tried at once.
+        let io = Arc::new(ScheduledIo {
+            state: SgxMutex::new(IoState {
+                ready: [true; 2],
+                ticks: [0; 2],
+                wakers: [None, None],
+            }),
+        });
+
+        let mut sources = reactor.sources();
+        let token = sources.next_token + 1;
+        let mut ev = epoll_event {
+            events: (libc::EPOLLIN | libc::EPOLLOUT | libc::EPOLLRDHUP | libc::EPOLLET) as u32,
+            u64: token,
+        };
+        cvt(unsafe {
+            libc::epoll_ctl(reactor.epoll.as_raw_fd(), libc::EPOLL_CTL_ADD, fd, &mut ev)
+        })?;
+        sources.next_token = token;
+        sources.map.insert(token, io.clone());
+        drop(sources);
+
+        Ok(Registration {
+            reactor,
+            token,
+            fd,
+            io,
+        })
+    }
+
+    fn poll_ready(&self, dir: Direction, cx: &mut Context<'_>) -> Poll<u64> {
+        let mut state = self.io.lock();
+        let i = dir as usize;
+        if state.ready[i] {
+            Poll::Ready(state.ticks[i])
+        } else {
+            state.wakers[i] = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    fn clear_ready(&self, dir: Direction, tick: u64) {
+        let mut state = self.io.lock();
+        let i = dir as usize;
+        if state.ticks[i] == tick {
+            state.ready[i] = false;
+        }
+    }
+
+    /// Runs the nonblocking operation `op` until it does not fail with
+    /// `WouldBlock`, waiting for the socket to become ready in between.
+    ///
+    /// Each direction holds one waker, so only one task at a time should
+    /// wait for a direction of a socket.
+    pub(crate) async fn io<R, F>(&self, dir: Direction, mut op: F) -> io::Result<R>
+    where
+        F: FnMut() -> io::Result<R>,
+    {
+        loop {
+            let tick = future::poll_fn(|cx| self.poll_ready(dir, cx)).await;
+            match op() {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => self.clear_ready(dir, tick),
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.reactor.deregister(self.token, self.fd);
+    }
+}
+
+mod libc {
+    pub use sgx_libc::ocall::{epoll_create1, epoll_ctl, epoll_wait, eventfd, read, write};
+    pub use sgx_libc::*;
+}
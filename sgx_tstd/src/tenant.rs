@@ -0,0 +1,598 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Tenant isolation inside one enclave.
+//!
+//! An enclave that serves several customers keeps their data apart with a
+//! [`Tenancy`], a registry of [`Tenant`]s that gives each tenant
+//!
+//! * its own keys, derived from the root key of the tenancy with
+//!   HKDF-SHA256 (RFC 5869). The tenant ID and a caller chosen label are part
+//!   of the HKDF info, so no two tenants or labels share a key;
+//! * its own quotas, on the number of requests it has in flight and on the
+//!   bytes it writes to its files;
+//! * its own namespace of protected files, `<fs root>/<tenant ID>/`, sealed
+//!   with a key of the tenant, so that a file of one tenant cannot be read as
+//!   a file of another even if the host moves it into the other namespace.
+//!
+//! The tenant travels with the request: [`Tenant::scope`] installs an
+//! [`EcallContext`] that carries it, threads spawned inside inherit it, and
+//! [`current`] finds it from anywhere below the ECALL entry point.
+//!
+//! The root key is not derived here. It typically comes from the seal key of
+//! the enclave or from a key management service, so that tenant keys survive
+//! restarts.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::tenant::{self, Tenancy, TenantLimits};
+//! use std::io::Write;
+//!
+//! let tenancy = Tenancy::new(&[7_u8; 32], "/var/lib/enclave/tenants").unwrap();
+//! let acme = tenancy
+//!     .register("acme", TenantLimits::new().max_requests(8))
+//!     .unwrap();
+//!
+//! acme.scope(|| {
+//!     let tenant = tenant::current().unwrap();
+//!     let mut key = [0_u8; 32];
+//!     tenant.derive_key(b"ledger signing", &mut key).unwrap();
+//!     let mut file = tenant.create("ledger").unwrap();
+//!     file.write_all(b"opening balance").unwrap();
+//! })
+//! .unwrap();
+//! ```
+
+use crate::collections::BTreeMap;
+use crate::ecall::{self, EcallContext};
+use crate::fmt;
+use crate::io::{self, Read, Seek, SeekFrom, Write};
+use crate::path::{Component, Path, PathBuf};
+use crate::ptr;
+use crate::quota::{Counter, Usage};
+use crate::sgxfs::{self, SgxFile};
+use crate::sync::{Arc, PoisonError, SgxRwLock};
+
+use sgx_types::{sgx_hmac_sha256_msg, sgx_key_128bit_t, sgx_status_t};
+
+/// The size of the root key of a [`Tenancy`].
+pub const TENANT_ROOT_KEY_SIZE: usize = 32;
+
+/// The longest tenant ID.
+pub const TENANT_ID_MAX_LEN: usize = 64;
+
+const EXTRACT_SALT: &[u8] = b"sgx tenancy v1";
+const TENANT_INFO: &[u8] = b"sgx tenant key v1";
+const FS_KEY_LABEL: &[u8] = b"sgx protected fs";
+
+/// A resource with a per-tenant quota.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TenantResource {
+    /// Requests of the tenant in flight, counted by [`Tenant::admit`].
+    Requests,
+    /// Bytes written to the protected files of the tenant since it was
+    /// registered.
+    FsWriteBytes,
+}
+
+impl TenantResource {
+    /// All resources.
+    pub const ALL: [TenantResource; 2] = [TenantResource::Requests, TenantResource::FsWriteBytes];
+}
+
+/// The quotas of a tenant.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TenantLimits {
+    requests: Option<u64>,
+    fs_write_bytes: Option<u64>,
+}
+
+impl TenantLimits {
+    /// Creates limits that leave every resource unlimited.
+    pub fn new() -> TenantLimits {
+        TenantLimits::default()
+    }
+
+    /// Limits the number of requests the tenant has in flight.
+    pub fn max_requests(mut self, max: u64) -> TenantLimits {
+        self.requests = Some(max);
+        self
+    }
+
+    /// Limits the total number of bytes written to the files of the tenant.
+    pub fn max_fs_write_bytes(mut self, max: u64) -> TenantLimits {
+        self.fs_write_bytes = Some(max);
+        self
+    }
+
+    /// Returns the limit of `resource`.
+    pub fn get(&self, resource: TenantResource) -> Option<u64> {
+        match resource {
+            TenantResource::Requests => self.requests,
+            TenantResource::FsWriteBytes => self.fs_write_bytes,
+        }
+    }
+}
+
+/// A registry of the tenants of the enclave.
+pub struct Tenancy {
+    prk: [u8; 32],
+    fs_root: PathBuf,
+    tenants: SgxRwLock<BTreeMap<String, Tenant>>,
+}
+
+impl Tenancy {
+    /// Creates an empty registry whose tenant keys derive from `root_key`
+    /// and whose tenant namespaces are directories below `fs_root`.
+    pub fn new<P: Into<PathBuf>>(
+        root_key: &[u8; TENANT_ROOT_KEY_SIZE],
+        fs_root: P,
+    ) -> io::Result<Tenancy> {
+        // HKDF-Extract; the salt separates these keys from other uses of the
+        // root key.
+        let prk = hmac_sha256(EXTRACT_SALT, &[root_key])?;
+        Ok(Tenancy {
+            prk,
+            fs_root: fs_root.into(),
+            tenants: SgxRwLock::new(BTreeMap::new()),
+        })
+    }
+
+    /// Registers the tenant `id` with the given quotas.
+    ///
+    /// A tenant ID is 1 to [`TENANT_ID_MAX_LEN`] ASCII letters, digits, `-`,
+    /// `_` and `.`, and is not `.` or `..`, so that it names one directory.
+    /// The directory of the tenant must exist on the host before it creates
+    /// files.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `id` is not a valid
+    /// tenant ID, and with [`io::ErrorKind::AlreadyExists`] if the tenant is
+    /// registered already.
+    pub fn register(&self, id: &str, limits: TenantLimits) -> io::Result<Tenant> {
+        check_id(id)?;
+        let mut tenants = self.tenants.write().unwrap_or_else(PoisonError::into_inner);
+        if tenants.contains_key(id) {
+            return Err(io::const_io_error!(
+                io::ErrorKind::AlreadyExists,
+                "the tenant is registered already",
+            ));
+        }
+        let prk = hkdf_expand(&self.prk, &[TENANT_INFO, &[id.len() as u8], id.as_bytes()])?;
+        let tenant = Tenant {
+            inner: Arc::new(TenantInner {
+                id: id.to_owned(),
+                prk,
+                fs_dir: self.fs_root.join(id),
+                requests: Counter::new(),
+                fs_write_bytes: Counter::new(),
+            }),
+        };
+        tenant.set_limits(&limits);
+        tenants.insert(id.to_owned(), tenant.clone());
+        Ok(tenant)
+    }
+
+    /// Returns the tenant `id`, if it is registered.
+    pub fn get(&self, id: &str) -> Option<Tenant> {
+        self.tenants
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(id)
+            .cloned()
+    }
+
+    /// Removes the tenant `id` from the registry. Handles to the tenant that
+    /// are still held keep working.
+    pub fn remove(&self, id: &str) -> Option<Tenant> {
+        self.tenants
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(id)
+    }
+
+    /// Returns the IDs of the registered tenants, in order.
+    pub fn ids(&self) -> Vec<String> {
+        self.tenants
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .cloned()
+            .collect()
+    }
+}
+
+impl Drop for Tenancy {
+    fn drop(&mut self) {
+        wipe(&mut self.prk);
+    }
+}
+
+impl fmt::Debug for Tenancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tenancy")
+            .field("fs_root", &self.fs_root)
+            .field("tenants", &self.ids())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A handle to a tenant of a [`Tenancy`].
+///
+/// Cloning is cheap; all clones share the same keys and quotas.
+#[derive(Clone)]
+pub struct Tenant {
+    inner: Arc<TenantInner>,
+}
+
+struct TenantInner {
+    id: String,
+    prk: [u8; 32],
+    fs_dir: PathBuf,
+    requests: Counter,
+    fs_write_bytes: Counter,
+}
+
+impl Drop for TenantInner {
+    fn drop(&mut self) {
+        wipe(&mut self.prk);
+    }
+}
+
+impl Tenant {
+    /// Returns the ID of the tenant.
+    pub fn id(&self) -> &str {
+        &self.inner.id
+    }
+
+    /// Fills `out` with the key of the tenant for `label`, at most 8160
+    /// bytes.
+    ///
+    /// The same tenant and label always give the same key; different tenants
+    /// or labels give independent keys.
+    pub fn derive_key(&self, label: &[u8], out: &mut [u8]) -> io::Result<()> {
+        hkdf_expand_into(&self.inner.prk, &[label], out)
+    }
+
+    /// Returns the key that seals the protected files of the tenant.
+    pub fn fs_key(&self) -> io::Result<sgx_key_128bit_t> {
+        let mut key = sgx_key_128bit_t::default();
+        self.derive_key(FS_KEY_LABEL, &mut key)?;
+        Ok(key)
+    }
+
+    /// Counts a request of the tenant as in flight until the returned
+    /// [`Admission`] is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::Other`] if the tenant has as many requests
+    /// in flight as its quota allows.
+    pub fn admit(&self) -> io::Result<Admission> {
+        if self.inner.requests.acquire(1, 1) == 0 {
+            return Err(io::const_io_error!(
+                io::ErrorKind::Other,
+                "tenant request quota exceeded",
+            ));
+        }
+        Ok(Admission {
+            tenant: self.clone(),
+        })
+    }
+
+    /// Admits a request of the tenant and runs `f` in a context that carries
+    /// the tenant.
+    ///
+    /// The context is a child of the current one, if any, so it keeps its
+    /// request ID, deadline and cancellation.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`admit`](Tenant::admit), and with
+    /// [`io::ErrorKind::PermissionDenied`] if the current context already
+    /// carries another tenant.
+    pub fn scope<F, R>(&self, f: F) -> io::Result<R>
+    where
+        F: FnOnce() -> R,
+    {
+        let parent = ecall::current();
+        if let Some(tenant) = parent.as_ref().and_then(EcallContext::tenant) {
+            if !Arc::ptr_eq(&tenant.inner, &self.inner) {
+                return Err(io::const_io_error!(
+                    io::ErrorKind::PermissionDenied,
+                    "the request belongs to another tenant",
+                ));
+            }
+        }
+        let _admission = self.admit()?;
+        let builder = match parent {
+            Some(ctx) => ctx.child(),
+            None => EcallContext::builder(),
+        };
+        Ok(builder.tenant(self.clone()).build().scope(f))
+    }
+
+    /// Opens the protected file `path` of the tenant in read-only mode.
+    ///
+    /// `path` is relative to the namespace of the tenant and may not leave
+    /// it.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<TenantFile> {
+        let path = self.path(path)?;
+        let file = SgxFile::open_ex(path, &self.fs_key()?)?;
+        Ok(TenantFile {
+            file,
+            tenant: self.clone(),
+        })
+    }
+
+    /// Creates or truncates the protected file `path` of the tenant and opens
+    /// it in write-only mode.
+    ///
+    /// `path` is relative to the namespace of the tenant and may not leave
+    /// it.
+    pub fn create<P: AsRef<Path>>(&self, path: P) -> io::Result<TenantFile> {
+        let path = self.path(path)?;
+        let file = SgxFile::create_ex(path, &self.fs_key()?)?;
+        Ok(TenantFile {
+            file,
+            tenant: self.clone(),
+        })
+    }
+
+    /// Removes the protected file `path` of the tenant.
+    pub fn remove_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        sgxfs::remove(self.path(path)?)
+    }
+
+    /// Returns the host path of the file `path` of the tenant.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `path` is absolute,
+    /// empty, or has a `.` or `..` component.
+    pub fn path<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
+        let path = path.as_ref();
+        let mut components = 0;
+        for component in path.components() {
+            match component {
+                Component::Normal(_) => components += 1,
+                _ => {
+                    return Err(io::const_io_error!(
+                        io::ErrorKind::InvalidInput,
+                        "the path leaves the namespace of the tenant",
+                    ));
+                }
+            }
+        }
+        if components == 0 {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "the path is empty"
+            ));
+        }
+        Ok(self.inner.fs_dir.join(path))
+    }
+
+    /// Puts the quotas in force, replacing the previous ones.
+    ///
+    /// Lowering a limit below the current usage takes nothing away; it only
+    /// refuses new uses until the usage is below the limit again.
+    pub fn set_limits(&self, limits: &TenantLimits) {
+        for resource in TenantResource::ALL {
+            self.counter(resource).set_limit(limits.get(resource));
+        }
+    }
+
+    /// Returns the quotas currently in force.
+    pub fn limits(&self) -> TenantLimits {
+        TenantLimits {
+            requests: self.usage(TenantResource::Requests).limit,
+            fs_write_bytes: self.usage(TenantResource::FsWriteBytes).limit,
+        }
+    }
+
+    /// Returns the usage of `resource` by the tenant.
+    pub fn usage(&self, resource: TenantResource) -> Usage {
+        self.counter(resource).usage()
+    }
+
+    fn counter(&self, resource: TenantResource) -> &Counter {
+        match resource {
+            TenantResource::Requests => &self.inner.requests,
+            TenantResource::FsWriteBytes => &self.inner.fs_write_bytes,
+        }
+    }
+}
+
+impl PartialEq for Tenant {
+    fn eq(&self, other: &Tenant) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for Tenant {}
+
+impl fmt::Debug for Tenant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tenant")
+            .field("id", &self.inner.id)
+            .field("fs_dir", &self.inner.fs_dir)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A request of a tenant in flight, created by [`Tenant::admit`].
+#[must_use = "the request is no longer counted once the admission is dropped"]
+#[derive(Debug)]
+pub struct Admission {
+    tenant: Tenant,
+}
+
+impl Admission {
+    /// Returns the tenant the request belongs to.
+    pub fn tenant(&self) -> &Tenant {
+        &self.tenant
+    }
+}
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        self.tenant.inner.requests.release(1);
+    }
+}
+
+/// A protected file in the namespace of a tenant.
+///
+/// Writes count against the [`TenantResource::FsWriteBytes`] quota of the
+/// tenant. They are shortened to the remaining budget, and fail with
+/// [`io::ErrorKind::FilesystemQuotaExceeded`] once it is used up.
+pub struct TenantFile {
+    file: SgxFile,
+    tenant: Tenant,
+}
+
+impl TenantFile {
+    /// Returns the tenant the file belongs to.
+    pub fn tenant(&self) -> &Tenant {
+        &self.tenant
+    }
+}
+
+impl fmt::Debug for TenantFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TenantFile")
+            .field("tenant", &self.tenant.inner.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Read for TenantFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for TenantFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return self.file.write(buf);
+        }
+        let quota = &self.tenant.inner.fs_write_bytes;
+        let granted = quota.acquire(1, buf.len() as u64);
+        if granted == 0 {
+            return Err(io::const_io_error!(
+                io::ErrorKind::FilesystemQuotaExceeded,
+                "tenant file write quota exceeded",
+            ));
+        }
+        let result = self.file.write(&buf[..granted as usize]);
+        let written = *result.as_ref().unwrap_or(&0) as u64;
+        quota.release(granted - written.min(granted));
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for TenantFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+/// Returns the tenant of the current request, if any.
+pub fn current() -> Option<Tenant> {
+    ecall::current().and_then(|ctx| ctx.tenant().cloned())
+}
+
+fn check_id(id: &str) -> io::Result<()> {
+    let valid = !id.is_empty()
+        && id.len() <= TENANT_ID_MAX_LEN
+        && id != "."
+        && id != ".."
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.');
+    if valid {
+        Ok(())
+    } else {
+        Err(io::const_io_error!(
+            io::ErrorKind::InvalidInput,
+            "invalid tenant ID"
+        ))
+    }
+}
+
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> Result<[u8; 32], sgx_status_t> {
+    let msg = parts.concat();
+    let mut mac = [0_u8; 32];
+    let status = unsafe {
+        sgx_hmac_sha256_msg(
+            msg.as_ptr(),
+            msg.len() as i32,
+            key.as_ptr(),
+            key.len() as i32,
+            mac.as_mut_ptr(),
+            mac.len() as i32,
+        )
+    };
+    match status {
+        sgx_status_t::SGX_SUCCESS => Ok(mac),
+        status => Err(status),
+    }
+}
+
+// HKDF-Expand, with the concatenation of `info` as the info.
+fn hkdf_expand_into(prk: &[u8; 32], info: &[&[u8]], out: &mut [u8]) -> io::Result<()> {
+    if out.len() > 255 * 32 {
+        return Err(io::const_io_error!(
+            io::ErrorKind::InvalidInput,
+            "the key is too long"
+        ));
+    }
+    let mut block = [0_u8; 32];
+    let mut prev_len = 0;
+    for (i, chunk) in out.chunks_mut(32).enumerate() {
+        let counter = [i as u8 + 1];
+        let mut parts = Vec::with_capacity(info.len() + 2);
+        parts.push(&block[..prev_len]);
+        parts.extend_from_slice(info);
+        parts.push(&counter);
+        let next = hmac_sha256(prk, &parts)?;
+        block = next;
+        chunk.copy_from_slice(&block[..chunk.len()]);
+        prev_len = block.len();
+    }
+    wipe(&mut block);
+    Ok(())
+}
+
+fn hkdf_expand(prk: &[u8; 32], info: &[&[u8]]) -> io::Result<[u8; 32]> {
+    let mut key = [0_u8; 32];
+    hkdf_expand_into(prk, info, &mut key)?;
+    Ok(key)
+}
+
+fn wipe(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+}
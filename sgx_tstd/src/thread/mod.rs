@@ -59,9 +59,13 @@ pub use sgx_trts::enclave::SgxThreadPolicy;
 #[macro_use]
 mod local;
 
+#[cfg(feature = "thread")]
+mod pool;
 #[cfg(feature = "thread")]
 mod scoped;
 
+#[cfg(feature = "thread")]
+pub use pool::ThreadPool;
 #[cfg(feature = "thread")]
 pub use scoped::{scope, Scope, ScopedJoinHandle};
 
@@ -130,6 +134,8 @@ pub use self::local::statik::Key as __StaticLocalKeyInner;
 pub struct Builder {
     // A name for the thread-to-be, for identification in panic messages
     name: Option<String>,
+    // The CPUs the thread-to-be asks the host to run on
+    affinity: Option<Vec<usize>>,
 }
 
 #[cfg(feature = "thread")]
@@ -153,7 +159,7 @@ impl Builder {
     /// ```
     pub fn new() -> Builder {
         assert!(rsgx_get_thread_policy() == SgxThreadPolicy::Bound, "The sgx thread policy must be Bound!");
-        Builder { name: None }
+        Builder { name: None, affinity: None }
     }
 
     /// Names the thread-to-be. Currently the name is used for identification
@@ -192,6 +198,33 @@ impl Builder {
         self
     }
 
+    /// Asks the host to run the thread-to-be on the given CPUs only, typically
+    /// a set from [`Topology::placement`](crate::topology::Topology::placement).
+    ///
+    /// The thread applies the hint with
+    /// [`set_affinity_hint`](crate::topology::set_affinity_hint) before it runs
+    /// its closure. The hint is best effort: an invalid set or a refusal of
+    /// the host is ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::thread;
+    ///
+    /// let handler = thread::Builder::new()
+    ///     .affinity_hint(vec![0, 1])
+    ///     .spawn(|| {
+    ///         // compute
+    ///     })
+    ///     .unwrap();
+    ///
+    /// handler.join().unwrap();
+    /// ```
+    pub fn affinity_hint(mut self, cpus: Vec<usize>) -> Builder {
+        self.affinity = Some(cpus);
+        self
+    }
+
     /// Spawns a new thread by taking ownership of the `Builder`, and returns an
     /// [`io::Result`] to its [`JoinHandle`].
     ///
@@ -314,7 +347,7 @@ impl Builder {
         T: Send + 'a,
         'scope: 'a,
     {
-        let Builder { name } = self;
+        let Builder { name, affinity } = self;
 
         let my_thread = SgxThread::new(name.map(|name| {
             CString::new(name).expect("thread name may not contain interior null bytes")
@@ -334,6 +367,8 @@ impl Builder {
         #[allow(clippy::redundant_clone)]
         crate::io::set_output_capture(output_capture.clone());
 
+        let ecall_context = crate::ecall::current();
+
         // Pass `f` in `MaybeUninit` because actually that closure might *run longer than the lifetime of `F`*.
         // See <https://github.com/rust-lang/rust/issues/101983> for more details.
         // To prevent leaks we use a wrapper that drops its contents.
@@ -363,9 +398,13 @@ impl Builder {
             if let Some(name) = their_thread.cname() {
                 imp::Thread::set_name(name);
             }
+            if let Some(cpus) = affinity {
+                let _ = crate::topology::set_affinity_hint(&cpus);
+            }
 
             #[cfg(feature = "stdio")]
             crate::io::set_output_capture(output_capture);
+            crate::ecall::set_current(ecall_context);
 
             // SAFETY: we constructed `f` initialized.
             let f = f.into_inner();
@@ -1473,33 +1512,14 @@ fn _assert_sync_and_send() {
 /// approximation of the actual amount of parallelism available at any given
 /// time. To get a more detailed or precise overview of the amount of
 /// parallelism available to the program, you may wish to use
-/// platform-specific APIs as well. The following platform limitations currently
-/// apply to `available_parallelism`:
-///
-/// On Windows:
-/// - It may undercount the amount of parallelism available on systems with more
-///   than 64 logical CPUs. However, programs typically need specific support to
-///   take advantage of more than 64 logical CPUs, and in the absence of such
-///   support, the number returned by this function accurately reflects the
-///   number of logical CPUs the program can use by default.
-/// - It may overcount the amount of parallelism available on systems limited by
-///   process-wide affinity masks, or job object limitations.
-///
-/// On Linux:
-/// - It may overcount the amount of parallelism available when limited by a
-///   process-wide affinity mask or cgroup quotas and `sched_getaffinity()` or cgroup fs can't be
-///   queried, e.g. due to sandboxing.
-/// - It may undercount the amount of parallelism if the current thread's affinity mask
-///   does not reflect the process' cpuset, e.g. due to pinned threads.
-/// - If the process is in a cgroup v1 cpu controller, this may need to
-///   scan mountpoints to find the corresponding cgroup v1 controller,
-///   which may take time on systems with large numbers of mountpoints.
-///   (This does not apply to cgroup v2, or to processes not in a
-///   cgroup.)
-///
-/// On all targets:
-/// - It may overcount the amount of parallelism available when running in a VM
-/// with CPU usage limits (e.g. an overcommitted host).
+/// platform-specific APIs as well.
+///
+/// Inside an enclave, every thread runs on a TCS of its own, and the enclave
+/// has no more TCSs than its configuration sets. `available_parallelism`
+/// returns that number less the TCS of the calling thread, which is how many
+/// threads it can spawn when no other thread is running. A spawn beyond the
+/// free TCSs fails with `SGX_ERROR_OUT_OF_TCS`; [`ThreadPool`] sizes itself
+/// with this function to stay within the limit.
 ///
 /// # Errors
 ///
@@ -0,0 +1,250 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use super::{available_parallelism, Builder, JoinHandle};
+use crate::boxed::Box;
+use crate::collections::VecDeque;
+use crate::ecall::{self, EcallContext};
+use crate::fmt;
+use crate::io;
+use crate::panic::{catch_unwind, AssertUnwindSafe};
+use crate::sync::{Arc, PoisonError, SgxCondvar, SgxMutex, SgxMutexGuard};
+use crate::vec::Vec;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// A queued job, with the request context of the thread that submitted it.
+struct Task {
+    job: Job,
+    context: Option<EcallContext>,
+}
+
+/// A fixed set of worker threads that run the jobs handed to them.
+///
+/// Every worker holds a TCS for the life of the pool. [`ThreadPool::new`]
+/// never starts more workers than [`available_parallelism`] reports, so a
+/// pool cannot take the last TCS from the thread that created it, and jobs
+/// queue up instead of failing to spawn when the enclave is busy.
+///
+/// A job runs under the [`EcallContext`] of the thread that handed it to
+/// [`execute`](ThreadPool::execute), and so under its tenant, not under the
+/// context of the thread that created the pool. Between jobs, workers have no
+/// context installed.
+///
+/// A job that panics does not bring down its worker; the panic is counted in
+/// [`panic_count`](ThreadPool::panic_count). Dropping the pool lets the
+/// workers finish the queued jobs, then joins them.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::mpsc::channel;
+/// use std::thread::{self, ThreadPool};
+///
+/// let workers = thread::available_parallelism().unwrap().get();
+/// let pool = ThreadPool::new(workers).unwrap();
+///
+/// let (tx, rx) = channel();
+/// for i in 0..16 {
+///     let tx = tx.clone();
+///     pool.execute(move || tx.send(i * i).unwrap());
+/// }
+/// drop(tx);
+/// assert_eq!(rx.iter().sum::<i32>(), 1240);
+/// ```
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+struct Shared {
+    state: SgxMutex<State>,
+    // Signalled when a job is queued or the pool shuts down.
+    job_ready: SgxCondvar,
+    // Signalled when the pool runs out of work.
+    idle: SgxCondvar,
+}
+
+struct State {
+    queue: VecDeque<Task>,
+    active: usize,
+    panicked: usize,
+    shutdown: bool,
+}
+
+impl Shared {
+    fn lock(&self) -> SgxMutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn work(&self) {
+        // Workers are spawned from the thread that creates the pool and
+        // inherit its context; they must not keep it.
+        ecall::set_current(None);
+        let mut state = self.lock();
+        loop {
+            if let Some(task) = state.queue.pop_front() {
+                state.active += 1;
+                drop(state);
+                ecall::set_current(task.context);
+                let result = catch_unwind(AssertUnwindSafe(task.job));
+                ecall::set_current(None);
+                state = self.lock();
+                state.active -= 1;
+                if result.is_err() {
+                    state.panicked += 1;
+                }
+                if state.active == 0 && state.queue.is_empty() {
+                    self.idle.notify_all();
+                }
+            } else if state.shutdown {
+                return;
+            } else {
+                state = self
+                    .job_ready
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+        }
+    }
+}
+
+impl ThreadPool {
+    /// Starts a pool of `size` workers, or of [`available_parallelism`]
+    /// workers if that is less.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `size` is zero, or if a worker cannot be spawned, which is
+    /// the case when other threads hold the TCSs the pool was counting on.
+    /// The workers started so far are joined before returning.
+    pub fn new(size: usize) -> io::Result<ThreadPool> {
+        ThreadPool::with_builder(size, Builder::new)
+    }
+
+    /// Starts a pool as [`ThreadPool::new`] does, with workers named
+    /// `"{name}-{index}"`.
+    pub fn with_name(name: &str, size: usize) -> io::Result<ThreadPool> {
+        let mut index = 0;
+        ThreadPool::with_builder(size, || {
+            index += 1;
+            Builder::new().name(format!("{}-{}", name, index - 1))
+        })
+    }
+
+    fn with_builder<B>(size: usize, mut builder: B) -> io::Result<ThreadPool>
+    where
+        B: FnMut() -> Builder,
+    {
+        if size == 0 {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "a thread pool needs at least one worker",
+            ));
+        }
+        let size = size.min(available_parallelism()?.get());
+
+        let mut pool = ThreadPool {
+            shared: Arc::new(Shared {
+                state: SgxMutex::new(State {
+                    queue: VecDeque::new(),
+                    active: 0,
+                    panicked: 0,
+                    shutdown: false,
+                }),
+                job_ready: SgxCondvar::new(),
+                idle: SgxCondvar::new(),
+            }),
+            workers: Vec::with_capacity(size),
+        };
+        for _ in 0..size {
+            let shared = pool.shared.clone();
+            // On failure, dropping the pool joins the workers started so far.
+            let worker = builder().spawn(move || shared.work())?;
+            pool.workers.push(worker);
+        }
+        Ok(pool)
+    }
+
+    /// Queues `job` to run on one of the workers, under the request context
+    /// of the calling thread.
+    pub fn execute<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let task = Task {
+            job: Box::new(job),
+            context: ecall::current(),
+        };
+        self.shared.lock().queue.push_back(task);
+        self.shared.job_ready.notify_one();
+    }
+
+    /// Blocks until the queue is empty and no job is running.
+    pub fn join(&self) {
+        let mut state = self.shared.lock();
+        while state.active > 0 || !state.queue.is_empty() {
+            state = self
+                .shared
+                .idle
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Returns the number of workers.
+    pub fn max_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Returns the number of jobs running.
+    pub fn active_count(&self) -> usize {
+        self.shared.lock().active
+    }
+
+    /// Returns the number of jobs waiting for a worker.
+    pub fn queued_count(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    /// Returns the number of jobs that panicked.
+    pub fn panic_count(&self) -> usize {
+        self.shared.lock().panicked
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shared.lock().shutdown = true;
+        self.shared.job_ready.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl fmt::Debug for ThreadPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.shared.lock();
+        f.debug_struct("ThreadPool")
+            .field("max_count", &self.workers.len())
+            .field("active_count", &state.active)
+            .field("queued_count", &state.queue.len())
+            .field("panic_count", &state.panicked)
+            .finish()
+    }
+}
@@ -139,6 +139,14 @@ impl ScopeData {
 /// such as local variables defined right before the scope, can be borrowed by the scoped threads.
 ///
 /// The `'env: 'scope` bound is part of the definition of the `Scope` type.
+///
+/// # Enclave threads
+///
+/// Every scoped thread runs on a TCS of its own from the moment it is spawned
+/// until it is joined, and the thread calling `scope` keeps its TCS while it
+/// waits for them. A scope with `n` threads thus holds `n + 1` TCSs at once;
+/// [`Scope::spawn`] panics when the enclave has none left, while
+/// [`Builder::spawn_scoped`] reports it as an error.
 #[track_caller]
 pub fn scope<'env, F, T>(f: F) -> T
 where
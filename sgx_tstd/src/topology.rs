@@ -0,0 +1,316 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Host CPU topology and affinity hints.
+//!
+//! Compute-heavy enclaves on multi-socket machines lose much of their
+//! throughput when the host scheduler migrates the threads running their TCS
+//! between sockets. [`Topology::host`] asks the host which CPUs are online,
+//! and on which core, package and NUMA node each of them sits, and
+//! [`Topology::placement`] turns that into one CPU set per worker of a pool.
+//! A worker then calls [`set_affinity_hint`], or is spawned with
+//! [`thread::Builder::affinity_hint`](crate::thread::Builder::affinity_hint),
+//! to ask the host to keep the host thread running its TCS on those CPUs.
+//!
+//! The host is not trusted. The topology it reports is checked to be
+//! well-formed: every identifier is in range and no CPU appears twice. It is
+//! not checked to be true, and the host is free to ignore the hints. Use
+//! both for performance only, never for security decisions.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::thread;
+//! use std::topology::{Placement, Topology};
+//!
+//! let topology = Topology::host()?;
+//! let workers = topology.len();
+//! for cpus in topology.placement(workers, Placement::Spread) {
+//!     thread::Builder::new()
+//!         .affinity_hint(cpus)
+//!         .spawn(|| {
+//!             // compute
+//!         })?;
+//! }
+//! ```
+
+use crate::collections::{BTreeMap, BTreeSet};
+use crate::io;
+use crate::mem;
+use crate::vec::Vec;
+use sgx_libc as libc;
+
+/// The largest CPU identifier plus one, as in the host's `cpu_set_t`.
+pub const MAX_CPUS: usize = libc::CPU_SETSIZE as usize;
+
+/// The largest NUMA node identifier plus one.
+pub const MAX_NODES: u32 = 1024;
+
+// Core identifiers are sparse on some machines, but stay far below this.
+const MAX_CORE_ID: u32 = 1 << 16;
+
+/// An online host CPU.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Cpu {
+    id: usize,
+    core: u32,
+    package: u32,
+    node: u32,
+}
+
+impl Cpu {
+    /// The identifier of the CPU, below [`MAX_CPUS`].
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The identifier of the physical core, unique within the package.
+    pub fn core(&self) -> u32 {
+        self.core
+    }
+
+    /// The identifier of the package (socket).
+    pub fn package(&self) -> u32 {
+        self.package
+    }
+
+    /// The NUMA node of the CPU, below [`MAX_NODES`].
+    pub fn node(&self) -> u32 {
+        self.node
+    }
+}
+
+/// How [`Topology::placement`] assigns CPUs to workers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Placement {
+    /// One CPU per worker, filling one node after the other, so that workers
+    /// share caches. Suits workers that share much data.
+    Compact,
+    /// One CPU per worker, round robin over the nodes, and over distinct
+    /// physical cores before their hyperthread siblings. Suits independent
+    /// workers.
+    Spread,
+    /// All CPUs of one node per worker, round robin over the nodes. Keeps
+    /// memory local while leaving the host free to balance within the node.
+    Node,
+}
+
+/// The online CPUs of the host, sorted by identifier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Topology {
+    cpus: Vec<Cpu>,
+}
+
+impl Topology {
+    /// Queries the topology of the host.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if the host reports an
+    /// empty or malformed topology, and with the error of the OCALL if the
+    /// host cannot report it.
+    pub fn host() -> io::Result<Topology> {
+        let mut entries = vec![0_u32; 4 * 64];
+        loop {
+            let mut ncpus = 0;
+            let ret = unsafe {
+                libc::ocall::cpu_topology(entries.as_mut_ptr(), entries.len(), &mut ncpus)
+            };
+            if ret == 0 {
+                entries.truncate(4 * ncpus);
+                return Topology::from_entries(&entries);
+            }
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::ERANGE) || ncpus > MAX_CPUS {
+                return Err(err);
+            }
+            entries.resize(4 * ncpus, 0);
+        }
+    }
+
+    fn from_entries(entries: &[u32]) -> io::Result<Topology> {
+        let invalid = || {
+            io::const_io_error!(
+                io::ErrorKind::InvalidData,
+                "the host reported a malformed CPU topology",
+            )
+        };
+
+        let mut cpus = Vec::with_capacity(entries.len() / 4);
+        for entry in entries.chunks_exact(4) {
+            let cpu = Cpu {
+                id: entry[0] as usize,
+                core: entry[1],
+                package: entry[2],
+                node: entry[3],
+            };
+            let ordered = cpus.last().map_or(true, |last: &Cpu| last.id < cpu.id);
+            if !ordered
+                || cpu.id >= MAX_CPUS
+                || cpu.core >= MAX_CORE_ID
+                || cpu.package as usize >= MAX_CPUS
+                || cpu.node >= MAX_NODES
+            {
+                return Err(invalid());
+            }
+            cpus.push(cpu);
+        }
+        if cpus.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Topology { cpus })
+    }
+
+    /// The online CPUs, sorted by identifier.
+    pub fn cpus(&self) -> &[Cpu] {
+        &self.cpus
+    }
+
+    /// Returns the CPU with the identifier `id`, if it is online.
+    pub fn cpu(&self, id: usize) -> Option<&Cpu> {
+        self.cpus
+            .binary_search_by_key(&id, |cpu| cpu.id)
+            .ok()
+            .map(|index| &self.cpus[index])
+    }
+
+    /// The number of online CPUs.
+    pub fn len(&self) -> usize {
+        self.cpus.len()
+    }
+
+    /// Always false: a topology has at least one CPU.
+    pub fn is_empty(&self) -> bool {
+        self.cpus.is_empty()
+    }
+
+    /// The NUMA nodes with online CPUs, sorted.
+    pub fn nodes(&self) -> Vec<u32> {
+        let nodes: BTreeSet<u32> = self.cpus.iter().map(|cpu| cpu.node).collect();
+        nodes.into_iter().collect()
+    }
+
+    /// The packages with online CPUs, sorted.
+    pub fn packages(&self) -> Vec<u32> {
+        let packages: BTreeSet<u32> = self.cpus.iter().map(|cpu| cpu.package).collect();
+        packages.into_iter().collect()
+    }
+
+    /// The identifiers of the online CPUs of `node`.
+    pub fn node_cpus(&self, node: u32) -> Vec<usize> {
+        self.cpus
+            .iter()
+            .filter(|cpu| cpu.node == node)
+            .map(|cpu| cpu.id)
+            .collect()
+    }
+
+    /// Assigns a CPU set to each of `workers` workers.
+    ///
+    /// Workers beyond the number of CPUs (or of nodes, for
+    /// [`Placement::Node`]) wrap around and share.
+    pub fn placement(&self, workers: usize, placement: Placement) -> Vec<Vec<usize>> {
+        let nodes = self.nodes();
+        match placement {
+            Placement::Compact => {
+                let mut order: Vec<&Cpu> = self.cpus.iter().collect();
+                order.sort_by_key(|cpu| (cpu.node, cpu.package, cpu.core, cpu.id));
+                (0..workers)
+                    .map(|worker| vec![order[worker % order.len()].id])
+                    .collect()
+            }
+            Placement::Spread => {
+                // Per node: the first CPU of each core, then the siblings.
+                let per_node: Vec<Vec<usize>> = nodes
+                    .iter()
+                    .map(|&node| {
+                        let mut siblings = BTreeMap::new();
+                        let mut keys: Vec<(usize, u32, u32, usize)> = self
+                            .cpus
+                            .iter()
+                            .filter(|cpu| cpu.node == node)
+                            .map(|cpu| {
+                                let rank = siblings.entry((cpu.package, cpu.core)).or_insert(0);
+                                *rank += 1;
+                                (*rank, cpu.package, cpu.core, cpu.id)
+                            })
+                            .collect();
+                        keys.sort_unstable();
+                        keys.into_iter().map(|key| key.3).collect()
+                    })
+                    .collect();
+                let mut order = Vec::with_capacity(self.cpus.len());
+                let deepest = per_node.iter().map(Vec::len).max().unwrap_or(0);
+                for depth in 0..deepest {
+                    order.extend(per_node.iter().filter_map(|cpus| cpus.get(depth)));
+                }
+                (0..workers)
+                    .map(|worker| vec![order[worker % order.len()]])
+                    .collect()
+            }
+            Placement::Node => (0..workers)
+                .map(|worker| self.node_cpus(nodes[worker % nodes.len()]))
+                .collect(),
+        }
+    }
+}
+
+fn cpu_set(cpus: &[usize]) -> io::Result<libc::cpu_set_t> {
+    if cpus.is_empty() || cpus.iter().any(|&cpu| cpu >= MAX_CPUS) {
+        return Err(io::const_io_error!(
+            io::ErrorKind::InvalidInput,
+            "CPU set is empty or out of range",
+        ));
+    }
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    for &cpu in cpus {
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    Ok(set)
+}
+
+/// Asks the host to run the calling thread on `cpus` only.
+///
+/// The hint binds the host thread currently running the TCS of the caller.
+/// With the `Bound` thread policy that thread stays with the TCS, so the
+/// hint holds for the lifetime of the enclave thread. The host may ignore it.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] if `cpus` is empty or holds an
+/// identifier of at least [`MAX_CPUS`], and with the error of the host
+/// otherwise, for example if none of `cpus` is online.
+pub fn set_affinity_hint(cpus: &[usize]) -> io::Result<()> {
+    let set = cpu_set(cpus)?;
+    let ret = unsafe { libc::ocall::sched_setaffinity(0, mem::size_of_val(&set), &set) };
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The CPUs the host reports the calling thread may run on.
+pub fn affinity_hint() -> io::Result<Vec<usize>> {
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    let ret = unsafe { libc::ocall::sched_getaffinity(0, mem::size_of_val(&set), &mut set) };
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((0..MAX_CPUS)
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .collect())
+}
@@ -15,6 +15,8 @@
 // specific language governing permissions and limitations
 // under the License..
 
+#[cfg(not(minimal_tcb))]
 pub mod fs;
+#[cfg(not(minimal_tcb))]
 pub mod path;
 pub mod time;
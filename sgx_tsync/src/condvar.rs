@@ -0,0 +1,131 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use core::fmt;
+use core::time::Duration;
+
+use sgx_libc as libc;
+
+use crate::mutex::MutexGuard;
+use crate::sys;
+
+/// A type indicating whether a timed wait on a condition variable returned
+/// due to a time out or not.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct WaitTimeoutResult(bool);
+
+impl WaitTimeoutResult {
+    /// Returns `true` if the wait was known to have timed out.
+    #[must_use]
+    pub fn timed_out(&self) -> bool {
+        self.0
+    }
+}
+
+/// A condition variable, the `no_std` counterpart of
+/// `sgx_tstd::sync::SgxCondvar`.
+///
+/// # Examples
+///
+/// ```
+/// use sgx_tsync::{Condvar, Mutex};
+///
+/// let ready = Mutex::new(false);
+/// let cvar = Condvar::new();
+///
+/// // Another thread sets `*ready = true` and calls `cvar.notify_one()`.
+/// let mut guard = ready.lock();
+/// while !*guard {
+///     guard = cvar.wait(guard);
+/// }
+/// ```
+pub struct Condvar {
+    inner: sys::MovableCondvar,
+}
+
+impl Condvar {
+    /// Creates a new condition variable which is ready to be waited on and
+    /// notified.
+    #[inline]
+    pub const fn new() -> Condvar {
+        Condvar { inner: sys::MovableCondvar::new() }
+    }
+
+    /// Blocks the current thread until this condition variable receives a
+    /// notification.
+    ///
+    /// The mutex behind `guard` is released while waiting and re-acquired
+    /// before returning. Spurious wakeups are possible.
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        unsafe {
+            let r = self.inner.wait(guard.mutex().raw());
+            debug_assert_eq!(r, Ok(()));
+        }
+        guard
+    }
+
+    /// Blocks the current thread until `condition` returns `false`.
+    pub fn wait_while<'a, T, F>(&self, mut guard: MutexGuard<'a, T>, mut condition: F) -> MutexGuard<'a, T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    /// Waits on this condition variable for a notification, timing out after
+    /// the specified duration.
+    pub fn wait_timeout<'a, T>(&self, guard: MutexGuard<'a, T>, dur: Duration) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        let timed_out = unsafe {
+            match self.inner.wait_timeout(guard.mutex().raw(), dur) {
+                Ok(_) => false,
+                Err(e) => e == libc::ETIMEDOUT,
+            }
+        };
+        (guard, WaitTimeoutResult(timed_out))
+    }
+
+    /// Wakes up one blocked thread on this condvar.
+    #[inline]
+    pub fn notify_one(&self) {
+        unsafe {
+            let _ = self.inner.notify_one();
+        }
+    }
+
+    /// Wakes up all blocked threads on this condvar.
+    #[inline]
+    pub fn notify_all(&self) {
+        unsafe {
+            let _ = self.inner.notify_all();
+        }
+    }
+}
+
+impl fmt::Debug for Condvar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Condvar").finish_non_exhaustive()
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Condvar {
+        Condvar::new()
+    }
+}
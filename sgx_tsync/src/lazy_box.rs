@@ -19,10 +19,11 @@
 
 // This is used to wrap pthread {Mutex, Condvar, RwLock} in.
 
-use crate::marker::PhantomData;
-use crate::ops::{Deref, DerefMut};
-use crate::ptr::null_mut;
-use crate::sync::atomic::{
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr::null_mut;
+use core::sync::atomic::{
     AtomicPtr,
     Ordering::{AcqRel, Acquire},
 };
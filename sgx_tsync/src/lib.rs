@@ -0,0 +1,74 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! # Trusted synchronization primitives without the std facade
+//!
+//! This crate holds the lock implementations that back `sgx_tstd::sync`
+//! and exposes them to `#![no_std]` enclaves that only link `alloc`.
+//! Small enclaves, for example ones that do nothing but produce a report
+//! or a quote, can pull in `sgx_tsync`, `sgx_tcrypto` and `sgx_tseal`
+//! without linking the rest of `sgx_tstd` (I/O, threads, unwinding,
+//! backtraces, untrusted file system), which keeps the trusted computing
+//! base small.
+//!
+//! The following primitives are provided:
+//!
+//! * [`Mutex`], a mutual exclusion lock protecting a value.
+//! * [`RwLock`], a reader-writer lock protecting a value.
+//! * [`Condvar`], a condition variable used with [`Mutex`].
+//! * [`SgxSpinlock`], a spin lock that never leaves the enclave.
+//!
+//! Unlike their `sgx_tstd::sync` counterparts these locks do not track
+//! poisoning: without the std panic runtime a panicking thread cannot be
+//! observed, so `lock` returns the guard directly.
+//!
+//! The raw, unsafe lock types used by `sgx_tstd` live in [`sys`]. Blocking
+//! waits use the `sgx_thread_wait_untrusted_event_ocall` family of OCALLs,
+//! so the enclave EDL must import `sgx_tstdc.edl`.
+
+#![no_std]
+#![cfg_attr(target_env = "sgx", feature(rustc_private))]
+#![feature(linked_list_remove)]
+#![feature(negative_impls)]
+#![allow(non_camel_case_types)]
+#![allow(clippy::missing_safety_doc)]
+#![allow(clippy::new_without_default)]
+
+#[cfg(target_env = "sgx")]
+extern crate sgx_types;
+
+#[cfg(target_env = "sgx")]
+extern crate sgx_libc;
+
+#[cfg(target_env = "sgx")]
+extern crate sgx_trts;
+
+extern crate alloc;
+
+pub mod lazy_box;
+pub mod sys;
+
+mod condvar;
+mod mutex;
+mod rwlock;
+mod spinlock;
+
+pub use self::condvar::{Condvar, WaitTimeoutResult};
+pub use self::mutex::{Mutex, MutexGuard};
+pub use self::rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use self::spinlock::{SgxSpinlock, SgxSpinlockGuard, SgxThreadSpinlock};
+pub use self::sys::RwLockPolicy;
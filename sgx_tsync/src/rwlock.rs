@@ -0,0 +1,251 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::time::Duration;
+
+use crate::sys::{self, RwLockPolicy};
+
+/// A reader-writer lock.
+///
+/// This is the `no_std` counterpart of `sgx_tstd::sync::SgxRwLock`. Any
+/// number of readers or at most one writer may hold the lock at a time.
+/// Poisoning is not tracked. See [`RwLockPolicy`] for the order in which
+/// waiting readers and writers enter.
+///
+/// # Examples
+///
+/// ```
+/// use sgx_tsync::RwLock;
+///
+/// let lock = RwLock::new(5);
+/// {
+///     let r1 = lock.read();
+///     let r2 = lock.read();
+///     assert_eq!(*r1 + *r2, 10);
+/// }
+/// *lock.write() += 1;
+/// assert_eq!(*lock.read(), 6);
+/// ```
+pub struct RwLock<T: ?Sized> {
+    inner: sys::MovableRwLock,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+/// RAII structure used to release the shared read access of a lock when
+/// dropped.
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct RwLockReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T: ?Sized> !Send for RwLockReadGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for RwLockReadGuard<'_, T> {}
+
+/// RAII structure used to release the exclusive write access of a lock when
+/// dropped.
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T: ?Sized> !Send for RwLockWriteGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for RwLockWriteGuard<'_, T> {}
+
+impl<T> RwLock<T> {
+    /// Creates a new instance of an `RwLock<T>` which is unlocked.
+    #[inline]
+    pub const fn new(t: T) -> RwLock<T> {
+        RwLock { inner: sys::MovableRwLock::new(), data: UnsafeCell::new(t) }
+    }
+
+    /// Creates a new instance of an `RwLock<T>` which is unlocked and admits
+    /// waiters according to `policy`.
+    pub fn with_policy(t: T, policy: RwLockPolicy) -> RwLock<T> {
+        let lock = RwLock::new(t);
+        unsafe { lock.inner.set_policy(policy) };
+        lock
+    }
+
+    /// Consumes this `RwLock`, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Locks this rwlock with shared read access, blocking the current thread
+    /// until it can be acquired.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        unsafe {
+            let r = self.inner.read();
+            debug_assert_eq!(r, Ok(()));
+            RwLockReadGuard { lock: self }
+        }
+    }
+
+    /// Locks this rwlock with shared read access, blocking the current thread
+    /// for at most about `dur`.
+    ///
+    /// Returns `None` if the lock could not be acquired in time.
+    pub fn read_timeout(&self, dur: Duration) -> Option<RwLockReadGuard<'_, T>> {
+        unsafe {
+            match self.inner.read_timeout(dur) {
+                Ok(_) => Some(RwLockReadGuard { lock: self }),
+                Err(_) => None,
+            }
+        }
+    }
+
+    /// Attempts to acquire this rwlock with shared read access without
+    /// blocking.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        unsafe {
+            match self.inner.try_read() {
+                Ok(_) => Some(RwLockReadGuard { lock: self }),
+                Err(_) => None,
+            }
+        }
+    }
+
+    /// Locks this rwlock with exclusive write access, blocking the current
+    /// thread until it can be acquired.
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        unsafe {
+            let r = self.inner.write();
+            debug_assert_eq!(r, Ok(()));
+            RwLockWriteGuard { lock: self }
+        }
+    }
+
+    /// Locks this rwlock with exclusive write access, blocking the current
+    /// thread for at most about `dur`.
+    ///
+    /// Returns `None` if the lock could not be acquired in time.
+    pub fn write_timeout(&self, dur: Duration) -> Option<RwLockWriteGuard<'_, T>> {
+        unsafe {
+            match self.inner.write_timeout(dur) {
+                Ok(_) => Some(RwLockWriteGuard { lock: self }),
+                Err(_) => None,
+            }
+        }
+    }
+
+    /// Attempts to lock this rwlock with exclusive write access without
+    /// blocking.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        unsafe {
+            match self.inner.try_write() {
+                Ok(_) => Some(RwLockWriteGuard { lock: self }),
+                Err(_) => None,
+            }
+        }
+    }
+
+    /// Returns the policy that decides whether waiting readers or writers
+    /// enter first.
+    pub fn policy(&self) -> RwLockPolicy {
+        unsafe { self.inner.policy() }
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> RwLock<T> {
+        RwLock::new(Default::default())
+    }
+}
+
+impl<T> From<T> for RwLock<T> {
+    fn from(t: T) -> Self {
+        RwLock::new(t)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("RwLock");
+        match self.try_read() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            let r = self.lock.inner.read_unlock();
+            debug_assert_eq!(r, Ok(()));
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            let r = self.lock.inner.write_unlock();
+            debug_assert_eq!(r, Ok(()));
+        }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
@@ -15,21 +15,21 @@
 // specific language governing permissions and limitations
 // under the License..
 
-use crate::boxed::Box;
-use crate::cell::UnsafeCell;
-use crate::collections::LinkedList;
-use crate::io::{self, Error};
-use crate::sync::SgxThreadSpinlock;
-use crate::sys_common::lazy_box::{LazyBox, LazyInit};
-use crate::sys::locks::mutex::{self, Mutex};
-use crate::thread::rsgx_thread_self;
-use crate::time::Duration;
-use crate::u64;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use alloc::collections::LinkedList;
+use crate::spinlock::SgxThreadSpinlock;
+use crate::lazy_box::{LazyBox, LazyInit};
+use crate::sys::mutex::{self, Mutex};
+use core::time::Duration;
 
 use sgx_libc as libc;
 use sgx_trts::enclave::SgxThreadData;
 use sgx_types::{sgx_thread_t, SysError, SGX_THREAD_T_NULL};
 
+use super::rsgx_thread_self;
+
 pub struct Condvar {
     inner: UnsafeCell<CondvarInner>,
 }
@@ -137,7 +137,7 @@ impl CondvarInner {
             }
         }
         self.lock.unlock();
-        mutex.lock();
+        let _ = mutex.lock();
         Ok(())
     }
 
@@ -154,7 +154,7 @@ impl CondvarInner {
         let mut ret = Ok(());
         loop {
             self.lock.unlock();
-            let mut result = 0;
+            let result;
             if waiter == SGX_THREAD_T_NULL {
                 result = mutex::thread_wait_event(SgxThreadData::current().get_tcs(), dur);
             } else {
@@ -173,7 +173,7 @@ impl CondvarInner {
                 .position(|&waiter| waiter == rsgx_thread_self())
             {
                 Some(pos) => {
-                    if result < 0 && Error::last_os_error().kind() == io::ErrorKind::TimedOut {
+                    if result < 0 && libc::errno() == libc::ETIMEDOUT {
                         self.queue.remove(pos);
                         ret = Err(libc::ETIMEDOUT);
                         break;
@@ -183,7 +183,7 @@ impl CondvarInner {
             }
         }
         self.lock.unlock();
-        mutex.lock();
+        let _ = mutex.lock();
         ret
     }
 
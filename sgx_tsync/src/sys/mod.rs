@@ -0,0 +1,38 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Raw enclave locks.
+//!
+//! These are the unsafe building blocks behind the safe wrappers in this
+//! crate and behind `sgx_tstd::sync`. Callers are responsible for pairing
+//! every lock with an unlock on the same thread.
+
+use sgx_types::{sgx_thread_self, sgx_thread_t};
+
+pub mod condvar;
+pub mod mutex;
+pub mod rwlock;
+
+pub use self::condvar::{Condvar, MovableCondvar};
+pub use self::mutex::{AdaptiveMutex, MovableAdaptiveMutex, MutexStats};
+pub use self::mutex::{MovableMutex, MovableReentrantMutex, Mutex, ReentrantMutex};
+pub use self::rwlock::{MovableRwLock, RwLock, RwLockPolicy};
+
+#[inline]
+pub(crate) fn rsgx_thread_self() -> sgx_thread_t {
+    unsafe { sgx_thread_self() }
+}
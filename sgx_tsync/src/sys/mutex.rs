@@ -15,17 +15,17 @@
 // specific language governing permissions and limitations
 // under the License..
 
-use crate::boxed::Box;
-use crate::cell::UnsafeCell;
-use crate::cmp;
-use crate::mem;
-use crate::collections::LinkedList;
-use crate::ptr;
-use crate::sync::SgxThreadSpinlock;
-use crate::sys_common::lazy_box::{LazyBox, LazyInit};
-use crate::thread::rsgx_thread_self;
-use crate::time::Duration;
-use crate::u64;
+use alloc::boxed::Box;
+use core::cell::UnsafeCell;
+use core::cmp;
+use core::hint;
+use core::mem;
+use alloc::collections::LinkedList;
+use core::ptr;
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use crate::spinlock::SgxThreadSpinlock;
+use crate::lazy_box::{LazyBox, LazyInit};
+use core::time::Duration;
 
 use sgx_libc as libc;
 use sgx_libc::{c_int, c_long, c_void, time_t, timespec};
@@ -33,6 +33,8 @@ use sgx_trts::enclave::SgxThreadData;
 use sgx_trts::error::set_errno;
 use sgx_types::{self, sgx_status_t, sgx_thread_t, SysError, SGX_THREAD_T_NULL};
 
+use super::rsgx_thread_self;
+
 pub struct Mutex {
     inner: UnsafeCell<MutexInner>,
 }
@@ -208,6 +210,212 @@ impl Drop for ReentrantMutex {
     }
 }
 
+/// Counters of an [`AdaptiveMutex`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MutexStats {
+    /// Locks taken.
+    pub acquisitions: u64,
+    /// Locks that were held by another thread when asked for.
+    pub contended: u64,
+    /// Contended locks taken while spinning, without sleeping.
+    pub spun: u64,
+    /// Sleeps outside the enclave waiting for the lock.
+    pub sleeps: u64,
+    /// Timed locks that gave up.
+    pub timeouts: u64,
+}
+
+/// A mutex that spins for a while before sleeping, and that can give up.
+///
+/// A contended `Mutex` sleeps on an OCALL straight away, and a thread woken
+/// when the lock is released pays for a second OCALL to get back in. Locks
+/// held for a short time are cheaper to wait for inside the enclave, so this
+/// mutex retries `spin_limit` times before it sleeps. It also counts how
+/// often it is contended, for profiling.
+pub struct AdaptiveMutex {
+    inner: UnsafeCell<MutexInner>,
+    spin_limit: AtomicU32,
+    acquisitions: AtomicU64,
+    contended: AtomicU64,
+    spun: AtomicU64,
+    sleeps: AtomicU64,
+    timeouts: AtomicU64,
+}
+
+pub type MovableAdaptiveMutex = LazyBox<AdaptiveMutex>;
+
+unsafe impl Send for AdaptiveMutex {}
+unsafe impl Sync for AdaptiveMutex {}
+
+impl LazyInit for AdaptiveMutex {
+    fn init() -> Box<Self> {
+        Box::new(Self::new())
+    }
+
+    fn destroy(mutex: Box<Self>) {
+        if unsafe { !mutex.is_locked() } {
+            drop(mutex);
+        } else {
+            mem::forget(mutex);
+        }
+    }
+
+    fn cancel_init(_: Box<Self>) {}
+}
+
+impl AdaptiveMutex {
+    /// The number of attempts at a held lock before sleeping, by default.
+    pub const DEFAULT_SPIN_LIMIT: u32 = 100;
+
+    pub const fn new() -> Self {
+        AdaptiveMutex {
+            inner: UnsafeCell::new(MutexInner::new(MutexControl::SGX_THREAD_MUTEX_NONRECURSIVE)),
+            spin_limit: AtomicU32::new(Self::DEFAULT_SPIN_LIMIT),
+            acquisitions: AtomicU64::new(0),
+            contended: AtomicU64::new(0),
+            spun: AtomicU64::new(0),
+            sleeps: AtomicU64::new(0),
+            timeouts: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub unsafe fn lock(&self) -> SysError {
+        if self.spin() {
+            return Ok(());
+        }
+        let mutex = &mut *self.inner.get();
+        mutex.lock_until(None, &self.sleeps)?;
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Locks the mutex, or fails with `ETIMEDOUT` once `dur` has passed.
+    ///
+    /// The time is read from the monotonic clock of the host, which can make
+    /// the lock give up early, but not wait for longer than the host lets it.
+    pub unsafe fn lock_timeout(&self, dur: Duration) -> SysError {
+        if self.spin() {
+            return Ok(());
+        }
+        let deadline = monotonic_now().map(|now| now.saturating_add(dur));
+        let mutex = &mut *self.inner.get();
+        match mutex.lock_until(Some(deadline.unwrap_or_default()), &self.sleeps) {
+            Ok(()) => {
+                self.acquisitions.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(e) => {
+                if e == libc::ETIMEDOUT {
+                    self.timeouts.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    #[inline]
+    pub unsafe fn try_lock(&self) -> SysError {
+        let mutex = &mut *self.inner.get();
+        let r = mutex.try_lock();
+        if r.is_ok() {
+            self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        }
+        r
+    }
+
+    #[inline]
+    pub unsafe fn unlock(&self) -> SysError {
+        let mutex = &mut *self.inner.get();
+        mutex.unlock()
+    }
+
+    #[inline]
+    pub unsafe fn destroy(&self) -> SysError {
+        let mutex = &mut *self.inner.get();
+        mutex.destroy()
+    }
+
+    /// Sets the number of attempts at a held lock before sleeping. Zero makes
+    /// the mutex sleep straight away, as `Mutex` does.
+    pub fn set_spin_limit(&self, limit: u32) {
+        self.spin_limit.store(limit, Ordering::Relaxed);
+    }
+
+    pub fn spin_limit(&self) -> u32 {
+        self.spin_limit.load(Ordering::Relaxed)
+    }
+
+    pub fn stats(&self) -> MutexStats {
+        MutexStats {
+            acquisitions: self.acquisitions.load(Ordering::Relaxed),
+            contended: self.contended.load(Ordering::Relaxed),
+            spun: self.spun.load(Ordering::Relaxed),
+            sleeps: self.sleeps.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset_stats(&self) {
+        self.acquisitions.store(0, Ordering::Relaxed);
+        self.contended.store(0, Ordering::Relaxed);
+        self.spun.store(0, Ordering::Relaxed);
+        self.sleeps.store(0, Ordering::Relaxed);
+        self.timeouts.store(0, Ordering::Relaxed);
+    }
+
+    // Takes the lock if it is free, or once it is freed within the spin
+    // limit. Returns false if the lock is still held, and the caller should
+    // sleep.
+    unsafe fn spin(&self) -> bool {
+        let mutex = &mut *self.inner.get();
+        if mutex.try_lock().is_ok() {
+            self.acquisitions.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+        self.contended.fetch_add(1, Ordering::Relaxed);
+        for i in 0..self.spin_limit() {
+            // Back off a little more on each attempt, to keep the spinlock
+            // of the mutex free for its owner.
+            for _ in 0..1 << cmp::min(i, 6) {
+                hint::spin_loop();
+            }
+            if mutex.try_lock().is_ok() {
+                self.acquisitions.fetch_add(1, Ordering::Relaxed);
+                self.spun.fetch_add(1, Ordering::Relaxed);
+                return true;
+            }
+        }
+        false
+    }
+
+    #[inline]
+    unsafe fn is_locked(&self) -> bool {
+        let mutex = &*self.inner.get();
+        mutex.is_locked()
+    }
+}
+
+impl Drop for AdaptiveMutex {
+    #[inline]
+    fn drop(&mut self) {
+        let r = unsafe { self.destroy() };
+        debug_assert_eq!(r, Ok(()));
+    }
+}
+
+fn monotonic_now() -> Option<Duration> {
+    let mut ts = timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let r = unsafe { libc::ocall::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    if r != 0 || ts.tv_sec < 0 || !(0..1_000_000_000).contains(&ts.tv_nsec) {
+        return None;
+    }
+    Some(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
 #[derive(Copy, PartialEq, Eq, Clone, Debug)]
 pub enum MutexControl {
     SGX_THREAD_MUTEX_NONRECURSIVE = 1,
@@ -269,6 +477,68 @@ impl MutexInner {
         }
     }
 
+    // Locks the mutex as `lock` does, giving up with `ETIMEDOUT` once the
+    // monotonic clock reaches `deadline`. Counts the sleeps in `sleeps`.
+    unsafe fn lock_until(&mut self, deadline: Option<Duration>, sleeps: &AtomicU64) -> SysError {
+        let forever = Duration::new(u64::MAX, 1_000_000_000 - 1);
+        loop {
+            self.lock.lock();
+            if self.owner == SGX_THREAD_T_NULL
+                && (self.queue.front() == Some(&rsgx_thread_self()) || self.queue.front().is_none())
+            {
+                if self.queue.front() == Some(&rsgx_thread_self()) {
+                    self.queue.pop_front();
+                }
+
+                self.owner = rsgx_thread_self();
+                self.refcount += 1;
+                self.lock.unlock();
+                return Ok(());
+            }
+
+            let timeout = match deadline {
+                None => forever,
+                Some(deadline) => match monotonic_now().and_then(|now| deadline.checked_sub(now)) {
+                    Some(left) if !left.is_zero() => left,
+                    _ => {
+                        let next = self.leave_queue();
+                        self.lock.unlock();
+                        if next != SGX_THREAD_T_NULL {
+                            thread_set_event(SgxThreadData::from_raw(next).get_tcs());
+                        }
+                        return Err(libc::ETIMEDOUT);
+                    }
+                },
+            };
+
+            if !self.queue.contains(&rsgx_thread_self()) {
+                self.queue.push_back(rsgx_thread_self());
+            }
+
+            self.lock.unlock();
+            sleeps.fetch_add(1, Ordering::Relaxed);
+            thread_wait_event(SgxThreadData::current().get_tcs(), timeout);
+        }
+    }
+
+    // Removes the current thread from the queue. If it was first in line for
+    // a free mutex, the wakeup it may have been sent is passed on: returns
+    // the thread now first in line, which the caller must wake.
+    unsafe fn leave_queue(&mut self) -> sgx_thread_t {
+        let was_first = self.queue.front() == Some(&rsgx_thread_self());
+        let mut rest = LinkedList::new();
+        while let Some(waiter) = self.queue.pop_front() {
+            if waiter != rsgx_thread_self() {
+                rest.push_back(waiter);
+            }
+        }
+        self.queue = rest;
+        match self.queue.front() {
+            Some(&next) if was_first && self.owner == SGX_THREAD_T_NULL => next,
+            _ => SGX_THREAD_T_NULL,
+        }
+    }
+
     unsafe fn try_lock(&mut self) -> SysError {
         self.lock.lock();
         if self.control == MutexControl::SGX_THREAD_MUTEX_RECURSIVE
@@ -0,0 +1,595 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use alloc::collections::LinkedList;
+use core::mem;
+use crate::spinlock::SgxThreadSpinlock;
+use crate::lazy_box::{LazyBox, LazyInit};
+use crate::sys::mutex;
+use core::time::Duration;
+
+use sgx_libc as libc;
+use sgx_trts::enclave::SgxThreadData;
+use sgx_types::{sgx_thread_t, SysError, SGX_THREAD_T_NULL};
+
+use super::rsgx_thread_self;
+
+/// An OS-based reader-writer lock.
+///
+/// This structure is entirely unsafe and serves as the lowest layer of a
+/// cross-platform binding of system rwlocks. It is recommended to use the
+/// safer types at the top level of this crate instead of this type.
+pub struct RwLock {
+    inner: UnsafeCell<RwLockInner>,
+}
+
+pub type MovableRwLock = LazyBox<RwLock>;
+
+unsafe impl Send for RwLock {}
+unsafe impl Sync for RwLock {}
+
+impl LazyInit for RwLock {
+    fn init() -> Box<Self> {
+        Box::new(Self::new())
+    }
+
+    fn destroy(rwlock: Box<Self>) {
+        // We're not allowed to pthread_rwlock_destroy a locked rwlock,
+        // so check first if it's unlocked.
+        if unsafe { rwlock.is_locked() } {
+            // The rwlock is locked. This happens if a RwLock{Read,Write}Guard is leaked.
+            // In this case, we just leak the RwLock too.
+            mem::forget(rwlock);
+        }
+    }
+
+    fn cancel_init(_: Box<Self>) {
+        // In this case, we can just drop it without any checks,
+        // since it cannot have been locked yet.
+    }
+}
+
+/// Which waiters a reader-writer lock lets in first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum RwLockPolicy {
+    /// Readers enter whenever no writer holds the lock, even if writers are
+    /// waiting. A steady stream of readers can starve writers.
+    #[default]
+    ReaderPreferred,
+    /// Readers wait while a writer is queued, and a released lock is handed
+    /// to the next queued writer before any reader.
+    WriterPreferred,
+}
+
+impl RwLock {
+    /// Creates a new reader-writer lock for use.
+    pub const fn new() -> Self {
+        RwLock {
+            inner: UnsafeCell::new(RwLockInner::new(RwLockPolicy::ReaderPreferred)),
+        }
+    }
+
+    /// Creates a new reader-writer lock that uses the given policy.
+    pub const fn new_with_policy(policy: RwLockPolicy) -> Self {
+        RwLock {
+            inner: UnsafeCell::new(RwLockInner::new(policy)),
+        }
+    }
+
+    /// Returns the policy of this lock.
+    #[inline]
+    pub unsafe fn policy(&self) -> RwLockPolicy {
+        let rwlock = &*self.inner.get();
+        rwlock.policy()
+    }
+
+    /// Changes the policy of this lock. Threads that already wait for the
+    /// lock are served under the new policy.
+    #[inline]
+    pub unsafe fn set_policy(&self, policy: RwLockPolicy) {
+        let rwlock = &mut *self.inner.get();
+        rwlock.set_policy(policy)
+    }
+
+    /// Acquires shared access to the underlying lock, blocking the current
+    /// thread to do so.
+    #[inline]
+    pub unsafe fn read(&self) -> SysError {
+        let rwlock = &mut *self.inner.get();
+        rwlock.read(Duration::new(u64::MAX, 1_000_000_000 - 1))
+    }
+
+    /// Acquires shared access to the underlying lock, blocking the current
+    /// thread for at most about `dur` to do so.
+    ///
+    /// Returns `Err(ETIMEDOUT)` if the lock could not be acquired in time.
+    #[inline]
+    pub unsafe fn read_timeout(&self, dur: Duration) -> SysError {
+        let rwlock = &mut *self.inner.get();
+        rwlock.read(dur)
+    }
+
+    /// Attempts to acquire shared access to this lock, returning whether it
+    /// succeeded or not.
+    ///
+    /// This function does not block the current thread.
+    #[inline]
+    pub unsafe fn try_read(&self) -> SysError {
+        let rwlock = &mut *self.inner.get();
+        rwlock.try_read()
+    }
+
+    /// Acquires write access to the underlying lock, blocking the current thread
+    /// to do so.
+    #[inline]
+    pub unsafe fn write(&self) -> SysError {
+        let rwlock = &mut *self.inner.get();
+        rwlock.write(Duration::new(u64::MAX, 1_000_000_000 - 1))
+    }
+
+    /// Acquires write access to the underlying lock, blocking the current
+    /// thread for at most about `dur` to do so.
+    ///
+    /// Returns `Err(ETIMEDOUT)` if the lock could not be acquired in time.
+    #[inline]
+    pub unsafe fn write_timeout(&self, dur: Duration) -> SysError {
+        let rwlock = &mut *self.inner.get();
+        rwlock.write(dur)
+    }
+
+    /// Attempts to acquire exclusive access to this lock, returning whether it
+    /// succeeded or not.
+    ///
+    /// This function does not block the current thread.
+    #[inline]
+    pub unsafe fn try_write(&self) -> SysError {
+        let rwlock = &mut *self.inner.get();
+        rwlock.try_write()
+    }
+
+    /// Turns exclusive access held by the current thread into shared access,
+    /// letting the waiting readers in.
+    ///
+    /// Returns `Err(EPERM)` if the current thread does not hold exclusive
+    /// access.
+    #[inline]
+    pub unsafe fn downgrade(&self) -> SysError {
+        let rwlock = &mut *self.inner.get();
+        rwlock.downgrade()
+    }
+
+    /// Attempts to turn shared access held by the current thread into
+    /// exclusive access, which succeeds if no other thread holds the lock.
+    ///
+    /// Returns `Err(EBUSY)` if other readers hold the lock, or another thread
+    /// waits to upgrade.
+    #[inline]
+    pub unsafe fn try_upgrade(&self) -> SysError {
+        let rwlock = &mut *self.inner.get();
+        rwlock.try_upgrade()
+    }
+
+    /// Turns shared access held by the current thread into exclusive access,
+    /// blocking until the other readers have left. New readers wait meanwhile.
+    ///
+    /// Returns `Err(EDEADLK)`, keeping shared access, if another thread waits
+    /// to upgrade already: each of the two would wait for the other to leave.
+    #[inline]
+    pub unsafe fn upgrade(&self) -> SysError {
+        let rwlock = &mut *self.inner.get();
+        rwlock.upgrade()
+    }
+
+    /// Unlocks previously acquired shared access to this lock.
+    #[inline]
+    pub unsafe fn read_unlock(&self) -> SysError {
+        let rwlock = &mut *self.inner.get();
+        rwlock.read_unlock()
+    }
+
+    /// Unlocks previously acquired exclusive access to this lock.
+    #[inline]
+    pub unsafe fn write_unlock(&self) -> SysError {
+        let rwlock = &mut *self.inner.get();
+        rwlock.write_unlock()
+    }
+
+    #[inline]
+    pub unsafe fn unlock(&self) -> SysError {
+        let rwlock = &mut *self.inner.get();
+        rwlock.unlock()
+    }
+
+    /// Destroys OS-related resources with this RWLock.
+    #[inline]
+    pub unsafe fn destroy(&self) -> SysError {
+        let rwlock = &mut *self.inner.get();
+        rwlock.destroy()
+    }
+
+    #[inline]
+    unsafe fn is_locked(&self) -> bool {
+        let rwlock = &*self.inner.get();
+        rwlock.is_locked()
+    }
+}
+
+impl Drop for RwLock {
+    fn drop(&mut self) {
+        let r = unsafe { self.destroy() };
+        debug_assert_eq!(r, Ok(()));
+    }
+}
+
+struct RwLockInner {
+    reader_count: u32,
+    writer_waiting: u32,
+    policy: RwLockPolicy,
+    lock: SgxThreadSpinlock,
+    owner: sgx_thread_t,
+    // A reader waiting for the other readers to leave, to upgrade. There is
+    // at most one, as two would wait for each other.
+    upgrader: sgx_thread_t,
+    reader_queue: LinkedList<sgx_thread_t>,
+    writer_queue: LinkedList<sgx_thread_t>,
+}
+
+impl RwLockInner {
+    const fn new(policy: RwLockPolicy) -> Self {
+        RwLockInner {
+            reader_count: 0,
+            writer_waiting: 0,
+            policy,
+            lock: SgxThreadSpinlock::new(),
+            owner: SGX_THREAD_T_NULL,
+            upgrader: SGX_THREAD_T_NULL,
+            reader_queue: LinkedList::new(),
+            writer_queue: LinkedList::new(),
+        }
+    }
+
+    unsafe fn policy(&self) -> RwLockPolicy {
+        self.lock.lock();
+        let policy = self.policy;
+        self.lock.unlock();
+        policy
+    }
+
+    unsafe fn set_policy(&mut self, policy: RwLockPolicy) {
+        self.lock.lock();
+        self.policy = policy;
+        let waiters = self.next_waiters();
+        self.lock.unlock();
+        Self::wake(&waiters);
+    }
+
+    // Must be called with `self.lock` held.
+    fn can_read(&self) -> bool {
+        self.owner == SGX_THREAD_T_NULL
+            && self.upgrader == SGX_THREAD_T_NULL
+            && (self.policy == RwLockPolicy::ReaderPreferred || self.writer_queue.is_empty())
+    }
+
+    // Must be called with `self.lock` held.
+    fn can_write(&self) -> bool {
+        self.owner == SGX_THREAD_T_NULL && self.reader_count == 0
+    }
+
+    // Collects the TCSs of the waiters that may enter now, in the order the
+    // policy prefers. Must be called with `self.lock` held.
+    unsafe fn next_waiters(&self) -> Vec<usize> {
+        let mut tcs_vec: Vec<usize> = Vec::new();
+        if self.owner != SGX_THREAD_T_NULL {
+            return tcs_vec;
+        }
+        if self.upgrader != SGX_THREAD_T_NULL {
+            // Nobody else enters before the upgrade.
+            if self.reader_count == 1 {
+                tcs_vec.push(SgxThreadData::from_raw(self.upgrader).get_tcs());
+            }
+            return tcs_vec;
+        }
+        let readers_first = self.policy == RwLockPolicy::ReaderPreferred;
+        if self.reader_count == 0 && (!readers_first || self.reader_queue.is_empty()) {
+            if let Some(td) = self.writer_queue.front() {
+                tcs_vec.push(SgxThreadData::from_raw(*td).get_tcs());
+                return tcs_vec;
+            }
+        }
+        if self.can_read() {
+            for waiter in self.reader_queue.iter() {
+                tcs_vec.push(SgxThreadData::from_raw(*waiter).get_tcs())
+            }
+        }
+        tcs_vec
+    }
+
+    unsafe fn wake(tcs_vec: &[usize]) {
+        match tcs_vec.len() {
+            0 => (),
+            1 => {
+                mutex::thread_set_event(tcs_vec[0]);
+            }
+            _ => {
+                mutex::thread_set_multiple_events(tcs_vec);
+            }
+        }
+    }
+
+    unsafe fn read(&mut self, dur: Duration) -> SysError {
+        let current = rsgx_thread_self();
+
+        self.lock.lock();
+        if self.can_read() {
+            self.reader_count += 1;
+        } else {
+            if self.owner == current {
+                self.lock.unlock();
+                return Err(libc::EDEADLK);
+            }
+
+            self.reader_queue.push_back(current);
+
+            loop {
+                self.lock.unlock();
+                let result =
+                    mutex::thread_wait_event(SgxThreadData::from_raw(current).get_tcs(), dur);
+
+                self.lock.lock();
+                if self.can_read() {
+                    self.reader_count += 1;
+                    if let Some(pos) = self
+                        .reader_queue
+                        .iter()
+                        .position(|&waiter| waiter == current)
+                    {
+                        self.reader_queue.remove(pos);
+                    }
+                    break;
+                }
+                if result < 0 && libc::errno() == libc::ETIMEDOUT {
+                    if let Some(pos) = self
+                        .reader_queue
+                        .iter()
+                        .position(|&waiter| waiter == current)
+                    {
+                        self.reader_queue.remove(pos);
+                    }
+                    self.lock.unlock();
+                    return Err(libc::ETIMEDOUT);
+                }
+            }
+        }
+        self.lock.unlock();
+        Ok(())
+    }
+
+    unsafe fn try_read(&mut self) -> SysError {
+        self.lock.lock();
+        let ret = if self.can_read() {
+            self.reader_count += 1;
+            Ok(())
+        } else {
+            Err(libc::EBUSY)
+        };
+        self.lock.unlock();
+        ret
+    }
+
+    unsafe fn write(&mut self, dur: Duration) -> SysError {
+        let current = rsgx_thread_self();
+
+        self.lock.lock();
+        if self.can_write() {
+            self.owner = current;
+        } else {
+            if self.owner == current {
+                self.lock.unlock();
+                return Err(libc::EDEADLK);
+            }
+
+            self.writer_queue.push_back(current);
+
+            loop {
+                self.lock.unlock();
+                let result =
+                    mutex::thread_wait_event(SgxThreadData::from_raw(current).get_tcs(), dur);
+
+                self.lock.lock();
+                if self.can_write() {
+                    self.owner = current;
+                    if let Some(pos) = self
+                        .writer_queue
+                        .iter()
+                        .position(|&waiter| waiter == current)
+                    {
+                        self.writer_queue.remove(pos);
+                    }
+                    break;
+                }
+                if result < 0 && libc::errno() == libc::ETIMEDOUT {
+                    if let Some(pos) = self
+                        .writer_queue
+                        .iter()
+                        .position(|&waiter| waiter == current)
+                    {
+                        self.writer_queue.remove(pos);
+                    }
+                    // This writer may have been woken as the next owner, or
+                    // may be the last writer that held readers back.
+                    let waiters = self.next_waiters();
+                    self.lock.unlock();
+                    Self::wake(&waiters);
+                    return Err(libc::ETIMEDOUT);
+                }
+            }
+        }
+        self.lock.unlock();
+        Ok(())
+    }
+
+    unsafe fn try_write(&mut self) -> SysError {
+        let current = rsgx_thread_self();
+
+        self.lock.lock();
+        let ret = if self.can_write() {
+            self.owner = current;
+            Ok(())
+        } else {
+            Err(libc::EBUSY)
+        };
+        self.lock.unlock();
+        ret
+    }
+
+    unsafe fn read_unlock(&mut self) -> SysError {
+        self.lock.lock();
+
+        if self.reader_count == 0 {
+            self.lock.unlock();
+            return Err(libc::EPERM);
+        }
+
+        self.reader_count -= 1;
+        if self.reader_count == 0 || (self.reader_count == 1 && self.upgrader != SGX_THREAD_T_NULL)
+        {
+            let waiters = self.next_waiters();
+            self.lock.unlock();
+            Self::wake(&waiters);
+        } else {
+            self.lock.unlock();
+        }
+        Ok(())
+    }
+
+    unsafe fn write_unlock(&mut self) -> SysError {
+        let current = rsgx_thread_self();
+
+        self.lock.lock();
+
+        if self.owner != current {
+            self.lock.unlock();
+            return Err(libc::EPERM);
+        }
+
+        self.owner = SGX_THREAD_T_NULL;
+        let waiters = self.next_waiters();
+        self.lock.unlock();
+        Self::wake(&waiters);
+        Ok(())
+    }
+
+    unsafe fn downgrade(&mut self) -> SysError {
+        let current = rsgx_thread_self();
+
+        self.lock.lock();
+
+        if self.owner != current {
+            self.lock.unlock();
+            return Err(libc::EPERM);
+        }
+
+        self.owner = SGX_THREAD_T_NULL;
+        self.reader_count = 1;
+        let waiters = self.next_waiters();
+        self.lock.unlock();
+        Self::wake(&waiters);
+        Ok(())
+    }
+
+    // Must be called with `self.lock` held, by a reader.
+    fn can_upgrade(&self, current: sgx_thread_t) -> bool {
+        self.owner == SGX_THREAD_T_NULL
+            && self.reader_count == 1
+            && (self.upgrader == SGX_THREAD_T_NULL || self.upgrader == current)
+    }
+
+    unsafe fn try_upgrade(&mut self) -> SysError {
+        let current = rsgx_thread_self();
+
+        self.lock.lock();
+        let ret = if self.can_upgrade(current) {
+            self.reader_count = 0;
+            self.owner = current;
+            Ok(())
+        } else {
+            Err(libc::EBUSY)
+        };
+        self.lock.unlock();
+        ret
+    }
+
+    unsafe fn upgrade(&mut self) -> SysError {
+        let current = rsgx_thread_self();
+
+        self.lock.lock();
+        if self.reader_count == 0 {
+            self.lock.unlock();
+            return Err(libc::EPERM);
+        }
+        if self.upgrader != SGX_THREAD_T_NULL {
+            self.lock.unlock();
+            return Err(libc::EDEADLK);
+        }
+
+        self.upgrader = current;
+        while !self.can_upgrade(current) {
+            self.lock.unlock();
+            mutex::thread_wait_event(
+                SgxThreadData::from_raw(current).get_tcs(),
+                Duration::new(u64::MAX, 1_000_000_000 - 1),
+            );
+            self.lock.lock();
+        }
+        self.upgrader = SGX_THREAD_T_NULL;
+        self.reader_count = 0;
+        self.owner = current;
+        self.lock.unlock();
+        Ok(())
+    }
+
+    unsafe fn unlock(&mut self) -> SysError {
+        if self.owner == rsgx_thread_self() {
+            self.write_unlock()
+        } else {
+            self.read_unlock()
+        }
+    }
+
+    unsafe fn destroy(&mut self) -> SysError {
+        if self.is_locked() {
+            Err(libc::EBUSY)
+        } else {
+            Ok(())
+        }
+    }
+
+    unsafe fn is_locked(&self) -> bool {
+        self.lock.lock();
+        let is_locked = self.owner != SGX_THREAD_T_NULL
+            || self.upgrader != SGX_THREAD_T_NULL
+            || self.reader_count != 0
+            || self.writer_waiting != 0
+            || !self.reader_queue.is_empty()
+            || !self.writer_queue.is_empty();
+        self.lock.unlock();
+        is_locked
+    }
+}
@@ -0,0 +1,469 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Microbenchmarks of the enclave synchronization primitives.
+//!
+//! [`run`] times each [`Primitive`] under every thread count of a
+//! [`BenchConfig`], and returns a [`Report`] that prints as a table, or as
+//! JSON or CSV for scripts comparing builds. The lock workloads have all
+//! threads take turns at incrementing one shared counter until it reaches
+//! `ops` per thread, and note how many increments each thread got: the
+//! report gives their fairness as Jain's index, from `1 / threads` when a
+//! single thread did all the work to `1.0` when all did the same.
+//!
+//! Every thread count needs as many free TCS, plus the one of the calling
+//! thread; counts beyond `std::thread::available_parallelism` are skipped.
+//! Time is read with `Instant`, that is from outside the enclave: the
+//! figures are for comparing primitives, not for anything that must be
+//! trusted.
+//!
+//! Requires the `bench` feature.
+//!
+//! ```ignore
+//! use sgx_tunittest::bench::{self, BenchConfig, Primitive};
+//!
+//! let config = BenchConfig {
+//!     primitives: vec![Primitive::Mutex, Primitive::AdaptiveMutex],
+//!     threads: vec![1, 2, 4],
+//!     ..BenchConfig::default()
+//! };
+//! let report = bench::run(&config);
+//! println!("{}", report);
+//! write_report(report.to_json());
+//! ```
+
+use std::fmt;
+use std::fmt::Write;
+use std::string::String;
+use std::sync::mpsc;
+use std::sync::{Arc, Barrier, MutexStats, SgxAdaptiveMutex, SgxCondvar, SgxMutex, SgxRwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+/// A workload of the suite.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Primitive {
+    /// `SgxMutex` guarding a counter.
+    Mutex,
+    /// `SgxAdaptiveMutex` guarding a counter.
+    AdaptiveMutex,
+    /// `SgxRwLock` guarding a counter, nine reads for one write.
+    RwLockRead,
+    /// `SgxRwLock` guarding a counter, written only.
+    RwLockWrite,
+    /// A token passed from thread to thread with an `SgxMutex` and an
+    /// `SgxCondvar`.
+    Condvar,
+    /// Every thread sending to the calling thread over one `mpsc` channel.
+    Channel,
+}
+
+impl Primitive {
+    pub const ALL: [Primitive; 6] = [
+        Primitive::Mutex,
+        Primitive::AdaptiveMutex,
+        Primitive::RwLockRead,
+        Primitive::RwLockWrite,
+        Primitive::Condvar,
+        Primitive::Channel,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Primitive::Mutex => "mutex",
+            Primitive::AdaptiveMutex => "adaptive_mutex",
+            Primitive::RwLockRead => "rwlock_read",
+            Primitive::RwLockWrite => "rwlock_write",
+            Primitive::Condvar => "condvar",
+            Primitive::Channel => "channel",
+        }
+    }
+}
+
+/// What to run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BenchConfig {
+    pub primitives: Vec<Primitive>,
+    /// The numbers of threads to run each primitive with.
+    pub threads: Vec<usize>,
+    /// The operations of each thread.
+    pub ops: u64,
+    /// The number of times each measurement is taken; the report keeps the
+    /// median.
+    pub rounds: u32,
+}
+
+impl Default for BenchConfig {
+    fn default() -> BenchConfig {
+        BenchConfig {
+            primitives: Primitive::ALL.to_vec(),
+            threads: vec![1, 2, 4, 8],
+            ops: 10_000,
+            rounds: 3,
+        }
+    }
+}
+
+/// The timing of one primitive under one number of threads.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measurement {
+    pub primitive: Primitive,
+    pub threads: usize,
+    /// The operations of all threads.
+    pub ops: u64,
+    pub elapsed: Duration,
+    /// Jain's fairness index of the operations done by each thread.
+    pub fairness: f64,
+    /// The counters of the lock, for [`Primitive::AdaptiveMutex`].
+    pub stats: Option<MutexStats>,
+}
+
+impl Measurement {
+    pub fn ns_per_op(&self) -> f64 {
+        self.elapsed.as_nanos() as f64 / self.ops.max(1) as f64
+    }
+
+    pub fn ops_per_sec(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.ops as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The results of [`run`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    /// The threads that could run besides the calling one.
+    pub max_threads: usize,
+    /// The thread counts of the configuration that were skipped for lack
+    /// of TCS.
+    pub skipped: Vec<usize>,
+    pub results: Vec<Measurement>,
+}
+
+impl Report {
+    /// Encodes the report as a JSON object.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"max_threads\":{},\"skipped\":{:?},\"results\":[",
+            self.max_threads, self.skipped
+        );
+        for (i, m) in self.results.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"primitive\":\"{}\",\"threads\":{},\"ops\":{},\"elapsed_ns\":{},\
+                 \"ns_per_op\":{:.3},\"ops_per_sec\":{:.1},\"fairness\":{:.4}",
+                m.primitive.name(),
+                m.threads,
+                m.ops,
+                m.elapsed.as_nanos(),
+                m.ns_per_op(),
+                m.ops_per_sec(),
+                m.fairness,
+            );
+            if let Some(stats) = m.stats {
+                let _ = write!(
+                    out,
+                    ",\"stats\":{{\"acquisitions\":{},\"contended\":{},\"spun\":{},\
+                     \"sleeps\":{},\"timeouts\":{}}}",
+                    stats.acquisitions, stats.contended, stats.spun, stats.sleeps, stats.timeouts
+                );
+            }
+            out.push('}');
+        }
+        out.push_str("]}");
+        out
+    }
+
+    /// Encodes the results as CSV, with a header line.
+    pub fn to_csv(&self) -> String {
+        let mut out =
+            String::from("primitive,threads,ops,elapsed_ns,ns_per_op,ops_per_sec,fairness\n");
+        for m in self.results.iter() {
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{:.3},{:.1},{:.4}",
+                m.primitive.name(),
+                m.threads,
+                m.ops,
+                m.elapsed.as_nanos(),
+                m.ns_per_op(),
+                m.ops_per_sec(),
+                m.fairness
+            );
+        }
+        out
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<16}{:>8}{:>12}{:>14}{:>10}",
+            "primitive", "threads", "ns/op", "ops/s", "fairness"
+        )?;
+        for m in self.results.iter() {
+            writeln!(
+                f,
+                "{:<16}{:>8}{:>12.1}{:>14.0}{:>10.3}",
+                m.primitive.name(),
+                m.threads,
+                m.ns_per_op(),
+                m.ops_per_sec(),
+                m.fairness
+            )?;
+        }
+        if !self.skipped.is_empty() {
+            writeln!(f, "skipped for lack of TCS: {:?} threads", self.skipped)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the benchmarks of `config`.
+pub fn run(config: &BenchConfig) -> Report {
+    let max_threads = thread::available_parallelism().map_or(0, |n| n.get());
+    let (threads, skipped): (Vec<usize>, Vec<usize>) = config
+        .threads
+        .iter()
+        .partition(|&&n| n > 0 && n <= max_threads);
+
+    let mut results = Vec::new();
+    for &primitive in config.primitives.iter() {
+        for &n in threads.iter() {
+            let mut rounds: Vec<Measurement> = (0..config.rounds.max(1))
+                .map(|_| measure(primitive, n, config.ops))
+                .collect();
+            rounds.sort_by_key(|m| m.elapsed);
+            results.push(rounds.swap_remove(rounds.len() / 2));
+        }
+    }
+    Report {
+        max_threads,
+        skipped,
+        results,
+    }
+}
+
+/// Times `primitive` once, with `threads` threads doing `ops` operations
+/// each.
+///
+/// # Panics
+///
+/// Panics if the threads cannot be spawned.
+pub fn measure(primitive: Primitive, threads: usize, ops: u64) -> Measurement {
+    let total = ops * threads as u64;
+    let (elapsed, counts, stats) = match primitive {
+        Primitive::Mutex => {
+            let lock = Arc::new(SgxMutex::new(0_u64));
+            let (elapsed, counts) = contend(threads, move || {
+                let mut count = lock.lock().unwrap();
+                if *count < total {
+                    *count += 1;
+                    true
+                } else {
+                    false
+                }
+            });
+            (elapsed, counts, None)
+        }
+        Primitive::AdaptiveMutex => {
+            let lock = Arc::new(SgxAdaptiveMutex::new(0_u64));
+            let worker = lock.clone();
+            let (elapsed, counts) = contend(threads, move || {
+                let mut count = worker.lock().unwrap();
+                if *count < total {
+                    *count += 1;
+                    true
+                } else {
+                    false
+                }
+            });
+            (elapsed, counts, Some(lock.stats()))
+        }
+        Primitive::RwLockRead | Primitive::RwLockWrite => {
+            let lock = Arc::new(SgxRwLock::new(0_u64));
+            let reads = if primitive == Primitive::RwLockRead {
+                9
+            } else {
+                0
+            };
+            let (elapsed, counts) = contend(threads, move || {
+                for _ in 0..reads {
+                    if *lock.read().unwrap() >= total {
+                        return false;
+                    }
+                }
+                let mut count = lock.write().unwrap();
+                if *count < total {
+                    *count += 1;
+                    true
+                } else {
+                    false
+                }
+            });
+            (elapsed, counts, None)
+        }
+        Primitive::Condvar => {
+            let (elapsed, counts) = pass_token(threads, total);
+            (elapsed, counts, None)
+        }
+        Primitive::Channel => {
+            let (elapsed, counts) = send_all(threads, ops);
+            (elapsed, counts, None)
+        }
+    };
+    Measurement {
+        primitive,
+        threads,
+        ops: total,
+        elapsed,
+        fairness: fairness(&counts),
+        stats,
+    }
+}
+
+// Spawns `threads` threads running `work` with their index, starts the clock
+// once all are ready, runs `main` on the calling thread and joins them.
+// Returns the time taken with the result of each thread.
+//
+// The handles are collected before the barrier, which only opens once every
+// thread is spawned.
+#[allow(clippy::needless_collect)]
+fn spawn_timed<W, M>(threads: usize, work: W, main: M) -> (Duration, Vec<u64>)
+where
+    W: Fn(usize) -> u64 + Send + Sync + 'static,
+    M: FnOnce(),
+{
+    let work = Arc::new(work);
+    let barrier = Arc::new(Barrier::new(threads + 1));
+    let handles: Vec<_> = (0..threads)
+        .map(|i| {
+            let work = work.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                work(i)
+            })
+        })
+        .collect();
+    barrier.wait();
+    let start = Instant::now();
+    main();
+    let counts = handles
+        .into_iter()
+        .map(|handle| handle.join().expect("benchmark thread panicked"))
+        .collect();
+    (start.elapsed(), counts)
+}
+
+// Runs `step` on `threads` threads until it returns `false`, and returns the
+// time taken with the number of steps of each thread.
+fn contend<F>(threads: usize, step: F) -> (Duration, Vec<u64>)
+where
+    F: Fn() -> bool + Send + Sync + 'static,
+{
+    spawn_timed(
+        threads,
+        move |_| {
+            let mut count = 0;
+            while step() {
+                count += 1;
+            }
+            count
+        },
+        || (),
+    )
+}
+
+// Passes a token `total` times around a ring of `threads` threads, each
+// waiting on one condition variable for its turn.
+fn pass_token(threads: usize, total: u64) -> (Duration, Vec<u64>) {
+    let state = Arc::new((SgxMutex::new((0_u64, 0_usize)), SgxCondvar::new()));
+    spawn_timed(
+        threads,
+        move |me| {
+            let (lock, cvar) = &*state;
+            let mut count = 0;
+            let mut token = lock.lock().unwrap();
+            loop {
+                while token.1 != me && token.0 < total {
+                    token = cvar.wait(token).unwrap();
+                }
+                if token.0 >= total {
+                    cvar.notify_all();
+                    return count;
+                }
+                token.0 += 1;
+                token.1 = (me + 1) % threads;
+                count += 1;
+                cvar.notify_all();
+            }
+        },
+        || (),
+    )
+}
+
+// Has every thread send `ops` messages over one bounded channel to the
+// calling thread. The count of a thread is the number of its messages among
+// the first half received, which shows how fairly the senders are served.
+fn send_all(threads: usize, ops: u64) -> (Duration, Vec<u64>) {
+    let (tx, rx) = mpsc::sync_channel(64);
+    let tx = SgxMutex::new(tx);
+    let total = ops * threads as u64;
+    let mut received = vec![0_u64; threads];
+    let (elapsed, _) = spawn_timed(
+        threads,
+        move |me| {
+            let tx = tx.lock().unwrap().clone();
+            for _ in 0..ops {
+                tx.send(me).unwrap();
+            }
+            ops
+        },
+        || {
+            for i in 0..total {
+                let sender = rx.recv().unwrap();
+                if i < total / 2 {
+                    received[sender] += 1;
+                }
+            }
+        },
+    );
+    (elapsed, received)
+}
+
+// Jain's fairness index of `counts`.
+fn fairness(counts: &[u64]) -> f64 {
+    let sum: f64 = counts.iter().map(|&c| c as f64).sum();
+    let squares: f64 = counts.iter().map(|&c| (c as f64) * (c as f64)).sum();
+    if squares == 0.0 {
+        1.0
+    } else {
+        sum * sum / (counts.len() as f64 * squares)
+    }
+}
@@ -67,6 +67,12 @@
 //! In this way, `vec[0]` would panic. But `should_panic!` catches it. Thus
 //! `foo_panic` would pass the unit test.
 //!
+//! Properties, tests that must hold for every input of some kind, are
+//! checked with the runner of the [`prop`] module, which generates random
+//! inputs and shrinks the failing ones to a minimal case. With the `bench`
+//! feature, the [`bench`] module times the synchronization primitives of
+//! sgx_tstd under a varying number of threads.
+//!
 
 #![cfg_attr(not(target_env = "sgx"), no_std)]
 #![cfg_attr(
@@ -77,10 +83,15 @@
 #[cfg(not(target_env = "sgx"))]
 #[macro_use]
 extern crate sgx_tstd as std;
+extern crate sgx_trts;
 
 use std::string::String;
 use std::vec::Vec;
 
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod prop;
+
 /// This macro implements the fail test.
 ///
 /// For example, in traditional Rust testing, we write
@@ -0,0 +1,955 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Property-based testing inside the enclave.
+//!
+//! A property is a test that takes an input and holds for every input a
+//! [`Strategy`] can generate. [`TestRunner::run`] checks it on random inputs;
+//! when one fails, the runner shrinks it to a minimal failing input before
+//! reporting, by walking the [`ValueTree`] the input was drawn from. The API
+//! follows that of the `proptest` crate, so properties written for it port
+//! with few changes: integer ranges, `any::<T>()`, [`collection::vec`],
+//! tuples, [`Just`] and [`Strategy::prop_map`] work as there, and so do the
+//! [`prop_assert!`] family of macros.
+//!
+//! The runner seeds its generator from the enclave RNG, `RDRAND` on hardware,
+//! unless [`Config::seed`] fixes it. A failure reports the seed, and a run
+//! with that seed fails the same way.
+//!
+//! ```
+//! use sgx_tunittest::prop::{any, collection, Config, TestRunner};
+//!
+//! fn seal_round_trip() {
+//!     let mut runner = TestRunner::new(Config::with_cases(64));
+//!     let inputs = (collection::vec(any::<u8>(), 0..1024), any::<u32>());
+//!     runner
+//!         .run(&inputs, |(text, aad)| {
+//!             let sealed = seal(&text, &aad.to_le_bytes());
+//!             prop_assert_eq!(unseal(&sealed), text);
+//!             Ok(())
+//!         })
+//!         .unwrap();
+//! }
+//!
+//! rsgx_unit_tests!(seal_round_trip);
+//! ```
+
+use std::boxed::Box;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Range, RangeInclusive};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::string::{String, ToString};
+use std::sync::Arc;
+
+use sgx_trts::trts::rsgx_read_rand;
+
+/// The random generator of a test run: SplitMix64, seeded once per run.
+#[derive(Clone, Debug)]
+pub struct TestRng {
+    state: u64,
+}
+
+impl TestRng {
+    pub fn from_seed(seed: u64) -> TestRng {
+        TestRng { state: seed }
+    }
+
+    /// Seeds a generator from the enclave RNG.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the enclave RNG fails.
+    pub fn from_enclave() -> TestRng {
+        let mut seed = [0_u8; 8];
+        rsgx_read_rand(&mut seed).expect("enclave RNG failed");
+        TestRng::from_seed(u64::from_le_bytes(seed))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, n)`, for `n > 0`.
+    pub fn below(&mut self, n: u128) -> u128 {
+        let x = ((self.next_u64() as u128) << 64) | self.next_u64() as u128;
+        x % n
+    }
+}
+
+/// A generated value, with the means to shrink it.
+///
+/// `simplify` moves to a simpler value; `complicate`, called after a
+/// simplification that made the test pass, moves back towards the last
+/// failing value. Both return `false` when they cannot move.
+pub trait ValueTree {
+    type Value: fmt::Debug;
+
+    fn current(&self) -> Self::Value;
+
+    fn simplify(&mut self) -> bool;
+
+    fn complicate(&mut self) -> bool;
+}
+
+/// A generator of values of one type.
+pub trait Strategy {
+    type Tree: ValueTree<Value = Self::Value>;
+    type Value: fmt::Debug;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> Self::Tree;
+
+    /// Generates values with `self` and maps them through `f`. Shrinking
+    /// happens on the values before the mapping.
+    fn prop_map<O: fmt::Debug, F: Fn(Self::Value) -> O>(self, f: F) -> Map<Self, F>
+    where
+        Self: Sized,
+    {
+        Map {
+            source: self,
+            f: Arc::new(f),
+        }
+    }
+
+    fn boxed(self) -> BoxedStrategy<Self::Value>
+    where
+        Self: Sized + 'static,
+        Self::Tree: 'static,
+    {
+        BoxedStrategy(Box::new(BoxedAdapter(self)))
+    }
+}
+
+impl<S: Strategy + ?Sized> Strategy for &S {
+    type Tree = S::Tree;
+    type Value = S::Value;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> S::Tree {
+        (**self).new_tree(runner)
+    }
+}
+
+/// A strategy with its type erased.
+pub struct BoxedStrategy<T>(Box<dyn Strategy<Value = T, Tree = Box<dyn ValueTree<Value = T>>>>);
+
+struct BoxedAdapter<S>(S);
+
+impl<S> Strategy for BoxedAdapter<S>
+where
+    S: Strategy,
+    S::Tree: 'static,
+{
+    type Tree = Box<dyn ValueTree<Value = S::Value>>;
+    type Value = S::Value;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> Self::Tree {
+        Box::new(self.0.new_tree(runner))
+    }
+}
+
+impl<T: fmt::Debug> Strategy for BoxedStrategy<T> {
+    type Tree = Box<dyn ValueTree<Value = T>>;
+    type Value = T;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> Self::Tree {
+        self.0.new_tree(runner)
+    }
+}
+
+impl<T: ValueTree + ?Sized> ValueTree for Box<T> {
+    type Value = T::Value;
+
+    fn current(&self) -> T::Value {
+        (**self).current()
+    }
+
+    fn simplify(&mut self) -> bool {
+        (**self).simplify()
+    }
+
+    fn complicate(&mut self) -> bool {
+        (**self).complicate()
+    }
+}
+
+/// The strategy of [`Strategy::prop_map`].
+pub struct Map<S, F> {
+    source: S,
+    f: Arc<F>,
+}
+
+pub struct MapTree<T, F> {
+    source: T,
+    f: Arc<F>,
+}
+
+impl<S, O, F> Strategy for Map<S, F>
+where
+    S: Strategy,
+    O: fmt::Debug,
+    F: Fn(S::Value) -> O,
+{
+    type Tree = MapTree<S::Tree, F>;
+    type Value = O;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> Self::Tree {
+        MapTree {
+            source: self.source.new_tree(runner),
+            f: self.f.clone(),
+        }
+    }
+}
+
+impl<T, O, F> ValueTree for MapTree<T, F>
+where
+    T: ValueTree,
+    O: fmt::Debug,
+    F: Fn(T::Value) -> O,
+{
+    type Value = O;
+
+    fn current(&self) -> O {
+        (self.f)(self.source.current())
+    }
+
+    fn simplify(&mut self) -> bool {
+        self.source.simplify()
+    }
+
+    fn complicate(&mut self) -> bool {
+        self.source.complicate()
+    }
+}
+
+/// A strategy that always generates the same value.
+#[derive(Clone, Copy, Debug)]
+pub struct Just<T: Clone + fmt::Debug>(pub T);
+
+impl<T: Clone + fmt::Debug> Strategy for Just<T> {
+    type Tree = Just<T>;
+    type Value = T;
+
+    fn new_tree(&self, _: &mut TestRunner) -> Just<T> {
+        self.clone()
+    }
+}
+
+impl<T: Clone + fmt::Debug> ValueTree for Just<T> {
+    type Value = T;
+
+    fn current(&self) -> T {
+        self.0.clone()
+    }
+
+    fn simplify(&mut self) -> bool {
+        false
+    }
+
+    fn complicate(&mut self) -> bool {
+        false
+    }
+}
+
+/// An integer type that strategies can generate.
+pub trait Int: Copy + fmt::Debug {
+    const MIN: i128;
+    const MAX: i128;
+
+    fn from_i128(v: i128) -> Self;
+
+    fn to_i128(self) -> i128;
+}
+
+macro_rules! int_impls {
+    ($($t:ty)*) => {$(
+        impl Int for $t {
+            const MIN: i128 = <$t>::MIN as i128;
+            const MAX: i128 = <$t>::MAX as i128;
+
+            fn from_i128(v: i128) -> $t {
+                v as $t
+            }
+
+            fn to_i128(self) -> i128 {
+                self as i128
+            }
+        }
+
+        impl Strategy for Range<$t> {
+            type Tree = IntTree<$t>;
+            type Value = $t;
+
+            fn new_tree(&self, runner: &mut TestRunner) -> IntTree<$t> {
+                assert!(self.start < self.end, "empty range {:?}", self);
+                IntTree::generate(runner, self.start as i128, self.end as i128 - 1)
+            }
+        }
+
+        impl Strategy for RangeInclusive<$t> {
+            type Tree = IntTree<$t>;
+            type Value = $t;
+
+            fn new_tree(&self, runner: &mut TestRunner) -> IntTree<$t> {
+                assert!(self.start() <= self.end(), "empty range {:?}", self);
+                IntTree::generate(runner, *self.start() as i128, *self.end() as i128)
+            }
+        }
+
+        impl Arbitrary for $t {
+            type Strategy = RangeInclusive<$t>;
+
+            fn arbitrary() -> RangeInclusive<$t> {
+                <$t>::MIN..=<$t>::MAX
+            }
+        }
+    )*};
+}
+
+int_impls!(u8 u16 u32 u64 usize i8 i16 i32 i64 isize);
+
+/// Shrinks an integer towards the value of its range closest to zero, by
+/// binary search on the distance between the two.
+#[derive(Clone, Debug)]
+pub struct IntTree<T> {
+    origin: i128,
+    negative: bool,
+    lo: u128,
+    curr: u128,
+    hi: u128,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Int> IntTree<T> {
+    fn generate(runner: &mut TestRunner, min: i128, max: i128) -> IntTree<T> {
+        let span = (max - min) as u128;
+        let start = if span == u128::MAX {
+            min + runner.rng().below(u128::MAX) as i128
+        } else {
+            min + runner.rng().below(span + 1) as i128
+        };
+        IntTree::new(start, min, max)
+    }
+
+    fn new(start: i128, min: i128, max: i128) -> IntTree<T> {
+        let origin = 0.clamp(min, max);
+        let distance = (start - origin).unsigned_abs();
+        IntTree {
+            origin,
+            negative: start < origin,
+            lo: 0,
+            curr: distance,
+            hi: distance,
+            _marker: PhantomData,
+        }
+    }
+
+    fn reposition(&mut self) -> bool {
+        let mid = self.lo + (self.hi - self.lo) / 2;
+        if mid == self.curr {
+            false
+        } else {
+            self.curr = mid;
+            true
+        }
+    }
+}
+
+impl<T: Int> ValueTree for IntTree<T> {
+    type Value = T;
+
+    fn current(&self) -> T {
+        let v = if self.negative {
+            self.origin - self.curr as i128
+        } else {
+            self.origin + self.curr as i128
+        };
+        T::from_i128(v)
+    }
+
+    fn simplify(&mut self) -> bool {
+        if self.hi <= self.lo {
+            return false;
+        }
+        self.hi = self.curr;
+        self.reposition()
+    }
+
+    fn complicate(&mut self) -> bool {
+        if self.hi <= self.lo {
+            return false;
+        }
+        self.lo = self.curr + 1;
+        self.reposition()
+    }
+}
+
+/// A type with a canonical strategy, as returned by [`any`].
+pub trait Arbitrary: Sized + fmt::Debug {
+    type Strategy: Strategy<Value = Self>;
+
+    fn arbitrary() -> Self::Strategy;
+}
+
+/// Returns the strategy generating any value of `T`.
+pub fn any<T: Arbitrary>() -> T::Strategy {
+    T::arbitrary()
+}
+
+impl Arbitrary for bool {
+    type Strategy = BoolStrategy;
+
+    fn arbitrary() -> BoolStrategy {
+        BoolStrategy
+    }
+}
+
+/// Generates `true` and `false`, shrinking to `false`.
+#[derive(Clone, Copy, Debug)]
+pub struct BoolStrategy;
+
+impl Strategy for BoolStrategy {
+    type Tree = BoolTree;
+    type Value = bool;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> BoolTree {
+        let value = runner.rng().next_u64() & 1 == 1;
+        BoolTree {
+            value,
+            shrunk: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BoolTree {
+    value: bool,
+    shrunk: bool,
+}
+
+impl ValueTree for BoolTree {
+    type Value = bool;
+
+    fn current(&self) -> bool {
+        self.value && !self.shrunk
+    }
+
+    fn simplify(&mut self) -> bool {
+        if self.value && !self.shrunk {
+            self.shrunk = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn complicate(&mut self) -> bool {
+        if self.shrunk {
+            self.shrunk = false;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+macro_rules! tuple_impls {
+    ($(($($s:ident $i:tt),+))*) => {$(
+        impl<$($s: Strategy),+> Strategy for ($($s,)+) {
+            type Tree = TupleTree<($($s::Tree,)+)>;
+            type Value = ($($s::Value,)+);
+
+            fn new_tree(&self, runner: &mut TestRunner) -> Self::Tree {
+                TupleTree {
+                    trees: ($(self.$i.new_tree(runner),)+),
+                    shrinker: 0,
+                    prev: None,
+                }
+            }
+        }
+
+        impl<$($s: ValueTree),+> ValueTree for TupleTree<($($s,)+)> {
+            type Value = ($($s::Value,)+);
+
+            fn current(&self) -> Self::Value {
+                ($(self.trees.$i.current(),)+)
+            }
+
+            fn simplify(&mut self) -> bool {
+                $(
+                    if self.shrinker == $i {
+                        if self.trees.$i.simplify() {
+                            self.prev = Some($i);
+                            return true;
+                        }
+                        self.shrinker += 1;
+                    }
+                )+
+                false
+            }
+
+            fn complicate(&mut self) -> bool {
+                match self.prev {
+                    $(Some($i) => {
+                        if self.trees.$i.complicate() {
+                            true
+                        } else {
+                            self.prev = None;
+                            false
+                        }
+                    })+
+                    _ => false,
+                }
+            }
+        }
+    )*};
+}
+
+/// Shrinks a tuple one component at a time, from the first.
+pub struct TupleTree<T> {
+    trees: T,
+    shrinker: usize,
+    prev: Option<usize>,
+}
+
+tuple_impls! {
+    (A 0)
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+}
+
+pub mod collection {
+    //! Strategies for collections.
+
+    use super::{Strategy, TestRunner, ValueTree};
+    use std::ops::{Range, RangeInclusive};
+    use std::vec::Vec;
+
+    /// The allowed sizes of a collection.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct SizeRange {
+        min: usize,
+        max: usize,
+    }
+
+    impl From<usize> for SizeRange {
+        fn from(n: usize) -> SizeRange {
+            SizeRange { min: n, max: n }
+        }
+    }
+
+    impl From<Range<usize>> for SizeRange {
+        fn from(r: Range<usize>) -> SizeRange {
+            assert!(r.start < r.end, "empty size range {:?}", r);
+            SizeRange {
+                min: r.start,
+                max: r.end - 1,
+            }
+        }
+    }
+
+    impl From<RangeInclusive<usize>> for SizeRange {
+        fn from(r: RangeInclusive<usize>) -> SizeRange {
+            assert!(r.start() <= r.end(), "empty size range {:?}", r);
+            SizeRange {
+                min: *r.start(),
+                max: *r.end(),
+            }
+        }
+    }
+
+    /// Generates vectors of elements drawn from `element`, with a length in
+    /// `size`.
+    pub fn vec<S: Strategy>(element: S, size: impl Into<SizeRange>) -> VecStrategy<S> {
+        VecStrategy {
+            element,
+            size: size.into(),
+        }
+    }
+
+    /// The strategy of [`vec`].
+    #[derive(Clone, Debug)]
+    pub struct VecStrategy<S> {
+        element: S,
+        size: SizeRange,
+    }
+
+    impl<S: Strategy> Strategy for VecStrategy<S> {
+        type Tree = VecTree<S::Tree>;
+        type Value = Vec<S::Value>;
+
+        fn new_tree(&self, runner: &mut TestRunner) -> Self::Tree {
+            let span = (self.size.max - self.size.min) as u128 + 1;
+            let len = self.size.min + runner.rng().below(span) as usize;
+            let elements: Vec<_> = (0..len).map(|_| self.element.new_tree(runner)).collect();
+            VecTree {
+                included: vec![true; elements.len()],
+                elements,
+                min_size: self.size.min,
+                next: 0,
+                prev: None,
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    enum Shrink {
+        Delete(usize),
+        Element(usize),
+    }
+
+    /// Shrinks a vector by removing its elements, one at a time from the
+    /// front, then by shrinking the elements left.
+    pub struct VecTree<T> {
+        elements: Vec<T>,
+        included: Vec<bool>,
+        min_size: usize,
+        // Position of the shrinking: deletions below `elements.len()`, then
+        // element shrinking.
+        next: usize,
+        prev: Option<Shrink>,
+    }
+
+    impl<T: ValueTree> ValueTree for VecTree<T> {
+        type Value = Vec<T::Value>;
+
+        fn current(&self) -> Self::Value {
+            self.elements
+                .iter()
+                .zip(self.included.iter())
+                .filter(|(_, included)| **included)
+                .map(|(element, _)| element.current())
+                .collect()
+        }
+
+        fn simplify(&mut self) -> bool {
+            let len = self.elements.len();
+            while self.next < len {
+                let i = self.next;
+                self.next += 1;
+                let size = self.included.iter().filter(|included| **included).count();
+                if size > self.min_size && self.included[i] {
+                    self.included[i] = false;
+                    self.prev = Some(Shrink::Delete(i));
+                    return true;
+                }
+            }
+            while self.next < 2 * len {
+                let i = self.next - len;
+                if self.included[i] && self.elements[i].simplify() {
+                    self.prev = Some(Shrink::Element(i));
+                    return true;
+                }
+                self.next += 1;
+            }
+            false
+        }
+
+        fn complicate(&mut self) -> bool {
+            match self.prev {
+                Some(Shrink::Delete(i)) => {
+                    // The element is needed for the failure.
+                    self.included[i] = true;
+                    self.prev = None;
+                    true
+                }
+                Some(Shrink::Element(i)) => {
+                    if self.elements[i].complicate() {
+                        true
+                    } else {
+                        self.prev = None;
+                        false
+                    }
+                }
+                None => false,
+            }
+        }
+    }
+}
+
+/// Why a test case failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestCaseError {
+    /// The input is not valid for the property; the case is skipped.
+    Reject(String),
+    /// The property does not hold.
+    Fail(String),
+}
+
+impl TestCaseError {
+    pub fn fail(reason: impl Into<String>) -> TestCaseError {
+        TestCaseError::Fail(reason.into())
+    }
+
+    pub fn reject(reason: impl Into<String>) -> TestCaseError {
+        TestCaseError::Reject(reason.into())
+    }
+}
+
+impl fmt::Display for TestCaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestCaseError::Reject(reason) => write!(f, "input rejected: {}", reason),
+            TestCaseError::Fail(reason) => write!(f, "case failed: {}", reason),
+        }
+    }
+}
+
+/// The result of a test case.
+pub type TestCaseResult = Result<(), TestCaseError>;
+
+/// Why a test run failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestError<T> {
+    /// Too many inputs were rejected.
+    Abort(String),
+    /// A case failed; holds the reason and the minimal failing input found.
+    Fail { reason: String, value: T, seed: u64 },
+}
+
+impl<T: fmt::Debug> fmt::Display for TestError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestError::Abort(reason) => write!(f, "test aborted: {}", reason),
+            TestError::Fail {
+                reason,
+                value,
+                seed,
+            } => write!(
+                f,
+                "test failed: {}; minimal failing input: {:?}; seed {:#018x}",
+                reason, value, seed
+            ),
+        }
+    }
+}
+
+/// The settings of a [`TestRunner`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// The number of cases to pass.
+    pub cases: u32,
+    /// The number of rejected inputs after which the run is aborted.
+    pub max_global_rejects: u32,
+    /// The number of steps spent shrinking a failing input.
+    pub max_shrink_iters: u32,
+    /// The seed of the run; drawn from the enclave RNG if `None`.
+    pub seed: Option<u64>,
+}
+
+impl Config {
+    pub fn with_cases(cases: u32) -> Config {
+        Config {
+            cases,
+            ..Config::default()
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            cases: 256,
+            max_global_rejects: 1024,
+            max_shrink_iters: 1024,
+            seed: None,
+        }
+    }
+}
+
+/// Runs a property on the inputs of a strategy.
+#[derive(Clone, Debug)]
+pub struct TestRunner {
+    config: Config,
+    seed: u64,
+    rng: TestRng,
+}
+
+impl Default for TestRunner {
+    fn default() -> TestRunner {
+        TestRunner::new(Config::default())
+    }
+}
+
+impl TestRunner {
+    pub fn new(config: Config) -> TestRunner {
+        let seed = match config.seed {
+            Some(seed) => seed,
+            None => TestRng::from_enclave().next_u64(),
+        };
+        TestRunner {
+            config,
+            seed,
+            rng: TestRng::from_seed(seed),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn rng(&mut self) -> &mut TestRng {
+        &mut self.rng
+    }
+
+    /// Checks `test` on `config.cases` inputs of `strategy`. A panic of
+    /// `test` fails the case like an error does.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first failure, with its input shrunk, or an abort if
+    /// `test` rejected too many inputs.
+    pub fn run<S, F>(&mut self, strategy: &S, test: F) -> Result<(), TestError<S::Value>>
+    where
+        S: Strategy,
+        F: Fn(S::Value) -> TestCaseResult,
+    {
+        let mut passed = 0;
+        let mut rejected = 0;
+        while passed < self.config.cases {
+            let mut tree = strategy.new_tree(self);
+            match run_case(&test, tree.current()) {
+                Ok(()) => passed += 1,
+                Err(TestCaseError::Reject(reason)) => {
+                    rejected += 1;
+                    if rejected >= self.config.max_global_rejects {
+                        return Err(TestError::Abort(format!(
+                            "too many inputs rejected, the last for: {}",
+                            reason
+                        )));
+                    }
+                }
+                Err(TestCaseError::Fail(reason)) => {
+                    let (reason, value) = self.shrink(&mut tree, &test, reason);
+                    return Err(TestError::Fail {
+                        reason,
+                        value,
+                        seed: self.seed,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn shrink<T, F>(&self, tree: &mut T, test: &F, reason: String) -> (String, T::Value)
+    where
+        T: ValueTree,
+        F: Fn(T::Value) -> TestCaseResult,
+    {
+        let mut failure = (reason, tree.current());
+        let mut iters = 0;
+        if !tree.simplify() {
+            return failure;
+        }
+        while iters < self.config.max_shrink_iters {
+            iters += 1;
+            let value = tree.current();
+            let more = match run_case(test, tree.current()) {
+                Err(TestCaseError::Fail(reason)) => {
+                    failure = (reason, value);
+                    tree.simplify()
+                }
+                _ => tree.complicate(),
+            };
+            if !more {
+                break;
+            }
+        }
+        failure
+    }
+}
+
+fn run_case<V, F>(test: &F, value: V) -> TestCaseResult
+where
+    F: Fn(V) -> TestCaseResult,
+{
+    match catch_unwind(AssertUnwindSafe(|| test(value))) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = if let Some(message) = payload.downcast_ref::<&'static str>() {
+                message.to_string()
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.clone()
+            } else {
+                "Box<dyn Any>".to_string()
+            };
+            Err(TestCaseError::Fail(format!("panicked: {}", message)))
+        }
+    }
+}
+
+/// Fails the test case if the condition does not hold.
+#[macro_export]
+macro_rules! prop_assert {
+    ($cond:expr) => {
+        $crate::prop_assert!($cond, concat!("assertion failed: ", stringify!($cond)))
+    };
+    ($cond:expr, $($fmt:tt)*) => {
+        if !$cond {
+            return ::core::result::Result::Err($crate::prop::TestCaseError::fail(format!($($fmt)*)));
+        }
+    };
+}
+
+/// Fails the test case if the two values differ.
+#[macro_export]
+macro_rules! prop_assert_eq {
+    ($left:expr, $right:expr) => {{
+        let (left, right) = (&$left, &$right);
+        $crate::prop_assert!(
+            *left == *right,
+            "assertion failed: `(left == right)`\n  left: `{:?}`,\n right: `{:?}`",
+            left,
+            right
+        );
+    }};
+}
+
+/// Fails the test case if the two values are equal.
+#[macro_export]
+macro_rules! prop_assert_ne {
+    ($left:expr, $right:expr) => {{
+        let (left, right) = (&$left, &$right);
+        $crate::prop_assert!(
+            *left != *right,
+            "assertion failed: `(left != right)`\n  left: `{:?}`,\n right: `{:?}`",
+            left,
+            right
+        );
+    }};
+}
+
+/// Rejects the input of the test case if the condition does not hold.
+#[macro_export]
+macro_rules! prop_assume {
+    ($cond:expr) => {
+        if !$cond {
+            return ::core::result::Result::Err($crate::prop::TestCaseError::reject(stringify!(
+                $cond
+            )));
+        }
+    };
+}
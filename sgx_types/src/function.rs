@@ -660,6 +660,15 @@ extern "C" {
     ) -> *const c_void;
     pub fn sgx_unregister_exception_handler(handler: *const c_void) -> uint32_t;
 
+    /* intel sgx sdk 2.19 */
+    pub fn sgx_register_aex_handler(
+        aex_node: *mut sgx_aex_mitigation_node_t,
+        handler: sgx_aex_mitigation_fn_t,
+        args: *const c_void,
+    ) -> sgx_status_t;
+    pub fn sgx_unregister_aex_handler(handler: sgx_aex_mitigation_fn_t) -> sgx_status_t;
+    pub fn sgx_set_ssa_aexnotify(is_enable: int32_t) -> sgx_status_t;
+
     //
     // sgx_edger8r.h
     //
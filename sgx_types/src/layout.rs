@@ -0,0 +1,187 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Compile-time layout assertions for the SDK structures.
+//!
+//! Every entry pins the size, alignment and field offsets that the C headers
+//! of the SDK give the structure. A mismatch fails the build of this crate,
+//! so hand edits to `types.rs` and SDK upgrades are checked the same way.
+//!
+//! The entries below are generated by `sgx_types_codegen layout` from the
+//! SDK 2.18 header excerpts pinned in `sgx_types_codegen/sdk.lock`; see
+//! `sgx_types_codegen/Readme.md` before editing them by hand.
+
+use crate::*;
+
+macro_rules! offset_of {
+    ($t:ty, $f:ident) => {{
+        let u = core::mem::MaybeUninit::<$t>::uninit();
+        let base = u.as_ptr();
+        unsafe { core::ptr::addr_of!((*base).$f).cast::<u8>().offset_from(base.cast::<u8>()) as usize }
+    }};
+}
+
+macro_rules! assert_layout {
+    ($t:ty, size = $size:expr, align = $align:expr $(, $f:ident = $off:expr)* $(,)?) => {
+        const _: () = {
+            assert!(core::mem::size_of::<$t>() == $size);
+            assert!(core::mem::align_of::<$t>() == $align);
+            $(assert!(offset_of!($t, $f) == $off);)*
+        };
+    };
+}
+
+//
+// sgx_attributes.h
+//
+assert_layout!(sgx_attributes_t, size = 16, align = 8, flags = 0, xfrm = 8);
+assert_layout!(sgx_misc_attribute_t, size = 24, align = 8, secs_attr = 0, misc_select = 16);
+
+//
+// sgx_key.h
+//
+assert_layout!(sgx_cpu_svn_t, size = 16, align = 1, svn = 0);
+assert_layout!(sgx_key_id_t, size = 32, align = 1, id = 0);
+assert_layout!(
+    sgx_key_request_t,
+    size = 512,
+    align = 8,
+    key_name = 0,
+    key_policy = 2,
+    isv_svn = 4,
+    reserved1 = 6,
+    cpu_svn = 8,
+    attribute_mask = 24,
+    key_id = 40,
+    misc_mask = 72,
+    config_svn = 76,
+    reserved2 = 78,
+);
+
+//
+// sgx_report.h
+//
+assert_layout!(sgx_measurement_t, size = 32, align = 1, m = 0);
+assert_layout!(sgx_report_data_t, size = 64, align = 1, d = 0);
+assert_layout!(
+    sgx_target_info_t,
+    size = 512,
+    align = 8,
+    mr_enclave = 0,
+    attributes = 32,
+    reserved1 = 48,
+    config_svn = 50,
+    misc_select = 52,
+    reserved2 = 56,
+    config_id = 64,
+    reserved3 = 128,
+);
+assert_layout!(
+    sgx_report_body_t,
+    size = 384,
+    align = 8,
+    cpu_svn = 0,
+    misc_select = 16,
+    reserved1 = 20,
+    isv_ext_prod_id = 32,
+    attributes = 48,
+    mr_enclave = 64,
+    reserved2 = 96,
+    mr_signer = 128,
+    reserved3 = 160,
+    config_id = 192,
+    isv_prod_id = 256,
+    isv_svn = 258,
+    config_svn = 260,
+    reserved4 = 262,
+    isv_family_id = 304,
+    report_data = 320,
+);
+assert_layout!(sgx_report_t, size = 432, align = 8, body = 0, key_id = 384, mac = 416);
+
+//
+// sgx_report2.h
+//
+assert_layout!(tee_cpu_svn_t, size = 16, align = 1, svn = 0);
+assert_layout!(tee_attributes_t, size = 8, align = 4, a = 0);
+assert_layout!(tee_report_type_t, size = 4, align = 1, report_type = 0, subtype = 1, version = 2, reserved = 3);
+assert_layout!(tee_measurement_t, size = 48, align = 1, m = 0);
+assert_layout!(tee_report_data_t, size = 64, align = 1, d = 0);
+assert_layout!(
+    sgx_report2_mac_struct_t,
+    size = 256,
+    align = 1,
+    report_type = 0,
+    reserved1 = 4,
+    cpu_svn = 16,
+    tee_tcb_info_hash = 32,
+    tee_info_hash = 80,
+    report_data = 128,
+    reserved2 = 192,
+    mac = 224,
+);
+assert_layout!(
+    sgx_report2_t,
+    size = 1024,
+    align = 1,
+    report_mac_struct = 0,
+    tee_tcb_info = 256,
+    reserved = 495,
+    tee_info = 512,
+);
+
+//
+// sgx_quote.h
+//
+assert_layout!(sgx_basename_t, size = 32, align = 1, name = 0);
+assert_layout!(
+    sgx_quote_t,
+    size = 436,
+    align = 1,
+    version = 0,
+    sign_type = 2,
+    epid_group_id = 4,
+    qe_svn = 8,
+    pce_svn = 10,
+    xeid = 12,
+    basename = 16,
+    report_body = 48,
+    signature_len = 432,
+    signature = 436,
+);
+
+//
+// sgx_tseal.h
+//
+assert_layout!(
+    sgx_aes_gcm_data_t,
+    size = 32,
+    align = 4,
+    payload_size = 0,
+    reserved = 4,
+    payload_tag = 16,
+    payload = 32,
+);
+assert_layout!(
+    sgx_sealed_data_t,
+    size = 560,
+    align = 8,
+    key_request = 0,
+    plain_text_offset = 512,
+    reserved = 516,
+    aes_data = 528,
+);
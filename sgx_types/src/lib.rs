@@ -78,3 +78,5 @@ pub use self::function::*;
 pub mod cpu_feature;
 pub mod marker;
 pub mod metadata;
+
+mod layout;
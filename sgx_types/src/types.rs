@@ -189,6 +189,8 @@ pub const SGX_KEYPOLICY_NOISVPRODID: uint16_t = 0x0004; /* Derive key without th
 pub const SGX_KEYPOLICY_CONFIGID: uint16_t = 0x0008; /* Derive key with the enclave's CONFIGID */
 pub const SGX_KEYPOLICY_ISVFAMILYID: uint16_t = 0x0010; /* Derive key with the enclave's ISVFAMILYID */
 pub const SGX_KEYPOLICY_ISVEXTPRODID: uint16_t = 0x0020; /* Derive key with the enclave's ISVEXTPRODID */
+pub const SGX_KEYPOLICY_KSS: uint16_t =
+    SGX_KEYPOLICY_CONFIGID | SGX_KEYPOLICY_ISVFAMILYID | SGX_KEYPOLICY_ISVEXTPRODID; /* All KSS fields, needs SGX_FLAGS_KSS */
 
 pub const SGX_KEYID_SIZE: size_t = 32;
 pub const SGX_CPUSVN_SIZE: size_t = 16;
@@ -1068,6 +1070,17 @@ impl_struct! {
 
 pub type sgx_exception_handler_t = extern "C" fn(info: *mut sgx_exception_info_t) -> int32_t;
 
+/* intel sgx sdk 2.19 */
+pub type sgx_aex_mitigation_fn_t = extern "C" fn(info: *const sgx_exception_info_t, args: *const c_void);
+
+impl_copy_clone! {
+    pub struct sgx_aex_mitigation_node_t {
+        pub handler: sgx_aex_mitigation_fn_t,
+        pub args: *const c_void,
+        pub next: *mut sgx_aex_mitigation_node_t,
+    }
+}
+
 //
 // sgx_tseal.h
 //
@@ -0,0 +1,70 @@
+/*
+ * Copyright (C) 2011-2021 Intel Corporation. All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions
+ * are met:
+ *
+ *   * Redistributions of source code must retain the above copyright
+ *     notice, this list of conditions and the following disclaimer.
+ *   * Redistributions in binary form must reproduce the above copyright
+ *     notice, this list of conditions and the following disclaimer in
+ *     the documentation and/or other materials provided with the
+ *     distribution.
+ *   * Neither the name of Intel Corporation nor the names of its
+ *     contributors may be used to endorse or promote products derived
+ *     from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+ * "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+ * LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+ * A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+ * OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+ * LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+ * DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+ * THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ */
+
+/*
+ * Excerpt of sgx_quote.h from the Intel SGX SDK 2.18: only the declarations
+ * mirrored by sgx_types are kept. See ../Readme.md.
+ */
+
+#ifndef _SGX_QUOTE_H_
+#define _SGX_QUOTE_H_
+
+#include <stdint.h>
+
+#include "sgx_key.h"
+#include "sgx_report.h"
+
+#pragma pack(push, 1)
+
+typedef uint8_t sgx_epid_group_id_t[4];
+
+typedef struct _basename_t
+{
+    uint8_t                 name[32];
+} sgx_basename_t;
+
+typedef struct _quote_t
+{
+    uint16_t            version;        /* 0   */
+    uint16_t            sign_type;      /* 2   */
+    sgx_epid_group_id_t epid_group_id;  /* 4   */
+    sgx_isv_svn_t       qe_svn;         /* 8   */
+    sgx_isv_svn_t       pce_svn;        /* 10  */
+    uint32_t            xeid;           /* 12  */
+    sgx_basename_t      basename;       /* 16  */
+    sgx_report_body_t   report_body;    /* 48  */
+    uint32_t            signature_len;  /* 432 */
+    uint8_t             signature[];    /* 436 */
+} sgx_quote_t;
+
+#pragma pack(pop)
+
+#endif
@@ -0,0 +1,439 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! x86_64 System V layout of the parsed declarations and evaluation of the
+//! integer constant expressions used for array lengths and enum values.
+
+use crate::c::{CType, Enum, Item, Struct, Tok};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub size: u64,
+    pub align: u64,
+}
+
+#[derive(Debug)]
+pub struct StructLayout {
+    pub layout: Layout,
+    pub offsets: Vec<u64>,
+}
+
+/// Everything the headers define, indexed by name.
+#[derive(Default)]
+pub struct Env {
+    pub defines: HashMap<String, Vec<Tok>>,
+    pub structs: HashMap<String, Struct>,
+    pub enums: HashMap<String, Enum>,
+    pub aliases: HashMap<String, CType>,
+    pub enum_values: HashMap<String, i128>,
+}
+
+fn builtin_layout(name: &str) -> Option<Layout> {
+    let n = match name {
+        "c_char" | "c_schar" | "c_uchar" | "bool" | "uint8_t" | "int8_t" => 1,
+        "c_short" | "c_ushort" | "uint16_t" | "int16_t" => 2,
+        "c_int" | "c_uint" | "c_float" | "uint32_t" | "int32_t" => 4,
+        "c_long" | "c_ulong" | "c_longlong" | "c_ulonglong" | "c_double" | "uint64_t" | "int64_t" | "size_t"
+        | "ssize_t" | "uintptr_t" | "intptr_t" => 8,
+        _ => return None,
+    };
+    Some(Layout { size: n, align: n })
+}
+
+fn align_up(v: u64, a: u64) -> u64 {
+    (v + a - 1) / a * a
+}
+
+impl Env {
+    pub fn new(items: &[Item]) -> Result<Env, String> {
+        let mut env = Env::default();
+        for item in items {
+            match item {
+                Item::Define(n, v) => {
+                    env.defines.insert(n.clone(), v.clone());
+                }
+                Item::Struct(s) => {
+                    env.structs.insert(s.name.clone(), s.clone());
+                }
+                Item::Enum(e) => {
+                    env.enums.insert(e.name.clone(), e.clone());
+                }
+                Item::Alias(n, t) => {
+                    env.aliases.insert(n.clone(), t.clone());
+                }
+            }
+        }
+        for item in items {
+            if let Item::Enum(e) = item {
+                let mut next = 0;
+                for (v, expr) in &e.variants {
+                    if let Some(expr) = expr {
+                        next = env.eval(expr)?;
+                    }
+                    env.enum_values.insert(v.clone(), next);
+                    next += 1;
+                }
+            }
+        }
+        Ok(env)
+    }
+
+    pub fn layout(&self, ty: &CType) -> Result<Layout, String> {
+        match ty {
+            CType::Ptr(..) => Ok(Layout { size: 8, align: 8 }),
+            CType::Array(elem, len) => {
+                let e = self.layout(elem)?;
+                let n = match len {
+                    Some(expr) => self.eval(expr)? as u64,
+                    None => 0,
+                };
+                Ok(Layout { size: e.size * n, align: e.align })
+            }
+            CType::Named(name) => {
+                if let Some(l) = builtin_layout(name) {
+                    Ok(l)
+                } else if let Some(t) = self.aliases.get(name) {
+                    self.layout(t)
+                } else if self.structs.contains_key(name) {
+                    Ok(self.struct_layout(name)?.layout)
+                } else if self.enums.contains_key(name) {
+                    Ok(Layout { size: 4, align: 4 })
+                } else {
+                    Err(format!("unknown type `{}`", name))
+                }
+            }
+        }
+    }
+
+    pub fn struct_layout(&self, name: &str) -> Result<StructLayout, String> {
+        let s = self.structs.get(name).ok_or_else(|| format!("unknown struct `{}`", name))?;
+        let mut offsets = Vec::with_capacity(s.fields.len());
+        let (mut size, mut align) = (0, 1);
+        for f in &s.fields {
+            let mut l = self.layout(&f.ty)?;
+            if let Some(p) = s.pack {
+                l.align = l.align.min(p);
+            }
+            align = align.max(l.align);
+            if s.union {
+                offsets.push(0);
+                size = size.max(l.size);
+            } else {
+                let off = align_up(size, l.align);
+                offsets.push(off);
+                size = off + l.size;
+            }
+        }
+        Ok(StructLayout { layout: Layout { size: align_up(size, align), align }, offsets })
+    }
+
+    /// Evaluates an integer constant expression.
+    pub fn eval(&self, toks: &[Tok]) -> Result<i128, String> {
+        let mut e = Eval { env: self, toks, pos: 0, depth: 0 };
+        let v = e.ternary()?;
+        if e.pos != toks.len() {
+            return Err(format!("trailing tokens in constant expression {:?}", &toks[e.pos..]));
+        }
+        Ok(v)
+    }
+}
+
+/// Parses an integer literal, dropping `U`/`L` suffixes.
+pub fn parse_int(lit: &str) -> Option<i128> {
+    let s = lit.trim_end_matches(|c| matches!(c, 'u' | 'U' | 'l' | 'L'));
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        i128::from_str_radix(hex, 16).ok()
+    } else if s.len() > 1 && s.starts_with('0') {
+        i128::from_str_radix(&s[1..], 8).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+struct Eval<'a> {
+    env: &'a Env,
+    toks: &'a [Tok],
+    pos: usize,
+    depth: usize,
+}
+
+const BINARY: &[&[&str]] = &[&["|"], &["^"], &["&"], &["<<", ">>"], &["+", "-"], &["*", "/", "%"]];
+
+impl<'a> Eval<'a> {
+    fn peek_punct(&self) -> Option<&'static str> {
+        match self.toks.get(self.pos) {
+            Some(Tok::Punct(p)) => Some(p),
+            _ => None,
+        }
+    }
+
+    fn ternary(&mut self) -> Result<i128, String> {
+        let c = self.binary(0)?;
+        if self.peek_punct() == Some("?") {
+            self.pos += 1;
+            let a = self.ternary()?;
+            if self.peek_punct() != Some(":") {
+                return Err("expected `:`".to_owned());
+            }
+            self.pos += 1;
+            let b = self.ternary()?;
+            return Ok(if c != 0 { a } else { b });
+        }
+        Ok(c)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<i128, String> {
+        if level == BINARY.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(op) = self.peek_punct().filter(|p| BINARY[level].contains(p)) {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = match op {
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "<<" => lhs << rhs,
+                ">>" => lhs >> rhs,
+                "+" => lhs + rhs,
+                "-" => lhs - rhs,
+                "*" => lhs * rhs,
+                "/" if rhs != 0 => lhs / rhs,
+                "%" if rhs != 0 => lhs % rhs,
+                _ => return Err("division by zero in constant expression".to_owned()),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<i128, String> {
+        match self.toks.get(self.pos).cloned() {
+            Some(Tok::Punct("-")) => {
+                self.pos += 1;
+                Ok(-self.unary()?)
+            }
+            Some(Tok::Punct("+")) => {
+                self.pos += 1;
+                self.unary()
+            }
+            Some(Tok::Punct("~")) => {
+                self.pos += 1;
+                // Constants in the SDK headers are at most 64 bits wide.
+                Ok(!self.unary()? & u64::MAX as i128)
+            }
+            Some(Tok::Punct("!")) => {
+                self.pos += 1;
+                Ok((self.unary()? == 0) as i128)
+            }
+            Some(Tok::Punct("(")) => {
+                self.pos += 1;
+                if let Some(ty) = self.cast_type() {
+                    self.pos += 1;
+                    let v = self.unary()?;
+                    return Ok(self.truncate(&ty, v));
+                }
+                let v = self.ternary()?;
+                self.close()?;
+                Ok(v)
+            }
+            Some(Tok::Ident(w)) if w == "sizeof" => {
+                self.pos += 1;
+                if self.peek_punct() != Some("(") {
+                    return Err("expected `(` after sizeof".to_owned());
+                }
+                self.pos += 1;
+                let ty = self.cast_type().ok_or("sizeof of an expression is not supported")?;
+                self.pos += 1;
+                Ok(self.env.layout(&ty)?.size as i128)
+            }
+            Some(Tok::Ident(w)) => {
+                self.pos += 1;
+                if let Some(v) = self.env.enum_values.get(&w) {
+                    return Ok(*v);
+                }
+                let body = self.env.defines.get(&w).ok_or_else(|| format!("undefined constant `{}`", w))?;
+                self.depth += 1;
+                if self.depth > 32 {
+                    return Err(format!("recursive definition of `{}`", w));
+                }
+                let v = self.env.eval(body);
+                self.depth -= 1;
+                v
+            }
+            Some(Tok::Num(n)) => {
+                self.pos += 1;
+                parse_int(&n).ok_or_else(|| format!("bad integer literal `{}`", n))
+            }
+            other => Err(format!("unexpected {:?} in constant expression", other)),
+        }
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        if self.peek_punct() != Some(")") {
+            return Err("expected `)`".to_owned());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // Recognizes `type)` after an opening parenthesis, leaving `pos` on `)`.
+    fn cast_type(&mut self) -> Option<CType> {
+        let start = self.pos;
+        let mut words = Vec::new();
+        while let Some(Tok::Ident(w)) = self.toks.get(self.pos) {
+            words.push(w.clone());
+            self.pos += 1;
+        }
+        let mut ptr = false;
+        while self.peek_punct() == Some("*") {
+            ptr = true;
+            self.pos += 1;
+        }
+        let name = match words.as_slice() {
+            [] => None,
+            [w] if builtin_layout(w).is_some() || self.env.aliases.contains_key(w) || self.env.structs.contains_key(w) => {
+                Some(w.clone())
+            }
+            [s, w] if s == "struct" && self.env.structs.contains_key(w) => Some(w.clone()),
+            _ => crate::c::builtin(&words).map(str::to_owned),
+        };
+        match name {
+            Some(n) if self.peek_punct() == Some(")") => {
+                let t = CType::Named(n);
+                Some(if ptr { CType::Ptr(Box::new(t), false) } else { t })
+            }
+            _ => {
+                self.pos = start;
+                None
+            }
+        }
+    }
+
+    fn truncate(&self, ty: &CType, v: i128) -> i128 {
+        match self.env.layout(ty) {
+            Ok(l) if l.size < 16 => {
+                let bits = l.size * 8;
+                let signed = matches!(ty, CType::Named(n) if n.starts_with("int") || n == "c_int" || n == "c_long" || n == "c_short");
+                let m = v & ((1i128 << bits) - 1);
+                if signed && m >> (bits - 1) == 1 {
+                    m - (1i128 << bits)
+                } else {
+                    m
+                }
+            }
+            _ => v,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::c;
+
+    fn env(src: &str) -> Env {
+        Env::new(&c::parse(src).unwrap()).unwrap()
+    }
+
+    fn pinned() -> Env {
+        let src = [
+            include_str!("../include/sgx_attributes.h"),
+            include_str!("../include/sgx_key.h"),
+            include_str!("../include/sgx_report.h"),
+            include_str!("../include/sgx_report2.h"),
+            include_str!("../include/sgx_quote.h"),
+            include_str!("../include/sgx_tseal.h"),
+        ]
+        .concat();
+        env(&src)
+    }
+
+    #[test]
+    fn natural_alignment_and_padding() {
+        let e = env("typedef struct { uint8_t a; uint32_t b; uint16_t c; uint64_t d; uint8_t e; } s_t;");
+        let l = e.struct_layout("s_t").unwrap();
+        assert_eq!(l.offsets, [0, 4, 8, 16, 24]);
+        assert_eq!((l.layout.size, l.layout.align), (32, 8));
+    }
+
+    #[test]
+    fn packed_struct() {
+        let src =
+            "#pragma pack(push, 1)\ntypedef struct { uint8_t a; uint32_t b; uint16_t c; } s_t;\n#pragma pack(pop)";
+        let e = env(src);
+        let l = e.struct_layout("s_t").unwrap();
+        assert_eq!(l.offsets, [0, 1, 5]);
+        assert_eq!((l.layout.size, l.layout.align), (7, 1));
+    }
+
+    #[test]
+    fn arrays_unions_enums_and_flexible_members() {
+        let src = "#define N (2 * 3)\ntypedef enum { A } e_t;\ntypedef uint16_t pair_t[2];\n\
+                   typedef union { uint8_t b[5]; uint32_t w; } u_t;\n\
+                   typedef struct { uint8_t m[N][2]; e_t e; u_t u; pair_t p; uint64_t tail[]; } s_t;";
+        let e = env(src);
+        let u = e.struct_layout("u_t").unwrap();
+        assert_eq!(u.offsets, [0, 0]);
+        assert_eq!((u.layout.size, u.layout.align), (8, 4));
+        let s = e.struct_layout("s_t").unwrap();
+        assert_eq!(s.offsets, [0, 12, 16, 24, 32]);
+        assert_eq!((s.layout.size, s.layout.align), (32, 8));
+    }
+
+    #[test]
+    fn constant_expressions() {
+        let e = env("#define A 0x10U\n#define B (A << 2)\n#define C ((uint8_t)~0)\ntypedef enum { X = B, Y } e_t;");
+        assert_eq!(e.eval(&[Tok::Ident("B".to_owned())]).unwrap(), 64);
+        assert_eq!(e.eval(&[Tok::Ident("C".to_owned())]).unwrap(), 255);
+        assert_eq!(e.enum_values["Y"], 65);
+        assert!(e.eval(&[Tok::Ident("MISSING".to_owned())]).is_err());
+    }
+
+    #[test]
+    fn pinned_key_request_layout() {
+        let l = pinned().struct_layout("sgx_key_request_t").unwrap();
+        assert_eq!(l.offsets, [0, 2, 4, 6, 8, 24, 40, 72, 76, 78]);
+        assert_eq!((l.layout.size, l.layout.align), (512, 8));
+    }
+
+    #[test]
+    fn pinned_report_and_quote_layout() {
+        let e = pinned();
+        let body = e.struct_layout("sgx_report_body_t").unwrap();
+        assert_eq!(body.offsets, [0, 16, 20, 32, 48, 64, 96, 128, 160, 192, 256, 258, 260, 262, 304, 320]);
+        assert_eq!((body.layout.size, body.layout.align), (384, 8));
+        let quote = e.struct_layout("sgx_quote_t").unwrap();
+        assert_eq!(quote.offsets, [0, 2, 4, 8, 10, 12, 16, 48, 432, 436]);
+        assert_eq!((quote.layout.size, quote.layout.align), (436, 1));
+        let report2 = e.struct_layout("sgx_report2_t").unwrap();
+        assert_eq!(report2.offsets, [0, 256, 495, 512]);
+        assert_eq!((report2.layout.size, report2.layout.align), (1024, 1));
+    }
+
+    #[test]
+    fn pinned_sealed_data_layout() {
+        let e = pinned();
+        let aes = e.struct_layout("sgx_aes_gcm_data_t").unwrap();
+        assert_eq!(aes.offsets, [0, 4, 16, 32]);
+        assert_eq!((aes.layout.size, aes.layout.align), (32, 4));
+        let sealed = e.struct_layout("sgx_sealed_data_t").unwrap();
+        assert_eq!(sealed.offsets, [0, 512, 516, 528]);
+        assert_eq!((sealed.layout.size, sealed.layout.align), (560, 8));
+    }
+}
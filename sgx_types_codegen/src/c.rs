@@ -0,0 +1,722 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! A small C declaration parser covering what the SDK headers use:
+//! object-like `#define`s, `#pragma pack`, and `typedef`s of structs, unions,
+//! enums, scalars and arrays. Function prototypes are skipped.
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Tok {
+    Ident(String),
+    Num(String),
+    Str(String),
+    Punct(&'static str),
+    /// `#pragma pack` state change; `None` restores natural alignment.
+    Pack(Option<u64>),
+    Define(String, Vec<Tok>),
+}
+
+impl fmt::Display for Tok {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tok::Ident(s) | Tok::Num(s) => f.write_str(s),
+            Tok::Str(s) => write!(f, "\"{}\"", s),
+            Tok::Punct(p) => f.write_str(p),
+            Tok::Pack(n) => write!(f, "#pragma pack({:?})", n),
+            Tok::Define(n, _) => write!(f, "#define {}", n),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CType {
+    /// A builtin (already mapped to its `sgx_types` alias) or a typedef name.
+    Named(String),
+    Ptr(Box<CType>, bool),
+    /// `None` is a flexible array member.
+    Array(Box<CType>, Option<Vec<Tok>>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Field {
+    pub name: String,
+    pub ty: CType,
+}
+
+#[derive(Clone, Debug)]
+pub struct Struct {
+    pub name: String,
+    pub union: bool,
+    pub pack: Option<u64>,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Enum {
+    pub name: String,
+    pub variants: Vec<(String, Option<Vec<Tok>>)>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Item {
+    Define(String, Vec<Tok>),
+    Struct(Struct),
+    Enum(Enum),
+    Alias(String, CType),
+}
+
+#[derive(Debug)]
+pub struct ParseError(pub String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+type Result<T> = std::result::Result<T, ParseError>;
+
+const PUNCTS: &[&str] = &[
+    "<<", ">>", "{", "}", "(", ")", "[", "]", ";", ",", "*", "=", "+", "-", "/", "%", "&", "|", "^", "~", "!",
+    ":", "<", ">", "?", ".",
+];
+
+fn strip_comments(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    if c == '\n' {
+                        out.push('\n');
+                    }
+                    prev = c;
+                }
+                out.push(' ');
+            }
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            ('"', _) => {
+                out.push('"');
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    out.push(c);
+                    if c == '"' && !escaped {
+                        break;
+                    }
+                    escaped = c == '\\' && !escaped;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out.replace("\\\n", " ")
+}
+
+fn lex_line(line: &str, out: &mut Vec<Tok>) -> Result<()> {
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < bytes.len() && ((bytes[i] as char).is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            out.push(Tok::Ident(line[start..i].to_owned()));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < bytes.len() && ((bytes[i] as char).is_ascii_alphanumeric() || bytes[i] == b'.') {
+                i += 1;
+            }
+            out.push(Tok::Num(line[start..i].to_owned()));
+        } else if c == '"' {
+            let start = i + 1;
+            i += 1;
+            while i < bytes.len() && bytes[i] != b'"' {
+                if bytes[i] == b'\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            out.push(Tok::Str(line[start..i.min(line.len())].to_owned()));
+            i += 1;
+        } else if c == '\'' {
+            // Character literals only appear in macros we do not evaluate.
+            let start = i;
+            i += 1;
+            while i < bytes.len() && bytes[i] != b'\'' {
+                i += 1;
+            }
+            i += 1;
+            out.push(Tok::Num(line[start..i.min(line.len())].to_owned()));
+        } else {
+            let rest = &line[i..];
+            match PUNCTS.iter().find(|p| rest.starts_with(**p)) {
+                Some(p) => {
+                    out.push(Tok::Punct(p));
+                    i += p.len();
+                }
+                None => return Err(ParseError(format!("unexpected character `{}` in `{}`", c, line.trim()))),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn lex_directive(line: &str, out: &mut Vec<Tok>) -> Result<()> {
+    let body = line.trim_start().trim_start_matches('#').trim_start();
+    if let Some(rest) = body.strip_prefix("define") {
+        let rest = rest.trim_start();
+        let name_len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        let (name, value) = rest.split_at(name_len);
+        // Function-like macros are not type definitions.
+        if !name.is_empty() && !value.starts_with('(') {
+            let mut toks = Vec::new();
+            lex_line(value, &mut toks)?;
+            out.push(Tok::Define(name.to_owned(), toks));
+        }
+    } else if let Some(rest) = body.strip_prefix("pragma") {
+        let rest: String = rest.chars().filter(|c| !c.is_whitespace()).collect();
+        if let Some(args) = rest.strip_prefix("pack(").and_then(|r| r.strip_suffix(')')) {
+            let last = args.rsplit(',').next().unwrap_or("");
+            let pack = if args.is_empty() || args == "pop" {
+                None
+            } else {
+                Some(last.parse().map_err(|_| ParseError(format!("bad pragma: {}", line.trim())))?)
+            };
+            out.push(Tok::Pack(pack));
+        }
+    }
+    Ok(())
+}
+
+pub fn tokenize(src: &str) -> Result<Vec<Tok>> {
+    let mut toks = Vec::new();
+    for line in strip_comments(src).lines() {
+        if line.trim_start().starts_with('#') {
+            lex_directive(line, &mut toks)?;
+        } else {
+            lex_line(line, &mut toks)?;
+        }
+    }
+    Ok(toks)
+}
+
+/// Maps the words of a builtin C type to its `sgx_types` alias.
+pub fn builtin(words: &[String]) -> Option<&'static str> {
+    let mut unsigned = false;
+    let mut longs = 0;
+    let mut base = None;
+    for w in words {
+        match w.as_str() {
+            "unsigned" => unsigned = true,
+            "signed" => {}
+            "long" => longs += 1,
+            "short" | "char" | "int" | "float" | "double" | "void" | "_Bool" | "bool" => base = Some(w.as_str()),
+            _ => return None,
+        }
+    }
+    Some(match (base, longs, unsigned) {
+        (Some("char"), _, false) if words.iter().any(|w| w == "signed") => "c_schar",
+        (Some("char"), _, false) => "c_char",
+        (Some("char"), _, true) => "c_uchar",
+        (Some("short"), _, false) => "c_short",
+        (Some("short"), _, true) => "c_ushort",
+        (Some("float"), _, _) => "c_float",
+        (Some("double"), _, _) => "c_double",
+        (Some("void"), _, _) => "c_void",
+        (Some("_Bool"), _, _) | (Some("bool"), _, _) => "bool",
+        (_, 0, false) => "c_int",
+        (_, 0, true) => "c_uint",
+        (_, 1, false) => "c_long",
+        (_, 1, true) => "c_ulong",
+        (_, _, false) => "c_longlong",
+        (_, _, true) => "c_ulonglong",
+    })
+}
+
+const SPECIFIER_WORDS: &[&str] = &["unsigned", "signed", "long", "short", "char", "int", "float", "double", "void", "_Bool"];
+const QUALIFIERS: &[&str] = &["const", "volatile", "restrict", "__restrict", "extern", "static", "inline"];
+
+pub struct Parser {
+    toks: Vec<Tok>,
+    pos: usize,
+    pack: Vec<Option<u64>>,
+    pub items: Vec<Item>,
+    anon: usize,
+}
+
+impl Parser {
+    pub fn new(toks: Vec<Tok>) -> Parser {
+        Parser { toks, pos: 0, pack: vec![None], items: Vec::new(), anon: 0 }
+    }
+
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Tok> {
+        let t = self.toks.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn is(&self, p: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Punct(q)) if *q == p)
+    }
+
+    fn is_ident(&self, w: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Ident(i)) if i == w)
+    }
+
+    fn expect(&mut self, p: &str) -> Result<()> {
+        match self.next() {
+            Some(Tok::Punct(q)) if q == p => Ok(()),
+            other => Err(ParseError(format!("expected `{}`, found {:?}", p, other))),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Tok::Ident(i)) => Ok(i),
+            other => Err(ParseError(format!("expected identifier, found {:?}", other))),
+        }
+    }
+
+    fn current_pack(&self) -> Option<u64> {
+        *self.pack.last().unwrap_or(&None)
+    }
+
+    // Consumes tokens up to the matching closing bracket of an already
+    // consumed opening one.
+    fn skip_balanced(&mut self, open: &str, close: &str) {
+        let mut depth = 1;
+        while let Some(t) = self.next() {
+            match t {
+                Tok::Punct(p) if p == open => depth += 1,
+                Tok::Punct(p) if p == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                Tok::Pack(p) => self.apply_pack(p),
+                _ => {}
+            }
+        }
+    }
+
+    fn apply_pack(&mut self, p: Option<u64>) {
+        if p.is_some() {
+            self.pack.push(p);
+        } else if self.pack.len() > 1 {
+            self.pack.pop();
+        }
+    }
+
+    fn skip_statement(&mut self) {
+        while let Some(t) = self.next() {
+            match t {
+                Tok::Punct(";") => return,
+                Tok::Punct("{") => {
+                    self.skip_balanced("{", "}");
+                    if !self.is(";") {
+                        return;
+                    }
+                }
+                Tok::Punct("(") => self.skip_balanced("(", ")"),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn parse(mut self) -> Result<Vec<Item>> {
+        while let Some(t) = self.peek().cloned() {
+            match t {
+                Tok::Define(name, body) => {
+                    self.pos += 1;
+                    self.items.push(Item::Define(name, body));
+                }
+                Tok::Pack(p) => {
+                    self.pos += 1;
+                    self.apply_pack(p);
+                }
+                Tok::Ident(ref w) if w == "extern" => {
+                    self.pos += 1;
+                    if let Some(Tok::Str(_)) = self.peek() {
+                        self.pos += 1;
+                        if self.is("{") {
+                            // `extern "C" {`: the closing brace is skipped below.
+                            self.pos += 1;
+                        }
+                    } else {
+                        self.skip_statement();
+                    }
+                }
+                Tok::Punct("}") | Tok::Punct(";") => self.pos += 1,
+                Tok::Ident(ref w) if w == "typedef" => {
+                    self.pos += 1;
+                    self.typedef()?;
+                }
+                Tok::Ident(ref w) if (w == "struct" || w == "union" || w == "enum") && self.is_definition() => {
+                    let (ty, _) = self.specifiers()?;
+                    if let CType::Named(_) = ty {
+                        self.skip_statement();
+                    }
+                }
+                _ => self.skip_statement(),
+            }
+        }
+        Ok(self.items)
+    }
+
+    // `struct tag {` at the current position.
+    fn is_definition(&self) -> bool {
+        matches!(
+            (self.toks.get(self.pos + 1), self.toks.get(self.pos + 2)),
+            (Some(Tok::Ident(_)), Some(Tok::Punct("{"))) | (Some(Tok::Punct("{")), _)
+        )
+    }
+
+    fn typedef(&mut self) -> Result<()> {
+        let (base, tag_body) = self.specifiers()?;
+        loop {
+            if self.is("(") {
+                // Function pointer typedef: `(*name)(args)`.
+                self.pos += 1;
+                self.expect("*")?;
+                let name = self.ident()?;
+                self.expect(")")?;
+                self.expect("(")?;
+                self.skip_balanced("(", ")");
+                self.items.push(Item::Alias(name, CType::Ptr(Box::new(CType::Named("c_void".to_owned())), false)));
+            } else {
+                let (name, ty) = self.declarator(base.clone())?;
+                if let (Some(tag), CType::Named(n)) = (&tag_body, &ty) {
+                    if n == tag {
+                        self.rename(tag, &name);
+                        if self.is(",") || self.is(";") {
+                            if self.is(",") {
+                                self.pos += 1;
+                                continue;
+                            }
+                            break;
+                        }
+                    }
+                }
+                if !matches!(&ty, CType::Named(n) if *n == name) {
+                    self.items.push(Item::Alias(name, ty));
+                }
+            }
+            if self.is(",") {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        self.expect(";")
+    }
+
+    // Gives a tagged or anonymous struct the name of its first typedef.
+    fn rename(&mut self, tag: &str, name: &str) {
+        for item in self.items.iter_mut().rev() {
+            match item {
+                Item::Struct(s) if s.name == tag => {
+                    s.name = name.to_owned();
+                    return;
+                }
+                Item::Enum(e) if e.name == tag => {
+                    e.name = name.to_owned();
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Parses declaration specifiers. Returns the base type and, if a body
+    /// was defined here, the provisional name of the new struct or enum.
+    fn specifiers(&mut self) -> Result<(CType, Option<String>)> {
+        let mut words = Vec::new();
+        loop {
+            match self.peek().cloned() {
+                Some(Tok::Ident(w)) if QUALIFIERS.contains(&w.as_str()) => self.pos += 1,
+                Some(Tok::Ident(w)) if SPECIFIER_WORDS.contains(&w.as_str()) => {
+                    self.pos += 1;
+                    words.push(w);
+                }
+                Some(Tok::Ident(w)) if (w == "struct" || w == "union" || w == "enum") && words.is_empty() => {
+                    self.pos += 1;
+                    let tag = match self.peek() {
+                        Some(Tok::Ident(_)) => Some(self.ident()?),
+                        _ => None,
+                    };
+                    let name = match &tag {
+                        Some(t) => t.clone(),
+                        None => {
+                            self.anon += 1;
+                            format!("__anon{}", self.anon)
+                        }
+                    };
+                    let defined = if self.is("{") {
+                        self.pos += 1;
+                        if w == "enum" {
+                            self.enum_body(&name)?;
+                        } else {
+                            self.struct_body(&name, w == "union")?;
+                        }
+                        Some(name.clone())
+                    } else {
+                        None
+                    };
+                    self.skip_qualifiers();
+                    return Ok((CType::Named(name), defined));
+                }
+                Some(Tok::Ident(w)) if words.is_empty() => {
+                    self.pos += 1;
+                    self.skip_qualifiers();
+                    return Ok((CType::Named(w), None));
+                }
+                _ => break,
+            }
+        }
+        match builtin(&words) {
+            Some(b) if !words.is_empty() => Ok((CType::Named(b.to_owned()), None)),
+            _ => Err(ParseError(format!("unsupported type specifiers {:?} near {:?}", words, self.peek()))),
+        }
+    }
+
+    fn skip_qualifiers(&mut self) {
+        while let Some(Tok::Ident(w)) = self.peek() {
+            if QUALIFIERS.contains(&w.as_str()) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn declarator(&mut self, base: CType) -> Result<(String, CType)> {
+        let mut ty = base;
+        while self.is("*") {
+            self.pos += 1;
+            let is_const = self.is_ident("const");
+            self.skip_qualifiers();
+            ty = CType::Ptr(Box::new(ty), is_const);
+        }
+        let name = self.ident()?;
+        let mut dims = Vec::new();
+        while self.is("[") {
+            self.pos += 1;
+            let mut expr = Vec::new();
+            while !self.is("]") {
+                expr.push(self.next().ok_or_else(|| ParseError("unterminated array".to_owned()))?);
+            }
+            self.pos += 1;
+            dims.push(if expr.is_empty() { None } else { Some(expr) });
+        }
+        // `T a[N][M]` is an array of N arrays of M.
+        for dim in dims.into_iter().rev() {
+            ty = CType::Array(Box::new(ty), dim);
+        }
+        if self.is(":") {
+            return Err(ParseError(format!("bit-field `{}` is not supported", name)));
+        }
+        Ok((name, ty))
+    }
+
+    fn struct_body(&mut self, name: &str, union: bool) -> Result<()> {
+        let pack = self.current_pack();
+        let mut fields = Vec::new();
+        while !self.is("}") {
+            if let Some(Tok::Pack(p)) = self.peek().cloned() {
+                self.pos += 1;
+                self.apply_pack(p);
+                continue;
+            }
+            let (base, defined) = self.specifiers()?;
+            if self.is(";") {
+                if defined.is_some() {
+                    return Err(ParseError(format!("anonymous member in `{}` is not supported", name)));
+                }
+                self.pos += 1;
+                continue;
+            }
+            loop {
+                let (field, ty) = self.declarator(base.clone())?;
+                if let (Some(tag), CType::Named(n)) = (&defined, &ty) {
+                    if n == tag && tag.starts_with("__anon") {
+                        self.rename(tag, &format!("{}_{}_t", name.trim_end_matches("_t"), field));
+                        let renamed = format!("{}_{}_t", name.trim_end_matches("_t"), field);
+                        fields.push(Field { name: field, ty: CType::Named(renamed) });
+                        if self.is(",") {
+                            self.pos += 1;
+                            continue;
+                        }
+                        break;
+                    }
+                }
+                fields.push(Field { name: field, ty });
+                if self.is(",") {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+            self.expect(";")?;
+        }
+        self.pos += 1;
+        self.items.push(Item::Struct(Struct { name: name.to_owned(), union, pack, fields }));
+        Ok(())
+    }
+
+    fn enum_body(&mut self, name: &str) -> Result<()> {
+        let mut variants = Vec::new();
+        while !self.is("}") {
+            let v = self.ident()?;
+            let value = if self.is("=") {
+                self.pos += 1;
+                let mut expr = Vec::new();
+                while !self.is(",") && !self.is("}") {
+                    expr.push(self.next().ok_or_else(|| ParseError("unterminated enum".to_owned()))?);
+                }
+                Some(expr)
+            } else {
+                None
+            };
+            variants.push((v, value));
+            if self.is(",") {
+                self.pos += 1;
+            }
+        }
+        self.pos += 1;
+        self.items.push(Item::Enum(Enum { name: name.to_owned(), variants }));
+        Ok(())
+    }
+}
+
+pub fn parse(src: &str) -> Result<Vec<Item>> {
+    Parser::new(tokenize(src)?).parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn structs(items: &[Item]) -> Vec<&Struct> {
+        items
+            .iter()
+            .filter_map(|i| match i {
+                Item::Struct(s) => Some(s),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tokenize_drops_comments_and_keeps_directives() {
+        let toks =
+            tokenize("/* a\n b */ #define N (4) // four\n#pragma pack(push, 1)\n#include <x.h>\nint n;").unwrap();
+        assert_eq!(
+            toks,
+            vec![
+                Tok::Define("N".to_owned(), vec![Tok::Punct("("), Tok::Num("4".to_owned()), Tok::Punct(")")]),
+                Tok::Pack(Some(1)),
+                Tok::Ident("int".to_owned()),
+                Tok::Ident("n".to_owned()),
+                Tok::Punct(";"),
+            ]
+        );
+    }
+
+    #[test]
+    fn typedef_renames_tagged_struct() {
+        let items = parse("#define N 4\ntypedef struct _t { uint8_t a[N]; uint16_t *b; } t_t;").unwrap();
+        let s = structs(&items);
+        assert_eq!(s.len(), 1);
+        assert_eq!(s[0].name, "t_t");
+        assert_eq!(s[0].pack, None);
+        assert_eq!(s[0].fields[0].name, "a");
+        assert_eq!(
+            s[0].fields[0].ty,
+            CType::Array(Box::new(CType::Named("uint8_t".to_owned())), Some(vec![Tok::Ident("N".to_owned())]))
+        );
+        assert_eq!(s[0].fields[1].ty, CType::Ptr(Box::new(CType::Named("uint16_t".to_owned())), false));
+    }
+
+    #[test]
+    fn pragma_pack_applies_until_pop() {
+        let src = "#pragma pack(push, 1)\ntypedef struct { uint32_t a; } p_t;\n#pragma pack(pop)\n\
+                   typedef struct { uint32_t a; } n_t;";
+        let items = parse(src).unwrap();
+        let s = structs(&items);
+        assert_eq!((s[0].name.as_str(), s[0].pack), ("p_t", Some(1)));
+        assert_eq!((s[1].name.as_str(), s[1].pack), ("n_t", None));
+    }
+
+    #[test]
+    fn flexible_and_nested_arrays() {
+        let items = parse("typedef struct { uint8_t m[2][3]; uint8_t tail[]; } a_t;").unwrap();
+        let f = &structs(&items)[0].fields;
+        let three = CType::Array(Box::new(CType::Named("uint8_t".to_owned())), Some(vec![Tok::Num("3".to_owned())]));
+        assert_eq!(f[0].ty, CType::Array(Box::new(three), Some(vec![Tok::Num("2".to_owned())])));
+        assert_eq!(f[1].ty, CType::Array(Box::new(CType::Named("uint8_t".to_owned())), None));
+    }
+
+    #[test]
+    fn aliases_enums_and_prototypes() {
+        let src = "typedef uint8_t mac_t[16];\ntypedef enum { A, B = 4, C } e_t;\n\
+                   extern \"C\" {\nint f(int x);\n}\ntypedef unsigned long long u_t;";
+        let items = parse(src).unwrap();
+        assert!(matches!(&items[0], Item::Alias(n, CType::Array(..)) if n == "mac_t"));
+        match &items[1] {
+            Item::Enum(e) => {
+                assert_eq!(e.name, "e_t");
+                let names: Vec<_> = e.variants.iter().map(|(v, _)| v.as_str()).collect();
+                assert_eq!(names, ["A", "B", "C"]);
+            }
+            other => panic!("expected an enum, found {:?}", other),
+        }
+        assert!(matches!(&items[2], Item::Alias(n, CType::Named(t)) if n == "u_t" && t == "c_ulonglong"));
+        assert_eq!(items.len(), 3);
+    }
+
+    #[test]
+    fn rejects_bit_fields_and_anonymous_members() {
+        let e = parse("typedef struct { uint32_t a : 3; } b_t;").unwrap_err();
+        assert!(e.0.contains("bit-field `a`"), "{}", e);
+        let e = parse("typedef struct { union { uint32_t a; uint8_t b; }; } u_t;").unwrap_err();
+        assert!(e.0.contains("anonymous member"), "{}", e);
+    }
+}
@@ -0,0 +1,274 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Rust emission in the style of `sgx_types/src/types.rs`.
+
+use crate::abi::Env;
+use crate::c::{CType, Item, Tok};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+const MAX_WIDTH: usize = 120;
+// `Copy`/`Clone`/`Default` are derived by `impl_struct!` only for arrays up
+// to this length; longer ones go through `impl_copy_clone!`.
+const MAX_DERIVE_ARRAY: u64 = 32;
+// C field names that are Rust keywords, renamed the way `types.rs` does.
+const FIELD_RENAMES: &[(&str, &str, &str)] = &[("tee_report_type_t", "type", "report_type")];
+
+pub struct Header<'a> {
+    pub name: &'a str,
+    pub items: Vec<Item>,
+}
+
+fn section(out: &mut String, header: &str) {
+    let _ = write!(out, "\n//\n// {}\n//\n", header);
+}
+
+fn field_name(strukt: &str, field: &str) -> String {
+    FIELD_RENAMES
+        .iter()
+        .find(|(s, f, _)| *s == strukt && *f == field)
+        .map_or_else(|| field.to_owned(), |(_, _, to)| (*to).to_owned())
+}
+
+fn rust_type(ty: &CType) -> String {
+    match ty {
+        CType::Named(n) => n.clone(),
+        CType::Ptr(t, true) => format!("*const {}", rust_type(t)),
+        CType::Ptr(t, false) => format!("*mut {}", rust_type(t)),
+        CType::Array(t, Some(len)) => format!("[{}; {}]", rust_type(t), rust_expr(len)),
+        CType::Array(t, None) => format!("[{}; 0]", rust_type(t)),
+    }
+}
+
+/// Translates a C constant expression, dropping casts and literal suffixes.
+fn rust_expr(toks: &[Tok]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < toks.len() {
+        // `(type)` casts become the type of the constant itself.
+        if let (Some(Tok::Punct("(")), Some(Tok::Ident(_)), Some(Tok::Punct(")"))) =
+            (toks.get(i), toks.get(i + 1), toks.get(i + 2))
+        {
+            if matches!(toks.get(i + 3), Some(Tok::Num(_)) | Some(Tok::Ident(_)) | Some(Tok::Punct("("))) {
+                i += 3;
+                continue;
+            }
+        }
+        match &toks[i] {
+            Tok::Num(n) => {
+                let lit = n.trim_end_matches(|c| matches!(c, 'u' | 'U' | 'l' | 'L'));
+                out.push_str(&lit.replace("0X", "0x"));
+            }
+            Tok::Punct("~") => out.push('!'),
+            Tok::Punct(p @ ("(" | "[")) => out.push_str(p),
+            Tok::Punct(p @ (")" | "]")) => {
+                out.truncate(out.trim_end().len());
+                out.push_str(p);
+            }
+            Tok::Punct(p) if i == 0 || matches!(toks[i - 1], Tok::Punct("(")) => out.push_str(p),
+            Tok::Punct(p) => {
+                out.truncate(out.trim_end().len());
+                let _ = write!(out, " {} ", p);
+            }
+            t => {
+                let _ = write!(out, "{}", t);
+            }
+        }
+        i += 1;
+    }
+    strip_parens(out.trim()).to_owned()
+}
+
+// Drops parentheses around the whole expression, which rustc warns about.
+fn strip_parens(mut expr: &str) -> &str {
+    while expr.starts_with('(') && expr.ends_with(')') {
+        let mut depth = 0;
+        let encloses = expr.char_indices().all(|(i, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            depth > 0 || i == expr.len() - 1
+        });
+        if !encloses {
+            break;
+        }
+        expr = &expr[1..expr.len() - 1];
+    }
+    expr
+}
+
+fn const_type(env: &Env, name: &str, body: &[Tok], lengths: &HashSet<String>) -> Option<&'static str> {
+    let value = env.eval(body).ok()?;
+    let wide = body.iter().any(|t| matches!(t, Tok::Num(n) if n.to_ascii_uppercase().ends_with("LL")));
+    Some(if lengths.contains(name) {
+        "size_t"
+    } else if wide || value > u32::MAX as i128 {
+        "uint64_t"
+    } else if value < 0 {
+        "int32_t"
+    } else {
+        "uint32_t"
+    })
+}
+
+fn array_lengths(ty: &CType, out: &mut HashSet<String>) {
+    match ty {
+        CType::Array(t, len) => {
+            for tok in len.iter().flatten() {
+                if let Tok::Ident(n) = tok {
+                    out.insert(n.clone());
+                }
+            }
+            array_lengths(t, out);
+        }
+        CType::Ptr(t, _) => array_lengths(t, out),
+        CType::Named(_) => {}
+    }
+}
+
+fn long_array(env: &Env, ty: &CType) -> bool {
+    match ty {
+        CType::Array(t, len) => {
+            let n = len.as_ref().and_then(|l| env.eval(l).ok()).unwrap_or(0);
+            n as u64 > MAX_DERIVE_ARRAY || long_array(env, t)
+        }
+        _ => false,
+    }
+}
+
+/// Emits the type definitions of `headers`.
+pub fn types(env: &Env, headers: &[Header<'_>]) -> Result<String, String> {
+    let mut lengths = HashSet::new();
+    for h in headers {
+        for item in &h.items {
+            match item {
+                Item::Struct(s) => s.fields.iter().for_each(|f| array_lengths(&f.ty, &mut lengths)),
+                Item::Alias(_, t) => array_lengths(t, &mut lengths),
+                _ => {}
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for h in headers {
+        section(&mut out, h.name);
+        let mut prev_block = None;
+        // Include guards and other valueless or non-integer macros are dropped.
+        let items = h.items.iter().filter(|i| match i {
+            Item::Define(name, body) => {
+                !body.iter().any(|t| matches!(t, Tok::Str(_))) && const_type(env, name, body, &lengths).is_some()
+            }
+            _ => true,
+        });
+        for item in items {
+            // Runs of constants and aliases are kept together; macro blocks
+            // are separated from each other and from the runs by a blank line.
+            let block = !matches!(item, Item::Define(..) | Item::Alias(..));
+            if prev_block.map_or(false, |p| p || block) {
+                out.push('\n');
+            }
+            prev_block = Some(block);
+            match item {
+                Item::Define(name, body) => {
+                    let ty = const_type(env, name, body, &lengths).unwrap_or_default();
+                    let _ = writeln!(out, "pub const {}: {} = {};", name, ty, rust_expr(body));
+                }
+                Item::Alias(name, ty) => {
+                    let _ = writeln!(out, "pub type {} = {};", name, rust_type(ty));
+                }
+                Item::Enum(e) => {
+                    let _ = writeln!(out, "impl_enum! {{");
+                    let _ = writeln!(out, "    #[repr(u32)]");
+                    let _ = writeln!(out, "    #[derive(Copy, Clone, PartialEq, Eq, Debug)]");
+                    let _ = writeln!(out, "    pub enum {} {{", e.name);
+                    for (v, _) in &e.variants {
+                        let value = env.enum_values[v];
+                        let _ = writeln!(out, "        {} = {},", v, value);
+                    }
+                    let _ = writeln!(out, "    }}\n}}");
+                }
+                Item::Struct(s) => {
+                    if s.union {
+                        let _ = writeln!(out, "// union {} is hand-written in types.rs", s.name);
+                        continue;
+                    }
+                    let long = s.fields.iter().any(|f| long_array(env, &f.ty));
+                    let mac = match (long, s.pack) {
+                        (true, Some(_)) => "impl_packed_copy_clone",
+                        (true, None) => "impl_copy_clone",
+                        (false, Some(_)) => "impl_packed_struct",
+                        (false, None) => "impl_struct",
+                    };
+                    let _ = writeln!(out, "{}! {{\n    pub struct {} {{", mac, s.name);
+                    for f in &s.fields {
+                        let _ = writeln!(out, "        pub {}: {},", field_name(&s.name, &f.name), rust_type(&f.ty));
+                    }
+                    let _ = writeln!(out, "    }}\n}}");
+                    if long {
+                        let size = env.struct_layout(&s.name)?.layout.size;
+                        let _ = writeln!(out, "\nimpl_struct_default! {{\n    {}; //{}\n}}", s.name, size);
+                        let _ = writeln!(out, "\nimpl_struct_ContiguousMemory! {{\n    {};\n}}", s.name);
+                    }
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn assert_line(out: &mut String, name: &str, size: u64, align: u64, fields: &[(String, u64)]) {
+    let mut args = vec![name.to_owned(), format!("size = {}", size), format!("align = {}", align)];
+    args.extend(fields.iter().map(|(f, off)| format!("{} = {}", f, off)));
+    let line = format!("assert_layout!({});", args.join(", "));
+    if line.len() <= MAX_WIDTH {
+        let _ = writeln!(out, "{}", line);
+    } else {
+        let _ = writeln!(out, "assert_layout!(");
+        for arg in args {
+            let _ = writeln!(out, "    {},", arg);
+        }
+        let _ = writeln!(out, ");");
+    }
+}
+
+/// Emits the `assert_layout!` entries for every struct in `headers`.
+pub fn layout(env: &Env, headers: &[Header<'_>]) -> Result<String, String> {
+    let mut out = String::new();
+    for h in headers {
+        let structs: Vec<_> = h
+            .items
+            .iter()
+            .filter_map(|i| match i {
+                Item::Struct(s) if !s.union => Some(s),
+                _ => None,
+            })
+            .collect();
+        if structs.is_empty() {
+            continue;
+        }
+        section(&mut out, h.name);
+        for s in structs {
+            let l = env.struct_layout(&s.name)?;
+            let fields: Vec<_> = s.fields.iter().map(|f| field_name(&s.name, &f.name)).zip(l.offsets).collect();
+            assert_line(&mut out, &s.name, l.layout.size, l.layout.align, &fields);
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,193 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Regenerates `sgx_types` definitions from a pinned set of SDK headers.
+//!
+//! ```text
+//! sgx_types_codegen pin <include_dir> <lock> <sdk_version> <header>...
+//! sgx_types_codegen types <include_dir> <lock> [out]
+//! sgx_types_codegen layout <include_dir> <lock> [out]
+//! ```
+//!
+//! `pin` records the SHA-256 of each header in the lock file. The other
+//! commands refuse to run unless the headers still match the lock, so the
+//! generated code always corresponds to a known SDK release.
+
+mod abi;
+mod c;
+mod emit;
+mod sha256;
+
+use abi::Env;
+use emit::Header;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+// Start of the first `//\n// header.h\n//` banner in a generated file;
+// everything before it is kept when the file is regenerated.
+fn first_section(text: &str) -> Option<usize> {
+    text.match_indices("\n//\n// ").map(|(i, _)| i).find(|&i| {
+        let rest = &text[i + 7..];
+        rest.split_once('\n').map_or(false, |(name, tail)| name.ends_with(".h") && tail.starts_with("//\n"))
+    })
+}
+
+struct Lock {
+    sdk: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Lock {
+    fn parse(text: &str) -> Result<Lock, String> {
+        let mut lines = text.lines().filter(|l| !l.trim().is_empty() && !l.starts_with('#'));
+        let sdk = lines
+            .next()
+            .and_then(|l| l.strip_prefix("sdk "))
+            .ok_or("lock file must start with `sdk <version>`")?
+            .trim()
+            .to_owned();
+        let mut headers = Vec::new();
+        for line in lines {
+            let (hash, name) = line.split_once("  ").ok_or_else(|| format!("malformed lock entry `{}`", line))?;
+            headers.push((hash.to_owned(), name.trim().to_owned()));
+        }
+        Ok(Lock { sdk, headers })
+    }
+
+    fn render(&self) -> String {
+        let mut out = format!("sdk {}\n", self.sdk);
+        for (hash, name) in &self.headers {
+            out.push_str(&format!("{}  {}\n", hash, name));
+        }
+        out
+    }
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn pin(dir: &Path, lock: &Path, sdk: &str, names: &[String]) -> Result<(), String> {
+    let mut headers = Vec::with_capacity(names.len());
+    for name in names {
+        let text = read(&dir.join(name))?;
+        // Fail early on headers the generator cannot handle.
+        c::parse(&text).map_err(|e| format!("{}: {}", name, e))?;
+        headers.push((sha256::hex_digest(text.as_bytes()), name.clone()));
+    }
+    let lock_text = Lock { sdk: sdk.to_owned(), headers }.render();
+    fs::write(lock, lock_text).map_err(|e| format!("{}: {}", lock.display(), e))
+}
+
+/// Loads the locked headers, checking each against its pinned digest.
+fn load(dir: &Path, lock: &Path) -> Result<Vec<(String, String)>, String> {
+    let lock = Lock::parse(&read(lock)?)?;
+    let mut sources = Vec::with_capacity(lock.headers.len());
+    for (hash, name) in lock.headers {
+        let text = read(&dir.join(&name))?;
+        let actual = sha256::hex_digest(text.as_bytes());
+        if actual != hash {
+            return Err(format!("{} does not match SDK {} (expected {}, found {})", name, lock.sdk, hash, actual));
+        }
+        sources.push((name, text));
+    }
+    Ok(sources)
+}
+
+fn generate(cmd: &str, dir: &Path, lock: &Path, out: Option<&Path>) -> Result<(), String> {
+    let sources = load(dir, lock)?;
+    let mut headers = Vec::with_capacity(sources.len());
+    for (name, text) in &sources {
+        let items = c::parse(text).map_err(|e| format!("{}: {}", name, e))?;
+        headers.push(Header { name, items });
+    }
+    let all: Vec<_> = headers.iter().flat_map(|h| h.items.iter().cloned()).collect();
+    let env = Env::new(&all)?;
+    let body = if cmd == "types" { emit::types(&env, &headers)? } else { emit::layout(&env, &headers)? };
+
+    match out {
+        Some(path) => {
+            let preamble = match fs::read_to_string(path) {
+                Ok(old) => first_section(&old).map(|i| old[..i].to_owned()).unwrap_or(old),
+                Err(_) => String::new(),
+            };
+            let text = format!("{}\n{}", preamble.trim_end(), body);
+            fs::write(path, text.trim_start()).map_err(|e| format!("{}: {}", path.display(), e))
+        }
+        None => {
+            print!("{}", body.trim_start());
+            Ok(())
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: sgx_types_codegen pin <include_dir> <lock> <sdk_version> <header>...");
+    eprintln!("       sgx_types_codegen types <include_dir> <lock> [out]");
+    eprintln!("       sgx_types_codegen layout <include_dir> <lock> [out]");
+    process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("pin") if args.len() >= 5 => pin(Path::new(&args[1]), Path::new(&args[2]), &args[3], &args[4..]),
+        Some(cmd @ ("types" | "layout")) if (3..=4).contains(&args.len()) => {
+            generate(cmd, Path::new(&args[1]), Path::new(&args[2]), args.get(3).map(Path::new))
+        }
+        _ => usage(),
+    };
+    if let Err(e) = result {
+        eprintln!("sgx_types_codegen: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_round_trip() {
+        let text = "# comment\nsdk 2.18\n\nab12  sgx_key.h\ncd34  sgx_report.h\n";
+        let lock = Lock::parse(text).unwrap();
+        assert_eq!(lock.sdk, "2.18");
+        assert_eq!(
+            lock.headers,
+            [("ab12".to_owned(), "sgx_key.h".to_owned()), ("cd34".to_owned(), "sgx_report.h".to_owned())]
+        );
+        assert_eq!(lock.render(), "sdk 2.18\nab12  sgx_key.h\ncd34  sgx_report.h\n");
+        assert!(Lock::parse("ab12  sgx_key.h\n").is_err());
+        assert!(Lock::parse("sdk 2.18\nab12 sgx_key.h\n").is_err());
+    }
+
+    #[test]
+    fn committed_lock_matches_headers() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let sources = load(&dir.join("include"), &dir.join("sdk.lock")).unwrap();
+        assert_eq!(sources.len(), 6);
+    }
+
+    #[test]
+    fn first_section_keeps_preamble() {
+        let text = "//! doc\n\nuse x;\n\n//\n// sgx_key.h\n//\nassert_layout!(a);\n";
+        assert_eq!(&text[..first_section(text).unwrap()], "//! doc\n\nuse x;\n");
+        assert_eq!(first_section("//! doc\n//\n// not a header\n//\n"), None);
+    }
+}
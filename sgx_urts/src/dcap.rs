@@ -0,0 +1,400 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! ECDSA attestation with the Intel DCAP libraries.
+//!
+//! The module implements the OCALLs of `sgx_dcap.edl`, through which
+//! `std::dcap::QuoteGenerator` in the enclave reaches the quoting enclave, and
+//! verifies quotes on the untrusted side with [`QuoteVerifier`]. The collateral
+//! that verification needs comes from a [`CollateralSource`]: the quote
+//! provider library configured on the platform by default, or a cache or
+//! service of the application.
+//!
+//! The module links against `sgx_dcap_ql`, `dcap_quoteprov` and
+//! `dcap_quoteverify`.
+
+use sgx_types::*;
+use std::ptr;
+use std::slice;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn check(ret: sgx_quote3_error_t) -> Result<(), sgx_quote3_error_t> {
+    match ret {
+        sgx_quote3_error_t::SGX_QL_SUCCESS => Ok(()),
+        _ => Err(ret),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn u_dcap_target_info_ocall(
+    target_info: *mut sgx_target_info_t,
+    quote_size: *mut u32,
+) -> u32 {
+    if target_info.is_null() || quote_size.is_null() {
+        return sgx_quote3_error_t::SGX_QL_ERROR_INVALID_PARAMETER as u32;
+    }
+    let ret = unsafe { sgx_qe_get_target_info(target_info) };
+    if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+        return ret as u32;
+    }
+    unsafe { sgx_qe_get_quote_size(quote_size) as u32 }
+}
+
+#[no_mangle]
+pub extern "C" fn u_dcap_quote_ocall(
+    report: *const sgx_report_t,
+    quote: *mut u8,
+    cap: u32,
+    quote_size: *mut u32,
+) -> u32 {
+    if report.is_null() || quote.is_null() || quote_size.is_null() {
+        return sgx_quote3_error_t::SGX_QL_ERROR_INVALID_PARAMETER as u32;
+    }
+    let mut size = 0_u32;
+    let ret = unsafe { sgx_qe_get_quote_size(&mut size) };
+    if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+        return ret as u32;
+    }
+    unsafe { *quote_size = size };
+    if size > cap {
+        return sgx_quote3_error_t::SGX_QL_ERROR_INVALID_PARAMETER as u32;
+    }
+    unsafe { sgx_qe_get_quote(report, size, quote) as u32 }
+}
+
+/// The TCB Info of a platform model, as signed by the Intel PCS.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TcbInfo {
+    /// The signed JSON structure.
+    pub body: Vec<u8>,
+    /// The PEM certificate chain of its signing key.
+    pub issuer_chain: Vec<u8>,
+}
+
+/// The identity of the quoting enclave, as signed by the Intel PCS.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QeIdentity {
+    /// The signed JSON structure.
+    pub body: Vec<u8>,
+    /// The PEM certificate chain of its signing key.
+    pub issuer_chain: Vec<u8>,
+}
+
+/// The collateral with which a quote is verified.
+///
+/// The buffers are kept as the quote provider returns them, including the
+/// terminating NUL of the PEM and JSON strings, which the verification library
+/// expects.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Collateral {
+    pub version: u32,
+    /// 0 for SGX, 0x81 for TDX.
+    pub tee_type: u32,
+    pub pck_crl_issuer_chain: Vec<u8>,
+    pub root_ca_crl: Vec<u8>,
+    pub pck_crl: Vec<u8>,
+    pub tcb_info: TcbInfo,
+    pub qe_identity: QeIdentity,
+}
+
+impl Collateral {
+    /// Copies the collateral that `raw` points to.
+    ///
+    /// # Safety
+    ///
+    /// Every buffer of `raw` must be valid for its size.
+    pub unsafe fn from_raw(raw: &sgx_ql_qve_collateral_t) -> Collateral {
+        unsafe fn copy(p: *const c_char, size: u32) -> Vec<u8> {
+            if p.is_null() || size == 0 {
+                Vec::new()
+            } else {
+                slice::from_raw_parts(p as *const u8, size as usize).to_vec()
+            }
+        }
+        Collateral {
+            version: raw.version,
+            tee_type: raw.tee_type,
+            pck_crl_issuer_chain: copy(raw.pck_crl_issuer_chain, raw.pck_crl_issuer_chain_size),
+            root_ca_crl: copy(raw.root_ca_crl, raw.root_ca_crl_size),
+            pck_crl: copy(raw.pck_crl, raw.pck_crl_size),
+            tcb_info: TcbInfo {
+                body: copy(raw.tcb_info, raw.tcb_info_size),
+                issuer_chain: copy(raw.tcb_info_issuer_chain, raw.tcb_info_issuer_chain_size),
+            },
+            qe_identity: QeIdentity {
+                body: copy(raw.qe_identity, raw.qe_identity_size),
+                issuer_chain: copy(
+                    raw.qe_identity_issuer_chain,
+                    raw.qe_identity_issuer_chain_size,
+                ),
+            },
+        }
+    }
+
+    /// Returns the raw form of the collateral, which borrows its buffers.
+    ///
+    /// The pointers are only valid while `self` is neither moved nor modified.
+    pub fn as_raw(&self) -> sgx_ql_qve_collateral_t {
+        fn raw(buf: &[u8]) -> (*mut c_char, u32) {
+            if buf.is_empty() {
+                (ptr::null_mut(), 0)
+            } else {
+                (buf.as_ptr() as *mut c_char, buf.len() as u32)
+            }
+        }
+        let (pck_crl_issuer_chain, pck_crl_issuer_chain_size) = raw(&self.pck_crl_issuer_chain);
+        let (root_ca_crl, root_ca_crl_size) = raw(&self.root_ca_crl);
+        let (pck_crl, pck_crl_size) = raw(&self.pck_crl);
+        let (tcb_info_issuer_chain, tcb_info_issuer_chain_size) = raw(&self.tcb_info.issuer_chain);
+        let (tcb_info, tcb_info_size) = raw(&self.tcb_info.body);
+        let (qe_identity_issuer_chain, qe_identity_issuer_chain_size) =
+            raw(&self.qe_identity.issuer_chain);
+        let (qe_identity, qe_identity_size) = raw(&self.qe_identity.body);
+        sgx_ql_qve_collateral_t {
+            version: self.version,
+            tee_type: self.tee_type,
+            pck_crl_issuer_chain,
+            pck_crl_issuer_chain_size,
+            root_ca_crl,
+            root_ca_crl_size,
+            pck_crl,
+            pck_crl_size,
+            tcb_info_issuer_chain,
+            tcb_info_issuer_chain_size,
+            tcb_info,
+            tcb_info_size,
+            qe_identity_issuer_chain,
+            qe_identity_issuer_chain_size,
+            qe_identity,
+            qe_identity_size,
+        }
+    }
+}
+
+/// A source of the collateral of quotes.
+///
+/// Implementations may cache collateral, which stays valid until its
+/// `nextUpdate` date, or fetch it from a PCCS of their own.
+pub trait CollateralSource {
+    fn collateral(&self, quote: &[u8]) -> Result<Collateral, sgx_quote3_error_t>;
+}
+
+/// The collateral of the quote provider library configured on the platform.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlatformCollateral;
+
+impl CollateralSource for PlatformCollateral {
+    fn collateral(&self, quote: &[u8]) -> Result<Collateral, sgx_quote3_error_t> {
+        let mut buf: *mut u8 = ptr::null_mut();
+        let mut size = 0_u32;
+        check(unsafe {
+            tee_qv_get_collateral(quote.as_ptr(), quote.len() as u32, &mut buf, &mut size)
+        })?;
+        if buf.is_null() || (size as usize) < std::mem::size_of::<sgx_ql_qve_collateral_t>() {
+            if !buf.is_null() {
+                unsafe { tee_qv_free_collateral(buf) };
+            }
+            return Err(sgx_quote3_error_t::SGX_QL_ERROR_UNEXPECTED);
+        }
+        // The buffer starts with the collateral structure, whose pointers
+        // refer to the rest of the buffer.
+        let collateral = unsafe { Collateral::from_raw(&*(buf as *const sgx_ql_qve_collateral_t)) };
+        unsafe { tee_qv_free_collateral(buf) };
+        Ok(collateral)
+    }
+}
+
+/// The supplemental data of a verification, which relying parties use to
+/// apply a policy of their own to platforms that are not up to date.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Supplemental {
+    /// The earliest issue date of the collateral, in seconds since the epoch.
+    pub earliest_issue_date: i64,
+    pub latest_issue_date: i64,
+    pub earliest_expiration_date: i64,
+    /// The date of the TCB level of the platform.
+    pub tcb_level_date_tag: i64,
+    pub pck_crl_num: u32,
+    pub root_ca_crl_num: u32,
+    pub tcb_eval_ref_num: u32,
+    pub tcb_cpusvn: [u8; SGX_CPUSVN_SIZE],
+    pub tcb_pce_isvsvn: sgx_isv_svn_t,
+    pub pce_id: u16,
+    /// Whether the platform is multi-package; `None` if unknown.
+    pub dynamic_platform: Option<bool>,
+    pub cached_keys: Option<bool>,
+    pub smt_enabled: Option<bool>,
+    /// The Intel security advisories that apply to the platform, such as
+    /// `INTEL-SA-00334`.
+    pub advisory_ids: Vec<String>,
+}
+
+impl Supplemental {
+    fn from_raw(raw: &sgx_ql_qv_supplemental_t) -> Supplemental {
+        fn flag(f: pck_cert_flag_enum_t) -> Option<bool> {
+            match f {
+                pck_cert_flag_enum_t::PCK_FLAG_FALSE => Some(false),
+                pck_cert_flag_enum_t::PCK_FLAG_TRUE => Some(true),
+                _ => None,
+            }
+        }
+        let sa_list: Vec<u8> = raw
+            .sa_list
+            .iter()
+            .take_while(|c| **c != 0)
+            .map(|c| *c as u8)
+            .collect();
+        Supplemental {
+            earliest_issue_date: raw.earliest_issue_date,
+            latest_issue_date: raw.latest_issue_date,
+            earliest_expiration_date: raw.earliest_expiration_date,
+            tcb_level_date_tag: raw.tcb_level_date_tag,
+            pck_crl_num: raw.pck_crl_num,
+            root_ca_crl_num: raw.root_ca_crl_num,
+            tcb_eval_ref_num: raw.tcb_eval_ref_num,
+            tcb_cpusvn: raw.tcb_cpusvn.svn,
+            tcb_pce_isvsvn: raw.tcb_pce_isvsvn,
+            pce_id: raw.pce_id,
+            dynamic_platform: flag(raw.dynamic_platform),
+            cached_keys: flag(raw.cached_keys),
+            smt_enabled: flag(raw.smt_enabled),
+            advisory_ids: String::from_utf8_lossy(&sa_list)
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+/// The outcome of the verification of a quote.
+#[derive(Clone, Debug)]
+pub struct Verification {
+    pub result: sgx_ql_qv_result_t,
+    /// Whether some of the collateral had expired at the verification date.
+    pub collateral_expired: bool,
+    /// The supplemental data, if the verification library provides it.
+    pub supplemental: Option<Supplemental>,
+}
+
+impl Verification {
+    /// Returns true if the quote is genuine and the platform at the latest TCB
+    /// level, with collateral that had not expired.
+    pub fn is_up_to_date(&self) -> bool {
+        self.result == sgx_ql_qv_result_t::SGX_QL_QV_RESULT_OK && !self.collateral_expired
+    }
+
+    /// Returns true if the quote is signed by a genuine platform, whatever its
+    /// TCB level. Whether an outdated platform is acceptable is up to the
+    /// relying party, with the help of the supplemental data.
+    pub fn is_genuine(&self) -> bool {
+        !matches!(
+            self.result,
+            sgx_ql_qv_result_t::SGX_QL_QV_RESULT_INVALID_SIGNATURE
+                | sgx_ql_qv_result_t::SGX_QL_QV_RESULT_REVOKED
+                | sgx_ql_qv_result_t::SGX_QL_QV_RESULT_UNSPECIFIED
+        )
+    }
+}
+
+/// Verifies DCAP quotes on the untrusted side.
+///
+/// Verification runs in the verification library, without the quote
+/// verification enclave, so its outcome is only as trustworthy as the host.
+/// An enclave that relies on a quote must verify it with the QvE or a verifier
+/// of its own.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QuoteVerifier {
+    expiration_date: Option<i64>,
+}
+
+impl QuoteVerifier {
+    pub fn new() -> QuoteVerifier {
+        QuoteVerifier::default()
+    }
+
+    /// Sets the date, in seconds since the epoch, at which the collateral must
+    /// not have expired. Defaults to the current time.
+    pub fn expiration_date(mut self, date: i64) -> QuoteVerifier {
+        self.expiration_date = Some(date);
+        self
+    }
+
+    /// Verifies `quote` with `collateral`, or with the collateral of the
+    /// platform's quote provider if `None`.
+    pub fn verify(
+        &self,
+        quote: &[u8],
+        collateral: Option<&Collateral>,
+    ) -> Result<Verification, sgx_quote3_error_t> {
+        let quote_size = u32::try_from(quote.len())
+            .map_err(|_| sgx_quote3_error_t::SGX_QL_ERROR_INVALID_PARAMETER)?;
+        let date = match self.expiration_date {
+            Some(date) => date,
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64),
+        };
+
+        let mut supplemental_size = 0_u32;
+        check(unsafe { sgx_qv_get_quote_supplemental_data_size(&mut supplemental_size) })?;
+        let mut supplemental = sgx_ql_qv_supplemental_t::default();
+        let (supplemental_ptr, supplemental_size) =
+            if supplemental_size as usize == std::mem::size_of::<sgx_ql_qv_supplemental_t>() {
+                (&mut supplemental as *mut _ as *mut u8, supplemental_size)
+            } else {
+                (ptr::null_mut(), 0)
+            };
+
+        let raw = collateral.map(Collateral::as_raw);
+        let mut expired = 0_u32;
+        let mut result = sgx_ql_qv_result_t::SGX_QL_QV_RESULT_UNSPECIFIED;
+        check(unsafe {
+            sgx_qv_verify_quote(
+                quote.as_ptr(),
+                quote_size,
+                raw.as_ref().map_or(ptr::null(), |r| r as *const _),
+                date as time_t,
+                &mut expired,
+                &mut result,
+                ptr::null_mut(),
+                supplemental_size,
+                supplemental_ptr,
+            )
+        })?;
+        Ok(Verification {
+            result,
+            collateral_expired: expired != 0,
+            supplemental: if supplemental_ptr.is_null() {
+                None
+            } else {
+                Some(Supplemental::from_raw(&supplemental))
+            },
+        })
+    }
+
+    /// Verifies `quote` with collateral fetched from `source`.
+    pub fn verify_with<S: CollateralSource + ?Sized>(
+        &self,
+        quote: &[u8],
+        source: &S,
+    ) -> Result<Verification, sgx_quote3_error_t> {
+        let collateral = source.collateral(quote)?;
+        self.verify(quote, Some(&collateral))
+    }
+}
@@ -15,6 +15,7 @@
 // specific language governing permissions and limitations
 // under the License..
 
+use crate::switchless::SwitchlessConfig;
 use sgx_types::*;
 use std::ffi::{CStr, CString};
 use std::io;
@@ -435,11 +436,38 @@ pub fn rsgx_create_enclave_with_workers(
     num_uworkers: u32,
     num_tworkers: u32,
 ) -> SgxResult<sgx_enclave_id_t> {
-    let us_config = sgx_uswitchless_config_t {
-        num_tworkers: num_tworkers as u64,
-        num_uworkers: num_uworkers as u64,
-        ..Default::default()
-    };
+    let config = SwitchlessConfig::new()
+        .untrusted_workers(num_uworkers)
+        .trusted_workers(num_tworkers);
+    rsgx_create_enclave_with_switchless(
+        file_name,
+        debug,
+        launch_token,
+        launch_token_updated,
+        misc_attr,
+        &config,
+    )
+}
+
+///
+/// Loads the enclave and initializes it with the switchless workers and
+/// tuning of `config`.
+///
+/// # Description
+///
+/// This is rsgx_create_enclave_with_workers, with every knob of the
+/// switchless library exposed. The worker events are counted in
+/// `switchless::stats`.
+///
+pub fn rsgx_create_enclave_with_switchless(
+    file_name: &CStr,
+    debug: i32,
+    launch_token: &mut sgx_launch_token_t,
+    launch_token_updated: &mut i32,
+    misc_attr: &mut sgx_misc_attribute_t,
+    config: &SwitchlessConfig,
+) -> SgxResult<sgx_enclave_id_t> {
+    let us_config = config.to_raw();
     let mut enclave_ex_p: [*const c_void; 32] = [ptr::null(); 32];
     enclave_ex_p[SGX_CREATE_ENCLAVE_EX_SWITCHLESS_BIT_IDX] =
         &us_config as *const sgx_uswitchless_config_t as *const c_void;
@@ -643,6 +671,34 @@ impl SgxEnclave {
         Ok(enclave)
     }
 
+    pub fn create_with_switchless<P: AsRef<Path>>(
+        file_name: P,
+        debug: i32,
+        launch_token: &mut sgx_launch_token_t,
+        launch_token_updated: &mut i32,
+        misc_attr: &mut sgx_misc_attribute_t,
+        config: &SwitchlessConfig,
+    ) -> SgxResult<SgxEnclave> {
+        let path: CString =
+            cstr(file_name.as_ref()).map_err(|_| sgx_status_t::SGX_ERROR_INVALID_ENCLAVE)?;
+        let enclave = rsgx_create_enclave_with_switchless(
+            path.as_c_str(),
+            debug,
+            launch_token,
+            launch_token_updated,
+            misc_attr,
+            config,
+        )
+        .map(|eid| SgxEnclave {
+            id: eid,
+            debug,
+            path: file_name.as_ref().to_owned(),
+        })?;
+
+        enclave.init();
+        Ok(enclave)
+    }
+
     pub fn create_from_buffer(
         buffer: &[u8],
         debug: i32,
@@ -692,6 +748,173 @@ impl SgxEnclave {
         rsgx_get_target_info(self.id)
     }
 
+    /// Reads the OCALL surface manifest the enclave registered with
+    /// `std::ocall::register_surface`, through the ECALL declared in
+    /// `sgx_ocall_surface.edl`.
+    #[cfg(feature = "ocall_surface")]
+    pub fn ocall_surface(&self) -> SgxResult<String> {
+        extern "C" {
+            fn t_ocall_surface_ecall(
+                eid: sgx_enclave_id_t,
+                retval: *mut sgx_status_t,
+                buf: *mut u8,
+                len: usize,
+                needed: *mut usize,
+            ) -> sgx_status_t;
+        }
+
+        let mut buf: Vec<u8> = Vec::new();
+        loop {
+            let mut retval = sgx_status_t::SGX_SUCCESS;
+            let mut needed = 0_usize;
+            let ret = unsafe {
+                t_ocall_surface_ecall(
+                    self.id,
+                    &mut retval as *mut sgx_status_t,
+                    if buf.is_empty() { ptr::null_mut() } else { buf.as_mut_ptr() },
+                    buf.len(),
+                    &mut needed as *mut usize,
+                )
+            };
+            if ret != sgx_status_t::SGX_SUCCESS {
+                return Err(ret);
+            }
+            match retval {
+                sgx_status_t::SGX_SUCCESS if needed <= buf.len() => {
+                    buf.truncate(needed);
+                    return String::from_utf8(buf).map_err(|_| sgx_status_t::SGX_ERROR_UNEXPECTED);
+                }
+                sgx_status_t::SGX_ERROR_INVALID_PARAMETER if needed > buf.len() => {
+                    buf.resize(needed, 0)
+                }
+                sgx_status_t::SGX_SUCCESS => return Err(sgx_status_t::SGX_ERROR_UNEXPECTED),
+                err => return Err(err),
+            }
+        }
+    }
+
+    /// Warms the enclave up before it serves traffic, through the ECALL
+    /// declared in `sgx_prewarm.edl`. See `std::prewarm`.
+    ///
+    /// Fails with `SGX_ERROR_UNEXPECTED` if a warm-up task failed; calling it
+    /// again retries the failed tasks only.
+    #[cfg(feature = "prewarm")]
+    pub fn prewarm(&self) -> SgxResult<()> {
+        extern "C" {
+            fn t_prewarm_ecall(eid: sgx_enclave_id_t, retval: *mut sgx_status_t) -> sgx_status_t;
+        }
+
+        let mut retval = sgx_status_t::SGX_SUCCESS;
+        let ret = unsafe { t_prewarm_ecall(self.id, &mut retval as *mut sgx_status_t) };
+        if ret != sgx_status_t::SGX_SUCCESS {
+            return Err(ret);
+        }
+        match retval {
+            sgx_status_t::SGX_SUCCESS => Ok(()),
+            err => Err(err),
+        }
+    }
+
+    /// Runs the health checks of the enclave through the ECALL declared in
+    /// `sgx_health.edl` and returns its readiness report. See `std::health`.
+    ///
+    /// The report comes from the enclave but is not authenticated: code on
+    /// the host can alter it.
+    #[cfg(feature = "health")]
+    pub fn health(&self) -> SgxResult<crate::health::HealthReport> {
+        extern "C" {
+            fn t_health_ecall(
+                eid: sgx_enclave_id_t,
+                retval: *mut sgx_status_t,
+                buf: *mut u8,
+                cap: usize,
+                len: *mut usize,
+            ) -> sgx_status_t;
+        }
+
+        let mut buf = vec![0_u8; 4096];
+        loop {
+            let mut retval = sgx_status_t::SGX_SUCCESS;
+            let mut len = 0_usize;
+            let ret = unsafe {
+                t_health_ecall(
+                    self.id,
+                    &mut retval as *mut sgx_status_t,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut len as *mut usize,
+                )
+            };
+            if ret != sgx_status_t::SGX_SUCCESS {
+                return Err(ret);
+            }
+            match retval {
+                sgx_status_t::SGX_SUCCESS if len <= buf.len() => {
+                    return crate::health::HealthReport::decode(&buf[..len])
+                        .ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED);
+                }
+                // Checks may have added to the report since; try again.
+                sgx_status_t::SGX_ERROR_OUT_OF_MEMORY if len > buf.len() && len <= 1 << 24 => {
+                    buf.resize(len, 0)
+                }
+                sgx_status_t::SGX_SUCCESS => return Err(sgx_status_t::SGX_ERROR_UNEXPECTED),
+                err => return Err(err),
+            }
+        }
+    }
+
+    /// Returns the transition counters of the host threads, with the counts
+    /// of asynchronous exits the enclave keeps per TCS, collected through the
+    /// ECALL declared in `sgx_perf.edl`. See `crate::perf` and `std::perf`.
+    ///
+    /// The ECALL that collects the counts is itself counted.
+    #[cfg(feature = "perf")]
+    pub fn perf(&self) -> SgxResult<crate::perf::PerfReport> {
+        extern "C" {
+            fn t_perf_ecall(
+                eid: sgx_enclave_id_t,
+                retval: *mut sgx_status_t,
+                buf: *mut u8,
+                cap: usize,
+                len: *mut usize,
+            ) -> sgx_status_t;
+        }
+
+        let mut buf = vec![0_u8; 1024];
+        loop {
+            let mut retval = sgx_status_t::SGX_SUCCESS;
+            let mut len = 0_usize;
+            let ret = unsafe {
+                t_perf_ecall(
+                    self.id,
+                    &mut retval as *mut sgx_status_t,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut len as *mut usize,
+                )
+            };
+            if ret != sgx_status_t::SGX_SUCCESS {
+                return Err(ret);
+            }
+            match retval {
+                sgx_status_t::SGX_SUCCESS if len <= buf.len() => {
+                    let tcss = crate::perf::PerfReport::decode_tcss(&buf[..len])
+                        .ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)?;
+                    return Ok(crate::perf::PerfReport {
+                        threads: crate::perf::threads(),
+                        tcss,
+                    });
+                }
+                // More threads may have enabled counting since; try again.
+                sgx_status_t::SGX_ERROR_OUT_OF_MEMORY if len > buf.len() && len <= 1 << 24 => {
+                    buf.resize(len, 0)
+                }
+                sgx_status_t::SGX_SUCCESS => return Err(sgx_status_t::SGX_ERROR_UNEXPECTED),
+                err => return Err(err),
+            }
+        }
+    }
+
     fn exit(&self) {
         #[cfg(feature = "global_exit")]
         {
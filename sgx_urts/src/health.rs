@@ -0,0 +1,131 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Readiness reports of enclaves, as returned by `SgxEnclave::health`.
+
+use std::time::Duration;
+
+/// The overall state of an enclave.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Readiness {
+    Ready,
+    Degraded,
+    NotReady,
+}
+
+/// The outcome of a check.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CheckStatus {
+    Pass,
+    Degraded,
+    Fail,
+}
+
+/// The outcome of one check run by the enclave.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HealthCheck {
+    pub name: String,
+    /// Whether a failure of the check makes the enclave not ready.
+    pub required: bool,
+    pub status: CheckStatus,
+    pub message: String,
+    pub elapsed: Duration,
+}
+
+/// The readiness report of an enclave.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HealthReport {
+    pub readiness: Readiness,
+    pub checks: Vec<HealthCheck>,
+}
+
+impl HealthReport {
+    /// Returns true if the enclave is ready or degraded.
+    pub fn is_serving(&self) -> bool {
+        self.readiness != Readiness::NotReady
+    }
+
+    /// Decodes a report in the format of `std::health::Report::encode`.
+    pub fn decode(bytes: &[u8]) -> Option<HealthReport> {
+        struct Reader<'a>(&'a [u8]);
+        impl<'a> Reader<'a> {
+            fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+                if self.0.len() < n {
+                    return None;
+                }
+                let (head, tail) = self.0.split_at(n);
+                self.0 = tail;
+                Some(head)
+            }
+            fn u8(&mut self) -> Option<u8> {
+                self.take(1).map(|b| b[0])
+            }
+            fn u16(&mut self) -> Option<u16> {
+                self.take(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+            }
+            fn u64(&mut self) -> Option<u64> {
+                let mut b = [0_u8; 8];
+                b.copy_from_slice(self.take(8)?);
+                Some(u64::from_le_bytes(b))
+            }
+            fn string(&mut self) -> Option<String> {
+                let len = self.u16()? as usize;
+                String::from_utf8(self.take(len)?.to_vec()).ok()
+            }
+        }
+
+        let mut reader = Reader(bytes);
+        if reader.u8()? != 1 {
+            return None;
+        }
+        let readiness = match reader.u8()? {
+            0 => Readiness::Ready,
+            1 => Readiness::Degraded,
+            2 => Readiness::NotReady,
+            _ => return None,
+        };
+        let count = reader.u16()? as usize;
+        let mut checks = Vec::with_capacity(count);
+        for _ in 0..count {
+            let name = reader.string()?;
+            let required = match reader.u8()? {
+                0 => true,
+                1 => false,
+                _ => return None,
+            };
+            let status = match reader.u8()? {
+                0 => CheckStatus::Pass,
+                1 => CheckStatus::Degraded,
+                2 => CheckStatus::Fail,
+                _ => return None,
+            };
+            let message = reader.string()?;
+            let elapsed = Duration::from_micros(reader.u64()?);
+            checks.push(HealthCheck {
+                name,
+                required,
+                status,
+                message,
+                elapsed,
+            });
+        }
+        if !reader.0.is_empty() {
+            return None;
+        }
+        Some(HealthReport { readiness, checks })
+    }
+}
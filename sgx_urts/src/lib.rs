@@ -22,16 +22,24 @@ extern crate libc;
 extern crate sgx_types;
 
 pub mod asyncio;
+#[cfg(feature = "dcap")]
+pub mod dcap;
 pub mod env;
 pub mod event;
 pub mod fd;
 pub mod file;
+#[cfg(feature = "health")]
+pub mod health;
+pub mod log;
 pub mod mem;
 pub mod net;
+#[cfg(feature = "perf")]
+pub mod perf;
 pub mod pipe;
 pub mod process;
 pub mod signal;
 pub mod socket;
+pub mod switchless;
 pub mod sys;
 pub mod thread;
 pub mod time;
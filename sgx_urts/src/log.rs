@@ -0,0 +1,107 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+use libc::{self, c_int, size_t, ssize_t};
+use std::io::{self, Write};
+use std::slice;
+use std::sync::RwLock;
+
+/// A host-side sink for records shipped by the enclave log pipeline.
+///
+/// The collector receives one batch at a time and returns how many records,
+/// counted from the front of the batch, it accepted. Records past that point
+/// are accounted as dropped by the enclave.
+pub type LogCollector = Box<dyn Fn(&[&[u8]]) -> usize + Send + Sync>;
+
+static COLLECTOR: RwLock<Option<LogCollector>> = RwLock::new(None);
+
+/// Installs the collector used for `u_log_ship_ocall`, replacing any previous one.
+///
+/// Without a collector, records are written to stderr one per line.
+pub fn set_log_collector(collector: LogCollector) {
+    let mut guard = COLLECTOR.write().unwrap_or_else(|e| e.into_inner());
+    *guard = Some(collector);
+}
+
+/// Removes the installed collector and falls back to stderr.
+pub fn clear_log_collector() {
+    let mut guard = COLLECTOR.write().unwrap_or_else(|e| e.into_inner());
+    *guard = None;
+}
+
+fn decode_batch(batch: &[u8], count: usize) -> Vec<&[u8]> {
+    let mut records = Vec::with_capacity(count);
+    let mut rest = batch;
+    while records.len() < count && rest.len() >= 4 {
+        let len = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        rest = &rest[4..];
+        if rest.len() < len {
+            break;
+        }
+        let (record, tail) = rest.split_at(len);
+        records.push(record);
+        rest = tail;
+    }
+    records
+}
+
+fn stderr_collector(records: &[&[u8]]) -> usize {
+    let stderr = io::stderr();
+    let mut out = stderr.lock();
+    let mut acked = 0;
+    for record in records {
+        if out.write_all(record).and_then(|_| out.write_all(b"\n")).is_err() {
+            break;
+        }
+        acked += 1;
+    }
+    let _ = out.flush();
+    acked
+}
+
+#[no_mangle]
+pub extern "C" fn u_log_ship_ocall(
+    error: *mut c_int,
+    batch: *const u8,
+    len: size_t,
+    count: size_t,
+) -> ssize_t {
+    let mut errno = 0;
+    let ret = if batch.is_null() && len != 0 {
+        errno = libc::EINVAL;
+        -1
+    } else {
+        let batch = if len == 0 {
+            &[][..]
+        } else {
+            unsafe { slice::from_raw_parts(batch, len) }
+        };
+        let records = decode_batch(batch, count);
+        let guard = COLLECTOR.read().unwrap_or_else(|e| e.into_inner());
+        let acked = match guard.as_ref() {
+            Some(collector) => collector(&records),
+            None => stderr_collector(&records),
+        };
+        acked.min(records.len()) as ssize_t
+    };
+    if !error.is_null() {
+        unsafe {
+            *error = errno;
+        }
+    }
+    ret
+}
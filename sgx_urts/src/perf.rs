@@ -0,0 +1,408 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Transition counters of the host threads, as returned by `SgxEnclave::perf`.
+//!
+//! The counters are kept by [`__wrap_sgx_ecall`], which stands in for
+//! `sgx_ecall` of the untrusted runtime when the application is linked with
+//! `-Wl,--wrap=sgx_ecall`. It counts the ECALLs of each host thread and gives
+//! the enclave an OCALL table of its own, whose entries time each OCALL
+//! before handing it to the bridge generated by edger8r. The linker option
+//! is required by the `perf` feature, since the wrapper makes the ECALL
+//! through `__real_sgx_ecall`; a build script can pass it with
+//! `cargo:rustc-link-arg=-Wl,--wrap=sgx_ecall`. ECALLs made from code not
+//! linked with the option, such as a library of its own, are not counted.
+//!
+//! An ECALL is two transitions (EENTER and EEXIT) and so is an OCALL (EEXIT
+//! and ERESUME). Asynchronous exits are invisible to the host; the enclave
+//! counts them itself through AEX-Notify (see `std::perf`), per TCS.
+//!
+//! Switchless calls do not leave the enclave and are not counted. The time
+//! of an OCALL includes the ECALLs nested in it.
+
+use sgx_types::*;
+use std::cell::Cell;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+/// The number of OCALL time buckets. Bucket 0 counts the OCALLs shorter than
+/// a microsecond, bucket `i` those of `2^(i - 1)` to `2^i` microseconds, and
+/// the last one all longer OCALLs as well.
+pub const OCALL_BUCKETS: usize = 20;
+
+/// The largest OCALL table whose OCALLs can be timed. The ECALLs of an
+/// enclave with more OCALLs are counted, their OCALLs are not.
+pub const MAX_OCALLS: usize = 512;
+
+/// The shortest OCALL counted in bucket `i`.
+pub fn bucket_floor(i: usize) -> Duration {
+    match i {
+        0 => Duration::ZERO,
+        i => Duration::from_micros(1 << (i.min(OCALL_BUCKETS) - 1)),
+    }
+}
+
+fn bucket(elapsed: Duration) -> usize {
+    let micros = elapsed.as_micros();
+    let bits = (u128::BITS - micros.leading_zeros()) as usize;
+    bits.min(OCALL_BUCKETS - 1)
+}
+
+struct Counters {
+    thread: ThreadId,
+    name: Option<String>,
+    ecalls: AtomicU64,
+    ocalls: AtomicU64,
+    ocall_nanos: AtomicU64,
+    ocall_buckets: [AtomicU64; OCALL_BUCKETS],
+}
+
+impl Counters {
+    fn snapshot(&self) -> ThreadPerf {
+        let mut ocall_buckets = [0; OCALL_BUCKETS];
+        for (count, counter) in ocall_buckets.iter_mut().zip(self.ocall_buckets.iter()) {
+            *count = counter.load(Ordering::Relaxed);
+        }
+        ThreadPerf {
+            thread: self.thread,
+            name: self.name.clone(),
+            ecalls: self.ecalls.load(Ordering::Relaxed),
+            ocalls: self.ocalls.load(Ordering::Relaxed),
+            ocall_time: Duration::from_nanos(self.ocall_nanos.load(Ordering::Relaxed)),
+            ocall_buckets,
+        }
+    }
+
+    fn reset(&self) {
+        self.ecalls.store(0, Ordering::Relaxed);
+        self.ocalls.store(0, Ordering::Relaxed);
+        self.ocall_nanos.store(0, Ordering::Relaxed);
+        for counter in self.ocall_buckets.iter() {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static THREADS: Mutex<Vec<Arc<Counters>>> = Mutex::new(Vec::new());
+
+// The counters of a thread, registered while the thread lives.
+struct Local(Arc<Counters>);
+
+impl Local {
+    fn register() -> Local {
+        let current = thread::current();
+        let counters = Arc::new(Counters {
+            thread: current.id(),
+            name: current.name().map(str::to_owned),
+            ecalls: AtomicU64::new(0),
+            ocalls: AtomicU64::new(0),
+            ocall_nanos: AtomicU64::new(0),
+            ocall_buckets: Default::default(),
+        });
+        THREADS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(counters.clone());
+        Local(counters)
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        THREADS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|counters| !Arc::ptr_eq(counters, &self.0));
+    }
+}
+
+thread_local! {
+    static LOCAL: Local = Local::register();
+    // The OCALL table of the innermost ECALL of the thread.
+    static CURRENT: Cell<*const RawTable> = Cell::new(ptr::null());
+}
+
+fn with_counters<F: FnOnce(&Counters)>(f: F) {
+    // Nothing is counted while the thread exits.
+    let _ = LOCAL.try_with(|local| f(&local.0));
+}
+
+/// The counters of one host thread.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThreadPerf {
+    pub thread: ThreadId,
+    pub name: Option<String>,
+    pub ecalls: u64,
+    pub ocalls: u64,
+    /// The time spent in OCALLs.
+    pub ocall_time: Duration,
+    /// The number of OCALLs per duration; see [`OCALL_BUCKETS`].
+    pub ocall_buckets: [u64; OCALL_BUCKETS],
+}
+
+impl ThreadPerf {
+    /// The number of enclave entries and exits.
+    pub fn transitions(&self) -> u64 {
+        (self.ecalls + self.ocalls) * 2
+    }
+}
+
+/// The exits counted by an enclave on one of its TCSs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TcsPerf {
+    /// The address of the TCS in the enclave.
+    pub tcs: u64,
+    pub aexs: u64,
+}
+
+/// The counters of the host threads, and of the TCSs of an enclave.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PerfReport {
+    /// The living host threads that made an ECALL, into any enclave.
+    pub threads: Vec<ThreadPerf>,
+    /// The TCSs of the enclave that enabled AEX counting.
+    pub tcss: Vec<TcsPerf>,
+}
+
+impl PerfReport {
+    /// The number of asynchronous exits on all TCSs.
+    pub fn aexs(&self) -> u64 {
+        self.tcss.iter().map(|tcs| tcs.aexs).sum()
+    }
+
+    /// The number of enclave entries and exits of all host threads.
+    pub fn transitions(&self) -> u64 {
+        self.threads.iter().map(ThreadPerf::transitions).sum()
+    }
+
+    /// Decodes the counts of the TCSs in the format of `std::perf::encode`.
+    pub fn decode_tcss(bytes: &[u8]) -> Option<Vec<TcsPerf>> {
+        let (&version, rest) = bytes.split_first()?;
+        if version != 1 || rest.len() < 4 {
+            return None;
+        }
+        let (count, rest) = rest.split_at(4);
+        let count = u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize;
+        if rest.len() != count.checked_mul(16)? {
+            return None;
+        }
+        let u64_at = |b: &[u8]| {
+            let mut le = [0_u8; 8];
+            le.copy_from_slice(b);
+            u64::from_le_bytes(le)
+        };
+        Some(
+            rest.chunks_exact(16)
+                .map(|entry| TcsPerf {
+                    tcs: u64_at(&entry[..8]),
+                    aexs: u64_at(&entry[8..]),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Returns true once an ECALL went through [`__wrap_sgx_ecall`].
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// The counters of the living host threads that made an ECALL.
+pub fn threads() -> Vec<ThreadPerf> {
+    THREADS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .map(|counters| counters.snapshot())
+        .collect()
+}
+
+/// The counters of the current thread.
+pub fn current() -> Option<ThreadPerf> {
+    LOCAL.try_with(|local| local.0.snapshot()).ok()
+}
+
+/// Sets the counters of all host threads to zero.
+pub fn reset() {
+    for counters in THREADS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+    {
+        counters.reset();
+    }
+}
+
+type OcallFn = unsafe extern "C" fn(*mut c_void) -> sgx_status_t;
+
+// The layout of `sgx_ocall_table_t` of edger8r.
+#[repr(C)]
+struct RawTable {
+    nr_ocall: usize,
+    table: [*const c_void; 0],
+}
+
+#[repr(C)]
+struct WrappedTable {
+    nr_ocall: usize,
+    table: [[OcallFn; 16]; MAX_OCALLS / 16],
+}
+
+unsafe extern "C" fn trampoline<const HI: usize, const LO: usize>(ms: *mut c_void) -> sgx_status_t {
+    call_ocall(HI * 16 + LO, ms)
+}
+
+macro_rules! row {
+    ($hi:literal) => {
+        [
+            trampoline::<$hi, 0>,
+            trampoline::<$hi, 1>,
+            trampoline::<$hi, 2>,
+            trampoline::<$hi, 3>,
+            trampoline::<$hi, 4>,
+            trampoline::<$hi, 5>,
+            trampoline::<$hi, 6>,
+            trampoline::<$hi, 7>,
+            trampoline::<$hi, 8>,
+            trampoline::<$hi, 9>,
+            trampoline::<$hi, 10>,
+            trampoline::<$hi, 11>,
+            trampoline::<$hi, 12>,
+            trampoline::<$hi, 13>,
+            trampoline::<$hi, 14>,
+            trampoline::<$hi, 15>,
+        ]
+    };
+}
+
+// Entry `i` calls OCALL `i` of the table of the innermost ECALL of the
+// calling thread, so one table serves all enclaves.
+static WRAPPED: WrappedTable = WrappedTable {
+    nr_ocall: MAX_OCALLS,
+    table: [
+        row!(0),
+        row!(1),
+        row!(2),
+        row!(3),
+        row!(4),
+        row!(5),
+        row!(6),
+        row!(7),
+        row!(8),
+        row!(9),
+        row!(10),
+        row!(11),
+        row!(12),
+        row!(13),
+        row!(14),
+        row!(15),
+        row!(16),
+        row!(17),
+        row!(18),
+        row!(19),
+        row!(20),
+        row!(21),
+        row!(22),
+        row!(23),
+        row!(24),
+        row!(25),
+        row!(26),
+        row!(27),
+        row!(28),
+        row!(29),
+        row!(30),
+        row!(31),
+    ],
+};
+
+unsafe fn call_ocall(index: usize, ms: *mut c_void) -> sgx_status_t {
+    let table = CURRENT.try_with(Cell::get).unwrap_or(ptr::null());
+    if table.is_null() || index >= (*table).nr_ocall {
+        return sgx_status_t::SGX_ERROR_INVALID_FUNCTION;
+    }
+    let entry = *(ptr::addr_of!((*table).table) as *const *const c_void).add(index);
+    if entry.is_null() {
+        return sgx_status_t::SGX_ERROR_INVALID_FUNCTION;
+    }
+    let ocall: OcallFn = mem::transmute(entry);
+
+    let start = Instant::now();
+    let ret = ocall(ms);
+    let elapsed = start.elapsed();
+    with_counters(|counters| {
+        counters.ocalls.fetch_add(1, Ordering::Relaxed);
+        let nanos = elapsed.as_nanos().min(u64::MAX as u128) as u64;
+        counters.ocall_nanos.fetch_add(nanos, Ordering::Relaxed);
+        counters.ocall_buckets[bucket(elapsed)].fetch_add(1, Ordering::Relaxed);
+    });
+    ret
+}
+
+extern "C" {
+    fn __real_sgx_ecall(
+        eid: sgx_enclave_id_t,
+        index: c_int,
+        ocall_table: *const c_void,
+        ms: *mut c_void,
+    ) -> sgx_status_t;
+}
+
+///
+/// Counts an ECALL of the current thread and times its OCALLs, then makes
+/// it through `sgx_ecall` of the untrusted runtime.
+///
+/// The bridges generated by edger8r call it in place of `sgx_ecall` when the
+/// application is linked with `-Wl,--wrap=sgx_ecall`.
+///
+/// # Safety
+///
+/// The arguments are those of `sgx_ecall`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn __wrap_sgx_ecall(
+    eid: sgx_enclave_id_t,
+    index: c_int,
+    ocall_table: *const c_void,
+    ms: *mut c_void,
+) -> sgx_status_t {
+    ACTIVE.store(true, Ordering::Relaxed);
+    with_counters(|counters| {
+        counters.ecalls.fetch_add(1, Ordering::Relaxed);
+    });
+
+    let original = ocall_table as *const RawTable;
+    if original.is_null() || (*original).nr_ocall > MAX_OCALLS {
+        return __real_sgx_ecall(eid, index, ocall_table, ms);
+    }
+    let outer = match CURRENT.try_with(|current| current.replace(original)) {
+        Ok(outer) => outer,
+        Err(_) => return __real_sgx_ecall(eid, index, ocall_table, ms),
+    };
+    let ret = __real_sgx_ecall(
+        eid,
+        index,
+        &WRAPPED as *const WrappedTable as *const c_void,
+        ms,
+    );
+    let _ = CURRENT.try_with(|current| current.set(outer));
+    ret
+}
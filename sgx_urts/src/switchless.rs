@@ -0,0 +1,248 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+//! Configuration and statistics of the switchless workers.
+//!
+//! The switchless library of the SDK starts its workers when the enclave is
+//! created and keeps their number for the life of the enclave. A worker that
+//! finds no task spins for `retries_before_sleep` rounds and then sleeps
+//! until a call wakes it up, so it is the spinning, not the number of
+//! workers, that burns cores at idle. [`SwitchlessConfig::adaptive`] sizes
+//! the workers for the peak load and lets them sleep quickly when the load
+//! drops; the enclave side (`std::switchless`) in turn sends its OCALLs the
+//! regular way once as many are queued as there are untrusted workers,
+//! instead of spinning on a busy mailbox.
+//!
+//! The worker events reported by the library are counted in [`stats`]. The
+//! library has no room for a context in its callbacks, so the counts cover
+//! all the enclaves of the process.
+//!
+//! # Examples
+//!
+//! ```
+//! use sgx_urts::switchless::{self, SwitchlessConfig};
+//!
+//! let config = SwitchlessConfig::adaptive().untrusted_workers(4);
+//! let enclave = SgxEnclave::create_with_switchless(path, debug, &mut token, &mut updated, &mut misc, &config)?;
+//! serve(&enclave);
+//! let stats = switchless::stats();
+//! println!("{:.1}% OCALLs missed", stats.untrusted.miss_ratio() * 100.0);
+//! ```
+
+use sgx_types::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Spins of a worker before it sleeps, and of a caller before it falls back
+/// to a regular call, in [`SwitchlessConfig::adaptive`].
+pub const ADAPTIVE_RETRIES: u32 = 2000;
+
+/// The tuning knobs of the switchless library.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SwitchlessConfig {
+    untrusted_workers: u32,
+    trusted_workers: u32,
+    pool_size_qwords: u32,
+    retries_before_fallback: u32,
+    retries_before_sleep: u32,
+}
+
+impl Default for SwitchlessConfig {
+    /// The defaults of the SDK: one worker of each type, one qword of tasks,
+    /// and 20000 spins before a fallback or a sleep.
+    fn default() -> SwitchlessConfig {
+        SwitchlessConfig {
+            untrusted_workers: 1,
+            trusted_workers: 1,
+            pool_size_qwords: SL_DEFUALT_MAX_TASKS_QWORDS,
+            retries_before_fallback: SL_DEFAULT_FALLBACK_RETRIES,
+            retries_before_sleep: SL_DEFAULT_SLEEP_RETRIES,
+        }
+    }
+}
+
+impl SwitchlessConfig {
+    pub fn new() -> SwitchlessConfig {
+        SwitchlessConfig::default()
+    }
+
+    /// A configuration for bursty loads: the workers sleep and the callers
+    /// fall back after [`ADAPTIVE_RETRIES`] spins, a tenth of the default.
+    pub fn adaptive() -> SwitchlessConfig {
+        SwitchlessConfig::default()
+            .retries_before_fallback(ADAPTIVE_RETRIES)
+            .retries_before_sleep(ADAPTIVE_RETRIES)
+    }
+
+    /// Sets the number of untrusted workers, serving switchless OCALLs.
+    pub fn untrusted_workers(mut self, workers: u32) -> SwitchlessConfig {
+        self.untrusted_workers = workers;
+        self
+    }
+
+    /// Sets the number of trusted workers, serving switchless ECALLs.
+    pub fn trusted_workers(mut self, workers: u32) -> SwitchlessConfig {
+        self.trusted_workers = workers;
+        self
+    }
+
+    /// Sets the size of the task pools, in qwords of 64 tasks, clamped to
+    /// `1..=8`.
+    pub fn pool_size_qwords(mut self, qwords: u32) -> SwitchlessConfig {
+        self.pool_size_qwords = qwords.clamp(1, SL_MAX_TASKS_MAX_QWORDS);
+        self
+    }
+
+    /// Sets how long a caller spins for a worker to pick its call up before
+    /// making a regular call.
+    pub fn retries_before_fallback(mut self, retries: u32) -> SwitchlessConfig {
+        self.retries_before_fallback = retries.max(1);
+        self
+    }
+
+    /// Sets how long an idle worker spins before it sleeps.
+    pub fn retries_before_sleep(mut self, retries: u32) -> SwitchlessConfig {
+        self.retries_before_sleep = retries.max(1);
+        self
+    }
+
+    /// Returns the configuration passed to `sgx_create_enclave_ex`, with the
+    /// callbacks that keep [`stats`].
+    pub fn to_raw(&self) -> sgx_uswitchless_config_t {
+        sgx_uswitchless_config_t {
+            switchless_calls_pool_size_qwords: self.pool_size_qwords as u64,
+            num_uworkers: self.untrusted_workers as u64,
+            num_tworkers: self.trusted_workers as u64,
+            retries_before_fallback: self.retries_before_fallback as u64,
+            retries_before_sleep: self.retries_before_sleep as u64,
+            callback_func: [on_worker_event; SGX_USWITCHLESS_WORKER_EVENT_NUM],
+        }
+    }
+}
+
+struct Counters {
+    running: AtomicU64,
+    idle: AtomicU64,
+    misses: AtomicU64,
+    processed: AtomicU64,
+    missed: AtomicU64,
+}
+
+impl Counters {
+    const fn new() -> Counters {
+        Counters {
+            running: AtomicU64::new(0),
+            idle: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            processed: AtomicU64::new(0),
+            missed: AtomicU64::new(0),
+        }
+    }
+
+    fn snapshot(&self) -> WorkerStats {
+        WorkerStats {
+            running: self.running.load(Ordering::Relaxed),
+            idle_events: self.idle.load(Ordering::Relaxed),
+            miss_events: self.misses.load(Ordering::Relaxed),
+            processed: self.processed.load(Ordering::Relaxed),
+            missed: self.missed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+static UNTRUSTED: Counters = Counters::new();
+static TRUSTED: Counters = Counters::new();
+
+extern "C" fn on_worker_event(
+    worker_type: sgx_uswitchless_worker_type_t,
+    worker_event: sgx_uswitchless_worker_event_t,
+    worker_stats: *const sgx_uswitchless_worker_stats_t,
+) {
+    let counters = match worker_type {
+        sgx_uswitchless_worker_type_t::SGX_USWITCHLESS_WORKER_TYPE_UNTRUSTED => &UNTRUSTED,
+        sgx_uswitchless_worker_type_t::SGX_USWITCHLESS_WORKER_TYPE_TRUSTED => &TRUSTED,
+    };
+    match worker_event {
+        sgx_uswitchless_worker_event_t::SGX_USWITCHLESS_WORKER_EVENT_START => {
+            counters.running.fetch_add(1, Ordering::Relaxed);
+        }
+        sgx_uswitchless_worker_event_t::SGX_USWITCHLESS_WORKER_EVENT_IDLE => {
+            counters.idle.fetch_add(1, Ordering::Relaxed);
+        }
+        sgx_uswitchless_worker_event_t::SGX_USWITCHLESS_WORKER_EVENT_MISS => {
+            counters.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        sgx_uswitchless_worker_event_t::SGX_USWITCHLESS_WORKER_EVENT_EXIT => {
+            let _ = counters
+                .running
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+        }
+        sgx_uswitchless_worker_event_t::SGX_USWITCHLESS_WORKER_EVENT_NUM => (),
+    }
+    if let Some(stats) = unsafe { worker_stats.as_ref() } {
+        // The library reports running totals over all its workers.
+        counters
+            .processed
+            .fetch_max(stats.processed, Ordering::Relaxed);
+        counters.missed.fetch_max(stats.missed, Ordering::Relaxed);
+    }
+}
+
+/// The statistics of the workers of one type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WorkerStats {
+    /// Workers started and not exited.
+    pub running: u64,
+    /// Times a worker went to sleep for lack of tasks.
+    pub idle_events: u64,
+    /// Times a worker found that calls had fallen back before it got to them.
+    pub miss_events: u64,
+    /// Calls served by the workers.
+    pub processed: u64,
+    /// Calls that fell back to regular calls, as seen by the workers.
+    pub missed: u64,
+}
+
+impl WorkerStats {
+    /// Returns the share of calls that fell back, or 0 before any call.
+    ///
+    /// A high ratio under load calls for more workers; a ratio near 0 with
+    /// frequent idle events, for fewer.
+    pub fn miss_ratio(&self) -> f64 {
+        match self.processed + self.missed {
+            0 => 0.0,
+            total => self.missed as f64 / total as f64,
+        }
+    }
+}
+
+/// The statistics of the switchless workers of the process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SwitchlessStats {
+    /// The workers serving OCALLs.
+    pub untrusted: WorkerStats,
+    /// The workers serving ECALLs.
+    pub trusted: WorkerStats,
+}
+
+/// Returns the statistics of the workers of the enclaves created with a
+/// [`SwitchlessConfig`].
+pub fn stats() -> SwitchlessStats {
+    SwitchlessStats {
+        untrusted: UNTRUSTED.snapshot(),
+        trusted: TRUSTED.snapshot(),
+    }
+}
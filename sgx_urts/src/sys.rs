@@ -16,7 +16,10 @@
 // under the License..
 
 use libc::{self, c_int, c_long, c_ulong, cpu_set_t, pid_t, size_t};
+use std::fs;
 use std::io::Error;
+use std::path::Path;
+use std::slice;
 
 #[no_mangle]
 pub extern "C" fn u_sysconf_ocall(error: *mut c_int, name: c_int) -> c_long {
@@ -94,3 +97,102 @@ pub extern "C" fn u_sched_getaffinity_ocall(
     }
     ret
 }
+
+// Parses a sysfs CPU list such as "0-3,8,10-11".
+fn parse_cpu_list(list: &str) -> Option<Vec<u32>> {
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|r| !r.is_empty()) {
+        let (first, last): (u32, u32) = match range.split_once('-') {
+            Some((first, last)) => (first.parse().ok()?, last.parse().ok()?),
+            None => {
+                let cpu = range.parse().ok()?;
+                (cpu, cpu)
+            }
+        };
+        if first > last {
+            return None;
+        }
+        cpus.extend(first..=last);
+    }
+    Some(cpus)
+}
+
+fn read_id(path: &Path) -> u32 {
+    // Missing files and the -1 of unknown packages map to 0.
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn cpu_node(cpu_dir: &Path) -> u32 {
+    fs::read_dir(cpu_dir)
+        .ok()
+        .and_then(|entries| {
+            entries.flatten().find_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.strip_prefix("node"))
+                    .and_then(|id| id.parse().ok())
+            })
+        })
+        .unwrap_or(0)
+}
+
+fn cpu_topology() -> Option<Vec<[u32; 4]>> {
+    let root = Path::new("/sys/devices/system/cpu");
+    let online = fs::read_to_string(root.join("online")).ok()?;
+    let cpus = parse_cpu_list(&online)?;
+    Some(
+        cpus.into_iter()
+            .map(|cpu| {
+                let dir = root.join(format!("cpu{}", cpu));
+                let core = read_id(&dir.join("topology/core_id"));
+                let package = read_id(&dir.join("topology/physical_package_id"));
+                [cpu, core, package, cpu_node(&dir)]
+            })
+            .collect(),
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn u_cpu_topology_ocall(
+    error: *mut c_int,
+    entries: *mut u32,
+    cap: size_t,
+    ncpus: *mut size_t,
+) -> c_int {
+    let mut errno = 0;
+    let mut count = 0;
+    let ret = match cpu_topology() {
+        Some(topology) => {
+            count = topology.len();
+            if count * 4 > cap || entries.is_null() {
+                errno = libc::ERANGE;
+                -1
+            } else {
+                let out = unsafe { slice::from_raw_parts_mut(entries, cap) };
+                for (chunk, cpu) in out.chunks_exact_mut(4).zip(topology.iter()) {
+                    chunk.copy_from_slice(cpu);
+                }
+                0
+            }
+        }
+        None => {
+            errno = libc::ENOENT;
+            -1
+        }
+    };
+    if !ncpus.is_null() {
+        unsafe {
+            *ncpus = count;
+        }
+    }
+    if !error.is_null() {
+        unsafe {
+            *error = errno;
+        }
+    }
+    ret
+}
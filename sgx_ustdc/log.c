@@ -0,0 +1,73 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License..
+
+#include <stdio.h>
+#include <stdint.h>
+#include <string.h>
+#include <errno.h>
+#include <sys/types.h>
+
+/* Records are encoded as a little-endian u32 length followed by the payload. */
+static int next_record(const uint8_t **cursor, const uint8_t *end, const uint8_t **rec, uint32_t *rec_len)
+{
+    uint32_t len;
+
+    if ((size_t)(end - *cursor) < sizeof(len)) {
+        return -1;
+    }
+    memcpy(&len, *cursor, sizeof(len));
+    *cursor += sizeof(len);
+    if ((size_t)(end - *cursor) < len) {
+        return -1;
+    }
+    *rec = *cursor;
+    *rec_len = len;
+    *cursor += len;
+    return 0;
+}
+
+ssize_t u_log_ship_ocall(int *error, const uint8_t *batch, size_t len, size_t count)
+{
+    const uint8_t *cursor = batch;
+    const uint8_t *end = batch + len;
+    const uint8_t *rec = NULL;
+    uint32_t rec_len = 0;
+    size_t acked = 0;
+
+    if (batch == NULL && len != 0) {
+        if (error) {
+            *error = EINVAL;
+        }
+        return -1;
+    }
+
+    while (acked < count) {
+        if (next_record(&cursor, end, &rec, &rec_len) != 0) {
+            break;
+        }
+        if (fwrite(rec, 1, rec_len, stderr) != rec_len || fputc('\n', stderr) == EOF) {
+            break;
+        }
+        acked += 1;
+    }
+    fflush(stderr);
+
+    if (error) {
+        *error = 0;
+    }
+    return (ssize_t)acked;
+}
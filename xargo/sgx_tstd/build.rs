@@ -24,6 +24,31 @@ fn main() {
         //println!("cargo:rustc-cfg=RUST_BACKTRACE=\"full\"");
     }
 
+    // `minimal` compiles out the env, untrusted fs and network OCALL proxies.
+    // It caps `net`, `untrusted_fs` and `pipe` instead of conflicting with
+    // them, so that features stay additive: the modules of those features are
+    // built only through the cfgs below, which `minimal` withholds, and an
+    // enclave built with `minimal` does not get the proxies back through a
+    // dependency enabling one of them.
+    let minimal = cfg!(feature = "minimal");
+    if minimal {
+        println!("cargo:rustc-cfg=minimal_tcb");
+    }
+    for (feature, enabled) in [
+        ("net", cfg!(feature = "net")),
+        ("untrusted_fs", cfg!(feature = "untrusted_fs")),
+        ("pipe", cfg!(feature = "pipe")),
+    ] {
+        if !enabled {
+            continue;
+        }
+        if minimal {
+            println!("cargo:warning=the `{}` feature of sgx_tstd is compiled out by `minimal`", feature);
+        } else {
+            println!("cargo:rustc-cfg=tstd_{}", feature);
+        }
+    }
+
     let mut sdk_dir = env::var("SGX_SDK")
                     .unwrap_or_else(|_| "/opt/sgxsdk".to_string());
 